@@ -804,6 +804,8 @@ app.post('/api/ens/register', async (req, res) => {
         ensName: result.ensName,
         walletAddress,
         txHash: result.txHash,
+        gasUsed: result.gasUsed,
+        gasCostWei: result.gasCostWei,
         message: `ENS name ${result.ensName} registered`,
       });
     } else {
@@ -820,6 +822,42 @@ app.post('/api/ens/register', async (req, res) => {
   }
 });
 
+// Re-point a drifted subdomain at its owner's wallet (SMS handler ENS verifier)
+app.post('/api/ens/repoint', async (req, res) => {
+  try {
+    const { ensName, walletAddress } = req.body;
+
+    if (!ensName || !walletAddress) {
+      return res.status(400).json({
+        success: false,
+        error: 'Missing ensName or walletAddress',
+      });
+    }
+
+    const cleanName = ensName.toLowerCase().trim().replace(/\.ttcip\.eth$/, '');
+    const result = await ensService.repointSubdomain(cleanName, walletAddress);
+
+    if (result.success) {
+      res.json({
+        success: true,
+        ensName: result.ensName,
+        walletAddress,
+        txHash: result.txHash,
+      });
+    } else {
+      res.status(400).json({
+        success: false,
+        error: result.error,
+      });
+    }
+  } catch (error: any) {
+    res.status(500).json({
+      success: false,
+      error: error.message,
+    });
+  }
+});
+
 // Resolve ENS name to address
 app.get('/api/ens/resolve/:ensName', async (req, res) => {
   try {
@@ -1,11 +1,10 @@
 /**
  * Deposit Monitor Service
  * Handles Alchemy webhook notifications for incoming deposits
- * Sends SMS notifications to users when deposits are detected
+ * Reports them to the SMS handler, which holds them as pending until they
+ * have enough confirmations and texts the user at each step
  */
 
-import { ethers } from 'ethers';
-import { SEPOLIA_CONFIG } from './contracts.config';
 import axios from 'axios';
 
 interface AlchemyWebhookEvent {
@@ -32,20 +31,22 @@ interface AlchemyWebhookEvent {
   };
 }
 
-interface DepositInfo {
-  userPhone: string;
-  amount: string;
-  token: string;
-  txHash: string;
-  newBalance: string;
-}
+/** Alchemy network -> SMS handler chain name */
+const CHAIN_BY_NETWORK: Record<string, string> = {
+  ETH_MAINNET: 'eth',
+  ETH_SEPOLIA: 'sepolia',
+  MATIC_MAINNET: 'polygon',
+  MATIC_AMOY: 'amoy',
+  BASE_MAINNET: 'base',
+  BASE_SEPOLIA: 'base-sepolia',
+  ARB_MAINNET: 'arb',
+  ARB_SEPOLIA: 'arb-sepolia',
+};
 
 export class DepositMonitor {
-  private provider: ethers.JsonRpcProvider;
   private smsHandlerUrl: string;
 
   constructor() {
-    this.provider = new ethers.JsonRpcProvider(SEPOLIA_CONFIG.rpcUrl);
     this.smsHandlerUrl = process.env.SMS_HANDLER_URL || 'http://localhost:8080';
   }
 
@@ -60,10 +61,16 @@ export class DepositMonitor {
       return;
     }
 
+    const chain = CHAIN_BY_NETWORK[webhookData.event.network];
+    if (!chain) {
+      console.log('⏭️  Skipping unsupported network:', webhookData.event.network);
+      return;
+    }
+
     for (const activity of webhookData.event.activity) {
       // Only process incoming transactions (deposits)
       if (activity.category === 'external' || activity.category === 'token') {
-        await this.handleDeposit(activity);
+        await this.handleDeposit(activity, chain);
       }
     }
   }
@@ -71,7 +78,7 @@ export class DepositMonitor {
   /**
    * Handle a deposit transaction
    */
-  private async handleDeposit(activity: any): Promise<void> {
+  private async handleDeposit(activity: any, chain: string): Promise<void> {
     const toAddress = activity.toAddress.toLowerCase();
     const amount = activity.value;
     const asset = activity.asset || 'MATIC';
@@ -83,28 +90,18 @@ export class DepositMonitor {
     try {
       // Get user info from database via SMS handler
       const userInfo = await this.getUserByWallet(toAddress);
-      
+
+      // Not a user wallet: it may be a per-user deposit address, which the
+      // SMS handler resolves to its owner itself
       if (!userInfo) {
-        console.log('⚠️  No user found for wallet:', toAddress);
-        return;
+        console.log('ℹ️  No user wallet matches, reporting as deposit address:', toAddress);
       }
 
-      // Get updated balance
-      const balance = await this.getWalletBalance(toAddress);
-
-      // Send SMS notification
-      await this.sendDepositNotification({
-        userPhone: userInfo.phone,
-        amount: `${amount} ${asset}`,
-        token: asset,
-        txHash,
-        newBalance: balance,
-      });
+      // Record as pending; the SMS handler confirms it (and texts the
+      // user) once the block is deep enough, or reverses it on a reorg
+      await this.recordDeposit(userInfo?.phone, toAddress, amount, asset, txHash, chain, activity.blockNum);
 
-      // Record deposit in database
-      await this.recordDeposit(userInfo.phone, amount, asset, txHash);
-
-      console.log(`✅ Deposit notification sent to ${userInfo.phone}`);
+      console.log(`✅ Deposit reported for ${userInfo?.phone ?? toAddress}`);
     } catch (error) {
       console.error('❌ Error handling deposit:', error);
     }
@@ -145,65 +142,28 @@ export class DepositMonitor {
   }
 
   /**
-   * Get wallet balance
-   */
-  private async getWalletBalance(walletAddress: string): Promise<string> {
-    try {
-      const balance = await this.provider.getBalance(walletAddress);
-      const ethBalance = ethers.formatEther(balance);
-      return `${parseFloat(ethBalance).toFixed(4)} MATIC`;
-    } catch (error) {
-      console.error('Error getting balance:', error);
-      return '0 MATIC';
-    }
-  }
-
-  /**
-   * Send SMS notification about deposit
-   */
-  private async sendDepositNotification(info: DepositInfo): Promise<void> {
-    const message = `✅ Deposit received!\n+${info.amount}\n\nNew balance: ${info.newBalance}\n\nTx: ${info.txHash.substring(0, 10)}...\n\nReply BALANCE for details`;
-
-    try {
-      // Send via SMS handler's internal notification endpoint
-      await axios.post(
-        `${this.smsHandlerUrl}/internal/notify`,
-        {
-          phone: info.userPhone,
-          message,
-        },
-        {
-          headers: {
-            'Content-Type': 'application/json',
-            'X-Internal-Secret': process.env.INTERNAL_SECRET || 'internal-secret-key',
-          },
-        }
-      );
-    } catch (error) {
-      console.error('Error sending SMS notification:', error);
-      // Fallback: log the notification
-      console.log(`📱 Would send SMS to ${info.userPhone}: ${message}`);
-    }
-  }
-
-  /**
-   * Record deposit in database
+   * Record deposit in database (pending until confirmed)
    */
   private async recordDeposit(
-    phone: string,
-    amount: string,
+    phone: string | undefined,
+    toAddress: string,
+    amount: number,
     token: string,
-    txHash: string
+    txHash: string,
+    chain: string,
+    blockNum: string
   ): Promise<void> {
     try {
       await axios.post(
         `${this.smsHandlerUrl}/internal/record-deposit`,
         {
           phone,
+          toAddress,
           amount,
           token,
           txHash,
-          source: 'external_wallet',
+          chain,
+          blockNum,
         },
         {
           headers: {
@@ -106,6 +106,10 @@ export class EnsService {
     success: boolean;
     ensName?: string;
     txHash?: string;
+    /** Gas units across every mint transaction (decimal string) */
+    gasUsed?: string;
+    /** What that gas cost, in wei (decimal string) */
+    gasCostWei?: string;
     error?: string;
   }> {
     try {
@@ -131,9 +135,11 @@ export class EnsService {
           const receipt = await tx.wait();
           console.log(`✅ ENS subdomain registered: ${name}.${this.parentDomain}`);
           console.log(`   Transaction: ${receipt?.hash}`);
+          const gas = { used: 0n, costWei: 0n };
+          addGas(gas, receipt);
           
           // Register subdomain in ENS registry
-          await this.registerInENSRegistry(name, walletAddress);
+          await this.registerInENSRegistry(name, walletAddress, gas);
           
           // Add wallet to blockchain monitoring for deposit detection
           await blockchainMonitor.addWallet(walletAddress);
@@ -142,6 +148,8 @@ export class EnsService {
             success: true,
             ensName: `${name}.${this.parentDomain}`,
             txHash: receipt?.hash || '',
+            gasUsed: gas.used.toString(),
+            gasCostWei: gas.costWei.toString(),
           };
         } catch (error: any) {
           console.error('On-chain minting failed:', error);
@@ -157,6 +165,8 @@ export class EnsService {
         success: true,
         ensName: fullName,
         txHash: '0x' + '0'.repeat(64), // Placeholder for memory-only
+        gasUsed: '0',
+        gasCostWei: '0',
       };
     } catch (error: any) {
       console.error('ENS registration error:', error);
@@ -190,6 +200,67 @@ export class EnsService {
     return this.registeredNames.get(ensName) || null;
   }
 
+  /**
+   * Point an existing subdomain back at `walletAddress`. We own ttcip.eth, so
+   * the subnode is reclaimed, its address record rewritten and ownership
+   * handed back to the wallet
+   */
+  async repointSubdomain(
+    name: string,
+    walletAddress: string
+  ): Promise<{
+    success: boolean;
+    ensName?: string;
+    txHash?: string;
+    error?: string;
+  }> {
+    const fullName = `${name}.${this.parentDomain}`;
+
+    if (!this.wallet) {
+      if (!this.registeredNames.has(fullName)) {
+        return { success: false, error: 'Name not registered' };
+      }
+      this.registeredNames.set(fullName, walletAddress);
+      console.log(`📝 ENS re-pointed (memory-only): ${fullName} → ${walletAddress}`);
+      return { success: true, ensName: fullName, txHash: '0x' + '0'.repeat(64) };
+    }
+
+    try {
+      const ENS_REGISTRY = '0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e';
+      const PUBLIC_RESOLVER = '0x8FADE66B79cC9f707aB26799354482EB93a5B7dD';
+      const ttcipNode = ethers.namehash(this.parentDomain);
+      const subdomainLabel = ethers.id(name);
+      const subdomainNode = ethers.namehash(fullName);
+
+      const ensRegistry = new ethers.Contract(
+        ENS_REGISTRY,
+        [
+          'function setSubnodeOwner(bytes32 node, bytes32 label, address owner) external returns (bytes32)',
+          'function setResolver(bytes32 node, address resolver) external',
+        ],
+        this.wallet
+      );
+      const publicResolver = new ethers.Contract(
+        PUBLIC_RESOLVER,
+        ['function setAddr(bytes32 node, address addr) external'],
+        this.wallet
+      );
+
+      console.log(`🔧 Re-pointing ${fullName} → ${walletAddress}`);
+      await (await ensRegistry.setSubnodeOwner(ttcipNode, subdomainLabel, await this.wallet.getAddress())).wait();
+      await (await ensRegistry.setResolver(subdomainNode, PUBLIC_RESOLVER)).wait();
+      const receipt = await (await publicResolver.setAddr(subdomainNode, walletAddress)).wait();
+      await (await ensRegistry.setSubnodeOwner(ttcipNode, subdomainLabel, walletAddress)).wait();
+      this.registeredNames.set(fullName, walletAddress);
+      console.log(`✅ ${fullName} re-pointed`);
+
+      return { success: true, ensName: fullName, txHash: receipt?.hash || '' };
+    } catch (error: any) {
+      console.error(`❌ Failed to re-point ${fullName}:`, error.message);
+      return { success: false, error: error.message };
+    }
+  }
+
   /**
    * Get all registered names (for testing)
    */
@@ -209,7 +280,8 @@ export class EnsService {
    */
   private async registerInENSRegistry(
     subdomain: string,
-    owner: string
+    owner: string,
+    gas: GasTotal
   ): Promise<void> {
     if (!this.wallet) {
       console.warn('⚠️  No wallet configured, skipping ENS registry registration');
@@ -239,13 +311,13 @@ export class EnsService {
 
       console.log(`  Step 1/3: Setting subdomain owner...`);
       const tx1 = await ensRegistry.setSubnodeOwner(ttcipNode, subdomainLabel, await this.wallet.getAddress());
-      await tx1.wait();
+      addGas(gas, await tx1.wait());
       console.log(`  ✅ Subdomain owner set`);
 
       // Step 2: Set resolver to Public Resolver
       console.log(`  Step 2/3: Setting resolver...`);
       const tx2 = await ensRegistry.setResolver(subdomainNode, PUBLIC_RESOLVER);
-      await tx2.wait();
+      addGas(gas, await tx2.wait());
       console.log(`  ✅ Resolver set`);
 
       // Step 3: Set address + name records on the Public Resolver
@@ -259,20 +331,20 @@ export class EnsService {
         this.wallet
       );
       const tx3 = await publicResolver.setAddr(subdomainNode, owner);
-      await tx3.wait();
+      addGas(gas, await tx3.wait());
       console.log(`  ✅ Address record set`);
 
       // Step 4: Set name record so ENS app can display human-readable label
       console.log(`  Step 4/5: Setting name record...`);
       const fullName = `${subdomain}.${this.parentDomain}`;
       const tx4 = await publicResolver.setName(subdomainNode, fullName);
-      await tx4.wait();
+      addGas(gas, await tx4.wait());
       console.log(`  ✅ Name record set: ${fullName}`);
 
       // Step 5: Transfer ownership to the actual owner
       console.log(`  Step 5/5: Transferring ownership to ${owner}...`);
       const tx5 = await ensRegistry.setSubnodeOwner(ttcipNode, subdomainLabel, owner);
-      await tx5.wait();
+      addGas(gas, await tx5.wait());
 
       console.log(`✅ ${subdomain}.${this.parentDomain} fully registered in ENS registry`);
     } catch (error: any) {
@@ -282,5 +354,17 @@ export class EnsService {
   }
 }
 
+/** Gas spent by the transactions of one mint, charged to the sponsoring campaign */
+interface GasTotal {
+  used: bigint;
+  costWei: bigint;
+}
+
+function addGas(total: GasTotal, receipt: ethers.TransactionReceipt | null): void {
+  if (!receipt) return;
+  total.used += receipt.gasUsed;
+  total.costWei += receipt.gasUsed * receipt.gasPrice;
+}
+
 // Export singleton instance
 export const ensService = new EnsService(process.env.ENS_PRIVATE_KEY || process.env.PRIVATE_KEY);
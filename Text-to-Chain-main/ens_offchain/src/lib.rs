@@ -0,0 +1,335 @@
+//! ENS resolution for offchain names
+//!
+//! `Provider::resolve_name` only asks the resolver set on the exact name,
+//! and fails when that resolver reverts. Names such as `alice.cb.id` are
+//! stored offchain instead: the resolver sits on a parent name (ENSIP-10
+//! wildcard) and answers with an `OffchainLookup` revert naming a gateway to
+//! ask (ERC-3668, CCIP-Read). `resolve_name` follows both and resolves
+//! ordinary onchain names as before.
+//!
+//! Used by the SMS service (SEND recipients, ENS verification) and the ENS
+//! service, so both get the same address for the same name.
+
+use std::time::Duration;
+
+use ethers::abi::{self, ParamType, Token};
+use ethers::providers::{ens, JsonRpcClient, Middleware, Provider, ProviderError, RpcError};
+use ethers::types::{Address, TransactionRequest};
+use ethers::utils::hex;
+
+/// `resolver(bytes32)` on the ENS registry
+const RESOLVER_SELECTOR: [u8; 4] = [0x01, 0x78, 0xb8, 0xbf];
+/// `addr(bytes32)`
+const ADDR_SELECTOR: [u8; 4] = [0x3b, 0x3b, 0x57, 0xde];
+/// `resolve(bytes,bytes)`, the ENSIP-10 extended resolver entry point
+const RESOLVE_SELECTOR: [u8; 4] = [0x90, 0x61, 0xb9, 0x23];
+/// `supportsInterface(bytes4)`
+const SUPPORTS_INTERFACE_SELECTOR: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+/// `OffchainLookup(address,string[],bytes,bytes4,bytes)`
+const OFFCHAIN_LOOKUP_SELECTOR: [u8; 4] = [0x55, 0x6f, 0x18, 0x30];
+
+/// Gateway round trips per call before giving up
+const MAX_LOOKUPS: usize = 4;
+const GATEWAY_TIMEOUT_SECS: u64 = 10;
+
+/// Address of an ENS name, onchain or offchain. A name without a resolver
+/// or an address is `ProviderError::EnsError`, as with `Provider::resolve_name`.
+pub async fn resolve_name<P: JsonRpcClient>(provider: &Provider<P>, name: &str) -> Result<Address, ProviderError> {
+    let not_found = || ProviderError::EnsError(name.to_string());
+    let Some((resolver, wildcard)) = find_resolver(provider, name).await? else {
+        return Err(not_found());
+    };
+
+    let addr_call = [&ADDR_SELECTOR[..], ens::namehash(name).as_bytes()].concat();
+    let result = if supports_interface(provider, resolver, RESOLVE_SELECTOR).await {
+        let call = encode_call(RESOLVE_SELECTOR, &[Token::Bytes(dns_encode(name).ok_or_else(not_found)?), Token::Bytes(addr_call)]);
+        // `resolve` wraps the `addr` answer in `bytes`
+        ccip_call(provider, resolver, call).await.map(|wrapped| {
+            let inner = abi::decode(&[ParamType::Bytes], &wrapped).ok().and_then(|tokens| tokens.into_iter().next());
+            inner.and_then(Token::into_bytes).unwrap_or_default()
+        })
+    } else if wildcard {
+        // A parent's resolver only answers for children through `resolve`
+        return Err(not_found());
+    } else {
+        ccip_call(provider, resolver, addr_call).await
+    };
+
+    let data = match result {
+        Ok(data) => data,
+        Err(e) if reverted(&e) => return Err(not_found()),
+        Err(e) => return Err(e),
+    };
+    match abi::decode(&[ParamType::Address], &data).ok().and_then(|tokens| tokens.into_iter().next()) {
+        Some(Token::Address(address)) if !address.is_zero() => Ok(address),
+        _ => Err(not_found()),
+    }
+}
+
+/// The resolver for `name` or its closest parent that has one, and whether
+/// it came from a parent
+async fn find_resolver<P: JsonRpcClient>(provider: &Provider<P>, name: &str) -> Result<Option<(Address, bool)>, ProviderError> {
+    let mut current = name;
+    loop {
+        let call = [&RESOLVER_SELECTOR[..], ens::namehash(current).as_bytes()].concat();
+        let data = provider.call(&TransactionRequest::new().to(ens::ENS_ADDRESS).data(call).into(), None).await?;
+        if let Ok(tokens) = abi::decode(&[ParamType::Address], &data) {
+            if let Some(Token::Address(resolver)) = tokens.into_iter().next().filter(|t| t != &Token::Address(Address::zero())) {
+                return Ok(Some((resolver, current != name)));
+            }
+        }
+        match current.split_once('.') {
+            Some((_, parent)) if !parent.is_empty() => current = parent,
+            _ => return Ok(None),
+        }
+    }
+}
+
+/// Whether `resolver` claims ERC-165 support for `interface`; errors count as no
+async fn supports_interface<P: JsonRpcClient>(provider: &Provider<P>, resolver: Address, interface: [u8; 4]) -> bool {
+    let call = encode_call(SUPPORTS_INTERFACE_SELECTOR, &[Token::FixedBytes(interface.to_vec())]);
+    match provider.call(&TransactionRequest::new().to(resolver).data(call).into(), None).await {
+        Ok(data) => matches!(abi::decode(&[ParamType::Bool], &data).as_deref(), Ok([Token::Bool(true)])),
+        Err(_) => false,
+    }
+}
+
+/// `eth_call`, answering any `OffchainLookup` revert through its gateway
+async fn ccip_call<P: JsonRpcClient>(provider: &Provider<P>, to: Address, data: Vec<u8>) -> Result<Vec<u8>, ProviderError> {
+    let mut data = data;
+    for _ in 0..MAX_LOOKUPS {
+        let error = match provider.call(&TransactionRequest::new().to(to).data(data.clone()).into(), None).await {
+            Ok(result) => return Ok(result.to_vec()),
+            Err(e) => e,
+        };
+        let Some(lookup) = revert_data(&error).as_deref().and_then(OffchainLookup::decode) else {
+            return Err(error);
+        };
+        // ERC-3668: a lookup raised by a contract we didn't call can't be trusted
+        if lookup.sender != to {
+            return Err(ProviderError::CustomError(format!("OffchainLookup sender {:?} is not {:?}", lookup.sender, to)));
+        }
+        let response = lookup.fetch().await?;
+        data = encode_call(lookup.callback, &[Token::Bytes(response), Token::Bytes(lookup.extra_data)]);
+    }
+    Err(ProviderError::CustomError(format!("no answer after {} CCIP-Read lookups", MAX_LOOKUPS)))
+}
+
+/// A contract's request to fetch data from a gateway and call back with it
+#[derive(Debug, Clone, PartialEq)]
+struct OffchainLookup {
+    sender: Address,
+    urls: Vec<String>,
+    call_data: Vec<u8>,
+    callback: [u8; 4],
+    extra_data: Vec<u8>,
+}
+
+impl OffchainLookup {
+    fn decode(revert: &[u8]) -> Option<Self> {
+        let args = revert.strip_prefix(&OFFCHAIN_LOOKUP_SELECTOR[..])?;
+        let types = [
+            ParamType::Address,
+            ParamType::Array(Box::new(ParamType::String)),
+            ParamType::Bytes,
+            ParamType::FixedBytes(4),
+            ParamType::Bytes,
+        ];
+        let mut tokens = abi::decode(&types, args).ok()?.into_iter();
+        Some(Self {
+            sender: tokens.next()?.into_address()?,
+            urls: tokens.next()?.into_array()?.into_iter().filter_map(Token::into_string).collect(),
+            call_data: tokens.next()?.into_bytes()?,
+            callback: tokens.next()?.into_fixed_bytes()?.try_into().ok()?,
+            extra_data: tokens.next()?.into_bytes()?,
+        })
+    }
+
+    /// `url` with `{sender}` and `{data}` filled in
+    fn gateway_url(&self, url: &str) -> String {
+        url.replace("{sender}", &format!("{:?}", self.sender)).replace("{data}", &hex_string(&self.call_data))
+    }
+
+    /// Ask each gateway in turn: GET when the URL carries `{data}`, POST
+    /// otherwise. A 4xx answer is final; other failures move on to the next.
+    async fn fetch(&self) -> Result<Vec<u8>, ProviderError> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(GATEWAY_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| ProviderError::CustomError(e.to_string()))?;
+        let mut last_error = "no gateway URLs".to_string();
+        for url in &self.urls {
+            let request = if url.contains("{data}") {
+                client.get(self.gateway_url(url))
+            } else {
+                let body = serde_json::json!({ "data": hex_string(&self.call_data), "sender": format!("{:?}", self.sender) });
+                client.post(self.gateway_url(url)).json(&body)
+            };
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(e) => {
+                    last_error = e.to_string();
+                    continue;
+                }
+            };
+            let status = response.status();
+            if status.is_client_error() {
+                return Err(ProviderError::CustomError(format!("CCIP-Read gateway refused the lookup ({})", status)));
+            }
+            if !status.is_success() {
+                last_error = format!("gateway answered {}", status);
+                continue;
+            }
+            let body: serde_json::Value = match response.json().await {
+                Ok(body) => body,
+                Err(e) => {
+                    last_error = e.to_string();
+                    continue;
+                }
+            };
+            match body["data"].as_str().and_then(|data| hex::decode(data.trim_start_matches("0x")).ok()) {
+                Some(data) => return Ok(data),
+                None => last_error = "gateway answer has no hex `data`".to_string(),
+            }
+        }
+        Err(ProviderError::CustomError(format!("CCIP-Read failed: {}", last_error)))
+    }
+}
+
+fn encode_call(selector: [u8; 4], args: &[Token]) -> Vec<u8> {
+    [&selector[..], &abi::encode(args)].concat()
+}
+
+fn hex_string(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// DNS wire format of a name (ENSIP-10), None for a label over 255 bytes
+fn dns_encode(name: &str) -> Option<Vec<u8>> {
+    let mut encoded = Vec::with_capacity(name.len() + 2);
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        encoded.push(u8::try_from(label.len()).ok()?);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    Some(encoded)
+}
+
+fn revert_data(error: &ProviderError) -> Option<Vec<u8>> {
+    error.as_error_response()?.as_revert_data().map(|data| data.to_vec())
+}
+
+/// The node answered with a revert rather than failing to answer
+fn reverted(error: &ProviderError) -> bool {
+    error.as_error_response().is_some_and(|response| response.is_revert())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::providers::{JsonRpcError, MockResponse};
+    use ethers::types::Bytes;
+    use ethers::utils::id;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_selectors() {
+        assert_eq!(id("resolver(bytes32)"), RESOLVER_SELECTOR);
+        assert_eq!(id("addr(bytes32)"), ADDR_SELECTOR);
+        assert_eq!(id("resolve(bytes,bytes)"), RESOLVE_SELECTOR);
+        assert_eq!(id("supportsInterface(bytes4)"), SUPPORTS_INTERFACE_SELECTOR);
+        assert_eq!(id("OffchainLookup(address,string[],bytes,bytes4,bytes)"), OFFCHAIN_LOOKUP_SELECTOR);
+    }
+
+    #[test]
+    fn test_dns_encode() {
+        assert_eq!(dns_encode("alice.cb.id").unwrap(), b"\x05alice\x02cb\x02id\x00");
+        assert!(dns_encode(&"a".repeat(256)).is_none());
+    }
+
+    fn lookup(sender: Address, urls: &[&str]) -> OffchainLookup {
+        OffchainLookup {
+            sender,
+            urls: urls.iter().map(|url| url.to_string()).collect(),
+            call_data: vec![0xab, 0xcd],
+            callback: [0xde, 0xad, 0xbe, 0xef],
+            extra_data: vec![0x01],
+        }
+    }
+
+    fn revert(lookup: &OffchainLookup) -> Vec<u8> {
+        let args = abi::encode(&[
+            Token::Address(lookup.sender),
+            Token::Array(lookup.urls.iter().cloned().map(Token::String).collect()),
+            Token::Bytes(lookup.call_data.clone()),
+            Token::FixedBytes(lookup.callback.to_vec()),
+            Token::Bytes(lookup.extra_data.clone()),
+        ]);
+        [&OFFCHAIN_LOOKUP_SELECTOR[..], &args].concat()
+    }
+
+    #[test]
+    fn test_offchain_lookup() {
+        let sender: Address = "0x00000000000000000000000000000000000000aa".parse().unwrap();
+        let expected = lookup(sender, &["https://gw.example/{sender}/{data}.json"]);
+        let decoded = OffchainLookup::decode(&revert(&expected)).unwrap();
+        assert_eq!(decoded, expected);
+        assert_eq!(
+            decoded.gateway_url(&decoded.urls[0]),
+            "https://gw.example/0x00000000000000000000000000000000000000aa/0xabcd.json"
+        );
+        assert!(OffchainLookup::decode(&[0u8; 36]).is_none());
+    }
+
+    /// One-shot gateway answering `{"data": "0x1234"}` to the first request
+    async fn gateway() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/{{sender}}/{{data}}.json", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let _ = socket.read(&mut request).await;
+            let body = r#"{"data":"0x1234"}"#;
+            let response = format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    fn word(token: Token) -> Bytes {
+        abi::encode(&[token]).into()
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_name_through_gateway() {
+        let resolver: Address = "0x00000000000000000000000000000000000000aa".parse().unwrap();
+        let alice: Address = "0x1111111111111111111111111111111111111111".parse().unwrap();
+        let offchain = lookup(resolver, &[&gateway().await]);
+        let (provider, mock) = Provider::mocked();
+
+        // Responses are taken last-pushed first, so push them in reverse
+        let answer = abi::encode(&[Token::Address(alice)]);
+        mock.push::<Bytes, _>(word(Token::Bytes(answer))).unwrap();
+        mock.push_response(MockResponse::Error(JsonRpcError {
+            code: 3,
+            message: "execution reverted".to_string(),
+            data: Some(serde_json::Value::String(hex_string(&revert(&offchain)))),
+        }));
+        mock.push::<Bytes, _>(word(Token::Bool(true))).unwrap();
+        mock.push::<Bytes, _>(word(Token::Address(resolver))).unwrap();
+        // alice.cb.id itself has no resolver; cb.id does
+        mock.push::<Bytes, _>(word(Token::Address(Address::zero()))).unwrap();
+
+        assert_eq!(resolve_name(&provider, "alice.cb.id").await.unwrap(), alice);
+    }
+
+    #[tokio::test]
+    async fn test_no_resolver_is_not_found() {
+        let (provider, mock) = Provider::mocked();
+        for _ in 0..3 {
+            mock.push::<Bytes, _>(word(Token::Address(Address::zero()))).unwrap();
+        }
+        assert!(matches!(resolve_name(&provider, "nobody.cb.id").await, Err(ProviderError::EnsError(_))));
+    }
+}
@@ -0,0 +1,16 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/// @notice Lets a test make any function revert on demand
+abstract contract FaultInjectable {
+    mapping(bytes4 => bool) public failing;
+
+    function setFailing(bytes4 selector, bool fail) external {
+        failing[selector] = fail;
+    }
+
+    modifier faultable() {
+        require(!failing[msg.sig], "injected fault");
+        _;
+    }
+}
@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import "./FaultInjectable.sol";
+
+/// @notice ENS registry subset used by EnsMinter
+contract MockENSRegistry is FaultInjectable {
+    mapping(bytes32 => address) public owner;
+    mapping(bytes32 => address) public resolver;
+
+    event NewOwner(bytes32 indexed node, bytes32 indexed label, address owner);
+
+    constructor() {
+        owner[bytes32(0)] = msg.sender;
+    }
+
+    modifier authorised(bytes32 node) {
+        require(owner[node] == msg.sender, "not owner");
+        _;
+    }
+
+    function setSubnodeOwner(bytes32 node, bytes32 label, address newOwner)
+        external
+        faultable
+        authorised(node)
+        returns (bytes32)
+    {
+        bytes32 subnode = keccak256(abi.encodePacked(node, label));
+        owner[subnode] = newOwner;
+        emit NewOwner(node, label, newOwner);
+        return subnode;
+    }
+
+    function setResolver(bytes32 node, address newResolver) external faultable authorised(node) {
+        resolver[node] = newResolver;
+    }
+}
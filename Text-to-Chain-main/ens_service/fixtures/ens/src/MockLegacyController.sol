@@ -0,0 +1,47 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+/// @notice Pre-NameWrapper controller ABI without commit-reveal, like the
+/// alternative controllers some testnets run: `registerWithConfig` in one
+/// transaction and a single-amount `rentPrice`
+contract MockLegacyController {
+    uint256 public constant PRICE_PER_SECOND = 1 gwei;
+
+    mapping(bytes32 => address) public registrant;
+
+    function available(string calldata name) external view returns (bool) {
+        return registrant[keccak256(bytes(name))] == address(0);
+    }
+
+    function rentPrice(string calldata, uint256 duration) public pure returns (uint256) {
+        return duration * PRICE_PER_SECOND;
+    }
+
+    function makeCommitmentWithConfig(string calldata name, address owner, bytes32 secret, address resolver, address addr)
+        external
+        pure
+        returns (bytes32)
+    {
+        return keccak256(abi.encodePacked(keccak256(bytes(name)), owner, resolver, addr, secret));
+    }
+
+    function registerWithConfig(
+        string calldata name,
+        address owner,
+        uint256 duration,
+        bytes32,
+        address,
+        address
+    ) external payable {
+        bytes32 label = keccak256(bytes(name));
+        require(registrant[label] == address(0), "unavailable");
+        uint256 price = rentPrice(name, duration);
+        require(msg.value >= price, "insufficient value");
+
+        registrant[label] = owner;
+
+        if (msg.value > price) {
+            payable(msg.sender).transfer(msg.value - price);
+        }
+    }
+}
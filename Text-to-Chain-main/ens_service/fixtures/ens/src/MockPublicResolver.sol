@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import "./FaultInjectable.sol";
+import "./MockENSRegistry.sol";
+
+/// @notice Public resolver subset (addr, multi-coin addr and contenthash
+/// records, multicall) used by EnsMinter
+contract MockPublicResolver is FaultInjectable {
+    uint256 private constant COIN_TYPE_ETH = 60;
+
+    MockENSRegistry public immutable registry;
+    mapping(bytes32 => mapping(uint256 => bytes)) private addresses;
+    mapping(bytes32 => bytes) public contenthash;
+
+    event AddrChanged(bytes32 indexed node, address a);
+    event AddressChanged(bytes32 indexed node, uint256 coinType, bytes newAddress);
+    event ContenthashChanged(bytes32 indexed node, bytes hash);
+
+    constructor(MockENSRegistry _registry) {
+        registry = _registry;
+    }
+
+    function setAddr(bytes32 node, address a) external faultable {
+        require(registry.owner(node) == msg.sender, "not owner");
+        _setAddr(node, COIN_TYPE_ETH, abi.encodePacked(a));
+    }
+
+    function setAddr(bytes32 node, uint256 coinType, bytes calldata a) external faultable {
+        require(registry.owner(node) == msg.sender, "not owner");
+        _setAddr(node, coinType, a);
+    }
+
+    function addr(bytes32 node) external view returns (address) {
+        bytes memory a = addresses[node][COIN_TYPE_ETH];
+        return a.length == 20 ? address(bytes20(a)) : address(0);
+    }
+
+    function addr(bytes32 node, uint256 coinType) external view returns (bytes memory) {
+        return addresses[node][coinType];
+    }
+
+    function setContenthash(bytes32 node, bytes calldata hash) external faultable {
+        require(registry.owner(node) == msg.sender, "not owner");
+        contenthash[node] = hash;
+        emit ContenthashChanged(node, hash);
+    }
+
+    /// @notice Runs each call against this resolver as the caller; a fault
+    /// injected on any of them reverts the batch
+    function multicall(bytes[] calldata data) external returns (bytes[] memory results) {
+        results = new bytes[](data.length);
+        for (uint256 i = 0; i < data.length; i++) {
+            (bool ok, bytes memory result) = address(this).delegatecall(data[i]);
+            if (!ok) {
+                assembly {
+                    revert(add(result, 32), mload(result))
+                }
+            }
+            results[i] = result;
+        }
+    }
+
+    function _setAddr(bytes32 node, uint256 coinType, bytes memory a) private {
+        addresses[node][coinType] = a;
+        emit AddressChanged(node, coinType, a);
+        if (coinType == COIN_TYPE_ETH) {
+            emit AddrChanged(node, address(bytes20(a)));
+        }
+    }
+}
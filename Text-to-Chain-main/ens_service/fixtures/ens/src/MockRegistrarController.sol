@@ -0,0 +1,79 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import "./FaultInjectable.sol";
+
+/// @notice ETHRegistrarController subset used by DomainRegistrar:
+/// commit-reveal with a configurable minimum commitment age
+contract MockRegistrarController is FaultInjectable {
+    uint256 public constant PRICE_PER_SECOND = 1 gwei;
+
+    uint256 public minCommitmentAge;
+    mapping(bytes32 => uint256) public commitments;
+    mapping(bytes32 => address) public registrant;
+
+    constructor(uint256 _minCommitmentAge) {
+        minCommitmentAge = _minCommitmentAge;
+    }
+
+    function setMinCommitmentAge(uint256 age) external {
+        minCommitmentAge = age;
+    }
+
+    function available(string calldata name) external view returns (bool) {
+        return registrant[keccak256(bytes(name))] == address(0);
+    }
+
+    function rentPrice(string calldata, uint256 duration) public pure returns (uint256 base, uint256 premium) {
+        return (duration * PRICE_PER_SECOND, 0);
+    }
+
+    function makeCommitment(
+        string calldata name,
+        address owner,
+        uint256 duration,
+        bytes32 secret,
+        address resolver,
+        bytes[] calldata data,
+        bool reverseRecord,
+        uint16 ownerControlledFuses
+    ) public pure returns (bytes32) {
+        return keccak256(
+            abi.encode(keccak256(bytes(name)), owner, duration, secret, resolver, data, reverseRecord, ownerControlledFuses)
+        );
+    }
+
+    function commit(bytes32 commitment) external faultable {
+        commitments[commitment] = block.timestamp;
+    }
+
+    function register(
+        string calldata name,
+        address owner,
+        uint256 duration,
+        bytes32 secret,
+        address resolver,
+        bytes[] calldata data,
+        bool reverseRecord,
+        uint16 ownerControlledFuses
+    ) external payable faultable {
+        bytes32 commitment =
+            makeCommitment(name, owner, duration, secret, resolver, data, reverseRecord, ownerControlledFuses);
+        uint256 committedAt = commitments[commitment];
+        require(committedAt != 0, "no commitment");
+        require(committedAt + minCommitmentAge <= block.timestamp, "commitment too new");
+
+        bytes32 label = keccak256(bytes(name));
+        require(registrant[label] == address(0), "unavailable");
+
+        (uint256 base,) = rentPrice(name, duration);
+        require(msg.value >= base, "insufficient value");
+
+        delete commitments[commitment];
+        registrant[label] = owner;
+
+        if (msg.value > base) {
+            payable(msg.sender).transfer(msg.value - base);
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! TTL cache for ENS forward and reverse lookups
+//!
+//! Mainnet lookups are slow and rate limited, and the same few names come up
+//! again and again. Found records are kept for `ttl`; names and addresses
+//! with no record are kept for the shorter `negative_ttl` so a freshly set
+//! record shows up soon. RPC errors are never cached.
+//!
+//! sms-request-handler carries the same cache in `src/wallet/ens_cache.rs`;
+//! both read `ENS_CACHE_TTL_SECS` and `ENS_CACHE_NEGATIVE_TTL_SECS`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use ethers::prelude::*;
+
+const DEFAULT_TTL_SECS: u64 = 300;
+const DEFAULT_NEGATIVE_TTL_SECS: u64 = 60;
+
+struct Cached<T> {
+    value: T,
+    expires: Instant,
+}
+
+pub struct EnsCache {
+    forward: Mutex<HashMap<String, Cached<Option<Address>>>>,
+    reverse: Mutex<HashMap<Address, Cached<Option<String>>>>,
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+impl EnsCache {
+    pub fn new(ttl: Duration, negative_ttl: Duration) -> Self {
+        Self {
+            forward: Mutex::new(HashMap::new()),
+            reverse: Mutex::new(HashMap::new()),
+            ttl,
+            negative_ttl,
+        }
+    }
+
+    /// TTLs from `ENS_CACHE_TTL_SECS` / `ENS_CACHE_NEGATIVE_TTL_SECS` (default 300 / 60)
+    pub fn from_env() -> Self {
+        let secs = |name: &str, default: u64| {
+            std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+        };
+        Self::new(
+            Duration::from_secs(secs("ENS_CACHE_TTL_SECS", DEFAULT_TTL_SECS)),
+            Duration::from_secs(secs("ENS_CACHE_NEGATIVE_TTL_SECS", DEFAULT_NEGATIVE_TTL_SECS)),
+        )
+    }
+
+    fn expiry<T>(&self, value: &Option<T>) -> Instant {
+        Instant::now() + if value.is_some() { self.ttl } else { self.negative_ttl }
+    }
+
+    /// Cached forward lookup; `fetch` runs on a miss and Ok(None) means "no record"
+    pub async fn resolve_with<F, Fut, E>(&self, name: &str, fetch: F) -> Result<Option<Address>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<Address>, E>>,
+    {
+        let key = name.trim().to_lowercase();
+        if let Some(hit) = self.forward.lock().unwrap().get(&key).filter(|c| c.expires > Instant::now()) {
+            return Ok(hit.value);
+        }
+
+        let value = fetch().await?;
+        let expires = self.expiry(&value);
+        self.forward.lock().unwrap().insert(key, Cached { value, expires });
+        Ok(value)
+    }
+
+    /// Cached reverse lookup (primary name); Ok(None) means "no primary name"
+    pub async fn lookup_with<F, Fut, E>(&self, address: Address, fetch: F) -> Result<Option<String>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<String>, E>>,
+    {
+        if let Some(hit) = self.reverse.lock().unwrap().get(&address).filter(|c| c.expires > Instant::now()) {
+            return Ok(hit.value.clone());
+        }
+
+        let value = fetch().await?;
+        let expires = self.expiry(&value);
+        self.reverse.lock().unwrap().insert(address, Cached { value: value.clone(), expires });
+        Ok(value)
+    }
+
+    /// `ens_offchain::resolve_name` (onchain, wildcard and CCIP-Read names) through the cache
+    pub async fn resolve_name(&self, provider: &Provider<Http>, name: &str) -> Result<Option<Address>, ProviderError> {
+        self.resolve_with(name, || async { not_found_as_none(ens_offchain::resolve_name(provider, name).await) }).await
+    }
+
+    /// `provider.lookup_address` through the cache
+    pub async fn lookup_address(&self, provider: &Provider<Http>, address: Address) -> Result<Option<String>, ProviderError> {
+        self.lookup_with(address, || async { not_found_as_none(provider.lookup_address(address).await) }).await
+    }
+
+    /// Drop one name or `0x` address; true if it was cached
+    pub fn invalidate(&self, key: &str) -> bool {
+        match key.trim().parse::<Address>() {
+            Ok(address) => self.reverse.lock().unwrap().remove(&address).is_some(),
+            Err(_) => self.forward.lock().unwrap().remove(&key.trim().to_lowercase()).is_some(),
+        }
+    }
+
+    /// Drop everything; returns how many entries were cached
+    pub fn clear(&self) -> usize {
+        let mut forward = self.forward.lock().unwrap();
+        let mut reverse = self.reverse.lock().unwrap();
+        let count = forward.len() + reverse.len();
+        forward.clear();
+        reverse.clear();
+        count
+    }
+}
+
+/// ethers reports a missing resolver / record as an error
+fn not_found_as_none<T>(result: Result<T, ProviderError>) -> Result<Option<T>, ProviderError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(ProviderError::EnsError(_)) | Err(ProviderError::EnsNotOwned(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_caches_hits_and_misses() {
+        let cache = EnsCache::new(Duration::from_secs(60), Duration::ZERO);
+        let calls = AtomicUsize::new(0);
+        let alice: Address = "0x1111111111111111111111111111111111111111".parse().unwrap();
+        let fetch = |value: Option<Address>| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async move { Ok::<_, ()>(value) }
+        };
+
+        assert_eq!(cache.resolve_with("Alice.eth", || fetch(Some(alice))).await, Ok(Some(alice)));
+        assert_eq!(cache.resolve_with("alice.eth", || fetch(None)).await, Ok(Some(alice)));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Negative results expire on their own (zero TTL here); errors aren't cached
+        assert_eq!(cache.resolve_with("bob.eth", || fetch(None)).await, Ok(None));
+        assert_eq!(cache.resolve_with("bob.eth", || async { Err::<Option<Address>, _>(()) }).await, Err(()));
+        assert_eq!(cache.resolve_with("bob.eth", || fetch(Some(alice))).await, Ok(Some(alice)));
+
+        assert!(cache.invalidate("ALICE.eth"));
+        assert_eq!(cache.resolve_with("alice.eth", || fetch(None)).await, Ok(None));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,333 @@
+//! Fault-injection tests for EnsMinter and DomainRegistrar
+//!
+//! Each test spawns its own anvil node, deploys the ENS fixtures from
+//! `fixtures/ens` and breaks things on purpose: reverting calls, slow
+//! (interval) mining and nonce gaps. Needs foundry (`anvil` and `forge`
+//! on PATH), so the tests are ignored by default; run them with
+//! `cargo test chaos -- --ignored`.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use ethers::abi::{Abi, Tokenize};
+use ethers::contract::ContractFactory;
+use ethers::prelude::*;
+use ethers::utils::{id, Anvil, AnvilInstance};
+use serde_json::json;
+
+use crate::coins::CoinType;
+use crate::ens::{labelhash, namehash, EnsMinter, MintStep, RepairStep, ENSRegistry};
+use crate::register::{ControllerAbi, DomainRegistrar};
+
+type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+const PARENT_DOMAIN: &str = "ttcip.eth";
+
+/// Upper bound for any single flow, so a wedged node fails the test
+const FLOW_TIMEOUT: Duration = Duration::from_secs(90);
+
+abigen!(
+    FaultSwitch,
+    r#"[
+        function setFailing(bytes4 selector, bool fail) external
+    ]"#
+);
+
+/// A local chain with the ENS fixtures deployed and `ttcip.eth` owned by
+/// the test signer
+struct EnsFixture {
+    // Kills the node when dropped
+    _anvil: AnvilInstance,
+    client: Arc<Client>,
+    registry: Address,
+    resolver: Address,
+    controller: Address,
+}
+
+impl EnsFixture {
+    async fn start() -> Self {
+        assert!(foundry_installed(), "chaos tests need anvil and forge on PATH (https://getfoundry.sh)");
+        if let Err(e) = build_fixtures() {
+            panic!("forge build of fixtures/ens failed:\n{}", e);
+        }
+
+        let anvil = Anvil::new().spawn();
+        let provider = Provider::<Http>::try_from(anvil.endpoint())
+            .expect("anvil endpoint")
+            .interval(Duration::from_millis(50));
+        let wallet = LocalWallet::from(anvil.keys()[0].clone()).with_chain_id(anvil.chain_id());
+        let client = Arc::new(SignerMiddleware::new(provider, wallet));
+
+        let registry = deploy(&client, "MockENSRegistry", ()).await;
+        let resolver = deploy(&client, "MockPublicResolver", registry).await;
+        let controller = deploy(&client, "MockRegistrarController", U256::zero()).await;
+
+        // root -> eth -> ttcip.eth
+        let ens = ENSRegistry::new(registry, client.clone());
+        for (parent, label) in [([0u8; 32], "eth"), (namehash("eth"), "ttcip")] {
+            ens.set_subnode_owner(parent, labelhash(label), client.address())
+                .send()
+                .await
+                .expect("send setSubnodeOwner")
+                .await
+                .expect("mine setSubnodeOwner");
+        }
+
+        Self { _anvil: anvil, client, registry, resolver, controller }
+    }
+
+    fn minter(&self) -> EnsMinter {
+        EnsMinter::with_contracts(self.client.clone(), PARENT_DOMAIN, self.registry, self.resolver).unwrap()
+    }
+
+    async fn registrar(&self) -> DomainRegistrar {
+        DomainRegistrar::with_contracts(self.client.clone(), self.controller, self.resolver).await.unwrap()
+    }
+
+    /// Make `signature` on `contract` revert (or stop reverting)
+    async fn set_failing(&self, contract: Address, signature: &str, fail: bool) {
+        FaultSwitch::new(contract, self.client.clone())
+            .set_failing(id(signature), fail)
+            .send()
+            .await
+            .expect("send setFailing")
+            .await
+            .expect("mine setFailing");
+    }
+
+    /// Raw anvil/evm RPC call
+    async fn rpc(&self, method: &str, params: serde_json::Value) {
+        self.client
+            .provider()
+            .request::<_, serde_json::Value>(method, params)
+            .await
+            .unwrap_or_else(|e| panic!("{} failed: {}", method, e));
+    }
+}
+
+fn foundry_installed() -> bool {
+    ["anvil", "forge"]
+        .iter()
+        .all(|bin| Command::new(bin).arg("--version").output().is_ok())
+}
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/ens")
+}
+
+/// Compile the fixtures once per test run
+fn build_fixtures() -> &'static Result<(), String> {
+    static BUILT: OnceLock<Result<(), String>> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let output = Command::new("forge")
+            .arg("build")
+            .arg("--root")
+            .arg(fixtures_dir())
+            .output()
+            .map_err(|e| e.to_string())?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    })
+}
+
+async fn deploy<T: Tokenize>(client: &Arc<Client>, name: &str, args: T) -> Address {
+    let path = fixtures_dir().join(format!("out/{0}.sol/{0}.json", name));
+    let artifact: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).expect("fixture artifact")).expect("artifact json");
+    let abi: Abi = serde_json::from_value(artifact["abi"].clone()).expect("artifact abi");
+    let bytecode: Bytes = artifact["bytecode"]["object"]
+        .as_str()
+        .expect("artifact bytecode")
+        .parse()
+        .expect("bytecode hex");
+
+    ContractFactory::new(abi, bytecode, client.clone())
+        .deploy(args)
+        .expect("constructor args")
+        .send()
+        .await
+        .unwrap_or_else(|e| panic!("deploying {} failed: {}", name, e))
+        .address()
+}
+
+async fn within<F: Future>(flow: F) -> F::Output {
+    tokio::time::timeout(FLOW_TIMEOUT, flow).await.expect("flow timed out")
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_mint_on_local_chain() {
+    let fixture = EnsFixture::start().await;
+    let minter = fixture.minter();
+    let alice = Address::from_low_u64_be(0xA11CE);
+
+    let outcome = within(minter.mint_subdomain("Alice", alice)).await.unwrap();
+
+    assert_eq!(outcome.subdomain, "alice.ttcip.eth");
+    assert!(!outcome.owner_tx.tx_hash.is_zero());
+    assert!(outcome.gas_used >= outcome.create_tx.gas_used + outcome.resolver_tx.gas_used + outcome.addr_tx.gas_used);
+    assert_eq!(minter.resolve_subdomain("alice").await.unwrap(), alice);
+    assert_eq!(minter.get_subdomain_owner("alice").await.unwrap(), alice);
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_contenthash_set_for_user_owned_name() {
+    let fixture = EnsFixture::start().await;
+    let minter = fixture.minter();
+    let dave = Address::from_low_u64_be(0xDA7E);
+    within(minter.mint_subdomain("dave", dave)).await.unwrap();
+
+    let contenthash = crate::profile::ipfs_contenthash("QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4").unwrap();
+    let txs = within(minter.set_contenthash("dave", contenthash.clone())).await.unwrap();
+
+    // Reclaim, set, hand back
+    assert_eq!(txs.len(), 3);
+    assert_eq!(minter.get_contenthash("dave").await.unwrap(), contenthash);
+    assert_eq!(minter.get_subdomain_owner("dave").await.unwrap(), dave);
+    assert!(minter.set_contenthash("nobody", contenthash).await.is_err());
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_chain_addrs_set_on_mint() {
+    let fixture = EnsFixture::start().await;
+    let minter = fixture.minter().with_chain_coins(vec![CoinType::Polygon, CoinType::Base]);
+    let erin = Address::from_low_u64_be(0xE21);
+
+    within(minter.mint_subdomain("erin", erin)).await.unwrap();
+
+    assert_eq!(minter.resolve_subdomain("erin").await.unwrap(), erin);
+    assert_eq!(minter.get_coin_addr("erin", CoinType::Polygon).await.unwrap(), erin.as_bytes());
+    assert_eq!(minter.get_coin_addr("erin", CoinType::Base).await.unwrap(), erin.as_bytes());
+    assert!(minter.get_coin_addr("erin", CoinType::Arbitrum).await.unwrap().is_empty());
+
+    // A Solana key set later, with the name handed back to erin
+    let key = CoinType::Solana.encode_address("4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T").unwrap();
+    let txs = within(minter.set_coin_addr("erin", CoinType::Solana, &key)).await.unwrap();
+    assert_eq!(txs.len(), 3);
+    assert_eq!(minter.get_coin_addr("erin", CoinType::Solana).await.unwrap(), key);
+    assert_eq!(minter.get_subdomain_owner("erin").await.unwrap(), erin);
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_revert_mid_mint_is_repaired() {
+    let fixture = EnsFixture::start().await;
+    let minter = fixture.minter();
+    let bob = Address::from_low_u64_be(0xB0B);
+
+    // Resolver rejects setAddr: owner and resolver are set, addr is not
+    fixture.set_failing(fixture.resolver, "setAddr(bytes32,address)", true).await;
+    let error = within(minter.mint_subdomain("bob", bob)).await.unwrap_err();
+    assert_eq!(error.failed_step, MintStep::SetAddr);
+    assert_eq!(
+        error.completed.iter().map(|(step, _)| *step).collect::<Vec<_>>(),
+        vec![MintStep::CreateSubdomain, MintStep::SetResolver]
+    );
+
+    let records = minter.get_subdomain_records("bob").await.unwrap();
+    assert_eq!(records.owner, fixture.client.address());
+    assert_eq!(records.resolver, fixture.resolver);
+    assert_eq!(records.addr, Address::zero());
+
+    fixture.set_failing(fixture.resolver, "setAddr(bytes32,address)", false).await;
+    let report = within(minter.verify_and_repair("bob", bob)).await.unwrap();
+
+    assert_eq!(report.steps, vec![RepairStep::SetAddr, RepairStep::SetOwner]);
+    assert_eq!(minter.resolve_subdomain("bob").await.unwrap(), bob);
+    assert_eq!(minter.get_subdomain_owner("bob").await.unwrap(), bob);
+
+    // Second run finds nothing to do
+    assert!(within(minter.verify_and_repair("bob", bob)).await.unwrap().was_healthy());
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_mint_with_slow_mining() {
+    let fixture = EnsFixture::start().await;
+    let minter = fixture.minter();
+    let carol = Address::from_low_u64_be(0xCA201);
+
+    // One block every 2 seconds instead of one per transaction
+    fixture.rpc("evm_setAutomine", json!([false])).await;
+    fixture.rpc("evm_setIntervalMining", json!([2])).await;
+
+    within(minter.mint_subdomain("carol", carol)).await.unwrap();
+
+    assert_eq!(minter.resolve_subdomain("carol").await.unwrap(), carol);
+    assert_eq!(minter.get_subdomain_owner("carol").await.unwrap(), carol);
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_mint_across_nonce_gap() {
+    let fixture = EnsFixture::start().await;
+    let minter = fixture.minter();
+    let dave = Address::from_low_u64_be(0xDA7E);
+    let signer = fixture.client.address();
+
+    // Queue a transaction one nonce ahead; it stays pending until the gap fills
+    let nonce = fixture.client.get_transaction_count(signer, None).await.unwrap();
+    let queued = TransactionRequest::new()
+        .to(Address::from_low_u64_be(0xFEED))
+        .value(1u64)
+        .nonce(nonce + 1);
+    let queued_hash = *fixture.client.send_transaction(queued, None).await.unwrap();
+
+    within(minter.mint_subdomain("dave", dave)).await.unwrap();
+
+    assert_eq!(minter.resolve_subdomain("dave").await.unwrap(), dave);
+    let receipt = fixture.client.get_transaction_receipt(queued_hash).await.unwrap();
+    assert!(receipt.is_some(), "queued transaction should be mined once the gap is filled");
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_register_survives_reverted_register() {
+    let fixture = EnsFixture::start().await;
+    let registrar = fixture.registrar().await;
+    let owner = fixture.client.address();
+    let register_sig = "register(string,address,uint256,bytes32,address,bytes[],bool,uint16)";
+
+    fixture.set_failing(fixture.controller, register_sig, true).await;
+    assert!(within(registrar.register_domain("chaos", owner, 1)).await.is_err());
+    // Committed but never registered - still available
+    assert!(registrar.is_available("chaos").await.unwrap());
+
+    fixture.set_failing(fixture.controller, register_sig, false).await;
+    let name = within(registrar.register_domain("chaos", owner, 1)).await.unwrap();
+
+    assert_eq!(name, "chaos.eth");
+    assert!(!registrar.is_available("chaos").await.unwrap());
+}
+
+#[tokio::test]
+#[ignore = "needs foundry (anvil and forge on PATH); run with --ignored"]
+async fn test_register_detects_controller_abi() {
+    let fixture = EnsFixture::start().await;
+    let owner = fixture.client.address();
+
+    let wrapped = fixture.registrar().await;
+    assert_eq!(wrapped.abi(), ControllerAbi::Wrapped);
+    assert!(!wrapped.is_commitment_free());
+
+    // A legacy controller with no commit step registers in one transaction
+    let controller = deploy(&fixture.client, "MockLegacyController", ()).await;
+    let legacy = DomainRegistrar::with_contracts(fixture.client.clone(), controller, fixture.resolver).await.unwrap();
+    assert_eq!(legacy.abi(), ControllerAbi::LegacyWithConfig);
+    assert!(legacy.is_commitment_free());
+    assert_eq!(legacy.get_price("legacy", 10).await.unwrap(), U256::from(10) * U256::exp10(9));
+    assert_eq!(within(legacy.register_domain("legacy", owner, 1)).await.unwrap(), "legacy.eth");
+    assert!(!legacy.is_available("legacy").await.unwrap());
+
+    // Anything else is refused up front
+    let err = DomainRegistrar::with_contracts(fixture.client.clone(), fixture.registry, fixture.resolver).await;
+    assert!(err.is_err());
+}
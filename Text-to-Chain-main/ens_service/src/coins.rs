@@ -0,0 +1,168 @@
+//! Per-chain addr records (ENSIP-9 / ENSIP-11)
+//!
+//! The Public Resolver keeps one address per coin type. ETH is SLIP-44 type
+//! 60 and is what the plain `setAddr(bytes32,address)` writes. EVM chains use
+//! ENSIP-11 types (`0x80000000 | chain id`) holding the same 20 bytes, and
+//! other chains use their SLIP-44 type, e.g. 501 for a Solana public key.
+
+use std::fmt;
+use std::str::FromStr;
+
+use ethers::types::Address;
+use ethers::utils::to_checksum;
+
+use crate::profile::{base58_decode, base58_encode};
+
+/// ENSIP-11 flag marking an EVM chain id as a coin type
+const EVM_COIN_FLAG: u64 = 0x8000_0000;
+
+/// Chains a subdomain can hold an address for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CoinType {
+    Eth,
+    Polygon,
+    Base,
+    Arbitrum,
+    Solana,
+}
+
+impl CoinType {
+    pub const ALL: [CoinType; 5] = [CoinType::Eth, CoinType::Polygon, CoinType::Base, CoinType::Arbitrum, CoinType::Solana];
+
+    /// Coin type as stored on the resolver
+    pub fn value(self) -> u64 {
+        match self {
+            CoinType::Eth => 60,
+            CoinType::Solana => 501,
+            evm => EVM_COIN_FLAG | evm.chain_id().unwrap_or_default(),
+        }
+    }
+
+    /// EVM chain id, None for non-EVM chains
+    pub fn chain_id(self) -> Option<u64> {
+        match self {
+            CoinType::Eth => Some(1),
+            CoinType::Polygon => Some(137),
+            CoinType::Base => Some(8453),
+            CoinType::Arbitrum => Some(42161),
+            CoinType::Solana => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CoinType::Eth => "eth",
+            CoinType::Polygon => "polygon",
+            CoinType::Base => "base",
+            CoinType::Arbitrum => "arbitrum",
+            CoinType::Solana => "solana",
+        }
+    }
+
+    /// Record bytes for an address written the chain's usual way
+    /// (`0x...` for EVM chains, base58 for Solana)
+    pub fn encode_address(self, address: &str) -> eyre::Result<Vec<u8>> {
+        let address = address.trim();
+        if self.chain_id().is_some() {
+            let address: Address = address.parse().map_err(|_| eyre::eyre!("invalid {} address {}", self, address))?;
+            return Ok(address.as_bytes().to_vec());
+        }
+        match base58_decode(address) {
+            Some(key) if key.len() == 32 => Ok(key),
+            _ => eyre::bail!("invalid {} address {}", self, address),
+        }
+    }
+
+    /// Address for record bytes, None when they don't fit the chain
+    pub fn format_address(self, bytes: &[u8]) -> Option<String> {
+        if self.chain_id().is_some() {
+            (bytes.len() == 20).then(|| to_checksum(&Address::from_slice(bytes), None))
+        } else {
+            (bytes.len() == 32).then(|| base58_encode(bytes))
+        }
+    }
+}
+
+impl fmt::Display for CoinType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for CoinType {
+    type Err = eyre::Report;
+
+    /// A chain name (`polygon`, `matic`, `sol`, ...) or a coin type number
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        let s = s.trim().to_lowercase();
+        let by_name = match s.as_str() {
+            "eth" | "ethereum" => Some(CoinType::Eth),
+            "polygon" | "matic" => Some(CoinType::Polygon),
+            "base" => Some(CoinType::Base),
+            "arbitrum" | "arb" => Some(CoinType::Arbitrum),
+            "solana" | "sol" => Some(CoinType::Solana),
+            _ => None,
+        };
+        by_name
+            .or_else(|| {
+                let value: u64 = s.parse().ok()?;
+                CoinType::ALL.into_iter().find(|coin| coin.value() == value)
+            })
+            .ok_or_else(|| eyre::eyre!("unknown coin type {}", s))
+    }
+}
+
+/// EVM chains from `ENS_CHAIN_COINS` (e.g. `polygon,base,arbitrum`) whose
+/// addr records are set to the user's address at mint time
+pub fn chain_coins_from_env() -> eyre::Result<Vec<CoinType>> {
+    let Some(list) = std::env::var("ENS_CHAIN_COINS").ok().filter(|list| !list.trim().is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let mut coins = Vec::new();
+    for name in list.split(',').filter(|name| !name.trim().is_empty()) {
+        let coin: CoinType = name.parse()?;
+        if coin.chain_id().is_none() {
+            eyre::bail!("ENS_CHAIN_COINS: {} is not an EVM chain; set it per name with `addr`", coin);
+        }
+        // ETH is always set by the mint itself
+        if coin != CoinType::Eth && !coins.contains(&coin) {
+            coins.push(coin);
+        }
+    }
+    Ok(coins)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coin_type_values() {
+        assert_eq!(CoinType::Eth.value(), 60);
+        assert_eq!(CoinType::Solana.value(), 501);
+        // ENSIP-11 examples
+        assert_eq!(CoinType::Polygon.value(), 2147483785);
+        assert_eq!(CoinType::Base.value(), 2147492101);
+        assert_eq!(CoinType::Arbitrum.value(), 2147525809);
+
+        assert_eq!("MATIC".parse::<CoinType>().unwrap(), CoinType::Polygon);
+        assert_eq!("2147492101".parse::<CoinType>().unwrap(), CoinType::Base);
+        assert!("dogecoin".parse::<CoinType>().is_err());
+    }
+
+    #[test]
+    fn test_address_encoding() {
+        let evm = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let bytes = CoinType::Base.encode_address(evm).unwrap();
+        assert_eq!(bytes.len(), 20);
+        assert_eq!(CoinType::Base.format_address(&bytes).as_deref(), Some(evm));
+        assert!(CoinType::Polygon.encode_address("not-an-address").is_err());
+
+        let solana = "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T";
+        let key = CoinType::Solana.encode_address(solana).unwrap();
+        assert_eq!(key.len(), 32);
+        assert_eq!(CoinType::Solana.format_address(&key).as_deref(), Some(solana));
+        assert!(CoinType::Solana.encode_address(evm).is_err());
+        assert!(CoinType::Solana.format_address(&bytes).is_none());
+    }
+}
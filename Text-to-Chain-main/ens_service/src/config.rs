@@ -0,0 +1,110 @@
+//! Chain settings shared by every command
+//!
+//! Read from the environment, with `.env` filling in whatever isn't set (see
+//! `.env.example`). Every problem is reported at once, and `Debug` never
+//! shows the private key.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use ethers::signers::LocalWallet;
+use reqwest::Url;
+
+#[derive(Clone)]
+pub struct EnsConfig {
+    /// Signing key; only commands that send transactions need it
+    pub private_key: Option<String>,
+    /// Sepolia RPC endpoint
+    pub rpc_url: String,
+    /// Parent domain names are minted under, e.g. `ttc.eth`
+    pub parent_domain: String,
+}
+
+impl EnsConfig {
+    /// Load `.env`, then read and check the environment
+    pub fn load() -> eyre::Result<Self> {
+        dotenvy::dotenv().ok();
+        let vars: BTreeMap<String, String> = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Self::from_vars(&vars)
+    }
+
+    fn from_vars(vars: &BTreeMap<String, String>) -> eyre::Result<Self> {
+        let get = |name: &str| vars.get(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut problems = Vec::new();
+
+        let private_key = get("PRIVATE_KEY");
+        if private_key.as_ref().is_some_and(|key| key.trim_start_matches("0x").parse::<LocalWallet>().is_err()) {
+            problems.push("PRIVATE_KEY: not a private key (expected 64 hex characters)".to_string());
+        }
+        let rpc_url = get("RPC_URL").unwrap_or_default();
+        match Url::parse(&rpc_url) {
+            _ if rpc_url.is_empty() => problems.push("RPC_URL: required but not set".to_string()),
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(url) => problems.push(format!("RPC_URL: expected an http:// or https:// URL, not {}://", url.scheme())),
+            Err(e) => problems.push(format!("RPC_URL: not a URL ({})", e)),
+        }
+        let parent_domain = get("PARENT_DOMAIN").unwrap_or_default().to_lowercase();
+        if parent_domain.is_empty() {
+            problems.push("PARENT_DOMAIN: required but not set".to_string());
+        } else if !parent_domain.ends_with(".eth") || parent_domain.starts_with('.') {
+            problems.push(format!("PARENT_DOMAIN: expected a .eth name, not {:?}", parent_domain));
+        }
+
+        if !problems.is_empty() {
+            eyre::bail!("Invalid configuration (see .env.example):\n  {}", problems.join("\n  "));
+        }
+        Ok(Self { private_key: private_key.map(|key| key.trim_start_matches("0x").to_string()), rpc_url, parent_domain })
+    }
+
+    /// The key transactions are signed with
+    pub fn signing_key(&self) -> eyre::Result<&str> {
+        self.private_key.as_deref().ok_or_else(|| eyre::eyre!("PRIVATE_KEY must be set to send transactions"))
+    }
+}
+
+impl fmt::Debug for EnsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnsConfig")
+            .field("private_key", &self.private_key.as_ref().map(|_| "<redacted>"))
+            .field("rpc_url", &self.rpc_url)
+            .field("parent_domain", &self.parent_domain)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    fn load(vars: &[(&str, &str)]) -> eyre::Result<EnsConfig> {
+        EnsConfig::from_vars(&vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+    }
+
+    #[test]
+    fn test_every_problem_reported() {
+        let error = load(&[("PRIVATE_KEY", "0x1234"), ("RPC_URL", "sepolia.example.com")]).unwrap_err().to_string();
+        assert_eq!(
+            error,
+            "Invalid configuration (see .env.example):\n  \
+             PRIVATE_KEY: not a private key (expected 64 hex characters)\n  \
+             RPC_URL: not a URL (relative URL without a base)\n  \
+             PARENT_DOMAIN: required but not set"
+        );
+    }
+
+    #[test]
+    fn test_private_key_only_needed_to_sign() {
+        let config = load(&[("RPC_URL", "https://sepolia.example.com"), ("PARENT_DOMAIN", "TTC.eth")]).unwrap();
+        assert_eq!(config.parent_domain, "ttc.eth");
+        assert!(config.signing_key().is_err());
+
+        let config = load(&[("PRIVATE_KEY", KEY), ("RPC_URL", "https://sepolia.example.com"), ("PARENT_DOMAIN", "ttc.eth")]).unwrap();
+        assert_eq!(config.signing_key().unwrap(), KEY.trim_start_matches("0x"));
+        let debug = format!("{:?}", config);
+        assert!(!debug.contains("4c0883a6") && debug.contains("<redacted>"));
+    }
+}
@@ -5,13 +5,16 @@ use ethers::prelude::*;
 use ethers::utils::keccak256;
 use std::sync::Arc;
 
+use crate::coins::CoinType;
+
 /// ENS Registry contract address (same on mainnet and Sepolia)
 pub const ENS_REGISTRY: &str = "0x00000000000C2E074eC69A0dFb2997BA6C7d2e1e";
 
 /// Public Resolver contract address on Sepolia
 pub const PUBLIC_RESOLVER_SEPOLIA: &str = "0xE99638b40E4Fff0129D56f03b55b6bbC4BBE49b5";
 
-/// ETH Registrar Controller on Sepolia (for registering .eth domains)
+/// ETH Registrar Controller on Sepolia (for registering .eth domains); used
+/// when `controller.ens.eth` doesn't resolve and ENS_CONTROLLER is unset
 pub const ETH_REGISTRAR_CONTROLLER_SEPOLIA: &str = "0xfb3cE5D01e0f33f41DbB39035dB9745962F1f968";
 
 // Generate contract bindings for ENS Registry
@@ -22,6 +25,7 @@ abigen!(
         function setResolver(bytes32 node, address resolver) external
         function owner(bytes32 node) external view returns (address)
         function resolver(bytes32 node) external view returns (address)
+        event NewOwner(bytes32 indexed node, bytes32 indexed label, address owner)
     ]"#
 );
 
@@ -31,10 +35,19 @@ abigen!(
     r#"[
         function setAddr(bytes32 node, address addr) external
         function addr(bytes32 node) external view returns (address)
+        function setAddr(bytes32 node, uint256 coinType, bytes a) external
+        function addr(bytes32 node, uint256 coinType) external view returns (bytes)
+        function multicall(bytes[] data) external returns (bytes[] results)
+        function setContenthash(bytes32 node, bytes hash) external
+        function contenthash(bytes32 node) external view returns (bytes)
+        event AddrChanged(bytes32 indexed node, address a)
+        event AddressChanged(bytes32 indexed node, uint256 coinType, bytes newAddress)
+        event ContenthashChanged(bytes32 indexed node, bytes hash)
     ]"#
 );
 
-// Generate contract bindings for ETH Registrar Controller (for registering .eth domains)
+// Generate contract bindings for ETH Registrar Controller (for registering .eth domains),
+// the NameWrapper-era ABI
 abigen!(
     ETHRegistrarController,
     r#"[
@@ -44,6 +57,20 @@ abigen!(
         function commit(bytes32 commitment) external
         function register(string name, address owner, uint256 duration, bytes32 secret, address resolver, bytes[] data, bool reverseRecord, uint16 ownerControlledFuses) external payable
         function minCommitmentAge() external view returns (uint256)
+        function maxCommitmentAge() external view returns (uint256)
+        function commitments(bytes32 commitment) external view returns (uint256)
+    ]"#
+);
+
+// Pre-NameWrapper controller, still deployed by some testnets and forks
+abigen!(
+    LegacyETHRegistrarController,
+    r#"[
+        function rentPrice(string name, uint256 duration) external view returns (uint256)
+        function makeCommitment(string name, address owner, bytes32 secret) external pure returns (bytes32)
+        function makeCommitmentWithConfig(string name, address owner, bytes32 secret, address resolver, address addr) external pure returns (bytes32)
+        function register(string name, address owner, uint256 duration, bytes32 secret) external payable
+        function registerWithConfig(string name, address owner, uint256 duration, bytes32 secret, address resolver, address addr) external payable
     ]"#
 );
 
@@ -70,12 +97,202 @@ pub fn namehash(name: &str) -> [u8; 32] {
     node
 }
 
+/// Node of a direct subdomain given the parent node and the label's hash
+/// (the same value `namehash` produces for `label.parent`)
+pub fn subnode(parent_node: [u8; 32], label_hash: [u8; 32]) -> [u8; 32] {
+    let mut combined = [0u8; 64];
+    combined[..32].copy_from_slice(&parent_node);
+    combined[32..].copy_from_slice(&label_hash);
+    keccak256(combined)
+}
+
 /// Calculate the labelhash (keccak256 of a label)
 /// e.g., labelhash("alice") -> bytes32  
 pub fn labelhash(label: &str) -> [u8; 32] {
     keccak256(label.as_bytes())
 }
 
+/// On-chain records of a subdomain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubdomainRecords {
+    pub owner: Address,
+    pub resolver: Address,
+    /// addr record as stored on our Public Resolver
+    pub addr: Address,
+}
+
+impl SubdomainRecords {
+    /// Read a subdomain's records from the Sepolia registry and Public
+    /// Resolver; needs no signing key
+    pub async fn read(provider: Arc<Provider<Http>>, subdomain: &str) -> eyre::Result<Self> {
+        let registry = ENSRegistry::new(ENS_REGISTRY.parse::<Address>()?, provider.clone());
+        let resolver = PublicResolver::new(PUBLIC_RESOLVER_SEPOLIA.parse::<Address>()?, provider);
+        Self::read_from(&registry, &resolver, namehash(subdomain)).await
+    }
+
+    async fn read_from<M: Middleware + 'static>(
+        registry: &ENSRegistry<M>,
+        resolver: &PublicResolver<M>,
+        node: [u8; 32],
+    ) -> eyre::Result<Self> {
+        let owner = registry.owner(node).call().await?;
+        let resolver_address = registry.resolver(node).call().await?;
+        let addr = resolver.addr(node).call().await.unwrap_or_default();
+
+        Ok(Self { owner, resolver: resolver_address, addr })
+    }
+}
+
+/// A single repair transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepairStep {
+    /// Take ownership of the subdomain so its records can be edited
+    ClaimOwnership,
+    /// Point the subdomain at the Public Resolver
+    SetResolver,
+    /// Set the addr record on the Public Resolver
+    SetAddr,
+    /// Hand ownership (back) to the expected address
+    SetOwner,
+}
+
+/// Result of a verify-and-repair run
+#[derive(Debug, Clone)]
+pub struct RepairReport {
+    pub subdomain: String,
+    pub before: SubdomainRecords,
+    pub steps: Vec<RepairStep>,
+    pub tx_hashes: Vec<H256>,
+}
+
+impl RepairReport {
+    /// True when nothing needed fixing
+    pub fn was_healthy(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// Work out which transactions are needed to bring a subdomain to the expected state
+/// (owned by and resolving to `expected_addr` through `resolver`).
+/// `minter` is the parent owner, which can always reclaim the subdomain.
+pub fn plan_repair(
+    records: &SubdomainRecords,
+    expected_addr: Address,
+    resolver: Address,
+    minter: Address,
+) -> Vec<RepairStep> {
+    let resolver_ok = records.resolver == resolver;
+    let addr_ok = records.addr == expected_addr;
+    let owner_ok = records.owner == expected_addr;
+
+    let mut steps = Vec::new();
+    let needs_records = !resolver_ok || !addr_ok;
+
+    if needs_records && records.owner != minter {
+        steps.push(RepairStep::ClaimOwnership);
+    }
+    if !resolver_ok {
+        steps.push(RepairStep::SetResolver);
+    }
+    if !addr_ok {
+        steps.push(RepairStep::SetAddr);
+    }
+    if !owner_ok || steps.contains(&RepairStep::ClaimOwnership) {
+        steps.push(RepairStep::SetOwner);
+    }
+
+    steps
+}
+
+/// A single transaction of a subdomain mint, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MintStep {
+    /// Create the subdomain owned by the minter
+    CreateSubdomain,
+    /// Point the subdomain at the Public Resolver
+    SetResolver,
+    /// Set the addr record to the target, plus any chain records the
+    /// minter mirrors it to
+    SetAddr,
+    /// Hand ownership to the target
+    TransferOwnership,
+}
+
+impl MintStep {
+    pub const ALL: [MintStep; 4] = [
+        MintStep::CreateSubdomain,
+        MintStep::SetResolver,
+        MintStep::SetAddr,
+        MintStep::TransferOwnership,
+    ];
+}
+
+/// Receipt details kept for one mined transaction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StepReceipt {
+    pub tx_hash: H256,
+    pub block_number: U64,
+    pub gas_used: U256,
+}
+
+impl StepReceipt {
+    /// A mined receipt; reverted transactions are errors
+    fn from_receipt(receipt: &TransactionReceipt) -> eyre::Result<Self> {
+        if receipt.status == Some(U64::zero()) {
+            eyre::bail!("transaction {:?} reverted", receipt.transaction_hash);
+        }
+        Ok(Self {
+            tx_hash: receipt.transaction_hash,
+            block_number: receipt.block_number.unwrap_or_default(),
+            gas_used: receipt.gas_used.unwrap_or_default(),
+        })
+    }
+}
+
+/// A completed mint
+#[derive(Debug, Clone)]
+pub struct MintOutcome {
+    pub subdomain: String,
+    /// setSubnodeOwner creating the name for the minter
+    pub create_tx: StepReceipt,
+    pub resolver_tx: StepReceipt,
+    pub addr_tx: StepReceipt,
+    /// setSubnodeOwner handing the name to the target
+    pub owner_tx: StepReceipt,
+    /// Total across all four transactions
+    pub gas_used: U256,
+}
+
+/// A mint that stopped part-way
+#[derive(Debug)]
+pub struct MintError {
+    pub subdomain: String,
+    pub failed_step: MintStep,
+    /// Steps that were mined before the failure, in order
+    pub completed: Vec<(MintStep, StepReceipt)>,
+    pub source: eyre::Report,
+}
+
+impl MintError {
+    /// Some transactions were mined, so the name is held by the minter with
+    /// incomplete records until `verify_and_repair` runs
+    pub fn is_partial(&self) -> bool {
+        !self.completed.is_empty()
+    }
+}
+
+impl std::fmt::Display for MintError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "minting {} failed at {:?}", self.subdomain, self.failed_step)?;
+        if self.is_partial() {
+            write!(f, " after {} of {} steps", self.completed.len(), MintStep::ALL.len())?;
+        }
+        write!(f, ": {}", self.source)
+    }
+}
+
+impl std::error::Error for MintError {}
+
 /// ENS Minter - handles on-chain subdomain registration
 /// Uses concrete type to avoid lifetime issues with async
 pub struct EnsMinter {
@@ -83,6 +300,8 @@ pub struct EnsMinter {
     resolver: PublicResolver<SignerMiddleware<Provider<Http>, LocalWallet>>,
     parent_domain: String,
     parent_node: [u8; 32],
+    /// EVM chains whose addr record is set alongside ETH
+    chain_coins: Vec<CoinType>,
 }
 
 impl EnsMinter {
@@ -91,9 +310,17 @@ impl EnsMinter {
         client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
         parent_domain: &str,
     ) -> eyre::Result<Self> {
-        let registry_address: Address = ENS_REGISTRY.parse()?;
-        let resolver_address: Address = PUBLIC_RESOLVER_SEPOLIA.parse()?;
-        
+        Self::with_contracts(client, parent_domain, ENS_REGISTRY.parse()?, PUBLIC_RESOLVER_SEPOLIA.parse()?)
+    }
+
+    /// Create a minter against a specific registry and resolver
+    /// (e.g. fixtures deployed on a local anvil node)
+    pub fn with_contracts(
+        client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
+        parent_domain: &str,
+        registry_address: Address,
+        resolver_address: Address,
+    ) -> eyre::Result<Self> {
         let registry = ENSRegistry::new(registry_address, client.clone());
         let resolver = PublicResolver::new(resolver_address, client);
         
@@ -104,9 +331,37 @@ impl EnsMinter {
             resolver,
             parent_domain: parent_domain.to_string(),
             parent_node,
+            chain_coins: Vec::new(),
         })
     }
+
+    /// Also set these EVM chains' addr records to the target on mint and
+    /// repair, in the same transaction as the ETH record
+    pub fn with_chain_coins(mut self, coins: Vec<CoinType>) -> Self {
+        self.chain_coins = coins.into_iter().filter(|coin| *coin != CoinType::Eth && coin.chain_id().is_some()).collect();
+        self
+    }
     
+    /// Address the minter signs with
+    pub fn address(&self) -> Address {
+        self.registry.client().address()
+    }
+
+    /// Domain the minter creates subdomains under
+    pub fn parent_domain(&self) -> &str {
+        &self.parent_domain
+    }
+
+    /// Chain the minter signs for
+    pub fn chain_id(&self) -> u64 {
+        self.registry.client().signer().chain_id()
+    }
+
+    /// Current gas price on the minter's chain
+    pub async fn gas_price(&self) -> eyre::Result<U256> {
+        Ok(self.registry.client().get_gas_price().await?)
+    }
+
     /// Check if we own the parent domain
     pub async fn verify_ownership(&self, expected_owner: Address) -> eyre::Result<bool> {
         let owner = self.registry.owner(self.parent_node).call().await?;
@@ -122,57 +377,213 @@ impl EnsMinter {
     }
     
     /// Mint a new subdomain
-    /// This sets the subdomain owner and points it to the resolver
+    /// The minter creates the subdomain for itself so it is authorised to set
+    /// the resolver and addr records, then hands ownership to the target.
+    /// A failure part-way leaves the name with the minter; the error says
+    /// which steps completed and `verify_and_repair` finishes the job.
     pub async fn mint_subdomain(
         &self,
         label: &str,
         target_address: Address,
-    ) -> eyre::Result<String> {
+    ) -> Result<MintOutcome, MintError> {
+        let label = label.to_lowercase();
+        let subdomain = format!("{}.{}", label, self.parent_domain);
+        let mut completed = Vec::with_capacity(MintStep::ALL.len());
+
+        for (i, step) in MintStep::ALL.into_iter().enumerate() {
+            tracing::info!(%subdomain, step = ?step, "Mint step {}/{}", i + 1, MintStep::ALL.len());
+            match self.run_mint_step(step, &label, target_address).await {
+                Ok(receipt) => {
+                    tracing::info!(%subdomain, step = ?step, tx = ?receipt.tx_hash, gas_used = %receipt.gas_used, "Mint step confirmed");
+                    completed.push((step, receipt));
+                }
+                Err(source) => {
+                    tracing::warn!(%subdomain, step = ?step, completed = completed.len(), "Mint step failed: {}", source);
+                    return Err(MintError { subdomain, failed_step: step, completed, source });
+                }
+            }
+        }
+
+        let receipt = |step: MintStep| completed.iter().find(|(s, _)| *s == step).map(|(_, r)| *r).unwrap_or_default();
+        let outcome = MintOutcome {
+            create_tx: receipt(MintStep::CreateSubdomain),
+            resolver_tx: receipt(MintStep::SetResolver),
+            addr_tx: receipt(MintStep::SetAddr),
+            owner_tx: receipt(MintStep::TransferOwnership),
+            gas_used: completed.iter().map(|(_, r)| r.gas_used).fold(U256::zero(), |total, gas| total + gas),
+            subdomain,
+        };
+        tracing::info!(subdomain = %outcome.subdomain, gas_used = %outcome.gas_used, "Subdomain minted");
+        Ok(outcome)
+    }
+
+    /// Send one mint transaction and wait for its receipt
+    async fn run_mint_step(&self, step: MintStep, label: &str, target_address: Address) -> eyre::Result<StepReceipt> {
+        let label_hash = labelhash(label);
+        let subdomain_node = subnode(self.parent_node, label_hash);
+        let receipt = match step {
+            MintStep::CreateSubdomain => {
+                let tx = self.registry.set_subnode_owner(self.parent_node, label_hash, self.address());
+                tx.send().await?.await?
+            }
+            MintStep::SetResolver => {
+                let tx = self.registry.set_resolver(subdomain_node, self.resolver.address());
+                tx.send().await?.await?
+            }
+            MintStep::SetAddr => self.send_addr_records(subdomain_node, target_address).await?,
+            MintStep::TransferOwnership => {
+                let tx = self.registry.set_subnode_owner(self.parent_node, label_hash, target_address);
+                tx.send().await?.await?
+            }
+        };
+        let receipt = receipt.ok_or_else(|| eyre::eyre!("transaction dropped before it was mined"))?;
+        StepReceipt::from_receipt(&receipt)
+    }
+    
+    /// Set the ETH addr record, batched through `multicall` with the chain
+    /// records when there are any
+    async fn send_addr_records(&self, node: [u8; 32], address: Address) -> eyre::Result<Option<TransactionReceipt>> {
+        if self.chain_coins.is_empty() {
+            return Ok(self.resolver.set_addr(node, address).send().await?.await?);
+        }
+        let mut calls = vec![self.resolver.set_addr(node, address).calldata().unwrap_or_default()];
+        for coin in &self.chain_coins {
+            let call = self.resolver.set_addr_with_node_and_coin_type(node, U256::from(coin.value()), address.as_bytes().to_vec().into());
+            calls.push(call.calldata().unwrap_or_default());
+        }
+        Ok(self.resolver.multicall(calls).send().await?.await?)
+    }
+
+    /// Read owner, resolver and addr records for a subdomain
+    pub async fn get_subdomain_records(&self, label: &str) -> eyre::Result<SubdomainRecords> {
+        let subdomain = format!("{}.{}", label.to_lowercase(), self.parent_domain);
+        SubdomainRecords::read_from(&self.registry, &self.resolver, namehash(&subdomain)).await
+    }
+
+    /// Check owner, resolver and addr records of a subdomain and re-issue only
+    /// the transactions that are missing or wrong (e.g. after a partial mint)
+    pub async fn verify_and_repair(
+        &self,
+        label: &str,
+        expected_addr: Address,
+    ) -> eyre::Result<RepairReport> {
         let label = label.to_lowercase();
         let label_hash = labelhash(&label);
         let subdomain = format!("{}.{}", label, self.parent_domain);
         let subdomain_node = namehash(&subdomain);
-        
-        println!("📝 Step 1/3: Setting subdomain owner...");
-        
-        // Step 1: Set subnode owner (creates the subdomain)
-        let tx = self.registry
-            .set_subnode_owner(self.parent_node, label_hash, target_address);
-        let pending = tx.send().await?;
-        let receipt = pending.await?;
-        
-        if let Some(receipt) = receipt {
-            println!("   ✅ Tx confirmed: {:?}", receipt.transaction_hash);
+        let resolver_address = self.resolver.address();
+        let minter_address = self.registry.client().address();
+
+        let before = self.get_subdomain_records(&label).await?;
+        let steps = plan_repair(&before, expected_addr, resolver_address, minter_address);
+        let mut tx_hashes = Vec::new();
+
+        for (i, step) in steps.iter().enumerate() {
+            tracing::info!(%subdomain, step = ?step, "Repair step {}/{}", i + 1, steps.len());
+
+            let receipt = match step {
+                RepairStep::ClaimOwnership => {
+                    let tx = self.registry.set_subnode_owner(self.parent_node, label_hash, minter_address);
+                    tx.send().await?.await?
+                }
+                RepairStep::SetResolver => {
+                    let tx = self.registry.set_resolver(subdomain_node, resolver_address);
+                    tx.send().await?.await?
+                }
+                RepairStep::SetAddr => self.send_addr_records(subdomain_node, expected_addr).await?,
+                RepairStep::SetOwner => {
+                    let tx = self.registry.set_subnode_owner(self.parent_node, label_hash, expected_addr);
+                    tx.send().await?.await?
+                }
+            };
+
+            if let Some(receipt) = receipt {
+                tracing::info!(%subdomain, step = ?step, tx = ?receipt.transaction_hash, "Repair step confirmed");
+                tx_hashes.push(receipt.transaction_hash);
+            }
         }
-        
-        println!("📝 Step 2/3: Setting resolver...");
-        
-        // Step 2: Set the resolver for the subdomain
-        let resolver_address: Address = PUBLIC_RESOLVER_SEPOLIA.parse()?;
-        let tx = self.registry
-            .set_resolver(subdomain_node, resolver_address);
-        let pending = tx.send().await?;
-        let receipt = pending.await?;
-        
-        if let Some(receipt) = receipt {
-            println!("   ✅ Tx confirmed: {:?}", receipt.transaction_hash);
+
+        Ok(RepairReport {
+            subdomain,
+            before,
+            steps,
+            tx_hashes,
+        })
+    }
+
+    /// Set the contenthash record (EIP-1577) of a minted subdomain.
+    /// The owner is usually the user by now, so the minter reclaims the name
+    /// for the one write and hands it straight back.
+    pub async fn set_contenthash(&self, label: &str, contenthash: Vec<u8>) -> eyre::Result<Vec<H256>> {
+        let node = namehash(&format!("{}.{}", label.to_lowercase(), self.parent_domain));
+        let tx_hashes = self.write_record(label, self.resolver.set_contenthash(node, contenthash.into())).await?;
+        tracing::info!(label, txs = tx_hashes.len(), "Contenthash set");
+        Ok(tx_hashes)
+    }
+
+    /// Set a minted subdomain's addr record for one chain (e.g. the user's
+    /// Solana key), reclaiming the name for the write like `set_contenthash`
+    pub async fn set_coin_addr(&self, label: &str, coin: CoinType, address: &[u8]) -> eyre::Result<Vec<H256>> {
+        let node = namehash(&format!("{}.{}", label.to_lowercase(), self.parent_domain));
+        let call = self.resolver.set_addr_with_node_and_coin_type(node, U256::from(coin.value()), address.to_vec().into());
+        let tx_hashes = self.write_record(label, call).await?;
+        tracing::info!(label, %coin, txs = tx_hashes.len(), "Chain addr set");
+        Ok(tx_hashes)
+    }
+
+    /// Addr record of a subdomain for one chain (empty when unset)
+    pub async fn get_coin_addr(&self, label: &str, coin: CoinType) -> eyre::Result<Vec<u8>> {
+        let subdomain = format!("{}.{}", label.to_lowercase(), self.parent_domain);
+        let addr = self.resolver.addr_with_coin_type(namehash(&subdomain), U256::from(coin.value())).call().await?;
+        Ok(addr.to_vec())
+    }
+
+    /// Send one resolver write for a minted subdomain, reclaiming it first
+    /// and handing it back after if the user owns it
+    async fn write_record(
+        &self,
+        label: &str,
+        call: ContractCall<SignerMiddleware<Provider<Http>, LocalWallet>, ()>,
+    ) -> eyre::Result<Vec<H256>> {
+        let label = label.to_lowercase();
+        let label_hash = labelhash(&label);
+        let subdomain = format!("{}.{}", label, self.parent_domain);
+        let minter_address = self.address();
+
+        let records = self.get_subdomain_records(&label).await?;
+        if records.owner.is_zero() {
+            eyre::bail!("{} is not minted", subdomain);
         }
-        
-        println!("📝 Step 3/3: Setting address record...");
-        
-        // Step 3: Set the address on the resolver
-        let tx = self.resolver
-            .set_addr(subdomain_node, target_address);
-        let pending = tx.send().await?;
-        let receipt = pending.await?;
-        
-        if let Some(receipt) = receipt {
-            println!("   ✅ Tx confirmed: {:?}", receipt.transaction_hash);
+        if records.resolver != self.resolver.address() {
+            eyre::bail!("{} does not use our resolver; run verify_and_repair first", subdomain);
         }
-        
-        Ok(subdomain)
+
+        let mut tx_hashes = Vec::new();
+        let reclaim = records.owner != minter_address;
+        if reclaim {
+            let tx = self.registry.set_subnode_owner(self.parent_node, label_hash, minter_address);
+            tx_hashes.push(Self::mined(tx.send().await?.await?)?);
+        }
+        tx_hashes.push(Self::mined(call.send().await?.await?)?);
+        if reclaim {
+            let tx = self.registry.set_subnode_owner(self.parent_node, label_hash, records.owner);
+            tx_hashes.push(Self::mined(tx.send().await?.await?)?);
+        }
+        Ok(tx_hashes)
     }
-    
+
+    /// Contenthash record of a subdomain (empty when unset)
+    pub async fn get_contenthash(&self, label: &str) -> eyre::Result<Vec<u8>> {
+        let subdomain = format!("{}.{}", label.to_lowercase(), self.parent_domain);
+        let hash = self.resolver.contenthash(namehash(&subdomain)).call().await?;
+        Ok(hash.to_vec())
+    }
+
+    fn mined(receipt: Option<TransactionReceipt>) -> eyre::Result<H256> {
+        let receipt = receipt.ok_or_else(|| eyre::eyre!("transaction dropped before it was mined"))?;
+        Ok(StepReceipt::from_receipt(&receipt)?.tx_hash)
+    }
+
     /// Resolve a subdomain to its address
     pub async fn resolve_subdomain(&self, label: &str) -> eyre::Result<Address> {
         let subdomain = format!("{}.{}", label.to_lowercase(), self.parent_domain);
@@ -202,6 +613,55 @@ mod tests {
         assert_eq!(hash.to_vec(), expected);
     }
     
+    fn addr(byte: u8) -> Address {
+        Address::from([byte; 20])
+    }
+
+    #[test]
+    fn test_plan_repair_healthy() {
+        let records = SubdomainRecords { owner: addr(1), resolver: addr(9), addr: addr(1) };
+        assert!(plan_repair(&records, addr(1), addr(9), addr(7)).is_empty());
+    }
+
+    #[test]
+    fn test_plan_repair_partial_mint() {
+        // Step 1 of mint succeeded (owner set), resolver and addr never set
+        let records = SubdomainRecords { owner: addr(1), resolver: Address::zero(), addr: Address::zero() };
+        assert_eq!(
+            plan_repair(&records, addr(1), addr(9), addr(7)),
+            vec![RepairStep::ClaimOwnership, RepairStep::SetResolver, RepairStep::SetAddr, RepairStep::SetOwner]
+        );
+    }
+
+    #[test]
+    fn test_plan_repair_owner_only() {
+        // Records are right but the minter still holds the name
+        let records = SubdomainRecords { owner: addr(7), resolver: addr(9), addr: addr(1) };
+        assert_eq!(plan_repair(&records, addr(1), addr(9), addr(7)), vec![RepairStep::SetOwner]);
+    }
+
+    #[test]
+    fn test_mint_error_reports_partial_state() {
+        let error = MintError {
+            subdomain: "bob.ttcip.eth".to_string(),
+            failed_step: MintStep::SetAddr,
+            completed: vec![(MintStep::CreateSubdomain, StepReceipt::default()), (MintStep::SetResolver, StepReceipt::default())],
+            source: eyre::eyre!("execution reverted"),
+        };
+        assert!(error.is_partial());
+        assert_eq!(error.to_string(), "minting bob.ttcip.eth failed at SetAddr after 2 of 4 steps: execution reverted");
+
+        let error = MintError { completed: vec![], failed_step: MintStep::CreateSubdomain, ..error };
+        assert!(!error.is_partial());
+        assert_eq!(error.to_string(), "minting bob.ttcip.eth failed at CreateSubdomain: execution reverted");
+    }
+
+    #[test]
+    fn test_subnode_matches_namehash() {
+        let node = subnode(namehash("eth"), labelhash("vitalik"));
+        assert_eq!(node, namehash("vitalik.eth"));
+    }
+
     #[test]
     fn test_labelhash() {
         // labelhash("vitalik") = keccak256("vitalik")
@@ -0,0 +1,277 @@
+//! Bulk subdomain import from CSV
+//!
+//! Partners arriving with an existing member list can have names pre-minted:
+//! `ttc_ens_research import members.csv` reads `label,address` rows,
+//! validates them, estimates the gas for the whole run, then mints in
+//! batches with retries and writes a per-row results report.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use ethers::prelude::*;
+
+use crate::ens::EnsMinter;
+use crate::names::NamePolicy;
+
+/// Approximate gas for the four mint transactions (create, resolver, addr, transfer)
+pub const MINT_GAS_UNITS: u64 = 250_000;
+
+/// Same rules as `JOIN <name>` over SMS
+const MIN_LABEL_LEN: usize = 3;
+const MAX_LABEL_LEN: usize = 20;
+
+/// A validated CSV row
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    /// 1-based line in the CSV
+    pub line: usize,
+    pub label: String,
+    pub address: Address,
+}
+
+/// A row that failed validation
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub line: usize,
+    pub raw: String,
+    pub reason: String,
+}
+
+/// Outcome of one row
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportStatus {
+    Minted,
+    /// A partial earlier mint was completed
+    Repaired,
+    /// Already minted to this address
+    Skipped,
+    /// Owned by someone else - never overwritten
+    Taken(Address),
+    Failed(String),
+}
+
+impl ImportStatus {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImportStatus::Minted => "minted",
+            ImportStatus::Repaired => "repaired",
+            ImportStatus::Skipped => "skipped",
+            ImportStatus::Taken(_) => "taken",
+            ImportStatus::Failed(_) => "failed",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            ImportStatus::Taken(owner) => format!("owned by {:?}", owner),
+            ImportStatus::Failed(reason) => reason.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Rows per batch (progress is logged after each)
+    pub batch_size: usize,
+    /// Attempts per row before it is reported as failed
+    pub max_attempts: u32,
+    /// Validate and estimate only
+    pub dry_run: bool,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self { batch_size: 25, max_attempts: 3, dry_run: false }
+    }
+}
+
+/// Parse and validate `label,address` rows. A header row, blank lines and
+/// `#` comments are skipped; duplicate and reserved labels are rejected.
+pub fn parse_csv(text: &str, name_policy: &NamePolicy) -> (Vec<ImportRow>, Vec<RowError>) {
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let mut fields = trimmed.split(',').map(|f| f.trim().trim_matches('"'));
+        let (label, address) = (fields.next().unwrap_or(""), fields.next().unwrap_or(""));
+        if line == 1 && label.eq_ignore_ascii_case("label") {
+            continue;
+        }
+
+        let error = |reason: &str| RowError { line, raw: raw.to_string(), reason: reason.to_string() };
+        let label = label.to_lowercase();
+        if let Err(reason) = validate_label(&label) {
+            errors.push(error(reason));
+            continue;
+        }
+        if let Some(kind) = name_policy.check(&label) {
+            errors.push(error(&format!("{} name", kind)));
+            continue;
+        }
+        let Ok(address) = address.parse::<Address>() else {
+            errors.push(error("invalid address"));
+            continue;
+        };
+        if address.is_zero() {
+            errors.push(error("zero address"));
+            continue;
+        }
+        if !seen.insert(label.clone()) {
+            errors.push(error("duplicate label"));
+            continue;
+        }
+        rows.push(ImportRow { line, label, address });
+    }
+
+    (rows, errors)
+}
+
+fn validate_label(label: &str) -> Result<(), &'static str> {
+    if label.len() < MIN_LABEL_LEN || label.len() > MAX_LABEL_LEN {
+        return Err("label must be 3-20 characters");
+    }
+    if !label.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err("label can only contain letters and numbers");
+    }
+    Ok(())
+}
+
+/// Estimated (gas units, cost in wei) to mint `count` names at the current gas price
+pub async fn estimate_cost(minter: &EnsMinter, count: usize) -> eyre::Result<(U256, U256)> {
+    let gas = U256::from(MINT_GAS_UNITS) * U256::from(count);
+    let price = minter.gas_price().await?;
+    Ok((gas, gas * price))
+}
+
+/// Mint every row, batch by batch; never overwrites a name owned by someone else
+pub async fn run_import(minter: &EnsMinter, rows: &[ImportRow], options: &ImportOptions) -> Vec<(ImportRow, ImportStatus)> {
+    let mut results = Vec::with_capacity(rows.len());
+    let batches = rows.len().div_ceil(options.batch_size.max(1));
+
+    for (batch_index, batch) in rows.chunks(options.batch_size.max(1)).enumerate() {
+        for row in batch {
+            let status = import_row(minter, row, options.max_attempts).await;
+            tracing::info!(line = row.line, label = %row.label, "{}", status.code());
+            results.push((row.clone(), status));
+        }
+
+        let done = results.len();
+        let failed = results.iter().filter(|(_, s)| matches!(s, ImportStatus::Failed(_))).count();
+        tracing::info!("Batch {}/{} done - {}/{} rows, {} failed", batch_index + 1, batches, done, rows.len(), failed);
+    }
+
+    results
+}
+
+async fn import_row(minter: &EnsMinter, row: &ImportRow, max_attempts: u32) -> ImportStatus {
+    let mut last_error = String::new();
+
+    for attempt in 1..=max_attempts.max(1) {
+        if attempt > 1 {
+            tokio::time::sleep(Duration::from_secs(2u64.pow(attempt - 1))).await;
+        }
+
+        let records = match minter.get_subdomain_records(&row.label).await {
+            Ok(records) => records,
+            Err(e) => {
+                last_error = e.to_string();
+                continue;
+            }
+        };
+
+        // Fresh name: mint. Ours from an interrupted run: finish it.
+        let result = if records.owner.is_zero() {
+            minter.mint_subdomain(&row.label, row.address).await.map(|_| ImportStatus::Minted).map_err(eyre::Report::from)
+        } else if records.owner == minter.address() {
+            minter.verify_and_repair(&row.label, row.address).await.map(|_| ImportStatus::Repaired)
+        } else if records.owner == row.address && records.addr == row.address {
+            return ImportStatus::Skipped;
+        } else {
+            return ImportStatus::Taken(records.owner);
+        };
+
+        match result {
+            Ok(status) => return status,
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+
+    ImportStatus::Failed(last_error)
+}
+
+/// CSV report: one line per input row, invalid rows included
+pub fn report_csv(results: &[(ImportRow, ImportStatus)], errors: &[RowError]) -> String {
+    let mut lines: Vec<(usize, String)> = results
+        .iter()
+        .map(|(row, status)| {
+            (row.line, format!("{},{},{:?},{},{}", row.line, row.label, row.address, status.code(), csv_field(&status.detail())))
+        })
+        .chain(errors.iter().map(|e| (e.line, format!("{},{},,invalid,{}", e.line, csv_field(&e.raw), csv_field(&e.reason)))))
+        .collect();
+    lines.sort_by_key(|(line, _)| *line);
+
+    let mut report = String::from("line,label,address,status,detail\n");
+    for (_, line) in lines {
+        let _ = writeln!(report, "{}", line);
+    }
+    report
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "0x1111111111111111111111111111111111111111";
+
+    #[test]
+    fn test_parse_csv_validates_rows() {
+        let csv = format!(
+            "label,address\nAlice,{ALICE}\n\n# comment\nbo,{ALICE}\ncarol,0x123\nalice,{ALICE}\ndave!,{ALICE}\neve,0x0000000000000000000000000000000000000000\nsupport,{ALICE}\n"
+        );
+        let (rows, errors) = parse_csv(&csv, &NamePolicy::default());
+
+        assert_eq!(rows, vec![ImportRow { line: 2, label: "alice".to_string(), address: ALICE.parse().unwrap() }]);
+        let reasons: Vec<(usize, &str)> = errors.iter().map(|e| (e.line, e.reason.as_str())).collect();
+        assert_eq!(
+            reasons,
+            vec![
+                (5, "label must be 3-20 characters"),
+                (6, "invalid address"),
+                (7, "duplicate label"),
+                (8, "label can only contain letters and numbers"),
+                (9, "zero address"),
+                (10, "reserved name"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_report_orders_by_line() {
+        let (rows, errors) = parse_csv(&format!("x,{ALICE}\nalice,{ALICE}\n"), &NamePolicy::default());
+        let results = vec![(rows[0].clone(), ImportStatus::Failed("nonce too low, retry".to_string()))];
+        let report = report_csv(&results, &errors);
+
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "line,label,address,status,detail");
+        assert!(lines[1].starts_with("1,\"x,0x1111"));
+        assert!(lines[1].ends_with(",,invalid,label must be 3-20 characters"));
+        assert!(lines[2].starts_with("2,alice,0x1111"));
+        assert!(lines[2].ends_with(",failed,\"nonce too low, retry\""));
+    }
+}
@@ -0,0 +1,327 @@
+//! Chain event indexer for subdomains of the parent domain
+//!
+//! Follows `NewOwner` events on the ENS Registry (filtered to the parent node)
+//! and `AddrChanged` events on the Public Resolver for the subdomains it has
+//! seen, and mirrors the result into an `ens_names` table. Listing and lookups
+//! read from that table instead of making one RPC call per name.
+
+use ethers::contract::parse_log;
+use ethers::prelude::*;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::ens::{
+    labelhash, namehash, subnode, AddrChangedFilter, NewOwnerFilter, ENS_REGISTRY,
+    PUBLIC_RESOLVER_SEPOLIA,
+};
+
+/// Blocks fetched per eth_getLogs call (most providers cap the range)
+const LOG_CHUNK_BLOCKS: u64 = 2_000;
+
+/// How far back to start when nothing is indexed yet and no start block is configured
+const DEFAULT_LOOKBACK_BLOCKS: u64 = 50_000;
+
+/// A subdomain as mirrored from chain events
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct IndexedName {
+    pub labelhash: String,
+    /// Plaintext label - events only carry the hash, so this is filled in
+    /// for names minted or repaired through this service
+    pub label: Option<String>,
+    pub owner: String,
+    pub addr: Option<String>,
+    pub updated_block: i64,
+}
+
+impl IndexedName {
+    /// Full name, with the label hash in brackets when the plaintext label is unknown
+    pub fn display_name(&self, parent_domain: &str) -> String {
+        match &self.label {
+            Some(label) => format!("{}.{}", label, parent_domain),
+            None => format!("[{}].{}", self.labelhash.trim_start_matches("0x"), parent_domain),
+        }
+    }
+}
+
+/// Mirrors on-chain subdomain state into a local SQLite table
+pub struct EnsIndexer {
+    provider: Arc<Provider<Http>>,
+    pool: SqlitePool,
+    parent_domain: String,
+    parent_node: [u8; 32],
+    registry: Address,
+    resolver: Address,
+    start_block: Option<u64>,
+}
+
+impl EnsIndexer {
+    /// Open (or create) the index database and its tables
+    pub async fn open(
+        provider: Arc<Provider<Http>>,
+        database_url: &str,
+        parent_domain: &str,
+        start_block: Option<u64>,
+    ) -> eyre::Result<Self> {
+        let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS ens_names (
+                node TEXT PRIMARY KEY,
+                labelhash TEXT NOT NULL,
+                label TEXT,
+                owner TEXT NOT NULL,
+                addr TEXT,
+                updated_block INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS ens_index_cursor (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                last_block INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(Self {
+            provider,
+            pool,
+            parent_domain: parent_domain.to_string(),
+            parent_node: namehash(parent_domain),
+            registry: ENS_REGISTRY.parse()?,
+            resolver: PUBLIC_RESOLVER_SEPOLIA.parse()?,
+            start_block,
+        })
+    }
+
+    pub fn parent_domain(&self) -> &str {
+        &self.parent_domain
+    }
+
+    /// Index all events between the last indexed block and the chain head.
+    /// Returns the number of events applied.
+    pub async fn sync(&self) -> eyre::Result<usize> {
+        let head = self.provider.get_block_number().await?.as_u64();
+        let mut from = match self.last_block().await? {
+            Some(last) => last + 1,
+            None => self
+                .start_block
+                .unwrap_or_else(|| head.saturating_sub(DEFAULT_LOOKBACK_BLOCKS)),
+        };
+
+        let mut applied = 0;
+        while from <= head {
+            let to = (from + LOG_CHUNK_BLOCKS - 1).min(head);
+            applied += self.index_range(from, to).await?;
+            self.set_last_block(to).await?;
+            from = to + 1;
+        }
+
+        Ok(applied)
+    }
+
+    /// Apply NewOwner then AddrChanged events for one block range
+    async fn index_range(&self, from: u64, to: u64) -> eyre::Result<usize> {
+        let mut applied = 0;
+
+        let new_owner = Filter::new()
+            .address(self.registry)
+            .topic0(NewOwnerFilter::signature())
+            .topic1(H256::from(self.parent_node))
+            .from_block(from)
+            .to_block(to);
+
+        for log in self.provider.get_logs(&new_owner).await? {
+            let block = log.block_number.map(|b| b.as_u64()).unwrap_or(to);
+            let event: NewOwnerFilter = parse_log(log)?;
+            self.apply_new_owner(event.label, event.owner, block).await?;
+            applied += 1;
+        }
+
+        // Only follow addr records for subdomains we know about
+        let nodes = self.known_nodes().await?;
+        if nodes.is_empty() {
+            return Ok(applied);
+        }
+
+        let addr_changed = Filter::new()
+            .address(self.resolver)
+            .topic0(AddrChangedFilter::signature())
+            .topic1(nodes)
+            .from_block(from)
+            .to_block(to);
+
+        for log in self.provider.get_logs(&addr_changed).await? {
+            let block = log.block_number.map(|b| b.as_u64()).unwrap_or(to);
+            let event: AddrChangedFilter = parse_log(log)?;
+            self.apply_addr_changed(event.node, event.a, block).await?;
+            applied += 1;
+        }
+
+        Ok(applied)
+    }
+
+    /// Record a (re)assignment of a subdomain's owner
+    async fn apply_new_owner(&self, label_hash: [u8; 32], owner: Address, block: u64) -> eyre::Result<()> {
+        let node = subnode(self.parent_node, label_hash);
+
+        sqlx::query(
+            r#"
+            INSERT INTO ens_names (node, labelhash, owner, updated_block)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (node) DO UPDATE SET owner = excluded.owner, updated_block = excluded.updated_block
+            "#,
+        )
+        .bind(format!("{:?}", H256::from(node)))
+        .bind(format!("{:?}", H256::from(label_hash)))
+        .bind(format!("{:?}", owner))
+        .bind(block as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Record a new addr for a known subdomain
+    async fn apply_addr_changed(&self, node: [u8; 32], addr: Address, block: u64) -> eyre::Result<()> {
+        sqlx::query("UPDATE ens_names SET addr = ?1, updated_block = ?2 WHERE node = ?3")
+            .bind(format!("{:?}", addr))
+            .bind(block as i64)
+            .bind(format!("{:?}", H256::from(node)))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Attach a plaintext label to its indexed node (events only carry the hash)
+    pub async fn record_label(&self, label: &str) -> eyre::Result<()> {
+        let label = label.to_lowercase();
+        let node = subnode(self.parent_node, labelhash(&label));
+
+        sqlx::query("UPDATE ens_names SET label = ?1 WHERE node = ?2")
+            .bind(&label)
+            .bind(format!("{:?}", H256::from(node)))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// All indexed subdomains, named ones first
+    pub async fn list(&self) -> eyre::Result<Vec<IndexedName>> {
+        let names = sqlx::query_as::<_, IndexedName>(
+            "SELECT labelhash, label, owner, addr, updated_block FROM ens_names
+             ORDER BY label IS NULL, label, labelhash",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(names)
+    }
+
+    /// Look up a subdomain by label
+    pub async fn lookup(&self, label: &str) -> eyre::Result<Option<IndexedName>> {
+        let node = subnode(self.parent_node, labelhash(&label.to_lowercase()));
+
+        let name = sqlx::query_as::<_, IndexedName>(
+            "SELECT labelhash, label, owner, addr, updated_block FROM ens_names WHERE node = ?1",
+        )
+        .bind(format!("{:?}", H256::from(node)))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(name)
+    }
+
+    async fn known_nodes(&self) -> eyre::Result<Vec<H256>> {
+        let rows: Vec<(String,)> = sqlx::query_as("SELECT node FROM ens_names")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().filter_map(|(node,)| node.parse().ok()).collect())
+    }
+
+    async fn last_block(&self) -> eyre::Result<Option<u64>> {
+        let row: Option<(i64,)> = sqlx::query_as("SELECT last_block FROM ens_index_cursor WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|(block,)| block as u64))
+    }
+
+    async fn set_last_block(&self, block: u64) -> eyre::Result<()> {
+        sqlx::query(
+            "INSERT INTO ens_index_cursor (id, last_block) VALUES (1, ?1)
+             ON CONFLICT (id) DO UPDATE SET last_block = excluded.last_block",
+        )
+        .bind(block as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Keep the index following the chain head, syncing once per `interval`
+pub fn spawn_watcher(indexer: Arc<EnsIndexer>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = indexer.sync().await {
+                eprintln!("⚠️  ENS indexer sync failed: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn indexer() -> EnsIndexer {
+        let provider = Arc::new(Provider::<Http>::try_from("http://localhost:8545").unwrap());
+        EnsIndexer::open(provider, "sqlite::memory:", "ttc.eth", Some(0)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_events_build_name_table() {
+        let indexer = indexer().await;
+        let owner = Address::from([1u8; 20]);
+        let addr = Address::from([2u8; 20]);
+
+        indexer.apply_new_owner(labelhash("alice"), owner, 10).await.unwrap();
+        indexer.apply_addr_changed(namehash("alice.ttc.eth"), addr, 11).await.unwrap();
+        // AddrChanged for a node we never saw is ignored
+        indexer.apply_addr_changed(namehash("bob.ttc.eth"), addr, 12).await.unwrap();
+
+        let names = indexer.list().await.unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].owner, format!("{:?}", owner));
+        assert_eq!(names[0].addr, Some(format!("{:?}", addr)));
+        assert!(names[0].display_name("ttc.eth").starts_with('['));
+
+        indexer.record_label("Alice").await.unwrap();
+        let alice = indexer.lookup("alice").await.unwrap().unwrap();
+        assert_eq!(alice.display_name("ttc.eth"), "alice.ttc.eth");
+        assert_eq!(alice.updated_block, 11);
+    }
+
+    #[tokio::test]
+    async fn test_new_owner_updates_existing_name() {
+        let indexer = indexer().await;
+
+        indexer.apply_new_owner(labelhash("alice"), Address::from([1u8; 20]), 10).await.unwrap();
+        indexer.apply_new_owner(labelhash("alice"), Address::from([3u8; 20]), 20).await.unwrap();
+
+        let names = indexer.list().await.unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].owner, format!("{:?}", Address::from([3u8; 20])));
+    }
+}
@@ -1,10 +1,25 @@
+mod cache;
+#[cfg(test)]
+mod chaos;
+mod coins;
+mod config;
 mod ens;
+mod import;
+mod indexer;
+mod names;
+mod profile;
+mod quote;
 mod register;
 mod sms;
 
+use cache::EnsCache;
+use clap::{Parser, Subcommand};
+use config::EnsConfig;
 use ens::EnsMinter;
+use indexer::EnsIndexer;
 use ethers::prelude::*;
 use ethers::signers::LocalWallet;
+use serde_json::json;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::io::{self, Write};
@@ -49,6 +64,71 @@ impl AddressBook {
     }
 }
 
+/// TTC ENS tools. With no command, runs the interactive address book.
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Print the result of the command as one JSON object
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand)]
+enum CliCommand {
+    /// Mint <label>.<PARENT_DOMAIN> pointing at an address
+    Mint {
+        #[arg(long)]
+        label: String,
+        #[arg(long)]
+        addr: Address,
+    },
+    /// Register a .eth parent domain to the signing wallet
+    Register {
+        /// Name without .eth
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..=5))]
+        years: u32,
+    },
+    /// Read a subdomain's owner, resolver and addr records
+    Resolve {
+        /// Label, or the full <label>.<PARENT_DOMAIN>
+        name: String,
+    },
+    /// Mint names in bulk from a label,address CSV
+    Import {
+        csv: String,
+        /// Where to write the results (default <file>.report.csv)
+        #[arg(long)]
+        report: Option<String>,
+        /// Rows per batch
+        #[arg(long, default_value_t = import::ImportOptions::default().batch_size)]
+        batch: usize,
+        /// Validate and estimate only
+        #[arg(long)]
+        dry_run: bool,
+        /// Mint without asking
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Pin a profile page for a minted subdomain
+    Profile {
+        label: String,
+        #[arg(required = true)]
+        display_name: Vec<String>,
+    },
+    /// Show or set a per-chain address
+    Addr {
+        label: String,
+        /// Chain name (polygon, base, solana, ...) or coin type number
+        coin: coins::CoinType,
+        /// Address to set; shows the current record when omitted
+        address: Option<String>,
+    },
+}
+
 fn print_menu() {
     println!("\n========================================");
     println!("       TTC ENS Address Book");
@@ -59,7 +139,9 @@ fn print_menu() {
     println!("4. Verify address on-chain (mainnet)");
     println!("5. 🔗 Mint subdomain on-chain (Sepolia)");
     println!("6. 🆕 Register parent domain (Sepolia)");
-    println!("7. Exit");
+    println!("7. 🩺 Audit/repair subdomain records (Sepolia)");
+    println!("8. 🧹 Clear ENS lookup cache");
+    println!("9. Exit");
     println!("========================================");
     print!("Choose an option: ");
     io::stdout().flush().unwrap();
@@ -73,21 +155,376 @@ fn read_input(prompt: &str) -> String {
     input.trim().to_string()
 }
 
-/// Load configuration from .env file
-fn load_config() -> Option<(String, String, String)> {
-    dotenv::dotenv().ok();
-    
-    let private_key = std::env::var("PRIVATE_KEY").ok()?;
-    let rpc_url = std::env::var("RPC_URL").ok()?;
-    let parent_domain = std::env::var("PARENT_DOMAIN").ok()?;
-    
-    Some((private_key, rpc_url, parent_domain))
+/// Open the on-chain name index (`ENS_INDEX_DB`, default ./ens_index.db) and
+/// start following registry/resolver events in the background
+async fn start_indexer(rpc_url: &str, parent_domain: &str) -> eyre::Result<Arc<EnsIndexer>> {
+    let database_url = std::env::var("ENS_INDEX_DB").unwrap_or_else(|_| "sqlite://ens_index.db".to_string());
+    let start_block = std::env::var("ENS_INDEX_START_BLOCK").ok().and_then(|b| b.parse().ok());
+
+    let provider = Arc::new(Provider::<Http>::try_from(rpc_url)?);
+    let indexer = Arc::new(EnsIndexer::open(provider, &database_url, parent_domain, start_block).await?);
+    indexer::spawn_watcher(indexer.clone(), std::time::Duration::from_secs(30));
+
+    Ok(indexer)
+}
+
+/// `import <file.csv> [--report <path>] [--batch <n>] [--dry-run] [--yes]`:
+/// validate a `label,address` CSV, estimate gas, mint and write a report
+async fn import_command(csv_path: &str, report: Option<String>, options: import::ImportOptions, confirmed: bool, as_json: bool) -> eyre::Result<()> {
+    let report_path = report.unwrap_or_else(|| format!("{}.report.csv", csv_path.trim_end_matches(".csv")));
+    if as_json && !confirmed && !options.dry_run {
+        eyre::bail!("import --json can't ask for confirmation; pass --yes or --dry-run");
+    }
+    let config = EnsConfig::load()?;
+    let (private_key, rpc_url, parent_domain) = (config.signing_key()?, &config.rpc_url, &config.parent_domain);
+
+    let name_policy = names::NamePolicy::from_env().await?;
+    let (rows, errors) = import::parse_csv(&std::fs::read_to_string(csv_path)?, &name_policy);
+    if !as_json {
+        println!("📄 {}: {} valid rows, {} invalid", csv_path, rows.len(), errors.len());
+        for error in &errors {
+            println!("   ❌ line {}: {} ({})", error.line, error.reason, error.raw.trim());
+        }
+    }
+
+    let minter = EnsMinter::new(connect(private_key, rpc_url).await?, parent_domain)?
+        .with_chain_coins(coins::chain_coins_from_env()?);
+    if !minter.verify_ownership(minter.address()).await? {
+        eyre::bail!("{:?} does not own {}", minter.address(), parent_domain);
+    }
+
+    let (gas, cost) = import::estimate_cost(&minter, rows.len()).await?;
+    if !as_json {
+        println!("⛽ Estimated {} gas, ~{} ETH at current prices", gas, ethers::utils::format_ether(cost));
+    }
+
+    let results = if options.dry_run || rows.is_empty() {
+        Vec::new()
+    } else if confirmed || read_input(&format!("Mint {} names under {}? (y/n): ", rows.len(), parent_domain)).to_lowercase() == "y" {
+        import::run_import(&minter, &rows, &options).await
+    } else {
+        println!("Cancelled.");
+        return Ok(());
+    };
+    std::fs::write(&report_path, import::report_csv(&results, &errors))?;
+
+    if as_json {
+        println!("{}", import_json(csv_path, &report_path, rows.len(), &errors, (gas, cost), &results));
+    } else if results.is_empty() {
+        println!("📝 Validation report written to {}", report_path);
+    } else {
+        println!("📝 Report written to {}", report_path);
+    }
+    Ok(())
+}
+
+/// `import --json` output: row counts by outcome, invalid rows and the gas estimate
+fn import_json(
+    csv_path: &str,
+    report_path: &str,
+    valid: usize,
+    errors: &[import::RowError],
+    (gas, cost): (U256, U256),
+    results: &[(import::ImportRow, import::ImportStatus)],
+) -> serde_json::Value {
+    let mut outcomes = serde_json::Map::new();
+    for (_, status) in results {
+        let count = outcomes.entry(status.code().to_string()).or_insert(json!(0));
+        *count = json!(count.as_u64().unwrap_or(0) + 1);
+    }
+    let invalid: Vec<_> = errors.iter().map(|e| json!({ "line": e.line, "reason": e.reason })).collect();
+    json!({
+        "csv": csv_path,
+        "report": report_path,
+        "valid": valid,
+        "invalid": invalid,
+        "estimated_gas": gas.to_string(),
+        "estimated_cost_wei": cost.to_string(),
+        "minted": !results.is_empty(),
+        "outcomes": outcomes,
+    })
+}
+
+/// `profile <label> <display name>`: pin a profile page for a minted
+/// subdomain to IPFS and set it as the name's contenthash
+async fn profile_command(label: &str, display_name: &[String], as_json: bool) -> eyre::Result<()> {
+    let display_name = display_name.join(" ");
+    let config = EnsConfig::load()?;
+    let (private_key, rpc_url, parent_domain) = (config.signing_key()?, &config.rpc_url, &config.parent_domain);
+    let Some(ipfs) = profile::IpfsClient::from_env() else {
+        eyre::bail!("IPFS_API_URL must be set to pin profile pages");
+    };
+
+    let minter = EnsMinter::new(connect(private_key, rpc_url).await?, parent_domain)?;
+    let previous = profile::contenthash_to_cid(&minter.get_contenthash(label).await?);
+    if let (Some(previous), false) = (&previous, as_json) {
+        println!("ℹ️  Replacing current page ipfs://{}", previous);
+    }
+    let published = profile::publish_profile(&minter, &ipfs, label, &display_name).await?;
+    let gateway = std::env::var("IPFS_GATEWAY_URL").unwrap_or_else(|_| "https://ipfs.io".to_string());
+    let page_url = format!("{}/ipfs/{}", gateway.trim_end_matches('/'), published.cid);
+
+    if as_json {
+        println!("{}", json!({
+            "name": published.subdomain,
+            "cid": published.cid,
+            "previous_cid": previous,
+            "url": page_url,
+            "txs": published.tx_hashes,
+        }));
+        return Ok(());
+    }
+    println!("✅ {} now points at ipfs://{}", published.subdomain, published.cid);
+    for tx in &published.tx_hashes {
+        println!("   tx: https://sepolia.etherscan.io/tx/{:?}", tx);
+    }
+    println!("🌐 {}", page_url);
+    Ok(())
+}
+
+/// `addr <label> <coin> [address]`: show or set a minted subdomain's addr
+/// record for one chain (`polygon`, `base`, `arbitrum`, `solana`, ...)
+async fn addr_command(label: &str, coin: coins::CoinType, address: Option<&str>, as_json: bool) -> eyre::Result<()> {
+    let config = EnsConfig::load()?;
+    let (private_key, rpc_url, parent_domain) = (config.signing_key()?, &config.rpc_url, &config.parent_domain);
+
+    let minter = EnsMinter::new(connect(private_key, rpc_url).await?, parent_domain)?;
+    let subdomain = format!("{}.{}", label.to_lowercase(), parent_domain);
+
+    let mut tx_hashes = Vec::new();
+    if let Some(address) = address {
+        tx_hashes = minter.set_coin_addr(label, coin, &coin.encode_address(address)?).await?;
+        if !as_json {
+            println!("✅ {} {} addr set (coin type {})", subdomain, coin, coin.value());
+            for tx in &tx_hashes {
+                println!("   tx: https://sepolia.etherscan.io/tx/{:?}", tx);
+            }
+        }
+    }
+    let record = minter.get_coin_addr(label, coin).await?;
+    if as_json {
+        println!("{}", json!({
+            "name": subdomain,
+            "coin": coin.name(),
+            "coin_type": coin.value(),
+            "address": coin.format_address(&record),
+            "record": format!("0x{}", hex::encode(&record)),
+            "txs": tx_hashes,
+        }));
+        return Ok(());
+    }
+    match coin.format_address(&record) {
+        Some(address) => println!("🔗 {} on {}: {}", subdomain, coin, address),
+        None if record.is_empty() => println!("🔗 {} has no {} address", subdomain, coin),
+        None => println!("🔗 {} on {}: 0x{} (unexpected length)", subdomain, coin, hex::encode(&record)),
+    }
+    Ok(())
+}
+
+/// Signing client for the configured wallet
+async fn connect(private_key: &str, rpc_url: &str) -> eyre::Result<Arc<SignerMiddleware<Provider<Http>, LocalWallet>>> {
+    let provider = Provider::<Http>::try_from(rpc_url)?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let wallet: LocalWallet = private_key.parse::<LocalWallet>()?.with_chain_id(chain_id);
+    Ok(Arc::new(SignerMiddleware::new(provider, wallet)))
+}
+
+/// `mint --label <label> --addr <0x...>`: mint without prompting
+async fn mint_command(label: &str, address: Address, as_json: bool) -> eyre::Result<()> {
+    let config = EnsConfig::load()?;
+    let (private_key, rpc_url, parent_domain) = (config.signing_key()?, &config.rpc_url, &config.parent_domain);
+    if let Some(kind) = names::NamePolicy::from_env().await?.check(label) {
+        eyre::bail!("{} is a {} name and can't be minted", label.to_lowercase(), kind);
+    }
+
+    let minter = EnsMinter::new(connect(private_key, rpc_url).await?, parent_domain)?
+        .with_chain_coins(coins::chain_coins_from_env()?);
+    if !minter.verify_ownership(minter.address()).await? {
+        eyre::bail!("{:?} does not own {}", minter.address(), parent_domain);
+    }
+    let outcome = minter.mint_subdomain(label, address).await?;
+
+    if as_json {
+        println!("{}", mint_json(&outcome, address));
+        return Ok(());
+    }
+    println!("✅ Minted {} → {:?}", outcome.subdomain, address);
+    for (step, receipt) in mint_steps(&outcome) {
+        println!("   {:<8} tx {:?} (block {}, gas {})", step, receipt.tx_hash, receipt.block_number, receipt.gas_used);
+    }
+    println!("   Gas used: {}", outcome.gas_used);
+    Ok(())
+}
+
+/// The four mint transactions, in the order they were sent
+fn mint_steps(outcome: &ens::MintOutcome) -> [(&'static str, &ens::StepReceipt); 4] {
+    [
+        ("create", &outcome.create_tx),
+        ("resolver", &outcome.resolver_tx),
+        ("addr", &outcome.addr_tx),
+        ("owner", &outcome.owner_tx),
+    ]
+}
+
+/// `mint --json` output; gas figures are strings since they are U256
+fn mint_json(outcome: &ens::MintOutcome, address: Address) -> serde_json::Value {
+    let txs: serde_json::Map<String, serde_json::Value> = mint_steps(outcome)
+        .iter()
+        .map(|(step, receipt)| {
+            let tx = json!({
+                "hash": receipt.tx_hash,
+                "block": receipt.block_number.as_u64(),
+                "gas_used": receipt.gas_used.to_string(),
+            });
+            (step.to_string(), tx)
+        })
+        .collect();
+    json!({
+        "subdomain": outcome.subdomain,
+        "address": address,
+        "gas_used": outcome.gas_used.to_string(),
+        "txs": txs,
+    })
+}
+
+/// Registration steps for the terminal, with the commitment wait as a
+/// countdown redrawn on one line
+fn print_registration_progress(progress: register::RegistrationProgress) {
+    use register::RegistrationProgress::*;
+    match progress {
+        Committing => println!("   Step 1/2: commit"),
+        Committed { tx } => println!("   Commit tx {:?}", tx),
+        Waiting { remaining, .. } => {
+            print!("\r   ⏳ Commitment matures in about {}s   ", remaining.as_secs());
+            let _ = io::stdout().flush();
+        }
+        Registering => println!("\r   Step 2/2: register                          "),
+        Registered { tx } => println!("   Register tx {:?}", tx),
+    }
+}
+
+/// `register --name <name> [--years <1-5>]`: register <name>.eth to the
+/// signing wallet without prompting
+async fn register_command(name: &str, years: u32, as_json: bool) -> eyre::Result<()> {
+    let config = EnsConfig::load()?;
+    let (private_key, rpc_url) = (config.signing_key()?, &config.rpc_url);
+    let client = connect(private_key, rpc_url).await?;
+    let owner = client.address();
+    let controller = std::env::var("ENS_CONTROLLER").ok();
+    let registrar = register::DomainRegistrar::new(client, controller.as_deref())
+        .await?
+        .with_commitment_wait(register::CommitmentWait::from_env()?);
+    let quote = registrar.quote(name, years, quote::eth_usd_price().await).await?;
+    if let Some(message) = quote.shortfall_message() {
+        eyre::bail!(message);
+    }
+    if !as_json {
+        for line in quote.lines() {
+            println!("{}", line);
+        }
+    }
+    let domain = if as_json {
+        registrar.register_domain(name, owner, years).await?
+    } else {
+        registrar.register_domain_with_progress(name, owner, years, print_registration_progress).await?
+    };
+
+    if as_json {
+        println!("{}", json!({
+            "domain": domain,
+            "owner": owner,
+            "years": years,
+            "controller": registrar.controller_address(),
+            "quote": {
+                "price_wei": quote.price.to_string(),
+                "value_wei": quote.value.to_string(),
+                "gas_wei": quote.gas_cost.to_string(),
+                "usd_per_eth": quote.usd_per_eth,
+                "summary": quote.sms_message(),
+            },
+        }));
+    } else {
+        println!("✅ Registered {} for {} year(s) to {:?}", domain, years, owner);
+    }
+    Ok(())
+}
+
+/// `resolve <label>`: read a subdomain's records from the chain
+async fn resolve_command(name: &str, as_json: bool) -> eyre::Result<()> {
+    let config = EnsConfig::load()?;
+    let label = name.strip_suffix(&format!(".{}", config.parent_domain)).unwrap_or(name);
+    let subdomain = format!("{}.{}", label.to_lowercase(), config.parent_domain);
+    let provider = Arc::new(Provider::<Http>::try_from(config.rpc_url.as_str())?);
+    let records = ens::SubdomainRecords::read(provider, &subdomain).await?;
+
+    if as_json {
+        println!("{}", resolve_json(&subdomain, &records));
+    } else if records.owner.is_zero() {
+        println!("❌ {} is not minted", subdomain);
+    } else {
+        println!("✅ {} → {:?}", subdomain, records.addr);
+        println!("   Owner:    {:?}", records.owner);
+        println!("   Resolver: {:?}", records.resolver);
+    }
+    Ok(())
+}
+
+/// `resolve --json` output; an unset addr record is null
+fn resolve_json(subdomain: &str, records: &ens::SubdomainRecords) -> serde_json::Value {
+    json!({
+        "name": subdomain,
+        "exists": !records.owner.is_zero(),
+        "owner": records.owner,
+        "resolver": records.resolver,
+        "addr": (!records.addr.is_zero()).then_some(records.addr),
+    })
+}
+
+async fn run_command(command: CliCommand, as_json: bool) -> eyre::Result<()> {
+    match command {
+        CliCommand::Mint { label, addr } => mint_command(&label, addr, as_json).await,
+        CliCommand::Register { name, years } => register_command(&name, years, as_json).await,
+        CliCommand::Resolve { name } => resolve_command(&name, as_json).await,
+        CliCommand::Import { csv, report, batch, dry_run, yes } => {
+            let options = import::ImportOptions { batch_size: batch, dry_run, ..Default::default() };
+            import_command(&csv, report, options, yes, as_json).await
+        }
+        CliCommand::Profile { label, display_name } => profile_command(&label, &display_name, as_json).await,
+        CliCommand::Addr { label, coin, address } => addr_command(&label, coin, address.as_deref(), as_json).await,
+    }
 }
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
-    // Load .env configuration
-    let config = load_config();
+    // Minting, registration and repair progress is logged to stderr, not
+    // printed, so --json output stays parseable
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "ttc_ens_research=info".into()),
+        )
+        .init();
+
+    let cli = Cli::parse();
+    if let Some(command) = cli.command {
+        let result = run_command(command, cli.json).await;
+        if let (Err(e), true) = (&result, cli.json) {
+            println!("{}", json!({ "error": format!("{:#}", e) }));
+            std::process::exit(1);
+        }
+        return result;
+    }
+
+    // Load .env configuration; the on-chain options also need the signing key
+    let config = match EnsConfig::load() {
+        Ok(EnsConfig { private_key: Some(private_key), rpc_url, parent_domain }) => Some((private_key, rpc_url, parent_domain)),
+        Ok(_) => None,
+        Err(e) => {
+            println!("⚠️  {:#}", e);
+            None
+        }
+    };
     let on_chain_enabled = config.is_some();
     
     // Get parent domain from config or use default
@@ -101,13 +538,29 @@ async fn main() -> eyre::Result<()> {
     // Provider for on-chain verification (mainnet - read only)
     let mainnet_rpc = "https://eth-mainnet.g.alchemy.com/v2/demo";
     let mainnet_provider = Provider::<Http>::try_from(mainnet_rpc)?;
+    let ens_cache = EnsCache::from_env();
+    let name_policy = match names::NamePolicy::from_env().await {
+        Ok(policy) => policy,
+        Err(e) => {
+            println!("⚠️  Reserved names list unavailable, using built-in names only: {}", e);
+            names::NamePolicy::default()
+        }
+    };
 
     println!("\n🚀 Welcome to TTC ENS Address Book!");
     println!("Create friendly names for wallet addresses.");
     
-    if on_chain_enabled {
+    // On-chain name index (Sepolia)
+    let mut name_index: Option<Arc<EnsIndexer>> = None;
+
+    if let Some((_, rpc_url, _)) = config.as_ref() {
         println!("✅ On-chain minting enabled (Sepolia)");
         println!("   Parent domain: {}", parent_domain);
+
+        match start_indexer(rpc_url, &parent_domain).await {
+            Ok(index) => name_index = Some(index),
+            Err(e) => println!("⚠️  On-chain name index unavailable: {}", e),
+        }
     } else {
         println!("⚠️  On-chain minting disabled - .env not configured");
         println!("   Copy .env.example to .env and fill in your values");
@@ -146,6 +599,7 @@ async fn main() -> eyre::Result<()> {
                         println!("\n✅ Success! Registered locally:");
                         println!("   Name:    {}", ens_name);
                         println!("   Address: {:?}", address);
+                        println!("   Check:   {}", fingerprint::fingerprint(address.as_fixed_bytes()));
                         
                         if on_chain_enabled {
                             println!("\n💡 Tip: Use option 5 to mint this on-chain!");
@@ -166,14 +620,51 @@ async fn main() -> eyre::Result<()> {
                         println!("\n✅ Found!");
                         println!("   {}.{} → {:?}", name.to_lowercase(), parent_domain, address);
                     }
-                    None => {
-                        println!("\n❌ Name '{}' not found in your address book.", name);
-                    }
+                    None => match name_index.as_ref() {
+                        Some(index) => match index.lookup(&name).await {
+                            Ok(Some(entry)) => {
+                                println!("\n✅ Found on-chain!");
+                                println!("   {} → {}", entry.display_name(&parent_domain), entry.addr.as_deref().unwrap_or("(no addr record)"));
+                                println!("   Last updated at block {}", entry.updated_block);
+                            }
+                            Ok(None) => println!("\n❌ Name '{}' not found locally or on-chain.", name),
+                            Err(e) => println!("\n❌ Index lookup failed: {}", e),
+                        },
+                        None => println!("\n❌ Name '{}' not found in your address book.", name),
+                    },
                 }
             }
 
             "3" => {
-                // List all names
+                // List all names - from the on-chain index when available
+                if let Some(index) = name_index.as_ref() {
+                    println!("\n🔄 Syncing on-chain index...");
+                    if let Err(e) = index.sync().await {
+                        println!("⚠️  Sync failed, showing last indexed state: {}", e);
+                    }
+
+                    match index.list().await {
+                        Ok(entries) if entries.is_empty() => {
+                            println!("\n📭 No subdomains of {} found on-chain.", index.parent_domain());
+                        }
+                        Ok(entries) => {
+                            println!("\n📖 On-chain names under {}:", index.parent_domain());
+                            println!("   {:<25} {:<44} {}", "ENS Name", "Address", "Owner");
+                            println!("   {}", "-".repeat(115));
+                            for entry in entries {
+                                println!(
+                                    "   {:<25} {:<44} {}",
+                                    entry.display_name(index.parent_domain()),
+                                    entry.addr.as_deref().unwrap_or("-"),
+                                    entry.owner
+                                );
+                            }
+                        }
+                        Err(e) => println!("❌ Failed to read index: {}", e),
+                    }
+                    continue;
+                }
+
                 let entries = address_book.list_all();
                 
                 if entries.is_empty() {
@@ -194,12 +685,24 @@ async fn main() -> eyre::Result<()> {
                 
                 println!("🔍 Looking up {} on mainnet...", ens_name);
                 
-                match mainnet_provider.resolve_name(&ens_name).await {
-                    Ok(address) => {
+                match ens_cache.resolve_name(&mainnet_provider, &ens_name).await {
+                    Ok(Some(address)) => {
                         println!("✅ Found on-chain: {} → {:?}", ens_name, address);
+                        // The reverse record proves the owner claims this name
+                        match ens_cache.lookup_address(&mainnet_provider, address).await {
+                            Ok(Some(primary)) if primary.eq_ignore_ascii_case(&ens_name) => {
+                                println!("   ✅ Reverse record matches");
+                            }
+                            Ok(Some(primary)) => println!("   ⚠️  Primary name is {}", primary),
+                            Ok(None) => println!("   ⚠️  No primary name set"),
+                            Err(e) => println!("   ⚠️  Reverse lookup failed: {}", e),
+                        }
+                    }
+                    Ok(None) => {
+                        println!("❌ Not found on mainnet: {}", ens_name);
                     }
                     Err(e) => {
-                        println!("❌ Not found on mainnet: {}", e);
+                        println!("❌ Lookup failed: {}", e);
                     }
                 }
             }
@@ -235,6 +738,10 @@ async fn main() -> eyre::Result<()> {
                     println!("❌ Name cannot be empty!");
                     continue;
                 }
+                if let Some(kind) = name_policy.check(&label) {
+                    println!("❌ {} is a {} name and can't be minted", label.to_lowercase(), kind);
+                    continue;
+                }
                 
                 // Confirm before minting
                 let full_name = format!("{}.{}", label.to_lowercase(), parent_domain);
@@ -259,7 +766,7 @@ async fn main() -> eyre::Result<()> {
                 let client = Arc::new(client);
                 
                 // Verify we own the parent domain
-                let minter = EnsMinter::new(client.clone(), &parent_domain)?;
+                let minter = EnsMinter::new(client.clone(), &parent_domain)?.with_chain_coins(coins::chain_coins_from_env()?);
                 let wallet_address = wallet.address();
                 
                 println!("🔍 Verifying ownership of {}...", parent_domain);
@@ -281,17 +788,33 @@ async fn main() -> eyre::Result<()> {
                 
                 // Mint the subdomain
                 match minter.mint_subdomain(&label, target_address).await {
-                    Ok(subdomain) => {
+                    Ok(outcome) => {
                         println!("\n🎉 SUCCESS! Subdomain minted on Sepolia!");
-                        println!("   Name:    {}", subdomain);
-                        println!("   Address: {:?}", target_address);
-                        println!("\n   Verify at: https://app.ens.domains/{}?chainId=11155111", subdomain);
+                        println!("   Name:     {}", outcome.subdomain);
+                        println!("   Address:  {:?}", target_address);
+                        for (step, receipt) in [
+                            ("Create  ", outcome.create_tx),
+                            ("Resolver", outcome.resolver_tx),
+                            ("Addr    ", outcome.addr_tx),
+                            ("Owner   ", outcome.owner_tx),
+                        ] {
+                            println!("   {} tx {:?} (block {}, gas {})", step, receipt.tx_hash, receipt.block_number, receipt.gas_used);
+                        }
+                        println!("   Gas used: {}", outcome.gas_used);
+                        println!("\n   Verify at: https://app.ens.domains/{}?chainId=11155111", outcome.subdomain);
                         
                         // Also register locally
                         address_book.register(&label, target_address);
+                        if let Some(index) = name_index.as_ref() {
+                            let _ = index.sync().await;
+                            let _ = index.record_label(&label).await;
+                        }
                     }
                     Err(e) => {
                         println!("\n❌ Failed to mint subdomain: {}", e);
+                        if e.is_partial() {
+                            println!("   Use option 7 to finish {}", e.subdomain);
+                        }
                     }
                 }
             }
@@ -328,6 +851,45 @@ async fn main() -> eyre::Result<()> {
                     }
                 };
                 
+                // Set up the signer
+                let provider = Provider::<Http>::try_from(rpc_url.as_str())?;
+                let chain_id = provider.get_chainid().await?.as_u64();
+                
+                let wallet: LocalWallet = private_key.parse::<LocalWallet>()?.with_chain_id(chain_id);
+                let client = SignerMiddleware::new(provider, wallet.clone());
+                let client = Arc::new(client);
+                
+                // Create registrar; ENS_CONTROLLER overrides the controller
+                // found through controller.ens.eth
+                let controller = std::env::var("ENS_CONTROLLER").ok();
+                let registrar = register::DomainRegistrar::new(client.clone(), controller.as_deref())
+                    .await?
+                    .with_commitment_wait(register::CommitmentWait::from_env()?);
+                println!(
+                    "   Controller: {:?} ({:?}{})",
+                    registrar.controller_address(),
+                    registrar.abi(),
+                    if registrar.is_commitment_free() { ", no commitment" } else { "" }
+                );
+                let wallet_address = wallet.address();
+                
+                // Quote before committing anything
+                let quote = match registrar.quote(&name, years, quote::eth_usd_price().await).await {
+                    Ok(quote) => quote,
+                    Err(e) => {
+                        println!("\n❌ Could not quote {}.eth: {}", name, e);
+                        continue;
+                    }
+                };
+                println!();
+                for line in quote.lines() {
+                    println!("{}", line);
+                }
+                if let Some(message) = quote.shortfall_message() {
+                    println!("\n❌ {}", message);
+                    continue;
+                }
+                
                 // Confirm before registering
                 println!("\n⚠️  About to register on Sepolia:");
                 println!("   Domain: {}.eth", name);
@@ -341,19 +903,8 @@ async fn main() -> eyre::Result<()> {
                 
                 println!("\n🚀 Starting registration process...\n");
                 
-                // Set up the signer
-                let provider = Provider::<Http>::try_from(rpc_url.as_str())?;
-                let chain_id = provider.get_chainid().await?.as_u64();
                 
-                let wallet: LocalWallet = private_key.parse::<LocalWallet>()?.with_chain_id(chain_id);
-                let client = SignerMiddleware::new(provider, wallet.clone());
-                let client = Arc::new(client);
-                
-                // Create registrar and register domain
-                let registrar = register::DomainRegistrar::new(client.clone())?;
-                let wallet_address = wallet.address();
-                
-                match registrar.register_domain(&name, wallet_address, years).await {
+                match registrar.register_domain_with_progress(&name, wallet_address, years, print_registration_progress).await {
                     Ok(domain) => {
                         println!("\n🎉 SUCCESS! Domain registered on Sepolia!");
                         println!("   Domain: {}", domain);
@@ -368,15 +919,180 @@ async fn main() -> eyre::Result<()> {
             }
 
             "7" => {
+                // Audit a subdomain and re-issue any missing/incorrect records
+                if !on_chain_enabled {
+                    println!("\n❌ On-chain repair is not configured!");
+                    println!("   1. Copy .env.example to .env");
+                    println!("   2. Fill in your PRIVATE_KEY, RPC_URL, and PARENT_DOMAIN");
+                    println!("   3. Restart the application");
+                    continue;
+                }
+
+                let (private_key, rpc_url, parent_domain) = config.as_ref().unwrap().clone();
+
+                println!("\n🩺 Subdomain Audit/Repair (Sepolia Testnet)");
+                println!("   Parent domain: {}", parent_domain);
+
+                let label = read_input(&format!("\nEnter subdomain name (<name>.{}): ", parent_domain));
+                if label.is_empty() {
+                    println!("❌ Name cannot be empty!");
+                    continue;
+                }
+
+                let address_str = read_input("Enter expected wallet address (0x...): ");
+                let expected_address: Address = match address_str.parse() {
+                    Ok(addr) => addr,
+                    Err(_) => {
+                        println!("❌ Invalid address format!");
+                        continue;
+                    }
+                };
+
+                // Set up the signer
+                let provider = Provider::<Http>::try_from(rpc_url.as_str())?;
+                let chain_id = provider.get_chainid().await?.as_u64();
+
+                let wallet: LocalWallet = private_key.parse::<LocalWallet>()?.with_chain_id(chain_id);
+                let client = SignerMiddleware::new(provider, wallet.clone());
+                let client = Arc::new(client);
+
+                let minter = EnsMinter::new(client.clone(), &parent_domain)?.with_chain_coins(coins::chain_coins_from_env()?);
+
+                println!("\n🔍 Checking records...");
+                match minter.get_subdomain_records(&label).await {
+                    Ok(records) => {
+                        println!("   Owner:    {:?}", records.owner);
+                        println!("   Resolver: {:?}", records.resolver);
+                        println!("   Addr:     {:?}", records.addr);
+                    }
+                    Err(e) => {
+                        println!("   ❌ Failed to read records: {}", e);
+                        continue;
+                    }
+                }
+
+                let confirm = read_input("Repair any missing/incorrect records? (y/n): ");
+                if confirm.to_lowercase() != "y" {
+                    println!("Cancelled.");
+                    continue;
+                }
+
+                match minter.verify_and_repair(&label, expected_address).await {
+                    Ok(report) if report.was_healthy() => {
+                        println!("\n✅ {} is healthy - nothing to repair.", report.subdomain);
+                    }
+                    Ok(report) => {
+                        println!("\n🎉 Repaired {} ({} tx)", report.subdomain, report.tx_hashes.len());
+                        // Keep the local book in sync with the repaired name
+                        address_book.register(&label, expected_address);
+                        if let Some(index) = name_index.as_ref() {
+                            let _ = index.sync().await;
+                            let _ = index.record_label(&label).await;
+                        }
+                    }
+                    Err(e) => {
+                        println!("\n❌ Repair failed: {}", e);
+                    }
+                }
+            }
+
+            "8" => {
+                // Force fresh mainnet lookups, e.g. right after a record changed
+                let key = read_input("\nName or 0x address to forget (blank = everything): ");
+                if key.is_empty() {
+                    println!("🧹 Cleared {} cached lookups", ens_cache.clear());
+                } else if ens_cache.invalidate(&key) {
+                    println!("🧹 Forgot {}", key);
+                } else {
+                    println!("   {} was not cached", key);
+                }
+            }
+
+            "9" => {
                 println!("\n👋 Goodbye!");
                 break;
             }
 
             _ => {
-                println!("\n❌ Invalid option. Please choose 1-7.");
+                println!("\n❌ Invalid option. Please choose 1-9.");
             }
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_subcommands() {
+        let cli = Cli::try_parse_from(["ens", "mint", "--label", "alice", "--addr", "0x1111111111111111111111111111111111111111", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(cli.command, Some(CliCommand::Mint { ref label, addr }) if label == "alice" && addr == Address::repeat_byte(0x11)));
+
+        let cli = Cli::try_parse_from(["ens", "--json", "register", "--name", "ttcip"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(cli.command, Some(CliCommand::Register { ref name, years: 1 }) if name == "ttcip"));
+        assert!(Cli::try_parse_from(["ens", "register", "--name", "ttcip", "--years", "6"]).is_err());
+
+        let cli = Cli::try_parse_from(["ens", "resolve", "alice.ttcip.eth"]).unwrap();
+        assert!(!cli.json);
+        assert!(matches!(cli.command, Some(CliCommand::Resolve { ref name }) if name == "alice.ttcip.eth"));
+
+        // --json is taken after import, profile and addr too
+        let cli = Cli::try_parse_from(["ens", "import", "names.csv", "--dry-run", "--batch", "5", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(
+            cli.command,
+            Some(CliCommand::Import { ref csv, report: None, batch: 5, dry_run: true, yes: false }) if csv == "names.csv"
+        ));
+        assert!(Cli::try_parse_from(["ens", "import", "names.csv", "--batch", "many"]).is_err());
+
+        let cli = Cli::try_parse_from(["ens", "profile", "alice", "Alice", "Mwangi", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(cli.command, Some(CliCommand::Profile { ref display_name, .. }) if display_name == &["Alice", "Mwangi"]));
+        assert!(Cli::try_parse_from(["ens", "profile", "alice"]).is_err());
+
+        let cli = Cli::try_parse_from(["ens", "addr", "alice", "sol", "--json"]).unwrap();
+        assert!(cli.json);
+        assert!(matches!(cli.command, Some(CliCommand::Addr { coin: coins::CoinType::Solana, address: None, .. })));
+        assert!(Cli::try_parse_from(["ens", "addr", "alice", "dogecoin"]).is_err());
+
+        assert!(Cli::try_parse_from(["ens"]).unwrap().command.is_none());
+        assert!(Cli::try_parse_from(["ens", "mint", "--label", "alice", "--addr", "0x12"]).is_err());
+    }
+
+    #[test]
+    fn test_mint_json() {
+        let receipt = |block: u64| ens::StepReceipt { tx_hash: H256::repeat_byte(block as u8), block_number: block.into(), gas_used: 21_000.into() };
+        let outcome = ens::MintOutcome {
+            subdomain: "alice.ttcip.eth".to_string(),
+            create_tx: receipt(1),
+            resolver_tx: receipt(2),
+            addr_tx: receipt(3),
+            owner_tx: receipt(4),
+            gas_used: 84_000.into(),
+        };
+        let output = mint_json(&outcome, Address::repeat_byte(0x11));
+        assert_eq!(output["subdomain"], "alice.ttcip.eth");
+        assert_eq!(output["address"], "0x1111111111111111111111111111111111111111");
+        assert_eq!(output["gas_used"], "84000");
+        assert_eq!(output["txs"]["owner"]["block"], 4);
+        assert_eq!(output["txs"]["create"]["gas_used"], "21000");
+        assert_eq!(output["txs"]["addr"]["hash"], format!("{:?}", H256::repeat_byte(3)));
+    }
+
+    #[test]
+    fn test_resolve_json() {
+        let records = ens::SubdomainRecords { owner: Address::repeat_byte(0x22), resolver: Address::repeat_byte(0x33), addr: Address::zero() };
+        let output = resolve_json("alice.ttcip.eth", &records);
+        assert_eq!(output["exists"], true);
+        assert_eq!(output["owner"], "0x2222222222222222222222222222222222222222");
+        assert!(output["addr"].is_null());
+
+        let missing = ens::SubdomainRecords { owner: Address::zero(), resolver: Address::zero(), addr: Address::zero() };
+        assert_eq!(resolve_json("bob.ttcip.eth", &missing)["exists"], false);
+    }
+}
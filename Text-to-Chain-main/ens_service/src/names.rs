@@ -0,0 +1,125 @@
+//! Subdomain labels that must not be minted
+//!
+//! The SMS service owns the list: built-in reserved labels plus the brand,
+//! offensive and pattern rules admins manage at `/admin/reserved-names`.
+//! With `ENS_RESERVED_NAMES_URL` pointing there, this CLI refuses the same
+//! labels; without it only the built-in labels and `ENS_DENY_PATTERN` apply.
+
+use regex::{Regex, RegexBuilder};
+
+/// Kept in step with `BUILTIN_RESERVED` in the SMS service
+pub const BUILTIN_RESERVED: [&str; 24] = [
+    "admin", "administrator", "root", "system", "support", "help", "helpdesk", "info", "security", "official",
+    "staff", "team", "moderator", "pay", "payment", "payments", "billing", "wallet", "account", "verify",
+    "textchain", "ttc", "ttcip", "ens",
+];
+
+#[derive(Debug)]
+enum Matcher {
+    /// The whole label
+    Exact(String),
+    /// Anywhere in the label (brand and offensive rules)
+    Contains(String),
+    Pattern(Regex),
+}
+
+/// One deny rule and what to call it in errors
+#[derive(Debug)]
+struct Rule {
+    matcher: Matcher,
+    kind: String,
+}
+
+impl Rule {
+    fn new(label: &str, kind: &str) -> eyre::Result<Self> {
+        let label = label.trim();
+        let matcher = match kind {
+            "reserved" => Matcher::Exact(label.to_lowercase()),
+            "brand" | "offensive" => Matcher::Contains(label.to_lowercase()),
+            "pattern" => Matcher::Pattern(RegexBuilder::new(label).case_insensitive(true).build()?),
+            other => eyre::bail!("unknown reserved-name kind {}", other),
+        };
+        Ok(Self { matcher, kind: kind.to_string() })
+    }
+
+    fn matches(&self, label: &str) -> bool {
+        match &self.matcher {
+            Matcher::Exact(reserved) => label == reserved,
+            Matcher::Contains(word) => label.contains(word.as_str()),
+            Matcher::Pattern(regex) => regex.is_match(label),
+        }
+    }
+}
+
+/// Deny rules checked before every mint
+#[derive(Debug)]
+pub struct NamePolicy {
+    rules: Vec<Rule>,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        let rules = BUILTIN_RESERVED
+            .iter()
+            .map(|label| Rule { matcher: Matcher::Exact(label.to_string()), kind: "reserved".to_string() })
+            .collect();
+        Self { rules }
+    }
+}
+
+impl NamePolicy {
+    /// Built-in labels, `ENS_DENY_PATTERN`, and the SMS service's list when
+    /// `ENS_RESERVED_NAMES_URL` is set
+    pub async fn from_env() -> eyre::Result<Self> {
+        let mut policy = Self::default();
+        if let Some(pattern) = std::env::var("ENS_DENY_PATTERN").ok().filter(|p| !p.trim().is_empty()) {
+            policy.rules.push(Rule::new(&pattern, "pattern")?);
+        }
+        if let Some(url) = std::env::var("ENS_RESERVED_NAMES_URL").ok().filter(|url| !url.trim().is_empty()) {
+            let listed = fetch_rules(&url).await?;
+            tracing::info!(rules = listed.len(), "Loaded reserved names from the SMS service");
+            policy.rules.extend(listed);
+        }
+        Ok(policy)
+    }
+
+    /// Why a label can't be minted, if it can't
+    pub fn check(&self, label: &str) -> Option<&str> {
+        let label = label.trim().to_lowercase();
+        self.rules.iter().find(|rule| rule.matches(&label)).map(|rule| rule.kind.as_str())
+    }
+}
+
+/// Rules from `GET /admin/reserved-names`; rows this CLI can't parse are skipped
+async fn fetch_rules(url: &str) -> eyre::Result<Vec<Rule>> {
+    let body: serde_json::Value = reqwest::get(url).await?.error_for_status()?.json().await?;
+    let rules = body["rules"]
+        .as_array()
+        .ok_or_else(|| eyre::eyre!("{} returned no rules", url))?
+        .iter()
+        .filter_map(|rule| {
+            let (label, kind) = (rule["label"].as_str()?, rule["kind"].as_str()?);
+            Rule::new(label, kind).map_err(|e| tracing::warn!(%label, "Skipping reserved name: {}", e)).ok()
+        })
+        .collect();
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_rules() {
+        let mut policy = NamePolicy::default();
+        policy.rules.push(Rule::new("paypal", "brand").unwrap());
+        policy.rules.push(Rule::new("^x{3,}", "pattern").unwrap());
+
+        assert_eq!(policy.check("Support"), Some("reserved"));
+        assert_eq!(policy.check("supporter"), None);
+        assert_eq!(policy.check("mypaypal"), Some("brand"));
+        assert_eq!(policy.check("xxxbob"), Some("pattern"));
+        assert_eq!(policy.check("alice"), None);
+        assert!(Rule::new("abc", "celebrity").is_err());
+    }
+}
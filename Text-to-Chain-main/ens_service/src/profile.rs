@@ -0,0 +1,296 @@
+//! Browser profile pages for subdomains
+//!
+//! A small static page with the user's display name and a payment QR code
+//! is pinned to IPFS, and its CID is set as the name's contenthash
+//! (EIP-1577). Content-hash aware gateways and browsers then show the page
+//! for `alice.ttc.eth`.
+
+use ethers::types::{Address, H256};
+use qrcode::render::svg;
+use qrcode::QrCode;
+
+use crate::ens::EnsMinter;
+
+/// Longest display name shown on a page
+pub const MAX_DISPLAY_NAME_LEN: usize = 64;
+
+/// `ipfs-ns` multicodec, varint encoded
+const IPFS_NAMESPACE: [u8; 2] = [0xe3, 0x01];
+/// CIDv1 version byte and the `dag-pb` codec CIDv0 implies
+const CID_V1: u8 = 0x01;
+const DAG_PB: u8 = 0x70;
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// Contenthash bytes for an IPFS CID, either CIDv0 (`Qm...`) or base32 CIDv1 (`b...`)
+pub fn ipfs_contenthash(cid: &str) -> eyre::Result<Vec<u8>> {
+    let cid = cid.trim();
+    let cid_bytes = if cid.starts_with("Qm") {
+        // CIDv0 is a bare sha2-256 multihash
+        let multihash = base58_decode(cid).ok_or_else(|| eyre::eyre!("invalid CIDv0 {}", cid))?;
+        if multihash.len() != 34 || multihash[..2] != [0x12, 0x20] {
+            eyre::bail!("CIDv0 {} is not a sha2-256 multihash", cid);
+        }
+        [&[CID_V1, DAG_PB][..], &multihash].concat()
+    } else if let Some(encoded) = cid.strip_prefix('b') {
+        let bytes = base32_decode(encoded).ok_or_else(|| eyre::eyre!("invalid base32 CID {}", cid))?;
+        if bytes.first() != Some(&CID_V1) {
+            eyre::bail!("{} is not a CIDv1", cid);
+        }
+        bytes
+    } else {
+        eyre::bail!("unsupported CID {}; expected Qm... or base32 b...", cid);
+    };
+    Ok([&IPFS_NAMESPACE[..], &cid_bytes].concat())
+}
+
+/// Base32 CIDv1 of an IPFS contenthash, None for other namespaces
+pub fn contenthash_to_cid(contenthash: &[u8]) -> Option<String> {
+    let cid = contenthash.strip_prefix(&IPFS_NAMESPACE[..])?;
+    (cid.first() == Some(&CID_V1)).then(|| format!("b{}", base32_encode(cid)))
+}
+
+/// EIP-681 payment request for an address
+pub fn payment_uri(address: Address, chain_id: u64) -> String {
+    format!("ethereum:{:?}@{}", address, chain_id)
+}
+
+/// The profile page: display name, ENS name, address and a QR code of the payment URI
+pub fn render_page(display_name: &str, subdomain: &str, address: Address, chain_id: u64) -> eyre::Result<String> {
+    let uri = payment_uri(address, chain_id);
+    let qr = QrCode::new(uri.as_bytes())?
+        .render::<svg::Color>()
+        .min_dimensions(240, 240)
+        .quiet_zone(true)
+        .build();
+    let display_name = escape_html(display_name.trim());
+    let subdomain = escape_html(subdomain);
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{display_name} · {subdomain}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; max-width: 420px; margin: 2rem auto; padding: 0 1rem; text-align: center; color: #1a1a1a; }}
+h1 {{ margin-bottom: 0.25rem; }}
+.ens {{ color: #5a5a5a; margin-top: 0; }}
+.address {{ font-family: monospace; font-size: 0.8rem; word-break: break-all; }}
+</style>
+</head>
+<body>
+<h1>{display_name}</h1>
+<p class="ens">{subdomain}</p>
+{qr}
+<p>Scan to pay</p>
+<p class="address">{address:?}</p>
+<p><a href="{uri}">Open in wallet</a></p>
+</body>
+</html>
+"#
+    ))
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// IPFS node or pinning service speaking the Kubo HTTP API
+pub struct IpfsClient {
+    api_url: String,
+    /// Bearer token for hosted pinning services
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+impl IpfsClient {
+    /// `IPFS_API_URL` (e.g. http://127.0.0.1:5001) and optional `IPFS_API_TOKEN`
+    pub fn from_env() -> Option<Self> {
+        let api_url = std::env::var("IPFS_API_URL").ok().filter(|url| !url.trim().is_empty())?;
+        let token = std::env::var("IPFS_API_TOKEN").ok().filter(|token| !token.is_empty());
+        Some(Self { api_url: api_url.trim_end_matches('/').to_string(), token, http: reqwest::Client::new() })
+    }
+
+    /// Add and pin a file, returning its CIDv1
+    pub async fn add(&self, file_name: &str, contents: String) -> eyre::Result<String> {
+        let part = reqwest::multipart::Part::text(contents)
+            .file_name(file_name.to_string())
+            .mime_str("text/html")?;
+        let form = reqwest::multipart::Form::new().part("file", part);
+        let mut request = self
+            .http
+            .post(format!("{}/api/v0/add?cid-version=1&pin=true", self.api_url))
+            .multipart(form);
+        if let Some(ref token) = self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            eyre::bail!("IPFS add failed: {}", response.status());
+        }
+        let body: serde_json::Value = response.json().await?;
+        body["Hash"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| eyre::eyre!("IPFS add response has no Hash"))
+    }
+}
+
+/// A profile page that is live on a subdomain
+#[derive(Debug, Clone)]
+pub struct PublishedProfile {
+    pub subdomain: String,
+    pub cid: String,
+    pub tx_hashes: Vec<H256>,
+}
+
+/// Render the page for a minted subdomain, pin it and point the name at it
+pub async fn publish_profile(
+    minter: &EnsMinter,
+    ipfs: &IpfsClient,
+    label: &str,
+    display_name: &str,
+) -> eyre::Result<PublishedProfile> {
+    let display_name = display_name.trim();
+    if display_name.is_empty() || display_name.chars().count() > MAX_DISPLAY_NAME_LEN {
+        eyre::bail!("display name must be 1-{} characters", MAX_DISPLAY_NAME_LEN);
+    }
+    let address = minter.resolve_subdomain(label).await?;
+    if address.is_zero() {
+        eyre::bail!("{} has no addr record", label);
+    }
+    let subdomain = format!("{}.{}", label.to_lowercase(), minter.parent_domain());
+
+    let page = render_page(display_name, &subdomain, address, minter.chain_id())?;
+    let cid = ipfs.add("index.html", page).await?;
+    tracing::info!(%subdomain, %cid, "Profile page pinned");
+
+    let tx_hashes = minter.set_contenthash(label, ipfs_contenthash(&cid)?).await?;
+    Ok(PublishedProfile { subdomain, cid, tx_hashes })
+}
+
+pub(crate) fn base58_decode(input: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in input.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    // Leading '1's are leading zero bytes
+    let zeros = input.bytes().take_while(|&c| c == b'1').count();
+    Some([vec![0; zeros], bytes].concat())
+}
+
+pub(crate) fn base58_encode(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut().rev() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.insert(0, (carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    // Leading zero bytes are leading '1's
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat_n('1', zeros).chain(digits.iter().map(|&d| BASE58_ALPHABET[d as usize] as char)).collect()
+}
+
+fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(input.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in input.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c.to_ascii_lowercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Example from EIP-1577
+    const CID_V0: &str = "QmRAQB6YaCyidP37UdDnjFY5vQuiBrcqdyoW1CuDgwxkD4";
+    const CONTENTHASH: &str = "e3010170122029f2d17be6139079dc48696d1f582a8530eb9805b561eda517e22a892c7e3f1f";
+
+    #[test]
+    fn test_ipfs_contenthash_v0() {
+        assert_eq!(hex::encode(ipfs_contenthash(CID_V0).unwrap()), CONTENTHASH);
+    }
+
+    #[test]
+    fn test_contenthash_roundtrips_through_cid_v1() {
+        let contenthash = hex::decode(CONTENTHASH).unwrap();
+        let cid = contenthash_to_cid(&contenthash).unwrap();
+        assert_eq!(cid, "bafybeibj6lixxzqtsb45ysdjnupvqkufgdvzqbnvmhw2kf7cfkesy7r7d4");
+        assert_eq!(ipfs_contenthash(&cid).unwrap(), contenthash);
+    }
+
+    #[test]
+    fn test_ipfs_contenthash_rejects_bad_cids() {
+        assert!(ipfs_contenthash("Qm0OIl").is_err());
+        assert!(ipfs_contenthash("zb2rhe5P4gXftAwvA4eXQ5HJwsER2owDyS9sKaQRRVQPn93bA").is_err());
+        assert!(ipfs_contenthash("").is_err());
+        assert!(contenthash_to_cid(&[0xe5, 0x01, 0x01]).is_none());
+    }
+
+    #[test]
+    fn test_render_page_escapes_display_name() {
+        let page = render_page("<b>Alice</b>", "alice.ttc.eth", Address::from_low_u64_be(0xA11CE), 11155111).unwrap();
+        assert!(page.contains("&lt;b&gt;Alice&lt;/b&gt;"));
+        assert!(!page.contains("<b>Alice"));
+        assert!(page.contains("ethereum:0x00000000000000000000000000000000000a11ce@11155111"));
+        assert!(page.contains("<svg"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Pre-flight quote for registering a .eth name
+//!
+//! Before anything is committed the registrar prices the name, estimates
+//! gas for the commit and register transactions, and reads the signer's
+//! balance. The quote shows all of it in ETH, and in USD when
+//! `TOKEN_PRICES_URL` gives an ETH price (same feed as the SMS handler:
+//! `{"prices": {"ETH": 3000.0}}`). A wallet that can't cover price + gas
+//! is refused up front instead of failing after the commitment wait. The
+//! CLI prints `lines()`; SMS replies use `sms_message()`.
+
+use ethers::types::{Address, U256};
+use ethers::utils::format_ether;
+use std::time::Duration;
+
+/// Gas for `commit(bytes32)`
+pub const COMMIT_GAS: u64 = 60_000;
+
+/// Gas for `register` with a resolver and reverse record, the most any ABI uses
+pub const REGISTER_GAS: u64 = 350_000;
+
+/// Price, gas and the paying wallet's balance for one registration
+#[derive(Debug, Clone)]
+pub struct RegistrationQuote {
+    /// Full name, e.g. `ttc.eth`
+    pub name: String,
+    pub years: u32,
+    /// Rent price (base + premium) in wei
+    pub price: U256,
+    /// What `register` sends: the price plus a buffer, the rest is refunded
+    pub value: U256,
+    /// Estimated gas for every transaction, in wei at the current gas price
+    pub gas_cost: U256,
+    pub payer: Address,
+    pub balance: U256,
+    /// USD per ETH, when a price feed is configured
+    pub usd_per_eth: Option<f64>,
+}
+
+impl RegistrationQuote {
+    /// Wei the wallet needs: the value sent plus gas
+    pub fn total(&self) -> U256 {
+        self.value + self.gas_cost
+    }
+
+    /// Wei missing from the wallet, if any
+    pub fn shortfall(&self) -> Option<U256> {
+        let total = self.total();
+        (self.balance < total).then(|| total - self.balance)
+    }
+
+    /// What to do when the wallet can't pay, None when it can
+    pub fn shortfall_message(&self) -> Option<String> {
+        let missing = self.shortfall()?;
+        Some(format!(
+            "Wallet {:?} has {} but needs about {} to register {}. Send at least {} to it and try again.",
+            self.payer,
+            self.eth(self.balance),
+            self.eth(self.total()),
+            self.name,
+            self.eth(missing)
+        ))
+    }
+
+    /// Quote for the CLI, one line each
+    pub fn lines(&self) -> Vec<String> {
+        vec![
+            format!("💰 Quote for {} ({} year(s)):", self.name, self.years),
+            format!("   Price:  {}", self.eth(self.price)),
+            format!("   Sent:   {} incl. 10% buffer, unused part refunded", self.eth(self.value)),
+            format!("   Gas:    ~{}", self.eth(self.gas_cost)),
+            format!("   Total:  {}", self.eth(self.total())),
+            format!("   Wallet: {:?} has {}", self.payer, self.eth(self.balance)),
+        ]
+    }
+
+    /// Short quote for an SMS reply or a one-line summary
+    pub fn sms_message(&self) -> String {
+        let total = format!("{} ETH", eth_digits(self.total(), 4));
+        let usd = self.usd_per_eth.map(|price| format!(" (~{})", usd(self.total(), price))).unwrap_or_default();
+        format!("{} for {} yr: {}{} incl. gas.", self.name, self.years, total, usd)
+    }
+
+    /// `0.003125 ETH (~$9.38)`, without the USD part when there is no price
+    fn eth(&self, wei: U256) -> String {
+        match self.usd_per_eth {
+            Some(price) => format!("{} ETH (~{})", eth_digits(wei, 6), usd(wei, price)),
+            None => format!("{} ETH", eth_digits(wei, 6)),
+        }
+    }
+}
+
+/// Wei as ETH with at most `digits` decimals, trailing zeros dropped
+fn eth_digits(wei: U256, digits: usize) -> String {
+    let ether = format_ether(wei);
+    let (whole, fraction) = ether.split_once('.').unwrap_or((&ether, ""));
+    let fraction = fraction.get(..digits).unwrap_or(fraction).trim_end_matches('0');
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+fn usd(wei: U256, usd_per_eth: f64) -> String {
+    let ether: f64 = format_ether(wei).parse().unwrap_or_default();
+    format!("${:.2}", ether * usd_per_eth)
+}
+
+/// USD per ETH from `TOKEN_PRICES_URL`; None when unset or unreachable,
+/// since the quote is still useful in ETH alone
+pub async fn eth_usd_price() -> Option<f64> {
+    let url = std::env::var("TOKEN_PRICES_URL").ok().filter(|url| !url.trim().is_empty())?;
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(3)).build().ok()?;
+    let body: serde_json::Value = match client.get(url.trim()).send().await {
+        Ok(response) => response.json().await.ok()?,
+        Err(e) => {
+            tracing::warn!("ETH price unavailable for the quote: {}", e);
+            return None;
+        }
+    };
+    parse_eth_price(&body)
+}
+
+fn parse_eth_price(body: &serde_json::Value) -> Option<f64> {
+    body["prices"]
+        .as_object()?
+        .iter()
+        .find(|(symbol, _)| symbol.eq_ignore_ascii_case("ETH"))
+        .and_then(|(_, price)| price.as_f64())
+        .filter(|price| price.is_finite() && *price > 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::parse_ether;
+
+    fn quote(balance: &str, usd_per_eth: Option<f64>) -> RegistrationQuote {
+        let price = parse_ether("0.003125").unwrap();
+        RegistrationQuote {
+            name: "ttc.eth".to_string(),
+            years: 1,
+            price,
+            value: price * 110 / 100,
+            gas_cost: parse_ether("0.00082").unwrap(),
+            payer: Address::zero(),
+            balance: parse_ether(balance).unwrap(),
+            usd_per_eth,
+        }
+    }
+
+    #[test]
+    fn test_quote_lines() {
+        let quote = quote("0.1", Some(3000.0));
+        assert_eq!(quote.total(), parse_ether("0.0042575").unwrap());
+        assert!(quote.shortfall().is_none() && quote.shortfall_message().is_none());
+        let lines = quote.lines();
+        assert_eq!(lines[1], "   Price:  0.003125 ETH (~$9.38)");
+        assert_eq!(lines[4], "   Total:  0.004257 ETH (~$12.77)");
+        assert_eq!(quote.sms_message(), "ttc.eth for 1 yr: 0.0042 ETH (~$12.77) incl. gas.");
+    }
+
+    #[test]
+    fn test_shortfall_is_actionable() {
+        let quote = quote("0.001", None);
+        assert_eq!(quote.shortfall(), Some(parse_ether("0.0032575").unwrap()));
+        let message = quote.shortfall_message().unwrap();
+        assert!(message.contains("has 0.001 ETH but needs about 0.004257 ETH to register ttc.eth"), "{}", message);
+        assert!(message.ends_with("Send at least 0.003257 ETH to it and try again."), "{}", message);
+    }
+
+    #[test]
+    fn test_eth_digits_and_price_feed() {
+        assert_eq!(eth_digits(parse_ether("1").unwrap(), 6), "1");
+        assert_eq!(eth_digits(parse_ether("0.0000001").unwrap(), 6), "0");
+        assert_eq!(parse_eth_price(&serde_json::json!({"prices": {"eth": 2500.5, "USDC": 1.0}})), Some(2500.5));
+        assert_eq!(parse_eth_price(&serde_json::json!({"prices": {"ETH": -1}})), None);
+        assert_eq!(parse_eth_price(&serde_json::json!({})), None);
+    }
+}
@@ -1,49 +1,239 @@
 //! ENS Domain Registration module
 //! Handles registering .eth domains directly via ETHRegistrarController on Sepolia
+//!
+//! ENS redeploys the controller on testnets from time to time, so its address
+//! is looked up rather than fixed: `ENS_CONTROLLER` (an address or a name)
+//! when set, else whatever `controller.ens.eth` resolves to, else the last
+//! known Sepolia deployment. The controller's ABI is then probed with its
+//! pure `makeCommitment*` functions, and a controller without
+//! `minCommitmentAge` is treated as commitment-free and registered in one
+//! transaction.
+//!
+//! Between commit and register the controller's `commitments` entry is
+//! polled until it is old enough, reading `minCommitmentAge` afresh each
+//! time, and the wait gives up after `ENS_COMMIT_MAX_WAIT_SECS`. Each step
+//! is reported to a callback, so the CLI can draw a countdown and other
+//! callers can pass it on.
 
 use ethers::prelude::*;
 use ethers::utils::keccak256;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::ens::{ETHRegistrarController, ETH_REGISTRAR_CONTROLLER_SEPOLIA, PUBLIC_RESOLVER_SEPOLIA};
+use crate::quote::{RegistrationQuote, COMMIT_GAS, REGISTER_GAS};
+use crate::ens::{
+    ETHRegistrarController, LegacyETHRegistrarController, ETH_REGISTRAR_CONTROLLER_SEPOLIA, PUBLIC_RESOLVER_SEPOLIA,
+};
+
+type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+/// Name ENS points at the current .eth registrar controller
+pub const CONTROLLER_NAME: &str = "controller.ens.eth";
+
+/// Duration used when probing `makeCommitment`; any non-zero value works
+const PROBE_DURATION: u64 = 365 * 24 * 60 * 60;
+
+/// What `register` sends for a rent price: 10% over, the controller refunds the rest
+pub fn with_buffer(price: U256) -> U256 {
+    price * 110 / 100
+}
+
+/// How long `register_domain` waits for a commitment to mature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitmentWait {
+    /// Longest wait between commit and register; a controller already asking
+    /// for more is refused before anything is sent
+    pub max_wait: Duration,
+    /// How often the commitment is checked against the chain
+    pub poll_interval: Duration,
+}
+
+impl Default for CommitmentWait {
+    fn default() -> Self {
+        Self { max_wait: Duration::from_secs(600), poll_interval: Duration::from_secs(3) }
+    }
+}
+
+impl CommitmentWait {
+    /// `ENS_COMMIT_MAX_WAIT_SECS` and `ENS_COMMIT_POLL_SECS`, defaults for
+    /// whichever is unset
+    pub fn from_env() -> eyre::Result<Self> {
+        let secs = |var: &str| -> eyre::Result<Option<Duration>> {
+            match std::env::var(var).ok().filter(|v| !v.trim().is_empty()) {
+                Some(value) => match value.trim().parse::<u64>() {
+                    Ok(secs) if secs > 0 => Ok(Some(Duration::from_secs(secs))),
+                    _ => Err(eyre::eyre!("{} must be a positive number of seconds, got {:?}", var, value)),
+                },
+                None => Ok(None),
+            }
+        };
+        let default = Self::default();
+        Ok(Self {
+            max_wait: secs("ENS_COMMIT_MAX_WAIT_SECS")?.unwrap_or(default.max_wait),
+            poll_interval: secs("ENS_COMMIT_POLL_SECS")?.unwrap_or(default.poll_interval),
+        })
+    }
+}
+
+/// A step of `register_domain_with_progress`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistrationProgress {
+    /// Sending the commit transaction
+    Committing,
+    Committed { tx: H256 },
+    /// The commitment isn't usable yet; `remaining` is the controller's
+    /// minimum age left at chain time
+    Waiting { elapsed: Duration, remaining: Duration },
+    /// Sending the register transaction
+    Registering,
+    Registered { tx: H256 },
+}
+
+/// Where a commitment stands at chain time `now` (seconds)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Maturity {
+    /// Not seen by the node yet
+    Missing,
+    Pending { remaining: u64 },
+    Ready,
+    /// Older than the controller's `maxCommitmentAge`; it has to be redone
+    Expired,
+}
+
+fn maturity(committed_at: u64, now: u64, min_age: u64, max_age: Option<u64>) -> Maturity {
+    if committed_at == 0 {
+        return Maturity::Missing;
+    }
+    let age = now.saturating_sub(committed_at);
+    if max_age.is_some_and(|max| age > max) {
+        Maturity::Expired
+    } else if age < min_age {
+        Maturity::Pending { remaining: min_age - age }
+    } else {
+        Maturity::Ready
+    }
+}
+
+/// Which register signature a controller accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControllerAbi {
+    /// NameWrapper-era controller: `register(name, owner, duration, secret,
+    /// resolver, data, reverseRecord, fuses)`, price as (base, premium)
+    Wrapped,
+    /// Pre-wrapper controller with `registerWithConfig(name, owner,
+    /// duration, secret, resolver, addr)`, price as one amount
+    LegacyWithConfig,
+    /// Oldest controller: `register(name, owner, duration, secret)` with no
+    /// resolver; records have to be set afterwards
+    Legacy,
+}
 
 /// Domain Registrar - handles registering .eth domains on Sepolia
 pub struct DomainRegistrar {
-    controller: ETHRegistrarController<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    controller: ETHRegistrarController<Client>,
+    legacy: LegacyETHRegistrarController<Client>,
     resolver_address: Address,
+    abi: ControllerAbi,
+    /// The controller has no commit step
+    commitment_free: bool,
+    wait: CommitmentWait,
 }
 
 impl DomainRegistrar {
-    /// Create a new domain registrar
-    pub fn new(
-        client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    /// Create a registrar against the discovered controller; `controller` is
+    /// ENS_CONTROLLER, an address or an ENS name
+    pub async fn new(client: Arc<Client>, controller: Option<&str>) -> eyre::Result<Self> {
+        let controller_address = discover_controller(&client, controller).await?;
+        Self::with_contracts(client, controller_address, PUBLIC_RESOLVER_SEPOLIA.parse()?).await
+    }
+
+    /// Create a registrar against a specific controller and resolver
+    /// (e.g. fixtures deployed on a local anvil node)
+    pub async fn with_contracts(
+        client: Arc<Client>,
+        controller_address: Address,
+        resolver_address: Address,
     ) -> eyre::Result<Self> {
-        let controller_address: Address = ETH_REGISTRAR_CONTROLLER_SEPOLIA.parse()?;
-        let resolver_address: Address = PUBLIC_RESOLVER_SEPOLIA.parse()?;
-        
-        let controller = ETHRegistrarController::new(controller_address, client);
-        
+        let controller = ETHRegistrarController::new(controller_address, client.clone());
+        let legacy = LegacyETHRegistrarController::new(controller_address, client);
+
+        let abi = detect_abi(&controller, &legacy, resolver_address).await?;
+        let commitment_free = match controller.min_commitment_age().call().await {
+            Ok(_) => false,
+            Err(e) if not_supported(&e) => true,
+            Err(e) => return Err(e.into()),
+        };
+
         Ok(Self {
             controller,
+            legacy,
             resolver_address,
+            abi,
+            commitment_free,
+            wait: CommitmentWait::default(),
         })
     }
-    
+
+    /// Wait for commitments per `wait` instead of the defaults
+    pub fn with_commitment_wait(mut self, wait: CommitmentWait) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    pub fn abi(&self) -> ControllerAbi {
+        self.abi
+    }
+
+    pub fn is_commitment_free(&self) -> bool {
+        self.commitment_free
+    }
+
+    pub fn controller_address(&self) -> Address {
+        self.controller.address()
+    }
+
     /// Check if a name is available for registration
     pub async fn is_available(&self, name: &str) -> eyre::Result<bool> {
         let available = self.controller.available(name.to_string()).call().await?;
         Ok(available)
     }
-    
+
     /// Get the price to register a name for a given duration (in seconds)
     pub async fn get_price(&self, name: &str, duration_seconds: u64) -> eyre::Result<U256> {
+        if self.abi != ControllerAbi::Wrapped {
+            return Ok(self.legacy.rent_price(name.to_string(), U256::from(duration_seconds)).call().await?);
+        }
         let (base, premium) = self.controller
             .rent_price(name.to_string(), U256::from(duration_seconds))
             .call()
             .await?;
         Ok(base + premium)
     }
-    
+
+    /// Price, gas and the signer's balance for registering `name`, before
+    /// anything is sent; fails when the name is taken
+    pub async fn quote(&self, name: &str, duration_years: u32, usd_per_eth: Option<f64>) -> eyre::Result<RegistrationQuote> {
+        if !self.is_available(name).await? {
+            return Err(eyre::eyre!("Name {}.eth is not available", name));
+        }
+        let price = self.get_price(name, duration_years as u64 * 365 * 24 * 60 * 60).await?;
+        let client = self.controller.client();
+        let gas_units = if self.commitment_free { REGISTER_GAS } else { COMMIT_GAS + REGISTER_GAS };
+        let gas_price = client.get_gas_price().await?;
+        let payer = client.address();
+        let balance = client.get_balance(payer, None).await?;
+        Ok(RegistrationQuote {
+            name: format!("{}.eth", name),
+            years: duration_years,
+            price,
+            value: with_buffer(price),
+            gas_cost: gas_price * gas_units,
+            payer,
+            balance,
+            usd_per_eth,
+        })
+    }
+
     /// Generate a random secret for the commitment
     pub fn generate_secret() -> [u8; 32] {
         let mut secret = [0u8; 32];
@@ -56,51 +246,134 @@ impl DomainRegistrar {
         secret.copy_from_slice(&hash);
         secret
     }
-    
-    /// Step 1: Make a commitment (to prevent front-running)
+
+    /// Commitment hash for a registration, in the controller's format
+    async fn make_commitment(
+        &self,
+        name: &str,
+        owner: Address,
+        duration_seconds: u64,
+        secret: [u8; 32],
+    ) -> eyre::Result<[u8; 32]> {
+        let commitment = match self.abi {
+            ControllerAbi::Wrapped => {
+                self.controller
+                    .make_commitment(
+                        name.to_string(),
+                        owner,
+                        U256::from(duration_seconds),
+                        secret,
+                        self.resolver_address,
+                        vec![],  // No additional data
+                        true,    // Set reverse record
+                        0,       // No fuses
+                    )
+                    .call()
+                    .await?
+            }
+            ControllerAbi::LegacyWithConfig => {
+                self.legacy
+                    .make_commitment_with_config(name.to_string(), owner, secret, self.resolver_address, owner)
+                    .call()
+                    .await?
+            }
+            ControllerAbi::Legacy => self.legacy.make_commitment(name.to_string(), owner, secret).call().await?,
+        };
+        Ok(commitment)
+    }
+
+    /// Step 1: Make a commitment (to prevent front-running); returns the
+    /// commitment hash and the commit transaction
     pub async fn commit(
         &self,
         name: &str,
         owner: Address,
         duration_seconds: u64,
         secret: [u8; 32],
-    ) -> eyre::Result<H256> {
+    ) -> eyre::Result<([u8; 32], H256)> {
         // Generate commitment hash
-        let commitment = self.controller
-            .make_commitment(
-                name.to_string(),
-                owner,
-                U256::from(duration_seconds),
-                secret,
-                self.resolver_address,
-                vec![],  // No additional data
-                true,    // Set reverse record
-                0,       // No fuses
-            )
-            .call()
-            .await?;
-        
-        println!("📝 Commitment hash: {:?}", commitment);
-        
+        let commitment = self.make_commitment(name, owner, duration_seconds, secret).await?;
+
+        tracing::info!(?commitment, "Submitting commitment");
+
         // Submit commitment
         let tx = self.controller.commit(commitment);
         let pending = tx.send().await?;
         let receipt = pending.await?;
-        
+
         if let Some(receipt) = receipt {
-            println!("   ✅ Commit tx confirmed: {:?}", receipt.transaction_hash);
-            return Ok(receipt.transaction_hash);
+            tracing::info!(tx = ?receipt.transaction_hash, "Commit confirmed");
+            return Ok((commitment, receipt.transaction_hash));
         }
-        
+
         Err(eyre::eyre!("Commit transaction failed"))
     }
-    
+
     /// Get minimum commitment age (wait time between commit and register)
     pub async fn get_min_commitment_age(&self) -> eyre::Result<u64> {
+        if self.commitment_free {
+            return Ok(0);
+        }
         let age = self.controller.min_commitment_age().call().await?;
         Ok(age.as_u64())
     }
-    
+
+    /// Longest a commitment stays usable, when the controller has a limit
+    async fn get_max_commitment_age(&self) -> eyre::Result<Option<u64>> {
+        match self.controller.max_commitment_age().call().await {
+            Ok(age) => Ok(Some(age.as_u64())),
+            Err(e) if not_supported(&e) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Poll `commitment` until the controller would accept a register with
+    /// it, or `max_wait` runs out
+    async fn wait_for_commitment<F>(&self, commitment: [u8; 32], on_progress: &mut F) -> eyre::Result<()>
+    where
+        F: FnMut(RegistrationProgress) + Send,
+    {
+        let client = self.controller.client();
+        let max_age = self.get_max_commitment_age().await?;
+        let started = Instant::now();
+        loop {
+            // Read every time: the controller's owner can change it mid-wait
+            let min_age = self.get_min_commitment_age().await?;
+            let committed_at = self.controller.commitments(commitment).call().await?.as_u64();
+            let block = client
+                .get_block(BlockNumber::Latest)
+                .await?
+                .ok_or_else(|| eyre::eyre!("RPC returned no latest block"))?;
+            // An idle chain (a local node mining on demand) has no new block
+            // to read the time from; the next one will be at least this late
+            let now = block.timestamp.as_u64().max(committed_at + started.elapsed().as_secs());
+
+            let remaining = match maturity(committed_at, now, min_age, max_age) {
+                Maturity::Ready => return Ok(()),
+                Maturity::Expired => {
+                    return Err(eyre::eyre!("Commitment expired before it could be used; start the registration again"));
+                }
+                Maturity::Pending { remaining } => remaining,
+                Maturity::Missing => min_age,
+            };
+            let elapsed = started.elapsed();
+            if elapsed >= self.wait.max_wait {
+                return Err(eyre::eyre!(
+                    "Commitment still not usable after {}s (ENS_COMMIT_MAX_WAIT_SECS)",
+                    self.wait.max_wait.as_secs()
+                ));
+            }
+            let remaining = Duration::from_secs(remaining);
+            on_progress(RegistrationProgress::Waiting { elapsed, remaining });
+            let pause = self
+                .wait
+                .poll_interval
+                .min(remaining.max(Duration::from_secs(1)))
+                .min(self.wait.max_wait - elapsed);
+            tokio::time::sleep(pause).await;
+        }
+    }
+
     /// Step 2: Register the domain (after waiting for commitment age)
     pub async fn register(
         &self,
@@ -110,77 +383,196 @@ impl DomainRegistrar {
         secret: [u8; 32],
         value: U256,
     ) -> eyre::Result<H256> {
-        let tx = self.controller
-            .register(
+        let duration = U256::from(duration_seconds);
+        let tx = match self.abi {
+            ControllerAbi::Wrapped => self.controller.register(
                 name.to_string(),
                 owner,
-                U256::from(duration_seconds),
+                duration,
                 secret,
                 self.resolver_address,
                 vec![],  // No additional data
                 true,    // Set reverse record
                 0,       // No fuses
-            )
-            .value(value);
-        
+            ),
+            ControllerAbi::LegacyWithConfig => {
+                self.legacy
+                    .register_with_config(name.to_string(), owner, duration, secret, self.resolver_address, owner)
+            }
+            ControllerAbi::Legacy => self.legacy.register(name.to_string(), owner, duration, secret),
+        }
+        .value(value);
+
         let pending = tx.send().await?;
         let receipt = pending.await?;
-        
+
         if let Some(receipt) = receipt {
-            println!("   ✅ Register tx confirmed: {:?}", receipt.transaction_hash);
+            tracing::info!(tx = ?receipt.transaction_hash, "Register confirmed");
             return Ok(receipt.transaction_hash);
         }
-        
+
         Err(eyre::eyre!("Register transaction failed"))
     }
-    
-    /// Full registration flow: commit, wait, register
+
+    /// Full registration flow: commit, wait, register (register only on a
+    /// commitment-free controller)
     pub async fn register_domain(
         &self,
         name: &str,
         owner: Address,
         duration_years: u32,
     ) -> eyre::Result<String> {
+        self.register_domain_with_progress(name, owner, duration_years, |_| {}).await
+    }
+
+    /// `register_domain`, reporting each step to `on_progress`
+    pub async fn register_domain_with_progress<F>(
+        &self,
+        name: &str,
+        owner: Address,
+        duration_years: u32,
+        mut on_progress: F,
+    ) -> eyre::Result<String>
+    where
+        F: FnMut(RegistrationProgress) + Send,
+    {
         let duration_seconds = duration_years as u64 * 365 * 24 * 60 * 60;
-        
+
         // Check availability
-        println!("🔍 Checking if {}.eth is available...", name);
         if !self.is_available(name).await? {
             return Err(eyre::eyre!("Name {}.eth is not available", name));
         }
-        println!("   ✅ Name is available!");
-        
+
         // Get price
-        println!("💰 Getting price...");
         let price = self.get_price(name, duration_seconds).await?;
-        let price_with_buffer = price * 110 / 100; // Add 10% buffer for gas fluctuations
-        println!("   Price: {} wei (+ 10% buffer)", price);
-        
+        let price_with_buffer = with_buffer(price);
+        tracing::info!(name, %price, "Name is available (price in wei, + 10% buffer)");
+
         // Generate secret
         let secret = Self::generate_secret();
-        
-        // Step 1: Commit
-        println!("\n📝 Step 1/2: Submitting commitment...");
-        self.commit(name, owner, duration_seconds, secret).await?;
-        
-        // Wait for minimum commitment age
-        let wait_time = self.get_min_commitment_age().await?;
-        println!("\n⏳ Waiting {} seconds for commitment to mature...", wait_time + 5);
-        
-        for i in (1..=(wait_time + 5)).rev() {
-            print!("\r   {} seconds remaining...  ", i);
-            std::io::Write::flush(&mut std::io::stdout()).unwrap();
-            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+        if self.commitment_free {
+            tracing::info!(name, "Registering (controller needs no commitment)");
+            on_progress(RegistrationProgress::Registering);
+            let tx = self.register(name, owner, duration_seconds, secret, price_with_buffer).await?;
+            on_progress(RegistrationProgress::Registered { tx });
+            let full_name = format!("{}.eth", name);
+            tracing::info!(%full_name, "Domain registered");
+            return Ok(full_name);
+        }
+
+        // Don't pay for a commit that can't be used within the cap
+        let min_age = self.get_min_commitment_age().await?;
+        if min_age > self.wait.max_wait.as_secs() {
+            return Err(eyre::eyre!(
+                "Controller needs a {}s commitment wait, over the {}s allowed (ENS_COMMIT_MAX_WAIT_SECS)",
+                min_age,
+                self.wait.max_wait.as_secs()
+            ));
         }
-        println!("\r   ✅ Wait complete!              ");
-        
+
+        // Step 1: Commit
+        tracing::info!(name, "Step 1/2: commit");
+        on_progress(RegistrationProgress::Committing);
+        let (commitment, tx) = self.commit(name, owner, duration_seconds, secret).await?;
+        on_progress(RegistrationProgress::Committed { tx });
+
+        // Wait until the controller accepts the commitment
+        tracing::info!(name, "Waiting about {}s for the commitment to mature", min_age);
+        self.wait_for_commitment(commitment, &mut on_progress).await?;
+
         // Step 2: Register
-        println!("\n📝 Step 2/2: Registering domain...");
-        self.register(name, owner, duration_seconds, secret, price_with_buffer).await?;
-        
+        tracing::info!(name, "Step 2/2: register");
+        on_progress(RegistrationProgress::Registering);
+        let tx = self.register(name, owner, duration_seconds, secret, price_with_buffer).await?;
+        on_progress(RegistrationProgress::Registered { tx });
+
         let full_name = format!("{}.eth", name);
-        println!("\n🎉 Successfully registered {}!", full_name);
-        
+        tracing::info!(%full_name, "Domain registered");
+
         Ok(full_name)
     }
 }
+
+/// Controller address: `configured` (an address or a name to resolve), else
+/// `controller.ens.eth`, else the built-in Sepolia deployment
+pub async fn discover_controller(client: &Client, configured: Option<&str>) -> eyre::Result<Address> {
+    if let Some(configured) = configured.map(str::trim).filter(|c| !c.is_empty()) {
+        if let Ok(address) = configured.parse::<Address>() {
+            return Ok(address);
+        }
+        return client
+            .resolve_name(configured)
+            .await
+            .map_err(|e| eyre::eyre!("ENS_CONTROLLER {} did not resolve: {}", configured, e));
+    }
+    match client.resolve_name(CONTROLLER_NAME).await {
+        Ok(address) if !address.is_zero() => {
+            tracing::info!(controller = ?address, "Registrar controller from {}", CONTROLLER_NAME);
+            Ok(address)
+        }
+        _ => {
+            tracing::warn!("{} did not resolve; using {}", CONTROLLER_NAME, ETH_REGISTRAR_CONTROLLER_SEPOLIA);
+            Ok(ETH_REGISTRAR_CONTROLLER_SEPOLIA.parse()?)
+        }
+    }
+}
+
+/// Probe the controller's pure commitment functions, newest ABI first
+async fn detect_abi(
+    controller: &ETHRegistrarController<Client>,
+    legacy: &LegacyETHRegistrarController<Client>,
+    resolver: Address,
+) -> eyre::Result<ControllerAbi> {
+    let (name, owner, secret) = ("probe".to_string(), Address::zero(), [0u8; 32]);
+
+    let wrapped = controller
+        .make_commitment(name.clone(), owner, U256::from(PROBE_DURATION), secret, resolver, vec![], false, 0)
+        .call()
+        .await;
+    match wrapped {
+        Ok(_) => return Ok(ControllerAbi::Wrapped),
+        Err(e) if not_supported(&e) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    match legacy.make_commitment_with_config(name.clone(), owner, secret, resolver, owner).call().await {
+        Ok(_) => return Ok(ControllerAbi::LegacyWithConfig),
+        Err(e) if not_supported(&e) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    match legacy.make_commitment(name, owner, secret).call().await {
+        Ok(_) => Ok(ControllerAbi::Legacy),
+        Err(e) if not_supported(&e) => {
+            Err(eyre::eyre!("{:?} is not a known registrar controller", controller.address()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The call reverted or returned nothing usable, as calling a function the
+/// contract doesn't have does; RPC failures are not this
+fn not_supported<M: Middleware>(error: &ContractError<M>) -> bool {
+    error.is_revert()
+        || matches!(
+            error,
+            ContractError::DecodingError(_) | ContractError::AbiError(_) | ContractError::DetokenizationError(_)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_maturity() {
+        assert_eq!(maturity(0, 1_000, 60, None), Maturity::Missing);
+        assert_eq!(maturity(1_000, 1_045, 60, None), Maturity::Pending { remaining: 15 });
+        assert_eq!(maturity(1_000, 1_060, 60, None), Maturity::Ready);
+        assert_eq!(maturity(1_000, 1_000, 0, Some(86_400)), Maturity::Ready);
+        assert_eq!(maturity(1_000, 90_000, 60, Some(86_400)), Maturity::Expired);
+        // A node behind the commit's block doesn't underflow
+        assert_eq!(maturity(1_000, 990, 60, None), Maturity::Pending { remaining: 60 });
+    }
+}
@@ -2,6 +2,7 @@
 //! Provides a simple interface for Twilio integration
 
 use crate::ens::EnsMinter;
+use crate::names::NamePolicy;
 use ethers::prelude::*;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -28,6 +29,8 @@ pub struct SmsHandler {
     names: HashMap<String, HashMap<String, Address>>,
     /// ENS minter for on-chain operations
     minter: Option<Arc<EnsMinter>>,
+    /// Labels that can't be registered
+    name_policy: Arc<NamePolicy>,
     /// Parent domain for display
     parent_domain: String,
 }
@@ -39,6 +42,7 @@ impl SmsHandler {
             states: HashMap::new(),
             names: HashMap::new(),
             minter: None,
+            name_policy: Arc::new(NamePolicy::default()),
             parent_domain: parent_domain.to_string(),
         }
     }
@@ -48,6 +52,11 @@ impl SmsHandler {
         self.minter = Some(minter);
     }
 
+    /// Refuse the labels in a shared reserved-names list
+    pub fn set_name_policy(&mut self, name_policy: Arc<NamePolicy>) {
+        self.name_policy = name_policy;
+    }
+
     /// Get the menu text
     fn menu_text(&self) -> String {
         "🌟 Welcome to Lumina ENS!\n\n\
@@ -119,7 +128,11 @@ impl SmsHandler {
         match address_str.parse::<Address>() {
             Ok(address) => {
                 self.states.insert(phone.to_string(), ConversationState::WaitingForName(address));
-                format!("✅ Got it!\n\nNow send a friendly name for:\n{:?}", address)
+                format!(
+                    "✅ Got it!\n\nNow send a friendly name for:\n{:?}\nCheck words: {}",
+                    address,
+                    fingerprint::fingerprint(address.as_fixed_bytes())
+                )
             }
             Err(_) => {
                 "❌ Invalid address!\n\nSend a valid wallet address (0x...) or 'cancel'".to_string()
@@ -144,6 +157,10 @@ impl SmsHandler {
             return "❌ Name must be 1-20 characters!\n\nTry again or send 'cancel'".to_string();
         }
 
+        if self.name_policy.check(name).is_some() {
+            return "❌ That name is reserved!\n\nTry another or send 'cancel'".to_string();
+        }
+
         // Register locally
         let user_names = self.names.entry(phone.to_string()).or_insert_with(HashMap::new);
         user_names.insert(name.to_string(), address);
@@ -220,6 +237,59 @@ impl SmsHandler {
     pub fn reset(&mut self, phone: &str) {
         self.states.insert(phone.to_string(), ConversationState::Menu);
     }
+
+    /// Snapshot of every (label, address) pair registered through this handler
+    pub fn registered_names(&self) -> Vec<(String, Address)> {
+        self.names
+            .values()
+            .flat_map(|names| names.iter().map(|(name, addr)| (name.clone(), *addr)))
+            .collect()
+    }
+}
+
+/// Verify and repair every name registered via SMS, healing partially-minted
+/// subdomains. Returns the number of names that needed repair.
+pub async fn repair_registered_names(handler: &SharedSmsHandler) -> usize {
+    // Snapshot under the lock, then release it so SMS handling isn't blocked on RPC
+    let (minter, names) = {
+        let handler = handler.lock().await;
+        (handler.minter.clone(), handler.registered_names())
+    };
+
+    let Some(minter) = minter else {
+        return 0;
+    };
+
+    let mut repaired = 0;
+    for (label, address) in names {
+        match minter.verify_and_repair(&label, address).await {
+            Ok(report) if !report.was_healthy() => {
+                println!("🔧 Repaired {}: {:?} via {:?}", report.subdomain, report.before, report.steps);
+                repaired += 1;
+            }
+            Ok(_) => {}
+            Err(e) => println!("⚠️ Repair check failed for {}: {}", label, e),
+        }
+    }
+
+    repaired
+}
+
+/// Run `repair_registered_names` once per `interval` (nightly in production)
+pub fn spawn_nightly_repair(
+    handler: SharedSmsHandler,
+    interval: std::time::Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // First tick fires immediately - skip it so startup isn't slowed by RPC calls
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let repaired = repair_registered_names(&handler).await;
+            println!("🩺 Nightly ENS repair complete: {} name(s) repaired", repaired);
+        }
+    })
 }
 
 /// Thread-safe wrapper for use with async web frameworks
@@ -257,10 +327,24 @@ mod tests {
         // Send address
         let reply = handler.handle_sms("+1234", "0x742d35Cc6634C0532925a3b844Bc9e7595f8fE8f").await;
         assert!(reply.contains("Got it"));
+        assert!(reply.contains("Check words: fortune upgrade stock"));
         
         // Send name
         let reply = handler.handle_sms("+1234", "alice").await;
         assert!(reply.contains("Done"));
         assert!(reply.contains("alice.eth"));
     }
+
+    #[tokio::test]
+    async fn test_repair_without_minter_is_noop() {
+        let handler = create_shared_handler("test.eth");
+        {
+            let mut h = handler.lock().await;
+            h.handle_sms("+1234", "1").await;
+            h.handle_sms("+1234", "0x742d35Cc6634C0532925a3b844Bc9e7595f8fE8f").await;
+            h.handle_sms("+1234", "alice").await;
+            assert_eq!(h.registered_names().len(), 1);
+        }
+        assert_eq!(repair_registered_names(&handler).await, 0);
+    }
 }
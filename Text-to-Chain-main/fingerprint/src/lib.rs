@@ -0,0 +1,84 @@
+//! Three-word fingerprints of Ethereum addresses
+//!
+//! A 0x address is easy to mistype and hard to read out over a phone call.
+//! Its fingerprint is three words from the BIP-39 English list, picked by the
+//! first 33 bits of SHA-256 over the 20 address bytes, so the two sides of a
+//! transfer can compare something short instead. The words depend only on
+//! the address bytes: case and the `0x` prefix make no difference.
+//!
+//! Used by the SMS service (SEND and SAVE replies) and the ENS CLI, so both
+//! show the same words for the same address.
+
+use coins_bip39::{English, Wordlist};
+use sha2::{Digest, Sha256};
+
+/// Words in a fingerprint (33 bits, about 8.6 billion combinations)
+pub const WORD_COUNT: usize = 3;
+
+/// Bits of the hash behind each word (2048-word list)
+const BITS_PER_WORD: usize = 11;
+
+/// Fingerprint words of a 20-byte address
+pub fn words(address: &[u8; 20]) -> [&'static str; WORD_COUNT] {
+    let hash = Sha256::digest(address);
+    // The first 33 bits fit in the top of a u64
+    let head = u64::from_be_bytes(hash[..8].try_into().expect("SHA-256 is 32 bytes"));
+    let list = English::get_all();
+    std::array::from_fn(|i| {
+        let shift = 64 - BITS_PER_WORD * (i + 1);
+        list[((head >> shift) & 0x7ff) as usize]
+    })
+}
+
+/// Fingerprint as space-separated words, e.g. `fortune upgrade stock`
+pub fn fingerprint(address: &[u8; 20]) -> String {
+    words(address).join(" ")
+}
+
+/// Fingerprint of a hex address, with or without `0x` (None if it isn't 20 bytes of hex)
+pub fn fingerprint_hex(address: &str) -> Option<String> {
+    let hex = address.trim();
+    let hex = hex.strip_prefix("0x").or_else(|| hex.strip_prefix("0X")).unwrap_or(hex);
+    if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0u8; 20];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(fingerprint(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "0x742d35Cc6634C0532925a3b844Bc9e7595f8fE8f";
+
+    #[test]
+    fn test_same_words_for_any_spelling() {
+        let words = fingerprint_hex(ADDRESS).unwrap();
+        assert_eq!(words.split(' ').count(), WORD_COUNT);
+        assert_eq!(fingerprint_hex(&ADDRESS.to_lowercase()), Some(words.clone()));
+        assert_eq!(fingerprint_hex(&ADDRESS[2..].to_uppercase()), Some(words.clone()));
+
+        // One changed character gives different words
+        let typo = ADDRESS.replace("8fE8f", "8fE8e");
+        assert_ne!(fingerprint_hex(&typo), Some(words));
+    }
+
+    #[test]
+    fn test_known_fingerprint() {
+        // SHA-256 of 20 zero bytes starts 0xde47c9b27e: words 1778, 498, 868
+        assert_eq!(words(&[0u8; 20]), ["tattoo", "dinner", "hold"]);
+        assert_eq!(fingerprint_hex(ADDRESS).as_deref(), Some("fortune upgrade stock"));
+    }
+
+    #[test]
+    fn test_rejects_non_addresses() {
+        assert_eq!(fingerprint_hex("0x1234"), None);
+        assert_eq!(fingerprint_hex(&format!("0x{}", "g".repeat(40))), None);
+        assert_eq!(fingerprint_hex(&format!("0x{}", "+f".repeat(20))), None);
+        assert_eq!(fingerprint_hex("alice.ttcip.eth"), None);
+    }
+}
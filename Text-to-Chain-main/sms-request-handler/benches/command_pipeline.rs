@@ -0,0 +1,63 @@
+//! Command pipeline benchmark
+//!
+//! Starts the server against mock Twilio and RPC and replays a few fixed
+//! traffic mixes, printing p50/p99 latency and DB/RPC calls per mix. Set
+//! `DATABASE_URL` to include the database; without it commands take the
+//! no-database paths. `BENCH_MAX_P99_MS` fails the run when any mix's p99
+//! is over budget, and `BENCH_DURATION_SECS` / `BENCH_RATE` size the runs.
+//!
+//! `cargo bench --bench command_pipeline`
+
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use loadgen::{measure, Mix, Mocks, RunConfig, Server};
+
+/// (name, mix); every user sends JOIN first
+const SCENARIOS: [(&str, &str); 4] = [
+    ("read-only", "5:BALANCE,2:HISTORY,1:HELP"),
+    ("sends", "1:SEND 1 USDC TO {peer}"),
+    ("parse errors", "1:HELLO THERE,1:SEND,1:BALANCE OF"),
+    ("mixed", "5:BALANCE,2:HISTORY,1:HELP,2:SEND 1 USDC TO {peer}"),
+];
+
+fn env_number(name: &str, default: f64) -> f64 {
+    std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let mocks = Mocks::start(0).await.expect("start mocks");
+    let server = Server::spawn(Path::new(env!("CARGO_BIN_EXE_textchain")), &mocks)
+        .await
+        .expect("start textchain");
+    let budget = std::env::var("BENCH_MAX_P99_MS").ok().and_then(|v| v.parse::<f64>().ok());
+
+    let mut over_budget = Vec::new();
+    for (i, (name, mix)) in SCENARIOS.into_iter().enumerate() {
+        let config = RunConfig {
+            rate: env_number("BENCH_RATE", 25.0),
+            duration: Duration::from_secs_f64(env_number("BENCH_DURATION_SECS", 10.0)),
+            users: 100,
+            mix: Mix::parse(mix).expect("scenario mix"),
+            // Users persist across scenarios, so only the first signs them up
+            setup: (i == 0).then(|| "JOIN".to_string()),
+            seed: i as u64 + 1,
+            ..RunConfig::default()
+        };
+        let report = measure(server.base_url(), &mocks, &config).await.expect("run scenario");
+        println!("== {} ==\n{}", name, report);
+
+        let p99_ms = report.overall.p99.as_secs_f64() * 1000.0;
+        if budget.is_some_and(|budget| p99_ms > budget) {
+            over_budget.push(format!("{} (p99 {:.1} ms)", name, p99_ms));
+        }
+    }
+
+    if over_budget.is_empty() {
+        return ExitCode::SUCCESS;
+    }
+    eprintln!("Over the BENCH_MAX_P99_MS budget: {}", over_budget.join(", "));
+    ExitCode::FAILURE
+}
@@ -1,18 +1,107 @@
 use axum::{
-    extract::State,
+    extract::{Path, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use uuid::Uuid;
 
-use crate::db::VoucherRepository;
+use crate::db::{FieldCipher, LocalDenomination, VoucherRepository, MAX_CODE_PREFIX_LEN, MAX_SEEDED_COUNT};
+use crate::money::Money;
+use crate::rates::{local_to_usdc, FxRates};
+use crate::voucher_cards::render_batch;
 
 /// Admin routes state
 #[derive(Clone)]
 pub struct AdminState {
     pub voucher_repo: Arc<VoucherRepository>,
-    pub admin_token: String,
+    pub admin_tokens: AdminTokens,
+    pub rates: FxRates,
+    /// Number printed on voucher cards for REDEEM
+    pub sms_number: String,
+    /// Decrypts phone numbers for the wallet lookup routes
+    pub cipher: FieldCipher,
+}
+
+/// The operator an admin request authenticated as, by token name. Handlers
+/// take it as `Extension<AdminCaller>` to record who made a change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminCaller(pub String);
+
+impl std::fmt::Display for AdminCaller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Admin bearer tokens and the operator each belongs to: `ADMIN_TOKEN` is
+/// "admin", and `ADMIN_TOKENS` adds one per operator as `name:token,...`
+#[derive(Clone, Default)]
+pub struct AdminTokens(Arc<Vec<(String, String)>>);
+
+impl AdminTokens {
+    pub fn new(admin_token: &str, named: &str) -> Self {
+        let mut tokens = vec![("admin".to_string(), admin_token.to_string())];
+        tokens.extend(parse_named_tokens(named).unwrap_or_default());
+        Self(Arc::new(tokens))
+    }
+
+    fn caller(&self, headers: &HeaderMap) -> Option<AdminCaller> {
+        self.0
+            .iter()
+            .find(|(_, token)| bearer_matches(headers, token))
+            .map(|(name, _)| AdminCaller(name.clone()))
+    }
+}
+
+/// `name:token` pairs from `ADMIN_TOKENS`; `None` if any entry lacks either part
+pub fn parse_named_tokens(named: &str) -> Option<Vec<(String, String)>> {
+    named
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (name, token) = entry.split_once(':')?;
+            let (name, token) = (name.trim(), token.trim());
+            (!name.is_empty() && !token.is_empty()).then(|| (name.to_string(), token.to_string()))
+        })
+        .collect()
+}
+
+/// Admin paths that check a token of their own: the event stream takes
+/// `ADMIN_EVENTS_TOKEN`, since browsers can't set headers on a WebSocket
+const SELF_AUTHENTICATED: &[&str] = &["/admin/events"];
+
+/// Middleware: reject every `/admin/` request without
+/// `Authorization: Bearer <token>` for one of the admin tokens, and tell the
+/// handler who it was. Applied to the whole app by path, so admin routes added
+/// later are covered too, and before idempotent replays so a stored response
+/// is never handed to an unauthenticated caller.
+pub async fn require_admin_token(State(tokens): State<AdminTokens>, mut request: Request, next: Next) -> Response {
+    if needs_admin_token(request.uri().path()) {
+        let Some(caller) = tokens.caller(request.headers()) else {
+            return StatusCode::UNAUTHORIZED.into_response();
+        };
+        request.extensions_mut().insert(caller);
+    }
+    next.run(request).await
+}
+
+fn needs_admin_token(path: &str) -> bool {
+    path.starts_with("/admin/") && !SELF_AUTHENTICATED.contains(&path)
+}
+
+/// An empty token matches nothing
+fn bearer_matches(headers: &HeaderMap, token: &str) -> bool {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    !token.is_empty() && bearer == Some(token)
 }
 
 /// Request to create vouchers
@@ -20,13 +109,33 @@ pub struct AdminState {
 pub struct CreateVouchersRequest {
     /// Number of vouchers to create
     pub count: usize,
-    /// USDC amount per voucher (e.g., 10.00 for $10)
-    pub usdc_amount: f64,
+    /// USDC amount per voucher (e.g., 10.00 or "10.00" for $10)
+    #[serde(default)]
+    pub usdc_amount: Option<Money>,
+    /// Local currency code (e.g. "KES", "NGN") - set with `local_amount`
+    /// instead of `usdc_amount`; USDC is computed at today's rate
+    pub currency: Option<String>,
+    /// Face value per voucher in `currency`
+    pub local_amount: Option<f64>,
     /// Optional prefix for voucher codes
     #[serde(default = "default_prefix")]
     pub prefix: String,
     /// Optional expiration days from now
     pub expires_in_days: Option<i64>,
+    /// Derive codes from a seed instead of storing them, for batches too
+    /// large to insert; list them with `GET /admin/vouchers/seeded/:id/codes`
+    #[serde(default)]
+    pub seeded: bool,
+}
+
+/// Most codes per `GET /admin/vouchers/seeded/:id/codes` page
+const MAX_SEEDED_PAGE: u64 = 100_000;
+
+#[derive(Debug, Deserialize)]
+pub struct SeededCodesQuery {
+    #[serde(default)]
+    pub offset: u64,
+    pub limit: Option<u64>,
 }
 
 fn default_prefix() -> String {
@@ -37,9 +146,38 @@ fn default_prefix() -> String {
 #[derive(Debug, Serialize)]
 pub struct CreateVouchersResponse {
     pub success: bool,
+    /// Batch id for `GET /admin/vouchers/batches/:id/pdf`, or for
+    /// `GET /admin/vouchers/seeded/:id/codes` when seeded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_id: Option<Uuid>,
     pub count: usize,
-    pub usdc_amount: f64,
+    pub usdc_amount: Money,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_amount: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fx_rate: Option<f64>,
+    /// Empty for seeded batches
     pub codes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CreateVouchersResponse {
+    fn failed(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            batch_id: None,
+            count: 0,
+            usdc_amount: Money::usdc(0),
+            currency: None,
+            local_amount: None,
+            fx_rate: None,
+            codes: vec![],
+            error: Some(error.into()),
+        }
+    }
 }
 
 /// Voucher stats response
@@ -48,8 +186,12 @@ pub struct VoucherStatsResponse {
     pub total: i64,
     pub unused: i64,
     pub redeemed: i64,
-    pub total_value_unused: f64,
-    pub total_value_redeemed: f64,
+    pub total_value_unused: Money,
+    pub total_value_redeemed: Money,
+    /// Including expired vouchers
+    pub total_value: Money,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 /// Create admin routes
@@ -58,6 +200,8 @@ pub fn admin_routes(state: AdminState) -> Router {
         .route("/vouchers", post(create_vouchers))
         .route("/vouchers", get(get_voucher_stats))
         .route("/vouchers/list", get(list_vouchers))
+        .route("/vouchers/batches/:batch_id/pdf", get(voucher_batch_pdf))
+        .route("/vouchers/seeded/:batch_id/codes", get(seeded_codes))
         .with_state(state)
 }
 
@@ -66,37 +210,175 @@ async fn create_vouchers(
     State(state): State<AdminState>,
     Json(req): Json<CreateVouchersRequest>,
 ) -> Json<CreateVouchersResponse> {
-    // Convert USDC to micro USDC (6 decimals)
-    let usdc_micro = (req.usdc_amount * 1_000_000.0) as i64;
+    // Either a USDC amount, or a local amount converted at the current rate
+    let (usdc_amount, local) = match (req.usdc_amount, req.currency, req.local_amount) {
+        (Some(usdc), None, None) => (usdc, None),
+        (None, Some(currency), Some(amount)) if amount > 0.0 => {
+            let currency = currency.trim().to_uppercase();
+            let rate = match state.rates.rate(&currency).await {
+                Ok(rate) => rate,
+                Err(e) => return Json(CreateVouchersResponse::failed(e.to_string())),
+            };
+            (local_to_usdc(amount, rate), Some(LocalDenomination { currency, amount, rate }))
+        }
+        _ => {
+            return Json(CreateVouchersResponse::failed(
+                "Set either usdc_amount, or currency and a positive local_amount",
+            ))
+        }
+    };
 
-    // Generate codes
-    let codes = VoucherRepository::generate_codes(req.count, &req.prefix);
+    if !usdc_amount.is_positive() {
+        return Json(CreateVouchersResponse::failed("Voucher amount must be positive"));
+    }
+
+    if req.prefix.len() > MAX_CODE_PREFIX_LEN || !req.prefix.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Json(CreateVouchersResponse::failed(format!(
+            "prefix must be at most {} letters",
+            MAX_CODE_PREFIX_LEN
+        )));
+    }
 
     // Calculate expiration
     let expires_at = req.expires_in_days.map(|days| {
         chrono::Utc::now() + chrono::Duration::days(days)
     });
 
-    // Create vouchers in database
-    match state.voucher_repo.create_batch(&codes, usdc_micro, expires_at).await {
-        Ok(vouchers) => {
+    if req.seeded {
+        return create_seeded(&state, req.count, &req.prefix, usdc_amount, expires_at, local).await;
+    }
+
+    // Generate codes and create vouchers in database
+    match state
+        .voucher_repo
+        .create_generated(req.count, &req.prefix, usdc_amount, expires_at, local.as_ref())
+        .await
+    {
+        Ok((batch_id, vouchers)) => {
             let created_codes: Vec<String> = vouchers.iter().map(|v| v.code.clone()).collect();
             Json(CreateVouchersResponse {
                 success: true,
+                batch_id: Some(batch_id),
                 count: created_codes.len(),
-                usdc_amount: req.usdc_amount,
+                usdc_amount,
+                currency: local.as_ref().map(|l| l.currency.clone()),
+                local_amount: local.as_ref().map(|l| l.amount),
+                fx_rate: local.as_ref().map(|l| l.rate),
                 codes: created_codes,
+                error: None,
             })
         }
         Err(e) => {
             tracing::error!("Failed to create vouchers: {}", e);
             Json(CreateVouchersResponse {
-                success: false,
-                count: 0,
-                usdc_amount: req.usdc_amount,
+                usdc_amount,
+                ..CreateVouchersResponse::failed("Database error")
+            })
+        }
+    }
+}
+
+/// Register a seeded batch; its codes are listed separately
+async fn create_seeded(
+    state: &AdminState,
+    count: usize,
+    prefix: &str,
+    usdc_amount: Money,
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    local: Option<LocalDenomination>,
+) -> Json<CreateVouchersResponse> {
+    if prefix.is_empty() {
+        return Json(CreateVouchersResponse::failed("seeded batches need a prefix"));
+    }
+    if count == 0 || count as i64 > MAX_SEEDED_COUNT {
+        return Json(CreateVouchersResponse::failed(format!("count must be 1-{}", MAX_SEEDED_COUNT)));
+    }
+
+    match state
+        .voucher_repo
+        .create_seeded(count as i64, prefix, usdc_amount, expires_at, local.as_ref())
+        .await
+    {
+        Ok(batch) => {
+            tracing::info!(batch = %batch.id, prefix = %batch.prefix, count = batch.count, "Seeded voucher batch created");
+            Json(CreateVouchersResponse {
+                success: true,
+                batch_id: Some(batch.id),
+                count,
+                usdc_amount,
+                currency: batch.local_currency,
+                local_amount: batch.local_amount,
+                fx_rate: batch.fx_rate,
                 codes: vec![],
+                error: None,
             })
         }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Json(CreateVouchersResponse {
+            usdc_amount,
+            ..CreateVouchersResponse::failed("prefix is already used by a seeded batch")
+        }),
+        Err(e) => {
+            tracing::error!("Failed to create seeded vouchers: {}", e);
+            Json(CreateVouchersResponse {
+                usdc_amount,
+                ..CreateVouchersResponse::failed("Database error")
+            })
+        }
+    }
+}
+
+/// Codes of a seeded batch, one per line, from `offset` (index of the first code)
+async fn seeded_codes(
+    State(state): State<AdminState>,
+    Path(batch_id): Path<Uuid>,
+    Query(query): Query<SeededCodesQuery>,
+) -> Response {
+    let batch = match state.voucher_repo.find_seeded(batch_id).await {
+        Ok(Some(batch)) => batch,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Seeded batch not found").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to load seeded batch: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    };
+    let limit = query.limit.unwrap_or(MAX_SEEDED_PAGE).clamp(1, MAX_SEEDED_PAGE);
+    let end = query.offset.saturating_add(limit).min(batch.count.max(0) as u64);
+
+    let mut body = String::new();
+    for index in query.offset..end {
+        body.push_str(&batch.code(index));
+        body.push('\n');
+    }
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body).into_response()
+}
+
+/// Printable cards for a voucher batch, one per voucher
+async fn voucher_batch_pdf(State(state): State<AdminState>, Path(batch_id): Path<Uuid>) -> Response {
+    let vouchers = match state.voucher_repo.list_batch(batch_id).await {
+        Ok(vouchers) if vouchers.is_empty() => return (StatusCode::NOT_FOUND, "Batch not found").into_response(),
+        Ok(vouchers) => vouchers,
+        Err(e) => {
+            tracing::error!("Failed to load voucher batch: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    };
+
+    match render_batch(&vouchers, &state.sms_number) {
+        Ok(pdf) => (
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"vouchers-{}.pdf\"", batch_id),
+                ),
+            ],
+            pdf,
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render voucher batch {}: {}", batch_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Render error").into_response()
+        }
     }
 }
 
@@ -104,7 +386,7 @@ async fn create_vouchers(
 #[derive(Debug, Serialize)]
 pub struct VoucherInfo {
     pub code: String,
-    pub usdc_amount: f64,
+    pub usdc_amount: Money,
     pub status: String,
     pub redeemed_by: Option<String>,
 }
@@ -117,17 +399,29 @@ pub struct ListVouchersResponse {
 
 /// Get voucher statistics
 async fn get_voucher_stats(State(state): State<AdminState>) -> Json<VoucherStatsResponse> {
-    // Query stats from database
-    let pool = &state.voucher_repo;
-    
-    // For now, return placeholder - would need to add stats query to repo
-    Json(VoucherStatsResponse {
-        total: 0,
-        unused: 0,
-        redeemed: 0,
-        total_value_unused: 0.0,
-        total_value_redeemed: 0.0,
-    })
+    match state.voucher_repo.stats().await {
+        Ok(stats) => Json(VoucherStatsResponse {
+            total: stats.total,
+            unused: stats.unused,
+            redeemed: stats.redeemed,
+            total_value_unused: stats.value_unused,
+            total_value_redeemed: stats.value_redeemed,
+            total_value: stats.value_total,
+            error: None,
+        }),
+        Err(e) => {
+            tracing::error!("Failed to load voucher stats: {}", e);
+            Json(VoucherStatsResponse {
+                total: 0,
+                unused: 0,
+                redeemed: 0,
+                total_value_unused: Money::usdc(0),
+                total_value_redeemed: Money::usdc(0),
+                total_value: Money::usdc(0),
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
 }
 
 /// List all vouchers (paginated)
@@ -137,3 +431,91 @@ async fn list_vouchers(State(_state): State<AdminState>) -> Json<ListVouchersRes
         vouchers: vec![],
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bearer_matches() {
+        let mut headers = HeaderMap::new();
+        assert!(!bearer_matches(&headers, "secret"));
+
+        headers.insert(header::AUTHORIZATION, "secret".parse().unwrap());
+        assert!(!bearer_matches(&headers, "secret"));
+        headers.insert(header::AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        assert!(!bearer_matches(&headers, "secret"));
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        assert!(bearer_matches(&headers, "secret"));
+
+        headers.insert(header::AUTHORIZATION, "Bearer ".parse().unwrap());
+        assert!(!bearer_matches(&headers, ""));
+    }
+
+    #[tokio::test]
+    async fn test_admin_paths_need_the_token() {
+        let ok = || async { "ok" };
+        let app = Router::new()
+            .route("/admin/partner-keys", post(ok))
+            .route("/admin/events", get(ok))
+            .route("/health", get(ok))
+            .layer(axum::middleware::from_fn_with_state(AdminTokens::new("secret", ""), require_admin_token));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let status = |request: reqwest::RequestBuilder| async move { request.send().await.unwrap().status().as_u16() };
+        assert_eq!(status(client.post(format!("{}/admin/partner-keys", base))).await, 401);
+        assert_eq!(status(client.post(format!("{}/admin/partner-keys", base)).bearer_auth("wrong")).await, 401);
+        assert_eq!(status(client.post(format!("{}/admin/partner-keys", base)).bearer_auth("secret")).await, 200);
+        // Unknown admin paths don't reveal what exists
+        assert_eq!(status(client.get(format!("{}/admin/nope", base))).await, 401);
+        assert_eq!(status(client.get(format!("{}/admin/events", base))).await, 200);
+        assert_eq!(status(client.get(format!("{}/health", base))).await, 200);
+    }
+
+    #[test]
+    fn test_needs_admin_token() {
+        // Routes that move funds, change live behaviour or read user data
+        let paths = [
+            "/admin/deposit-sweeps/run",
+            "/admin/kill-switch",
+            "/admin/chains",
+            "/admin/kyc/00000000-0000-0000-0000-000000000000",
+            "/admin/config/reload",
+            "/admin/reserved-names",
+            "/admin/broadcast",
+            "/admin/tokens/base/USDC",
+            "/admin/referrals",
+            "/admin/spam/samples",
+            "/admin/statements/00000000-0000-0000-0000-000000000000",
+            "/admin/summary",
+        ];
+        for path in paths {
+            assert!(needs_admin_token(path), "{path}");
+        }
+        assert!(!needs_admin_token("/admin/events"));
+        assert!(!needs_admin_token("/administrator"));
+        assert!(!needs_admin_token("/partner/deposits"));
+    }
+
+    #[test]
+    fn test_admin_tokens_name_the_caller() {
+        let tokens = AdminTokens::new("root-token", "alice:a-token, bob:b-token");
+        let caller = |token: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+            tokens.caller(&headers)
+        };
+        assert_eq!(caller("root-token"), Some(AdminCaller("admin".to_string())));
+        assert_eq!(caller("a-token"), Some(AdminCaller("alice".to_string())));
+        assert_eq!(caller("b-token"), Some(AdminCaller("bob".to_string())));
+        assert_eq!(caller("alice"), None);
+
+        assert_eq!(parse_named_tokens(""), Some(vec![]));
+        assert_eq!(parse_named_tokens("alice"), None);
+        assert_eq!(parse_named_tokens("alice:"), None);
+        assert_eq!(parse_named_tokens(":token"), None);
+    }
+}
@@ -0,0 +1,161 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{Agent, AgentError, AgentRepository};
+use crate::money::Money;
+
+/// Admin agent routes state
+#[derive(Clone)]
+pub struct AdminAgentState {
+    pub agent_repo: AgentRepository,
+}
+
+/// Request to register a cash agent
+#[derive(Debug, Deserialize)]
+pub struct RegisterAgentRequest {
+    /// Agent's phone number in E.164 format
+    pub phone: String,
+    /// Name shown to customers
+    pub name: String,
+}
+
+/// Request to top up an agent's float
+#[derive(Debug, Deserialize)]
+pub struct TopUpRequest {
+    /// Amount in USDC (e.g., 1000 or "1000.50")
+    pub amount: Money,
+    /// Bank transfer / receipt reference for reconciliation
+    pub reference: Option<String>,
+}
+
+/// Agent info with current float
+#[derive(Debug, Serialize)]
+pub struct AgentInfo {
+    pub id: Uuid,
+    pub phone: String,
+    pub name: String,
+    pub active: bool,
+    /// Float in USDC
+    pub float: Money,
+    pub created_at: String,
+}
+
+impl AgentInfo {
+    fn new(agent: Agent, float: i64) -> Self {
+        Self {
+            id: agent.id,
+            phone: agent.phone,
+            name: agent.name,
+            active: agent.active,
+            float: Money::usdc(float),
+            created_at: agent.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Single agent response
+#[derive(Debug, Serialize)]
+pub struct AgentResponse {
+    pub success: bool,
+    pub agent: Option<AgentInfo>,
+    pub error: Option<String>,
+}
+
+impl AgentResponse {
+    fn ok(agent: AgentInfo) -> Self {
+        Self { success: true, agent: Some(agent), error: None }
+    }
+
+    fn failed(error: impl ToString) -> Self {
+        Self { success: false, agent: None, error: Some(error.to_string()) }
+    }
+}
+
+/// List agents response
+#[derive(Debug, Serialize)]
+pub struct ListAgentsResponse {
+    pub success: bool,
+    pub agents: Vec<AgentInfo>,
+}
+
+/// Create admin agent routes, mounted under `/admin` behind the admin token
+pub fn admin_agent_routes(agent_repo: AgentRepository) -> Router {
+    Router::new()
+        .route("/agents", post(register_agent))
+        .route("/agents", get(list_agents))
+        .route("/agents/:id/float", post(top_up_float))
+        .with_state(AdminAgentState { agent_repo })
+}
+
+/// Register a phone number as a cash agent
+async fn register_agent(
+    State(state): State<AdminAgentState>,
+    Json(req): Json<RegisterAgentRequest>,
+) -> Json<AgentResponse> {
+    if !req.phone.starts_with('+') || req.name.trim().is_empty() {
+        return Json(AgentResponse::failed("phone must be E.164 (+...) and name non-empty"));
+    }
+
+    match state.agent_repo.register(&req.phone, req.name.trim()).await {
+        Ok(agent) => {
+            let float = state.agent_repo.float_balance(agent.id).await.unwrap_or(0);
+            tracing::info!(agent = %agent.id, "Agent registered");
+            Json(AgentResponse::ok(AgentInfo::new(agent, float)))
+        }
+        Err(e) => {
+            tracing::error!("Failed to register agent: {}", e);
+            Json(AgentResponse::failed("Database error"))
+        }
+    }
+}
+
+/// List agents with their float
+async fn list_agents(State(state): State<AdminAgentState>) -> Json<ListAgentsResponse> {
+    match state.agent_repo.list_with_float().await {
+        Ok(agents) => Json(ListAgentsResponse {
+            success: true,
+            agents: agents.into_iter().map(|(agent, float)| AgentInfo::new(agent, float)).collect(),
+        }),
+        Err(e) => {
+            tracing::error!("Failed to list agents: {}", e);
+            Json(ListAgentsResponse { success: false, agents: vec![] })
+        }
+    }
+}
+
+/// Credit an agent's float after receiving their cash/bank deposit
+async fn top_up_float(
+    State(state): State<AdminAgentState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<TopUpRequest>,
+) -> Json<AgentResponse> {
+    if !req.amount.is_positive() {
+        return Json(AgentResponse::failed("Amount must be positive"));
+    }
+
+    let agent = match state.agent_repo.find(id).await {
+        Ok(Some(agent)) => agent,
+        Ok(None) => return Json(AgentResponse::failed("Agent not found")),
+        Err(e) => {
+            tracing::error!("Failed to fetch agent: {}", e);
+            return Json(AgentResponse::failed("Database error"));
+        }
+    };
+
+    match state.agent_repo.top_up(id, req.amount.micros(), req.reference.as_deref()).await {
+        Ok(float) => {
+            tracing::info!(agent = %id, amount = %req.amount, "Agent float topped up");
+            Json(AgentResponse::ok(AgentInfo::new(agent, float)))
+        }
+        Err(AgentError::Ledger(e)) => Json(AgentResponse::failed(e)),
+        Err(e) => {
+            tracing::error!("Failed to top up float: {}", e);
+            Json(AgentResponse::failed("Database error"))
+        }
+    }
+}
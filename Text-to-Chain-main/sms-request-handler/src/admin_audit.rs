@@ -0,0 +1,73 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use serde::Serialize;
+
+use crate::db::{AuditLogRepository, AuditVerification};
+
+/// Largest admin request body accepted (matches axum's default limit)
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+/// Request body characters kept in the audit detail
+const MAX_DETAIL_BODY: usize = 2000;
+
+/// Audit chain check result
+#[derive(Debug, Serialize)]
+pub struct VerifyResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<AuditVerification>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Create admin audit routes
+pub fn admin_audit_routes(audit: AuditLogRepository) -> Router {
+    Router::new()
+        .route("/audit/verify", get(verify_audit_log))
+        .with_state(audit)
+}
+
+/// Walk the audit chain and report the first tampered row, if any
+async fn verify_audit_log(State(audit): State<AuditLogRepository>) -> Json<VerifyResponse> {
+    match audit.verify().await {
+        Ok(verification) => {
+            if !verification.valid {
+                tracing::error!(seq = ?verification.first_invalid_seq, "Audit log chain is broken");
+            }
+            Json(VerifyResponse { success: true, verification: Some(verification), error: None })
+        }
+        Err(e) => Json(VerifyResponse { success: false, verification: None, error: Some(e.to_string()) }),
+    }
+}
+
+/// Middleware: record every state-changing `/admin` request (method, path,
+/// body and response status) in the audit log
+pub async fn audit_admin_requests(State(audit): State<AuditLogRepository>, request: Request, next: Next) -> Response {
+    let is_admin_write = request.uri().path().starts_with("/admin/")
+        && !matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !is_admin_write {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let body_text: String = String::from_utf8_lossy(&bytes).chars().take(MAX_DETAIL_BODY).collect();
+    let target = parts.uri.path_and_query().map(|p| p.as_str().to_string()).unwrap_or_default();
+    let method = parts.method.clone();
+
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+
+    let detail = format!("{} {} {} => {}", method, target, body_text.trim(), response.status().as_u16());
+    if let Err(e) = audit.record_admin("ADMIN", &detail).await {
+        tracing::error!(%method, %target, "Audit log write failed: {}", e);
+    }
+    response
+}
@@ -0,0 +1,191 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::beta::{notify_admitted, BetaAccess};
+use crate::db::{generate_code, AllowlistEntry, WaitlistEntry};
+use crate::sms::SmsGateway;
+
+/// Prefix for generated invite codes
+const INVITE_PREFIX: &str = "INV";
+const MAX_INVITES_PER_REQUEST: usize = 500;
+
+#[derive(Clone)]
+pub struct AdminBetaState {
+    pub beta: BetaAccess,
+    /// Tells waitlisted numbers when an admin lets them in
+    pub twilio: SmsGateway,
+}
+
+/// Request to allowlist numbers
+#[derive(Debug, Deserialize)]
+pub struct AllowRequest {
+    pub phones: Vec<String>,
+    pub note: Option<String>,
+}
+
+/// Request to generate invite codes
+#[derive(Debug, Deserialize)]
+pub struct CreateInvitesRequest {
+    pub count: usize,
+    /// Sign-ups per code (default 1)
+    #[serde(default = "default_max_uses")]
+    pub max_uses: i32,
+}
+
+fn default_max_uses() -> i32 {
+    1
+}
+
+#[derive(Debug, Serialize)]
+pub struct BetaStatusResponse {
+    pub success: bool,
+    pub capacity: i64,
+    /// Wallets plus admitted numbers not yet signed up
+    pub seats_taken: i64,
+    pub allowlisted: usize,
+    pub waiting: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BetaListResponse<T> {
+    pub success: bool,
+    pub entries: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<T> BetaListResponse<T> {
+    fn from_result(result: Result<Vec<T>, sqlx::Error>) -> Self {
+        match result {
+            Ok(entries) => Self { success: true, entries, error: None },
+            Err(e) => Self { success: false, entries: vec![], error: Some(e.to_string()) },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BetaActionResponse {
+    pub success: bool,
+    /// Numbers allowlisted, or invite codes created
+    pub items: Vec<String>,
+    /// Waitlisted numbers that were let in and notified
+    pub admitted: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BetaActionResponse {
+    fn failed(error: impl ToString) -> Self {
+        Self { success: false, items: vec![], admitted: 0, error: Some(error.to_string()) }
+    }
+}
+
+/// Create admin beta access routes
+pub fn admin_beta_routes(state: AdminBetaState) -> Router {
+    Router::new()
+        .route("/beta", get(beta_status))
+        .route("/beta/allowlist", get(list_allowlist).post(allow_numbers))
+        .route("/beta/allowlist/:phone", delete(disallow_number))
+        .route("/beta/invites", post(create_invites))
+        .route("/beta/waitlist", get(list_waitlist))
+        .with_state(state)
+}
+
+/// Seats, allowlist and waitlist at a glance
+async fn beta_status(State(state): State<AdminBetaState>) -> Json<BetaStatusResponse> {
+    let repo = state.beta.repo();
+    let counts = async {
+        let seats_taken = repo.seats_taken().await?;
+        let allowlisted = repo.list_allowlist().await?.len();
+        let waiting = repo.list_waitlist().await?.iter().filter(|e| e.admitted_at.is_none()).count();
+        Ok::<_, sqlx::Error>((seats_taken, allowlisted, waiting))
+    };
+
+    match counts.await {
+        Ok((seats_taken, allowlisted, waiting)) => Json(BetaStatusResponse {
+            success: true,
+            capacity: state.beta.capacity(),
+            seats_taken,
+            allowlisted,
+            waiting,
+            error: None,
+        }),
+        Err(e) => Json(BetaStatusResponse {
+            success: false,
+            capacity: state.beta.capacity(),
+            seats_taken: 0,
+            allowlisted: 0,
+            waiting: 0,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+async fn list_allowlist(State(state): State<AdminBetaState>) -> Json<BetaListResponse<AllowlistEntry>> {
+    Json(BetaListResponse::from_result(state.beta.repo().list_allowlist().await))
+}
+
+async fn list_waitlist(State(state): State<AdminBetaState>) -> Json<BetaListResponse<WaitlistEntry>> {
+    Json(BetaListResponse::from_result(state.beta.repo().list_waitlist().await))
+}
+
+/// Allowlist numbers; any that were waiting are told they're in
+async fn allow_numbers(State(state): State<AdminBetaState>, Json(req): Json<AllowRequest>) -> Json<BetaActionResponse> {
+    let mut items = Vec::new();
+    let mut admitted = 0;
+
+    for phone in req.phones.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        if !phone.starts_with('+') {
+            return Json(BetaActionResponse::failed(format!("Phone must be in E.164 format: {}", phone)));
+        }
+        match state.beta.repo().allow(phone, req.note.as_deref()).await {
+            Ok(was_waiting) => {
+                if was_waiting {
+                    notify_admitted(&state.twilio, phone).await;
+                    admitted += 1;
+                }
+                items.push(phone.to_string());
+            }
+            Err(e) => return Json(BetaActionResponse { success: false, items, admitted, error: Some(e.to_string()) }),
+        }
+    }
+
+    tracing::info!(allowed = items.len(), admitted, "Beta allowlist updated");
+    Json(BetaActionResponse { success: true, items, admitted, error: None })
+}
+
+async fn disallow_number(State(state): State<AdminBetaState>, Path(phone): Path<String>) -> Json<BetaActionResponse> {
+    match state.beta.repo().disallow(&phone).await {
+        Ok(true) => Json(BetaActionResponse { success: true, items: vec![phone], admitted: 0, error: None }),
+        Ok(false) => Json(BetaActionResponse::failed(format!("{} is not allowlisted", phone))),
+        Err(e) => Json(BetaActionResponse::failed(e)),
+    }
+}
+
+/// Generate invite codes for INVITE <code>
+async fn create_invites(State(state): State<AdminBetaState>, Json(req): Json<CreateInvitesRequest>) -> Json<BetaActionResponse> {
+    if req.count == 0 || req.count > MAX_INVITES_PER_REQUEST {
+        return Json(BetaActionResponse::failed(format!("count must be 1-{}", MAX_INVITES_PER_REQUEST)));
+    }
+    if req.max_uses < 1 {
+        return Json(BetaActionResponse::failed("max_uses must be at least 1"));
+    }
+
+    let mut items = Vec::with_capacity(req.count);
+    for _ in 0..req.count {
+        let code = generate_code(INVITE_PREFIX);
+        if let Err(e) = state.beta.repo().create_invite(&code, req.max_uses).await {
+            return Json(BetaActionResponse { success: false, items, admitted: 0, error: Some(e.to_string()) });
+        }
+        items.push(code);
+    }
+
+    tracing::info!(count = items.len(), max_uses = req.max_uses, "Beta invites created");
+    Json(BetaActionResponse { success: true, items, admitted: 0, error: None })
+}
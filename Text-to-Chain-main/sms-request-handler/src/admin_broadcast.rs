@@ -0,0 +1,194 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::broadcast::{in_countries, render, BroadcastMessage, BroadcastQueue, MAX_TEMPLATE_LEN};
+use crate::db::{Broadcast, BroadcastRepository, BroadcastSegment};
+use crate::sms::display::short_phone;
+use crate::sms::SmsGateway;
+
+/// Rendered messages shown by a dry run
+const PREVIEW_COUNT: usize = 5;
+/// Broadcasts listed by `GET /admin/broadcasts`
+const LIST_LIMIT: i64 = 50;
+
+#[derive(Clone)]
+pub struct AdminBroadcastState {
+    pub repo: BroadcastRepository,
+    pub queue: BroadcastQueue,
+    /// Opted-out numbers are left out before queueing
+    pub twilio: SmsGateway,
+}
+
+/// Request to broadcast a message
+#[derive(Debug, Deserialize)]
+pub struct BroadcastRequest {
+    /// Message text; `{name}` and `{balance}` are filled per recipient
+    pub template: String,
+    #[serde(default)]
+    pub segment: BroadcastSegment,
+    /// Preview recipients and messages without sending
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreviewMessage {
+    /// Masked recipient number
+    pub to: String,
+    /// As it will go out, after SMS_GSM7 substitutions
+    pub body: String,
+    pub segments: u32,
+    /// gsm7 or ucs2
+    pub encoding: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BroadcastResponse {
+    pub success: bool,
+    pub dry_run: bool,
+    /// Numbers that will be (or would be) messaged
+    pub recipients: usize,
+    /// Segment members left out because they opted out
+    pub excluded: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub preview: Vec<PreviewMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub broadcast: Option<Broadcast>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BroadcastResponse {
+    fn failed(error: impl ToString) -> Self {
+        Self {
+            success: false,
+            dry_run: false,
+            recipients: 0,
+            excluded: 0,
+            preview: vec![],
+            broadcast: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BroadcastListResponse {
+    pub success: bool,
+    pub broadcasts: Vec<Broadcast>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Create admin broadcast routes
+pub fn admin_broadcast_routes(state: AdminBroadcastState) -> Router {
+    Router::new()
+        .route("/broadcast", post(create_broadcast))
+        .route("/broadcasts", get(list_broadcasts))
+        .route("/broadcasts/:id", get(get_broadcast))
+        .with_state(state)
+}
+
+/// Send (or with `dry_run`, preview) a templated message to a user segment
+async fn create_broadcast(
+    State(state): State<AdminBroadcastState>,
+    Json(req): Json<BroadcastRequest>,
+) -> Json<BroadcastResponse> {
+    let template = req.template.trim();
+    if template.is_empty() || template.len() > MAX_TEMPLATE_LEN {
+        return Json(BroadcastResponse::failed(format!("Template must be 1-{} characters", MAX_TEMPLATE_LEN)));
+    }
+    if let (Some(min), Some(max)) = (req.segment.min_balance, req.segment.max_balance) {
+        if min.micros() > max.micros() {
+            return Json(BroadcastResponse::failed("min_balance is above max_balance"));
+        }
+    }
+
+    let members = match state.repo.segment_members(&req.segment).await {
+        Ok(members) => in_countries(members, &req.segment),
+        Err(e) => {
+            tracing::error!("Failed to select broadcast segment: {}", e);
+            return Json(BroadcastResponse::failed("Database error"));
+        }
+    };
+    let opt_outs = state.twilio.opt_outs();
+    let (members, opted_out): (Vec<_>, Vec<_>) = members.into_iter().partition(|m| !opt_outs.is_opted_out(&m.phone));
+    let messages: Vec<BroadcastMessage> = members
+        .iter()
+        .map(|member| BroadcastMessage { phone: member.phone.clone(), body: render(template, member) })
+        .collect();
+
+    if req.dry_run {
+        let preview = messages
+            .iter()
+            .take(PREVIEW_COUNT)
+            .map(|m| {
+                let composed = state.twilio.composer().compose(&m.body);
+                PreviewMessage {
+                    to: short_phone(&m.phone),
+                    body: composed.body.into_owned(),
+                    segments: composed.segments,
+                    encoding: composed.encoding.as_str(),
+                }
+            })
+            .collect();
+        return Json(BroadcastResponse {
+            success: true,
+            dry_run: true,
+            recipients: messages.len(),
+            excluded: opted_out.len(),
+            preview,
+            broadcast: None,
+            error: None,
+        });
+    }
+    if messages.is_empty() {
+        return Json(BroadcastResponse::failed("No recipients match this segment"));
+    }
+
+    let broadcast = match state.repo.create(template, &req.segment, messages.len() as i32, opted_out.len() as i32).await {
+        Ok(broadcast) => broadcast,
+        Err(e) => {
+            tracing::error!("Failed to create broadcast: {}", e);
+            return Json(BroadcastResponse::failed("Database error"));
+        }
+    };
+    let recipients = messages.len();
+    if let Err(e) = state.queue.enqueue(broadcast.id, messages) {
+        let _ = state.repo.delete(broadcast.id).await;
+        return Json(BroadcastResponse::failed(e));
+    }
+    tracing::info!(id = %broadcast.id, recipients, excluded = opted_out.len(), "Broadcast queued");
+
+    Json(BroadcastResponse {
+        success: true,
+        dry_run: false,
+        recipients,
+        excluded: opted_out.len(),
+        preview: vec![],
+        broadcast: Some(broadcast),
+        error: None,
+    })
+}
+
+/// Recent broadcasts with delivery counts
+async fn list_broadcasts(State(state): State<AdminBroadcastState>) -> Json<BroadcastListResponse> {
+    match state.repo.list(LIST_LIMIT).await {
+        Ok(broadcasts) => Json(BroadcastListResponse { success: true, broadcasts, error: None }),
+        Err(e) => Json(BroadcastListResponse { success: false, broadcasts: vec![], error: Some(e.to_string()) }),
+    }
+}
+
+/// One broadcast's delivery counts
+async fn get_broadcast(State(state): State<AdminBroadcastState>, Path(id): Path<Uuid>) -> Json<BroadcastListResponse> {
+    match state.repo.find(id).await {
+        Ok(Some(broadcast)) => Json(BroadcastListResponse { success: true, broadcasts: vec![broadcast], error: None }),
+        Ok(None) => Json(BroadcastListResponse { success: false, broadcasts: vec![], error: Some("Broadcast not found".to_string()) }),
+        Err(e) => Json(BroadcastListResponse { success: false, broadcasts: vec![], error: Some(e.to_string()) }),
+    }
+}
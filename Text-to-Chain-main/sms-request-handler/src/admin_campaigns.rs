@@ -0,0 +1,161 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{Campaign, CampaignRepository};
+
+/// Request to create a campaign
+#[derive(Debug, Deserialize)]
+pub struct CreateCampaignRequest {
+    pub name: String,
+    /// Partner paying for the campaign
+    pub partner: String,
+    /// ENS mint gas budget in gwei
+    pub gas_budget_gwei: i64,
+}
+
+/// Request to add to a campaign's budget
+#[derive(Debug, Deserialize)]
+pub struct TopUpCampaignRequest {
+    pub gas_gwei: i64,
+}
+
+/// Request to pause or resume a campaign
+#[derive(Debug, Deserialize)]
+pub struct SetActiveRequest {
+    pub active: bool,
+}
+
+/// Campaign with its mint stats
+#[derive(Debug, Serialize)]
+pub struct CampaignInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub partner: String,
+    pub active: bool,
+    pub gas_budget_gwei: i64,
+    pub gas_spent_gwei: i64,
+    pub remaining_gwei: i64,
+    pub mints: i32,
+    /// Gas units per sponsored mint (0 before the first)
+    pub avg_gas_per_mint: i64,
+    pub created_at: String,
+}
+
+impl From<Campaign> for CampaignInfo {
+    fn from(campaign: Campaign) -> Self {
+        Self {
+            remaining_gwei: campaign.remaining_gwei(),
+            avg_gas_per_mint: if campaign.mints > 0 { campaign.gas_used / campaign.mints as i64 } else { 0 },
+            id: campaign.id,
+            name: campaign.name,
+            partner: campaign.partner,
+            active: campaign.active,
+            gas_budget_gwei: campaign.gas_budget_gwei,
+            gas_spent_gwei: campaign.gas_spent_gwei,
+            mints: campaign.mints,
+            created_at: campaign.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Single campaign response
+#[derive(Debug, Serialize)]
+pub struct CampaignResponse {
+    pub success: bool,
+    pub campaign: Option<CampaignInfo>,
+    pub error: Option<String>,
+}
+
+impl CampaignResponse {
+    fn found(campaign: Result<Option<Campaign>, sqlx::Error>) -> Self {
+        match campaign {
+            Ok(Some(campaign)) => Self { success: true, campaign: Some(campaign.into()), error: None },
+            Ok(None) => Self::failed("Campaign not found"),
+            Err(e) => {
+                tracing::error!("Campaign query failed: {}", e);
+                Self::failed("Database error")
+            }
+        }
+    }
+
+    fn failed(error: impl ToString) -> Self {
+        Self { success: false, campaign: None, error: Some(error.to_string()) }
+    }
+}
+
+/// List campaigns response
+#[derive(Debug, Serialize)]
+pub struct ListCampaignsResponse {
+    pub success: bool,
+    pub campaigns: Vec<CampaignInfo>,
+}
+
+/// Create admin campaign routes
+pub fn admin_campaign_routes(campaigns: CampaignRepository) -> Router {
+    Router::new()
+        .route("/campaigns", post(create_campaign))
+        .route("/campaigns", get(list_campaigns))
+        .route("/campaigns/:id", get(get_campaign))
+        .route("/campaigns/:id/budget", post(top_up_campaign))
+        .route("/campaigns/:id/active", put(set_active))
+        .with_state(campaigns)
+}
+
+/// Start a campaign; it pays for mints from now on
+async fn create_campaign(
+    State(campaigns): State<CampaignRepository>,
+    Json(req): Json<CreateCampaignRequest>,
+) -> Json<CampaignResponse> {
+    if req.name.trim().is_empty() || req.partner.trim().is_empty() || req.gas_budget_gwei <= 0 {
+        return Json(CampaignResponse::failed("name and partner must be non-empty and gas_budget_gwei positive"));
+    }
+    let created = campaigns.create(req.name.trim(), req.partner.trim(), req.gas_budget_gwei).await;
+    if let Ok(ref campaign) = created {
+        tracing::info!(campaign = %campaign.id, partner = %campaign.partner, budget_gwei = campaign.gas_budget_gwei, "Campaign created");
+    }
+    Json(CampaignResponse::found(created.map(Some)))
+}
+
+/// Campaigns with budget, spend and mint counts
+async fn list_campaigns(State(campaigns): State<CampaignRepository>) -> Json<ListCampaignsResponse> {
+    match campaigns.list().await {
+        Ok(list) => Json(ListCampaignsResponse { success: true, campaigns: list.into_iter().map(Into::into).collect() }),
+        Err(e) => {
+            tracing::error!("Failed to list campaigns: {}", e);
+            Json(ListCampaignsResponse { success: false, campaigns: vec![] })
+        }
+    }
+}
+
+/// One campaign's budget, spend and mint counts
+async fn get_campaign(State(campaigns): State<CampaignRepository>, Path(id): Path<Uuid>) -> Json<CampaignResponse> {
+    Json(CampaignResponse::found(campaigns.find(id).await))
+}
+
+/// Add gas budget, e.g. when a partner extends a campaign
+async fn top_up_campaign(
+    State(campaigns): State<CampaignRepository>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<TopUpCampaignRequest>,
+) -> Json<CampaignResponse> {
+    if req.gas_gwei <= 0 {
+        return Json(CampaignResponse::failed("gas_gwei must be positive"));
+    }
+    tracing::info!(campaign = %id, gas_gwei = req.gas_gwei, "Campaign budget topped up");
+    Json(CampaignResponse::found(campaigns.top_up(id, req.gas_gwei).await))
+}
+
+/// Pause or resume a campaign
+async fn set_active(
+    State(campaigns): State<CampaignRepository>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<SetActiveRequest>,
+) -> Json<CampaignResponse> {
+    tracing::info!(campaign = %id, active = req.active, "Campaign activity changed");
+    Json(CampaignResponse::found(campaigns.set_active(id, req.active).await))
+}
@@ -0,0 +1,47 @@
+use axum::{extract::State, routing::get, Json, Router};
+use serde::Serialize;
+
+use crate::wallet::chain_registry::{ChainRegistry, ChainRegistryError, ChainState, NewChain};
+
+/// Registered chains
+#[derive(Debug, Serialize)]
+pub struct ChainsResponse {
+    pub success: bool,
+    pub chains: Vec<ChainState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ChainsResponse {
+    fn ok(registry: &ChainRegistry) -> Self {
+        Self { success: true, chains: registry.snapshot(), error: None }
+    }
+
+    fn failed(registry: &ChainRegistry, error: impl ToString) -> Self {
+        Self { success: false, chains: registry.snapshot(), error: Some(error.to_string()) }
+    }
+}
+
+/// Create admin chain registry routes, mounted under `/admin` behind the admin token
+pub fn admin_chain_routes(registry: ChainRegistry) -> Router {
+    Router::new()
+        .route("/chains", get(list_chains).post(add_chain))
+        .with_state(registry)
+}
+
+/// List every chain, built-in and registered
+async fn list_chains(State(registry): State<ChainRegistry>) -> Json<ChainsResponse> {
+    Json(ChainsResponse::ok(&registry))
+}
+
+/// Register an EVM chain (or update one registered earlier); usable at once
+async fn add_chain(State(registry): State<ChainRegistry>, Json(req): Json<NewChain>) -> Json<ChainsResponse> {
+    match registry.add(req).await {
+        Ok(_) => Json(ChainsResponse::ok(&registry)),
+        Err(ChainRegistryError::Database(e)) => {
+            tracing::error!("Failed to save chain: {}", e);
+            Json(ChainsResponse::failed(&registry, "Database error"))
+        }
+        Err(e) => Json(ChainsResponse::failed(&registry, e)),
+    }
+}
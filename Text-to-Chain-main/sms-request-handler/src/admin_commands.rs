@@ -0,0 +1,50 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{UnrecognizedCommand, UnrecognizedCommandRepository};
+
+/// Words returned when no limit is given
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct UnrecognizedQuery {
+    /// Top N first words (default 50, max 500)
+    pub limit: Option<i64>,
+}
+
+/// Unrecognized first words, most frequent first
+#[derive(Debug, Serialize)]
+pub struct UnrecognizedResponse {
+    pub success: bool,
+    pub commands: Vec<UnrecognizedCommand>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Create admin routes for what users type that isn't a command
+pub fn admin_command_routes(repo: UnrecognizedCommandRepository) -> Router {
+    Router::new()
+        .route("/unrecognized-commands", get(list_unrecognized))
+        .with_state(repo)
+}
+
+/// Words people expect to be commands, for deciding what to add next
+async fn list_unrecognized(
+    State(repo): State<UnrecognizedCommandRepository>,
+    Query(query): Query<UnrecognizedQuery>,
+) -> Json<UnrecognizedResponse> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match repo.top(limit).await {
+        Ok(commands) => Json(UnrecognizedResponse { success: true, commands, error: None }),
+        Err(e) => {
+            tracing::error!("Failed to list unrecognized commands: {}", e);
+            Json(UnrecognizedResponse { success: false, commands: Vec::new(), error: Some("Database error".to_string()) })
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::{extract::State, routing::{get, post}, Json, Router};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::live_config::{AppliedConfig, ConfigStore, RejectedConfig};
+
+/// Live config in effect
+#[derive(Debug, Serialize)]
+pub struct ConfigResponse {
+    pub success: bool,
+    /// Whether this request applied a new file (reload only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub applied: Option<bool>,
+    pub current: Arc<AppliedConfig>,
+    /// Last file that failed validation, until a good one replaces it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rejected: Option<RejectedConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ConfigResponse {
+    fn new(store: &ConfigStore) -> Self {
+        Self { success: true, applied: None, current: store.snapshot(), rejected: store.rejected(), error: None }
+    }
+}
+
+/// Last applied version only
+#[derive(Debug, Serialize)]
+pub struct ConfigVersionResponse {
+    pub version: String,
+    pub checksum: String,
+    pub applied_at: DateTime<Utc>,
+    /// Whether the file on disk was rejected after this version applied
+    pub pending_error: bool,
+}
+
+/// Create admin live config routes
+pub fn admin_config_routes(store: ConfigStore) -> Router {
+    Router::new()
+        .route("/config", get(get_config))
+        .route("/config/version", get(get_config_version))
+        .route("/config/reload", post(reload_config))
+        .with_state(store)
+}
+
+/// Settings in effect and any rejected file
+async fn get_config(State(store): State<ConfigStore>) -> Json<ConfigResponse> {
+    Json(ConfigResponse::new(&store))
+}
+
+async fn get_config_version(State(store): State<ConfigStore>) -> Json<ConfigVersionResponse> {
+    let current = store.snapshot();
+    Json(ConfigVersionResponse {
+        version: current.version.clone(),
+        checksum: current.checksum.clone(),
+        applied_at: current.applied_at,
+        pending_error: store.rejected().is_some(),
+    })
+}
+
+/// Apply the file now instead of at the next check
+async fn reload_config(State(store): State<ConfigStore>) -> Json<ConfigResponse> {
+    match store.reload() {
+        Ok(applied) => Json(ConfigResponse { applied: Some(applied), ..ConfigResponse::new(&store) }),
+        Err(e) => Json(ConfigResponse {
+            success: false,
+            applied: Some(false),
+            error: Some(e.to_string()),
+            ..ConfigResponse::new(&store)
+        }),
+    }
+}
@@ -0,0 +1,262 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use ethers::providers::Middleware;
+use ethers::types::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::wallet::ens_cache::EnsCache;
+use crate::wallet::{create_chain_provider, Chain};
+
+/// Approximate gas per minted name (create, resolver, addr, transfer);
+/// matches `ens_service::import::MINT_GAS_UNITS`
+const MINT_GAS_UNITS: u64 = 250_000;
+/// Rows accepted per request; larger lists go through the ens_service CLI
+const MAX_IMPORT_ROWS: usize = 500;
+const MAX_ATTEMPTS: u32 = 3;
+
+#[derive(Clone)]
+pub struct AdminEnsState {
+    pub backend_url: String,
+    pub http: reqwest::Client,
+    /// Shared with SEND recipient resolution
+    pub cache: Arc<EnsCache>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    /// Validate and estimate only
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// One CSV row in the import report
+#[derive(Debug, Serialize)]
+pub struct ImportRowResult {
+    pub line: usize,
+    pub label: String,
+    pub address: Option<String>,
+    /// valid (dry run), minted, taken, failed or invalid
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportResponse {
+    pub success: bool,
+    pub valid: usize,
+    pub invalid: usize,
+    pub estimated_gas: u64,
+    /// Estimated cost in ETH at the current Sepolia gas price
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_cost_eth: Option<String>,
+    pub results: Vec<ImportRowResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Result of a cache invalidation or reverse lookup
+#[derive(Debug, Serialize)]
+pub struct CacheResponse {
+    pub success: bool,
+    /// Entries dropped from the cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<usize>,
+    /// Primary name of the address, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Create admin ENS routes
+pub fn admin_ens_routes(backend_url: String, cache: Arc<EnsCache>) -> Router {
+    Router::new()
+        .route("/ens/import", post(import_names))
+        .route("/ens/cache", delete(clear_cache))
+        .route("/ens/cache/:key", delete(invalidate_cache))
+        .route("/ens/reverse/:address", get(reverse_lookup))
+        .with_state(AdminEnsState { backend_url, http: reqwest::Client::new(), cache })
+}
+
+/// Forget every cached ENS lookup
+async fn clear_cache(State(state): State<AdminEnsState>) -> Json<CacheResponse> {
+    let removed = state.cache.clear();
+    tracing::info!(removed, "ENS cache cleared");
+    Json(CacheResponse { success: true, removed: Some(removed), name: None, error: None })
+}
+
+/// Forget one name or `0x` address, e.g. right after its record changed
+async fn invalidate_cache(State(state): State<AdminEnsState>, Path(key): Path<String>) -> Json<CacheResponse> {
+    let removed = usize::from(state.cache.invalidate(&key));
+    Json(CacheResponse { success: true, removed: Some(removed), name: None, error: None })
+}
+
+/// Primary ENS name of an address on mainnet, through the cache
+async fn reverse_lookup(State(state): State<AdminEnsState>, Path(address): Path<String>) -> Json<CacheResponse> {
+    let Ok(address) = address.parse::<Address>() else {
+        return Json(CacheResponse { success: false, removed: None, name: None, error: Some("Invalid address".to_string()) });
+    };
+    match state.cache.lookup_address(&create_chain_provider(Chain::EthereumMainnet), address).await {
+        Ok(name) => Json(CacheResponse { success: true, removed: None, name, error: None }),
+        Err(e) => Json(CacheResponse { success: false, removed: None, name: None, error: Some(e.to_string()) }),
+    }
+}
+
+/// Pre-mint `<label>.ttcip.eth` names from a `label,address` CSV body
+async fn import_names(
+    State(state): State<AdminEnsState>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Json<ImportResponse> {
+    let (rows, mut results) = parse_csv(&body);
+    let (valid, invalid) = (rows.len(), results.len());
+    let estimated_gas = MINT_GAS_UNITS * valid as u64;
+    let mut response = ImportResponse {
+        success: true,
+        valid,
+        invalid,
+        estimated_gas,
+        estimated_cost_eth: estimate_cost_eth(estimated_gas).await,
+        results: vec![],
+        error: None,
+    };
+
+    if valid > MAX_IMPORT_ROWS {
+        response.success = false;
+        response.error = Some(format!("At most {} rows per request; use the ens_service import CLI", MAX_IMPORT_ROWS));
+        return Json(response);
+    }
+
+    for (line, label, address) in rows {
+        let (status, detail) = if query.dry_run {
+            ("valid", None)
+        } else {
+            register(&state, &label, address).await
+        };
+        results.push(ImportRowResult {
+            line,
+            label,
+            address: Some(format!("{:?}", address)),
+            status,
+            detail,
+        });
+    }
+    results.sort_by_key(|r| r.line);
+
+    tracing::info!(valid, invalid, dry_run = query.dry_run, "ENS CSV import processed");
+    response.results = results;
+    Json(response)
+}
+
+/// Mint one name through the backend, retrying transient failures
+async fn register(state: &AdminEnsState, label: &str, address: Address) -> (&'static str, Option<String>) {
+    let mut last_error = String::new();
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        if attempt > 1 {
+            tokio::time::sleep(Duration::from_secs(2u64.pow(attempt - 1))).await;
+        }
+
+        let check = state
+            .http
+            .get(format!("{}/api/ens/check/{}", state.backend_url, label))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        match check {
+            Ok(resp) => match resp.json::<serde_json::Value>().await {
+                Ok(data) if !data["available"].as_bool().unwrap_or(false) => {
+                    return ("taken", data["reason"].as_str().map(String::from));
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    last_error = e.to_string();
+                    continue;
+                }
+            },
+            Err(e) => {
+                last_error = e.to_string();
+                continue;
+            }
+        }
+
+        let registered = state
+            .http
+            .post(format!("{}/api/ens/register", state.backend_url))
+            .json(&serde_json::json!({ "ensName": label, "walletAddress": format!("{:?}", address) }))
+            .send()
+            .await;
+        match registered {
+            Ok(resp) if resp.status().is_success() => {
+                state.cache.invalidate(&format!("{}.ttcip.eth", label));
+                let data = resp.json::<serde_json::Value>().await.unwrap_or_default();
+                return ("minted", data["txHash"].as_str().map(String::from));
+            }
+            Ok(resp) => last_error = resp.text().await.unwrap_or_default(),
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+
+    tracing::warn!(label = %label, "ENS import row failed: {}", last_error);
+    ("failed", Some(last_error))
+}
+
+async fn estimate_cost_eth(gas: u64) -> Option<String> {
+    let price = create_chain_provider(Chain::EthereumSepolia).get_gas_price().await.ok()?;
+    Some(ethers::utils::format_ether(U256::from(gas) * price))
+}
+
+/// Valid (line, label, address) rows plus a result for every invalid row.
+/// Same rules as `JOIN <name>`: 3-20 letters and digits, one row per label.
+fn parse_csv(text: &str) -> (Vec<(usize, String, Address)>, Vec<ImportRowResult>) {
+    let mut rows = Vec::new();
+    let mut invalid = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let mut fields = trimmed.split(',').map(|f| f.trim().trim_matches('"'));
+        let (label, address) = (fields.next().unwrap_or("").to_lowercase(), fields.next().unwrap_or(""));
+        if line == 1 && label == "label" {
+            continue;
+        }
+
+        let reason = if label.len() < 3 || label.len() > 20 {
+            Some("label must be 3-20 characters")
+        } else if !label.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Some("label can only contain letters and numbers")
+        } else {
+            match address.parse::<Address>() {
+                Ok(address) if address.is_zero() => Some("zero address"),
+                Ok(_) if !seen.insert(label.clone()) => Some("duplicate label"),
+                Ok(address) => {
+                    rows.push((line, label.clone(), address));
+                    None
+                }
+                Err(_) => Some("invalid address"),
+            }
+        };
+        if let Some(reason) = reason {
+            invalid.push(ImportRowResult {
+                line,
+                label,
+                address: Some(address.to_string()).filter(|a| !a.is_empty()),
+                status: "invalid",
+                detail: Some(reason.to_string()),
+            });
+        }
+    }
+
+    (rows, invalid)
+}
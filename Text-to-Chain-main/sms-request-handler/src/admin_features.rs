@@ -0,0 +1,78 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::features::{Feature, FeatureFlags, FeatureState};
+
+/// Request to override a feature flag
+#[derive(Debug, Deserialize)]
+pub struct SetFeatureRequest {
+    /// true/false to override, null to fall back to FEATURES_DISABLED
+    pub enabled: Option<bool>,
+}
+
+/// Current feature flags
+#[derive(Debug, Serialize)]
+pub struct FeaturesResponse {
+    pub success: bool,
+    pub features: Vec<FeatureState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl FeaturesResponse {
+    fn ok(flags: &FeatureFlags) -> Self {
+        Self { success: true, features: flags.snapshot(), error: None }
+    }
+
+    fn failed(flags: &FeatureFlags, error: impl ToString) -> Self {
+        Self { success: false, features: flags.snapshot(), error: Some(error.to_string()) }
+    }
+}
+
+/// Create admin feature flag routes
+pub fn admin_feature_routes(flags: FeatureFlags) -> Router {
+    Router::new()
+        .route("/features", get(list_features))
+        .route("/features/reload", post(reload_features))
+        .route("/features/:name", put(set_feature))
+        .with_state(flags)
+}
+
+/// List every feature and whether it is enabled
+async fn list_features(State(flags): State<FeatureFlags>) -> Json<FeaturesResponse> {
+    Json(FeaturesResponse::ok(&flags))
+}
+
+/// Override (or clear the override of) one feature; takes effect immediately
+async fn set_feature(
+    State(flags): State<FeatureFlags>,
+    Path(name): Path<String>,
+    Json(req): Json<SetFeatureRequest>,
+) -> Json<FeaturesResponse> {
+    let Some(feature) = Feature::from_name(&name) else {
+        return Json(FeaturesResponse::failed(&flags, format!("Unknown feature: {}", name)));
+    };
+
+    match flags.set_override(feature, req.enabled).await {
+        Ok(()) => Json(FeaturesResponse::ok(&flags)),
+        Err(e) => {
+            tracing::error!("Failed to save feature flag: {}", e);
+            Json(FeaturesResponse::failed(&flags, "Database error"))
+        }
+    }
+}
+
+/// Re-read overrides after editing `feature_flags` directly
+async fn reload_features(State(flags): State<FeatureFlags>) -> Json<FeaturesResponse> {
+    match flags.reload().await {
+        Ok(()) => Json(FeaturesResponse::ok(&flags)),
+        Err(e) => {
+            tracing::error!("Failed to reload feature flags: {}", e);
+            Json(FeaturesResponse::failed(&flags, "Database error"))
+        }
+    }
+}
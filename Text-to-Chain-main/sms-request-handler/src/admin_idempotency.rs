@@ -0,0 +1,136 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+
+use crate::db::{IdempotencyClaim, IdempotencyRepository, StoredResponse};
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on responses served from the idempotency cache
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Largest admin request body accepted (matches axum's default limit)
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+/// Largest response stored for replay
+const MAX_RESPONSE_BYTES: usize = 10 * 1024 * 1024;
+const MAX_KEY_LEN: usize = 255;
+/// Hours a key (and its stored response) is remembered
+pub const KEY_TTL_HOURS: u32 = 24;
+
+/// Middleware: make `/admin` mutations sent with an `Idempotency-Key` header
+/// safe to retry. The first request runs and its response is stored; a
+/// retry with the same key and the same method, path and body gets that
+/// response back without running again. Server errors aren't stored, so
+/// those can be retried for real.
+pub async fn idempotent_admin_requests(
+    State(repo): State<IdempotencyRepository>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_admin_write = request.uri().path().starts_with("/admin/")
+        && !matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let key = request
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string());
+    let Some(key) = key.filter(|_| is_admin_write) else {
+        return next.run(request).await;
+    };
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return (StatusCode::BAD_REQUEST, "Idempotency-Key must be 1-255 characters").into_response();
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    };
+    let target = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or_default();
+    let fingerprint = request_fingerprint(&parts.method, target, &bytes);
+
+    match repo.claim(&key, &fingerprint).await {
+        Ok(IdempotencyClaim::Started) => {}
+        Ok(IdempotencyClaim::Replay(stored)) => {
+            tracing::info!(key = %key, "Replaying admin response for idempotency key");
+            return replay(stored);
+        }
+        Ok(IdempotencyClaim::InProgress) => {
+            return (StatusCode::CONFLICT, "A request with this Idempotency-Key is still in progress").into_response();
+        }
+        Ok(IdempotencyClaim::Mismatch) => {
+            return (StatusCode::UNPROCESSABLE_ENTITY, "Idempotency-Key was already used for a different request")
+                .into_response();
+        }
+        Err(e) => {
+            tracing::error!(key = %key, "Idempotency lookup failed: {}", e);
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+
+    if response.status().is_server_error() {
+        if let Err(e) = repo.release(&key).await {
+            tracing::error!(key = %key, "Failed to release idempotency key: {}", e);
+        }
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_RESPONSE_BYTES).await else {
+        let _ = repo.release(&key).await;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    };
+    let stored = StoredResponse {
+        status: parts.status.as_u16() as i16,
+        content_type: parts
+            .headers
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string),
+        body: bytes.to_vec(),
+    };
+    if let Err(e) = repo.complete(&key, &stored).await {
+        tracing::error!(key = %key, "Failed to store idempotent response: {}", e);
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+fn replay(stored: StoredResponse) -> Response {
+    let status = StatusCode::from_u16(stored.status as u16).unwrap_or(StatusCode::OK);
+    let mut response = (status, stored.body).into_response();
+    if let Some(content_type) = stored.content_type.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    response.headers_mut().insert(REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+/// SHA-256 over method, path and body, so a key can't be reused for a different request
+fn request_fingerprint(method: &Method, target: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    for part in [method.as_str().as_bytes(), target.as_bytes(), body] {
+        hasher.update((part.len() as u64).to_be_bytes());
+        hasher.update(part);
+    }
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_fingerprint() {
+        let body = br#"{"count":10,"usdc_amount":5}"#;
+        let fingerprint = request_fingerprint(&Method::POST, "/admin/vouchers", body);
+        assert_eq!(fingerprint.len(), 64);
+        assert_eq!(fingerprint, request_fingerprint(&Method::POST, "/admin/vouchers", body));
+        assert_ne!(fingerprint, request_fingerprint(&Method::POST, "/admin/vouchers", br#"{"count":11,"usdc_amount":5}"#));
+        assert_ne!(fingerprint, request_fingerprint(&Method::PUT, "/admin/vouchers", body));
+    }
+}
@@ -0,0 +1,71 @@
+use axum::{extract::State, routing::get, Extension, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::admin::AdminCaller;
+use crate::db::{AuditLogRepository, KillSwitchState};
+use crate::kill_switch::KillSwitch;
+
+/// Request to engage or release the kill switch
+#[derive(Debug, Deserialize)]
+pub struct SetKillSwitchRequest {
+    pub engaged: bool,
+    /// Why, kept with the state and in the audit log
+    pub reason: String,
+}
+
+/// Kill switch state after a read or change
+#[derive(Debug, Serialize)]
+pub struct KillSwitchResponse {
+    pub success: bool,
+    pub state: KillSwitchState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct AdminKillSwitchState {
+    pub switch: KillSwitch,
+    pub audit: AuditLogRepository,
+}
+
+/// Create admin kill switch routes
+pub fn admin_kill_switch_routes(state: AdminKillSwitchState) -> Router {
+    Router::new()
+        .route("/kill-switch", get(get_kill_switch).post(set_kill_switch))
+        .with_state(state)
+}
+
+async fn get_kill_switch(State(state): State<AdminKillSwitchState>) -> Json<KillSwitchResponse> {
+    Json(KillSwitchResponse { success: true, state: state.switch.state().await, error: None })
+}
+
+/// Engage or release for the whole fleet; takes effect on the next command.
+/// The actor is whoever the admin token belongs to.
+async fn set_kill_switch(
+    State(state): State<AdminKillSwitchState>,
+    Extension(AdminCaller(actor)): Extension<AdminCaller>,
+    Json(req): Json<SetKillSwitchRequest>,
+) -> Json<KillSwitchResponse> {
+    let (actor, reason) = (actor.as_str(), req.reason.trim());
+    if reason.is_empty() {
+        return Json(KillSwitchResponse {
+            success: false,
+            state: state.switch.state().await,
+            error: Some("reason is required".to_string()),
+        });
+    }
+
+    match state.switch.set(req.engaged, actor, reason).await {
+        Ok(changed) => {
+            let action = if changed.engaged { "KILL_SWITCH_ENGAGED" } else { "KILL_SWITCH_RELEASED" };
+            if let Err(e) = state.audit.record_admin(action, &format!("{}: {}", actor, reason)).await {
+                tracing::error!(action, "Audit log write failed: {}", e);
+            }
+            Json(KillSwitchResponse { success: true, state: changed, error: None })
+        }
+        Err(e) => {
+            tracing::error!("Failed to set kill switch: {}", e);
+            Json(KillSwitchResponse { success: false, state: state.switch.state().await, error: Some(e.to_string()) })
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use axum::{
+    extract::{Path, State},
+    routing::get,
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::admin::AdminCaller;
+use crate::db::{KycRepository, TierChange, MAX_KYC_TIER};
+
+/// Request to move a user to another tier
+#[derive(Debug, Deserialize)]
+pub struct SetTierRequest {
+    /// 0 (receive only), 1 (phone verified) or 2 (ID checked)
+    pub tier: i16,
+    /// Why, kept in the user's tier history
+    pub reason: String,
+}
+
+/// A user's tier and how they got there
+#[derive(Debug, Serialize)]
+pub struct KycResponse {
+    pub success: bool,
+    pub user_id: Uuid,
+    pub tier: Option<i16>,
+    pub history: Vec<TierChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl KycResponse {
+    fn failed(user_id: Uuid, error: impl ToString) -> Self {
+        Self { success: false, user_id, tier: None, history: vec![], error: Some(error.to_string()) }
+    }
+}
+
+/// Create admin KYC tier routes
+pub fn admin_kyc_routes(repo: KycRepository) -> Router {
+    Router::new()
+        .route("/kyc/:user_id", get(get_tier).post(set_tier))
+        .with_state(repo)
+}
+
+/// Current tier and every change, oldest first
+async fn get_tier(State(repo): State<KycRepository>, Path(user_id): Path<Uuid>) -> Json<KycResponse> {
+    let tier = match repo.tier(user_id).await {
+        Ok(Some(tier)) => tier,
+        Ok(None) => return Json(KycResponse::failed(user_id, "User not found")),
+        Err(e) => {
+            tracing::error!("Failed to fetch KYC tier: {}", e);
+            return Json(KycResponse::failed(user_id, "Database error"));
+        }
+    };
+    match repo.history(user_id).await {
+        Ok(history) => Json(KycResponse { success: true, user_id, tier: Some(tier), history, error: None }),
+        Err(e) => {
+            tracing::error!("Failed to fetch KYC history: {}", e);
+            Json(KycResponse::failed(user_id, "Database error"))
+        }
+    }
+}
+
+/// Upgrade (e.g. after an ID check) or downgrade a user, with a reason;
+/// the change is recorded against the admin token's name
+async fn set_tier(
+    State(repo): State<KycRepository>,
+    Extension(caller): Extension<AdminCaller>,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<SetTierRequest>,
+) -> Json<KycResponse> {
+    if !(0..=MAX_KYC_TIER).contains(&req.tier) {
+        return Json(KycResponse::failed(user_id, format!("tier must be 0 to {}", MAX_KYC_TIER)));
+    }
+    let reason = req.reason.trim();
+    if reason.is_empty() {
+        return Json(KycResponse::failed(user_id, "reason is required"));
+    }
+
+    match repo.set_tier(user_id, req.tier, reason, &caller.0).await {
+        Ok(Some(previous)) => {
+            tracing::info!(user = %user_id, from = previous, to = req.tier, reason, admin = %caller, "KYC tier set by admin");
+            get_tier(State(repo), Path(user_id)).await
+        }
+        Ok(None) => Json(KycResponse::failed(user_id, "User not found")),
+        Err(e) => {
+            tracing::error!("Failed to set KYC tier: {}", e);
+            Json(KycResponse::failed(user_id, "Database error"))
+        }
+    }
+}
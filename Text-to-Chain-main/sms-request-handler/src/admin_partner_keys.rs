@@ -0,0 +1,269 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db::{PartnerKey, PartnerKeyRepository, PartnerScope, PartnerUsage};
+use crate::money::Money;
+use crate::partner_webhooks::new_secret;
+
+/// Default requests per minute for new keys
+const DEFAULT_RATE_PER_MINUTE: i32 = 60;
+/// Default requests per UTC day for new keys
+const DEFAULT_DAILY_QUOTA: i32 = 10_000;
+/// Longest usage window admins can ask for
+const MAX_USAGE_DAYS: i32 = 90;
+
+/// Admin partner key routes state
+#[derive(Clone)]
+pub struct AdminPartnerKeyState {
+    pub keys: PartnerKeyRepository,
+}
+
+/// Request to issue a partner key
+#[derive(Debug, Deserialize)]
+pub struct CreatePartnerKeyRequest {
+    /// Partner name, shown in logs and SMS notices
+    pub partner: String,
+    /// Scope names, e.g. ["deposit:create"]
+    pub scopes: Vec<String>,
+    pub rate_per_minute: Option<i32>,
+    pub daily_quota: Option<i32>,
+}
+
+/// Request to change a key's limits (0 = unlimited)
+#[derive(Debug, Deserialize)]
+pub struct PartnerKeyLimitsRequest {
+    pub rate_per_minute: i32,
+    pub daily_quota: i32,
+}
+
+/// Request to set a key's webhook; issues a new webhook secret
+#[derive(Debug, Deserialize)]
+pub struct PartnerWebhookRequest {
+    /// http(s) URL for signed webhooks; leave out for none
+    pub url: Option<String>,
+    /// Refuse the key's calls unless they are signed
+    #[serde(default)]
+    pub signed_requests: bool,
+}
+
+/// Single key response; `key` and `webhook_secret` are only set when issued
+#[derive(Debug, Serialize)]
+pub struct PartnerKeyResponse {
+    pub success: bool,
+    pub partner_key: Option<PartnerKey>,
+    pub key: Option<String>,
+    pub webhook_secret: Option<String>,
+    pub error: Option<String>,
+}
+
+impl PartnerKeyResponse {
+    fn ok(partner_key: PartnerKey) -> Self {
+        Self { success: true, partner_key: Some(partner_key), key: None, webhook_secret: None, error: None }
+    }
+
+    fn failed(error: impl ToString) -> Self {
+        Self { success: false, partner_key: None, key: None, webhook_secret: None, error: Some(error.to_string()) }
+    }
+}
+
+/// List keys response
+#[derive(Debug, Serialize)]
+pub struct ListPartnerKeysResponse {
+    pub success: bool,
+    pub partner_keys: Vec<PartnerKey>,
+}
+
+/// One day of usage
+#[derive(Debug, Serialize)]
+pub struct UsageInfo {
+    pub day: String,
+    pub endpoint: String,
+    pub requests: i32,
+    pub rejected: i32,
+    /// USDC credited or issued
+    pub amount: Money,
+}
+
+impl From<PartnerUsage> for UsageInfo {
+    fn from(usage: PartnerUsage) -> Self {
+        Self {
+            day: usage.day.to_string(),
+            endpoint: usage.endpoint,
+            requests: usage.requests,
+            rejected: usage.rejected,
+            amount: Money::usdc(usage.amount),
+        }
+    }
+}
+
+/// Key usage response
+#[derive(Debug, Serialize)]
+pub struct PartnerUsageResponse {
+    pub success: bool,
+    pub usage: Vec<UsageInfo>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UsageQuery {
+    #[serde(default = "default_usage_days")]
+    pub days: i32,
+}
+
+fn default_usage_days() -> i32 {
+    30
+}
+
+/// Create admin partner key routes
+pub fn admin_partner_key_routes(keys: PartnerKeyRepository) -> Router {
+    Router::new()
+        .route("/partner-keys", post(create_key))
+        .route("/partner-keys", get(list_keys))
+        .route("/partner-keys/:id/limits", put(set_limits))
+        .route("/partner-keys/:id/webhook", post(set_webhook))
+        .route("/partner-keys/:id/revoke", post(revoke_key))
+        .route("/partner-keys/:id/usage", get(key_usage))
+        .with_state(AdminPartnerKeyState { keys })
+}
+
+/// Issue a key; the plaintext is in this response only
+async fn create_key(
+    State(state): State<AdminPartnerKeyState>,
+    Json(req): Json<CreatePartnerKeyRequest>,
+) -> Json<PartnerKeyResponse> {
+    let scopes: Option<Vec<PartnerScope>> = req.scopes.iter().map(|s| PartnerScope::parse(s)).collect();
+    let scopes = match scopes {
+        Some(scopes) if !scopes.is_empty() => scopes,
+        _ => {
+            let names: Vec<&str> = PartnerScope::ALL.iter().map(|s| s.as_str()).collect();
+            return Json(PartnerKeyResponse::failed(format!("scopes must be some of: {}", names.join(", "))));
+        }
+    };
+    let rate = req.rate_per_minute.unwrap_or(DEFAULT_RATE_PER_MINUTE);
+    let quota = req.daily_quota.unwrap_or(DEFAULT_DAILY_QUOTA);
+    if req.partner.trim().is_empty() || rate < 0 || quota < 0 {
+        return Json(PartnerKeyResponse::failed("partner must be non-empty and limits non-negative"));
+    }
+
+    match state.keys.create(req.partner.trim(), &scopes, rate, quota).await {
+        Ok((partner_key, key)) => {
+            tracing::info!(key = %partner_key.id, partner = %partner_key.partner, scopes = %partner_key.scopes, "Partner key issued");
+            Json(PartnerKeyResponse { key: Some(key), ..PartnerKeyResponse::ok(partner_key) })
+        }
+        Err(e) => {
+            tracing::error!("Failed to create partner key: {}", e);
+            Json(PartnerKeyResponse::failed("Database error"))
+        }
+    }
+}
+
+/// List keys, newest first
+async fn list_keys(State(state): State<AdminPartnerKeyState>) -> Json<ListPartnerKeysResponse> {
+    match state.keys.list().await {
+        Ok(partner_keys) => Json(ListPartnerKeysResponse { success: true, partner_keys }),
+        Err(e) => {
+            tracing::error!("Failed to list partner keys: {}", e);
+            Json(ListPartnerKeysResponse { success: false, partner_keys: vec![] })
+        }
+    }
+}
+
+/// Change a key's rate limit and daily quota
+async fn set_limits(
+    State(state): State<AdminPartnerKeyState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<PartnerKeyLimitsRequest>,
+) -> Json<PartnerKeyResponse> {
+    if req.rate_per_minute < 0 || req.daily_quota < 0 {
+        return Json(PartnerKeyResponse::failed("limits must be non-negative"));
+    }
+    match state.keys.set_limits(id, req.rate_per_minute, req.daily_quota).await {
+        Ok(Some(partner_key)) => {
+            tracing::info!(key = %id, rate = req.rate_per_minute, quota = req.daily_quota, "Partner key limits changed");
+            Json(PartnerKeyResponse::ok(partner_key))
+        }
+        Ok(None) => Json(PartnerKeyResponse::failed("Partner key not found")),
+        Err(e) => {
+            tracing::error!("Failed to update partner key: {}", e);
+            Json(PartnerKeyResponse::failed("Database error"))
+        }
+    }
+}
+
+/// Set the webhook URL and signed-request requirement under a fresh webhook
+/// secret, returned in this response only
+async fn set_webhook(
+    State(state): State<AdminPartnerKeyState>,
+    Path(id): Path<Uuid>,
+    Json(req): Json<PartnerWebhookRequest>,
+) -> Json<PartnerKeyResponse> {
+    let url = req.url.as_deref().map(str::trim).filter(|url| !url.is_empty());
+    if let Some(url) = url {
+        let valid = reqwest::Url::parse(url).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+        if !valid {
+            return Json(PartnerKeyResponse::failed("url must be an http(s) URL"));
+        }
+    }
+
+    let secret = new_secret();
+    match state.keys.set_webhook(id, url, req.signed_requests, &secret).await {
+        Ok(Some(partner_key)) => {
+            tracing::info!(key = %id, url = ?url, signed_requests = req.signed_requests, "Partner webhook set");
+            Json(PartnerKeyResponse { webhook_secret: Some(secret), ..PartnerKeyResponse::ok(partner_key) })
+        }
+        Ok(None) => Json(PartnerKeyResponse::failed("Partner key not found")),
+        Err(e) => {
+            tracing::error!("Failed to set partner webhook: {}", e);
+            Json(PartnerKeyResponse::failed("Database error"))
+        }
+    }
+}
+
+/// Stop a key from authenticating
+async fn revoke_key(State(state): State<AdminPartnerKeyState>, Path(id): Path<Uuid>) -> Json<PartnerKeyResponse> {
+    match state.keys.revoke(id).await {
+        Ok(Some(partner_key)) => {
+            tracing::info!(key = %id, partner = %partner_key.partner, "Partner key revoked");
+            Json(PartnerKeyResponse::ok(partner_key))
+        }
+        Ok(None) => Json(PartnerKeyResponse::failed("Partner key not found")),
+        Err(e) => {
+            tracing::error!("Failed to revoke partner key: {}", e);
+            Json(PartnerKeyResponse::failed("Database error"))
+        }
+    }
+}
+
+/// Served and rejected requests per day and endpoint
+async fn key_usage(
+    State(state): State<AdminPartnerKeyState>,
+    Path(id): Path<Uuid>,
+    Query(query): Query<UsageQuery>,
+) -> Json<PartnerUsageResponse> {
+    let days = query.days.clamp(1, MAX_USAGE_DAYS);
+    let failed = |error: &str| Json(PartnerUsageResponse { success: false, usage: vec![], error: Some(error.to_string()) });
+    match state.keys.find(id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return failed("Partner key not found"),
+        Err(e) => {
+            tracing::error!("Failed to fetch partner key: {}", e);
+            return failed("Database error");
+        }
+    }
+    match state.keys.usage(id, days).await {
+        Ok(usage) => Json(PartnerUsageResponse {
+            success: true,
+            usage: usage.into_iter().map(UsageInfo::from).collect(),
+            error: None,
+        }),
+        Err(e) => {
+            tracing::error!("Failed to fetch partner usage: {}", e);
+            failed("Database error")
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{ReferralRepository, ReferralTotals, ReferrerStats, RejectionCount};
+
+const DEFAULT_DAYS: i64 = 30;
+const MAX_REFERRERS: i64 = 200;
+
+/// Period and size of the referral report
+#[derive(Debug, Deserialize)]
+pub struct ReferralReportQuery {
+    /// Referrals made in the last `days` days (default 30)
+    pub days: Option<i64>,
+    /// Referrers listed (default 20)
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReferralReportResponse {
+    pub success: bool,
+    pub days: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totals: Option<ReferralTotals>,
+    /// Why bonuses were refused, most common first
+    pub rejections: Vec<RejectionCount>,
+    /// By credited referrals
+    pub referrers: Vec<ReferrerStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Create admin referral reporting routes
+pub fn admin_referral_routes(repo: ReferralRepository) -> Router {
+    Router::new().route("/referrals", get(referral_report)).with_state(repo)
+}
+
+/// Sign-ups, bonuses paid, refusals and the top referrers over a period
+async fn referral_report(
+    State(repo): State<ReferralRepository>,
+    Query(query): Query<ReferralReportQuery>,
+) -> Json<ReferralReportResponse> {
+    let days = query.days.unwrap_or(DEFAULT_DAYS).max(1);
+    let limit = query.limit.unwrap_or(20).clamp(1, MAX_REFERRERS);
+    let since = Utc::now() - Duration::days(days);
+
+    let report = async {
+        let totals = repo.totals(since).await?;
+        let rejections = repo.rejections(since).await?;
+        let referrers = repo.top_referrers(since, limit).await?;
+        Ok::<_, sqlx::Error>((totals, rejections, referrers))
+    };
+    match report.await {
+        Ok((totals, rejections, referrers)) => {
+            Json(ReferralReportResponse { success: true, days, totals: Some(totals), rejections, referrers, error: None })
+        }
+        Err(e) => {
+            tracing::error!("Failed to build referral report: {}", e);
+            Json(ReferralReportResponse {
+                success: false,
+                days,
+                totals: None,
+                rejections: vec![],
+                referrers: vec![],
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::name_policy::{NamePolicy, NamePolicyError, NameRule};
+
+/// Request to reserve a label
+#[derive(Debug, Deserialize)]
+pub struct AddReservedNameRequest {
+    /// Label, or a regex for `pattern`
+    pub label: String,
+    /// `reserved` (default), `brand`, `offensive` or `pattern`
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    pub reason: Option<String>,
+}
+
+fn default_kind() -> String {
+    "reserved".to_string()
+}
+
+/// Every rule in effect
+#[derive(Debug, Serialize)]
+pub struct ReservedNamesResponse {
+    pub success: bool,
+    pub rules: Vec<NameRule>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReservedNamesResponse {
+    fn ok(policy: &NamePolicy) -> Self {
+        Self { success: true, rules: policy.rules(), error: None }
+    }
+
+    fn failed(policy: &NamePolicy, error: impl ToString) -> Self {
+        Self { success: false, rules: policy.rules(), error: Some(error.to_string()) }
+    }
+}
+
+/// Whether a label could be minted
+#[derive(Debug, Serialize)]
+pub struct NameCheckResponse {
+    pub label: String,
+    pub allowed: bool,
+    /// Rule the label breaks
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<NameRule>,
+}
+
+/// Create admin reserved-name routes
+pub fn admin_reserved_name_routes(policy: NamePolicy) -> Router {
+    Router::new()
+        .route("/reserved-names", get(list_reserved_names).post(add_reserved_name))
+        .route("/reserved-names/reload", post(reload_reserved_names))
+        .route("/reserved-names/check/:label", get(check_name))
+        .route("/reserved-names/:label", delete(remove_reserved_name))
+        .with_state(policy)
+}
+
+/// Built-in, configured and admin rules
+async fn list_reserved_names(State(policy): State<NamePolicy>) -> Json<ReservedNamesResponse> {
+    Json(ReservedNamesResponse::ok(&policy))
+}
+
+/// Reserve a label or pattern; takes effect for the next mint
+async fn add_reserved_name(
+    State(policy): State<NamePolicy>,
+    Json(req): Json<AddReservedNameRequest>,
+) -> Json<ReservedNamesResponse> {
+    match policy.add(&req.label, &req.kind, req.reason).await {
+        Ok(_) => Json(ReservedNamesResponse::ok(&policy)),
+        Err(NamePolicyError::Database(e)) => {
+            tracing::error!("Failed to save reserved name: {}", e);
+            Json(ReservedNamesResponse::failed(&policy, "Database error"))
+        }
+        Err(e) => Json(ReservedNamesResponse::failed(&policy, e)),
+    }
+}
+
+/// Release an admin rule (built-in and configured rules can't be removed)
+async fn remove_reserved_name(State(policy): State<NamePolicy>, Path(label): Path<String>) -> Json<ReservedNamesResponse> {
+    match policy.remove(&label).await {
+        Ok(true) => Json(ReservedNamesResponse::ok(&policy)),
+        Ok(false) => Json(ReservedNamesResponse::failed(&policy, format!("{} is not an admin rule", label))),
+        Err(e) => {
+            tracing::error!("Failed to remove reserved name: {}", e);
+            Json(ReservedNamesResponse::failed(&policy, "Database error"))
+        }
+    }
+}
+
+/// Re-read rules after editing `reserved_names` directly
+async fn reload_reserved_names(State(policy): State<NamePolicy>) -> Json<ReservedNamesResponse> {
+    match policy.reload().await {
+        Ok(()) => Json(ReservedNamesResponse::ok(&policy)),
+        Err(e) => {
+            tracing::error!("Failed to reload reserved names: {}", e);
+            Json(ReservedNamesResponse::failed(&policy, "Database error"))
+        }
+    }
+}
+
+/// Try a label against the rules without minting
+async fn check_name(State(policy): State<NamePolicy>, Path(label): Path<String>) -> Json<NameCheckResponse> {
+    let rule = policy.check(&label);
+    Json(NameCheckResponse { label, allowed: rule.is_none(), rule })
+}
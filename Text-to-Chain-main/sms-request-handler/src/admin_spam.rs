@@ -0,0 +1,115 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{SpamSample, SpamSampleRepository};
+use crate::sms::spam::{MutedNumber, SpamFilter};
+
+const MAX_SAMPLES: i64 = 500;
+
+/// The live filter (for mutes) and its stored samples
+#[derive(Clone)]
+pub struct AdminSpamState {
+    pub filter: SpamFilter,
+    pub samples: SpamSampleRepository,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SampleQuery {
+    /// Only samples not yet reviewed (default true)
+    pub pending: Option<bool>,
+    /// Samples returned (default 50)
+    pub limit: Option<i64>,
+}
+
+/// Reviewer's call on a sample
+#[derive(Debug, Deserialize)]
+pub struct ReviewRequest {
+    /// `spam` or `ham`
+    pub verdict: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SamplesResponse {
+    pub success: bool,
+    pub samples: Vec<SpamSample>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReviewResponse {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<SpamSample>,
+    /// Whether a `ham` review cleared strikes or a mute
+    pub lifted: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ReviewResponse {
+    fn failed(error: impl ToString) -> Self {
+        Self { success: false, sample: None, lifted: false, error: Some(error.to_string()) }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MutedResponse {
+    pub success: bool,
+    pub muted: Vec<MutedNumber>,
+}
+
+/// Create admin spam review routes
+pub fn admin_spam_routes(state: AdminSpamState) -> Router {
+    Router::new()
+        .route("/spam/samples", get(list_samples))
+        .route("/spam/samples/:id/review", post(review_sample))
+        .route("/spam/muted", get(list_muted))
+        .with_state(state)
+}
+
+/// Flagged and dropped messages, newest first
+async fn list_samples(State(state): State<AdminSpamState>, Query(query): Query<SampleQuery>) -> Json<SamplesResponse> {
+    let limit = query.limit.unwrap_or(50).clamp(1, MAX_SAMPLES);
+    match state.samples.list(query.pending.unwrap_or(true), limit).await {
+        Ok(samples) => Json(SamplesResponse { success: true, samples, error: None }),
+        Err(e) => {
+            tracing::error!("Failed to list spam samples: {}", e);
+            Json(SamplesResponse { success: false, samples: vec![], error: Some("Database error".to_string()) })
+        }
+    }
+}
+
+/// Mark a sample spam or ham; ham clears the sender's strikes and mute
+async fn review_sample(
+    State(state): State<AdminSpamState>,
+    Path(id): Path<i64>,
+    Json(req): Json<ReviewRequest>,
+) -> Json<ReviewResponse> {
+    let verdict = req.verdict.trim().to_lowercase();
+    if verdict != "spam" && verdict != "ham" {
+        return Json(ReviewResponse::failed("verdict must be spam or ham"));
+    }
+
+    match state.samples.review(id, &verdict).await {
+        Ok(Some(sample)) => {
+            let lifted = verdict == "ham" && state.filter.lift(&sample.phone);
+            tracing::info!(id, verdict = %verdict, lifted, "Spam sample reviewed");
+            Json(ReviewResponse { success: true, sample: Some(sample), lifted, error: None })
+        }
+        Ok(None) => Json(ReviewResponse::failed("Sample not found")),
+        Err(e) => {
+            tracing::error!("Failed to review spam sample: {}", e);
+            Json(ReviewResponse::failed("Database error"))
+        }
+    }
+}
+
+/// Numbers muted for repeated spam on this instance
+async fn list_muted(State(state): State<AdminSpamState>) -> Json<MutedResponse> {
+    Json(MutedResponse { success: true, muted: state.filter.muted() })
+}
@@ -0,0 +1,54 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::reporting::{Month, StatementFormat, StatementQuery, StatementState};
+
+/// Create admin statement routes
+pub fn admin_statement_routes(state: StatementState) -> Router {
+    Router::new()
+        .route("/statements/:user_id", get(get_statement))
+        .with_state(state)
+}
+
+/// A user's statement for `?month=YYYY-MM` (default last month), as JSON,
+/// CSV or PDF
+async fn get_statement(
+    State(state): State<StatementState>,
+    Path(user_id): Path<Uuid>,
+    Query(query): Query<StatementQuery>,
+) -> Response {
+    let today = Utc::now().date_naive();
+    let month = match query.month.as_deref() {
+        Some(month) => match Month::parse(month, today) {
+            Some(month) => month,
+            None => return (StatusCode::BAD_REQUEST, "month must be YYYY-MM").into_response(),
+        },
+        None => Month::containing(today).previous(),
+    };
+    let Some(format) = StatementFormat::parse(query.format.as_deref().unwrap_or("json")) else {
+        return (StatusCode::BAD_REQUEST, "format must be json, csv or pdf").into_response();
+    };
+
+    let user = match state.users.find_by_id(user_id).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return (StatusCode::NOT_FOUND, "User not found").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to fetch user for statement: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response();
+        }
+    };
+    match state.load(&user, month).await {
+        Ok(statement) => format.respond(&statement),
+        Err(e) => {
+            tracing::error!("Failed to load statement for {}: {}", user_id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::wallet::token_registry::{TokenError, TokenRegistry, TokenState};
+
+/// Request to override a token address
+#[derive(Debug, Deserialize)]
+pub struct SetTokenRequest {
+    /// Contract address to use, or null to fall back to TOKEN_ADDRESSES / the built-in address
+    pub address: Option<String>,
+}
+
+/// Current token addresses
+#[derive(Debug, Serialize)]
+pub struct TokensResponse {
+    pub success: bool,
+    pub tokens: Vec<TokenState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TokensResponse {
+    fn ok(registry: &TokenRegistry) -> Self {
+        Self { success: true, tokens: registry.snapshot(), error: None }
+    }
+
+    fn failed(registry: &TokenRegistry, error: impl ToString) -> Self {
+        Self { success: false, tokens: registry.snapshot(), error: Some(error.to_string()) }
+    }
+}
+
+/// Create admin token address routes
+pub fn admin_token_routes(registry: TokenRegistry) -> Router {
+    Router::new()
+        .route("/tokens", get(list_tokens))
+        .route("/tokens/reload", post(reload_tokens))
+        .route("/tokens/:chain/:symbol", put(set_token))
+        .with_state(registry)
+}
+
+/// List every chain/token address and where it comes from
+async fn list_tokens(State(registry): State<TokenRegistry>) -> Json<TokensResponse> {
+    Json(TokensResponse::ok(&registry))
+}
+
+/// Override (or clear the override of) one token address; takes effect immediately
+async fn set_token(
+    State(registry): State<TokenRegistry>,
+    Path((chain, symbol)): Path<(String, String)>,
+    Json(req): Json<SetTokenRequest>,
+) -> Json<TokensResponse> {
+    match registry.set_override(&chain, &symbol, req.address.as_deref()).await {
+        Ok(()) => Json(TokensResponse::ok(&registry)),
+        Err(TokenError::Database(e)) => {
+            tracing::error!("Failed to save token override: {}", e);
+            Json(TokensResponse::failed(&registry, "Database error"))
+        }
+        Err(e) => Json(TokensResponse::failed(&registry, e)),
+    }
+}
+
+/// Re-read overrides after editing `token_overrides` directly
+async fn reload_tokens(State(registry): State<TokenRegistry>) -> Json<TokensResponse> {
+    match registry.reload().await {
+        Ok(()) => Json(TokensResponse::ok(&registry)),
+        Err(e) => {
+            tracing::error!("Failed to reload token overrides: {}", e);
+            Json(TokensResponse::failed(&registry, "Database error"))
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::{TranscriptEntry, TranscriptRepository};
+
+/// Messages returned when no limit is given
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 500;
+
+#[derive(Debug, Deserialize)]
+pub struct TranscriptQuery {
+    /// Latest N messages (default 100, max 500)
+    pub limit: Option<i64>,
+}
+
+/// One message as shown to support staff
+#[derive(Debug, Serialize)]
+pub struct TranscriptMessage {
+    /// "in" = sent by the user, "out" = sent by us
+    pub direction: String,
+    pub body: String,
+    pub at: String,
+}
+
+impl From<TranscriptEntry> for TranscriptMessage {
+    fn from(entry: TranscriptEntry) -> Self {
+        Self {
+            direction: entry.direction,
+            body: entry.body,
+            at: entry.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Transcript for one user, oldest message first
+#[derive(Debug, Serialize)]
+pub struct TranscriptResponse {
+    pub success: bool,
+    pub phone: String,
+    pub messages: Vec<TranscriptMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Create admin transcript routes
+pub fn admin_transcript_routes(repo: TranscriptRepository) -> Router {
+    Router::new()
+        .route("/users/:phone/transcript", get(get_transcript))
+        .with_state(repo)
+}
+
+/// What a user typed and what the bot replied, within the retention period
+async fn get_transcript(
+    State(repo): State<TranscriptRepository>,
+    Path(phone): Path<String>,
+    Query(query): Query<TranscriptQuery>,
+) -> Json<TranscriptResponse> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match repo.for_phone(&phone, limit).await {
+        Ok(entries) => Json(TranscriptResponse {
+            success: true,
+            phone,
+            messages: entries.into_iter().map(TranscriptMessage::from).collect(),
+            error: None,
+        }),
+        Err(e) => {
+            tracing::error!("Failed to fetch transcript: {}", e);
+            Json(TranscriptResponse {
+                success: false,
+                phone,
+                messages: vec![],
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::money::{Currency, Money};
+use crate::wallet::tokens::IERC20;
+use crate::wallet::{SafeClient, SafeTransaction};
+
+/// Admin treasury routes state
+#[derive(Clone)]
+pub struct AdminTreasuryState {
+    pub safe: Arc<SafeClient>,
+    /// Backend hot wallet key, used for withdrawals below the multisig threshold
+    pub hot_wallet_key: String,
+    pub large_withdrawal_thresholds: WithdrawalThresholds,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ThresholdError {
+    #[error("Invalid SAFE_LARGE_WITHDRAWAL_THRESHOLDS entry {0}")]
+    Entry(String),
+}
+
+/// Per-token amount at or above which a withdrawal is proposed to the Safe
+#[derive(Debug, Clone, Default)]
+pub struct WithdrawalThresholds {
+    thresholds: HashMap<Currency, Money>,
+}
+
+impl WithdrawalThresholds {
+    /// `USDC:1000,ETH:0.5` -> token symbol -> threshold
+    pub fn parse(spec: &str) -> Result<Self, ThresholdError> {
+        let thresholds = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .map(|entry| {
+                let invalid = || ThresholdError::Entry(entry.to_string());
+                let (symbol, amount) = entry.split_once(':').ok_or_else(invalid)?;
+                let currency = Currency::parse(symbol).ok_or_else(invalid)?;
+                let threshold = Money::parse(amount, currency).ok().filter(Money::is_positive).ok_or_else(invalid)?;
+                Ok((currency, threshold))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { thresholds })
+    }
+
+    /// Whether `amount` must go through the Safe; tokens without a
+    /// threshold always do
+    pub fn requires_multisig(&self, amount: Money) -> bool {
+        self.thresholds
+            .get(&amount.currency())
+            .is_none_or(|threshold| amount.micros() >= threshold.micros())
+    }
+}
+
+/// Request to withdraw funds from the treasury
+#[derive(Debug, Deserialize)]
+pub struct WithdrawRequest {
+    /// Destination address
+    pub to: String,
+    /// Amount in token units (e.g., 1500 or "1500.25" for USDC)
+    pub amount: Money,
+    /// ERC20 token address, or omitted for the native token
+    pub token: Option<String>,
+}
+
+/// Request to sweep the full treasury balance of a token to another address
+#[derive(Debug, Deserialize)]
+pub struct SweepRequest {
+    /// ERC20 token address, or omitted for the native token
+    pub token: Option<String>,
+    /// Destination address
+    pub to: String,
+}
+
+/// Treasury operation response
+#[derive(Debug, Serialize)]
+pub struct TreasuryResponse {
+    pub success: bool,
+    /// "multisig" when proposed to the Safe, "hot_wallet" when sent directly
+    pub route: Option<String>,
+    pub safe_tx_hash: Option<String>,
+    pub tx_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+impl TreasuryResponse {
+    fn proposed(safe_tx_hash: H256) -> Self {
+        Self {
+            success: true,
+            route: Some("multisig".to_string()),
+            safe_tx_hash: Some(format!("{:?}", safe_tx_hash)),
+            tx_hash: None,
+            error: None,
+        }
+    }
+
+    fn sent(tx_hash: H256) -> Self {
+        Self {
+            success: true,
+            route: Some("hot_wallet".to_string()),
+            safe_tx_hash: None,
+            tx_hash: Some(format!("{:?}", tx_hash)),
+            error: None,
+        }
+    }
+
+    fn executed(safe_tx_hash: H256, tx_hash: H256) -> Self {
+        Self {
+            success: true,
+            route: Some("multisig".to_string()),
+            safe_tx_hash: Some(format!("{:?}", safe_tx_hash)),
+            tx_hash: Some(format!("{:?}", tx_hash)),
+            error: None,
+        }
+    }
+
+    fn failed(error: impl ToString) -> Self {
+        Self {
+            success: false,
+            route: None,
+            safe_tx_hash: None,
+            tx_hash: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Pending Safe transaction info
+#[derive(Debug, Serialize)]
+pub struct SafeTransactionInfo {
+    pub success: bool,
+    pub safe_tx_hash: String,
+    pub to: String,
+    pub value: String,
+    pub nonce: u64,
+    pub confirmations: usize,
+    pub confirmations_required: Option<usize>,
+    pub is_executed: bool,
+    pub tx_hash: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Create admin treasury routes
+pub fn admin_treasury_routes(state: AdminTreasuryState) -> Router {
+    Router::new()
+        .route("/treasury/withdrawals", post(withdraw))
+        .route("/treasury/sweep", post(sweep))
+        .route("/treasury/transactions/:safe_tx_hash", get(get_transaction))
+        .route("/treasury/transactions/:safe_tx_hash/execute", post(execute_transaction))
+        .with_state(state)
+}
+
+/// Withdraw from the treasury - large amounts are proposed to the Safe,
+/// small ones are paid from the hot wallet
+async fn withdraw(
+    State(state): State<AdminTreasuryState>,
+    Json(req): Json<WithdrawRequest>,
+) -> Json<TreasuryResponse> {
+    let to: Address = match req.to.parse() {
+        Ok(addr) => addr,
+        Err(_) => return Json(TreasuryResponse::failed("Invalid destination address")),
+    };
+    let token: Option<Address> = match req.token.as_deref().map(str::parse) {
+        Some(Ok(addr)) => Some(addr),
+        Some(Err(_)) => return Json(TreasuryResponse::failed("Invalid token address")),
+        None => None,
+    };
+
+    if !req.amount.is_positive() {
+        return Json(TreasuryResponse::failed("Amount must be positive"));
+    }
+
+    let provider = state.safe.provider();
+    let (decimals, symbol) = match token {
+        Some(token) => {
+            let erc20 = IERC20::new(token, provider.clone());
+            let (decimals, symbol) = (erc20.decimals(), erc20.symbol());
+            match tokio::try_join!(decimals.call(), symbol.call()) {
+                Ok(info) => info,
+                Err(e) => return Json(TreasuryResponse::failed(e)),
+            }
+        }
+        None => (18, state.safe.chain().native_token().to_string()),
+    };
+    let Some(currency) = Currency::parse(&symbol) else {
+        return Json(TreasuryResponse::failed(format!("Unsupported token symbol {}", symbol)));
+    };
+    // The body carries a bare number; it is in units of the token withdrawn
+    let value = Money::from_micros(req.amount.micros(), currency);
+    let amount: U256 = match ethers::utils::parse_units(value.amount(), decimals as u32) {
+        Ok(units) => units.into(),
+        Err(e) => return Json(TreasuryResponse::failed(e)),
+    };
+
+    if state.large_withdrawal_thresholds.requires_multisig(value) {
+        tracing::info!(to = ?to, amount = %value, "Large withdrawal - proposing to treasury Safe");
+        return Json(propose(&state.safe, token, to, amount, "textchain-withdrawal").await);
+    }
+
+    let wallet: LocalWallet = match state.hot_wallet_key.trim_start_matches("0x").parse::<LocalWallet>() {
+        Ok(w) => w.with_chain_id(state.safe.chain().chain_id()),
+        Err(_) => return Json(TreasuryResponse::failed("Hot wallet not configured")),
+    };
+    let client = Arc::new(SignerMiddleware::new(provider, wallet));
+
+    let result = match token {
+        Some(token) => {
+            let call = IERC20::new(token, client).transfer(to, amount);
+            call.send().await.map(|pending| pending.tx_hash()).map_err(|e| e.to_string())
+        }
+        None => {
+            let tx = TransactionRequest::new().to(to).value(amount);
+            client.send_transaction(tx, None).await.map(|pending| pending.tx_hash()).map_err(|e| e.to_string())
+        }
+    };
+
+    match result {
+        Ok(tx_hash) => Json(TreasuryResponse::sent(tx_hash)),
+        Err(e) => {
+            tracing::error!("Hot wallet withdrawal failed: {}", e);
+            Json(TreasuryResponse::failed(e))
+        }
+    }
+}
+
+/// Sweep the whole treasury balance of a token - always proposed through the Safe
+/// regardless of amount
+async fn sweep(
+    State(state): State<AdminTreasuryState>,
+    Json(req): Json<SweepRequest>,
+) -> Json<TreasuryResponse> {
+    let to: Address = match req.to.parse() {
+        Ok(addr) => addr,
+        Err(_) => return Json(TreasuryResponse::failed("Invalid destination address")),
+    };
+    let token: Option<Address> = match req.token.as_deref().map(str::parse) {
+        Some(Ok(addr)) => Some(addr),
+        Some(Err(_)) => return Json(TreasuryResponse::failed("Invalid token address")),
+        None => None,
+    };
+
+    let provider = state.safe.provider();
+    let safe_address = state.safe.safe_address();
+    let balance = match token {
+        Some(token) => IERC20::new(token, provider).balance_of(safe_address).call().await.map_err(|e| e.to_string()),
+        None => provider.get_balance(safe_address, None).await.map_err(|e| e.to_string()),
+    };
+
+    match balance {
+        Ok(balance) if balance.is_zero() => Json(TreasuryResponse::failed("Nothing to sweep")),
+        Ok(balance) => Json(propose(&state.safe, token, to, balance, "textchain-sweep").await),
+        Err(e) => Json(TreasuryResponse::failed(e)),
+    }
+}
+
+async fn propose(
+    safe: &SafeClient,
+    token: Option<Address>,
+    to: Address,
+    amount: U256,
+    origin: &str,
+) -> TreasuryResponse {
+    let nonce = match safe.nonce().await {
+        Ok(n) => n,
+        Err(e) => return TreasuryResponse::failed(e),
+    };
+
+    let tx = match token {
+        Some(token) => SafeTransaction::erc20_transfer(token, to, amount, nonce, safe.provider()),
+        None => SafeTransaction::native_transfer(to, amount, nonce),
+    };
+
+    match safe.propose(&tx, origin).await {
+        Ok(safe_tx_hash) => TreasuryResponse::proposed(safe_tx_hash),
+        Err(e) => {
+            tracing::error!("Safe proposal failed: {}", e);
+            TreasuryResponse::failed(e)
+        }
+    }
+}
+
+/// Get a proposed Safe transaction and its confirmation count
+async fn get_transaction(
+    State(state): State<AdminTreasuryState>,
+    Path(safe_tx_hash): Path<String>,
+) -> Json<SafeTransactionInfo> {
+    let not_found = |error: String| SafeTransactionInfo {
+        success: false,
+        safe_tx_hash: safe_tx_hash.clone(),
+        to: String::new(),
+        value: String::new(),
+        nonce: 0,
+        confirmations: 0,
+        confirmations_required: None,
+        is_executed: false,
+        tx_hash: None,
+        error: Some(error),
+    };
+
+    let hash: H256 = match safe_tx_hash.parse() {
+        Ok(h) => h,
+        Err(_) => return Json(not_found("Invalid Safe transaction hash".to_string())),
+    };
+
+    match state.safe.status(hash).await {
+        Ok(status) => Json(SafeTransactionInfo {
+            success: true,
+            safe_tx_hash: format!("{:?}", status.safe_tx_hash),
+            to: format!("{:?}", status.to),
+            value: status.value,
+            nonce: status.nonce,
+            confirmations: status.confirmations.len(),
+            confirmations_required: status.confirmations_required,
+            is_executed: status.is_executed,
+            tx_hash: status.transaction_hash.map(|h| format!("{:?}", h)),
+            error: None,
+        }),
+        Err(e) => Json(not_found(e.to_string())),
+    }
+}
+
+/// Execute a Safe transaction once the owner threshold is reached
+async fn execute_transaction(
+    State(state): State<AdminTreasuryState>,
+    Path(safe_tx_hash): Path<String>,
+) -> Json<TreasuryResponse> {
+    let hash: H256 = match safe_tx_hash.parse() {
+        Ok(h) => h,
+        Err(_) => return Json(TreasuryResponse::failed("Invalid Safe transaction hash")),
+    };
+
+    match state.safe.execute_if_ready(hash).await {
+        Ok(tx_hash) => Json(TreasuryResponse::executed(hash, tx_hash)),
+        Err(e) => Json(TreasuryResponse::failed(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thresholds_are_per_token() {
+        let thresholds = WithdrawalThresholds::parse("USDC:1000, eth:0.5").unwrap();
+        let usdc = |amount: &str| Money::parse(amount, Currency::USDC).unwrap();
+        let eth = |amount: &str| Money::parse(amount, Currency::parse("ETH").unwrap()).unwrap();
+
+        assert!(!thresholds.requires_multisig(usdc("999.999999")));
+        assert!(thresholds.requires_multisig(usdc("1000")));
+        assert!(!thresholds.requires_multisig(eth("0.4")));
+        assert!(thresholds.requires_multisig(eth("0.5")));
+        // No threshold for the token: always through the Safe
+        assert!(thresholds.requires_multisig(Money::parse("1", Currency::parse("DAI").unwrap()).unwrap()));
+
+        assert!(WithdrawalThresholds::parse("").unwrap().requires_multisig(usdc("0.01")));
+        assert_eq!(WithdrawalThresholds::parse("USDC").unwrap_err(), ThresholdError::Entry("USDC".to_string()));
+        assert!(WithdrawalThresholds::parse("USDC:abc").is_err());
+        assert!(WithdrawalThresholds::parse("USDC:0").is_err());
+    }
+}
@@ -1,12 +1,17 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
     routing::get,
     Json, Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::sync::Arc;
 
+use crate::db::{micro_to_f64, FieldCipher, UserRepository, UserSearch, UserSummary};
+use crate::wallet::address::{display_address, qr_png};
+
 /// Wallet info response
 #[derive(Debug, Serialize)]
 pub struct WalletInfo {
@@ -24,6 +29,56 @@ pub struct ListWalletsResponse {
     pub wallets: Vec<WalletInfo>,
 }
 
+/// Search results per page when no limit is given
+const DEFAULT_SEARCH_LIMIT: i64 = 50;
+const MAX_SEARCH_LIMIT: i64 = 500;
+
+/// Page of `GET /admin/users/search`; filters and sort come from `UserSearch`
+#[derive(Debug, Deserialize)]
+pub struct SearchPage {
+    pub offset: Option<i64>,
+    /// Default 50, max 500
+    pub limit: Option<i64>,
+}
+
+/// A user in search results
+#[derive(Debug, Serialize)]
+pub struct UserResult {
+    pub id: String,
+    pub phone: String,
+    pub wallet_address: String,
+    pub ens_name: Option<String>,
+    /// Cash balance in USDC
+    pub balance: f64,
+    pub created_at: String,
+}
+
+impl From<UserSummary> for UserResult {
+    fn from(user: UserSummary) -> Self {
+        Self {
+            id: user.id.to_string(),
+            phone: user.phone,
+            wallet_address: display_address(&user.wallet_address),
+            ens_name: user.ens_name,
+            balance: micro_to_f64(user.balance),
+            created_at: user.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// User search response
+#[derive(Debug, Serialize)]
+pub struct SearchUsersResponse {
+    pub success: bool,
+    /// Users matching the filters, across all pages
+    pub total: i64,
+    pub offset: i64,
+    pub limit: i64,
+    pub users: Vec<UserResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 /// Get wallet by phone response
 #[derive(Debug, Serialize)]
 pub struct GetWalletResponse {
@@ -35,15 +90,21 @@ pub struct GetWalletResponse {
 #[derive(Clone)]
 pub struct AdminWalletState {
     pub db_pool: Arc<PgPool>,
+    /// Decrypts phone numbers and computes their lookup keys
+    pub cipher: FieldCipher,
+    pub users: UserRepository,
 }
 
 /// Create admin wallet routes
-pub fn admin_wallet_routes(db_pool: Arc<PgPool>) -> Router {
-    let state = AdminWalletState { db_pool };
-    
+pub fn admin_wallet_routes(db_pool: Arc<PgPool>, cipher: FieldCipher) -> Router {
+    let users = UserRepository::new((*db_pool).clone(), cipher.clone());
+    let state = AdminWalletState { db_pool, cipher, users };
+
     Router::new()
         .route("/wallets", get(list_all_wallets))
         .route("/wallets/:phone", get(get_wallet_by_phone))
+        .route("/wallets/:phone/qr.png", get(get_deposit_qr))
+        .route("/users/search", get(search_users))
         .with_state(state)
 }
 
@@ -52,7 +113,8 @@ async fn list_all_wallets(
     State(state): State<AdminWalletState>,
 ) -> Json<ListWalletsResponse> {
     let result = sqlx::query_as::<_, (String, String, Option<String>, chrono::DateTime<chrono::Utc>)>(
-        "SELECT phone, wallet_address, ens_name, created_at FROM users ORDER BY created_at DESC LIMIT 100"
+        "SELECT COALESCE(phone_encrypted, phone), wallet_address, ens_name, created_at
+         FROM users ORDER BY created_at DESC LIMIT 100"
     )
     .fetch_all(&*state.db_pool)
     .await;
@@ -62,8 +124,8 @@ async fn list_all_wallets(
             let wallets: Vec<WalletInfo> = rows
                 .into_iter()
                 .map(|(phone, wallet_address, ens_name, created_at)| WalletInfo {
-                    phone,
-                    wallet_address,
+                    phone: decrypt_phone(&state.cipher, &phone),
+                    wallet_address: display_address(&wallet_address),
                     ens_name,
                     created_at: created_at.to_rfc3339(),
                 })
@@ -86,15 +148,49 @@ async fn list_all_wallets(
     }
 }
 
+/// Users by phone prefix, ENS name, wallet address, signup date and cash
+/// balance, sorted and paginated
+async fn search_users(
+    State(state): State<AdminWalletState>,
+    Query(search): Query<UserSearch>,
+    Query(page): Query<SearchPage>,
+) -> Json<SearchUsersResponse> {
+    let offset = page.offset.unwrap_or(0).max(0);
+    let limit = page.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).clamp(1, MAX_SEARCH_LIMIT);
+
+    match state.users.search(&search, offset, limit).await {
+        Ok((users, total)) => Json(SearchUsersResponse {
+            success: true,
+            total,
+            offset,
+            limit,
+            users: users.into_iter().map(UserResult::from).collect(),
+            error: None,
+        }),
+        Err(e) => {
+            tracing::error!("User search failed: {}", e);
+            Json(SearchUsersResponse {
+                success: false,
+                total: 0,
+                offset,
+                limit,
+                users: vec![],
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
+
 /// Get wallet by phone number
 async fn get_wallet_by_phone(
     State(state): State<AdminWalletState>,
     Path(phone): Path<String>,
 ) -> Json<GetWalletResponse> {
     let result = sqlx::query_as::<_, (String, String, Option<String>, chrono::DateTime<chrono::Utc>)>(
-        "SELECT phone, wallet_address, ens_name, created_at FROM users WHERE phone = $1"
+        "SELECT COALESCE(phone_encrypted, phone), wallet_address, ens_name, created_at
+         FROM users WHERE phone = ANY($1)"
     )
-    .bind(&phone)
+    .bind(state.cipher.lookup_keys(&phone))
     .fetch_optional(&*state.db_pool)
     .await;
 
@@ -103,8 +199,8 @@ async fn get_wallet_by_phone(
             Json(GetWalletResponse {
                 success: true,
                 wallet: Some(WalletInfo {
-                    phone,
-                    wallet_address,
+                    phone: decrypt_phone(&state.cipher, &phone),
+                    wallet_address: display_address(&wallet_address),
                     ens_name,
                     created_at: created_at.to_rfc3339(),
                 }),
@@ -123,3 +219,40 @@ async fn get_wallet_by_phone(
         }
     }
 }
+
+/// Deposit QR code (PNG) for a user's checksummed wallet address,
+/// for printing on posters and in agent shops
+async fn get_deposit_qr(
+    State(state): State<AdminWalletState>,
+    Path(phone): Path<String>,
+) -> Response {
+    let result = sqlx::query_scalar::<_, String>("SELECT wallet_address FROM users WHERE phone = ANY($1)")
+        .bind(state.cipher.lookup_keys(&phone))
+        .fetch_optional(&*state.db_pool)
+        .await;
+
+    let wallet_address = match result {
+        Ok(Some(address)) => address,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Wallet not found").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to fetch wallet: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    match qr_png(&display_address(&wallet_address)) {
+        Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render QR code: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Plaintext phone for display, or a placeholder if it can't be decrypted
+fn decrypt_phone(cipher: &FieldCipher, stored: &str) -> String {
+    cipher.decrypt(stored).unwrap_or_else(|e| {
+        tracing::error!("Failed to decrypt phone: {}", e);
+        "<encrypted>".to_string()
+    })
+}
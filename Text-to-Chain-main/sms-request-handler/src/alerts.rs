@@ -0,0 +1,498 @@
+//! Operator alerts to Slack and PagerDuty
+//!
+//! Events that need a person - the treasury or another signer below its gas
+//! threshold, an RPC circuit opening, a reconciliation finding money or names
+//! that don't match the chain, a cash payout lost on the way, a failed
+//! database backup, a job queue backing up - are posted to every Slack
+//! webhook in `ALERT_SLACK_WEBHOOKS` and triggered on the PagerDuty Events
+//! API with `ALERT_PAGERDUTY_ROUTING_KEY`. Each destination has its own lowest
+//! severity, so Slack can hear about warnings while only errors page someone.
+//!
+//! Every alert carries a dedupe key, prefixed with `ALERT_DEPLOYMENT` so
+//! staging and production never share an incident. PagerDuty folds repeats
+//! into one incident, and the same key isn't sent again for
+//! `ALERT_REPEAT_MINUTES`. When the condition clears the incident is resolved
+//! and Slack is told. Code anywhere calls `raise` and `resolve`; until
+//! `install` runs they only log.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::config::OperatorAlertConfig;
+use crate::db::SendJobRepository;
+use crate::money::Money;
+use crate::wallet::Chain;
+use crate::workers::WorkerPool;
+
+/// The destinations `raise` and `resolve` send to, once installed
+static INSTALLED: LazyLock<RwLock<Option<OperatorAlerts>>> = LazyLock::new(Default::default);
+
+/// How urgent an alert is, using PagerDuty's levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+    Critical,
+}
+
+impl Severity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "info" => Some(Severity::Info),
+            "warning" => Some(Severity::Warning),
+            "error" => Some(Severity::Error),
+            "critical" => Some(Severity::Critical),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+            Severity::Critical => "critical",
+        }
+    }
+
+    fn slack_emoji(self) -> &'static str {
+        match self {
+            Severity::Info => ":information_source:",
+            Severity::Warning => ":warning:",
+            Severity::Error => ":x:",
+            Severity::Critical => ":rotating_light:",
+        }
+    }
+}
+
+/// One event for the operators
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    /// Same key, same incident: repeats fold together until it is resolved
+    pub dedup_key: String,
+    pub severity: Severity,
+    /// Kind of event, e.g. `treasury_low`
+    pub class: &'static str,
+    pub summary: String,
+    pub details: Value,
+}
+
+impl Alert {
+    /// A treasury or signer wallet below its gas threshold
+    pub fn treasury_low(label: &str, chain: Chain, address: &str, balance: f64, threshold: f64) -> Self {
+        Self {
+            dedup_key: Self::treasury_low_key(label, chain),
+            severity: Severity::Critical,
+            class: "treasury_low",
+            summary: format!(
+                "{} wallet {} has {} {} on {} (min {})",
+                label,
+                address,
+                balance,
+                chain.native_token(),
+                chain.name(),
+                threshold
+            ),
+            details: json!({
+                "label": label,
+                "chain": chain.short_code(),
+                "address": address,
+                "balance": balance,
+                "threshold": threshold,
+            }),
+        }
+    }
+
+    pub fn treasury_low_key(label: &str, chain: Chain) -> String {
+        format!("treasury_low:{}:{}", label, chain.short_code())
+    }
+
+    /// A chain's RPC failed often enough that calls now fail fast
+    pub fn circuit_open(chain: Chain) -> Self {
+        Self {
+            dedup_key: Self::circuit_open_key(chain),
+            severity: Severity::Error,
+            class: "circuit_open",
+            summary: format!("RPC circuit open for {}: calls fail fast until a probe gets through", chain.name()),
+            details: json!({ "chain": chain.short_code(), "chain_id": chain.chain_id() }),
+        }
+    }
+
+    pub fn circuit_open_key(chain: Chain) -> String {
+        format!("circuit_open:{}", chain.short_code())
+    }
+
+    /// A background check found records that disagree with the chain
+    pub fn reconciliation_mismatch(check: &str, mismatches: usize, details: Value) -> Self {
+        Self {
+            dedup_key: format!("reconciliation:{}", check),
+            severity: Severity::Error,
+            class: "reconciliation_mismatch",
+            summary: format!("{} reconciliation found {} mismatch(es)", check, mismatches),
+            details: json!({ "check": check, "mismatches": mismatches, "detail": details }),
+        }
+    }
+
+    /// A cash payout whose transaction the chain doesn't know; not refunded
+    /// automatically since it may still land
+    pub fn payout_stuck(payout: &str, chain: Chain, tx_hash: &str, minutes: i64) -> Self {
+        Self {
+            dedup_key: format!("payout_stuck:{}", payout),
+            severity: Severity::Error,
+            class: "payout_stuck",
+            summary: format!("Cash payout {} on {} not seen on chain after {} min", payout, chain.name(), minutes),
+            details: json!({ "payout": payout, "chain": chain.short_code(), "tx_hash": tx_hash, "minutes": minutes }),
+        }
+    }
+
+    /// A payout went out but couldn't be marked sent; the poll still
+    /// follows it by the hash stored before the broadcast
+    pub fn payout_unrecorded(payout: &str, chain: Chain, tx_hash: &str, error: &str) -> Self {
+        Self {
+            dedup_key: format!("payout_unrecorded:{}", payout),
+            severity: Severity::Warning,
+            class: "payout_unrecorded",
+            summary: format!("Cash payout {} on {} sent but not marked sent: {}", payout, chain.name(), error),
+            details: json!({ "payout": payout, "chain": chain.short_code(), "tx_hash": tx_hash, "error": error }),
+        }
+    }
+
+    /// A name moved to its buyer on chain but the sale wasn't settled, so the
+    /// price is still in escrow
+    pub fn name_sale_unsettled(listing: &str, name: &str, price: Money, error: &str) -> Self {
+        Self {
+            dedup_key: format!("name_sale_unsettled:{}", listing),
+            severity: Severity::Error,
+            class: "name_sale_unsettled",
+            summary: format!("{} moved to its buyer but sale {} wasn't settled; {} left in escrow: {}", name, listing, price, error),
+            details: json!({ "listing": listing, "name": name, "price": price, "token": price.currency().code(), "error": error }),
+        }
+    }
+
+    /// A scheduled database backup didn't make it to the bucket
+    pub fn backup_failed(error: &str) -> Self {
+        Self {
+            dedup_key: Self::backup_failed_key(),
+            severity: Severity::Error,
+            class: "backup_failed",
+            summary: format!("Database backup failed: {}", error),
+            details: json!({ "error": error }),
+        }
+    }
+
+    pub fn backup_failed_key() -> String {
+        "backup_failed".to_string()
+    }
+
+    /// Work waiting in a queue at or above the backlog limit
+    pub fn queue_backlog(queue: &str, waiting: u64, limit: u64) -> Self {
+        Self {
+            dedup_key: Self::queue_backlog_key(queue),
+            severity: Severity::Warning,
+            class: "queue_backlog",
+            summary: format!("{} queue backed up: {} waiting (alert at {})", queue, waiting, limit),
+            details: json!({ "queue": queue, "waiting": waiting, "limit": limit }),
+        }
+    }
+
+    pub fn queue_backlog_key(queue: &str) -> String {
+        format!("queue_backlog:{}", queue)
+    }
+}
+
+/// Slack webhooks and a PagerDuty service for this deployment
+#[derive(Clone)]
+pub struct OperatorAlerts {
+    deployment: String,
+    slack_urls: Vec<String>,
+    slack_min: Severity,
+    pagerduty_key: Option<String>,
+    pagerduty_min: Severity,
+    pagerduty_url: String,
+    repeat: Duration,
+    /// Open alerts by dedupe key, with when each was last sent
+    open: Arc<Mutex<HashMap<String, (Severity, Instant)>>>,
+    http: reqwest::Client,
+}
+
+impl OperatorAlerts {
+    /// None when neither Slack nor PagerDuty is configured
+    pub fn from_config(config: &OperatorAlertConfig) -> Option<Self> {
+        if !config.is_enabled() {
+            return None;
+        }
+        Some(Self {
+            deployment: config.deployment.trim().to_string(),
+            slack_urls: config.slack_urls(),
+            slack_min: Severity::parse(&config.slack_min_severity).unwrap_or(Severity::Warning),
+            pagerduty_key: Some(config.pagerduty_routing_key.trim().to_string()).filter(|key| !key.is_empty()),
+            pagerduty_min: Severity::parse(&config.pagerduty_min_severity).unwrap_or(Severity::Error),
+            pagerduty_url: config.pagerduty_url.trim().to_string(),
+            repeat: Duration::from_secs(config.repeat_minutes * 60),
+            open: Arc::new(Mutex::new(HashMap::new())),
+            http: reqwest::Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default(),
+        })
+    }
+
+    /// Where alerts go, for the startup log
+    pub fn destinations(&self) -> Vec<String> {
+        let mut destinations = Vec::new();
+        if !self.slack_urls.is_empty() {
+            destinations.push(format!("slack x{} ({}+)", self.slack_urls.len(), self.slack_min.as_str()));
+        }
+        if self.pagerduty_key.is_some() {
+            destinations.push(format!("pagerduty ({}+)", self.pagerduty_min.as_str()));
+        }
+        destinations
+    }
+
+    /// Make these the destinations of `raise` and `resolve`
+    pub fn install(self) {
+        *INSTALLED.write().unwrap_or_else(|e| e.into_inner()) = Some(self);
+    }
+
+    /// Send an alert unless the same key went out within the repeat window
+    pub async fn send(&self, alert: &Alert) {
+        let now = Instant::now();
+        {
+            let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+            if !should_send(open.get(&alert.dedup_key).map(|(_, at)| *at), now, self.repeat) {
+                return;
+            }
+            open.insert(alert.dedup_key.clone(), (alert.severity, now));
+        }
+
+        if alert.severity >= self.slack_min {
+            let body = self.slack_message(alert);
+            for url in &self.slack_urls {
+                self.post("Slack", url, &body).await;
+            }
+        }
+        if let Some(ref key) = self.pagerduty_key {
+            if alert.severity >= self.pagerduty_min {
+                self.post("PagerDuty", &self.pagerduty_url, &self.pagerduty_trigger(key, alert)).await;
+            }
+        }
+    }
+
+    /// Close an open alert; keys that aren't open are ignored
+    pub async fn clear(&self, dedup_key: &str) {
+        let Some((severity, _)) = self.open.lock().unwrap_or_else(|e| e.into_inner()).remove(dedup_key) else {
+            return;
+        };
+
+        if severity >= self.slack_min {
+            let body = json!({ "text": format!(":white_check_mark: [{}] resolved: {}", self.deployment, dedup_key) });
+            for url in &self.slack_urls {
+                self.post("Slack", url, &body).await;
+            }
+        }
+        if let Some(ref key) = self.pagerduty_key {
+            if severity >= self.pagerduty_min {
+                let body = json!({
+                    "routing_key": key,
+                    "event_action": "resolve",
+                    "dedup_key": self.dedup_key(dedup_key),
+                });
+                self.post("PagerDuty", &self.pagerduty_url, &body).await;
+            }
+        }
+    }
+
+    fn dedup_key(&self, key: &str) -> String {
+        format!("{}:{}", self.deployment, key)
+    }
+
+    fn slack_message(&self, alert: &Alert) -> Value {
+        json!({
+            "text": format!(
+                "{} [{}] {}: {}",
+                alert.severity.slack_emoji(),
+                self.deployment,
+                alert.severity.as_str().to_uppercase(),
+                alert.summary
+            ),
+        })
+    }
+
+    /// Events API v2 trigger
+    fn pagerduty_trigger(&self, routing_key: &str, alert: &Alert) -> Value {
+        json!({
+            "routing_key": routing_key,
+            "event_action": "trigger",
+            "dedup_key": self.dedup_key(&alert.dedup_key),
+            "payload": {
+                "summary": format!("[{}] {}", self.deployment, alert.summary),
+                "source": self.deployment,
+                "severity": alert.severity,
+                "class": alert.class,
+                "custom_details": alert.details,
+            },
+        })
+    }
+
+    async fn post(&self, target: &str, url: &str, body: &Value) {
+        match self.http.post(url).json(body).send().await {
+            Ok(response) if !response.status().is_success() => {
+                tracing::warn!(status = %response.status(), "{} alert refused", target);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("{} alert failed: {}", target, e),
+        }
+    }
+}
+
+/// First time for a key, or the last send is older than `repeat`
+fn should_send(last: Option<Instant>, now: Instant, repeat: Duration) -> bool {
+    last.is_none_or(|at| now.duration_since(at) >= repeat)
+}
+
+fn installed() -> Option<OperatorAlerts> {
+    INSTALLED.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Log an alert and send it in the background, if alerting is installed
+pub fn raise(alert: Alert) {
+    tracing::warn!(key = %alert.dedup_key, severity = alert.severity.as_str(), "Operator alert: {}", alert.summary);
+    let (Some(alerts), Ok(runtime)) = (installed(), tokio::runtime::Handle::try_current()) else {
+        return;
+    };
+    runtime.spawn(async move { alerts.send(&alert).await });
+}
+
+/// Resolve an alert in the background once its condition has cleared
+pub fn resolve(dedup_key: String) {
+    let (Some(alerts), Ok(runtime)) = (installed(), tokio::runtime::Handle::try_current()) else {
+        return;
+    };
+    runtime.spawn(async move { alerts.clear(&dedup_key).await });
+}
+
+/// Watches the worker lanes and the SEND queue for work piling up
+pub struct BacklogWatch {
+    workers: WorkerPool,
+    send_jobs: Option<SendJobRepository>,
+    limit: u64,
+    poll: Duration,
+}
+
+impl BacklogWatch {
+    /// None when `ALERT_QUEUE_BACKLOG` is 0
+    pub fn from_config(config: &OperatorAlertConfig, workers: WorkerPool) -> Option<Self> {
+        (config.queue_backlog > 0).then(|| Self {
+            workers,
+            send_jobs: None,
+            limit: config.queue_backlog,
+            poll: Duration::from_secs(config.queue_poll_secs.max(1)),
+        })
+    }
+
+    /// Also alert on SENDs that are due but unclaimed
+    pub fn set_send_jobs(&mut self, send_jobs: SendJobRepository) {
+        self.send_jobs = Some(send_jobs);
+    }
+
+    pub fn start(self) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.poll);
+            loop {
+                ticker.tick().await;
+                self.check().await;
+            }
+        });
+    }
+
+    async fn check(&self) {
+        let mut queues: Vec<(String, u64)> = self
+            .workers
+            .metrics()
+            .into_iter()
+            .map(|lane| (format!("workers.{}", lane.class), lane.queued as u64))
+            .collect();
+        if let Some(ref send_jobs) = self.send_jobs {
+            match send_jobs.summary().await {
+                Ok(summary) => queues.push(("send_jobs".to_string(), summary.due.max(0) as u64)),
+                Err(e) => tracing::warn!("SEND queue backlog check failed: {}", e),
+            }
+        }
+
+        for (queue, waiting) in queues {
+            if waiting >= self.limit {
+                raise(Alert::queue_backlog(&queue, waiting, self.limit));
+            } else {
+                resolve(Alert::queue_backlog_key(&queue));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alerts() -> OperatorAlerts {
+        OperatorAlerts::from_config(&OperatorAlertConfig {
+            deployment: "prod-eu".to_string(),
+            slack_webhooks: "https://hooks.slack.example/T1".into(),
+            slack_min_severity: "warning".to_string(),
+            pagerduty_routing_key: "R0UT1NG".into(),
+            pagerduty_min_severity: "error".to_string(),
+            pagerduty_url: "https://events.pagerduty.example/v2/enqueue".to_string(),
+            repeat_minutes: 60,
+            queue_backlog: 100,
+            queue_poll_secs: 60,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_severity_order_and_parse() {
+        assert!(Severity::Critical > Severity::Error && Severity::Warning > Severity::Info);
+        assert_eq!(Severity::parse(" Critical "), Some(Severity::Critical));
+        assert_eq!(Severity::parse("page"), None);
+        assert_eq!(alerts().destinations(), ["slack x1 (warning+)", "pagerduty (error+)"]);
+    }
+
+    #[test]
+    fn test_pagerduty_trigger() {
+        let alert = Alert::circuit_open(Chain::EthereumSepolia);
+        let event = alerts().pagerduty_trigger("R0UT1NG", &alert);
+        assert_eq!(event["event_action"], "trigger");
+        assert_eq!(event["dedup_key"], format!("prod-eu:circuit_open:{}", Chain::EthereumSepolia.short_code()));
+        assert_eq!(event["payload"]["severity"], "error");
+        assert_eq!(event["payload"]["source"], "prod-eu");
+        assert_eq!(event["payload"]["class"], "circuit_open");
+    }
+
+    #[test]
+    fn test_slack_message() {
+        let alert = Alert::queue_backlog("workers.on-chain", 120, 100);
+        assert_eq!(
+            alerts().slack_message(&alert)["text"],
+            ":warning: [prod-eu] WARNING: workers.on-chain queue backed up: 120 waiting (alert at 100)"
+        );
+    }
+
+    #[test]
+    fn test_name_sale_unsettled() {
+        let alert = Alert::name_sale_unsettled("l-1", "alice.ttcip.eth", Money::usdc(2_500_000), "pool timed out");
+        assert_eq!(alert.dedup_key, "name_sale_unsettled:l-1");
+        assert_eq!(alert.summary, "alice.ttcip.eth moved to its buyer but sale l-1 wasn't settled; 2.5 USDC left in escrow: pool timed out");
+        assert_eq!((alert.details["price"].as_f64(), alert.details["token"].as_str()), (Some(2.5), Some("USDC")));
+    }
+
+    #[test]
+    fn test_should_send() {
+        let now = Instant::now();
+        let repeat = Duration::from_secs(3600);
+        assert!(should_send(None, now, repeat));
+        assert!(!should_send(Some(now), now + Duration::from_secs(60), repeat));
+        assert!(should_send(Some(now), now + repeat, repeat));
+    }
+}
@@ -0,0 +1,166 @@
+//! Encrypted backup file format
+//!
+//! `TTCBK1\n`, then the dump in 1 MiB chunks, each stored as
+//! `nonce (12) | ciphertext length (u32 BE) | AES-256-GCM ciphertext`. Every
+//! chunk is authenticated together with the backup's name, its index and
+//! whether it is the last one, so chunks can't be reordered, swapped
+//! between backups, or cut off the end without `open` failing.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use rand::RngCore;
+
+const MAGIC: &[u8] = b"TTCBK1\n";
+/// Plaintext bytes per chunk
+pub const CHUNK_SIZE: usize = 1 << 20;
+/// GCM tag appended to each chunk
+const TAG_LEN: usize = 16;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ArchiveError {
+    #[error("Not a TextChain backup")]
+    BadMagic,
+    #[error("Backup is truncated")]
+    Truncated,
+    #[error("Chunk {0} failed authentication (wrong key, wrong name or tampered)")]
+    Authentication(u64),
+}
+
+fn aad(name: &str, index: u64, last: bool) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(name.len() + 32);
+    aad.extend_from_slice(b"textchain/backup/v1\0");
+    aad.extend_from_slice(name.as_bytes());
+    aad.push(0);
+    aad.extend_from_slice(&index.to_be_bytes());
+    aad.push(last as u8);
+    aad
+}
+
+/// Encrypts a dump as it is read, one chunk at a time
+pub struct Sealer {
+    cipher: Aes256Gcm,
+    name: String,
+    out: Vec<u8>,
+    pending: Vec<u8>,
+    index: u64,
+}
+
+impl Sealer {
+    pub fn new(key: &[u8; 32], name: &str) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(key.into()),
+            name: name.to_string(),
+            out: MAGIC.to_vec(),
+            pending: Vec::with_capacity(CHUNK_SIZE),
+            index: 0,
+        }
+    }
+
+    pub fn write(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = (CHUNK_SIZE - self.pending.len()).min(data.len());
+            self.pending.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            // A full chunk is only sealed once more data shows it isn't the last
+            if self.pending.len() == CHUNK_SIZE && !data.is_empty() {
+                self.seal_pending(false);
+            }
+        }
+    }
+
+    fn seal_pending(&mut self, last: bool) {
+        let mut nonce = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let aad = aad(&self.name, self.index, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: &self.pending, aad: &aad })
+            .expect("AES-GCM encrypts any chunk under 64 GiB");
+        self.out.extend_from_slice(&nonce);
+        self.out.extend_from_slice(&(ciphertext.len() as u32).to_be_bytes());
+        self.out.extend_from_slice(&ciphertext);
+        self.pending.clear();
+        self.index += 1;
+    }
+
+    /// Seal what is left as the last chunk; returns the file and its chunk count
+    pub fn finish(mut self) -> (Vec<u8>, u64) {
+        self.seal_pending(true);
+        (self.out, self.index)
+    }
+}
+
+/// Decrypt and authenticate a whole backup file
+pub fn open(key: &[u8; 32], name: &str, data: &[u8]) -> Result<Vec<u8>, ArchiveError> {
+    let mut rest = data.strip_prefix(MAGIC).ok_or(ArchiveError::BadMagic)?;
+    let cipher = Aes256Gcm::new(key.into());
+    let mut plain = Vec::with_capacity(data.len());
+    let mut index = 0u64;
+    loop {
+        if rest.len() < 16 {
+            return Err(ArchiveError::Truncated);
+        }
+        let (nonce, after) = rest.split_at(12);
+        let len = u32::from_be_bytes([after[0], after[1], after[2], after[3]]) as usize;
+        let after = &after[4..];
+        if len < TAG_LEN || after.len() < len {
+            return Err(ArchiveError::Truncated);
+        }
+        let (ciphertext, next) = after.split_at(len);
+        let last = next.is_empty();
+        let chunk = cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad(name, index, last) })
+            .map_err(|_| ArchiveError::Authentication(index))?;
+        plain.extend_from_slice(&chunk);
+        if last {
+            return Ok(plain);
+        }
+        rest = next;
+        index += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    fn seal(name: &str, data: &[u8], piece: usize) -> (Vec<u8>, u64) {
+        let mut sealer = Sealer::new(&KEY, name);
+        for part in data.chunks(piece.max(1)) {
+            sealer.write(part);
+        }
+        sealer.finish()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        for size in [0, 10, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE - 5] {
+            let data: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            let (file, chunks) = seal("20261016T020000Z", &data, 70_000);
+            assert_eq!(chunks as usize, size.div_ceil(CHUNK_SIZE).max(1), "size {}", size);
+            assert_eq!(open(&KEY, "20261016T020000Z", &file).unwrap(), data, "size {}", size);
+        }
+    }
+
+    #[test]
+    fn test_tampering_is_detected() {
+        let data = vec![1u8; 2 * CHUNK_SIZE + 100];
+        let (file, _) = seal("a", &data, CHUNK_SIZE);
+
+        assert_eq!(open(&[8; 32], "a", &file), Err(ArchiveError::Authentication(0)));
+        assert_eq!(open(&KEY, "b", &file), Err(ArchiveError::Authentication(0)));
+        assert_eq!(open(&KEY, "a", &file[1..]), Err(ArchiveError::BadMagic));
+
+        let mut flipped = file.clone();
+        let at = flipped.len() - 20;
+        flipped[at] ^= 1;
+        assert_eq!(open(&KEY, "a", &flipped), Err(ArchiveError::Authentication(2)));
+
+        // Dropping the last chunk leaves a chunk that wasn't sealed as last
+        let first_two = MAGIC.len() + 2 * (12 + 4 + CHUNK_SIZE + TAG_LEN);
+        assert_eq!(open(&KEY, "a", &file[..first_two]), Err(ArchiveError::Authentication(1)));
+        assert_eq!(open(&KEY, "a", &file[..file.len() - 1]), Err(ArchiveError::Truncated));
+    }
+}
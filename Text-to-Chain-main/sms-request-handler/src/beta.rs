@@ -0,0 +1,90 @@
+//! Beta launch mode
+//!
+//! With `BETA_MODE` on, only allowlisted numbers can create a wallet. A
+//! number gets on the allowlist through an admin, an `INVITE <code>` text or
+//! its turn on the waitlist: everyone else who tries to sign up is put on
+//! `beta_waitlist`. While `BETA_CAPACITY` is set, the longest-waiting
+//! numbers are let in as seats free up and told by SMS.
+
+use crate::config::BetaConfig;
+use crate::db::BetaRepository;
+use crate::sms::SmsGateway;
+
+/// Sent to a waitlisted number once it may sign up
+pub const ADMITTED_MESSAGE: &str = "You're in! TextChain is ready for you.\nReply START to create your wallet.";
+
+/// Whether a number may complete onboarding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Allowed,
+    /// Position among numbers still waiting (1 = next)
+    Waitlisted(i64),
+}
+
+pub fn waitlist_reply(position: i64) -> String {
+    format!(
+        "TextChain is in private beta.\nYou're #{} on the waitlist - we'll text you when it's your turn.\nHave an invite? Reply INVITE <code>",
+        position
+    )
+}
+
+#[derive(Clone)]
+pub struct BetaAccess {
+    repo: BetaRepository,
+    /// Total seats (wallets + admitted numbers); 0 = no automatic release
+    capacity: i64,
+}
+
+impl BetaAccess {
+    /// None when `BETA_MODE` is off
+    pub fn from_config(config: &BetaConfig, repo: BetaRepository) -> Option<Self> {
+        config.enabled.then(|| Self { repo, capacity: config.capacity })
+    }
+
+    pub fn repo(&self) -> &BetaRepository {
+        &self.repo
+    }
+
+    pub fn capacity(&self) -> i64 {
+        self.capacity
+    }
+
+    /// Allowlisted numbers pass; anyone else joins (or stays on) the waitlist
+    pub async fn admission(&self, phone: &str) -> Result<Admission, sqlx::Error> {
+        if self.repo.is_allowlisted(phone).await? {
+            return Ok(Admission::Allowed);
+        }
+        let position = self.repo.join_waitlist(phone).await?;
+        tracing::info!(phone = %phone, position, "Number waitlisted");
+        Ok(Admission::Waitlisted(position))
+    }
+
+    /// Seats left under `BETA_CAPACITY` (0 without a capacity)
+    pub async fn free_seats(&self) -> Result<i64, sqlx::Error> {
+        if self.capacity <= 0 {
+            return Ok(0);
+        }
+        Ok((self.capacity - self.repo.seats_taken().await?).max(0))
+    }
+
+    /// Let in as many waiting numbers as there are free seats; returns how many
+    pub async fn release(&self, twilio: &SmsGateway) -> Result<usize, sqlx::Error> {
+        let free = self.free_seats().await?;
+        if free <= 0 {
+            return Ok(0);
+        }
+
+        let admitted = self.repo.admit_next(free).await?;
+        for phone in &admitted {
+            notify_admitted(twilio, phone).await;
+        }
+        Ok(admitted.len())
+    }
+}
+
+/// Tell a number it may now sign up; waits for quiet hours to end
+pub async fn notify_admitted(twilio: &SmsGateway, phone: &str) {
+    if let Err(e) = twilio.send_notification(phone, ADMITTED_MESSAGE).await {
+        tracing::warn!(phone = %phone, "Failed to notify admitted number: {}", e);
+    }
+}
@@ -0,0 +1,80 @@
+//! ttc-admin: operational commands for a running server, over its local
+//! admin socket (see `local_admin` in the server)
+//!
+//! ```text
+//! ttc-admin [--socket <path>] <command>
+//!
+//!   ping                 check the server is up
+//!   flush-caches         clear the ENS cache, re-read flags, tokens, reserved names
+//!   reconcile            run deposit confirmations and ENS verification now
+//!   jobs                 worker lanes, SEND queue and outbox
+//!   resend <id>          send an outbox message now
+//!   backup               take an encrypted database backup now
+//!   backups              list backups in the bucket
+//!   verify-backup <name> download, decrypt and check a backup (or `latest`)
+//! ```
+//!
+//! The socket is `--socket`, else `LOCAL_ADMIN_SOCKET`, else
+//! `/run/textchain/admin.sock`.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::process::ExitCode;
+use std::time::Duration;
+
+const DEFAULT_SOCKET: &str = "/run/textchain/admin.sock";
+const USAGE: &str = "Usage: ttc-admin [--socket <path>] <ping|flush-caches|reconcile|jobs|resend <id>|backup|backups|verify-backup <name>>";
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let mut socket = std::env::var("LOCAL_ADMIN_SOCKET").ok().filter(|s| !s.trim().is_empty());
+    if let Some(at) = args.iter().position(|arg| arg == "--socket") {
+        if at + 1 >= args.len() {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+        socket = Some(args.remove(at + 1));
+        args.remove(at);
+    }
+    if args.is_empty() || args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    }
+    let socket = socket.unwrap_or_else(|| DEFAULT_SOCKET.to_string());
+
+    let reply = match request(&socket, &args.join(" ")) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("ttc-admin: {}: {}", socket, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let reply: serde_json::Value = match serde_json::from_str(&reply) {
+        Ok(reply) => reply,
+        Err(_) => {
+            eprintln!("ttc-admin: unexpected reply: {}", reply.trim());
+            return ExitCode::FAILURE;
+        }
+    };
+    if reply["ok"].as_bool() == Some(true) {
+        match &reply["result"] {
+            serde_json::Value::String(text) => println!("{}", text),
+            result => println!("{}", serde_json::to_string_pretty(result).unwrap_or_default()),
+        }
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("ttc-admin: {}", reply["error"].as_str().unwrap_or("failed"));
+        ExitCode::FAILURE
+    }
+}
+
+/// Send one command line and read the one-line reply
+fn request(socket: &str, command: &str) -> std::io::Result<String> {
+    let mut stream = UnixStream::connect(socket)?;
+    // reconcile waits for a deposit poll and an ENS batch, backup for a whole dump
+    stream.set_read_timeout(Some(Duration::from_secs(3600)))?;
+    stream.write_all(format!("{}\n", command).as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    Ok(reply)
+}
@@ -0,0 +1,146 @@
+//! Admin SMS broadcasts
+//!
+//! `POST /admin/broadcast` renders a template for every user in a segment
+//! and hands the messages to one process-wide queue. The queue sends at
+//! `BROADCAST_RATE_PER_SEC` as notifications, so quiet hours and opt-outs
+//! apply, and counts each outcome on the broadcast row.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, watch};
+use uuid::Uuid;
+
+use crate::db::{BroadcastOutcome, BroadcastRepository, BroadcastSegment, SegmentMember};
+use crate::sms::gateway::SmsError;
+use crate::sms::{Delivery, SmsGateway};
+
+/// Broadcasts that can wait behind the one being sent
+const QUEUE_DEPTH: usize = 16;
+/// Longest template accepted (four SMS segments)
+pub const MAX_TEMPLATE_LEN: usize = 640;
+
+#[derive(Debug, thiserror::Error)]
+pub enum BroadcastError {
+    #[error("Broadcast queue is full, try again later")]
+    QueueFull,
+}
+
+/// One rendered message
+#[derive(Debug, Clone)]
+pub struct BroadcastMessage {
+    pub phone: String,
+    pub body: String,
+}
+
+struct BroadcastJob {
+    id: Uuid,
+    messages: Vec<BroadcastMessage>,
+}
+
+/// Rate-limited sender shared by all broadcasts
+#[derive(Clone)]
+pub struct BroadcastQueue {
+    jobs: mpsc::Sender<BroadcastJob>,
+    /// Messages per second, picked up by the sender before its next message
+    rate: Arc<watch::Sender<f64>>,
+}
+
+impl BroadcastQueue {
+    /// Start the sender task
+    pub fn start(repo: BroadcastRepository, twilio: SmsGateway, rate_per_sec: f64) -> Self {
+        let (jobs, mut rx) = mpsc::channel::<BroadcastJob>(QUEUE_DEPTH);
+        let (rate, mut rate_rx) = watch::channel(rate_per_sec);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(send_period(rate_per_sec));
+            while let Some(job) = rx.recv().await {
+                if let Err(e) = repo.mark_sending(job.id).await {
+                    tracing::warn!(id = %job.id, "Failed to mark broadcast sending: {}", e);
+                }
+                for message in &job.messages {
+                    if rate_rx.has_changed().unwrap_or(false) {
+                        let rate_per_sec = *rate_rx.borrow_and_update();
+                        ticker = tokio::time::interval(send_period(rate_per_sec));
+                        tracing::info!(rate_per_sec, "Broadcast send rate changed");
+                    }
+                    ticker.tick().await;
+                    let outcome = match twilio.send_notification(&message.phone, &message.body).await {
+                        Ok(Delivery::Sent(_)) => BroadcastOutcome::Sent,
+                        Ok(Delivery::Deferred(_) | Delivery::HeldForOutage) => BroadcastOutcome::Deferred,
+                        Err(SmsError::OptedOut) => BroadcastOutcome::Excluded,
+                        Err(e) => {
+                            tracing::warn!(id = %job.id, to = %message.phone, "Broadcast message not sent: {}", e);
+                            BroadcastOutcome::Failed
+                        }
+                    };
+                    if let Err(e) = repo.record(job.id, outcome).await {
+                        tracing::warn!(id = %job.id, "Failed to record broadcast outcome: {}", e);
+                    }
+                }
+                match repo.finish(job.id).await {
+                    Ok(()) => tracing::info!(id = %job.id, messages = job.messages.len(), "Broadcast finished"),
+                    Err(e) => tracing::warn!(id = %job.id, "Failed to finish broadcast: {}", e),
+                }
+            }
+        });
+        Self { jobs, rate: Arc::new(rate) }
+    }
+
+    /// Change the send rate, including for a broadcast already underway
+    pub fn set_rate(&self, rate_per_sec: f64) {
+        self.rate.send_replace(rate_per_sec);
+    }
+
+    pub fn enqueue(&self, id: Uuid, messages: Vec<BroadcastMessage>) -> Result<(), BroadcastError> {
+        self.jobs
+            .try_send(BroadcastJob { id, messages })
+            .map_err(|_| BroadcastError::QueueFull)
+    }
+}
+
+fn send_period(rate_per_sec: f64) -> Duration {
+    Duration::from_secs_f64(1.0 / rate_per_sec.max(0.01))
+}
+
+/// Fill `{name}` (ENS name, else "there") and `{balance}` (cash balance)
+pub fn render(template: &str, member: &SegmentMember) -> String {
+    let name = member.ens_name.as_deref().unwrap_or("there");
+    template
+        .replace("{name}", name)
+        .replace("{balance}", &member.balance.format(2))
+}
+
+/// Whether `phone` has one of the calling codes (all match when empty)
+pub fn matches_country(phone: &str, country_codes: &[String]) -> bool {
+    let digits = phone.trim_start_matches('+');
+    country_codes.is_empty()
+        || country_codes
+            .iter()
+            .map(|code| code.trim().trim_start_matches('+'))
+            .any(|code| !code.is_empty() && digits.starts_with(code))
+}
+
+/// Segment members in the selected countries
+pub fn in_countries(members: Vec<SegmentMember>, segment: &BroadcastSegment) -> Vec<SegmentMember> {
+    members
+        .into_iter()
+        .filter(|member| matches_country(&member.phone, &segment.country_codes))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    #[test]
+    fn test_render_and_select() {
+        let member = SegmentMember { phone: "+254700000001".to_string(), ens_name: None, balance: Money::usdc(12_500_000) };
+        assert_eq!(render("Hi {name}, you have {balance} USDC", &member), "Hi there, you have 12.50 USDC");
+
+        assert!(matches_country(&member.phone, &[]));
+        assert!(matches_country(&member.phone, &["+254".to_string()]));
+        assert!(matches_country(&member.phone, &["234".to_string(), "254".to_string()]));
+        assert!(!matches_country(&member.phone, &["1".to_string()]));
+    }
+}
@@ -0,0 +1,182 @@
+//! Agent cash-in / cash-out over SMS
+//!
+//! A registered agent opens a request with `CASHIN <customer-phone> <amount>`
+//! or `CASHOUT <customer-phone> <amount>`. Agent and customer each receive
+//! their own code and reply `CONFIRM <code>`; the ledger transfer settles
+//! once both codes are in.
+
+use super::parser::CommandProcessor;
+use crate::db::{micro_to_f64, f64_to_micro, user_account, AgentError, CashKind, CashRequest, ConfirmOutcome, LedgerError, CASH_REQUEST_TTL_MINUTES};
+use crate::sms::Delivery;
+
+impl CommandProcessor {
+    /// Open a cash-in or cash-out request (agents only)
+    pub(super) async fn agent_cash_response(&self, from: &str, kind: CashKind, customer: &str, amount: f64) -> String {
+        let (Some(agents), Some(user_repo)) = (&self.agent_repo, &self.user_repo) else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let agent = match agents.find_active_by_phone(from).await {
+            Ok(Some(agent)) => agent,
+            Ok(None) => return "Only registered agents can do this.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let amount_micro = f64_to_micro(amount);
+        if amount_micro <= 0 {
+            return "Amount must be positive.".to_string();
+        }
+        if customer == from {
+            return "Agents can't cash in/out for themselves.".to_string();
+        }
+
+        let customer_user = match user_repo.find_by_phone(customer).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return format!("{} has no wallet.\nAsk them to text JOIN", customer),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        // Early balance check; the ledger re-checks when the request settles
+        let (source_balance, short_msg) = match kind {
+            CashKind::CashIn => (agents.float_balance(agent.id).await, "Float too low."),
+            CashKind::CashOut => match self.ledger_repo {
+                Some(ref ledger) => (ledger.balance(&user_account(customer_user.id)).await, "Customer balance too low."),
+                None => return "DB offline. Try later.".to_string(),
+            },
+        };
+        match source_balance {
+            Ok(balance) if balance < amount_micro => return short_msg.to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+            Ok(_) => {}
+        }
+
+        let request = match agents.create_request(&agent, customer_user.id, customer, kind, amount_micro).await {
+            Ok(request) => request,
+            Err(e) => {
+                tracing::error!("Failed to create cash request: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let customer_message = match kind {
+            CashKind::CashIn => format!(
+                "Agent {} will add {:.2} to your wallet for cash.\nOnly after paying, reply:\nCONFIRM {}",
+                agent.name, amount, request.customer_code
+            ),
+            CashKind::CashOut => format!(
+                "Agent {} will pay you {:.2} cash from your wallet.\nOnly after receiving cash, reply:\nCONFIRM {}",
+                agent.name, amount, request.customer_code
+            ),
+        };
+        self.notify(customer, &self.greeted(customer, customer_message).await).await;
+
+        let action = match kind {
+            CashKind::CashIn => "received the cash",
+            CashKind::CashOut => "handed over the cash",
+        };
+        format!(
+            "{} {:.2} for {} started.\nOnce you have {}, reply:\nCONFIRM {}\n\nExpires in {} min.",
+            if kind == CashKind::CashIn { "Cash-in" } else { "Cash-out" },
+            amount,
+            customer,
+            action,
+            request.agent_code,
+            CASH_REQUEST_TTL_MINUTES
+        )
+    }
+
+    /// CONFIRM <code> from either party
+    pub(super) async fn confirm_response(&self, from: &str, code: &str) -> String {
+        let Some(ref agents) = self.agent_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        match agents.confirm(from, code).await {
+            Ok(ConfirmOutcome::Waiting(request)) if from == request.agent_phone => {
+                format!("Confirmed. Waiting for {} to confirm.", request.customer_phone)
+            }
+            Ok(ConfirmOutcome::Waiting(_)) => "Confirmed. Waiting for the agent to confirm.".to_string(),
+            Ok(ConfirmOutcome::Settled(request)) => {
+                let (agent_msg, customer_msg) = settled_messages(&request);
+                let (other, mine, theirs) = if from == request.agent_phone {
+                    (&request.customer_phone, agent_msg, customer_msg)
+                } else {
+                    (&request.agent_phone, customer_msg, agent_msg)
+                };
+                self.notify_update(other, &theirs).await;
+                if let (CashKind::CashOut, Some(ref ledger)) = (request.kind(), &self.ledger_repo) {
+                    match ledger.balance(&user_account(request.customer_id)).await {
+                        Ok(balance) => self.check_low_balance(request.customer_id, &request.customer_phone, balance, request.amount).await,
+                        Err(e) => tracing::warn!(request = %request.id, "Balance after cash-out unavailable: {}", e),
+                    }
+                }
+                mine
+            }
+            Err(AgentError::NotFound) => "No pending request for that code.".to_string(),
+            Err(AgentError::Expired) => "Request expired. Ask the agent to start again.".to_string(),
+            Err(AgentError::Ledger(LedgerError::InsufficientFunds)) => {
+                "Failed: insufficient balance. No money was moved.".to_string()
+            }
+            Err(e) => {
+                tracing::error!("Cash request confirmation failed: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    /// FLOAT - agent's current float
+    pub(super) async fn float_response(&self, from: &str) -> String {
+        let Some(ref agents) = self.agent_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let agent = match agents.find_active_by_phone(from).await {
+            Ok(Some(agent)) => agent,
+            Ok(None) => return "Only registered agents have a float.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        match agents.float_balance(agent.id).await {
+            Ok(balance) => format!("Agent float: {:.2} USDC", micro_to_f64(balance)),
+            Err(_) => "Error. Try later.".to_string(),
+        }
+    }
+
+    /// Send an SMS to someone other than the sender (no-op without a notifier)
+    pub(super) async fn notify(&self, to: &str, body: &str) {
+        let Some(ref notifier) = self.notifier else {
+            return;
+        };
+        if let Err(e) = notifier.send_sms(to, body).await {
+            tracing::warn!(to = %to, "Notification not sent: {}", e);
+        }
+    }
+
+    /// Like `notify`, but held until morning if it is night for the recipient
+    pub(super) async fn notify_receipt(&self, to: &str, body: &str) {
+        let Some(ref notifier) = self.notifier else {
+            return;
+        };
+        match notifier.send_notification(to, body).await {
+            Ok(Delivery::Sent(result)) => tracing::debug!(to = %to, sid = %result.message_sid, "Receipt sent"),
+            Ok(Delivery::Deferred(until)) => tracing::debug!(to = %to, %until, "Receipt deferred for quiet hours"),
+            Ok(Delivery::HeldForOutage) => tracing::debug!(to = %to, "Receipt held for carrier outage"),
+            Err(e) => tracing::warn!(to = %to, "Notification not sent: {}", e),
+        }
+    }
+}
+
+/// Completion messages for (agent, customer)
+fn settled_messages(request: &CashRequest) -> (String, String) {
+    let amount = micro_to_f64(request.amount);
+    match request.kind() {
+        CashKind::CashIn => (
+            format!("Cash-in done: {:.2} sent to {}.", amount, request.customer_phone),
+            format!("Cash-in done: {:.2} added to your wallet.\nReply BALANCE to check.", amount),
+        ),
+        CashKind::CashOut => (
+            format!("Cash-out done: {:.2} added to your float.", amount),
+            format!("Cash-out done: {:.2} paid out from your wallet.", amount),
+        ),
+    }
+}
@@ -0,0 +1,130 @@
+//! Balance alerts: ALERT BELOW <amount> and ALERT DEPOSIT
+//!
+//! A low-balance warning goes out when a debit takes the custodial cash
+//! balance from at or over the user's threshold to under it, so a run of
+//! small sends below the line warns once. Deposit notifications are on
+//! until the user turns them off; reversals are always sent.
+
+use uuid::Uuid;
+
+use super::parser::CommandProcessor;
+use crate::db::AlertSettings;
+use crate::money::{Currency, Money};
+
+/// What ALERT changes (None on the command = show current settings)
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlertSetting {
+    /// ALERT BELOW <amount> | ALERT BELOW OFF (None)
+    Below(Option<Money>),
+    /// ALERT DEPOSIT [ON|OFF] (None = toggle)
+    Deposit(Option<bool>),
+}
+
+pub(super) const ALERT_USAGE: &str = "Usage: ALERT BELOW <amount>, ALERT BELOW OFF or ALERT DEPOSIT [ON|OFF]";
+
+/// ALERT arguments after the keyword
+pub(super) fn parse_alert(args: &[&str]) -> Result<Option<AlertSetting>, &'static str> {
+    let switch = |arg: Option<&&str>| match arg.copied() {
+        None => Ok(None),
+        Some("ON") => Ok(Some(true)),
+        Some("OFF") => Ok(Some(false)),
+        Some(_) => Err(ALERT_USAGE),
+    };
+    match args {
+        [] => Ok(None),
+        ["BELOW", "OFF"] => Ok(Some(AlertSetting::Below(None))),
+        ["BELOW", amount] | ["BELOW", amount, "USDC"] => match Money::parse(amount, Currency::USDC) {
+            Ok(amount) if amount.is_positive() => Ok(Some(AlertSetting::Below(Some(amount)))),
+            _ => Err("Invalid amount"),
+        },
+        ["DEPOSIT" | "DEPOSITS", rest @ ..] if rest.len() <= 1 => switch(rest.first()).map(|on| Some(AlertSetting::Deposit(on))),
+        _ => Err(ALERT_USAGE),
+    }
+}
+
+fn describe(settings: &AlertSettings) -> String {
+    let below = match settings.below {
+        Some(threshold) => format!("Low balance alert under {} USDC.", Money::usdc(threshold).format(2)),
+        None => "No low balance alert.".to_string(),
+    };
+    let deposits = if settings.deposit_notifications { "on" } else { "off" };
+    format!("{}\nDeposit notifications {}.", below, deposits)
+}
+
+impl CommandProcessor {
+    /// ALERT [BELOW <amount>|BELOW OFF|DEPOSIT [ON|OFF]]
+    pub(super) async fn alert_response(&self, from: &str, setting: Option<AlertSetting>) -> String {
+        let (Some(alerts), Some(user_repo)) = (&self.alerts, &self.user_repo) else {
+            return "Alerts are not available.".to_string();
+        };
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let updated = match setting {
+            None => alerts.get(user.id).await,
+            Some(AlertSetting::Below(amount)) => alerts.set_below(user.id, amount.map(|amount| amount.micros())).await,
+            Some(AlertSetting::Deposit(Some(on))) => alerts.set_deposit_notifications(user.id, on).await,
+            Some(AlertSetting::Deposit(None)) => match alerts.get(user.id).await {
+                Ok(current) => alerts.set_deposit_notifications(user.id, !current.deposit_notifications).await,
+                Err(e) => Err(e),
+            },
+        };
+        match updated {
+            Ok(settings) => describe(&settings),
+            Err(e) => {
+                tracing::error!(user = %user.id, "Alert settings failed: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    /// Warn the user if a debit of `debited` took their cash balance
+    /// (`balance` after it) under their ALERT BELOW threshold
+    pub(super) async fn check_low_balance(&self, user_id: Uuid, phone: &str, balance: i64, debited: i64) {
+        let Some(ref alerts) = self.alerts else {
+            return;
+        };
+        let settings = match alerts.get(user_id).await {
+            Ok(settings) => settings,
+            Err(e) => {
+                tracing::warn!(user = %user_id, "Alert settings lookup failed: {}", e);
+                return;
+            }
+        };
+        if !settings.crossed_below(balance + debited, balance) {
+            return;
+        }
+        tracing::info!(user = %user_id, balance, "Low balance alert");
+        self.notify_receipt(
+            phone,
+            &format!(
+                "Low balance: {} USDC, under your {} alert.\nALERT BELOW OFF to stop these.",
+                Money::usdc(balance).format(2),
+                Money::usdc(settings.below.unwrap_or_default()).format(2)
+            ),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_alert() {
+        assert_eq!(parse_alert(&[]), Ok(None));
+        assert_eq!(parse_alert(&["BELOW", "5"]), Ok(Some(AlertSetting::Below(Some(Money::usdc(5_000_000))))));
+        assert_eq!(parse_alert(&["BELOW", "2.5", "USDC"]), Ok(Some(AlertSetting::Below(Some(Money::usdc(2_500_000))))));
+        assert_eq!(parse_alert(&["BELOW", "OFF"]), Ok(Some(AlertSetting::Below(None))));
+        assert_eq!(parse_alert(&["DEPOSIT"]), Ok(Some(AlertSetting::Deposit(None))));
+        assert_eq!(parse_alert(&["DEPOSIT", "OFF"]), Ok(Some(AlertSetting::Deposit(Some(false)))));
+        assert_eq!(parse_alert(&["BELOW", "-1"]), Err("Invalid amount"));
+        assert_eq!(parse_alert(&["BELOW", "0.0000001"]), Err("Invalid amount"));
+        assert_eq!(parse_alert(&["BELOW"]), Err(ALERT_USAGE));
+        assert_eq!(parse_alert(&["DEPOSIT", "MAYBE"]), Err(ALERT_USAGE));
+    }
+}
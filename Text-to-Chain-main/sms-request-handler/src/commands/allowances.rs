@@ -0,0 +1,119 @@
+//! APPROVE / REVOKE / ALLOWANCES - ERC-20 allowances for power users
+//!
+//! Approvals are sent straight from the user's EOA on the TXTC chain, so the
+//! wallet needs a little ETH for gas. Replies always say who can spend what.
+
+use std::sync::Arc;
+
+use ethers::types::{Address, U256};
+
+use super::parser::CommandProcessor;
+use crate::db::User;
+use crate::sms::{NumberLocale, SmsDisplay};
+use crate::wallet::allowance::{approval_warning, read_allowances, send_approve, AllowanceError, ApprovableToken, Spender};
+use crate::wallet::payment_uri::TXTC_CHAIN;
+use crate::wallet::{create_chain_provider, ChainProvider};
+
+impl CommandProcessor {
+    /// APPROVE <token> <amount> [spender]
+    pub(super) async fn approve_response(&self, from: &str, token: &str, amount: f64, spender: Option<&str>) -> String {
+        let (token, spender) = match (ApprovableToken::from_symbol(token), Spender::parse(spender)) {
+            (Ok(token), Ok(spender)) => (token, spender),
+            (Err(e), _) | (_, Err(e)) => return allowance_error_reply(&e),
+        };
+        let units = match token.units(amount) {
+            Ok(units) => units,
+            Err(e) => return allowance_error_reply(&e),
+        };
+
+        match self.submit_approve(from, &token, &spender, units).await {
+            Ok(tx) => format!(
+                "Approving {} {} for {}.\n{}\nTx: {}",
+                amount,
+                token.symbol,
+                spender.label(),
+                approval_warning(&spender),
+                tx
+            ),
+            Err(reply) => reply,
+        }
+    }
+
+    /// REVOKE <token> [spender]
+    pub(super) async fn revoke_response(&self, from: &str, token: &str, spender: Option<&str>) -> String {
+        let (token, spender) = match (ApprovableToken::from_symbol(token), Spender::parse(spender)) {
+            (Ok(token), Ok(spender)) => (token, spender),
+            (Err(e), _) | (_, Err(e)) => return allowance_error_reply(&e),
+        };
+
+        match self.submit_approve(from, &token, &spender, U256::zero()).await {
+            Ok(tx) => format!("Revoking {} access for {}.\nTx: {}", token.symbol, spender.label(), tx),
+            Err(reply) => reply,
+        }
+    }
+
+    /// ALLOWANCES - non-zero approvals for the known spenders
+    pub(super) async fn allowances_response(&self, from: &str) -> String {
+        let user = match self.allowance_user(from).await {
+            Ok(user) => user,
+            Err(reply) => return reply,
+        };
+        let Ok(owner) = user.wallet_address.parse::<Address>() else {
+            return "Error. Try later.".to_string();
+        };
+
+        match read_allowances(self.txtc_chain_provider(), owner).await {
+            Ok(entries) if entries.is_empty() => "No token approvals.".to_string(),
+            Ok(entries) => {
+                let lines: Vec<String> = entries.iter().map(|e| e.sms_string(NumberLocale::for_phone(from))).collect();
+                format!("Approvals:\n{}\n\nReply REVOKE <token> to remove.", lines.join("\n"))
+            }
+            Err(e) => {
+                tracing::error!("Failed to read allowances for {}: {}", from, e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    pub(super) async fn allowance_user(&self, from: &str) -> Result<User, String> {
+        let Some(ref repo) = self.user_repo else {
+            return Err("DB offline. Try later.".to_string());
+        };
+        match repo.find_by_phone(from).await {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err("No wallet. Reply JOIN first.".to_string()),
+            Err(_) => Err("Error. Try later.".to_string()),
+        }
+    }
+
+    /// Sign and send `approve` from the sender's wallet; Err is the SMS reply
+    async fn submit_approve(&self, from: &str, token: &ApprovableToken, spender: &Spender, units: U256) -> Result<String, String> {
+        let user = self.allowance_user(from).await?;
+        let key = self.key_vault.open_private_key(&user.encrypted_private_key).map_err(|e| {
+            tracing::error!("Failed to unseal key for {}: {}", from, e);
+            "Error. Try later.".to_string()
+        })?;
+
+        send_approve(self.txtc_chain_provider(), TXTC_CHAIN, &key, token, spender, units)
+            .await
+            .map(|tx| format!("{:?}", tx))
+            .map_err(|e| {
+                tracing::error!("Approve from {} failed: {}", from, e);
+                allowance_error_reply(&e)
+            })
+    }
+
+    fn txtc_chain_provider(&self) -> Arc<ChainProvider> {
+        self.multi_chain.get(TXTC_CHAIN).unwrap_or_else(|| create_chain_provider(TXTC_CHAIN))
+    }
+}
+
+fn allowance_error_reply(error: &AllowanceError) -> String {
+    match error {
+        AllowanceError::UnknownToken(_) => "Token not supported. Use TXTC or USDC.".to_string(),
+        AllowanceError::InvalidSpender(_) => "Invalid spender. Use ROUTER or a 0x address.".to_string(),
+        AllowanceError::InvalidAmount => "Invalid amount".to_string(),
+        AllowanceError::NoGas => format!("Not enough {} ETH for gas. Reply DEPOSIT to fund.", TXTC_CHAIN.name()),
+        AllowanceError::Transaction(_) => "Error. Try later.".to_string(),
+    }
+}
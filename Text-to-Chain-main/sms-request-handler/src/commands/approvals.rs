@@ -0,0 +1,302 @@
+//! Second approval for large SENDs
+//!
+//! A SEND above the token's threshold is held instead of going out and the
+//! sender gets a code to `CONFIRM`. With a guardian set (`GUARDIAN +phone`)
+//! the guardian then gets their own code and the transfer goes once they
+//! confirm too. Without one, the sender's confirmation only counts after
+//! the cooling period. Guardian changes and removals wait out the same
+//! period and the old guardian is told, so a stolen phone can't simply
+//! switch the guardian off first.
+
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+
+use super::parser::CommandProcessor;
+use crate::config::TransferApprovalConfig;
+use crate::db::{ApprovalError, ApprovalOutcome, NewPendingTransfer, PendingTransfer, TransferApprovalRepository, User};
+use crate::wallet::address::{check_words_line, display_address};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransferPolicyError {
+    #[error("Invalid TRANSFER_APPROVAL_THRESHOLDS entry {0}")]
+    Threshold(String),
+}
+
+/// Which SENDs need a second approval, and the held transfers themselves
+#[derive(Clone)]
+pub struct TransferPolicy {
+    /// Upper-case token -> largest amount sent without approval
+    thresholds: HashMap<String, f64>,
+    cooling: Duration,
+    ttl: Duration,
+    repo: TransferApprovalRepository,
+}
+
+impl TransferPolicy {
+    /// None when `TRANSFER_APPROVAL_THRESHOLDS` is empty
+    pub fn from_config(config: &TransferApprovalConfig, repo: TransferApprovalRepository) -> Result<Option<Self>, TransferPolicyError> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            thresholds: parse_thresholds(&config.thresholds)?,
+            cooling: Duration::minutes(config.cooling_minutes.max(0)),
+            ttl: Duration::minutes(config.ttl_minutes.max(1)),
+            repo,
+        }))
+    }
+
+    /// Tokens with a threshold, for the startup log
+    pub fn tokens(&self) -> Vec<&str> {
+        let mut tokens: Vec<&str> = self.thresholds.keys().map(String::as_str).collect();
+        tokens.sort_unstable();
+        tokens
+    }
+
+    fn requires_approval(&self, token: &str, amount: f64) -> bool {
+        self.thresholds.get(&token.to_uppercase()).is_some_and(|limit| amount > *limit)
+    }
+}
+
+/// `USDC:100,TXTC:5000` -> token -> threshold
+pub(super) fn parse_thresholds(spec: &str) -> Result<HashMap<String, f64>, TransferPolicyError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(|entry| {
+            entry
+                .split_once(':')
+                .and_then(|(token, limit)| {
+                    let limit = limit.trim().parse::<f64>().ok().filter(|l| l.is_finite() && *l >= 0.0)?;
+                    Some((token.trim().to_uppercase(), limit))
+                })
+                .filter(|(token, _)| !token.is_empty())
+                .ok_or_else(|| TransferPolicyError::Threshold(entry.to_string()))
+        })
+        .collect()
+}
+
+impl CommandProcessor {
+    /// Hold a SEND that is over its threshold; None lets it go out now
+    pub(super) async fn hold_large_send(
+        &self,
+        sender: &User,
+        amount: f64,
+        token: &str,
+        recipient_address: &str,
+        recipient: &str,
+    ) -> Option<String> {
+        let policy = self.transfer_policy.as_ref().filter(|p| p.requires_approval(token, amount))?;
+
+        let guardian = match policy.repo.guardian(sender.id).await {
+            Ok(guardian) => guardian,
+            Err(e) => {
+                tracing::error!(user = %sender.id, "Guardian lookup failed: {}", e);
+                return Some("Error. Try later.".to_string());
+            }
+        };
+        let now = Utc::now();
+        // With a guardian there is nothing to wait for
+        let not_before = if guardian.is_some() { now } else { now + policy.cooling };
+        let held = policy
+            .repo
+            .hold(&NewPendingTransfer {
+                sender_id: sender.id,
+                sender_phone: &sender.phone,
+                token,
+                amount,
+                recipient,
+                recipient_address,
+                approver_phone: guardian.as_deref(),
+                not_before,
+                expires_at: not_before + policy.ttl,
+            })
+            .await;
+        let held = match held {
+            Ok(held) => held,
+            Err(e) => {
+                tracing::error!(user = %sender.id, "Failed to hold transfer: {}", e);
+                return Some("Error. Try later.".to_string());
+            }
+        };
+        tracing::info!(transfer = %held.id, user = %sender.id, amount, token, guardian = guardian.is_some(), "Large transfer held for approval");
+
+        let summary = format!("{} {} to {}{}", amount, token, display_address(recipient), check_words_line(recipient));
+        Some(match guardian {
+            Some(ref guardian) => format!(
+                "Large transfer: {}.\nReply CONFIRM {} to send it. Your guardian {} will then be asked to approve.",
+                summary, held.sender_code, guardian
+            ),
+            None => format!(
+                "Large transfer: {}.\nFor your safety it waits {} min. After that, reply CONFIRM {} within {} min to send it.",
+                summary,
+                policy.cooling.num_minutes(),
+                held.sender_code,
+                policy.ttl.num_minutes()
+            ),
+        })
+    }
+
+    /// CONFIRM <code> for a held transfer; None when the code isn't one
+    pub(super) async fn approve_transfer_response(&self, from: &str, code: &str) -> Option<String> {
+        let policy = self.transfer_policy.as_ref()?;
+
+        match policy.repo.confirm(from, code).await {
+            Ok(ApprovalOutcome::AwaitingApprover { transfer, first }) => {
+                let guardian = transfer.approver_phone.clone().unwrap_or_default();
+                if first {
+                    let request = format!(
+                        "{} wants to send {} {} to {}.\nIf you agree, reply CONFIRM {}. Otherwise ignore this.",
+                        transfer.sender_phone,
+                        transfer.amount,
+                        transfer.token,
+                        display_address(&transfer.recipient),
+                        transfer.approver_code.as_deref().unwrap_or_default()
+                    );
+                    self.notify(&guardian, &self.greeted(&guardian, request).await).await;
+                }
+                Some(format!("Confirmed. Waiting for {} to approve.", guardian))
+            }
+            Ok(ApprovalOutcome::TooEarly(transfer)) => {
+                let wait = (transfer.not_before - Utc::now()).num_minutes() + 1;
+                Some(format!("Too early: this transfer can be confirmed in {} min.", wait))
+            }
+            Ok(ApprovalOutcome::Approved(transfer)) => Some(self.send_approved(from, transfer).await),
+            Err(ApprovalError::NotFound) => None,
+            Err(ApprovalError::Expired) => Some("That transfer expired. Send it again to start over.".to_string()),
+            Err(e) => {
+                tracing::error!("Transfer approval failed: {}", e);
+                Some("Error. Try later.".to_string())
+            }
+        }
+    }
+
+    /// Send an approved transfer and tell whoever didn't give the last CONFIRM
+    async fn send_approved(&self, from: &str, transfer: PendingTransfer) -> String {
+        let (Some(policy), Some(user_repo)) = (&self.transfer_policy, &self.user_repo) else {
+            return "DB offline. Try later.".to_string();
+        };
+        let sender = match user_repo.find_by_id(transfer.sender_id).await {
+            Ok(Some(sender)) => sender,
+            _ => {
+                let _ = policy.repo.finish(transfer.id, false).await;
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        tracing::info!(transfer = %transfer.id, user = %sender.id, "Sending approved transfer");
+        let result = self
+            .execute_send(&sender, transfer.amount, &transfer.token, &transfer.recipient_address, &transfer.recipient)
+            .await;
+        let sent = result.is_ok();
+        let (Ok(reply) | Err(reply)) = result;
+        if let Err(e) = policy.repo.finish(transfer.id, sent).await {
+            tracing::warn!(transfer = %transfer.id, "Failed to record approved transfer result: {}", e);
+        }
+
+        if from == sender.phone {
+            return reply;
+        }
+        // The guardian gave the final approval; the sender gets the result
+        self.notify_receipt(&sender.phone, &format!("Your guardian approved.\n{}", reply)).await;
+        if sent {
+            format!("Approved. {} {} is on its way to {}.", transfer.amount, transfer.token, display_address(&transfer.recipient))
+        } else {
+            "Approved, but the transfer failed. The sender has been told.".to_string()
+        }
+    }
+
+    /// GUARDIAN shows, GUARDIAN <phone> sets and GUARDIAN OFF removes the
+    /// number that approves large transfers
+    pub(super) async fn guardian_response(&self, from: &str, arg: Option<&str>) -> String {
+        let (Some(policy), Some(user_repo)) = (&self.transfer_policy, &self.user_repo) else {
+            return "Transfer approvals are not available.".to_string();
+        };
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let current = match policy.repo.guardian(user.id).await {
+            Ok(current) => current,
+            Err(e) => {
+                tracing::error!(user = %user.id, "Guardian lookup failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let new_guardian = match arg.map(str::trim) {
+            None => {
+                return match current {
+                    Some(guardian) => format!("Your guardian is {}.\nGUARDIAN OFF to remove.", guardian),
+                    None => format!(
+                        "No guardian set. Large transfers wait {} min.\nGUARDIAN <phone> to have someone approve them instead.",
+                        policy.cooling.num_minutes()
+                    ),
+                };
+            }
+            Some(off) if off.eq_ignore_ascii_case("OFF") => None,
+            Some(phone) if phone.starts_with('+') => {
+                if phone == from {
+                    return "Your guardian must be someone else.".to_string();
+                }
+                match user_repo.exists(phone).await {
+                    Ok(true) => Some(phone),
+                    Ok(false) => return format!("{} hasn't joined yet.\nAsk them to text JOIN", phone),
+                    Err(_) => return "Error. Try later.".to_string(),
+                }
+            }
+            Some(_) => return "Usage: GUARDIAN <phone> or GUARDIAN OFF".to_string(),
+        };
+        if new_guardian == current.as_deref() {
+            return "No change.".to_string();
+        }
+
+        // Only the first guardian applies at once; changes wait so the
+        // current guardian can react
+        let effective_at = if current.is_some() { Utc::now() + policy.cooling } else { Utc::now() };
+        if let Err(e) = policy.repo.set_guardian(user.id, new_guardian, effective_at).await {
+            tracing::error!(user = %user.id, "Failed to set guardian: {}", e);
+            return "Error. Try later.".to_string();
+        }
+        tracing::info!(user = %user.id, removed = new_guardian.is_none(), "Guardian change requested");
+
+        if let Some(ref old) = current {
+            self.notify(
+                old,
+                &format!(
+                    "{} changed who approves their large transfers; you stop being their guardian in {} min. If they didn't ask for this, call them now.",
+                    from,
+                    policy.cooling.num_minutes()
+                ),
+            )
+            .await;
+        }
+        if let Some(guardian) = new_guardian {
+            self.notify(guardian, &format!("{} made you their guardian: you'll be asked to approve their large transfers.", from))
+                .await;
+        }
+
+        match (new_guardian, current.is_some()) {
+            (Some(guardian), false) => format!("{} now approves your large transfers.", guardian),
+            (Some(guardian), true) => format!("{} will approve your large transfers in {} min.", guardian, policy.cooling.num_minutes()),
+            (None, _) => format!("Guardian removed in {} min. Large transfers will then wait instead.", policy.cooling.num_minutes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_thresholds() {
+        let thresholds = parse_thresholds("usdc:100, TXTC:5000,ETH:0.05").unwrap();
+        assert_eq!(thresholds.get("USDC"), Some(&100.0));
+        assert_eq!(thresholds.get("ETH"), Some(&0.05));
+        assert!(parse_thresholds("USDC").is_err());
+        assert!(parse_thresholds("USDC:-1").is_err());
+        assert!(parse_thresholds(":5").is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! Beta access: the onboarding gate and INVITE <code>
+
+use super::parser::CommandProcessor;
+use super::referrals::referral_code;
+use crate::beta::{waitlist_reply, Admission};
+use crate::db::{check_code, CodeCheck, REFERRAL_PREFIX};
+
+impl CommandProcessor {
+    /// None if the number may create a wallet, otherwise the reply to send
+    pub(super) async fn beta_gate(&self, from: &str) -> Option<String> {
+        let beta = self.beta.as_ref()?;
+        match beta.admission(from).await {
+            Ok(Admission::Allowed) => None,
+            Ok(Admission::Waitlisted(position)) => Some(waitlist_reply(position)),
+            Err(e) => {
+                tracing::error!("Beta admission check failed: {}", e);
+                Some("Error. Try later.".to_string())
+            }
+        }
+    }
+
+    /// INVITE <code>: redeem a beta invite, then start signup
+    pub(super) async fn invite_response(&self, from: &str, code: &str) -> String {
+        // A friend's code rather than a beta invite
+        if self.referrals.is_some() && code.to_uppercase().starts_with(REFERRAL_PREFIX) {
+            return match referral_code(code) {
+                Some(code) => self.referral_invite_response(from, &code).await,
+                None => "Invalid invite code. Check it and try again.".to_string(),
+            };
+        }
+        let Some(ref beta) = self.beta else {
+            return "No invite needed.\nReply START to create your wallet.".to_string();
+        };
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        match user_repo.exists(from).await {
+            Ok(true) => return "You already have a wallet.\nReply MENU for commands.".to_string(),
+            Ok(false) => {}
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        let code = match check_code(code) {
+            CodeCheck::Valid(code) | CodeCheck::Legacy(code) => code,
+            CodeCheck::Mistyped => return "Invalid invite code. Check it and try again.".to_string(),
+        };
+
+        match beta.repo().redeem_invite(&code, from).await {
+            Ok(true) => {
+                tracing::info!(phone = %from, "Beta invite redeemed");
+                let signup = if self.flow_repo.is_some() {
+                    self.start_onboarding(from).await
+                } else {
+                    self.join_response(from, None).await
+                };
+                format!("Invite accepted!\n\n{}", signup)
+            }
+            Ok(false) => "Invite code not valid or used up.".to_string(),
+            Err(e) => {
+                tracing::error!("Invite redemption failed: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+}
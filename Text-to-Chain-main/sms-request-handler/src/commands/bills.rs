@@ -0,0 +1,284 @@
+//! BILL / PAY - payment requests between merchants and customers
+//!
+//! `BILL 12.50 USDC lunch` gives the merchant a short code. A customer
+//! texts `PAY <code>` to see the bill and replies YES (or sends
+//! `PAY <code> YES` in one go). The payment is an ordinary transfer to the
+//! merchant's wallet: USDC on the ledger, TXTC and ETH on-chain, with the
+//! same kill switch, KYC and large-transfer checks as SEND. It goes out at
+//! once rather than waiting out the SEND cancellation window, and the
+//! merchant gets a receipt.
+
+use chrono::Utc;
+
+use super::flow::{Flow, FlowData, Step, StepResult};
+use super::parser::{Command, CommandProcessor};
+use super::transfers::INTERNAL_TOKEN;
+use crate::db::{is_bill_code, Bill, User};
+use crate::money::{Currency, Money};
+use crate::wallet::address::display_address;
+
+const CODE: &str = "code";
+const MAX_MEMO_LEN: usize = 60;
+const BILL_USAGE: &str = "Use: BILL <amount> [token] <memo>\nExample: BILL 12.50 USDC lunch";
+
+fn check_code(input: &str) -> Result<String, String> {
+    let code = input.trim().to_uppercase();
+    if !is_bill_code(&code) {
+        return Err("Reply the 6-character bill code.".to_string());
+    }
+    Ok(code)
+}
+
+fn check_confirm(input: &str) -> Result<String, String> {
+    match input.to_uppercase().as_str() {
+        "YES" | "Y" => Ok("YES".to_string()),
+        "NO" | "N" => Ok("NO".to_string()),
+        _ => Err("Reply YES to pay or NO to stop.".to_string()),
+    }
+}
+
+fn is_answer(_: &str, command: &Command) -> bool {
+    matches!(command, Command::Unknown(_))
+}
+
+/// Who a merchant or payer is shown as: their ENS name, or short address;
+/// never the phone number
+pub(super) fn label(user: &User) -> String {
+    user.ens_name.clone().unwrap_or_else(|| display_address(&user.wallet_address))
+}
+
+/// Code, YES
+pub(super) struct PayBill;
+
+impl Flow for PayBill {
+    const NAME: &'static str = "pay";
+    const STEPS: &'static [Step] = &[
+        Step {
+            name: CODE,
+            prompt: |_| "Which bill? Reply its code\nReply EXIT to stop".to_string(),
+            accepts: is_answer,
+            validate: check_code,
+            skippable: false,
+            back: false,
+        },
+        Step {
+            name: "confirm",
+            prompt: |data| format!("Pay bill {}?\nReply YES, NO or BACK", data[CODE]),
+            accepts: is_answer,
+            validate: check_confirm,
+            skippable: false,
+            back: true,
+        },
+    ];
+    const TIMEOUT_MINS: i64 = 10;
+    const REMINDER: &'static str = "Finish paying your bill:";
+    const EXIT_REPLY: &'static str = "Bill not paid.";
+    const EXPIRED_REPLY: &'static str = "That PAY timed out. Nothing was paid.\nStart again with PAY <code>.";
+
+    async fn answer(processor: &CommandProcessor, from: &str, step: &Step, data: &mut FlowData) -> StepResult {
+        let code = data[CODE].clone();
+        if step.name == CODE {
+            return match processor.bill_details(from, &code).await {
+                Ok(details) => StepResult::Next(Some(details)),
+                Err(reason) => StepResult::Retry(reason),
+            };
+        }
+        if data[step.name] == "NO" {
+            return StepResult::Done(Self::EXIT_REPLY.to_string());
+        }
+        let command = Command::Pay { code: Some(code), confirmed: true };
+        StepResult::Done(processor.execute(from, command).await)
+    }
+}
+
+impl CommandProcessor {
+    /// BILL <amount> [token] <memo>
+    pub(super) async fn bill_response(&self, from: &str, amount: f64, token: &str, memo: &str) -> String {
+        let (Some(ref user_repo), Some(ref bills)) = (&self.user_repo, &self.bills) else {
+            return "Bills are not available.".to_string();
+        };
+        let token = token.to_uppercase();
+        if !matches!(token.as_str(), "TXTC" | "ETH" | INTERNAL_TOKEN) {
+            return format!("Supported tokens: TXTC, ETH, USDC\n{}", BILL_USAGE);
+        }
+        let memo = memo.trim();
+        if memo.is_empty() {
+            return BILL_USAGE.to_string();
+        }
+        if memo.chars().count() > MAX_MEMO_LEN {
+            return format!("Memo too long (max {} characters).", MAX_MEMO_LEN);
+        }
+        let amount = match Currency::parse(&token).map(|currency| Money::from_f64(amount, currency)) {
+            Some(Ok(amount)) if amount.is_positive() => amount,
+            _ => return "Invalid amount".to_string(),
+        };
+
+        let merchant = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        match bills.create(merchant.id, amount, memo).await {
+            Ok(bill) => {
+                tracing::info!(merchant = %merchant.id, code = %bill.code, amount = %bill.amount, "Bill created");
+                format!(
+                    "Bill {}: {} for {}\nYour customer texts PAY {} to pay.\nValid {}h.",
+                    bill.code,
+                    bill.amount,
+                    bill.memo,
+                    bill.code,
+                    (bill.expires_at - Utc::now()).num_hours().max(1)
+                )
+            }
+            Err(e) => {
+                tracing::error!(merchant = %merchant.id, "Failed to create bill: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    /// PAY [<code>] [YES]
+    pub(super) async fn pay_response(&self, from: &str, code: Option<String>, confirmed: bool) -> String {
+        let Some(code) = code else {
+            if self.flow_repo.is_none() {
+                return "Use: PAY <code>".to_string();
+            }
+            return self.start_flow::<PayBill>(from, FlowData::new()).await;
+        };
+        if confirmed {
+            return self.pay_bill(from, &code).await;
+        }
+
+        let details = match self.bill_details(from, &code).await {
+            Ok(details) => details,
+            Err(reason) => return reason,
+        };
+        if self.flow_repo.is_none() {
+            return format!("{}\nReply PAY {} YES to pay.", details, code);
+        }
+        let data = FlowData::from([(CODE.to_string(), code)]);
+        format!("{}\n\n{}", details, self.start_flow::<PayBill>(from, data).await)
+    }
+
+    /// What a bill is for and who it pays, or why it can't be paid
+    pub(super) async fn bill_details(&self, from: &str, code: &str) -> Result<String, String> {
+        let (Some(ref user_repo), Some(ref bills)) = (&self.user_repo, &self.bills) else {
+            return Err("Bills are not available.".to_string());
+        };
+        let payer = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return Err("No wallet. Reply JOIN first.".to_string()),
+            Err(_) => return Err("Error. Try later.".to_string()),
+        };
+        let bill = match bills.find(code).await {
+            Ok(Some(bill)) => bill,
+            Ok(None) => return Err(format!("No bill with code {}.", code)),
+            Err(_) => return Err("Error. Try later.".to_string()),
+        };
+        if let Some(reason) = unpayable(&bill, &payer) {
+            return Err(reason);
+        }
+        let merchant = match user_repo.find_by_id(bill.merchant_id).await {
+            Ok(Some(user)) => user,
+            _ => return Err("Error. Try later.".to_string()),
+        };
+        Ok(format!("Bill {} from {}:\n{} for {}", bill.code, label(&merchant), bill.amount, bill.memo))
+    }
+
+    /// Settle a bill from the sender's wallet and send the merchant a receipt
+    async fn pay_bill(&self, from: &str, code: &str) -> String {
+        let (Some(ref user_repo), Some(ref bills)) = (&self.user_repo, &self.bills) else {
+            return "Bills are not available.".to_string();
+        };
+        let payer = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let bill = match bills.claim(code, payer.id).await {
+            Ok(Some(bill)) => bill,
+            Ok(None) => {
+                // Say why: unknown, paid, expired or the payer's own
+                return match self.bill_details(from, code).await {
+                    Ok(_) => format!("Bill {} is being paid. Try again in a minute.", code),
+                    Err(reason) => reason,
+                };
+            }
+            Err(e) => {
+                tracing::error!(code, "Failed to claim bill: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let reply = match user_repo.find_by_id(bill.merchant_id).await {
+            Ok(Some(merchant)) => self.settle_bill(&payer, &merchant, &bill).await,
+            _ => Err("Error. Try later.".to_string()),
+        };
+        match reply {
+            Ok(reply) => {
+                tracing::info!(code, payer = %payer.id, merchant = %bill.merchant_id, "Bill paid");
+                if let Err(e) = bills.settle(bill.id).await {
+                    tracing::error!(code, "Failed to mark bill paid: {}", e);
+                }
+                format!("Bill {} paid.\n{}", bill.code, reply)
+            }
+            Err(reply) => {
+                if let Err(e) = bills.release(bill.id).await {
+                    tracing::error!(code, "Failed to reopen bill: {}", e);
+                }
+                reply
+            }
+        }
+    }
+
+    /// Transfer a claimed bill's amount to the merchant (Err = nothing moved)
+    async fn settle_bill(&self, payer: &User, merchant: &User, bill: &Bill) -> Result<String, String> {
+        let amount = bill.amount.to_f64();
+        let token = bill.amount.currency().code().to_string();
+        let recipient = label(merchant);
+
+        // The limits a SEND of the same amount would meet
+        let as_send = Command::Send { amount, token: token.clone(), recipient: merchant.wallet_address.clone(), repeat: false };
+        if let Some(reply) = self.kyc_hold(&payer.phone, &as_send).await {
+            return Err(reply);
+        }
+        if let Some(reply) = self.hold_large_send(payer, amount, &token, &merchant.wallet_address, &recipient).await {
+            return Err(format!("{}\nBill {} stays open until it is paid.", reply, bill.code));
+        }
+
+        let reply = self.execute_send(payer, amount, &token, &merchant.wallet_address, &recipient).await?;
+        self.notify_update(
+            &merchant.phone,
+            &format!("Bill {} paid: {} for {}\nFrom {}", bill.code, bill.amount, bill.memo, label(payer)),
+        )
+        .await;
+        Ok(reply)
+    }
+}
+
+/// Why a bill can't be paid by `payer`, if it can't
+fn unpayable(bill: &Bill, payer: &User) -> Option<String> {
+    if bill.merchant_id == payer.id {
+        return Some("That's your own bill. Give the code to your customer.".to_string());
+    }
+    match bill.status.as_str() {
+        "paid" => Some(format!("Bill {} is already paid.", bill.code)),
+        "open" if !bill.is_payable() => Some(format!("Bill {} has expired. Ask for a new one.", bill.code)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers_checked() {
+        assert_eq!(check_code(" k7pq2m "), Ok("K7PQ2M".to_string()));
+        assert!(check_code("K7PQ2").is_err());
+        // 0 and 1 are never in a code
+        assert!(check_code("K7PQ20").is_err());
+        assert_eq!(check_confirm("y"), Ok("YES".to_string()));
+        assert!(check_confirm("later").is_err());
+    }
+}
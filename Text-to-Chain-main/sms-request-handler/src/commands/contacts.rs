@@ -0,0 +1,164 @@
+//! Contact names in free-text commands
+//!
+//! Names can have spaces ("aunt mary") and are written in all sorts of
+//! ways: `send 5 to aunt mary`, `send 5 to Aunt Mary's wallet`. A recipient
+//! is matched against the sender's whole address book, trying its longest
+//! run of leading words first, so trailing words ("now", "pls") don't stop
+//! a match. When more than one contact fits, the sender is asked which.
+
+use super::parser::CommandProcessor;
+use crate::db::Contact;
+use crate::sms::SmsDisplay;
+
+/// Words that may follow a possessive name: "mary's wallet"
+const POSSESSED: &[&str] = &["wallet", "phone", "number", "account", "address"];
+
+/// Contacts listed in a "which one?" reply
+const MAX_CHOICES: usize = 4;
+
+/// Outcome of looking a name up in the address book
+#[derive(Debug)]
+pub(super) enum ContactMatch<'a> {
+    Found(&'a Contact),
+    /// Several contacts fit equally well
+    Ambiguous(Vec<&'a Contact>),
+    NotFound,
+}
+
+/// Lowercase words of a name or recipient phrase, possessive `'s` removed;
+/// a possessive word ends the name when only a word like "wallet" follows
+pub(super) fn name_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut tokens = text.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        let word = token.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'' && c != '’').to_lowercase();
+        let (word, possessive) = match word.strip_suffix("'s").or_else(|| word.strip_suffix("’s")) {
+            Some(stem) => (stem.to_string(), true),
+            None => (word, false),
+        };
+        if !word.is_empty() {
+            words.push(word);
+        }
+        let possessed = |next: &&str| POSSESSED.contains(&next.trim_end_matches(|c: char| !c.is_alphanumeric()).to_lowercase().as_str());
+        if possessive && tokens.peek().is_some_and(possessed) {
+            break;
+        }
+    }
+    words
+}
+
+/// Best contact for `recipient`: the longest leading run of its words that
+/// is exactly some contact's name, else contacts whose name contains it
+pub(super) fn match_contact<'a>(contacts: &'a [Contact], recipient: &str) -> ContactMatch<'a> {
+    let wanted = name_words(recipient);
+    let names: Vec<Vec<String>> = contacts.iter().map(|c| name_words(&c.name)).collect();
+
+    for len in (1..=wanted.len()).rev() {
+        let prefix = &wanted[..len];
+        let exact: Vec<&Contact> = contacts.iter().zip(&names).filter(|(_, name)| name.as_slice() == prefix).map(|(c, _)| c).collect();
+        match exact.len() {
+            0 => continue,
+            1 => return ContactMatch::Found(exact[0]),
+            _ => return ContactMatch::Ambiguous(exact),
+        }
+    }
+
+    // "mary" on its own: any contact with those words in its name
+    let joined = wanted.join(" ");
+    let partial: Vec<&Contact> = contacts
+        .iter()
+        .zip(&names)
+        .filter(|(_, name)| !joined.is_empty() && format!(" {} ", name.join(" ")).contains(&format!(" {} ", joined)))
+        .map(|(c, _)| c)
+        .collect();
+    match partial.len() {
+        0 => ContactMatch::NotFound,
+        1 => ContactMatch::Found(partial[0]),
+        _ => ContactMatch::Ambiguous(partial),
+    }
+}
+
+/// "Which one?" reply; `retry` is the command to repeat with a full name
+pub(super) fn choose_contact_reply(contacts: &[Contact], retry: &str) -> String {
+    let mut lines: Vec<String> = contacts.iter().take(MAX_CHOICES).map(|c| c.to_sms_string()).collect();
+    if contacts.len() > MAX_CHOICES {
+        lines.push(format!("+{} more", contacts.len() - MAX_CHOICES));
+    }
+    format!("Which contact?\n{}\nReply {} <full name>", lines.join("\n"), retry)
+}
+
+impl CommandProcessor {
+    /// Contacts `recipient` may mean: one, several to choose from, or none
+    pub(super) async fn find_contact(&self, from: &str, recipient: &str) -> Result<Vec<Contact>, sqlx::Error> {
+        match self.address_book_repo {
+            Some(ref address_book) => address_book.list_all(from).await,
+            None => Ok(vec![]),
+        }
+        .map(|contacts| match match_contact(&contacts, recipient) {
+            ContactMatch::Found(contact) => vec![contact.clone()],
+            ContactMatch::Ambiguous(matches) => matches.into_iter().cloned().collect(),
+            ContactMatch::NotFound => vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(name: &str, phone: &str) -> Contact {
+        Contact {
+            id: uuid::Uuid::new_v4(),
+            user_phone: "+254700000000".to_string(),
+            name: name.to_string(),
+            contact_phone: Some(phone.to_string()),
+            wallet_address: None,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    fn found<'a>(result: ContactMatch<'a>) -> Option<&'a str> {
+        match result {
+            ContactMatch::Found(contact) => Some(contact.name.as_str()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_name_words() {
+        assert_eq!(name_words("Aunt Mary's wallet"), ["aunt", "mary"]);
+        assert_eq!(name_words("aunt  mary’s phone!"), ["aunt", "mary"]);
+        assert_eq!(name_words("MARY'S SHOP"), ["mary", "shop"]);
+        assert_eq!(name_words("mary."), ["mary"]);
+    }
+
+    #[test]
+    fn test_longest_match_wins() {
+        let book = vec![
+            contact("AUNT MARY", "+254700000001"),
+            contact("MARY", "+254700000002"),
+            contact("AUNT", "+254700000003"),
+        ];
+        assert_eq!(found(match_contact(&book, "aunt mary")), Some("AUNT MARY"));
+        assert_eq!(found(match_contact(&book, "aunt mary's wallet")), Some("AUNT MARY"));
+        assert_eq!(found(match_contact(&book, "aunt mary now pls")), Some("AUNT MARY"));
+        assert_eq!(found(match_contact(&book, "aunt")), Some("AUNT"));
+        assert_eq!(found(match_contact(&book, "mary")), Some("MARY"));
+        assert!(matches!(match_contact(&book, "bob"), ContactMatch::NotFound));
+    }
+
+    #[test]
+    fn test_ambiguous_partial_match() {
+        let book = vec![contact("AUNT MARY", "+254700000001"), contact("MARY JANE", "+254700000002"), contact("BOB", "+254700000003")];
+        let ContactMatch::Ambiguous(matches) = match_contact(&book, "mary") else {
+            panic!("expected two matches");
+        };
+        assert_eq!(matches.len(), 2);
+        let reply = choose_contact_reply(&matches.into_iter().cloned().collect::<Vec<_>>(), "SEND 5 TXTC TO");
+        assert!(reply.starts_with("Which contact?\nAUNT MARY: +254700000001\nMARY JANE: +254700000002"));
+        assert!(reply.ends_with("Reply SEND 5 TXTC TO <full name>"));
+
+        // Whole words only: "ma" is not "mary"
+        assert!(matches!(match_contact(&book, "ma"), ContactMatch::NotFound));
+    }
+}
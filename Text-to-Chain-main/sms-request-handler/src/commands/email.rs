@@ -0,0 +1,84 @@
+//! EMAIL: link an address that may send commands for this phone
+
+use rand::Rng;
+
+use super::parser::CommandProcessor;
+use crate::db::EmailVerification;
+
+/// Minutes a mailed verification code stays valid
+const CODE_TTL_MINUTES: i32 = 15;
+
+impl CommandProcessor {
+    /// EMAIL [<address> | <code> | OFF]
+    pub(super) async fn email_response(&self, from: &str, arg: Option<&str>) -> String {
+        let Some(ref channel) = self.email else {
+            return "Email commands are not available.".to_string();
+        };
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        match user_repo.exists(from).await {
+            Ok(true) => {}
+            Ok(false) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        let links = &channel.links;
+        match arg {
+            None => match links.email_for_phone(from).await {
+                Ok(Some(email)) => format!("Linked email: {}\nReply EMAIL OFF to unlink.", email),
+                Ok(None) => "No email linked.\nReply EMAIL <address> to send commands by email.".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            },
+            Some(arg) if arg.eq_ignore_ascii_case("OFF") => match links.unlink(from).await {
+                Ok(true) => "Email unlinked. Commands from it will be ignored.".to_string(),
+                Ok(false) => "No email linked.".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            },
+            Some(code) if code.chars().all(|c| c.is_ascii_digit()) => match links.verify(from, code).await {
+                Ok(EmailVerification::Verified(email)) => {
+                    tracing::info!(phone = %from, "Email linked");
+                    format!("Email linked: {}\nSend a command as the first line of an email and we'll reply there.", email)
+                }
+                Ok(EmailVerification::WrongCode) => "Wrong code. Check the email and try again.".to_string(),
+                Ok(EmailVerification::NoPending) => "No pending email link.\nReply EMAIL <address> to get a new code.".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            },
+            Some(address) => {
+                let email = address.trim().to_lowercase();
+                if !is_valid_email(&email) {
+                    return "Invalid email address.\nExample: EMAIL you@example.com".to_string();
+                }
+
+                let code = rand::thread_rng().gen_range(100_000..1_000_000).to_string();
+                match links.start_link(from, &email, &code, CODE_TTL_MINUTES).await {
+                    Ok(true) => {}
+                    Ok(false) => return "That email is linked to another number.".to_string(),
+                    Err(e) => {
+                        tracing::error!("Failed to start email link: {}", e);
+                        return "Error. Try later.".to_string();
+                    }
+                }
+
+                let body = format!(
+                    "Your TextChain code is {}.\n\nText EMAIL {} from your phone within {} minutes to let this address send commands for your wallet.\n\nIf you didn't ask for this, ignore this email.",
+                    code, code, CODE_TTL_MINUTES
+                );
+                if let Err(e) = channel.client.send_email(&email, "Your TextChain code", &body, None).await {
+                    tracing::error!("Failed to send email verification code: {}", e);
+                    return "Couldn't send to that address. Check it and try again.".to_string();
+                }
+                format!("Code sent to {}.\nReply EMAIL <code> within {} min.", email, CODE_TTL_MINUTES)
+            }
+        }
+    }
+}
+
+/// Loose shape check; the code round trip proves the address works
+fn is_valid_email(email: &str) -> bool {
+    email.len() <= 254
+        && !email.contains(char::is_whitespace)
+        && email
+            .split_once('@')
+            .is_some_and(|(local, domain)| !local.is_empty() && !domain.contains('@') && domain.contains('.'))
+}
@@ -0,0 +1,292 @@
+//! Multi-step SMS flows
+//!
+//! A flow is declared as a list of `Step`s: its prompt, which messages
+//! answer it, how an answer is checked, and whether it can be skipped or
+//! stepped back from. The engine handles the rest the same way for every
+//! flow:
+//! - answers are saved in `flow_sessions` as they arrive, so a flow resumes
+//!   across messages and restarts
+//! - BACK, SKIP and EXIT work at any step (CANCEL on its own is a carrier
+//!   opt-out keyword, so flows can't use it)
+//! - any other command runs as usual, followed by the pending prompt
+//! - a flow left idle past `Flow::TIMEOUT_MINS` is dropped
+//!
+//! Starting a flow replaces whichever one the number had in progress.
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, Utc};
+
+use super::bills::PayBill;
+use super::guided_send::GuidedSend;
+use super::onboarding::Onboarding;
+use super::repeat_send::RepeatSend;
+use super::parser::{Command, CommandProcessor};
+use crate::db::FlowSession;
+
+/// Answers given so far, by step name
+pub(super) type FlowData = BTreeMap<String, String>;
+
+/// One question in a flow
+pub(super) struct Step {
+    pub name: &'static str,
+    pub prompt: fn(&FlowData) -> String,
+    /// Whether a message answers this step; anything else runs as a command
+    pub accepts: fn(&str, &Command) -> bool,
+    /// Normalise an answer, or say why it can't be used
+    pub validate: fn(&str) -> Result<String, String>,
+    /// SKIP moves on without an answer
+    pub skippable: bool,
+    /// BACK returns to the previous step
+    pub back: bool,
+}
+
+/// What a flow made of a checked answer
+pub(super) enum StepResult {
+    /// On to the next step, with text to show above its prompt
+    Next(Option<String>),
+    /// Ask the same step again after this text
+    Retry(String),
+    /// The flow is over and this is the reply
+    Done(String),
+}
+
+/// A multi-step conversation
+pub(super) trait Flow {
+    /// Stored in `flow_sessions.flow`
+    const NAME: &'static str;
+    const STEPS: &'static [Step];
+    /// Idle minutes before the flow is dropped
+    const TIMEOUT_MINS: i64;
+    /// Heading above the pending prompt after another command
+    const REMINDER: &'static str;
+    /// Reply to EXIT
+    const EXIT_REPLY: &'static str;
+    /// Reply to an answer that comes after the flow timed out
+    const EXPIRED_REPLY: &'static str;
+
+    /// Act on a checked answer, already stored in `data` under the step name
+    async fn answer(processor: &CommandProcessor, from: &str, step: &Step, data: &mut FlowData) -> StepResult;
+}
+
+/// A valid answer for every step in `data`, with the rest left out
+fn checked<F: Flow>(mut data: FlowData) -> FlowData {
+    data.retain(|name, value| {
+        let Some(step) = F::STEPS.iter().find(|step| step.name == name) else {
+            return false;
+        };
+        match (step.validate)(value) {
+            Ok(checked) => {
+                *value = checked;
+                true
+            }
+            Err(_) => false,
+        }
+    });
+    data
+}
+
+/// Index of the first step still waiting for an answer
+fn first_open<F: Flow>(data: &FlowData) -> usize {
+    F::STEPS.iter().position(|step| !data.contains_key(step.name)).unwrap_or(0)
+}
+
+fn with_prompt(text: &str, step: &Step, data: &FlowData) -> String {
+    format!("{}\n\n{}", text, (step.prompt)(data))
+}
+
+impl CommandProcessor {
+    /// Begin `F`, skipping steps already answered correctly in `data`, and
+    /// return the first prompt
+    pub(super) async fn start_flow<F: Flow>(&self, from: &str, data: FlowData) -> String {
+        let data = checked::<F>(data);
+        let index = first_open::<F>(&data);
+        self.save_flow::<F>(from, index, &data).await;
+        (F::STEPS[index].prompt)(&data)
+    }
+
+    /// Prompt for the step `F` is waiting on, if the number is in `F`
+    pub(super) async fn flow_prompt<F: Flow>(&self, from: &str) -> Option<String> {
+        let session = self.flow_repo.as_ref()?.find(from).await.ok()??;
+        if session.flow != F::NAME {
+            return None;
+        }
+        let data = decode(&session.data);
+        let step = F::STEPS.iter().find(|step| step.name == session.step).unwrap_or(&F::STEPS[0]);
+        Some((step.prompt)(&data))
+    }
+
+    /// Route a message through the number's flow, or into onboarding if the
+    /// sender is new. Returns `None` when it should run as a normal command.
+    pub(super) async fn flow_intercept(&self, from: &str, body: &str, command: &Command) -> Option<String> {
+        let flows = self.flow_repo.as_ref()?;
+        if *command == Command::Start {
+            return Some(self.start_onboarding(from).await);
+        }
+
+        let session = match flows.find(from).await {
+            Ok(session) => session,
+            Err(e) => {
+                tracing::error!("Flow lookup failed: {}", e);
+                return None;
+            }
+        };
+        let Some(session) = session else {
+            return self.first_contact(from, command).await;
+        };
+
+        match session.flow.as_str() {
+            Onboarding::NAME => self.continue_flow::<Onboarding>(from, session, body, command).await,
+            GuidedSend::NAME => self.continue_flow::<GuidedSend>(from, session, body, command).await,
+            PayBill::NAME => self.continue_flow::<PayBill>(from, session, body, command).await,
+            RepeatSend::NAME => self.continue_flow::<RepeatSend>(from, session, body, command).await,
+            other => {
+                tracing::warn!(phone = %from, flow = other, "Dropping unknown flow");
+                self.end_flow(from, other).await;
+                None
+            }
+        }
+    }
+
+    async fn continue_flow<F: Flow>(&self, from: &str, session: FlowSession, body: &str, command: &Command) -> Option<String> {
+        let mut data = decode(&session.data);
+        let index = F::STEPS.iter().position(|step| step.name == session.step).unwrap_or(0);
+        let step = &F::STEPS[index];
+        let input = body.trim();
+        let word = input.to_uppercase();
+        let is_answer = (step.accepts)(input, command);
+
+        if session.updated_at + Duration::minutes(F::TIMEOUT_MINS) < Utc::now() {
+            tracing::info!(phone = %from, flow = F::NAME, step = step.name, "Flow timed out");
+            self.end_flow(from, F::NAME).await;
+            let is_flow_word = matches!(word.as_str(), "BACK" | "SKIP" | "EXIT");
+            return (is_answer || is_flow_word).then(|| F::EXPIRED_REPLY.to_string());
+        }
+
+        match word.as_str() {
+            "EXIT" => {
+                self.end_flow(from, F::NAME).await;
+                return Some(F::EXIT_REPLY.to_string());
+            }
+            "BACK" if step.back && index > 0 => {
+                let previous = &F::STEPS[index - 1];
+                data.remove(previous.name);
+                self.save_flow::<F>(from, index - 1, &data).await;
+                return Some((previous.prompt)(&data));
+            }
+            "SKIP" if step.skippable => return Some(self.advance_flow::<F>(from, index, &data, None).await),
+            _ => {}
+        }
+
+        if !is_answer {
+            let reply = self.execute(from, command.clone()).await;
+            if command.starts_flow() {
+                return Some(reply);
+            }
+            return Some(with_prompt(&format!("{}\n\n{}", reply, F::REMINDER), step, &data));
+        }
+
+        match (step.validate)(input) {
+            Ok(value) => data.insert(step.name.to_string(), value),
+            Err(reason) => return Some(with_prompt(&reason, step, &data)),
+        };
+        let reply = match F::answer(self, from, step, &mut data).await {
+            StepResult::Next(note) => self.advance_flow::<F>(from, index, &data, note).await,
+            StepResult::Retry(reason) => {
+                data.remove(step.name);
+                with_prompt(&reason, step, &data)
+            }
+            StepResult::Done(reply) => {
+                self.end_flow(from, F::NAME).await;
+                reply
+            }
+        };
+        Some(reply)
+    }
+
+    /// Move past step `index`; past the last step the flow ends
+    async fn advance_flow<F: Flow>(&self, from: &str, index: usize, data: &FlowData, note: Option<String>) -> String {
+        let Some(next) = F::STEPS.get(index + 1) else {
+            self.end_flow(from, F::NAME).await;
+            return note.unwrap_or_else(|| "Done.".to_string());
+        };
+        self.save_flow::<F>(from, index + 1, data).await;
+        match note {
+            Some(note) => with_prompt(&note, next, data),
+            None => (next.prompt)(data),
+        }
+    }
+
+    async fn save_flow<F: Flow>(&self, from: &str, index: usize, data: &FlowData) {
+        let Some(ref flows) = self.flow_repo else {
+            return;
+        };
+        let data = serde_json::to_string(data).unwrap_or_else(|_| "{}".to_string());
+        if let Err(e) = flows.save(from, F::NAME, F::STEPS[index].name, &data).await {
+            tracing::error!(phone = %from, flow = F::NAME, "Failed to save flow: {}", e);
+        }
+    }
+
+    /// Drop `flow`, unless the last step started another one
+    async fn end_flow(&self, from: &str, flow: &str) {
+        if let Some(ref flows) = self.flow_repo {
+            if let Err(e) = flows.finish(from, flow).await {
+                tracing::error!(phone = %from, "Failed to end flow: {}", e);
+            }
+        }
+    }
+}
+
+fn decode(data: &str) -> FlowData {
+    serde_json::from_str(data).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Sample;
+
+    impl Flow for Sample {
+        const NAME: &'static str = "sample";
+        const STEPS: &'static [Step] = &[
+            Step {
+                name: "count",
+                prompt: |_| "How many?".to_string(),
+                accepts: |_, _| true,
+                validate: |input| input.parse::<u32>().map(|n| n.to_string()).map_err(|_| "A number".to_string()),
+                skippable: false,
+                back: false,
+            },
+            Step {
+                name: "label",
+                prompt: |data| format!("Label for {}?", data["count"]),
+                accepts: |_, _| true,
+                validate: |input| Ok(input.to_lowercase()),
+                skippable: true,
+                back: true,
+            },
+        ];
+        const TIMEOUT_MINS: i64 = 5;
+        const REMINDER: &'static str = "Still waiting:";
+        const EXIT_REPLY: &'static str = "Stopped.";
+        const EXPIRED_REPLY: &'static str = "Timed out.";
+
+        async fn answer(_: &CommandProcessor, _: &str, _: &Step, _: &mut FlowData) -> StepResult {
+            StepResult::Next(None)
+        }
+    }
+
+    #[test]
+    fn test_prefilled_answers_are_checked() {
+        let data = FlowData::from([("count".to_string(), "07".to_string()), ("other".to_string(), "x".to_string())]);
+        let data = checked::<Sample>(data);
+        assert_eq!(data, FlowData::from([("count".to_string(), "7".to_string())]));
+        assert_eq!(first_open::<Sample>(&data), 1);
+
+        let bad = checked::<Sample>(FlowData::from([("count".to_string(), "lots".to_string())]));
+        assert!(bad.is_empty());
+        assert_eq!(first_open::<Sample>(&bad), 0);
+        assert_eq!(with_prompt("A number", &Sample::STEPS[0], &bad), "A number\n\nHow many?");
+    }
+}
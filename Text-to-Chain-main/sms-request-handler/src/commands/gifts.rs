@@ -0,0 +1,224 @@
+//! ENS name gifts: GIFT NAME <label> <recipient>, GIFT ACCEPT, GIFT DECLINE
+//!
+//! The giver picks a free `<label>.ttcip.eth` for another user who has no
+//! name yet. Nothing is minted until the recipient replies GIFT ACCEPT; the
+//! name then points at the recipient's wallet. The mint's gas goes on the
+//! gift (and on the paying campaign's mint record) against the giver.
+
+use super::contacts::choose_contact_reply;
+use super::parser::{CommandProcessor, EnsRegistrationError, MINTS_PAUSED_REPLY};
+use crate::db::{User, GIFT_EXPIRY_HOURS};
+
+/// What GIFT does
+#[derive(Debug, Clone, PartialEq)]
+pub enum GiftAction {
+    /// GIFT NAME <label> <phone or contact>
+    Offer { label: String, recipient: String },
+    Accept,
+    Decline,
+}
+
+pub(super) const GIFT_USAGE: &str = "Usage: GIFT NAME <name> <phone or contact>, GIFT ACCEPT or GIFT DECLINE";
+
+/// GIFT arguments after the keyword, as typed
+pub(super) fn parse_gift(args: &[&str]) -> Result<GiftAction, &'static str> {
+    match args {
+        [keyword, label, recipient @ ..] if keyword.eq_ignore_ascii_case("NAME") && !recipient.is_empty() => {
+            let label = label.to_lowercase();
+            Ok(GiftAction::Offer {
+                label: label.strip_suffix(".ttcip.eth").unwrap_or(&label).to_string(),
+                recipient: recipient.join(" "),
+            })
+        }
+        [keyword] if keyword.eq_ignore_ascii_case("ACCEPT") => Ok(GiftAction::Accept),
+        [keyword] if keyword.eq_ignore_ascii_case("DECLINE") => Ok(GiftAction::Decline),
+        _ => Err(GIFT_USAGE),
+    }
+}
+
+/// How a user is named to others: their ENS name, else their number
+fn display_name(user: &User) -> &str {
+    user.ens_name.as_deref().unwrap_or(&user.phone)
+}
+
+impl CommandProcessor {
+    /// GIFT NAME <label> <recipient> | GIFT ACCEPT | GIFT DECLINE
+    pub(super) async fn gift_response(&self, from: &str, action: GiftAction) -> String {
+        let (Some(user_repo), Some(_)) = (&self.user_repo, &self.name_gifts) else {
+            return "Name gifts are not available.".to_string();
+        };
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        match action {
+            GiftAction::Offer { label, recipient } => self.offer_gift(&user, &label, &recipient).await,
+            GiftAction::Accept => self.accept_gift(&user).await,
+            GiftAction::Decline => self.decline_gift(&user).await,
+        }
+    }
+
+    /// A registered user for a phone number or address book name
+    async fn gift_recipient(&self, from: &str, label: &str, recipient: &str) -> Result<User, String> {
+        let Some(ref user_repo) = self.user_repo else {
+            return Err("DB offline. Try later.".to_string());
+        };
+        let found = if recipient.starts_with('+') {
+            user_repo.find_by_phone(recipient).await
+        } else {
+            let contacts = self.find_contact(from, recipient).await.map_err(|_| "Error. Try later.".to_string())?;
+            match contacts.as_slice() {
+                [] => return Err(format!("{} is not in your contacts.\nUse their phone number (+...).", recipient)),
+                [contact] => match (&contact.contact_phone, &contact.wallet_address) {
+                    (Some(phone), _) => user_repo.find_by_phone(phone).await,
+                    (None, Some(address)) => user_repo.find_by_wallet(address).await,
+                    (None, None) => Ok(None),
+                },
+                _ => return Err(choose_contact_reply(&contacts, &format!("GIFT NAME {}", label))),
+            }
+        };
+        match found {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => Err(format!("{} hasn't joined yet.\nAsk them to text JOIN", recipient)),
+            Err(_) => Err("Error looking up recipient.".to_string()),
+        }
+    }
+
+    async fn offer_gift(&self, giver: &User, label: &str, recipient: &str) -> String {
+        let Some(ref gifts) = self.name_gifts else {
+            return "Name gifts are not available.".to_string();
+        };
+        let recipient = match self.gift_recipient(&giver.phone, label, recipient).await {
+            Ok(user) => user,
+            Err(reply) => return reply,
+        };
+        if recipient.id == giver.id {
+            return format!("To take {} yourself, reply JOIN {}", label, label);
+        }
+        if let Some(ref name) = recipient.ens_name {
+            return format!("{} already has the name {}.", recipient.phone, name);
+        }
+
+        match self.check_ens_name(label).await {
+            Ok(()) => {}
+            Err(EnsRegistrationError::Invalid(reason)) => return reason.to_string(),
+            Err(EnsRegistrationError::Unavailable(reason)) => return format!("❌ {}\nTry another name.", reason),
+            Err(EnsRegistrationError::Service(msg)) => return msg.to_string(),
+            Err(EnsRegistrationError::Unfunded) => return MINTS_PAUSED_REPLY.to_string(),
+        }
+
+        match gifts.offer(giver.id, recipient.id, label).await {
+            Ok(Some(gift)) => {
+                tracing::info!(gift = %gift.id, giver = %giver.id, recipient = %recipient.id, "ENS name gift offered");
+                self.notify_receipt(
+                    &recipient.phone,
+                    &format!(
+                        "{} wants to give you the name {}.ttcip.eth, so people can pay you by name.\nReply GIFT ACCEPT to take it or GIFT DECLINE. The offer lasts {} hours.",
+                        display_name(giver),
+                        label,
+                        GIFT_EXPIRY_HOURS
+                    ),
+                )
+                .await;
+                format!("Offered {}.ttcip.eth to {}.\nWe'll text you when they answer.", label, recipient.phone)
+            }
+            Ok(None) => format!("{} already has a name gift waiting.", recipient.phone),
+            Err(e) => {
+                tracing::error!(giver = %giver.id, "Failed to offer name gift: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    async fn accept_gift(&self, user: &User) -> String {
+        let (Some(gifts), Some(user_repo)) = (&self.name_gifts, &self.user_repo) else {
+            return "Name gifts are not available.".to_string();
+        };
+        if let Some(ref name) = user.ens_name {
+            return format!("You already have the name {}.\nReply GIFT DECLINE to turn the gift down.", name);
+        }
+        let gift = match gifts.claim(user.id).await {
+            Ok(Some(gift)) => gift,
+            Ok(None) => return "No name gift waiting for you.".to_string(),
+            Err(e) => {
+                tracing::error!(user = %user.id, "Failed to claim name gift: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+        let giver = user_repo.find_by_id(gift.giver_id).await.ok().flatten();
+
+        let result = self.register_ens_name(&user.phone, &user.wallet_address, &gift.label, Some(&gift)).await;
+        let retry = matches!(result, Err(EnsRegistrationError::Service(_) | EnsRegistrationError::Unfunded));
+        if result.is_err() {
+            if let Err(e) = gifts.release(gift.id, retry).await {
+                tracing::error!(gift = %gift.id, "Failed to release name gift: {}", e);
+            }
+        }
+
+        match result {
+            Ok(full_ens) => {
+                if let Some(ref giver) = giver {
+                    self.notify_receipt(&giver.phone, &format!("{} accepted your gift: {} is now their name.", user.phone, full_ens))
+                        .await;
+                }
+                format!("You are now {}!\nFriends can pay you at this name, e.g. SEND 5 TXTC TO {}", full_ens, full_ens)
+            }
+            Err(EnsRegistrationError::Invalid(reason)) => format!("{}\nThe gift was cancelled.", reason),
+            Err(EnsRegistrationError::Unavailable(reason)) => {
+                if let Some(ref giver) = giver {
+                    self.notify_receipt(
+                        &giver.phone,
+                        &format!("Your gift {}.ttcip.eth to {} was cancelled: {}.", gift.label, user.phone, reason),
+                    )
+                    .await;
+                }
+                format!("❌ {}\nThe gift was cancelled. Reply JOIN <name> to pick your own.", reason)
+            }
+            Err(EnsRegistrationError::Service(msg)) => format!("{}\nReply GIFT ACCEPT to try again.", msg),
+            Err(EnsRegistrationError::Unfunded) => format!("{}\nReply GIFT ACCEPT later.", MINTS_PAUSED_REPLY),
+        }
+    }
+
+    async fn decline_gift(&self, user: &User) -> String {
+        let (Some(gifts), Some(user_repo)) = (&self.name_gifts, &self.user_repo) else {
+            return "Name gifts are not available.".to_string();
+        };
+        match gifts.decline(user.id).await {
+            Ok(Some(gift)) => {
+                if let Ok(Some(giver)) = user_repo.find_by_id(gift.giver_id).await {
+                    self.notify_receipt(&giver.phone, &format!("{} declined your gift {}.ttcip.eth.", user.phone, gift.label))
+                        .await;
+                }
+                format!("Gift of {}.ttcip.eth declined.", gift.label)
+            }
+            Ok(None) => "No name gift waiting for you.".to_string(),
+            Err(e) => {
+                tracing::error!(user = %user.id, "Failed to decline name gift: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_gift() {
+        assert_eq!(
+            parse_gift(&["name", "Mary", "+254700000001"]),
+            Ok(GiftAction::Offer { label: "mary".to_string(), recipient: "+254700000001".to_string() })
+        );
+        assert_eq!(
+            parse_gift(&["NAME", "mary.ttcip.eth", "aunt", "mary"]),
+            Ok(GiftAction::Offer { label: "mary".to_string(), recipient: "aunt mary".to_string() })
+        );
+        assert_eq!(parse_gift(&["accept"]), Ok(GiftAction::Accept));
+        assert_eq!(parse_gift(&["DECLINE"]), Ok(GiftAction::Decline));
+        assert_eq!(parse_gift(&["NAME", "mary"]), Err(GIFT_USAGE));
+        assert_eq!(parse_gift(&[]), Err(GIFT_USAGE));
+    }
+}
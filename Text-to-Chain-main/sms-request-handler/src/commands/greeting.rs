@@ -0,0 +1,119 @@
+//! GREETING [<words> | OFF]: a phrase that proves a text came from us
+//!
+//! The user picks it during onboarding or with GREETING, and it heads every
+//! PIN prompt and withdrawal confirmation sent to them (`Command::greeted`,
+//! WalletConnect signing requests, agent cash-outs, guardian approvals). A
+//! spoofed text can't know it. It is stored encrypted like the phone number.
+
+use super::parser::CommandProcessor;
+
+/// Longest greeting, so it stays a short first line
+pub const MAX_GREETING_LEN: usize = 24;
+
+pub(super) const GREETING_USAGE: &str =
+    "Reply GREETING <words only you know>, e.g. GREETING purple llama.\nWe'll start PIN and withdrawal texts with it.";
+
+/// A greeting the user can set: a few words, never a number that could be a PIN
+pub(super) fn check_greeting(input: &str) -> Result<String, String> {
+    let greeting = input.split_whitespace().collect::<Vec<_>>().join(" ");
+    if greeting.chars().count() < 3 || greeting.chars().count() > MAX_GREETING_LEN {
+        return Err(format!("Greeting must be 3-{} characters.", MAX_GREETING_LEN));
+    }
+    if !greeting.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '\'' | '.' | '!')) {
+        return Err("Use letters, numbers and spaces only.".to_string());
+    }
+    if !greeting.chars().any(char::is_alphabetic) {
+        return Err("Use words, not numbers - never your PIN.".to_string());
+    }
+    Ok(greeting)
+}
+
+/// Put the recipient's greeting on the first line of a sensitive message
+pub fn with_greeting(greeting: Option<&str>, body: &str) -> String {
+    match greeting {
+        Some(greeting) => format!("\"{}\"\n{}", greeting, body),
+        None => body.to_string(),
+    }
+}
+
+impl CommandProcessor {
+    /// GREETING shows it, GREETING <words> sets it, GREETING OFF removes it
+    pub(super) async fn greeting_response(&self, from: &str, arg: Option<&str>) -> String {
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        match user_repo.exists(from).await {
+            Ok(true) => {}
+            Ok(false) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        let (greeting, reply) = match arg {
+            None => {
+                return match user_repo.greeting(from).await {
+                    Ok(Some(greeting)) => format!(
+                        "Your greeting: \"{}\"\nPIN and withdrawal texts from us start with it. A text without it isn't from us.",
+                        greeting
+                    ),
+                    Ok(None) => format!("No greeting set.\n{}", GREETING_USAGE),
+                    Err(_) => "Error. Try later.".to_string(),
+                };
+            }
+            Some(arg) if arg.eq_ignore_ascii_case("OFF") => {
+                (None, "Greeting removed. PIN and withdrawal texts will no longer start with it.".to_string())
+            }
+            Some(arg) => match check_greeting(arg) {
+                Ok(greeting) => {
+                    let reply = format!(
+                        // The first line goes to the audit log, so the greeting stays off it
+                        "Greeting set.\nPIN and withdrawal texts from us will start with \"{}\". Never share it.",
+                        greeting
+                    );
+                    (Some(greeting), reply)
+                }
+                Err(reason) => return reason,
+            },
+        };
+
+        if let Err(e) = user_repo.set_greeting(from, greeting.as_deref()).await {
+            tracing::error!("Failed to save greeting: {}", e);
+            return "Error. Try later.".to_string();
+        }
+        reply
+    }
+
+    /// `body` headed by the recipient's greeting, when they have one
+    pub(super) async fn greeted(&self, to: &str, body: String) -> String {
+        let Some(ref user_repo) = self.user_repo else {
+            return body;
+        };
+        match user_repo.greeting(to).await {
+            Ok(greeting) => with_greeting(greeting.as_deref(), &body),
+            Err(e) => {
+                tracing::warn!("Greeting lookup failed: {}", e);
+                body
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_greeting() {
+        assert_eq!(check_greeting("  purple   llama "), Ok("purple llama".to_string()));
+        assert_eq!(check_greeting("Mama's kitchen!"), Ok("Mama's kitchen!".to_string()));
+        assert!(check_greeting("hi").is_err());
+        assert!(check_greeting("a very long greeting that goes on").is_err());
+        assert!(check_greeting("1234").is_err());
+        assert!(check_greeting("<script>").is_err());
+    }
+
+    #[test]
+    fn test_with_greeting() {
+        assert_eq!(with_greeting(Some("purple llama"), "Reply SIGN <PIN>"), "\"purple llama\"\nReply SIGN <PIN>");
+        assert_eq!(with_greeting(None, "Reply SIGN <PIN>"), "Reply SIGN <PIN>");
+    }
+}
@@ -0,0 +1,135 @@
+//! SEND one question at a time
+//!
+//! `SEND` on its own (or `SEND 10 [TOKEN]`) asks for the amount, the
+//! recipient and a YES before running the full `SEND <amount> <token> <to>`,
+//! so every check on a normal SEND still applies.
+
+use super::flow::{Flow, FlowData, Step, StepResult};
+use super::parser::{Command, CommandProcessor};
+use super::transfers::INTERNAL_TOKEN;
+
+const AMOUNT: &str = "amount";
+const RECIPIENT: &str = "recipient";
+const SEND_USAGE: &str = "Use: SEND <amount> <token> <recipient>\nExample: SEND 10 TXTC swarnim.ttcip.eth";
+
+/// "10" or "10 usdc" as "10 USDC"; TXTC when no token is given
+fn check_amount(input: &str) -> Result<String, String> {
+    let mut parts = input.split_whitespace();
+    let amount = parts
+        .next()
+        .and_then(|amount| amount.parse::<f64>().ok())
+        .filter(|amount| amount.is_finite() && *amount > 0.0)
+        .ok_or_else(|| "Reply an amount, e.g. 10 or 0.01 ETH".to_string())?;
+    let token = parts.next().map(str::to_uppercase).unwrap_or_else(|| "TXTC".to_string());
+    if parts.next().is_some() || !matches!(token.as_str(), "TXTC" | "ETH" | INTERNAL_TOKEN) {
+        return Err("Supported tokens: TXTC, ETH, USDC".to_string());
+    }
+    Ok(format!("{} {}", amount, token))
+}
+
+fn check_recipient(input: &str) -> Result<String, String> {
+    let recipient = input.trim_end_matches(['.', ',', '!', '?']).trim();
+    if recipient.is_empty() {
+        return Err("Reply a name, phone or address.".to_string());
+    }
+    Ok(recipient.to_string())
+}
+
+fn check_confirm(input: &str) -> Result<String, String> {
+    match input.to_uppercase().as_str() {
+        "YES" | "Y" => Ok("YES".to_string()),
+        "NO" | "N" => Ok("NO".to_string()),
+        _ => Err("Reply YES to send or NO to stop.".to_string()),
+    }
+}
+
+fn is_answer(_: &str, command: &Command) -> bool {
+    // A bare name or address is the recipient, not a lookup
+    matches!(command, Command::Unknown(_) | Command::Lookup { .. })
+}
+
+/// Amount, recipient, YES
+pub(super) struct GuidedSend;
+
+impl Flow for GuidedSend {
+    const NAME: &'static str = "send";
+    const STEPS: &'static [Step] = &[
+        Step {
+            name: AMOUNT,
+            prompt: |_| "How much? e.g. 10 or 0.01 ETH\nReply EXIT to stop".to_string(),
+            accepts: is_answer,
+            validate: check_amount,
+            skippable: false,
+            back: false,
+        },
+        Step {
+            name: RECIPIENT,
+            prompt: |data| format!("Send {} to who?\nReply a name, phone or address", data[AMOUNT]),
+            accepts: is_answer,
+            validate: check_recipient,
+            skippable: false,
+            back: true,
+        },
+        Step {
+            name: "confirm",
+            prompt: |data| format!("Send {} to {}?\nReply YES, BACK to change or EXIT", data[AMOUNT], data[RECIPIENT]),
+            accepts: is_answer,
+            validate: check_confirm,
+            skippable: false,
+            back: true,
+        },
+    ];
+    const TIMEOUT_MINS: i64 = 10;
+    const REMINDER: &'static str = "Finish your SEND:";
+    const EXIT_REPLY: &'static str = "SEND stopped. Nothing was sent.";
+    const EXPIRED_REPLY: &'static str = "That SEND timed out. Nothing was sent.\nStart again with SEND.";
+
+    async fn answer(processor: &CommandProcessor, from: &str, step: &Step, data: &mut FlowData) -> StepResult {
+        if step.name != "confirm" {
+            return StepResult::Next(None);
+        }
+        if data[step.name] == "NO" {
+            return StepResult::Done(Self::EXIT_REPLY.to_string());
+        }
+        let Some((amount, token)) = data[AMOUNT].split_once(' ') else {
+            return StepResult::Done(SEND_USAGE.to_string());
+        };
+        let command = Command::Send {
+            amount: amount.parse().unwrap_or_default(),
+            token: token.to_string(),
+            recipient: data[RECIPIENT].clone(),
+            repeat: false,
+        };
+        StepResult::Done(processor.execute(from, command).await)
+    }
+}
+
+impl CommandProcessor {
+    /// SEND without all its details
+    pub(super) async fn guided_send_response(&self, from: &str, amount: Option<String>) -> String {
+        if self.flow_repo.is_none() {
+            return SEND_USAGE.to_string();
+        }
+        let data = amount.into_iter().map(|amount| (AMOUNT.to_string(), amount)).collect();
+        self.start_flow::<GuidedSend>(from, data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answers_checked() {
+        assert_eq!(check_amount("10"), Ok("10 TXTC".to_string()));
+        assert_eq!(check_amount("0.5 usdc"), Ok("0.5 USDC".to_string()));
+        assert!(check_amount("ten").is_err());
+        assert!(check_amount("-3").is_err());
+        assert_eq!(check_amount("10 bob"), Err("Supported tokens: TXTC, ETH, USDC".to_string()));
+
+        assert_eq!(check_recipient("aunt mary."), Ok("aunt mary".to_string()));
+        assert!(check_recipient("?").is_err());
+        assert_eq!(check_confirm("y"), Ok("YES".to_string()));
+        assert!(check_confirm("maybe").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Onboarding tiers: VERIFY, VERIFY <code>, VERIFY ID
+//!
+//! New users can receive but not send (tier 0). VERIFY texts a code to the
+//! phone itself, not back on the channel the command came in on, so a
+//! linked email can't stand in for the phone; texting it back unlocks small
+//! sends (tier 1). VERIFY ID links to the ID form, and an admin moves the
+//! user to tier 2 once it has been checked. Each tier caps single sends
+//! per token; tokens without a cap are not limited.
+
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use super::approvals::{parse_thresholds, TransferPolicyError};
+use super::parser::{Command, CommandProcessor};
+use crate::config::KycConfig;
+use crate::db::{KycRepository, PhoneVerification, User};
+
+/// Minutes a VERIFY code is valid
+const CODE_TTL_MINUTES: i32 = 10;
+
+#[derive(Debug, thiserror::Error)]
+pub enum KycPolicyError {
+    #[error("Invalid KYC tier limit {0}")]
+    Limit(String),
+}
+
+/// Largest single send per upper-case token, for tiers 1 and 2
+#[derive(Debug, Clone, Default)]
+pub(super) struct TierLimits {
+    tier1: HashMap<String, f64>,
+    tier2: HashMap<String, f64>,
+}
+
+/// Why an outgoing command is refused at the user's tier
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum TierRefusal {
+    ReceiveOnly,
+    OverLimit { tier: i16, limit: f64 },
+}
+
+impl TierLimits {
+    fn limit(&self, tier: i16, token: &str) -> Option<f64> {
+        let limits = if tier >= 2 { &self.tier2 } else { &self.tier1 };
+        limits.get(&token.to_uppercase()).copied()
+    }
+
+    pub(super) fn check(&self, tier: i16, token: &str, amount: f64) -> Option<TierRefusal> {
+        if tier <= 0 {
+            return Some(TierRefusal::ReceiveOnly);
+        }
+        self.limit(tier, token).filter(|limit| amount > *limit).map(|limit| TierRefusal::OverLimit { tier, limit })
+    }
+
+    /// "20 USDC, 1000 TXTC" for a tier (None = no limits)
+    fn describe(&self, tier: i16) -> Option<String> {
+        let limits = if tier >= 2 { &self.tier2 } else { &self.tier1 };
+        let mut entries: Vec<String> = limits.iter().map(|(token, limit)| format!("{} {}", limit, token)).collect();
+        entries.sort_unstable();
+        (!entries.is_empty()).then(|| entries.join(", "))
+    }
+}
+
+/// Tier limits and the tiers themselves
+#[derive(Clone)]
+pub struct KycPolicy {
+    limits: TierLimits,
+    id_url: String,
+    repo: KycRepository,
+}
+
+impl KycPolicy {
+    /// None unless `KYC_TIERS` is on
+    pub fn from_config(config: &KycConfig, repo: KycRepository) -> Result<Option<Self>, KycPolicyError> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        let parse = |spec: &str| parse_thresholds(spec).map_err(|TransferPolicyError::Threshold(entry)| KycPolicyError::Limit(entry));
+        Ok(Some(Self {
+            limits: TierLimits { tier1: parse(&config.tier1_limits)?, tier2: parse(&config.tier2_limits)? },
+            id_url: config.id_url.trim().to_string(),
+            repo,
+        }))
+    }
+}
+
+impl CommandProcessor {
+    /// Refusal for an outgoing command over the sender's tier; None lets it run
+    pub(super) async fn kyc_hold(&self, from: &str, command: &Command) -> Option<String> {
+        let (policy, user_repo) = (self.kyc.as_ref()?, self.user_repo.as_ref()?);
+        let (amount, token) = command.outgoing()?;
+        // Unknown senders get the command's own "JOIN first" reply
+        let user = user_repo.find_by_phone(from).await.ok().flatten()?;
+        let tier = match policy.repo.tier(user.id).await {
+            Ok(tier) => tier.unwrap_or_default(),
+            Err(e) => {
+                tracing::error!(user = %user.id, "KYC tier lookup failed: {}", e);
+                return Some("Error. Try later.".to_string());
+            }
+        };
+        let refusal = policy.limits.check(tier, token, amount)?;
+        tracing::info!(user = %user.id, tier, command = command.name(), amount, token, "Command over KYC tier");
+        Some(match refusal {
+            TierRefusal::ReceiveOnly => match policy.limits.limit(1, token) {
+                Some(limit) => format!("Your account can receive but not send yet.\nReply VERIFY to unlock sends up to {} {}.", limit, token.to_uppercase()),
+                None => "Your account can receive but not send yet.\nReply VERIFY to unlock sending.".to_string(),
+            },
+            TierRefusal::OverLimit { tier: 1, limit } if !policy.id_url.is_empty() => {
+                format!("Your limit is {} {} per send.\nReply VERIFY ID to raise it.", limit, token.to_uppercase())
+            }
+            TierRefusal::OverLimit { limit, .. } => format!("Your limit is {} {} per send.", limit, token.to_uppercase()),
+        })
+    }
+
+    /// VERIFY | VERIFY <code> | VERIFY ID
+    pub(super) async fn verify_response(&self, from: &str, arg: Option<&str>) -> String {
+        let (Some(policy), Some(user_repo)) = (&self.kyc, &self.user_repo) else {
+            return "Verification is not needed.".to_string();
+        };
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let tier = match policy.repo.tier(user.id).await {
+            Ok(tier) => tier.unwrap_or_default(),
+            Err(e) => {
+                tracing::error!(user = %user.id, "KYC tier lookup failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        match arg {
+            None if tier == 0 => self.send_phone_code(policy, &user).await,
+            None => {
+                let limits = policy.limits.describe(tier).unwrap_or_else(|| "no limit".to_string());
+                let next = if tier < 2 && !policy.id_url.is_empty() { "\nReply VERIFY ID to raise it." } else { "" };
+                format!("Verified (tier {}).\nSend limit: {} per send.{}", tier, limits, next)
+            }
+            Some(id) if id.eq_ignore_ascii_case("ID") => {
+                if tier == 0 {
+                    "Reply VERIFY to confirm your number first.".to_string()
+                } else if tier >= 2 {
+                    "Your ID is already verified.".to_string()
+                } else if policy.id_url.is_empty() {
+                    "ID checks are not available yet.".to_string()
+                } else {
+                    format!("Submit your ID here:\n{}?ref={}\nYour limit goes up once it's checked.", policy.id_url, user.id)
+                }
+            }
+            Some(code) => match policy.repo.verify_phone_code(user.id, code).await {
+                Ok(PhoneVerification::Verified) => {
+                    tracing::info!(user = %user.id, "Phone verified for KYC");
+                    let limits = policy.limits.describe(1.max(tier)).unwrap_or_else(|| "no limit".to_string());
+                    format!("Number verified. You can now send up to {} per send.", limits)
+                }
+                Ok(PhoneVerification::WrongCode) => "Wrong code. Check the SMS and try again.".to_string(),
+                Ok(PhoneVerification::NoPending) => "No code waiting.\nReply VERIFY to get a new one.".to_string(),
+                Err(e) => {
+                    tracing::error!(user = %user.id, "KYC code check failed: {}", e);
+                    "Error. Try later.".to_string()
+                }
+            },
+        }
+    }
+
+    /// Text a VERIFY code to the user's phone
+    async fn send_phone_code(&self, policy: &KycPolicy, user: &User) -> String {
+        if self.notifier.is_none() {
+            return "Verification is not available right now.".to_string();
+        }
+        let code = rand::thread_rng().gen_range(100_000..1_000_000).to_string();
+        if let Err(e) = policy.repo.start_phone_code(user.id, &code, CODE_TTL_MINUTES).await {
+            tracing::error!(user = %user.id, "Failed to store KYC code: {}", e);
+            return "Error. Try later.".to_string();
+        }
+        self.notify_receipt(&user.phone, &format!("Your TextChain code is {}.\nReply VERIFY {} within {} min.", code, code, CODE_TTL_MINUTES))
+            .await;
+        format!("Code sent by SMS.\nReply VERIFY <code> within {} min.", CODE_TTL_MINUTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> TierLimits {
+        TierLimits {
+            tier1: parse_thresholds("USDC:20,TXTC:1000").unwrap(),
+            tier2: parse_thresholds("USDC:500").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_tier_limits() {
+        let limits = limits();
+        assert_eq!(limits.check(0, "USDC", 1.0), Some(TierRefusal::ReceiveOnly));
+        assert_eq!(limits.check(1, "usdc", 20.0), None);
+        assert_eq!(limits.check(1, "USDC", 25.0), Some(TierRefusal::OverLimit { tier: 1, limit: 20.0 }));
+        assert_eq!(limits.check(2, "USDC", 25.0), None);
+        assert_eq!(limits.check(2, "USDC", 600.0), Some(TierRefusal::OverLimit { tier: 2, limit: 500.0 }));
+        // Tokens without a cap at the tier
+        assert_eq!(limits.check(1, "ETH", 5.0), None);
+        assert_eq!(limits.check(2, "TXTC", 5000.0), None);
+        assert_eq!(limits.describe(1).as_deref(), Some("1000 TXTC, 20 USDC"));
+    }
+}
@@ -0,0 +1,205 @@
+//! Bare ENS name or 0x address: say who it is
+//!
+//! A message that is only `alice.ttcip.eth`, `vitalik.eth` or a `0x` address
+//! is answered straight away with what we know about it: whether a name is
+//! one of our subdomains and where it points, whether the address belongs to
+//! a TextChain user (by their name, never their number), and its mainnet ENS
+//! record. It doesn't start a flow or need a wallet, so it works as a quick
+//! check before a SEND.
+
+use ethers::types::Address;
+
+use super::parser::{is_ttcip_name, CommandProcessor};
+use crate::sms::display::short_address;
+use crate::sms::{NumberLocale, SmsDisplay};
+use crate::wallet::address::{check_words_line, checksummed, parse_address};
+use crate::wallet::{create_chain_provider, Chain};
+
+/// The whole message, when it is an ENS name or looks like an address
+pub(super) fn lookup_target(text: &str) -> Option<&str> {
+    let text = text.trim();
+    if text.split_whitespace().count() != 1 {
+        return None;
+    }
+    let hex = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X"));
+    if hex.is_some_and(|hex| hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit())) {
+        return Some(text);
+    }
+    let labels: Vec<&str> = text.split('.').collect();
+    let is_name = labels.len() >= 2
+        && labels.last().is_some_and(|tld| tld.eq_ignore_ascii_case("eth"))
+        && labels.iter().all(|l| !l.is_empty() && l.chars().all(|c| c.is_alphanumeric() || c == '-'));
+    is_name.then_some(text)
+}
+
+/// Whether the address is a TextChain user's wallet
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Membership {
+    User { name: Option<String> },
+    NotUser,
+    Unknown,
+}
+
+/// What a lookup found, ready to format
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum LookupReport {
+    Name {
+        name: String,
+        /// `*.ttcip.eth`
+        ours: bool,
+        /// Where it points; None = no record
+        address: Option<Address>,
+        member: Membership,
+    },
+    Address {
+        address: Address,
+        member: Membership,
+        /// Mainnet primary name; Err = the lookup failed
+        primary_name: Result<Option<String>, ()>,
+    },
+    /// The name's record couldn't be read
+    Unavailable { name: String },
+}
+
+impl Membership {
+    fn line(&self) -> Option<String> {
+        match self {
+            Membership::User { name: Some(name) } => Some(format!("TextChain user: {}", name)),
+            Membership::User { name: None } => Some("TextChain user: yes".to_string()),
+            Membership::NotUser => Some("TextChain user: no".to_string()),
+            Membership::Unknown => None,
+        }
+    }
+}
+
+/// No numbers in these, so every locale reads the same
+impl SmsDisplay for LookupReport {
+    fn sms_string(&self, _locale: NumberLocale) -> String {
+        let lines: Vec<String> = match self {
+            LookupReport::Name { name, ours: true, address: None, .. } => {
+                vec![name.clone(), "TextChain name, not registered yet.".to_string()]
+            }
+            LookupReport::Name { name, ours: false, address: None, .. } => {
+                vec![name.clone(), "No mainnet ENS record.".to_string()]
+            }
+            LookupReport::Name { name, ours, address: Some(address), member } => {
+                let source = if *ours { "TextChain name" } else { "Mainnet ENS" };
+                let mut lines = vec![name.clone(), format!("{} -> {}", source, checksummed(address))];
+                lines.extend(member.line());
+                lines.push(check_words_line(&checksummed(address)).trim_start().to_string());
+                lines
+            }
+            LookupReport::Address { address, member, primary_name } => {
+                let mut lines = vec![short_address(&checksummed(address))];
+                lines.extend(member.line());
+                lines.push(match primary_name {
+                    Ok(Some(name)) => format!("Mainnet name: {}", name),
+                    Ok(None) => "Mainnet name: none".to_string(),
+                    Err(()) => "Mainnet name: unavailable".to_string(),
+                });
+                lines
+            }
+            LookupReport::Unavailable { name } => vec![name.clone(), "Lookup failed. Try later.".to_string()],
+        };
+        lines.join("\n")
+    }
+}
+
+impl CommandProcessor {
+    /// Reply to a bare name or address
+    pub(super) async fn lookup_response(&self, target: &str) -> String {
+        let report = if target.starts_with("0x") || target.starts_with("0X") {
+            match parse_address(target) {
+                Ok(address) => self.lookup_address(address).await,
+                Err(e) => return e.sms_message(),
+            }
+        } else {
+            self.lookup_name(&target.to_lowercase()).await
+        };
+        report.to_sms_string()
+    }
+
+    async fn lookup_name(&self, name: &str) -> LookupReport {
+        let address = match self.resolve_ens(name).await {
+            Ok(address) => address,
+            Err(e) => {
+                tracing::warn!(name, "ENS lookup failed: {}", e);
+                return LookupReport::Unavailable { name: name.to_string() };
+            }
+        };
+        let member = match address {
+            Some(address) => self.membership(address).await,
+            None => Membership::Unknown,
+        };
+        LookupReport::Name { name: name.to_string(), ours: is_ttcip_name(name), address, member }
+    }
+
+    async fn lookup_address(&self, address: Address) -> LookupReport {
+        let primary_name = self
+            .ens_cache
+            .lookup_address(&create_chain_provider(Chain::EthereumMainnet), address)
+            .await
+            .map_err(|e| tracing::warn!(?address, "ENS reverse lookup failed: {}", e));
+        LookupReport::Address { address, member: self.membership(address).await, primary_name }
+    }
+
+    async fn membership(&self, address: Address) -> Membership {
+        let Some(ref users) = self.user_repo else {
+            return Membership::Unknown;
+        };
+        match users.find_by_wallet(&format!("{:?}", address)).await {
+            Ok(Some(user)) => Membership::User { name: user.ens_name },
+            Ok(None) => Membership::NotUser,
+            Err(e) => {
+                tracing::warn!(?address, "Lookup of wallet owner failed: {}", e);
+                Membership::Unknown
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "0x742d35Cc6634C0532925a3b844Bc454e4438f44e";
+
+    #[test]
+    fn test_lookup_target() {
+        assert_eq!(lookup_target(" alice.ttcip.eth "), Some("alice.ttcip.eth"));
+        assert_eq!(lookup_target("Vitalik.ETH"), Some("Vitalik.ETH"));
+        assert_eq!(lookup_target(ADDRESS), Some(ADDRESS));
+        assert_eq!(lookup_target("0x742d35cc6634c0532925a3b844bc454e4438f44"), None);
+        for text in ["eth", ".eth", "alice..eth", "alice.com", "SEND 5 USDC alice.eth", "i.e."] {
+            assert_eq!(lookup_target(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_report_snapshots() {
+        let address: Address = ADDRESS.parse().unwrap();
+        let ours = LookupReport::Name {
+            name: "alice.ttcip.eth".to_string(),
+            ours: true,
+            address: Some(address),
+            member: Membership::User { name: Some("alice.ttcip.eth".to_string()) },
+        };
+        insta::assert_snapshot!(ours.to_sms_string(), @r"
+        alice.ttcip.eth
+        TextChain name -> 0x742d35Cc6634C0532925a3b844Bc454e4438f44e
+        TextChain user: alice.ttcip.eth
+        Check words: promote topic armed
+        ");
+        let free = LookupReport::Name { name: "bob.ttcip.eth".to_string(), ours: true, address: None, member: Membership::Unknown };
+        insta::assert_snapshot!(free.to_sms_string(), @r"
+        bob.ttcip.eth
+        TextChain name, not registered yet.
+        ");
+        let reverse = LookupReport::Address { address, member: Membership::NotUser, primary_name: Ok(Some("shop.eth".to_string())) };
+        insta::assert_snapshot!(reverse.to_sms_string(), @r"
+        0x742d...f44e
+        TextChain user: no
+        Mainnet name: shop.eth
+        ");
+    }
+}
@@ -0,0 +1,198 @@
+//! Command counts per market
+//!
+//! Every processed command is counted by name, the sender's calling code
+//! and carrier, and whether it went through, so signup conversion and
+//! failure rates can be compared across countries and networks.
+//! `GET /metrics/commands` reports the counts since startup.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::features::NOT_AVAILABLE_REPLY;
+use crate::sms::lookup::calling_code;
+use crate::sms::CarrierLookup;
+
+/// Country label for numbers that aren't E.164
+const UNKNOWN_COUNTRY: &str = "other";
+/// Carrier label before (or without) a lookup
+const UNKNOWN_CARRIER: &str = "unknown";
+
+/// First words of replies to commands that did not go through
+const FAILURE_PREFIXES: [&str; 12] = [
+    "Error",
+    "Failed",
+    "DB offline",
+    "Database error",
+    "Network error",
+    "No wallet",
+    "Invalid",
+    "Insufficient",
+    "Not enough",
+    "Wrong",
+    "Transfer failed",
+    "Usage",
+];
+
+/// Command name, calling code, carrier
+type SegmentKey = (&'static str, String, String);
+
+/// How a command went, judged from its reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ok,
+    Failed,
+    /// Switched off by a feature flag
+    Unavailable,
+}
+
+impl Outcome {
+    pub fn of(command: &str, reply: &str) -> Self {
+        if reply == NOT_AVAILABLE_REPLY {
+            Outcome::Unavailable
+        } else if command == "UNKNOWN" || FAILURE_PREFIXES.iter().any(|prefix| reply.starts_with(prefix)) {
+            Outcome::Failed
+        } else {
+            Outcome::Ok
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Counts {
+    total: u64,
+    failed: u64,
+    unavailable: u64,
+}
+
+/// Counts for one command from one country and carrier
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandSegment {
+    pub command: &'static str,
+    /// E.164 calling code, or `other`
+    pub country: String,
+    /// Carrier from Twilio Lookup, or `unknown`
+    pub carrier: String,
+    pub total: u64,
+    pub failed: u64,
+    pub unavailable: u64,
+}
+
+/// Everything counted since `since`, as reported by `/metrics/commands`
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandReport {
+    pub since: DateTime<Utc>,
+    pub segments: Vec<CommandSegment>,
+}
+
+/// Shared per-segment command counters
+#[derive(Clone)]
+pub struct CommandMetrics {
+    since: DateTime<Utc>,
+    counts: Arc<Mutex<HashMap<SegmentKey, Counts>>>,
+    /// Carrier of each sender (None = every carrier is `unknown`)
+    lookup: Option<CarrierLookup>,
+}
+
+impl Default for CommandMetrics {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl CommandMetrics {
+    pub fn new(lookup: Option<CarrierLookup>) -> Self {
+        Self { since: Utc::now(), counts: Arc::default(), lookup }
+    }
+
+    /// Calling code and carrier of a sender
+    pub fn segment(&self, phone: &str) -> (String, String) {
+        let country = calling_code(phone).unwrap_or(UNKNOWN_COUNTRY).to_string();
+        let carrier = self
+            .lookup
+            .as_ref()
+            .and_then(|lookup| lookup.carrier(phone))
+            .unwrap_or_else(|| UNKNOWN_CARRIER.to_string());
+        (country, carrier)
+    }
+
+    /// Count a processed command
+    pub fn record(&self, phone: &str, command: &'static str, reply: &str) {
+        let (country, carrier) = self.segment(phone);
+        let outcome = Outcome::of(command, reply);
+        if let Ok(mut counts) = self.counts.lock() {
+            let counts = counts.entry((command, country, carrier)).or_default();
+            counts.total += 1;
+            match outcome {
+                Outcome::Ok => {}
+                Outcome::Failed => counts.failed += 1,
+                Outcome::Unavailable => counts.unavailable += 1,
+            }
+        }
+    }
+
+    /// Times each command went through, over every market; ranks
+    /// suggestions for unrecognized input
+    pub fn usage(&self) -> HashMap<&'static str, u64> {
+        let mut usage = HashMap::new();
+        if let Ok(counts) = self.counts.lock() {
+            for ((command, _, _), counts) in counts.iter() {
+                *usage.entry(*command).or_default() += counts.total - counts.failed - counts.unavailable;
+            }
+        }
+        usage
+    }
+
+    pub fn report(&self) -> CommandReport {
+        let mut segments: Vec<CommandSegment> = self
+            .counts
+            .lock()
+            .map(|counts| {
+                counts
+                    .iter()
+                    .map(|((command, country, carrier), counts)| CommandSegment {
+                        command,
+                        country: country.clone(),
+                        carrier: carrier.clone(),
+                        total: counts.total,
+                        failed: counts.failed,
+                        unavailable: counts.unavailable,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        segments.sort_by(|a, b| (a.command, &a.country, &a.carrier).cmp(&(b.command, &b.country, &b.carrier)));
+        CommandReport { since: self.since, segments }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome_from_reply() {
+        assert_eq!(Outcome::of("BALANCE", "Balance: 10 TXTC"), Outcome::Ok);
+        assert_eq!(Outcome::of("SEND", "No wallet. Reply JOIN first."), Outcome::Failed);
+        assert_eq!(Outcome::of("SWAP", NOT_AVAILABLE_REPLY), Outcome::Unavailable);
+        assert_eq!(Outcome::of("UNKNOWN", "Reply MENU for commands"), Outcome::Failed);
+    }
+
+    #[test]
+    fn test_record_segments_by_country() {
+        let metrics = CommandMetrics::default();
+        metrics.record("+254712345678", "JOIN", "Welcome!");
+        metrics.record("+254712345679", "JOIN", "Error. Try later.");
+        metrics.record("+2348012345678", "JOIN", "Welcome!");
+
+        let report = metrics.report();
+        assert_eq!(report.segments.len(), 2);
+        let kenya = &report.segments[1];
+        assert_eq!((kenya.country.as_str(), kenya.carrier.as_str()), ("254", "unknown"));
+        assert_eq!((kenya.total, kenya.failed), (2, 1));
+        assert_eq!(report.segments[0].country, "234");
+        assert_eq!(metrics.usage().get("JOIN"), Some(&2));
+    }
+}
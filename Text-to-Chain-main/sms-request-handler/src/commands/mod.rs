@@ -1,3 +1,33 @@
+pub mod agents;
+pub mod alerts;
+pub mod allowances;
+pub mod approvals;
+pub mod beta;
+pub mod bills;
+pub mod contacts;
+pub mod email;
+pub mod flow;
+pub mod gifts;
+pub mod greeting;
+pub mod guided_send;
+pub mod kyc;
+pub mod lookup;
+pub mod metrics;
+pub mod name_market;
+pub mod notify;
+pub mod onboarding;
 pub mod parser;
+pub mod payment_request;
+pub mod receipts;
+pub mod referrals;
+pub mod repeat_send;
+pub mod savings;
+pub mod send_minimums;
+pub mod send_queue;
+pub mod statements;
+pub mod suggestions;
+pub mod transfers;
+pub mod walletconnect;
+pub mod withdrawal_allowlist;
 
 pub use parser::CommandProcessor;
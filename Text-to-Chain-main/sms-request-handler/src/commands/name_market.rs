@@ -0,0 +1,264 @@
+//! Selling ENS names between users: SELL NAME <name> <price>, BUY NAME <name>
+//!
+//! A user lists their own `<name>.ttcip.eth` at a price in USDC and any user
+//! without a name can buy it from their cash balance. The price sits in
+//! escrow while the backend points the name at the buyer's wallet and hands
+//! them ownership; the seller is then paid and both users' names are updated
+//! together (see `db::NameListingRepository`). If the chain update fails the
+//! buyer is refunded and the name stays listed. If the name moved but the
+//! sale can't be settled, settling is retried and operators are alerted
+//! when it still fails, since the price is then stuck in escrow.
+
+use std::time::Duration;
+
+use serde_json::json;
+
+use super::bills::label;
+use super::parser::CommandProcessor;
+use crate::alerts::{self, Alert};
+use crate::db::{user_account, NameListing, NameListingRepository, NameSaleError, User};
+use crate::money::{Currency, Money};
+
+pub(super) const SELL_NAME_USAGE: &str = "Usage: SELL NAME <name> <price in USDC>, or SELL NAME <name> OFF";
+pub(super) const BUY_NAME_USAGE: &str = "Usage: BUY NAME <name>";
+
+/// Tries at settling a sale whose name already moved
+const SETTLE_ATTEMPTS: u32 = 3;
+/// Wait before the first retry, growing with each attempt
+const SETTLE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// Label from `alice` or `alice.ttcip.eth`, as typed
+fn name_label(input: &str) -> String {
+    let label = input.to_lowercase();
+    label.strip_suffix(".ttcip.eth").unwrap_or(&label).to_string()
+}
+
+/// SELL arguments after the keyword: NAME <name> <price | OFF>
+pub(super) fn parse_sell_name(args: &[&str]) -> Result<(String, Option<Money>), &'static str> {
+    match args {
+        [keyword, label, price] if keyword.eq_ignore_ascii_case("NAME") => {
+            if price.eq_ignore_ascii_case("OFF") {
+                return Ok((name_label(label), None));
+            }
+            match Money::parse(price.trim_start_matches('$'), Currency::USDC) {
+                Ok(price) if price.is_positive() => Ok((name_label(label), Some(price))),
+                _ => Err(SELL_NAME_USAGE),
+            }
+        }
+        _ => Err(SELL_NAME_USAGE),
+    }
+}
+
+/// BUY arguments after the keyword: NAME <name> [YES]
+pub(super) fn parse_buy_name(args: &[&str]) -> Result<(String, bool), &'static str> {
+    match args {
+        [keyword, label] if keyword.eq_ignore_ascii_case("NAME") => Ok((name_label(label), false)),
+        [keyword, label, yes] if keyword.eq_ignore_ascii_case("NAME") && yes.eq_ignore_ascii_case("YES") => {
+            Ok((name_label(label), true))
+        }
+        _ => Err(BUY_NAME_USAGE),
+    }
+}
+
+impl CommandProcessor {
+    /// SELL NAME <name> <price> lists the user's name; OFF takes it down
+    pub(super) async fn sell_name_response(&self, from: &str, label: &str, price: Option<Money>) -> String {
+        let (Some(listings), Some(user_repo)) = (&self.name_market, &self.user_repo) else {
+            return "Name sales are not available.".to_string();
+        };
+        let seller = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let full_name = format!("{}.ttcip.eth", label);
+        if seller.ens_name.as_deref() != Some(full_name.as_str()) {
+            return format!("{} isn't your name. You can only sell your own.", full_name);
+        }
+
+        let Some(price) = price else {
+            return match listings.withdraw(seller.id, label).await {
+                Ok(Some(_)) => format!("{} is no longer for sale.", full_name),
+                Ok(None) => format!("{} isn't listed.", full_name),
+                Err(e) => {
+                    tracing::error!(seller = %seller.id, "Failed to withdraw name listing: {}", e);
+                    "Error. Try later.".to_string()
+                }
+            };
+        };
+
+        match listings.list(seller.id, label, price).await {
+            Ok(Some(listing)) => {
+                tracing::info!(listing = %listing.id, seller = %seller.id, name = %full_name, %price, "ENS name listed for sale");
+                format!(
+                    "{} is for sale at {}.\nBuyers reply BUY NAME {}. You'll be paid in cash once it moves.\nSELL NAME {} OFF takes it down.",
+                    full_name, price.format_with_code(2), label, label
+                )
+            }
+            Ok(None) => format!("Someone is buying {} right now.", full_name),
+            Err(e) => {
+                tracing::error!(seller = %seller.id, "Failed to list name: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    /// BUY NAME <name> quotes the price; BUY NAME <name> YES buys it
+    pub(super) async fn buy_name_response(&self, from: &str, label: &str, confirmed: bool) -> String {
+        let (Some(listings), Some(user_repo)) = (&self.name_market, &self.user_repo) else {
+            return "Name sales are not available.".to_string();
+        };
+        let buyer = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let full_name = format!("{}.ttcip.eth", label);
+        if let Some(ref name) = buyer.ens_name {
+            return format!("You already have the name {}. A wallet holds one name.", name);
+        }
+
+        let listing = match listings.find_open(label).await {
+            Ok(Some(listing)) => listing,
+            Ok(None) => return format!("{} is not for sale.", full_name),
+            Err(e) => {
+                tracing::error!(name = %full_name, "Failed to look up name listing: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+        if listing.status == "selling" {
+            return format!("Someone is buying {} right now.", full_name);
+        }
+        // A listing outlives a name the seller no longer has
+        let seller = match user_repo.find_by_id(listing.seller_id).await {
+            Ok(Some(seller)) if seller.ens_name.as_deref() == Some(full_name.as_str()) => seller,
+            Ok(_) => return format!("{} is not for sale.", full_name),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        if !confirmed {
+            return format!(
+                "{} is for sale for {} from your cash balance.\nReply BUY NAME {} YES to buy it.",
+                full_name, listing.price.format_with_code(2), label
+            );
+        }
+        self.buy_name(&buyer, &seller, listing).await
+    }
+
+    /// Escrow, move the name on chain, then settle or refund
+    async fn buy_name(&self, buyer: &User, seller: &User, listing: NameListing) -> String {
+        let Some(ref listings) = self.name_market else {
+            return "Name sales are not available.".to_string();
+        };
+        let full_name = listing.full_name();
+        let price = listing.price.format_with_code(2);
+
+        let listing = match listings.escrow(listing.id, buyer.id).await {
+            Ok(listing) => listing,
+            Err(NameSaleError::NotListed) => return format!("{} is not for sale.", full_name),
+            Err(NameSaleError::InsufficientFunds) => {
+                let balance = match self.ledger_repo {
+                    Some(ref ledger) => ledger.balance(&user_account(buyer.id)).await.unwrap_or(0),
+                    None => 0,
+                };
+                return format!(
+                    "Not enough cash: {} costs {} and you have {}.",
+                    full_name,
+                    price,
+                    Money::usdc(balance).format_with_code(2)
+                );
+            }
+            Err(e) => {
+                tracing::error!(listing = %listing.id, "Failed to escrow name purchase: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let tx_hash = match self.point_name(&full_name, &buyer.wallet_address).await {
+            Ok(tx_hash) => tx_hash,
+            Err(e) => {
+                tracing::error!(listing = %listing.id, name = %full_name, "ENS transfer failed: {}", e);
+                if let Err(e) = listings.refund(&listing).await {
+                    tracing::error!(listing = %listing.id, "Failed to refund name purchase: {}", e);
+                    return "Error. Try later.".to_string();
+                }
+                return format!("Couldn't move {} to you right now. Your {} was returned.", full_name, price);
+            }
+        };
+
+        if let Err(e) = settle_sale(listings, &listing, tx_hash.as_deref()).await {
+            // The name already points at the buyer, so the escrow can't be
+            // refunded; operators have to settle it
+            tracing::error!(listing = %listing.id, name = %full_name, "Name moved on chain but the sale wasn't settled: {}", e);
+            alerts::raise(Alert::name_sale_unsettled(&listing.id.to_string(), &full_name, listing.price, &e.to_string()));
+            return format!("{} now points at your wallet, but the sale is still being finished. Support has been told; your {} is held until then.", full_name, price);
+        }
+        self.ens_cache.invalidate(&full_name);
+        tracing::info!(listing = %listing.id, buyer = %buyer.id, seller = %seller.id, name = %full_name, "ENS name sold");
+
+        self.notify_receipt(
+            &seller.phone,
+            &format!("{} sold to {} for {}. The money is in your cash balance.", full_name, label(buyer), price),
+        )
+        .await;
+        format!("You are now {}!\nPaid {}. Friends can pay you at this name.", full_name, price)
+    }
+
+    /// Point the name at `wallet` and make it the owner, via the backend;
+    /// the transaction hash when there is one
+    async fn point_name(&self, full_name: &str, wallet: &str) -> Result<Option<String>, String> {
+        let response = reqwest::Client::new()
+            .post(format!("{}/api/ens/repoint", self.backend_url))
+            .json(&json!({ "ensName": full_name, "walletAddress": wallet }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let body = response.json::<serde_json::Value>().await.unwrap_or_default();
+        if status.is_success() {
+            return Ok(body["txHash"].as_str().map(str::to_string));
+        }
+        Err(body["error"].as_str().map(str::to_string).unwrap_or_else(|| status.to_string()))
+    }
+}
+
+/// Settle a sale whose name already moved, retrying database failures
+async fn settle_sale(listings: &NameListingRepository, listing: &NameListing, tx_hash: Option<&str>) -> Result<(), NameSaleError> {
+    let mut attempt = 1;
+    loop {
+        match listings.settle(listing, tx_hash).await {
+            Err(NameSaleError::Database(e)) if attempt < SETTLE_ATTEMPTS => {
+                tracing::warn!(listing = %listing.id, attempt, "Settling name sale failed, retrying: {}", e);
+                tokio::time::sleep(SETTLE_RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sell_name() {
+        assert_eq!(parse_sell_name(&["name", "Alice", "5"]), Ok(("alice".to_string(), Some(Money::usdc(5_000_000)))));
+        assert_eq!(parse_sell_name(&["NAME", "alice.ttcip.eth", "$2.50"]), Ok(("alice".to_string(), Some(Money::usdc(2_500_000)))));
+        assert_eq!(parse_sell_name(&["NAME", "alice", "off"]), Ok(("alice".to_string(), None)));
+        assert!(parse_sell_name(&["NAME", "alice"]).is_err());
+        assert!(parse_sell_name(&["NAME", "alice", "0"]).is_err());
+        assert!(parse_sell_name(&["NAME", "alice", "free"]).is_err());
+        assert!(parse_sell_name(&["NAME", "alice", "1e3"]).is_err());
+        assert!(parse_sell_name(&["NAME", "alice", "0.0000001"]).is_err());
+        assert!(parse_sell_name(&["alice", "5"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_buy_name() {
+        assert_eq!(parse_buy_name(&["name", "Alice"]), Ok(("alice".to_string(), false)));
+        assert_eq!(parse_buy_name(&["NAME", "alice.ttcip.eth", "yes"]), Ok(("alice".to_string(), true)));
+        assert!(parse_buy_name(&["NAME"]).is_err());
+        assert!(parse_buy_name(&["NAME", "alice", "NO"]).is_err());
+    }
+}
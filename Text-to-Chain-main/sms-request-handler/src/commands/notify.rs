@@ -0,0 +1,59 @@
+//! NOTIFY [ALL | DIGEST | OFF]: how deposit and receipt texts arrive
+//!
+//! DIGEST batches them into one summary a day and OFF stops them (see
+//! `notify_digest`). Codes, approvals and low-balance alerts are unaffected.
+
+use super::parser::CommandProcessor;
+use crate::db::NotifyMode;
+
+pub(super) const NOTIFY_USAGE: &str = "Usage: NOTIFY ALL, NOTIFY DIGEST or NOTIFY OFF";
+
+fn describe(mode: NotifyMode, hour: u32, pending: i64) -> String {
+    match mode {
+        NotifyMode::All => "Notifications: ALL. Deposits and receipts are texted right away.".to_string(),
+        NotifyMode::Digest if pending > 0 => format!(
+            "Notifications: DIGEST. One summary a day at {:02}:00 UTC, {} waiting.",
+            hour, pending
+        ),
+        NotifyMode::Digest => format!("Notifications: DIGEST. One summary a day at {:02}:00 UTC.", hour),
+        NotifyMode::Off => "Notifications: OFF. No deposit or receipt texts.\nReply NOTIFY ALL to turn them back on.".to_string(),
+    }
+}
+
+impl CommandProcessor {
+    /// NOTIFY shows the mode; NOTIFY <mode> changes it
+    pub(super) async fn notify_response(&self, from: &str, mode: Option<NotifyMode>) -> String {
+        let (Some(notifications), Some(user_repo)) = (&self.notifications, &self.user_repo) else {
+            return "Notification settings are not available.".to_string();
+        };
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let repo = notifications.repo();
+        let updated = match mode {
+            Some(mode) => repo.set_mode(user.id, mode).await.map(|_| mode),
+            None => repo.mode(user.id).await,
+        };
+        let mode = match updated {
+            Ok(mode) => mode,
+            Err(e) => {
+                tracing::error!(user = %user.id, "Notify settings failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+        let pending = repo.pending(user.id).await.unwrap_or(0);
+        describe(mode, notifications.digest_hour(), pending)
+    }
+
+    /// Deposit or receipt notice: texted, queued for the digest or dropped
+    /// per the recipient's NOTIFY mode (held for quiet hours when texted)
+    pub(super) async fn notify_update(&self, to: &str, body: &str) {
+        match self.notifications {
+            Some(ref notifications) => notifications.deliver(to, body).await,
+            None => self.notify_receipt(to, body).await,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Guided signup flow for new numbers
+//!
+//! START (or the first message from an unknown number) creates a wallet and
+//! walks the user through naming it, setting a PIN, choosing an anti-phishing
+//! greeting (`greeting`) and a first balance check.
+//! It runs on the flow engine (`flow`), so it resumes where it left off on
+//! the next message.
+
+use super::flow::{Flow, FlowData, Step, StepResult};
+use super::greeting::check_greeting;
+use super::parser::{hash_pin, Command, CommandProcessor, EnsRegistrationError, MINTS_PAUSED_REPLY};
+use crate::wallet::address::display_address;
+
+const CHOOSE_NAME: &str = "choose_name";
+const SET_PIN: &str = "set_pin";
+const SET_GREETING: &str = "set_greeting";
+const ALL_SET: &str = "You're all set! Reply MENU for commands.";
+
+/// A single bare word (name, PIN or SKIP) rather than a command
+fn is_step_input(body: &str, command: &Command) -> bool {
+    matches!(command, Command::Unknown(_)) && body.split_whitespace().count() == 1
+}
+
+fn check_pin(pin: &str) -> Result<String, String> {
+    if CommandProcessor::is_valid_pin(pin) {
+        Ok(hash_pin(pin))
+    } else {
+        Err("PIN must be 4-6 digits.".to_string())
+    }
+}
+
+/// Name, PIN, greeting, first BALANCE
+pub(super) struct Onboarding;
+
+impl Flow for Onboarding {
+    const NAME: &'static str = "onboarding";
+    const STEPS: &'static [Step] = &[
+        Step {
+            name: CHOOSE_NAME,
+            prompt: |_| "Step 1/4: Pick a name for name.ttcip.eth\nReply with a name or SKIP".to_string(),
+            accepts: is_step_input,
+            validate: |name| Ok(name.to_lowercase()),
+            skippable: true,
+            back: false,
+        },
+        Step {
+            name: SET_PIN,
+            prompt: |_| "Step 2/4: Set a 4-6 digit PIN\nReply with your PIN or SKIP".to_string(),
+            accepts: is_step_input,
+            validate: check_pin,
+            skippable: true,
+            back: false,
+        },
+        Step {
+            name: SET_GREETING,
+            prompt: |_| {
+                "Step 3/4: Pick a greeting only you know, e.g. purple llama. PIN and withdrawal texts from us will start with it.\nReply with it or SKIP".to_string()
+            },
+            accepts: |_, command| matches!(command, Command::Unknown(_)),
+            validate: check_greeting,
+            skippable: true,
+            back: false,
+        },
+        Step {
+            name: "first_balance",
+            prompt: |_| "Step 4/4: Reply BALANCE to check your wallet".to_string(),
+            accepts: |_, command| *command == Command::Balance,
+            validate: |_| Ok(String::new()),
+            skippable: false,
+            back: false,
+        },
+    ];
+    const TIMEOUT_MINS: i64 = 30 * 24 * 60;
+    const REMINDER: &'static str = "Finish setup:";
+    const EXIT_REPLY: &'static str = "Setup stopped. Your wallet is ready.\nReply MENU for commands.";
+    const EXPIRED_REPLY: &'static str = "Setup timed out. Your wallet is ready.\nReply MENU for commands.";
+
+    async fn answer(processor: &CommandProcessor, from: &str, step: &Step, data: &mut FlowData) -> StepResult {
+        match step.name {
+            CHOOSE_NAME => processor.onboarding_name(from, &data[CHOOSE_NAME]).await,
+            SET_PIN => processor.onboarding_pin(from, &data[SET_PIN]).await,
+            SET_GREETING => processor.onboarding_greeting(from, &data[SET_GREETING]).await,
+            _ => StepResult::Done(format!("{}\n\n{}", processor.balance_response(from).await, ALL_SET)),
+        }
+    }
+}
+
+impl CommandProcessor {
+    /// Unknown numbers that aren't explicitly joining start onboarding
+    pub(super) async fn first_contact(&self, from: &str, command: &Command) -> Option<String> {
+        if matches!(command, Command::Join { .. } | Command::Invite { .. } | Command::Lookup { .. }) {
+            return None;
+        }
+        match self.user_repo.as_ref()?.exists(from).await {
+            Ok(false) => Some(self.start_onboarding(from).await),
+            _ => None,
+        }
+    }
+
+    /// Create the wallet for a new number, or resume an unfinished signup
+    pub(super) async fn start_onboarding(&self, from: &str) -> String {
+        let (Some(_), Some(user_repo)) = (&self.flow_repo, &self.user_repo) else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => {
+                return match self.flow_prompt::<Onboarding>(from).await {
+                    Some(prompt) => format!(
+                        "Welcome back!\nWallet: {}\n\n{}",
+                        display_address(&user.wallet_address),
+                        prompt
+                    ),
+                    None => format!(
+                        "Welcome back!\n\nYour wallet:\n{}\n\nReply BALANCE or DEPOSIT",
+                        display_address(&user.wallet_address)
+                    ),
+                };
+            }
+            Ok(None) => {}
+            Err(e) => {
+                tracing::error!("DB error: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        }
+
+        // Beta mode: numbers not yet allowed in go on the waitlist
+        if let Some(reply) = self.beta_gate(from).await {
+            return reply;
+        }
+
+        let wallet = match self.create_user_wallet(from).await {
+            Ok(wallet) => wallet,
+            Err(msg) => return msg.to_string(),
+        };
+        // Arc wallet is best-effort; cashout creates it lazily otherwise
+        let _ = self.create_arc_wallet(from).await;
+
+        let prompt = self.start_flow::<Onboarding>(from, FlowData::new()).await;
+        tracing::info!(phone = %from, wallet = %wallet.address_string(), "Onboarding started");
+
+        format!("Welcome to TextChain!\nWallet created:\n{}\n\n{}", wallet.address_string(), prompt)
+    }
+
+    /// Register the chosen name for the new wallet
+    async fn onboarding_name(&self, from: &str, name: &str) -> StepResult {
+        let Some(user) = self.find_onboarding_user(from).await else {
+            return StepResult::Done("Error. Try later.".to_string());
+        };
+        match self.register_ens_name(from, &user.wallet_address, name, None).await {
+            Ok(full_ens) => StepResult::Next(Some(format!("Registered {}!", full_ens))),
+            Err(EnsRegistrationError::Invalid(reason)) => StepResult::Retry(reason.to_string()),
+            Err(EnsRegistrationError::Unavailable(reason)) => StepResult::Retry(format!("❌ {}", reason)),
+            Err(EnsRegistrationError::Service(msg)) => StepResult::Retry(msg.to_string()),
+            Err(EnsRegistrationError::Unfunded) => StepResult::Retry(MINTS_PAUSED_REPLY.to_string()),
+        }
+    }
+
+    /// Save the (already hashed) PIN
+    async fn onboarding_pin(&self, from: &str, pin_hash: &str) -> StepResult {
+        let Some(ref user_repo) = self.user_repo else {
+            return StepResult::Retry("DB offline. Try later.".to_string());
+        };
+        if let Err(e) = user_repo.update_pin(from, pin_hash).await {
+            tracing::error!("Failed to save PIN: {}", e);
+            return StepResult::Retry("Error. Try later.".to_string());
+        }
+        StepResult::Next(Some("PIN set!".to_string()))
+    }
+
+    async fn onboarding_greeting(&self, from: &str, greeting: &str) -> StepResult {
+        let Some(ref user_repo) = self.user_repo else {
+            return StepResult::Retry("DB offline. Try later.".to_string());
+        };
+        if let Err(e) = user_repo.set_greeting(from, Some(greeting)).await {
+            tracing::error!("Failed to save greeting: {}", e);
+            return StepResult::Retry("Error. Try later.".to_string());
+        }
+        StepResult::Next(Some(format!("Greeting set: \"{}\"", greeting)))
+    }
+
+    async fn find_onboarding_user(&self, from: &str) -> Option<crate::db::User> {
+        let repo = self.user_repo.as_ref()?;
+        repo.find_by_phone(from).await.ok().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps() {
+        let names: Vec<&str> = Onboarding::STEPS.iter().map(|step| step.name).collect();
+        // Step names match the rows carried over from onboarding_sessions
+        assert_eq!(names, ["choose_name", "set_pin", "set_greeting", "first_balance"]);
+        assert!(check_pin("12ab").is_err());
+        assert_eq!(check_pin("1234"), Ok(hash_pin("1234")));
+    }
+
+    #[test]
+    fn test_step_input_detection() {
+        assert!(is_step_input("alice", &Command::Unknown("ALICE".to_string())));
+        assert!(is_step_input("1234", &Command::Unknown("1234".to_string())));
+        assert!(!is_step_input("BALANCE", &Command::Balance));
+        assert!(!is_step_input("hello there", &Command::Unknown("HELLO THERE".to_string())));
+    }
+}
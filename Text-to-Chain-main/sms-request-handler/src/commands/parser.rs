@@ -1,17 +1,64 @@
 use std::sync::Arc;
+use ethers::providers::Middleware;
+use ethers::types::Address;
 use sha2::Digest;
-use crate::db::{UserRepository, VoucherRepository, DepositRepository, AddressBookRepository};
-use crate::wallet::{AmoyProvider, UserWallet, Chain, MultiChainProvider};
+use super::alerts::{parse_alert, AlertSetting};
+use super::contacts::choose_contact_reply;
+use super::gifts::{parse_gift, GiftAction};
+use super::name_market::{parse_buy_name, parse_sell_name};
+use super::approvals::TransferPolicy;
+use super::notify::NOTIFY_USAGE;
+use super::repeat_send::RepeatSendGuard;
+use super::send_minimums::SendMinimums;
+use super::withdrawal_allowlist::WithdrawalAllowlist;
+use super::send_queue::SendQueue;
+use super::lookup::lookup_target;
+use super::suggestions::is_unrecognized;
+use super::kyc::KycPolicy;
+use super::metrics::CommandMetrics;
+use super::transfers::INTERNAL_TOKEN;
+use crate::config::{ServicesConfig, DEFAULT_ARC_URL, DEFAULT_BACKEND_URL};
+use crate::db::{AuditLogRepository, BalanceAlertRepository, BillRepository, NameGift, NameGiftRepository, NameListingRepository, NotifyMode, UnrecognizedCommandRepository, CampaignRepository, SavingsRepository, StatementRepository, MintCost, Sponsorship, User, UserRepository, VoucherRepository, DepositRepository, AddressBookRepository, FlowRepository, AgentRepository, LedgerRepository, PaymentLinkRepository, WalletConnectRepository, CodeCheck, check_code, CashKind, user_account, micro_to_f64};
+use crate::sms::sim_swap::hold_reply;
+use crate::sms::{MessagePriority, NumberLocale, SimSwapGuard, SmsDisplay, SmsGateway};
+use crate::wallet::{AmoyProvider, UserWallet, Chain, DepositAddresses, MultiChainProvider, KeyVault, create_chain_provider};
+use crate::wallet::address::{check_words_line, display_address, parse_address};
+use crate::wallet::ens_cache::EnsCache;
+use crate::wallet::faucet::Faucet;
+use crate::wallet::payment_uri::TXTC_CHAIN;
+use crate::wallet::savings::SavingsVault;
+use crate::wallet::tokens::format_all_balances;
+use crate::beta::BetaAccess;
+use crate::money::{Currency, Money};
+use crate::email::EmailChannel;
+use crate::events::{EventBus, Topic};
+use crate::receipts::ReceiptSigner;
+use crate::features::{Feature, FeatureFlags, NOT_AVAILABLE_REPLY};
+use crate::kill_switch::KillSwitch;
+use crate::name_policy::NamePolicy;
+use crate::notify_digest::Notifications;
+use crate::payouts::Payouts;
+use crate::rates::{Prices, TokenPrices};
+use crate::referrals::Referrals;
+use crate::walletconnect_bridge::WalletConnectBridge;
+use crate::workers::TaskClass;
 
 /// Parsed SMS command
 #[derive(Debug, Clone, PartialEq)]
 pub enum Command {
     /// Show help/available commands
     Help,
+    /// Start (or resume) guided onboarding
+    Start,
     /// Register a new user with optional ENS name
     Join { ens_name: Option<String> },
+    /// INVITE <code>: sign up with a beta invite or a friend's referral code;
+    /// INVITE alone: the sender's own referral code
+    Invite { code: Option<String> },
     /// Check account balance
     Balance,
+    /// Native and USDC balances on every chain: BALANCE ALL
+    BalanceAll,
     /// Set or change PIN
     Pin { new_pin: Option<String> },
     /// Send money to someone
@@ -19,7 +66,11 @@ pub enum Command {
         amount: f64,
         token: String,
         recipient: String,
+        /// Ends in AGAIN: go ahead even if it repeats a recent transfer
+        repeat: bool,
     },
+    /// SEND with details missing: asks for them one at a time
+    SendGuided { amount: Option<String> },
     /// Check deposit address
     Deposit,
     /// Check transaction history
@@ -39,39 +90,472 @@ pub enum Command {
         from_chain: String,
         to_chain: String,
     },
-    /// Save a contact: SAVE <name> <phone>
-    Save { name: String, phone: String },
+    /// Save a contact: SAVE <name> <phone|0x address>
+    Save { name: String, contact: String },
     /// List contacts
     Contacts,
     /// Switch chain: CHAIN <name>
     SwitchChain { chain: String },
+    /// Agent cash-in/out: CASHIN <phone> <amount> / CASHOUT <phone> <amount>
+    AgentCash { kind: CashKind, customer: String, amount: f64 },
+    /// Confirm a pending cash request: CONFIRM <code>
+    Confirm { code: String },
+    /// Agent float balance
+    Float,
+    /// Ask to be paid via an EIP-681 link: REQUEST <amount> [token]
+    Request { amount: f64, token: String },
+    /// Pair with a dApp over WalletConnect
+    Connect,
+    /// End all WalletConnect sessions
+    Disconnect,
+    /// Approve the pending dApp request: SIGN <PIN>
+    Sign { pin: Option<String> },
+    /// Decline the pending dApp request
+    Reject,
+    /// Let a contract spend wallet tokens: APPROVE <token> <amount> [spender]
+    Approve { token: String, amount: f64, spender: Option<String> },
+    /// Reset an allowance to zero: REVOKE <token> [spender]
+    Revoke { token: String, spender: Option<String> },
+    /// List non-zero allowances
+    Allowances,
+    /// Move wallet USDC into the savings vault: SAVE <amount>
+    SaveFunds { amount: Money },
+    /// Take USDC back out of savings: UNSAVE <amount> | UNSAVE ALL (None)
+    Unsave { amount: Option<Money> },
+    /// Link an email address: EMAIL [<address> | <code> | OFF]
+    Email { arg: Option<String> },
+    /// Signed proof of a transfer: RECEIPT <ref>
+    Receipt { reference: String },
+    /// Who approves large transfers: GUARDIAN [<phone> | OFF]
+    Guardian { arg: Option<String> },
+    /// Low-balance and deposit notifications: ALERT [BELOW <amount> | DEPOSIT]
+    Alert { setting: Option<AlertSetting> },
+    /// Give another user an ENS name: GIFT NAME <label> <recipient> | GIFT ACCEPT | GIFT DECLINE
+    Gift { action: GiftAction },
+    /// Raise the KYC tier: VERIFY | VERIFY <code> | VERIFY ID
+    Verify { arg: Option<String> },
+    /// Monthly cash statement: STATEMENT [<YYYY-MM> | <month name>]
+    Statement { month: Option<String> },
+    /// Stop the last SEND while it waits to go out: CANCEL SEND
+    CancelSend,
+    /// How deposit and receipt texts arrive: NOTIFY [ALL | DIGEST | OFF]
+    Notify { mode: Option<NotifyMode> },
+    /// Ask a customer for payment: BILL <amount> [token] <memo>
+    Bill { amount: f64, token: String, memo: String },
+    /// Pay a merchant's bill: PAY [<code>] [YES]
+    Pay { code: Option<String>, confirmed: bool },
+    /// Anti-phishing phrase on sensitive texts: GREETING [<words> | OFF]
+    Greeting { arg: Option<String> },
+    /// List your ENS name for sale: SELL NAME <name> <price> | SELL NAME <name> OFF
+    SellName { label: String, price: Option<Money> },
+    /// Buy a listed ENS name: BUY NAME <name> [YES]
+    BuyName { label: String, confirmed: bool },
+    /// Open a saved address before its withdrawal cooldown ends: UNLOCK <name> <PIN>
+    Unlock { name: String, pin: Option<String> },
+    /// A bare ENS name or 0x address, answered with who it is
+    Lookup { target: String },
     /// Unknown command
     Unknown(String),
 }
 
+impl Command {
+    /// Worker class used when the command is processed in the background
+    pub fn task_class(&self) -> TaskClass {
+        match self {
+            Command::Send { .. }
+            | Command::Balance
+            | Command::BalanceAll
+            | Command::Redeem { .. }
+            | Command::Swap { .. }
+            | Command::Cashout { .. }
+            | Command::Buy { .. }
+            | Command::Bridge { .. }
+            | Command::SaveFunds { .. }
+            | Command::Unsave { .. }
+            | Command::Gift { action: GiftAction::Accept }
+            | Command::Pay { confirmed: true, .. }
+            | Command::BuyName { confirmed: true, .. }
+            | Command::SwitchChain { .. } => TaskClass::OnChain,
+            Command::AgentCash { .. } | Command::Confirm { .. } => TaskClass::DbHeavy,
+            Command::History | Command::Contacts | Command::Statement { .. } | Command::CancelSend | Command::SendGuided { .. } => TaskClass::DbHeavy,
+            Command::Pay { .. } => TaskClass::DbHeavy,
+            _ => TaskClass::SmsReply,
+        }
+    }
+
+    /// Command name used in metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Help => "HELP",
+            Command::Start => "START",
+            Command::Join { .. } => "JOIN",
+            Command::Invite { .. } => "INVITE",
+            Command::Balance => "BALANCE",
+            Command::BalanceAll => "BALANCE_ALL",
+            Command::Pin { .. } => "PIN",
+            Command::Send { .. } | Command::SendGuided { .. } => "SEND",
+            Command::Deposit => "DEPOSIT",
+            Command::History => "HISTORY",
+            Command::Redeem { .. } => "REDEEM",
+            Command::Swap { .. } => "SWAP",
+            Command::Cashout { .. } => "CASHOUT",
+            Command::Buy { .. } => "BUY",
+            Command::Bridge { .. } => "BRIDGE",
+            Command::Save { .. } => "SAVE_CONTACT",
+            Command::Contacts => "CONTACTS",
+            Command::SwitchChain { .. } => "CHAIN",
+            Command::AgentCash { kind: CashKind::CashIn, .. } => "AGENT_CASHIN",
+            Command::AgentCash { kind: CashKind::CashOut, .. } => "AGENT_CASHOUT",
+            Command::Confirm { .. } => "CONFIRM",
+            Command::Float => "FLOAT",
+            Command::Request { .. } => "REQUEST",
+            Command::Connect => "CONNECT",
+            Command::Disconnect => "DISCONNECT",
+            Command::Sign { .. } => "SIGN",
+            Command::Reject => "REJECT",
+            Command::Approve { .. } => "APPROVE",
+            Command::Revoke { .. } => "REVOKE",
+            Command::Allowances => "ALLOWANCES",
+            Command::SaveFunds { .. } => "SAVE",
+            Command::Unsave { .. } => "UNSAVE",
+            Command::Email { .. } => "EMAIL",
+            Command::Receipt { .. } => "RECEIPT",
+            Command::Guardian { .. } => "GUARDIAN",
+            Command::Alert { .. } => "ALERT",
+            Command::Gift { .. } => "GIFT",
+            Command::Verify { .. } => "VERIFY",
+            Command::Statement { .. } => "STATEMENT",
+            Command::CancelSend => "CANCEL",
+            Command::Notify { .. } => "NOTIFY",
+            Command::Bill { .. } => "BILL",
+            Command::Pay { .. } => "PAY",
+            Command::Greeting { .. } => "GREETING",
+            Command::SellName { .. } => "SELL_NAME",
+            Command::BuyName { .. } => "BUY_NAME",
+            Command::Unlock { .. } => "UNLOCK",
+            Command::Lookup { .. } => "LOOKUP",
+            Command::Unknown(_) => "UNKNOWN",
+        }
+    }
+
+    /// Feature flag gating this command (None = always available)
+    pub fn feature(&self) -> Option<Feature> {
+        match self {
+            Command::Send { .. } | Command::SendGuided { .. } => Some(Feature::Send),
+            Command::Swap { .. } => Some(Feature::Swap),
+            Command::Bridge { .. } => Some(Feature::Bridge),
+            Command::Cashout { .. } => Some(Feature::Cashout),
+            Command::Buy { .. } => Some(Feature::Buy),
+            Command::Redeem { .. } => Some(Feature::Redeem),
+            Command::Request { .. } => Some(Feature::Request),
+            Command::Bill { .. } | Command::Pay { .. } => Some(Feature::Bills),
+            Command::Save { .. } | Command::Contacts | Command::Unlock { .. } => Some(Feature::Contacts),
+            Command::SwitchChain { .. } => Some(Feature::Chain),
+            Command::AgentCash { .. } | Command::Float => Some(Feature::Agents),
+            Command::Connect | Command::Disconnect | Command::Sign { .. } | Command::Reject => {
+                Some(Feature::WalletConnect)
+            }
+            Command::Approve { .. } | Command::Revoke { .. } | Command::Allowances => Some(Feature::Allowances),
+            Command::SaveFunds { .. } | Command::Unsave { .. } => Some(Feature::Savings),
+            _ => None,
+        }
+    }
+
+    /// Audit log action for commands that change state (None = read-only)
+    pub fn audit_action(&self) -> Option<&'static str> {
+        match self {
+            Command::Start | Command::Join { .. } => Some("JOIN"),
+            Command::Invite { .. } => Some("INVITE"),
+            Command::Pin { .. } => Some("PIN"),
+            Command::Send { .. } => Some("SEND"),
+            Command::Redeem { .. } => Some("REDEEM"),
+            Command::Swap { .. } => Some("SWAP"),
+            Command::Cashout { .. } => Some("CASHOUT"),
+            Command::Buy { .. } => Some("BUY"),
+            Command::Bridge { .. } => Some("BRIDGE"),
+            Command::SwitchChain { .. } => Some("CHAIN"),
+            Command::AgentCash { kind: CashKind::CashIn, .. } => Some("AGENT_CASHIN"),
+            Command::AgentCash { kind: CashKind::CashOut, .. } => Some("AGENT_CASHOUT"),
+            Command::Confirm { .. } => Some("CONFIRM"),
+            Command::Sign { .. } => Some("SIGN"),
+            Command::Reject => Some("REJECT"),
+            Command::Approve { .. } => Some("APPROVE"),
+            Command::Revoke { .. } => Some("REVOKE"),
+            Command::SaveFunds { .. } => Some("SAVE"),
+            Command::Unsave { .. } => Some("UNSAVE"),
+            Command::Email { arg: Some(_) } => Some("EMAIL"),
+            Command::Guardian { arg: Some(_) } => Some("GUARDIAN"),
+            Command::Alert { setting: Some(_) } => Some("ALERT"),
+            Command::Gift { .. } => Some("GIFT"),
+            Command::Verify { .. } => Some("VERIFY"),
+            Command::CancelSend => Some("CANCEL_SEND"),
+            Command::Bill { .. } => Some("BILL"),
+            Command::Pay { confirmed: true, .. } => Some("PAY"),
+            Command::Notify { mode: Some(_) } => Some("NOTIFY"),
+            Command::Greeting { arg: Some(_) } => Some("GREETING"),
+            Command::SellName { .. } => Some("SELL_NAME"),
+            Command::BuyName { confirmed: true, .. } => Some("BUY_NAME"),
+            Command::Unlock { pin: Some(_), .. } => Some("UNLOCK"),
+            _ => None,
+        }
+    }
+
+    /// The command as written to the audit log, PINs left out
+    pub(crate) fn audit_detail(&self) -> String {
+        match self {
+            Command::Pin { .. } => "Pin".to_string(),
+            Command::Sign { .. } => "Sign".to_string(),
+            Command::Email { .. } => "Email".to_string(),
+            Command::Verify { .. } => "Verify".to_string(),
+            Command::Greeting { .. } => "Greeting".to_string(),
+            Command::Unlock { name, .. } => format!("Unlock {}", name),
+            other => format!("{:?}", other),
+        }
+    }
+
+    /// Moves funds (or signs for them), so it stops while the kill switch is engaged
+    pub fn moves_money(&self) -> bool {
+        matches!(
+            self,
+            Command::Send { .. }
+                | Command::Redeem { .. }
+                | Command::Swap { .. }
+                | Command::Cashout { .. }
+                | Command::Buy { .. }
+                | Command::Bridge { .. }
+                | Command::AgentCash { .. }
+                | Command::Confirm { .. }
+                | Command::Sign { .. }
+                | Command::Approve { .. }
+                | Command::SaveFunds { .. }
+                | Command::Unsave { .. }
+                | Command::Pay { confirmed: true, .. }
+                | Command::BuyName { confirmed: true, .. }
+        )
+    }
+
+    /// Moves money out or changes security settings, so it waits out a recent SIM swap
+    pub fn sim_swap_sensitive(&self) -> bool {
+        matches!(
+            self,
+            Command::Send { .. }
+                | Command::Cashout { .. }
+                | Command::Bridge { .. }
+                | Command::Approve { .. }
+                | Command::Sign { .. }
+                | Command::Pin { new_pin: Some(_) }
+                | Command::Pay { confirmed: true, .. }
+                | Command::Guardian { arg: Some(_) }
+                | Command::Greeting { arg: Some(_) }
+                | Command::SellName { .. }
+                | Command::BuyName { confirmed: true, .. }
+                | Command::Unlock { .. }
+        )
+    }
+
+    /// PIN prompts and withdrawal confirmations, headed by the user's
+    /// anti-phishing greeting
+    pub fn greeted(&self) -> bool {
+        matches!(
+            self,
+            Command::Pin { .. }
+                | Command::Sign { .. }
+                | Command::Cashout { .. }
+                | Command::Bridge { .. }
+                | Command::Unsave { .. }
+                | Command::Confirm { .. }
+                | Command::Unlock { .. }
+        )
+    }
+
+    /// Amount and token leaving the wallet, checked against the KYC tier
+    pub fn outgoing(&self) -> Option<(f64, &str)> {
+        match self {
+            Command::Send { amount, token, .. }
+            | Command::Cashout { amount, token }
+            | Command::Bridge { amount, token, .. }
+            | Command::Approve { token, amount, .. } => Some((*amount, token)),
+            _ => None,
+        }
+    }
+
+    /// Whether running the command begins a multi-step flow
+    pub fn starts_flow(&self) -> bool {
+        matches!(self, Command::Start | Command::SendGuided { .. } | Command::Pay { confirmed: false, .. })
+    }
+
+    /// Menus and listings may be trimmed when over the SMS budget; anything
+    /// reporting money movement or carrying a code is sent in full
+    pub fn reply_priority(&self) -> MessagePriority {
+        match self {
+            Command::Help | Command::History | Command::Contacts | Command::Unknown(_) => MessagePriority::Normal,
+            _ => MessagePriority::Critical,
+        }
+    }
+}
+
 /// Command processor that parses and executes commands
 #[derive(Clone)]
 pub struct CommandProcessor {
-    user_repo: Option<UserRepository>,
-    voucher_repo: Option<VoucherRepository>,
-    deposit_repo: Option<DepositRepository>,
-    address_book_repo: Option<AddressBookRepository>,
-    provider: Arc<AmoyProvider>,
-    multi_chain: MultiChainProvider,
-    backend_url: String,
+    pub(super) user_repo: Option<UserRepository>,
+    pub(super) voucher_repo: Option<VoucherRepository>,
+    pub(super) deposit_repo: Option<DepositRepository>,
+    pub(super) address_book_repo: Option<AddressBookRepository>,
+    /// Multi-step flows: onboarding, guided SEND and PAY
+    pub(super) flow_repo: Option<FlowRepository>,
+    pub(super) agent_repo: Option<AgentRepository>,
+    pub(super) ledger_repo: Option<LedgerRepository>,
+    /// Sends SMS to parties other than the sender (e.g. agent customers)
+    pub(super) notifier: Option<SmsGateway>,
+    /// Short links for REQUEST payment URIs
+    pub(super) link_repo: Option<PaymentLinkRepository>,
+    /// Public base URL short links are served from (empty = no short links)
+    pub(super) link_base_url: String,
+    /// WalletConnect sessions and the bridge that relays them
+    pub(super) wc_repo: Option<WalletConnectRepository>,
+    pub(super) wc_bridge: Option<WalletConnectBridge>,
+    /// Gas for new wallets in test deployments
+    pub(super) faucet: Option<Arc<Faucet>>,
+    /// Hash-chained record of state-changing commands
+    pub(super) audit: Option<AuditLogRepository>,
+    /// Beta launch mode: allowlist, invites and waitlist
+    pub(super) beta: Option<BetaAccess>,
+    /// Email command channel: EMAIL links and verification mail
+    pub(super) email: Option<EmailChannel>,
+    /// Signs RECEIPT proofs
+    pub(super) receipts: Option<ReceiptSigner>,
+    /// Recent ENS lookups for SEND recipients
+    pub(super) ens_cache: Arc<EnsCache>,
+    /// Commands switched off in this deployment
+    pub(super) features: FeatureFlags,
+    /// Emergency stop for money-moving commands
+    pub(super) kill_switch: KillSwitch,
+    /// Live admin event stream
+    pub(super) events: EventBus,
+    /// Command counts per country and carrier
+    pub(super) metrics: CommandMetrics,
+    /// First words of messages that weren't a command
+    pub(super) unrecognized: Option<UnrecognizedCommandRepository>,
+    /// Partner campaigns paying ENS mint gas
+    pub(super) campaigns: Option<CampaignRepository>,
+    /// Second approval for large SENDs
+    pub(super) transfer_policy: Option<TransferPolicy>,
+    /// Smallest on-chain SEND/BRIDGE per chain
+    pub(super) send_minimums: Option<SendMinimums>,
+    /// Cash SENDs to outside addresses, paid on chain
+    pub(super) payouts: Option<Payouts>,
+    /// Subdomain labels that can't be minted
+    pub(super) name_policy: NamePolicy,
+    /// Per-user deposit addresses shown by DEPOSIT
+    pub(super) deposit_addresses: Option<DepositAddresses>,
+    /// Refuses sensitive commands shortly after a SIM change
+    pub(super) sim_swap: Option<SimSwapGuard>,
+    /// ALERT settings: low-balance warnings and deposit notifications
+    pub(super) alerts: Option<BalanceAlertRepository>,
+    /// Onboarding tiers and their send limits
+    pub(super) kyc: Option<KycPolicy>,
+    /// GIFT NAME offers waiting for the recipient
+    pub(super) name_gifts: Option<NameGiftRepository>,
+    pub(super) name_market: Option<NameListingRepository>,
+    /// SAVE / UNSAVE positions and the vault they go into
+    pub(super) savings_repo: Option<SavingsRepository>,
+    pub(super) savings_vault: Option<SavingsVault>,
+    /// SENDs held for their cancellation window
+    pub(super) send_queue: Option<SendQueue>,
+    /// Recent transfers, so an identical SEND asks for AGAIN
+    pub(super) repeat_sends: Option<RepeatSendGuard>,
+    /// Saved addresses that on-chain SENDs are limited to
+    pub(super) withdrawals: Option<WithdrawalAllowlist>,
+    /// NOTIFY modes and the daily digest for deposit and receipt texts
+    pub(super) notifications: Option<Notifications>,
+    /// USD prices shown next to balances
+    pub(super) prices: Option<TokenPrices>,
+    /// STATEMENT activity and its download links
+    pub(super) statements: Option<StatementRepository>,
+    /// BILL codes waiting for PAY
+    pub(super) bills: Option<BillRepository>,
+    /// INVITE referral codes and their bonuses
+    pub(super) referrals: Option<Referrals>,
+    /// Minutes a statement download link stays valid
+    pub(super) statement_link_minutes: i64,
+    pub(super) key_vault: KeyVault,
+    pub(super) provider: Arc<AmoyProvider>,
+    pub(super) multi_chain: MultiChainProvider,
+    pub(super) backend_url: String,
+    /// Arc wallet service for USDC cashout
+    pub(super) arc_url: String,
 }
 
+/// Why an ENS name could not be registered
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum EnsRegistrationError {
+    /// Name fails local validation
+    Invalid(&'static str),
+    /// Name is taken or reserved
+    Unavailable(String),
+    /// Backend ENS service failed
+    Service(&'static str),
+    /// Every active campaign has spent its mint gas budget
+    Unfunded,
+}
+
+/// Reply when no campaign has gas budget left for new names
+pub(super) const MINTS_PAUSED_REPLY: &str = "New names are paused: the sponsor's gas budget is used up.\nYour wallet works as usual.";
+
 impl CommandProcessor {
-    pub fn new(user_repo: Option<UserRepository>, provider: Arc<AmoyProvider>) -> Self {
-        let backend_url = std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    /// `key_vault` seals the private keys of wallets created here
+    pub fn new(user_repo: Option<UserRepository>, key_vault: KeyVault, provider: Arc<AmoyProvider>) -> Self {
         Self { 
             user_repo,
             voucher_repo: None,
             deposit_repo: None,
             address_book_repo: None,
+            flow_repo: None,
+            agent_repo: None,
+            ledger_repo: None,
+            notifier: None,
+            link_repo: None,
+            link_base_url: String::new(),
+            wc_repo: None,
+            wc_bridge: None,
+            faucet: None,
+            audit: None,
+            beta: None,
+            email: None,
+            receipts: None,
+            ens_cache: Arc::new(EnsCache::default()),
+            features: FeatureFlags::default(),
+            kill_switch: KillSwitch::default(),
+            events: EventBus::default(),
+            metrics: CommandMetrics::default(),
+            unrecognized: None,
+            campaigns: None,
+            transfer_policy: None,
+            send_minimums: None,
+            payouts: None,
+            withdrawals: None,
+            name_policy: NamePolicy::default(),
+            deposit_addresses: None,
+            sim_swap: None,
+            alerts: None,
+            kyc: None,
+            name_gifts: None,
+            name_market: None,
+            savings_repo: None,
+            savings_vault: None,
+            send_queue: None,
+            repeat_sends: None,
+            notifications: None,
+            prices: None,
+            statements: None,
+            bills: None,
+            referrals: None,
+            statement_link_minutes: 0,
+            key_vault,
             provider,
             multi_chain: MultiChainProvider::new(),
-            backend_url,
+            backend_url: DEFAULT_BACKEND_URL.to_string(),
+            arc_url: DEFAULT_ARC_URL.to_string(),
         }
     }
 
@@ -81,20 +565,303 @@ impl CommandProcessor {
         voucher_repo: Option<VoucherRepository>,
         deposit_repo: Option<DepositRepository>,
         address_book_repo: Option<AddressBookRepository>,
+        key_vault: KeyVault,
         provider: Arc<AmoyProvider>,
     ) -> Self {
-        let backend_url = std::env::var("BACKEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
         Self {
             user_repo,
             voucher_repo,
             deposit_repo,
             address_book_repo,
+            flow_repo: None,
+            agent_repo: None,
+            ledger_repo: None,
+            notifier: None,
+            link_repo: None,
+            link_base_url: String::new(),
+            wc_repo: None,
+            wc_bridge: None,
+            faucet: None,
+            audit: None,
+            beta: None,
+            email: None,
+            receipts: None,
+            ens_cache: Arc::new(EnsCache::default()),
+            features: FeatureFlags::default(),
+            kill_switch: KillSwitch::default(),
+            events: EventBus::default(),
+            metrics: CommandMetrics::default(),
+            unrecognized: None,
+            campaigns: None,
+            transfer_policy: None,
+            send_minimums: None,
+            payouts: None,
+            withdrawals: None,
+            name_policy: NamePolicy::default(),
+            deposit_addresses: None,
+            sim_swap: None,
+            alerts: None,
+            kyc: None,
+            name_gifts: None,
+            name_market: None,
+            savings_repo: None,
+            savings_vault: None,
+            send_queue: None,
+            repeat_sends: None,
+            notifications: None,
+            prices: None,
+            statements: None,
+            bills: None,
+            referrals: None,
+            statement_link_minutes: 0,
+            key_vault,
             provider,
             multi_chain: MultiChainProvider::new(),
-            backend_url,
+            backend_url: DEFAULT_BACKEND_URL.to_string(),
+            arc_url: DEFAULT_ARC_URL.to_string(),
         }
     }
 
+    /// Enable multi-step flows: guided onboarding for new numbers and SEND
+    /// without all its details
+    pub fn set_flow_repo(&mut self, flow_repo: FlowRepository) {
+        self.flow_repo = Some(flow_repo);
+    }
+
+    /// Enable agent cash-in/out and custodial balances
+    pub fn set_agent_repos(&mut self, agent_repo: AgentRepository, ledger_repo: LedgerRepository) {
+        self.agent_repo = Some(agent_repo);
+        self.ledger_repo = Some(ledger_repo);
+    }
+
+    /// Client used to message parties other than the sender
+    pub fn set_notifier(&mut self, notifier: SmsGateway) {
+        self.notifier = Some(notifier);
+    }
+
+    /// Enable short links for REQUEST, served under `{base_url}/p/<code>`
+    pub fn set_payment_links(&mut self, link_repo: PaymentLinkRepository, base_url: &str) {
+        self.link_repo = Some(link_repo);
+        self.link_base_url = base_url.trim_end_matches('/').to_string();
+    }
+
+    /// Enable CONNECT / SIGN / REJECT / DISCONNECT
+    pub fn set_walletconnect(&mut self, wc_repo: WalletConnectRepository, bridge: WalletConnectBridge) {
+        self.wc_repo = Some(wc_repo);
+        self.wc_bridge = Some(bridge);
+    }
+
+    /// Fund every new wallet from a testnet faucet
+    pub fn set_faucet(&mut self, faucet: Arc<Faucet>) {
+        self.faucet = Some(faucet);
+    }
+
+    /// Record state-changing commands in the hash-chained audit log
+    pub fn set_audit_log(&mut self, audit: AuditLogRepository) {
+        self.audit = Some(audit);
+    }
+
+    /// Only allowlisted numbers may sign up; others join the waitlist
+    pub fn set_beta(&mut self, beta: BetaAccess) {
+        self.beta = Some(beta);
+    }
+
+    /// Enable EMAIL linking for the email command channel
+    pub fn set_email(&mut self, channel: EmailChannel) {
+        self.email = Some(channel);
+    }
+
+    /// Enable RECEIPT proofs signed with the receipt key
+    pub fn set_receipts(&mut self, signer: ReceiptSigner) {
+        self.receipts = Some(signer);
+    }
+
+    /// Charge ENS mint gas to partner campaigns
+    pub fn set_campaigns(&mut self, campaigns: CampaignRepository) {
+        self.campaigns = Some(campaigns);
+    }
+
+    /// Hold large SENDs for a guardian's approval or a cooling period
+    pub fn set_transfer_policy(&mut self, policy: TransferPolicy) {
+        self.transfer_policy = Some(policy);
+    }
+
+    /// Enable per-chain minimum amounts for on-chain transfers
+    pub fn set_send_minimums(&mut self, minimums: SendMinimums) {
+        self.send_minimums = Some(minimums);
+    }
+
+    /// Pay cash SENDs to outside addresses on chain
+    pub fn set_payouts(&mut self, payouts: Payouts) {
+        self.payouts = Some(payouts);
+    }
+
+    /// Where the backend and Arc services are (`BACKEND_URL`, `ARC_SERVICE_URL`)
+    pub fn set_services(&mut self, services: &ServicesConfig) {
+        self.backend_url = services.backend_url.clone();
+        self.arc_url = services.arc_url.clone();
+    }
+
+    /// Point contract API calls at a stand-in backend
+    #[cfg(all(test, feature = "db-tests"))]
+    pub fn set_backend_url(&mut self, backend_url: String) {
+        self.backend_url = backend_url;
+    }
+
+    /// Limit on-chain SENDs to addresses saved past a cooldown
+    pub fn set_withdrawal_allowlist(&mut self, allowlist: WithdrawalAllowlist) {
+        self.withdrawals = Some(allowlist);
+    }
+
+    /// Hold SENDs for a cancellation window before they go out
+    pub fn set_send_queue(&mut self, queue: SendQueue) {
+        self.send_queue = Some(queue);
+    }
+
+    /// Ask for AGAIN before a SEND that repeats a recent transfer
+    pub fn set_repeat_sends(&mut self, guard: RepeatSendGuard) {
+        self.repeat_sends = Some(guard);
+    }
+
+    /// Route deposit and receipt texts by each user's NOTIFY mode
+    pub fn set_notifications(&mut self, notifications: Notifications) {
+        self.notifications = Some(notifications);
+    }
+
+    /// Give users INVITE codes and pay referral bonuses on sign-up
+    pub fn set_referrals(&mut self, referrals: Referrals) {
+        self.referrals = Some(referrals);
+    }
+
+    /// Show USD values next to balances
+    pub fn set_prices(&mut self, prices: TokenPrices) {
+        self.prices = Some(prices);
+    }
+
+    /// Current token prices; empty without a feed or while it is down
+    pub(super) async fn token_prices(&self) -> Prices {
+        match self.prices {
+            Some(ref prices) => prices.prices().await,
+            None => Prices::default(),
+        }
+    }
+
+    /// Hold users to their onboarding tier's send limits
+    pub fn set_kyc_policy(&mut self, policy: KycPolicy) {
+        self.kyc = Some(policy);
+    }
+
+    /// Enable SAVE / UNSAVE into an ERC-4626 vault
+    pub fn set_savings(&mut self, repo: SavingsRepository, vault: SavingsVault) {
+        self.savings_repo = Some(repo);
+        self.savings_vault = Some(vault);
+    }
+
+    /// Share the ENS lookup cache (e.g. with the admin invalidation endpoints)
+    pub fn set_ens_cache(&mut self, ens_cache: Arc<EnsCache>) {
+        self.ens_cache = ens_cache;
+    }
+
+    /// Gate commands behind per-deployment feature flags
+    pub fn set_features(&mut self, features: FeatureFlags) {
+        self.features = features;
+    }
+
+    pub fn features(&self) -> &FeatureFlags {
+        &self.features
+    }
+
+    /// Stop money-moving commands while engaged
+    pub fn set_kill_switch(&mut self, kill_switch: KillSwitch) {
+        self.kill_switch = kill_switch;
+    }
+
+    pub fn kill_switch(&self) -> &KillSwitch {
+        &self.kill_switch
+    }
+
+    pub fn ens_cache(&self) -> &Arc<EnsCache> {
+        &self.ens_cache
+    }
+
+    pub fn backend_url(&self) -> &str {
+        &self.backend_url
+    }
+
+    /// Publish commands, transfers and failures to the admin event stream
+    pub fn set_events(&mut self, events: EventBus) {
+        self.events = events;
+    }
+
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Refuse reserved, brand and offensive subdomain labels
+    pub fn set_name_policy(&mut self, name_policy: NamePolicy) {
+        self.name_policy = name_policy;
+    }
+
+    /// Show users their own HD deposit address instead of their wallet
+    pub fn set_deposit_addresses(&mut self, deposit_addresses: DepositAddresses) {
+        self.deposit_addresses = Some(deposit_addresses);
+    }
+
+    /// Check for a recent SIM swap before sensitive commands
+    pub fn set_sim_swap(&mut self, guard: SimSwapGuard) {
+        self.sim_swap = Some(guard);
+    }
+
+    /// Enable ALERT and low-balance warnings after cash debits
+    pub fn set_alerts(&mut self, alerts: BalanceAlertRepository) {
+        self.alerts = Some(alerts);
+    }
+
+    /// Enable GIFT NAME
+    pub fn set_name_gifts(&mut self, name_gifts: NameGiftRepository) {
+        self.name_gifts = Some(name_gifts);
+    }
+
+    /// Enable SELL NAME / BUY NAME
+    pub fn set_name_market(&mut self, listings: NameListingRepository) {
+        self.name_market = Some(listings);
+    }
+
+    /// Enable STATEMENT; full statements are linked under the REQUEST short
+    /// link base URL for `link_minutes`
+    /// Enable BILL / PAY
+    pub fn set_bills(&mut self, bills: BillRepository) {
+        self.bills = Some(bills);
+    }
+
+    pub fn set_statements(&mut self, statements: StatementRepository, link_minutes: i64) {
+        self.statements = Some(statements);
+        self.statement_link_minutes = link_minutes;
+    }
+
+    /// Count commands per country and carrier
+    pub fn set_metrics(&mut self, metrics: CommandMetrics) {
+        self.metrics = metrics;
+    }
+
+    /// Count unrecognized messages for product to review
+    pub fn set_unrecognized_log(&mut self, log: UnrecognizedCommandRepository) {
+        self.unrecognized = Some(log);
+    }
+
+    pub fn metrics(&self) -> &CommandMetrics {
+        &self.metrics
+    }
+
+    /// Report an incoming command on the admin event stream, PINs left out
+    pub fn publish_incoming(&self, from: &str, command: &Command) {
+        let (country, carrier) = self.metrics.segment(from);
+        self.events.publish(
+            Topic::Sms,
+            serde_json::json!({ "from": from, "command": command.audit_detail(), "country": country, "carrier": carrier }),
+        );
+    }
+
     /// Process an incoming SMS and return the response
     pub async fn process(&self, from: &str, body: &str) -> String {
         let command = self.parse(body);
@@ -105,7 +872,21 @@ impl CommandProcessor {
             "Processing command"
         );
 
-        self.execute(from, command).await
+        // Activity for broadcast segments; never blocks the command
+        if let Some(ref user_repo) = self.user_repo {
+            if let Err(e) = user_repo.touch_activity(from).await {
+                tracing::warn!("Failed to record user activity: {}", e);
+            }
+        }
+
+        let name = command.name();
+        let reply = match self.flow_intercept(from, body, &command).await {
+            Some(reply) => reply,
+            None if is_unrecognized(body, &command) => self.unrecognized_response(body).await,
+            None => self.execute(from, command).await,
+        };
+        self.metrics.record(from, name, &reply);
+        reply
     }
 
     /// Parse SMS text into a structured command
@@ -118,13 +899,22 @@ impl CommandProcessor {
         if parts.is_empty() {
             return Command::Unknown("".to_string());
         }
+        // Just a name or an address: no keyword needed
+        if let Some(target) = lookup_target(original) {
+            return Command::Lookup { target: target.to_string() };
+        }
 
         match parts[0] {
             "COMMANDS" | "MENU" | "?" => Command::Help,
-            "JOIN" | "START" | "REGISTER" => {
+            "START" => Command::Start,
+            "INVITE" => Command::Invite {
+                code: original_parts.get(1).map(|_| original_parts[1..].join("")),
+            },
+            "JOIN" | "REGISTER" => {
                 let ens_name = parts.get(1).map(|s| s.to_lowercase());
                 Command::Join { ens_name }
             },
+            "BALANCE" | "BAL" if parts.get(1) == Some(&"ALL") => Command::BalanceAll,
             "BALANCE" | "BAL" => Command::Balance,
             "PIN" => {
                 let new_pin = parts.get(1).map(|s| s.to_string());
@@ -141,10 +931,95 @@ impl CommandProcessor {
                 }
             }
             "SWAP" | "EXCHANGE" => self.parse_swap(&parts),
+            "CASHIN" => self.parse_agent_cash(&parts, CashKind::CashIn),
+            "CASHOUT" | "CASH" if parts.get(1).is_some_and(|p| p.starts_with('+')) => {
+                self.parse_agent_cash(&parts, CashKind::CashOut)
+            }
             "CASHOUT" | "CASH" => self.parse_cashout(&parts),
+            "CONFIRM" => match parts.get(1) {
+                Some(code) => Command::Confirm { code: code.to_string() },
+                None => Command::Unknown("Usage: CONFIRM <code>".to_string()),
+            },
+            "FLOAT" => Command::Float,
+            "REQUEST" | "INVOICE" => self.parse_request(&parts),
+            "BILL" => self.parse_bill(&parts, &original_parts),
+            "PAY" => Command::Pay {
+                code: parts.get(1).map(|code| code.to_string()),
+                confirmed: matches!(parts.get(2), Some(&"YES") | Some(&"Y")),
+            },
+            "CONNECT" | "WALLETCONNECT" => Command::Connect,
+            "DISCONNECT" => Command::Disconnect,
+            "SIGN" => Command::Sign { pin: parts.get(1).map(|s| s.to_string()) },
+            "REJECT" | "DECLINE" => Command::Reject,
+            "APPROVE" => self.parse_approve(&parts, &original_parts),
+            "REVOKE" => match parts.get(1) {
+                Some(token) => Command::Revoke {
+                    token: token.to_string(),
+                    spender: original_parts.get(2).map(|s| s.to_string()),
+                },
+                None => Command::Unknown("Usage: REVOKE <token> [spender]\nExample: REVOKE TXTC".to_string()),
+            },
+            "ALLOWANCES" | "APPROVALS" => Command::Allowances,
+            "RECEIPT" | "PROOF" => match parts.get(1) {
+                Some(reference) => Command::Receipt { reference: reference.to_string() },
+                None => Command::Unknown("Usage: RECEIPT <ref>\nThe ref is in your send confirmation.".to_string()),
+            },
+            "EMAIL" => Command::Email { arg: original_parts.get(1).map(|s| s.to_string()) },
+            "GUARDIAN" => Command::Guardian { arg: parts.get(1).map(|s| s.to_string()) },
+            "GREETING" => Command::Greeting {
+                arg: (original_parts.len() > 1).then(|| original_parts[1..].join(" ")),
+            },
+            "ALERT" | "ALERTS" => match parse_alert(&parts[1..]) {
+                Ok(setting) => Command::Alert { setting },
+                Err(usage) => Command::Unknown(usage.to_string()),
+            },
+            "GIFT" => match parse_gift(&original_parts[1..]) {
+                Ok(action) => Command::Gift { action },
+                Err(usage) => Command::Unknown(usage.to_string()),
+            },
+            "VERIFY" => Command::Verify { arg: parts.get(1).map(|s| s.to_string()) },
+            "STATEMENT" | "STMT" => Command::Statement { month: parts.get(1).map(|s| s.to_string()) },
+            // A bare CANCEL is a carrier opt-out keyword and never gets here
+            "CANCEL" if parts.len() > 1 => Command::CancelSend,
+            "NOTIFY" | "NOTIFICATIONS" => match parts.get(1) {
+                None => Command::Notify { mode: None },
+                Some(mode) if parts.len() == 2 => match NotifyMode::parse(mode) {
+                    Some(mode) => Command::Notify { mode: Some(mode) },
+                    None => Command::Unknown(NOTIFY_USAGE.to_string()),
+                },
+                Some(_) => Command::Unknown(NOTIFY_USAGE.to_string()),
+            },
+            "BUY" if parts.get(1) == Some(&"NAME") => match parse_buy_name(&original_parts[1..]) {
+                Ok((label, confirmed)) => Command::BuyName { label, confirmed },
+                Err(usage) => Command::Unknown(usage.to_string()),
+            },
+            "SELL" => match parse_sell_name(&original_parts[1..]) {
+                Ok((label, price)) => Command::SellName { label, price },
+                Err(usage) => Command::Unknown(usage.to_string()),
+            },
             "BUY" | "TOPUP" | "PURCHASE" => self.parse_buy(&parts),
             "BRIDGE" | "CROSS" => self.parse_bridge(&parts),
+            "SAVE" if is_savings_amount(&parts) => match Money::parse(parts[1], Currency::USDC) {
+                Ok(amount) if amount.is_positive() => Command::SaveFunds { amount },
+                _ => Command::Unknown("Invalid amount".to_string()),
+            },
             "SAVE" | "ADD" => self.parse_save(&parts),
+            "UNSAVE" | "WITHDRAW" => match parts.get(1).copied() {
+                Some("ALL") => Command::Unsave { amount: None },
+                Some(amount) => match Money::parse(amount, Currency::USDC) {
+                    Ok(amount) if amount.is_positive() => Command::Unsave { amount: Some(amount) },
+                    _ => Command::Unknown("Invalid amount".to_string()),
+                },
+                None => Command::Unknown("Usage: UNSAVE <amount> or UNSAVE ALL".to_string()),
+            },
+            "UNLOCK" => match parts[1..] {
+                [] => Command::Unknown("Usage: UNLOCK <name> <PIN>".to_string()),
+                [ref name @ .., pin] if !name.is_empty() && pin.chars().all(|c| c.is_ascii_digit()) => Command::Unlock {
+                    name: name.join(" "),
+                    pin: Some(pin.to_string()),
+                },
+                ref name => Command::Unlock { name: name.join(" "), pin: None },
+            },
             "CONTACTS" | "BOOK" => Command::Contacts,
             "CHAIN" | "NETWORK" => {
                 if parts.len() < 2 {
@@ -157,14 +1032,20 @@ impl CommandProcessor {
         }
     }
 
-    /// Parse SAVE command: SAVE <name> <phone>
+    /// Parse SAVE command: SAVE <name> <phone|0x address>
+    /// Names may have spaces: SAVE AUNT MARY +254700000001
     fn parse_save(&self, parts: &[&str]) -> Command {
         if parts.len() < 3 {
-            return Command::Unknown("Usage: SAVE <name> <phone>".to_string());
+            return Command::Unknown("Usage: SAVE <name> <phone or 0x address>".to_string());
         }
+        // The contact starts at the first phone number or address
+        let split = parts[2..]
+            .iter()
+            .position(|part| part.starts_with('+') || part.starts_with("0X"))
+            .map_or(2, |i| i + 2);
         Command::Save {
-            name: parts[1].to_string(),
-            phone: parts[2..].join(" "),
+            name: parts[1..split].join(" "),
+            contact: parts[split..].join(" "),
         }
     }
 
@@ -172,9 +1053,18 @@ impl CommandProcessor {
     /// Supports: SEND 10 TXTC TO swarnim.ttcip.eth
     ///           SEND 10 TXTC swarnim.ttcip.eth
     ///           SEND 0.001 ETH 0xabc...
+    ///           SEND 5 TO aunt mary (TXTC)
+    ///           SEND / SEND 10 [TOKEN] (asks for the rest)
     fn parse_send(&self, parts: &[&str]) -> Command {
         if parts.len() < 4 {
-            return Command::Unknown("Use: SEND <amount> <token> <recipient>\nExample: SEND 10 TXTC swarnim.ttcip.eth".to_string());
+            let amount = match parts {
+                [_] => None,
+                [_, amount] => Some(amount.to_string()),
+                [_, amount, to] if to.eq_ignore_ascii_case("TO") => Some(amount.to_string()),
+                [_, amount, token] => Some(format!("{} {}", amount, token)),
+                _ => None,
+            };
+            return Command::SendGuided { amount };
         }
 
         let amount = match parts[1].parse::<f64>() {
@@ -182,14 +1072,21 @@ impl CommandProcessor {
             Err(_) => return Command::Unknown("Invalid amount".to_string()),
         };
 
-        let token = parts[2].to_string();
-
-        // Check if "TO" keyword is present (optional)
-        let recipient = if parts.len() >= 5 && parts[3].eq_ignore_ascii_case("TO") {
-            parts[4..].join(" ")
+        // Token may be left out before TO: SEND 5 TO aunt mary
+        let (token, rest) = if parts[2].eq_ignore_ascii_case("TO") {
+            ("TXTC".to_string(), &parts[3..])
+        } else if parts.len() >= 5 && parts[3].eq_ignore_ascii_case("TO") {
+            (parts[2].to_string(), &parts[4..])
         } else {
-            parts[3..].join(" ")
+            (parts[2].to_string(), &parts[3..])
+        };
+        // A trailing AGAIN confirms a repeated SEND
+        let (rest, repeat) = match rest.split_last() {
+            Some((last, names)) if last.eq_ignore_ascii_case("AGAIN") && !names.is_empty() => (names, true),
+            _ => (rest, false),
         };
+        // "aunt mary." is a name, not an ENS domain
+        let recipient = rest.join(" ").trim_end_matches(['.', ',', '!', '?']).to_string();
 
         if recipient.is_empty() {
             return Command::Unknown("Missing recipient.\nExample: SEND 10 TXTC swarnim.ttcip.eth".to_string());
@@ -199,6 +1096,7 @@ impl CommandProcessor {
             amount,
             token,
             recipient,
+            repeat,
         }
     }
 
@@ -289,16 +1187,105 @@ impl CommandProcessor {
         }
     }
 
+    /// Parse agent commands: CASHIN <phone> <amount> / CASHOUT <phone> <amount>
+    fn parse_agent_cash(&self, parts: &[&str], kind: CashKind) -> Command {
+        let usage = match kind {
+            CashKind::CashIn => "Usage: CASHIN <phone> <amount>",
+            CashKind::CashOut => "Usage: CASHOUT <phone> <amount>",
+        };
+        if parts.len() < 3 || !parts[1].starts_with('+') {
+            return Command::Unknown(usage.to_string());
+        }
+
+        match parts[2].parse::<f64>() {
+            Ok(amount) if amount > 0.0 => Command::AgentCash {
+                kind,
+                customer: parts[1].to_string(),
+                amount,
+            },
+            _ => Command::Unknown("Invalid amount".to_string()),
+        }
+    }
+
+    /// Parse REQUEST command: REQUEST <amount> [TXTC|ETH]
+    fn parse_request(&self, parts: &[&str]) -> Command {
+        if parts.len() < 2 {
+            return Command::Unknown("Usage: REQUEST <amount> [TXTC|ETH]\nExample: REQUEST 10 TXTC".to_string());
+        }
+
+        match parts[1].parse::<f64>() {
+            Ok(amount) if amount > 0.0 => Command::Request {
+                amount,
+                token: parts.get(2).unwrap_or(&"TXTC").to_string(),
+            },
+            _ => Command::Unknown("Invalid amount".to_string()),
+        }
+    }
+
+    /// Parse BILL command: BILL <amount> [token] <memo>
+    /// (a missing memo or bad amount is explained by `bill_response`)
+    fn parse_bill(&self, parts: &[&str], original_parts: &[&str]) -> Command {
+        let amount = parts.get(1).and_then(|amount| amount.parse::<f64>().ok()).unwrap_or_default();
+        let (token, memo_start) = match parts.get(2) {
+            Some(&token) if matches!(token, "TXTC" | "ETH" | "USDC") => (token, 3),
+            _ => ("TXTC", 2),
+        };
+        let memo = original_parts.get(memo_start..).unwrap_or_default().join(" ");
+        Command::Bill { amount, token: token.to_string(), memo }
+    }
+
+    /// Parse APPROVE command: APPROVE <token> <amount> [spender]
+    /// Spender defaults to the swap router; unlimited approvals are refused
+    fn parse_approve(&self, parts: &[&str], original_parts: &[&str]) -> Command {
+        if parts.len() < 3 {
+            return Command::Unknown("Usage: APPROVE <token> <amount> [spender]\nExample: APPROVE TXTC 50".to_string());
+        }
+        if matches!(parts[2], "MAX" | "ALL" | "UNLIMITED") {
+            return Command::Unknown("Unlimited approvals are not allowed. Approve only what you plan to spend.\nExample: APPROVE TXTC 50".to_string());
+        }
+
+        match parts[2].parse::<f64>() {
+            Ok(amount) if amount > 0.0 => Command::Approve {
+                token: parts[1].to_string(),
+                amount,
+                spender: original_parts.get(3).map(|s| s.to_string()),
+            },
+            _ => Command::Unknown("Invalid amount".to_string()),
+        }
+    }
+
     /// Execute a parsed command and return the response text
-    async fn execute(&self, from: &str, command: Command) -> String {
-        match command {
+    pub(super) async fn execute(&self, from: &str, command: Command) -> String {
+        if command.feature().is_some_and(|feature| !self.features.is_enabled(feature)) {
+            return NOT_AVAILABLE_REPLY.to_string();
+        }
+        if command.moves_money() && self.kill_switch.is_engaged().await {
+            tracing::warn!(from = %from, command = command.name(), "Command refused by kill switch");
+            return self.kill_switch.message().to_string();
+        }
+        if let Some(reply) = self.sim_swap_hold(from, &command).await {
+            return reply;
+        }
+        if let Some(reply) = self.kyc_hold(from, &command).await {
+            return reply;
+        }
+
+        let audit = command.audit_action().map(|action| (action, command.audit_detail()));
+        let greeted = command.greeted();
+
+        let reply = match command {
             Command::Help => self.help_response(),
+            Command::Start => self.join_response(from, None).await,
             Command::Join { ens_name } => self.join_response(from, ens_name).await,
+            Command::Invite { code: Some(code) } => self.invite_response(from, &code).await,
+            Command::Invite { code: None } => self.invite_code_response(from).await,
             Command::Balance => self.balance_response(from).await,
+            Command::BalanceAll => self.balance_all_response(from).await,
             Command::Pin { new_pin } => self.pin_response(from, new_pin).await,
-            Command::Send { amount, token, recipient } => {
-                self.send_response(from, amount, &token, &recipient).await
+            Command::Send { amount, token, recipient, repeat } => {
+                self.send_response(from, amount, &token, &recipient, repeat).await
             }
+            Command::SendGuided { amount } => self.guided_send_response(from, amount).await,
             Command::Deposit => self.deposit_response(from).await,
             Command::History => self.history_response(from).await,
             Command::Redeem { code } => self.redeem_response(from, &code).await,
@@ -308,18 +1295,80 @@ impl CommandProcessor {
             Command::Bridge { amount, token, from_chain, to_chain } => {
                 self.bridge_response(from, amount, &token, &from_chain, &to_chain).await
             }
-            Command::Save { name, phone } => self.save_response(from, &name, &phone).await,
+            Command::Save { name, contact } => self.save_response(from, &name, &contact).await,
             Command::Contacts => self.contacts_response(from).await,
             Command::SwitchChain { chain } => self.chain_response(from, &chain).await,
+            Command::AgentCash { kind, customer, amount } => {
+                self.agent_cash_response(from, kind, &customer, amount).await
+            }
+            // Held transfers first; other codes belong to agent cash requests
+            Command::Confirm { code } => match self.approve_transfer_response(from, &code).await {
+                Some(reply) => reply,
+                None => self.confirm_response(from, &code).await,
+            },
+            Command::Float => self.float_response(from).await,
+            Command::Request { amount, token } => self.request_response(from, amount, &token).await,
+            Command::Connect => self.connect_response(from).await,
+            Command::Disconnect => self.disconnect_response(from).await,
+            Command::Sign { pin } => self.sign_response(from, pin).await,
+            Command::Reject => self.reject_response(from).await,
+            Command::Approve { token, amount, spender } => {
+                self.approve_response(from, &token, amount, spender.as_deref()).await
+            }
+            Command::Revoke { token, spender } => self.revoke_response(from, &token, spender.as_deref()).await,
+            Command::Allowances => self.allowances_response(from).await,
+            Command::SaveFunds { amount } => self.save_funds_response(from, amount).await,
+            Command::Unsave { amount } => self.unsave_response(from, amount).await,
+            Command::Email { arg } => self.email_response(from, arg.as_deref()).await,
+            Command::Receipt { reference } => self.receipt_response(from, &reference).await,
+            Command::Guardian { arg } => self.guardian_response(from, arg.as_deref()).await,
+            Command::Alert { setting } => self.alert_response(from, setting).await,
+            Command::Gift { action } => self.gift_response(from, action).await,
+            Command::Verify { arg } => self.verify_response(from, arg.as_deref()).await,
+            Command::Statement { month } => self.statement_response(from, month.as_deref()).await,
+            Command::CancelSend => self.cancel_send_response(from).await,
+            Command::Notify { mode } => self.notify_response(from, mode).await,
+            Command::Bill { amount, token, memo } => self.bill_response(from, amount, &token, &memo).await,
+            Command::Pay { code, confirmed } => self.pay_response(from, code, confirmed).await,
+            Command::Greeting { arg } => self.greeting_response(from, arg.as_deref()).await,
+            Command::SellName { label, price } => self.sell_name_response(from, &label, price).await,
+            Command::BuyName { label, confirmed } => self.buy_name_response(from, &label, confirmed).await,
+            Command::Unlock { name, pin } => self.unlock_response(from, &name, pin).await,
+            Command::Lookup { target } => self.lookup_response(&target).await,
             Command::Unknown(text) => self.unknown_response(&text),
+        };
+
+        if let (Some((action, detail)), Some(ref repo)) = (audit, &self.audit) {
+            let outcome = reply.lines().next().unwrap_or_default();
+            if let Err(e) = repo.record_command(from, action, &format!("{} => {}", detail, outcome)).await {
+                tracing::error!(from = %from, action, "Audit log write failed: {}", e);
+            }
+        }
+        if greeted {
+            return self.greeted(from, reply).await;
+        }
+        reply
+    }
+
+    /// Refusal for a sensitive command from a number that recently changed SIM
+    async fn sim_swap_hold(&self, from: &str, command: &Command) -> Option<String> {
+        let guard = self.sim_swap.as_ref().filter(|_| command.sim_swap_sensitive())?;
+        let check = guard.check(from).await;
+        let reply = hold_reply(command.name(), &check)?;
+        tracing::warn!(from = %from, command = command.name(), check = ?check, "Sensitive command held after SIM swap");
+        if let Some(ref audit) = self.audit {
+            if let Err(e) = audit.record_command(from, "SIM_SWAP_HOLD", &format!("{} => {:?}", command.audit_detail(), check)).await {
+                tracing::error!(from = %from, "Audit log write failed: {}", e);
+            }
         }
+        Some(reply)
     }
 
     fn help_response(&self) -> String {
-        "Text-to-Chain Commands:\nJOIN <name> - Create wallet\nBALANCE - Check balance\nSEND 10 TXTC TO name.ttcip.eth\nBUY 10 - Buy TXTC with airtime\nDEPOSIT - Get deposit address\nREDEEM <code> - Redeem voucher\nSWAP 10 TXTC - Swap to ETH\nCASHOUT 10 TXTC - Cash out to USDC\nCASHOUT 0.001 ETH - Cash out ETH\nMENU - Show this help".to_string()
+        "Text-to-Chain Commands:\nSTART - Guided setup\nJOIN <name> - Create wallet\nBALANCE - Check balance\nBALANCE ALL - Every chain\nSEND 10 TXTC TO name.ttcip.eth\nSEND - Step by step\nBUY 10 - Buy TXTC with airtime\nDEPOSIT - Get deposit address\nREQUEST 10 TXTC - Payment link\nBILL 12 USDC lunch - Bill a customer\nPAY <code> - Pay a bill\nCONNECT - Link a dApp\nALLOWANCES - Token approvals\nSAVE 10 - Earn yield on USDC\nREDEEM <code> - Redeem voucher\nSWAP 10 TXTC - Swap to ETH\nCASHOUT 10 TXTC - Cash out to USDC\nCASHOUT 0.001 ETH - Cash out ETH\nCONFIRM <code> - Confirm agent cash\nALERT BELOW 5 - Low balance alert\nGIFT NAME bob +254... - Give a name\nSELL NAME bob 5 - Sell your name\nBUY NAME bob - Buy a listed name\nVERIFY - Raise send limits\nSTATEMENT - Last month's activity\nCANCEL SEND - Stop a SEND just sent\nNOTIFY DIGEST - Daily summary texts\nGREETING <words> - Spot fake texts\nINVITE - Invite a friend\nMENU - Show this help".to_string()
     }
 
-    async fn join_response(&self, from: &str, ens_name: Option<String>) -> String {
+    pub(super) async fn join_response(&self, from: &str, ens_name: Option<String>) -> String {
         // Check if database is available
         let Some(ref repo) = self.user_repo else {
             return "DB offline. Try later.".to_string();
@@ -327,81 +1376,27 @@ impl CommandProcessor {
 
         // If ENS name provided, validate and register it
         if let Some(name) = ens_name {
-            // Validate format
-            if name.len() < 3 || name.len() > 20 {
-                return "ENS name must be 3-20 characters.\n\nTry again: JOIN <name>\nExample: JOIN alice".to_string();
-            }
-            if !name.chars().all(|c| c.is_alphanumeric()) {
-                return "ENS name can only contain letters and numbers.\n\nTry again: JOIN <name>".to_string();
-            }
-
             // Check if user already has a wallet
-            match repo.find_by_phone(from).await {
-                Ok(Some(user)) => {
-                    // User exists, register ENS name
-                    let client = reqwest::Client::new();
-                    
-                    // Check if name is available
-                    let check_result = client
-                        .get(&format!("{}/api/ens/check/{}", self.backend_url, name))
-                        .send()
-                        .await;
-
-                    match check_result {
-                        Ok(resp) if resp.status().is_success() => {
-                            if let Ok(check_data) = resp.json::<serde_json::Value>().await {
-                                if !check_data["available"].as_bool().unwrap_or(false) {
-                                    let reason = check_data["reason"].as_str().unwrap_or("Name not available");
-                                    return format!(
-                                        "❌ {}\n\nTry another name:\nJOIN <name>\n\nExamples: alice, bob123, john",
-                                        reason
-                                    );
-                                }
-                            }
-                        }
-                        _ => {
-                            return "Error checking name availability. Try later.".to_string();
-                        }
+            return match repo.find_by_phone(from).await {
+                Ok(Some(user)) => match self.register_ens_name(from, &user.wallet_address, &name, None).await {
+                    Ok(full_ens) => format!(
+                        "Registered!\n{}\nWallet: {}\n\nReply DEPOSIT to fund.",
+                        full_ens,
+                        display_address(&user.wallet_address)
+                    ),
+                    Err(EnsRegistrationError::Invalid(reason)) => {
+                        format!("{}\n\nTry again: JOIN <name>\nExample: JOIN alice", reason)
                     }
-
-                    // Name is available, register it
-                    let full_ens = format!("{}.ttcip.eth", name);
-                    let register_result = client
-                        .post(&format!("{}/api/ens/register", self.backend_url))
-                        .json(&serde_json::json!({
-                            "ensName": name,
-                            "walletAddress": user.wallet_address
-                        }))
-                        .send()
-                        .await;
-
-                    match register_result {
-                        Ok(resp) if resp.status().is_success() => {
-                            // Save ENS name to database
-                            let full_ens = format!("{}.ttcip.eth", name);
-                            if let Err(e) = repo.update_ens_name(from, &full_ens).await {
-                                tracing::error!("Failed to save ENS name to database: {}", e);
-                            }
-                            
-                            // TODO: Mint ENS subdomain on-chain here
-                            return format!(
-                                "Registered!\n{}\nWallet: {}\n\nReply DEPOSIT to fund.",
-                                full_ens,
-                                user.wallet_address
-                            );
-                        }
-                        _ => {
-                            return "Error registering ENS name. Try later.".to_string();
-                        }
-                    }
-                }
-                Ok(None) => {
-                    return "Please use JOIN first to create your wallet.".to_string();
-                }
-                Err(_) => {
-                    return "Error. Try later.".to_string();
-                }
-            }
+                    Err(EnsRegistrationError::Unavailable(reason)) => format!(
+                        "❌ {}\n\nTry another name:\nJOIN <name>\n\nExamples: alice, bob123, john",
+                        reason
+                    ),
+                    Err(EnsRegistrationError::Service(msg)) => msg.to_string(),
+                    Err(EnsRegistrationError::Unfunded) => format!("{}\nTry JOIN <name> again later.", MINTS_PAUSED_REPLY),
+                },
+                Ok(None) => "Please use JOIN first to create your wallet.".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            };
         }
 
         // No ENS name provided - check if user already exists
@@ -410,62 +1405,35 @@ impl CommandProcessor {
                 // User already has wallet, just show welcome message
                 return format!(
                     "Welcome back!\n\nYour wallet:\n{}\n\nReply BALANCE or DEPOSIT",
-                    user.wallet_address
+                    display_address(&user.wallet_address)
                 );
             }
             Ok(None) => {
-                // New user - create wallet and prompt for ENS name
-                let wallet = match UserWallet::create_new() {
-                    Ok(w) => w,
-                    Err(e) => {
-                        tracing::error!("Wallet error: {}", e);
-                        return "Error creating wallet.".to_string();
-                    }
-                };
-
-                // Encrypt private key
-                let encrypted_key = hex::encode(wallet.private_key_bytes());
+                // Beta mode: numbers not yet allowed in go on the waitlist
+                if let Some(reply) = self.beta_gate(from).await {
+                    return reply;
+                }
 
-                // Save to database
-                match repo.create(from, &wallet.address_string(), &encrypted_key).await {
-                    Ok(_) => {
+                // New user - create wallet and prompt for ENS name
+                match self.create_user_wallet(from).await {
+                    Ok(wallet) => {
                         // Create Arc wallet for USDC cashout
-                        let arc_url = std::env::var("ARC_SERVICE_URL").unwrap_or_else(|_| "http://arc:8084".to_string());
-                        let client = reqwest::Client::new();
-                        let arc_wallet = match client
-                            .post(&format!("{}/api/arc/wallet", arc_url))
-                            .json(&serde_json::json!({ "phone": from }))
-                            .timeout(std::time::Duration::from_secs(10))
-                            .send()
-                            .await
-                        {
-                            Ok(resp) => {
-                                if let Ok(data) = resp.json::<serde_json::Value>().await {
-                                    data["wallet"]["address"].as_str().unwrap_or("").to_string()
-                                } else {
-                                    String::new()
-                                }
-                            }
-                            Err(_) => String::new(),
-                        };
+                        let arc_wallet = self.create_arc_wallet(from).await;
 
                         if arc_wallet.is_empty() {
                             format!(
                                 "Wallet created!\n{}\n\nNow pick a name:\nJOIN <name>\n\nEx: JOIN alice",
-                                wallet.address_string()
+                                display_address(&wallet.address_string())
                             )
                         } else {
                             format!(
                                 "Wallet created!\n{}\nArc (USDC): {}...\n\nNow pick a name:\nJOIN <name>\n\nEx: JOIN alice",
-                                wallet.address_string(),
+                                display_address(&wallet.address_string()),
                                 &arc_wallet[..10.min(arc_wallet.len())]
                             )
                         }
                     }
-                    Err(e) => {
-                        tracing::error!("DB save error: {}", e);
-                        "Error saving wallet.".to_string()
-                    }
+                    Err(msg) => msg.to_string(),
                 }
             }
             Err(e) => {
@@ -475,7 +1443,198 @@ impl CommandProcessor {
         }
     }
 
-    async fn balance_response(&self, from: &str) -> String {
+    /// Create the user's Arc wallet for USDC cashout (empty string if unavailable)
+    pub(super) async fn create_arc_wallet(&self, from: &str) -> String {
+        let arc_url = &self.arc_url;
+        let client = reqwest::Client::new();
+        match client
+            .post(format!("{}/api/arc/wallet", arc_url))
+            .json(&serde_json::json!({ "phone": from }))
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await
+        {
+            Ok(resp) => {
+                if let Ok(data) = resp.json::<serde_json::Value>().await {
+                    data["wallet"]["address"].as_str().unwrap_or("").to_string()
+                } else {
+                    String::new()
+                }
+            }
+            Err(_) => String::new(),
+        }
+    }
+
+    /// Generate a wallet for a new user and store its key sealed by the KeyVault
+    pub(super) async fn create_user_wallet(&self, from: &str) -> Result<UserWallet, &'static str> {
+        let Some(ref repo) = self.user_repo else {
+            return Err("DB offline. Try later.");
+        };
+
+        let wallet = UserWallet::create_new().map_err(|e| {
+            tracing::error!("Wallet error: {}", e);
+            "Error creating wallet."
+        })?;
+
+        let encrypted_key = self.key_vault.seal(&wallet.private_key_bytes()).map_err(|e| {
+            tracing::error!("Key vault error: {}", e);
+            "Error creating wallet."
+        })?;
+
+        let user = repo.create(from, &wallet.address_string(), &encrypted_key).await.map_err(|e| {
+            tracing::error!("DB save error: {}", e);
+            "Error saving wallet."
+        })?;
+
+        if let Some(ref faucet) = self.faucet {
+            faucet.spawn_fund(wallet.address);
+        }
+        // The seat is now held by the wallet, not the waitlist entry
+        if let Some(ref beta) = self.beta {
+            if let Err(e) = beta.repo().remove_from_waitlist(from).await {
+                tracing::warn!("Failed to clear waitlist entry: {}", e);
+            }
+        }
+        self.settle_referral(from, user.id).await;
+
+        Ok(wallet)
+    }
+
+    /// Resolve an ENS name through the lookup cache; Ok(None) = no record
+    pub(super) async fn resolve_ens(&self, name: &str) -> Result<Option<Address>, String> {
+        if is_ttcip_name(name) {
+            self.ens_cache.resolve_with(name, || self.resolve_ttcip(name)).await
+        } else {
+            self.ens_cache
+                .resolve_name(&create_chain_provider(Chain::EthereumMainnet), name)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    }
+
+    /// `<name>.ttcip.eth` via the backend's registrar lookup
+    async fn resolve_ttcip(&self, name: &str) -> Result<Option<Address>, String> {
+        let resp = reqwest::Client::new()
+            .get(format!("{}/api/ens/resolve/{}", self.backend_url, name))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        let json = resp.json::<serde_json::Value>().await.map_err(|e| e.to_string())?;
+        Ok(json["address"].as_str().and_then(|addr| addr.parse().ok()))
+    }
+
+    /// Validate `name` and check `<name>.ttcip.eth` is still available
+    pub(super) async fn check_ens_name(&self, name: &str) -> Result<(), EnsRegistrationError> {
+        // Validate format
+        if name.len() < 3 || name.len() > 20 {
+            return Err(EnsRegistrationError::Invalid("ENS name must be 3-20 characters."));
+        }
+        if !name.chars().all(|c| c.is_alphanumeric()) {
+            return Err(EnsRegistrationError::Invalid("ENS name can only contain letters and numbers."));
+        }
+        if let Some(rule) = self.name_policy.check(name) {
+            tracing::info!(name = %name, rule = %rule.label, kind = ?rule.kind, "ENS mint refused: reserved name");
+            return Err(EnsRegistrationError::Unavailable(format!("{} is reserved", name.to_lowercase())));
+        }
+
+        let client = reqwest::Client::new();
+
+        // Check if name is available
+        let check_result = client
+            .get(format!("{}/api/ens/check/{}", self.backend_url, name))
+            .send()
+            .await;
+
+        match check_result {
+            Ok(resp) if resp.status().is_success() => {
+                if let Ok(check_data) = resp.json::<serde_json::Value>().await {
+                    if !check_data["available"].as_bool().unwrap_or(false) {
+                        let reason = check_data["reason"].as_str().unwrap_or("Name not available");
+                        return Err(EnsRegistrationError::Unavailable(reason.to_string()));
+                    }
+                }
+            }
+            _ => return Err(EnsRegistrationError::Service("Error checking name availability. Try later.")),
+        }
+        Ok(())
+    }
+
+    /// Validate, check availability of, and register `<name>.ttcip.eth` for
+    /// a wallet; `gift` is set when another user is giving the name
+    pub(super) async fn register_ens_name(
+        &self,
+        from: &str,
+        wallet_address: &str,
+        name: &str,
+        gift: Option<&NameGift>,
+    ) -> Result<String, EnsRegistrationError> {
+        self.check_ens_name(name).await?;
+        let client = reqwest::Client::new();
+
+        // While campaigns are active, one of them pays for the mint
+        let sponsor = match self.campaigns {
+            Some(ref campaigns) => match campaigns.sponsor().await {
+                Ok(Sponsorship::Campaign(campaign)) => Some(campaign),
+                Ok(Sponsorship::Unsponsored) => None,
+                Ok(Sponsorship::Exhausted) => {
+                    tracing::warn!(name = %name, "ENS mint refused: campaign gas budgets exhausted");
+                    return Err(EnsRegistrationError::Unfunded);
+                }
+                Err(e) => {
+                    tracing::error!("Failed to look up mint sponsor: {}", e);
+                    return Err(EnsRegistrationError::Service("Error registering ENS name. Try later."));
+                }
+            },
+            None => None,
+        };
+
+        // Name is available, register it
+        let register_result = client
+            .post(format!("{}/api/ens/register", self.backend_url))
+            .json(&serde_json::json!({
+                "ensName": name,
+                "walletAddress": wallet_address
+            }))
+            .send()
+            .await;
+
+        match register_result {
+            Ok(resp) if resp.status().is_success() => {
+                // Save ENS name to database
+                let full_ens = format!("{}.ttcip.eth", name);
+                let minted = resp.json::<serde_json::Value>().await.unwrap_or_default();
+                let cost = MintCost::from_wei(
+                    minted["gasUsed"].as_str().unwrap_or("0"),
+                    minted["gasCostWei"].as_str().unwrap_or("0"),
+                )
+                .unwrap_or_default();
+                let giver = gift.map(|gift| gift.giver_id);
+                if let (Some(ref campaign), Some(ref campaigns)) = (&sponsor, &self.campaigns) {
+                    match campaigns.record_mint(campaign.id, &full_ens, minted["txHash"].as_str(), cost, giver).await {
+                        Ok(()) => tracing::info!(campaign = %campaign.id, name = %full_ens, gas_used = cost.gas_used, cost_gwei = cost.cost_gwei, "ENS mint charged to campaign"),
+                        Err(e) => tracing::error!(campaign = %campaign.id, "Failed to charge ENS mint to campaign: {}", e),
+                    }
+                }
+                if let (Some(gift), Some(ref gifts)) = (gift, &self.name_gifts) {
+                    let campaign = sponsor.as_ref().map(|campaign| campaign.id);
+                    if let Err(e) = gifts.record_mint(gift.id, campaign, minted["txHash"].as_str(), cost).await {
+                        tracing::error!(gift = %gift.id, "Failed to record gifted ENS mint: {}", e);
+                    }
+                }
+                // A cached "not found" from before registration would hide the new name
+                self.ens_cache.invalidate(&full_ens);
+                if let Some(ref repo) = self.user_repo {
+                    if let Err(e) = repo.update_ens_name(from, &full_ens).await {
+                        tracing::error!("Failed to save ENS name to database: {}", e);
+                    }
+                }
+                Ok(full_ens)
+            }
+            _ => Err(EnsRegistrationError::Service("Error registering ENS name. Try later.")),
+        }
+    }
+
+    pub(super) async fn balance_response(&self, from: &str) -> String {
         let Some(ref repo) = self.user_repo else {
             return "Balance: $0.00\nDB offline.".to_string();
         };
@@ -510,6 +1669,18 @@ impl CommandProcessor {
             }
         };
 
+        // Custodial balance held on the ledger (agent cash-ins etc.)
+        let custodial = match self.ledger_repo {
+            Some(ref ledger) => ledger.balance(&user_account(user.id)).await.unwrap_or(0),
+            None => 0,
+        };
+        let custodial_line = if custodial > 0 {
+            format!("\nCash balance: {:.2} USDC", micro_to_f64(custodial))
+        } else {
+            String::new()
+        };
+        let savings_line = self.savings_line(&user).await;
+
         if result["success"].as_bool().unwrap_or(false) {
             let txtc_balance = result["balances"]["txtc"].as_str().unwrap_or("0");
             let eth_balance = result["balances"]["eth"].as_str().unwrap_or("0");
@@ -519,10 +1690,18 @@ impl CommandProcessor {
             let eth: f64 = eth_balance.parse().unwrap_or(0.0);
             
             if txtc > 0.0 || eth > 0.0 {
+                let prices = self.token_prices().await;
                 format!(
-                    "Balance:\n{} TXTC\n{} ETH\n\nSepolia testnet",
-                    txtc, eth
+                    "Balance:\n{} TXTC{}\n{} ETH{}{}{}\n\nSepolia testnet",
+                    txtc,
+                    prices.annotate(txtc, "TXTC"),
+                    eth,
+                    prices.annotate(eth, "ETH"),
+                    custodial_line,
+                    savings_line
                 )
+            } else if custodial > 0 || !savings_line.is_empty() {
+                format!("Balance:{}{}\n\nReply DEPOSIT to fund wallet.", custodial_line, savings_line)
             } else {
                 "Balance: $0.00\n\nReply DEPOSIT to fund wallet.".to_string()
             }
@@ -531,17 +1710,47 @@ impl CommandProcessor {
         }
     }
 
+    /// BALANCE ALL: every chain at once; a chain that doesn't answer in time
+    /// gets its own line instead of holding up the rest
+    async fn balance_all_response(&self, from: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        let user = match repo.find_by_phone(from).await {
+            Ok(Some(u)) => u,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let Ok(address) = user.wallet_address.parse::<Address>() else {
+            return "Error. Try later.".to_string();
+        };
+
+        let (balances, prices) = tokio::join!(self.multi_chain.balances_everywhere(address), self.token_prices());
+        let custodial = match self.ledger_repo {
+            Some(ref ledger) => ledger.balance(&user_account(user.id)).await.unwrap_or(0),
+            None => 0,
+        };
+        let mut reply = format_all_balances(&balances, &prices, NumberLocale::for_phone(from));
+        if custodial > 0 {
+            reply.push_str(&format!("\nCash balance: {:.2} USDC", micro_to_f64(custodial)));
+        }
+        if let Some(ref minimums) = self.send_minimums {
+            for line in minimums.dust_lines(&balances) {
+                reply.push_str(&format!("\n{}", line));
+            }
+        }
+        reply
+    }
+
     async fn pin_response(&self, from: &str, new_pin: Option<String>) -> String {
         match new_pin {
             Some(pin) => {
-                if pin.len() < 4 || pin.len() > 6 || !pin.chars().all(|c| c.is_ascii_digit()) {
+                if !Self::is_valid_pin(&pin) {
                     "PIN must be 4-6 digits.\nExample: PIN 1234".to_string()
                 } else {
                     // Save PIN hash
                     if let Some(ref repo) = self.user_repo {
-                        // Simple hash for demo (use bcrypt in production)
-                        let pin_hash = format!("{:x}", sha2::Sha256::digest(pin.as_bytes()));
-                        if repo.update_pin(from, &pin_hash).await.is_ok() {
+                        if repo.update_pin(from, &hash_pin(&pin)).await.is_ok() {
                             return "PIN set!".to_string();
                         }
                     }
@@ -552,11 +1761,22 @@ impl CommandProcessor {
         }
     }
 
-    async fn send_response(&self, from: &str, amount: f64, token: &str, recipient: &str) -> String {
+    /// Check PIN format: 4-6 digits
+    pub(super) fn is_valid_pin(pin: &str) -> bool {
+        (4..=6).contains(&pin.len()) && pin.chars().all(|c| c.is_ascii_digit())
+    }
+
+    async fn send_response(&self, from: &str, amount: f64, token: &str, recipient: &str, repeat: bool) -> String {
         let token_upper = token.to_uppercase();
-        // Support TXTC and ETH
-        if token_upper != "TXTC" && token_upper != "ETH" {
-            return format!("Supported tokens: TXTC, ETH\nExample: SEND 10 TXTC swarnim.ttcip.eth");
+        // Support TXTC and ETH on-chain, plus USDC cash balances between users
+        if token_upper != "TXTC" && token_upper != "ETH" && token_upper != INTERNAL_TOKEN {
+            return format!("Supported tokens: TXTC, ETH, USDC\nExample: SEND 10 TXTC swarnim.ttcip.eth");
+        }
+        // On-chain sends settle where user wallets live; cash sends cost no gas
+        if token_upper != INTERNAL_TOKEN {
+            if let Some(reply) = self.check_send_minimum(TXTC_CHAIN, &token_upper, amount) {
+                return reply;
+            }
         }
 
         // Get sender's wallet and private key
@@ -571,9 +1791,12 @@ impl CommandProcessor {
         };
 
         // Resolve recipient address (wallet address, phone number, or ENS name)
-        let recipient_address = if recipient.starts_with("0x") && recipient.len() == 42 {
-            // Already a wallet address
-            recipient.to_string()
+        let recipient_address = if recipient.starts_with("0x") || recipient.starts_with("0X") {
+            // Already a wallet address - reject typos caught by the EIP-55 checksum
+            match parse_address(recipient) {
+                Ok(address) => format!("{:?}", address),
+                Err(e) => return e.sms_message(),
+            }
         } else if recipient.starts_with("+") {
             // Phone number - look up in database
             match user_repo.find_by_phone(recipient).await {
@@ -582,28 +1805,22 @@ impl CommandProcessor {
                 Err(_) => { return "Error looking up recipient.".to_string(); },
             }
         } else if recipient.contains(".eth") || recipient.contains(".") {
-            // ENS name (e.g., swarnim.ttcip.eth) - resolve via backend
-            let client = reqwest::Client::new();
-            let resolve_url = format!("{}/api/ens/resolve/{}", self.backend_url, recipient);
-            match client.get(&resolve_url).send().await {
-                Ok(resp) => {
-                    match resp.json::<serde_json::Value>().await {
-                        Ok(json) => {
-                            if let Some(addr) = json["address"].as_str() {
-                                addr.to_string()
-                            } else {
-                                return format!("Could not resolve {}.\nUse wallet address instead.", recipient);
-                            }
-                        },
-                        Err(_) => { return format!("Could not resolve {}.", recipient); },
-                    }
-                },
-                Err(_) => { return "Network error resolving ENS. Try later.".to_string(); },
+            // ENS name: our own subdomains via the backend, anything else on mainnet
+            match self.resolve_ens(recipient).await {
+                Ok(Some(address)) => format!("{:?}", address),
+                Ok(None) => return format!("Could not resolve {}.\nUse wallet address instead.", recipient),
+                Err(e) => {
+                    tracing::warn!(name = %recipient, "ENS resolution failed: {}", e);
+                    return "Network error resolving ENS. Try later.".to_string();
+                }
             }
         } else {
-            // Try as contact name from address book
-            if let Some(ref address_book) = self.address_book_repo {
-                match address_book.find_by_name(from, recipient).await {
+            // Try as contact name from address book, longest name first
+            if self.address_book_repo.is_some() {
+                match self.find_contact(from, recipient).await {
+                    Ok(contacts) if contacts.len() > 1 => {
+                        return choose_contact_reply(&contacts, &format!("SEND {} {} TO", amount, token_upper));
+                    }
                     Ok(contacts) if !contacts.is_empty() => {
                         let contact = &contacts[0];
                         if let Some(ref addr) = contact.wallet_address {
@@ -624,6 +1841,64 @@ impl CommandProcessor {
             }
         };
 
+        // Addresses outside TextChain have to be saved long enough first
+        if let Some(reply) = self.check_withdrawal(from, &recipient_address).await {
+            return reply;
+        }
+
+        // The same transfer again within a few minutes is likely a double send
+        if !repeat {
+            if let Some(reply) = self.hold_repeat_send(&sender, amount, &token_upper, &recipient_address, recipient).await {
+                return reply;
+            }
+        }
+
+        // Large amounts wait for a second approval
+        if let Some(reply) = self.hold_large_send(&sender, amount, &token_upper, &recipient_address, recipient).await {
+            return reply;
+        }
+
+        // Everything else waits out the cancellation window, when there is one
+        if self.send_queue.is_some() {
+            return self.queue_send(&sender, amount, &token_upper, &recipient_address, recipient).await;
+        }
+
+        match self.execute_send(&sender, amount, &token_upper, &recipient_address, recipient).await {
+            Ok(reply) | Err(reply) => reply,
+        }
+    }
+
+    /// Move the funds of a SEND whose recipient is resolved (Err = refused or failed)
+    pub(super) async fn execute_send(&self, sender: &User, amount: f64, token_upper: &str, recipient_address: &str, recipient: &str) -> Result<String, String> {
+        let reply = self.move_funds(sender, amount, token_upper, recipient_address, recipient).await?;
+        self.record_send(sender, amount, token_upper, recipient_address, recipient).await;
+        Ok(reply)
+    }
+
+    async fn move_funds(&self, sender: &User, amount: f64, token_upper: &str, recipient_address: &str, recipient: &str) -> Result<String, String> {
+        let from = sender.phone.as_str();
+
+        // Cash balances settle on the ledger when both sides are users; USDC
+        // the ledger can't settle goes on chain from the sender's wallet
+        if token_upper == INTERNAL_TOKEN {
+            let cash = Money::from_f64(amount, Currency::USDC).map_err(|e| e.to_string())?;
+            if let Some(result) = self.cash_send(sender, cash, recipient_address, recipient).await {
+                return result;
+            }
+            if let Some(reply) = self.check_send_minimum(TXTC_CHAIN, token_upper, amount) {
+                return Err(reply);
+            }
+        }
+
+        // Unseal the sender's key for the signing backend
+        let sender_key = match self.key_vault.open_private_key(&sender.encrypted_private_key) {
+            Ok(key) => hex::encode(key),
+            Err(e) => {
+                tracing::error!("Failed to unseal key for {}: {}", from, e);
+                return Err("Error. Try later.".to_string());
+            }
+        };
+
         // Route through Yellow Network for instant finality
         let client = reqwest::Client::new();
         let api_url = &format!("{}/api/send-yellow", self.backend_url);
@@ -638,7 +1913,7 @@ impl CommandProcessor {
                 "amount": amount.to_string(),
                 "token": token_upper,
                 "userPhone": from,
-                "senderKey": sender.encrypted_private_key
+                "senderKey": sender_key
             }))
             .timeout(std::time::Duration::from_secs(30))
             .send()
@@ -647,7 +1922,7 @@ impl CommandProcessor {
             Ok(resp) => resp,
             Err(e) => {
                 tracing::error!("Failed to call Yellow API: {}", e);
-                return "Network error. Try later.".to_string();
+                return Err("Network error. Try later.".to_string());
             }
         };
 
@@ -656,23 +1931,31 @@ impl CommandProcessor {
             Ok(json) => json,
             Err(e) => {
                 tracing::error!("Failed to parse API response: {}", e);
-                return "Error processing response.".to_string();
+                return Err("Error processing response.".to_string());
             }
         };
 
         if result["success"].as_bool().unwrap_or(false) {
-            format!(
-                "Sending {} {} to {}...\n\nQueued via Yellow Network.\nYou'll get SMS when complete.",
-                amount, token_upper, recipient
-            )
+            self.events.publish(Topic::Transfer, serde_json::json!({
+                "kind": "yellow",
+                "from": sender.wallet_address,
+                "to": recipient_address,
+                "amount": amount,
+                "token": token_upper,
+            }));
+            Ok(format!(
+                "Sending {} {} to {}...{}\n\nQueued via Yellow Network.\nYou'll get SMS when complete.",
+                amount, token_upper, display_address(recipient), check_words_line(recipient)
+            ))
         } else {
             let error_msg = result["error"].as_str().unwrap_or("Unknown error");
             tracing::error!("Transfer failed: {}", error_msg);
+            self.events.publish(Topic::Error, serde_json::json!({ "source": "send", "from": from, "error": error_msg }));
             
             if error_msg.contains("insufficient") || error_msg.contains("balance") {
-                "Insufficient balance.".to_string()
+                Err("Insufficient balance.".to_string())
             } else {
-                "Transfer failed. Try later.".to_string()
+                Err("Transfer failed. Try later.".to_string())
             }
         }
     }
@@ -684,12 +1967,22 @@ impl CommandProcessor {
 
         match repo.find_by_phone(from).await {
             Ok(Some(user)) => {
-                let deposit_address = if let Some(ref ens) = user.ens_name {
+                // Deposit addresses are swept, so the ENS name (which
+                // resolves to the wallet) isn't offered alongside them
+                let deposit_address = if let Some(ref addresses) = self.deposit_addresses {
+                    match addresses.for_user(from).await {
+                        Ok(assigned) => display_address(&assigned.address),
+                        Err(e) => {
+                            tracing::error!("Failed to assign deposit address: {}", e);
+                            return "Error. Try later.".to_string();
+                        }
+                    }
+                } else if let Some(ref ens) = user.ens_name {
                     ens.clone()
                 } else {
-                    user.wallet_address.clone()
+                    display_address(&user.wallet_address)
                 };
-                
+
                 format!(
                     "Fund wallet:\nDial *384*46750#\nOr REDEEM <code>\nOr send to:\n{}",
                     deposit_address
@@ -705,9 +1998,8 @@ impl CommandProcessor {
         if let Some(ref deposit_repo) = self.deposit_repo {
             if let Ok(deposits) = deposit_repo.get_recent(from, 5).await {
                 if !deposits.is_empty() {
-                    let history: Vec<String> = deposits.iter()
-                        .map(|d| format!("${:.2} via {}", d.amount_as_f64(), d.source))
-                        .collect();
+                    let locale = NumberLocale::for_phone(from);
+                    let history: Vec<String> = deposits.iter().map(|d| d.sms_string(locale)).collect();
                     return format!("Recent deposits:\n{}", history.join("\n"));
                 }
             }
@@ -716,6 +2008,15 @@ impl CommandProcessor {
     }
 
     async fn redeem_response(&self, from: &str, code: &str) -> String {
+        // Catch typos before any lookup
+        let code = match check_code(code) {
+            CodeCheck::Valid(code) | CodeCheck::Legacy(code) => code,
+            CodeCheck::Mistyped => {
+                return format!("Code {} doesn't look right - did you mistype it?\nCheck the card and reply REDEEM <code>", code);
+            }
+        };
+        let code = code.as_str();
+
         // Check if user has wallet
         let Some(ref user_repo) = self.user_repo else {
             return "DB offline. Try later.".to_string();
@@ -728,6 +2029,13 @@ impl CommandProcessor {
             Err(_) => return "Error. Try later.".to_string(),
         };
 
+        // Face value as issued (local-currency vouchers show both amounts);
+        // a seeded batch's code gets its row here, before the backend looks
+        let face_value = match self.voucher_repo {
+            Some(ref repo) => repo.find_or_issue(code).await.ok().flatten().map(|v| v.sms_string(NumberLocale::for_phone(from))),
+            None => None,
+        };
+
         // Call Contract API to redeem voucher on-chain
         let client = reqwest::Client::new();
         let api_url = &format!("{}/api/redeem", self.backend_url);
@@ -767,9 +2075,10 @@ impl CommandProcessor {
             
             tracing::info!("Voucher redeemed successfully: {} TXTC + {} ETH, tx: {}", token_amount, eth_amount, tx_hash);
             
+            let value = face_value.map(|v| format!("Value: {}\n", v)).unwrap_or_default();
             format!(
-                "Voucher redeemed!\n\nReceived:\n{} TXTC\n{} ETH (gas)\n\nReply BALANCE to check.",
-                token_amount, eth_amount
+                "Voucher redeemed!\n{}\nReceived:\n{} TXTC\n{} ETH (gas)\n\nReply BALANCE to check.",
+                value, token_amount, eth_amount
             )
         } else {
             let error_msg = result["error"].as_str().unwrap_or("Unknown error");
@@ -870,7 +2179,7 @@ impl CommandProcessor {
             Err(_) => return "Error. Try later.".to_string(),
         };
 
-        let arc_url = std::env::var("ARC_SERVICE_URL").unwrap_or_else(|_| "http://arc:8084".to_string());
+        let arc_url = &self.arc_url;
         let client = reqwest::Client::new();
         let token_upper = token.to_uppercase();
 
@@ -906,6 +2215,10 @@ impl CommandProcessor {
             Err(_) => return "Error. Try later.".to_string(),
         };
 
+        if let Some(reply) = Chain::from_input(from_chain).and_then(|chain| self.check_send_minimum(chain, token, amount)) {
+            return reply;
+        }
+
         let client = reqwest::Client::new();
 
         tracing::info!(
@@ -952,13 +2265,26 @@ impl CommandProcessor {
         }
     }
 
-    async fn save_response(&self, from: &str, name: &str, phone: &str) -> String {
+    async fn save_response(&self, from: &str, name: &str, contact: &str) -> String {
         let Some(ref address_book) = self.address_book_repo else {
             return "Address book offline.".to_string();
         };
 
-        match address_book.add_contact(from, name, Some(phone), None).await {
-            Ok(_) => format!("Saved {} as {}.", phone, name),
+        // A wallet address is saved checksummed, with words to confirm it by
+        let is_address = contact.starts_with("0x") || contact.starts_with("0X");
+        let saved = if is_address {
+            let address = match parse_address(contact) {
+                Ok(address) => format!("{:?}", address),
+                Err(e) => return e.sms_message(),
+            };
+            address_book.add_contact(from, name, None, Some(&address)).await
+        } else {
+            address_book.add_contact(from, name, Some(contact), None).await
+        };
+
+        let cooldown = if is_address { self.withdrawal_cooldown_note() } else { String::new() };
+        match saved {
+            Ok(_) => format!("Saved {} as {}.{}{}", display_address(contact), name, check_words_line(contact), cooldown),
             Err(_) => "Error saving contact.".to_string(),
         }
     }
@@ -970,7 +2296,7 @@ impl CommandProcessor {
 
         match address_book.list_all(from).await {
             Ok(contacts) if contacts.is_empty() => {
-                "No contacts yet.\n\nSAVE <name> <phone>".to_string()
+                "No contacts yet.\n\nSAVE <name> <phone or 0x address>".to_string()
             }
             Ok(contacts) => {
                 let list: Vec<String> = contacts.iter()
@@ -991,6 +2317,11 @@ impl CommandProcessor {
             );
         };
 
+        // Make sure the chain is reachable before switching to it
+        if let Err(e) = self.multi_chain.call(chain, |p| async move { p.get_block_number().await }).await {
+            return e.sms_message();
+        }
+
         // For now, just acknowledge - could save preference to DB
         format!(
             "Switched to {}!\n\nChain ID: {}\nNative: {}",
@@ -1012,6 +2343,23 @@ impl CommandProcessor {
     }
 }
 
+/// SAVE <amount> [USDC] moves money into savings; SAVE <name> <phone> saves a contact
+fn is_savings_amount(parts: &[&str]) -> bool {
+    let amount = parts.get(1).is_some_and(|p| p.parse::<f64>().is_ok());
+    amount && (parts.len() == 2 || (parts.len() == 3 && parts[2] == "USDC"))
+}
+
+/// Hash a PIN for storage
+/// Simple hash for demo (use bcrypt in production)
+pub(crate) fn hash_pin(pin: &str) -> String {
+    format!("{:x}", sha2::Sha256::digest(pin.as_bytes()))
+}
+
+/// Our own subdomains, resolved through the registrar instead of mainnet
+pub(super) fn is_ttcip_name(name: &str) -> bool {
+    name.to_lowercase().ends_with(".ttcip.eth")
+}
+
 impl std::fmt::Debug for CommandProcessor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("CommandProcessor")
@@ -1026,9 +2374,10 @@ impl std::fmt::Debug for CommandProcessor {
 mod tests {
     use super::*;
     use crate::wallet::create_shared_provider;
+    use crate::kill_switch::KillSwitchError;
 
     fn test_processor() -> CommandProcessor {
-        CommandProcessor::new(None, create_shared_provider())
+        CommandProcessor::new(None, KeyVault::unsealed(), create_shared_provider())
     }
 
     #[test]
@@ -1044,7 +2393,9 @@ mod tests {
         let processor = test_processor();
         assert_eq!(processor.parse("JOIN"), Command::Join { ens_name: None });
         assert_eq!(processor.parse("JOIN john"), Command::Join { ens_name: Some("john".to_string()) });
-        assert_eq!(processor.parse("start"), Command::Join { ens_name: None });
+        assert_eq!(processor.parse("start"), Command::Start);
+        assert_eq!(processor.parse("invite inv-abcd efgh"), Command::Invite { code: Some("inv-abcdefgh".to_string()) });
+        assert_eq!(processor.parse("INVITE"), Command::Invite { code: None });
     }
 
     #[test]
@@ -1052,6 +2403,7 @@ mod tests {
         let processor = test_processor();
         assert_eq!(processor.parse("BALANCE"), Command::Balance);
         assert_eq!(processor.parse("bal"), Command::Balance);
+        assert_eq!(processor.parse("balance all"), Command::BalanceAll);
     }
 
     #[test]
@@ -1059,8 +2411,149 @@ mod tests {
         let processor = test_processor();
         
         let cmd = processor.parse("SEND 10 USDC TO +917123456789");
-        assert!(matches!(cmd, Command::Send { amount, token, recipient } 
+        assert!(matches!(cmd, Command::Send { amount, token, recipient, repeat: false }
             if amount == 10.0 && token == "USDC" && recipient == "+917123456789"));
+
+        // Contact names with spaces, token left out
+        let send = |token: &str, recipient: &str| Command::Send { amount: 5.0, token: token.to_string(), recipient: recipient.to_string(), repeat: false };
+        assert_eq!(processor.parse("send 5 to aunt mary"), send("TXTC", "aunt mary"));
+        assert_eq!(processor.parse("SEND 5 USDC TO Aunt Mary's wallet."), send("USDC", "Aunt Mary's wallet"));
+        assert_eq!(processor.parse("SEND 5 TXTC aunt mary"), send("TXTC", "aunt mary"));
+        // AGAIN confirms a repeated SEND; on its own it is the recipient
+        assert_eq!(
+            processor.parse("send 5 txtc aunt mary again"),
+            Command::Send { amount: 5.0, token: "txtc".to_string(), recipient: "aunt mary".to_string(), repeat: true }
+        );
+        assert_eq!(processor.parse("SEND 5 TO again"), send("TXTC", "again"));
+        assert_eq!(processor.parse("SEND"), Command::SendGuided { amount: None });
+        assert_eq!(processor.parse("send 10 usdc"), Command::SendGuided { amount: Some("10 usdc".to_string()) });
+        assert_eq!(processor.parse("SEND 5 to"), Command::SendGuided { amount: Some("5".to_string()) });
+        assert_eq!(processor.parse("SAVE aunt mary +254700000001"), Command::Save { name: "AUNT MARY".to_string(), contact: "+254700000001".to_string() });
+    }
+
+    #[test]
+    fn test_parse_agent_cash() {
+        let processor = test_processor();
+
+        assert_eq!(
+            processor.parse("CASHIN +254700000001 500"),
+            Command::AgentCash { kind: CashKind::CashIn, customer: "+254700000001".to_string(), amount: 500.0 }
+        );
+        assert_eq!(
+            processor.parse("cashout +254700000001 20"),
+            Command::AgentCash { kind: CashKind::CashOut, customer: "+254700000001".to_string(), amount: 20.0 }
+        );
+        // Wallet cashout is unchanged
+        assert!(matches!(processor.parse("CASHOUT 10 TXTC"), Command::Cashout { .. }));
+        assert_eq!(processor.parse("CONFIRM 123456"), Command::Confirm { code: "123456".to_string() });
+        assert_eq!(processor.parse("guardian +254700000002"), Command::Guardian { arg: Some("+254700000002".to_string()) });
+        assert_eq!(processor.parse("GUARDIAN off"), Command::Guardian { arg: Some("OFF".to_string()) });
+        assert_eq!(processor.parse("alert below 5"), Command::Alert { setting: Some(AlertSetting::Below(Some(Money::usdc(5_000_000)))) });
+        assert!(matches!(processor.parse("ALERT SOMETIMES"), Command::Unknown(_)));
+        assert_eq!(processor.parse("gift accept"), Command::Gift { action: GiftAction::Accept });
+        assert!(matches!(processor.parse("GIFT NAME Mary aunt mary"), Command::Gift { action: GiftAction::Offer { .. } }));
+        assert_eq!(processor.parse("verify"), Command::Verify { arg: None });
+        assert_eq!(processor.parse("VERIFY 123456"), Command::Verify { arg: Some("123456".to_string()) });
+        assert_eq!(processor.parse("verify id"), Command::Verify { arg: Some("ID".to_string()) });
+        assert_eq!(processor.parse("statement"), Command::Statement { month: None });
+        assert_eq!(processor.parse("STMT sep"), Command::Statement { month: Some("SEP".to_string()) });
+        assert_eq!(processor.parse("cancel send"), Command::CancelSend);
+        assert!(matches!(processor.parse("CANCEL"), Command::Unknown(_)));
+        assert_eq!(processor.parse("notify"), Command::Notify { mode: None });
+        assert_eq!(processor.parse("NOTIFY digest"), Command::Notify { mode: Some(NotifyMode::Digest) });
+        assert_eq!(processor.parse("notify off"), Command::Notify { mode: Some(NotifyMode::Off) });
+        assert_eq!(processor.parse("greeting Purple  Llama"), Command::Greeting { arg: Some("Purple Llama".to_string()) });
+        assert_eq!(processor.parse("GREETING"), Command::Greeting { arg: None });
+        assert_eq!(processor.parse("sell name Alice 5"), Command::SellName { label: "alice".to_string(), price: Some(Money::usdc(5_000_000)) });
+        assert_eq!(processor.parse("BUY NAME alice yes"), Command::BuyName { label: "alice".to_string(), confirmed: true });
+        assert!(matches!(processor.parse("BUY 10"), Command::Buy { .. }));
+        assert!(matches!(processor.parse("SELL alice"), Command::Unknown(_)));
+        assert!(matches!(processor.parse("NOTIFY SOMETIMES"), Command::Unknown(_)));
+        assert_eq!(
+            processor.parse("bill 12.50 usdc Lunch for 2"),
+            Command::Bill { amount: 12.5, token: "USDC".to_string(), memo: "Lunch for 2".to_string() }
+        );
+        assert_eq!(processor.parse("BILL 3 haircut"), Command::Bill { amount: 3.0, token: "TXTC".to_string(), memo: "haircut".to_string() });
+        assert_eq!(processor.parse("BILL"), Command::Bill { amount: 0.0, token: "TXTC".to_string(), memo: String::new() });
+        assert_eq!(processor.parse("pay"), Command::Pay { code: None, confirmed: false });
+        assert_eq!(processor.parse("pay k7pq2m"), Command::Pay { code: Some("K7PQ2M".to_string()), confirmed: false });
+        assert_eq!(processor.parse("PAY K7PQ2M yes"), Command::Pay { code: Some("K7PQ2M".to_string()), confirmed: true });
+        assert!(processor.parse("PAY K7PQ2M YES").moves_money());
+        assert!(!processor.parse("PAY K7PQ2M").moves_money());
+        assert_eq!(processor.parse("SEND 5 USDC TO +254700000001").outgoing(), Some((5.0, "USDC")));
+        assert_eq!(processor.parse("BALANCE").outgoing(), None);
+    }
+
+    #[test]
+    fn test_parse_request() {
+        let processor = test_processor();
+
+        assert_eq!(processor.parse("REQUEST 10"), Command::Request { amount: 10.0, token: "TXTC".to_string() });
+        assert_eq!(processor.parse("request 0.01 eth"), Command::Request { amount: 0.01, token: "ETH".to_string() });
+        assert!(matches!(processor.parse("REQUEST abc"), Command::Unknown(_)));
+    }
+
+    #[test]
+    fn test_parse_unlock() {
+        let processor = test_processor();
+
+        assert_eq!(
+            processor.parse("unlock aunt mary 1234"),
+            Command::Unlock { name: "AUNT MARY".to_string(), pin: Some("1234".to_string()) }
+        );
+        assert_eq!(processor.parse("UNLOCK SHOP"), Command::Unlock { name: "SHOP".to_string(), pin: None });
+        assert!(matches!(processor.parse("UNLOCK"), Command::Unknown(_)));
+        assert_eq!(processor.parse("UNLOCK SHOP 1234").audit_detail(), "Unlock SHOP");
+    }
+
+    #[test]
+    fn test_parse_savings() {
+        let processor = test_processor();
+
+        assert_eq!(processor.parse("SAVE 10"), Command::SaveFunds { amount: Money::usdc(10_000_000) });
+        assert_eq!(processor.parse("save 2.5 usdc"), Command::SaveFunds { amount: Money::usdc(2_500_000) });
+        assert_eq!(processor.parse("UNSAVE 4"), Command::Unsave { amount: Some(Money::usdc(4_000_000)) });
+        assert_eq!(processor.parse("unsave all"), Command::Unsave { amount: None });
+        assert!(matches!(processor.parse("SAVE 0"), Command::Unknown(_)));
+        assert!(matches!(processor.parse("UNSAVE"), Command::Unknown(_)));
+        // Contacts keep SAVE <name> <phone|0x address>
+        assert!(matches!(processor.parse("SAVE mum +254700000001"), Command::Save { .. }));
+        assert!(matches!(
+            processor.parse("SAVE shop 0x742d35Cc6634C0532925a3b844Bc9e7595f8fE8f"),
+            Command::Save { contact, .. } if contact.eq_ignore_ascii_case("0x742d35Cc6634C0532925a3b844Bc9e7595f8fE8f")
+        ));
+    }
+
+    #[test]
+    fn test_parse_walletconnect() {
+        let processor = test_processor();
+
+        assert_eq!(processor.parse("connect"), Command::Connect);
+        assert_eq!(processor.parse("SIGN 1234"), Command::Sign { pin: Some("1234".to_string()) });
+        assert_eq!(processor.parse("SIGN"), Command::Sign { pin: None });
+        assert_eq!(processor.parse("decline"), Command::Reject);
+        assert_eq!(processor.parse("DISCONNECT"), Command::Disconnect);
+    }
+
+    #[test]
+    fn test_parse_allowances() {
+        let processor = test_processor();
+
+        assert_eq!(
+            processor.parse("approve txtc 50"),
+            Command::Approve { token: "TXTC".to_string(), amount: 50.0, spender: None }
+        );
+        assert_eq!(
+            processor.parse("APPROVE USDC 5 0xAbC0000000000000000000000000000000000001"),
+            Command::Approve {
+                token: "USDC".to_string(),
+                amount: 5.0,
+                spender: Some("0xAbC0000000000000000000000000000000000001".to_string()),
+            }
+        );
+        assert!(matches!(processor.parse("APPROVE TXTC MAX"), Command::Unknown(msg) if msg.starts_with("Unlimited")));
+        assert_eq!(processor.parse("REVOKE txtc"), Command::Revoke { token: "TXTC".to_string(), spender: None });
+        assert_eq!(processor.parse("ALLOWANCES"), Command::Allowances);
     }
 
     #[test]
@@ -1074,6 +2567,14 @@ mod tests {
         assert!(matches!(cmd, Command::Pin { new_pin: None }));
     }
 
+    #[test]
+    fn test_task_class() {
+        let processor = test_processor();
+        assert_eq!(processor.parse("SEND 1 TXTC TO +1234").task_class(), TaskClass::OnChain);
+        assert_eq!(processor.parse("HISTORY").task_class(), TaskClass::DbHeavy);
+        assert_eq!(processor.parse("MENU").task_class(), TaskClass::SmsReply);
+    }
+
     #[test]
     fn test_parse_unknown() {
         let processor = test_processor();
@@ -1081,4 +2582,51 @@ mod tests {
         let cmd = processor.parse("FOOBAR");
         assert!(matches!(cmd, Command::Unknown(_)));
     }
+
+    #[tokio::test]
+    async fn test_disabled_feature_is_refused() {
+        let mut processor = test_processor();
+        let config = crate::config::FeaturesConfig { disabled: "swap".to_string(), reload_secs: 0 };
+        processor.set_features(FeatureFlags::from_config(&config).unwrap());
+
+        assert_eq!(processor.parse("SWAP 5 TXTC").feature(), Some(Feature::Swap));
+        assert_eq!(processor.process("+15551234567", "SWAP 5 TXTC").await, NOT_AVAILABLE_REPLY);
+        assert_eq!(processor.parse("BALANCE").feature(), None);
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch_stops_money_commands() {
+        let mut processor = test_processor();
+        let config = crate::config::KillSwitchConfig { engaged: true, message: "Paused.".to_string() };
+        processor.set_kill_switch(KillSwitch::from_config(&config, None));
+
+        assert_eq!(processor.process("+15551234567", "SEND 5 USDC TO +15557654321").await, "Paused.");
+        assert_eq!(processor.process("+15551234567", "UNSAVE ALL").await, "Paused.");
+        assert!(!processor.parse("BALANCE").moves_money());
+        assert!(!processor.parse("HISTORY").moves_money());
+        assert_ne!(processor.process("+15551234567", "HELP").await, "Paused.");
+        assert!(matches!(processor.kill_switch().set(false, "ops", "test").await, Err(KillSwitchError::ForcedByConfig)));
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_command_held_after_sim_swap() {
+        let mut processor = test_processor();
+        let config = crate::config::SimSwapConfig {
+            provider: "mock".to_string(),
+            window_hours: 72,
+            cache_minutes: 60,
+            block_on_error: false,
+            mock: format!("+15551234567={}", chrono::Utc::now().to_rfc3339()),
+        };
+        let twilio = crate::config::TwilioConfig { account_sid: String::new(), auth_token: Default::default(), phone_number: String::new(), api_base: String::new() };
+        processor.set_sim_swap(SimSwapGuard::from_config(&config, &twilio).unwrap().unwrap());
+
+        let reply = processor.process("+15551234567", "CASHOUT 5 TXTC").await;
+        assert!(reply.starts_with("This number moved to a new SIM recently, so CASHOUT is paused"));
+        // Not sensitive, and other numbers, go on as usual
+        assert!(!processor.process("+15551234567", "MENU").await.contains("new SIM"));
+        assert!(!processor.process("+15557654321", "CASHOUT 5 TXTC").await.contains("new SIM"));
+        assert!(!processor.parse("PIN").sim_swap_sensitive());
+        assert!(processor.parse("PIN 1234").sim_swap_sensitive());
+    }
 }
@@ -0,0 +1,57 @@
+//! REQUEST - payment links for smartphone payers
+//!
+//! Builds an EIP-681 URI for the sender's wallet so a friend with MetaMask
+//! (or any EIP-681 wallet) can pay with one tap. When short links are
+//! enabled the URI is stored and a `/p/<code>` link is sent instead.
+
+use ethers::types::Address;
+
+use super::parser::CommandProcessor;
+use crate::wallet::address::display_address;
+use crate::wallet::payment_uri::{payment_uri, PaymentAsset, TXTC_CHAIN};
+
+impl CommandProcessor {
+    /// REQUEST <amount> [token]
+    pub(super) async fn request_response(&self, from: &str, amount: f64, token: &str) -> String {
+        let Some(ref repo) = self.user_repo else {
+            return "DB offline. Reply JOIN first.".to_string();
+        };
+        let Some(asset) = PaymentAsset::from_symbol(token) else {
+            return "Supported tokens: TXTC, ETH\nExample: REQUEST 10 TXTC".to_string();
+        };
+
+        let user = match repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        let Ok(recipient) = user.wallet_address.parse::<Address>() else {
+            return "Error. Try later.".to_string();
+        };
+
+        let uri = match payment_uri(TXTC_CHAIN, recipient, asset, amount) {
+            Ok(uri) => uri,
+            Err(_) => return "Invalid amount".to_string(),
+        };
+
+        let link = match self.link_repo {
+            Some(ref links) if !self.link_base_url.is_empty() => match links.create(from, &uri).await {
+                Ok(code) => format!("{}/p/{}", self.link_base_url, code),
+                Err(e) => {
+                    tracing::warn!("Failed to create payment short link: {}", e);
+                    uri
+                }
+            },
+            _ => uri,
+        };
+
+        format!(
+            "Request {} {} on {}\nShare this link - it opens their wallet with the payment filled in:\n{}\n\nOr they can send to:\n{}",
+            amount,
+            token.to_uppercase(),
+            TXTC_CHAIN.name(),
+            link,
+            user.ens_name.clone().unwrap_or_else(|| display_address(&user.wallet_address))
+        )
+    }
+}
@@ -0,0 +1,112 @@
+//! RECEIPT <ref>: signed proof of a ledger transfer
+//!
+//! Where the sender's carrier takes MMS the receipt also comes as a picture
+//! (see `receipt_image`), which is easier to forward as proof of payment.
+//! Everywhere else, or if the MMS fails, it is a text.
+
+use uuid::Uuid;
+
+use super::parser::CommandProcessor;
+use crate::db::{user_account, UserRepository};
+use crate::receipts::Receipt;
+use crate::sms::gateway::SmsError;
+use crate::wallet::address::display_address;
+
+/// Shortest transfer id prefix accepted as a ref
+const MIN_REF_LEN: usize = 8;
+
+/// Short ref shown in send confirmations
+pub fn transfer_ref(transfer_id: Uuid) -> String {
+    transfer_id.to_string()[..MIN_REF_LEN].to_string()
+}
+
+impl CommandProcessor {
+    pub(super) async fn receipt_response(&self, from: &str, reference: &str) -> String {
+        let Some(ref signer) = self.receipts else {
+            return "Receipts are not available.".to_string();
+        };
+        let (Some(ref user_repo), Some(ref ledger)) = (&self.user_repo, &self.ledger_repo) else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let reference = reference.to_lowercase();
+        if reference.len() < MIN_REF_LEN || !reference.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+            return "Invalid ref.\nUse the ref from your send confirmation, e.g. RECEIPT 1a2b3c4d".to_string();
+        }
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let transfer = match ledger.find_transfers(&user_account(user.id), &reference).await {
+            Ok(transfers) if transfers.len() > 1 => return "More than one transfer matches.\nSend more of the ref.".to_string(),
+            Ok(mut transfers) => match transfers.pop() {
+                Some(transfer) => transfer,
+                None => return format!("No transfer found with ref {}.", reference),
+            },
+            Err(e) => {
+                tracing::error!("Receipt lookup failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let receipt = Receipt {
+            from: party(user_repo, &transfer.from_account).await,
+            to: party(user_repo, &transfer.to_account).await,
+            amount: transfer.amount,
+            token: "USDC".to_string(),
+            timestamp: transfer.created_at.timestamp(),
+            tx: format!("ledger:{}", transfer.transfer_id),
+        };
+        let proof = match signer.sign(&receipt).await {
+            Ok(proof) => proof,
+            Err(e) => {
+                tracing::error!("Receipt signing failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let check = if self.link_base_url.is_empty() {
+            format!("Proof: {}", proof)
+        } else {
+            format!("Verify: {}/receipts/verify?proof={}", self.link_base_url, proof)
+        };
+        let text = format!(
+            "Receipt {}\n{}\nFrom {}\nTo {}\n{}\n{}",
+            transfer_ref(transfer.transfer_id),
+            receipt.amount_with_token(),
+            display_address(&receipt.from),
+            display_address(&receipt.to),
+            transfer.created_at.format("%Y-%m-%d %H:%M UTC"),
+            check
+        );
+
+        // The picture is fetched from our public URL
+        let Some(notifier) = self.notifier.as_ref().filter(|_| !self.link_base_url.is_empty()) else {
+            return text;
+        };
+        let media_url = format!("{}/receipts/image.png?proof={}", self.link_base_url, proof);
+        match notifier.send_mms(from, &text, &media_url).await {
+            Ok(_) => format!("Receipt {} sent as a picture.", transfer_ref(transfer.transfer_id)),
+            Err(SmsError::MediaUnsupported) => text,
+            Err(e) => {
+                tracing::warn!(to = %from, "Receipt MMS failed, sending text: {}", e);
+                text
+            }
+        }
+    }
+}
+
+/// Wallet address for user accounts; other ledger accounts as they are
+async fn party(user_repo: &UserRepository, account: &str) -> String {
+    let user_id = account.strip_prefix("user:").and_then(|id| id.parse::<Uuid>().ok());
+    match user_id {
+        Some(id) => match user_repo.find_by_id(id).await {
+            Ok(Some(user)) => display_address(&user.wallet_address),
+            _ => account.to_string(),
+        },
+        None => account.to_string(),
+    }
+}
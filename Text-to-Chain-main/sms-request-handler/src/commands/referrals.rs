@@ -0,0 +1,180 @@
+//! INVITE: a user's referral code, and signing up with one
+
+use uuid::Uuid;
+
+use super::parser::CommandProcessor;
+use crate::db::{check_code, Claim, CodeCheck, Settlement, REFERRAL_PREFIX};
+use crate::money::Money;
+use crate::sms::sim_swap::SimSwapCheck;
+
+/// A code typed after INVITE that is a referral code rather than a beta invite
+pub(super) fn referral_code(input: &str) -> Option<String> {
+    match check_code(input) {
+        CodeCheck::Valid(code) if code.starts_with(REFERRAL_PREFIX) => Some(code),
+        _ => None,
+    }
+}
+
+/// What the referrer and the new user each get, for the INVITE reply
+fn bonus_line(referrer: Money, new_user: Money) -> String {
+    match (referrer.is_positive(), new_user.is_positive()) {
+        (true, true) => format!("You get {} and they get {} when they join.", referrer, new_user),
+        (true, false) => format!("You get {} for each friend who joins.", referrer),
+        _ => format!("They get {} when they join.", new_user),
+    }
+}
+
+impl CommandProcessor {
+    /// INVITE: the sender's personal referral code
+    pub(super) async fn invite_code_response(&self, from: &str) -> String {
+        let Some(ref referrals) = self.referrals else {
+            return "Use: INVITE <code>".to_string();
+        };
+        let Some(ref user_repo) = self.user_repo else {
+            return "DB offline. Try later.".to_string();
+        };
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "Create your wallet first.\nReply START to begin.".to_string(),
+            Err(e) => {
+                tracing::error!("DB error: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let counts = async {
+            let code = referrals.repo().code_for(user.id, from).await?;
+            let credited = referrals.repo().credited_count(user.id).await?;
+            Ok::<_, sqlx::Error>((code, credited))
+        };
+        let (code, credited) = match counts.await {
+            Ok(counts) => counts,
+            Err(e) => {
+                tracing::error!("Referral code lookup failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let max = referrals.max_per_referrer();
+        let bonus = if max > 0 && credited >= max {
+            format!("You've had all {} invite bonuses. Thanks for spreading the word!", max)
+        } else {
+            bonus_line(referrals.referrer_bonus(), referrals.new_user_bonus())
+        };
+        let mut reply = format!("Your invite code: {}\nFriends text INVITE {} to this number to sign up.\n{}", code, code, bonus);
+        if credited > 0 {
+            reply.push_str(&format!("\nFriends joined: {}", credited));
+        }
+        reply
+    }
+
+    /// INVITE <referral code> from a number without a wallet: remember who
+    /// referred it, then start signup
+    pub(super) async fn referral_invite_response(&self, from: &str, code: &str) -> String {
+        let (Some(referrals), Some(user_repo)) = (&self.referrals, &self.user_repo) else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        match user_repo.exists(from).await {
+            Ok(true) => return "You already have a wallet.\nReply INVITE for your own code.".to_string(),
+            Ok(false) => {}
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        match referrals.claim(code, from).await {
+            Ok(Claim::Recorded) => tracing::info!(phone = %from, "Referral code used"),
+            // The first code used stands; signup carries on
+            Ok(Claim::AlreadyClaimed) => {}
+            Ok(Claim::UnknownCode) => return "Invite code not found. Check it and try again.".to_string(),
+            Err(e) => {
+                tracing::error!("Referral claim failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        }
+
+        let signup = if self.flow_repo.is_some() {
+            self.start_onboarding(from).await
+        } else {
+            self.join_response(from, None).await
+        };
+        format!("Invite accepted!\n\n{}", signup)
+    }
+
+    /// Pay the referral bonuses for a wallet just created, and tell both sides
+    pub(super) async fn settle_referral(&self, from: &str, user_id: Uuid) {
+        let Some(ref referrals) = self.referrals else {
+            return;
+        };
+        match referrals.repo().is_pending(from).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                tracing::warn!(phone = %from, "Referral lookup failed: {}", e);
+                return;
+            }
+        }
+        // Billed per lookup, so only numbers that used a code are checked
+        let sim_swapped = match self.sim_swap {
+            Some(ref guard) => guard.check(from).await != SimSwapCheck::Clear,
+            None => false,
+        };
+
+        let (referrer_id, referrer_bonus, referee_bonus) = match referrals.settle(from, user_id, sim_swapped).await {
+            Ok(Some(Settlement::Credited { referrer_id, referrer_bonus, referee_bonus })) => {
+                (referrer_id, referrer_bonus, referee_bonus)
+            }
+            Ok(_) => return,
+            Err(e) => {
+                tracing::error!(phone = %from, "Referral settlement failed: {}", e);
+                return;
+            }
+        };
+
+        if referee_bonus > 0 {
+            let bonus = Money::from_micros(referee_bonus, referrals.new_user_bonus().currency());
+            self.notify_receipt(from, &format!("Welcome bonus: {} from your invite.\nReply BALANCE to check.", bonus))
+                .await;
+        }
+        if referrer_bonus > 0 {
+            let Some(ref user_repo) = self.user_repo else {
+                return;
+            };
+            match user_repo.find_by_id(referrer_id).await {
+                Ok(Some(referrer)) => {
+                    let bonus = Money::from_micros(referrer_bonus, referrals.referrer_bonus().currency());
+                    let body = format!("A friend joined with your invite: +{}.\nReply BALANCE to check.", bonus);
+                    self.notify_receipt(&referrer.phone, &body).await;
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!(referrer = %referrer_id, "Referrer lookup failed: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::generate_code;
+    use crate::money::Currency;
+
+    #[test]
+    fn test_referral_code() {
+        let code = generate_code(REFERRAL_PREFIX);
+        assert_eq!(referral_code(&code.to_lowercase()), Some(code.clone()));
+        assert_eq!(referral_code(&generate_code("INV")), None);
+
+        let mut mistyped = code.clone();
+        let last = mistyped.pop().unwrap();
+        mistyped.push(if last == 'A' { 'B' } else { 'A' });
+        assert_eq!(referral_code(&mistyped), None);
+    }
+
+    #[test]
+    fn test_bonus_line() {
+        let usdc = |micros| Money::from_micros(micros, Currency::USDC);
+        assert_eq!(bonus_line(usdc(2_000_000), usdc(1_500_000)), "You get 2 USDC and they get 1.5 USDC when they join.");
+        assert_eq!(bonus_line(usdc(2_000_000), usdc(0)), "You get 2 USDC for each friend who joins.");
+        assert_eq!(bonus_line(usdc(0), usdc(1_000_000)), "They get 1 USDC when they join.");
+    }
+}
@@ -0,0 +1,178 @@
+//! Second thoughts on a repeated SEND
+//!
+//! A SEND of the same amount and token to the same address as one made in
+//! the last `REPEAT_SEND_MINUTES` doesn't go out straight away: the sender
+//! is told about the earlier one and replies AGAIN to send it anyway.
+//! `SEND 10 USDC mom AGAIN` skips the question, so it also works without
+//! flows. The repeated SEND still meets every other check.
+
+use chrono::{DateTime, Utc};
+
+use super::flow::{Flow, FlowData, Step, StepResult};
+use super::parser::{Command, CommandProcessor};
+use crate::config::RepeatSendConfig;
+use crate::db::{NewRecentSend, RecentSendRepository, User};
+use crate::wallet::address::display_address;
+
+const SEND: &str = "send";
+
+/// "10 USDC mom": an amount, a token and a recipient
+fn check_send(input: &str) -> Result<String, String> {
+    let mut parts = input.split_whitespace();
+    let amount = parts.next().and_then(|amount| amount.parse::<f64>().ok());
+    match (amount, parts.next(), parts.next()) {
+        (Some(_), Some(_), Some(_)) => Ok(input.trim().to_string()),
+        _ => Err("Use: SEND <amount> <token> <recipient>".to_string()),
+    }
+}
+
+fn check_again(input: &str) -> Result<String, String> {
+    match input.to_uppercase().as_str() {
+        "AGAIN" => Ok("AGAIN".to_string()),
+        "NO" | "N" => Ok("NO".to_string()),
+        _ => Err("Reply AGAIN to send it again or NO to stop.".to_string()),
+    }
+}
+
+fn is_answer(_: &str, command: &Command) -> bool {
+    matches!(command, Command::Unknown(_))
+}
+
+/// "2 min ago", for the earlier transfer
+fn ago(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    match (now - at).num_minutes() {
+        minutes if minutes < 1 => "under a minute ago".to_string(),
+        minutes => format!("{} min ago", minutes),
+    }
+}
+
+/// The SEND (filled in when the flow starts), AGAIN
+pub(super) struct RepeatSend;
+
+impl Flow for RepeatSend {
+    const NAME: &'static str = "again";
+    const STEPS: &'static [Step] = &[
+        Step {
+            name: SEND,
+            prompt: |_| "Use: SEND <amount> <token> <recipient>".to_string(),
+            accepts: is_answer,
+            validate: check_send,
+            skippable: false,
+            back: false,
+        },
+        Step {
+            name: "again",
+            prompt: |_| "Reply AGAIN to repeat it or NO to stop".to_string(),
+            accepts: is_answer,
+            validate: check_again,
+            skippable: false,
+            back: false,
+        },
+    ];
+    const TIMEOUT_MINS: i64 = 10;
+    const REMINDER: &'static str = "Your SEND is waiting:";
+    const EXIT_REPLY: &'static str = "SEND stopped. Nothing was sent.";
+    const EXPIRED_REPLY: &'static str = "That SEND timed out. Nothing was sent.";
+
+    async fn answer(processor: &CommandProcessor, from: &str, step: &Step, data: &mut FlowData) -> StepResult {
+        if step.name == SEND {
+            return StepResult::Next(None);
+        }
+        if data[step.name] == "NO" {
+            return StepResult::Done(Self::EXIT_REPLY.to_string());
+        }
+        let command = processor.parse(&format!("SEND {} AGAIN", data[SEND]));
+        StepResult::Done(processor.execute(from, command).await)
+    }
+}
+
+/// Where recent transfers are kept, and how far back they count
+#[derive(Clone)]
+pub struct RepeatSendGuard {
+    repo: RecentSendRepository,
+    window_minutes: i32,
+}
+
+impl RepeatSendGuard {
+    /// None when `REPEAT_SEND_MINUTES` is 0
+    pub fn from_config(config: &RepeatSendConfig, repo: RecentSendRepository) -> Option<Self> {
+        config.is_enabled().then(|| Self { repo, window_minutes: config.window_minutes })
+    }
+
+    pub fn window_minutes(&self) -> i32 {
+        self.window_minutes
+    }
+}
+
+impl CommandProcessor {
+    /// Ask before a SEND that repeats a recent identical transfer
+    pub(super) async fn hold_repeat_send(
+        &self,
+        sender: &User,
+        amount: f64,
+        token: &str,
+        recipient_address: &str,
+        recipient: &str,
+    ) -> Option<String> {
+        let guard = self.repeat_sends.as_ref()?;
+        let send = NewRecentSend { sender_id: sender.id, token, amount, recipient, recipient_address };
+        let earlier = match guard.repo.latest_match(&send, guard.window_minutes).await {
+            Ok(earlier) => earlier?,
+            Err(e) => {
+                // Not worth blocking the SEND over
+                tracing::warn!(user = %sender.id, "Repeat SEND lookup failed: {}", e);
+                return None;
+            }
+        };
+        tracing::info!(user = %sender.id, amount, token, "Repeated SEND held for AGAIN");
+
+        let warning = format!(
+            "You already sent {} {} to {} {}.",
+            amount,
+            token,
+            display_address(recipient),
+            ago(earlier, Utc::now())
+        );
+        let details = format!("{} {} {}", amount, token, recipient);
+        if self.flow_repo.is_none() {
+            return Some(format!("{}\nReply SEND {} AGAIN to repeat it.", warning, details));
+        }
+        let data = FlowData::from([(SEND.to_string(), details)]);
+        Some(format!("{}\n{}", warning, self.start_flow::<RepeatSend>(&sender.phone, data).await))
+    }
+
+    /// Remember a transfer that went out, for `hold_repeat_send`
+    pub(super) async fn record_send(&self, sender: &User, amount: f64, token: &str, recipient_address: &str, recipient: &str) {
+        let Some(ref guard) = self.repeat_sends else {
+            return;
+        };
+        let send = NewRecentSend { sender_id: sender.id, token, amount, recipient, recipient_address };
+        if let Err(e) = guard.repo.record(&send).await {
+            tracing::error!(user = %sender.id, "Failed to record SEND: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_answers_checked() {
+        assert_eq!(check_send(" 10 USDC aunt mary "), Ok("10 USDC aunt mary".to_string()));
+        assert!(check_send("10 USDC").is_err());
+        assert!(check_send("ten USDC mom").is_err());
+        assert_eq!(check_again("again"), Ok("AGAIN".to_string()));
+        assert_eq!(check_again("n"), Ok("NO".to_string()));
+        assert!(check_again("YES").is_err());
+    }
+
+    #[test]
+    fn test_ago() {
+        let now = Utc::now();
+        assert_eq!(ago(now - Duration::seconds(20), now), "under a minute ago");
+        assert_eq!(ago(now - Duration::seconds(150), now), "2 min ago");
+    }
+}
@@ -0,0 +1,135 @@
+//! SAVE / UNSAVE - USDC savings that earn vault yield
+//!
+//! Deposits and withdrawals are sent from the user's EOA, so like APPROVE
+//! they need a little ETH for gas. Each one waits until mined, then the
+//! shares moved are recorded in `savings`; BALANCE shows the position.
+
+use std::sync::Arc;
+
+use super::parser::CommandProcessor;
+use crate::db::{SavingsRepository, User};
+use crate::money::Money;
+use crate::wallet::savings::{accrued_yield, SavingsError, SavingsVault};
+use crate::wallet::{create_chain_provider, ChainProvider};
+
+impl CommandProcessor {
+    /// SAVE <amount>
+    pub(super) async fn save_funds_response(&self, from: &str, amount: Money) -> String {
+        let (repo, vault) = match self.savings() {
+            Some(savings) => savings,
+            None => return "Savings not available yet.".to_string(),
+        };
+        let (user, key) = match self.savings_signer(from).await {
+            Ok(signer) => signer,
+            Err(reply) => return reply,
+        };
+
+        let receipt = match vault.deposit(self.savings_provider(vault), &key, amount.micros()).await {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                tracing::error!("SAVE from {} failed: {}", from, e);
+                return savings_error_reply(&e);
+            }
+        };
+        tracing::info!(from = %from, tx = ?receipt.tx_hash, shares = %receipt.shares, "Savings deposit");
+
+        let vault_address = format!("{:?}", vault.address);
+        match repo.record_deposit(user.id, vault.chain.chain_id(), &vault_address, receipt.shares, receipt.assets).await {
+            Ok(position) => format!(
+                "Saved {}.\nSavings: {}\nTx: {:?}\n\nReply UNSAVE <amount> to withdraw.",
+                Money::usdc(receipt.assets).format_with_code(2),
+                Money::usdc(position.principal).format_with_code(2),
+                receipt.tx_hash
+            ),
+            Err(e) => {
+                tracing::error!(tx = ?receipt.tx_hash, "Failed to record savings deposit for {}: {}", from, e);
+                format!("Saved {}.\nTx: {:?}", Money::usdc(receipt.assets).format_with_code(2), receipt.tx_hash)
+            }
+        }
+    }
+
+    /// UNSAVE <amount> | UNSAVE ALL
+    pub(super) async fn unsave_response(&self, from: &str, amount: Option<Money>) -> String {
+        let (repo, vault) = match self.savings() {
+            Some(savings) => savings,
+            None => return "Savings not available yet.".to_string(),
+        };
+        let (user, key) = match self.savings_signer(from).await {
+            Ok(signer) => signer,
+            Err(reply) => return reply,
+        };
+
+        let receipt = match vault.withdraw(self.savings_provider(vault), &key, amount.map(|amount| amount.micros())).await {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                tracing::error!("UNSAVE from {} failed: {}", from, e);
+                return savings_error_reply(&e);
+            }
+        };
+        tracing::info!(from = %from, tx = ?receipt.tx_hash, shares = %receipt.shares, "Savings withdrawal");
+
+        let vault_address = format!("{:?}", vault.address);
+        if let Err(e) = repo.record_withdrawal(user.id, &vault_address, receipt.shares).await {
+            tracing::error!(tx = ?receipt.tx_hash, "Failed to record savings withdrawal for {}: {}", from, e);
+        }
+        format!(
+            "Withdrew {} to your wallet.\nTx: {:?}",
+            Money::usdc(receipt.assets).format_with_code(2),
+            receipt.tx_hash
+        )
+    }
+
+    /// BALANCE line for the user's savings, empty when they have none
+    pub(super) async fn savings_line(&self, user: &User) -> String {
+        let Some((repo, vault)) = self.savings() else {
+            return String::new();
+        };
+        let position = match repo.find(user.id, &format!("{:?}", vault.address)).await {
+            Ok(Some(position)) if !position.shares().is_zero() => position,
+            Ok(_) => return String::new(),
+            Err(e) => {
+                tracing::warn!("Failed to read savings for {}: {}", user.id, e);
+                return String::new();
+            }
+        };
+        match vault.value_of(self.savings_provider(vault), position.shares()).await {
+            Ok(value) => format!(
+                "\nSavings: {} (+{} yield)",
+                Money::usdc(value).format_with_code(2),
+                Money::usdc(accrued_yield(value, position.principal)).format(2)
+            ),
+            // Vault unreachable: the amount put in is still worth showing
+            Err(e) => {
+                tracing::warn!("Failed to value savings for {}: {}", user.id, e);
+                format!("\nSavings: {}", Money::usdc(position.principal).format_with_code(2))
+            }
+        }
+    }
+
+    fn savings(&self) -> Option<(&SavingsRepository, SavingsVault)> {
+        Some((self.savings_repo.as_ref()?, self.savings_vault?))
+    }
+
+    /// The sender and their unsealed key; Err is the SMS reply
+    async fn savings_signer(&self, from: &str) -> Result<(User, [u8; 32]), String> {
+        let user = self.allowance_user(from).await?;
+        let key = self.key_vault.open_private_key(&user.encrypted_private_key).map_err(|e| {
+            tracing::error!("Failed to unseal key for {}: {}", from, e);
+            "Error. Try later.".to_string()
+        })?;
+        Ok((user, key))
+    }
+
+    fn savings_provider(&self, vault: SavingsVault) -> Arc<ChainProvider> {
+        self.multi_chain.get(vault.chain).unwrap_or_else(|| create_chain_provider(vault.chain))
+    }
+}
+
+fn savings_error_reply(error: &SavingsError) -> String {
+    match error {
+        SavingsError::InvalidAmount => "Invalid amount".to_string(),
+        SavingsError::InsufficientFunds => "Not enough USDC. Reply BALANCE to check.".to_string(),
+        SavingsError::NoGas => "Not enough ETH for gas. Reply DEPOSIT to fund.".to_string(),
+        SavingsError::WrongAsset | SavingsError::Transaction(_) => "Error. Try later.".to_string(),
+    }
+}
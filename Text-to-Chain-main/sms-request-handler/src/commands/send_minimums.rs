@@ -0,0 +1,173 @@
+//! Smallest amounts worth sending per chain
+//!
+//! An on-chain transfer of a few cents costs more in gas than it moves, so
+//! SEND and BRIDGE refuse amounts under the chain's minimum for the token.
+//! Cash (ledger) transfers cost no gas and have no minimum. BALANCE ALL
+//! points out balances too small to send, so users know to top them up
+//! rather than leave them stranded.
+
+use std::collections::HashMap;
+
+use super::parser::CommandProcessor;
+use crate::config::SendMinimumConfig;
+use crate::wallet::chains::{Chain, ChainError};
+use crate::wallet::tokens::ChainBalances;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SendMinimumError {
+    #[error("Invalid SEND_MINIMUMS entry {0}")]
+    Entry(String),
+}
+
+/// Minimum on-chain send per chain and token
+#[derive(Debug, Clone, Default)]
+pub struct SendMinimums {
+    /// (chain, upper-case token) -> smallest amount sent
+    minimums: HashMap<(Chain, String), f64>,
+}
+
+impl SendMinimums {
+    /// None when `SEND_MINIMUMS` is empty
+    pub fn from_config(config: &SendMinimumConfig) -> Result<Option<Self>, SendMinimumError> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        Ok(Some(Self { minimums: parse_minimums(&config.minimums)? }))
+    }
+
+    /// Chains with a minimum, for the startup log
+    pub fn chains(&self) -> Vec<&'static str> {
+        let mut chains: Vec<&str> = self.minimums.keys().map(|(chain, _)| chain.short_code()).collect();
+        chains.sort_unstable();
+        chains.dedup();
+        chains
+    }
+
+    pub fn minimum(&self, chain: Chain, token: &str) -> Option<f64> {
+        self.minimums.get(&(chain, token.to_uppercase())).copied()
+    }
+
+    /// The minimum `amount` falls short of, if any
+    pub fn shortfall(&self, chain: Chain, token: &str, amount: f64) -> Option<f64> {
+        self.minimum(chain, token).filter(|minimum| amount < *minimum)
+    }
+
+    /// One line per non-zero balance under its chain's minimum
+    pub fn dust_lines(&self, balances: &[(Chain, Result<ChainBalances, ChainError>)]) -> Vec<String> {
+        balances
+            .iter()
+            .filter_map(|(_, result)| result.as_ref().ok())
+            .flat_map(|balances| std::iter::once(&balances.native).chain(balances.usdc.as_ref()))
+            .filter_map(|balance| {
+                let amount: f64 = balance.formatted().parse().ok().filter(|a| *a > 0.0)?;
+                let minimum = self.shortfall(balance.chain, &balance.symbol, amount)?;
+                Some(dust_line(amount, &balance.symbol, balance.chain, minimum))
+            })
+            .collect()
+    }
+}
+
+/// `sepolia:ETH:0.0005,base:USDC:1` -> (chain, token) -> minimum
+fn parse_minimums(spec: &str) -> Result<HashMap<(Chain, String), f64>, SendMinimumError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(|entry| {
+            let mut parts = entry.split(':').map(str::trim);
+            let (Some(chain), Some(token), Some(minimum), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+                return Err(SendMinimumError::Entry(entry.to_string()));
+            };
+            let chain = Chain::from_input(chain);
+            let minimum = minimum.parse::<f64>().ok().filter(|m| m.is_finite() && *m > 0.0);
+            match (chain, minimum) {
+                (Some(chain), Some(minimum)) if !token.is_empty() => Ok(((chain, token.to_uppercase()), minimum)),
+                _ => Err(SendMinimumError::Entry(entry.to_string())),
+            }
+        })
+        .collect()
+}
+
+/// Reply to a SEND or BRIDGE under the minimum
+fn below_minimum_reply(minimum: f64, token: &str, chain: Chain) -> String {
+    format!(
+        "Minimum send on {} is {} {}.\nSmaller amounts cost more in gas than they move.",
+        chain.name(),
+        minimum,
+        token
+    )
+}
+
+fn dust_line(amount: f64, token: &str, chain: Chain, minimum: f64) -> String {
+    format!(
+        "Your {} {} on {} is below the send minimum ({} {}). Top it up to send it.",
+        amount,
+        token,
+        chain.name(),
+        minimum,
+        token
+    )
+}
+
+impl CommandProcessor {
+    /// Refuse an on-chain transfer under the chain's minimum; None lets it go
+    pub(super) fn check_send_minimum(&self, chain: Chain, token: &str, amount: f64) -> Option<String> {
+        let minimum = self.send_minimums.as_ref()?.shortfall(chain, token, amount)?;
+        Some(below_minimum_reply(minimum, &token.to_uppercase(), chain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wallet::tokens::TokenBalance;
+    use ethers::types::U256;
+
+    fn minimums(spec: &str) -> SendMinimums {
+        SendMinimums::from_config(&SendMinimumConfig { minimums: spec.to_string() }).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_parse_minimums() {
+        let minimums = minimums("sepolia:ETH:0.0005, base:usdc:1");
+        assert_eq!(minimums.minimum(Chain::EthereumSepolia, "eth"), Some(0.0005));
+        assert_eq!(minimums.minimum(Chain::BaseMainnet, "USDC"), Some(1.0));
+        assert_eq!(minimums.minimum(Chain::BaseMainnet, "ETH"), None);
+        assert_eq!(minimums.chains(), ["BASE", "ETH-T"]);
+
+        assert!(SendMinimums::from_config(&SendMinimumConfig { minimums: " ".to_string() }).unwrap().is_none());
+        for bad in ["nowhere:USDC:1", "base:USDC", "base:USDC:0", "base::1", "base:USDC:1:2"] {
+            assert!(SendMinimums::from_config(&SendMinimumConfig { minimums: bad.to_string() }).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_shortfall() {
+        let minimums = minimums("base:USDC:1");
+        assert_eq!(minimums.shortfall(Chain::BaseMainnet, "USDC", 0.5), Some(1.0));
+        assert_eq!(minimums.shortfall(Chain::BaseMainnet, "USDC", 1.0), None);
+        assert_eq!(minimums.shortfall(Chain::ArbitrumOne, "USDC", 0.5), None);
+        assert_eq!(
+            below_minimum_reply(1.0, "USDC", Chain::BaseMainnet),
+            "Minimum send on Base is 1 USDC.\nSmaller amounts cost more in gas than they move."
+        );
+    }
+
+    #[test]
+    fn test_dust_lines() {
+        let minimums = minimums("base:USDC:1,base:ETH:0.001");
+        let balance = |symbol: &str, balance: u64, decimals: u8| TokenBalance {
+            chain: Chain::BaseMainnet,
+            symbol: symbol.to_string(),
+            balance: U256::from(balance),
+            decimals,
+        };
+        let base = ChainBalances {
+            chain: Chain::BaseMainnet,
+            // Nothing at all isn't dust
+            native: balance("ETH", 0, 18),
+            usdc: Some(balance("USDC", 30_000, 6)),
+        };
+        let lines = minimums.dust_lines(&[(Chain::BaseMainnet, Ok(base)), (Chain::ArbitrumOne, Err(ChainError::Timeout(Chain::ArbitrumOne)))]);
+        assert_eq!(lines, ["Your 0.03 USDC on Base is below the send minimum (1 USDC). Top it up to send it."]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Cancellation window for SENDs
+//!
+//! With `SEND_CANCEL_SECS` set, a SEND that passes every check is written
+//! to `send_jobs` instead of going out, and the sender has that long to
+//! reply `CANCEL SEND`. A poller on every instance then claims due jobs and
+//! broadcasts them, texting the sender the usual result. A bare `CANCEL` is
+//! a carrier opt-out keyword, so the command always takes a second word.
+
+use std::time::Duration;
+
+use super::parser::CommandProcessor;
+use crate::config::SendQueueConfig;
+use crate::db::{NewSendJob, SendJob, SendJobRepository, User};
+use crate::wallet::address::{check_words_line, display_address};
+
+/// Jobs claimed per poll
+const CLAIM_BATCH: i64 = 20;
+/// Seconds between polls for due jobs
+const POLL_SECS: u64 = 1;
+/// How far back CANCEL SEND looks to explain why nothing was cancelled
+const RECENT_MINUTES: i32 = 10;
+
+/// Where confirmed SENDs wait, and for how long
+#[derive(Clone)]
+pub struct SendQueue {
+    repo: SendJobRepository,
+    window_secs: i64,
+}
+
+impl SendQueue {
+    /// None when `SEND_CANCEL_SECS` is 0
+    pub fn from_config(config: &SendQueueConfig, repo: SendJobRepository) -> Option<Self> {
+        config.is_enabled().then(|| Self { repo, window_secs: config.cancel_secs })
+    }
+
+    pub fn window_secs(&self) -> i64 {
+        self.window_secs
+    }
+}
+
+impl CommandProcessor {
+    /// Queue a resolved SEND and tell the sender how to stop it
+    pub(super) async fn queue_send(&self, sender: &User, amount: f64, token: &str, recipient_address: &str, recipient: &str) -> String {
+        let Some(ref queue) = self.send_queue else {
+            return "DB offline. Try later.".to_string();
+        };
+        let job = NewSendJob { sender_id: sender.id, token, amount, recipient, recipient_address };
+        match queue.repo.enqueue(&job, queue.window_secs).await {
+            Ok(job) => {
+                tracing::info!(job = %job.id, user = %sender.id, amount, token, "SEND queued");
+                format!(
+                    "Sending {} {} to {}{} in {}s.\nReply CANCEL SEND to stop it.",
+                    amount,
+                    token,
+                    display_address(recipient),
+                    check_words_line(recipient),
+                    queue.window_secs
+                )
+            }
+            Err(e) => {
+                tracing::error!(user = %sender.id, "Failed to queue SEND: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    /// CANCEL SEND: stop the sender's latest SEND if it hasn't gone out
+    pub(super) async fn cancel_send_response(&self, from: &str) -> String {
+        let (Some(queue), Some(user_repo)) = (&self.send_queue, &self.user_repo) else {
+            return "Nothing to cancel: SENDs go out right away.".to_string();
+        };
+        let sender = match user_repo.find_by_phone(from).await {
+            Ok(Some(u)) => u,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        match queue.repo.cancel_latest(sender.id).await {
+            Ok(Some(job)) => {
+                tracing::info!(job = %job.id, user = %sender.id, "SEND cancelled");
+                format!("Cancelled. {} {} to {} was not sent.", job.amount, job.token, display_address(&job.recipient))
+            }
+            Ok(None) => match queue.repo.latest(sender.id, RECENT_MINUTES).await {
+                Ok(Some(job)) if job.status == "running" || job.status == "sent" => {
+                    "Too late: that SEND already went out.".to_string()
+                }
+                Ok(_) => "No SEND to cancel.".to_string(),
+                Err(_) => "Error. Try later.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(user = %sender.id, "Failed to cancel SEND: {}", e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+
+    /// Send every job whose window has closed; returns how many were claimed
+    pub async fn run_due_sends(&self) -> Result<usize, sqlx::Error> {
+        let Some(ref queue) = self.send_queue else {
+            return Ok(0);
+        };
+        let jobs = queue.repo.claim_due(CLAIM_BATCH).await?;
+        let claimed = jobs.len();
+        for job in jobs {
+            self.run_send_job(queue, job).await;
+        }
+        Ok(claimed)
+    }
+
+    async fn run_send_job(&self, queue: &SendQueue, job: SendJob) {
+        let Some(ref user_repo) = self.user_repo else {
+            return;
+        };
+        let sender = match user_repo.find_by_id(job.sender_id).await {
+            Ok(Some(sender)) => sender,
+            Ok(None) => {
+                let _ = queue.repo.finish(job.id, false, "Sender not found").await;
+                return;
+            }
+            Err(e) => {
+                tracing::error!(job = %job.id, "Sender lookup failed: {}", e);
+                let _ = queue.repo.finish(job.id, false, "Error. Try later.").await;
+                return;
+            }
+        };
+
+        // The switch may have been thrown while the job waited
+        let result = if self.kill_switch.is_engaged().await {
+            Err(self.kill_switch.message().to_string())
+        } else {
+            tracing::info!(job = %job.id, user = %sender.id, "Sending queued SEND");
+            self.execute_send(&sender, job.amount, &job.token, &job.recipient_address, &job.recipient).await
+        };
+        let sent = result.is_ok();
+        let (Ok(reply) | Err(reply)) = result;
+        if let Err(e) = queue.repo.finish(job.id, sent, &reply).await {
+            tracing::warn!(job = %job.id, "Failed to record SEND result: {}", e);
+        }
+        self.notify(&sender.phone, &reply).await;
+    }
+
+    /// Poll for due SENDs in the background (no-op without a queue)
+    pub fn start_send_queue(&self) {
+        if self.send_queue.is_none() {
+            return;
+        }
+        let processor = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(POLL_SECS));
+            loop {
+                ticker.tick().await;
+                match processor.run_due_sends().await {
+                    Ok(0) => {}
+                    Ok(claimed) => tracing::info!(claimed, "Ran queued SENDs"),
+                    Err(e) => tracing::warn!("Queued SEND poll failed: {}", e),
+                }
+            }
+        });
+    }
+}
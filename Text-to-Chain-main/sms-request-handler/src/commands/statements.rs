@@ -0,0 +1,55 @@
+//! STATEMENT [month]: the month's cash activity by SMS, with a short-lived
+//! link to the full PDF (or CSV)
+
+use chrono::Utc;
+
+use super::parser::CommandProcessor;
+use crate::reporting::{load_statement, Month};
+
+impl CommandProcessor {
+    pub(super) async fn statement_response(&self, from: &str, month: Option<&str>) -> String {
+        let (Some(ref user_repo), Some(ref statements)) = (&self.user_repo, &self.statements) else {
+            return "DB offline. Try later.".to_string();
+        };
+
+        let today = Utc::now().date_naive();
+        let current = Month::containing(today);
+        let month = match month {
+            Some(text) => match Month::parse(text, today) {
+                Some(month) if month > current => return format!("No statement for {} yet.", month.label()),
+                Some(month) => month,
+                None => return "Usage: STATEMENT [month]\nExample: STATEMENT 2026-09 or STATEMENT SEP".to_string(),
+            },
+            None => current.previous(),
+        };
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+
+        let statement = match load_statement(statements, &user, month).await {
+            Ok(statement) => statement,
+            Err(e) => {
+                tracing::error!("Statement failed: {}", e);
+                return "Error. Try later.".to_string();
+            }
+        };
+        let summary = statement.sms_summary(month == current);
+
+        if self.link_base_url.is_empty() {
+            return summary;
+        }
+        match statements.create_link(user.id, month.first_day(), self.statement_link_minutes).await {
+            Ok(token) => format!(
+                "{}\nFull PDF ({} min): {}/statements/{}",
+                summary, self.statement_link_minutes, self.link_base_url, token
+            ),
+            Err(e) => {
+                tracing::warn!("Failed to create statement link: {}", e);
+                summary
+            }
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! "Did you mean BALANCE?" for messages that aren't a command
+//!
+//! A message is unrecognized when the parser found no command word at all;
+//! a known command with bad arguments gets its own usage reply instead.
+//! The first word is compared with every command word by edit distance (a
+//! swapped pair of letters counts once); later words only count when they
+//! are a command word, as in "what is my balance". The closest commands
+//! are suggested, and on a tie the one users get through with most often
+//! since startup wins (`CommandMetrics::usage`). The first word is counted
+//! in `unrecognized_commands` for product to review.
+
+use std::collections::HashMap;
+
+use super::parser::{Command, CommandProcessor};
+
+/// Commands as suggested, the words that run them, and their metrics name
+const COMMAND_WORDS: &[(&str, &[&str], &str)] = &[
+    ("BALANCE", &["BALANCE", "BAL"], "BALANCE"),
+    ("SEND", &["SEND"], "SEND"),
+    ("JOIN", &["JOIN", "REGISTER"], "JOIN"),
+    ("DEPOSIT", &["DEPOSIT", "RECEIVE"], "DEPOSIT"),
+    ("HISTORY", &["HISTORY", "TRANSACTIONS", "TXS"], "HISTORY"),
+    ("COMMANDS", &["COMMANDS", "MENU", "HELP"], "HELP"),
+    ("START", &["START"], "START"),
+    ("INVITE", &["INVITE"], "INVITE"),
+    ("PIN", &["PIN"], "PIN"),
+    ("REDEEM", &["REDEEM", "VOUCHER", "CODE"], "REDEEM"),
+    ("SWAP", &["SWAP", "EXCHANGE"], "SWAP"),
+    ("CASHIN", &["CASHIN"], "AGENT_CASHIN"),
+    ("CASHOUT", &["CASHOUT", "CASH"], "CASHOUT"),
+    ("CONFIRM", &["CONFIRM"], "CONFIRM"),
+    ("FLOAT", &["FLOAT"], "FLOAT"),
+    ("REQUEST", &["REQUEST", "INVOICE"], "REQUEST"),
+    ("BILL", &["BILL"], "BILL"),
+    ("PAY", &["PAY"], "PAY"),
+    ("CONNECT", &["CONNECT", "WALLETCONNECT"], "CONNECT"),
+    ("DISCONNECT", &["DISCONNECT"], "DISCONNECT"),
+    ("SIGN", &["SIGN"], "SIGN"),
+    ("REJECT", &["REJECT", "DECLINE"], "REJECT"),
+    ("APPROVE", &["APPROVE"], "APPROVE"),
+    ("REVOKE", &["REVOKE"], "REVOKE"),
+    ("ALLOWANCES", &["ALLOWANCES", "APPROVALS"], "ALLOWANCES"),
+    ("RECEIPT", &["RECEIPT", "PROOF"], "RECEIPT"),
+    ("EMAIL", &["EMAIL"], "EMAIL"),
+    ("GUARDIAN", &["GUARDIAN"], "GUARDIAN"),
+    ("GREETING", &["GREETING"], "GREETING"),
+    ("ALERT", &["ALERT", "ALERTS"], "ALERT"),
+    ("GIFT", &["GIFT"], "GIFT"),
+    ("VERIFY", &["VERIFY"], "VERIFY"),
+    ("STATEMENT", &["STATEMENT", "STMT"], "STATEMENT"),
+    ("CANCEL SEND", &["CANCEL"], "CANCEL"),
+    ("NOTIFY", &["NOTIFY", "NOTIFICATIONS"], "NOTIFY"),
+    ("SELL NAME", &["SELL"], "SELL_NAME"),
+    ("BUY", &["BUY", "TOPUP", "PURCHASE"], "BUY"),
+    ("BRIDGE", &["BRIDGE", "CROSS"], "BRIDGE"),
+    ("SAVE", &["SAVE", "ADD"], "SAVE"),
+    ("UNSAVE", &["UNSAVE", "WITHDRAW"], "UNSAVE"),
+    ("UNLOCK", &["UNLOCK"], "UNLOCK"),
+    ("CONTACTS", &["CONTACTS", "BOOK"], "CONTACTS"),
+    ("CHAIN", &["CHAIN", "NETWORK"], "CHAIN"),
+];
+
+/// Most commands suggested at once
+const MAX_SUGGESTIONS: usize = 2;
+/// Words of a message compared with command words
+const MAX_WORDS: usize = 4;
+
+/// Whether the parser fell through without finding a command; its fallback
+/// keeps the whole message, where a usage reply never does
+pub(super) fn is_unrecognized(body: &str, command: &Command) -> bool {
+    let text = body.trim().to_uppercase();
+    matches!(command, Command::Unknown(unknown) if !text.is_empty() && *unknown == text)
+}
+
+/// Edit distance where swapping two neighbouring letters counts as one edit
+pub(super) fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (rows[i - 1][j] + 1).min(rows[i][j - 1] + 1).min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// How far `word` may be from a command word and still be a typo of it
+fn max_distance(word: &str) -> usize {
+    if word.chars().count() <= 5 {
+        1
+    } else {
+        2
+    }
+}
+
+/// Distance from `word` to a command word; a cut-off start of one counts as one edit
+fn distance(word: &str, command_word: &str) -> usize {
+    if word.chars().count() >= 3 && command_word.starts_with(word) && word != command_word {
+        return 1;
+    }
+    edit_distance(word, command_word)
+}
+
+/// Commands the message was most likely meant to be, closest first; ties
+/// go to the command used most
+pub(super) fn suggest(text: &str, usage: &HashMap<&'static str, u64>) -> Vec<&'static str> {
+    let words: Vec<(bool, String)> = text
+        .split_whitespace()
+        .take(MAX_WORDS)
+        .enumerate()
+        .map(|(i, word)| (i == 0, word.trim_matches(|c: char| !c.is_alphanumeric()).to_uppercase()))
+        .filter(|(_, word)| !word.is_empty())
+        .collect();
+
+    let mut candidates: Vec<(usize, u64, usize, &'static str)> = COMMAND_WORDS
+        .iter()
+        .enumerate()
+        .filter_map(|(order, (shown, command_words, name))| {
+            let closest = words
+                .iter()
+                .flat_map(|(first, word)| {
+                    let max = if *first { max_distance(word) } else { 0 };
+                    command_words
+                        .iter()
+                        .map(move |command_word| distance(word, command_word))
+                        .filter(move |d| *d <= max)
+                })
+                .min()?;
+            Some((closest, usage.get(name).copied().unwrap_or(0), order, *shown))
+        })
+        .collect();
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+    candidates.into_iter().take(MAX_SUGGESTIONS).map(|(_, _, _, shown)| shown).collect()
+}
+
+/// Reply to an unrecognized message
+pub(super) fn suggestion_reply(text: &str, suggestions: &[&str]) -> String {
+    match suggestions {
+        [] => format!("Unknown: {}\n\nReply COMMANDS for help.", text.chars().take(15).collect::<String>()),
+        [only] => format!("Did you mean {}?\nReply COMMANDS for help.", only),
+        [first, second, ..] => format!("Did you mean {} or {}?\nReply COMMANDS for help.", first, second),
+    }
+}
+
+/// `text` with digits hidden, so PINs, amounts and numbers aren't kept
+fn masked(text: &str, max_chars: usize) -> String {
+    text.chars().take(max_chars).map(|c| if c.is_ascii_digit() { '#' } else { c }).collect()
+}
+
+impl CommandProcessor {
+    /// Point the sender at the closest commands and count what they typed
+    pub(super) async fn unrecognized_response(&self, body: &str) -> String {
+        let text = body.trim().to_uppercase();
+        let suggestions = suggest(&text, &self.metrics.usage());
+
+        if let Some(ref log) = self.unrecognized {
+            let word = masked(text.split_whitespace().next().unwrap_or_default(), 20);
+            if let Err(e) = log.record(&word, &masked(&text, 40), suggestions.first().copied()).await {
+                tracing::warn!("Failed to record unrecognized command: {}", e);
+            }
+        }
+        suggestion_reply(&text, &suggestions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("BALANCE", "BALANCE"), 0);
+        assert_eq!(edit_distance("BALNCE", "BALANCE"), 1);
+        assert_eq!(edit_distance("SNED", "SEND"), 1);
+        assert_eq!(edit_distance("HSITORY", "HISTORY"), 1);
+        assert_eq!(edit_distance("", "PIN"), 3);
+    }
+
+    #[test]
+    fn test_suggest_typos() {
+        let usage = HashMap::new();
+        assert_eq!(suggest("BALNCE", &usage), ["BALANCE"]);
+        assert_eq!(suggest("BALA", &usage), ["BALANCE"]);
+        assert_eq!(suggest("SNED 5 USDC TO BOB", &usage), ["SEND"]);
+        assert_eq!(suggest("WHAT IS MY BALANCE?", &usage), ["BALANCE"]);
+        assert_eq!(suggest("CANCEL", &usage), ["CANCEL SEND"]);
+        assert_eq!(suggest("HELP ME", &usage), ["COMMANDS"]);
+        assert!(suggest("HELLO THERE", &usage).is_empty());
+        assert!(suggest("1234", &usage).is_empty());
+    }
+
+    #[test]
+    fn test_suggest_ties_go_to_the_most_used() {
+        // PAY and BUY are both one edit from PUY
+        let mut usage = HashMap::new();
+        assert_eq!(suggest("PUY", &usage), ["PAY", "BUY"]);
+        usage.insert("BUY", 10);
+        usage.insert("PAY", 2);
+        assert_eq!(suggest("PUY", &usage), ["BUY", "PAY"]);
+    }
+
+    #[test]
+    fn test_is_unrecognized() {
+        assert!(is_unrecognized("balnce", &Command::Unknown("BALNCE".to_string())));
+        assert!(!is_unrecognized("REDEEM", &Command::Unknown("Usage: REDEEM <code>".to_string())));
+        assert!(!is_unrecognized("", &Command::Unknown(String::new())));
+        assert!(!is_unrecognized("balance", &Command::Balance));
+    }
+
+    #[test]
+    fn test_suggestion_reply_and_masking() {
+        assert_eq!(suggestion_reply("BALNCE", &["BALANCE"]), "Did you mean BALANCE?\nReply COMMANDS for help.");
+        assert_eq!(suggestion_reply("PUY", &["PAY", "BUY"]), "Did you mean PAY or BUY?\nReply COMMANDS for help.");
+        assert!(suggestion_reply("HELLO", &[]).starts_with("Unknown: HELLO"));
+        assert_eq!(masked("PIN 1234", 40), "PIN ####");
+        assert_eq!(masked("ABCDEFGHIJ", 4), "ABCD");
+    }
+}
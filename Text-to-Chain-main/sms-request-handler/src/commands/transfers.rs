@@ -0,0 +1,140 @@
+//! Internal SEND between users' custodial cash balances
+//!
+//! Cash balances (agent cash-ins and the like) live on the ledger, so a
+//! `SEND <amount> USDC` to another TextChain user is a single ledger
+//! transfer: it settles instantly, with no transaction and no gas fee.
+//! TXTC and ETH sit in users' own wallets and keep going on-chain. With
+//! cash payouts on (`CASH_PAYOUT_CHAIN`), USDC to an outside address is paid
+//! out on chain from the cash balance (see `crate::payouts`); without them
+//! it is sent on chain from the sender's wallet like any other token.
+
+use super::parser::CommandProcessor;
+use super::receipts::transfer_ref;
+use crate::db::{user_account, LedgerError, LedgerRepository, User};
+use crate::events::Topic;
+use crate::money::Money;
+use crate::payouts::PayError;
+use crate::sms::display::short_hash;
+use crate::wallet::address::display_address;
+
+/// Token held on the custodial ledger
+pub const INTERNAL_TOKEN: &str = "USDC";
+/// Ledger entry kind for user-to-user sends
+const P2P_KIND: &str = "p2p";
+
+impl CommandProcessor {
+    /// SEND <amount> USDC: settle on the ledger when the recipient is a
+    /// user, or pay out from the cash balance when payouts are on. None when
+    /// the ledger can't settle it and it should go on chain instead.
+    pub(super) async fn cash_send(&self, sender: &User, amount: Money, recipient_address: &str, recipient: &str) -> Option<Result<String, String>> {
+        let (Some(ref ledger), Some(ref user_repo)) = (&self.ledger_repo, &self.user_repo) else {
+            return None;
+        };
+        if !amount.is_positive() {
+            return Some(Err("Invalid amount".to_string()));
+        }
+
+        match user_repo.find_by_wallet(recipient_address).await {
+            Ok(Some(recipient_user)) => Some(self.internal_send(ledger, sender, amount, &recipient_user, recipient).await),
+            Ok(None) if self.payouts.is_some() => Some(self.cash_payout(sender, amount, recipient_address, recipient).await),
+            Ok(None) => None,
+            Err(_) => Some(Err("Error looking up recipient.".to_string())),
+        }
+    }
+
+    /// Debit the sender's cash balance and credit the recipient's in one
+    /// ledger transaction (Err = nothing moved)
+    async fn internal_send(&self, ledger: &LedgerRepository, sender: &User, amount: Money, recipient_user: &User, recipient: &str) -> Result<String, String> {
+        if recipient_user.id == sender.id {
+            return Err("You can't send to yourself.".to_string());
+        }
+
+        let from_account = user_account(sender.id);
+        let reference = recipient_user.id.to_string();
+        let transfer_id = match ledger.transfer(&from_account, &user_account(recipient_user.id), amount.micros(), P2P_KIND, Some(&reference)).await {
+            Ok(transfer_id) => {
+                tracing::info!(%transfer_id, from = %sender.id, to = %recipient_user.id, amount = %amount, "Internal transfer settled");
+                self.events.publish(Topic::Transfer, serde_json::json!({
+                    "kind": "internal",
+                    "ref": transfer_ref(transfer_id),
+                    "from": sender.id,
+                    "to": recipient_user.id,
+                    "amount": amount,
+                    "token": amount.currency().code(),
+                }));
+                transfer_id
+            }
+            Err(LedgerError::InsufficientFunds) => return Err(self.insufficient_cash(ledger, &from_account).await),
+            Err(e) => {
+                tracing::error!("Internal transfer failed: {}", e);
+                self.events.publish(Topic::Error, serde_json::json!({ "source": "internal_send", "from": sender.id, "error": e.to_string() }));
+                return Err("Transfer failed. Try later.".to_string());
+            }
+        };
+
+        let from_label = sender.ens_name.clone().unwrap_or_else(|| sender.phone.clone());
+        self.notify_update(
+            &recipient_user.phone,
+            &format!(
+                "Received {} from {}.\nRef {}\nReply BALANCE to check.",
+                amount.format_with_code(2),
+                from_label,
+                transfer_ref(transfer_id)
+            ),
+        )
+        .await;
+
+        let balance = ledger.balance(&from_account).await.unwrap_or(0);
+        self.check_low_balance(sender.id, &sender.phone, balance, amount.micros()).await;
+        let proof_hint = if self.receipts.is_some() {
+            format!("\nReply RECEIPT {} for proof.", transfer_ref(transfer_id))
+        } else {
+            String::new()
+        };
+        Ok(format!(
+            "Sent {} to {}.\nInstant, no fee. Ref {}\nCash balance: {}{}",
+            amount.format_with_code(2),
+            display_address(recipient),
+            transfer_ref(transfer_id),
+            Money::usdc(balance).format_with_code(2),
+            proof_hint
+        ))
+    }
+
+    /// SEND <amount> USDC <address> outside TextChain: debit the cash
+    /// balance and pay it out on chain; refunded if it doesn't go through
+    async fn cash_payout(&self, sender: &User, amount: Money, recipient_address: &str, recipient: &str) -> Result<String, String> {
+        let (Some(ref payouts), Some(ref ledger)) = (&self.payouts, &self.ledger_repo) else {
+            return Err("USDC sends are not available.".to_string());
+        };
+        let from_account = user_account(sender.id);
+        let payout = match payouts.pay(sender.id, amount, recipient_address).await {
+            Ok(payout) => payout,
+            Err(PayError::Ledger(LedgerError::InsufficientFunds)) => return Err(self.insufficient_cash(ledger, &from_account).await),
+            Err(PayError::Refunded(_)) => {
+                return Err("Transfer failed, nothing was taken from your balance. Try later.".to_string());
+            }
+            Err(e) => {
+                tracing::error!("Cash payout failed: {}", e);
+                self.events.publish(Topic::Error, serde_json::json!({ "source": "cash_payout", "from": sender.id, "error": e.to_string() }));
+                return Err("Transfer failed. Try later.".to_string());
+            }
+        };
+
+        let balance = ledger.balance(&from_account).await.unwrap_or(0);
+        self.check_low_balance(sender.id, &sender.phone, balance, amount.micros()).await;
+        Ok(format!(
+            "Sending {} to {} on {}.\nTx {}\nCash balance: {}\nRefunded if it fails.",
+            amount.format_with_code(2),
+            display_address(recipient),
+            payouts.chain().name(),
+            short_hash(payout.tx_hash.as_deref().unwrap_or_default()),
+            Money::usdc(balance).format_with_code(2)
+        ))
+    }
+
+    async fn insufficient_cash(&self, ledger: &LedgerRepository, account: &str) -> String {
+        let balance = ledger.balance(account).await.unwrap_or(0);
+        format!("Insufficient cash balance.\nCash balance: {}", Money::usdc(balance).format_with_code(2))
+    }
+}
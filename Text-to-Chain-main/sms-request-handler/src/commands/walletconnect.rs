@@ -0,0 +1,163 @@
+//! CONNECT - WalletConnect for smartphone users
+//!
+//! `CONNECT` replies with a `wc:` pairing URI to paste into a dApp. dApp
+//! signature requests arrive through the bridge and wait for the user to
+//! reply `SIGN <PIN>` or `REJECT`; `DISCONNECT` ends every session.
+
+use chrono::{TimeZone, Utc};
+use ethers::signers::LocalWallet;
+use serde_json::Value;
+
+use super::parser::{hash_pin, CommandProcessor};
+use crate::wallet::walletconnect::{sign_request, Pairing, WcError, PAIRING_TTL_SECS, USER_REJECTED_CODE};
+
+impl CommandProcessor {
+    /// CONNECT - issue a pairing URI for the sender's wallet
+    pub(super) async fn connect_response(&self, from: &str) -> String {
+        let (Some(wc), Some(bridge), Some(user_repo)) = (&self.wc_repo, &self.wc_bridge, &self.user_repo) else {
+            return "WalletConnect is not available.".to_string();
+        };
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        if user.pin_hash.is_none() {
+            return "Set a PIN first - every signature needs it.\nReply: PIN <4-6 digits>".to_string();
+        }
+
+        let pairing = Pairing::generate(Utc::now().timestamp());
+        let Some(expires_at) = Utc.timestamp_opt(pairing.expiry, 0).single() else {
+            return "Error. Try later.".to_string();
+        };
+        if let Err(e) = wc.create_session(from, &pairing.topic, expires_at).await {
+            tracing::error!("Failed to store WalletConnect pairing: {}", e);
+            return "Error. Try later.".to_string();
+        }
+        if let Err(e) = bridge.pair(&pairing.topic, &pairing.sym_key, pairing.expiry, &user.wallet_address).await {
+            tracing::error!("WalletConnect bridge pairing failed: {}", e);
+            let _ = wc.close_session(&pairing.topic).await;
+            return "WalletConnect unavailable. Try later.".to_string();
+        }
+
+        format!(
+            "Paste this in the dApp's WalletConnect box (valid {} min):\n{}\n\nYou'll get an SMS for every signature.",
+            PAIRING_TTL_SECS / 60,
+            pairing.uri()
+        )
+    }
+
+    /// SIGN <PIN> - approve the oldest pending dApp request
+    pub(super) async fn sign_response(&self, from: &str, pin: Option<String>) -> String {
+        let (Some(wc), Some(bridge), Some(user_repo)) = (&self.wc_repo, &self.wc_bridge, &self.user_repo) else {
+            return "WalletConnect is not available.".to_string();
+        };
+        let Some(pin) = pin else {
+            return "Reply: SIGN <PIN>".to_string();
+        };
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        if user.pin_hash.as_deref() != Some(hash_pin(&pin).as_str()) {
+            return "Wrong PIN.".to_string();
+        }
+
+        let request = match wc.next_pending(from).await {
+            Ok(Some(request)) => request,
+            Ok(None) => return "Nothing to sign.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        // Claim it first so a repeated SIGN can't answer twice
+        match wc.finish_request(request.id, "approved").await {
+            Ok(true) => {}
+            Ok(false) => return "Already handled.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        let wallet = match self
+            .key_vault
+            .open_private_key(&user.encrypted_private_key)
+            .map_err(|e| e.to_string())
+            .and_then(|key| LocalWallet::from_bytes(&key).map_err(|e| e.to_string()))
+        {
+            Ok(wallet) => wallet,
+            Err(e) => {
+                tracing::error!("Failed to unseal key for {}: {}", from, e);
+                return "Error. Try later.".to_string();
+            }
+        };
+
+        let params: Value = serde_json::from_str(&request.params).unwrap_or(Value::Null);
+        let peer = request.peer_name.clone().unwrap_or_else(|| "the dApp".to_string());
+        let sent = match sign_request(&wallet, &request.method, &params).await {
+            Ok(signature) => bridge.respond(&request.topic, request.rpc_id, signature).await,
+            Err(e) => {
+                tracing::warn!("WalletConnect request {} not signed: {}", request.id, e);
+                let _ = bridge.respond_error(&request.topic, request.rpc_id, USER_REJECTED_CODE, &e.to_string()).await;
+                return match e {
+                    WcError::WrongAccount(_) => format!("{} asked for another account. Not signed.", peer),
+                    _ => format!("Could not sign request from {}.", peer),
+                };
+            }
+        };
+
+        match sent {
+            Ok(()) => format!("Signed for {}.", peer),
+            Err(e) => {
+                tracing::error!("WalletConnect response not delivered: {}", e);
+                format!("Signed, but {} could not be reached. Try again in the dApp.", peer)
+            }
+        }
+    }
+
+    /// REJECT - decline the oldest pending dApp request
+    pub(super) async fn reject_response(&self, from: &str) -> String {
+        let (Some(wc), Some(bridge)) = (&self.wc_repo, &self.wc_bridge) else {
+            return "WalletConnect is not available.".to_string();
+        };
+
+        let request = match wc.next_pending(from).await {
+            Ok(Some(request)) => request,
+            Ok(None) => return "Nothing to reject.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        match wc.finish_request(request.id, "rejected").await {
+            Ok(true) => {}
+            Ok(false) => return "Already handled.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        }
+
+        if let Err(e) = bridge
+            .respond_error(&request.topic, request.rpc_id, USER_REJECTED_CODE, "User rejected")
+            .await
+        {
+            tracing::warn!("WalletConnect rejection not delivered: {}", e);
+        }
+        format!("Rejected request from {}.", request.peer_name.unwrap_or_else(|| "the dApp".to_string()))
+    }
+
+    /// DISCONNECT - end all WalletConnect sessions
+    pub(super) async fn disconnect_response(&self, from: &str) -> String {
+        let (Some(wc), Some(bridge)) = (&self.wc_repo, &self.wc_bridge) else {
+            return "WalletConnect is not available.".to_string();
+        };
+
+        let topics = match wc.close_all(from).await {
+            Ok(topics) => topics,
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        if topics.is_empty() {
+            return "No dApps connected.".to_string();
+        }
+        for topic in &topics {
+            if let Err(e) = bridge.disconnect(topic).await {
+                tracing::warn!("WalletConnect disconnect of {} not delivered: {}", topic, e);
+            }
+        }
+        format!("Disconnected {} session(s).", topics.len())
+    }
+}
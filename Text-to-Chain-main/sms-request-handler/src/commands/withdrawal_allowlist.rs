@@ -0,0 +1,176 @@
+//! Withdrawals only to addresses saved a while ago
+//!
+//! With `WITHDRAWAL_ALLOWLIST` on, an on-chain SEND to an address outside
+//! TextChain only goes out once that address has been in the sender's
+//! address book for `WITHDRAWAL_COOLDOWN_HOURS`. Someone who takes over a
+//! phone can't drain the wallet straight to an address of their own: it has
+//! to sit in the book first, where the owner can spot it. `UNLOCK <name>
+//! <PIN>` opens a saved address after `WITHDRAWAL_UNLOCK_MINUTES` instead.
+//! Sends to other users' wallets aren't withdrawals and pass, and so do cash
+//! (USDC) sends unless cash payouts are on.
+
+use chrono::{DateTime, Duration, Utc};
+use ethers::types::Address;
+
+use super::parser::{hash_pin, CommandProcessor};
+use crate::config::WithdrawalAllowlistConfig;
+use crate::db::{AddressBookRepository, SavedAddress};
+use crate::wallet::address::display_address;
+
+#[derive(Clone)]
+pub struct WithdrawalAllowlist {
+    book: AddressBookRepository,
+    cooldown: Duration,
+    unlock_delay: Duration,
+}
+
+impl WithdrawalAllowlist {
+    /// None when `WITHDRAWAL_ALLOWLIST` is off
+    pub fn from_config(config: &WithdrawalAllowlistConfig, book: AddressBookRepository) -> Option<Self> {
+        config.is_enabled().then(|| Self {
+            book,
+            cooldown: Duration::hours(config.cooldown_hours.into()),
+            unlock_delay: Duration::minutes(config.unlock_minutes.into()),
+        })
+    }
+
+    pub fn cooldown_hours(&self) -> i64 {
+        self.cooldown.num_hours()
+    }
+
+    /// When sends to a saved address open: after the cooldown, or sooner once unlocked
+    fn opens_at(&self, saved: &SavedAddress) -> DateTime<Utc> {
+        let cooled = saved.created_at + self.cooldown;
+        saved.unlock_at.map_or(cooled, |unlocked| unlocked.min(cooled))
+    }
+}
+
+/// `5h 20m`, `24h` or `45 min`, rounded up
+fn wait_text(wait: Duration) -> String {
+    let minutes = (wait.num_seconds() + 59) / 60;
+    match minutes {
+        m if m < 60 => format!("{} min", m.max(1)),
+        m if m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{}h {}m", m / 60, m % 60),
+    }
+}
+
+fn not_saved_reply(address: &str, cooldown_hours: i64) -> String {
+    format!(
+        "Withdrawals only go to saved addresses.\nSAVE <name> {} - sends to it open after {}h.",
+        address, cooldown_hours
+    )
+}
+
+fn cooling_reply(name: &str, wait: Duration, unlock_delay: Duration) -> String {
+    format!(
+        "Sends to {} open in {}.\nReply UNLOCK {} <PIN> to open it in {}.",
+        name,
+        wait_text(wait),
+        name,
+        wait_text(unlock_delay)
+    )
+}
+
+impl CommandProcessor {
+    /// Refuse an on-chain SEND to an address that hasn't been saved long
+    /// enough; None lets it go
+    pub(super) async fn check_withdrawal(&self, from: &str, recipient_address: &str) -> Option<String> {
+        let allowlist = self.withdrawals.as_ref()?;
+        let address = format!("{:?}", recipient_address.parse::<Address>().ok()?);
+
+        // Another user's wallet keeps the money in TextChain
+        if let Some(ref users) = self.user_repo {
+            match users.find_by_wallet(&address).await {
+                Ok(Some(_)) => return None,
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!("Withdrawal check failed for {}: {}", from, e);
+                    return Some("Error. Try later.".to_string());
+                }
+            }
+        }
+
+        let saved = match allowlist.book.find_saved_address(from, &address).await {
+            Ok(saved) => saved,
+            Err(e) => {
+                tracing::error!("Withdrawal check failed for {}: {}", from, e);
+                return Some("Error. Try later.".to_string());
+            }
+        };
+        let Some(saved) = saved else {
+            return Some(not_saved_reply(&display_address(&address), allowlist.cooldown_hours()));
+        };
+        let wait = allowlist.opens_at(&saved) - Utc::now();
+        (wait > Duration::zero()).then(|| cooling_reply(&saved.name, wait, allowlist.unlock_delay))
+    }
+
+    /// Line for the SAVE reply of a wallet address
+    pub(super) fn withdrawal_cooldown_note(&self) -> String {
+        match self.withdrawals {
+            Some(ref allowlist) => format!("\nSends to it open in {}h.", allowlist.cooldown_hours()),
+            None => String::new(),
+        }
+    }
+
+    /// UNLOCK <name> <PIN> - open a saved address before its cooldown ends
+    pub(super) async fn unlock_response(&self, from: &str, name: &str, pin: Option<String>) -> String {
+        let (Some(allowlist), Some(user_repo)) = (&self.withdrawals, &self.user_repo) else {
+            return "Saved addresses have no waiting time.".to_string();
+        };
+        let Some(pin) = pin else {
+            return format!("Reply: UNLOCK {} <PIN>", name);
+        };
+
+        let user = match user_repo.find_by_phone(from).await {
+            Ok(Some(user)) => user,
+            Ok(None) => return "No wallet. Reply JOIN first.".to_string(),
+            Err(_) => return "Error. Try later.".to_string(),
+        };
+        if user.pin_hash.as_deref() != Some(hash_pin(&pin).as_str()) {
+            return "Wrong PIN.".to_string();
+        }
+
+        let unlock_at = Utc::now() + allowlist.unlock_delay;
+        match allowlist.book.unlock_address(from, name, unlock_at).await {
+            Ok(saved) if saved.is_empty() => format!("No saved address named {}.", name),
+            Ok(saved) => {
+                let opens_at = saved.iter().map(|s| allowlist.opens_at(s)).max().unwrap_or(unlock_at);
+                tracing::info!(user = %user.id, name, %opens_at, "Saved address unlocked");
+                match opens_at - Utc::now() {
+                    wait if wait > Duration::zero() => format!("Sends to {} open in {}.", name, wait_text(wait)),
+                    _ => format!("Sends to {} are open.", name),
+                }
+            }
+            Err(e) => {
+                tracing::error!("Unlock failed for {}: {}", from, e);
+                "Error. Try later.".to_string()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_text() {
+        assert_eq!(wait_text(Duration::seconds(20)), "1 min");
+        assert_eq!(wait_text(Duration::minutes(45)), "45 min");
+        assert_eq!(wait_text(Duration::minutes(320)), "5h 20m");
+        assert_eq!(wait_text(Duration::hours(24)), "24h");
+    }
+
+    #[test]
+    fn test_replies() {
+        assert_eq!(
+            not_saved_reply("0x742d35Cc6634C0532925a3b844Bc454e4438f44e", 24),
+            "Withdrawals only go to saved addresses.\nSAVE <name> 0x742d35Cc6634C0532925a3b844Bc454e4438f44e - sends to it open after 24h."
+        );
+        assert_eq!(
+            cooling_reply("SHOP", Duration::minutes(300), Duration::minutes(60)),
+            "Sends to SHOP open in 5h.\nReply UNLOCK SHOP <PIN> to open it in 1h."
+        );
+    }
+}
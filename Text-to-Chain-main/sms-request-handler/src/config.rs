@@ -1,24 +1,228 @@
+//! Service configuration
+//!
+//! `AppConfig::load` layers its sources, lowest first: the defaults below,
+//! `textchain.toml` (or the file `CONFIG_TOML` names), `.env`, then the
+//! process environment. Every layer uses the environment variable names,
+//! e.g. `SERVER_PORT = 8080` in the file. Settings are checked before
+//! anything starts: URLs must parse, addresses must match their checksum and
+//! keys must decode, and every problem is reported together. Secrets are
+//! held as `Secret`, so `{:?}` on the config is safe to log.
+
+use std::collections::BTreeMap;
 use std::env;
+use std::fmt;
+use std::ops::Deref;
+
+use ethers::signers::LocalWallet;
+use figment::providers::{Format, Serialized, Toml};
+use figment::Figment;
+use reqwest::Url;
+
+use crate::admin::parse_named_tokens;
+use crate::alerts::Severity;
+use crate::db::PhoneStorage;
+use crate::money::{Currency, Money};
+use crate::sms::composer::Gsm7Mode;
+use crate::wallet::address::{parse_address, AddressError};
+use crate::wallet::deposit_address::DepositKeys;
+
+/// Settings file read when `CONFIG_TOML` isn't set; missing is fine
+pub const DEFAULT_CONFIG_TOML: &str = "textchain.toml";
+
+/// A setting that must never reach a log; `{:?}` only says whether it is set
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Secret {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_empty() { "\"\"" } else { "<redacted>" })
+    }
+}
 
 #[derive(Debug, Clone)]
-pub struct Config {
+pub struct AppConfig {
     pub twilio: TwilioConfig,
+    pub vonage: VonageConfig,
+    pub sms_routing: SmsRoutingConfig,
     pub server: ServerConfig,
+    pub services: ServicesConfig,
+    pub database: DatabaseConfig,
+    pub vault: VaultConfig,
     pub aa: AaConfig,
-    pub admin_private_key: String,
+    pub safe: SafeConfig,
+    pub workers: WorkerConfig,
+    pub graphql: GraphqlConfig,
+    pub rates: RatesConfig,
+    pub sms_cost: SmsCostConfig,
+    pub walletconnect: WalletConnectConfig,
+    pub features: FeaturesConfig,
+    pub kill_switch: KillSwitchConfig,
+    pub transcripts: TranscriptConfig,
+    pub quiet_hours: QuietHoursConfig,
+    pub sms_outages: SmsOutageConfig,
+    pub faucet: FaucetConfig,
+    pub ens_cache: EnsCacheConfig,
+    pub ens_verify: EnsVerifyConfig,
+    pub beta: BetaConfig,
+    pub referrals: ReferralConfig,
+    pub email: EmailConfig,
+    pub receipts: ReceiptConfig,
+    pub deposits: DepositConfig,
+    pub deposit_addresses: DepositAddressConfig,
+    pub tokens: TokensConfig,
+    pub broadcast: BroadcastConfig,
+    pub events: EventsConfig,
+    pub savings: SavingsConfig,
+    pub gas: GasMonitorConfig,
+    pub alerts: OperatorAlertConfig,
+    pub transfer_approval: TransferApprovalConfig,
+    pub send_minimums: SendMinimumConfig,
+    pub send_queue: SendQueueConfig,
+    pub withdrawals: WithdrawalAllowlistConfig,
+    pub payouts: PayoutConfig,
+    pub backups: BackupConfig,
+    pub repeat_send: RepeatSendConfig,
+    pub notify_digest: NotifyDigestConfig,
+    pub kyc: KycConfig,
+    pub carrier_lookup: CarrierLookupConfig,
+    pub sim_swap: SimSwapConfig,
+    pub spam: SpamConfig,
+    pub ens_names: NamePolicyConfig,
+    pub live: LiveConfigConfig,
+    pub local_admin: LocalAdminConfig,
+    pub voice: VoiceConfig,
+    pub admin_private_key: Secret,
+    /// Bearer token for `/admin/*`; required with a database, which is
+    /// when the admin API is served
+    pub admin_token: Secret,
+    /// Extra admin tokens, one per operator, as `name:token,...`
+    pub admin_tokens: Secret,
 }
 
 #[derive(Debug, Clone)]
 pub struct TwilioConfig {
     pub account_sid: String,
-    pub auth_token: String,
+    pub auth_token: Secret,
     pub phone_number: String,
+    /// Messaging API base URL (a mock when load testing)
+    pub api_base: String,
+}
+
+/// Vonage (Nexmo) SMS API, off unless the key and secret are set
+#[derive(Debug, Clone, Default)]
+pub struct VonageConfig {
+    pub api_key: String,
+    pub api_secret: Secret,
+    /// Sender number or alphanumeric ID
+    pub from: String,
+    /// Signing secret for inbound webhooks (empty = accept unsigned)
+    pub signature_secret: Secret,
+}
+
+impl VonageConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.api_key.is_empty() && !self.api_secret.is_empty()
+    }
+}
+
+/// Which provider sends to which destination
+#[derive(Debug, Clone)]
+pub struct SmsRoutingConfig {
+    /// Provider for numbers without a route (`twilio` or `vonage`)
+    pub default_provider: String,
+    /// Provider by calling code, e.g. `254:vonage,234:vonage`
+    pub routes: String,
+    /// Calling codes whose carriers take MMS, e.g. `1,44`
+    pub mms_codes: String,
+}
+
+impl Default for SmsRoutingConfig {
+    fn default() -> Self {
+        Self { default_provider: "twilio".to_string(), routes: String::new(), mms_codes: "1".to_string() }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Public URL of this service, used for short links (empty = none)
+    pub public_base_url: String,
+    /// Minutes a STATEMENT download link stays valid
+    pub statement_link_minutes: i64,
+}
+
+/// Other TextChain services this one calls
+#[derive(Debug, Clone)]
+pub struct ServicesConfig {
+    /// Node backend for ENS, balances, swaps and bridges
+    pub backend_url: String,
+    /// Arc wallet service for USDC cashout
+    pub arc_url: String,
+}
+
+impl Default for ServicesConfig {
+    fn default() -> Self {
+        Self { backend_url: DEFAULT_BACKEND_URL.to_string(), arc_url: DEFAULT_ARC_URL.to_string() }
+    }
+}
+
+/// Default `BACKEND_URL`
+pub const DEFAULT_BACKEND_URL: &str = "http://localhost:3000";
+/// Default `ARC_SERVICE_URL`
+pub const DEFAULT_ARC_URL: &str = "http://arc:8084";
+
+/// Encryption of stored keys and personal data
+#[derive(Debug, Clone, Default)]
+pub struct VaultConfig {
+    /// 32-byte hex master key for wallet keys, phone numbers and backups
+    pub master_key: Secret,
+    /// Key being rotated away from, still accepted for reads (empty = none)
+    pub previous_master_key: Secret,
+    /// Store keys as plain hex without a master key (local development only)
+    pub allow_plaintext: bool,
+    /// encrypted (default) or hashed
+    pub phone_storage: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    /// Postgres for users, ledger and queues (empty = run without a database)
+    pub url: Secret,
+    /// Connections for SMS commands and every write
+    pub write_pool_size: u32,
+    /// Connections for admin lists, exports and the partner API
+    pub read_pool_size: u32,
+    /// Read replica for the read pool (empty = DATABASE_URL)
+    pub read_url: Secret,
+    /// Repository calls at least this long are logged and counted as slow
+    pub slow_query_ms: u64,
 }
 
 #[derive(Debug, Clone)]
@@ -28,46 +232,1293 @@ pub struct AaConfig {
     pub simple_account_factory_address: String,
 }
 
-impl Config {
-    /// Load configuration from environment variables
-    pub fn from_env() -> Result<Self, ConfigError> {
+#[derive(Debug, Clone)]
+pub struct SafeConfig {
+    /// Treasury Safe address (empty = multisig disabled)
+    pub safe_address: String,
+    /// Safe Transaction Service base URL
+    pub tx_service_url: String,
+    /// Chain the Safe is deployed on
+    pub chain: String,
+    /// Per-token amounts at or above which withdrawals go through the Safe,
+    /// e.g. `USDC:1000,ETH:0.5`; tokens not listed always do
+    pub large_withdrawal_thresholds: String,
+}
+
+impl SafeConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.safe_address.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    pub sms_reply_concurrency: usize,
+    pub on_chain_concurrency: usize,
+    pub db_heavy_concurrency: usize,
+    /// Max tasks waiting per class before new work is rejected
+    pub max_queue: usize,
+    /// Seconds a command may run before the sender gets a holding reply (0 = never)
+    pub command_timeout_secs: u64,
+    /// Per-command overrides, e.g. `SEND:30,SWAP:45`
+    pub command_timeouts: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphqlConfig {
+    /// Partner API keys with scopes, e.g. `key1:users|deposits,key2:*` (empty = disabled)
+    pub api_keys: Secret,
+}
+
+impl GraphqlConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.api_keys.trim().is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RatesConfig {
+    /// Fallback rates in local units per USDC, e.g. `KES:129.5,NGN:1550`
+    pub fx_rates: String,
+    /// Live rate source returning `{"rates": {...}}` with a USD base (empty = fallback only)
+    pub source_url: String,
+    /// Token price feed returning `{"prices": {"ETH": 1520.4, ...}}` in USD (empty = no prices)
+    pub price_url: String,
+    /// Seconds prices (or a failed fetch) are reused before asking again
+    pub price_ttl_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmsCostConfig {
+    /// USD per segment by calling code, e.g. `1:0.0079,254:0.12`
+    pub rates: String,
+    /// USD per segment for destinations not in `rates`
+    pub default_rate: f64,
+    /// Daily outbound budget in USD (0 = unlimited)
+    pub daily_budget: f64,
+    /// Warn once spend reaches this percentage of the budget
+    pub alert_percent: f64,
+    /// Trim non-critical messages to one segment while over budget
+    pub trim_over_budget: bool,
+    /// Keeping messages in GSM-7: off, substitute or strict
+    pub gsm7: String,
+}
+
+impl Default for SmsCostConfig {
+    fn default() -> Self {
+        Self {
+            rates: String::new(),
+            default_rate: 0.05,
+            daily_budget: 0.0,
+            alert_percent: 80.0,
+            trim_over_budget: false,
+            gsm7: "off".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WalletConnectConfig {
+    /// WalletConnect bridge sidecar base URL (empty = CONNECT disabled)
+    pub bridge_url: String,
+    /// Shared bearer token for bridge <-> server calls
+    pub bridge_token: Secret,
+}
+
+impl WalletConnectConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.bridge_url.is_empty() && !self.bridge_token.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FeaturesConfig {
+    /// Features off by default in this deployment, e.g. `swap,bridge`
+    pub disabled: String,
+    /// Seconds between reloads of `feature_flags` overrides (0 = startup only)
+    pub reload_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct KillSwitchConfig {
+    /// Stop money-moving commands from startup; the admin API can't release it
+    pub engaged: bool,
+    /// Reply to a command refused while the switch is engaged
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct TranscriptConfig {
+    /// Days to keep support transcripts (0 = transcripts off)
+    pub retention_days: u32,
+}
+
+impl TranscriptConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.retention_days > 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct QuietHoursConfig {
+    /// Local quiet window as `<start>-<end>` hours, e.g. `21-8` (empty = off)
+    pub hours: String,
+    /// Zone for calling codes without a known zone
+    pub default_timezone: String,
+    /// Extra or overriding zones: `1:America/Chicago,254:Africa/Nairobi`
+    pub timezones: String,
+}
+
+/// Carrier outage detection per calling code
+#[derive(Debug, Clone)]
+pub struct SmsOutageConfig {
+    /// Different numbers in one calling code whose sends must fail within
+    /// the window to mark it down (0 = off)
+    pub threshold: usize,
+    pub window_secs: u64,
+    /// Seconds between retries of a held message while a code is down
+    pub probe_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FaucetConfig {
+    /// Testnets to fund new wallets on, e.g. `sepolia,amoy` (empty = off)
+    pub chains: String,
+    /// Native tokens per new wallet (ETH / MATIC)
+    pub drip_amount: f64,
+    /// Treasury faucet wallet for chains without a faucet URL
+    pub private_key: Secret,
+    /// External faucet APIs per chain: `amoy=https://...,sepolia=https://...`
+    pub urls: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnsCacheConfig {
+    /// Seconds to keep a found ENS record
+    pub ttl_secs: u64,
+    /// Seconds to remember that a name or address has no record
+    pub negative_ttl_secs: u64,
+}
+
+/// Background re-resolution of stored ENS names
+#[derive(Debug, Clone)]
+pub struct EnsVerifyConfig {
+    /// Seconds between verification batches (0 = off)
+    pub interval_secs: u64,
+    /// Names resolved per batch, least recently checked first
+    pub batch_size: i64,
+    /// Re-point drifted `.ttcip.eth` names at the user's wallet
+    pub repair: bool,
+}
+
+impl EnsVerifyConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.interval_secs > 0 && self.batch_size > 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BetaConfig {
+    /// Only allowlisted numbers may sign up; others join the waitlist
+    pub enabled: bool,
+    /// Seats (wallets + admitted numbers) before the waitlist stops moving (0 = manual only)
+    pub capacity: i64,
+    /// Seconds between checks for free seats
+    pub release_secs: u64,
+}
+
+/// INVITE referral bonuses, off while both bonuses are 0
+#[derive(Debug, Clone)]
+pub struct ReferralConfig {
+    /// Credited to the user whose code was used
+    pub referrer_bonus: Money,
+    /// Credited to the new user
+    pub new_user_bonus: Money,
+    /// Referrals a user is paid for, ever (0 = no limit)
+    pub max_per_referrer: i64,
+    /// Referrals a user is paid for in any 24 hours (0 = no limit)
+    pub max_per_day: i64,
+}
+
+impl ReferralConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.referrer_bonus.is_positive() || self.new_user_bonus.is_positive()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    /// SMTP relay for replies (empty = email commands off)
+    pub smtp_host: String,
+    /// STARTTLS submission port
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: Secret,
+    /// Address replies and verification codes are sent from
+    pub from_address: String,
+    /// Shared secret the inbound webhook must carry as `?token=`
+    pub inbound_token: Secret,
+}
+
+impl EmailConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.smtp_host.is_empty() && !self.from_address.is_empty() && !self.inbound_token.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReceiptConfig {
+    /// Private key that signs RECEIPT proofs (empty = receipts off)
+    pub signing_key: Secret,
+}
+
+#[derive(Debug, Clone)]
+pub struct DepositConfig {
+    /// Blocks before an on-chain deposit counts, per chain: `amoy:64,sepolia:12`
+    pub confirmations: String,
+    /// Seconds between confirmation checks
+    pub poll_secs: u64,
+    /// Shared secret the deposit monitor sends as `X-Internal-Secret` (empty = off)
+    pub internal_secret: Secret,
+}
+
+impl DepositConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.internal_secret.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DepositAddressConfig {
+    /// BIP-39 seed that per-user deposit addresses are derived from (empty = off)
+    pub mnemonic: Secret,
+    /// Where sweeps send deposited funds (empty = the admin hot wallet)
+    pub treasury_address: String,
+    /// Seconds between sweeps
+    pub sweep_secs: u64,
+    /// Smallest balance per token worth sweeping, e.g. `USDC:10,ETH:0.01`;
+    /// tokens not listed are swept whenever they hold more than the gas costs
+    pub sweep_minimums: String,
+    /// Addresses swept per run
+    pub sweep_batch: i64,
+}
+
+impl DepositAddressConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.mnemonic.trim().is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokensConfig {
+    /// Token contract overrides: `arb-sepolia:USDC=0x...,amoy:TXTC=0x...`
+    pub addresses: String,
+    /// Seconds between re-reads of `token_overrides` (0 = startup only)
+    pub reload_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BroadcastConfig {
+    /// Admin broadcast messages sent per second
+    pub rate_per_sec: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct EventsConfig {
+    /// Token for the `/admin/events` WebSocket (empty = off)
+    pub token: Secret,
+}
+
+impl EventsConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.token.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SavingsConfig {
+    /// ERC-4626 vault holding USDC on the TXTC chain (empty = SAVE off)
+    pub vault_address: String,
+}
+
+impl SavingsConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.vault_address.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GasMonitorConfig {
+    /// Lowest native balance per chain before alerting, e.g.
+    /// `sepolia:0.05,amoy:0.5` (empty = monitor off)
+    pub thresholds: String,
+    /// Other signers to watch, `label:0x...` or `label:0x...@chain`
+    /// (the admin and faucet keys are watched automatically)
+    pub wallets: String,
+    /// Seconds between balance checks
+    pub poll_secs: u64,
+    /// Operator numbers texted when a wallet runs low
+    pub alert_phones: String,
+    /// URL POSTed a JSON alert when a wallet runs low (empty = none)
+    pub alert_webhook_url: String,
+    /// Hours before alerting again about a wallet that is still low
+    pub repeat_hours: u64,
+}
+
+impl GasMonitorConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.thresholds.trim().is_empty()
+    }
+}
+
+/// Where critical events page the operators
+#[derive(Debug, Clone)]
+pub struct OperatorAlertConfig {
+    /// Names this deployment in alerts and their dedupe keys, e.g. `prod-eu`
+    pub deployment: String,
+    /// Slack incoming webhook URLs, comma-separated (empty = no Slack)
+    pub slack_webhooks: Secret,
+    /// Lowest severity posted to Slack: info, warning, error or critical
+    pub slack_min_severity: String,
+    /// PagerDuty Events API v2 routing key (empty = no PagerDuty)
+    pub pagerduty_routing_key: Secret,
+    /// Lowest severity that pages through PagerDuty
+    pub pagerduty_min_severity: String,
+    pub pagerduty_url: String,
+    /// Minutes before an alert with the same dedupe key is sent again
+    pub repeat_minutes: u64,
+    /// Tasks waiting in a worker lane or due SENDs before a backlog alert (0 = never)
+    pub queue_backlog: u64,
+    /// Seconds between job queue checks
+    pub queue_poll_secs: u64,
+}
+
+impl OperatorAlertConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.slack_webhooks.trim().is_empty() || !self.pagerduty_routing_key.trim().is_empty()
+    }
+
+    pub fn slack_urls(&self) -> Vec<String> {
+        self.slack_webhooks.split(',').map(str::trim).filter(|url| !url.is_empty()).map(str::to_string).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransferApprovalConfig {
+    /// SEND amounts per token above which a second approval is needed,
+    /// e.g. `USDC:100,TXTC:5000,ETH:0.05` (empty = off)
+    pub thresholds: String,
+    /// Minutes a held transfer waits when the sender has no guardian; also
+    /// how long a guardian change takes to apply
+    pub cooling_minutes: i64,
+    /// Minutes a held transfer stays open after it could first be approved
+    pub ttl_minutes: i64,
+}
+
+impl TransferApprovalConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.thresholds.trim().is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SendMinimumConfig {
+    /// Smallest on-chain SEND/BRIDGE per chain and token, e.g.
+    /// `sepolia:ETH:0.0005,base:USDC:1` (empty = no minimums)
+    pub minimums: String,
+}
+
+impl SendMinimumConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.minimums.trim().is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SendQueueConfig {
+    /// Seconds a confirmed SEND waits before broadcast so CANCEL SEND can
+    /// stop it (0 = send immediately)
+    pub cancel_secs: i64,
+}
+
+impl SendQueueConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.cancel_secs > 0
+    }
+}
+
+/// On-chain SENDs only to addresses saved in the address book a while ago
+#[derive(Debug, Clone)]
+pub struct WithdrawalAllowlistConfig {
+    /// Hold on-chain SENDs to saved addresses (off = any address)
+    pub enabled: bool,
+    /// Hours a newly saved address waits before sends to it open
+    pub cooldown_hours: u32,
+    /// Minutes UNLOCK <name> <PIN> takes to open a saved address early
+    pub unlock_minutes: u32,
+}
+
+impl WithdrawalAllowlistConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PayoutConfig {
+    /// Chain cash SENDs to outside addresses are paid on (empty = users only)
+    pub chain: String,
+    /// Seconds between checks of sent payouts
+    pub poll_secs: u64,
+}
+
+impl PayoutConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.chain.trim().is_empty()
+    }
+}
+
+/// Encrypted database backups, off unless a bucket is set
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// S3-compatible API, e.g. `https://s3.eu-west-1.amazonaws.com`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: Secret,
+    /// Object name prefix, e.g. `prod/`
+    pub prefix: String,
+    /// `endpoint/bucket/key` instead of `bucket.endpoint/key`
+    pub path_style: bool,
+    /// Hours between scheduled backups (0 = only on `ttc-admin backup`)
+    pub interval_hours: u64,
+    /// Backups kept in the bucket; older ones are deleted (0 = keep all)
+    pub keep: usize,
+    pub pg_dump: String,
+    pub pg_restore: String,
+}
+
+impl BackupConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.bucket.trim().is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RepeatSendConfig {
+    /// Minutes within which an identical SEND needs AGAIN to go out
+    /// (0 = never asked)
+    pub window_minutes: i32,
+}
+
+impl RepeatSendConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.window_minutes > 0
+    }
+}
+
+/// Daily summary for users on NOTIFY DIGEST
+#[derive(Debug, Clone)]
+pub struct NotifyDigestConfig {
+    /// UTC hour (0-23) the digest covers up to and goes out at
+    pub hour: u32,
+}
+
+/// Onboarding tiers: 0 receive only, 1 after phone verification, 2 after ID
+#[derive(Debug, Clone)]
+pub struct KycConfig {
+    /// Hold users to their tier's limits (off = no tiers)
+    pub enabled: bool,
+    /// Largest single send per token at tier 1, e.g. `USDC:20,TXTC:1000`
+    pub tier1_limits: String,
+    /// Same at tier 2 (empty = no limit)
+    pub tier2_limits: String,
+    /// Where users submit ID; VERIFY ID links here (empty = not offered)
+    pub id_url: String,
+}
+
+impl KycConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CarrierLookupConfig {
+    /// Look up senders' carriers with Twilio Lookup (billed per number)
+    pub enabled: bool,
+    /// Days before a number's carrier is looked up again
+    pub refresh_days: i64,
+}
+
+impl CarrierLookupConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimSwapConfig {
+    /// `twilio`, `mock` or empty (no checks)
+    pub provider: String,
+    /// Hours after a SIM change during which sensitive commands are refused
+    pub window_hours: i64,
+    /// Minutes a lookup result is reused
+    pub cache_minutes: i64,
+    /// Refuse sensitive commands when the lookup fails (default: allow)
+    pub block_on_error: bool,
+    /// Swap dates for the mock provider, `+phone=RFC 3339 date,...`
+    pub mock: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpamConfig {
+    /// Screen inbound messages for spam before the command processor
+    pub enabled: bool,
+    /// Score (0-1) from which a message is flagged: still processed, but
+    /// counted against the number and kept as a sample
+    pub flag_score: f64,
+    /// Score from which a message is dropped without a reply
+    pub drop_score: f64,
+    /// Flagged or dropped messages within `strike_window_minutes` that mute a number
+    pub strikes: u32,
+    pub strike_window_minutes: i64,
+    /// First mute; each further one is twice as long, up to a day
+    pub mute_minutes: i64,
+    /// External model scoring messages (empty = rules only)
+    pub model_url: String,
+    /// Bearer token for the model
+    pub model_token: Secret,
+    pub model_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NamePolicyConfig {
+    /// Regex of subdomain labels that can't be minted, on top of the
+    /// built-in and admin-managed lists (empty = none)
+    pub deny_pattern: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LiveConfigConfig {
+    /// JSON file of settings that apply without a restart (empty = off)
+    pub file: String,
+    /// Seconds between checks of the file for changes (0 = startup and on demand)
+    pub reload_secs: u64,
+}
+
+impl LiveConfigConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.file.trim().is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LocalAdminConfig {
+    /// Unix socket for `ttc-admin` (empty = only when systemd passes one)
+    pub socket: String,
+}
+
+impl LocalAdminConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.socket.trim().is_empty()
+    }
+}
+
+/// Twilio Voice menu at `/voice/incoming`
+#[derive(Debug, Clone)]
+pub struct VoiceConfig {
+    pub enabled: bool,
+    /// Text-to-speech language, e.g. `en-US`, `sw-KE`
+    pub language: String,
+}
+
+impl AppConfig {
+    /// Load the layered settings and check them; every problem found is
+    /// reported at once
+    pub fn load() -> Result<Self, ConfigError> {
         dotenvy::dotenv().ok();
+        let config = Self::from_figment(Self::figment())?;
+        tracing::debug!(?config, "Configuration loaded");
+        Ok(config)
+    }
 
-        Ok(Config {
+    /// `CONFIG_TOML` (or `textchain.toml`) under the environment
+    pub fn figment() -> Figment {
+        let file = env::var("CONFIG_TOML").unwrap_or_else(|_| DEFAULT_CONFIG_TOML.to_string());
+        let vars: BTreeMap<String, String> = env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+            .collect();
+        Figment::new().merge(Toml::file(file)).merge(Serialized::defaults(vars))
+    }
+
+    /// Build and validate the config from already layered settings
+    pub fn from_figment(figment: Figment) -> Result<Self, ConfigError> {
+        let mut source = Source::new(&figment)?;
+        let config = Self::read(&mut source);
+        let mut problems = source.problems;
+        problems.extend(config.problems());
+        if !problems.is_empty() {
+            return Err(ConfigError::Invalid(problems));
+        }
+        Ok(config)
+    }
+
+    fn read(source: &mut Source) -> Self {
+        AppConfig {
             twilio: TwilioConfig {
-                account_sid: env::var("TWILIO_ACCOUNT_SID")
-                    .map_err(|_| ConfigError::Missing("TWILIO_ACCOUNT_SID"))?,
-                auth_token: env::var("TWILIO_AUTH_TOKEN")
-                    .map_err(|_| ConfigError::Missing("TWILIO_AUTH_TOKEN"))?,
-                phone_number: env::var("TWILIO_PHONE_NUMBER")
-                    .map_err(|_| ConfigError::Missing("TWILIO_PHONE_NUMBER"))?,
+                account_sid: source.required("TWILIO_ACCOUNT_SID"),
+                auth_token: source.required("TWILIO_AUTH_TOKEN").into(),
+                phone_number: source.required("TWILIO_PHONE_NUMBER"),
+                api_base: source.string_or("TWILIO_API_BASE", "https://api.twilio.com"),
+            },
+            vonage: VonageConfig {
+                api_key: source.string("VONAGE_API_KEY"),
+                api_secret: source.string("VONAGE_API_SECRET").into(),
+                from: source.string("VONAGE_FROM"),
+                signature_secret: source.string("VONAGE_SIGNATURE_SECRET").into(),
+            },
+            sms_routing: SmsRoutingConfig {
+                default_provider: source.string_or("SMS_PROVIDER", "twilio"),
+                routes: source.string("SMS_ROUTES"),
+                mms_codes: source.string_or("MMS_CALLING_CODES", "1"),
             },
             server: ServerConfig {
-                host: env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-                port: env::var("SERVER_PORT")
-                    .unwrap_or_else(|_| "3000".to_string())
-                    .parse()
-                    .map_err(|_| ConfigError::Invalid("SERVER_PORT"))?,
+                host: source.string_or("SERVER_HOST", "0.0.0.0"),
+                port: source.parse("SERVER_PORT", 3000),
+                public_base_url: source.string("PUBLIC_BASE_URL"),
+                statement_link_minutes: source.parse("STATEMENT_LINK_MINUTES", 60),
+            },
+            services: ServicesConfig {
+                backend_url: source.string_or("BACKEND_URL", DEFAULT_BACKEND_URL),
+                arc_url: source.string_or("ARC_SERVICE_URL", DEFAULT_ARC_URL),
+            },
+            database: DatabaseConfig {
+                url: source.string("DATABASE_URL").into(),
+                write_pool_size: source.parse("DB_WRITE_POOL_SIZE", 5),
+                read_pool_size: source.parse("DB_READ_POOL_SIZE", 5),
+                read_url: source.string("DATABASE_READ_URL").into(),
+                slow_query_ms: source.parse("DB_SLOW_QUERY_MS", 250),
+            },
+            vault: VaultConfig {
+                master_key: source.string("KEY_VAULT_MASTER_KEY").into(),
+                previous_master_key: source.string("KEY_VAULT_PREVIOUS_MASTER_KEY").into(),
+                allow_plaintext: source.parse("KEY_VAULT_ALLOW_PLAINTEXT", false),
+                phone_storage: source.string("PHONE_STORAGE"),
             },
             aa: AaConfig {
-                bundler_url: env::var("BUNDLER_URL").unwrap_or_else(|_| "".to_string()),
-                entry_point_address: env::var("ENTRY_POINT_ADDRESS").unwrap_or_else(|_| "".to_string()),
-                simple_account_factory_address: env::var("SIMPLE_ACCOUNT_FACTORY_ADDRESS").unwrap_or_else(|_| "".to_string()),
+                bundler_url: source.string("BUNDLER_URL"),
+                entry_point_address: source.string("ENTRY_POINT_ADDRESS"),
+                simple_account_factory_address: source.string("SIMPLE_ACCOUNT_FACTORY_ADDRESS"),
             },
-            admin_private_key: env::var("ADMIN_PRIVATE_KEY").unwrap_or_else(|_| "".to_string()),
-        })
+            safe: SafeConfig {
+                safe_address: source.string("SAFE_ADDRESS"),
+                tx_service_url: source.string_or("SAFE_TX_SERVICE_URL", "https://safe-transaction-sepolia.safe.global"),
+                chain: source.string_or("SAFE_CHAIN", "sepolia"),
+                large_withdrawal_thresholds: source.string_or("SAFE_LARGE_WITHDRAWAL_THRESHOLDS", "USDC:1000"),
+            },
+            workers: WorkerConfig {
+                sms_reply_concurrency: source.parse("WORKER_SMS_REPLY_CONCURRENCY", 32),
+                on_chain_concurrency: source.parse("WORKER_ON_CHAIN_CONCURRENCY", 8),
+                db_heavy_concurrency: source.parse("WORKER_DB_HEAVY_CONCURRENCY", 4),
+                max_queue: source.parse("WORKER_MAX_QUEUE", 100),
+                command_timeout_secs: source.parse("COMMAND_TIMEOUT_SECS", 10),
+                command_timeouts: source.string("COMMAND_TIMEOUTS"),
+            },
+            graphql: GraphqlConfig {
+                api_keys: source.string("GRAPHQL_API_KEYS").into(),
+            },
+            rates: RatesConfig {
+                fx_rates: source.string("FX_RATES"),
+                source_url: source.string("FX_RATES_URL"),
+                price_url: source.string("TOKEN_PRICES_URL"),
+                price_ttl_secs: source.parse("TOKEN_PRICES_TTL_SECS", 300),
+            },
+            sms_cost: SmsCostConfig {
+                rates: source.string("SMS_RATES"),
+                default_rate: source.parse("SMS_DEFAULT_RATE", 0.05),
+                daily_budget: source.parse("SMS_DAILY_BUDGET", 0.0),
+                alert_percent: source.parse("SMS_BUDGET_ALERT_PERCENT", 80.0),
+                trim_over_budget: source.parse("SMS_TRIM_OVER_BUDGET", false),
+                gsm7: source.string_or("SMS_GSM7", "off"),
+            },
+            walletconnect: WalletConnectConfig {
+                bridge_url: source.string("WALLETCONNECT_BRIDGE_URL"),
+                bridge_token: source.string("WALLETCONNECT_BRIDGE_TOKEN").into(),
+            },
+            features: FeaturesConfig {
+                disabled: source.string("FEATURES_DISABLED"),
+                reload_secs: source.parse("FEATURES_RELOAD_SECS", 60),
+            },
+            kill_switch: KillSwitchConfig {
+                engaged: source.parse("KILL_SWITCH", false),
+                message: source.string_or(
+                    "KILL_SWITCH_MESSAGE",
+                    "Payments are paused for maintenance. Your balance is safe. Try again later.",
+                ),
+            },
+            transcripts: TranscriptConfig {
+                retention_days: source.parse("TRANSCRIPT_RETENTION_DAYS", 0),
+            },
+            quiet_hours: QuietHoursConfig {
+                hours: source.string("QUIET_HOURS"),
+                default_timezone: source.string_or("QUIET_HOURS_DEFAULT_TZ", "UTC"),
+                timezones: source.string("QUIET_HOURS_TIMEZONES"),
+            },
+            sms_outages: SmsOutageConfig {
+                threshold: source.parse("SMS_OUTAGE_THRESHOLD", 3),
+                window_secs: source.parse("SMS_OUTAGE_WINDOW_SECS", 300),
+                probe_secs: source.parse("SMS_OUTAGE_PROBE_SECS", 120),
+            },
+            faucet: FaucetConfig {
+                chains: source.string("FAUCET_CHAINS"),
+                drip_amount: source.parse("FAUCET_DRIP_AMOUNT", 0.01),
+                private_key: source.string("FAUCET_PRIVATE_KEY").into(),
+                urls: source.string("FAUCET_URLS"),
+            },
+            ens_cache: EnsCacheConfig {
+                ttl_secs: source.parse("ENS_CACHE_TTL_SECS", 300),
+                negative_ttl_secs: source.parse("ENS_CACHE_NEGATIVE_TTL_SECS", 60),
+            },
+            ens_verify: EnsVerifyConfig {
+                interval_secs: source.parse("ENS_VERIFY_INTERVAL_SECS", 600),
+                batch_size: source.parse("ENS_VERIFY_BATCH", 50),
+                repair: source.parse("ENS_VERIFY_REPAIR", true),
+            },
+            beta: BetaConfig {
+                enabled: source.parse("BETA_MODE", false),
+                capacity: source.parse("BETA_CAPACITY", 0),
+                release_secs: source.parse("BETA_RELEASE_SECS", 300),
+            },
+            referrals: ReferralConfig {
+                referrer_bonus: source.usdc("REFERRAL_BONUS"),
+                new_user_bonus: source.usdc("REFERRAL_NEW_USER_BONUS"),
+                max_per_referrer: source.parse("REFERRAL_MAX_PER_REFERRER", 20),
+                max_per_day: source.parse("REFERRAL_MAX_PER_DAY", 3),
+            },
+            email: EmailConfig {
+                smtp_host: source.string("SMTP_HOST"),
+                smtp_port: source.parse("SMTP_PORT", 587),
+                smtp_username: source.string("SMTP_USERNAME"),
+                smtp_password: source.string("SMTP_PASSWORD").into(),
+                from_address: source.string("EMAIL_FROM_ADDRESS"),
+                inbound_token: source.string("EMAIL_INBOUND_TOKEN").into(),
+            },
+            receipts: ReceiptConfig {
+                signing_key: source.string("RECEIPT_SIGNING_KEY").into(),
+            },
+            deposits: DepositConfig {
+                confirmations: source.string("DEPOSIT_CONFIRMATIONS"),
+                poll_secs: source.parse("DEPOSIT_POLL_SECS", 30),
+                internal_secret: source.string("INTERNAL_SECRET").into(),
+            },
+            deposit_addresses: DepositAddressConfig {
+                mnemonic: source.string("DEPOSIT_MNEMONIC").into(),
+                treasury_address: source.string("DEPOSIT_TREASURY_ADDRESS"),
+                sweep_secs: source.parse("DEPOSIT_SWEEP_SECS", 3600),
+                sweep_minimums: source.string("DEPOSIT_SWEEP_MINIMUMS"),
+                sweep_batch: source.parse("DEPOSIT_SWEEP_BATCH", 50),
+            },
+            tokens: TokensConfig {
+                addresses: source.string("TOKEN_ADDRESSES"),
+                reload_secs: source.parse("TOKEN_OVERRIDES_RELOAD_SECS", 60),
+            },
+            broadcast: BroadcastConfig {
+                rate_per_sec: source.parse("BROADCAST_RATE_PER_SEC", 5.0),
+            },
+            events: EventsConfig {
+                token: source.string("ADMIN_EVENTS_TOKEN").into(),
+            },
+            savings: SavingsConfig {
+                vault_address: source.string("SAVINGS_VAULT_ADDRESS"),
+            },
+            gas: GasMonitorConfig {
+                thresholds: source.string("GAS_TANK_THRESHOLDS"),
+                wallets: source.string("GAS_TANK_WALLETS"),
+                poll_secs: source.parse("GAS_TANK_POLL_SECS", 300),
+                alert_phones: source.string("GAS_ALERT_PHONES"),
+                alert_webhook_url: source.string("GAS_ALERT_WEBHOOK_URL"),
+                repeat_hours: source.parse("GAS_ALERT_REPEAT_HOURS", 6),
+            },
+            alerts: OperatorAlertConfig {
+                deployment: source.string_or("ALERT_DEPLOYMENT", "textchain"),
+                slack_webhooks: source.string("ALERT_SLACK_WEBHOOKS").into(),
+                slack_min_severity: source.string_or("ALERT_SLACK_MIN_SEVERITY", "warning"),
+                pagerduty_routing_key: source.string("ALERT_PAGERDUTY_ROUTING_KEY").into(),
+                pagerduty_min_severity: source.string_or("ALERT_PAGERDUTY_MIN_SEVERITY", "error"),
+                pagerduty_url: source.string_or("ALERT_PAGERDUTY_URL", "https://events.pagerduty.com/v2/enqueue"),
+                repeat_minutes: source.parse("ALERT_REPEAT_MINUTES", 60),
+                queue_backlog: source.parse("ALERT_QUEUE_BACKLOG", 100),
+                queue_poll_secs: source.parse("ALERT_QUEUE_POLL_SECS", 60),
+            },
+            transfer_approval: TransferApprovalConfig {
+                thresholds: source.string("TRANSFER_APPROVAL_THRESHOLDS"),
+                cooling_minutes: source.parse("TRANSFER_COOLING_MINUTES", 30),
+                ttl_minutes: source.parse("TRANSFER_APPROVAL_TTL_MINUTES", 60),
+            },
+            send_minimums: SendMinimumConfig {
+                minimums: source.string("SEND_MINIMUMS"),
+            },
+            send_queue: SendQueueConfig {
+                cancel_secs: source.parse("SEND_CANCEL_SECS", 30),
+            },
+            withdrawals: WithdrawalAllowlistConfig {
+                enabled: source.parse("WITHDRAWAL_ALLOWLIST", false),
+                cooldown_hours: source.parse("WITHDRAWAL_COOLDOWN_HOURS", 24),
+                unlock_minutes: source.parse("WITHDRAWAL_UNLOCK_MINUTES", 60),
+            },
+            backups: BackupConfig {
+                endpoint: source.string_or("BACKUP_S3_ENDPOINT", "https://s3.amazonaws.com"),
+                bucket: source.string("BACKUP_S3_BUCKET"),
+                region: source.string_or("BACKUP_S3_REGION", "us-east-1"),
+                access_key: source.string("BACKUP_S3_ACCESS_KEY"),
+                secret_key: source.string("BACKUP_S3_SECRET_KEY").into(),
+                prefix: source.string_or("BACKUP_PREFIX", "textchain/"),
+                path_style: source.parse("BACKUP_S3_PATH_STYLE", true),
+                interval_hours: source.parse("BACKUP_INTERVAL_HOURS", 24),
+                keep: source.parse("BACKUP_KEEP", 14),
+                pg_dump: source.string_or("BACKUP_PG_DUMP", "pg_dump"),
+                pg_restore: source.string_or("BACKUP_PG_RESTORE", "pg_restore"),
+            },
+            payouts: PayoutConfig {
+                chain: source.string("CASH_PAYOUT_CHAIN"),
+                poll_secs: source.parse("CASH_PAYOUT_POLL_SECS", 30),
+            },
+            repeat_send: RepeatSendConfig {
+                window_minutes: source.parse("REPEAT_SEND_MINUTES", 10),
+            },
+            notify_digest: NotifyDigestConfig {
+                hour: source.parse("NOTIFY_DIGEST_HOUR", 18),
+            },
+            kyc: KycConfig {
+                enabled: source.parse("KYC_TIERS", false),
+                tier1_limits: source.string_or("KYC_TIER1_LIMITS", "USDC:20,TXTC:1000,ETH:0.01"),
+                tier2_limits: source.string_or("KYC_TIER2_LIMITS", "USDC:500,TXTC:25000,ETH:0.25"),
+                id_url: source.string("KYC_ID_URL"),
+            },
+            carrier_lookup: CarrierLookupConfig {
+                enabled: source.parse("CARRIER_LOOKUP", false),
+                refresh_days: source.parse("CARRIER_LOOKUP_REFRESH_DAYS", 30),
+            },
+            sim_swap: SimSwapConfig {
+                provider: source.string("SIM_SWAP_PROVIDER"),
+                window_hours: source.parse("SIM_SWAP_WINDOW_HOURS", 72),
+                cache_minutes: source.parse("SIM_SWAP_CACHE_MINUTES", 60),
+                block_on_error: source.parse("SIM_SWAP_BLOCK_ON_ERROR", false),
+                mock: source.string("SIM_SWAP_MOCK"),
+            },
+            spam: SpamConfig {
+                enabled: source.parse("SPAM_FILTER", false),
+                flag_score: source.parse("SPAM_FLAG_SCORE", 0.5),
+                drop_score: source.parse("SPAM_DROP_SCORE", 0.9),
+                strikes: source.parse("SPAM_STRIKES", 3),
+                strike_window_minutes: source.parse("SPAM_STRIKE_WINDOW_MINUTES", 60),
+                mute_minutes: source.parse("SPAM_MUTE_MINUTES", 15),
+                model_url: source.string("SPAM_MODEL_URL"),
+                model_token: source.string("SPAM_MODEL_TOKEN").into(),
+                model_timeout_ms: source.parse("SPAM_MODEL_TIMEOUT_MS", 800),
+            },
+            ens_names: NamePolicyConfig {
+                deny_pattern: source.string("ENS_DENY_PATTERN"),
+            },
+            live: LiveConfigConfig {
+                file: source.string("CONFIG_FILE"),
+                reload_secs: source.parse("CONFIG_RELOAD_SECS", 10),
+            },
+            local_admin: LocalAdminConfig {
+                socket: source.string("LOCAL_ADMIN_SOCKET"),
+            },
+            voice: VoiceConfig {
+                enabled: source.parse("VOICE_IVR", false),
+                language: source.string_or("VOICE_LANGUAGE", "en-US"),
+            },
+            admin_private_key: source.string("ADMIN_PRIVATE_KEY").into(),
+            admin_token: source.string("ADMIN_TOKEN").into(),
+            admin_tokens: source.string("ADMIN_TOKENS").into(),
+        }
     }
 
     /// Get server bind address
     pub fn bind_addr(&self) -> String {
         format!("{}:{}", self.server.host, self.server.port)
     }
+
+    /// Settings that parsed but can't work
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let urls = [
+            ("TWILIO_API_BASE", self.twilio.api_base.as_str()),
+            ("PUBLIC_BASE_URL", &self.server.public_base_url),
+            ("BACKEND_URL", &self.services.backend_url),
+            ("ARC_SERVICE_URL", &self.services.arc_url),
+            ("BUNDLER_URL", &self.aa.bundler_url),
+            ("SAFE_TX_SERVICE_URL", &self.safe.tx_service_url),
+            ("FX_RATES_URL", &self.rates.source_url),
+            ("TOKEN_PRICES_URL", &self.rates.price_url),
+            ("WALLETCONNECT_BRIDGE_URL", &self.walletconnect.bridge_url),
+            ("GAS_ALERT_WEBHOOK_URL", &self.gas.alert_webhook_url),
+            ("KYC_ID_URL", &self.kyc.id_url),
+            ("ALERT_PAGERDUTY_URL", &self.alerts.pagerduty_url),
+            ("BACKUP_S3_ENDPOINT", &self.backups.endpoint),
+            ("SPAM_MODEL_URL", &self.spam.model_url),
+        ];
+        for (name, value) in urls {
+            check(&mut problems, name, value, http_url);
+        }
+        for url in self.alerts.slack_urls() {
+            check(&mut problems, "ALERT_SLACK_WEBHOOKS", &url, http_url);
+        }
+        check(&mut problems, "DATABASE_URL", &self.database.url, postgres_url);
+        check(&mut problems, "DATABASE_READ_URL", &self.database.read_url, postgres_url);
+
+        let addresses = [
+            ("ENTRY_POINT_ADDRESS", self.aa.entry_point_address.as_str()),
+            ("SIMPLE_ACCOUNT_FACTORY_ADDRESS", &self.aa.simple_account_factory_address),
+            ("SAFE_ADDRESS", &self.safe.safe_address),
+            ("DEPOSIT_TREASURY_ADDRESS", &self.deposit_addresses.treasury_address),
+            ("SAVINGS_VAULT_ADDRESS", &self.savings.vault_address),
+        ];
+        for (name, value) in addresses {
+            check(&mut problems, name, value, address);
+        }
+
+        let keys = [
+            ("ADMIN_PRIVATE_KEY", &self.admin_private_key),
+            ("FAUCET_PRIVATE_KEY", &self.faucet.private_key),
+            ("RECEIPT_SIGNING_KEY", &self.receipts.signing_key),
+        ];
+        for (name, value) in keys {
+            check(&mut problems, name, value, private_key);
+        }
+        check(&mut problems, "DEPOSIT_MNEMONIC", &self.deposit_addresses.mnemonic, mnemonic);
+
+        let severities = [
+            ("ALERT_SLACK_MIN_SEVERITY", &self.alerts.slack_min_severity),
+            ("ALERT_PAGERDUTY_MIN_SEVERITY", &self.alerts.pagerduty_min_severity),
+        ];
+        for (name, value) in severities {
+            if Severity::parse(value).is_none() {
+                problems.push(format!("{}: expected info, warning, error or critical, not {:?}", name, value));
+            }
+        }
+        if PhoneStorage::parse(&self.vault.phone_storage).is_none() {
+            problems.push(format!("PHONE_STORAGE: expected encrypted or hashed, not {:?}", self.vault.phone_storage));
+        }
+        if Gsm7Mode::parse(&self.sms_cost.gsm7).is_none() {
+            problems.push(format!("SMS_GSM7: expected off, substitute or strict, not {:?}", self.sms_cost.gsm7));
+        }
+        // The admin API is served with a database; "admin123" was the old default
+        let admin_token = self.admin_token.expose().trim();
+        if !self.database.url.is_empty() && admin_token.is_empty() {
+            problems.push("ADMIN_TOKEN: required with DATABASE_URL".to_string());
+        } else if admin_token == "admin123" {
+            problems.push("ADMIN_TOKEN: admin123 is the old default, choose another".to_string());
+        }
+        if parse_named_tokens(&self.admin_tokens).is_none() {
+            problems.push("ADMIN_TOKENS: expected name:token pairs separated by commas".to_string());
+        }
+        let referrals = &self.referrals;
+        if referrals.referrer_bonus.micros() < 0 || referrals.new_user_bonus.micros() < 0 {
+            problems.push("REFERRAL_BONUS / REFERRAL_NEW_USER_BONUS: must be 0 or more".to_string());
+        }
+        let spam = &self.spam;
+        if !(0.0..=1.0).contains(&spam.flag_score) || !(0.0..=1.0).contains(&spam.drop_score) || spam.flag_score > spam.drop_score {
+            problems.push("SPAM_FLAG_SCORE / SPAM_DROP_SCORE: must be from 0 to 1, flag no higher than drop".to_string());
+        }
+        if self.notify_digest.hour > 23 {
+            problems.push("NOTIFY_DIGEST_HOUR: must be an hour from 0 to 23".to_string());
+        }
+        // UNLOCK would wait longer than the cooldown it skips
+        let withdrawals = &self.withdrawals;
+        if withdrawals.enabled && u64::from(withdrawals.unlock_minutes) >= u64::from(withdrawals.cooldown_hours) * 60 {
+            problems.push("WITHDRAWAL_UNLOCK_MINUTES: must be shorter than WITHDRAWAL_COOLDOWN_HOURS".to_string());
+        }
+        // Payouts are sent from the hot wallet
+        if self.payouts.is_enabled() && self.admin_private_key.expose().trim().is_empty() {
+            problems.push("CASH_PAYOUT_CHAIN: needs ADMIN_PRIVATE_KEY to pay out".to_string());
+        }
+        let backups = &self.backups;
+        if backups.is_enabled() && (backups.access_key.trim().is_empty() || backups.secret_key.expose().trim().is_empty()) {
+            problems.push("BACKUP_S3_BUCKET: needs BACKUP_S3_ACCESS_KEY and BACKUP_S3_SECRET_KEY".to_string());
+        }
+        // Twilio signs the public URL it called
+        if self.voice.enabled && self.server.public_base_url.trim().is_empty() {
+            problems.push("VOICE_IVR: needs PUBLIC_BASE_URL to check Twilio signatures".to_string());
+        }
+        problems
+    }
+}
+
+/// Settings from every layer, by variable name, and what was wrong with them
+struct Source {
+    values: BTreeMap<String, serde_json::Value>,
+    problems: Vec<String>,
+}
+
+impl Source {
+    fn new(figment: &Figment) -> Result<Self, ConfigError> {
+        let values = figment.extract().map_err(|e| ConfigError::Read(e.to_string()))?;
+        Ok(Self { values, problems: Vec::new() })
+    }
+
+    /// The setting as text; numbers and booleans from the file count too
+    fn get(&self, name: &str) -> Option<String> {
+        match self.values.get(name)? {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(value) => Some(value.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+
+    /// An optional setting, empty when unset
+    fn string(&self, name: &str) -> String {
+        self.get(name).unwrap_or_default()
+    }
+
+    fn string_or(&self, name: &str, default: &str) -> String {
+        self.get(name).unwrap_or_else(|| default.to_string())
+    }
+
+    fn required(&mut self, name: &str) -> String {
+        self.get(name).unwrap_or_else(|| {
+            self.problems.push(format!("{}: required but not set", name));
+            String::new()
+        })
+    }
+
+    /// An optional typed setting, falling back to a default
+    fn parse<T: std::str::FromStr>(&mut self, name: &str, default: T) -> T {
+        let Some(value) = self.get(name) else {
+            return default;
+        };
+        match value.trim().parse() {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                self.problems.push(format!("{}: {:?} is not {}", name, value, expected::<T>()));
+                default
+            }
+        }
+    }
+
+    /// An optional USDC amount, 0 when unset
+    fn usdc(&mut self, name: &str) -> Money {
+        let Some(value) = self.get(name) else {
+            return Money::usdc(0);
+        };
+        Money::parse(&value, Currency::USDC).unwrap_or_else(|_| {
+            self.problems.push(format!("{}: {:?} is not an amount", name, value));
+            Money::usdc(0)
+        })
+    }
+}
+
+/// What a setting of type `T` should look like, for error messages
+fn expected<T>() -> &'static str {
+    match std::any::type_name::<T>() {
+        "bool" => "true or false",
+        "f64" => "a number",
+        "u16" | "u32" | "u64" | "usize" => "a whole number of 0 or more",
+        _ => "a whole number",
+    }
+}
+
+/// Run `valid` on a setting that is set, noting why it failed
+fn check(problems: &mut Vec<String>, name: &str, value: &str, valid: fn(&str) -> Result<(), String>) {
+    let value = value.trim();
+    if value.is_empty() {
+        return;
+    }
+    if let Err(reason) = valid(value) {
+        problems.push(format!("{}: {}", name, reason));
+    }
+}
+
+fn http_url(value: &str) -> Result<(), String> {
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+        Ok(url) => Err(format!("expected an http:// or https:// URL, not {}://", url.scheme())),
+        Err(e) => Err(format!("not a URL ({})", e)),
+    }
+}
+
+/// Never echoes the value, which may carry a password
+fn postgres_url(value: &str) -> Result<(), String> {
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "postgres" | "postgresql") => Ok(()),
+        Ok(url) => Err(format!("expected a postgres:// URL, not {}://", url.scheme())),
+        Err(e) => Err(format!("not a URL ({})", e)),
+    }
+}
+
+fn address(value: &str) -> Result<(), String> {
+    match parse_address(value) {
+        Ok(_) => Ok(()),
+        Err(AddressError::Invalid) => Err("not an address (0x followed by 40 hex characters)".to_string()),
+        Err(AddressError::BadChecksum(correct)) => Err(format!("checksum mismatch, did you mean {}?", correct)),
+    }
+}
+
+fn private_key(value: &str) -> Result<(), String> {
+    match value.trim_start_matches("0x").parse::<LocalWallet>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("not a private key (expected 64 hex characters)".to_string()),
+    }
+}
+
+fn mnemonic(value: &str) -> Result<(), String> {
+    match DepositKeys::from_phrase(value) {
+        Ok(_) => Ok(()),
+        Err(_) => Err("not a valid BIP-39 phrase".to_string()),
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
-    #[error("Missing environment variable: {0}")]
-    Missing(&'static str),
-    #[error("Invalid value for: {0}")]
-    Invalid(&'static str),
+    /// The settings file exists but isn't valid TOML
+    #[error("Could not read settings: {0}")]
+    Read(String),
+    #[error("Invalid configuration:\n  {}", .0.join("\n  "))]
+    Invalid(Vec<String>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(file: &str, env: &[(&str, &str)]) -> Result<AppConfig, ConfigError> {
+        let env: BTreeMap<&str, &str> = env.iter().copied().collect();
+        AppConfig::from_figment(Figment::new().merge(Toml::string(file)).merge(Serialized::defaults(env)))
+    }
+
+    const TWILIO: &str = r#"
+        TWILIO_ACCOUNT_SID = "AC123"
+        TWILIO_AUTH_TOKEN = "twilio-token"
+        TWILIO_PHONE_NUMBER = "+15550001111"
+    "#;
+
+    #[test]
+    fn test_layers() {
+        let file = format!("{}\nSERVER_PORT = 8080\nSMS_PROVIDER = \"vonage\"\nKYC_TIERS = true", TWILIO);
+        let config = load(&file, &[("SMS_PROVIDER", "twilio"), ("DB_SLOW_QUERY_MS", "500")]).unwrap();
+        // Numbers and booleans in the file, environment over the file, defaults under both
+        assert_eq!(config.server.port, 8080);
+        assert!(config.kyc.enabled);
+        assert_eq!(config.sms_routing.default_provider, "twilio");
+        assert_eq!(config.database.slow_query_ms, 500);
+        assert_eq!(config.server.host, "0.0.0.0");
+        assert_eq!(config.twilio.auth_token.expose(), "twilio-token");
+    }
+
+    #[test]
+    fn test_database_pools() {
+        let config = load(TWILIO, &[]).unwrap();
+        assert_eq!((config.database.write_pool_size, config.database.read_pool_size), (5, 5));
+        assert!(config.database.read_url.is_empty());
+
+        let env = [
+            ("DB_WRITE_POOL_SIZE", "20"),
+            ("DB_READ_POOL_SIZE", "4"),
+            ("DATABASE_READ_URL", "postgres://app@replica/textchain"),
+        ];
+        let config = load(TWILIO, &env).unwrap();
+        assert_eq!((config.database.write_pool_size, config.database.read_pool_size), (20, 4));
+        assert_eq!(config.database.read_url.expose(), "postgres://app@replica/textchain");
+    }
+
+    #[test]
+    fn test_every_problem_reported() {
+        let env = [
+            ("SERVER_PORT", "http"),
+            ("SAFE_ADDRESS", "0x5AAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+            ("SAVINGS_VAULT_ADDRESS", "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+            ("ADMIN_PRIVATE_KEY", "0x1234"),
+            ("DATABASE_URL", "mysql://app:hunter2@db/textchain"),
+            ("PHONE_STORAGE", "plain"),
+            ("TOKEN_PRICES_URL", "prices.example.com"),
+            ("NOTIFY_DIGEST_HOUR", "24"),
+            ("VOICE_IVR", "true"),
+            ("WITHDRAWAL_ALLOWLIST", "true"),
+            ("WITHDRAWAL_COOLDOWN_HOURS", "1"),
+            ("WITHDRAWAL_UNLOCK_MINUTES", "90"),
+            ("ALERT_SLACK_WEBHOOKS", "https://hooks.slack.example/T1, hooks.slack.example/T2"),
+            ("ALERT_PAGERDUTY_MIN_SEVERITY", "page"),
+        ];
+        let Err(ConfigError::Invalid(problems)) = load("", &env) else {
+            panic!("expected problems");
+        };
+        assert_eq!(
+            problems,
+            [
+                "TWILIO_ACCOUNT_SID: required but not set",
+                "TWILIO_AUTH_TOKEN: required but not set",
+                "TWILIO_PHONE_NUMBER: required but not set",
+                "SERVER_PORT: \"http\" is not a whole number of 0 or more",
+                "TOKEN_PRICES_URL: not a URL (relative URL without a base)",
+                "ALERT_SLACK_WEBHOOKS: not a URL (relative URL without a base)",
+                "DATABASE_URL: expected a postgres:// URL, not mysql://",
+                "SAFE_ADDRESS: checksum mismatch, did you mean 0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed?",
+                "ADMIN_PRIVATE_KEY: not a private key (expected 64 hex characters)",
+                "ALERT_PAGERDUTY_MIN_SEVERITY: expected info, warning, error or critical, not \"page\"",
+                "PHONE_STORAGE: expected encrypted or hashed, not \"plain\"",
+                "ADMIN_TOKEN: required with DATABASE_URL",
+                "NOTIFY_DIGEST_HOUR: must be an hour from 0 to 23",
+                "WITHDRAWAL_UNLOCK_MINUTES: must be shorter than WITHDRAWAL_COOLDOWN_HOURS",
+                "VOICE_IVR: needs PUBLIC_BASE_URL to check Twilio signatures",
+            ]
+        );
+        assert!(load("SERVER_PORT = [", &[]).is_err_and(|e| matches!(e, ConfigError::Read(_))));
+    }
+
+    #[test]
+    fn test_admin_token_required_with_a_database() {
+        let database = ("DATABASE_URL", "postgres://app@db/textchain");
+        let problems = |env: &[(&str, &str)]| match load(TWILIO, env) {
+            Err(ConfigError::Invalid(problems)) => problems,
+            _ => vec![],
+        };
+        assert_eq!(problems(&[database]), ["ADMIN_TOKEN: required with DATABASE_URL"]);
+        assert_eq!(problems(&[database, ("ADMIN_TOKEN", " ")]), ["ADMIN_TOKEN: required with DATABASE_URL"]);
+        assert_eq!(problems(&[("ADMIN_TOKEN", "admin123")]), ["ADMIN_TOKEN: admin123 is the old default, choose another"]);
+        assert!(problems(&[database, ("ADMIN_TOKEN", "a-long-random-token")]).is_empty());
+        // Without a database there is no admin API to protect
+        assert!(problems(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_referral_bonuses() {
+        let config = load(TWILIO, &[]).unwrap();
+        assert!(!config.referrals.is_enabled());
+
+        let config = load(&format!("{}\nREFERRAL_BONUS = 2.5", TWILIO), &[("REFERRAL_NEW_USER_BONUS", "0.1")]).unwrap();
+        assert_eq!((config.referrals.referrer_bonus, config.referrals.new_user_bonus), (Money::usdc(2_500_000), Money::usdc(100_000)));
+        assert!(config.referrals.is_enabled());
+
+        let Err(ConfigError::Invalid(problems)) = load(TWILIO, &[("REFERRAL_BONUS", "two"), ("REFERRAL_NEW_USER_BONUS", "-1")]) else {
+            panic!("expected problems");
+        };
+        assert_eq!(
+            problems,
+            ["REFERRAL_BONUS: \"two\" is not an amount", "REFERRAL_BONUS / REFERRAL_NEW_USER_BONUS: must be 0 or more"]
+        );
+    }
+
+    #[test]
+    fn test_debug_redacts_secrets() {
+        let env = [
+            ("DATABASE_URL", "postgres://app:hunter2@db/textchain"),
+            ("ADMIN_TOKEN", "admin-secret-token"),
+            ("KEY_VAULT_MASTER_KEY", "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"),
+            ("ADMIN_PRIVATE_KEY", "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"),
+        ];
+        let config = load(TWILIO, &env).unwrap();
+        let debug = format!("{:?}", config);
+        for secret in ["twilio-token", "hunter2", "4c0883a6", "admin-secret", "0001020304"] {
+            assert!(!debug.contains(secret), "{} leaked", secret);
+        }
+        assert!(debug.contains("auth_token: <redacted>"));
+        assert!(debug.contains("account_sid: \"AC123\""));
+        // Unset secrets say so
+        assert!(debug.contains("smtp_password: \"\""));
+    }
 }
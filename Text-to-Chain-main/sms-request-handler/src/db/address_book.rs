@@ -1,6 +1,13 @@
 use sqlx::PgPool;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use ethers::types::Address;
+
+use super::encryption::{decode_error, FieldCipher};
+use super::metrics::QueryTimer;
+use crate::sms::display::{short_address, NumberLocale, SmsDisplay};
+use crate::wallet::address::checksummed;
+use crate::wallet::KeyVault;
 
 /// Contact in address book
 #[derive(Debug, Clone, sqlx::FromRow)]
@@ -13,26 +20,55 @@ pub struct Contact {
     pub created_at: DateTime<Utc>,
 }
 
-impl Contact {
-    /// Format for SMS display
-    pub fn to_sms_string(&self) -> String {
+/// `mum: +254700000001` or `shop: 0x742d...f44e`
+impl SmsDisplay for Contact {
+    fn sms_string(&self, _locale: NumberLocale) -> String {
         match (&self.contact_phone, &self.wallet_address) {
-            (Some(phone), _) => format!("{}: {}", self.name, phone),
-            (_, Some(addr)) => format!("{}: {}...{}", self.name, &addr[..6], &addr[38..]),
+            // A hashed number can't be shown
+            (Some(phone), _) if !KeyVault::is_blind_index(phone) => format!("{}: {}", self.name, phone),
+            (_, Some(addr)) => format!("{}: {}", self.name, short_address(addr)),
             _ => self.name.clone(),
         }
     }
 }
 
+/// A saved wallet address, for the withdrawal allowlist
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SavedAddress {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// Set by UNLOCK: sends open then, if sooner than the cooldown
+    pub unlock_at: Option<DateTime<Utc>>,
+}
+
 /// Address book repository for database operations
+///
+/// `user_phone` is stored as a blind index; `contact_phone` and
+/// `wallet_address` are encrypted, with blind indexes alongside for lookups
+/// and uniqueness. With phones hashed, `contact_phone` holds the blind index
+/// too; user lookups accept it in place of the number. Contact names stay
+/// plaintext for partial-match search.
 #[derive(Clone)]
 pub struct AddressBookRepository {
     pool: PgPool,
+    cipher: FieldCipher,
 }
 
 impl AddressBookRepository {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    /// Restore plaintext fields of a loaded contact
+    fn decrypt(&self, user_phone: &str, mut contact: Contact) -> Result<Contact, sqlx::Error> {
+        contact.user_phone = user_phone.to_string();
+        contact.contact_phone = self.cipher.decrypt_opt(contact.contact_phone.as_deref()).map_err(decode_error)?;
+        contact.wallet_address = self.cipher.decrypt_opt(contact.wallet_address.as_deref()).map_err(decode_error)?;
+        Ok(contact)
+    }
+
+    fn decrypt_all(&self, user_phone: &str, contacts: Vec<Contact>) -> Result<Vec<Contact>, sqlx::Error> {
+        contacts.into_iter().map(|c| self.decrypt(user_phone, c)).collect()
     }
 
     /// Add a new contact
@@ -43,72 +79,91 @@ impl AddressBookRepository {
         contact_phone: Option<&str>,
         wallet_address: Option<&str>,
     ) -> Result<Contact, sqlx::Error> {
+        let _timer = QueryTimer::start("address_book.add_contact");
         let id = Uuid::new_v4();
         
-        sqlx::query_as::<_, Contact>(
+        let contact = sqlx::query_as::<_, Contact>(
             r#"
-            INSERT INTO address_book (id, user_phone, name, contact_phone, wallet_address)
-            VALUES ($1, $2, $3, $4, $5)
-            ON CONFLICT (user_phone, COALESCE(contact_phone, ''), COALESCE(wallet_address, ''))
+            INSERT INTO address_book
+                (id, user_phone, name, contact_phone, contact_phone_index, wallet_address, wallet_address_index)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (user_phone, COALESCE(contact_phone_index, ''), COALESCE(wallet_address_index, ''))
             DO UPDATE SET name = EXCLUDED.name
             RETURNING id, user_phone, name, contact_phone, wallet_address, created_at
             "#
         )
         .bind(id)
-        .bind(user_phone)
+        .bind(self.cipher.blind_index(user_phone))
         .bind(name)
-        .bind(contact_phone)
-        .bind(wallet_address)
+        .bind(contact_phone.map(|phone| self.cipher.seal_phone(phone)).transpose().map_err(decode_error)?)
+        .bind(self.cipher.blind_index_opt(contact_phone))
+        .bind(self.cipher.encrypt_opt(wallet_address).map_err(decode_error)?)
+        .bind(self.cipher.blind_index_opt(wallet_address))
         .fetch_one(&self.pool)
-        .await
+        .await?;
+
+        self.decrypt(user_phone, contact)
     }
 
     /// Find contacts by name (partial match)
     pub async fn find_by_name(&self, user_phone: &str, name: &str) -> Result<Vec<Contact>, sqlx::Error> {
-        sqlx::query_as::<_, Contact>(
+        let _timer = QueryTimer::start("address_book.find_by_name");
+        let contacts = sqlx::query_as::<_, Contact>(
             "SELECT id, user_phone, name, contact_phone, wallet_address, created_at 
              FROM address_book 
-             WHERE user_phone = $1 AND UPPER(name) LIKE UPPER($2)
+             WHERE user_phone = ANY($1) AND UPPER(name) LIKE UPPER($2)
              ORDER BY name"
         )
-        .bind(user_phone)
+        .bind(self.cipher.lookup_keys(user_phone))
         .bind(format!("%{}%", name))
         .fetch_all(&self.pool)
-        .await
+        .await?;
+
+        self.decrypt_all(user_phone, contacts)
     }
 
     /// Find contact by phone number
     pub async fn find_by_phone(&self, user_phone: &str, contact_phone: &str) -> Result<Option<Contact>, sqlx::Error> {
-        sqlx::query_as::<_, Contact>(
+        let _timer = QueryTimer::start("address_book.find_by_phone");
+        // Legacy rows have no index yet but still hold the plaintext number
+        let contact = sqlx::query_as::<_, Contact>(
             "SELECT id, user_phone, name, contact_phone, wallet_address, created_at 
              FROM address_book 
-             WHERE user_phone = $1 AND contact_phone = $2"
+             WHERE user_phone = ANY($1)
+               AND (contact_phone_index = ANY($2) OR (contact_phone_index IS NULL AND contact_phone = $3))"
         )
-        .bind(user_phone)
+        .bind(self.cipher.lookup_keys(user_phone))
+        .bind(self.cipher.lookup_keys(contact_phone))
         .bind(contact_phone)
         .fetch_optional(&self.pool)
-        .await
+        .await?;
+
+        contact.map(|c| self.decrypt(user_phone, c)).transpose()
     }
 
     /// Get all contacts for a user
     pub async fn list_all(&self, user_phone: &str) -> Result<Vec<Contact>, sqlx::Error> {
-        sqlx::query_as::<_, Contact>(
+        let _timer = QueryTimer::start("address_book.list_all");
+        let contacts = sqlx::query_as::<_, Contact>(
             "SELECT id, user_phone, name, contact_phone, wallet_address, created_at 
              FROM address_book 
-             WHERE user_phone = $1 
+             WHERE user_phone = ANY($1) 
              ORDER BY name"
         )
-        .bind(user_phone)
+        .bind(self.cipher.lookup_keys(user_phone))
         .fetch_all(&self.pool)
-        .await
+        .await?;
+
+        self.decrypt_all(user_phone, contacts)
     }
 
     /// Delete a contact
     pub async fn delete(&self, user_phone: &str, name: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("address_book.delete");
         let result = sqlx::query(
-            "DELETE FROM address_book WHERE user_phone = $1 AND UPPER(name) = UPPER($2)"
+            "DELETE FROM address_book WHERE user_phone = ANY($1) AND UPPER(name) = UPPER($2)"
         )
-        .bind(user_phone)
+        .bind(self.cipher.lookup_keys(user_phone))
         .bind(name)
         .execute(&self.pool)
         .await?;
@@ -116,8 +171,51 @@ impl AddressBookRepository {
         Ok(result.rows_affected() > 0)
     }
 
+    /// The earliest saved contact with `wallet_address` (lower-case hex)
+    pub async fn find_saved_address(&self, user_phone: &str, wallet_address: &str) -> Result<Option<SavedAddress>, sqlx::Error> {
+        let _timer = QueryTimer::start("address_book.find_saved_address");
+        // Saved lower-case by SAVE, but older contacts may be checksummed
+        let mut keys = self.cipher.lookup_keys(wallet_address);
+        if let Ok(address) = wallet_address.parse::<Address>() {
+            keys.extend(self.cipher.lookup_keys(&format!("{:?}", address)));
+            keys.extend(self.cipher.lookup_keys(&checksummed(&address)));
+        }
+        // Legacy rows have no index yet but still hold the plaintext address
+        sqlx::query_as::<_, SavedAddress>(
+            "SELECT name, created_at, withdrawal_unlock_at AS unlock_at
+             FROM address_book
+             WHERE user_phone = ANY($1)
+               AND (wallet_address_index = ANY($2) OR (wallet_address_index IS NULL AND LOWER(wallet_address) = $3))
+             ORDER BY created_at
+             LIMIT 1"
+        )
+        .bind(self.cipher.lookup_keys(user_phone))
+        .bind(keys)
+        .bind(wallet_address.to_lowercase())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Open withdrawals to the addresses saved as `name` at `at`, unless
+    /// they open sooner already; returns the contacts it applied to
+    pub async fn unlock_address(&self, user_phone: &str, name: &str, at: DateTime<Utc>) -> Result<Vec<SavedAddress>, sqlx::Error> {
+        let _timer = QueryTimer::start("address_book.unlock_address");
+        sqlx::query_as::<_, SavedAddress>(
+            "UPDATE address_book
+             SET withdrawal_unlock_at = LEAST(withdrawal_unlock_at, $3)
+             WHERE user_phone = ANY($1) AND UPPER(name) = UPPER($2) AND wallet_address IS NOT NULL
+             RETURNING name, created_at, withdrawal_unlock_at AS unlock_at"
+        )
+        .bind(self.cipher.lookup_keys(user_phone))
+        .bind(name)
+        .bind(at)
+        .fetch_all(&self.pool)
+        .await
+    }
+
     /// Resolve a recipient - could be a name, phone, or address
     pub async fn resolve_recipient(&self, user_phone: &str, input: &str) -> Option<String> {
+        let _timer = QueryTimer::start("address_book.resolve_recipient");
         // If it looks like a phone number or address, return as-is
         if input.starts_with('+') || input.starts_with("0x") {
             return Some(input.to_string());
@@ -0,0 +1,313 @@
+//! Cash agents and their cash-in / cash-out requests
+//!
+//! An agent holds a float on the ledger. Cash-in moves float to a customer
+//! in exchange for cash handed to the agent; cash-out moves customer balance
+//! to the agent's float in exchange for cash handed to the customer. Each
+//! request carries one confirmation code per party and only settles once
+//! both have confirmed.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::ledger::{agent_account, transfer_in, user_account, LedgerError, LedgerRepository, FLOAT_TOPUP_ACCOUNT};
+use super::metrics::QueryTimer;
+
+/// How long both parties have to confirm a cash request
+pub const CASH_REQUEST_TTL_MINUTES: i64 = 15;
+
+/// Registered cash agent
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Agent {
+    pub id: Uuid,
+    pub phone: String,
+    pub name: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Direction of a cash request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CashKind {
+    /// Customer hands cash to the agent, agent float credits the customer
+    CashIn,
+    /// Agent hands cash to the customer, customer balance credits the float
+    CashOut,
+}
+
+impl CashKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CashKind::CashIn => "cashin",
+            CashKind::CashOut => "cashout",
+        }
+    }
+}
+
+/// Pending or settled cash-in / cash-out
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct CashRequest {
+    pub id: Uuid,
+    pub agent_id: Uuid,
+    pub agent_phone: String,
+    pub customer_id: Uuid,
+    pub customer_phone: String,
+    pub kind: String,
+    pub amount: i64,
+    pub agent_code: String,
+    pub customer_code: String,
+    pub agent_confirmed: bool,
+    pub customer_confirmed: bool,
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl CashRequest {
+    pub fn kind(&self) -> CashKind {
+        if self.kind == "cashout" {
+            CashKind::CashOut
+        } else {
+            CashKind::CashIn
+        }
+    }
+
+    /// Ledger accounts (from, to) for settlement
+    fn accounts(&self) -> (String, String) {
+        let agent = agent_account(self.agent_id);
+        let customer = user_account(self.customer_id);
+        match self.kind() {
+            CashKind::CashIn => (agent, customer),
+            CashKind::CashOut => (customer, agent),
+        }
+    }
+}
+
+/// Result of a CONFIRM
+#[derive(Debug)]
+pub enum ConfirmOutcome {
+    /// Code accepted, waiting on the other party
+    Waiting(CashRequest),
+    /// Both parties confirmed and the ledger transfer settled
+    Settled(CashRequest),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AgentError {
+    #[error("No pending request for that code")]
+    NotFound,
+    #[error("Request expired")]
+    Expired,
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+const CASH_REQUEST_COLUMNS: &str = "id, agent_id, agent_phone, customer_id, customer_phone, kind, amount, agent_code, customer_code, agent_confirmed, customer_confirmed, status, expires_at";
+
+#[derive(Clone)]
+pub struct AgentRepository {
+    pool: PgPool,
+    ledger: LedgerRepository,
+}
+
+impl AgentRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { ledger: LedgerRepository::new(pool.clone()), pool }
+    }
+
+    /// Register (or re-activate) an agent
+    pub async fn register(&self, phone: &str, name: &str) -> Result<Agent, sqlx::Error> {
+        let _timer = QueryTimer::start("agents.register");
+        sqlx::query_as::<_, Agent>(
+            "INSERT INTO agents (id, phone, name) VALUES ($1, $2, $3)
+             ON CONFLICT (phone) DO UPDATE SET name = EXCLUDED.name, active = TRUE
+             RETURNING id, phone, name, active, created_at"
+        )
+        .bind(Uuid::new_v4())
+        .bind(phone)
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn find_active_by_phone(&self, phone: &str) -> Result<Option<Agent>, sqlx::Error> {
+        let _timer = QueryTimer::start("agents.find_active_by_phone");
+        sqlx::query_as::<_, Agent>(
+            "SELECT id, phone, name, active, created_at FROM agents WHERE phone = $1 AND active"
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn find(&self, id: Uuid) -> Result<Option<Agent>, sqlx::Error> {
+        let _timer = QueryTimer::start("agents.find");
+        sqlx::query_as::<_, Agent>("SELECT id, phone, name, active, created_at FROM agents WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// All agents with their current float
+    pub async fn list_with_float(&self) -> Result<Vec<(Agent, i64)>, sqlx::Error> {
+        let _timer = QueryTimer::start("agents.list_with_float");
+        let agents = sqlx::query_as::<_, Agent>(
+            "SELECT id, phone, name, active, created_at FROM agents ORDER BY created_at"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut result = Vec::with_capacity(agents.len());
+        for agent in agents {
+            let float = self.float_balance(agent.id).await?;
+            result.push((agent, float));
+        }
+        Ok(result)
+    }
+
+    pub async fn float_balance(&self, agent_id: Uuid) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("agents.float_balance");
+        self.ledger.balance(&agent_account(agent_id)).await
+    }
+
+    /// Credit an agent's float from an off-ledger deposit
+    pub async fn top_up(&self, agent_id: Uuid, amount: i64, reference: Option<&str>) -> Result<i64, AgentError> {
+        let _timer = QueryTimer::start("agents.top_up");
+        self.ledger
+            .transfer(FLOAT_TOPUP_ACCOUNT, &agent_account(agent_id), amount, "float_topup", reference)
+            .await?;
+        Ok(self.float_balance(agent_id).await?)
+    }
+
+    /// Open a cash request with fresh confirmation codes for both parties
+    pub async fn create_request(
+        &self,
+        agent: &Agent,
+        customer_id: Uuid,
+        customer_phone: &str,
+        kind: CashKind,
+        amount: i64,
+    ) -> Result<CashRequest, sqlx::Error> {
+        let _timer = QueryTimer::start("agents.create_request");
+        let (agent_code, customer_code) = generate_code_pair();
+
+        sqlx::query_as::<_, CashRequest>(&format!(
+            "INSERT INTO agent_cash_requests
+                (id, agent_id, agent_phone, customer_id, customer_phone, kind, amount, agent_code, customer_code, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+             RETURNING {}",
+            CASH_REQUEST_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(agent.id)
+        .bind(&agent.phone)
+        .bind(customer_id)
+        .bind(customer_phone)
+        .bind(kind.as_str())
+        .bind(amount)
+        .bind(agent_code)
+        .bind(customer_code)
+        .bind(Utc::now() + Duration::minutes(CASH_REQUEST_TTL_MINUTES))
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Record one party's confirmation; settles on the ledger once both
+    /// have confirmed. Settlement and the status change commit together.
+    pub async fn confirm(&self, phone: &str, code: &str) -> Result<ConfirmOutcome, AgentError> {
+        let _timer = QueryTimer::start("agents.confirm");
+        let mut tx = self.pool.begin().await?;
+
+        let request = sqlx::query_as::<_, CashRequest>(&format!(
+            "SELECT {} FROM agent_cash_requests
+             WHERE status = 'pending'
+               AND ((agent_phone = $1 AND agent_code = $2) OR (customer_phone = $1 AND customer_code = $2))
+             FOR UPDATE",
+            CASH_REQUEST_COLUMNS
+        ))
+        .bind(phone)
+        .bind(code)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(AgentError::NotFound)?;
+
+        if request.expires_at <= Utc::now() {
+            sqlx::query("UPDATE agent_cash_requests SET status = 'expired' WHERE id = $1")
+                .bind(request.id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            return Err(AgentError::Expired);
+        }
+
+        let column = if request.agent_phone == phone && request.agent_code == code {
+            "agent_confirmed"
+        } else {
+            "customer_confirmed"
+        };
+        let mut request = sqlx::query_as::<_, CashRequest>(&format!(
+            "UPDATE agent_cash_requests SET {} = TRUE WHERE id = $1 RETURNING {}",
+            column, CASH_REQUEST_COLUMNS
+        ))
+        .bind(request.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if !(request.agent_confirmed && request.customer_confirmed) {
+            tx.commit().await?;
+            return Ok(ConfirmOutcome::Waiting(request));
+        }
+
+        let (from, to) = request.accounts();
+        let reference = request.id.to_string();
+        match transfer_in(&mut tx, &from, &to, request.amount, request.kind.as_str(), Some(&reference)).await {
+            Ok(_) => {
+                sqlx::query("UPDATE agent_cash_requests SET status = 'settled', settled_at = NOW() WHERE id = $1")
+                    .bind(request.id)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await?;
+                request.status = "settled".to_string();
+                Ok(ConfirmOutcome::Settled(request))
+            }
+            Err(LedgerError::InsufficientFunds) => {
+                // Roll back the confirmation, then mark the request failed
+                tx.rollback().await?;
+                sqlx::query("UPDATE agent_cash_requests SET status = 'failed' WHERE id = $1")
+                    .bind(request.id)
+                    .execute(&self.pool)
+                    .await?;
+                Err(LedgerError::InsufficientFunds.into())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Two distinct 6-digit codes, one per party
+pub(super) fn generate_code_pair() -> (String, String) {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let agent_code: u32 = rng.gen_range(100_000..1_000_000);
+    let mut customer_code: u32 = rng.gen_range(100_000..1_000_000);
+    while customer_code == agent_code {
+        customer_code = rng.gen_range(100_000..1_000_000);
+    }
+    (agent_code.to_string(), customer_code.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_pair_is_distinct_six_digits() {
+        for _ in 0..100 {
+            let (agent, customer) = generate_code_pair();
+            assert_ne!(agent, customer);
+            assert_eq!(agent.len(), 6);
+            assert_eq!(customer.len(), 6);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::encryption::FieldCipher;
+use super::metrics::QueryTimer;
+
+/// A user's ALERT settings
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct AlertSettings {
+    /// Warn when the cash balance drops under this, in micro-USDC (None = off)
+    pub below: Option<i64>,
+    /// SMS for incoming deposits
+    pub deposit_notifications: bool,
+}
+
+impl Default for AlertSettings {
+    fn default() -> Self {
+        Self { below: None, deposit_notifications: true }
+    }
+}
+
+impl AlertSettings {
+    /// Whether a debit took the balance from at or over the threshold to
+    /// under it; staying under it does not warn again
+    pub fn crossed_below(&self, before: i64, after: i64) -> bool {
+        self.below.is_some_and(|threshold| before >= threshold && after < threshold)
+    }
+}
+
+/// Low-balance and deposit notification preferences (ALERT)
+#[derive(Clone)]
+pub struct BalanceAlertRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl BalanceAlertRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    /// Settings for a user (defaults if never changed)
+    pub async fn get(&self, user_id: Uuid) -> Result<AlertSettings, sqlx::Error> {
+        let _timer = QueryTimer::start("balance_alerts.get");
+        let settings = sqlx::query_as::<_, AlertSettings>(
+            "SELECT below, deposit_notifications FROM balance_alerts WHERE user_id = $1"
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(settings.unwrap_or_default())
+    }
+
+    /// Whether deposits to this phone's owner are notified (true for unknown numbers)
+    pub async fn deposit_notifications(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("balance_alerts.deposit_notifications");
+        let enabled = sqlx::query_scalar::<_, bool>(
+            "SELECT a.deposit_notifications FROM balance_alerts a
+             JOIN users u ON u.id = a.user_id
+             WHERE u.phone = ANY($1)"
+        )
+        .bind(self.cipher.lookup_keys(phone))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(enabled.unwrap_or(true))
+    }
+
+    /// Set or clear the low-balance threshold
+    pub async fn set_below(&self, user_id: Uuid, below: Option<i64>) -> Result<AlertSettings, sqlx::Error> {
+        let _timer = QueryTimer::start("balance_alerts.set_below");
+        sqlx::query_as::<_, AlertSettings>(
+            "INSERT INTO balance_alerts (user_id, below) VALUES ($1, $2)
+             ON CONFLICT (user_id) DO UPDATE SET below = EXCLUDED.below, updated_at = NOW()
+             RETURNING below, deposit_notifications"
+        )
+        .bind(user_id)
+        .bind(below)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Switch deposit notifications on or off
+    pub async fn set_deposit_notifications(&self, user_id: Uuid, enabled: bool) -> Result<AlertSettings, sqlx::Error> {
+        let _timer = QueryTimer::start("balance_alerts.set_deposit_notifications");
+        sqlx::query_as::<_, AlertSettings>(
+            "INSERT INTO balance_alerts (user_id, deposit_notifications) VALUES ($1, $2)
+             ON CONFLICT (user_id) DO UPDATE SET deposit_notifications = EXCLUDED.deposit_notifications, updated_at = NOW()
+             RETURNING below, deposit_notifications"
+        )
+        .bind(user_id)
+        .bind(enabled)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crossed_below_only_on_the_way_down() {
+        let settings = AlertSettings { below: Some(5_000_000), ..AlertSettings::default() };
+        assert!(settings.crossed_below(6_000_000, 4_000_000));
+        assert!(settings.crossed_below(5_000_000, 4_999_999));
+        assert!(!settings.crossed_below(4_000_000, 3_000_000));
+        assert!(!settings.crossed_below(9_000_000, 5_000_000));
+        assert!(!AlertSettings::default().crossed_below(6_000_000, 0));
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// A number on the waitlist
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct WaitlistEntry {
+    pub phone: String,
+    pub joined_at: DateTime<Utc>,
+    /// Set once the number was let in (by capacity or by an admin)
+    pub admitted_at: Option<DateTime<Utc>>,
+}
+
+/// An allowlisted number
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct AllowlistEntry {
+    pub phone: String,
+    pub note: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+/// Beta launch mode: allowlist, invite codes and the waitlist
+#[derive(Clone)]
+pub struct BetaRepository {
+    pool: PgPool,
+}
+
+impl BetaRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn is_allowlisted(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.is_allowlisted");
+        sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM beta_allowlist WHERE phone = $1)")
+            .bind(phone)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Allowlist a number; a waitlisted number is marked admitted. Returns
+    /// true if the number was waiting (and should be told it's in)
+    pub async fn allow(&self, phone: &str, note: Option<&str>) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.allow");
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO beta_allowlist (phone, note, added_at) VALUES ($1, $2, NOW())
+             ON CONFLICT (phone) DO UPDATE SET note = COALESCE(EXCLUDED.note, beta_allowlist.note)",
+        )
+        .bind(phone)
+        .bind(note)
+        .execute(&mut *tx)
+        .await?;
+        let admitted = sqlx::query("UPDATE beta_waitlist SET admitted_at = NOW() WHERE phone = $1 AND admitted_at IS NULL")
+            .bind(phone)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(admitted > 0)
+    }
+
+    pub async fn disallow(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.disallow");
+        let result = sqlx::query("DELETE FROM beta_allowlist WHERE phone = $1")
+            .bind(phone)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn list_allowlist(&self) -> Result<Vec<AllowlistEntry>, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.list_allowlist");
+        sqlx::query_as::<_, AllowlistEntry>("SELECT phone, note, added_at FROM beta_allowlist ORDER BY added_at")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn create_invite(&self, code: &str, max_uses: i32) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("beta.create_invite");
+        sqlx::query("INSERT INTO beta_invites (code, max_uses, uses, created_at) VALUES ($1, $2, 0, NOW())")
+            .bind(code)
+            .bind(max_uses)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Use up one redemption of an invite and allowlist the number; false if
+    /// the code is unknown or used up
+    pub async fn redeem_invite(&self, code: &str, phone: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.redeem_invite");
+        let mut tx = self.pool.begin().await?;
+        let used = sqlx::query("UPDATE beta_invites SET uses = uses + 1 WHERE code = $1 AND uses < max_uses")
+            .bind(code)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if used == 0 {
+            return Ok(false);
+        }
+        sqlx::query(
+            "INSERT INTO beta_allowlist (phone, note, added_at) VALUES ($1, $2, NOW())
+             ON CONFLICT (phone) DO NOTHING",
+        )
+        .bind(phone)
+        .bind(format!("invite {}", code))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM beta_waitlist WHERE phone = $1")
+            .bind(phone)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Add a number to the waitlist (no-op if already there); returns its
+    /// 1-based position among numbers still waiting
+    pub async fn join_waitlist(&self, phone: &str) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.join_waitlist");
+        sqlx::query("INSERT INTO beta_waitlist (phone, joined_at) VALUES ($1, NOW()) ON CONFLICT (phone) DO NOTHING")
+            .bind(phone)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM beta_waitlist
+             WHERE admitted_at IS NULL
+               AND joined_at <= (SELECT joined_at FROM beta_waitlist WHERE phone = $1)",
+        )
+        .bind(phone)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn list_waitlist(&self) -> Result<Vec<WaitlistEntry>, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.list_waitlist");
+        sqlx::query_as::<_, WaitlistEntry>("SELECT phone, joined_at, admitted_at FROM beta_waitlist ORDER BY joined_at")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// The number signed up; it no longer holds a waitlist place
+    pub async fn remove_from_waitlist(&self, phone: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("beta.remove_from_waitlist");
+        sqlx::query("DELETE FROM beta_waitlist WHERE phone = $1")
+            .bind(phone)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Wallets created plus numbers admitted that haven't signed up yet
+    pub async fn seats_taken(&self) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.seats_taken");
+        sqlx::query_scalar::<_, i64>(
+            "SELECT (SELECT COUNT(*) FROM users) + (SELECT COUNT(*) FROM beta_waitlist WHERE admitted_at IS NOT NULL)",
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Admit the `limit` longest-waiting numbers; returns their phones
+    pub async fn admit_next(&self, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("beta.admit_next");
+        let mut tx = self.pool.begin().await?;
+        let phones = sqlx::query_scalar::<_, String>(
+            "UPDATE beta_waitlist SET admitted_at = NOW() WHERE phone IN (
+                 SELECT phone FROM beta_waitlist
+                 WHERE admitted_at IS NULL
+                 ORDER BY joined_at
+                 LIMIT $1
+                 FOR UPDATE SKIP LOCKED
+             )
+             RETURNING phone",
+        )
+        .bind(limit)
+        .fetch_all(&mut *tx)
+        .await?;
+        for phone in &phones {
+            sqlx::query(
+                "INSERT INTO beta_allowlist (phone, note, added_at) VALUES ($1, 'waitlist', NOW())
+                 ON CONFLICT (phone) DO NOTHING",
+            )
+            .bind(phone)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(phones)
+    }
+}
@@ -0,0 +1,156 @@
+//! Merchant bills (BILL / PAY)
+//!
+//! A bill is `open` until a customer pays it or it expires. Paying claims it
+//! (`paying`) so two customers can't settle the same code at once; the
+//! transfer then marks it `paid`, or puts it back to `open` if it failed.
+
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgPool, Row};
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+use crate::money::{Currency, Money};
+
+/// How long a customer has to pay
+pub const BILL_EXPIRY_HOURS: i64 = 24;
+pub const BILL_CODE_LEN: usize = 6;
+/// No 0/O or 1/I, so codes survive being read aloud
+const BILL_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// Columns selected into `Bill`
+const BILL_COLUMNS: &str = "id, code, merchant_id, amount, token, memo, status, expires_at";
+
+#[derive(Debug, Clone)]
+pub struct Bill {
+    pub id: Uuid,
+    pub code: String,
+    pub merchant_id: Uuid,
+    /// In `token` micro-units
+    pub amount: Money,
+    pub memo: String,
+    /// open, paying, paid
+    pub status: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Bill {
+    /// Open and not yet expired
+    pub fn is_payable(&self) -> bool {
+        self.status == "open" && self.expires_at > Utc::now()
+    }
+}
+
+/// The stored micro-units are in the row's token
+impl FromRow<'_, PgRow> for Bill {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        let token: String = row.try_get("token")?;
+        let currency = Currency::parse(&token).unwrap_or(Currency::USDC);
+        Ok(Self {
+            id: row.try_get("id")?,
+            code: row.try_get("code")?,
+            merchant_id: row.try_get("merchant_id")?,
+            amount: Money::from_micros(row.try_get("amount")?, currency),
+            memo: row.try_get("memo")?,
+            status: row.try_get("status")?,
+            expires_at: row.try_get("expires_at")?,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct BillRepository {
+    pool: PgPool,
+}
+
+impl BillRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Open a bill under a fresh code
+    pub async fn create(&self, merchant_id: Uuid, amount: Money, memo: &str) -> Result<Bill, sqlx::Error> {
+        let _timer = QueryTimer::start("bills.create");
+        let expires_at = Utc::now() + Duration::hours(BILL_EXPIRY_HOURS);
+        // A clash on the code is retried with another
+        for _ in 0..5 {
+            let bill = sqlx::query_as::<_, Bill>(&format!(
+                "INSERT INTO bills (id, code, merchant_id, amount, token, memo, status, expires_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, 'open', $7)
+                 ON CONFLICT (code) DO NOTHING
+                 RETURNING {}",
+                BILL_COLUMNS
+            ))
+            .bind(Uuid::new_v4())
+            .bind(generate_bill_code())
+            .bind(merchant_id)
+            .bind(amount.micros())
+            .bind(amount.currency().code())
+            .bind(memo)
+            .bind(expires_at)
+            .fetch_optional(&self.pool)
+            .await?;
+            if let Some(bill) = bill {
+                return Ok(bill);
+            }
+        }
+        Err(sqlx::Error::Protocol("no free bill code".to_string()))
+    }
+
+    /// A bill by code, in any status
+    pub async fn find(&self, code: &str) -> Result<Option<Bill>, sqlx::Error> {
+        let _timer = QueryTimer::start("bills.find");
+        sqlx::query_as::<_, Bill>(&format!("SELECT {} FROM bills WHERE code = $1", BILL_COLUMNS))
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Claim an open, unexpired bill for payment (None = not payable)
+    pub async fn claim(&self, code: &str, payer_id: Uuid) -> Result<Option<Bill>, sqlx::Error> {
+        let _timer = QueryTimer::start("bills.claim");
+        sqlx::query_as::<_, Bill>(&format!(
+            "UPDATE bills SET status = 'paying', payer_id = $2
+             WHERE code = $1 AND status = 'open' AND expires_at > NOW() AND merchant_id <> $2
+             RETURNING {}",
+            BILL_COLUMNS
+        ))
+        .bind(code)
+        .bind(payer_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// The claimed bill's transfer went through
+    pub async fn settle(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("bills.settle");
+        sqlx::query("UPDATE bills SET status = 'paid', paid_at = NOW() WHERE id = $1 AND status = 'paying'")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The claimed bill wasn't paid; it can be paid again
+    pub async fn release(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("bills.release");
+        sqlx::query("UPDATE bills SET status = 'open', payer_id = NULL WHERE id = $1 AND status = 'paying'")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
+
+fn generate_bill_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..BILL_CODE_LEN)
+        .map(|_| BILL_CODE_CHARS[rng.gen_range(0..BILL_CODE_CHARS.len())] as char)
+        .collect()
+}
+
+/// Whether text could be a bill code, ignoring case
+pub fn is_bill_code(code: &str) -> bool {
+    code.len() == BILL_CODE_LEN && code.bytes().all(|b| BILL_CODE_CHARS.contains(&b.to_ascii_uppercase()))
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::encryption::{decode_error, FieldCipher};
+use super::metrics::QueryTimer;
+use crate::money::Money;
+
+/// Which users a broadcast goes to; every set filter must match
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BroadcastSegment {
+    /// Calling codes, e.g. `["254", "+234"]` (empty = every country)
+    #[serde(default)]
+    pub country_codes: Vec<String>,
+    /// Sent a command in the last N days
+    pub active_within_days: Option<u32>,
+    /// No command in the last N days (or never)
+    pub inactive_for_days: Option<u32>,
+    /// Cash balance bounds, inclusive
+    pub min_balance: Option<Money>,
+    pub max_balance: Option<Money>,
+}
+
+/// A user in a broadcast segment
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SegmentMember {
+    pub phone: String,
+    pub ens_name: Option<String>,
+    /// Cash balance
+    pub balance: Money,
+}
+
+/// Broadcast with its delivery counts
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct Broadcast {
+    pub id: Uuid,
+    pub template: String,
+    /// `BroadcastSegment` as JSON
+    pub segment: String,
+    /// "queued", "sending", "done" or "interrupted"
+    pub status: String,
+    pub recipients: i32,
+    pub sent: i32,
+    /// Held for the recipient's quiet hours
+    pub deferred: i32,
+    pub failed: i32,
+    /// Opted out (at queue time or since)
+    pub excluded: i32,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// How one broadcast message went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BroadcastOutcome {
+    Sent,
+    Deferred,
+    Failed,
+    Excluded,
+}
+
+impl BroadcastOutcome {
+    fn column(&self) -> &'static str {
+        match self {
+            BroadcastOutcome::Sent => "sent",
+            BroadcastOutcome::Deferred => "deferred",
+            BroadcastOutcome::Failed => "failed",
+            BroadcastOutcome::Excluded => "excluded",
+        }
+    }
+}
+
+const BROADCAST_COLUMNS: &str =
+    "id, template, segment, status, recipients, sent, deferred, failed, excluded, created_at, started_at, finished_at";
+
+/// Admin broadcasts and segment selection
+#[derive(Clone)]
+pub struct BroadcastRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl BroadcastRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    /// Users matching the activity and balance filters. Phones are stored
+    /// encrypted, so country codes are matched by the caller.
+    pub async fn segment_members(&self, segment: &BroadcastSegment) -> Result<Vec<SegmentMember>, sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.segment_members");
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT phone, ens_name, balance FROM (
+                SELECT COALESCE(u.phone_encrypted, u.phone) AS phone, u.ens_name, u.last_active_at,
+                       COALESCE((SELECT SUM(e.delta) FROM ledger_entries e WHERE e.account = 'user:' || u.id::text), 0)::BIGINT AS balance
+                FROM users u
+             ) s WHERE TRUE",
+        );
+        if let Some(days) = segment.active_within_days {
+            query.push(" AND last_active_at >= NOW() - make_interval(days => ").push_bind(days as i32).push(")");
+        }
+        if let Some(days) = segment.inactive_for_days {
+            query
+                .push(" AND (last_active_at IS NULL OR last_active_at < NOW() - make_interval(days => ")
+                .push_bind(days as i32)
+                .push("))");
+        }
+        if let Some(min) = segment.min_balance {
+            query.push(" AND balance >= ").push_bind(min.micros());
+        }
+        if let Some(max) = segment.max_balance {
+            query.push(" AND balance <= ").push_bind(max.micros());
+        }
+
+        query
+            .build_query_as::<SegmentMember>()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|mut member| {
+                member.phone = self.cipher.decrypt(&member.phone).map_err(decode_error)?;
+                Ok(member)
+            })
+            .collect()
+    }
+
+    pub async fn create(&self, template: &str, segment: &BroadcastSegment, recipients: i32, excluded: i32) -> Result<Broadcast, sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.create");
+        let segment = serde_json::to_string(segment).unwrap_or_default();
+        sqlx::query_as::<_, Broadcast>(&format!(
+            "INSERT INTO broadcasts (id, template, segment, status, recipients, excluded)
+             VALUES ($1, $2, $3, 'queued', $4, $5)
+             RETURNING {}",
+            BROADCAST_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(template)
+        .bind(segment)
+        .bind(recipients)
+        .bind(excluded)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn find(&self, id: Uuid) -> Result<Option<Broadcast>, sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.find");
+        sqlx::query_as::<_, Broadcast>(&format!("SELECT {} FROM broadcasts WHERE id = $1", BROADCAST_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Most recent broadcasts first
+    pub async fn list(&self, limit: i64) -> Result<Vec<Broadcast>, sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.list");
+        sqlx::query_as::<_, Broadcast>(&format!(
+            "SELECT {} FROM broadcasts ORDER BY created_at DESC LIMIT $1",
+            BROADCAST_COLUMNS
+        ))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn mark_sending(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.mark_sending");
+        sqlx::query("UPDATE broadcasts SET status = 'sending', started_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Count one message's outcome
+    pub async fn record(&self, id: Uuid, outcome: BroadcastOutcome) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.record");
+        let column = outcome.column();
+        sqlx::query(&format!("UPDATE broadcasts SET {} = {} + 1 WHERE id = $1", column, column))
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn finish(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.finish");
+        sqlx::query("UPDATE broadcasts SET status = 'done', finished_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Drop a broadcast that was never queued
+    pub async fn delete(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.delete");
+        sqlx::query("DELETE FROM broadcasts WHERE id = $1 AND status = 'queued'")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Broadcasts cut off by a restart (the queue lives in memory); returns how many
+    pub async fn mark_interrupted(&self) -> Result<u64, sqlx::Error> {
+        let _timer = QueryTimer::start("broadcasts.mark_interrupted");
+        let result = sqlx::query(
+            "UPDATE broadcasts SET status = 'interrupted', finished_at = NOW() WHERE status IN ('queued', 'sending')",
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,196 @@
+//! Partner campaigns that sponsor ENS mint gas
+//!
+//! While any campaign is active, every ENS mint is charged to one: the
+//! oldest active campaign with budget left. Budgets are in gwei. A mint's
+//! cost is only known once it is mined, so the last mint of a campaign can
+//! take it slightly over budget. Once every active campaign is spent, mints
+//! are refused until an admin tops one up or adds another.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// A partner campaign and its gas spending
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Campaign {
+    pub id: Uuid,
+    pub name: String,
+    pub partner: String,
+    pub gas_budget_gwei: i64,
+    pub gas_spent_gwei: i64,
+    /// Gas units across all sponsored mints
+    pub gas_used: i64,
+    pub mints: i32,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Campaign {
+    pub fn remaining_gwei(&self) -> i64 {
+        (self.gas_budget_gwei - self.gas_spent_gwei).max(0)
+    }
+}
+
+/// Who pays for the next mint
+#[derive(Debug, Clone)]
+pub enum Sponsorship {
+    /// No active campaigns; the operator pays
+    Unsponsored,
+    Campaign(Campaign),
+    /// Active campaigns exist but none has budget left
+    Exhausted,
+}
+
+/// Gas actually spent by one mint
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MintCost {
+    pub gas_used: i64,
+    pub cost_gwei: i64,
+}
+
+impl MintCost {
+    /// From the backend's decimal `gasUsed` and `gasCostWei`; wei round up to whole gwei
+    pub fn from_wei(gas_used: &str, cost_wei: &str) -> Option<Self> {
+        let gas_used = gas_used.trim().parse::<u64>().ok()?;
+        let cost_wei = cost_wei.trim().parse::<u128>().ok()?;
+        Some(Self {
+            gas_used: i64::try_from(gas_used).ok()?,
+            cost_gwei: i64::try_from(cost_wei.div_ceil(1_000_000_000)).ok()?,
+        })
+    }
+}
+
+const CAMPAIGN_COLUMNS: &str = "id, name, partner, gas_budget_gwei, gas_spent_gwei, gas_used, mints, active, created_at";
+
+#[derive(Clone)]
+pub struct CampaignRepository {
+    pool: PgPool,
+}
+
+impl CampaignRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn create(&self, name: &str, partner: &str, gas_budget_gwei: i64) -> Result<Campaign, sqlx::Error> {
+        let _timer = QueryTimer::start("campaigns.create");
+        sqlx::query_as::<_, Campaign>(&format!(
+            "INSERT INTO campaigns (id, name, partner, gas_budget_gwei) VALUES ($1, $2, $3, $4) RETURNING {}",
+            CAMPAIGN_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(name)
+        .bind(partner)
+        .bind(gas_budget_gwei)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn find(&self, id: Uuid) -> Result<Option<Campaign>, sqlx::Error> {
+        let _timer = QueryTimer::start("campaigns.find");
+        sqlx::query_as::<_, Campaign>(&format!("SELECT {} FROM campaigns WHERE id = $1", CAMPAIGN_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Oldest first
+    pub async fn list(&self) -> Result<Vec<Campaign>, sqlx::Error> {
+        let _timer = QueryTimer::start("campaigns.list");
+        sqlx::query_as::<_, Campaign>(&format!("SELECT {} FROM campaigns ORDER BY created_at", CAMPAIGN_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Add to a campaign's budget
+    pub async fn top_up(&self, id: Uuid, gas_gwei: i64) -> Result<Option<Campaign>, sqlx::Error> {
+        let _timer = QueryTimer::start("campaigns.top_up");
+        sqlx::query_as::<_, Campaign>(&format!(
+            "UPDATE campaigns SET gas_budget_gwei = gas_budget_gwei + $2 WHERE id = $1 RETURNING {}",
+            CAMPAIGN_COLUMNS
+        ))
+        .bind(id)
+        .bind(gas_gwei)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn set_active(&self, id: Uuid, active: bool) -> Result<Option<Campaign>, sqlx::Error> {
+        let _timer = QueryTimer::start("campaigns.set_active");
+        sqlx::query_as::<_, Campaign>(&format!(
+            "UPDATE campaigns SET active = $2 WHERE id = $1 RETURNING {}",
+            CAMPAIGN_COLUMNS
+        ))
+        .bind(id)
+        .bind(active)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// The campaign that pays for the next mint
+    pub async fn sponsor(&self) -> Result<Sponsorship, sqlx::Error> {
+        let _timer = QueryTimer::start("campaigns.sponsor");
+        let active = sqlx::query_as::<_, Campaign>(&format!(
+            "SELECT {} FROM campaigns WHERE active ORDER BY created_at",
+            CAMPAIGN_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        if active.is_empty() {
+            return Ok(Sponsorship::Unsponsored);
+        }
+        Ok(active
+            .into_iter()
+            .find(|campaign| campaign.remaining_gwei() > 0)
+            .map(Sponsorship::Campaign)
+            .unwrap_or(Sponsorship::Exhausted))
+    }
+
+    /// Charge a mined ENS name to a campaign; `gifted_by` is the user who
+    /// gave the name, when it was a GIFT NAME
+    pub async fn record_mint(&self, campaign_id: Uuid, ens_name: &str, tx_hash: Option<&str>, cost: MintCost, gifted_by: Option<Uuid>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("campaigns.record_mint");
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO campaign_mints (id, campaign_id, ens_name, tx_hash, gas_used, cost_gwei, gifted_by)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(Uuid::new_v4())
+        .bind(campaign_id)
+        .bind(ens_name)
+        .bind(tx_hash)
+        .bind(cost.gas_used)
+        .bind(cost.cost_gwei)
+        .bind(gifted_by)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE campaigns
+             SET gas_spent_gwei = gas_spent_gwei + $2, gas_used = gas_used + $3, mints = mints + 1
+             WHERE id = $1",
+        )
+        .bind(campaign_id)
+        .bind(cost.cost_gwei)
+        .bind(cost.gas_used)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mint_cost_from_wei() {
+        let cost = MintCost::from_wei("250000", "7500000000000001").unwrap();
+        assert_eq!(cost, MintCost { gas_used: 250_000, cost_gwei: 7_500_001 });
+        assert_eq!(MintCost::from_wei("0", "0"), Some(MintCost::default()));
+        assert_eq!(MintCost::from_wei("", "1"), None);
+        assert_eq!(MintCost::from_wei("1", "-5"), None);
+    }
+}
@@ -0,0 +1,74 @@
+//! EVM chains registered at runtime (see `wallet::chain_registry`)
+
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+const CHAIN_COLUMNS: &str = "chain_id, name, short_code, native_token, rpc_urls, explorer_url, testnet, confirmations, tokens";
+
+/// A stored chain registration
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ChainRow {
+    pub chain_id: i64,
+    pub name: String,
+    pub short_code: String,
+    pub native_token: String,
+    pub rpc_urls: Vec<String>,
+    pub explorer_url: Option<String>,
+    pub testnet: bool,
+    pub confirmations: i64,
+    /// `SYMBOL=address` entries
+    pub tokens: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct ChainRepository {
+    pool: PgPool,
+}
+
+impl ChainRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Every registered chain, oldest first
+    pub async fn list(&self) -> Result<Vec<ChainRow>, sqlx::Error> {
+        let _timer = QueryTimer::start("chains.list");
+        sqlx::query_as::<_, ChainRow>(&format!("SELECT {} FROM chains ORDER BY created_at", CHAIN_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Save a chain, replacing any earlier registration of its id
+    pub async fn upsert(&self, chain: &ChainRow) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("chains.upsert");
+        sqlx::query(
+            r#"
+            INSERT INTO chains (chain_id, name, short_code, native_token, rpc_urls, explorer_url, testnet, confirmations, tokens)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (chain_id) DO UPDATE SET
+                name = EXCLUDED.name,
+                short_code = EXCLUDED.short_code,
+                native_token = EXCLUDED.native_token,
+                rpc_urls = EXCLUDED.rpc_urls,
+                explorer_url = EXCLUDED.explorer_url,
+                testnet = EXCLUDED.testnet,
+                confirmations = EXCLUDED.confirmations,
+                tokens = EXCLUDED.tokens,
+                updated_at = NOW()
+            "#
+        )
+        .bind(chain.chain_id)
+        .bind(&chain.name)
+        .bind(&chain.short_code)
+        .bind(&chain.native_token)
+        .bind(&chain.rpc_urls)
+        .bind(&chain.explorer_url)
+        .bind(chain.testnet)
+        .bind(chain.confirmations)
+        .bind(&chain.tokens)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+//! Progress of one-off fixes to historical rows (see `maintenance`)
+//!
+//! Each job has one row, keyed by name. Its cursor is the id of the last row
+//! it looked at, saved after every batch so an interrupted run picks up
+//! where it stopped. A completed job is not run again unless restarted.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// Where a job got to, and what it did on the way
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct DataMigration {
+    pub name: String,
+    /// `running` until the last batch, then `completed`
+    pub status: String,
+    /// Id of the last row scanned; None before the first batch
+    pub cursor: Option<String>,
+    pub scanned: i64,
+    pub changed: i64,
+    pub failed: i64,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl DataMigration {
+    pub fn is_completed(&self) -> bool {
+        self.status == "completed"
+    }
+}
+
+const COLUMNS: &str = "name, status, cursor, scanned, changed, failed, started_at, updated_at, completed_at";
+
+#[derive(Clone)]
+pub struct DataMigrationRepository {
+    pool: PgPool,
+}
+
+impl DataMigrationRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, name: &str) -> Result<Option<DataMigration>, sqlx::Error> {
+        let _timer = QueryTimer::start("data_migrations.get");
+        sqlx::query_as::<_, DataMigration>(&format!("SELECT {} FROM data_migrations WHERE name = $1", COLUMNS))
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn list(&self) -> Result<Vec<DataMigration>, sqlx::Error> {
+        let _timer = QueryTimer::start("data_migrations.list");
+        sqlx::query_as::<_, DataMigration>(&format!("SELECT {} FROM data_migrations ORDER BY name", COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Mark a job running, keeping its cursor and counts; `restart` clears
+    /// them so it goes over every row again
+    pub async fn begin(&self, name: &str, restart: bool) -> Result<DataMigration, sqlx::Error> {
+        let _timer = QueryTimer::start("data_migrations.begin");
+        sqlx::query_as::<_, DataMigration>(&format!(
+            r#"
+            INSERT INTO data_migrations (name, status) VALUES ($1, 'running')
+            ON CONFLICT (name) DO UPDATE SET
+                status = 'running',
+                cursor = CASE WHEN $2 THEN NULL ELSE data_migrations.cursor END,
+                scanned = CASE WHEN $2 THEN 0 ELSE data_migrations.scanned END,
+                changed = CASE WHEN $2 THEN 0 ELSE data_migrations.changed END,
+                failed = CASE WHEN $2 THEN 0 ELSE data_migrations.failed END,
+                started_at = CASE WHEN $2 THEN NOW() ELSE data_migrations.started_at END,
+                updated_at = NOW(),
+                completed_at = NULL
+            RETURNING {}
+            "#,
+            COLUMNS
+        ))
+        .bind(name)
+        .bind(restart)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Save a finished batch: move the cursor and add its counts
+    pub async fn advance(&self, name: &str, cursor: &str, scanned: u64, changed: u64, failed: u64) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("data_migrations.advance");
+        sqlx::query(
+            "UPDATE data_migrations
+             SET cursor = $2, scanned = scanned + $3, changed = changed + $4, failed = failed + $5, updated_at = NOW()
+             WHERE name = $1"
+        )
+        .bind(name)
+        .bind(cursor)
+        .bind(scanned as i64)
+        .bind(changed as i64)
+        .bind(failed as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn complete(&self, name: &str) -> Result<DataMigration, sqlx::Error> {
+        let _timer = QueryTimer::start("data_migrations.complete");
+        sqlx::query_as::<_, DataMigration>(&format!(
+            "UPDATE data_migrations SET status = 'completed', completed_at = NOW(), updated_at = NOW()
+             WHERE name = $1
+             RETURNING {}",
+            COLUMNS
+        ))
+        .bind(name)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgPool, Row};
+use uuid::Uuid;
+
+use crate::money::{Currency, Money};
+use super::metrics::QueryTimer;
+
+/// A user's HD-derived deposit address
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DepositAddress {
+    pub user_phone: String,
+    /// Last component of `m/44'/60'/0'/0/<index>`
+    pub derivation_index: i64,
+    /// Lowercase hex
+    pub address: String,
+}
+
+/// Confirmed deposits to one address in one token, not yet swept
+#[derive(Debug, Clone)]
+pub struct SweepCandidate {
+    pub address: String,
+    pub derivation_index: i64,
+    pub chain: String,
+    pub token: String,
+    /// Sum of the confirmed, unswept deposits (the chain balance is what gets swept)
+    pub credited: Money,
+}
+
+impl FromRow<'_, PgRow> for SweepCandidate {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        let token: String = row.try_get("token")?;
+        let currency = Currency::parse(&token).unwrap_or(Currency::USDC);
+        Ok(Self {
+            address: row.try_get("address")?,
+            derivation_index: row.try_get("derivation_index")?,
+            chain: row.try_get("chain")?,
+            credited: Money::from_micros(row.try_get("credited")?, currency),
+            token,
+        })
+    }
+}
+
+/// One consolidation transfer from a deposit address to the treasury
+#[derive(Debug, Clone, serde::Serialize, sqlx::FromRow)]
+pub struct DepositSweep {
+    pub id: Uuid,
+    pub chain: String,
+    pub token: String,
+    pub address: String,
+    /// Whole tokens moved, as a decimal string
+    pub amount: String,
+    pub tx_hash: String,
+    /// Treasury transfer that paid this sweep's gas, if one was needed
+    pub gas_tx_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Deposit addresses and the sweeps that empty them
+#[derive(Clone)]
+pub struct DepositAddressRepository {
+    pool: PgPool,
+}
+
+impl DepositAddressRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find_by_user(&self, phone: &str) -> Result<Option<DepositAddress>, sqlx::Error> {
+        let _timer = QueryTimer::start("deposit_addresses.find_by_user");
+        sqlx::query_as::<_, DepositAddress>(
+            "SELECT user_phone, derivation_index, address FROM deposit_addresses WHERE user_phone = $1",
+        )
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn find_by_address(&self, address: &str) -> Result<Option<DepositAddress>, sqlx::Error> {
+        let _timer = QueryTimer::start("deposit_addresses.find_by_address");
+        sqlx::query_as::<_, DepositAddress>(
+            "SELECT user_phone, derivation_index, address FROM deposit_addresses WHERE address = $1",
+        )
+        .bind(address.to_lowercase())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Reserve the next unused derivation index
+    pub async fn next_index(&self) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("deposit_addresses.next_index");
+        sqlx::query_scalar("SELECT nextval('deposit_address_index')").fetch_one(&self.pool).await
+    }
+
+    /// Store a user's address; the existing one if they already have one
+    pub async fn assign(&self, phone: &str, derivation_index: i64, address: &str) -> Result<DepositAddress, sqlx::Error> {
+        let _timer = QueryTimer::start("deposit_addresses.assign");
+        let inserted = sqlx::query_as::<_, DepositAddress>(
+            r#"
+            INSERT INTO deposit_addresses (user_phone, derivation_index, address)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (user_phone) DO NOTHING
+            RETURNING user_phone, derivation_index, address
+            "#,
+        )
+        .bind(phone)
+        .bind(derivation_index)
+        .bind(address.to_lowercase())
+        .fetch_optional(&self.pool)
+        .await?;
+        match inserted {
+            Some(address) => Ok(address),
+            None => self.find_by_user(phone).await?.ok_or(sqlx::Error::RowNotFound),
+        }
+    }
+
+    /// Confirmed deposits to deposit addresses that haven't been swept yet
+    pub async fn sweep_candidates(&self, limit: i64) -> Result<Vec<SweepCandidate>, sqlx::Error> {
+        let _timer = QueryTimer::start("deposit_addresses.sweep_candidates");
+        sqlx::query_as::<_, SweepCandidate>(
+            r#"
+            SELECT a.address, a.derivation_index, d.chain, d.token, SUM(d.amount)::BIGINT AS credited
+            FROM deposits d
+            JOIN deposit_addresses a ON a.address = d.deposit_address
+            WHERE d.status = 'confirmed' AND d.sweep_id IS NULL AND d.chain IS NOT NULL AND d.token IS NOT NULL
+            GROUP BY a.address, a.derivation_index, d.chain, d.token
+            ORDER BY MIN(d.created_at)
+            LIMIT $1
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record a sent sweep and mark the deposits it covers
+    pub async fn record_sweep(
+        &self,
+        chain: &str,
+        token: &str,
+        address: &str,
+        amount: &str,
+        tx_hash: &str,
+        gas_tx_hash: Option<&str>,
+    ) -> Result<DepositSweep, sqlx::Error> {
+        let _timer = QueryTimer::start("deposit_addresses.record_sweep");
+        let mut tx = self.pool.begin().await?;
+        let sweep = sqlx::query_as::<_, DepositSweep>(
+            r#"
+            INSERT INTO deposit_sweeps (id, chain, token, address, amount, tx_hash, gas_tx_hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id, chain, token, address, amount, tx_hash, gas_tx_hash, created_at
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(chain)
+        .bind(token)
+        .bind(address)
+        .bind(amount)
+        .bind(tx_hash)
+        .bind(gas_tx_hash)
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            "UPDATE deposits SET sweep_id = $1
+             WHERE deposit_address = $2 AND chain = $3 AND token = $4 AND status = 'confirmed' AND sweep_id IS NULL",
+        )
+        .bind(sweep.id)
+        .bind(address)
+        .bind(chain)
+        .bind(token)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(sweep)
+    }
+
+    /// Most recent sweeps first
+    pub async fn list_sweeps(&self, limit: i64) -> Result<Vec<DepositSweep>, sqlx::Error> {
+        let _timer = QueryTimer::start("deposit_addresses.list_sweeps");
+        sqlx::query_as::<_, DepositSweep>(
+            "SELECT id, chain, token, address, amount, tx_hash, gas_tx_hash, created_at
+             FROM deposit_sweeps ORDER BY created_at DESC LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -1,7 +1,13 @@
-use sqlx::PgPool;
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, PgPool, Row};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
+use crate::money::{Currency, Money};
+use crate::sms::display::{NumberLocale, SmsDisplay};
+use super::encryption::FieldCipher;
+use super::metrics::QueryTimer;
+
 /// Deposit source type
 #[derive(Debug, Clone, PartialEq)]
 pub enum DepositSource {
@@ -20,103 +26,236 @@ impl std::fmt::Display for DepositSource {
     }
 }
 
+/// Columns selected into `Deposit`
+pub const DEPOSIT_COLUMNS: &str =
+    "id, user_phone, amount, source, source_ref, chain, created_at, status, token, block_number, block_hash, confirmations";
+
 /// Deposit record in database
-#[derive(Debug, Clone, sqlx::FromRow)]
+#[derive(Debug, Clone)]
 pub struct Deposit {
     pub id: Uuid,
+    /// Depositor's number, or its blind index when phones are hashed
     pub user_phone: String,
-    pub amount: i64,          // Amount in micro USDC (6 decimals)
+    /// In `token` for on-chain deposits, otherwise USDC
+    pub amount: Money,
     pub source: String,       // "voucher", "onchain", "partner"
     pub source_ref: Option<String>,  // voucher code, tx hash, or partner ref
     pub chain: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// "pending" until deep enough on chain, "confirmed", or "reversed" if a
+    /// reorg dropped it; vouchers and partner credits are confirmed at once
+    pub status: String,
+    /// Token symbol for on-chain deposits
+    pub token: Option<String>,
+    /// Block the deposit transaction was last seen in
+    pub block_number: Option<i64>,
+    pub block_hash: Option<String>,
+    /// Blocks on top of (and including) `block_number` at the last check
+    pub confirmations: i32,
+}
+
+/// The stored micro-units are in the row's token, so `amount` is decoded
+/// with that currency rather than as plain micro-USDC
+impl FromRow<'_, PgRow> for Deposit {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        let token: Option<String> = row.try_get("token")?;
+        let currency = token.as_deref().and_then(Currency::parse).unwrap_or(Currency::USDC);
+        Ok(Self {
+            id: row.try_get("id")?,
+            user_phone: row.try_get("user_phone")?,
+            amount: Money::from_micros(row.try_get("amount")?, currency),
+            source: row.try_get("source")?,
+            source_ref: row.try_get("source_ref")?,
+            chain: row.try_get("chain")?,
+            created_at: row.try_get("created_at")?,
+            status: row.try_get("status")?,
+            token,
+            block_number: row.try_get("block_number")?,
+            block_hash: row.try_get("block_hash")?,
+            confirmations: row.try_get("confirmations")?,
+        })
+    }
 }
 
 impl Deposit {
-    /// Get amount as f64 (human readable)
-    pub fn amount_as_f64(&self) -> f64 {
-        self.amount as f64 / 1_000_000.0
+    pub fn is_pending(&self) -> bool {
+        self.status == "pending"
+    }
+}
+
+/// HISTORY line, e.g. `$12.50 via voucher (pending)`
+impl SmsDisplay for Deposit {
+    fn sms_string(&self, locale: NumberLocale) -> String {
+        let pending = if self.is_pending() { " (pending)" } else { "" };
+        format!("${} via {}{}", locale.number(&self.amount.format(2)), self.source, pending)
     }
 }
 
 /// Deposit repository for database operations
+///
+/// `user_phone` holds the number, or its blind index when phones are
+/// hashed; lookups match either.
 #[derive(Clone)]
 pub struct DepositRepository {
     pool: PgPool,
+    cipher: FieldCipher,
 }
 
 impl DepositRepository {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
     }
 
     /// Record a new deposit from voucher redemption
     pub async fn create_from_voucher(
         &self,
         phone: &str,
-        amount: i64,
+        amount: Money,
         voucher_code: &str,
     ) -> Result<Deposit, sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.create_from_voucher");
         let id = Uuid::new_v4();
         
-        sqlx::query_as::<_, Deposit>(
-            r#"
-            INSERT INTO deposits (id, user_phone, amount, source, source_ref)
-            VALUES ($1, $2, $3, 'voucher', $4)
-            RETURNING id, user_phone, amount, source, source_ref, chain, created_at
-            "#
-        )
+        sqlx::query_as::<_, Deposit>(&format!(
+            "INSERT INTO deposits (id, user_phone, amount, source, source_ref)
+             VALUES ($1, $2, $3, 'voucher', $4)
+             RETURNING {}",
+            DEPOSIT_COLUMNS
+        ))
         .bind(id)
-        .bind(phone)
+        .bind(self.cipher.phone_ref(phone))
         .bind(amount)
         .bind(voucher_code)
         .fetch_one(&self.pool)
         .await
     }
 
-    /// Record an on-chain deposit
-    pub async fn create_from_chain(
+    /// Record an on-chain deposit of `amount` (in its token) as pending
+    /// until it has enough confirmations. A reversed deposit seen again
+    /// (re-mined after a reorg) goes back to pending. None if this transfer
+    /// is already being tracked.
+    pub async fn record_pending(
         &self,
         phone: &str,
-        amount: i64,
+        amount: Money,
         tx_hash: &str,
         chain: &str,
-    ) -> Result<Deposit, sqlx::Error> {
+        block_number: i64,
+    ) -> Result<Option<Deposit>, sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.record_pending");
         let id = Uuid::new_v4();
-        
-        sqlx::query_as::<_, Deposit>(
-            r#"
-            INSERT INTO deposits (id, user_phone, amount, source, source_ref, chain)
-            VALUES ($1, $2, $3, 'onchain', $4, $5)
-            RETURNING id, user_phone, amount, source, source_ref, chain, created_at
-            "#
-        )
+
+        sqlx::query_as::<_, Deposit>(&format!(
+            "INSERT INTO deposits (id, user_phone, amount, source, source_ref, chain, token, block_number, status)
+             VALUES ($1, $2, $3, 'onchain', $4, $5, $6, $7, 'pending')
+             ON CONFLICT (chain, source_ref, token) WHERE source = 'onchain' DO UPDATE
+                SET status = 'pending', block_number = EXCLUDED.block_number, block_hash = NULL,
+                    confirmations = 0, confirmed_at = NULL, reversed_at = NULL
+                WHERE deposits.status = 'reversed'
+             RETURNING {}",
+            DEPOSIT_COLUMNS
+        ))
         .bind(id)
-        .bind(phone)
+        .bind(self.cipher.phone_ref(phone))
         .bind(amount)
-        .bind(tx_hash)
+        .bind(tx_hash.to_lowercase())
         .bind(chain)
-        .fetch_one(&self.pool)
+        .bind(amount.currency().code())
+        .bind(block_number)
+        .fetch_optional(&self.pool)
         .await
     }
 
+    /// Note the per-user deposit address a transfer was sent to, so the
+    /// sweeper can consolidate it once confirmed
+    pub async fn set_deposit_address(&self, id: Uuid, address: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.set_deposit_address");
+        sqlx::query("UPDATE deposits SET deposit_address = $2 WHERE id = $1")
+            .bind(id)
+            .bind(address.to_lowercase())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// On-chain deposits to check: pending ones, plus ones confirmed in the
+    /// last `reorg_window_minutes` so a late reorg can still reverse them
+    pub async fn list_watched(&self, reorg_window_minutes: i32, limit: i64) -> Result<Vec<Deposit>, sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.list_watched");
+        sqlx::query_as::<_, Deposit>(&format!(
+            "SELECT {} FROM deposits
+             WHERE source = 'onchain'
+               AND (status = 'pending'
+                    OR (status = 'confirmed' AND confirmed_at > NOW() - make_interval(mins => $1)))
+             ORDER BY created_at
+             LIMIT $2",
+            DEPOSIT_COLUMNS
+        ))
+        .bind(reorg_window_minutes)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record where the transaction sits now and how deep it is
+    pub async fn update_block(&self, id: Uuid, block_number: i64, block_hash: &str, confirmations: i32) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.update_block");
+        sqlx::query("UPDATE deposits SET block_number = $2, block_hash = $3, confirmations = $4 WHERE id = $1")
+            .bind(id)
+            .bind(block_number)
+            .bind(block_hash)
+            .bind(confirmations)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// pending -> confirmed; false if the deposit was no longer pending
+    pub async fn mark_confirmed(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.mark_confirmed");
+        let result = sqlx::query(
+            "UPDATE deposits SET status = 'confirmed', confirmed_at = NOW() WHERE id = $1 AND status = 'pending'",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// pending/confirmed -> reversed, taking it out of the balance;
+    /// false if it was already reversed
+    pub async fn mark_reversed(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.mark_reversed");
+        let result = sqlx::query(
+            "UPDATE deposits SET status = 'reversed', reversed_at = NOW() WHERE id = $1 AND status <> 'reversed'",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
     /// Get all deposits for a user
     pub async fn find_by_user(&self, phone: &str) -> Result<Vec<Deposit>, sqlx::Error> {
-        sqlx::query_as::<_, Deposit>(
-            "SELECT id, user_phone, amount, source, source_ref, chain, created_at 
-             FROM deposits WHERE user_phone = $1 ORDER BY created_at DESC"
-        )
-        .bind(phone)
+        let _timer = QueryTimer::start("deposits.find_by_user");
+        sqlx::query_as::<_, Deposit>(&format!(
+            "SELECT {} FROM deposits WHERE user_phone = ANY($1) ORDER BY created_at DESC",
+            DEPOSIT_COLUMNS
+        ))
+        .bind(self.cipher.lookup_keys(phone))
         .fetch_all(&self.pool)
         .await
     }
 
-    /// Get total USDC balance for a user (from all deposits)
-    pub async fn get_balance(&self, phone: &str) -> Result<i64, sqlx::Error> {
-        let result = sqlx::query_scalar::<_, i64>(
-            "SELECT COALESCE(SUM(amount), 0) FROM deposits WHERE user_phone = $1"
+    /// Get total USDC balance for a user (from confirmed deposits only;
+    /// deposits of other tokens are not USDC and don't count)
+    pub async fn get_balance(&self, phone: &str) -> Result<Money, sqlx::Error> {
+        let _timer = QueryTimer::start("deposits.get_balance");
+        let result = sqlx::query_scalar::<_, Money>(
+            "SELECT COALESCE(SUM(amount), 0)::BIGINT FROM deposits
+             WHERE user_phone = ANY($1) AND status = 'confirmed' AND COALESCE(token, 'USDC') = 'USDC'"
         )
-        .bind(phone)
+        .bind(self.cipher.lookup_keys(phone))
         .fetch_one(&self.pool)
         .await?;
         
@@ -125,19 +264,19 @@ impl DepositRepository {
 
     /// Get balance as formatted string
     pub async fn get_balance_formatted(&self, phone: &str) -> Result<String, sqlx::Error> {
-        let balance = self.get_balance(phone).await?;
-        let usdc = balance as f64 / 1_000_000.0;
-        Ok(format!("{:.2}", usdc))
+        let _timer = QueryTimer::start("deposits.get_balance_formatted");
+        Ok(self.get_balance(phone).await?.format(2))
     }
 
-    /// Get recent deposits (last N)
+    /// Get recent deposits (last N), leaving out reversed ones
     pub async fn get_recent(&self, phone: &str, limit: i64) -> Result<Vec<Deposit>, sqlx::Error> {
-        sqlx::query_as::<_, Deposit>(
-            "SELECT id, user_phone, amount, source, source_ref, chain, created_at 
-             FROM deposits WHERE user_phone = $1 
-             ORDER BY created_at DESC LIMIT $2"
-        )
-        .bind(phone)
+        let _timer = QueryTimer::start("deposits.get_recent");
+        sqlx::query_as::<_, Deposit>(&format!(
+            "SELECT {} FROM deposits WHERE user_phone = ANY($1) AND status <> 'reversed'
+             ORDER BY created_at DESC LIMIT $2",
+            DEPOSIT_COLUMNS
+        ))
+        .bind(self.cipher.lookup_keys(phone))
         .bind(limit)
         .fetch_all(&self.pool)
         .await
@@ -0,0 +1,135 @@
+use sqlx::PgPool;
+
+use super::encryption::{decode_error, FieldCipher};
+use super::metrics::QueryTimer;
+
+/// Wrong codes allowed before a pending link has to be restarted
+const MAX_CODE_ATTEMPTS: i32 = 5;
+
+/// Outcome of EMAIL <code>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmailVerification {
+    /// The address is now linked to the phone
+    Verified(String),
+    WrongCode,
+    /// No pending link, or it expired / ran out of attempts
+    NoPending,
+}
+
+/// Email addresses allowed to send commands for a phone. An address only
+/// counts once the phone has texted back the code mailed to it; each phone
+/// has at most one address. Phones are stored encrypted with a blind index
+/// for lookups, like other personal data columns.
+#[derive(Clone)]
+pub struct EmailLinkRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl EmailLinkRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    /// Start linking `email` to a phone, replacing any earlier link for it.
+    /// False if the address is already verified for another phone
+    pub async fn start_link(&self, phone: &str, email: &str, code: &str, ttl_minutes: i32) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("email_links.start_link");
+        let encrypted = self.cipher.encrypt(phone).map_err(decode_error)?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM email_links WHERE phone_index = ANY($1)")
+            .bind(self.cipher.lookup_keys(phone))
+            .execute(&mut *tx)
+            .await?;
+        let stored = sqlx::query(
+            "INSERT INTO email_links (email, phone, phone_index, code, code_expires_at, attempts, created_at)
+             VALUES ($1, $2, $3, $4, NOW() + make_interval(mins => $5), 0, NOW())
+             ON CONFLICT (email) DO UPDATE SET
+                 phone = EXCLUDED.phone,
+                 phone_index = EXCLUDED.phone_index,
+                 code = EXCLUDED.code,
+                 code_expires_at = EXCLUDED.code_expires_at,
+                 attempts = 0,
+                 created_at = NOW()
+             WHERE email_links.verified_at IS NULL",
+        )
+        .bind(email)
+        .bind(encrypted)
+        .bind(self.cipher.blind_index(phone))
+        .bind(code)
+        .bind(ttl_minutes)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if stored == 0 {
+            return Ok(false);
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Check a code against the phone's pending link
+    pub async fn verify(&self, phone: &str, code: &str) -> Result<EmailVerification, sqlx::Error> {
+        let _timer = QueryTimer::start("email_links.verify");
+        let keys = self.cipher.lookup_keys(phone);
+        let verified = sqlx::query_scalar::<_, String>(
+            "UPDATE email_links SET verified_at = NOW(), code = NULL, code_expires_at = NULL
+             WHERE phone_index = ANY($1) AND verified_at IS NULL
+               AND code = $2 AND code_expires_at > NOW() AND attempts < $3
+             RETURNING email",
+        )
+        .bind(&keys)
+        .bind(code)
+        .bind(MAX_CODE_ATTEMPTS)
+        .fetch_optional(&self.pool)
+        .await?;
+        if let Some(email) = verified {
+            return Ok(EmailVerification::Verified(email));
+        }
+
+        let pending = sqlx::query(
+            "UPDATE email_links SET attempts = attempts + 1
+             WHERE phone_index = ANY($1) AND verified_at IS NULL
+               AND code_expires_at > NOW() AND attempts < $2",
+        )
+        .bind(&keys)
+        .bind(MAX_CODE_ATTEMPTS)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(if pending > 0 { EmailVerification::WrongCode } else { EmailVerification::NoPending })
+    }
+
+    /// Remove the phone's link (pending or verified); true if there was one
+    pub async fn unlink(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("email_links.unlink");
+        let result = sqlx::query("DELETE FROM email_links WHERE phone_index = ANY($1)")
+            .bind(self.cipher.lookup_keys(phone))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// The verified address for a phone
+    pub async fn email_for_phone(&self, phone: &str) -> Result<Option<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("email_links.email_for_phone");
+        sqlx::query_scalar::<_, String>(
+            "SELECT email FROM email_links WHERE phone_index = ANY($1) AND verified_at IS NOT NULL",
+        )
+        .bind(self.cipher.lookup_keys(phone))
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// The phone a verified address acts for
+    pub async fn phone_for_email(&self, email: &str) -> Result<Option<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("email_links.phone_for_email");
+        let stored = sqlx::query_scalar::<_, String>(
+            "SELECT phone FROM email_links WHERE email = $1 AND verified_at IS NOT NULL",
+        )
+        .bind(email)
+        .fetch_optional(&self.pool)
+        .await?;
+        stored.map(|phone| self.cipher.decrypt(&phone).map_err(decode_error)).transpose()
+    }
+}
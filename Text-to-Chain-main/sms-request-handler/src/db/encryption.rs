@@ -0,0 +1,423 @@
+//! Field-level encryption for personal data columns
+//!
+//! Encrypted columns:
+//! - `users.phone_encrypted` (with `users.phone` holding the blind index)
+//! - `address_book.user_phone` (blind index only)
+//! - `address_book.contact_phone`, `address_book.wallet_address`
+//!   (with `contact_phone_index` / `wallet_address_index` blind indexes)
+//! - `sms_transcripts.body` (with `sms_transcripts.phone` holding the blind
+//!   index; not rewritten by `reencrypt_all`, rows age out instead)
+//!
+//! Values are sealed with the KeyVault master key. Equality lookups go through
+//! blind indexes (keyed HMAC), and every lookup also matches the previous
+//! key's index and the legacy plaintext value, so rows written before the key
+//! was set (or rotated) stay readable until `reencrypt_all` has run.
+//!
+//! With `PHONE_STORAGE=hashed` no phone number is kept in a form that can be
+//! turned back into the number: `users.phone_encrypted` stays empty,
+//! `address_book.contact_phone` and `deposits.user_phone` hold the blind
+//! index, and `hash_phones` converts rows written before. Lookups work the
+//! same; messaging a stored number doesn't (see `SmsError::HashedNumber`).
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::VaultConfig;
+use crate::wallet::vault::VaultError;
+use crate::wallet::KeyVault;
+
+/// How phone numbers are kept (`PHONE_STORAGE`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PhoneStorage {
+    /// Blind index for lookups plus an encrypted copy
+    #[default]
+    Encrypted,
+    /// Blind index only; the number can't be read back from the database
+    Hashed,
+}
+
+impl PhoneStorage {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "encrypted" => Some(PhoneStorage::Encrypted),
+            "hashed" => Some(PhoneStorage::Hashed),
+            _ => None,
+        }
+    }
+}
+
+/// Encrypts/decrypts designated columns and computes their lookup keys
+#[derive(Clone, Debug)]
+pub struct FieldCipher {
+    vault: KeyVault,
+    /// Key being rotated away from, still accepted for reads and lookups
+    previous: Option<KeyVault>,
+    phones: PhoneStorage,
+}
+
+impl FieldCipher {
+    pub fn new(vault: KeyVault, previous: Option<KeyVault>) -> Self {
+        Self { vault, previous, phones: PhoneStorage::Encrypted }
+    }
+
+    pub fn with_phone_storage(mut self, phones: PhoneStorage) -> Self {
+        self.phones = phones;
+        self
+    }
+
+    /// Load from `KEY_VAULT_MASTER_KEY`, optional `KEY_VAULT_PREVIOUS_MASTER_KEY`
+    /// and `PHONE_STORAGE` (checked when the config is loaded)
+    pub fn from_config(config: &VaultConfig) -> Result<Self, VaultError> {
+        let phones = PhoneStorage::parse(&config.phone_storage).unwrap_or_default();
+        let vault = KeyVault::from_config(config)?;
+        Ok(Self::new(vault, KeyVault::previous(config)).with_phone_storage(phones))
+    }
+
+    pub fn phone_storage(&self) -> PhoneStorage {
+        self.phones
+    }
+
+    /// Whether blind indexes are keyed; without a master key they fall back
+    /// to the plaintext, so hashed phone storage needs one
+    pub fn is_keyed(&self) -> bool {
+        self.vault.is_sealed()
+    }
+
+    /// Recoverable copy of a phone number (None when phones are hashed)
+    pub fn encrypt_phone(&self, phone: &str) -> Result<Option<String>, VaultError> {
+        match self.phones {
+            PhoneStorage::Encrypted => self.encrypt(phone).map(Some),
+            PhoneStorage::Hashed => Ok(None),
+        }
+    }
+
+    /// Value for a column that holds a phone number encrypted: its blind
+    /// index instead when phones are hashed
+    pub fn seal_phone(&self, phone: &str) -> Result<String, VaultError> {
+        match self.phones {
+            PhoneStorage::Encrypted => self.encrypt(phone),
+            PhoneStorage::Hashed => Ok(self.blind_index(phone)),
+        }
+    }
+
+    /// Value for a column that holds a phone number as-is: its blind index
+    /// instead when phones are hashed
+    pub fn phone_ref(&self, phone: &str) -> String {
+        match self.phones {
+            PhoneStorage::Encrypted => phone.to_string(),
+            PhoneStorage::Hashed => self.blind_index(phone),
+        }
+    }
+
+    /// Encrypt a value for storage (stored as-is when no master key is set)
+    pub fn encrypt(&self, value: &str) -> Result<String, VaultError> {
+        if !self.vault.is_sealed() {
+            return Ok(value.to_string());
+        }
+        self.vault.seal(value.as_bytes())
+    }
+
+    pub fn encrypt_opt(&self, value: Option<&str>) -> Result<Option<String>, VaultError> {
+        value.map(|v| self.encrypt(v)).transpose()
+    }
+
+    /// Decrypt a stored value; legacy plaintext values are returned unchanged
+    pub fn decrypt(&self, stored: &str) -> Result<String, VaultError> {
+        if !KeyVault::is_sealed_value(stored) {
+            return Ok(stored.to_string());
+        }
+
+        let bytes = match self.vault.open(stored) {
+            Ok(bytes) => bytes,
+            Err(e) => match self.previous.as_ref().map(|previous| previous.open(stored)) {
+                Some(Ok(bytes)) => bytes,
+                _ => return Err(e),
+            },
+        };
+        String::from_utf8(bytes).map_err(|_| VaultError::Malformed)
+    }
+
+    pub fn decrypt_opt(&self, stored: Option<&str>) -> Result<Option<String>, VaultError> {
+        stored.map(|v| self.decrypt(v)).transpose()
+    }
+
+    /// Value written to a blind index column
+    pub fn blind_index(&self, value: &str) -> String {
+        self.vault.blind_index(value).unwrap_or_else(|| value.to_string())
+    }
+
+    pub fn blind_index_opt(&self, value: Option<&str>) -> Option<String> {
+        value.map(|v| self.blind_index(v))
+    }
+
+    /// Blind indexes of every leading prefix of a phone number ("+2", "+25",
+    /// ... up to the whole number), for prefix search without the plaintext
+    pub fn prefix_indexes(&self, phone: &str) -> Vec<String> {
+        phone
+            .char_indices()
+            .skip(1)
+            .map(|(i, c)| self.blind_index(&phone[..i + c.len_utf8()]))
+            .collect()
+    }
+
+    /// Every value a blind index column may hold for `value`: the current
+    /// index, the previous key's index and the legacy plaintext
+    pub fn lookup_keys(&self, value: &str) -> Vec<String> {
+        let mut keys = vec![self.blind_index(value)];
+        if let Some(previous) = self.previous.as_ref().and_then(|p| p.blind_index(value)) {
+            keys.push(previous);
+        }
+        if !keys.iter().any(|k| k == value) {
+            keys.push(value.to_string());
+        }
+        keys
+    }
+}
+
+/// Map a vault error into the repository error type
+pub(crate) fn decode_error(e: VaultError) -> sqlx::Error {
+    sqlx::Error::Decode(Box::new(e))
+}
+
+/// Rows rewritten by `reencrypt_all`
+#[derive(Debug, Default)]
+pub struct ReencryptStats {
+    pub users: u64,
+    pub contacts: u64,
+    /// Deposits whose phone was hashed (`hash_phones` only)
+    pub deposits: u64,
+    pub failed: u64,
+}
+
+/// Rewrite every designated column with the current key: encrypts legacy
+/// plaintext rows and moves rows sealed with the previous key to the new one.
+/// Phones follow `PHONE_STORAGE`; a phone already stored only as a blind
+/// index can't be recovered and is left as it is.
+pub async fn reencrypt_all(pool: &PgPool, cipher: &FieldCipher) -> Result<ReencryptStats, sqlx::Error> {
+    let mut stats = ReencryptStats::default();
+
+    let contacts = sqlx::query_as::<_, (Uuid, String, Option<String>, Option<String>, Option<String>)>(
+        "SELECT c.id, c.user_phone, c.contact_phone, c.wallet_address, u.phone_encrypted
+         FROM address_book c LEFT JOIN users u ON u.phone = c.user_phone"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    // Contacts first: their owner is found by joining on the users row's
+    // current `phone` value, which the users pass below rewrites
+    for (id, user_phone, contact_phone, wallet_address, owner_phone_encrypted) in contacts {
+        let decrypted = (
+            cipher.decrypt_opt(contact_phone.as_deref()),
+            cipher.decrypt_opt(wallet_address.as_deref()),
+        );
+        let (Ok(contact_phone), Ok(wallet_address)) = decrypted else {
+            tracing::error!(contact = %id, "Cannot decrypt contact");
+            stats.failed += 1;
+            continue;
+        };
+        let (contact_phone, contact_phone_index) = match contact_phone.as_deref() {
+            Some(hashed) if KeyVault::is_blind_index(hashed) => (Some(hashed.to_string()), Some(hashed.to_string())),
+            Some(phone) => (Some(cipher.seal_phone(phone).map_err(decode_error)?), Some(cipher.blind_index(phone))),
+            None => (None, None),
+        };
+
+        // `user_phone` is a one-way index; recover the owner's number from
+        // the users table, or it is still legacy plaintext
+        let owner_index = match owner_phone_encrypted.as_deref().map(|v| cipher.decrypt(v)) {
+            Some(Ok(plaintext)) => cipher.blind_index(&plaintext),
+            Some(Err(_)) => {
+                stats.failed += 1;
+                continue;
+            }
+            // Orphaned contact already indexed - keep its owner as-is
+            None if KeyVault::is_blind_index(&user_phone) => user_phone,
+            None => cipher.blind_index(&user_phone),
+        };
+
+        let result = sqlx::query(
+            "UPDATE address_book
+             SET user_phone = $1, contact_phone = $2, contact_phone_index = $3,
+                 wallet_address = $4, wallet_address_index = $5
+             WHERE id = $6"
+        )
+        .bind(owner_index)
+        .bind(contact_phone)
+        .bind(contact_phone_index)
+        .bind(cipher.encrypt_opt(wallet_address.as_deref()).map_err(decode_error)?)
+        .bind(cipher.blind_index_opt(wallet_address.as_deref()))
+        .bind(id)
+        .execute(pool)
+        .await;
+
+        match result {
+            Ok(_) => stats.contacts += 1,
+            Err(e) => {
+                tracing::error!(contact = %id, "Failed to re-encrypt contact: {}", e);
+                stats.failed += 1;
+            }
+        }
+    }
+
+    let users = sqlx::query_as::<_, (Uuid, String, Option<String>)>(
+        "SELECT id, phone, phone_encrypted FROM users"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for (id, phone, phone_encrypted) in users {
+        let plaintext = match phone_encrypted.as_deref().map(|v| cipher.decrypt(v)) {
+            Some(Ok(plaintext)) => plaintext,
+            Some(Err(e)) => {
+                tracing::error!(user = %id, "Cannot decrypt phone: {}", e);
+                stats.failed += 1;
+                continue;
+            }
+            // Hashed already; nothing to rewrite
+            None if KeyVault::is_blind_index(&phone) => continue,
+            // Legacy row: `phone` still holds the plaintext number
+            None => phone,
+        };
+
+        let encrypted = cipher.encrypt_phone(&plaintext).map_err(decode_error)?;
+        let result = sqlx::query("UPDATE users SET phone = $1, phone_encrypted = $2, phone_prefixes = $3 WHERE id = $4")
+            .bind(cipher.blind_index(&plaintext))
+            .bind(encrypted)
+            .bind(cipher.prefix_indexes(&plaintext))
+            .bind(id)
+            .execute(pool)
+            .await;
+
+        match result {
+            Ok(_) => stats.users += 1,
+            Err(e) => {
+                tracing::error!(user = %id, "Failed to re-encrypt user: {}", e);
+                stats.failed += 1;
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Convert existing rows to `PHONE_STORAGE=hashed`: drops the encrypted
+/// copies of users' and contacts' numbers (as `reencrypt_all`) and replaces
+/// plaintext numbers on deposits with their blind index
+pub async fn hash_phones(pool: &PgPool, cipher: &FieldCipher) -> Result<ReencryptStats, sqlx::Error> {
+    let mut stats = reencrypt_all(pool, cipher).await?;
+
+    let phones = sqlx::query_scalar::<_, String>(
+        "SELECT DISTINCT user_phone FROM deposits WHERE user_phone NOT LIKE 'bi1:%'"
+    )
+    .fetch_all(pool)
+    .await?;
+    for phone in phones {
+        let result = sqlx::query("UPDATE deposits SET user_phone = $1 WHERE user_phone = $2")
+            .bind(cipher.phone_ref(&phone))
+            .bind(&phone)
+            .execute(pool)
+            .await;
+        match result {
+            Ok(done) => stats.deposits += done.rows_affected(),
+            Err(e) => {
+                tracing::error!("Failed to hash deposit phone: {}", e);
+                stats.failed += 1;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+/// Fill in `users.phone_prefixes` for rows created before admin user search;
+/// returns the number of rows updated
+pub async fn backfill_phone_prefixes(pool: &PgPool, cipher: &FieldCipher) -> Result<u64, sqlx::Error> {
+    let users = sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT id, COALESCE(phone_encrypted, phone) FROM users WHERE phone_prefixes IS NULL"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut updated = 0;
+    for (id, stored) in users {
+        // A hashed number has no prefixes to derive
+        if KeyVault::is_blind_index(&stored) {
+            continue;
+        }
+        let phone = match cipher.decrypt(&stored) {
+            Ok(phone) => phone,
+            Err(e) => {
+                tracing::error!(user = %id, "Cannot decrypt phone: {}", e);
+                continue;
+            }
+        };
+        sqlx::query("UPDATE users SET phone_prefixes = $1 WHERE id = $2")
+            .bind(cipher.prefix_indexes(&phone))
+            .bind(id)
+            .execute(pool)
+            .await?;
+        updated += 1;
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_field_roundtrip_and_legacy_plaintext() {
+        let cipher = FieldCipher::new(KeyVault::new(MASTER).unwrap(), None);
+
+        let stored = cipher.encrypt("+15551234567").unwrap();
+        assert_ne!(stored, "+15551234567");
+        assert_eq!(cipher.decrypt(&stored).unwrap(), "+15551234567");
+        assert_eq!(cipher.decrypt("+15550000000").unwrap(), "+15550000000");
+
+        let prefixes = cipher.prefix_indexes("+15551234567");
+        assert_eq!(prefixes.len(), 11);
+        assert_eq!(prefixes[2], cipher.blind_index("+155"));
+        assert_eq!(prefixes[10], cipher.blind_index("+15551234567"));
+    }
+
+    #[test]
+    fn test_rotation_reads_previous_key() {
+        let old = KeyVault::new(&"ff".repeat(32)).unwrap();
+        let stored = FieldCipher::new(old.clone(), None).encrypt("+15551234567").unwrap();
+
+        let rotated = FieldCipher::new(KeyVault::new(MASTER).unwrap(), Some(old.clone()));
+        assert_eq!(rotated.decrypt(&stored).unwrap(), "+15551234567");
+
+        let keys = rotated.lookup_keys("+15551234567");
+        assert_eq!(keys.len(), 3);
+        assert!(keys.contains(&old.blind_index("+15551234567").unwrap()));
+        assert!(keys.contains(&"+15551234567".to_string()));
+    }
+
+    #[test]
+    fn test_hashed_phones_keep_only_the_index() {
+        let cipher = FieldCipher::new(KeyVault::new(MASTER).unwrap(), None).with_phone_storage(PhoneStorage::Hashed);
+        let index = cipher.blind_index("+15551234567");
+
+        assert_eq!(cipher.encrypt_phone("+15551234567").unwrap(), None);
+        assert_eq!(cipher.seal_phone("+15551234567").unwrap(), index);
+        assert_eq!(cipher.phone_ref("+15551234567"), index);
+        // A stored index reads back as itself and finds the same rows
+        assert_eq!(cipher.decrypt(&index).unwrap(), index);
+        assert!(cipher.lookup_keys(&index).contains(&index));
+
+        let encrypted = FieldCipher::new(KeyVault::new(MASTER).unwrap(), None);
+        assert_eq!(encrypted.phone_ref("+15551234567"), "+15551234567");
+        assert_eq!(PhoneStorage::parse("HASHED"), Some(PhoneStorage::Hashed));
+        assert_eq!(PhoneStorage::parse("salted"), None);
+    }
+
+    #[test]
+    fn test_plaintext_cipher_is_passthrough() {
+        let cipher = FieldCipher::new(KeyVault::unsealed(), None);
+        assert_eq!(cipher.encrypt("+1555").unwrap(), "+1555");
+        assert_eq!(cipher.blind_index("+1555"), "+1555");
+        assert_eq!(cipher.lookup_keys("+1555"), vec!["+1555".to_string()]);
+        assert_eq!(cipher.prefix_indexes("+1555"), ["+1", "+15", "+155", "+1555"]);
+    }
+}
@@ -0,0 +1,196 @@
+//! Results of re-resolving `users.ens_name` (see `ens_verifier`)
+//!
+//! One row per user holding the last verdict for the name they had when it
+//! was checked. A failed lookup keeps the previous verdict and only records
+//! the error, so a flaky RPC doesn't clear a flagged mismatch.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// What a stored name resolved to on its last check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnsCheckStatus {
+    /// Resolves to the user's wallet
+    Ok,
+    /// Resolves to some other address
+    Mismatch,
+    /// No address record
+    Missing,
+    /// Drifted and re-pointed at the user's wallet
+    Repaired,
+    /// Never resolved successfully
+    Unresolved,
+}
+
+impl EnsCheckStatus {
+    pub const ALL: [EnsCheckStatus; 5] = [
+        EnsCheckStatus::Ok,
+        EnsCheckStatus::Mismatch,
+        EnsCheckStatus::Missing,
+        EnsCheckStatus::Repaired,
+        EnsCheckStatus::Unresolved,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnsCheckStatus::Ok => "ok",
+            EnsCheckStatus::Mismatch => "mismatch",
+            EnsCheckStatus::Missing => "missing",
+            EnsCheckStatus::Repaired => "repaired",
+            EnsCheckStatus::Unresolved => "unresolved",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        EnsCheckStatus::ALL.into_iter().find(|status| status.as_str() == name)
+    }
+
+    /// The name doesn't point at the user's wallet
+    pub fn is_drift(&self) -> bool {
+        matches!(self, EnsCheckStatus::Mismatch | EnsCheckStatus::Missing)
+    }
+}
+
+/// A user's stored name, due for a check
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct StoredEnsName {
+    pub user_id: Uuid,
+    pub ens_name: String,
+    pub wallet_address: String,
+}
+
+/// Last check of one user's name
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct EnsCheck {
+    pub user_id: Uuid,
+    pub ens_name: String,
+    pub wallet_address: String,
+    pub status: String,
+    /// Address the name resolved to, if it had one
+    pub resolved_address: Option<String>,
+    /// Why the last lookup or repair failed
+    pub error: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct EnsCheckRepository {
+    pool: PgPool,
+}
+
+impl EnsCheckRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Up to `limit` stored names, never-checked and renamed ones first, then
+    /// the least recently checked
+    pub async fn due(&self, limit: i64) -> Result<Vec<StoredEnsName>, sqlx::Error> {
+        let _timer = QueryTimer::start("ens_checks.due");
+        sqlx::query_as::<_, StoredEnsName>(
+            "SELECT u.id AS user_id, u.ens_name, u.wallet_address
+             FROM users u
+             LEFT JOIN ens_checks c ON c.user_id = u.id AND c.ens_name = u.ens_name
+             WHERE u.ens_name IS NOT NULL AND u.ens_name <> ''
+             ORDER BY c.checked_at ASC NULLS FIRST, u.id
+             LIMIT $1"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record a verdict for `name`, clearing any earlier error
+    pub async fn record(
+        &self,
+        name: &StoredEnsName,
+        status: EnsCheckStatus,
+        resolved_address: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("ens_checks.record");
+        sqlx::query(
+            r#"
+            INSERT INTO ens_checks (user_id, ens_name, wallet_address, status, resolved_address, error, checked_at)
+            VALUES ($1, $2, $3, $4, $5, NULL, NOW())
+            ON CONFLICT (user_id) DO UPDATE SET
+                ens_name = EXCLUDED.ens_name,
+                wallet_address = EXCLUDED.wallet_address,
+                status = EXCLUDED.status,
+                resolved_address = EXCLUDED.resolved_address,
+                error = NULL,
+                checked_at = EXCLUDED.checked_at
+            "#
+        )
+        .bind(name.user_id)
+        .bind(&name.ens_name)
+        .bind(&name.wallet_address)
+        .bind(status.as_str())
+        .bind(resolved_address)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Record a failed lookup or repair. The verdict stands unless the name
+    /// changed since, in which case it becomes `unresolved`
+    pub async fn record_error(&self, name: &StoredEnsName, error: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("ens_checks.record_error");
+        sqlx::query(
+            r#"
+            INSERT INTO ens_checks (user_id, ens_name, wallet_address, status, error, checked_at)
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (user_id) DO UPDATE SET
+                status = CASE WHEN ens_checks.ens_name = EXCLUDED.ens_name THEN ens_checks.status ELSE EXCLUDED.status END,
+                resolved_address = CASE WHEN ens_checks.ens_name = EXCLUDED.ens_name THEN ens_checks.resolved_address END,
+                ens_name = EXCLUDED.ens_name,
+                wallet_address = EXCLUDED.wallet_address,
+                error = EXCLUDED.error,
+                checked_at = EXCLUDED.checked_at
+            "#
+        )
+        .bind(name.user_id)
+        .bind(&name.ens_name)
+        .bind(&name.wallet_address)
+        .bind(EnsCheckStatus::Unresolved.as_str())
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Users per verdict, for names still held by the user they were checked for
+    pub async fn status_counts(&self) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        let _timer = QueryTimer::start("ens_checks.status_counts");
+        sqlx::query_as::<_, (String, i64)>(
+            "SELECT c.status, COUNT(*)
+             FROM ens_checks c
+             JOIN users u ON u.id = c.user_id AND u.ens_name = c.ens_name
+             GROUP BY c.status"
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Current checks with `status`, most recently checked first
+    pub async fn list(&self, status: EnsCheckStatus, limit: i64) -> Result<Vec<EnsCheck>, sqlx::Error> {
+        let _timer = QueryTimer::start("ens_checks.list");
+        sqlx::query_as::<_, EnsCheck>(
+            "SELECT c.user_id, c.ens_name, c.wallet_address, c.status, c.resolved_address, c.error, c.checked_at
+             FROM ens_checks c
+             JOIN users u ON u.id = c.user_id AND u.ens_name = c.ens_name
+             WHERE c.status = $1
+             ORDER BY c.checked_at DESC
+             LIMIT $2"
+        )
+        .bind(status.as_str())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
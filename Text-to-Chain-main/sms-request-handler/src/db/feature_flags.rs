@@ -0,0 +1,49 @@
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// Per-deployment feature flag overrides (see `features::FeatureFlags`)
+#[derive(Clone)]
+pub struct FeatureFlagRepository {
+    pool: PgPool,
+}
+
+impl FeatureFlagRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// All overrides as (feature, enabled)
+    pub async fn list(&self) -> Result<Vec<(String, bool)>, sqlx::Error> {
+        let _timer = QueryTimer::start("feature_flags.list");
+        sqlx::query_as::<_, (String, bool)>("SELECT feature, enabled FROM feature_flags")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn set(&self, feature: &str, enabled: bool) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("feature_flags.set");
+        sqlx::query(
+            r#"
+            INSERT INTO feature_flags (feature, enabled, updated_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (feature) DO UPDATE SET enabled = EXCLUDED.enabled, updated_at = NOW()
+            "#
+        )
+        .bind(feature)
+        .bind(enabled)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Remove an override so the config default applies again
+    pub async fn clear(&self, feature: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("feature_flags.clear");
+        sqlx::query("DELETE FROM feature_flags WHERE feature = $1")
+            .bind(feature)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
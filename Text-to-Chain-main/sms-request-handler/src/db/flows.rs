@@ -0,0 +1,73 @@
+//! Conversation state for multi-step SMS flows (see `commands::flow`)
+//!
+//! A number has at most one flow in progress. `data` holds the answers given
+//! so far as a JSON object of strings, so a flow resumes where it left off
+//! across messages and restarts.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// A flow in progress
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct FlowSession {
+    /// Flow name, e.g. `onboarding` or `send`
+    pub flow: String,
+    /// Step waiting for an answer
+    pub step: String,
+    pub data: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct FlowRepository {
+    pool: PgPool,
+}
+
+impl FlowRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// The flow in progress for a number, expired or not
+    pub async fn find(&self, phone: &str) -> Result<Option<FlowSession>, sqlx::Error> {
+        let _timer = QueryTimer::start("flows.find");
+        sqlx::query_as!(
+            FlowSession,
+            "SELECT flow, step, data, updated_at FROM flow_sessions WHERE phone = $1",
+            phone
+        )
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Store the current step and answers, replacing any other flow
+    pub async fn save(&self, phone: &str, flow: &str, step: &str, data: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("flows.save");
+        sqlx::query!(
+            r#"
+            INSERT INTO flow_sessions (phone, flow, step, data, updated_at)
+            VALUES ($1, $2, $3, $4, NOW())
+            ON CONFLICT (phone) DO UPDATE
+            SET flow = EXCLUDED.flow, step = EXCLUDED.step, data = EXCLUDED.data, updated_at = NOW()
+            "#,
+            phone,
+            flow,
+            step,
+            data
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// End the number's flow, if it is still `flow`
+    pub async fn finish(&self, phone: &str, flow: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("flows.finish");
+        sqlx::query!("DELETE FROM flow_sessions WHERE phone = $1 AND flow = $2", phone, flow)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,123 @@
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// A request still marked in progress after this long is assumed abandoned
+/// (e.g. the server restarted mid-request) and may be retried
+const STALE_IN_PROGRESS_SECS: i32 = 300;
+
+/// A response stored for an idempotency key
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct StoredResponse {
+    pub status: i16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// What to do with a request carrying an idempotency key
+#[derive(Debug)]
+pub enum IdempotencyClaim {
+    /// First time this key is seen: run the request
+    Started,
+    /// Already completed: send the stored response again
+    Replay(StoredResponse),
+    /// Another request with this key is still running
+    InProgress,
+    /// The key was used for a different request
+    Mismatch,
+}
+
+/// Admin request/response cache keyed by `Idempotency-Key`, so a retried
+/// mutation returns the first response instead of running twice
+#[derive(Clone)]
+pub struct IdempotencyRepository {
+    pool: PgPool,
+}
+
+impl IdempotencyRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Claim `key` for a request identified by `fingerprint` (method, path and body hash)
+    pub async fn claim(&self, key: &str, fingerprint: &str) -> Result<IdempotencyClaim, sqlx::Error> {
+        let _timer = QueryTimer::start("idempotency.claim");
+        // Take over a claim left behind by a request that never finished
+        sqlx::query(
+            "DELETE FROM admin_idempotency
+             WHERE key = $1 AND status IS NULL AND created_at < NOW() - make_interval(secs => $2)",
+        )
+        .bind(key)
+        .bind(STALE_IN_PROGRESS_SECS as f64)
+        .execute(&self.pool)
+        .await?;
+
+        let inserted = sqlx::query(
+            "INSERT INTO admin_idempotency (key, fingerprint, created_at) VALUES ($1, $2, NOW())
+             ON CONFLICT (key) DO NOTHING",
+        )
+        .bind(key)
+        .bind(fingerprint)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        if inserted > 0 {
+            return Ok(IdempotencyClaim::Started);
+        }
+
+        let row = sqlx::query_as::<_, (String, Option<i16>, Option<String>, Option<Vec<u8>>)>(
+            "SELECT fingerprint, status, content_type, body FROM admin_idempotency WHERE key = $1",
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(match row {
+            // Released between our insert and select; let the caller retry
+            None => IdempotencyClaim::InProgress,
+            Some((stored, _, _, _)) if stored != fingerprint => IdempotencyClaim::Mismatch,
+            Some((_, Some(status), content_type, body)) => IdempotencyClaim::Replay(StoredResponse {
+                status,
+                content_type,
+                body: body.unwrap_or_default(),
+            }),
+            Some((_, None, _, _)) => IdempotencyClaim::InProgress,
+        })
+    }
+
+    /// Store the response for a claimed key
+    pub async fn complete(&self, key: &str, response: &StoredResponse) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("idempotency.complete");
+        sqlx::query(
+            "UPDATE admin_idempotency SET status = $2, content_type = $3, body = $4, completed_at = NOW()
+             WHERE key = $1",
+        )
+        .bind(key)
+        .bind(response.status)
+        .bind(&response.content_type)
+        .bind(&response.body)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Drop a claim so the request can be retried (e.g. after a server error)
+    pub async fn release(&self, key: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("idempotency.release");
+        sqlx::query("DELETE FROM admin_idempotency WHERE key = $1 AND status IS NULL")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete keys older than `hours`; returns rows removed
+    pub async fn purge_older_than(&self, hours: u32) -> Result<u64, sqlx::Error> {
+        let _timer = QueryTimer::start("idempotency.purge_older_than");
+        let result = sqlx::query("DELETE FROM admin_idempotency WHERE created_at < NOW() - make_interval(hours => $1)")
+            .bind(hours as i32)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
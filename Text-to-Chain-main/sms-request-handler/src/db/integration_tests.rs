@@ -0,0 +1,1635 @@
+//! Repository tests against a real Postgres
+//!
+//! Run with `cargo test --features db-tests`. Each test gets a fresh,
+//! migrated database: on the server at `TEST_DATABASE_URL` when set,
+//! otherwise in a throwaway `postgres:16-alpine` container started with the
+//! docker CLI. The database (or container) is removed when the test ends.
+
+use std::process::Command;
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{Executor, PgPool};
+use uuid::Uuid;
+
+use super::*;
+use crate::beta::{Admission, BetaAccess};
+use crate::commands::CommandProcessor;
+use crate::config::BetaConfig;
+use crate::money::{Currency, Money};
+use crate::sms::SmsDisplay;
+use crate::wallet::KeyVault;
+
+const POSTGRES_IMAGE: &str = "postgres:16-alpine";
+const MASTER_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+/// Where the test database lives
+enum Server {
+    External(Box<PgConnectOptions>),
+    Container { id: String },
+}
+
+/// A fresh database with every migration applied
+struct TestDb {
+    pool: PgPool,
+    name: String,
+    server: Server,
+}
+
+impl TestDb {
+    async fn new() -> Self {
+        let (admin, server) = match std::env::var("TEST_DATABASE_URL") {
+            Ok(url) => {
+                let options = PgConnectOptions::from_str(&url).expect("valid TEST_DATABASE_URL");
+                (options.clone(), Server::External(Box::new(options)))
+            }
+            Err(_) => start_container().await,
+        };
+
+        let name = format!("textchain_test_{}", Uuid::new_v4().simple());
+        let admin_pool = connect(admin.clone()).await;
+        admin_pool
+            .execute(format!("CREATE DATABASE {}", name).as_str())
+            .await
+            .expect("create test database");
+        admin_pool.close().await;
+
+        let pool = connect(admin.database(&name)).await;
+        run_migrations(&pool).await.expect("migrations");
+        Self { pool, name, server }
+    }
+
+    fn cipher(&self) -> FieldCipher {
+        FieldCipher::new(KeyVault::new(MASTER_KEY).unwrap(), None)
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        match &self.server {
+            Server::External(admin) => {
+                // Drop runs outside any async context, so use a runtime of our own
+                let (admin, name) = (admin.as_ref().clone(), self.name.clone());
+                let dropped = std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+                    runtime.block_on(async {
+                        let pool = PgPoolOptions::new().max_connections(1).connect_with(admin).await?;
+                        pool.execute(format!("DROP DATABASE IF EXISTS {} WITH (FORCE)", name).as_str()).await
+                    })
+                })
+                .join();
+                if !matches!(dropped, Ok(Ok(_))) {
+                    eprintln!("failed to drop test database {}", self.name);
+                }
+            }
+            Server::Container { id } => {
+                let _ = Command::new("docker").args(["rm", "-f", id]).output();
+            }
+        }
+    }
+}
+
+/// Start a Postgres container on a random local port and wait for it
+async fn start_container() -> (PgConnectOptions, Server) {
+    let run = Command::new("docker")
+        .args(["run", "-d", "--rm", "-e", "POSTGRES_PASSWORD=postgres", "-p", "127.0.0.1::5432", POSTGRES_IMAGE])
+        .output()
+        .expect("docker CLI (or set TEST_DATABASE_URL)");
+    assert!(run.status.success(), "docker run failed: {}", String::from_utf8_lossy(&run.stderr));
+    let id = String::from_utf8_lossy(&run.stdout).trim().to_string();
+
+    let port = Command::new("docker").args(["port", &id, "5432/tcp"]).output().expect("docker port");
+    let port: u16 = String::from_utf8_lossy(&port.stdout)
+        .lines()
+        .next()
+        .and_then(|line| line.rsplit(':').next())
+        .and_then(|port| port.trim().parse().ok())
+        .expect("mapped Postgres port");
+
+    let options = PgConnectOptions::new()
+        .host("127.0.0.1")
+        .port(port)
+        .username("postgres")
+        .password("postgres")
+        .database("postgres");
+    (options, Server::Container { id })
+}
+
+/// Connect, retrying while a new server is still starting up
+async fn connect(options: PgConnectOptions) -> PgPool {
+    let mut attempts = 0;
+    loop {
+        match PgPoolOptions::new().max_connections(10).connect_with(options.clone()).await {
+            Ok(pool) => return pool,
+            Err(e) if attempts < 60 => {
+                attempts += 1;
+                tracing::debug!("Test database not ready: {}", e);
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+            Err(e) => panic!("test database unreachable: {}", e),
+        }
+    }
+}
+
+// Fixtures
+
+const ALICE: &str = "+254700000001";
+const BOB: &str = "+254700000002";
+const ALICE_WALLET: &str = "0x1111111111111111111111111111111111111111";
+
+async fn seed_user(db: &TestDb, phone: &str, wallet: &str) -> User {
+    UserRepository::new(db.pool.clone(), db.cipher())
+        .create(phone, wallet, "sealed-key")
+        .await
+        .expect("seed user")
+}
+
+async fn seed_voucher(db: &TestDb, usdc_amount: Money, expires_at: Option<chrono::DateTime<Utc>>) -> Voucher {
+    let (_, mut vouchers) = VoucherRepository::new(db.pool.clone())
+        .create_generated(1, "TST", usdc_amount, expires_at, None)
+        .await
+        .expect("seed voucher");
+    vouchers.remove(0)
+}
+
+// UserRepository
+
+#[tokio::test]
+async fn test_user_create_and_lookups() {
+    let db = TestDb::new().await;
+    let users = UserRepository::new(db.pool.clone(), db.cipher());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    assert_eq!(alice.phone, ALICE);
+
+    // The stored phone is the blind index, never the number itself
+    let stored: String = sqlx::query_scalar("SELECT phone FROM users WHERE id = $1")
+        .bind(alice.id)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_ne!(stored, ALICE);
+
+    assert_eq!(users.find_by_phone(ALICE).await.unwrap().unwrap().id, alice.id);
+    assert_eq!(users.find_by_id(alice.id).await.unwrap().unwrap().phone, ALICE);
+    assert_eq!(users.find_by_wallet(ALICE_WALLET).await.unwrap().unwrap().id, alice.id);
+    assert!(users.find_by_phone(BOB).await.unwrap().is_none());
+    assert!(users.exists(ALICE).await.unwrap());
+    assert!(!users.exists(BOB).await.unwrap());
+
+    users.update_pin(ALICE, "pin-hash").await.unwrap();
+    users.update_ens_name(ALICE, "alice.ttcip.eth").await.unwrap();
+    let updated = users.find_by_phone(ALICE).await.unwrap().unwrap();
+    assert_eq!(updated.pin_hash.as_deref(), Some("pin-hash"));
+    assert_eq!(updated.ens_name.as_deref(), Some("alice.ttcip.eth"));
+}
+
+#[tokio::test]
+async fn test_user_phone_is_unique() {
+    let db = TestDb::new().await;
+    seed_user(&db, ALICE, ALICE_WALLET).await;
+
+    let duplicate = UserRepository::new(db.pool.clone(), db.cipher())
+        .create(ALICE, "0x2222222222222222222222222222222222222222", "sealed-key")
+        .await;
+    assert!(matches!(duplicate, Err(sqlx::Error::Database(e)) if e.is_unique_violation()));
+}
+
+// VoucherRepository
+
+#[tokio::test]
+async fn test_voucher_redeem_once() {
+    let db = TestDb::new().await;
+    let vouchers = VoucherRepository::new(db.pool.clone());
+    let voucher = seed_voucher(&db, Money::usdc(5_000_000), None).await;
+
+    let redeemed = vouchers.redeem(&voucher.code.to_lowercase(), ALICE).await.unwrap();
+    assert_eq!(redeemed.status, "redeemed");
+    assert_eq!(redeemed.redeemed_by.as_deref(), Some(ALICE));
+    assert!(redeemed.redeemed_at.is_some());
+
+    assert!(matches!(vouchers.redeem(&voucher.code, BOB).await, Err(VoucherError::AlreadyRedeemed)));
+    assert!(matches!(vouchers.redeem("TST-NOPE", ALICE).await, Err(VoucherError::NotFound)));
+
+    let expired = seed_voucher(&db, Money::usdc(1_000_000), Some(Utc::now() - chrono::Duration::hours(1))).await;
+    assert!(matches!(vouchers.redeem(&expired.code, ALICE).await, Err(VoucherError::Expired)));
+}
+
+#[tokio::test]
+async fn test_voucher_stats() {
+    let db = TestDb::new().await;
+    let vouchers = VoucherRepository::new(db.pool.clone());
+    let redeemed = seed_voucher(&db, Money::usdc(5_000_000), None).await;
+    seed_voucher(&db, Money::usdc(2_500_000), None).await;
+    vouchers.redeem(&redeemed.code, ALICE).await.unwrap();
+
+    let stats = vouchers.stats().await.unwrap();
+    assert_eq!((stats.total, stats.unused, stats.redeemed), (2, 1, 1));
+    assert_eq!(stats.value_unused, Money::usdc(2_500_000));
+    assert_eq!(stats.value_redeemed, Money::usdc(5_000_000));
+    assert_eq!(stats.value_total, Money::usdc(7_500_000));
+}
+
+#[tokio::test]
+async fn test_voucher_concurrent_redemption() {
+    let db = TestDb::new().await;
+    let voucher = seed_voucher(&db, Money::usdc(5_000_000), None).await;
+
+    let attempts: Vec<_> = (0..8)
+        .map(|i| {
+            let vouchers = VoucherRepository::new(db.pool.clone());
+            let code = voucher.code.clone();
+            tokio::spawn(async move { vouchers.redeem(&code, &format!("+2547000001{:02}", i)).await })
+        })
+        .collect();
+
+    let mut winners = Vec::new();
+    for attempt in attempts {
+        match attempt.await.unwrap() {
+            Ok(voucher) => winners.push(voucher),
+            Err(VoucherError::AlreadyRedeemed) => {}
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+    assert_eq!(winners.len(), 1, "exactly one redemption wins");
+
+    let stored = VoucherRepository::new(db.pool.clone()).find_by_code(&voucher.code).await.unwrap().unwrap();
+    assert_eq!(stored.redeemed_by, winners[0].redeemed_by);
+}
+
+#[tokio::test]
+async fn test_seeded_vouchers_issue_on_first_use() {
+    let db = TestDb::new().await;
+    let vouchers = VoucherRepository::new(db.pool.clone());
+    let batch = vouchers.create_seeded(1_000, "big", Money::usdc(2_000_000), None, None).await.unwrap();
+    assert_eq!(batch.prefix, "BIG");
+    assert!(vouchers.list_batch(batch.id).await.unwrap().is_empty());
+
+    let code = batch.code(42);
+    let redeemed = vouchers.redeem(&code.to_lowercase(), ALICE).await.unwrap();
+    assert_eq!((redeemed.status.as_str(), redeemed.batch_id), ("redeemed", Some(batch.id)));
+    assert_eq!(redeemed.usdc_amount, Money::usdc(2_000_000));
+    assert!(matches!(vouchers.redeem(&code, BOB).await, Err(VoucherError::AlreadyRedeemed)));
+
+    // Right prefix and check character, wrong seed
+    let forged = SeededBatch { seed: "00".repeat(32), ..batch.clone() }.code(7);
+    assert!(matches!(vouchers.redeem(&forged, ALICE).await, Err(VoucherError::NotFound)));
+    assert!(vouchers.find_by_code(&forged).await.unwrap().is_none());
+
+    let stats = vouchers.stats().await.unwrap();
+    assert_eq!((stats.total, stats.unused, stats.redeemed), (1_000, 999, 1));
+    assert_eq!(stats.value_unused, Money::usdc(1_998_000_000));
+
+    let duplicate = vouchers.create_seeded(10, "BIG", Money::usdc(1_000_000), None, None).await;
+    assert!(matches!(duplicate, Err(sqlx::Error::Database(e)) if e.is_unique_violation()));
+}
+
+// DepositRepository
+
+#[tokio::test]
+async fn test_deposit_balance_math() {
+    let db = TestDb::new().await;
+    let deposits = DepositRepository::new(db.pool.clone(), db.cipher());
+
+    // Voucher deposits count straight away
+    deposits.create_from_voucher(ALICE, Money::usdc(5_000_000), "TST1").await.unwrap();
+    deposits.create_from_voucher(ALICE, Money::usdc(2_500_000), "TST2").await.unwrap();
+    deposits.create_from_voucher(BOB, Money::usdc(1_000_000), "TST3").await.unwrap();
+    assert_eq!(deposits.get_balance(ALICE).await.unwrap(), Money::usdc(7_500_000));
+
+    // On-chain deposits only once confirmed
+    let pending = deposits
+        .record_pending(ALICE, Money::usdc(1_250_000), "0xABC", "sepolia", 100)
+        .await
+        .unwrap()
+        .expect("new deposit");
+    assert!(pending.is_pending());
+    assert_eq!(deposits.get_balance(ALICE).await.unwrap(), Money::usdc(7_500_000));
+    // The same transfer seen again is not a second deposit
+    assert!(deposits.record_pending(ALICE, Money::usdc(1_250_000), "0xabc", "sepolia", 100).await.unwrap().is_none());
+
+    assert!(deposits.mark_confirmed(pending.id).await.unwrap());
+    assert!(!deposits.mark_confirmed(pending.id).await.unwrap());
+    assert_eq!(deposits.get_balance(ALICE).await.unwrap(), Money::usdc(8_750_000));
+    assert_eq!(deposits.get_balance_formatted(ALICE).await.unwrap(), "8.75");
+
+    // A reorg takes it back out; re-mined, it is pending again
+    assert!(deposits.mark_reversed(pending.id).await.unwrap());
+    assert!(!deposits.mark_reversed(pending.id).await.unwrap());
+    assert_eq!(deposits.get_balance(ALICE).await.unwrap(), Money::usdc(7_500_000));
+    assert_eq!(deposits.get_recent(ALICE, 10).await.unwrap().len(), 2);
+    let remined = deposits.record_pending(ALICE, Money::usdc(1_250_000), "0xabc", "sepolia", 101).await.unwrap().unwrap();
+    assert_eq!(remined.id, pending.id);
+    assert!(remined.is_pending());
+
+    assert_eq!(deposits.get_balance(BOB).await.unwrap(), Money::usdc(1_000_000));
+    // Other tokens are not counted as USDC
+    let eth = Money::from_micros(500_000, Currency::parse("ETH").unwrap());
+    let eth_deposit = deposits.record_pending(BOB, eth, "0xdef", "sepolia", 100).await.unwrap().unwrap();
+    assert_eq!(eth_deposit.amount, eth);
+    deposits.mark_confirmed(eth_deposit.id).await.unwrap();
+    assert_eq!(deposits.get_balance(BOB).await.unwrap(), Money::usdc(1_000_000));
+    assert_eq!(deposits.get_balance("+254700000099").await.unwrap(), Money::usdc(0));
+}
+
+// DepositAddressRepository
+
+#[tokio::test]
+async fn test_deposit_address_sweeps() {
+    let db = TestDb::new().await;
+    let addresses = DepositAddressRepository::new(db.pool.clone());
+    let deposits = DepositRepository::new(db.pool.clone(), db.cipher());
+    let address = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8";
+
+    let index = addresses.next_index().await.unwrap();
+    let assigned = addresses.assign(ALICE, index, address).await.unwrap();
+    assert_eq!(assigned.address, address.to_lowercase());
+    // A second assignment keeps the first address
+    let again = addresses.assign(ALICE, index + 1, "0x3C44CdDdB6a900fa2b585dd299e03d12FA4293BC").await.unwrap();
+    assert_eq!(again.derivation_index, index);
+    assert_eq!(addresses.find_by_address(address).await.unwrap().unwrap().user_phone, ALICE);
+
+    // Only confirmed deposits to the address are swept, once
+    let first = deposits.record_pending(ALICE, Money::usdc(2_000_000), "0x01", "sepolia", 100).await.unwrap().unwrap();
+    let second = deposits.record_pending(ALICE, Money::usdc(3_000_000), "0x02", "sepolia", 101).await.unwrap().unwrap();
+    deposits.set_deposit_address(first.id, address).await.unwrap();
+    deposits.set_deposit_address(second.id, address).await.unwrap();
+    deposits.mark_confirmed(first.id).await.unwrap();
+    assert_eq!(addresses.sweep_candidates(10).await.unwrap()[0].credited, Money::usdc(2_000_000));
+    deposits.mark_confirmed(second.id).await.unwrap();
+    let candidates = addresses.sweep_candidates(10).await.unwrap();
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].credited, Money::usdc(5_000_000));
+
+    let sweep = addresses
+        .record_sweep("POL-T", "USDC", &assigned.address, "5.0", "0xsweep", Some("0xgas"))
+        .await
+        .unwrap();
+    assert_eq!(sweep.gas_tx_hash.as_deref(), Some("0xgas"));
+    // Recorded against a different chain code: nothing marked
+    assert_eq!(addresses.sweep_candidates(10).await.unwrap().len(), 1);
+    addresses.record_sweep("sepolia", "USDC", &assigned.address, "5.0", "0xsweep2", None).await.unwrap();
+    assert!(addresses.sweep_candidates(10).await.unwrap().is_empty());
+    assert_eq!(addresses.list_sweeps(10).await.unwrap().len(), 2);
+}
+
+// AddressBookRepository
+
+#[tokio::test]
+async fn test_address_book_conflicts() {
+    let db = TestDb::new().await;
+    let book = AddressBookRepository::new(db.pool.clone(), db.cipher());
+
+    let mum = book.add_contact(ALICE, "mum", Some(BOB), None).await.unwrap();
+    // Same number again: the existing contact is renamed, not duplicated
+    let renamed = book.add_contact(ALICE, "mother", Some(BOB), None).await.unwrap();
+    assert_eq!(renamed.id, mum.id);
+    assert_eq!(renamed.name, "mother");
+    assert_eq!(book.list_all(ALICE).await.unwrap().len(), 1);
+
+    // Same name, different target: a second contact
+    book.add_contact(ALICE, "mother", None, Some(ALICE_WALLET)).await.unwrap();
+    assert_eq!(book.find_by_name(ALICE, "MOTH").await.unwrap().len(), 2);
+
+    // Other users' books are separate
+    book.add_contact(BOB, "mum", Some(BOB), None).await.unwrap();
+    assert_eq!(book.list_all(ALICE).await.unwrap().len(), 2);
+    assert_eq!(book.list_all(BOB).await.unwrap().len(), 1);
+
+    let found = book.find_by_phone(ALICE, BOB).await.unwrap().unwrap();
+    assert_eq!(found.contact_phone.as_deref(), Some(BOB));
+    assert!(book.resolve_recipient(ALICE, "mother").await.is_some());
+
+    assert!(book.delete(ALICE, "MOTHER").await.unwrap());
+    assert!(book.list_all(ALICE).await.unwrap().is_empty());
+    assert!(!book.delete(ALICE, "mother").await.unwrap());
+}
+
+#[tokio::test]
+async fn test_saved_address_unlock() {
+    let db = TestDb::new().await;
+    let book = AddressBookRepository::new(db.pool.clone(), db.cipher());
+    let shop = "0x742d35cc6634c0532925a3b844bc454e4438f44e";
+
+    assert!(book.find_saved_address(ALICE, shop).await.unwrap().is_none());
+    book.add_contact(ALICE, "shop", None, Some(shop)).await.unwrap();
+    // Found however the address is written, and only in the owner's book
+    let saved = book.find_saved_address(ALICE, "0x742d35Cc6634C0532925a3b844Bc454e4438f44e").await.unwrap().unwrap();
+    assert_eq!((saved.name.as_str(), saved.unlock_at), ("shop", None));
+    assert!(book.find_saved_address(BOB, shop).await.unwrap().is_none());
+
+    let soon = Utc::now() + chrono::Duration::minutes(60);
+    let unlocked = book.unlock_address(ALICE, "SHOP", soon).await.unwrap();
+    assert_eq!(unlocked.len(), 1);
+    assert!(unlocked[0].unlock_at.is_some());
+    // A later UNLOCK never pushes the time back
+    let later = book.unlock_address(ALICE, "shop", soon + chrono::Duration::hours(5)).await.unwrap();
+    assert_eq!(later[0].unlock_at, unlocked[0].unlock_at);
+
+    // Phone contacts have nothing to unlock
+    book.add_contact(ALICE, "mum", Some(BOB), None).await.unwrap();
+    assert!(book.unlock_address(ALICE, "mum", soon).await.unwrap().is_empty());
+}
+
+// Hashed phone storage
+
+#[tokio::test]
+async fn test_hash_phones_converts_and_lookups_still_match() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    AddressBookRepository::new(db.pool.clone(), db.cipher())
+        .add_contact(ALICE, "mum", Some(BOB), None)
+        .await
+        .unwrap();
+    DepositRepository::new(db.pool.clone(), db.cipher())
+        .create_from_voucher(ALICE, Money::usdc(5_000_000), "TST1")
+        .await
+        .unwrap();
+
+    let hashed = db.cipher().with_phone_storage(PhoneStorage::Hashed);
+    let stats = hash_phones(&db.pool, &hashed).await.unwrap();
+    assert_eq!((stats.users, stats.contacts, stats.deposits, stats.failed), (2, 1, 1, 0));
+
+    // No column can be turned back into a number
+    let encrypted: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE phone_encrypted IS NOT NULL")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(encrypted, 0);
+    let contact_phone: String = sqlx::query_scalar("SELECT contact_phone FROM address_book").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(contact_phone, hashed.blind_index(BOB));
+    let deposit_phone: String = sqlx::query_scalar("SELECT user_phone FROM deposits").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(deposit_phone, hashed.blind_index(ALICE));
+
+    // Lookups by number still match, and the stored index finds the user too
+    let users = UserRepository::new(db.pool.clone(), hashed.clone());
+    let found = users.find_by_phone(ALICE).await.unwrap().unwrap();
+    assert_eq!((found.id, found.phone.as_str()), (alice.id, ALICE));
+    assert_eq!(users.find_by_id(alice.id).await.unwrap().unwrap().phone, hashed.blind_index(ALICE));
+    let book = AddressBookRepository::new(db.pool.clone(), hashed.clone());
+    let mum = book.find_by_phone(ALICE, BOB).await.unwrap().unwrap();
+    assert!(users.find_by_phone(mum.contact_phone.as_deref().unwrap()).await.unwrap().is_some());
+    assert_eq!(mum.to_sms_string(), "mum");
+    let deposits = DepositRepository::new(db.pool.clone(), hashed.clone());
+    assert_eq!(deposits.get_balance(ALICE).await.unwrap(), Money::usdc(5_000_000));
+
+    // New rows are hashed from the start, and a second run has nothing to do
+    let carol = users.create("+254700000003", "0x3333333333333333333333333333333333333333", "sealed-key").await.unwrap();
+    assert_eq!(carol.phone, "+254700000003");
+    let again = hash_phones(&db.pool, &hashed).await.unwrap();
+    assert_eq!((again.users, again.contacts, again.deposits, again.failed), (0, 1, 0, 0));
+}
+
+// PartnerKeyRepository
+
+#[tokio::test]
+async fn test_partner_deposit_idempotency_and_usage() {
+    let db = TestDb::new().await;
+    let keys = PartnerKeyRepository::new(db.pool.clone(), db.cipher());
+    let ledger = LedgerRepository::new(db.pool.clone());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+
+    let (key, plaintext) = keys.create("M-Pesa", &[PartnerScope::DepositCreate], 60, 100).await.unwrap();
+    assert!(key.has_scope(PartnerScope::DepositCreate));
+    assert!(!key.has_scope(PartnerScope::VoucherCreate));
+    assert_eq!(keys.authenticate(&plaintext).await.unwrap().unwrap().id, key.id);
+    assert!(keys.authenticate("ttcpk_wrong").await.unwrap().is_none());
+
+    // A retried reference is credited once
+    let (deposit, created) = keys.credit_deposit(key.id, alice.id, 2_000_000, "MP-1").await.unwrap();
+    assert!(created);
+    let (again, created) = keys.credit_deposit(key.id, alice.id, 2_000_000, "MP-1").await.unwrap();
+    assert!(!created);
+    assert_eq!(again.id, deposit.id);
+    assert!(matches!(
+        keys.credit_deposit(key.id, alice.id, 3_000_000, "MP-1").await,
+        Err(PartnerDepositError::ReferenceConflict)
+    ));
+    assert_eq!(ledger.balance(&user_account(alice.id)).await.unwrap(), 2_000_000);
+    assert_eq!(ledger.balance(&partner_account(key.id)).await.unwrap(), -2_000_000);
+
+    keys.record_usage(key.id, "deposits", true, 2_000_000).await.unwrap();
+    keys.record_usage(key.id, "deposits", false, 0).await.unwrap();
+    assert_eq!(keys.requests_today(key.id).await.unwrap(), 1);
+    let usage = keys.usage(key.id, 1).await.unwrap();
+    assert_eq!((usage[0].requests, usage[0].rejected, usage[0].amount), (1, 1, 2_000_000));
+
+    // Webhook secrets are read back for signing; nonces are accepted once
+    assert!(keys.webhook_secret(key.id).await.unwrap().is_none());
+    let updated = keys.set_webhook(key.id, Some("https://partner.example/hooks"), true, "whsec_test").await.unwrap().unwrap();
+    assert_eq!((updated.webhook_url.as_deref(), updated.signed_requests), (Some("https://partner.example/hooks"), true));
+    assert_eq!(keys.webhook_secret(key.id).await.unwrap().as_deref(), Some("whsec_test"));
+    assert!(keys.claim_nonce(key.id, "n1", 600).await.unwrap());
+    assert!(!keys.claim_nonce(key.id, "n1", 600).await.unwrap());
+    assert!(keys.claim_nonce(key.id, "n2", 600).await.unwrap());
+
+    keys.revoke(key.id).await.unwrap();
+    assert!(keys.authenticate(&plaintext).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_held_transfer_approval() {
+    let db = TestDb::new().await;
+    let approvals = TransferApprovalRepository::new(db.pool.clone());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let now = Utc::now();
+
+    approvals.set_guardian(alice.id, Some(BOB), now).await.unwrap();
+    assert_eq!(approvals.guardian(alice.id).await.unwrap().as_deref(), Some(BOB));
+    // A pending removal doesn't apply until its time
+    approvals.set_guardian(alice.id, None, now + chrono::Duration::minutes(30)).await.unwrap();
+    assert_eq!(approvals.guardian(alice.id).await.unwrap().as_deref(), Some(BOB));
+
+    let held = |approver_phone, not_before| NewPendingTransfer {
+        sender_id: alice.id,
+        sender_phone: ALICE,
+        token: "USDC",
+        amount: 500.0,
+        recipient: "bob.ttcip.eth",
+        recipient_address: "0x2222222222222222222222222222222222222222",
+        approver_phone,
+        not_before,
+        expires_at: now + chrono::Duration::hours(1),
+    };
+
+    // Guardian: sender first, then the guardian's code, which is only valid after
+    let transfer = approvals.hold(&held(Some(BOB), now)).await.unwrap();
+    let approver_code = transfer.approver_code.clone().unwrap();
+    assert!(matches!(approvals.confirm(BOB, &approver_code).await, Err(ApprovalError::NotFound)));
+    assert!(matches!(
+        approvals.confirm(ALICE, &transfer.sender_code).await,
+        Ok(ApprovalOutcome::AwaitingApprover { first: true, .. })
+    ));
+    assert!(matches!(
+        approvals.confirm(ALICE, &transfer.sender_code).await,
+        Ok(ApprovalOutcome::AwaitingApprover { first: false, .. })
+    ));
+    assert!(matches!(approvals.confirm(BOB, &approver_code).await, Ok(ApprovalOutcome::Approved(_))));
+    // Claimed once only
+    assert!(matches!(approvals.confirm(BOB, &approver_code).await, Err(ApprovalError::NotFound)));
+    approvals.finish(transfer.id, true).await.unwrap();
+
+    // No guardian: refused until the cooling period is over
+    let cooling = approvals.hold(&held(None, now + chrono::Duration::minutes(30))).await.unwrap();
+    assert!(matches!(approvals.confirm(ALICE, &cooling.sender_code).await, Ok(ApprovalOutcome::TooEarly(_))));
+    sqlx::query("UPDATE pending_transfers SET not_before = NOW() - INTERVAL '1 minute' WHERE id = $1")
+        .bind(cooling.id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    assert!(matches!(approvals.confirm(ALICE, &cooling.sender_code).await, Ok(ApprovalOutcome::Approved(_))));
+
+    let expired = approvals.hold(&NewPendingTransfer { expires_at: now, ..held(None, now) }).await.unwrap();
+    assert!(matches!(approvals.confirm(ALICE, &expired.sender_code).await, Err(ApprovalError::Expired)));
+}
+
+#[tokio::test]
+async fn test_phone_carrier_upsert() {
+    let db = TestDb::new().await;
+    let carriers = PhoneCarrierRepository::new(db.pool.clone());
+
+    carriers.upsert(ALICE, Some("Safaricom"), Some("mobile")).await.unwrap();
+    carriers.upsert(BOB, None, None).await.unwrap();
+    // A refresh replaces the earlier result
+    carriers.upsert(ALICE, Some("Airtel"), Some("mobile")).await.unwrap();
+
+    let mut rows = carriers.list().await.unwrap();
+    rows.sort_by(|a, b| a.phone.cmp(&b.phone));
+    let found: Vec<(&str, Option<&str>)> = rows.iter().map(|r| (r.phone.as_str(), r.carrier.as_deref())).collect();
+    let mut expected = vec![(ALICE, Some("Airtel")), (BOB, None)];
+    expected.sort();
+    assert_eq!(found, expected);
+}
+
+#[tokio::test]
+async fn test_reserved_names() {
+    let db = TestDb::new().await;
+    let reserved = ReservedNameRepository::new(db.pool.clone());
+
+    reserved.add("paypal", "brand", None).await.unwrap();
+    let updated = reserved.add("paypal", "reserved", Some("partner")).await.unwrap();
+    assert_eq!((updated.kind.as_str(), updated.reason.as_deref()), ("reserved", Some("partner")));
+    assert_eq!(reserved.list().await.unwrap().len(), 1);
+
+    assert!(reserved.remove("paypal").await.unwrap());
+    assert!(!reserved.remove("paypal").await.unwrap());
+    assert!(reserved.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_balance_alert_settings() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let alerts = BalanceAlertRepository::new(db.pool.clone(), db.cipher());
+
+    assert_eq!(alerts.get(alice.id).await.unwrap(), AlertSettings::default());
+    assert!(alerts.deposit_notifications(ALICE).await.unwrap());
+
+    let settings = alerts.set_below(alice.id, Some(5_000_000)).await.unwrap();
+    assert_eq!(settings, AlertSettings { below: Some(5_000_000), deposit_notifications: true });
+    alerts.set_deposit_notifications(alice.id, false).await.unwrap();
+    assert!(!alerts.deposit_notifications(ALICE).await.unwrap());
+    // Unknown numbers keep the default
+    assert!(alerts.deposit_notifications(BOB).await.unwrap());
+
+    let settings = alerts.set_below(alice.id, None).await.unwrap();
+    assert_eq!(settings, AlertSettings { below: None, deposit_notifications: false });
+}
+
+#[tokio::test]
+async fn test_user_search_filters_and_pages() {
+    let db = TestDb::new().await;
+    let users = UserRepository::new(db.pool.clone(), db.cipher());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    seed_user(&db, "+15550000003", "0x3333333333333333333333333333333333333333").await;
+    users.update_ens_name(ALICE, "alice.ttcip.eth").await.unwrap();
+    LedgerRepository::new(db.pool.clone())
+        .transfer("system:test", &user_account(alice.id), 7_000_000, "cash_in", None)
+        .await
+        .unwrap();
+
+    let search = |f: fn(&mut UserSearch)| {
+        let mut search = UserSearch::default();
+        f(&mut search);
+        search
+    };
+
+    let (found, total) = users.search(&search(|s| s.phone_prefix = Some("+2547".into())), 0, 10).await.unwrap();
+    assert_eq!(total, 2);
+    assert!(found.iter().all(|u| u.phone.starts_with("+2547")));
+
+    let (found, _) = users.search(&search(|s| s.ens_name = Some("ALI".into())), 0, 10).await.unwrap();
+    assert_eq!(found.iter().map(|u| u.phone.as_str()).collect::<Vec<_>>(), [ALICE]);
+    let (found, _) = users.search(&search(|s| s.wallet_address = Some("0X2222222222222222222222222222222222222222".into())), 0, 10).await.unwrap();
+    assert_eq!(found[0].phone, BOB);
+    let (found, total) = users.search(&search(|s| s.min_balance = Some(Money::usdc(5_000_000))), 0, 10).await.unwrap();
+    assert_eq!((total, found[0].balance), (1, 7_000_000));
+    // As the admin route reads them from the query string
+    let bounded: UserSearch = serde_urlencoded::from_str("min_balance=6.5&max_balance=7").unwrap();
+    assert_eq!((bounded.min_balance, bounded.max_balance), (Some(Money::usdc(6_500_000)), Some(Money::usdc(7_000_000))));
+    let (found, total) = users.search(&bounded, 0, 10).await.unwrap();
+    assert_eq!((total, found[0].phone.as_str()), (1, ALICE));
+    let (_, total) = users.search(&search(|s| s.created_to = Some(Utc::now() - chrono::Duration::days(1))), 0, 10).await.unwrap();
+    assert_eq!(total, 0);
+
+    // Paging keeps the total; balance sort puts Alice first
+    let sorted = search(|s| {
+        s.sort = UserSort::Balance;
+        s.order = SortOrder::Desc;
+    });
+    let (first, total) = users.search(&sorted, 0, 2).await.unwrap();
+    let (rest, _) = users.search(&sorted, 2, 2).await.unwrap();
+    assert_eq!((total, first.len(), rest.len()), (3, 2, 1));
+    assert_eq!(first[0].phone, ALICE);
+}
+
+#[tokio::test]
+async fn test_name_gift_lifecycle() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let bob = seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    let gifts = NameGiftRepository::new(db.pool.clone());
+
+    let gift = gifts.offer(alice.id, bob.id, "bobby").await.unwrap().expect("offered");
+    assert_eq!((gift.giver_id, gift.label.as_str()), (alice.id, "bobby"));
+    // One waiting gift per recipient
+    assert!(gifts.offer(alice.id, bob.id, "robert").await.unwrap().is_none());
+
+    // A failed mint that may be retried goes back to pending; claims are exclusive
+    let claimed = gifts.claim(bob.id).await.unwrap().expect("claimed");
+    assert!(gifts.claim(bob.id).await.unwrap().is_none());
+    gifts.release(claimed.id, true).await.unwrap();
+    let claimed = gifts.claim(bob.id).await.unwrap().expect("claimed again");
+
+    let cost = MintCost { gas_used: 90_000, cost_gwei: 2_700 };
+    gifts.record_mint(claimed.id, None, Some("0xabc"), cost).await.unwrap();
+    let (status, cost_gwei): (String, Option<i64>) =
+        sqlx::query_as("SELECT status, cost_gwei FROM name_gifts WHERE id = $1").bind(claimed.id).fetch_one(&db.pool).await.unwrap();
+    assert_eq!((status.as_str(), cost_gwei), ("accepted", Some(2_700)));
+
+    // Settled gifts no longer block a new one, which can be declined
+    gifts.offer(bob.id, alice.id, "ally").await.unwrap().expect("offered");
+    assert_eq!(gifts.decline(alice.id).await.unwrap().map(|g| g.label), Some("ally".to_string()));
+    assert!(gifts.decline(alice.id).await.unwrap().is_none());
+    assert!(gifts.offer(bob.id, alice.id, "ally").await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_name_sale_escrow_settle_and_refund() {
+    let db = TestDb::new().await;
+    let users = UserRepository::new(db.pool.clone(), db.cipher());
+    let ledger = LedgerRepository::new(db.pool.clone());
+    let listings = NameListingRepository::new(db.pool.clone());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let bob = seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    users.update_ens_name(ALICE, "alice.ttcip.eth").await.unwrap();
+
+    let listing = listings.list(alice.id, "alice", Money::usdc(5_000_000)).await.unwrap().expect("listed");
+    // Relisting changes the price of the open listing
+    let relisted = listings.list(alice.id, "alice", Money::usdc(3_000_000)).await.unwrap().expect("relisted");
+    assert_eq!((relisted.id, relisted.price), (listing.id, Money::usdc(3_000_000)));
+    assert!(matches!(listings.escrow(listing.id, alice.id).await, Err(NameSaleError::NotListed)));
+    assert!(matches!(listings.escrow(listing.id, bob.id).await, Err(NameSaleError::InsufficientFunds)));
+    assert_eq!(listings.find_open("alice").await.unwrap().unwrap().status, "listed");
+
+    ledger.transfer("system:test", &user_account(bob.id), 4_000_000, "cash_in", None).await.unwrap();
+    let selling = listings.escrow(listing.id, bob.id).await.unwrap();
+    assert_eq!(ledger.balance(&selling.escrow_account()).await.unwrap(), 3_000_000);
+    // Nobody else can buy it, nor the seller reprice it, while it is being sold
+    assert!(matches!(listings.escrow(listing.id, bob.id).await, Err(NameSaleError::NotListed)));
+    assert!(listings.list(alice.id, "alice", Money::usdc(1_000_000)).await.unwrap().is_none());
+
+    // A failed chain update refunds the buyer and lists the name again
+    listings.refund(&selling).await.unwrap();
+    assert_eq!(ledger.balance(&user_account(bob.id)).await.unwrap(), 4_000_000);
+    assert_eq!(listings.find_open("alice").await.unwrap().unwrap().status, "listed");
+
+    let selling = listings.escrow(listing.id, bob.id).await.unwrap();
+    listings.settle(&selling, Some("0xabc")).await.unwrap();
+    assert_eq!(ledger.balance(&user_account(alice.id)).await.unwrap(), 3_000_000);
+    assert_eq!(ledger.balance(&user_account(bob.id)).await.unwrap(), 1_000_000);
+    assert_eq!(ledger.balance(&selling.escrow_account()).await.unwrap(), 0);
+    assert_eq!(users.find_by_id(alice.id).await.unwrap().unwrap().ens_name, None);
+    assert_eq!(users.find_by_id(bob.id).await.unwrap().unwrap().ens_name.as_deref(), Some("alice.ttcip.eth"));
+    assert!(listings.find_open("alice").await.unwrap().is_none());
+    assert!(matches!(listings.settle(&selling, None).await, Err(NameSaleError::NotListed)));
+
+    // The new owner can list it and take it down again
+    listings.list(bob.id, "alice", Money::usdc(2_000_000)).await.unwrap().expect("listed");
+    assert!(listings.withdraw(alice.id, "alice").await.unwrap().is_none());
+    assert!(listings.withdraw(bob.id, "alice").await.unwrap().is_some());
+    assert!(listings.find_open("alice").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_cash_payout_refund_links_entries() {
+    let db = TestDb::new().await;
+    let ledger = LedgerRepository::new(db.pool.clone());
+    let payouts = PayoutRepository::new(db.pool.clone());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let account = user_account(alice.id);
+    let shop = "0x742d35cc6634c0532925a3b844bc454e4438f44e";
+
+    assert!(matches!(payouts.create(alice.id, Money::usdc(5_000_000), "base", shop).await, Err(LedgerError::InsufficientFunds)));
+    ledger.transfer("system:test", &account, 8_000_000, "cash_in", None).await.unwrap();
+
+    // Debited before anything is broadcast, then followed once sent
+    let payout = payouts.create(alice.id, Money::usdc(5_000_000), "base", shop).await.unwrap();
+    assert_eq!((payout.status.as_str(), payout.amount), ("pending", Money::usdc(5_000_000)));
+    assert_eq!(ledger.balance(&account).await.unwrap(), 3_000_000);
+    assert_eq!(ledger.balance(PAYOUT_ACCOUNT).await.unwrap(), 5_000_000);
+    payouts.set_tx_hash(payout.id, "0xabc").await.unwrap();
+    assert!(payouts.list_unsettled(10, 10).await.unwrap().is_empty());
+    payouts.mark_sent(payout.id, "0xabc").await.unwrap();
+    let sent = payouts.list_unsettled(10, 10).await.unwrap();
+    assert_eq!((sent.len(), sent[0].tx_hash.as_deref()), (1, Some("0xabc")));
+
+    // A failed transaction gives the money back once, linked to the debit
+    let refunded = payouts.refund(payout.id, "the transaction failed").await.unwrap().expect("refunded");
+    assert_eq!((refunded.status.as_str(), refunded.reason.as_deref()), ("reversed", Some("the transaction failed")));
+    assert_eq!(ledger.balance(&account).await.unwrap(), 8_000_000);
+    assert_eq!(ledger.balance(PAYOUT_ACCOUNT).await.unwrap(), 0);
+    assert!(payouts.refund(payout.id, "again").await.unwrap().is_none());
+    assert!(!payouts.mark_confirmed(payout.id).await.unwrap());
+    let (kind, reference): (String, Option<String>) =
+        sqlx::query_as("SELECT kind, reference FROM ledger_entries WHERE transfer_id = $1 AND account = $2")
+            .bind(refunded.refund_transfer_id.unwrap())
+            .bind(&account)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!((kind.as_str(), reference), ("payout_refund", Some(payout.transfer_id.to_string())));
+
+    // A confirmed payout can't be refunded
+    let confirmed = payouts.create(alice.id, Money::usdc(1_000_000), "base", shop).await.unwrap();
+    payouts.mark_sent(confirmed.id, "0xdef").await.unwrap();
+    assert!(payouts.mark_confirmed(confirmed.id).await.unwrap());
+    assert!(payouts.refund(confirmed.id, "late").await.unwrap().is_none());
+    assert_eq!(ledger.balance(&account).await.unwrap(), 7_000_000);
+
+    // A send that was never marked sent is picked up once it is stale, and
+    // can still be confirmed from its stored hash
+    let unrecorded = payouts.create(alice.id, Money::usdc(1_000_000), "base", shop).await.unwrap();
+    payouts.set_tx_hash(unrecorded.id, "0x123").await.unwrap();
+    sqlx::query("UPDATE cash_payouts SET created_at = NOW() - INTERVAL '11 minutes' WHERE id = $1")
+        .bind(unrecorded.id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let stale = payouts.list_unsettled(10, 10).await.unwrap();
+    assert_eq!((stale.len(), stale[0].status.as_str(), stale[0].tx_hash.as_deref()), (1, "pending", Some("0x123")));
+    assert!(payouts.mark_confirmed(unrecorded.id).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_outbox_holds_for_outage() {
+    let db = TestDb::new().await;
+    let outbox = SmsOutboxRepository::new(db.pool.clone());
+    outbox.hold_for_outage("+254700000001", "first", "254").await.unwrap();
+    outbox.hold_for_outage("+254700000002", "second", "254").await.unwrap();
+    outbox.enqueue("+447700900000", "morning", Utc::now()).await.unwrap();
+
+    // Held messages stay out of the regular flush until released
+    let due: Vec<String> = outbox.take_due(10).await.unwrap().into_iter().map(|m| m.body).collect();
+    assert_eq!(due, ["morning"]);
+    assert_eq!(outbox.held_counts().await.unwrap(), [("254".to_string(), 2)]);
+
+    let probe = outbox.oldest_held("254").await.unwrap().expect("held message");
+    assert_eq!(probe.body, "first");
+    outbox.remove(probe.id).await.unwrap();
+    assert_eq!(outbox.release_outage("254").await.unwrap(), 1);
+    assert!(outbox.held_counts().await.unwrap().is_empty());
+    let due: Vec<String> = outbox.take_due(10).await.unwrap().into_iter().map(|m| m.body).collect();
+    assert_eq!(due, ["second"]);
+
+    // An operator can pull any waiting message out to send it now
+    outbox.enqueue("+447700900000", "tomorrow", Utc::now() + chrono::Duration::hours(8)).await.unwrap();
+    let (waiting, total) = outbox.oldest(10).await.unwrap();
+    assert_eq!((waiting.len(), total), (1, 1));
+    assert_eq!(outbox.take(waiting[0].id).await.unwrap().map(|m| m.body).as_deref(), Some("tomorrow"));
+    assert!(outbox.take(waiting[0].id).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_kyc_tiers() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let kyc = KycRepository::new(db.pool.clone());
+    assert_eq!(kyc.tier(alice.id).await.unwrap(), Some(0));
+
+    // Wrong codes count against the code; the right one raises tier 0 to 1
+    kyc.start_phone_code(alice.id, "123456", 10).await.unwrap();
+    assert_eq!(kyc.verify_phone_code(alice.id, "654321").await.unwrap(), PhoneVerification::WrongCode);
+    assert_eq!(kyc.verify_phone_code(alice.id, "123456").await.unwrap(), PhoneVerification::Verified);
+    assert_eq!(kyc.verify_phone_code(alice.id, "123456").await.unwrap(), PhoneVerification::NoPending);
+    assert_eq!(kyc.tier(alice.id).await.unwrap(), Some(1));
+
+    // Admin changes keep their reason; repeating the current tier records nothing
+    assert_eq!(kyc.set_tier(alice.id, 2, "passport checked", "admin").await.unwrap(), Some(1));
+    assert_eq!(kyc.set_tier(alice.id, 2, "again", "admin").await.unwrap(), Some(2));
+    // A later phone code doesn't lower an upgraded user
+    kyc.start_phone_code(alice.id, "111111", 10).await.unwrap();
+    assert_eq!(kyc.verify_phone_code(alice.id, "111111").await.unwrap(), PhoneVerification::Verified);
+    assert_eq!(kyc.set_tier(alice.id, 0, "fraud report", "admin").await.unwrap(), Some(2));
+    assert_eq!(kyc.set_tier(Uuid::new_v4(), 1, "nobody", "admin").await.unwrap(), None);
+
+    let history: Vec<(i16, i16, String)> =
+        kyc.history(alice.id).await.unwrap().into_iter().map(|c| (c.from_tier, c.to_tier, c.reason)).collect();
+    assert_eq!(
+        history,
+        [(0, 1, "phone verified".to_string()), (1, 2, "passport checked".to_string()), (2, 0, "fraud report".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn test_statement_activity_and_links() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let bob = seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    let ledger = LedgerRepository::new(db.pool.clone());
+    let statements = StatementRepository::new(db.pool.clone());
+    let (alice_account, bob_account) = (user_account(alice.id), user_account(bob.id));
+
+    ledger.transfer("system:test", &alice_account, 10_000_000, "cashin", None).await.unwrap();
+    let before = Utc::now();
+    ledger.transfer(&alice_account, &bob_account, 4_000_000, "p2p", None).await.unwrap();
+    let after = Utc::now();
+
+    // Opening balance counts only earlier entries; each entry knows its other side
+    assert_eq!(statements.balance_at(&alice_account, before).await.unwrap(), 10_000_000);
+    assert_eq!(statements.balance_at(&alice_account, after).await.unwrap(), 6_000_000);
+    let entries = statements.entries(&alice_account, before, after).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!((entries[0].delta, entries[0].counterparty.as_str()), (-4_000_000, bob_account.as_str()));
+
+    let month = before.date_naive();
+    let token = statements.create_link(alice.id, month, 10).await.unwrap();
+    assert_eq!(statements.resolve_link(&token).await.unwrap(), Some((alice.id, month)));
+    let expired = statements.create_link(alice.id, month, -1).await.unwrap();
+    assert_eq!(statements.resolve_link(&expired).await.unwrap(), None);
+    assert_eq!(statements.resolve_link("nope").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_kill_switch_state() {
+    let db = TestDb::new().await;
+    let switch = KillSwitchRepository::new(db.pool.clone());
+    assert_eq!(switch.get().await.unwrap(), KillSwitchState::default());
+
+    let engaged = switch.set(true, "ops-oncall", "treasury key rotation").await.unwrap();
+    assert!(engaged.engaged && engaged.changed_at.is_some());
+    assert_eq!(switch.get().await.unwrap(), engaged);
+
+    switch.set(false, "ops-lead", "rotation done").await.unwrap();
+    let released = switch.get().await.unwrap();
+    assert_eq!((released.engaged, released.changed_by.as_str()), (false, "ops-lead"));
+}
+
+#[tokio::test]
+async fn test_ens_checks_rotate_and_keep_verdicts() {
+    let db = TestDb::new().await;
+    let users = UserRepository::new(db.pool.clone(), db.cipher());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    users.update_ens_name(ALICE, "alice.ttcip.eth").await.unwrap();
+    let checks = EnsCheckRepository::new(db.pool.clone());
+
+    // Only users holding a name are due
+    let due = checks.due(10).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!((due[0].user_id, due[0].ens_name.as_str()), (alice.id, "alice.ttcip.eth"));
+
+    let elsewhere = "0x3333333333333333333333333333333333333333";
+    checks.record(&due[0], EnsCheckStatus::Mismatch, Some(elsewhere)).await.unwrap();
+    // A failed lookup keeps the mismatch flagged
+    checks.record_error(&due[0], "rpc timeout").await.unwrap();
+    let flagged = checks.list(EnsCheckStatus::Mismatch, 10).await.unwrap();
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].resolved_address.as_deref(), Some(elsewhere));
+    assert_eq!(flagged[0].error.as_deref(), Some("rpc timeout"));
+    assert_eq!(checks.status_counts().await.unwrap(), [("mismatch".to_string(), 1)]);
+
+    // A new name is checked afresh; the old verdict no longer counts
+    users.update_ens_name(ALICE, "alice2.ttcip.eth").await.unwrap();
+    assert!(checks.status_counts().await.unwrap().is_empty());
+    let due = checks.due(10).await.unwrap();
+    checks.record_error(&due[0], "rpc timeout").await.unwrap();
+    assert_eq!(checks.list(EnsCheckStatus::Unresolved, 10).await.unwrap()[0].resolved_address, None);
+    checks.record(&due[0], EnsCheckStatus::Ok, Some(ALICE_WALLET)).await.unwrap();
+    let ok = checks.list(EnsCheckStatus::Ok, 10).await.unwrap();
+    assert_eq!((ok[0].ens_name.as_str(), ok[0].error.as_deref()), ("alice2.ttcip.eth", None));
+}
+
+#[tokio::test]
+async fn test_send_jobs_cancel_and_claim_are_exclusive() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let jobs = SendJobRepository::new(db.pool.clone());
+    let job = |amount| NewSendJob {
+        sender_id: alice.id,
+        token: "TXTC",
+        amount,
+        recipient: "bob.ttcip.eth",
+        recipient_address: "0x2222222222222222222222222222222222222222",
+    };
+
+    // Nothing is claimed while the window is open, and CANCEL takes the latest
+    jobs.enqueue(&job(1.0), 60).await.unwrap();
+    let second = jobs.enqueue(&job(2.0), 60).await.unwrap();
+    assert!(jobs.claim_due(10).await.unwrap().is_empty());
+    let cancelled = jobs.cancel_latest(alice.id).await.unwrap().unwrap();
+    assert_eq!((cancelled.id, cancelled.status.as_str()), (second.id, "cancelled"));
+
+    // Once claimed, a job can't be cancelled and isn't claimed again
+    let due = jobs.enqueue(&job(3.0), 0).await.unwrap();
+    let claimed = jobs.claim_due(10).await.unwrap();
+    assert_eq!(claimed.len(), 1);
+    assert_eq!((claimed[0].id, claimed[0].status.as_str()), (due.id, "running"));
+    assert!(jobs.claim_due(10).await.unwrap().is_empty());
+    let summary = jobs.summary().await.unwrap();
+    assert_eq!((summary.queued, summary.due, summary.running, summary.stuck), (1, 0, 1, 0));
+    sqlx::query("UPDATE send_jobs SET started_at = NOW() - INTERVAL '1 hour' WHERE id = $1")
+        .bind(due.id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(jobs.summary().await.unwrap().stuck, 1);
+    let still_queued = jobs.cancel_latest(alice.id).await.unwrap().unwrap();
+    assert_eq!(still_queued.amount, 1.0);
+    assert!(jobs.cancel_latest(alice.id).await.unwrap().is_none());
+
+    jobs.finish(due.id, true, "Sent").await.unwrap();
+    assert_eq!(jobs.latest(alice.id, 10).await.unwrap().unwrap().status, "sent");
+}
+
+#[tokio::test]
+async fn test_recent_sends_match_sent_and_queued() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let recent = RecentSendRepository::new(db.pool.clone());
+    let jobs = SendJobRepository::new(db.pool.clone());
+    let send = |amount, recipient_address| NewRecentSend {
+        sender_id: alice.id,
+        token: "USDC",
+        amount,
+        recipient: "mom",
+        recipient_address,
+    };
+    let mom = "0xabcdef2222222222222222222222222222222222";
+
+    assert!(recent.latest_match(&send(10.0, mom), 10).await.unwrap().is_none());
+    recent.record(&send(10.0, mom)).await.unwrap();
+    // Same transfer, address in any case
+    let earlier = recent.latest_match(&send(10.0, "0xABCDEF2222222222222222222222222222222222"), 10).await.unwrap();
+    assert!(earlier.is_some_and(|at| at <= Utc::now()));
+    assert!(recent.latest_match(&send(11.0, mom), 10).await.unwrap().is_none());
+    assert!(recent.latest_match(&send(10.0, ALICE_WALLET), 10).await.unwrap().is_none());
+
+    // Outside the window it no longer counts
+    sqlx::query("UPDATE recent_sends SET created_at = NOW() - INTERVAL '20 minutes'")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    assert!(recent.latest_match(&send(10.0, mom), 10).await.unwrap().is_none());
+
+    // A SEND waiting out its cancellation window counts until cancelled
+    let job = NewSendJob { sender_id: alice.id, token: "USDC", amount: 10.0, recipient: "mom", recipient_address: mom };
+    jobs.enqueue(&job, 60).await.unwrap();
+    assert!(recent.latest_match(&send(10.0, mom), 10).await.unwrap().is_some());
+    jobs.cancel_latest(alice.id).await.unwrap().unwrap();
+    assert!(recent.latest_match(&send(10.0, mom), 10).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_notify_mode_and_digest_queue() {
+    let db = TestDb::new().await;
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let notifications = NotificationRepository::new(db.pool.clone(), db.cipher());
+
+    assert_eq!(notifications.mode_for_phone(ALICE).await.unwrap(), Some((alice.id, NotifyMode::All)));
+    assert_eq!(notifications.mode_for_phone(BOB).await.unwrap(), None);
+    notifications.set_mode(alice.id, NotifyMode::Digest).await.unwrap();
+    assert_eq!(notifications.mode(alice.id).await.unwrap(), NotifyMode::Digest);
+
+    notifications.queue(alice.id, "Deposit confirmed: 5 on Base.").await.unwrap();
+    notifications.queue(alice.id, "Received 2.00 USDC from bob.").await.unwrap();
+    assert_eq!(notifications.pending(alice.id).await.unwrap(), 2);
+
+    // Only notices queued before the cutoff are taken, and only once
+    let before = chrono::Utc::now() - chrono::Duration::hours(1);
+    assert!(notifications.take_before(before).await.unwrap().is_empty());
+    let taken = notifications.take_before(chrono::Utc::now()).await.unwrap();
+    let bodies: Vec<&str> = taken.iter().map(|item| item.body.as_str()).collect();
+    assert_eq!(bodies, vec!["Deposit confirmed: 5 on Base.", "Received 2.00 USDC from bob."]);
+    assert!(notifications.take_before(chrono::Utc::now()).await.unwrap().is_empty());
+    assert_eq!(notifications.pending(alice.id).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_chain_registrations_round_trip() {
+    let db = TestDb::new().await;
+    let chains = ChainRepository::new(db.pool.clone());
+    let mut row = ChainRow {
+        chain_id: 100,
+        name: "Gnosis".to_string(),
+        short_code: "GNO".to_string(),
+        native_token: "XDAI".to_string(),
+        rpc_urls: vec!["https://rpc.gnosischain.com".to_string(), "https://gnosis.drpc.org".to_string()],
+        explorer_url: Some("https://gnosisscan.io".to_string()),
+        testnet: false,
+        confirmations: 20,
+        tokens: vec!["USDC=0xddafbb505ad214d7b80b1f830fccc89b60fb7a83".to_string()],
+    };
+    chains.upsert(&row).await.unwrap();
+
+    // Re-registering the id replaces the earlier row
+    row.rpc_urls.truncate(1);
+    row.tokens.clear();
+    chains.upsert(&row).await.unwrap();
+    let stored = chains.list().await.unwrap();
+    assert_eq!(stored.len(), 1);
+    assert_eq!(stored[0].rpc_urls, vec!["https://rpc.gnosischain.com".to_string()]);
+    assert!(stored[0].tokens.is_empty());
+    assert_eq!((stored[0].chain_id, stored[0].confirmations), (100, 20));
+}
+
+#[tokio::test]
+async fn test_chain_registration_needs_the_admin_token() {
+    use crate::admin::{require_admin_token, AdminTokens};
+    use crate::admin_chains::admin_chain_routes;
+    use crate::wallet::chain_registry::ChainRegistry;
+
+    let db = TestDb::new().await;
+    let chains = ChainRepository::new(db.pool.clone());
+    let registry = ChainRegistry::load(chains.clone()).await.unwrap();
+    let app = axum::Router::new()
+        .nest("/admin", admin_chain_routes(registry))
+        .layer(axum::middleware::from_fn_with_state(AdminTokens::new("secret", ""), require_admin_token));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/admin/chains", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let chain = serde_json::json!({ "name": "Auth Test Chain", "chain_id": 918_273, "rpc_urls": ["https://rpc.example.org"] });
+    let client = reqwest::Client::new();
+    let response = client.post(&url).json(&chain).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(chains.list().await.unwrap().is_empty());
+
+    let response = client.post(&url).bearer_auth("secret").json(&chain).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(chains.list().await.unwrap()[0].chain_id, 918_273);
+}
+
+#[tokio::test]
+async fn test_flow_sessions() {
+    let db = TestDb::new().await;
+    let flows = FlowRepository::new(db.pool.clone());
+
+    flows.save(ALICE, "send", "recipient", r#"{"amount":"10 TXTC"}"#).await.unwrap();
+    let session = flows.find(ALICE).await.unwrap().unwrap();
+    assert_eq!((session.flow.as_str(), session.step.as_str()), ("send", "recipient"));
+    assert_eq!(session.data, r#"{"amount":"10 TXTC"}"#);
+
+    // One flow per number: starting another replaces it
+    flows.save(ALICE, "onboarding", "set_pin", "{}").await.unwrap();
+    assert_eq!(flows.find(ALICE).await.unwrap().unwrap().flow, "onboarding");
+    // Ending a flow leaves the one that replaced it
+    flows.finish(ALICE, "send").await.unwrap();
+    assert_eq!(flows.find(ALICE).await.unwrap().unwrap().flow, "onboarding");
+    flows.finish(ALICE, "onboarding").await.unwrap();
+    assert!(flows.find(ALICE).await.unwrap().is_none());
+
+    // Unfinished signups from the old table carry over once
+    sqlx::query("INSERT INTO onboarding_sessions (phone, step) VALUES ($1, 'set_pin'), ($2, 'complete')")
+        .bind(ALICE)
+        .bind(BOB)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    run_migrations(&db.pool).await.unwrap();
+    let carried = flows.find(ALICE).await.unwrap().unwrap();
+    assert_eq!((carried.flow.as_str(), carried.step.as_str()), ("onboarding", "set_pin"));
+    assert!(flows.find(BOB).await.unwrap().is_none());
+
+    flows.finish(ALICE, "onboarding").await.unwrap();
+    run_migrations(&db.pool).await.unwrap();
+    assert!(flows.find(ALICE).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_metrics_record_acquire_and_queries() {
+    use tracing_subscriber::{layer::SubscriberExt, Layer};
+
+    let db = TestDb::new().await;
+    let subscriber = tracing_subscriber::registry()
+        .with(metrics::AcquireLayer.with_filter(metrics::AcquireLayer::filter()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    // As create_pool builds it, on the current-thread test runtime
+    let pool = PgPoolOptions::new()
+        .acquire_time_level(log::LevelFilter::Trace)
+        .connect_with(db.pool.connect_options().as_ref().clone())
+        .await
+        .unwrap();
+    ReservedNameRepository::new(pool).list().await.unwrap();
+
+    let out = metrics::render(&[]);
+    let count = |name: &str| -> u64 {
+        out.lines().find_map(|line| line.strip_prefix(name)?.trim().parse().ok()).unwrap_or(0)
+    };
+    assert!(count("textchain_db_acquire_seconds_count") > 0);
+    assert!(count("textchain_db_query_seconds_count{query=\"reserved_names.list\"}") > 0);
+}
+
+#[tokio::test]
+async fn test_bills() {
+    let db = TestDb::new().await;
+    let bills = BillRepository::new(db.pool.clone());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let bob = seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+
+    let amount = Money::from_micros(12_500_000, Currency::USDC);
+    let bill = bills.create(alice.id, amount, "lunch").await.unwrap();
+    assert!(is_bill_code(&bill.code));
+    assert!(bill.is_payable());
+    let found = bills.find(&bill.code).await.unwrap().unwrap();
+    assert_eq!((found.amount, found.memo.as_str()), (amount, "lunch"));
+
+    // The merchant can't pay their own bill
+    assert!(bills.claim(&bill.code, alice.id).await.unwrap().is_none());
+
+    // A claimed bill can't be claimed twice, and is open again once released
+    let claimed = bills.claim(&bill.code, bob.id).await.unwrap().unwrap();
+    assert_eq!(claimed.status, "paying");
+    assert!(bills.claim(&bill.code, bob.id).await.unwrap().is_none());
+    bills.release(claimed.id).await.unwrap();
+    assert_eq!(bills.find(&bill.code).await.unwrap().unwrap().status, "open");
+
+    let claimed = bills.claim(&bill.code, bob.id).await.unwrap().unwrap();
+    bills.settle(claimed.id).await.unwrap();
+    assert_eq!(bills.find(&bill.code).await.unwrap().unwrap().status, "paid");
+    assert!(bills.claim(&bill.code, bob.id).await.unwrap().is_none());
+
+    // Expired bills can't be paid
+    let late = bills.create(alice.id, amount, "dinner").await.unwrap();
+    sqlx::query("UPDATE bills SET expires_at = NOW() - INTERVAL '1 minute' WHERE id = $1")
+        .bind(late.id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    assert!(bills.claim(&late.code, bob.id).await.unwrap().is_none());
+    assert!(!bills.find(&late.code).await.unwrap().unwrap().is_payable());
+}
+
+#[tokio::test]
+async fn test_data_migrations_resume_and_skip_when_completed() {
+    use crate::maintenance::{Job, Maintenance, MigrateArgs};
+
+    let db = TestDb::new().await;
+    let messy = "+254 700-000-001";
+    let alice = seed_user(&db, messy, "0xabcdef1111111111111111111111111111111111").await;
+    seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    AddressBookRepository::new(db.pool.clone(), db.cipher())
+        .add_contact(messy, "bob", Some(BOB), None)
+        .await
+        .unwrap();
+    DepositRepository::new(db.pool.clone(), db.cipher())
+        .create_from_voucher(messy, Money::usdc(5_000_000), "TST1")
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO vouchers (id, code, usdc_amount) VALUES ($1, 'tst-lower', 1), ($2, 'TST-UPPER', 1)")
+        .bind(Uuid::new_v4())
+        .bind(Uuid::new_v4())
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+    let maintenance = Maintenance::new(db.pool.clone(), db.cipher());
+    let args = |list: &[&str]| MigrateArgs::parse(list.iter().map(|arg| arg.to_string())).unwrap();
+
+    // A dry run reports without writing or recording progress
+    let dry = maintenance.run(Job::UppercaseVouchers, &args(&["--dry-run"])).await.unwrap();
+    assert_eq!((dry.scanned, dry.changed, dry.finished), (2, 1, true));
+    assert!(VoucherRepository::new(db.pool.clone()).find_by_code("TST-LOWER").await.unwrap().is_some());
+    let lower: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM vouchers WHERE code = 'tst-lower'").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(lower, 1);
+    assert!(DataMigrationRepository::new(db.pool.clone()).get("uppercase_vouchers").await.unwrap().is_none());
+
+    // One batch of one user, then the rest from the saved cursor
+    let first = maintenance.run(Job::NormalizePhones, &args(&["--batch-size", "1", "--max-batches", "1"])).await.unwrap();
+    assert_eq!((first.scanned, first.finished), (1, false));
+    let rest = maintenance.run(Job::NormalizePhones, &args(&["--batch-size", "1"])).await.unwrap();
+    assert_eq!((first.scanned + rest.scanned, first.changed + rest.changed, rest.failed, rest.finished), (2, 1, 0, true));
+    let state = DataMigrationRepository::new(db.pool.clone()).get("normalize_phones").await.unwrap().unwrap();
+    assert!(state.is_completed());
+    assert_eq!((state.scanned, state.changed), (2, 1));
+
+    // The user, their contacts and deposits all answer to the clean number
+    let users = UserRepository::new(db.pool.clone(), db.cipher());
+    assert_eq!(users.find_by_phone(ALICE).await.unwrap().unwrap().id, alice.id);
+    assert!(users.find_by_phone(messy).await.unwrap().is_none());
+    let book = AddressBookRepository::new(db.pool.clone(), db.cipher());
+    assert!(book.find_by_phone(ALICE, BOB).await.unwrap().is_some());
+    let deposits = DepositRepository::new(db.pool.clone(), db.cipher());
+    assert_eq!(deposits.get_balance(ALICE).await.unwrap(), Money::usdc(5_000_000));
+
+    // Completed jobs are skipped unless restarted
+    assert!(maintenance.run(Job::NormalizePhones, &args(&[])).await.unwrap().skipped);
+    let again = maintenance.run(Job::NormalizePhones, &args(&["--restart"])).await.unwrap();
+    assert_eq!((again.scanned, again.changed, again.skipped), (2, 0, false));
+
+    let vouchers = maintenance.run(Job::UppercaseVouchers, &args(&[])).await.unwrap();
+    assert_eq!(vouchers.changed, 1);
+    let lower: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM vouchers WHERE code <> UPPER(code)").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(lower, 0);
+
+    let wallets = maintenance.run(Job::ChecksumWallets, &args(&[])).await.unwrap();
+    assert_eq!(wallets.changed, 1);
+    let wallet: String = sqlx::query_scalar("SELECT wallet_address FROM users WHERE id = $1").bind(alice.id).fetch_one(&db.pool).await.unwrap();
+    assert_eq!(wallet, crate::wallet::address::display_address("0xabcdef1111111111111111111111111111111111"));
+
+    // Each real run is audited
+    let audited: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_log WHERE action = 'DATA_MIGRATION'").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(audited, 5);
+}
+
+#[tokio::test]
+async fn test_referral_bonus_paid_once_per_number_block() {
+    let db = TestDb::new().await;
+    let ledger = LedgerRepository::new(db.pool.clone());
+    let referrals = ReferralRepository::new(db.pool.clone(), db.cipher());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let code = referrals.code_for(alice.id, ALICE).await.unwrap();
+    assert!(code.starts_with(REFERRAL_PREFIX));
+    assert_eq!(referrals.code_for(alice.id, ALICE).await.unwrap(), code);
+
+    let (carol, dave) = ("+254711111001", "+254711111002");
+    assert_eq!(referrals.claim("REF0000000000", carol).await.unwrap(), Claim::UnknownCode);
+    for phone in [BOB, carol, dave] {
+        assert_eq!(referrals.claim(&code, phone).await.unwrap(), Claim::Recorded);
+    }
+    assert_eq!(referrals.claim(&code, carol).await.unwrap(), Claim::AlreadyClaimed);
+    assert!(referrals.is_pending(carol).await.unwrap());
+
+    let never = |_: &ReferralFacts| None;
+    let carol_user = seed_user(&db, carol, "0x3333333333333333333333333333333333333333").await;
+    let settled = referrals.settle(carol, carol_user.id, 2_000_000, 1_000_000, never).await.unwrap();
+    assert_eq!(settled, Some(Settlement::Credited { referrer_id: alice.id, referrer_bonus: 2_000_000, referee_bonus: 1_000_000 }));
+    assert_eq!(ledger.balance(&user_account(alice.id)).await.unwrap(), 2_000_000);
+    assert_eq!(ledger.balance(&user_account(carol_user.id)).await.unwrap(), 1_000_000);
+    assert_eq!(ledger.balance(REFERRAL_ACCOUNT).await.unwrap(), -3_000_000);
+    assert_eq!(referrals.settle(carol, carol_user.id, 2_000_000, 1_000_000, never).await.unwrap(), None);
+
+    // Carol's neighbour, and Bob next to Alice herself
+    let mut seen = Vec::new();
+    let dave_user = seed_user(&db, dave, "0x4444444444444444444444444444444444444444").await;
+    let settled = referrals
+        .settle(dave, dave_user.id, 2_000_000, 1_000_000, |facts| {
+            seen.push(facts.clone());
+            facts.block_already_credited.then_some("repeat_block")
+        })
+        .await
+        .unwrap();
+    assert_eq!(settled, Some(Settlement::Rejected { referrer_id: alice.id, reason: "repeat_block" }));
+    assert_eq!((seen[0].credited_total, seen[0].credited_today, seen[0].same_block_as_referrer), (1, 1, false));
+    let bob = seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    referrals
+        .settle(BOB, bob.id, 2_000_000, 1_000_000, |facts| {
+            seen.push(facts.clone());
+            None
+        })
+        .await
+        .unwrap();
+    assert!(seen[1].same_block_as_referrer && !seen[1].block_already_credited);
+    assert_eq!(ledger.balance(&user_account(dave_user.id)).await.unwrap(), 0);
+
+    let since = Utc::now() - chrono::Duration::days(1);
+    let totals = referrals.totals(since).await.unwrap();
+    assert_eq!((totals.pending, totals.credited, totals.rejected, totals.active), (0, 2, 1, 0));
+    assert_eq!(totals.bonus_paid, Money::from_micros(6_000_000, Currency::USDC));
+    let rejections = referrals.rejections(since).await.unwrap();
+    assert_eq!((rejections[0].reason.as_str(), rejections[0].count), ("repeat_block", 1));
+
+    // Carol spends, so she counts as active
+    ledger.transfer(&user_account(carol_user.id), "system:test", 500_000, "send", None).await.unwrap();
+    let top = referrals.top_referrers(since, 10).await.unwrap();
+    assert_eq!((top[0].code.as_str(), top[0].referred, top[0].credited, top[0].rejected, top[0].active), (code.as_str(), 3, 2, 1, 1));
+}
+
+#[tokio::test]
+async fn test_greeting_stored_encrypted() {
+    let db = TestDb::new().await;
+    seed_user(&db, ALICE, ALICE_WALLET).await;
+    let users = UserRepository::new(db.pool.clone(), db.cipher());
+
+    assert_eq!(users.greeting(ALICE).await.unwrap(), None);
+    users.set_greeting(ALICE, Some("purple llama")).await.unwrap();
+    assert_eq!(users.greeting(ALICE).await.unwrap().as_deref(), Some("purple llama"));
+    let stored: String = sqlx::query_scalar("SELECT greeting FROM users").fetch_one(&db.pool).await.unwrap();
+    assert!(!stored.contains("llama"));
+
+    users.set_greeting(ALICE, None).await.unwrap();
+    assert_eq!(users.greeting(ALICE).await.unwrap(), None);
+    assert_eq!(users.greeting(BOB).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_unrecognized_commands_counted_per_word() {
+    let db = TestDb::new().await;
+    let repo = UnrecognizedCommandRepository::new(db.pool.clone());
+
+    repo.record("BALNCE", "BALNCE", Some("BALANCE")).await.unwrap();
+    repo.record("HELLO", "HELLO", None).await.unwrap();
+    repo.record("HELLO", "HELLO THERE", None).await.unwrap();
+
+    let top = repo.top(10).await.unwrap();
+    assert_eq!(top.len(), 2);
+    assert_eq!((top[0].word.as_str(), top[0].count), ("HELLO", 2));
+    assert_eq!(top[0].example, "HELLO THERE");
+    assert!(top[0].last_seen >= top[0].first_seen);
+    assert_eq!(top[1].suggestion.as_deref(), Some("BALANCE"));
+    assert_eq!(repo.top(1).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_spam_samples_review() {
+    let db = TestDb::new().await;
+    let repo = SpamSampleRepository::new(db.pool.clone(), db.cipher());
+    repo.record(ALICE, "Claim your prize https://example.com", 0.95, "drop", &["link".to_string(), "scam_phrase".to_string()])
+        .await
+        .unwrap();
+    repo.record(BOB, "www.example.com", 0.5, "flag", &["link".to_string()]).await.unwrap();
+
+    // Encrypted at rest, readable through the repository
+    let stored: String = sqlx::query_scalar("SELECT body FROM spam_samples WHERE score = 0.5").fetch_one(&db.pool).await.unwrap();
+    assert_ne!(stored, "www.example.com");
+    let pending = repo.list(true, 10).await.unwrap();
+    assert_eq!(pending.iter().map(|s| s.phone.as_str()).collect::<Vec<_>>(), [BOB, ALICE]);
+    assert_eq!((pending[1].body.as_str(), pending[1].reasons.as_str()), ("Claim your prize https://example.com", "link,scam_phrase"));
+
+    let reviewed = repo.review(pending[0].id, "ham").await.unwrap().unwrap();
+    assert_eq!((reviewed.phone.as_str(), reviewed.review.as_deref()), (BOB, Some("ham")));
+    assert!(reviewed.reviewed_at.is_some());
+    assert_eq!(repo.list(true, 10).await.unwrap().len(), 1);
+    assert_eq!(repo.list(false, 10).await.unwrap().len(), 2);
+    assert!(repo.review(-1, "spam").await.unwrap().is_none());
+}
+
+// BetaRepository
+
+fn beta_access(db: &TestDb, capacity: i64) -> BetaAccess {
+    let config = BetaConfig { enabled: true, capacity, release_secs: 60 };
+    BetaAccess::from_config(&config, BetaRepository::new(db.pool.clone())).expect("beta mode on")
+}
+
+#[tokio::test]
+async fn test_beta_invite_max_uses_under_concurrent_redemption() {
+    let db = TestDb::new().await;
+    let code = generate_code("INV");
+    BetaRepository::new(db.pool.clone()).create_invite(&code, 3).await.unwrap();
+
+    let attempts: Vec<_> = (0..10)
+        .map(|i| {
+            let beta = BetaRepository::new(db.pool.clone());
+            let code = code.clone();
+            tokio::spawn(async move { beta.redeem_invite(&code, &format!("+2547000002{:02}", i)).await })
+        })
+        .collect();
+    let mut redeemed = 0;
+    for attempt in attempts {
+        if attempt.await.unwrap().unwrap() {
+            redeemed += 1;
+        }
+    }
+    assert_eq!(redeemed, 3, "no more redemptions than max_uses");
+
+    let beta = BetaRepository::new(db.pool.clone());
+    let uses: i32 = sqlx::query_scalar("SELECT uses FROM beta_invites WHERE code = $1").bind(&code).fetch_one(&db.pool).await.unwrap();
+    assert_eq!(uses, 3);
+    assert_eq!(beta.list_allowlist().await.unwrap().len(), 3);
+    assert!(!beta.redeem_invite(&code, ALICE).await.unwrap());
+    assert!(!beta.redeem_invite("INV-UNKNOWN", ALICE).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_beta_waitlist_positions_and_order() {
+    let db = TestDb::new().await;
+    let beta = BetaRepository::new(db.pool.clone());
+    let carol = "+254700000003";
+
+    assert_eq!(beta.join_waitlist(ALICE).await.unwrap(), 1);
+    assert_eq!(beta.join_waitlist(BOB).await.unwrap(), 2);
+    assert_eq!(beta.join_waitlist(carol).await.unwrap(), 3);
+    // Joining again keeps the original place
+    assert_eq!(beta.join_waitlist(BOB).await.unwrap(), 2);
+
+    let waitlist = beta.list_waitlist().await.unwrap();
+    assert_eq!(waitlist.iter().map(|e| e.phone.as_str()).collect::<Vec<_>>(), [ALICE, BOB, carol]);
+    assert!(waitlist.iter().all(|e| e.admitted_at.is_none()));
+
+    // An admin letting Alice in moves everyone behind her up
+    assert!(beta.allow(ALICE, Some("friend")).await.unwrap());
+    assert!(beta.is_allowlisted(ALICE).await.unwrap());
+    assert_eq!(beta.join_waitlist(BOB).await.unwrap(), 1);
+    assert_eq!(beta.join_waitlist(carol).await.unwrap(), 2);
+    // Already allowlisted: nothing left to admit
+    assert!(!beta.allow(ALICE, None).await.unwrap());
+    assert_eq!(beta.list_allowlist().await.unwrap()[0].note.as_deref(), Some("friend"));
+
+    assert!(beta.disallow(ALICE).await.unwrap());
+    assert!(!beta.disallow(ALICE).await.unwrap());
+    assert!(!beta.is_allowlisted(ALICE).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_beta_admit_next_within_capacity() {
+    let db = TestDb::new().await;
+    let access = beta_access(&db, 3);
+    let beta = access.repo();
+    let waiting = ["+254700000011", "+254700000012", "+254700000013"];
+    seed_user(&db, ALICE, ALICE_WALLET).await;
+    for phone in waiting {
+        beta.join_waitlist(phone).await.unwrap();
+    }
+
+    // One wallet holds a seat, so two are free
+    assert_eq!(beta.seats_taken().await.unwrap(), 1);
+    assert_eq!(access.free_seats().await.unwrap(), 2);
+    let admitted = beta.admit_next(access.free_seats().await.unwrap()).await.unwrap();
+    assert_eq!(admitted, waiting[..2]);
+    assert!(beta.is_allowlisted(waiting[0]).await.unwrap());
+    assert!(!beta.is_allowlisted(waiting[2]).await.unwrap());
+
+    // Admitted numbers hold their seats until they sign up
+    assert_eq!(beta.seats_taken().await.unwrap(), 3);
+    assert_eq!(access.free_seats().await.unwrap(), 0);
+    assert_eq!(beta.join_waitlist(waiting[2]).await.unwrap(), 1);
+
+    // Without a capacity nothing is released automatically
+    assert_eq!(beta_access(&db, 0).free_seats().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_beta_gate_on_signup() {
+    let db = TestDb::new().await;
+    let users = UserRepository::new(db.pool.clone(), db.cipher());
+    let access = beta_access(&db, 0);
+    let mut processor = CommandProcessor::with_repos(
+        Some(users.clone()),
+        None,
+        None,
+        None,
+        KeyVault::new(MASTER_KEY).unwrap(),
+        crate::wallet::create_shared_provider(),
+    );
+    processor.set_beta(access.clone());
+
+    // Not allowlisted: waitlisted, no wallet
+    let reply = processor.process(BOB, "START").await;
+    assert!(reply.contains("#1 on the waitlist"), "{}", reply);
+    assert!(!users.exists(BOB).await.unwrap());
+    assert_eq!(access.admission(BOB).await.unwrap(), Admission::Waitlisted(1));
+
+    // Allowlisted: the wallet is created and the waitlist place released
+    access.repo().allow(BOB, None).await.unwrap();
+    let reply = processor.process(BOB, "START").await;
+    assert!(reply.starts_with("Wallet created!"), "{}", reply);
+    assert!(users.exists(BOB).await.unwrap());
+    assert!(access.repo().list_waitlist().await.unwrap().is_empty());
+
+    // An invite lets a number straight in
+    let code = generate_code("INV");
+    access.repo().create_invite(&code, 1).await.unwrap();
+    assert!(processor.process(ALICE, "INVITE NOPE").await.contains("Invalid invite code"));
+    let reply = processor.process(ALICE, &format!("INVITE {}", code.to_lowercase())).await;
+    assert!(reply.starts_with("Invite accepted!"), "{}", reply);
+    assert!(users.exists(ALICE).await.unwrap());
+    assert!(processor.process("+254700000003", &format!("INVITE {}", code)).await.contains("used up"));
+}
+
+// Internal transfers
+
+fn cash_processor(db: &TestDb) -> CommandProcessor {
+    let mut processor = CommandProcessor::with_repos(
+        Some(UserRepository::new(db.pool.clone(), db.cipher())),
+        None,
+        None,
+        None,
+        KeyVault::new(MASTER_KEY).unwrap(),
+        crate::wallet::create_shared_provider(),
+    );
+    processor.set_agent_repos(AgentRepository::new(db.pool.clone()), LedgerRepository::new(db.pool.clone()));
+    processor
+}
+
+#[tokio::test]
+async fn test_usdc_send_between_users_settles_on_ledger() {
+    let db = TestDb::new().await;
+    let ledger = LedgerRepository::new(db.pool.clone());
+    let alice = seed_user(&db, ALICE, ALICE_WALLET).await;
+    let bob = seed_user(&db, BOB, "0x2222222222222222222222222222222222222222").await;
+    ledger.transfer("system:test", &user_account(alice.id), 10_000_000, "cash_in", None).await.unwrap();
+    let processor = cash_processor(&db);
+
+    let reply = processor.process(ALICE, &format!("SEND 2.5 USDC TO {}", BOB)).await;
+    assert!(reply.starts_with("Sent 2.50 USDC to"), "{}", reply);
+    assert!(reply.contains("Cash balance: 7.50 USDC"), "{}", reply);
+    assert_eq!(ledger.balance(&user_account(alice.id)).await.unwrap(), 7_500_000);
+    assert_eq!(ledger.balance(&user_account(bob.id)).await.unwrap(), 2_500_000);
+
+    // More than the balance moves nothing
+    let reply = processor.process(ALICE, &format!("SEND 8 USDC TO {}", BOB)).await;
+    assert_eq!(reply, "Insufficient cash balance.\nCash balance: 7.50 USDC");
+    assert_eq!(ledger.balance(&user_account(alice.id)).await.unwrap(), 7_500_000);
+    assert_eq!(ledger.balance(&user_account(bob.id)).await.unwrap(), 2_500_000);
+
+    // Nor does sending to yourself
+    let reply = processor.process(ALICE, &format!("SEND 1 USDC TO {}", ALICE)).await;
+    assert_eq!(reply, "You can't send to yourself.");
+    assert_eq!(ledger.balance(&user_account(alice.id)).await.unwrap(), 7_500_000);
+}
+
+#[tokio::test]
+async fn test_usdc_send_to_outside_address_goes_on_chain() {
+    use axum::{routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    let db = TestDb::new().await;
+    let ledger = LedgerRepository::new(db.pool.clone());
+    let vault = KeyVault::new(MASTER_KEY).unwrap();
+    let alice = UserRepository::new(db.pool.clone(), db.cipher())
+        .create(ALICE, ALICE_WALLET, &vault.seal(&[7u8; 32]).unwrap())
+        .await
+        .unwrap();
+    ledger.transfer("system:test", &user_account(alice.id), 10_000_000, "cash_in", None).await.unwrap();
+
+    // Stand-in for the signing backend, recording what it was asked to send
+    let sent = Arc::new(Mutex::new(None));
+    let recorded = sent.clone();
+    let app = Router::new().route(
+        "/api/send-yellow",
+        post(move |Json(body): Json<serde_json::Value>| async move {
+            *recorded.lock().unwrap() = Some(body);
+            Json(serde_json::json!({ "success": true }))
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let backend_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut processor = cash_processor(&db);
+    processor.set_backend_url(backend_url);
+    let shop = "0x742d35cc6634c0532925a3b844bc454e4438f44e";
+    let reply = processor.process(ALICE, &format!("SEND 3 USDC TO {}", shop)).await;
+    assert!(reply.starts_with("Sending 3 USDC to"), "{}", reply);
+
+    let body = sent.lock().unwrap().take().expect("sent on chain");
+    assert_eq!(body["token"], "USDC");
+    assert_eq!(body["toAddress"], shop);
+    assert_eq!(body["senderKey"], hex::encode([7u8; 32]));
+    // The cash balance is left alone
+    assert_eq!(ledger.balance(&user_account(alice.id)).await.unwrap(), 10_000_000);
+}
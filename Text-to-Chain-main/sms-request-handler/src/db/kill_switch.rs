@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// Whether money-moving commands are stopped, and the last change
+#[derive(Debug, Clone, Default, PartialEq, Serialize, sqlx::FromRow)]
+pub struct KillSwitchState {
+    pub engaged: bool,
+    pub reason: String,
+    /// Who flipped it (an operator name, or `config` for KILL_SWITCH)
+    pub changed_by: String,
+    pub changed_at: Option<DateTime<Utc>>,
+}
+
+/// The fleet-wide kill switch, one row shared by every instance (see
+/// `kill_switch::KillSwitch`)
+#[derive(Clone)]
+pub struct KillSwitchRepository {
+    pool: PgPool,
+}
+
+impl KillSwitchRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Current state (disengaged if it was never flipped)
+    pub async fn get(&self) -> Result<KillSwitchState, sqlx::Error> {
+        let _timer = QueryTimer::start("kill_switch.get");
+        let state = sqlx::query_as::<_, KillSwitchState>(
+            "SELECT engaged, reason, changed_by, changed_at FROM kill_switch WHERE id = 1"
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(state.unwrap_or_default())
+    }
+
+    pub async fn set(&self, engaged: bool, changed_by: &str, reason: &str) -> Result<KillSwitchState, sqlx::Error> {
+        let _timer = QueryTimer::start("kill_switch.set");
+        sqlx::query_as::<_, KillSwitchState>(
+            r#"
+            INSERT INTO kill_switch (id, engaged, reason, changed_by, changed_at)
+            VALUES (1, $1, $2, $3, NOW())
+            ON CONFLICT (id) DO UPDATE SET
+                engaged = EXCLUDED.engaged,
+                reason = EXCLUDED.reason,
+                changed_by = EXCLUDED.changed_by,
+                changed_at = EXCLUDED.changed_at
+            RETURNING engaged, reason, changed_by, changed_at
+            "#
+        )
+        .bind(engaged)
+        .bind(reason)
+        .bind(changed_by)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,171 @@
+//! Onboarding KYC tiers
+//!
+//! Every user starts at tier 0 (receive only). Texting back a code sent to
+//! the phone raises them to tier 1; tier 2 is set by an admin once the ID
+//! submitted through the VERIFY ID link has been checked. Each change is
+//! kept with who made it and why.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// Highest tier
+pub const MAX_KYC_TIER: i16 = 2;
+
+/// Wrong codes allowed before VERIFY has to be sent again
+const MAX_CODE_ATTEMPTS: i32 = 5;
+
+/// Outcome of VERIFY <code>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhoneVerification {
+    /// The user is now at tier 1 or above
+    Verified,
+    WrongCode,
+    /// No code waiting, or it expired / ran out of attempts
+    NoPending,
+}
+
+/// One tier change, oldest first in a user's history
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct TierChange {
+    pub from_tier: i16,
+    pub to_tier: i16,
+    pub reason: String,
+    /// `sms` for phone verification, otherwise `admin`
+    pub changed_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct KycRepository {
+    pool: PgPool,
+}
+
+impl KycRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// A user's tier (None = no such user)
+    pub async fn tier(&self, user_id: Uuid) -> Result<Option<i16>, sqlx::Error> {
+        let _timer = QueryTimer::start("kyc.tier");
+        sqlx::query_scalar::<_, i16>("SELECT kyc_tier FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Move a user to `tier`, returning the tier they had (None = no such
+    /// user). Setting the current tier again records nothing
+    pub async fn set_tier(&self, user_id: Uuid, tier: i16, reason: &str, changed_by: &str) -> Result<Option<i16>, sqlx::Error> {
+        let _timer = QueryTimer::start("kyc.set_tier");
+        let mut tx = self.pool.begin().await?;
+        let Some(previous) = sqlx::query_scalar::<_, i16>("SELECT kyc_tier FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+        if previous != tier {
+            sqlx::query("UPDATE users SET kyc_tier = $2 WHERE id = $1")
+                .bind(user_id)
+                .bind(tier)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "INSERT INTO kyc_tier_changes (id, user_id, from_tier, to_tier, reason, changed_by)
+                 VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(Uuid::new_v4())
+            .bind(user_id)
+            .bind(previous)
+            .bind(tier)
+            .bind(reason)
+            .bind(changed_by)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(Some(previous))
+    }
+
+    /// Every tier change for a user, oldest first
+    pub async fn history(&self, user_id: Uuid) -> Result<Vec<TierChange>, sqlx::Error> {
+        let _timer = QueryTimer::start("kyc.history");
+        sqlx::query_as::<_, TierChange>(
+            "SELECT from_tier, to_tier, reason, changed_by, created_at
+             FROM kyc_tier_changes WHERE user_id = $1 ORDER BY created_at, id",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Store a phone code for VERIFY, replacing any earlier one
+    pub async fn start_phone_code(&self, user_id: Uuid, code: &str, ttl_minutes: i32) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("kyc.start_phone_code");
+        sqlx::query(
+            "INSERT INTO kyc_phone_codes (user_id, code, expires_at, attempts)
+             VALUES ($1, $2, NOW() + make_interval(mins => $3), 0)
+             ON CONFLICT (user_id) DO UPDATE SET code = EXCLUDED.code, expires_at = EXCLUDED.expires_at, attempts = 0",
+        )
+        .bind(user_id)
+        .bind(code)
+        .bind(ttl_minutes)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Check a VERIFY code; a match takes a tier 0 user to tier 1
+    pub async fn verify_phone_code(&self, user_id: Uuid, code: &str) -> Result<PhoneVerification, sqlx::Error> {
+        let _timer = QueryTimer::start("kyc.verify_phone_code");
+        let mut tx = self.pool.begin().await?;
+        let matched = sqlx::query(
+            "DELETE FROM kyc_phone_codes
+             WHERE user_id = $1 AND code = $2 AND expires_at > NOW() AND attempts < $3",
+        )
+        .bind(user_id)
+        .bind(code)
+        .bind(MAX_CODE_ATTEMPTS)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        if matched > 0 {
+            // Users already past tier 0 keep their tier
+            let raised = sqlx::query("UPDATE users SET kyc_tier = 1 WHERE id = $1 AND kyc_tier = 0")
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+            if raised > 0 {
+                sqlx::query(
+                    "INSERT INTO kyc_tier_changes (id, user_id, from_tier, to_tier, reason, changed_by)
+                     VALUES ($1, $2, 0, 1, 'phone verified', 'sms')",
+                )
+                .bind(Uuid::new_v4())
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            return Ok(PhoneVerification::Verified);
+        }
+        tx.commit().await?;
+
+        let pending = sqlx::query(
+            "UPDATE kyc_phone_codes SET attempts = attempts + 1
+             WHERE user_id = $1 AND expires_at > NOW() AND attempts < $2",
+        )
+        .bind(user_id)
+        .bind(MAX_CODE_ATTEMPTS)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(if pending > 0 { PhoneVerification::WrongCode } else { PhoneVerification::NoPending })
+    }
+}
@@ -0,0 +1,183 @@
+//! Custodial double-entry ledger
+//!
+//! Every transfer writes a debit and a credit entry sharing a `transfer_id`
+//! and updates both running balances in the same transaction. Amounts are
+//! micro-USDC (6 decimals), like voucher amounts.
+//!
+//! Accounts are strings: `user:<id>`, `agent:<id>`, and `system:*` accounts
+//! for money entering or leaving the ledger (those may go negative).
+
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// Source of agent float top-ups (cash or bank transfer received off-ledger)
+pub const FLOAT_TOPUP_ACCOUNT: &str = "system:float-topup";
+
+/// Ledger account for a registered user
+pub fn user_account(user_id: Uuid) -> String {
+    format!("user:{}", user_id)
+}
+
+/// Ledger account holding an agent's float
+pub fn agent_account(agent_id: Uuid) -> String {
+    format!("agent:{}", agent_id)
+}
+
+/// Convert micro-USDC to display units
+pub fn micro_to_f64(amount: i64) -> f64 {
+    amount as f64 / 1_000_000.0
+}
+
+/// Convert display units to micro-USDC
+pub fn f64_to_micro(amount: f64) -> i64 {
+    (amount * 1_000_000.0).round() as i64
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LedgerError {
+    #[error("Insufficient balance")]
+    InsufficientFunds,
+    #[error("Amount must be positive")]
+    InvalidAmount,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Both sides of one ledger transfer
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct LedgerTransfer {
+    pub transfer_id: Uuid,
+    pub from_account: String,
+    pub to_account: String,
+    pub amount: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Clone)]
+pub struct LedgerRepository {
+    pool: PgPool,
+}
+
+impl LedgerRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Current balance of an account (0 if it has never been used)
+    pub async fn balance(&self, account: &str) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("ledger.balance");
+        let balance = sqlx::query_scalar!("SELECT balance FROM ledger_balances WHERE account = $1", account)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(balance.unwrap_or(0))
+    }
+
+    /// Move `amount` between two accounts atomically
+    pub async fn transfer(
+        &self,
+        from: &str,
+        to: &str,
+        amount: i64,
+        kind: &str,
+        reference: Option<&str>,
+    ) -> Result<Uuid, LedgerError> {
+        let _timer = QueryTimer::start("ledger.transfer");
+        let mut tx = self.pool.begin().await?;
+        let transfer_id = transfer_in(&mut tx, from, to, amount, kind, reference).await?;
+        tx.commit().await?;
+        Ok(transfer_id)
+    }
+
+    /// Transfers `account` took part in whose id starts with `id_prefix`,
+    /// newest first (at most two, enough to tell a prefix is ambiguous)
+    pub async fn find_transfers(&self, account: &str, id_prefix: &str) -> Result<Vec<LedgerTransfer>, sqlx::Error> {
+        let _timer = QueryTimer::start("ledger.find_transfers");
+        sqlx::query_as!(
+            LedgerTransfer,
+            r#"SELECT c.transfer_id, d.account AS from_account, c.account AS to_account,
+                      c.delta AS amount, c.created_at AS "created_at!"
+               FROM ledger_entries c
+               JOIN ledger_entries d ON d.transfer_id = c.transfer_id AND d.delta < 0
+               WHERE c.delta > 0
+                 AND c.transfer_id::text LIKE $2 || '%'
+                 AND (c.account = $1 OR d.account = $1)
+               ORDER BY c.created_at DESC
+               LIMIT 2"#,
+            account,
+            id_prefix.to_lowercase()
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+/// Transfer inside a caller's transaction, so it commits or rolls back
+/// together with the caller's own writes
+pub async fn transfer_in(
+    tx: &mut Transaction<'_, Postgres>,
+    from: &str,
+    to: &str,
+    amount: i64,
+    kind: &str,
+    reference: Option<&str>,
+) -> Result<Uuid, LedgerError> {
+    if amount <= 0 {
+        return Err(LedgerError::InvalidAmount);
+    }
+
+    for account in [from, to] {
+        sqlx::query!("INSERT INTO ledger_balances (account, balance) VALUES ($1, 0) ON CONFLICT (account) DO NOTHING", account)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    // Conditional debit: the row lock serialises concurrent transfers and
+    // the balance check happens against the locked value
+    let debited = sqlx::query!(
+        "UPDATE ledger_balances SET balance = balance - $1, updated_at = NOW()
+         WHERE account = $2 AND (balance >= $1 OR account LIKE 'system:%')",
+        amount,
+        from
+    )
+    .execute(&mut **tx)
+    .await?;
+    if debited.rows_affected() == 0 {
+        return Err(LedgerError::InsufficientFunds);
+    }
+
+    sqlx::query!("UPDATE ledger_balances SET balance = balance + $1, updated_at = NOW() WHERE account = $2", amount, to)
+        .execute(&mut **tx)
+        .await?;
+
+    let transfer_id = Uuid::new_v4();
+    for (account, delta) in [(from, -amount), (to, amount)] {
+        sqlx::query!(
+            "INSERT INTO ledger_entries (id, transfer_id, account, delta, kind, reference)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+            Uuid::new_v4(),
+            transfer_id,
+            account,
+            delta,
+            kind,
+            reference
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(transfer_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_micro_conversion() {
+        assert_eq!(f64_to_micro(12.5), 12_500_000);
+        assert_eq!(f64_to_micro(0.1 + 0.2), 300_000);
+        assert_eq!(micro_to_f64(1_250_000), 1.25);
+    }
+}
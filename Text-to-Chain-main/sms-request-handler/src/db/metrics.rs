@@ -0,0 +1,205 @@
+//! Database timings in the Prometheus text format
+//!
+//! Every repository method holds a `QueryTimer`, so `GET /metrics/db` has a
+//! latency histogram per method, plus a count of calls slower than
+//! `DB_SLOW_QUERY_MS` (each also logged as a warning). Connection acquire
+//! waits come from sqlx's own acquire events, caught by `AcquireLayer`.
+//! Set beside the pool gauges, they show whether slow replies are spent
+//! waiting for a connection, in a query, or elsewhere (usually RPC).
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::field::{Field, Visit};
+use tracing_subscriber::layer::{Context, Layer};
+
+use super::PoolMetrics;
+
+/// Histogram bucket bounds in seconds
+const BUCKETS: [f64; 11] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Where sqlx reports how long `acquire` took
+const ACQUIRE_TARGET: &str = "sqlx::pool::acquire";
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    /// Observations per bucket (not cumulative), the last one being +Inf
+    buckets: [u64; BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, secs: f64) {
+        let bucket = BUCKETS.iter().position(|bound| secs <= *bound).unwrap_or(BUCKETS.len());
+        self.buckets[bucket] += 1;
+        self.sum += secs;
+        self.count += 1;
+    }
+
+    /// `_bucket`, `_sum` and `_count` lines; `labels` is empty or ends with a comma
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().map(|b| b.to_string()).chain(["+Inf".to_string()]).zip(self.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "{}_bucket{{{}le=\"{}\"}} {}", name, labels, bound, cumulative);
+        }
+        let labels = match labels.trim_end_matches(',') {
+            "" => String::new(),
+            labels => format!("{{{}}}", labels),
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct QueryStats {
+    latency: Histogram,
+    slow: u64,
+}
+
+/// Per repository method, e.g. `users.find_by_phone`
+static QUERIES: LazyLock<Mutex<BTreeMap<&'static str, QueryStats>>> = LazyLock::new(Mutex::default);
+static ACQUIRE: LazyLock<Mutex<Histogram>> = LazyLock::new(Mutex::default);
+static SLOW_QUERY_MS: AtomicU64 = AtomicU64::new(250);
+
+/// Calls at least this long are counted and logged as slow
+pub fn set_slow_query_threshold(threshold: Duration) {
+    SLOW_QUERY_MS.store(threshold.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// Times a repository call until dropped
+#[must_use]
+pub struct QueryTimer {
+    query: &'static str,
+    started: Instant,
+}
+
+impl QueryTimer {
+    pub fn start(query: &'static str) -> Self {
+        Self { query, started: Instant::now() }
+    }
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        let slow = elapsed.as_millis() as u64 >= SLOW_QUERY_MS.load(Ordering::Relaxed);
+        if slow {
+            tracing::warn!(query = self.query, elapsed_ms = elapsed.as_millis() as u64, "Slow database query");
+        }
+        let mut queries = QUERIES.lock().unwrap_or_else(|e| e.into_inner());
+        let stats = queries.entry(self.query).or_default();
+        stats.latency.observe(elapsed.as_secs_f64());
+        stats.slow += u64::from(slow);
+    }
+}
+
+/// Records connection acquire times from sqlx's `sqlx::pool::acquire` events
+///
+/// Needs pools built with `acquire_time_level` on (see `create_pool`), and
+/// its own filter on the subscriber so the events reach it at TRACE.
+pub struct AcquireLayer;
+
+impl AcquireLayer {
+    /// Target filter for the layer
+    pub fn filter() -> tracing_subscriber::filter::Targets {
+        tracing_subscriber::filter::Targets::new().with_target(ACQUIRE_TARGET, tracing::Level::TRACE)
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for AcquireLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() != ACQUIRE_TARGET {
+            return;
+        }
+        let mut visitor = AcquiredAfter(None);
+        event.record(&mut visitor);
+        if let Some(secs) = visitor.0 {
+            ACQUIRE.lock().unwrap_or_else(|e| e.into_inner()).observe(secs);
+        }
+    }
+}
+
+struct AcquiredAfter(Option<f64>);
+
+impl Visit for AcquiredAfter {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // sqlx spells it this way
+        if field.name() == "aquired_after_secs" || field.name() == "acquired_after_secs" {
+            self.0 = Some(value);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+/// Gauge name, help text and value for one pool
+type PoolGauge = (&'static str, &'static str, fn(&PoolMetrics) -> u64);
+
+/// Pool gauges, acquire waits and query timings as Prometheus text
+pub fn render(pools: &[PoolMetrics]) -> String {
+    let mut out = String::new();
+
+    let gauges: [PoolGauge; 4] = [
+        ("textchain_db_pool_max_connections", "Configured pool size", |p| p.max_connections as u64),
+        ("textchain_db_pool_connections", "Open connections, idle or in use", |p| p.size as u64),
+        ("textchain_db_pool_idle_connections", "Idle connections", |p| p.idle as u64),
+        ("textchain_db_pool_in_use_connections", "Connections checked out", |p| p.in_use as u64),
+    ];
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge", name, help, name);
+        for pool in pools {
+            let _ = writeln!(out, "{}{{pool=\"{}\"}} {}", name, pool.pool, value(pool));
+        }
+    }
+
+    let name = "textchain_db_acquire_seconds";
+    let _ = writeln!(out, "# HELP {} Wait for a pool connection, both pools\n# TYPE {} histogram", name, name);
+    ACQUIRE.lock().unwrap_or_else(|e| e.into_inner()).render(&mut out, name, "");
+
+    let queries = QUERIES.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let name = "textchain_db_query_seconds";
+    let _ = writeln!(out, "# HELP {} Repository call duration, connection wait included\n# TYPE {} histogram", name, name);
+    for (query, stats) in &queries {
+        stats.latency.render(&mut out, name, &format!("query=\"{}\",", query));
+    }
+    let name = "textchain_db_slow_queries_total";
+    let _ = writeln!(out, "# HELP {} Repository calls over DB_SLOW_QUERY_MS\n# TYPE {} counter", name, name);
+    for (query, stats) in &queries {
+        let _ = writeln!(out, "{}{{query=\"{}\"}} {}", name, query, stats.slow);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_is_cumulative() {
+        let mut histogram = Histogram::default();
+        for secs in [0.0005, 0.02, 0.02, 9.0] {
+            histogram.observe(secs);
+        }
+        let mut out = String::new();
+        histogram.render(&mut out, "t", "query=\"users.find\",");
+        assert!(out.contains("t_bucket{query=\"users.find\",le=\"0.001\"} 1\n"));
+        assert!(out.contains("t_bucket{query=\"users.find\",le=\"0.025\"} 3\n"));
+        assert!(out.contains("t_bucket{query=\"users.find\",le=\"5\"} 3\n"));
+        assert!(out.contains("t_bucket{query=\"users.find\",le=\"+Inf\"} 4\n"));
+        assert!(out.contains("t_count{query=\"users.find\"} 4\n"));
+    }
+
+    #[test]
+    fn test_timer_records_query() {
+        drop(QueryTimer::start("test.timer"));
+        let out = render(&[]);
+        assert!(out.contains("textchain_db_query_seconds_count{query=\"test.timer\"} 1\n"));
+        assert!(out.contains("textchain_db_slow_queries_total{query=\"test.timer\"} 0\n"));
+    }
+}
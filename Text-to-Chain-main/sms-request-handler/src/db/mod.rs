@@ -1,24 +1,170 @@
 pub mod address_book;
+pub mod agents;
+pub mod audit_log;
+pub mod balance_alerts;
+pub mod beta;
+pub mod bills;
+pub mod broadcasts;
+pub mod campaigns;
+pub mod chains;
+pub mod data_migrations;
+pub mod deposit_addresses;
 pub mod deposits;
+pub mod email_links;
+pub mod encryption;
+pub mod ens_checks;
+pub mod feature_flags;
+pub mod flows;
+pub mod idempotency;
+pub mod kill_switch;
+pub mod kyc;
+pub mod ledger;
+pub mod metrics;
+pub mod name_gifts;
+pub mod name_listings;
+pub mod notifications;
+pub mod opt_outs;
+pub mod partner_keys;
+pub mod payment_links;
+pub mod payouts;
+pub mod phone_carriers;
+pub mod recent_sends;
+pub mod referrals;
+pub mod reserved_names;
+pub mod savings;
+pub mod send_jobs;
+pub mod sms_outbox;
+pub mod sms_spend;
+pub mod spam_samples;
+pub mod statements;
+pub mod token_overrides;
+pub mod transfer_approvals;
+pub mod transcripts;
+pub mod unrecognized_commands;
 pub mod users;
 pub mod vouchers;
+pub mod walletconnect;
 
 pub use address_book::*;
+pub use agents::*;
+pub use audit_log::*;
+pub use balance_alerts::*;
+pub use beta::*;
+pub use bills::*;
+pub use broadcasts::*;
+pub use campaigns::*;
+pub use chains::*;
+pub use data_migrations::*;
+pub use deposit_addresses::*;
 pub use deposits::*;
+pub use email_links::*;
+pub use encryption::*;
+pub use ens_checks::*;
+pub use feature_flags::*;
+pub use flows::*;
+pub use idempotency::*;
+pub use kill_switch::*;
+pub use kyc::*;
+pub use ledger::*;
+pub use name_gifts::*;
+pub use name_listings::*;
+pub use notifications::*;
+pub use opt_outs::*;
+pub use partner_keys::*;
+pub use payment_links::*;
+pub use payouts::*;
+pub use phone_carriers::*;
+pub use recent_sends::*;
+pub use referrals::*;
+pub use reserved_names::*;
+pub use savings::*;
+pub use send_jobs::*;
+pub use sms_outbox::*;
+pub use sms_spend::*;
+pub use spam_samples::*;
+pub use statements::*;
+pub use token_overrides::*;
+pub use transfer_approvals::*;
+pub use transcripts::*;
+pub use unrecognized_commands::*;
 pub use users::*;
 pub use vouchers::*;
+pub use walletconnect::*;
 
+#[cfg(all(test, feature = "db-tests"))]
+mod integration_tests;
+
+use serde::Serialize;
 use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 
+use crate::config::DatabaseConfig;
+
 /// Create a database connection pool
-pub async fn create_pool(database_url: &str) -> Result<PgPool, sqlx::Error> {
+pub async fn create_pool(database_url: &str, max_connections: u32) -> Result<PgPool, sqlx::Error> {
     PgPoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
+        // Every acquire is reported for metrics::AcquireLayer
+        .acquire_time_level(log::LevelFilter::Trace)
         .connect(database_url)
         .await
 }
 
+/// Separate pools so admin lists, exports and the partner API can't starve
+/// SMS command queries of connections. Everything that writes, or must see
+/// its own writes, uses `write`; `read` may point at a replica.
+#[derive(Clone, Debug)]
+pub struct DbPools {
+    pub write: PgPool,
+    pub read: PgPool,
+}
+
+/// Point-in-time connection counts for one pool
+#[derive(Debug, Clone, Serialize)]
+pub struct PoolMetrics {
+    pub pool: &'static str,
+    pub max_connections: u32,
+    /// Open connections, idle or in use
+    pub size: u32,
+    pub idle: usize,
+    pub in_use: usize,
+}
+
+impl DbPools {
+    /// Connect both pools; the read pool uses `DATABASE_READ_URL` when set
+    pub async fn connect(database_url: &str, config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        metrics::set_slow_query_threshold(std::time::Duration::from_millis(config.slow_query_ms));
+        let write = create_pool(database_url, config.write_pool_size).await?;
+        let read = create_pool(read_url(database_url, config), config.read_pool_size).await?;
+        Ok(Self { write, read })
+    }
+
+    pub fn metrics(&self) -> Vec<PoolMetrics> {
+        [("write", &self.write), ("read", &self.read)]
+            .into_iter()
+            .map(|(name, pool)| {
+                let size = pool.size();
+                let idle = pool.num_idle();
+                PoolMetrics {
+                    pool: name,
+                    max_connections: pool.options().get_max_connections(),
+                    size,
+                    idle,
+                    in_use: (size as usize).saturating_sub(idle),
+                }
+            })
+            .collect()
+    }
+}
+
+/// `DATABASE_READ_URL`, or the primary when no replica is configured
+fn read_url<'a>(database_url: &'a str, config: &'a DatabaseConfig) -> &'a str {
+    match config.read_url.trim() {
+        "" => database_url,
+        url => url,
+    }
+}
+
 /// Run database migrations
 pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     tracing::info!("Creating users table...");
@@ -47,6 +193,44 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // Field-level encryption: `phone` holds a blind index, the number itself
+    // is sealed in `phone_encrypted`
+    sqlx::query("ALTER TABLE users ALTER COLUMN phone TYPE VARCHAR(80)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS phone_encrypted TEXT")
+        .execute(pool)
+        .await?;
+
+    // Last inbound command, for activity-based broadcast segments
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS last_active_at TIMESTAMP WITH TIME ZONE")
+        .execute(pool)
+        .await?;
+
+    // Admin user search: blind indexes of each phone prefix (filled in by
+    // `backfill_phone_prefixes` for older rows), plus the other filter and
+    // sort columns
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS phone_prefixes TEXT[]")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_phone_prefixes ON users USING GIN (phone_prefixes)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_ens_name_lower ON users (LOWER(ens_name) text_pattern_ops)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_wallet_lower ON users (LOWER(wallet_address))")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_created_at ON users (created_at, id)")
+        .execute(pool)
+        .await?;
+
     tracing::info!("Creating vouchers table...");
     // Vouchers table
     sqlx::query(
@@ -64,15 +248,52 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
+    // Local-currency denomination, fixed at creation time
+    sqlx::query(
+        "ALTER TABLE vouchers
+            ADD COLUMN IF NOT EXISTS local_currency VARCHAR(3),
+            ADD COLUMN IF NOT EXISTS local_amount DOUBLE PRECISION,
+            ADD COLUMN IF NOT EXISTS fx_rate DOUBLE PRECISION",
+    )
+    .execute(pool)
+    .await?;
+
+    // Admin batch, for printing a batch's cards
+    sqlx::query("ALTER TABLE vouchers ADD COLUMN IF NOT EXISTS batch_id UUID")
+        .execute(pool)
+        .await?;
+
     tracing::info!("Creating indices for vouchers...");
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_vouchers_code ON vouchers(code)")
         .execute(pool)
         .await?;
     
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_vouchers_batch ON vouchers(batch_id)")
+        .execute(pool)
+        .await?;
+
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_vouchers_status ON vouchers(status)")
         .execute(pool)
         .await?;
 
+    // Seeded batches: codes derived from the seed, rows written on first use
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS voucher_seeds (
+            id UUID PRIMARY KEY,
+            prefix VARCHAR(8) UNIQUE NOT NULL,
+            seed VARCHAR(64) NOT NULL,
+            count BIGINT NOT NULL,
+            usdc_amount BIGINT NOT NULL,
+            expires_at TIMESTAMP WITH TIME ZONE,
+            local_currency VARCHAR(3),
+            local_amount DOUBLE PRECISION,
+            fx_rate DOUBLE PRECISION,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
     tracing::info!("Creating deposits table...");
     // Deposits table
     sqlx::query(
@@ -98,6 +319,32 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
+    // Confirmation tracking: on-chain deposits count once their block is deep
+    // enough and are reversed if a reorg drops them
+    sqlx::query(
+        "ALTER TABLE deposits
+            ADD COLUMN IF NOT EXISTS status VARCHAR(20) NOT NULL DEFAULT 'confirmed',
+            ADD COLUMN IF NOT EXISTS token VARCHAR(20),
+            ADD COLUMN IF NOT EXISTS block_number BIGINT,
+            ADD COLUMN IF NOT EXISTS block_hash VARCHAR(66),
+            ADD COLUMN IF NOT EXISTS confirmations INTEGER NOT NULL DEFAULT 0,
+            ADD COLUMN IF NOT EXISTS confirmed_at TIMESTAMP WITH TIME ZONE,
+            ADD COLUMN IF NOT EXISTS reversed_at TIMESTAMP WITH TIME ZONE",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_deposits_onchain_tx
+            ON deposits(chain, source_ref, token) WHERE source = 'onchain'",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_deposits_pending ON deposits(created_at) WHERE status = 'pending'")
+        .execute(pool)
+        .await?;
+
     tracing::info!("Creating address_book table...");
     // Address book table
     sqlx::query(
@@ -113,20 +360,33 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
     .execute(pool)
     .await?;
 
-    tracing::info!("Creating indices for address_book...");
-    // Ensure unique constraint exists (using index for flexibility with nulls)
+    // Field-level encryption: contact phone/address are sealed and looked up
+    // (and kept unique) through blind index columns
+    tracing::info!("Adding encrypted field columns to address_book...");
     sqlx::query(
-        "CREATE UNIQUE INDEX IF NOT EXISTS idx_address_book_entries 
-         ON address_book (user_phone, COALESCE(contact_phone, ''), COALESCE(wallet_address, ''))"
+        "ALTER TABLE address_book
+            ALTER COLUMN user_phone TYPE VARCHAR(80),
+            ALTER COLUMN contact_phone TYPE TEXT,
+            ALTER COLUMN wallet_address TYPE TEXT,
+            ADD COLUMN IF NOT EXISTS contact_phone_index VARCHAR(80),
+            ADD COLUMN IF NOT EXISTS wallet_address_index VARCHAR(80)"
     )
     .execute(pool)
     .await?;
 
-    // Fix column size if it was created with VARCHAR(20)
-    // We ignore error if it fails (e.g. DB doesn't support generic ALTER or already done)
-    let _ = sqlx::query("ALTER TABLE address_book ALTER COLUMN contact_phone TYPE VARCHAR(50)")
+    tracing::info!("Creating indices for address_book...");
+    // Superseded by the blind-index based unique index below
+    sqlx::query("DROP INDEX IF EXISTS idx_address_book_entries")
         .execute(pool)
-        .await;
+        .await?;
+
+    // Ensure unique constraint exists (using index for flexibility with nulls)
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_address_book_entries_v2
+         ON address_book (user_phone, COALESCE(contact_phone_index, ''), COALESCE(wallet_address_index, ''))"
+    )
+    .execute(pool)
+    .await?;
 
     sqlx::query("CREATE INDEX IF NOT EXISTS idx_address_book_user ON address_book(user_phone)")
         .execute(pool)
@@ -136,7 +396,1062 @@ pub async fn run_migrations(pool: &PgPool) -> Result<(), sqlx::Error> {
         .execute(pool)
         .await?;
 
-    tracing::info!("Database migrations completed");
-    Ok(())
-}
+    tracing::info!("Creating onboarding_sessions table...");
+    // Onboarding sessions table (resumable signup conversation state)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS onboarding_sessions (
+            phone VARCHAR(20) PRIMARY KEY,
+            step VARCHAR(20) NOT NULL,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating sms_opt_outs table...");
+    // SMS opt-outs (STOP/UNSTOP carrier compliance)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS sms_opt_outs (
+            phone VARCHAR(20) PRIMARY KEY,
+            keyword VARCHAR(20) NOT NULL,
+            opted_out_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating sms_spend_daily table...");
+    // Outbound SMS spend per day and calling code (budget tracking)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS sms_spend_daily (
+            day DATE NOT NULL,
+            country_code VARCHAR(8) NOT NULL,
+            messages BIGINT NOT NULL DEFAULT 0,
+            segments BIGINT NOT NULL DEFAULT 0,
+            cost DOUBLE PRECISION NOT NULL DEFAULT 0,
+            PRIMARY KEY (day, country_code)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating payment_links table...");
+    // Short links for REQUEST (EIP-681 payment URIs)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS payment_links (
+            code VARCHAR(16) PRIMARY KEY,
+            phone VARCHAR(20) NOT NULL,
+            uri TEXT NOT NULL,
+            visits INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating WalletConnect tables...");
+    // WalletConnect pairings (CONNECT) and dApp requests awaiting SMS approval
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS wc_sessions (
+            topic VARCHAR(64) PRIMARY KEY,
+            phone VARCHAR(20) NOT NULL,
+            status VARCHAR(10) NOT NULL,
+            peer_name VARCHAR(100),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS wc_requests (
+            id UUID PRIMARY KEY,
+            topic VARCHAR(64) NOT NULL REFERENCES wc_sessions(topic),
+            phone VARCHAR(20) NOT NULL,
+            rpc_id BIGINT NOT NULL,
+            method VARCHAR(40) NOT NULL,
+            params TEXT NOT NULL,
+            status VARCHAR(10) NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_wc_requests_pending ON wc_requests(phone, status)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating feature_flags table...");
+    // Operator overrides of the FEATURES_DISABLED defaults
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS feature_flags (
+            feature VARCHAR(40) PRIMARY KEY,
+            enabled BOOLEAN NOT NULL,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating token_overrides table...");
+    // Operator overrides of built-in / TOKEN_ADDRESSES contract addresses
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS token_overrides (
+            chain VARCHAR(20) NOT NULL,
+            symbol VARCHAR(20) NOT NULL,
+            address VARCHAR(42) NOT NULL,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            PRIMARY KEY (chain, symbol)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating sms_transcripts table...");
+    // Opt-in support transcripts, purged after TRANSCRIPT_RETENTION_DAYS
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS sms_transcripts (
+            id BIGSERIAL PRIMARY KEY,
+            phone VARCHAR(128) NOT NULL,
+            direction VARCHAR(3) NOT NULL,
+            body TEXT NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sms_transcripts_phone ON sms_transcripts(phone, created_at)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating sms_outbox table...");
+    // Non-urgent notifications held until the end of the recipient's quiet hours
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS sms_outbox (
+            id UUID PRIMARY KEY,
+            phone VARCHAR(20) NOT NULL,
+            body TEXT NOT NULL,
+            send_after TIMESTAMP WITH TIME ZONE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sms_outbox_due ON sms_outbox(send_after)")
+        .execute(pool)
+        .await?;
+
+    // Set while the message waits out a carrier outage in that calling code
+    sqlx::query("ALTER TABLE sms_outbox ADD COLUMN IF NOT EXISTS outage_code VARCHAR(4)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_sms_outbox_outage ON sms_outbox(outage_code) WHERE outage_code IS NOT NULL")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating audit_log table...");
+    // Hash-chained audit trail of state-changing commands and admin actions
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            seq BIGSERIAL PRIMARY KEY,
+            actor VARCHAR(80) NOT NULL,
+            action VARCHAR(32) NOT NULL,
+            detail TEXT NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            prev_hash CHAR(64) NOT NULL,
+            hash CHAR(64) NOT NULL UNIQUE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+         BEGIN
+             RAISE EXCEPTION 'audit_log is append-only';
+         END;
+         $$ LANGUAGE plpgsql",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("DROP TRIGGER IF EXISTS audit_log_append_only ON audit_log")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "CREATE TRIGGER audit_log_append_only BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+         FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only()",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating beta access tables...");
+    // Beta launch mode: who may sign up, invite codes and the waitlist
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS beta_allowlist (
+            phone VARCHAR(20) PRIMARY KEY,
+            note TEXT,
+            added_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS beta_invites (
+            code VARCHAR(20) PRIMARY KEY,
+            max_uses INTEGER NOT NULL,
+            uses INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS beta_waitlist (
+            phone VARCHAR(20) PRIMARY KEY,
+            joined_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            admitted_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_beta_waitlist_joined ON beta_waitlist(joined_at)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating email_links table...");
+    // Email addresses verified to act for a phone; phones encrypted + blind indexed
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS email_links (
+            email VARCHAR(254) PRIMARY KEY,
+            phone TEXT NOT NULL,
+            phone_index VARCHAR(128) NOT NULL,
+            code VARCHAR(12),
+            code_expires_at TIMESTAMP WITH TIME ZONE,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            verified_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_email_links_phone ON email_links(phone_index)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating admin_idempotency table...");
+    // Responses to admin mutations sent with an Idempotency-Key, replayed on retry
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS admin_idempotency (
+            key VARCHAR(255) PRIMARY KEY,
+            fingerprint CHAR(64) NOT NULL,
+            status SMALLINT,
+            content_type TEXT,
+            body BYTEA,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            completed_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating broadcasts table...");
+    // Admin bulk SMS, with delivery counts per broadcast
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS broadcasts (
+            id UUID PRIMARY KEY,
+            template TEXT NOT NULL,
+            segment TEXT NOT NULL,
+            status VARCHAR(20) NOT NULL,
+            recipients INTEGER NOT NULL,
+            sent INTEGER NOT NULL DEFAULT 0,
+            deferred INTEGER NOT NULL DEFAULT 0,
+            failed INTEGER NOT NULL DEFAULT 0,
+            excluded INTEGER NOT NULL DEFAULT 0,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+            started_at TIMESTAMP WITH TIME ZONE,
+            finished_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating campaign tables...");
+    // Partner budgets for ENS mint gas, and the mints charged to them
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS campaigns (
+            id UUID PRIMARY KEY,
+            name TEXT NOT NULL,
+            partner TEXT NOT NULL,
+            gas_budget_gwei BIGINT NOT NULL,
+            gas_spent_gwei BIGINT NOT NULL DEFAULT 0,
+            gas_used BIGINT NOT NULL DEFAULT 0,
+            mints INTEGER NOT NULL DEFAULT 0,
+            active BOOLEAN NOT NULL DEFAULT TRUE,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS campaign_mints (
+            id UUID PRIMARY KEY,
+            campaign_id UUID NOT NULL REFERENCES campaigns(id),
+            ens_name TEXT NOT NULL,
+            tx_hash VARCHAR(66),
+            gas_used BIGINT NOT NULL,
+            cost_gwei BIGINT NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Mints of gifted names are charged to the campaign but made on the giver's behalf
+    sqlx::query("ALTER TABLE campaign_mints ADD COLUMN IF NOT EXISTS gifted_by UUID REFERENCES users(id)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating name gifts table...");
+    // GIFT NAME offers; one waiting offer per recipient
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS name_gifts (
+            id UUID PRIMARY KEY,
+            giver_id UUID NOT NULL REFERENCES users(id),
+            recipient_id UUID NOT NULL REFERENCES users(id),
+            label VARCHAR(20) NOT NULL,
+            status VARCHAR(20) NOT NULL,
+            campaign_id UUID REFERENCES campaigns(id),
+            tx_hash VARCHAR(66),
+            gas_used BIGINT,
+            cost_gwei BIGINT,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            resolved_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_name_gifts_waiting ON name_gifts(recipient_id)
+         WHERE status IN ('pending', 'minting')",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_name_gifts_giver ON name_gifts(giver_id, created_at)")
+        .execute(pool)
+        .await?;
 
+    tracing::info!("Creating savings tables...");
+    // Vault shares per user for SAVE / UNSAVE, and the USDC put in
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS savings (
+            user_id UUID NOT NULL REFERENCES users(id),
+            chain_id BIGINT NOT NULL,
+            vault VARCHAR(42) NOT NULL,
+            shares NUMERIC(78, 0) NOT NULL DEFAULT 0,
+            principal BIGINT NOT NULL DEFAULT 0,
+            updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (user_id, vault)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating partner API tables...");
+    // Hashed partner keys with their scopes and limits
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS partner_api_keys (
+            id UUID PRIMARY KEY,
+            partner TEXT NOT NULL,
+            key_hash VARCHAR(64) NOT NULL UNIQUE,
+            key_prefix VARCHAR(16) NOT NULL,
+            scopes TEXT NOT NULL,
+            rate_per_minute INTEGER NOT NULL DEFAULT 60,
+            daily_quota INTEGER NOT NULL DEFAULT 10000,
+            active BOOLEAN NOT NULL DEFAULT TRUE,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            last_used_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Requests per key, UTC day and endpoint, for quotas and admin metering
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS partner_api_usage (
+            key_id UUID NOT NULL REFERENCES partner_api_keys(id),
+            day DATE NOT NULL,
+            endpoint VARCHAR(32) NOT NULL,
+            requests INTEGER NOT NULL DEFAULT 0,
+            rejected INTEGER NOT NULL DEFAULT 0,
+            amount BIGINT NOT NULL DEFAULT 0,
+            PRIMARY KEY (key_id, day, endpoint)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // One deposit per partner reference, so retries are not credited twice
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS partner_deposits (
+            id UUID PRIMARY KEY,
+            key_id UUID NOT NULL REFERENCES partner_api_keys(id),
+            reference VARCHAR(100) NOT NULL,
+            user_id UUID NOT NULL REFERENCES users(id),
+            amount BIGINT NOT NULL,
+            transfer_id UUID,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            UNIQUE (key_id, reference)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Guardian changes, newest effective row wins (NULL phone = removed)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS transfer_guardians (
+            id BIGSERIAL PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id),
+            guardian_phone VARCHAR(20),
+            effective_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_transfer_guardians_user ON transfer_guardians(user_id, effective_at DESC)")
+        .execute(pool)
+        .await?;
+
+    // Large SENDs held until the sender and their guardian (or the cooling period) approve
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS pending_transfers (
+            id UUID PRIMARY KEY,
+            sender_id UUID NOT NULL REFERENCES users(id),
+            sender_phone VARCHAR(20) NOT NULL,
+            token VARCHAR(10) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            recipient VARCHAR(255) NOT NULL,
+            recipient_address VARCHAR(42) NOT NULL,
+            sender_code VARCHAR(6) NOT NULL,
+            approver_phone VARCHAR(20),
+            approver_code VARCHAR(6),
+            sender_confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+            status VARCHAR(10) NOT NULL DEFAULT 'pending',
+            not_before TIMESTAMP WITH TIME ZONE NOT NULL,
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            approved_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_pending_transfers_pending ON pending_transfers(status, sender_phone, approver_phone)")
+        .execute(pool)
+        .await?;
+
+    // Twilio Lookup results behind per-carrier command metrics
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS phone_carriers (
+            phone VARCHAR(20) PRIMARY KEY,
+            carrier VARCHAR(100),
+            line_type VARCHAR(20),
+            looked_up_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Subdomain labels that can't be minted, managed at /admin/reserved-names
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS reserved_names (
+            label VARCHAR(100) PRIMARY KEY,
+            kind VARCHAR(10) NOT NULL DEFAULT 'reserved',
+            reason TEXT,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Per-user HD deposit addresses and the sweeps consolidating them into the treasury
+    sqlx::query("CREATE SEQUENCE IF NOT EXISTS deposit_address_index START 1")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS deposit_addresses (
+            user_phone VARCHAR(20) PRIMARY KEY,
+            derivation_index BIGINT NOT NULL UNIQUE,
+            address VARCHAR(42) NOT NULL UNIQUE,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS deposit_sweeps (
+            id UUID PRIMARY KEY,
+            chain VARCHAR(20) NOT NULL,
+            token VARCHAR(20) NOT NULL,
+            address VARCHAR(42) NOT NULL,
+            amount VARCHAR(80) NOT NULL,
+            tx_hash VARCHAR(66) NOT NULL,
+            gas_tx_hash VARCHAR(66),
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "ALTER TABLE deposits
+            ADD COLUMN IF NOT EXISTS deposit_address VARCHAR(42),
+            ADD COLUMN IF NOT EXISTS sweep_id UUID",
+    )
+    .execute(pool)
+    .await?;
+    sqlx::query(
+        "CREATE INDEX IF NOT EXISTS idx_deposits_unswept ON deposits(deposit_address)
+         WHERE deposit_address IS NOT NULL AND sweep_id IS NULL",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating ledger tables...");
+    // Custodial ledger: running balances plus one entry per side of each transfer
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS ledger_balances (
+            account VARCHAR(80) PRIMARY KEY,
+            balance BIGINT NOT NULL DEFAULT 0,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS ledger_entries (
+            id UUID PRIMARY KEY,
+            transfer_id UUID NOT NULL,
+            account VARCHAR(80) NOT NULL,
+            delta BIGINT NOT NULL,
+            kind VARCHAR(30) NOT NULL,
+            reference VARCHAR(100),
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_ledger_entries_account ON ledger_entries(account, created_at)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating agents tables...");
+    // Cash agents (float lives on the ledger as agent:<id>)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS agents (
+            id UUID PRIMARY KEY,
+            phone VARCHAR(20) UNIQUE NOT NULL,
+            name VARCHAR(100) NOT NULL,
+            active BOOLEAN NOT NULL DEFAULT TRUE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS agent_cash_requests (
+            id UUID PRIMARY KEY,
+            agent_id UUID NOT NULL REFERENCES agents(id),
+            agent_phone VARCHAR(20) NOT NULL,
+            customer_id UUID NOT NULL,
+            customer_phone VARCHAR(20) NOT NULL,
+            kind VARCHAR(10) NOT NULL,
+            amount BIGINT NOT NULL,
+            agent_code VARCHAR(6) NOT NULL,
+            customer_code VARCHAR(6) NOT NULL,
+            agent_confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+            customer_confirmed BOOLEAN NOT NULL DEFAULT FALSE,
+            status VARCHAR(10) NOT NULL DEFAULT 'pending',
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            settled_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_agent_cash_requests_pending ON agent_cash_requests(status, agent_phone, customer_phone)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating balance_alerts table...");
+    // ALERT settings; users without a row get the defaults
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS balance_alerts (
+            user_id UUID PRIMARY KEY REFERENCES users(id),
+            below BIGINT,
+            deposit_notifications BOOLEAN NOT NULL DEFAULT TRUE,
+            updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating KYC tables...");
+    // Onboarding tier: 0 receive only, 1 phone verified, 2 ID checked
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS kyc_tier SMALLINT NOT NULL DEFAULT 0")
+        .execute(pool)
+        .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS kyc_tier_changes (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id),
+            from_tier SMALLINT NOT NULL,
+            to_tier SMALLINT NOT NULL,
+            reason TEXT NOT NULL,
+            changed_by VARCHAR(40) NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_kyc_tier_changes_user ON kyc_tier_changes(user_id, created_at)")
+        .execute(pool)
+        .await?;
+
+    // Outstanding VERIFY codes, one per user
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS kyc_phone_codes (
+            user_id UUID PRIMARY KEY REFERENCES users(id),
+            code VARCHAR(6) NOT NULL,
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            attempts INT NOT NULL DEFAULT 0
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating statement_links table...");
+    // Short-lived links to full STATEMENT downloads
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS statement_links (
+            token VARCHAR(32) PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id),
+            month DATE NOT NULL,
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating kill_switch table...");
+    // Emergency stop for money-moving commands; a single row read by every instance
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS kill_switch (
+            id SMALLINT PRIMARY KEY CHECK (id = 1),
+            engaged BOOLEAN NOT NULL,
+            reason TEXT NOT NULL,
+            changed_by VARCHAR(80) NOT NULL,
+            changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    tracing::info!("Creating ens_checks table...");
+    // Last on-chain check of each user's stored ENS name
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS ens_checks (
+            user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+            ens_name VARCHAR(255) NOT NULL,
+            wallet_address VARCHAR(42) NOT NULL,
+            status VARCHAR(20) NOT NULL,
+            resolved_address VARCHAR(42),
+            error TEXT,
+            checked_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_ens_checks_status ON ens_checks (status, checked_at)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating send_jobs table...");
+    // SENDs held for their cancellation window before broadcast
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS send_jobs (
+            id UUID PRIMARY KEY,
+            sender_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            token VARCHAR(10) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            recipient VARCHAR(255) NOT NULL,
+            recipient_address VARCHAR(42) NOT NULL,
+            status VARCHAR(10) NOT NULL DEFAULT 'queued',
+            execute_after TIMESTAMP WITH TIME ZONE NOT NULL,
+            reply TEXT,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            started_at TIMESTAMP WITH TIME ZONE,
+            finished_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_send_jobs_due ON send_jobs (status, execute_after)")
+        .execute(pool)
+        .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_send_jobs_sender ON send_jobs (sender_id, created_at DESC)")
+        .execute(pool)
+        .await?;
+
+    // With PHONE_STORAGE=hashed, deposits keep the blind index of the number
+    tracing::info!("Widening deposits.user_phone for hashed phones...");
+    sqlx::query("ALTER TABLE deposits ALTER COLUMN user_phone TYPE VARCHAR(80)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating chains table...");
+    // EVM chains added at runtime (see wallet::chain_registry); tokens are
+    // SYMBOL=address entries
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS chains (
+            chain_id BIGINT PRIMARY KEY,
+            name VARCHAR(50) NOT NULL,
+            short_code VARCHAR(20) NOT NULL,
+            native_token VARCHAR(10) NOT NULL,
+            rpc_urls TEXT[] NOT NULL,
+            explorer_url TEXT,
+            testnet BOOLEAN NOT NULL DEFAULT FALSE,
+            confirmations BIGINT NOT NULL,
+            tokens TEXT[] NOT NULL DEFAULT '{}',
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // NOTIFY: all, digest or off for deposits and receipts
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS notify_mode VARCHAR(10) NOT NULL DEFAULT 'all'")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating notification_digest table...");
+    // Notices held for NOTIFY DIGEST users until the daily summary
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS notification_digest (
+            id BIGSERIAL PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            body TEXT NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            sent_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_notification_digest_unsent ON notification_digest (user_id, created_at) WHERE sent_at IS NULL")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating flow_sessions table...");
+    // Multi-step SMS flows (onboarding, guided SEND); one per number
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS flow_sessions (
+            phone VARCHAR(64) PRIMARY KEY,
+            flow VARCHAR(32) NOT NULL,
+            step VARCHAR(32) NOT NULL,
+            data TEXT NOT NULL DEFAULT '{}',
+            updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Unfinished signups from onboarding_sessions carry on as onboarding flows
+    sqlx::query(
+        "WITH moved AS (DELETE FROM onboarding_sessions RETURNING phone, step, updated_at)
+         INSERT INTO flow_sessions (phone, flow, step, data, updated_at)
+         SELECT phone, 'onboarding', step, '{}', updated_at FROM moved WHERE step <> 'complete'
+         ON CONFLICT (phone) DO NOTHING",
+    )
+    .execute(pool)
+    .await?;
+
+    // Partner webhooks and signed partner requests
+    sqlx::query("ALTER TABLE partner_api_keys ADD COLUMN IF NOT EXISTS webhook_url TEXT")
+        .execute(pool)
+        .await?;
+    sqlx::query("ALTER TABLE partner_api_keys ADD COLUMN IF NOT EXISTS webhook_secret TEXT")
+        .execute(pool)
+        .await?;
+    sqlx::query("ALTER TABLE partner_api_keys ADD COLUMN IF NOT EXISTS signed_requests BOOLEAN NOT NULL DEFAULT FALSE")
+        .execute(pool)
+        .await?;
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS partner_request_nonces (
+            key_id UUID NOT NULL REFERENCES partner_api_keys(id) ON DELETE CASCADE,
+            nonce VARCHAR(64) NOT NULL,
+            seen_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            PRIMARY KEY (key_id, nonce)
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Merchant bills (BILL / PAY)
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS bills (
+            id UUID PRIMARY KEY,
+            code VARCHAR(12) NOT NULL UNIQUE,
+            merchant_id UUID NOT NULL REFERENCES users(id),
+            amount BIGINT NOT NULL,
+            token VARCHAR(10) NOT NULL,
+            memo VARCHAR(60) NOT NULL,
+            status VARCHAR(20) NOT NULL,
+            payer_id UUID REFERENCES users(id),
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+            paid_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_bills_merchant ON bills(merchant_id, created_at)")
+        .execute(pool)
+        .await?;
+
+    // Transfers checked for repeated SENDs
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS recent_sends (
+            id UUID PRIMARY KEY,
+            sender_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            token VARCHAR(10) NOT NULL,
+            amount DOUBLE PRECISION NOT NULL,
+            recipient VARCHAR(255) NOT NULL,
+            recipient_address VARCHAR(42) NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_recent_sends_sender ON recent_sends(sender_id, created_at)")
+        .execute(pool)
+        .await?;
+
+    // Progress of one-off fixes to historical rows
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS data_migrations (
+            name VARCHAR(60) PRIMARY KEY,
+            status VARCHAR(20) NOT NULL,
+            cursor TEXT,
+            scanned BIGINT NOT NULL DEFAULT 0,
+            changed BIGINT NOT NULL DEFAULT 0,
+            failed BIGINT NOT NULL DEFAULT 0,
+            started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            completed_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // Anti-phishing greeting shown on sensitive messages, encrypted
+    sqlx::query("ALTER TABLE users ADD COLUMN IF NOT EXISTS greeting TEXT")
+        .execute(pool)
+        .await?;
+
+    // ENS names users sell to each other
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS name_listings (
+            id UUID PRIMARY KEY,
+            label VARCHAR(63) NOT NULL,
+            seller_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            price BIGINT NOT NULL,
+            status VARCHAR(20) NOT NULL,
+            buyer_id UUID REFERENCES users(id),
+            tx_hash VARCHAR(66),
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            sold_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_name_listings_open ON name_listings(label)
+         WHERE status IN ('listed', 'selling')",
+    )
+    .execute(pool)
+    .await?;
+
+    // First words of messages that weren't a command
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS unrecognized_commands (
+            word VARCHAR(20) PRIMARY KEY,
+            example VARCHAR(40) NOT NULL,
+            suggestion VARCHAR(20),
+            count BIGINT NOT NULL DEFAULT 1,
+            first_seen TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            last_seen TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    // UNLOCK opens a saved address to withdrawals before its cooldown ends
+    sqlx::query("ALTER TABLE address_book ADD COLUMN IF NOT EXISTS withdrawal_unlock_at TIMESTAMP WITH TIME ZONE")
+        .execute(pool)
+        .await?;
+
+    // SEND USDC to an outside address: paid on chain, refunded if it fails
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS cash_payouts (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES users(id),
+            amount BIGINT NOT NULL,
+            chain VARCHAR(20) NOT NULL,
+            to_address VARCHAR(42) NOT NULL,
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            transfer_id UUID NOT NULL,
+            tx_hash VARCHAR(66),
+            refund_transfer_id UUID,
+            reason TEXT,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_cash_payouts_open ON cash_payouts(created_at) WHERE status IN ('pending', 'sent')")
+        .execute(pool)
+        .await?;
+
+    // INVITE referral codes and the sign-ups made with them; phones and
+    // number blocks are blind indexes
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS referral_codes (
+            code VARCHAR(24) PRIMARY KEY,
+            user_id UUID NOT NULL UNIQUE REFERENCES users(id),
+            phone_block VARCHAR(80) NOT NULL,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS referrals (
+            id UUID PRIMARY KEY,
+            code VARCHAR(24) NOT NULL REFERENCES referral_codes(code),
+            referrer_id UUID NOT NULL REFERENCES users(id),
+            referee_phone VARCHAR(80) NOT NULL UNIQUE,
+            referee_id UUID REFERENCES users(id),
+            referee_block VARCHAR(80),
+            status VARCHAR(20) NOT NULL DEFAULT 'pending',
+            reason VARCHAR(40),
+            referrer_bonus BIGINT NOT NULL DEFAULT 0,
+            referee_bonus BIGINT NOT NULL DEFAULT 0,
+            referrer_transfer_id UUID,
+            referee_transfer_id UUID,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+            settled_at TIMESTAMP WITH TIME ZONE
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_referrals_referrer ON referrals(referrer_id, status)")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Creating spam_samples table...");
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS spam_samples (
+            id BIGSERIAL PRIMARY KEY,
+            phone VARCHAR(80) NOT NULL,
+            phone_encrypted TEXT,
+            body TEXT NOT NULL,
+            score DOUBLE PRECISION NOT NULL,
+            action VARCHAR(10) NOT NULL,
+            reasons TEXT NOT NULL DEFAULT '',
+            review VARCHAR(10),
+            reviewed_at TIMESTAMP WITH TIME ZONE,
+            created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_spam_samples_pending ON spam_samples(created_at) WHERE review IS NULL")
+        .execute(pool)
+        .await?;
+
+    tracing::info!("Database migrations completed");
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(read_url: &str) -> DatabaseConfig {
+        DatabaseConfig {
+            url: "postgres://app@primary/textchain".into(),
+            write_pool_size: 8,
+            read_pool_size: 3,
+            read_url: read_url.into(),
+            slow_query_ms: 250,
+        }
+    }
+
+    #[test]
+    fn test_read_url_falls_back_to_primary() {
+        let primary = "postgres://app@primary/textchain";
+        assert_eq!(read_url(primary, &config("")), primary);
+        assert_eq!(read_url(primary, &config("  ")), primary);
+        assert_eq!(read_url(primary, &config("postgres://app@replica/textchain")), "postgres://app@replica/textchain");
+    }
+
+    #[tokio::test]
+    async fn test_metrics_per_pool() {
+        let lazy = |max: u32| PgPoolOptions::new().max_connections(max).connect_lazy("postgres://app@localhost/textchain").unwrap();
+        let pools = DbPools { write: lazy(8), read: lazy(3) };
+
+        let metrics = pools.metrics();
+        assert_eq!(metrics.iter().map(|m| m.pool).collect::<Vec<_>>(), ["write", "read"]);
+        assert_eq!(metrics[0].max_connections, 8);
+        assert_eq!(metrics[1].max_connections, 3);
+        // Nothing has connected yet
+        assert!(metrics.iter().all(|m| m.size == 0 && m.idle == 0 && m.in_use == 0));
+    }
+}
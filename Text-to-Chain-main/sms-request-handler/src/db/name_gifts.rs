@@ -0,0 +1,130 @@
+//! ENS names given by one user to another (GIFT NAME)
+//!
+//! A gift waits as `pending` until the recipient accepts or declines it, or
+//! it expires. Accepting claims it (`minting`) so a repeated GIFT ACCEPT
+//! can't mint twice; the mint then settles it as `accepted`, with the gas it
+//! cost, or puts it back to `pending` if the mint can be retried.
+
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::campaigns::MintCost;
+use super::metrics::QueryTimer;
+
+/// How long the recipient has to answer
+pub const GIFT_EXPIRY_HOURS: i64 = 72;
+
+/// A name offered to another user
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct NameGift {
+    pub id: Uuid,
+    pub giver_id: Uuid,
+    /// Label without `.ttcip.eth`
+    pub label: String,
+}
+
+/// Columns of `name_gifts` beyond these record the status (pending,
+/// minting, accepted, declined, expired or failed) and the mint's gas
+const NAME_GIFT_COLUMNS: &str = "id, giver_id, label";
+
+#[derive(Clone)]
+pub struct NameGiftRepository {
+    pool: PgPool,
+}
+
+impl NameGiftRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Offer a name; None if the recipient already has a gift waiting
+    pub async fn offer(&self, giver_id: Uuid, recipient_id: Uuid, label: &str) -> Result<Option<NameGift>, sqlx::Error> {
+        let _timer = QueryTimer::start("name_gifts.offer");
+        let mut tx = self.pool.begin().await?;
+        // Lapsed offers don't block a new one
+        sqlx::query("UPDATE name_gifts SET status = 'expired' WHERE recipient_id = $1 AND status = 'pending' AND expires_at <= NOW()")
+            .bind(recipient_id)
+            .execute(&mut *tx)
+            .await?;
+        let gift = sqlx::query_as::<_, NameGift>(&format!(
+            "INSERT INTO name_gifts (id, giver_id, recipient_id, label, status, expires_at)
+             VALUES ($1, $2, $3, $4, 'pending', $5)
+             ON CONFLICT (recipient_id) WHERE status IN ('pending', 'minting') DO NOTHING
+             RETURNING {}",
+            NAME_GIFT_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(giver_id)
+        .bind(recipient_id)
+        .bind(label)
+        .bind(Utc::now() + Duration::hours(GIFT_EXPIRY_HOURS))
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(gift)
+    }
+
+    /// Claim the recipient's waiting gift for minting (None = nothing waiting)
+    pub async fn claim(&self, recipient_id: Uuid) -> Result<Option<NameGift>, sqlx::Error> {
+        let _timer = QueryTimer::start("name_gifts.claim");
+        sqlx::query_as::<_, NameGift>(&format!(
+            "UPDATE name_gifts SET status = 'minting'
+             WHERE recipient_id = $1 AND status = 'pending' AND expires_at > NOW()
+             RETURNING {}",
+            NAME_GIFT_COLUMNS
+        ))
+        .bind(recipient_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Decline the recipient's waiting gift (None = nothing waiting)
+    pub async fn decline(&self, recipient_id: Uuid) -> Result<Option<NameGift>, sqlx::Error> {
+        let _timer = QueryTimer::start("name_gifts.decline");
+        sqlx::query_as::<_, NameGift>(&format!(
+            "UPDATE name_gifts SET status = 'declined', resolved_at = NOW()
+             WHERE recipient_id = $1 AND status = 'pending' AND expires_at > NOW()
+             RETURNING {}",
+            NAME_GIFT_COLUMNS
+        ))
+        .bind(recipient_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// A claimed gift was minted; the gas is attributed to the giver
+    pub async fn record_mint(&self, id: Uuid, campaign_id: Option<Uuid>, tx_hash: Option<&str>, cost: MintCost) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("name_gifts.record_mint");
+        sqlx::query(
+            "UPDATE name_gifts
+             SET status = 'accepted', campaign_id = $2, tx_hash = $3, gas_used = $4, cost_gwei = $5, resolved_at = NOW()
+             WHERE id = $1",
+        )
+        .bind(id)
+        .bind(campaign_id)
+        .bind(tx_hash)
+        .bind(cost.gas_used)
+        .bind(cost.cost_gwei)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// A claimed gift could not be minted: back to `pending` if it may be
+    /// retried, otherwise `failed`
+    pub async fn release(&self, id: Uuid, retry: bool) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("name_gifts.release");
+        sqlx::query(
+            "UPDATE name_gifts
+             SET status = CASE WHEN $2 THEN 'pending' ELSE 'failed' END,
+                 resolved_at = CASE WHEN $2 THEN NULL ELSE NOW() END
+             WHERE id = $1 AND status = 'minting'",
+        )
+        .bind(id)
+        .bind(retry)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
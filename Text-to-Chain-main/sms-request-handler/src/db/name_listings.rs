@@ -0,0 +1,197 @@
+//! ENS names listed for sale between users (SELL NAME / BUY NAME)
+//!
+//! A listing is `listed` until a buyer takes it. Buying moves the price from
+//! the buyer's cash balance into an escrow account and marks the listing
+//! `selling` in one transaction. Once the name points at the buyer on chain,
+//! `settle` pays the seller and moves the name between the two users' rows,
+//! again in one transaction; if the chain update failed, `refund` returns
+//! the money and lists the name again. A seller can take down a `listed`
+//! name (`withdrawn`). Only one listing per name is open at a time.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::ledger::{transfer_in, user_account, LedgerError};
+use super::metrics::QueryTimer;
+use crate::money::Money;
+
+/// Columns selected into `NameListing`
+const LISTING_COLUMNS: &str = "id, label, seller_id, price, status, buyer_id";
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct NameListing {
+    pub id: Uuid,
+    /// Subdomain label, without `.ttcip.eth`
+    pub label: String,
+    pub seller_id: Uuid,
+    pub price: Money,
+    /// listed, selling, sold, withdrawn
+    pub status: String,
+    pub buyer_id: Option<Uuid>,
+}
+
+impl NameListing {
+    pub fn full_name(&self) -> String {
+        format!("{}.ttcip.eth", self.label)
+    }
+
+    /// Where the buyer's money waits while the name changes hands
+    pub fn escrow_account(&self) -> String {
+        format!("escrow:name:{}", self.id)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NameSaleError {
+    #[error("Name is not listed")]
+    NotListed,
+    #[error("Insufficient balance")]
+    InsufficientFunds,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<LedgerError> for NameSaleError {
+    fn from(e: LedgerError) -> Self {
+        match e {
+            LedgerError::Database(e) => NameSaleError::Database(e),
+            LedgerError::InsufficientFunds | LedgerError::InvalidAmount => NameSaleError::InsufficientFunds,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NameListingRepository {
+    pool: PgPool,
+}
+
+impl NameListingRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// List a name, or change the price of the seller's open listing.
+    /// None when someone is buying it right now.
+    pub async fn list(&self, seller_id: Uuid, label: &str, price: Money) -> Result<Option<NameListing>, sqlx::Error> {
+        let _timer = QueryTimer::start("name_listings.list");
+        sqlx::query_as::<_, NameListing>(&format!(
+            "INSERT INTO name_listings (id, label, seller_id, price, status)
+             VALUES ($1, $2, $3, $4, 'listed')
+             ON CONFLICT (label) WHERE status IN ('listed', 'selling') DO UPDATE SET price = EXCLUDED.price
+             WHERE name_listings.status = 'listed' AND name_listings.seller_id = EXCLUDED.seller_id
+             RETURNING {}",
+            LISTING_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(label)
+        .bind(seller_id)
+        .bind(price)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Take down the seller's listing (None = nothing listed to take down)
+    pub async fn withdraw(&self, seller_id: Uuid, label: &str) -> Result<Option<NameListing>, sqlx::Error> {
+        let _timer = QueryTimer::start("name_listings.withdraw");
+        sqlx::query_as::<_, NameListing>(&format!(
+            "UPDATE name_listings SET status = 'withdrawn'
+             WHERE label = $1 AND seller_id = $2 AND status = 'listed'
+             RETURNING {}",
+            LISTING_COLUMNS
+        ))
+        .bind(label)
+        .bind(seller_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// The open listing for a name, listed or being sold
+    pub async fn find_open(&self, label: &str) -> Result<Option<NameListing>, sqlx::Error> {
+        let _timer = QueryTimer::start("name_listings.find_open");
+        sqlx::query_as::<_, NameListing>(&format!(
+            "SELECT {} FROM name_listings WHERE label = $1 AND status IN ('listed', 'selling')",
+            LISTING_COLUMNS
+        ))
+        .bind(label)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Claim a listed name for the buyer and move the price into escrow
+    pub async fn escrow(&self, id: Uuid, buyer_id: Uuid) -> Result<NameListing, NameSaleError> {
+        let _timer = QueryTimer::start("name_listings.escrow");
+        let mut tx = self.pool.begin().await?;
+        let listing = sqlx::query_as::<_, NameListing>(&format!(
+            "UPDATE name_listings SET status = 'selling', buyer_id = $2
+             WHERE id = $1 AND status = 'listed' AND seller_id <> $2
+             RETURNING {}",
+            LISTING_COLUMNS
+        ))
+        .bind(id)
+        .bind(buyer_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(NameSaleError::NotListed)?;
+
+        transfer_in(&mut tx, &user_account(buyer_id), &listing.escrow_account(), listing.price.micros(), "name_escrow", Some(&listing.label))
+            .await?;
+        tx.commit().await?;
+        Ok(listing)
+    }
+
+    /// The name now points at the buyer: pay the seller and move the name
+    /// from the seller's row to the buyer's
+    pub async fn settle(&self, listing: &NameListing, tx_hash: Option<&str>) -> Result<(), NameSaleError> {
+        let _timer = QueryTimer::start("name_listings.settle");
+        let buyer_id = listing.buyer_id.ok_or(NameSaleError::NotListed)?;
+        let mut tx = self.pool.begin().await?;
+        let settled = sqlx::query(
+            "UPDATE name_listings SET status = 'sold', tx_hash = $2, sold_at = NOW()
+             WHERE id = $1 AND status = 'selling'"
+        )
+        .bind(listing.id)
+        .bind(tx_hash)
+        .execute(&mut *tx)
+        .await?;
+        if settled.rows_affected() == 0 {
+            return Err(NameSaleError::NotListed);
+        }
+
+        transfer_in(&mut tx, &listing.escrow_account(), &user_account(listing.seller_id), listing.price.micros(), "name_sale", Some(&listing.label))
+            .await?;
+        sqlx::query("UPDATE users SET ens_name = NULL WHERE id = $1 AND ens_name = $2")
+            .bind(listing.seller_id)
+            .bind(listing.full_name())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE users SET ens_name = $2 WHERE id = $1")
+            .bind(buyer_id)
+            .bind(listing.full_name())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The name couldn't be moved: give the buyer their money back and list
+    /// the name again
+    pub async fn refund(&self, listing: &NameListing) -> Result<(), NameSaleError> {
+        let _timer = QueryTimer::start("name_listings.refund");
+        let buyer_id = listing.buyer_id.ok_or(NameSaleError::NotListed)?;
+        let mut tx = self.pool.begin().await?;
+        let reopened = sqlx::query(
+            "UPDATE name_listings SET status = 'listed', buyer_id = NULL WHERE id = $1 AND status = 'selling'"
+        )
+        .bind(listing.id)
+        .execute(&mut *tx)
+        .await?;
+        if reopened.rows_affected() == 0 {
+            return Err(NameSaleError::NotListed);
+        }
+
+        transfer_in(&mut tx, &listing.escrow_account(), &user_account(buyer_id), listing.price.micros(), "name_refund", Some(&listing.label))
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
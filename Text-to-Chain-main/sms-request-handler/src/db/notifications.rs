@@ -0,0 +1,125 @@
+//! Notification preferences (NOTIFY) and the daily digest queue
+//!
+//! `users.notify_mode` decides what happens to non-critical notices such as
+//! deposits and receipts: `all` texts them at once, `digest` queues them in
+//! `notification_digest` for the daily summary and `off` drops them.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::encryption::FieldCipher;
+use super::metrics::QueryTimer;
+
+/// How a user wants non-critical notifications
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotifyMode {
+    #[default]
+    All,
+    Digest,
+    Off,
+}
+
+impl NotifyMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotifyMode::All => "all",
+            NotifyMode::Digest => "digest",
+            NotifyMode::Off => "off",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode.trim().to_lowercase().as_str() {
+            "all" | "on" => Some(NotifyMode::All),
+            "digest" | "daily" => Some(NotifyMode::Digest),
+            "off" => Some(NotifyMode::Off),
+            _ => None,
+        }
+    }
+}
+
+/// A queued notice taken for a digest
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DigestItem {
+    pub user_id: Uuid,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct NotificationRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl NotificationRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    pub async fn mode(&self, user_id: Uuid) -> Result<NotifyMode, sqlx::Error> {
+        let _timer = QueryTimer::start("notifications.mode");
+        let mode = sqlx::query_scalar::<_, String>("SELECT notify_mode FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(mode.as_deref().and_then(NotifyMode::parse).unwrap_or_default())
+    }
+
+    /// Owner of `phone` and their mode (None for unknown numbers)
+    pub async fn mode_for_phone(&self, phone: &str) -> Result<Option<(Uuid, NotifyMode)>, sqlx::Error> {
+        let _timer = QueryTimer::start("notifications.mode_for_phone");
+        let row = sqlx::query_as::<_, (Uuid, String)>("SELECT id, notify_mode FROM users WHERE phone = ANY($1)")
+            .bind(self.cipher.lookup_keys(phone))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|(user_id, mode)| (user_id, NotifyMode::parse(&mode).unwrap_or_default())))
+    }
+
+    pub async fn set_mode(&self, user_id: Uuid, mode: NotifyMode) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("notifications.set_mode");
+        sqlx::query("UPDATE users SET notify_mode = $2 WHERE id = $1")
+            .bind(user_id)
+            .bind(mode.as_str())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Hold a notice for the user's next digest
+    pub async fn queue(&self, user_id: Uuid, body: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("notifications.queue");
+        sqlx::query("INSERT INTO notification_digest (user_id, body) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(body)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Notices waiting for the user's next digest
+    pub async fn pending(&self, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("notifications.pending");
+        sqlx::query_scalar("SELECT COUNT(*) FROM notification_digest WHERE user_id = $1 AND sent_at IS NULL")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Claim every unsent notice queued before `cutoff`, oldest first. Each
+    /// notice is claimed once, however many instances run the digest.
+    pub async fn take_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<DigestItem>, sqlx::Error> {
+        let _timer = QueryTimer::start("notifications.take_before");
+        let mut items = sqlx::query_as::<_, DigestItem>(
+            "UPDATE notification_digest SET sent_at = NOW()
+             WHERE sent_at IS NULL AND created_at < $1
+             RETURNING user_id, body, created_at"
+        )
+        .bind(cutoff)
+        .fetch_all(&self.pool)
+        .await?;
+        items.sort_by_key(|item| item.created_at);
+        Ok(items)
+    }
+}
@@ -0,0 +1,51 @@
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// SMS opt-out repository - numbers that replied STOP (or similar) and must
+/// not receive any further messages until they opt back in
+#[derive(Clone)]
+pub struct OptOutRepository {
+    pool: PgPool,
+}
+
+impl OptOutRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// All currently opted-out numbers
+    pub async fn list(&self) -> Result<Vec<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("opt_outs.list");
+        sqlx::query_scalar::<_, String>("SELECT phone FROM sms_opt_outs")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Record an opt-out and the keyword used
+    pub async fn opt_out(&self, phone: &str, keyword: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("opt_outs.opt_out");
+        sqlx::query(
+            r#"
+            INSERT INTO sms_opt_outs (phone, keyword, opted_out_at)
+            VALUES ($1, $2, NOW())
+            ON CONFLICT (phone) DO UPDATE SET keyword = EXCLUDED.keyword, opted_out_at = NOW()
+            "#
+        )
+        .bind(phone)
+        .bind(keyword)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Remove an opt-out
+    pub async fn opt_in(&self, phone: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("opt_outs.opt_in");
+        sqlx::query("DELETE FROM sms_opt_outs WHERE phone = $1")
+            .bind(phone)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
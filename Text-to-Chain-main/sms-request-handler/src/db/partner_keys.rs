@@ -0,0 +1,418 @@
+//! Partner API keys, their usage, and the deposits they credit
+//!
+//! Keys are shown once at creation; only a SHA-256 hash is stored. Money a
+//! partner credits comes out of its own `system:partner:<key id>` ledger
+//! account, which goes negative by the amount to settle with the partner.
+//! Webhook secrets (see `partner_webhooks`) are kept encrypted, since they
+//! have to be read back to sign.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::encryption::{decode_error, FieldCipher};
+use super::ledger::{transfer_in, user_account, LedgerError};
+use super::metrics::QueryTimer;
+
+/// Prefix on every partner key, so leaked keys are easy to spot
+const KEY_PREFIX: &str = "ttcpk_";
+/// Characters of a key kept for display
+const DISPLAY_PREFIX_LEN: usize = 12;
+/// Ledger kind for partner-credited deposits
+pub const PARTNER_DEPOSIT_KIND: &str = "partner_deposit";
+
+/// What a partner key may do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PartnerScope {
+    #[serde(rename = "deposit:create")]
+    DepositCreate,
+    #[serde(rename = "voucher:create")]
+    VoucherCreate,
+}
+
+impl PartnerScope {
+    pub const ALL: [PartnerScope; 2] = [PartnerScope::DepositCreate, PartnerScope::VoucherCreate];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PartnerScope::DepositCreate => "deposit:create",
+            PartnerScope::VoucherCreate => "voucher:create",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        PartnerScope::ALL.into_iter().find(|scope| scope.as_str() == name)
+    }
+}
+
+/// Ledger account a partner's credits are drawn from
+pub fn partner_account(key_id: Uuid) -> String {
+    format!("system:partner:{}", key_id)
+}
+
+fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PartnerKey {
+    pub id: Uuid,
+    pub partner: String,
+    /// Start of the key, for telling keys apart
+    pub key_prefix: String,
+    /// Comma-separated scope names
+    pub scopes: String,
+    /// Requests per minute (0 = unlimited)
+    pub rate_per_minute: i32,
+    /// Requests per UTC day (0 = unlimited)
+    pub daily_quota: i32,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// Where signed webhooks go
+    pub webhook_url: Option<String>,
+    /// Calls to `/partner/*` must be signed with the webhook secret
+    pub signed_requests: bool,
+}
+
+impl PartnerKey {
+    pub fn has_scope(&self, scope: PartnerScope) -> bool {
+        self.scopes.split(',').any(|name| PartnerScope::parse(name) == Some(scope))
+    }
+}
+
+/// One key's requests to one endpoint on one day
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PartnerUsage {
+    pub day: NaiveDate,
+    pub endpoint: String,
+    /// Requests that were served
+    pub requests: i32,
+    /// Requests refused for scope, rate or quota
+    pub rejected: i32,
+    /// Micro-USDC credited or issued
+    pub amount: i64,
+}
+
+/// A deposit credited by a partner
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PartnerDeposit {
+    pub id: Uuid,
+    pub key_id: Uuid,
+    /// The partner's own reference; one deposit per reference
+    pub reference: String,
+    pub user_id: Uuid,
+    /// Micro-USDC
+    pub amount: i64,
+    pub transfer_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PartnerDepositError {
+    #[error("Reference already used for a different deposit")]
+    ReferenceConflict,
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+}
+
+impl From<sqlx::Error> for PartnerDepositError {
+    fn from(e: sqlx::Error) -> Self {
+        PartnerDepositError::Ledger(LedgerError::Database(e))
+    }
+}
+
+const KEY_COLUMNS: &str =
+    "id, partner, key_prefix, scopes, rate_per_minute, daily_quota, active, created_at, last_used_at, webhook_url, signed_requests";
+const DEPOSIT_COLUMNS: &str = "id, key_id, reference, user_id, amount, transfer_id, created_at";
+
+#[derive(Clone)]
+pub struct PartnerKeyRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl PartnerKeyRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    /// Issue a key; the plaintext is returned only here
+    pub async fn create(
+        &self,
+        partner: &str,
+        scopes: &[PartnerScope],
+        rate_per_minute: i32,
+        daily_quota: i32,
+    ) -> Result<(PartnerKey, String), sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.create");
+        let mut secret = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let key = format!("{}{}", KEY_PREFIX, hex::encode(secret));
+        let scopes: Vec<&str> = scopes.iter().map(PartnerScope::as_str).collect();
+
+        let created = sqlx::query_as::<_, PartnerKey>(&format!(
+            "INSERT INTO partner_api_keys (id, partner, key_hash, key_prefix, scopes, rate_per_minute, daily_quota)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             RETURNING {}",
+            KEY_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(partner)
+        .bind(hash_key(&key))
+        .bind(&key[..DISPLAY_PREFIX_LEN])
+        .bind(scopes.join(","))
+        .bind(rate_per_minute)
+        .bind(daily_quota)
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((created, key))
+    }
+
+    /// The active key matching a presented secret
+    pub async fn authenticate(&self, key: &str) -> Result<Option<PartnerKey>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.authenticate");
+        sqlx::query_as::<_, PartnerKey>(&format!(
+            "UPDATE partner_api_keys SET last_used_at = NOW() WHERE key_hash = $1 AND active RETURNING {}",
+            KEY_COLUMNS
+        ))
+        .bind(hash_key(key.trim()))
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn list(&self) -> Result<Vec<PartnerKey>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.list");
+        sqlx::query_as::<_, PartnerKey>(&format!("SELECT {} FROM partner_api_keys ORDER BY created_at", KEY_COLUMNS))
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn find(&self, id: Uuid) -> Result<Option<PartnerKey>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.find");
+        sqlx::query_as::<_, PartnerKey>(&format!("SELECT {} FROM partner_api_keys WHERE id = $1", KEY_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Change a key's limits
+    pub async fn set_limits(&self, id: Uuid, rate_per_minute: i32, daily_quota: i32) -> Result<Option<PartnerKey>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.set_limits");
+        sqlx::query_as::<_, PartnerKey>(&format!(
+            "UPDATE partner_api_keys SET rate_per_minute = $2, daily_quota = $3 WHERE id = $1 RETURNING {}",
+            KEY_COLUMNS
+        ))
+        .bind(id)
+        .bind(rate_per_minute)
+        .bind(daily_quota)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Stop accepting a key; its history stays
+    pub async fn revoke(&self, id: Uuid) -> Result<Option<PartnerKey>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.revoke");
+        sqlx::query_as::<_, PartnerKey>(&format!(
+            "UPDATE partner_api_keys SET active = FALSE WHERE id = $1 RETURNING {}",
+            KEY_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Set where webhooks go and whether calls must be signed, under a new
+    /// webhook secret (`None` url = no webhooks)
+    pub async fn set_webhook(
+        &self,
+        id: Uuid,
+        url: Option<&str>,
+        signed_requests: bool,
+        secret: &str,
+    ) -> Result<Option<PartnerKey>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.set_webhook");
+        sqlx::query_as::<_, PartnerKey>(&format!(
+            "UPDATE partner_api_keys SET webhook_url = $2, signed_requests = $3, webhook_secret = $4
+             WHERE id = $1 RETURNING {}",
+            KEY_COLUMNS
+        ))
+        .bind(id)
+        .bind(url)
+        .bind(signed_requests)
+        .bind(self.cipher.encrypt(secret).map_err(decode_error)?)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// The key's webhook secret, if one was issued
+    pub async fn webhook_secret(&self, id: Uuid) -> Result<Option<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.webhook_secret");
+        let stored = sqlx::query_scalar::<_, Option<String>>("SELECT webhook_secret FROM partner_api_keys WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        stored.map(|secret| self.cipher.decrypt(&secret).map_err(decode_error)).transpose()
+    }
+
+    /// Accept a signed request's nonce once; false when it was seen before.
+    /// Nonces older than `keep_secs` are forgotten, as their timestamps
+    /// would be refused anyway.
+    pub async fn claim_nonce(&self, key_id: Uuid, nonce: &str, keep_secs: i64) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.claim_nonce");
+        sqlx::query("DELETE FROM partner_request_nonces WHERE key_id = $1 AND seen_at < NOW() - make_interval(secs => $2)")
+            .bind(key_id)
+            .bind(keep_secs as f64)
+            .execute(&self.pool)
+            .await?;
+        let claimed = sqlx::query(
+            "INSERT INTO partner_request_nonces (key_id, nonce) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+        )
+        .bind(key_id)
+        .bind(nonce)
+        .execute(&self.pool)
+        .await?;
+        Ok(claimed.rows_affected() == 1)
+    }
+
+    /// Requests served today, across endpoints
+    pub async fn requests_today(&self, key_id: Uuid) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.requests_today");
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COALESCE(SUM(requests), 0)::BIGINT FROM partner_api_usage
+             WHERE key_id = $1 AND day = (NOW() AT TIME ZONE 'UTC')::DATE",
+        )
+        .bind(key_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Count one request against today's usage
+    pub async fn record_usage(&self, key_id: Uuid, endpoint: &str, served: bool, amount: i64) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.record_usage");
+        let (requests, rejected) = if served { (1, 0) } else { (0, 1) };
+        sqlx::query(
+            "INSERT INTO partner_api_usage (key_id, day, endpoint, requests, rejected, amount)
+             VALUES ($1, (NOW() AT TIME ZONE 'UTC')::DATE, $2, $3, $4, $5)
+             ON CONFLICT (key_id, day, endpoint) DO UPDATE
+             SET requests = partner_api_usage.requests + EXCLUDED.requests,
+                 rejected = partner_api_usage.rejected + EXCLUDED.rejected,
+                 amount = partner_api_usage.amount + EXCLUDED.amount",
+        )
+        .bind(key_id)
+        .bind(endpoint)
+        .bind(requests)
+        .bind(rejected)
+        .bind(amount)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Daily usage for the last `days` days, newest first
+    pub async fn usage(&self, key_id: Uuid, days: i32) -> Result<Vec<PartnerUsage>, sqlx::Error> {
+        let _timer = QueryTimer::start("partner_keys.usage");
+        sqlx::query_as::<_, PartnerUsage>(
+            "SELECT day, endpoint, requests, rejected, amount FROM partner_api_usage
+             WHERE key_id = $1 AND day > (NOW() AT TIME ZONE 'UTC')::DATE - $2
+             ORDER BY day DESC, endpoint",
+        )
+        .bind(key_id)
+        .bind(days)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Credit a user's cash balance from the partner's account. Retrying
+    /// with the same reference returns the first deposit (false = not new).
+    pub async fn credit_deposit(
+        &self,
+        key_id: Uuid,
+        user_id: Uuid,
+        amount: i64,
+        reference: &str,
+    ) -> Result<(PartnerDeposit, bool), PartnerDepositError> {
+        let _timer = QueryTimer::start("partner_keys.credit_deposit");
+        let mut tx = self.pool.begin().await?;
+        let inserted = sqlx::query_as::<_, PartnerDeposit>(&format!(
+            "INSERT INTO partner_deposits (id, key_id, reference, user_id, amount)
+             VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (key_id, reference) DO NOTHING
+             RETURNING {}",
+            DEPOSIT_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(key_id)
+        .bind(reference)
+        .bind(user_id)
+        .bind(amount)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(deposit) = inserted else {
+            tx.rollback().await?;
+            let existing = sqlx::query_as::<_, PartnerDeposit>(&format!(
+                "SELECT {} FROM partner_deposits WHERE key_id = $1 AND reference = $2",
+                DEPOSIT_COLUMNS
+            ))
+            .bind(key_id)
+            .bind(reference)
+            .fetch_one(&self.pool)
+            .await?;
+            if existing.user_id != user_id || existing.amount != amount {
+                return Err(PartnerDepositError::ReferenceConflict);
+            }
+            return Ok((existing, false));
+        };
+
+        let transfer_id = transfer_in(
+            &mut tx,
+            &partner_account(key_id),
+            &user_account(user_id),
+            amount,
+            PARTNER_DEPOSIT_KIND,
+            Some(reference),
+        )
+        .await?;
+        sqlx::query("UPDATE partner_deposits SET transfer_id = $2 WHERE id = $1")
+            .bind(deposit.id)
+            .bind(transfer_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok((PartnerDeposit { transfer_id: Some(transfer_id), ..deposit }, true))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        assert_eq!(PartnerScope::parse(" Deposit:Create "), Some(PartnerScope::DepositCreate));
+        assert_eq!(PartnerScope::parse("deposits"), None);
+
+        let key = PartnerKey {
+            id: Uuid::nil(),
+            partner: "mpesa".to_string(),
+            key_prefix: "ttcpk_abcdef".to_string(),
+            scopes: "deposit:create".to_string(),
+            rate_per_minute: 60,
+            daily_quota: 0,
+            active: true,
+            created_at: Utc::now(),
+            last_used_at: None,
+            webhook_url: None,
+            signed_requests: false,
+        };
+        assert!(key.has_scope(PartnerScope::DepositCreate));
+        assert!(!key.has_scope(PartnerScope::VoucherCreate));
+    }
+}
@@ -0,0 +1,52 @@
+use rand::Rng;
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// Length of short link codes (62^7 possibilities)
+const LINK_CODE_LEN: usize = 7;
+
+const LINK_CODE_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Short links to EIP-681 payment URIs, so a REQUEST reply fits in an SMS
+#[derive(Clone)]
+pub struct PaymentLinkRepository {
+    pool: PgPool,
+}
+
+impl PaymentLinkRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Store a payment URI and return its short code
+    pub async fn create(&self, phone: &str, uri: &str) -> Result<String, sqlx::Error> {
+        let _timer = QueryTimer::start("payment_links.create");
+        let code = generate_link_code();
+        sqlx::query("INSERT INTO payment_links (code, phone, uri) VALUES ($1, $2, $3)")
+            .bind(&code)
+            .bind(phone)
+            .bind(uri)
+            .execute(&self.pool)
+            .await?;
+        Ok(code)
+    }
+
+    /// Payment URI for a short code, counting the visit
+    pub async fn resolve(&self, code: &str) -> Result<Option<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("payment_links.resolve");
+        sqlx::query_scalar::<_, String>(
+            "UPDATE payment_links SET visits = visits + 1 WHERE code = $1 RETURNING uri"
+        )
+        .bind(code)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
+
+fn generate_link_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..LINK_CODE_LEN)
+        .map(|_| LINK_CODE_CHARS[rng.gen_range(0..LINK_CODE_CHARS.len())] as char)
+        .collect()
+}
@@ -0,0 +1,164 @@
+//! Cash balance paid out on-chain (SEND USDC to an outside address)
+//!
+//! The sender's cash balance is debited into `system:payouts` and the payout
+//! recorded in one transaction, before anything is broadcast. The signed
+//! transaction's hash is stored next, still `pending`, so a payout is never
+//! on chain without a record of it. A payout then goes `sent` -> `confirmed`.
+//! If it can never be delivered, because the broadcast is refused or the
+//! transaction reverts, `refund` credits the amount back and marks it
+//! `reversed`, again in one transaction. The refund's ledger entries carry the original
+//! transfer id as their reference, and the payout keeps both ids.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::ledger::{transfer_in, user_account, LedgerError};
+use super::metrics::QueryTimer;
+use crate::money::Money;
+
+/// Ledger account holding cash on its way out to the chain
+pub const PAYOUT_ACCOUNT: &str = "system:payouts";
+/// Ledger entry kinds of the debit and of its refund
+const PAYOUT_KIND: &str = "payout";
+const REFUND_KIND: &str = "payout_refund";
+
+/// Columns selected into `Payout`
+const PAYOUT_COLUMNS: &str =
+    "id, user_id, amount, chain, to_address, status, transfer_id, tx_hash, refund_transfer_id, reason, created_at";
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Payout {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub amount: Money,
+    /// Short code of the chain it is paid on
+    pub chain: String,
+    pub to_address: String,
+    /// pending, sent, confirmed, reversed
+    pub status: String,
+    /// Ledger transfer that debited the sender
+    pub transfer_id: Uuid,
+    pub tx_hash: Option<String>,
+    /// Ledger transfer that gave the money back
+    pub refund_transfer_id: Option<Uuid>,
+    /// Why it was reversed
+    pub reason: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Clone)]
+pub struct PayoutRepository {
+    pool: PgPool,
+}
+
+impl PayoutRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Debit the sender and record the payout, before it is broadcast
+    pub async fn create(&self, user_id: Uuid, amount: Money, chain: &str, to_address: &str) -> Result<Payout, LedgerError> {
+        let _timer = QueryTimer::start("payouts.create");
+        let mut tx = self.pool.begin().await?;
+        let transfer_id = transfer_in(&mut tx, &user_account(user_id), PAYOUT_ACCOUNT, amount.micros(), PAYOUT_KIND, Some(to_address)).await?;
+        let payout = sqlx::query_as::<_, Payout>(&format!(
+            "INSERT INTO cash_payouts (id, user_id, amount, chain, to_address, transfer_id)
+             VALUES ($1, $2, $3, $4, $5, $6)
+             RETURNING {}",
+            PAYOUT_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(user_id)
+        .bind(amount)
+        .bind(chain)
+        .bind(to_address)
+        .bind(transfer_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(payout)
+    }
+
+    /// Hash of the signed transaction, kept before it is broadcast
+    pub async fn set_tx_hash(&self, id: Uuid, tx_hash: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("payouts.set_tx_hash");
+        sqlx::query("UPDATE cash_payouts SET tx_hash = $2, updated_at = NOW() WHERE id = $1 AND status = 'pending'")
+            .bind(id)
+            .bind(tx_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The transaction is out; the monitor follows it from here
+    pub async fn mark_sent(&self, id: Uuid, tx_hash: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("payouts.mark_sent");
+        sqlx::query("UPDATE cash_payouts SET status = 'sent', tx_hash = $2, updated_at = NOW() WHERE id = $1 AND status = 'pending'")
+            .bind(id)
+            .bind(tx_hash)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deep enough to count as delivered; false if it was already settled
+    pub async fn mark_confirmed(&self, id: Uuid) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("payouts.mark_confirmed");
+        let updated = sqlx::query(
+            "UPDATE cash_payouts SET status = 'confirmed', updated_at = NOW() WHERE id = $1 AND status IN ('pending', 'sent')",
+        )
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(updated.rows_affected() > 0)
+    }
+
+    /// Payouts waiting for their transaction, oldest first: every sent one,
+    /// and pending ones left behind for `stale_minutes` because their send
+    /// was cut short or never recorded
+    pub async fn list_unsettled(&self, limit: i64, stale_minutes: i64) -> Result<Vec<Payout>, sqlx::Error> {
+        let _timer = QueryTimer::start("payouts.list_unsettled");
+        sqlx::query_as::<_, Payout>(&format!(
+            "SELECT {} FROM cash_payouts
+             WHERE status = 'sent' OR (status = 'pending' AND created_at < NOW() - make_interval(mins => $2::int))
+             ORDER BY created_at LIMIT $1",
+            PAYOUT_COLUMNS
+        ))
+        .bind(limit)
+        .bind(stale_minutes as i32)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Give the sender their money back and mark the payout reversed. None
+    /// when it was already confirmed or refunded, so nothing is paid twice.
+    pub async fn refund(&self, id: Uuid, reason: &str) -> Result<Option<Payout>, LedgerError> {
+        let _timer = QueryTimer::start("payouts.refund");
+        let mut tx = self.pool.begin().await?;
+        let reversed = sqlx::query_as::<_, Payout>(&format!(
+            "UPDATE cash_payouts SET status = 'reversed', reason = $2, updated_at = NOW()
+             WHERE id = $1 AND status IN ('pending', 'sent')
+             RETURNING {}",
+            PAYOUT_COLUMNS
+        ))
+        .bind(id)
+        .bind(reason)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(mut payout) = reversed else {
+            return Ok(None);
+        };
+
+        let original = payout.transfer_id.to_string();
+        let refund_id = transfer_in(&mut tx, PAYOUT_ACCOUNT, &user_account(payout.user_id), payout.amount.micros(), REFUND_KIND, Some(&original)).await?;
+        sqlx::query("UPDATE cash_payouts SET refund_transfer_id = $2 WHERE id = $1")
+            .bind(payout.id)
+            .bind(refund_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        payout.refund_transfer_id = Some(refund_id);
+        Ok(Some(payout))
+    }
+}
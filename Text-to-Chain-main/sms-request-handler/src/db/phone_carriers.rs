@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// Carrier of a number as last reported by Twilio Lookup
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PhoneCarrier {
+    pub phone: String,
+    /// None when Lookup knew the number but not its carrier
+    pub carrier: Option<String>,
+    pub looked_up_at: DateTime<Utc>,
+}
+
+/// Cached carrier lookups, so each number is only looked up once per refresh period
+#[derive(Clone)]
+pub struct PhoneCarrierRepository {
+    pool: PgPool,
+}
+
+impl PhoneCarrierRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Every cached lookup
+    pub async fn list(&self) -> Result<Vec<PhoneCarrier>, sqlx::Error> {
+        let _timer = QueryTimer::start("phone_carriers.list");
+        sqlx::query_as::<_, PhoneCarrier>("SELECT phone, carrier, looked_up_at FROM phone_carriers")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Store a fresh lookup; `line_type` is `mobile`, `landline`, `nonFixedVoip`, ...
+    pub async fn upsert(&self, phone: &str, carrier: Option<&str>, line_type: Option<&str>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("phone_carriers.upsert");
+        sqlx::query(
+            r#"
+            INSERT INTO phone_carriers (phone, carrier, line_type, looked_up_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (phone) DO UPDATE
+                SET carrier = EXCLUDED.carrier, line_type = EXCLUDED.line_type, looked_up_at = NOW()
+            "#,
+        )
+        .bind(phone)
+        .bind(carrier)
+        .bind(line_type)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
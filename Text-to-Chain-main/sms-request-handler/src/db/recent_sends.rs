@@ -0,0 +1,81 @@
+//! Transfers each sender made recently, so a repeated SEND can be caught
+//!
+//! A row is written when a SEND's funds move. SENDs still waiting out their
+//! cancellation window are in `send_jobs` and count as well. Rows older than
+//! a day are dropped as the sender makes new transfers.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// A transfer to remember
+#[derive(Debug, Clone)]
+pub struct NewRecentSend<'a> {
+    pub sender_id: Uuid,
+    pub token: &'a str,
+    pub amount: f64,
+    pub recipient: &'a str,
+    pub recipient_address: &'a str,
+}
+
+#[derive(Clone)]
+pub struct RecentSendRepository {
+    pool: PgPool,
+}
+
+impl RecentSendRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Remember a transfer and forget the sender's ones older than a day
+    pub async fn record(&self, send: &NewRecentSend<'_>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("recent_sends.record");
+        sqlx::query(
+            "INSERT INTO recent_sends (id, sender_id, token, amount, recipient, recipient_address)
+             VALUES ($1, $2, $3, $4, $5, $6)"
+        )
+        .bind(Uuid::new_v4())
+        .bind(send.sender_id)
+        .bind(send.token)
+        .bind(send.amount)
+        .bind(send.recipient)
+        .bind(send.recipient_address)
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("DELETE FROM recent_sends WHERE sender_id = $1 AND created_at < NOW() - INTERVAL '1 day'")
+            .bind(send.sender_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// When the sender last made an identical transfer in the last
+    /// `minutes`, sent or still queued
+    pub async fn latest_match(&self, send: &NewRecentSend<'_>, minutes: i32) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        let _timer = QueryTimer::start("recent_sends.latest_match");
+        sqlx::query_scalar(
+            "SELECT created_at FROM (
+                 SELECT created_at FROM recent_sends
+                 WHERE sender_id = $1 AND token = $2 AND amount = $3 AND LOWER(recipient_address) = LOWER($4)
+                 UNION ALL
+                 SELECT created_at FROM send_jobs
+                 WHERE sender_id = $1 AND token = $2 AND amount = $3 AND LOWER(recipient_address) = LOWER($4)
+                   AND status IN ('queued', 'running')
+             ) matches
+             WHERE created_at > NOW() - make_interval(mins => $5)
+             ORDER BY created_at DESC
+             LIMIT 1"
+        )
+        .bind(send.sender_id)
+        .bind(send.token)
+        .bind(send.amount)
+        .bind(send.recipient_address)
+        .bind(minutes)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
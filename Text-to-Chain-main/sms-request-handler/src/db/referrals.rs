@@ -0,0 +1,377 @@
+//! Referral codes and the sign-ups made with them
+//!
+//! A user's code lives in `referral_codes`. `INVITE <code>` from a new number
+//! records a `pending` referral, keyed by the number's blind index. Once that
+//! number's wallet exists the referral is settled in one transaction: the
+//! referrer's row is locked so concurrent sign-ups count against the caps
+//! one at a time, and the referral ends `credited`, with a bonus transfer
+//! from `system:referrals` to each side, or `rejected` with a reason.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::encryption::FieldCipher;
+use super::ledger::{transfer_in, user_account, LedgerError};
+use super::metrics::QueryTimer;
+use super::vouchers::generate_code;
+use crate::money::Money;
+
+/// Ledger account referral bonuses are paid from
+pub const REFERRAL_ACCOUNT: &str = "system:referrals";
+/// Ledger entry kind of a referral bonus
+pub const REFERRAL_KIND: &str = "referral_bonus";
+/// Prefix of personal referral codes, so INVITE can tell them from beta invites
+pub const REFERRAL_PREFIX: &str = "REF";
+/// Trailing digits dropped to get a number's block
+const BLOCK_DIGITS: usize = 3;
+/// Fresh codes tried before giving up on collisions
+const MAX_CODE_ATTEMPTS: u32 = 5;
+
+/// A number without its last digits: numbers handed out together, like a
+/// batch of SIMs or virtual numbers, share it
+pub fn number_block(phone: &str) -> &str {
+    let digits = phone.chars().filter(char::is_ascii_digit).count();
+    if digits <= BLOCK_DIGITS + 4 {
+        return phone;
+    }
+    &phone[..phone.len() - BLOCK_DIGITS]
+}
+
+/// What settling a referral goes by, read with the referrer's row locked
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReferralFacts {
+    /// The new number is in the referrer's own number block
+    pub same_block_as_referrer: bool,
+    /// The referrer already has a credited referral from this block
+    pub block_already_credited: bool,
+    /// The referrer's credited referrals, ever and in the last 24 hours
+    pub credited_total: i64,
+    pub credited_today: i64,
+    /// Line type from a cached carrier lookup (`mobile`, `nonFixedVoip`, ...)
+    pub line_type: Option<String>,
+}
+
+/// How a pending referral ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum Settlement {
+    Credited { referrer_id: Uuid, referrer_bonus: i64, referee_bonus: i64 },
+    Rejected { referrer_id: Uuid, reason: &'static str },
+}
+
+/// Outcome of `INVITE <code>` from a new number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    Recorded,
+    /// The number already used a code; the first one stands
+    AlreadyClaimed,
+    UnknownCode,
+}
+
+/// Referral counts over a period
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ReferralTotals {
+    /// Codes used by numbers that haven't created a wallet yet
+    pub pending: i64,
+    pub credited: i64,
+    pub rejected: i64,
+    /// Both sides' bonuses together
+    pub bonus_paid: Money,
+    /// Credited referees who did anything with their wallet beyond the bonus
+    pub active: i64,
+}
+
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct RejectionCount {
+    pub reason: String,
+    pub count: i64,
+}
+
+/// One referrer's results over a period
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ReferrerStats {
+    pub code: String,
+    pub user_id: Uuid,
+    pub ens_name: Option<String>,
+    /// Numbers that used the code
+    pub referred: i64,
+    pub credited: i64,
+    pub rejected: i64,
+    pub bonus_paid: Money,
+    pub active: i64,
+}
+
+/// A credited referee with ledger activity other than its bonus
+const ACTIVE_REFEREE: &str = "r.status = 'credited' AND EXISTS (
+    SELECT 1 FROM ledger_entries e
+    WHERE e.account = 'user:' || r.referee_id::text AND e.kind <> 'referral_bonus'
+)";
+
+#[derive(Clone)]
+pub struct ReferralRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl ReferralRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    /// The user's code, created on first use
+    pub async fn code_for(&self, user_id: Uuid, phone: &str) -> Result<String, sqlx::Error> {
+        let _timer = QueryTimer::start("referrals.code_for");
+        let existing = sqlx::query_scalar::<_, String>("SELECT code FROM referral_codes WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        if let Some(code) = existing {
+            return Ok(code);
+        }
+
+        let block = self.cipher.blind_index(number_block(phone));
+        let mut attempts = 0;
+        loop {
+            let result = sqlx::query_scalar::<_, String>(
+                "INSERT INTO referral_codes (code, user_id, phone_block) VALUES ($1, $2, $3)
+                 ON CONFLICT (user_id) DO UPDATE SET user_id = EXCLUDED.user_id
+                 RETURNING code",
+            )
+            .bind(generate_code(REFERRAL_PREFIX))
+            .bind(user_id)
+            .bind(&block)
+            .fetch_one(&self.pool)
+            .await;
+            match result {
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() && attempts < MAX_CODE_ATTEMPTS => attempts += 1,
+                other => return other,
+            }
+        }
+    }
+
+    /// Referrals credited to the user so far
+    pub async fn credited_count(&self, user_id: Uuid) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("referrals.credited_count");
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM referrals WHERE referrer_id = $1 AND status = 'credited'")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Record that a number without a wallet used a code
+    pub async fn claim(&self, code: &str, phone: &str) -> Result<Claim, sqlx::Error> {
+        let _timer = QueryTimer::start("referrals.claim");
+        let referrer = sqlx::query_scalar::<_, Uuid>("SELECT user_id FROM referral_codes WHERE code = $1")
+            .bind(code)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(referrer_id) = referrer else {
+            return Ok(Claim::UnknownCode);
+        };
+
+        let inserted = sqlx::query(
+            "INSERT INTO referrals (id, code, referrer_id, referee_phone)
+             SELECT $1, $2, $3, $4
+             WHERE NOT EXISTS (SELECT 1 FROM referrals WHERE referee_phone = ANY($5))
+             ON CONFLICT (referee_phone) DO NOTHING",
+        )
+        .bind(Uuid::new_v4())
+        .bind(code)
+        .bind(referrer_id)
+        .bind(self.cipher.blind_index(phone))
+        .bind(self.cipher.lookup_keys(phone))
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+        Ok(if inserted > 0 { Claim::Recorded } else { Claim::AlreadyClaimed })
+    }
+
+    /// Whether the number used a code and hasn't been settled yet
+    pub async fn is_pending(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("referrals.is_pending");
+        sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM referrals WHERE referee_phone = ANY($1) AND status = 'pending')",
+        )
+        .bind(self.cipher.lookup_keys(phone))
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Settle the number's pending referral now that it has a wallet.
+    /// `reject` sees the facts and returns a reason to pay nothing; None
+    /// when the number has no pending referral
+    pub async fn settle(
+        &self,
+        phone: &str,
+        referee_id: Uuid,
+        referrer_bonus: i64,
+        referee_bonus: i64,
+        reject: impl FnOnce(&ReferralFacts) -> Option<&'static str>,
+    ) -> Result<Option<Settlement>, LedgerError> {
+        let _timer = QueryTimer::start("referrals.settle");
+        let mut tx = self.pool.begin().await?;
+        let pending = sqlx::query_as::<_, (Uuid, Uuid)>(
+            "SELECT id, referrer_id FROM referrals
+             WHERE referee_phone = ANY($1) AND status = 'pending'
+             FOR UPDATE",
+        )
+        .bind(self.cipher.lookup_keys(phone))
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some((id, referrer_id)) = pending else {
+            return Ok(None);
+        };
+
+        // Serialises settlements for the same referrer, so the caps hold
+        let referrer_block = sqlx::query_scalar::<_, String>(
+            "SELECT phone_block FROM referral_codes WHERE user_id = $1 FOR UPDATE",
+        )
+        .bind(referrer_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let block_keys = self.cipher.lookup_keys(number_block(phone));
+        let (credited_total, credited_today, block_already_credited) = sqlx::query_as::<_, (i64, i64, bool)>(
+            "SELECT COUNT(*),
+                    COUNT(*) FILTER (WHERE settled_at > NOW() - INTERVAL '1 day'),
+                    COALESCE(BOOL_OR(referee_block = ANY($2)), FALSE)
+             FROM referrals
+             WHERE referrer_id = $1 AND status = 'credited'",
+        )
+        .bind(referrer_id)
+        .bind(&block_keys)
+        .fetch_one(&mut *tx)
+        .await?;
+        let line_type = sqlx::query_scalar::<_, Option<String>>("SELECT line_type FROM phone_carriers WHERE phone = $1")
+            .bind(phone)
+            .fetch_optional(&mut *tx)
+            .await?
+            .flatten();
+
+        let facts = ReferralFacts {
+            same_block_as_referrer: block_keys.contains(&referrer_block),
+            block_already_credited,
+            credited_total,
+            credited_today,
+            line_type,
+        };
+        let block = self.cipher.blind_index(number_block(phone));
+
+        let settlement = match reject(&facts) {
+            Some(reason) => {
+                sqlx::query(
+                    "UPDATE referrals
+                     SET status = 'rejected', reason = $2, referee_id = $3, referee_block = $4, settled_at = NOW()
+                     WHERE id = $1",
+                )
+                .bind(id)
+                .bind(reason)
+                .bind(referee_id)
+                .bind(&block)
+                .execute(&mut *tx)
+                .await?;
+                Settlement::Rejected { referrer_id, reason }
+            }
+            None => {
+                let reference = id.to_string();
+                let payees = [(user_account(referrer_id), referrer_bonus), (user_account(referee_id), referee_bonus)];
+                let mut transfer_ids = [None, None];
+                for (transfer_id, (account, amount)) in transfer_ids.iter_mut().zip(payees) {
+                    if amount > 0 {
+                        *transfer_id =
+                            Some(transfer_in(&mut tx, REFERRAL_ACCOUNT, &account, amount, REFERRAL_KIND, Some(&reference)).await?);
+                    }
+                }
+                sqlx::query(
+                    "UPDATE referrals
+                     SET status = 'credited', referee_id = $2, referee_block = $3, referrer_bonus = $4, referee_bonus = $5,
+                         referrer_transfer_id = $6, referee_transfer_id = $7, settled_at = NOW()
+                     WHERE id = $1",
+                )
+                .bind(id)
+                .bind(referee_id)
+                .bind(&block)
+                .bind(referrer_bonus)
+                .bind(referee_bonus)
+                .bind(transfer_ids[0])
+                .bind(transfer_ids[1])
+                .execute(&mut *tx)
+                .await?;
+                Settlement::Credited { referrer_id, referrer_bonus, referee_bonus }
+            }
+        };
+        tx.commit().await?;
+        Ok(Some(settlement))
+    }
+
+    /// Counts for referrals made since `since`
+    pub async fn totals(&self, since: DateTime<Utc>) -> Result<ReferralTotals, sqlx::Error> {
+        let _timer = QueryTimer::start("referrals.totals");
+        sqlx::query_as::<_, ReferralTotals>(&format!(
+            "SELECT COUNT(*) FILTER (WHERE r.status = 'pending') AS pending,
+                    COUNT(*) FILTER (WHERE r.status = 'credited') AS credited,
+                    COUNT(*) FILTER (WHERE r.status = 'rejected') AS rejected,
+                    COALESCE(SUM(r.referrer_bonus + r.referee_bonus), 0)::BIGINT AS bonus_paid,
+                    COUNT(*) FILTER (WHERE {}) AS active
+             FROM referrals r
+             WHERE r.created_at >= $1",
+            ACTIVE_REFEREE
+        ))
+        .bind(since)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Why referrals made since `since` were rejected, most common first
+    pub async fn rejections(&self, since: DateTime<Utc>) -> Result<Vec<RejectionCount>, sqlx::Error> {
+        let _timer = QueryTimer::start("referrals.rejections");
+        sqlx::query_as::<_, RejectionCount>(
+            "SELECT reason, COUNT(*) AS count FROM referrals
+             WHERE status = 'rejected' AND created_at >= $1
+             GROUP BY reason
+             ORDER BY count DESC, reason",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Referrers by credited referrals since `since`
+    pub async fn top_referrers(&self, since: DateTime<Utc>, limit: i64) -> Result<Vec<ReferrerStats>, sqlx::Error> {
+        let _timer = QueryTimer::start("referrals.top_referrers");
+        sqlx::query_as::<_, ReferrerStats>(&format!(
+            "SELECT c.code, c.user_id, u.ens_name,
+                    COUNT(*) AS referred,
+                    COUNT(*) FILTER (WHERE r.status = 'credited') AS credited,
+                    COUNT(*) FILTER (WHERE r.status = 'rejected') AS rejected,
+                    COALESCE(SUM(r.referrer_bonus + r.referee_bonus), 0)::BIGINT AS bonus_paid,
+                    COUNT(*) FILTER (WHERE {}) AS active
+             FROM referrals r
+             JOIN referral_codes c ON c.code = r.code
+             JOIN users u ON u.id = c.user_id
+             WHERE r.created_at >= $1
+             GROUP BY c.code, c.user_id, u.ens_name
+             ORDER BY credited DESC, referred DESC, c.code
+             LIMIT $2",
+            ACTIVE_REFEREE
+        ))
+        .bind(since)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_block() {
+        assert_eq!(number_block("+254712345678"), "+254712345");
+        assert_eq!(number_block("+15551234567"), "+15551234");
+        // Too short to have a meaningful block
+        assert_eq!(number_block("+4412345"), "+4412345");
+    }
+}
@@ -0,0 +1,64 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// A name (or pattern) that can't be minted as a subdomain
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ReservedName {
+    /// Lower-case label, or a regex for `pattern` entries
+    pub label: String,
+    /// `reserved` (exact label), `brand` / `offensive` (anywhere in the
+    /// label) or `pattern` (regex)
+    pub kind: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Admin-managed deny-list for ENS subdomains
+#[derive(Clone)]
+pub struct ReservedNameRepository {
+    pool: PgPool,
+}
+
+impl ReservedNameRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<ReservedName>, sqlx::Error> {
+        let _timer = QueryTimer::start("reserved_names.list");
+        sqlx::query_as::<_, ReservedName>("SELECT label, kind, reason, created_at FROM reserved_names ORDER BY kind, label")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Add an entry, replacing the kind and reason of an existing one
+    pub async fn add(&self, label: &str, kind: &str, reason: Option<&str>) -> Result<ReservedName, sqlx::Error> {
+        let _timer = QueryTimer::start("reserved_names.add");
+        sqlx::query_as::<_, ReservedName>(
+            r#"
+            INSERT INTO reserved_names (label, kind, reason)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (label) DO UPDATE SET kind = EXCLUDED.kind, reason = EXCLUDED.reason
+            RETURNING label, kind, reason, created_at
+            "#,
+        )
+        .bind(label)
+        .bind(kind)
+        .bind(reason)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Remove an entry; false when there was none
+    pub async fn remove(&self, label: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("reserved_names.remove");
+        let result = sqlx::query("DELETE FROM reserved_names WHERE label = $1")
+            .bind(label)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,86 @@
+use ethers::types::U256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// A user's shares in a savings vault
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SavingsPosition {
+    /// Vault shares as a decimal string (NUMERIC in the table; they can exceed BIGINT)
+    pub shares: String,
+    /// USDC put in and not yet taken out, in micro-USDC
+    pub principal: i64,
+}
+
+impl SavingsPosition {
+    pub fn shares(&self) -> U256 {
+        U256::from_dec_str(&self.shares).unwrap_or_default()
+    }
+}
+
+const POSITION_COLUMNS: &str = "shares::TEXT AS shares, principal";
+
+#[derive(Clone)]
+pub struct SavingsRepository {
+    pool: PgPool,
+}
+
+impl SavingsRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find(&self, user_id: Uuid, vault: &str) -> Result<Option<SavingsPosition>, sqlx::Error> {
+        let _timer = QueryTimer::start("savings.find");
+        sqlx::query_as::<_, SavingsPosition>(&format!(
+            "SELECT {} FROM savings WHERE user_id = $1 AND vault = $2",
+            POSITION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(vault.to_lowercase())
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Add the shares minted by a SAVE
+    pub async fn record_deposit(&self, user_id: Uuid, chain_id: u64, vault: &str, shares: U256, assets: i64) -> Result<SavingsPosition, sqlx::Error> {
+        let _timer = QueryTimer::start("savings.record_deposit");
+        sqlx::query_as::<_, SavingsPosition>(&format!(
+            "INSERT INTO savings (user_id, chain_id, vault, shares, principal)
+             VALUES ($1, $2, $3, $4::NUMERIC, $5)
+             ON CONFLICT (user_id, vault) DO UPDATE
+             SET shares = savings.shares + EXCLUDED.shares, principal = savings.principal + EXCLUDED.principal, updated_at = NOW()
+             RETURNING {}",
+            POSITION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(chain_id as i64)
+        .bind(vault.to_lowercase())
+        .bind(shares.to_string())
+        .bind(assets)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Remove the shares burned by an UNSAVE. Principal shrinks in proportion
+    /// to the shares burned, so yield earned on the rest stays yield.
+    pub async fn record_withdrawal(&self, user_id: Uuid, vault: &str, burned: U256) -> Result<Option<SavingsPosition>, sqlx::Error> {
+        let _timer = QueryTimer::start("savings.record_withdrawal");
+        sqlx::query_as::<_, SavingsPosition>(&format!(
+            "UPDATE savings
+             SET principal = CASE WHEN shares <= $3::NUMERIC THEN 0
+                                  ELSE principal - TRUNC(principal * $3::NUMERIC / shares)::BIGINT END,
+                 shares = GREATEST(shares - $3::NUMERIC, 0),
+                 updated_at = NOW()
+             WHERE user_id = $1 AND vault = $2
+             RETURNING {}",
+            POSITION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(vault.to_lowercase())
+        .bind(burned.to_string())
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,166 @@
+//! SENDs waiting out their cancellation window (see `commands::send_queue`)
+//!
+//! A job is `queued` until its `execute_after`, then claimed as `running`
+//! and finished as `sent` or `failed`. CANCEL moves a `queued` job to
+//! `cancelled`. Claim and cancel both only update rows still `queued`, so
+//! whichever commits first wins and the other finds nothing. A job left
+//! `running` by a crash is never picked up again, so nothing is sent twice.
+
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+const SEND_JOB_COLUMNS: &str = "id, sender_id, token, amount, recipient, recipient_address, status";
+
+/// A SEND that passed every check and waits to be broadcast
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SendJob {
+    pub id: Uuid,
+    pub sender_id: Uuid,
+    pub token: String,
+    pub amount: f64,
+    /// Recipient as the sender wrote it
+    pub recipient: String,
+    /// Address it resolved to when the SEND was queued
+    pub recipient_address: String,
+    /// queued, running, sent, failed or cancelled
+    pub status: String,
+}
+
+/// Jobs not yet finished, for `ttc-admin jobs`
+#[derive(Debug, Clone, Default, Serialize, sqlx::FromRow)]
+pub struct SendQueueSummary {
+    pub queued: i64,
+    /// Queued jobs whose window has closed but that nobody has claimed
+    pub due: i64,
+    pub running: i64,
+    /// Running for over ten minutes, most likely left behind by a crash
+    pub stuck: i64,
+}
+
+/// A SEND to queue
+#[derive(Debug, Clone)]
+pub struct NewSendJob<'a> {
+    pub sender_id: Uuid,
+    pub token: &'a str,
+    pub amount: f64,
+    pub recipient: &'a str,
+    pub recipient_address: &'a str,
+}
+
+#[derive(Clone)]
+pub struct SendJobRepository {
+    pool: PgPool,
+}
+
+impl SendJobRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Queue a SEND to run once `delay_secs` have passed
+    pub async fn enqueue(&self, job: &NewSendJob<'_>, delay_secs: i64) -> Result<SendJob, sqlx::Error> {
+        let _timer = QueryTimer::start("send_jobs.enqueue");
+        sqlx::query_as::<_, SendJob>(&format!(
+            "INSERT INTO send_jobs (id, sender_id, token, amount, recipient, recipient_address, execute_after)
+             VALUES ($1, $2, $3, $4, $5, $6, NOW() + make_interval(secs => $7))
+             RETURNING {}",
+            SEND_JOB_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(job.sender_id)
+        .bind(job.token)
+        .bind(job.amount)
+        .bind(job.recipient)
+        .bind(job.recipient_address)
+        .bind(delay_secs as f64)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Cancel the sender's most recent job that hasn't been claimed
+    pub async fn cancel_latest(&self, sender_id: Uuid) -> Result<Option<SendJob>, sqlx::Error> {
+        let _timer = QueryTimer::start("send_jobs.cancel_latest");
+        sqlx::query_as::<_, SendJob>(&format!(
+            "UPDATE send_jobs SET status = 'cancelled', finished_at = NOW()
+             WHERE id = (
+                 SELECT id FROM send_jobs
+                 WHERE sender_id = $1 AND status = 'queued'
+                 ORDER BY created_at DESC
+                 LIMIT 1
+             ) AND status = 'queued'
+             RETURNING {}",
+            SEND_JOB_COLUMNS
+        ))
+        .bind(sender_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// The sender's most recent job created in the last `minutes`
+    pub async fn latest(&self, sender_id: Uuid, minutes: i32) -> Result<Option<SendJob>, sqlx::Error> {
+        let _timer = QueryTimer::start("send_jobs.latest");
+        sqlx::query_as::<_, SendJob>(&format!(
+            "SELECT {} FROM send_jobs
+             WHERE sender_id = $1 AND created_at > NOW() - make_interval(mins => $2)
+             ORDER BY created_at DESC
+             LIMIT 1",
+            SEND_JOB_COLUMNS
+        ))
+        .bind(sender_id)
+        .bind(minutes)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Claim up to `limit` jobs whose window has closed
+    pub async fn claim_due(&self, limit: i64) -> Result<Vec<SendJob>, sqlx::Error> {
+        let _timer = QueryTimer::start("send_jobs.claim_due");
+        sqlx::query_as::<_, SendJob>(&format!(
+            "UPDATE send_jobs SET status = 'running', started_at = NOW()
+             WHERE id IN (
+                 SELECT id FROM send_jobs
+                 WHERE status = 'queued' AND execute_after <= NOW()
+                 ORDER BY execute_after
+                 LIMIT $1
+                 FOR UPDATE SKIP LOCKED
+             ) AND status = 'queued'
+             RETURNING {}",
+            SEND_JOB_COLUMNS
+        ))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Record how a claimed job ended, with the reply the sender got
+    pub async fn finish(&self, id: Uuid, sent: bool, reply: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("send_jobs.finish");
+        sqlx::query(
+            "UPDATE send_jobs SET status = $2, reply = $3, finished_at = NOW() WHERE id = $1 AND status = 'running'"
+        )
+        .bind(id)
+        .bind(if sent { "sent" } else { "failed" })
+        .bind(reply)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Counts of unfinished jobs
+    pub async fn summary(&self) -> Result<SendQueueSummary, sqlx::Error> {
+        let _timer = QueryTimer::start("send_jobs.summary");
+        sqlx::query_as::<_, SendQueueSummary>(
+            "SELECT
+                 COUNT(*) FILTER (WHERE status = 'queued') AS queued,
+                 COUNT(*) FILTER (WHERE status = 'queued' AND execute_after <= NOW()) AS due,
+                 COUNT(*) FILTER (WHERE status = 'running') AS running,
+                 COUNT(*) FILTER (WHERE status = 'running' AND started_at < NOW() - INTERVAL '10 minutes') AS stuck
+             FROM send_jobs WHERE status IN ('queued', 'running')"
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+}
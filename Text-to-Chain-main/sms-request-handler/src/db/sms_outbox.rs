@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// A notification held back by quiet hours or a carrier outage
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DeferredSms {
+    pub id: Uuid,
+    pub phone: String,
+    pub body: String,
+}
+
+/// A waiting message as operators see it, without its body
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct OutboxEntry {
+    pub id: Uuid,
+    pub phone: String,
+    pub send_after: DateTime<Utc>,
+    /// Calling code whose outage it waits for
+    pub outage_code: Option<String>,
+}
+
+/// Non-urgent outbound messages waiting for the recipient's morning, or
+/// for a carrier outage in their calling code to end
+#[derive(Clone, Debug)]
+pub struct SmsOutboxRepository {
+    pool: PgPool,
+}
+
+impl SmsOutboxRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn enqueue(&self, phone: &str, body: &str, send_after: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.enqueue");
+        sqlx::query("INSERT INTO sms_outbox (id, phone, body, send_after) VALUES ($1, $2, $3, $4)")
+            .bind(Uuid::new_v4())
+            .bind(phone)
+            .bind(body)
+            .bind(send_after)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Remove and return up to `limit` messages whose time has come
+    pub async fn take_due(&self, limit: i64) -> Result<Vec<DeferredSms>, sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.take_due");
+        sqlx::query_as::<_, DeferredSms>(
+            r#"
+            DELETE FROM sms_outbox WHERE id IN (
+                SELECT id FROM sms_outbox
+                WHERE send_after <= NOW() AND outage_code IS NULL
+                ORDER BY send_after
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, phone, body
+            "#
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Hold a message until the outage in its calling code is over
+    pub async fn hold_for_outage(&self, phone: &str, body: &str, calling_code: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.hold_for_outage");
+        sqlx::query("INSERT INTO sms_outbox (id, phone, body, send_after, outage_code) VALUES ($1, $2, $3, NOW(), $4)")
+            .bind(Uuid::new_v4())
+            .bind(phone)
+            .bind(body)
+            .bind(calling_code)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Oldest message held for an outage, to probe the carrier with
+    pub async fn oldest_held(&self, calling_code: &str) -> Result<Option<DeferredSms>, sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.oldest_held");
+        sqlx::query_as::<_, DeferredSms>(
+            "SELECT id, phone, body FROM sms_outbox WHERE outage_code = $1 ORDER BY send_after, id LIMIT 1",
+        )
+        .bind(calling_code)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Remove and return one message, to send it now whatever it waits for
+    pub async fn take(&self, id: Uuid) -> Result<Option<DeferredSms>, sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.take");
+        sqlx::query_as::<_, DeferredSms>("DELETE FROM sms_outbox WHERE id = $1 RETURNING id, phone, body")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// The `limit` messages waiting longest, and how many wait in all
+    pub async fn oldest(&self, limit: i64) -> Result<(Vec<OutboxEntry>, i64), sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.oldest");
+        let entries = sqlx::query_as::<_, OutboxEntry>(
+            "SELECT id, phone, send_after, outage_code FROM sms_outbox ORDER BY send_after, id LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        let total = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM sms_outbox")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok((entries, total))
+    }
+
+    pub async fn remove(&self, id: Uuid) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.remove");
+        sqlx::query("DELETE FROM sms_outbox WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Hand a calling code's held messages to the regular flush
+    pub async fn release_outage(&self, calling_code: &str) -> Result<u64, sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.release_outage");
+        let result = sqlx::query("UPDATE sms_outbox SET outage_code = NULL WHERE outage_code = $1")
+            .bind(calling_code)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Messages held per calling code
+    pub async fn held_counts(&self) -> Result<Vec<(String, i64)>, sqlx::Error> {
+        let _timer = QueryTimer::start("sms_outbox.held_counts");
+        sqlx::query_as::<_, (String, i64)>(
+            "SELECT outage_code, COUNT(*) FROM sms_outbox WHERE outage_code IS NOT NULL GROUP BY outage_code",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+}
@@ -0,0 +1,49 @@
+use chrono::NaiveDate;
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// Daily outbound SMS spend per calling code, so budgets survive restarts
+#[derive(Clone)]
+pub struct SmsSpendRepository {
+    pool: PgPool,
+}
+
+impl SmsSpendRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Add one sent message to the day's totals
+    pub async fn record(&self, day: NaiveDate, country: &str, segments: i64, cost: f64) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("sms_spend.record");
+        sqlx::query(
+            r#"
+            INSERT INTO sms_spend_daily (day, country_code, messages, segments, cost)
+            VALUES ($1, $2, 1, $3, $4)
+            ON CONFLICT (day, country_code) DO UPDATE SET
+                messages = sms_spend_daily.messages + 1,
+                segments = sms_spend_daily.segments + EXCLUDED.segments,
+                cost = sms_spend_daily.cost + EXCLUDED.cost
+            "#
+        )
+        .bind(day)
+        .bind(country)
+        .bind(segments)
+        .bind(cost)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// (country code, messages, segments, cost) for a day
+    pub async fn day_totals(&self, day: NaiveDate) -> Result<Vec<(String, i64, i64, f64)>, sqlx::Error> {
+        let _timer = QueryTimer::start("sms_spend.day_totals");
+        sqlx::query_as::<_, (String, i64, i64, f64)>(
+            "SELECT country_code, messages, segments, cost FROM sms_spend_daily WHERE day = $1"
+        )
+        .bind(day)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
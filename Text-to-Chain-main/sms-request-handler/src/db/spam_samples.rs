@@ -0,0 +1,108 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::encryption::{decode_error, FieldCipher};
+use super::metrics::QueryTimer;
+
+/// A flagged or dropped inbound message kept for review
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SpamSample {
+    pub id: i64,
+    pub phone: String,
+    pub body: String,
+    pub score: f64,
+    /// `flag` (processed anyway) or `drop`
+    pub action: String,
+    /// Comma-separated, e.g. `link,phishing_phrase`
+    pub reasons: String,
+    /// `spam` or `ham` once reviewed
+    pub review: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Samples of suspected spam. Like transcripts, the phone is stored as a
+/// blind index (plus an encrypted copy when phones aren't hashed) and the
+/// body encrypted, with PINs masked before it gets here.
+#[derive(Clone)]
+pub struct SpamSampleRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl SpamSampleRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    pub async fn record(&self, phone: &str, body: &str, score: f64, action: &str, reasons: &[String]) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("spam_samples.record");
+        sqlx::query(
+            "INSERT INTO spam_samples (phone, phone_encrypted, body, score, action, reasons)
+             VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(self.cipher.blind_index(phone))
+        .bind(self.cipher.encrypt_phone(phone).map_err(decode_error)?)
+        .bind(self.cipher.encrypt(body).map_err(decode_error)?)
+        .bind(score)
+        .bind(action)
+        .bind(reasons.join(","))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Newest samples first; only those not yet reviewed when `pending`
+    pub async fn list(&self, pending: bool, limit: i64) -> Result<Vec<SpamSample>, sqlx::Error> {
+        let _timer = QueryTimer::start("spam_samples.list");
+        let samples = sqlx::query_as::<_, SpamSample>(
+            "SELECT id, COALESCE(phone_encrypted, phone) AS phone, body, score, action, reasons,
+                    review, reviewed_at, created_at
+             FROM spam_samples
+             WHERE NOT $1 OR review IS NULL
+             ORDER BY created_at DESC, id DESC
+             LIMIT $2",
+        )
+        .bind(pending)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        samples
+            .into_iter()
+            .map(|sample| {
+                Ok(SpamSample {
+                    phone: self.cipher.decrypt(&sample.phone).map_err(decode_error)?,
+                    body: self.cipher.decrypt(&sample.body).map_err(decode_error)?,
+                    ..sample
+                })
+            })
+            .collect()
+    }
+
+    /// Mark a sample `spam` or `ham`; returns it, or None if there is no such sample
+    pub async fn review(&self, id: i64, review: &str) -> Result<Option<SpamSample>, sqlx::Error> {
+        let _timer = QueryTimer::start("spam_samples.review");
+        let sample = sqlx::query_as::<_, SpamSample>(
+            "UPDATE spam_samples SET review = $2, reviewed_at = NOW()
+             WHERE id = $1
+             RETURNING id, COALESCE(phone_encrypted, phone) AS phone, body, score, action, reasons,
+                       review, reviewed_at, created_at",
+        )
+        .bind(id)
+        .bind(review)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        sample
+            .map(|sample| {
+                Ok(SpamSample {
+                    phone: self.cipher.decrypt(&sample.phone).map_err(decode_error)?,
+                    body: self.cipher.decrypt(&sample.body).map_err(decode_error)?,
+                    ..sample
+                })
+            })
+            .transpose()
+    }
+}
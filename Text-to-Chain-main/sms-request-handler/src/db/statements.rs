@@ -0,0 +1,105 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rand::Rng;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// Length of statement link tokens; longer than payment link codes since
+/// these open a user's full activity
+const STATEMENT_TOKEN_LEN: usize = 24;
+
+const STATEMENT_TOKEN_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// One entry on an account with the account on the other side of its transfer
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct StatementEntry {
+    pub transfer_id: Uuid,
+    /// Micro-USDC, negative when money left the account
+    pub delta: i64,
+    pub kind: String,
+    /// `user:<id>`, `agent:<id>`, `partner:<id>` or `system:*`
+    pub counterparty: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Ledger activity for STATEMENT and the short-lived links to full statements
+#[derive(Clone)]
+pub struct StatementRepository {
+    pool: PgPool,
+}
+
+impl StatementRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Balance of `account` just before `at`
+    pub async fn balance_at(&self, account: &str, at: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+        let _timer = QueryTimer::start("statements.balance_at");
+        sqlx::query_scalar::<_, i64>(
+            "SELECT COALESCE(SUM(delta), 0)::BIGINT FROM ledger_entries WHERE account = $1 AND created_at < $2"
+        )
+        .bind(account)
+        .bind(at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Entries on `account` from `from` up to (not including) `to`, oldest first
+    pub async fn entries(&self, account: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<StatementEntry>, sqlx::Error> {
+        let _timer = QueryTimer::start("statements.entries");
+        sqlx::query_as::<_, StatementEntry>(
+            "SELECT e.transfer_id, e.delta, e.kind, COALESCE(o.account, '') AS counterparty, e.created_at
+             FROM ledger_entries e
+             LEFT JOIN ledger_entries o ON o.transfer_id = e.transfer_id AND o.account <> e.account
+             WHERE e.account = $1 AND e.created_at >= $2 AND e.created_at < $3
+             ORDER BY e.created_at, e.id"
+        )
+        .bind(account)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Token for a link to the user's statement of the month starting on
+    /// `month`, valid for `ttl_minutes`. Expired links are cleared out here
+    pub async fn create_link(&self, user_id: Uuid, month: NaiveDate, ttl_minutes: i64) -> Result<String, sqlx::Error> {
+        let _timer = QueryTimer::start("statements.create_link");
+        sqlx::query("DELETE FROM statement_links WHERE expires_at < NOW()")
+            .execute(&self.pool)
+            .await?;
+
+        let token = generate_token();
+        sqlx::query(
+            "INSERT INTO statement_links (token, user_id, month, expires_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(mins => $4))"
+        )
+        .bind(&token)
+        .bind(user_id)
+        .bind(month)
+        .bind(ttl_minutes as i32)
+        .execute(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    /// User and month behind a link that has not expired
+    pub async fn resolve_link(&self, token: &str) -> Result<Option<(Uuid, NaiveDate)>, sqlx::Error> {
+        let _timer = QueryTimer::start("statements.resolve_link");
+        sqlx::query_as::<_, (Uuid, NaiveDate)>(
+            "SELECT user_id, month FROM statement_links WHERE token = $1 AND expires_at > NOW()"
+        )
+        .bind(token)
+        .fetch_optional(&self.pool)
+        .await
+    }
+}
+
+fn generate_token() -> String {
+    let mut rng = rand::thread_rng();
+    (0..STATEMENT_TOKEN_LEN)
+        .map(|_| STATEMENT_TOKEN_CHARS[rng.gen_range(0..STATEMENT_TOKEN_CHARS.len())] as char)
+        .collect()
+}
@@ -0,0 +1,51 @@
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// Operator overrides of token contract addresses (see `wallet::token_registry`)
+#[derive(Clone)]
+pub struct TokenOverrideRepository {
+    pool: PgPool,
+}
+
+impl TokenOverrideRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// All overrides as (chain, symbol, address)
+    pub async fn list(&self) -> Result<Vec<(String, String, String)>, sqlx::Error> {
+        let _timer = QueryTimer::start("token_overrides.list");
+        sqlx::query_as::<_, (String, String, String)>("SELECT chain, symbol, address FROM token_overrides")
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn set(&self, chain: &str, symbol: &str, address: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("token_overrides.set");
+        sqlx::query(
+            r#"
+            INSERT INTO token_overrides (chain, symbol, address, updated_at)
+            VALUES ($1, $2, $3, NOW())
+            ON CONFLICT (chain, symbol) DO UPDATE SET address = EXCLUDED.address, updated_at = NOW()
+            "#
+        )
+        .bind(chain)
+        .bind(symbol)
+        .bind(address)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Remove an override so the config or built-in address applies again
+    pub async fn clear(&self, chain: &str, symbol: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("token_overrides.clear");
+        sqlx::query("DELETE FROM token_overrides WHERE chain = $1 AND symbol = $2")
+            .bind(chain)
+            .bind(symbol)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use super::encryption::{decode_error, FieldCipher};
+use super::metrics::QueryTimer;
+
+/// One stored message; `direction` is "in" (from the user) or "out"
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct TranscriptEntry {
+    pub direction: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Support transcripts: inbound and outbound messages per user, kept for the
+/// configured retention period. Phones are stored as blind indexes and
+/// bodies encrypted, like other personal data columns.
+#[derive(Clone)]
+pub struct TranscriptRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl TranscriptRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    pub async fn record(&self, phone: &str, direction: &str, body: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("transcripts.record");
+        let body = self.cipher.encrypt(body).map_err(decode_error)?;
+        sqlx::query("INSERT INTO sms_transcripts (phone, direction, body, created_at) VALUES ($1, $2, $3, NOW())")
+            .bind(self.cipher.blind_index(phone))
+            .bind(direction)
+            .bind(body)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The latest `limit` messages for a phone, oldest first
+    pub async fn for_phone(&self, phone: &str, limit: i64) -> Result<Vec<TranscriptEntry>, sqlx::Error> {
+        let _timer = QueryTimer::start("transcripts.for_phone");
+        let rows = sqlx::query_as::<_, TranscriptEntry>(
+            r#"
+            SELECT direction, body, created_at FROM (
+                SELECT id, direction, body, created_at FROM sms_transcripts
+                WHERE phone = ANY($1)
+                ORDER BY created_at DESC, id DESC
+                LIMIT $2
+            ) latest
+            ORDER BY created_at, id
+            "#
+        )
+        .bind(self.cipher.lookup_keys(phone))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|entry| {
+                Ok(TranscriptEntry {
+                    body: self.cipher.decrypt(&entry.body).map_err(decode_error)?,
+                    ..entry
+                })
+            })
+            .collect()
+    }
+
+    /// Delete messages older than the retention period; returns rows removed
+    pub async fn purge_older_than(&self, days: u32) -> Result<u64, sqlx::Error> {
+        let _timer = QueryTimer::start("transcripts.purge_older_than");
+        let result = sqlx::query("DELETE FROM sms_transcripts WHERE created_at < NOW() - make_interval(days => $1)")
+            .bind(days as i32)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,216 @@
+//! Large SENDs held for a second approval, and the guardians who give it
+//!
+//! A held transfer carries a code for the sender and, when the sender has
+//! a guardian, a second code for the guardian. The sender confirms first;
+//! the guardian's code is only sent out after that. Without a guardian the
+//! sender's confirmation is accepted once the cooling period has passed.
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::agents::generate_code_pair;
+use super::metrics::QueryTimer;
+
+/// A SEND waiting for approval
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PendingTransfer {
+    pub id: Uuid,
+    pub sender_id: Uuid,
+    pub sender_phone: String,
+    pub token: String,
+    pub amount: f64,
+    /// Recipient as the sender wrote it
+    pub recipient: String,
+    /// Address it resolved to when the transfer was held
+    pub recipient_address: String,
+    pub sender_code: String,
+    pub approver_phone: Option<String>,
+    pub approver_code: Option<String>,
+    pub sender_confirmed: bool,
+    /// Sender confirmations before this are refused when there is no guardian
+    pub not_before: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A transfer to hold
+#[derive(Debug, Clone)]
+pub struct NewPendingTransfer<'a> {
+    pub sender_id: Uuid,
+    pub sender_phone: &'a str,
+    pub token: &'a str,
+    pub amount: f64,
+    pub recipient: &'a str,
+    pub recipient_address: &'a str,
+    pub approver_phone: Option<&'a str>,
+    pub not_before: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Result of a CONFIRM for a held transfer
+#[derive(Debug)]
+pub enum ApprovalOutcome {
+    /// Sender confirmed; the guardian still has to. `first` is false when
+    /// the sender had already confirmed
+    AwaitingApprover { transfer: PendingTransfer, first: bool },
+    /// No guardian and the cooling period is still running
+    TooEarly(PendingTransfer),
+    /// Fully approved and claimed for sending; report back with `finish`
+    Approved(PendingTransfer),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApprovalError {
+    #[error("No held transfer for that code")]
+    NotFound,
+    #[error("Held transfer expired")]
+    Expired,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+const PENDING_TRANSFER_COLUMNS: &str = "id, sender_id, sender_phone, token, amount, recipient, recipient_address, sender_code, approver_phone, approver_code, sender_confirmed, not_before, expires_at";
+
+#[derive(Clone)]
+pub struct TransferApprovalRepository {
+    pool: PgPool,
+}
+
+impl TransferApprovalRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Guardian in effect for a user, if any
+    pub async fn guardian(&self, user_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("transfer_approvals.guardian");
+        let row: Option<(Option<String>,)> = sqlx::query_as(
+            "SELECT guardian_phone FROM transfer_guardians
+             WHERE user_id = $1 AND effective_at <= NOW()
+             ORDER BY effective_at DESC, created_at DESC LIMIT 1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.and_then(|(phone,)| phone))
+    }
+
+    /// Set or (with None) remove a user's guardian from `effective_at` on;
+    /// an earlier change that has not applied yet is dropped
+    pub async fn set_guardian(&self, user_id: Uuid, guardian_phone: Option<&str>, effective_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("transfer_approvals.set_guardian");
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM transfer_guardians WHERE user_id = $1 AND effective_at > NOW()")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO transfer_guardians (user_id, guardian_phone, effective_at) VALUES ($1, $2, $3)")
+            .bind(user_id)
+            .bind(guardian_phone)
+            .bind(effective_at)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Hold a transfer with fresh codes
+    pub async fn hold(&self, transfer: &NewPendingTransfer<'_>) -> Result<PendingTransfer, sqlx::Error> {
+        let _timer = QueryTimer::start("transfer_approvals.hold");
+        let (sender_code, approver_code) = generate_code_pair();
+        let approver_code = transfer.approver_phone.map(|_| approver_code);
+        sqlx::query_as::<_, PendingTransfer>(&format!(
+            "INSERT INTO pending_transfers
+                (id, sender_id, sender_phone, token, amount, recipient, recipient_address,
+                 sender_code, approver_phone, approver_code, not_before, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+             RETURNING {}",
+            PENDING_TRANSFER_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(transfer.sender_id)
+        .bind(transfer.sender_phone)
+        .bind(transfer.token)
+        .bind(transfer.amount)
+        .bind(transfer.recipient)
+        .bind(transfer.recipient_address)
+        .bind(sender_code)
+        .bind(transfer.approver_phone)
+        .bind(approver_code)
+        .bind(transfer.not_before)
+        .bind(transfer.expires_at)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Record a CONFIRM from the sender or the guardian. An approved
+    /// transfer is moved out of `pending` in the same statement, so it is
+    /// only ever sent once.
+    pub async fn confirm(&self, phone: &str, code: &str) -> Result<ApprovalOutcome, ApprovalError> {
+        let _timer = QueryTimer::start("transfer_approvals.confirm");
+        let mut tx = self.pool.begin().await?;
+
+        let transfer = sqlx::query_as::<_, PendingTransfer>(&format!(
+            "SELECT {} FROM pending_transfers
+             WHERE status = 'pending'
+               AND ((sender_phone = $1 AND sender_code = $2)
+                    OR (approver_phone = $1 AND approver_code = $2 AND sender_confirmed))
+             FOR UPDATE",
+            PENDING_TRANSFER_COLUMNS
+        ))
+        .bind(phone)
+        .bind(code)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(ApprovalError::NotFound)?;
+
+        let now = Utc::now();
+        if transfer.expires_at <= now {
+            sqlx::query("UPDATE pending_transfers SET status = 'expired' WHERE id = $1")
+                .bind(transfer.id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            return Err(ApprovalError::Expired);
+        }
+
+        let by_sender = transfer.sender_phone == phone && transfer.sender_code == code;
+        let outcome = match (by_sender, transfer.approver_phone.is_some()) {
+            (true, true) => {
+                let first = !transfer.sender_confirmed;
+                let transfer = sqlx::query_as::<_, PendingTransfer>(&format!(
+                    "UPDATE pending_transfers SET sender_confirmed = TRUE WHERE id = $1 RETURNING {}",
+                    PENDING_TRANSFER_COLUMNS
+                ))
+                .bind(transfer.id)
+                .fetch_one(&mut *tx)
+                .await?;
+                ApprovalOutcome::AwaitingApprover { transfer, first }
+            }
+            (true, false) if now < transfer.not_before => ApprovalOutcome::TooEarly(transfer),
+            _ => {
+                let transfer = sqlx::query_as::<_, PendingTransfer>(&format!(
+                    "UPDATE pending_transfers SET sender_confirmed = TRUE, status = 'approved', approved_at = NOW()
+                     WHERE id = $1 RETURNING {}",
+                    PENDING_TRANSFER_COLUMNS
+                ))
+                .bind(transfer.id)
+                .fetch_one(&mut *tx)
+                .await?;
+                ApprovalOutcome::Approved(transfer)
+            }
+        };
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    /// Record whether an approved transfer went out
+    pub async fn finish(&self, id: Uuid, sent: bool) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("transfer_approvals.finish");
+        sqlx::query("UPDATE pending_transfers SET status = $2 WHERE id = $1 AND status = 'approved'")
+            .bind(id)
+            .bind(if sent { "sent" } else { "failed" })
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
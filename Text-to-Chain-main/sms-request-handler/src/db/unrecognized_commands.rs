@@ -0,0 +1,63 @@
+//! What users type that isn't a command, counted per first word so
+//! product can see which words people expect to work
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use super::metrics::QueryTimer;
+
+/// One first word and how often it came in
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct UnrecognizedCommand {
+    /// First word, upper-cased, digits masked
+    pub word: String,
+    /// Latest message starting with it, digits masked
+    pub example: String,
+    /// What the sender was pointed to last time, if anything
+    pub suggestion: Option<String>,
+    pub count: i64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+#[derive(Clone)]
+pub struct UnrecognizedCommandRepository {
+    pool: PgPool,
+}
+
+impl UnrecognizedCommandRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn record(&self, word: &str, example: &str, suggestion: Option<&str>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("unrecognized_commands.record");
+        sqlx::query(
+            "INSERT INTO unrecognized_commands (word, example, suggestion) VALUES ($1, $2, $3)
+             ON CONFLICT (word) DO UPDATE SET
+                 example = EXCLUDED.example,
+                 suggestion = EXCLUDED.suggestion,
+                 count = unrecognized_commands.count + 1,
+                 last_seen = NOW()"
+        )
+        .bind(word)
+        .bind(example)
+        .bind(suggestion)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Most frequent first
+    pub async fn top(&self, limit: i64) -> Result<Vec<UnrecognizedCommand>, sqlx::Error> {
+        let _timer = QueryTimer::start("unrecognized_commands.top");
+        sqlx::query_as::<_, UnrecognizedCommand>(
+            "SELECT word, example, suggestion, count, first_seen, last_seen FROM unrecognized_commands
+             ORDER BY count DESC, last_seen DESC LIMIT $1"
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+}
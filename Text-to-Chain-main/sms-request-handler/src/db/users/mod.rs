@@ -0,0 +1,215 @@
+//! Users: the phone → wallet mapping
+//!
+//! Lookups made for every message are checked against the schema at compile
+//! time (`sqlx::query!`, see "Compile-time checked queries" in the README).
+//! The admin search builds its SQL from the filters it is given, so it lives
+//! in `search` and stays runtime-checked.
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::encryption::{decode_error, FieldCipher};
+use super::metrics::QueryTimer;
+use crate::wallet::KeyVault;
+
+mod search;
+
+pub use search::*;
+
+/// User record in database
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct User {
+    pub id: Uuid,
+    pub phone: String,
+    pub wallet_address: String,
+    pub encrypted_private_key: String,
+    pub pin_hash: Option<String>,
+    pub ens_name: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// User repository for database operations
+///
+/// The phone number is stored encrypted in `phone_encrypted`, while the
+/// `phone` column holds its blind index for lookups. With phones hashed
+/// there is no encrypted copy, and `User::phone` is the blind index unless
+/// the user was found by their number.
+///
+/// Queries select `COALESCE(phone_encrypted, phone) AS "phone!"` into
+/// `User::phone`, and `created_at` as non-null: it always has its default.
+#[derive(Clone)]
+pub struct UserRepository {
+    pool: PgPool,
+    cipher: FieldCipher,
+}
+
+impl UserRepository {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self { pool, cipher }
+    }
+
+    fn decrypt(&self, mut user: User) -> Result<User, sqlx::Error> {
+        user.phone = self.cipher.decrypt(&user.phone).map_err(decode_error)?;
+        Ok(user)
+    }
+
+    /// Find user by phone number
+    pub async fn find_by_phone(&self, phone: &str) -> Result<Option<User>, sqlx::Error> {
+        let _timer = QueryTimer::start("users.find_by_phone");
+        let user = sqlx::query_as!(
+            User,
+            r#"SELECT id, COALESCE(phone_encrypted, phone) AS "phone!", wallet_address, encrypted_private_key,
+                      pin_hash, ens_name, created_at AS "created_at!"
+               FROM users WHERE phone = ANY($1)"#,
+            &self.cipher.lookup_keys(phone)
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        user.map(|u| {
+            let mut user = self.decrypt(u)?;
+            // Hashed: the caller already knows the number
+            if KeyVault::is_blind_index(&user.phone) {
+                user.phone = phone.to_string();
+            }
+            Ok(user)
+        })
+        .transpose()
+    }
+
+    pub async fn find_by_id(&self, id: Uuid) -> Result<Option<User>, sqlx::Error> {
+        let _timer = QueryTimer::start("users.find_by_id");
+        let user = sqlx::query_as!(
+            User,
+            r#"SELECT id, COALESCE(phone_encrypted, phone) AS "phone!", wallet_address, encrypted_private_key,
+                      pin_hash, ens_name, created_at AS "created_at!"
+               FROM users WHERE id = $1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        user.map(|u| self.decrypt(u)).transpose()
+    }
+
+    /// Find the user owning a wallet address (case-insensitive)
+    pub async fn find_by_wallet(&self, wallet_address: &str) -> Result<Option<User>, sqlx::Error> {
+        let _timer = QueryTimer::start("users.find_by_wallet");
+        let user = sqlx::query_as!(
+            User,
+            r#"SELECT id, COALESCE(phone_encrypted, phone) AS "phone!", wallet_address, encrypted_private_key,
+                      pin_hash, ens_name, created_at AS "created_at!"
+               FROM users WHERE LOWER(wallet_address) = LOWER($1)"#,
+            wallet_address
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        user.map(|u| self.decrypt(u)).transpose()
+    }
+
+    /// Create a new user
+    pub async fn create(
+        &self,
+        phone: &str,
+        wallet_address: &str,
+        encrypted_private_key: &str,
+    ) -> Result<User, sqlx::Error> {
+        let _timer = QueryTimer::start("users.create");
+        let id = Uuid::new_v4();
+        let phone_encrypted = self.cipher.encrypt_phone(phone).map_err(decode_error)?;
+
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (id, phone, phone_encrypted, phone_prefixes, wallet_address, encrypted_private_key)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, COALESCE(phone_encrypted, phone) AS "phone!", wallet_address, encrypted_private_key,
+                      pin_hash, ens_name, created_at AS "created_at!"
+            "#,
+            id,
+            self.cipher.blind_index(phone),
+            phone_encrypted,
+            &self.cipher.prefix_indexes(phone),
+            wallet_address,
+            encrypted_private_key
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        let mut user = self.decrypt(user)?;
+        user.phone = phone.to_string();
+        Ok(user)
+    }
+
+    /// Update user's PIN hash
+    pub async fn update_pin(&self, phone: &str, pin_hash: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("users.update_pin");
+        sqlx::query!(
+            "UPDATE users SET pin_hash = $1 WHERE phone = ANY($2)",
+            pin_hash,
+            &self.cipher.lookup_keys(phone)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Update user's ENS name
+    pub async fn update_ens_name(&self, phone: &str, ens_name: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("users.update_ens_name");
+        sqlx::query!(
+            "UPDATE users SET ens_name = $1 WHERE phone = ANY($2)",
+            ens_name,
+            &self.cipher.lookup_keys(phone)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Save the anti-phishing greeting, encrypted; None removes it
+    pub async fn set_greeting(&self, phone: &str, greeting: Option<&str>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("users.set_greeting");
+        sqlx::query!(
+            "UPDATE users SET greeting = $1 WHERE phone = ANY($2)",
+            self.cipher.encrypt_opt(greeting).map_err(decode_error)?,
+            &self.cipher.lookup_keys(phone)
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The user's anti-phishing greeting, if they set one
+    pub async fn greeting(&self, phone: &str) -> Result<Option<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("users.greeting");
+        let stored = sqlx::query_scalar!("SELECT greeting FROM users WHERE phone = ANY($1)", &self.cipher.lookup_keys(phone))
+            .fetch_optional(&self.pool)
+            .await?
+            .flatten();
+        self.cipher.decrypt_opt(stored.as_deref()).map_err(decode_error)
+    }
+
+    /// Record that the user just sent a command
+    pub async fn touch_activity(&self, phone: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("users.touch_activity");
+        sqlx::query!("UPDATE users SET last_active_at = NOW() WHERE phone = ANY($1)", &self.cipher.lookup_keys(phone))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Check if user exists
+    pub async fn exists(&self, phone: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("users.exists");
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM users WHERE phone = ANY($1)) AS "exists!""#,
+            &self.cipher.lookup_keys(phone)
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(exists)
+    }
+}
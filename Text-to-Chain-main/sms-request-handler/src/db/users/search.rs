@@ -0,0 +1,146 @@
+//! Admin user search
+//!
+//! The filters, sort column and order decide the SQL, so it is assembled with
+//! `QueryBuilder` and checked at runtime, unlike the lookups in `users`.
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::{Postgres, QueryBuilder};
+use uuid::Uuid;
+
+use super::UserRepository;
+use crate::db::encryption::decode_error;
+use crate::db::metrics::QueryTimer;
+use crate::money::Money;
+
+/// Admin user search filters; every set filter must match
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UserSearch {
+    /// Start of the phone number, e.g. `+2547`
+    pub phone_prefix: Option<String>,
+    /// Start of the ENS name, case-insensitive
+    pub ens_name: Option<String>,
+    /// Whole wallet address, case-insensitive
+    pub wallet_address: Option<String>,
+    /// Signup time bounds, inclusive
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    /// Cash balance bounds, inclusive
+    pub min_balance: Option<Money>,
+    pub max_balance: Option<Money>,
+    #[serde(default)]
+    pub sort: UserSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserSort {
+    #[default]
+    CreatedAt,
+    Balance,
+    EnsName,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl UserSort {
+    fn column(&self) -> &'static str {
+        match self {
+            UserSort::CreatedAt => "u.created_at",
+            UserSort::Balance => "balance",
+            UserSort::EnsName => "LOWER(u.ens_name)",
+        }
+    }
+}
+
+/// A user in admin search results
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct UserSummary {
+    pub id: Uuid,
+    pub phone: String,
+    pub wallet_address: String,
+    pub ens_name: Option<String>,
+    /// Cash balance in micro-USDC
+    pub balance: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// `LIKE` pattern matching values that start with `prefix`
+fn like_prefix(prefix: &str) -> String {
+    let escaped = prefix.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("{}%", escaped.to_lowercase())
+}
+
+impl UserRepository {
+    /// Users matching `search`, one page in the requested order, and the
+    /// number matching in total
+    pub async fn search(&self, search: &UserSearch, offset: i64, limit: i64) -> Result<(Vec<UserSummary>, i64), sqlx::Error> {
+        let _timer = QueryTimer::start("users.search");
+        let from = "FROM users u LEFT JOIN ledger_balances b ON b.account = 'user:' || u.id::text WHERE TRUE";
+
+        let mut count = QueryBuilder::<Postgres>::new(format!("SELECT COUNT(*) {}", from));
+        self.push_filters(&mut count, search);
+        let total = count.build_query_scalar::<i64>().fetch_one(&self.pool).await?;
+
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "SELECT u.id, COALESCE(u.phone_encrypted, u.phone) AS phone, u.wallet_address, u.ens_name,
+                    COALESCE(b.balance, 0) AS balance, u.created_at {}",
+            from
+        ));
+        self.push_filters(&mut query, search);
+        let order = match search.order {
+            SortOrder::Asc => "ASC NULLS LAST",
+            SortOrder::Desc => "DESC NULLS LAST",
+        };
+        query
+            .push(format!(" ORDER BY {} {}, u.id {}", search.sort.column(), order, order))
+            .push(" OFFSET ")
+            .push_bind(offset)
+            .push(" LIMIT ")
+            .push_bind(limit);
+
+        let users = query
+            .build_query_as::<UserSummary>()
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|mut user| {
+                user.phone = self.cipher.decrypt(&user.phone).map_err(decode_error)?;
+                Ok(user)
+            })
+            .collect::<Result<_, sqlx::Error>>()?;
+        Ok((users, total))
+    }
+
+    fn push_filters(&self, query: &mut QueryBuilder<'_, Postgres>, search: &UserSearch) {
+        if let Some(prefix) = search.phone_prefix.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+            query.push(" AND u.phone_prefixes && ").push_bind(self.cipher.lookup_keys(prefix));
+        }
+        if let Some(name) = search.ens_name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            query.push(" AND LOWER(u.ens_name) LIKE ").push_bind(like_prefix(name));
+        }
+        if let Some(address) = search.wallet_address.as_deref().map(str::trim).filter(|a| !a.is_empty()) {
+            query.push(" AND LOWER(u.wallet_address) = LOWER(").push_bind(address.to_string()).push(")");
+        }
+        if let Some(from) = search.created_from {
+            query.push(" AND u.created_at >= ").push_bind(from);
+        }
+        if let Some(to) = search.created_to {
+            query.push(" AND u.created_at <= ").push_bind(to);
+        }
+        if let Some(min) = search.min_balance {
+            query.push(" AND COALESCE(b.balance, 0) >= ").push_bind(min.micros());
+        }
+        if let Some(max) = search.max_balance {
+            query.push(" AND COALESCE(b.balance, 0) <= ").push_bind(max.micros());
+        }
+    }
+}
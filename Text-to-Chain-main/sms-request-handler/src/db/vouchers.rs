@@ -1,6 +1,14 @@
 use sqlx::PgPool;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::money::Money;
+use crate::sms::display::{NumberLocale, SmsDisplay};
+use super::metrics::QueryTimer;
+
+type HmacSha256 = Hmac<Sha256>;
 
 /// Voucher status
 #[derive(Debug, Clone, PartialEq, sqlx::Type)]
@@ -29,20 +37,60 @@ impl std::fmt::Display for VoucherStatus {
 pub struct Voucher {
     pub id: Uuid,
     pub code: String,
-    pub usdc_amount: i64, // Amount in cents (6 decimal places = micro USDC)
+    pub usdc_amount: Money,
     pub status: String,
     pub redeemed_by: Option<String>,
     pub redeemed_at: Option<DateTime<Utc>>,
     pub expires_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    /// ISO 4217 code when the voucher was issued in a local currency
+    pub local_currency: Option<String>,
+    pub local_amount: Option<f64>,
+    /// Local units per USDC used to compute `usdc_amount`
+    pub fx_rate: Option<f64>,
+    /// Admin batch the voucher was created in
+    pub batch_id: Option<Uuid>,
 }
 
-impl Voucher {
-    /// Get USDC amount as f64
-    pub fn usdc_as_f64(&self) -> f64 {
-        self.usdc_amount as f64 / 1_000_000.0
+/// Columns selected into `Voucher`
+pub const VOUCHER_COLUMNS: &str =
+    "id, code, usdc_amount, status, redeemed_by, redeemed_at, expires_at, created_at, local_currency, local_amount, fx_rate, batch_id";
+
+/// Voucher counts and face value by status
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoucherStats {
+    pub total: i64,
+    pub unused: i64,
+    pub redeemed: i64,
+    pub value_unused: Money,
+    pub value_redeemed: Money,
+    /// Face value of every voucher, including expired ones
+    pub value_total: Money,
+}
+
+/// Local-currency face value of a voucher batch
+#[derive(Debug, Clone)]
+pub struct LocalDenomination {
+    pub currency: String,
+    pub amount: f64,
+    pub rate: f64,
+}
+
+/// Face value, e.g. `KES 1,000 (7.72 USDC)` or `10 USDC`
+impl SmsDisplay for Voucher {
+    fn sms_string(&self, locale: NumberLocale) -> String {
+        match (&self.local_currency, self.local_amount) {
+            (Some(currency), Some(amount)) => {
+                let local = crate::rates::format_local(currency, amount);
+                let (code, value) = local.split_once(' ').unwrap_or((currency, &local));
+                format!("{} {} ({} USDC)", code, locale.number(value), locale.number(&self.usdc_amount.format(2)))
+            }
+            _ => self.usdc_amount.sms_string(locale),
+        }
     }
+}
 
+impl Voucher {
     /// Check if voucher is valid for redemption
     pub fn is_valid(&self) -> bool {
         self.status == "unused" && 
@@ -50,6 +98,65 @@ impl Voucher {
     }
 }
 
+/// Bits of a seeded code's body holding its index; the rest is its HMAC tag
+const SEEDED_INDEX_BITS: u32 = 25;
+const SEEDED_TAG_BITS: u32 = 5 * CODE_BODY_LEN as u32 - SEEDED_INDEX_BITS;
+
+/// Most codes in one seeded batch (about 33.5 million)
+pub const MAX_SEEDED_COUNT: i64 = 1 << SEEDED_INDEX_BITS;
+
+/// Voucher batch whose codes are derived from a secret seed instead of
+/// stored. A code's body is its index in the batch followed by an HMAC tag
+/// of that index, so it can be checked without a row; the row is written
+/// when the code is first used.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct SeededBatch {
+    /// Also the `batch_id` of its codes' rows once issued
+    pub id: Uuid,
+    /// Unique among seeded batches: it tells which batch a code is from
+    pub prefix: String,
+    /// 32 random bytes as hex
+    pub seed: String,
+    pub count: i64,
+    pub usdc_amount: Money,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub local_currency: Option<String>,
+    pub local_amount: Option<f64>,
+    pub fx_rate: Option<f64>,
+}
+
+const SEEDED_COLUMNS: &str =
+    "id, prefix, seed, count, usdc_amount, expires_at, local_currency, local_amount, fx_rate";
+
+impl SeededBatch {
+    fn tag(&self, index: u64) -> u64 {
+        let mut mac = HmacSha256::new_from_slice(self.seed.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(self.prefix.as_bytes());
+        mac.update(&index.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+        u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 is 32 bytes")) >> (64 - SEEDED_TAG_BITS)
+    }
+
+    /// Code number `index` (from 0) of the batch
+    pub fn code(&self, index: u64) -> String {
+        let bits = (index << SEEDED_TAG_BITS) | self.tag(index);
+        let body: String = (0..CODE_BODY_LEN)
+            .rev()
+            .map(|i| CODE_ALPHABET[((bits >> (5 * i)) & 31) as usize] as char)
+            .collect();
+        let check = check_char(&body).expect("body uses the code alphabet");
+        format!("{}{}{}", self.prefix, body, check)
+    }
+
+    /// Index of a normalized code in this batch (None = not one of its codes)
+    pub fn index_of(&self, code: &str) -> Option<u64> {
+        let body = code.strip_prefix(self.prefix.as_str())?.get(..CODE_BODY_LEN)?;
+        let bits = body.chars().try_fold(0u64, |bits, c| Some(bits << 5 | u64::from(code_value(c)?)))?;
+        let (index, tag) = (bits >> SEEDED_TAG_BITS, bits & ((1 << SEEDED_TAG_BITS) - 1));
+        (index < self.count.max(0) as u64 && tag == self.tag(index)).then_some(index)
+    }
+}
+
 /// Voucher repository for database operations
 #[derive(Clone)]
 pub struct VoucherRepository {
@@ -63,19 +170,63 @@ impl VoucherRepository {
 
     /// Find voucher by code
     pub async fn find_by_code(&self, code: &str) -> Result<Option<Voucher>, sqlx::Error> {
-        sqlx::query_as::<_, Voucher>(
-            "SELECT id, code, usdc_amount, status, redeemed_by, redeemed_at, expires_at, created_at 
-             FROM vouchers WHERE UPPER(code) = UPPER($1)"
-        )
+        let _timer = QueryTimer::start("vouchers.find_by_code");
+        sqlx::query_as::<_, Voucher>(&format!(
+            "SELECT {} FROM vouchers WHERE UPPER(code) = UPPER($1)",
+            VOUCHER_COLUMNS
+        ))
         .bind(code)
         .fetch_optional(&self.pool)
         .await
     }
 
+    /// Find a voucher by code, writing its row first if it is an unused
+    /// code of a seeded batch
+    pub async fn find_or_issue(&self, code: &str) -> Result<Option<Voucher>, sqlx::Error> {
+        if let Some(voucher) = self.find_by_code(code).await? {
+            return Ok(Some(voucher));
+        }
+
+        let _timer = QueryTimer::start("vouchers.find_or_issue");
+        let code = code.to_uppercase();
+        let Some(prefix) = code.len().checked_sub(CODE_BODY_LEN + 1).and_then(|len| code.get(..len)) else {
+            return Ok(None);
+        };
+        let batch = sqlx::query_as::<_, SeededBatch>(&format!(
+            "SELECT {} FROM voucher_seeds WHERE prefix = $1",
+            SEEDED_COLUMNS
+        ))
+        .bind(prefix)
+        .fetch_optional(&self.pool)
+        .await?;
+        let Some(batch) = batch.filter(|batch| batch.index_of(&code).is_some()) else {
+            return Ok(None);
+        };
+
+        // A concurrent first use may have written it already
+        sqlx::query(
+            "INSERT INTO vouchers (id, code, usdc_amount, status, expires_at, local_currency, local_amount, fx_rate, batch_id)
+             VALUES ($1, $2, $3, 'unused', $4, $5, $6, $7, $8)
+             ON CONFLICT (code) DO NOTHING"
+        )
+        .bind(Uuid::new_v4())
+        .bind(&code)
+        .bind(batch.usdc_amount)
+        .bind(batch.expires_at)
+        .bind(&batch.local_currency)
+        .bind(batch.local_amount)
+        .bind(batch.fx_rate)
+        .bind(batch.id)
+        .execute(&self.pool)
+        .await?;
+        self.find_by_code(&code).await
+    }
+
     /// Redeem a voucher for a user
     pub async fn redeem(&self, code: &str, phone: &str) -> Result<Voucher, VoucherError> {
+        let _timer = QueryTimer::start("vouchers.redeem");
         // First, find and validate the voucher
-        let voucher = self.find_by_code(code).await
+        let voucher = self.find_or_issue(code).await
             .map_err(|e| VoucherError::DatabaseError(e.to_string()))?
             .ok_or(VoucherError::NotFound)?;
 
@@ -88,8 +239,9 @@ impl VoucherRepository {
             return Err(VoucherError::Expired);
         }
 
-        // Update voucher status
-        sqlx::query(
+        // Update voucher status; only one of several concurrent redemptions
+        // gets the row while it is still unused
+        let result = sqlx::query(
             "UPDATE vouchers SET status = 'redeemed', redeemed_by = $1, redeemed_at = NOW() 
              WHERE id = $2 AND status = 'unused'"
         )
@@ -98,6 +250,9 @@ impl VoucherRepository {
         .execute(&self.pool)
         .await
         .map_err(|e| VoucherError::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(VoucherError::AlreadyRedeemed);
+        }
 
         // Return updated voucher
         self.find_by_code(code).await
@@ -105,49 +260,249 @@ impl VoucherRepository {
             .ok_or(VoucherError::DatabaseError("Failed to fetch updated voucher".to_string()))
     }
 
-    /// Create a batch of vouchers (admin function)
-    pub async fn create_batch(
+    /// Create a batch of `count` vouchers (admin function), drawing a new
+    /// code whenever one collides with an existing voucher
+    pub async fn create_generated(
         &self,
-        codes: &[String],
-        usdc_amount: i64,
+        count: usize,
+        prefix: &str,
+        usdc_amount: Money,
         expires_at: Option<DateTime<Utc>>,
-    ) -> Result<Vec<Voucher>, sqlx::Error> {
-        let mut vouchers = Vec::new();
-
-        for code in codes {
-            let id = Uuid::new_v4();
-            let voucher = sqlx::query_as::<_, Voucher>(
-                r#"
-                INSERT INTO vouchers (id, code, usdc_amount, status, expires_at)
-                VALUES ($1, $2, $3, 'unused', $4)
-                RETURNING id, code, usdc_amount, status, redeemed_by, redeemed_at, expires_at, created_at
-                "#
-            )
-            .bind(id)
-            .bind(code.to_uppercase())
-            .bind(usdc_amount)
-            .bind(expires_at)
-            .fetch_one(&self.pool)
-            .await?;
+        local: Option<&LocalDenomination>,
+    ) -> Result<(Uuid, Vec<Voucher>), sqlx::Error> {
+        let _timer = QueryTimer::start("vouchers.create_generated");
+        let batch_id = Uuid::new_v4();
+        let mut vouchers = Vec::with_capacity(count);
 
+        for _ in 0..count {
+            let mut attempt = 0;
+            let voucher = loop {
+                match self.insert(batch_id, &generate_code(prefix), usdc_amount, expires_at, local).await {
+                    Err(sqlx::Error::Database(e)) if e.is_unique_violation() && attempt < MAX_CODE_ATTEMPTS => {
+                        attempt += 1;
+                        tracing::warn!(attempt, "Voucher code collision, regenerating");
+                    }
+                    result => break result?,
+                }
+            };
             vouchers.push(voucher);
         }
 
-        Ok(vouchers)
+        Ok((batch_id, vouchers))
+    }
+
+    /// Register a seeded batch of `count` codes; no voucher rows are written
+    pub async fn create_seeded(
+        &self,
+        count: i64,
+        prefix: &str,
+        usdc_amount: Money,
+        expires_at: Option<DateTime<Utc>>,
+        local: Option<&LocalDenomination>,
+    ) -> Result<SeededBatch, sqlx::Error> {
+        let _timer = QueryTimer::start("vouchers.create_seeded");
+        let seed: [u8; 32] = rand::random();
+        sqlx::query_as::<_, SeededBatch>(&format!(
+            "INSERT INTO voucher_seeds (id, prefix, seed, count, usdc_amount, expires_at, local_currency, local_amount, fx_rate)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+             RETURNING {}",
+            SEEDED_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(prefix.to_uppercase())
+        .bind(hex::encode(seed))
+        .bind(count)
+        .bind(usdc_amount)
+        .bind(expires_at)
+        .bind(local.map(|l| l.currency.clone()))
+        .bind(local.map(|l| l.amount))
+        .bind(local.map(|l| l.rate))
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn find_seeded(&self, batch_id: Uuid) -> Result<Option<SeededBatch>, sqlx::Error> {
+        let _timer = QueryTimer::start("vouchers.find_seeded");
+        sqlx::query_as::<_, SeededBatch>(&format!("SELECT {} FROM voucher_seeds WHERE id = $1", SEEDED_COLUMNS))
+            .bind(batch_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Counts and face value of all vouchers, seeded codes not yet used
+    /// counted as unused
+    pub async fn stats(&self) -> Result<VoucherStats, sqlx::Error> {
+        let _timer = QueryTimer::start("vouchers.stats");
+        let rows = sqlx::query_as::<_, (String, i64, Money)>(
+            "SELECT status, COUNT(*), COALESCE(SUM(usdc_amount), 0)::BIGINT FROM vouchers GROUP BY status",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut stats = VoucherStats {
+            total: 0,
+            unused: 0,
+            redeemed: 0,
+            value_unused: Money::usdc(0),
+            value_redeemed: Money::usdc(0),
+            value_total: Money::usdc(0),
+        };
+        // Seeded codes without a row yet
+        let unissued = sqlx::query_as::<_, (i64, Money)>(
+            "SELECT COALESCE(SUM(s.count - i.issued), 0)::BIGINT,
+                    COALESCE(SUM((s.count - i.issued) * s.usdc_amount), 0)::BIGINT
+             FROM voucher_seeds s
+             CROSS JOIN LATERAL (SELECT COUNT(*) AS issued FROM vouchers v WHERE v.batch_id = s.id) i",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        for (status, count, value) in rows.into_iter().chain([("unused".to_string(), unissued.0, unissued.1)]) {
+            stats.total += count;
+            stats.value_total = stats
+                .value_total
+                .checked_add(value)
+                .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+            match status.as_str() {
+                "unused" => {
+                    stats.unused += count;
+                    stats.value_unused = stats.value_unused.checked_add(value).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+                }
+                "redeemed" => (stats.redeemed, stats.value_redeemed) = (count, value),
+                _ => {}
+            }
+        }
+        Ok(stats)
+    }
+
+    /// All vouchers created in one admin batch, in creation order
+    pub async fn list_batch(&self, batch_id: Uuid) -> Result<Vec<Voucher>, sqlx::Error> {
+        let _timer = QueryTimer::start("vouchers.list_batch");
+        sqlx::query_as::<_, Voucher>(&format!(
+            "SELECT {} FROM vouchers WHERE batch_id = $1 ORDER BY created_at, code",
+            VOUCHER_COLUMNS
+        ))
+        .bind(batch_id)
+        .fetch_all(&self.pool)
+        .await
     }
 
-    /// Generate random voucher codes
-    pub fn generate_codes(count: usize, prefix: &str) -> Vec<String> {
-        use rand::Rng;
-        let mut rng = rand::thread_rng();
-        
-        (0..count)
-            .map(|_| {
-                let random: u32 = rng.gen_range(100000..999999);
-                format!("{}{}", prefix, random)
+    async fn insert(
+        &self,
+        batch_id: Uuid,
+        code: &str,
+        usdc_amount: Money,
+        expires_at: Option<DateTime<Utc>>,
+        local: Option<&LocalDenomination>,
+    ) -> Result<Voucher, sqlx::Error> {
+        sqlx::query_as::<_, Voucher>(&format!(
+            r#"
+            INSERT INTO vouchers (id, code, usdc_amount, status, expires_at, local_currency, local_amount, fx_rate, batch_id)
+            VALUES ($1, $2, $3, 'unused', $4, $5, $6, $7, $8)
+            RETURNING {}
+            "#,
+            VOUCHER_COLUMNS
+        ))
+        .bind(Uuid::new_v4())
+        .bind(code.to_uppercase())
+        .bind(usdc_amount)
+        .bind(expires_at)
+        .bind(local.map(|l| l.currency.clone()))
+        .bind(local.map(|l| l.amount))
+        .bind(local.map(|l| l.rate))
+        .bind(batch_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+}
+
+/// Crockford base32: no I, L, O or U, so codes survive being read aloud
+const CODE_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Random characters per code (50 bits)
+pub const CODE_BODY_LEN: usize = 10;
+
+/// Longest prefix that still fits `vouchers.code`
+pub const MAX_CODE_PREFIX_LEN: usize = 8;
+
+/// Fresh codes tried per voucher before giving up on collisions
+const MAX_CODE_ATTEMPTS: u32 = 5;
+
+/// Generate `<prefix><10 random base32 chars><check char>`
+pub fn generate_code(prefix: &str) -> String {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+
+    let body: String = (0..CODE_BODY_LEN)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect();
+    let check = check_char(&body).expect("body uses the code alphabet");
+    format!("{}{}{}", prefix.to_uppercase(), body, check)
+}
+
+/// Luhn mod 32 check character; catches any single-character typo and
+/// most swaps of adjacent characters
+fn check_char(body: &str) -> Option<char> {
+    let n = CODE_ALPHABET.len() as u32;
+    let mut factor = 2;
+    let mut sum = 0;
+    for c in body.chars().rev() {
+        let addend = factor * code_value(c)?;
+        sum += addend / n + addend % n;
+        factor = if factor == 2 { 1 } else { 2 };
+    }
+    Some(CODE_ALPHABET[((n - sum % n) % n) as usize] as char)
+}
+
+fn code_value(c: char) -> Option<u32> {
+    CODE_ALPHABET.iter().position(|&a| a as char == c).map(|p| p as u32)
+}
+
+/// Result of checking a code typed by a user
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodeCheck {
+    /// Check character matches; the normalized code to look up
+    Valid(String),
+    /// Pre-checksum code (`<prefix><6 digits>`), can only be checked in the DB
+    Legacy(String),
+    /// Neither format - most likely a typo
+    Mistyped,
+}
+
+/// Validate a voucher code before it reaches the database. Spaces and
+/// dashes are ignored, and the easily-confused O, I and L are read as 0, 1, 1.
+pub fn check_code(input: &str) -> CodeCheck {
+    let code: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect::<String>()
+        .to_uppercase();
+
+    // Prefix stays as typed; only the checked tail is normalized
+    let tail_len = CODE_BODY_LEN + 1;
+    if code.is_ascii() && (tail_len..=tail_len + MAX_CODE_PREFIX_LEN).contains(&code.len()) {
+        let (prefix, tail) = code.split_at(code.len() - tail_len);
+        let tail: String = tail
+            .chars()
+            .map(|c| match c {
+                'O' => '0',
+                'I' | 'L' => '1',
+                other => other,
             })
-            .collect()
+            .collect();
+        let (body, check) = tail.split_at(CODE_BODY_LEN);
+        if check_char(body).is_some_and(|expected| check.starts_with(expected)) {
+            return CodeCheck::Valid(format!("{}{}", prefix, tail));
+        }
     }
+
+    let digits = code.chars().rev().take_while(|c| c.is_ascii_digit()).count();
+    let prefix_len = code.len() - digits;
+    if digits == 6 && prefix_len <= MAX_CODE_PREFIX_LEN && code[..prefix_len].chars().all(|c| c.is_ascii_alphabetic()) {
+        return CodeCheck::Legacy(code);
+    }
+
+    CodeCheck::Mistyped
 }
 
 #[derive(Debug, Clone)]
@@ -170,3 +525,65 @@ impl std::fmt::Display for VoucherError {
 }
 
 impl std::error::Error for VoucherError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_codes_validate() {
+        for _ in 0..100 {
+            let code = generate_code("ttc");
+            assert_eq!(code.len(), 3 + CODE_BODY_LEN + 1);
+            assert!(code.starts_with("TTC"));
+            assert_eq!(check_code(&code), CodeCheck::Valid(code.clone()));
+        }
+    }
+
+    #[test]
+    fn test_typos_are_caught() {
+        let code = format!("TTC{}{}", "0123456789", check_char("0123456789").unwrap());
+
+        // Lowercase, dashes and O/I look-alikes are accepted
+        let typed = format!("ttc-o1234-56789{}", &code[13..]).to_lowercase();
+        assert_eq!(check_code(&typed), CodeCheck::Valid(code.clone()));
+
+        // Single wrong character and adjacent swap
+        assert_eq!(check_code(&code.replacen('5', "6", 1)), CodeCheck::Mistyped);
+        assert_eq!(check_code(&code.replacen("45", "54", 1)), CodeCheck::Mistyped);
+    }
+
+    fn seeded_batch(seed: &str) -> SeededBatch {
+        SeededBatch {
+            id: Uuid::new_v4(),
+            prefix: "BIG".to_string(),
+            seed: seed.to_string(),
+            count: 1_000_000,
+            usdc_amount: Money::usdc(1_000_000),
+            expires_at: None,
+            local_currency: None,
+            local_amount: None,
+            fx_rate: None,
+        }
+    }
+
+    #[test]
+    fn test_seeded_codes_check_without_rows() {
+        let batch = seeded_batch("aa".repeat(32).as_str());
+        for index in [0, 1, 77_777, 999_999] {
+            let code = batch.code(index);
+            assert_eq!(check_code(&code), CodeCheck::Valid(code.clone()));
+            assert_eq!(batch.index_of(&code), Some(index));
+        }
+
+        // Past the batch size, or from another seed, the code is not in the batch
+        assert_eq!(batch.index_of(&batch.code(1_000_000)), None);
+        assert_eq!(batch.index_of(&seeded_batch("bb".repeat(32).as_str()).code(5)), None);
+    }
+
+    #[test]
+    fn test_legacy_codes_pass_through() {
+        assert_eq!(check_code("ttc123456"), CodeCheck::Legacy("TTC123456".to_string()));
+        assert_eq!(check_code("TTC12345"), CodeCheck::Mistyped);
+    }
+}
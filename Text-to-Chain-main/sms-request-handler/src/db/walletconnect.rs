@@ -0,0 +1,157 @@
+//! WalletConnect pairings and the dApp requests awaiting SMS approval
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::metrics::QueryTimer;
+
+/// How long a signature request waits for SIGN / REJECT
+pub const WC_REQUEST_TTL_MINUTES: i64 = 5;
+
+/// A pairing created by CONNECT
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WcSession {
+    pub topic: String,
+    pub phone: String,
+    /// pending (URI issued), active (dApp connected) or closed
+    pub status: String,
+    pub peer_name: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A dApp request waiting for the user
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct WcRequest {
+    pub id: Uuid,
+    pub topic: String,
+    /// JSON-RPC id assigned by the dApp
+    pub rpc_id: i64,
+    pub method: String,
+    /// JSON-encoded params array
+    pub params: String,
+    pub peer_name: Option<String>,
+}
+
+const WC_SESSION_COLUMNS: &str = "topic, phone, status, peer_name, expires_at";
+
+const WC_REQUEST_COLUMNS: &str = "r.id, r.topic, r.rpc_id, r.method, r.params, s.peer_name";
+
+#[derive(Clone)]
+pub struct WalletConnectRepository {
+    pool: PgPool,
+}
+
+impl WalletConnectRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Record a new pairing for a phone number
+    pub async fn create_session(&self, phone: &str, topic: &str, expires_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.create_session");
+        sqlx::query("INSERT INTO wc_sessions (topic, phone, status, expires_at) VALUES ($1, $2, 'pending', $3)")
+            .bind(topic)
+            .bind(phone)
+            .bind(expires_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn find_session(&self, topic: &str) -> Result<Option<WcSession>, sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.find_session");
+        sqlx::query_as::<_, WcSession>(&format!("SELECT {} FROM wc_sessions WHERE topic = $1", WC_SESSION_COLUMNS))
+            .bind(topic)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Mark a pairing as connected to a dApp; None if the URI expired or
+    /// the session was closed
+    pub async fn activate_session(&self, topic: &str, peer_name: &str) -> Result<Option<WcSession>, sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.activate_session");
+        sqlx::query_as::<_, WcSession>(&format!(
+            "UPDATE wc_sessions SET status = 'active', peer_name = $2
+             WHERE topic = $1 AND (status = 'active' OR (status = 'pending' AND expires_at > NOW()))
+             RETURNING {}",
+            WC_SESSION_COLUMNS
+        ))
+        .bind(topic)
+        .bind(peer_name)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Close one session (dApp disconnected)
+    pub async fn close_session(&self, topic: &str) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.close_session");
+        sqlx::query("UPDATE wc_sessions SET status = 'closed' WHERE topic = $1")
+            .bind(topic)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Close every open session for a phone, returning their topics
+    pub async fn close_all(&self, phone: &str) -> Result<Vec<String>, sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.close_all");
+        sqlx::query_scalar::<_, String>(
+            "UPDATE wc_sessions SET status = 'closed' WHERE phone = $1 AND status <> 'closed' RETURNING topic"
+        )
+        .bind(phone)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Queue a dApp request for approval
+    pub async fn add_request(
+        &self,
+        session: &WcSession,
+        rpc_id: i64,
+        method: &str,
+        params: &str,
+    ) -> Result<(), sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.add_request");
+        sqlx::query(
+            "INSERT INTO wc_requests (id, topic, phone, rpc_id, method, params, status, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6, 'pending', $7)"
+        )
+        .bind(Uuid::new_v4())
+        .bind(&session.topic)
+        .bind(&session.phone)
+        .bind(rpc_id)
+        .bind(method)
+        .bind(params)
+        .bind(Utc::now() + Duration::minutes(WC_REQUEST_TTL_MINUTES))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Oldest unexpired pending request for a phone
+    pub async fn next_pending(&self, phone: &str) -> Result<Option<WcRequest>, sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.next_pending");
+        sqlx::query_as::<_, WcRequest>(&format!(
+            "SELECT {} FROM wc_requests r JOIN wc_sessions s ON s.topic = r.topic
+             WHERE r.phone = $1 AND r.status = 'pending' AND r.expires_at > NOW() AND s.status = 'active'
+             ORDER BY r.created_at
+             LIMIT 1",
+            WC_REQUEST_COLUMNS
+        ))
+        .bind(phone)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Move a request out of pending; false if it was already handled
+    pub async fn finish_request(&self, id: Uuid, status: &str) -> Result<bool, sqlx::Error> {
+        let _timer = QueryTimer::start("walletconnect.finish_request");
+        let result = sqlx::query("UPDATE wc_requests SET status = $2 WHERE id = $1 AND status = 'pending'")
+            .bind(id)
+            .bind(status)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
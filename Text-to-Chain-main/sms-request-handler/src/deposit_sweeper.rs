@@ -0,0 +1,483 @@
+//! Consolidating per-user deposit addresses into the treasury
+//!
+//! Confirmed deposits to HD deposit addresses (see
+//! `wallet::deposit_address`) are swept to `DEPOSIT_TREASURY_ADDRESS` every
+//! `DEPOSIT_SWEEP_SECS`. Each run works out all transfers for a chain before
+//! sending any, to keep gas down:
+//!
+//! - one gas price per chain per run, with exact gas limits, so a top-up
+//!   covers its sweeps without leaving dust behind
+//! - balances under `DEPOSIT_SWEEP_MINIMUMS` wait until more arrives
+//! - native funds already at an address pay for its token sweeps; only the
+//!   shortfall is topped up, once per address whatever its token count
+//! - top-ups go out back to back from the hot wallet with sequential nonces
+//!   and are awaited together before the sweeps are sent
+//!
+//! Every sweep transaction is recorded in `deposit_sweeps` and the deposits
+//! it covers are marked, so they are not swept twice.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::Middleware;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, BlockNumber, TransactionRequest, U256};
+use ethers::utils::{format_units, parse_units};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::config::DepositAddressConfig;
+use crate::db::{DepositSweep, SweepCandidate};
+use crate::money::{Currency, Money};
+use crate::wallet::token_registry::token_address;
+use crate::wallet::{create_chain_provider, Chain, ChainProvider, DepositAddresses, IERC20};
+
+/// Gas limit of a plain native transfer
+const NATIVE_TRANSFER_GAS: u64 = 21_000;
+/// Gas limit of a token sweep; an ERC-20 transfer to an existing holder
+/// takes 35k-60k
+const TOKEN_TRANSFER_GAS: u64 = 65_000;
+/// Sweeps listed by the admin API
+const LIST_LIMIT: i64 = 100;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SweepError {
+    #[error("Invalid deposit sweep config: {0}")]
+    Config(String),
+    #[error("RPC error: {0}")]
+    Rpc(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// What one deposit address holds of one token, read at sweep time
+#[derive(Debug, Clone)]
+struct Holding {
+    address: Address,
+    /// None for the chain's native token
+    token: Option<Address>,
+    balance: U256,
+    /// Smallest balance worth sweeping
+    minimum: U256,
+}
+
+/// Transfers for one chain, worked out before anything is sent
+#[derive(Debug, Default, PartialEq)]
+struct SweepPlan {
+    /// Gas the hot wallet sends each address first
+    top_ups: Vec<(Address, U256)>,
+    /// Holding index and the amount to move
+    sweeps: Vec<(usize, U256)>,
+    /// Holding index and why it waits for a later run
+    skipped: Vec<(usize, &'static str)>,
+}
+
+fn plan(holdings: &[Holding], native_balances: &HashMap<Address, U256>, gas_price: U256) -> SweepPlan {
+    let token_fee = gas_price * TOKEN_TRANSFER_GAS;
+    let native_fee = gas_price * NATIVE_TRANSFER_GAS;
+    let mut plan = SweepPlan::default();
+    let mut gas_needed: HashMap<Address, U256> = HashMap::new();
+
+    for (i, holding) in holdings.iter().enumerate().filter(|(_, h)| h.token.is_some()) {
+        if holding.balance.is_zero() || holding.balance < holding.minimum {
+            plan.skipped.push((i, "below the sweep minimum"));
+            continue;
+        }
+        *gas_needed.entry(holding.address).or_default() += token_fee;
+        plan.sweeps.push((i, holding.balance));
+    }
+
+    // Native funds at an address with token sweeps pay their gas; the rest
+    // goes next run
+    for (i, holding) in holdings.iter().enumerate().filter(|(_, h)| h.token.is_none()) {
+        if gas_needed.contains_key(&holding.address) {
+            plan.skipped.push((i, "paying for token sweeps"));
+            continue;
+        }
+        match holding.balance.checked_sub(native_fee) {
+            Some(value) if !value.is_zero() && holding.balance >= holding.minimum => plan.sweeps.push((i, value)),
+            _ => plan.skipped.push((i, "below the sweep minimum")),
+        }
+    }
+
+    plan.top_ups = gas_needed
+        .into_iter()
+        .filter_map(|(address, needed)| {
+            let held = native_balances.get(&address).copied().unwrap_or_default();
+            (held < needed).then(|| (address, needed - held))
+        })
+        .collect();
+    plan.top_ups.sort();
+    plan
+}
+
+/// Result of one sweep run
+#[derive(Debug, Default, Serialize)]
+pub struct SweepReport {
+    pub swept: Vec<DepositSweep>,
+    /// Balances left for a later run (too small, or unknown token/chain)
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Sweeps deposit addresses into the treasury
+#[derive(Clone)]
+pub struct DepositSweeper {
+    addresses: DepositAddresses,
+    treasury: Address,
+    /// Hot wallet paying token sweep gas (None = token sweeps wait for
+    /// native deposits to pay for them)
+    funder: Option<LocalWallet>,
+    minimums: HashMap<Currency, Money>,
+    batch: i64,
+    /// One run at a time, whether timed or triggered by an admin
+    running: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl DepositSweeper {
+    pub fn from_config(
+        config: &DepositAddressConfig,
+        addresses: DepositAddresses,
+        admin_private_key: &str,
+    ) -> Result<Self, SweepError> {
+        let funder = match admin_private_key.trim() {
+            "" => None,
+            key => Some(
+                key.trim_start_matches("0x")
+                    .parse::<LocalWallet>()
+                    .map_err(|_| SweepError::Config("ADMIN_PRIVATE_KEY is not a private key".to_string()))?,
+            ),
+        };
+        let treasury = match (config.treasury_address.trim(), &funder) {
+            ("", Some(funder)) => funder.address(),
+            ("", None) => return Err(SweepError::Config("set DEPOSIT_TREASURY_ADDRESS or ADMIN_PRIVATE_KEY".to_string())),
+            (address, _) => address
+                .parse()
+                .map_err(|_| SweepError::Config(format!("DEPOSIT_TREASURY_ADDRESS {}", address)))?,
+        };
+        Ok(Self {
+            addresses,
+            treasury,
+            funder,
+            minimums: parse_minimums(&config.sweep_minimums)?,
+            batch: config.sweep_batch.max(1),
+            running: Arc::default(),
+        })
+    }
+
+    pub fn treasury(&self) -> Address {
+        self.treasury
+    }
+
+    /// Sweep every address with confirmed, unswept deposits (up to the batch size)
+    pub async fn run(&self) -> Result<SweepReport, SweepError> {
+        let _running = self.running.lock().await;
+        let candidates = self.addresses.repo().sweep_candidates(self.batch).await?;
+        let mut report = SweepReport::default();
+
+        let mut by_chain: HashMap<Chain, Vec<SweepCandidate>> = HashMap::new();
+        for candidate in candidates {
+            match Chain::from_input(&candidate.chain) {
+                Some(chain) => by_chain.entry(chain).or_default().push(candidate),
+                None => {
+                    tracing::warn!(chain = %candidate.chain, address = %candidate.address, "Not sweeping an unknown chain");
+                    report.skipped += 1;
+                }
+            }
+        }
+
+        for (chain, candidates) in by_chain {
+            if let Err(e) = self.sweep_chain(chain, &candidates, &mut report).await {
+                tracing::warn!(%chain, "Deposit sweep failed: {}", e);
+                report.failed += candidates.len();
+            }
+        }
+        Ok(report)
+    }
+
+    /// Read balances, plan, top up, then sweep. Errors before anything is
+    /// sent fail the whole chain; later ones only the sweep concerned.
+    async fn sweep_chain(&self, chain: Chain, candidates: &[SweepCandidate], report: &mut SweepReport) -> Result<(), SweepError> {
+        let provider = create_chain_provider(chain);
+        let rpc = |e: &dyn std::fmt::Display| SweepError::Rpc(e.to_string());
+        let gas_price = provider.get_gas_price().await.map_err(|e| rpc(&e))?;
+
+        let mut holdings = Vec::new();
+        let mut swept = Vec::new();
+        let mut native_balances: HashMap<Address, U256> = HashMap::new();
+        for candidate in candidates {
+            let Ok(address) = candidate.address.parse::<Address>() else {
+                report.skipped += 1;
+                continue;
+            };
+            if let std::collections::hash_map::Entry::Vacant(entry) = native_balances.entry(address) {
+                entry.insert(provider.get_balance(address, None).await.map_err(|e| rpc(&e))?);
+            }
+
+            let (token, balance, decimals) = if candidate.token == chain.native_token() {
+                (None, native_balances[&address], 18u8)
+            } else {
+                let Some(token) = token_address(chain, &candidate.token) else {
+                    tracing::warn!(%chain, token = %candidate.token, "Not sweeping a token without a known contract");
+                    report.skipped += 1;
+                    continue;
+                };
+                let contract = IERC20::new(token, provider.clone());
+                let balance = contract.balance_of(address).call().await.map_err(|e| rpc(&e))?;
+                let decimals = contract.decimals().call().await.map_err(|e| rpc(&e))?;
+                (Some(token), balance, decimals)
+            };
+            let minimum = Currency::parse(&candidate.token)
+                .and_then(|currency| self.minimums.get(&currency))
+                .and_then(|minimum| parse_units(minimum.amount(), decimals as u32).ok())
+                .map(U256::from)
+                .unwrap_or_default();
+
+            holdings.push(Holding { address, token, balance, minimum });
+            swept.push((candidate, decimals));
+        }
+
+        let plan = plan(&holdings, &native_balances, gas_price);
+        for (i, reason) in &plan.skipped {
+            tracing::debug!(%chain, address = %swept[*i].0.address, token = %swept[*i].0.token, reason, "Sweep deferred");
+        }
+        report.skipped += plan.skipped.len();
+
+        let (gas_txs, unfunded) = self.top_up(chain, &provider, &plan.top_ups, gas_price).await?;
+
+        for (i, amount) in plan.sweeps {
+            let (candidate, decimals) = swept[i];
+            let holding = &holdings[i];
+            if unfunded.contains(&holding.address) {
+                report.failed += 1;
+                continue;
+            }
+            match self.send_sweep(chain, &provider, candidate, holding, amount, gas_price).await {
+                Ok(tx_hash) => {
+                    let amount = format_units(amount, decimals as u32).unwrap_or_else(|_| amount.to_string());
+                    let gas_tx = gas_txs.get(&holding.address).map(String::as_str);
+                    let sweep = self
+                        .addresses
+                        .repo()
+                        .record_sweep(&candidate.chain, &candidate.token, &candidate.address, &amount, &tx_hash, gas_tx)
+                        .await?;
+                    tracing::info!(%chain, address = %candidate.address, token = %candidate.token, %amount, credited = %candidate.credited, %tx_hash, "Deposit address swept");
+                    report.swept.push(sweep);
+                }
+                Err(e) => {
+                    tracing::warn!(%chain, address = %candidate.address, token = %candidate.token, "Sweep not sent: {}", e);
+                    report.failed += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Send every top-up with consecutive nonces, then wait for all of
+    /// them. Returns each funded address's top-up hash, and the addresses
+    /// that could not be funded.
+    async fn top_up(
+        &self,
+        chain: Chain,
+        provider: &Arc<ChainProvider>,
+        top_ups: &[(Address, U256)],
+        gas_price: U256,
+    ) -> Result<(HashMap<Address, String>, HashSet<Address>), SweepError> {
+        let mut gas_txs = HashMap::new();
+        let mut unfunded: HashSet<Address> = top_ups.iter().map(|(address, _)| *address).collect();
+        if top_ups.is_empty() {
+            return Ok((gas_txs, unfunded));
+        }
+        let Some(ref funder) = self.funder else {
+            tracing::warn!(%chain, addresses = top_ups.len(), "Token sweeps need gas but no hot wallet is configured");
+            return Ok((gas_txs, unfunded));
+        };
+
+        let client = SignerMiddleware::new(provider.clone(), funder.clone().with_chain_id(chain.chain_id()));
+        let mut nonce = provider
+            .get_transaction_count(funder.address(), Some(BlockNumber::Pending.into()))
+            .await
+            .map_err(|e| SweepError::Rpc(e.to_string()))?;
+        let mut pending = Vec::new();
+        for (address, amount) in top_ups {
+            let tx = TransactionRequest::new()
+                .to(*address)
+                .value(*amount)
+                .gas(NATIVE_TRANSFER_GAS)
+                .gas_price(gas_price)
+                .nonce(nonce);
+            match client.send_transaction(tx, None).await {
+                Ok(sent) => {
+                    nonce += U256::one();
+                    gas_txs.insert(*address, format!("{:?}", sent.tx_hash()));
+                    pending.push((*address, sent));
+                }
+                Err(e) => {
+                    tracing::warn!(%chain, address = ?address, "Gas top-up not sent: {}", e);
+                    // Later nonces would be stuck behind the gap
+                    break;
+                }
+            }
+        }
+
+        let addresses: Vec<Address> = pending.iter().map(|(address, _)| *address).collect();
+        let receipts = futures::future::join_all(pending.into_iter().map(|(_, sent)| sent)).await;
+        for (address, receipt) in addresses.into_iter().zip(receipts) {
+            match receipt {
+                Ok(Some(receipt)) if receipt.status == Some(1u64.into()) => {
+                    unfunded.remove(&address);
+                }
+                other => {
+                    tracing::warn!(%chain, address = ?address, result = ?other.map(|r| r.map(|r| r.status)), "Gas top-up did not land");
+                    gas_txs.remove(&address);
+                }
+            }
+        }
+        Ok((gas_txs, unfunded))
+    }
+
+    /// Move one balance to the treasury, signed by the deposit address's key
+    async fn send_sweep(
+        &self,
+        chain: Chain,
+        provider: &Arc<ChainProvider>,
+        candidate: &SweepCandidate,
+        holding: &Holding,
+        amount: U256,
+        gas_price: U256,
+    ) -> Result<String, String> {
+        let signer = self
+            .addresses
+            .keys()
+            .signer(candidate.derivation_index)
+            .map_err(|e| e.to_string())?
+            .with_chain_id(chain.chain_id());
+        if signer.address() != holding.address {
+            return Err(format!("index {} does not derive {}", candidate.derivation_index, candidate.address));
+        }
+        let client = Arc::new(SignerMiddleware::new(provider.clone(), signer));
+
+        let tx_hash = match holding.token {
+            Some(token) => {
+                let call = IERC20::new(token, client)
+                    .transfer(self.treasury, amount)
+                    .gas(TOKEN_TRANSFER_GAS)
+                    .gas_price(gas_price);
+                call.send().await.map(|sent| sent.tx_hash()).map_err(|e| e.to_string())?
+            }
+            None => {
+                let tx = TransactionRequest::new()
+                    .to(self.treasury)
+                    .value(amount)
+                    .gas(NATIVE_TRANSFER_GAS)
+                    .gas_price(gas_price);
+                client.send_transaction(tx, None).await.map(|sent| sent.tx_hash()).map_err(|e| e.to_string())?
+            }
+        };
+        Ok(format!("{:?}", tx_hash))
+    }
+}
+
+/// Parse `DEPOSIT_SWEEP_MINIMUMS`, e.g. `USDC:10,ETH:0.01`
+fn parse_minimums(spec: &str) -> Result<HashMap<Currency, Money>, SweepError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .split_once(':')
+                .and_then(|(token, amount)| {
+                    let currency = Currency::parse(token.trim())?;
+                    Some((currency, Money::parse(amount.trim(), currency).ok()?))
+                })
+                .ok_or_else(|| SweepError::Config(format!("DEPOSIT_SWEEP_MINIMUMS entry {}", entry)))
+        })
+        .collect()
+}
+
+/// Admin routes: recent sweeps, and a sweep on demand. Mounted under `/admin`,
+/// so both need the admin token.
+pub fn sweep_routes(sweeper: DepositSweeper) -> Router {
+    Router::new()
+        .route("/deposit-sweeps", get(list_sweeps))
+        .route("/deposit-sweeps/run", post(run_sweeps))
+        .with_state(sweeper)
+}
+
+async fn list_sweeps(State(sweeper): State<DepositSweeper>) -> (StatusCode, Json<Value>) {
+    match sweeper.addresses.repo().list_sweeps(LIST_LIMIT).await {
+        Ok(sweeps) => (
+            StatusCode::OK,
+            Json(json!({ "treasury": format!("{:?}", sweeper.treasury), "sweeps": sweeps })),
+        ),
+        Err(e) => {
+            tracing::error!("Failed to list deposit sweeps: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "database error" })))
+        }
+    }
+}
+
+async fn run_sweeps(State(sweeper): State<DepositSweeper>) -> (StatusCode, Json<Value>) {
+    match sweeper.run().await {
+        Ok(report) => (StatusCode::OK, Json(json!(report))),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn holding(address: u8, token: Option<u8>, balance: u64, minimum: u64) -> Holding {
+        Holding {
+            address: Address::repeat_byte(address),
+            token: token.map(Address::repeat_byte),
+            balance: balance.into(),
+            minimum: minimum.into(),
+        }
+    }
+
+    #[test]
+    fn test_plan_tops_up_shortfall_once_per_address() {
+        let gas_price = U256::from(10);
+        let holdings = [
+            holding(1, Some(0xaa), 500, 100),
+            holding(1, Some(0xbb), 700, 0),
+            holding(1, None, 300_000, 0),
+            holding(2, Some(0xaa), 50, 100),
+            holding(3, None, 1_000_000, 0),
+        ];
+        let natives = HashMap::from([
+            (Address::repeat_byte(1), U256::from(300_000)),
+            (Address::repeat_byte(3), U256::from(1_000_000)),
+        ]);
+
+        let plan = plan(&holdings, &natives, gas_price);
+        // Two token sweeps at 650k wei each, less the 300k already there
+        assert_eq!(plan.top_ups, vec![(Address::repeat_byte(1), U256::from(1_000_000))]);
+        assert_eq!(plan.sweeps, vec![(0, 500.into()), (1, 700.into()), (4, U256::from(1_000_000 - 210_000))]);
+        assert_eq!(plan.skipped, vec![(3, "below the sweep minimum"), (2, "paying for token sweeps")]);
+    }
+
+    #[test]
+    fn test_plan_skips_native_dust() {
+        let plan = plan(&[holding(1, None, 200_000, 0)], &HashMap::new(), U256::from(10));
+        assert!(plan.sweeps.is_empty() && plan.top_ups.is_empty());
+        assert_eq!(plan.skipped.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_minimums() {
+        let minimums = parse_minimums("USDC:10, ETH:0.01").unwrap();
+        assert_eq!(minimums[&Currency::USDC], Money::usdc(10_000_000));
+        assert!(parse_minimums("USDC").is_err());
+        assert!(parse_minimums("").unwrap().is_empty());
+    }
+}
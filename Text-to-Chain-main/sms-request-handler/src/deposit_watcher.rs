@@ -0,0 +1,485 @@
+//! On-chain deposit confirmations
+//!
+//! The deposit monitor posts incoming transfers to
+//! `POST /internal/record-deposit` as soon as they are mined. They are
+//! recorded as pending and only count once their block is buried under the
+//! chain's confirmation depth (`DEPOSIT_CONFIRMATIONS`, else
+//! `Chain::default_confirmations`). A background poll follows each pending
+//! deposit, and each recently confirmed one for a while longer: if its
+//! transaction fails or its block is reorged away, the deposit is reversed.
+//! The user gets an SMS at every state change, except confirmations when
+//! they have turned deposit notifications off (`ALERT DEPOSIT OFF`).
+//!
+//! With deposit addresses on (`DEPOSIT_MNEMONIC`), the monitor can report a
+//! transfer by the address it was sent to instead of the phone; it is
+//! credited to that address's owner and later swept into the treasury.
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use ethers::providers::Middleware;
+use ethers::types::{BlockNumber, H256, U64};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::alerts::{self, Alert};
+use crate::config::DepositConfig;
+use crate::db::{BalanceAlertRepository, Deposit, DepositAddressRepository, DepositRepository};
+use crate::events::{EventBus, Topic};
+use crate::money::{Currency, Money};
+use crate::notify_digest::Notifications;
+use crate::sms::display::short_hash;
+use crate::sms::{NumberLocale, SmsDisplay, SmsGateway};
+use crate::wallet::{Chain, ChainError, MultiChainProvider};
+
+pub const INTERNAL_SECRET_HEADER: &str = "x-internal-secret";
+/// Minutes a confirmed deposit is still checked for reorgs
+const REORG_WATCH_MINUTES: i32 = 60;
+/// Deposits checked per poll
+const POLL_BATCH: i64 = 200;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DepositError {
+    #[error("Invalid deposit config: {0}")]
+    Config(String),
+}
+
+/// Confirmation depth per chain
+#[derive(Debug, Clone, Default)]
+pub struct ConfirmationDepths {
+    overrides: HashMap<Chain, u64>,
+}
+
+impl ConfirmationDepths {
+    /// Parse `DEPOSIT_CONFIRMATIONS`, e.g. `amoy:64,sepolia:12`
+    pub fn from_config(config: &DepositConfig) -> Result<Self, DepositError> {
+        let mut overrides = HashMap::new();
+        for entry in config.confirmations.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (chain, depth) = entry
+                .split_once(':')
+                .and_then(|(chain, depth)| Some((Chain::from_input(chain.trim())?, depth.trim().parse::<u64>().ok()?)))
+                .filter(|(_, depth)| *depth > 0)
+                .ok_or_else(|| DepositError::Config(format!("DEPOSIT_CONFIRMATIONS entry {}", entry)))?;
+            overrides.insert(chain, depth);
+        }
+        Ok(Self { overrides })
+    }
+
+    pub fn for_chain(&self, chain: Chain) -> u64 {
+        self.overrides.get(&chain).copied().unwrap_or_else(|| chain.default_confirmations())
+    }
+}
+
+/// What the chain currently says about a deposit's transaction
+#[derive(Debug, Clone, Default)]
+struct Sighting {
+    head: i64,
+    /// Block now including the transaction, and whether it succeeded
+    included: Option<(i64, String, bool)>,
+    /// Hash of the block now at the deposit's recorded height
+    recorded_height_hash: Option<String>,
+}
+
+/// Next step for a watched deposit
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// Record the block and depth; no state change
+    Track { block_number: i64, block_hash: String, confirmations: i32 },
+    /// Deep enough: pending -> confirmed
+    Confirm { block_number: i64, block_hash: String, confirmations: i32 },
+    /// The transaction is gone from the chain or failed
+    Reverse(&'static str),
+    /// Nothing conclusive this round (e.g. a lagging RPC node)
+    Wait,
+}
+
+fn next_step(deposit: &Deposit, sighting: &Sighting, required: u64) -> Step {
+    if let Some((block_number, ref block_hash, succeeded)) = sighting.included {
+        if !succeeded {
+            return Step::Reverse("the transaction failed");
+        }
+        let confirmations = (sighting.head - block_number + 1).max(0) as i32;
+        let block_hash = block_hash.clone();
+        return if deposit.is_pending() && confirmations as u64 >= required {
+            Step::Confirm { block_number, block_hash, confirmations }
+        } else {
+            Step::Track { block_number, block_hash, confirmations }
+        };
+    }
+
+    // Not in any block: its block was replaced, or it never made it back in
+    let replaced = matches!(
+        (&deposit.block_hash, &sighting.recorded_height_hash),
+        (Some(seen), Some(now)) if seen != now
+    );
+    let overdue = deposit
+        .block_number
+        .is_some_and(|block| sighting.head >= block + required as i64);
+    if replaced || overdue {
+        Step::Reverse("a chain reorg dropped it")
+    } else {
+        Step::Wait
+    }
+}
+
+/// Follows pending and recently confirmed on-chain deposits
+#[derive(Clone)]
+pub struct DepositWatcher {
+    repo: DepositRepository,
+    chains: MultiChainProvider,
+    twilio: SmsGateway,
+    depths: ConfirmationDepths,
+    events: EventBus,
+    /// Who has turned deposit notifications off
+    alerts: Option<BalanceAlertRepository>,
+    /// NOTIFY modes: confirmations can wait for the daily digest
+    notifications: Option<Notifications>,
+}
+
+impl DepositWatcher {
+    pub fn new(repo: DepositRepository, chains: MultiChainProvider, twilio: SmsGateway, depths: ConfirmationDepths, events: EventBus) -> Self {
+        Self { repo, chains, twilio, depths, events, alerts: None, notifications: None }
+    }
+
+    /// Skip confirmation SMS for users with deposit notifications off
+    pub fn set_alerts(&mut self, alerts: BalanceAlertRepository) {
+        self.alerts = Some(alerts);
+    }
+
+    /// Route deposit texts by each user's NOTIFY mode
+    pub fn set_notifications(&mut self, notifications: Notifications) {
+        self.notifications = Some(notifications);
+    }
+
+    /// Check every watched deposit once; returns how many changed state
+    pub async fn poll(&self) -> Result<usize, sqlx::Error> {
+        let deposits = self.repo.list_watched(REORG_WATCH_MINUTES, POLL_BATCH).await?;
+        let mut heads = HashMap::new();
+        let mut changed = 0;
+
+        for deposit in deposits {
+            let Some(chain) = deposit.chain.as_deref().and_then(Chain::from_input) else {
+                tracing::warn!(id = %deposit.id, chain = ?deposit.chain, "Watched deposit has an unknown chain");
+                continue;
+            };
+            let head = match heads.get(&chain) {
+                Some(head) => *head,
+                None => match self.chains.call(chain, |p| async move { p.get_block_number().await }).await {
+                    Ok(head) => *heads.entry(chain).or_insert(head.as_u64() as i64),
+                    Err(e) => {
+                        tracing::warn!(%chain, "Skipping deposit checks: {}", e);
+                        continue;
+                    }
+                },
+            };
+            let sighting = match self.sight(chain, &deposit, head).await {
+                Ok(sighting) => sighting,
+                Err(e) => {
+                    tracing::warn!(id = %deposit.id, "Deposit check failed: {}", e);
+                    continue;
+                }
+            };
+
+            match next_step(&deposit, &sighting, self.depths.for_chain(chain)) {
+                Step::Track { block_number, block_hash, confirmations } => {
+                    if deposit.block_hash.as_ref().is_some_and(|seen| *seen != block_hash) {
+                        tracing::warn!(id = %deposit.id, %chain, block_number, "Deposit re-included after a reorg");
+                    }
+                    self.repo.update_block(deposit.id, block_number, &block_hash, confirmations).await?;
+                }
+                Step::Confirm { block_number, block_hash, confirmations } => {
+                    self.repo.update_block(deposit.id, block_number, &block_hash, confirmations).await?;
+                    if self.repo.mark_confirmed(deposit.id).await? {
+                        tracing::info!(id = %deposit.id, %chain, confirmations, "Deposit confirmed");
+                        self.publish(&deposit, chain, "confirmed");
+                        self.notify(&deposit, &format!(
+                            "Deposit confirmed: {} on {}.\nReply BALANCE to check.",
+                            deposit.amount.sms_string(NumberLocale::for_phone(&deposit.user_phone)),
+                            chain.name()
+                        ))
+                        .await;
+                        changed += 1;
+                    }
+                }
+                Step::Reverse(reason) => {
+                    let was_confirmed = !deposit.is_pending();
+                    if self.repo.mark_reversed(deposit.id).await? {
+                        tracing::warn!(id = %deposit.id, %chain, was_confirmed, reason, "Deposit reversed");
+                        // A credited deposit that is gone from the chain leaves the ledger ahead of it
+                        if was_confirmed {
+                            alerts::raise(Alert::reconciliation_mismatch(
+                                "deposits",
+                                1,
+                                json!({ "deposit": deposit.id, "chain": chain.short_code(), "reason": reason }),
+                            ));
+                        }
+                        self.publish(&deposit, chain, "reversed");
+                        let credit = if was_confirmed { "It has been taken off your balance." } else { "It was not credited." };
+                        let message = format!(
+                            "Deposit reversed: {} on {}, because {}.\n{}\nTx {}",
+                            deposit.amount.sms_string(NumberLocale::for_phone(&deposit.user_phone)),
+                            chain.name(),
+                            reason,
+                            credit,
+                            short_hash(deposit.source_ref.as_deref().unwrap_or_default())
+                        );
+                        // Money leaving a balance is not held for quiet hours
+                        if let Err(e) = self.twilio.send_sms(&deposit.user_phone, &message).await {
+                            tracing::warn!(to = %deposit.user_phone, "Deposit reversal SMS not sent: {}", e);
+                        }
+                        changed += 1;
+                    }
+                }
+                Step::Wait => {}
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Look up the deposit's transaction, and the block at its recorded
+    /// height when the transaction can't be found
+    async fn sight(&self, chain: Chain, deposit: &Deposit, head: i64) -> Result<Sighting, ChainError> {
+        let tx_hash: H256 = deposit.source_ref.as_deref().and_then(|h| h.parse().ok()).unwrap_or_default();
+        let receipt = self
+            .chains
+            .call(chain, |p| async move { p.get_transaction_receipt(tx_hash).await })
+            .await?;
+
+        let included = receipt.and_then(|receipt| {
+            let block_number = receipt.block_number?.as_u64() as i64;
+            let block_hash = format!("{:?}", receipt.block_hash?);
+            Some((block_number, block_hash, receipt.status != Some(U64::zero())))
+        });
+
+        let recorded_height_hash = match (included.is_none(), deposit.block_number) {
+            (true, Some(height)) => self
+                .chains
+                .call(chain, |p| async move { p.get_block(BlockNumber::Number(U64::from(height as u64))).await })
+                .await?
+                .and_then(|block| block.hash)
+                .map(|hash| format!("{:?}", hash)),
+            _ => None,
+        };
+
+        Ok(Sighting { head, included, recorded_height_hash })
+    }
+
+    /// Report a deposit state change on the admin event stream
+    fn publish(&self, deposit: &Deposit, chain: Chain, status: &str) {
+        self.events.publish(Topic::Deposit, json!({
+            "id": deposit.id,
+            "status": status,
+            "chain": chain.short_code(),
+            "amount": deposit.amount,
+            "token": deposit.token,
+            "tx_hash": deposit.source_ref,
+        }));
+    }
+
+    async fn notify(&self, deposit: &Deposit, body: &str) {
+        if let Some(ref alerts) = self.alerts {
+            match alerts.deposit_notifications(&deposit.user_phone).await {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(e) => tracing::warn!(id = %deposit.id, "Deposit notification setting unavailable: {}", e),
+            }
+        }
+        if let Some(ref notifications) = self.notifications {
+            notifications.deliver(&deposit.user_phone, body).await;
+        } else if let Err(e) = self.twilio.send_notification(&deposit.user_phone, body).await {
+            tracing::warn!(to = %deposit.user_phone, "Deposit notification not sent: {}", e);
+        }
+    }
+}
+
+/// Transfer reported by the deposit monitor
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DepositReport {
+    /// Owner of the receiving wallet; may be left out for deposit addresses
+    #[serde(default)]
+    phone: Option<String>,
+    /// Receiving address
+    #[serde(default)]
+    to_address: Option<String>,
+    /// Whole tokens, e.g. 0.5
+    amount: f64,
+    token: String,
+    tx_hash: String,
+    chain: String,
+    /// Block number, hex (`0x1a2b`) or decimal
+    block_num: String,
+}
+
+/// Deposit intake route state
+#[derive(Clone)]
+pub struct DepositIntake {
+    pub watcher: DepositWatcher,
+    pub internal_secret: String,
+    /// Per-user deposit addresses (requires DEPOSIT_MNEMONIC)
+    pub addresses: Option<DepositAddressRepository>,
+}
+
+/// Internal route the deposit monitor reports transfers to
+pub fn deposit_routes(intake: DepositIntake) -> Router {
+    Router::new()
+        .route("/internal/record-deposit", post(record_deposit))
+        .with_state(intake)
+}
+
+async fn record_deposit(
+    State(state): State<DepositIntake>,
+    headers: HeaderMap,
+    Json(report): Json<DepositReport>,
+) -> (StatusCode, Json<Value>) {
+    let authorized = headers
+        .get(INTERNAL_SECRET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|secret| secret == state.internal_secret);
+    if !authorized {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "error": "unauthorized" })));
+    }
+
+    let Some(chain) = Chain::from_input(&report.chain) else {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": format!("unknown chain {}", report.chain) })));
+    };
+    let Some(block_number) = parse_block_number(&report.block_num) else {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid blockNum" })));
+    };
+    let amount = Currency::parse(&report.token).and_then(|token| Money::from_f64(report.amount, token).ok());
+    let Some(amount) = amount.filter(|a| a.is_positive() && report.tx_hash.parse::<H256>().is_ok()) else {
+        return (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid amount, token or txHash" })));
+    };
+
+    // A transfer to a deposit address belongs to that address's owner
+    let deposit_address = match (&state.addresses, report.to_address.as_deref()) {
+        (Some(addresses), Some(to)) => match addresses.find_by_address(to).await {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::error!("Failed to look up deposit address: {}", e);
+                return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "database error" })));
+            }
+        },
+        _ => None,
+    };
+    let phone = match (&deposit_address, report.phone) {
+        (Some(address), _) => address.user_phone.clone(),
+        (None, Some(phone)) => phone,
+        (None, None) => return (StatusCode::NOT_FOUND, Json(json!({ "error": "unknown deposit address" }))),
+    };
+
+    let watcher = &state.watcher;
+    let deposit = match watcher
+        .repo
+        .record_pending(&phone, amount, &report.tx_hash, chain.short_code(), block_number)
+        .await
+    {
+        Ok(Some(deposit)) => deposit,
+        Ok(None) => return (StatusCode::OK, Json(json!({ "status": "duplicate" }))),
+        Err(e) => {
+            tracing::error!("Failed to record deposit: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "database error" })));
+        }
+    };
+
+    if let Some(ref address) = deposit_address {
+        if let Err(e) = watcher.repo.set_deposit_address(deposit.id, &address.address).await {
+            tracing::error!(id = %deposit.id, "Failed to tag deposit with its deposit address: {}", e);
+        }
+    }
+
+    let required = watcher.depths.for_chain(chain);
+    tracing::info!(id = %deposit.id, %chain, block_number, required, "Deposit pending confirmation");
+    watcher.publish(&deposit, chain, "pending");
+    watcher
+        .notify(&deposit, &format!(
+            "Deposit on the way: {} on {}.\nIt counts after {} confirmations; we'll text you then.",
+            deposit.amount.sms_string(NumberLocale::for_phone(&deposit.user_phone)),
+            chain.name(),
+            required
+        ))
+        .await;
+
+    (StatusCode::OK, Json(json!({ "id": deposit.id, "status": deposit.status, "confirmations_required": required })))
+}
+
+fn parse_block_number(value: &str) -> Option<i64> {
+    let value = value.trim();
+    match value.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+    .filter(|n| *n >= 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn deposit(status: &str, block_number: i64, block_hash: Option<&str>) -> Deposit {
+        Deposit {
+            id: Uuid::new_v4(),
+            user_phone: "+15550100".to_string(),
+            amount: Money::usdc(500_000),
+            source: "onchain".to_string(),
+            source_ref: Some(format!("{:?}", H256::repeat_byte(0xab))),
+            chain: Some("POL-T".to_string()),
+            created_at: Utc::now(),
+            status: status.to_string(),
+            token: Some("USDC".to_string()),
+            block_number: Some(block_number),
+            block_hash: block_hash.map(str::to_string),
+            confirmations: 0,
+        }
+    }
+
+    #[test]
+    fn test_next_step() {
+        let pending = deposit("pending", 100, Some("0xaaa"));
+        let included = |head, hash: &str, ok| Sighting { head, included: Some((100, hash.to_string(), ok)), recorded_height_hash: None };
+
+        // Short of the depth, then deep enough
+        assert_eq!(
+            next_step(&pending, &included(110, "0xaaa", true), 12),
+            Step::Track { block_number: 100, block_hash: "0xaaa".to_string(), confirmations: 11 }
+        );
+        assert!(matches!(next_step(&pending, &included(111, "0xaaa", true), 12), Step::Confirm { confirmations: 12, .. }));
+        assert_eq!(next_step(&pending, &included(111, "0xaaa", false), 12), Step::Reverse("the transaction failed"));
+
+        // Confirmed deposits are only tracked while still included
+        let confirmed = deposit("confirmed", 100, Some("0xaaa"));
+        assert!(matches!(next_step(&confirmed, &included(200, "0xaaa", true), 12), Step::Track { .. }));
+
+        // Missing: block replaced, overdue, or not conclusive yet
+        let missing = |head, now: Option<&str>| Sighting { head, included: None, recorded_height_hash: now.map(str::to_string) };
+        assert_eq!(next_step(&confirmed, &missing(105, Some("0xbbb")), 12), Step::Reverse("a chain reorg dropped it"));
+        assert_eq!(next_step(&pending, &missing(105, Some("0xaaa")), 12), Step::Wait);
+        assert_eq!(next_step(&deposit("pending", 100, None), &missing(105, None), 12), Step::Wait);
+        assert_eq!(next_step(&deposit("pending", 100, None), &missing(112, None), 12), Step::Reverse("a chain reorg dropped it"));
+    }
+
+    #[test]
+    fn test_confirmation_depths() {
+        let config = |confirmations: &str| DepositConfig {
+            confirmations: confirmations.to_string(),
+            poll_secs: 30,
+            internal_secret: Default::default(),
+        };
+        let depths = ConfirmationDepths::from_config(&config("amoy:200, sepolia:3")).unwrap();
+        assert_eq!(depths.for_chain(Chain::PolygonAmoy), 200);
+        assert_eq!(depths.for_chain(Chain::EthereumSepolia), 3);
+        assert_eq!(depths.for_chain(Chain::BaseSepolia), Chain::BaseSepolia.default_confirmations());
+
+        assert!(ConfirmationDepths::from_config(&config("nowhere:5")).is_err());
+        assert!(ConfirmationDepths::from_config(&config("amoy:0")).is_err());
+        assert_eq!(parse_block_number("0x1a"), Some(26));
+        assert_eq!(parse_block_number("26"), Some(26));
+    }
+}
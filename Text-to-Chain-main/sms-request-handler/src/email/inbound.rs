@@ -0,0 +1,298 @@
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::StatusCode,
+    routing::post,
+    Router,
+};
+use base64::Engine;
+use mail_parser::MessageParser;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::commands::parser::Command;
+use crate::commands::CommandProcessor;
+use crate::db::EmailLinkRepository;
+use crate::email::EmailClient;
+use crate::workers::WorkerPool;
+
+/// Reply to a sender whose address isn't linked to a wallet
+const NOT_LINKED_REPLY: &str =
+    "This address isn't linked to a TextChain wallet.\nText EMAIL <your address> from your phone to link it.";
+
+/// Email links can only be changed from the phone itself
+const LINK_BY_SMS_REPLY: &str = "Email links can only be changed by SMS.\nText EMAIL <address> or EMAIL OFF from your phone.";
+
+/// Email command channel: verified links plus the SMTP client for replies
+#[derive(Clone)]
+pub struct EmailChannel {
+    pub links: EmailLinkRepository,
+    pub client: EmailClient,
+    pub inbound_token: String,
+}
+
+#[derive(Clone)]
+struct EmailState {
+    channel: EmailChannel,
+    command_processor: Arc<CommandProcessor>,
+    workers: WorkerPool,
+    http: reqwest::Client,
+}
+
+#[derive(Debug, Deserialize)]
+struct InboundQuery {
+    #[serde(default)]
+    token: String,
+}
+
+/// SNS envelope around an SES receipt notification
+#[derive(Debug, Deserialize)]
+struct SnsEnvelope {
+    #[serde(rename = "Type")]
+    kind: String,
+    #[serde(rename = "Message", default)]
+    message: String,
+    #[serde(rename = "SubscribeURL")]
+    subscribe_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SesNotification {
+    receipt: SesReceipt,
+    /// Raw MIME message (SNS action only)
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SesReceipt {
+    spf_verdict: SesVerdict,
+    dkim_verdict: SesVerdict,
+    #[serde(default)]
+    action: SesAction,
+}
+
+#[derive(Debug, Deserialize)]
+struct SesVerdict {
+    status: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SesAction {
+    encoding: Option<String>,
+}
+
+/// An authenticated inbound email reduced to what the command channel needs
+#[derive(Debug, Clone, PartialEq)]
+struct InboundEmail {
+    from: String,
+    subject: String,
+    command: String,
+    message_id: Option<String>,
+}
+
+/// Create the inbound email webhook route
+pub fn email_routes(channel: EmailChannel, command_processor: Arc<CommandProcessor>, workers: WorkerPool) -> Router {
+    let state = EmailState { channel, command_processor, workers, http: reqwest::Client::new() };
+    Router::new()
+        .route("/email/inbound", post(inbound_email))
+        .with_state(state)
+}
+
+/// Inbound email webhook: an SES receipt via SNS (JSON) or a raw MIME message
+/// from an MTA that adds `Authentication-Results`. Answers 200 straight away
+/// and replies by email once the command has run.
+async fn inbound_email(
+    State(state): State<EmailState>,
+    Query(query): Query<InboundQuery>,
+    body: Bytes,
+) -> StatusCode {
+    if query.token != state.channel.inbound_token {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let email = if body.first() == Some(&b'{') {
+        let Ok(envelope) = serde_json::from_slice::<SnsEnvelope>(&body) else {
+            return StatusCode::BAD_REQUEST;
+        };
+        match envelope.kind.as_str() {
+            "SubscriptionConfirmation" => return confirm_subscription(&state.http, envelope.subscribe_url.as_deref()).await,
+            "Notification" => match parse_ses(&envelope.message) {
+                Some(email) => email,
+                None => return StatusCode::OK,
+            },
+            _ => return StatusCode::OK,
+        }
+    } else {
+        match parse_mime(&body, true) {
+            Some(email) => email,
+            None => return StatusCode::OK,
+        }
+    };
+
+    tracing::info!(from = %email.from, command = %email.command, "Received email command");
+
+    let phone = match state.channel.links.phone_for_email(&email.from).await {
+        Ok(Some(phone)) => phone,
+        Ok(None) => {
+            send_reply(&state.channel.client, &email, NOT_LINKED_REPLY).await;
+            return StatusCode::OK;
+        }
+        Err(e) => {
+            tracing::error!("Email link lookup failed: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+
+    let command = state.command_processor.parse(&email.command);
+    if matches!(command, Command::Email { .. }) {
+        send_reply(&state.channel.client, &email, LINK_BY_SMS_REPLY).await;
+        return StatusCode::OK;
+    }
+
+    let processor = state.command_processor.clone();
+    let client = state.channel.client.clone();
+    let task = async move {
+        let reply = processor.process(&phone, &email.command).await;
+        send_reply(&client, &email, &reply).await;
+    };
+    if state.workers.spawn(command.task_class(), task).is_err() {
+        // Let the sender's MTA / SNS retry later
+        return StatusCode::SERVICE_UNAVAILABLE;
+    }
+    StatusCode::OK
+}
+
+async fn send_reply(client: &EmailClient, email: &InboundEmail, reply: &str) {
+    let subject = if email.subject.is_empty() {
+        "TextChain".to_string()
+    } else {
+        format!("Re: {}", email.subject)
+    };
+    if let Err(e) = client.send_email(&email.from, &subject, reply, email.message_id.as_deref()).await {
+        tracing::error!(to = %email.from, "Failed to send email reply: {}", e);
+    }
+}
+
+/// Confirm the SNS subscription; only AWS-hosted URLs are followed
+async fn confirm_subscription(http: &reqwest::Client, url: Option<&str>) -> StatusCode {
+    let Some(url) = url.and_then(|u| reqwest::Url::parse(u).ok()) else {
+        return StatusCode::BAD_REQUEST;
+    };
+    if url.scheme() != "https" || !url.host_str().is_some_and(|h| h.ends_with(".amazonaws.com")) {
+        tracing::warn!(url = %url, "Refusing SNS subscription URL");
+        return StatusCode::BAD_REQUEST;
+    }
+    match http.get(url).send().await {
+        Ok(resp) if resp.status().is_success() => {
+            tracing::info!("Confirmed SNS subscription for inbound email");
+            StatusCode::OK
+        }
+        Ok(resp) => {
+            tracing::warn!(status = %resp.status(), "SNS subscription confirmation rejected");
+            StatusCode::BAD_GATEWAY
+        }
+        Err(e) => {
+            tracing::warn!("SNS subscription confirmation failed: {}", e);
+            StatusCode::BAD_GATEWAY
+        }
+    }
+}
+
+/// Parse an SES receipt notification; None unless SPF or DKIM passed
+fn parse_ses(message: &str) -> Option<InboundEmail> {
+    let notification: SesNotification = serde_json::from_str(message).ok()?;
+    let receipt = &notification.receipt;
+    if receipt.spf_verdict.status != "PASS" && receipt.dkim_verdict.status != "PASS" {
+        tracing::warn!("Dropping inbound email that failed SPF and DKIM");
+        return None;
+    }
+
+    let content = notification.content?;
+    let raw = if receipt.action.encoding.as_deref() == Some("BASE64") {
+        base64::engine::general_purpose::STANDARD.decode(content.trim()).ok()?
+    } else {
+        content.into_bytes()
+    };
+    parse_mime(&raw, false)
+}
+
+/// Parse a raw MIME message. With `check_auth`, the receiving MTA's
+/// `Authentication-Results` header must report an SPF or DKIM pass
+fn parse_mime(raw: &[u8], check_auth: bool) -> Option<InboundEmail> {
+    let message = MessageParser::default().parse(raw)?;
+
+    if check_auth {
+        let passed = message
+            .header_raw("Authentication-Results")
+            .map(|results| results.to_lowercase())
+            .is_some_and(|results| results.contains("dkim=pass") || results.contains("spf=pass"));
+        if !passed {
+            tracing::warn!("Dropping inbound email without an SPF or DKIM pass");
+            return None;
+        }
+    }
+
+    let from = message.from()?.first()?.address()?.trim().to_lowercase();
+    let subject = message.subject().unwrap_or_default().trim().to_string();
+    let body = message.body_text(0).unwrap_or_default();
+    let command = command_text(&subject, &body)?;
+
+    Some(InboundEmail {
+        from,
+        subject,
+        command,
+        message_id: message.message_id().map(str::to_string),
+    })
+}
+
+/// The command is the first line the sender wrote, ignoring the quoted
+/// message and signature; an empty body falls back to the subject
+fn command_text(subject: &str, body: &str) -> Option<String> {
+    for line in body.lines().map(str::trim) {
+        if line.starts_with('>') || line == "--" || (line.starts_with("On ") && line.ends_with("wrote:")) {
+            break;
+        }
+        if !line.is_empty() {
+            return Some(line.to_string());
+        }
+    }
+
+    let mut subject = subject.trim();
+    while let Some(rest) = ["re:", "fwd:", "fw:"]
+        .iter()
+        .find(|prefix| subject.len() >= prefix.len() && subject[..prefix.len()].eq_ignore_ascii_case(prefix))
+        .map(|prefix| subject[prefix.len()..].trim_start())
+    {
+        subject = rest;
+    }
+    (!subject.is_empty()).then(|| subject.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_text() {
+        assert_eq!(command_text("hi", "BALANCE\n\nSent from my phone").as_deref(), Some("BALANCE"));
+        assert_eq!(
+            command_text("Re: TextChain", "\nSEND 5 TXTC bob.ttcip.eth\n\nOn Mon, TextChain wrote:\n> Balance: 10").as_deref(),
+            Some("SEND 5 TXTC bob.ttcip.eth")
+        );
+        assert_eq!(command_text("RE: Fwd: balance", "> quoted\n").as_deref(), Some("balance"));
+        assert_eq!(command_text("Re:", "").as_deref(), None);
+    }
+
+    #[test]
+    fn test_parse_mime_requires_authentication() {
+        let raw = "From: Alice <Alice@Example.com>\r\nSubject: balance\r\nMessage-ID: <abc@example.com>\r\n\r\n";
+        assert_eq!(parse_mime(raw.as_bytes(), true), None);
+
+        let authed = format!("Authentication-Results: mx.example.net; spf=pass smtp.mailfrom=example.com\r\n{}", raw);
+        let email = parse_mime(authed.as_bytes(), true).unwrap();
+        assert_eq!(email.from, "alice@example.com");
+        assert_eq!(email.command, "balance");
+        assert_eq!(email.message_id.as_deref(), Some("abc@example.com"));
+    }
+}
@@ -0,0 +1,13 @@
+//! Email command channel
+//!
+//! A phone links one address with `EMAIL <address>` and the code mailed to
+//! it. Mail from a linked address then runs through the same
+//! `CommandProcessor` as SMS, as that phone, and the reply goes back by
+//! SMTP. Inbound mail arrives on `/email/inbound` from SES (via SNS) or any
+//! MTA that can POST raw MIME.
+
+pub mod inbound;
+pub mod smtp;
+
+pub use inbound::{email_routes, EmailChannel};
+pub use smtp::EmailClient;
@@ -0,0 +1,51 @@
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::EmailConfig;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EmailError {
+    #[error("Invalid address: {0}")]
+    Address(#[from] lettre::address::AddressError),
+    #[error("Failed to build message: {0}")]
+    Message(#[from] lettre::error::Error),
+    #[error("SMTP error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+}
+
+/// Sends command replies and verification codes over SMTP (STARTTLS)
+#[derive(Clone)]
+pub struct EmailClient {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailClient {
+    pub fn new(config: &EmailConfig) -> Result<Self, EmailError> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?.port(config.smtp_port);
+        if !config.smtp_username.is_empty() {
+            builder = builder.credentials(Credentials::new(config.smtp_username.clone(), config.smtp_password.to_string()));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: format!("TextChain <{}>", config.from_address).parse()?,
+        })
+    }
+
+    /// Send a plain-text email; `in_reply_to` threads it under the sender's message
+    pub async fn send_email(&self, to: &str, subject: &str, body: &str, in_reply_to: Option<&str>) -> Result<(), EmailError> {
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        if let Some(id) = in_reply_to {
+            let id = format!("<{}>", id.trim_matches(|c| c == '<' || c == '>'));
+            builder = builder.in_reply_to(id.clone()).references(id);
+        }
+
+        self.transport.send(builder.body(body.to_string())?).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,356 @@
+//! Background checks that stored ENS names still point at their wallets
+//!
+//! `users.ens_name` is written once, at mint or claim time, but the record
+//! behind it can change: a user re-points a name they own, or a mint's last
+//! step never landed. Every `ENS_VERIFY_INTERVAL_SECS` the
+//! `ENS_VERIFY_BATCH` least recently checked names are resolved on-chain,
+//! bypassing the lookup cache, and compared with the user's wallet.
+//! `.ttcip.eth` names resolve on Sepolia and, since we own the parent, a
+//! drifted one is re-pointed through the backend when `ENS_VERIFY_REPAIR` is
+//! set. Other names are only flagged. Verdicts are kept in `ens_checks`,
+//! listed at `/admin/ens/checks` and counted at `/metrics/ens`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use ethers::providers::{Http, Provider};
+use ethers::types::Address;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::alerts::{self, Alert};
+use crate::config::EnsVerifyConfig;
+use crate::db::{EnsCheckRepository, EnsCheckStatus, StoredEnsName};
+use crate::wallet::ens_cache::{not_found_as_none, EnsCache};
+use crate::wallet::{create_chain_provider, Chain};
+
+/// Parent name we control and can repair under
+const TTCIP_SUFFIX: &str = ".ttcip.eth";
+const DEFAULT_LIST_LIMIT: i64 = 100;
+const MAX_LIST_LIMIT: i64 = 1000;
+
+/// Checks and repairs since startup, for `/metrics/ens`
+#[derive(Debug, Default, Clone)]
+struct Totals {
+    /// Verdicts by status, plus `error` for failed lookups
+    checks: BTreeMap<&'static str, u64>,
+    repairs_ok: u64,
+    repairs_failed: u64,
+    last_run: Option<DateTime<Utc>>,
+}
+
+/// Re-resolves stored names in batches and repairs the ones we can
+#[derive(Clone)]
+pub struct EnsVerifier {
+    repo: EnsCheckRepository,
+    /// Shared with SEND, so a drifted name stops resolving from the cache
+    cache: Arc<EnsCache>,
+    mainnet: Arc<Provider<Http>>,
+    sepolia: Arc<Provider<Http>>,
+    backend_url: String,
+    http: reqwest::Client,
+    batch_size: i64,
+    repair: bool,
+    period: Duration,
+    totals: Arc<Mutex<Totals>>,
+}
+
+impl EnsVerifier {
+    /// None when ENS_VERIFY_INTERVAL_SECS or ENS_VERIFY_BATCH is 0
+    pub fn from_config(
+        config: &EnsVerifyConfig,
+        repo: EnsCheckRepository,
+        cache: Arc<EnsCache>,
+        backend_url: &str,
+    ) -> Option<Self> {
+        if !config.is_enabled() {
+            return None;
+        }
+        Some(Self {
+            repo,
+            cache,
+            mainnet: create_chain_provider(Chain::EthereumMainnet),
+            sepolia: create_chain_provider(Chain::EthereumSepolia),
+            backend_url: backend_url.trim_end_matches('/').to_string(),
+            http: reqwest::Client::new(),
+            batch_size: config.batch_size,
+            repair: config.repair,
+            period: Duration::from_secs(config.interval_secs),
+            totals: Arc::default(),
+        })
+    }
+
+    pub fn repairs(&self) -> bool {
+        self.repair
+    }
+
+    /// Check a batch now and then every `ENS_VERIFY_INTERVAL_SECS`
+    pub fn start(&self) {
+        let verifier = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(verifier.period);
+            loop {
+                ticker.tick().await;
+                match verifier.run().await {
+                    Ok(0) => {}
+                    Ok(drifted) => {
+                        tracing::warn!(drifted, "Stored ENS names no longer point at their wallets");
+                        alerts::raise(Alert::reconciliation_mismatch(
+                            "ens_names",
+                            drifted,
+                            json!({ "repair": verifier.repair }),
+                        ));
+                    }
+                    Err(e) => tracing::warn!("ENS verification failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Check one batch; returns how many names were left drifted
+    pub async fn run(&self) -> Result<usize, sqlx::Error> {
+        let due = self.repo.due(self.batch_size).await?;
+        let mut drifted = 0;
+        for name in &due {
+            if self.check(name).await?.is_drift() {
+                drifted += 1;
+            }
+        }
+        self.totals.lock().unwrap_or_else(|e| e.into_inner()).last_run = Some(Utc::now());
+        Ok(drifted)
+    }
+
+    /// Resolve, compare, repair if we can, and record the verdict
+    async fn check(&self, name: &StoredEnsName) -> Result<EnsCheckStatus, sqlx::Error> {
+        let resolved = match self.resolve(&name.ens_name).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                tracing::warn!(user = %name.user_id, name = %name.ens_name, "ENS check lookup failed: {}", e);
+                self.count("error");
+                self.repo.record_error(name, &e).await?;
+                return Ok(EnsCheckStatus::Unresolved);
+            }
+        };
+        let resolved_text = resolved.map(|address| format!("{:?}", address));
+
+        let status = classify(&name.wallet_address, resolved);
+        if !status.is_drift() {
+            self.count(status.as_str());
+            self.repo.record(name, status, resolved_text.as_deref()).await?;
+            return Ok(status);
+        }
+
+        tracing::warn!(
+            user = %name.user_id,
+            name = %name.ens_name,
+            wallet = %name.wallet_address,
+            resolved = ?resolved_text,
+            status = status.as_str(),
+            "Stored ENS name drifted"
+        );
+        // Whatever SEND has cached for the name is stale now
+        self.cache.invalidate(&name.ens_name);
+
+        if self.repair && is_ttcip_name(&name.ens_name) {
+            match self.repoint(name).await {
+                Ok(()) => {
+                    tracing::info!(user = %name.user_id, name = %name.ens_name, "Re-pointed drifted ENS name");
+                    self.record_repair(true);
+                    self.count(EnsCheckStatus::Repaired.as_str());
+                    self.repo.record(name, EnsCheckStatus::Repaired, Some(&name.wallet_address)).await?;
+                    return Ok(EnsCheckStatus::Repaired);
+                }
+                Err(e) => {
+                    tracing::warn!(user = %name.user_id, name = %name.ens_name, "ENS repair failed: {}", e);
+                    self.record_repair(false);
+                    self.count(status.as_str());
+                    self.repo.record(name, status, resolved_text.as_deref()).await?;
+                    self.repo.record_error(name, &format!("repair failed: {}", e)).await?;
+                    return Ok(status);
+                }
+            }
+        }
+
+        self.count(status.as_str());
+        self.repo.record(name, status, resolved_text.as_deref()).await?;
+        Ok(status)
+    }
+
+    /// Current on-chain address, skipping the cache; Ok(None) = no record
+    async fn resolve(&self, name: &str) -> Result<Option<Address>, String> {
+        let provider = if is_ttcip_name(name) { &self.sepolia } else { &self.mainnet };
+        not_found_as_none(ens_offchain::resolve_name(provider, name).await).map_err(|e| e.to_string())
+    }
+
+    /// Point a `.ttcip.eth` name back at the user's wallet via the backend
+    async fn repoint(&self, name: &StoredEnsName) -> Result<(), String> {
+        let response = self
+            .http
+            .post(format!("{}/api/ens/repoint", self.backend_url))
+            .json(&json!({ "ensName": name.ens_name, "walletAddress": name.wallet_address }))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+        let status = response.status();
+        let body = response.json::<serde_json::Value>().await.unwrap_or_default();
+        Err(body["error"].as_str().map(str::to_string).unwrap_or_else(|| status.to_string()))
+    }
+
+    fn count(&self, outcome: &'static str) {
+        *self.totals.lock().unwrap_or_else(|e| e.into_inner()).checks.entry(outcome).or_default() += 1;
+    }
+
+    fn record_repair(&self, ok: bool) {
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        if ok {
+            totals.repairs_ok += 1;
+        } else {
+            totals.repairs_failed += 1;
+        }
+    }
+
+    /// Prometheus text for `/metrics/ens`
+    async fn render(&self) -> Result<String, sqlx::Error> {
+        let counts = self.repo.status_counts().await?;
+        let totals = self.totals.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Ok(render(&counts, &totals))
+    }
+}
+
+/// Verdict for a name that resolved to `resolved`
+fn classify(wallet_address: &str, resolved: Option<Address>) -> EnsCheckStatus {
+    match resolved {
+        None => EnsCheckStatus::Missing,
+        Some(address) if wallet_address.trim().parse::<Address>().is_ok_and(|wallet| wallet == address) => EnsCheckStatus::Ok,
+        Some(_) => EnsCheckStatus::Mismatch,
+    }
+}
+
+fn is_ttcip_name(name: &str) -> bool {
+    name.trim().to_lowercase().ends_with(TTCIP_SUFFIX)
+}
+
+fn render(counts: &[(String, i64)], totals: &Totals) -> String {
+    let mut out = String::new();
+
+    let name = "textchain_ens_names";
+    let _ = writeln!(out, "# HELP {} Stored ENS names by last verdict\n# TYPE {} gauge", name, name);
+    for status in EnsCheckStatus::ALL {
+        let count = counts.iter().find(|(s, _)| s == status.as_str()).map_or(0, |(_, n)| *n);
+        let _ = writeln!(out, "{}{{status=\"{}\"}} {}", name, status.as_str(), count);
+    }
+
+    let name = "textchain_ens_checks_total";
+    let _ = writeln!(out, "# HELP {} Names checked since startup, by outcome\n# TYPE {} counter", name, name);
+    for (outcome, count) in &totals.checks {
+        let _ = writeln!(out, "{}{{outcome=\"{}\"}} {}", name, outcome, count);
+    }
+
+    let name = "textchain_ens_repairs_total";
+    let _ = writeln!(out, "# HELP {} Drifted .ttcip.eth names re-pointed since startup\n# TYPE {} counter", name, name);
+    let _ = writeln!(out, "{}{{result=\"ok\"}} {}", name, totals.repairs_ok);
+    let _ = writeln!(out, "{}{{result=\"failed\"}} {}", name, totals.repairs_failed);
+
+    let name = "textchain_ens_last_run_timestamp_seconds";
+    let _ = writeln!(out, "# HELP {} When the last batch finished\n# TYPE {} gauge", name, name);
+    let _ = writeln!(out, "{} {}", name, totals.last_run.map_or(0, |at| at.timestamp()));
+    out
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChecksQuery {
+    /// mismatch (default), missing, repaired, unresolved or ok
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+/// `GET /metrics/ens`
+pub fn ens_metrics_routes(verifier: EnsVerifier) -> Router {
+    Router::new()
+        .route("/metrics/ens", get(ens_metrics))
+        .with_state(verifier)
+}
+
+/// `GET /ens/checks?status=&limit=`, nested under `/admin`
+pub fn admin_ens_check_routes(verifier: EnsVerifier) -> Router {
+    Router::new()
+        .route("/ens/checks", get(list_checks))
+        .with_state(verifier)
+}
+
+async fn ens_metrics(State(verifier): State<EnsVerifier>) -> impl IntoResponse {
+    match verifier.render().await {
+        Ok(body) => (StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body),
+        Err(e) => {
+            tracing::error!("ENS metrics failed: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], String::new())
+        }
+    }
+}
+
+async fn list_checks(
+    State(verifier): State<EnsVerifier>,
+    Query(query): Query<ChecksQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let status = match query.status.as_deref() {
+        None => EnsCheckStatus::Mismatch,
+        Some(name) => EnsCheckStatus::parse(name).ok_or_else(|| {
+            (StatusCode::BAD_REQUEST, Json(json!({ "success": false, "error": "Unknown status" })))
+        })?,
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_LIMIT).clamp(1, MAX_LIST_LIMIT);
+    match verifier.repo.list(status, limit).await {
+        Ok(checks) => Ok(Json(json!({ "success": true, "status": status, "checks": checks }))),
+        Err(e) => {
+            tracing::error!("Failed to list ENS checks: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "success": false, "error": "Database error" }))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let wallet = "0x1111111111111111111111111111111111111111";
+        let address: Address = wallet.parse().unwrap();
+        assert_eq!(classify(wallet, Some(address)), EnsCheckStatus::Ok);
+        // Stored addresses may be checksummed or not
+        assert_eq!(classify(&wallet.to_uppercase().replacen("0X", "0x", 1), Some(address)), EnsCheckStatus::Ok);
+        assert_eq!(classify(wallet, Some(Address::zero())), EnsCheckStatus::Mismatch);
+        assert_eq!(classify(wallet, None), EnsCheckStatus::Missing);
+        assert_eq!(classify("not an address", Some(address)), EnsCheckStatus::Mismatch);
+        assert!(is_ttcip_name("Alice.TTCIP.eth"));
+        assert!(!is_ttcip_name("alice.eth"));
+    }
+
+    #[test]
+    fn test_render() {
+        let mut totals = Totals::default();
+        totals.checks.insert("ok", 3);
+        totals.checks.insert("mismatch", 1);
+        totals.repairs_ok = 1;
+        let out = render(&[("ok".to_string(), 7), ("mismatch".to_string(), 2)], &totals);
+        assert!(out.contains("textchain_ens_names{status=\"ok\"} 7\n"));
+        assert!(out.contains("textchain_ens_names{status=\"mismatch\"} 2\n"));
+        assert!(out.contains("textchain_ens_names{status=\"missing\"} 0\n"));
+        assert!(out.contains("textchain_ens_checks_total{outcome=\"mismatch\"} 1\n"));
+        assert!(out.contains("textchain_ens_repairs_total{result=\"ok\"} 1\n"));
+        assert!(out.contains("textchain_ens_last_run_timestamp_seconds 0\n"));
+    }
+}
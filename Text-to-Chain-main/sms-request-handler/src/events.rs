@@ -0,0 +1,213 @@
+//! Live admin event stream
+//!
+//! Handlers publish incoming SMS, transfers, deposit state changes and
+//! errors to one in-process `EventBus`. `GET /admin/events` upgrades to a
+//! WebSocket and streams them as JSON, so an ops dashboard shows activity
+//! without polling. Clients authenticate with `ADMIN_EVENTS_TOKEN` (`?token=`
+//! or `Authorization: Bearer`) and pick topics with `?topics=sms,deposit`,
+//! or by sending `{"topics": [...]}` at any time.
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Events held for a slow client before it starts missing them
+const EVENT_BUFFER: usize = 256;
+
+/// What an event is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topic {
+    /// Incoming SMS command
+    Sms,
+    /// SEND between wallets or cash balances
+    Transfer,
+    /// On-chain deposit pending, confirmed or reversed
+    Deposit,
+    /// Failed replies and background task errors
+    Error,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 4] = [Topic::Sms, Topic::Transfer, Topic::Deposit, Topic::Error];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "sms" => Some(Topic::Sms),
+            "transfer" | "transfers" => Some(Topic::Transfer),
+            "deposit" | "deposits" => Some(Topic::Deposit),
+            "error" | "errors" => Some(Topic::Error),
+            _ => None,
+        }
+    }
+}
+
+/// One streamed event
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub topic: Topic,
+    pub at: DateTime<Utc>,
+    pub data: Value,
+}
+
+/// Fan-out of events to every connected admin client
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<Event>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUFFER);
+        Self { tx }
+    }
+}
+
+impl EventBus {
+    /// Publish to current subscribers; dropped when nobody is listening
+    pub fn publish(&self, topic: Topic, data: Value) {
+        if self.tx.receiver_count() > 0 {
+            let _ = self.tx.send(Event { topic, at: Utc::now(), data });
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.tx.subscribe()
+    }
+}
+
+/// Comma-separated topic names; empty means every topic
+fn parse_topics<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<HashSet<Topic>, String> {
+    let topics = names
+        .into_iter()
+        .flat_map(|names| names.split(','))
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| Topic::parse(name).ok_or_else(|| format!("unknown topic {}", name.trim())))
+        .collect::<Result<HashSet<_>, _>>()?;
+    Ok(if topics.is_empty() { Topic::ALL.into_iter().collect() } else { topics })
+}
+
+/// Token from `?token=` or an `Authorization: Bearer` header
+fn authorized(headers: &HeaderMap, query_token: Option<&str>, expected: &str) -> bool {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    !expected.is_empty() && query_token.or(bearer).is_some_and(|token| token == expected)
+}
+
+/// Admin event stream route state
+#[derive(Clone)]
+pub struct AdminEventsState {
+    pub bus: EventBus,
+    pub token: String,
+}
+
+/// Create admin event stream routes
+pub fn admin_event_routes(state: AdminEventsState) -> Router {
+    Router::new()
+        .route("/events", get(events_socket))
+        .with_state(state)
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    token: Option<String>,
+    topics: Option<String>,
+}
+
+/// Message a client sends to change its topics
+#[derive(Debug, Deserialize)]
+struct Subscription {
+    topics: Vec<String>,
+}
+
+async fn events_socket(
+    State(state): State<AdminEventsState>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Response {
+    if !authorized(&headers, query.token.as_deref(), &state.token) {
+        return (StatusCode::UNAUTHORIZED, Json(json!({ "error": "unauthorized" }))).into_response();
+    }
+    let topics = match parse_topics(query.topics.as_deref()) {
+        Ok(topics) => topics,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e }))).into_response(),
+    };
+    let events = state.bus.subscribe();
+    ws.on_upgrade(move |socket| stream_events(socket, events, topics))
+}
+
+/// Forward matching events until the client goes away
+async fn stream_events(mut socket: WebSocket, mut events: broadcast::Receiver<Event>, mut topics: HashSet<Topic>) {
+    tracing::info!(topics = ?topics, "Admin event stream connected");
+    loop {
+        let outgoing = tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) if topics.contains(&event.topic) => serde_json::to_value(&event).unwrap_or_default(),
+                Ok(_) => continue,
+                // Too slow to keep up: say how many were skipped and carry on
+                Err(RecvError::Lagged(missed)) => json!({ "topic": "lagged", "missed": missed }),
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let subscription = serde_json::from_str::<Subscription>(&text).map_err(|e| e.to_string());
+                    match subscription.and_then(|s| parse_topics(s.topics.iter().map(String::as_str))) {
+                        Ok(subscribed) => {
+                            topics = subscribed;
+                            json!({ "topics": topics })
+                        }
+                        Err(e) => json!({ "error": e }),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by axum
+                Some(Ok(_)) => continue,
+            },
+        };
+        if socket.send(Message::Text(outgoing.to_string())).await.is_err() {
+            break;
+        }
+    }
+    tracing::info!("Admin event stream disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_topics() {
+        assert_eq!(parse_topics(None).unwrap().len(), Topic::ALL.len());
+        assert_eq!(parse_topics(Some("sms, deposits")).unwrap(), HashSet::from([Topic::Sms, Topic::Deposit]));
+        assert_eq!(parse_topics(["error", "transfer"]).unwrap(), HashSet::from([Topic::Error, Topic::Transfer]));
+        assert!(parse_topics(Some("sms,balance")).is_err());
+    }
+
+    #[test]
+    fn test_authorized() {
+        let mut headers = HeaderMap::new();
+        assert!(authorized(&headers, Some("secret"), "secret"));
+        assert!(!authorized(&headers, Some("wrong"), "secret"));
+        assert!(!authorized(&headers, None, "secret"));
+        assert!(!authorized(&headers, Some(""), ""));
+
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+        assert!(authorized(&headers, None, "secret"));
+    }
+}
@@ -0,0 +1,251 @@
+//! Per-deployment feature flags
+//!
+//! Defaults come from `FEATURES_DISABLED`, or `features_disabled` in the live
+//! config file; rows in `feature_flags` override them per feature. Overrides are loaded at startup and reloaded from the
+//! database periodically or on demand, so operators can switch a command off
+//! in one market without a redeploy.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+
+use crate::config::FeaturesConfig;
+use crate::db::FeatureFlagRepository;
+
+/// Reply for a command switched off in this deployment
+pub const NOT_AVAILABLE_REPLY: &str = "Sorry, this service is not available in your region.";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FeatureError {
+    #[error("Unknown feature: {0}")]
+    Unknown(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Feature that can be switched on or off per deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    Send,
+    Swap,
+    Bridge,
+    Cashout,
+    Buy,
+    Redeem,
+    Request,
+    Contacts,
+    Chain,
+    Agents,
+    WalletConnect,
+    Allowances,
+    Savings,
+    Bills,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 14] = [
+        Feature::Send,
+        Feature::Swap,
+        Feature::Bridge,
+        Feature::Cashout,
+        Feature::Buy,
+        Feature::Redeem,
+        Feature::Request,
+        Feature::Contacts,
+        Feature::Chain,
+        Feature::Agents,
+        Feature::WalletConnect,
+        Feature::Allowances,
+        Feature::Savings,
+        Feature::Bills,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::Send => "send",
+            Feature::Swap => "swap",
+            Feature::Bridge => "bridge",
+            Feature::Cashout => "cashout",
+            Feature::Buy => "buy",
+            Feature::Redeem => "redeem",
+            Feature::Request => "request",
+            Feature::Contacts => "contacts",
+            Feature::Chain => "chain",
+            Feature::Agents => "agents",
+            Feature::WalletConnect => "walletconnect",
+            Feature::Allowances => "allowances",
+            Feature::Savings => "savings",
+            Feature::Bills => "bills",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim().to_lowercase();
+        Feature::ALL.into_iter().find(|f| f.name() == name)
+    }
+}
+
+impl std::fmt::Display for Feature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Current state of one feature
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureState {
+    pub feature: Feature,
+    pub enabled: bool,
+    /// Set by a `feature_flags` row rather than the config default
+    pub overridden: bool,
+}
+
+/// Shared feature flags: config defaults plus database overrides
+#[derive(Clone, Default)]
+pub struct FeatureFlags {
+    disabled_by_default: Arc<RwLock<Vec<Feature>>>,
+    overrides: Arc<RwLock<HashMap<Feature, bool>>>,
+    repo: Option<FeatureFlagRepository>,
+}
+
+impl FeatureFlags {
+    /// Flags from config only (no database)
+    pub fn from_config(config: &FeaturesConfig) -> Result<Self, FeatureError> {
+        let disabled_by_default = parse_features(config.disabled.split(','))?;
+        Ok(Self {
+            disabled_by_default: Arc::new(RwLock::new(disabled_by_default)),
+            ..Self::default()
+        })
+    }
+
+    /// Flags from config with overrides loaded from the database
+    pub async fn load(config: &FeaturesConfig, repo: FeatureFlagRepository) -> Result<Self, FeatureError> {
+        let mut flags = Self::from_config(config)?;
+        flags.repo = Some(repo);
+        flags.reload().await?;
+        Ok(flags)
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        let overridden = self.overrides.read().ok().and_then(|o| o.get(&feature).copied());
+        overridden.unwrap_or_else(|| {
+            let defaults = self.disabled_by_default.read().unwrap_or_else(|e| e.into_inner());
+            !defaults.contains(&feature)
+        })
+    }
+
+    /// Replace the features off by default; overrides still win
+    pub fn set_defaults(&self, disabled: Vec<Feature>) {
+        if let Ok(mut defaults) = self.disabled_by_default.write() {
+            *defaults = disabled;
+        }
+    }
+
+    /// Re-read overrides from the database; unknown feature names are skipped
+    pub async fn reload(&self) -> Result<(), sqlx::Error> {
+        let Some(ref repo) = self.repo else {
+            return Ok(());
+        };
+        let overrides: HashMap<Feature, bool> = repo
+            .list()
+            .await?
+            .into_iter()
+            .filter_map(|(name, enabled)| match Feature::from_name(&name) {
+                Some(feature) => Some((feature, enabled)),
+                None => {
+                    tracing::warn!(feature = %name, "Ignoring unknown feature flag override");
+                    None
+                }
+            })
+            .collect();
+        if let Ok(mut current) = self.overrides.write() {
+            *current = overrides;
+        }
+        Ok(())
+    }
+
+    /// Set (Some) or clear (None) an override and persist it
+    pub async fn set_override(&self, feature: Feature, enabled: Option<bool>) -> Result<(), sqlx::Error> {
+        if let Some(ref repo) = self.repo {
+            match enabled {
+                Some(enabled) => repo.set(feature.name(), enabled).await?,
+                None => repo.clear(feature.name()).await?,
+            }
+        }
+        if let Ok(mut overrides) = self.overrides.write() {
+            match enabled {
+                Some(enabled) => overrides.insert(feature, enabled),
+                None => overrides.remove(&feature),
+            };
+        }
+        tracing::info!(feature = %feature, enabled = ?enabled, "Feature flag override changed");
+        Ok(())
+    }
+
+    /// State of every feature
+    pub fn snapshot(&self) -> Vec<FeatureState> {
+        let overrides = self.overrides.read().map(|o| o.clone()).unwrap_or_default();
+        Feature::ALL
+            .into_iter()
+            .map(|feature| FeatureState {
+                feature,
+                enabled: self.is_enabled(feature),
+                overridden: overrides.contains_key(&feature),
+            })
+            .collect()
+    }
+}
+
+/// Parse feature names, skipping blanks
+pub fn parse_features<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Vec<Feature>, FeatureError> {
+    names
+        .into_iter()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| Feature::from_name(name).ok_or_else(|| FeatureError::Unknown(name.to_string())))
+        .collect()
+}
+
+impl std::fmt::Debug for FeatureFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FeatureFlags")
+            .field("disabled_by_default", &self.disabled_by_default)
+            .field("persistent", &self.repo.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_defaults_and_overrides() {
+        let config = FeaturesConfig { disabled: "swap, Bridge".to_string(), reload_secs: 60 };
+        let flags = FeatureFlags::from_config(&config).unwrap();
+
+        assert!(!flags.is_enabled(Feature::Swap));
+        assert!(!flags.is_enabled(Feature::Bridge));
+        assert!(flags.is_enabled(Feature::Send));
+
+        flags.set_override(Feature::Swap, Some(true)).await.unwrap();
+        flags.set_override(Feature::Send, Some(false)).await.unwrap();
+        assert!(flags.is_enabled(Feature::Swap));
+        assert!(!flags.is_enabled(Feature::Send));
+
+        // Clearing an override falls back to the config default
+        flags.set_override(Feature::Swap, None).await.unwrap();
+        assert!(!flags.is_enabled(Feature::Swap));
+
+        // New defaults apply under existing overrides
+        flags.set_defaults(vec![Feature::Buy]);
+        assert!(flags.is_enabled(Feature::Swap));
+        assert!(!flags.is_enabled(Feature::Buy));
+        assert!(!flags.is_enabled(Feature::Send));
+
+        let bad = FeaturesConfig { disabled: "swap,nft".to_string(), reload_secs: 60 };
+        assert!(matches!(FeatureFlags::from_config(&bad), Err(FeatureError::Unknown(name)) if name == "nft"));
+    }
+}
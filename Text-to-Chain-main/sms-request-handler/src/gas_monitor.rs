@@ -0,0 +1,392 @@
+//! Native gas balances of the service's signing wallets
+//!
+//! The admin (treasury hot wallet) and faucet keys, plus any wallets listed
+//! in `GAS_TANK_WALLETS` such as the ENS minter, are checked on every chain
+//! in `GAS_TANK_THRESHOLDS`. A wallet below its chain's threshold texts the
+//! operators, POSTs the alert webhook and raises a `treasury_low` operator
+//! alert, then stays quiet for `GAS_ALERT_REPEAT_HOURS` unless it recovers
+//! and drops again.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, routing::get, Json, Router};
+use chrono::{DateTime, Utc};
+use ethers::providers::Middleware;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, U256};
+use ethers::utils::{format_ether, parse_ether};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::alerts::{self, Alert};
+use crate::config::{FaucetConfig, GasMonitorConfig};
+use crate::sms::SmsGateway;
+use crate::wallet::{Chain, MultiChainProvider};
+
+#[derive(Debug, thiserror::Error)]
+pub enum GasMonitorError {
+    #[error("Invalid gas monitor config: {0}")]
+    Config(String),
+}
+
+/// One wallet on one chain
+#[derive(Debug, Clone, PartialEq)]
+struct Tank {
+    label: String,
+    chain: Chain,
+    address: Address,
+    threshold: U256,
+}
+
+/// Last reading of one wallet on one chain
+#[derive(Debug, Clone, Serialize)]
+pub struct TankStatus {
+    pub label: String,
+    pub chain: &'static str,
+    pub address: String,
+    /// Native tokens (ETH / MATIC); None until read successfully
+    pub balance: Option<f64>,
+    pub threshold: f64,
+    pub low: bool,
+    /// Why the last read failed, if it did
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub checked_at: Option<DateTime<Utc>>,
+}
+
+/// Watches signer balances and alerts operators when they run low
+#[derive(Clone)]
+pub struct GasMonitor {
+    tanks: Arc<Vec<Tank>>,
+    chains: MultiChainProvider,
+    statuses: Arc<RwLock<Vec<TankStatus>>>,
+    /// When each low tank was last alerted about
+    alerted: Arc<Mutex<HashMap<(String, Chain), Instant>>>,
+    twilio: SmsGateway,
+    alert_phones: Vec<String>,
+    webhook_url: Option<String>,
+    http: reqwest::Client,
+    poll: Duration,
+    repeat: Duration,
+}
+
+impl GasMonitor {
+    /// None when `GAS_TANK_THRESHOLDS` is empty
+    pub fn from_config(
+        config: &GasMonitorConfig,
+        admin_private_key: &str,
+        faucet: &FaucetConfig,
+        twilio: SmsGateway,
+    ) -> Result<Option<Self>, GasMonitorError> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        let thresholds = parse_thresholds(&config.thresholds)?;
+
+        let mut wallets = Vec::new();
+        if let Some(address) = signer_address(admin_private_key, "ADMIN_PRIVATE_KEY")? {
+            wallets.push(("admin".to_string(), address, None));
+        }
+        if let Some(address) = signer_address(&faucet.private_key, "FAUCET_PRIVATE_KEY")? {
+            // The faucet only spends on its own chains
+            for name in faucet.chains.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                if let Some(chain) = Chain::from_input(name) {
+                    wallets.push(("faucet".to_string(), address, Some(chain)));
+                }
+            }
+        }
+        wallets.extend(parse_wallets(&config.wallets)?);
+
+        let tanks = tanks_for(&thresholds, &wallets);
+        if tanks.is_empty() {
+            return Err(GasMonitorError::Config("no wallets to watch on the GAS_TANK_THRESHOLDS chains".to_string()));
+        }
+        let statuses = tanks.iter().map(TankStatus::unchecked).collect();
+
+        Ok(Some(Self {
+            tanks: Arc::new(tanks),
+            chains: MultiChainProvider::with_chains(&thresholds.iter().map(|(chain, _)| *chain).collect::<Vec<_>>()),
+            statuses: Arc::new(RwLock::new(statuses)),
+            alerted: Arc::new(Mutex::new(HashMap::new())),
+            twilio,
+            alert_phones: config
+                .alert_phones
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(str::to_string)
+                .collect(),
+            webhook_url: Some(config.alert_webhook_url.trim().to_string()).filter(|url| !url.is_empty()),
+            http: reqwest::Client::new(),
+            poll: Duration::from_secs(config.poll_secs.max(1)),
+            repeat: Duration::from_secs(config.repeat_hours * 3600),
+        }))
+    }
+
+    /// Wallet count and the chains they are on, for the startup log
+    pub fn describe(&self) -> (usize, Vec<&'static str>) {
+        let mut chains: Vec<&'static str> = self.tanks.iter().map(|t| t.chain.short_code()).collect();
+        chains.sort_unstable();
+        chains.dedup();
+        (self.tanks.len(), chains)
+    }
+
+    /// Check balances now and then every `GAS_TANK_POLL_SECS`
+    pub fn start(&self) {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(monitor.poll);
+            loop {
+                ticker.tick().await;
+                monitor.check().await;
+            }
+        });
+    }
+
+    /// Latest reading of every watched wallet
+    pub fn statuses(&self) -> Vec<TankStatus> {
+        self.statuses.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    async fn check(&self) {
+        let mut statuses = Vec::with_capacity(self.tanks.len());
+        for tank in self.tanks.iter() {
+            let address = tank.address;
+            let read = self
+                .chains
+                .call(tank.chain, |provider| async move { provider.get_balance(address, None).await })
+                .await;
+            let mut status = TankStatus::unchecked(tank);
+            status.checked_at = Some(Utc::now());
+            match read {
+                Ok(balance) => {
+                    status.balance = Some(ether_f64(balance));
+                    status.low = balance < tank.threshold;
+                    self.after_read(tank, &status).await;
+                }
+                Err(e) => {
+                    tracing::warn!(label = %tank.label, chain = %tank.chain, "Gas balance check failed: {}", e);
+                    status.error = Some(e.to_string());
+                }
+            }
+            statuses.push(status);
+        }
+        *self.statuses.write().unwrap_or_else(|e| e.into_inner()) = statuses;
+    }
+
+    /// Alert on a low tank unless alerted recently; forget recovered ones
+    async fn after_read(&self, tank: &Tank, status: &TankStatus) {
+        let key = (tank.label.clone(), tank.chain);
+        let now = Instant::now();
+        let alert = {
+            let mut alerted = self.alerted.lock().unwrap_or_else(|e| e.into_inner());
+            if !status.low {
+                if alerted.remove(&key).is_some() {
+                    tracing::info!(label = %tank.label, chain = %tank.chain, balance = ?status.balance, "Gas tank refilled");
+                    alerts::resolve(Alert::treasury_low_key(&tank.label, tank.chain));
+                }
+                false
+            } else if should_alert(alerted.get(&key).copied(), now, self.repeat) {
+                alerted.insert(key, now);
+                true
+            } else {
+                false
+            }
+        };
+        if alert {
+            self.alert(tank, status).await;
+        }
+    }
+
+    async fn alert(&self, tank: &Tank, status: &TankStatus) {
+        let balance = status.balance.unwrap_or_default();
+        tracing::error!(
+            label = %tank.label,
+            chain = %tank.chain,
+            address = %status.address,
+            balance,
+            threshold = status.threshold,
+            "Gas tank low"
+        );
+        alerts::raise(Alert::treasury_low(&tank.label, tank.chain, &status.address, balance, status.threshold));
+
+        let body = format!(
+            "TextChain gas low: {} wallet {} has {} {} on {} (min {}). Top it up.",
+            tank.label,
+            short_address(&status.address),
+            balance,
+            tank.chain.native_token(),
+            tank.chain.name(),
+            status.threshold
+        );
+        for phone in &self.alert_phones {
+            if let Err(e) = self.twilio.send_sms(phone, &body).await {
+                tracing::warn!(to = %phone, "Gas alert SMS not sent: {}", e);
+            }
+        }
+
+        if let Some(ref url) = self.webhook_url {
+            let payload = json!({ "event": "gas_low", "tank": status, "native_token": tank.chain.native_token() });
+            match self.http.post(url).json(&payload).send().await {
+                Ok(response) if !response.status().is_success() => {
+                    tracing::warn!(status = %response.status(), "Gas alert webhook refused");
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Gas alert webhook failed: {}", e),
+            }
+        }
+    }
+}
+
+impl TankStatus {
+    fn unchecked(tank: &Tank) -> Self {
+        Self {
+            label: tank.label.clone(),
+            chain: tank.chain.short_code(),
+            address: format!("{:?}", tank.address),
+            balance: None,
+            threshold: ether_f64(tank.threshold),
+            low: false,
+            error: None,
+            checked_at: None,
+        }
+    }
+}
+
+/// First alert for a low tank, or the last one is older than `repeat`
+fn should_alert(last: Option<Instant>, now: Instant, repeat: Duration) -> bool {
+    last.is_none_or(|at| now.duration_since(at) >= repeat)
+}
+
+fn ether_f64(wei: U256) -> f64 {
+    format_ether(wei).parse().unwrap_or(f64::MAX)
+}
+
+fn short_address(address: &str) -> &str {
+    address.get(..10).unwrap_or(address)
+}
+
+fn signer_address(private_key: &str, name: &str) -> Result<Option<Address>, GasMonitorError> {
+    match private_key.trim() {
+        "" => Ok(None),
+        key => key
+            .trim_start_matches("0x")
+            .parse::<LocalWallet>()
+            .map(|wallet| Some(wallet.address()))
+            .map_err(|_| GasMonitorError::Config(name.to_string())),
+    }
+}
+
+/// `sepolia:0.05,amoy:0.5` -> minimum wei per chain
+fn parse_thresholds(spec: &str) -> Result<Vec<(Chain, U256)>, GasMonitorError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(|entry| {
+            entry
+                .split_once(':')
+                .and_then(|(chain, min)| Some((Chain::from_input(chain.trim())?, parse_ether(min.trim()).ok()?)))
+                .ok_or_else(|| GasMonitorError::Config(format!("GAS_TANK_THRESHOLDS entry {}", entry)))
+        })
+        .collect()
+}
+
+/// `minter:0xabc...` or `minter:0xabc...@sepolia`
+fn parse_wallets(spec: &str) -> Result<Vec<(String, Address, Option<Chain>)>, GasMonitorError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|e| !e.is_empty())
+        .map(|entry| {
+            let invalid = || GasMonitorError::Config(format!("GAS_TANK_WALLETS entry {}", entry));
+            let (label, rest) = entry.split_once(':').ok_or_else(invalid)?;
+            let (address, chain) = match rest.split_once('@') {
+                Some((address, chain)) => (address, Some(Chain::from_input(chain.trim()).ok_or_else(invalid)?)),
+                None => (rest, None),
+            };
+            let address = address.trim().parse::<Address>().map_err(|_| invalid())?;
+            let label = label.trim();
+            if label.is_empty() {
+                return Err(invalid());
+            }
+            Ok((label.to_string(), address, chain))
+        })
+        .collect()
+}
+
+/// Every wallet on every threshold chain it applies to
+fn tanks_for(thresholds: &[(Chain, U256)], wallets: &[(String, Address, Option<Chain>)]) -> Vec<Tank> {
+    let mut tanks = Vec::new();
+    for (chain, threshold) in thresholds {
+        for (label, address, only) in wallets {
+            if only.is_none_or(|only| only == *chain) {
+                tanks.push(Tank { label: label.clone(), chain: *chain, address: *address, threshold: *threshold });
+            }
+        }
+    }
+    tanks
+}
+
+/// `GET /metrics/gas-tanks`
+pub fn gas_routes(monitor: GasMonitor) -> Router {
+    Router::new()
+        .route("/metrics/gas-tanks", get(gas_tanks))
+        .with_state(monitor)
+}
+
+async fn gas_tanks(State(monitor): State<GasMonitor>) -> Json<Vec<TankStatus>> {
+    Json(monitor.statuses())
+}
+
+impl GasMonitor {
+    /// Which signers need topping up, for /admin/summary
+    pub fn summary(&self) -> serde_json::Map<String, serde_json::Value> {
+        let gas_tanks = self.statuses();
+        let low = gas_tanks.iter().filter(|t| t.low).count();
+        let unreadable = gas_tanks.iter().filter(|t| t.error.is_some()).count();
+        let mut summary = serde_json::Map::new();
+        summary.insert("low_gas".to_string(), json!(low));
+        summary.insert("unreadable_gas".to_string(), json!(unreadable));
+        summary.insert("gas_tanks".to_string(), json!(gas_tanks));
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_expand() {
+        let thresholds = parse_thresholds("sepolia:0.05, amoy:0.5").unwrap();
+        assert_eq!(thresholds[0], (Chain::EthereumSepolia, parse_ether("0.05").unwrap()));
+        assert!(parse_thresholds("sepolia").is_err());
+        assert!(parse_thresholds("nowhere:1").is_err());
+
+        let minter: Address = "0x1111111111111111111111111111111111111111".parse().unwrap();
+        let wallets = parse_wallets("minter:0x1111111111111111111111111111111111111111@sepolia").unwrap();
+        assert_eq!(wallets, vec![("minter".to_string(), minter, Some(Chain::EthereumSepolia))]);
+        assert!(parse_wallets("minter:0x123").is_err());
+        assert!(parse_wallets(":0x1111111111111111111111111111111111111111").is_err());
+
+        let mut all = wallets.clone();
+        all.push(("admin".to_string(), Address::zero(), None));
+        let tanks = tanks_for(&thresholds, &all);
+        // The minter is only watched on Sepolia; admin on both chains
+        let watched: Vec<(&str, Chain)> = tanks.iter().map(|t| (t.label.as_str(), t.chain)).collect();
+        assert_eq!(
+            watched,
+            vec![("minter", Chain::EthereumSepolia), ("admin", Chain::EthereumSepolia), ("admin", Chain::PolygonAmoy)]
+        );
+    }
+
+    #[test]
+    fn test_should_alert() {
+        let now = Instant::now();
+        let repeat = Duration::from_secs(3600);
+        assert!(should_alert(None, now, repeat));
+        assert!(!should_alert(Some(now), now + Duration::from_secs(60), repeat));
+        assert!(should_alert(Some(now), now + repeat, repeat));
+    }
+}
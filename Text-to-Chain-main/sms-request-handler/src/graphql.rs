@@ -0,0 +1,491 @@
+//! Read-only GraphQL API for partner dashboards
+//!
+//! Exposes users, vouchers and deposits as cursor-paginated connections.
+//! Partners authenticate with `Authorization: Bearer <key>`; each key carries
+//! scopes that gate both whole queries and sensitive fields.
+
+use async_graphql::connection::{Connection, Edge};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, Error, Guard, InputObject, Object, Result,
+    Schema, SimpleObject,
+};
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::{Deposit, FieldCipher, User, Voucher, DEPOSIT_COLUMNS, VOUCHER_COLUMNS};
+
+/// Page size when `first` is not given
+const DEFAULT_PAGE_SIZE: i64 = 20;
+
+/// Largest page a partner can request
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Access scope granted to a partner API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Users,
+    Vouchers,
+    Deposits,
+    /// Phone numbers and voucher codes
+    Sensitive,
+}
+
+impl Scope {
+    fn parse(s: &str) -> Option<Vec<Scope>> {
+        match s.trim().to_lowercase().as_str() {
+            "users" => Some(vec![Scope::Users]),
+            "vouchers" => Some(vec![Scope::Vouchers]),
+            "deposits" => Some(vec![Scope::Deposits]),
+            "sensitive" => Some(vec![Scope::Sensitive]),
+            "*" => Some(vec![Scope::Users, Scope::Vouchers, Scope::Deposits, Scope::Sensitive]),
+            _ => None,
+        }
+    }
+}
+
+/// Parse `GRAPHQL_API_KEYS`: comma-separated `key:scope|scope` entries,
+/// e.g. `k1:users|deposits,k2:*`. Entries with unknown scopes are skipped.
+pub fn parse_api_keys(raw: &str) -> HashMap<String, HashSet<Scope>> {
+    raw.split(',')
+        .filter_map(|entry| {
+            let (key, scopes) = entry.trim().split_once(':')?;
+            if key.is_empty() {
+                return None;
+            }
+            let scopes = scopes
+                .split('|')
+                .map(Scope::parse)
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .flatten()
+                .collect();
+            Some((key.to_string(), scopes))
+        })
+        .collect()
+}
+
+/// Scopes of the partner making the current request
+struct PartnerScopes(HashSet<Scope>);
+
+/// Rejects the field unless the partner key has `scope`
+struct ScopeGuard(Scope);
+
+impl ScopeGuard {
+    fn new(scope: Scope) -> Self {
+        Self(scope)
+    }
+}
+
+impl Guard for ScopeGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_opt::<PartnerScopes>() {
+            Some(scopes) if scopes.0.contains(&self.0) => Ok(()),
+            _ => Err(Error::new(format!("Missing scope: {:?}", self.0).to_lowercase())),
+        }
+    }
+}
+
+/// Opaque keyset cursor over `(created_at, id)`
+fn encode_cursor(created_at: DateTime<Utc>, id: Uuid) -> String {
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("{}|{}", created_at.to_rfc3339(), id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(DateTime<Utc>, Uuid)> {
+    let invalid = || Error::new("Invalid cursor");
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(cursor)
+        .map_err(|_| invalid())?;
+    let text = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let (created_at, id) = text.split_once('|').ok_or_else(invalid)?;
+    Ok((
+        DateTime::parse_from_rfc3339(created_at).map_err(|_| invalid())?.with_timezone(&Utc),
+        id.parse().map_err(|_| invalid())?,
+    ))
+}
+
+/// Append keyset pagination (newest first) and fetch one extra row to detect a next page
+fn paginate(query: &mut QueryBuilder<'_, Postgres>, first: Option<i32>, after: Option<&str>) -> Result<i64> {
+    let limit = first.map(i64::from).unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    if let Some(after) = after {
+        let (created_at, id) = decode_cursor(after)?;
+        query.push(" AND (created_at, id) < (").push_bind(created_at).push(", ").push_bind(id).push(")");
+    }
+    query.push(" ORDER BY created_at DESC, id DESC LIMIT ").push_bind(limit + 1);
+    Ok(limit)
+}
+
+/// Build a connection from `limit + 1` fetched rows
+fn connection<T, N: async_graphql::OutputType>(
+    mut rows: Vec<T>,
+    limit: i64,
+    has_previous_page: bool,
+    key: impl Fn(&T) -> (DateTime<Utc>, Uuid),
+    node: impl Fn(T) -> Result<N>,
+) -> Result<Connection<String, N>> {
+    let has_next_page = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+
+    let mut connection = Connection::new(has_previous_page, has_next_page);
+    for row in rows {
+        let (created_at, id) = key(&row);
+        connection.edges.push(Edge::new(encode_cursor(created_at, id), node(row)?));
+    }
+    Ok(connection)
+}
+
+/// Wallet user
+#[derive(SimpleObject)]
+pub struct UserNode {
+    id: Uuid,
+    #[graphql(guard = "ScopeGuard::new(Scope::Sensitive)")]
+    phone: String,
+    wallet_address: String,
+    ens_name: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+/// Voucher code
+#[derive(SimpleObject)]
+pub struct VoucherNode {
+    id: Uuid,
+    #[graphql(guard = "ScopeGuard::new(Scope::Sensitive)")]
+    code: String,
+    /// Amount in USDC
+    usdc_amount: f64,
+    /// Local currency the voucher was issued in, if any
+    local_currency: Option<String>,
+    local_amount: Option<f64>,
+    /// Local units per USDC at creation time
+    fx_rate: Option<f64>,
+    status: String,
+    #[graphql(guard = "ScopeGuard::new(Scope::Sensitive)")]
+    redeemed_by: Option<String>,
+    redeemed_at: Option<DateTime<Utc>>,
+    expires_at: Option<DateTime<Utc>>,
+    created_at: DateTime<Utc>,
+}
+
+/// Credited deposit
+#[derive(SimpleObject)]
+pub struct DepositNode {
+    id: Uuid,
+    #[graphql(guard = "ScopeGuard::new(Scope::Sensitive)")]
+    user_phone: String,
+    /// Amount in USDC
+    amount: f64,
+    source: String,
+    source_ref: Option<String>,
+    chain: Option<String>,
+    /// "pending" until final on chain, "confirmed", or "reversed" after a reorg
+    status: String,
+    /// Blocks deep at the last check (on-chain deposits)
+    confirmations: i32,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(InputObject, Default)]
+pub struct UserFilter {
+    /// Exact phone number (requires the sensitive scope)
+    phone: Option<String>,
+    wallet_address: Option<String>,
+    has_ens_name: Option<bool>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Enum, Copy, Clone, PartialEq, Eq)]
+pub enum VoucherStatusFilter {
+    Unused,
+    Redeemed,
+    Expired,
+}
+
+#[derive(InputObject, Default)]
+pub struct VoucherFilter {
+    status: Option<VoucherStatusFilter>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+}
+
+#[derive(InputObject, Default)]
+pub struct DepositFilter {
+    /// Exact phone number (requires the sensitive scope)
+    user_phone: Option<String>,
+    /// "voucher", "onchain" or "partner"
+    source: Option<String>,
+    chain: Option<String>,
+    /// "pending", "confirmed" or "reversed"
+    status: Option<String>,
+    created_after: Option<DateTime<Utc>>,
+    created_before: Option<DateTime<Utc>>,
+}
+
+fn push_created_range(
+    query: &mut QueryBuilder<'_, Postgres>,
+    after: Option<DateTime<Utc>>,
+    before: Option<DateTime<Utc>>,
+) {
+    if let Some(after) = after {
+        query.push(" AND created_at >= ").push_bind(after);
+    }
+    if let Some(before) = before {
+        query.push(" AND created_at < ").push_bind(before);
+    }
+}
+
+fn require_sensitive(ctx: &Context<'_>) -> Result<()> {
+    match ctx.data_opt::<PartnerScopes>() {
+        Some(scopes) if scopes.0.contains(&Scope::Sensitive) => Ok(()),
+        _ => Err(Error::new("Filtering by phone requires the sensitive scope")),
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Wallet users, newest first
+    #[graphql(guard = "ScopeGuard::new(Scope::Users)")]
+    async fn users(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        filter: Option<UserFilter>,
+    ) -> Result<Connection<String, UserNode>> {
+        let pool = ctx.data::<PgPool>()?;
+        let cipher = ctx.data::<FieldCipher>()?;
+        let filter = filter.unwrap_or_default();
+
+        let mut query = QueryBuilder::<Postgres>::new(
+            "SELECT id, COALESCE(phone_encrypted, phone) AS phone, wallet_address, encrypted_private_key,
+                    pin_hash, ens_name, created_at
+             FROM users WHERE TRUE",
+        );
+        if let Some(phone) = filter.phone.as_deref() {
+            require_sensitive(ctx)?;
+            query.push(" AND phone = ANY(").push_bind(cipher.lookup_keys(phone)).push(")");
+        }
+        if let Some(wallet) = filter.wallet_address {
+            query.push(" AND LOWER(wallet_address) = LOWER(").push_bind(wallet).push(")");
+        }
+        match filter.has_ens_name {
+            Some(true) => {
+                query.push(" AND ens_name IS NOT NULL");
+            }
+            Some(false) => {
+                query.push(" AND ens_name IS NULL");
+            }
+            None => {}
+        }
+        push_created_range(&mut query, filter.created_after, filter.created_before);
+        let limit = paginate(&mut query, first, after.as_deref())?;
+
+        let rows = query.build_query_as::<User>().fetch_all(pool).await?;
+        connection(rows, limit, after.is_some(), |u| (u.created_at, u.id), |u| {
+            Ok(UserNode {
+                id: u.id,
+                phone: cipher.decrypt(&u.phone)?,
+                wallet_address: u.wallet_address,
+                ens_name: u.ens_name,
+                created_at: u.created_at,
+            })
+        })
+    }
+
+    /// Voucher codes, newest first
+    #[graphql(guard = "ScopeGuard::new(Scope::Vouchers)")]
+    async fn vouchers(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        filter: Option<VoucherFilter>,
+    ) -> Result<Connection<String, VoucherNode>> {
+        let pool = ctx.data::<PgPool>()?;
+        let filter = filter.unwrap_or_default();
+
+        let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM vouchers WHERE TRUE", VOUCHER_COLUMNS));
+        if let Some(status) = filter.status {
+            let status = match status {
+                VoucherStatusFilter::Unused => "unused",
+                VoucherStatusFilter::Redeemed => "redeemed",
+                VoucherStatusFilter::Expired => "expired",
+            };
+            query.push(" AND status = ").push_bind(status);
+        }
+        push_created_range(&mut query, filter.created_after, filter.created_before);
+        let limit = paginate(&mut query, first, after.as_deref())?;
+
+        let rows = query.build_query_as::<Voucher>().fetch_all(pool).await?;
+        connection(rows, limit, after.is_some(), |v| (v.created_at, v.id), |v| {
+            Ok(VoucherNode {
+                id: v.id,
+                usdc_amount: v.usdc_amount.to_f64(),
+                local_currency: v.local_currency,
+                local_amount: v.local_amount,
+                fx_rate: v.fx_rate,
+                code: v.code,
+                status: v.status,
+                redeemed_by: v.redeemed_by,
+                redeemed_at: v.redeemed_at,
+                expires_at: v.expires_at,
+                created_at: v.created_at,
+            })
+        })
+    }
+
+    /// Deposits credited to users, newest first
+    #[graphql(guard = "ScopeGuard::new(Scope::Deposits)")]
+    async fn deposits(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+        filter: Option<DepositFilter>,
+    ) -> Result<Connection<String, DepositNode>> {
+        let pool = ctx.data::<PgPool>()?;
+        let cipher = ctx.data::<FieldCipher>()?;
+        let filter = filter.unwrap_or_default();
+
+        let mut query = QueryBuilder::<Postgres>::new(format!("SELECT {} FROM deposits WHERE TRUE", DEPOSIT_COLUMNS));
+        if let Some(phone) = filter.user_phone {
+            require_sensitive(ctx)?;
+            query.push(" AND user_phone = ANY(").push_bind(cipher.lookup_keys(&phone)).push(")");
+        }
+        if let Some(source) = filter.source {
+            query.push(" AND source = ").push_bind(source.to_lowercase());
+        }
+        if let Some(chain) = filter.chain {
+            query.push(" AND chain = ").push_bind(chain);
+        }
+        if let Some(status) = filter.status {
+            query.push(" AND status = ").push_bind(status.to_lowercase());
+        }
+        push_created_range(&mut query, filter.created_after, filter.created_before);
+        let limit = paginate(&mut query, first, after.as_deref())?;
+
+        let rows = query.build_query_as::<Deposit>().fetch_all(pool).await?;
+        connection(rows, limit, after.is_some(), |d| (d.created_at, d.id), |d| {
+            Ok(DepositNode {
+                id: d.id,
+                amount: d.amount.to_f64(),
+                user_phone: d.user_phone,
+                source: d.source,
+                source_ref: d.source_ref,
+                chain: d.chain,
+                status: d.status,
+                confirmations: d.confirmations,
+                created_at: d.created_at,
+            })
+        })
+    }
+}
+
+pub type PartnerSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// GraphQL route state
+#[derive(Clone)]
+pub struct GraphqlState {
+    pub schema: PartnerSchema,
+    pub api_keys: Arc<HashMap<String, HashSet<Scope>>>,
+}
+
+impl GraphqlState {
+    pub fn new(pool: PgPool, cipher: FieldCipher, api_keys: HashMap<String, HashSet<Scope>>) -> Self {
+        let schema = Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+            .data(pool)
+            .data(cipher)
+            .limit_depth(8)
+            .finish();
+        Self {
+            schema,
+            api_keys: Arc::new(api_keys),
+        }
+    }
+}
+
+/// Create the partner GraphQL route
+pub fn graphql_routes(state: GraphqlState) -> Router {
+    Router::new()
+        .route("/graphql", post(graphql_handler))
+        .with_state(state)
+}
+
+async fn graphql_handler(
+    State(state): State<GraphqlState>,
+    headers: HeaderMap,
+    Json(request): Json<async_graphql::Request>,
+) -> Result<Json<async_graphql::Response>, StatusCode> {
+    let key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let scopes = state.api_keys.get(key.trim()).ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let request = request.data(PartnerScopes(scopes.clone()));
+    Ok(Json(state.schema.execute(request).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_keys() {
+        let keys = parse_api_keys("k1:users|deposits, k2:*,bad:nope,:users");
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys["k1"], HashSet::from([Scope::Users, Scope::Deposits]));
+        assert!(keys["k2"].contains(&Scope::Sensitive));
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let created_at = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let id = Uuid::new_v4();
+        assert_eq!(decode_cursor(&encode_cursor(created_at, id)).unwrap(), (created_at, id));
+        assert!(decode_cursor("not-a-cursor").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sensitive_field_requires_scope() {
+        struct Root;
+
+        #[Object]
+        impl Root {
+            async fn user(&self) -> UserNode {
+                UserNode {
+                    id: Uuid::nil(),
+                    phone: "+15551234567".to_string(),
+                    wallet_address: "0x0".to_string(),
+                    ens_name: None,
+                    created_at: Utc::now(),
+                }
+            }
+        }
+
+        let schema = Schema::new(Root, EmptyMutation, EmptySubscription);
+        let query = "{ user { walletAddress phone } }";
+
+        let denied = schema
+            .execute(async_graphql::Request::new(query).data(PartnerScopes(HashSet::from([Scope::Users]))))
+            .await;
+        assert_eq!(denied.errors.len(), 1);
+
+        let allowed = schema
+            .execute(async_graphql::Request::new(query).data(PartnerScopes(HashSet::from([Scope::Sensitive]))))
+            .await;
+        assert!(allowed.errors.is_empty());
+    }
+}
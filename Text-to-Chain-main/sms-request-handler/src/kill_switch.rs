@@ -0,0 +1,99 @@
+//! Emergency kill switch for commands that move money
+//!
+//! While engaged, SEND, CASHOUT, SWAP and every other command that moves
+//! funds gets a fixed reply instead of running; BALANCE, HISTORY and other
+//! lookups keep working. The state is one `kill_switch` row that each
+//! money-moving command reads, so a flip on one instance applies to the
+//! whole fleet at once. `KILL_SWITCH=true` engages it from config, and then
+//! it can't be released from the admin API.
+
+use std::sync::{Arc, RwLock};
+
+use crate::config::KillSwitchConfig;
+use crate::db::{KillSwitchRepository, KillSwitchState};
+
+/// Name recorded as `changed_by` when KILL_SWITCH engages it
+const CONFIG_ACTOR: &str = "config";
+
+#[derive(Debug, thiserror::Error)]
+pub enum KillSwitchError {
+    #[error("Engaged by KILL_SWITCH; unset it and restart to release")]
+    ForcedByConfig,
+    #[error("No database; set KILL_SWITCH instead")]
+    NoDatabase,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Shared kill switch state
+#[derive(Clone, Default)]
+pub struct KillSwitch {
+    repo: Option<KillSwitchRepository>,
+    /// Engaged by config; admins can't release it
+    forced: bool,
+    message: String,
+    /// Last state read, used when the database can't be reached
+    last: Arc<RwLock<KillSwitchState>>,
+}
+
+impl KillSwitch {
+    pub fn from_config(config: &KillSwitchConfig, repo: Option<KillSwitchRepository>) -> Self {
+        Self {
+            repo,
+            forced: config.engaged,
+            message: config.message.clone(),
+            last: Arc::default(),
+        }
+    }
+
+    /// Reply to a command refused while engaged
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Current state, read from the database so every instance agrees. If
+    /// the read fails, the last state seen stands
+    pub async fn state(&self) -> KillSwitchState {
+        if self.forced {
+            return KillSwitchState {
+                engaged: true,
+                reason: "KILL_SWITCH is set".to_string(),
+                changed_by: CONFIG_ACTOR.to_string(),
+                changed_at: None,
+            };
+        }
+        let Some(ref repo) = self.repo else {
+            return KillSwitchState::default();
+        };
+        match repo.get().await {
+            Ok(state) => {
+                if let Ok(mut last) = self.last.write() {
+                    *last = state.clone();
+                }
+                state
+            }
+            Err(e) => {
+                tracing::warn!("Kill switch read failed, using last state: {}", e);
+                self.last.read().map(|last| last.clone()).unwrap_or_default()
+            }
+        }
+    }
+
+    pub async fn is_engaged(&self) -> bool {
+        self.state().await.engaged
+    }
+
+    /// Engage or release for every instance
+    pub async fn set(&self, engaged: bool, changed_by: &str, reason: &str) -> Result<KillSwitchState, KillSwitchError> {
+        if self.forced {
+            return Err(KillSwitchError::ForcedByConfig);
+        }
+        let repo = self.repo.as_ref().ok_or(KillSwitchError::NoDatabase)?;
+        let state = repo.set(engaged, changed_by, reason).await?;
+        if let Ok(mut last) = self.last.write() {
+            *last = state.clone();
+        }
+        tracing::warn!(engaged, changed_by, reason, "Kill switch changed");
+        Ok(state)
+    }
+}
@@ -0,0 +1,426 @@
+//! Settings that change without a restart
+//!
+//! `CONFIG_FILE` names a JSON file of RPC endpoints, default-off features,
+//! the SMS fee schedule and the broadcast rate limit. It is re-read every
+//! `CONFIG_RELOAD_SECS` and on `POST /admin/config/reload`. A changed file
+//! is validated as a whole, swapped in as one snapshot and pushed to the
+//! components that use it; an invalid one is reported and the last good
+//! snapshot stays in effect. Anything the file leaves out falls back to its
+//! environment variable.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::broadcast::BroadcastQueue;
+use crate::config::{AppConfig, SmsCostConfig};
+use crate::features::{parse_features, Feature, FeatureError, FeatureFlags};
+use crate::sms::cost::RateTable;
+use crate::sms::SpendTracker;
+use crate::wallet::{set_rpc_urls, Chain};
+
+#[derive(Debug, thiserror::Error)]
+pub enum LiveConfigError {
+    #[error("Failed to read {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("Invalid config file: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Unknown chain in rpc_urls: {0}")]
+    UnknownChain(String),
+    #[error("Invalid RPC URL for {0}: {1}")]
+    InvalidRpcUrl(String, String),
+    #[error("Invalid features_disabled: {0}")]
+    Feature(#[from] FeatureError),
+    #[error("Invalid {0}: {1}")]
+    Invalid(&'static str, String),
+}
+
+/// Contents of the config file; every section is optional
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LiveConfig {
+    /// Operator's label for this revision (default: start of the checksum)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// RPC endpoint per chain, keyed as in SMS commands (`amoy`, `base-sepolia`)
+    #[serde(default)]
+    pub rpc_urls: BTreeMap<String, String>,
+    /// Features off by default, in place of FEATURES_DISABLED
+    #[serde(default)]
+    pub features_disabled: Option<Vec<String>>,
+    #[serde(default)]
+    pub sms_cost: SmsCostOverrides,
+    #[serde(default)]
+    pub rate_limits: RateLimitOverrides,
+}
+
+/// Fee schedule, in place of the matching SMS_* variables
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmsCostOverrides {
+    /// Per-segment rates, e.g. `1:0.0079,254:0.12`
+    pub rates: Option<String>,
+    pub default_rate: Option<f64>,
+    pub daily_budget: Option<f64>,
+    pub alert_percent: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimitOverrides {
+    /// In place of BROADCAST_RATE_PER_SEC
+    pub broadcast_per_sec: Option<f64>,
+}
+
+/// Environment values the file can override
+#[derive(Debug, Clone)]
+struct Defaults {
+    features_disabled: String,
+    sms_cost: SmsCostConfig,
+    broadcast_per_sec: f64,
+}
+
+impl Defaults {
+    fn from_config(config: &AppConfig) -> Self {
+        Self {
+            features_disabled: config.features.disabled.clone(),
+            sms_cost: config.sms_cost.clone(),
+            broadcast_per_sec: config.broadcast.rate_per_sec,
+        }
+    }
+}
+
+/// Settings in effect: the file merged over the environment
+#[derive(Debug, Clone)]
+struct Settings {
+    rpc_urls: HashMap<Chain, String>,
+    features_disabled: Vec<Feature>,
+    sms_cost: SmsCostConfig,
+    broadcast_per_sec: f64,
+}
+
+impl LiveConfig {
+    pub fn parse(json: &str) -> Result<Self, LiveConfigError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Check every setting and merge them over the environment
+    fn resolve(&self, defaults: &Defaults) -> Result<Settings, LiveConfigError> {
+        let mut rpc_urls = HashMap::new();
+        for (name, url) in &self.rpc_urls {
+            let chain = Chain::from_input(name).ok_or_else(|| LiveConfigError::UnknownChain(name.clone()))?;
+            let valid = reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https"));
+            if !valid {
+                return Err(LiveConfigError::InvalidRpcUrl(name.clone(), url.clone()));
+            }
+            rpc_urls.insert(chain, url.clone());
+        }
+
+        let features_disabled = match self.features_disabled {
+            Some(ref names) => parse_features(names.iter().map(String::as_str))?,
+            None => parse_features(defaults.features_disabled.split(','))?,
+        };
+
+        let overrides = &self.sms_cost;
+        let sms_cost = SmsCostConfig {
+            rates: overrides.rates.clone().unwrap_or_else(|| defaults.sms_cost.rates.clone()),
+            default_rate: overrides.default_rate.unwrap_or(defaults.sms_cost.default_rate),
+            daily_budget: overrides.daily_budget.unwrap_or(defaults.sms_cost.daily_budget),
+            alert_percent: overrides.alert_percent.unwrap_or(defaults.sms_cost.alert_percent),
+            trim_over_budget: defaults.sms_cost.trim_over_budget,
+            gsm7: defaults.sms_cost.gsm7.clone(),
+        };
+        if let Some(entry) = RateTable::invalid_entry(&sms_cost.rates) {
+            return Err(LiveConfigError::Invalid("sms_cost.rates", entry));
+        }
+        check_range("sms_cost.default_rate", sms_cost.default_rate, 0.0, f64::MAX)?;
+        check_range("sms_cost.daily_budget", sms_cost.daily_budget, 0.0, f64::MAX)?;
+        check_range("sms_cost.alert_percent", sms_cost.alert_percent, 0.0, 100.0)?;
+
+        let broadcast_per_sec = self.rate_limits.broadcast_per_sec.unwrap_or(defaults.broadcast_per_sec);
+        check_range("rate_limits.broadcast_per_sec", broadcast_per_sec, 0.01, 1000.0)?;
+
+        Ok(Settings { rpc_urls, features_disabled, sms_cost, broadcast_per_sec })
+    }
+}
+
+fn check_range(field: &'static str, value: f64, min: f64, max: f64) -> Result<(), LiveConfigError> {
+    if value.is_finite() && value >= min && value <= max {
+        Ok(())
+    } else {
+        Err(LiveConfigError::Invalid(field, format!("{} is outside {}..={}", value, min, max)))
+    }
+}
+
+fn checksum(contents: &str) -> String {
+    hex::encode(Sha256::digest(contents.as_bytes()))
+}
+
+/// Snapshot in effect, as reported by `GET /admin/config`
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedConfig {
+    /// The file's `version`, else the first 12 characters of its checksum
+    pub version: String,
+    /// SHA-256 of the file as read
+    pub checksum: String,
+    pub applied_at: DateTime<Utc>,
+    pub config: LiveConfig,
+    #[serde(skip)]
+    settings: Settings,
+}
+
+impl AppliedConfig {
+    fn new(checksum: String, config: LiveConfig, settings: Settings) -> Self {
+        let version = config.version.clone().unwrap_or_else(|| checksum[..12].to_string());
+        Self { version, checksum, applied_at: Utc::now(), config, settings }
+    }
+}
+
+/// Latest file that could not be applied, kept until a good one is
+#[derive(Debug, Clone, Serialize)]
+pub struct RejectedConfig {
+    /// Empty when the file couldn't be read
+    pub checksum: String,
+    pub error: String,
+    pub rejected_at: DateTime<Utc>,
+}
+
+/// The current snapshot plus the components it is pushed to
+#[derive(Clone)]
+pub struct ConfigStore {
+    path: PathBuf,
+    defaults: Arc<Defaults>,
+    current: Arc<RwLock<Arc<AppliedConfig>>>,
+    rejected: Arc<RwLock<Option<RejectedConfig>>>,
+    features: Option<FeatureFlags>,
+    costs: Option<SpendTracker>,
+    broadcasts: Option<BroadcastQueue>,
+}
+
+impl ConfigStore {
+    /// Read and apply `CONFIG_FILE` (None when unset). RPC overrides apply
+    /// at once; other settings reach each component as it is attached.
+    pub fn load(config: &AppConfig) -> Result<Option<Self>, LiveConfigError> {
+        if !config.live.is_enabled() {
+            return Ok(None);
+        }
+        Self::open(Path::new(config.live.file.trim()), Defaults::from_config(config)).map(Some)
+    }
+
+    fn open(path: &Path, defaults: Defaults) -> Result<Self, LiveConfigError> {
+        let contents = read(path)?;
+        let config = LiveConfig::parse(&contents)?;
+        let settings = config.resolve(&defaults)?;
+        set_rpc_urls(&settings.rpc_urls);
+        Ok(Self {
+            path: path.to_path_buf(),
+            defaults: Arc::new(defaults),
+            current: Arc::new(RwLock::new(Arc::new(AppliedConfig::new(checksum(&contents), config, settings)))),
+            rejected: Arc::new(RwLock::new(None)),
+            features: None,
+            costs: None,
+            broadcasts: None,
+        })
+    }
+
+    /// Config in effect
+    pub fn snapshot(&self) -> Arc<AppliedConfig> {
+        self.current.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn rejected(&self) -> Option<RejectedConfig> {
+        self.rejected.read().ok().and_then(|r| r.clone())
+    }
+
+    pub fn set_features(&mut self, features: FeatureFlags) {
+        features.set_defaults(self.snapshot().settings.features_disabled.clone());
+        self.features = Some(features);
+    }
+
+    pub fn set_costs(&mut self, costs: SpendTracker) {
+        costs.reconfigure(&self.snapshot().settings.sms_cost);
+        self.costs = Some(costs);
+    }
+
+    pub fn set_broadcasts(&mut self, broadcasts: BroadcastQueue) {
+        broadcasts.set_rate(self.snapshot().settings.broadcast_per_sec);
+        self.broadcasts = Some(broadcasts);
+    }
+
+    /// Re-read the file and apply it if it changed; true when applied
+    pub fn reload(&self) -> Result<bool, LiveConfigError> {
+        // Held throughout so concurrent reloads apply one at a time
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        let contents = match read(&self.path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(self.reject(String::new(), e)),
+        };
+        let checksum = checksum(&contents);
+        if checksum == current.checksum {
+            self.clear_rejected();
+            return Ok(false);
+        }
+
+        let parsed = LiveConfig::parse(&contents).and_then(|config| {
+            let settings = config.resolve(&self.defaults)?;
+            Ok((config, settings))
+        });
+        let (config, settings) = match parsed {
+            Ok(parsed) => parsed,
+            Err(e) => return Err(self.reject(checksum, e)),
+        };
+
+        self.apply(&settings);
+        let applied = AppliedConfig::new(checksum, config, settings);
+        tracing::info!(version = %applied.version, previous = %current.version, "Live config applied");
+        *current = Arc::new(applied);
+        self.clear_rejected();
+        Ok(true)
+    }
+
+    /// Check the file every `period`
+    pub fn start(self, period: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                // Failures are logged by reload and stay visible on /admin/config
+                let _ = self.reload();
+            }
+        });
+    }
+
+    fn apply(&self, settings: &Settings) {
+        set_rpc_urls(&settings.rpc_urls);
+        if let Some(ref features) = self.features {
+            features.set_defaults(settings.features_disabled.clone());
+        }
+        if let Some(ref costs) = self.costs {
+            costs.reconfigure(&settings.sms_cost);
+        }
+        if let Some(ref broadcasts) = self.broadcasts {
+            broadcasts.set_rate(settings.broadcast_per_sec);
+        }
+    }
+
+    /// Record a rejected file, logging only the first failure of each version
+    fn reject(&self, checksum: String, error: LiveConfigError) -> LiveConfigError {
+        if let Ok(mut rejected) = self.rejected.write() {
+            if rejected.as_ref().is_none_or(|r| r.checksum != checksum || checksum.is_empty()) {
+                tracing::warn!(path = %self.path.display(), "Live config rejected, keeping the last good one: {}", error);
+            }
+            *rejected = Some(RejectedConfig { checksum, error: error.to_string(), rejected_at: Utc::now() });
+        }
+        error
+    }
+
+    fn clear_rejected(&self) {
+        if let Ok(mut rejected) = self.rejected.write() {
+            *rejected = None;
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String, LiveConfigError> {
+    std::fs::read_to_string(path).map_err(|e| LiveConfigError::Read(path.display().to_string(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Defaults {
+        Defaults {
+            features_disabled: "swap".to_string(),
+            sms_cost: SmsCostConfig { daily_budget: 20.0, ..SmsCostConfig::default() },
+            broadcast_per_sec: 5.0,
+        }
+    }
+
+    #[test]
+    fn test_resolve_merges_over_environment() {
+        let config = LiveConfig::parse(
+            r#"{
+                "version": "2024-06-01",
+                "rpc_urls": { "amoy": "https://amoy.example.com/rpc" },
+                "sms_cost": { "daily_budget": 50, "rates": "254:0.12" }
+            }"#,
+        )
+        .unwrap();
+        let settings = config.resolve(&defaults()).unwrap();
+
+        assert_eq!(settings.rpc_urls[&Chain::PolygonAmoy], "https://amoy.example.com/rpc");
+        assert_eq!(settings.features_disabled, vec![Feature::Swap]);
+        assert_eq!(settings.sms_cost.daily_budget, 50.0);
+        assert_eq!(settings.sms_cost.rates, "254:0.12");
+        assert_eq!(settings.sms_cost.alert_percent, SmsCostConfig::default().alert_percent);
+        assert_eq!(settings.broadcast_per_sec, 5.0);
+
+        // An empty list switches every feature back on
+        let config = LiveConfig::parse(r#"{ "features_disabled": [] }"#).unwrap();
+        assert!(config.resolve(&defaults()).unwrap().features_disabled.is_empty());
+    }
+
+    #[test]
+    fn test_rejects_invalid_settings() {
+        let resolve = |json: &str| LiveConfig::parse(json).and_then(|c| c.resolve(&defaults()));
+
+        assert!(matches!(resolve(r#"{ "rpc_url": {} }"#), Err(LiveConfigError::Parse(_))));
+        assert!(matches!(resolve(r#"{ "sms_cost": { "budget": 1 } }"#), Err(LiveConfigError::Parse(_))));
+        assert!(matches!(
+            resolve(r#"{ "rpc_urls": { "solana": "https://x.example.com" } }"#),
+            Err(LiveConfigError::UnknownChain(name)) if name == "solana"
+        ));
+        assert!(matches!(
+            resolve(r#"{ "rpc_urls": { "base": "ws://x.example.com" } }"#),
+            Err(LiveConfigError::InvalidRpcUrl(..))
+        ));
+        assert!(matches!(resolve(r#"{ "features_disabled": ["nft"] }"#), Err(LiveConfigError::Feature(_))));
+        assert!(matches!(
+            resolve(r#"{ "sms_cost": { "rates": "1:0.01,uk:0.04" } }"#),
+            Err(LiveConfigError::Invalid("sms_cost.rates", entry)) if entry == "uk:0.04"
+        ));
+        assert!(matches!(
+            resolve(r#"{ "sms_cost": { "daily_budget": -1 } }"#),
+            Err(LiveConfigError::Invalid("sms_cost.daily_budget", _))
+        ));
+        assert!(matches!(
+            resolve(r#"{ "rate_limits": { "broadcast_per_sec": 0 } }"#),
+            Err(LiveConfigError::Invalid("rate_limits.broadcast_per_sec", _))
+        ));
+    }
+
+    #[test]
+    fn test_reload_swaps_only_valid_files() {
+        let path = std::env::temp_dir().join(format!("live-config-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{ "features_disabled": ["bridge"] }"#).unwrap();
+
+        let mut store = ConfigStore::open(&path, defaults()).unwrap();
+        let flags = FeatureFlags::default();
+        store.set_features(flags.clone());
+        assert!(!flags.is_enabled(Feature::Bridge));
+        let first = store.snapshot();
+        assert!(!store.reload().unwrap());
+
+        std::fs::write(&path, r#"{ "version": "v2", "features_disabled": ["nft"] }"#).unwrap();
+        assert!(store.reload().is_err());
+        assert_eq!(store.snapshot().checksum, first.checksum);
+        assert!(store.rejected().is_some());
+        assert!(!flags.is_enabled(Feature::Bridge));
+
+        std::fs::write(&path, r#"{ "version": "v3", "features_disabled": ["buy"] }"#).unwrap();
+        assert!(store.reload().unwrap());
+        assert_eq!(store.snapshot().version, "v3");
+        assert!(store.rejected().is_none());
+        assert!(flags.is_enabled(Feature::Bridge));
+        assert!(!flags.is_enabled(Feature::Buy));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+//! Local admin socket for `ttc-admin` (src/bin/ttc-admin.rs)
+//!
+//! Operational tasks that have no place on the public `/admin` API: flush
+//! caches, run reconciliation now, look at the job queues, re-send a
+//! message stuck in the outbox, and take, list or verify database backups. The server listens on a unix socket, either
+//! one systemd passes it (socket activation, `LISTEN_FDS`) or one it binds
+//! at `LOCAL_ADMIN_SOCKET` with mode 0600, so only the service user and root
+//! can connect. Each connection sends one command line and gets one line of
+//! JSON back: `{"ok": true, "result": ...}` or `{"ok": false, "error": ...}`.
+
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use uuid::Uuid;
+
+use crate::backups::Backups;
+use crate::config::LocalAdminConfig;
+use crate::db::{AuditLogRepository, SendJobRepository, SmsOutboxRepository};
+use crate::deposit_watcher::DepositWatcher;
+use crate::ens_verifier::EnsVerifier;
+use crate::features::FeatureFlags;
+use crate::name_policy::NamePolicy;
+use crate::sms::display::short_phone;
+use crate::sms::{MessagePriority, SmsGateway};
+use crate::wallet::ens_cache::EnsCache;
+use crate::wallet::token_registry::TokenRegistry;
+use crate::workers::WorkerPool;
+
+/// First descriptor systemd passes (`SD_LISTEN_FDS_START`)
+const LISTEN_FDS_START: i32 = 3;
+/// Longest command line read from a client
+const MAX_REQUEST_LEN: u64 = 1024;
+/// Outbox messages listed by `jobs`
+const OUTBOX_PREVIEW: i64 = 20;
+
+/// One command from `ttc-admin`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Ping,
+    FlushCaches,
+    Reconcile,
+    Jobs,
+    Resend(Uuid),
+    Backup,
+    Backups,
+    /// Backup name or `latest`
+    VerifyBackup(String),
+}
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["ping"] => Ok(Request::Ping),
+            ["flush-caches"] => Ok(Request::FlushCaches),
+            ["reconcile"] => Ok(Request::Reconcile),
+            ["jobs"] => Ok(Request::Jobs),
+            ["resend", id] => id.parse().map(Request::Resend).map_err(|_| format!("Not a message id: {}", id)),
+            ["resend"] => Err("Usage: resend <outbox message id>".to_string()),
+            ["backup"] => Ok(Request::Backup),
+            ["backups"] => Ok(Request::Backups),
+            ["verify-backup", name] => Ok(Request::VerifyBackup(name.to_string())),
+            ["verify-backup"] => Err("Usage: verify-backup <name|latest>".to_string()),
+            [] => Err("Empty command".to_string()),
+            [other, ..] => Err(format!(
+                "Unknown command: {} (ping, flush-caches, reconcile, jobs, resend <id>, backup, backups, verify-backup <name>)",
+                other
+            )),
+        }
+    }
+
+    /// Whether it changes anything, and so goes in the audit log
+    fn is_action(&self) -> bool {
+        !matches!(self, Request::Ping | Request::Jobs | Request::Backups)
+    }
+}
+
+/// What the socket can reach; everything past the caches and worker pool
+/// needs the database
+#[derive(Clone)]
+pub struct LocalAdmin {
+    ens_cache: Arc<EnsCache>,
+    workers: WorkerPool,
+    reloadable: Option<(FeatureFlags, TokenRegistry, NamePolicy)>,
+    sms: Option<(SmsGateway, SmsOutboxRepository)>,
+    send_jobs: Option<SendJobRepository>,
+    deposits: Option<DepositWatcher>,
+    ens_verifier: Option<EnsVerifier>,
+    audit: Option<AuditLogRepository>,
+    backups: Option<Backups>,
+}
+
+impl LocalAdmin {
+    pub fn new(ens_cache: Arc<EnsCache>, workers: WorkerPool) -> Self {
+        Self { ens_cache, workers, reloadable: None, sms: None, send_jobs: None, deposits: None, ens_verifier: None, audit: None, backups: None }
+    }
+
+    /// Re-read feature flags, token overrides and reserved names on flush-caches
+    pub fn set_reloadable(&mut self, features: FeatureFlags, tokens: TokenRegistry, name_policy: NamePolicy) {
+        self.reloadable = Some((features, tokens, name_policy));
+    }
+
+    /// Enable outbox listing and resend
+    pub fn set_sms(&mut self, gateway: SmsGateway, outbox: SmsOutboxRepository) {
+        self.sms = Some((gateway, outbox));
+    }
+
+    pub fn set_send_jobs(&mut self, send_jobs: SendJobRepository) {
+        self.send_jobs = Some(send_jobs);
+    }
+
+    /// Enable deposit confirmations on reconcile
+    pub fn set_deposits(&mut self, watcher: DepositWatcher) {
+        self.deposits = Some(watcher);
+    }
+
+    /// Enable an ENS verification batch on reconcile
+    pub fn set_ens_verifier(&mut self, verifier: EnsVerifier) {
+        self.ens_verifier = Some(verifier);
+    }
+
+    pub fn set_audit(&mut self, audit: AuditLogRepository) {
+        self.audit = Some(audit);
+    }
+
+    /// Enable backup, backups and verify-backup
+    pub fn set_backups(&mut self, backups: Backups) {
+        self.backups = Some(backups);
+    }
+
+    /// Serve on the socket systemd passed or the configured one; false when
+    /// there is neither
+    pub fn start(self, config: &LocalAdminConfig) -> std::io::Result<bool> {
+        let listener = match activated_listener()? {
+            Some(listener) => {
+                tracing::info!("Local admin socket passed by systemd");
+                listener
+            }
+            None if config.is_enabled() => {
+                let listener = bind(Path::new(config.socket.trim()))?;
+                tracing::info!(socket = %config.socket.trim(), "Local admin socket listening");
+                listener
+            }
+            None => return Ok(false),
+        };
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let admin = self.clone();
+                        tokio::spawn(async move { admin.serve(stream).await });
+                    }
+                    Err(e) => {
+                        tracing::warn!("Local admin accept failed: {}", e);
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        });
+        Ok(true)
+    }
+
+    /// Read one command, answer it and hang up
+    async fn serve(&self, stream: UnixStream) {
+        let uid = stream.peer_cred().map(|cred| cred.uid()).ok();
+        let (read, mut write) = stream.into_split();
+        let mut line = String::new();
+        let read = tokio::time::timeout(
+            Duration::from_secs(10),
+            BufReader::new(read.take(MAX_REQUEST_LEN)).read_line(&mut line),
+        )
+        .await;
+        if !matches!(read, Ok(Ok(n)) if n > 0) {
+            return;
+        }
+
+        let reply = match Request::parse(line.trim()) {
+            Ok(request) => {
+                tracing::info!(?uid, ?request, "Local admin command");
+                if request.is_action() {
+                    if let Some(ref audit) = self.audit {
+                        let detail = format!("uid={} {}", uid.map_or("?".to_string(), |uid| uid.to_string()), line.trim());
+                        if let Err(e) = audit.record_admin("LOCAL_ADMIN", &detail).await {
+                            tracing::warn!("Failed to audit local admin command: {}", e);
+                        }
+                    }
+                }
+                match self.handle(request).await {
+                    Ok(result) => json!({ "ok": true, "result": result }),
+                    Err(error) => json!({ "ok": false, "error": error }),
+                }
+            }
+            Err(error) => json!({ "ok": false, "error": error }),
+        };
+        let _ = write.write_all(format!("{}\n", reply).as_bytes()).await;
+        let _ = write.shutdown().await;
+    }
+
+    async fn handle(&self, request: Request) -> Result<Value, String> {
+        match request {
+            Request::Ping => Ok(json!("pong")),
+            Request::FlushCaches => self.flush_caches().await,
+            Request::Reconcile => Ok(self.reconcile().await),
+            Request::Jobs => self.jobs().await,
+            Request::Resend(id) => self.resend(id).await,
+            Request::Backup => {
+                let manifest = self.backups()?.run().await.map_err(|e| e.to_string())?;
+                Ok(json!(manifest))
+            }
+            Request::Backups => Ok(json!(self.backups()?.list().await.map_err(|e| e.to_string())?)),
+            Request::VerifyBackup(name) => {
+                let (manifest, entries) = self.backups()?.verify(&name).await.map_err(|e| e.to_string())?;
+                Ok(json!({ "verified": manifest, "archive_entries": entries }))
+            }
+        }
+    }
+
+    fn backups(&self) -> Result<&Backups, String> {
+        self.backups.as_ref().ok_or_else(|| "Backups are off (BACKUP_S3_BUCKET)".to_string())
+    }
+
+    async fn flush_caches(&self) -> Result<Value, String> {
+        let ens_entries = self.ens_cache.clear();
+        tracing::info!(removed = ens_entries, "ENS cache cleared from local admin");
+        let mut result = json!({ "ens_cache_cleared": ens_entries });
+        if let Some((ref features, ref tokens, ref name_policy)) = self.reloadable {
+            features.reload().await.map_err(|e| format!("Feature flag reload failed: {}", e))?;
+            tokens.reload().await.map_err(|e| format!("Token override reload failed: {}", e))?;
+            name_policy.reload().await.map_err(|e| format!("Reserved name reload failed: {}", e))?;
+            result["reloaded"] = json!(["feature_flags", "token_overrides", "reserved_names"]);
+        }
+        Ok(result)
+    }
+
+    /// Run the periodic checks now instead of waiting for their tick
+    async fn reconcile(&self) -> Value {
+        let deposits = match self.deposits {
+            Some(ref watcher) => match watcher.poll().await {
+                Ok(changed) => json!({ "updated": changed }),
+                Err(e) => json!({ "error": e.to_string() }),
+            },
+            None => json!("off"),
+        };
+        let ens_names = match self.ens_verifier {
+            Some(ref verifier) => match verifier.run().await {
+                Ok(drifted) => json!({ "drifted": drifted }),
+                Err(e) => json!({ "error": e.to_string() }),
+            },
+            None => json!("off"),
+        };
+        json!({ "deposit_confirmations": deposits, "ens_verification": ens_names })
+    }
+
+    async fn jobs(&self) -> Result<Value, String> {
+        let mut result = json!({ "workers": self.workers.metrics() });
+        if let Some(ref send_jobs) = self.send_jobs {
+            let summary = send_jobs.summary().await.map_err(|e| e.to_string())?;
+            result["send_queue"] = json!(summary);
+        }
+        if let Some((_, ref outbox)) = self.sms {
+            let (mut waiting, total) = outbox.oldest(OUTBOX_PREVIEW).await.map_err(|e| e.to_string())?;
+            for entry in &mut waiting {
+                entry.phone = short_phone(&entry.phone);
+            }
+            result["outbox"] = json!({ "total": total, "oldest": waiting });
+        }
+        Ok(result)
+    }
+
+    /// Send one outbox message now; it goes back in the outbox if that fails
+    async fn resend(&self, id: Uuid) -> Result<Value, String> {
+        let Some((ref gateway, ref outbox)) = self.sms else {
+            return Err("The outbox needs a database".to_string());
+        };
+        let message = outbox
+            .take(id)
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("No outbox message {}", id))?;
+        match gateway.send_sms_with_priority(&message.phone, &message.body, MessagePriority::Normal).await {
+            Ok(result) => {
+                tracing::info!(id = %id, sid = %result.message_sid, "Outbox message re-sent from local admin");
+                Ok(json!({ "sent": id, "sid": result.message_sid }))
+            }
+            Err(e) => {
+                if let Err(requeue) = outbox.enqueue(&message.phone, &message.body, chrono::Utc::now()).await {
+                    tracing::error!(id = %id, "Failed to put message back in the outbox: {}", requeue);
+                }
+                Err(format!("Not sent: {}", e))
+            }
+        }
+    }
+}
+
+/// The listening socket systemd passed this process, if any
+fn activated_listener() -> std::io::Result<Option<UnixListener>> {
+    let for_us = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id());
+    let fds = std::env::var("LISTEN_FDS").ok().and_then(|n| n.parse::<u32>().ok()).unwrap_or(0);
+    if !for_us || fds == 0 {
+        return Ok(None);
+    }
+    // SAFETY: with LISTEN_PID set to our pid, systemd passed descriptor 3 as
+    // an open listening socket, and nothing else in the process owns it
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(LISTEN_FDS_START) };
+    listener.set_nonblocking(true)?;
+    UnixListener::from_std(listener).map(Some)
+}
+
+/// Bind `path` for the service user only, replacing a socket left by an
+/// earlier run
+fn bind(path: &Path) -> std::io::Result<UnixListener> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty() && !dir.exists()) {
+        std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(Request::parse("ping"), Ok(Request::Ping));
+        assert_eq!(Request::parse("  jobs "), Ok(Request::Jobs));
+        assert_eq!(Request::parse("flush-caches"), Ok(Request::FlushCaches));
+        let id = Uuid::new_v4();
+        assert_eq!(Request::parse(&format!("resend {}", id)), Ok(Request::Resend(id)));
+        assert!(Request::parse("resend").is_err());
+        assert!(Request::parse("resend 42").is_err());
+        assert!(Request::parse("").is_err());
+        assert!(Request::parse("drop-tables").is_err());
+        assert_eq!(Request::parse("verify-backup latest"), Ok(Request::VerifyBackup("latest".to_string())));
+        assert!(Request::parse("verify-backup").is_err());
+        assert!(Request::Reconcile.is_action() && !Request::Jobs.is_action());
+        assert!(Request::Backup.is_action() && !Request::Backups.is_action());
+    }
+}
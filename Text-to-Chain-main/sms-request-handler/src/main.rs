@@ -1,34 +1,138 @@
 mod admin;
+mod admin_agents;
+mod admin_audit;
+mod admin_beta;
+mod admin_broadcast;
+mod admin_campaigns;
+mod admin_chains;
+mod admin_commands;
+mod admin_config;
+mod admin_ens;
+mod admin_features;
+mod admin_idempotency;
+mod admin_kill_switch;
+mod admin_kyc;
+mod admin_partner_keys;
+mod admin_referrals;
+mod admin_reserved_names;
+mod admin_spam;
+mod admin_statements;
+mod admin_tokens;
+mod admin_transcripts;
+mod admin_treasury;
 mod admin_wallet;
+mod alerts;
+mod backups;
+mod beta;
+mod broadcast;
 mod commands;
 mod config;
 mod db;
+mod deposit_sweeper;
+mod deposit_watcher;
+mod email;
+mod ens_verifier;
+mod events;
+mod features;
+mod gas_monitor;
+mod graphql;
+mod kill_switch;
+mod live_config;
+mod local_admin;
+mod maintenance;
+mod money;
+mod name_policy;
+mod notify_digest;
+mod partner_api;
+mod partner_webhooks;
+mod payment_links;
+mod payouts;
+mod rates;
+mod receipt_image;
+mod receipts;
+mod referrals;
+mod reporting;
 mod routes;
 mod sms;
+mod voice;
+mod voucher_cards;
 mod wallet;
+mod walletconnect_bridge;
+mod workers;
 mod yellow_client;
 
-use config::Config;
+use config::AppConfig;
 use commands::CommandProcessor;
-use db::{create_pool, run_migrations, UserRepository, VoucherRepository, DepositRepository, AddressBookRepository};
-use routes::{create_router, create_router_with_admin};
-use sms::TwilioClient;
-use wallet::create_shared_provider;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use commands::approvals::TransferPolicy;
+use commands::kyc::KycPolicy;
+use commands::metrics::CommandMetrics;
+use commands::repeat_send::RepeatSendGuard;
+use commands::send_minimums::SendMinimums;
+use commands::withdrawal_allowlist::WithdrawalAllowlist;
+use commands::send_queue::SendQueue;
+use db::{backfill_phone_prefixes, hash_phones, reencrypt_all, NameGiftRepository, NameListingRepository, NotificationRepository, AuditLogRepository, BalanceAlertRepository, BetaRepository, BillRepository, BroadcastRepository, CampaignRepository, ChainRepository, DbPools, EmailLinkRepository, EnsCheckRepository, IdempotencyRepository, KillSwitchRepository, KycRepository, run_migrations, FieldCipher, PhoneStorage, UserRepository, VoucherRepository, DepositAddressRepository, DepositRepository, AddressBookRepository, FlowRepository, OptOutRepository, PartnerKeyRepository, SmsSpendRepository, SmsOutboxRepository, StatementRepository, TransferApprovalRepository, AgentRepository, PaymentLinkRepository, PayoutRepository, PhoneCarrierRepository, ReferralRepository, ReservedNameRepository, RecentSendRepository, LedgerRepository, SavingsRepository, SendJobRepository, SpamSampleRepository, WalletConnectRepository, FeatureFlagRepository, TokenOverrideRepository, TranscriptRepository, UnrecognizedCommandRepository};
+use routes::{create_router, create_router_with_admin, OptionalRoutes};
+use sms::{CarrierLookup, CarrierOutages, Composer, Gsm7Mode, OptOutList, QuietHours, SimSwapGuard, SmsGateway, SmsRouter, SpamFilter, SpendTracker, TranscriptLog};
+use wallet::{create_chain_provider, create_shared_provider, Chain, DepositAddresses, DepositKeys, KeyVault, MultiChainProvider, SafeClient};
+use wallet::ens_cache::EnsCache;
+use wallet::faucet::Faucet;
+use wallet::payment_uri::TXTC_CHAIN;
+use wallet::savings::SavingsVault;
+use notify_digest::Notifications;
+use payouts::Payouts;
+use wallet::chain_registry::ChainRegistry;
+use wallet::token_registry::TokenRegistry;
+use admin::{AdminState, AdminTokens};
+use admin_beta::AdminBetaState;
+use admin_broadcast::AdminBroadcastState;
+use admin_idempotency::KEY_TTL_HOURS;
+use admin_spam::AdminSpamState;
+use admin_treasury::{AdminTreasuryState, WithdrawalThresholds};
+use beta::BetaAccess;
+use referrals::Referrals;
+use broadcast::BroadcastQueue;
+use deposit_sweeper::DepositSweeper;
+use deposit_watcher::{ConfirmationDepths, DepositIntake, DepositWatcher};
+use email::{EmailChannel, EmailClient};
+use ens_verifier::EnsVerifier;
+use local_admin::LocalAdmin;
+use events::{AdminEventsState, EventBus};
+use features::FeatureFlags;
+use kill_switch::KillSwitch;
+use name_policy::NamePolicy;
+use alerts::{BacklogWatch, OperatorAlerts};
+use backups::Backups;
+use gas_monitor::GasMonitor;
+use graphql::GraphqlState;
+use live_config::ConfigStore;
+use maintenance::{Maintenance, MigrateArgs};
+use partner_api::{PartnerApiState, RateLimiter};
+use partner_webhooks::PartnerWebhooks;
+use rates::{FxRates, TokenPrices};
+use receipts::ReceiptSigner;
+use voice::{VoiceChannel, VoiceIvr};
+use reporting::StatementState;
+use walletconnect_bridge::{WalletConnectBridge, WalletConnectState};
+use workers::WorkerPool;
+use std::sync::Arc;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Initialize tracing
+    // Initialize tracing; sqlx acquire events feed the DB metrics whatever RUST_LOG says
     tracing_subscriber::registry()
         .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "textchain=debug,tower_http=debug".into()),
+            tracing_subscriber::fmt::layer().with_filter(
+                tracing_subscriber::EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| "textchain=debug,tower_http=debug".into()),
+            ),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(db::metrics::AcquireLayer.with_filter(db::metrics::AcquireLayer::filter()))
         .init();
 
     // Load configuration
-    let config = Config::from_env()?;
+    let config = AppConfig::load()?;
     
     tracing::info!(
         host = %config.server.host,
@@ -36,52 +140,780 @@ async fn main() -> anyhow::Result<()> {
         "Starting TextChain SMS backend"
     );
 
-    // Get admin token from env (defaults to "admin123" for dev)
-    let admin_token = std::env::var("ADMIN_TOKEN").unwrap_or_else(|_| "admin123".to_string());
+    // Admin tokens: ADMIN_TOKEN (required with a database) plus ADMIN_TOKENS
+    let admin_tokens = AdminTokens::new(config.admin_token.expose(), config.admin_tokens.expose());
 
     // Initialize database (optional - will work without if DATABASE_URL not set)
-    let db_pool = if let Ok(database_url) = std::env::var("DATABASE_URL") {
+    let db_pools = if !config.database.url.is_empty() {
+        // One-off tool: `textchain --restore <name|latest> [--verify-only]`
+        // checks a backup and, unless verifying only, replaces the database
+        // with it and exits. Run it with the server stopped.
+        if let Some(at) = std::env::args().position(|arg| arg == "--restore") {
+            let Some(name) = std::env::args().nth(at + 1).filter(|name| !name.starts_with("--")) else {
+                anyhow::bail!("Usage: textchain --restore <name|latest> [--verify-only]");
+            };
+            let vault = KeyVault::from_config(&config.vault)?;
+            let backups = Backups::from_config(&config.backups, &vault, KeyVault::previous(&config.vault).as_ref(), config.database.url.expose())?
+                .ok_or_else(|| anyhow::anyhow!("--restore needs BACKUP_S3_BUCKET"))?;
+            if std::env::args().any(|arg| arg == "--verify-only") {
+                let (manifest, entries) = backups.verify(&name).await?;
+                tracing::info!(name = %manifest.name, created_at = %manifest.created_at, entries, "Backup is intact");
+            } else {
+                backups.restore(&name).await?;
+            }
+            return Ok(());
+        }
+
         tracing::info!("Connecting to database...");
-        let pool = create_pool(&database_url).await?;
+        let pools = DbPools::connect(config.database.url.expose(), &config.database).await?;
+        tracing::info!(
+            write = config.database.write_pool_size,
+            read = config.database.read_pool_size,
+            replica = !config.database.read_url.is_empty(),
+            "Database pools ready"
+        );
+        let pool = pools.write.clone();
         run_migrations(&pool).await?;
-        Some(pool)
+
+        // One-off tool: `textchain --migrate` brings the schema up to date and
+        // exits (e.g. on a scratch database for `cargo sqlx prepare`)
+        if std::env::args().any(|arg| arg == "--migrate") {
+            tracing::info!("Migrations applied");
+            return Ok(());
+        }
+
+        // One-off tool: `textchain --reencrypt-fields` rewrites encrypted columns
+        // with the current KEY_VAULT_MASTER_KEY and exits
+        if std::env::args().any(|arg| arg == "--reencrypt-fields") {
+            let cipher = FieldCipher::from_config(&config.vault)?;
+            if cipher.phone_storage() == PhoneStorage::Hashed && !cipher.is_keyed() {
+                anyhow::bail!("PHONE_STORAGE=hashed needs KEY_VAULT_MASTER_KEY");
+            }
+            let stats = reencrypt_all(&pool, &cipher).await?;
+            tracing::info!(
+                users = stats.users,
+                contacts = stats.contacts,
+                failed = stats.failed,
+                "Field re-encryption finished"
+            );
+            return Ok(());
+        }
+
+        // One-off tool: `textchain --hash-phones` converts stored phone numbers
+        // to blind indexes for PHONE_STORAGE=hashed and exits
+        if std::env::args().any(|arg| arg == "--hash-phones") {
+            let cipher = FieldCipher::from_config(&config.vault)?;
+            if cipher.phone_storage() != PhoneStorage::Hashed {
+                anyhow::bail!("--hash-phones needs PHONE_STORAGE=hashed");
+            }
+            if !cipher.is_keyed() {
+                anyhow::bail!("--hash-phones needs KEY_VAULT_MASTER_KEY");
+            }
+            let stats = hash_phones(&pool, &cipher).await?;
+            tracing::info!(
+                users = stats.users,
+                contacts = stats.contacts,
+                deposits = stats.deposits,
+                failed = stats.failed,
+                "Phone hashing finished"
+            );
+            return Ok(());
+        }
+
+        // One-off tool: `textchain --data-migrate [job ...] [--dry-run]` fixes
+        // rows written in older formats (see maintenance.rs) and exits
+        if let Some(at) = std::env::args().position(|arg| arg == "--data-migrate") {
+            let args = MigrateArgs::parse(std::env::args().skip(at + 1)).map_err(anyhow::Error::msg)?;
+            let maintenance = Maintenance::new(pool.clone(), FieldCipher::from_config(&config.vault)?);
+            for job in &args.jobs {
+                let report = maintenance.run(*job, &args).await?;
+                tracing::info!(
+                    job = %job,
+                    dry_run = args.dry_run,
+                    scanned = report.scanned,
+                    changed = report.changed,
+                    failed = report.failed,
+                    finished = report.finished,
+                    skipped = report.skipped,
+                    "Data migration run finished"
+                );
+            }
+            for state in maintenance.status().await? {
+                tracing::info!(
+                    job = %state.name,
+                    status = %state.status,
+                    scanned = state.scanned,
+                    changed = state.changed,
+                    failed = state.failed,
+                    "Data migration progress"
+                );
+            }
+            return Ok(());
+        }
+
+        // Phone prefixes for admin user search, for users created before it
+        match backfill_phone_prefixes(&pool, &FieldCipher::from_config(&config.vault)?).await {
+            Ok(0) => {}
+            Ok(updated) => tracing::info!(updated, "Backfilled phone prefixes for user search"),
+            Err(e) => tracing::warn!("Phone prefix backfill failed: {}", e),
+        }
+
+        Some(pools)
     } else {
         tracing::warn!("DATABASE_URL not set - running without database");
         None
     };
 
+    // Chains added through /admin/chains, registered before anything
+    // resolves chain names or builds providers
+    let chains = match db_pools {
+        Some(ref pools) => Some(ChainRegistry::load(ChainRepository::new(pools.write.clone())).await?),
+        None => None,
+    };
+
+    // Live config (optional - CONFIG_FILE): RPC endpoints, default-off features,
+    // SMS fees and the broadcast rate, applied again whenever the file changes.
+    // Loaded before the providers so they start on the configured endpoints,
+    // and after the chain registry so it can name registered chains
+    let mut live_config = ConfigStore::load(&config)?;
+    if let Some(ref store) = live_config {
+        tracing::info!(file = %config.live.file, version = %store.snapshot().version, "Live config loaded");
+    }
+
     // Initialize blockchain provider
     let provider = create_shared_provider();
     tracing::info!("Connected to Polygon Amoy testnet");
 
     // Initialize services
-    let twilio = TwilioClient::new(&config.twilio);
+    let router = SmsRouter::from_config(&config.twilio, &config.vonage, &config.sms_routing)?;
+    tracing::info!(?router, "SMS providers configured");
+    let mut twilio = SmsGateway::new(router);
+    let costs = SpendTracker::new(&config.sms_cost);
+    if let Some(ref mut store) = live_config {
+        store.set_costs(costs.clone());
+    }
+    twilio.set_costs(costs);
+    // Characters outside GSM-7 swapped or dropped before sending (SMS_GSM7, checked at load)
+    let gsm7 = Gsm7Mode::parse(&config.sms_cost.gsm7).unwrap_or(Gsm7Mode::Off);
+    if gsm7 != Gsm7Mode::Off {
+        tracing::info!(mode = ?gsm7, "Outbound SMS kept to GSM-7");
+    }
+    twilio.set_composer(Composer::new(gsm7));
+    let workers = WorkerPool::new(&config.workers);
+    // Critical events to Slack and PagerDuty (optional - ALERT_SLACK_WEBHOOKS,
+    // ALERT_PAGERDUTY_ROUTING_KEY), with job queue backlogs checked here
+    let mut backlog = None;
+    if let Some(operator_alerts) = OperatorAlerts::from_config(&config.alerts) {
+        tracing::info!(deployment = %config.alerts.deployment, destinations = ?operator_alerts.destinations(), "Operator alerts enabled");
+        operator_alerts.install();
+        backlog = BacklogWatch::from_config(&config.alerts, workers.clone());
+    }
+    // Forward/reverse ENS lookups, shared by SEND and the admin cache endpoints
+    let ens_cache = Arc::new(EnsCache::from_config(&config.ens_cache));
+    // Live activity for /admin/events, published whether or not anyone listens
+    let events = EventBus::default();
+    // ttc-admin over a unix socket (optional - LOCAL_ADMIN_SOCKET or systemd)
+    let mut local_admin = LocalAdmin::new(ens_cache.clone(), workers.clone());
+
+    // Inbound spam screening (optional - SPAM_FILTER), with samples kept for
+    // review at /admin/spam when there is a database
+    let spam_filter = SpamFilter::from_config(&config.spam);
+    if let Some(ref filter) = spam_filter {
+        tracing::info!(classifiers = ?filter.classifiers(), flag_score = config.spam.flag_score, drop_score = config.spam.drop_score, "Spam screening enabled");
+    }
 
     // Build router based on whether database is available
-    let app = if let Some(ref pool) = db_pool {
-        let user_repo = UserRepository::new(pool.clone());
+    let app = if let Some(ref pools) = db_pools {
+        let pool = &pools.write;
+        // STOP/START opt-outs, persisted so suppression survives restarts
+        twilio.set_opt_outs(OptOutList::load(OptOutRepository::new(pool.clone())).await?);
+        // Resume today's SMS spend so the daily budget survives restarts
+        let costs = SpendTracker::load(&config.sms_cost, SmsSpendRepository::new(pool.clone())).await?;
+        if let Some(ref mut store) = live_config {
+            store.set_costs(costs.clone());
+        }
+        twilio.set_costs(costs);
+
+        // Quiet hours (optional - QUIET_HOURS): non-urgent notifications wait in
+        // sms_outbox until the recipient's morning, flushed every minute
+        twilio.set_outbox(SmsOutboxRepository::new(pool.clone()));
+        if let Some(quiet_hours) = QuietHours::from_config(&config.quiet_hours)? {
+            twilio.set_quiet_hours(quiet_hours);
+            tracing::info!(hours = %config.quiet_hours.hours, "Quiet hours enabled");
+        }
+        // Carrier outages (SMS_OUTAGE_THRESHOLD, 0 = off): notifications to a
+        // failing calling code wait in sms_outbox until a probe gets through
+        if let Some(outages) = CarrierOutages::from_config(&config.sms_outages) {
+            twilio.set_outages(outages).await?;
+            tracing::info!(threshold = config.sms_outages.threshold, "Carrier outage detection enabled");
+        }
+
+        let cipher = FieldCipher::from_config(&config.vault)?;
+        // Without a master key the blind index is the number itself
+        if cipher.phone_storage() == PhoneStorage::Hashed {
+            if !cipher.is_keyed() {
+                anyhow::bail!("PHONE_STORAGE=hashed needs KEY_VAULT_MASTER_KEY");
+            }
+            tracing::info!("Phone numbers stored hashed; users are only messaged in reply");
+        }
+
+        // Support transcripts (optional - requires TRANSCRIPT_RETENTION_DAYS),
+        // with an hourly purge of messages past the retention period
+        let transcripts = if config.transcripts.is_enabled() {
+            let repo = TranscriptRepository::new(pool.clone(), cipher.clone());
+            twilio.set_transcripts(TranscriptLog::new(repo.clone()));
+            let purge_repo = repo.clone();
+            let retention_days = config.transcripts.retention_days;
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(std::time::Duration::from_secs(3600));
+                loop {
+                    ticker.tick().await;
+                    match purge_repo.purge_older_than(retention_days).await {
+                        Ok(0) => {}
+                        Ok(purged) => tracing::info!(purged, "Purged expired transcript messages"),
+                        Err(e) => tracing::warn!("Transcript purge failed: {}", e),
+                    }
+                }
+            });
+            tracing::info!(retention_days, "Support transcripts enabled");
+            Some(repo)
+        } else {
+            None
+        };
+        let spam = spam_filter.map(|mut filter| {
+            let samples = SpamSampleRepository::new(pool.clone(), cipher.clone());
+            filter.set_samples(samples.clone());
+            twilio.set_spam_filter(filter.clone());
+            AdminSpamState { filter, samples }
+        });
+        let user_repo = UserRepository::new(pool.clone(), cipher.clone());
         let voucher_repo = VoucherRepository::new(pool.clone());
-        let deposit_repo = DepositRepository::new(pool.clone());
-        let address_book_repo = AddressBookRepository::new(pool.clone());
+        let deposit_repo = DepositRepository::new(pool.clone(), cipher.clone());
+
+        // Per-user HD deposit addresses (optional - DEPOSIT_MNEMONIC), credited
+        // like any deposit and swept into the treasury
+        let deposit_addresses = if config.deposit_addresses.is_enabled() {
+            let keys = DepositKeys::from_phrase(&config.deposit_addresses.mnemonic)?;
+            Some(DepositAddresses::new(keys, DepositAddressRepository::new(pool.clone())))
+        } else {
+            None
+        };
+
+        // NOTIFY modes for deposit and receipt texts, and the daily digest
+        // for users on NOTIFY DIGEST (NOTIFY_DIGEST_HOUR, UTC)
+        let notifications = Notifications::new(
+            &config.notify_digest,
+            NotificationRepository::new(pool.clone(), cipher.clone()),
+            user_repo.clone(),
+            twilio.clone(),
+        );
+        notifications.start();
 
-        let command_processor = CommandProcessor::with_repos(
+        // On-chain deposits (optional - INTERNAL_SECRET): reported by the deposit
+        // monitor, pending until DEPOSIT_CONFIRMATIONS deep and reversed on reorg
+        let deposits = if config.deposits.is_enabled() {
+            let depths = ConfirmationDepths::from_config(&config.deposits)?;
+            let mut watcher = DepositWatcher::new(deposit_repo.clone(), MultiChainProvider::new(), twilio.clone(), depths, events.clone());
+            watcher.set_alerts(BalanceAlertRepository::new(pool.clone(), cipher.clone()));
+            watcher.set_notifications(notifications.clone());
+            let poll_watcher = watcher.clone();
+            local_admin.set_deposits(watcher.clone());
+            let period = std::time::Duration::from_secs(config.deposits.poll_secs.max(1));
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(period);
+                loop {
+                    ticker.tick().await;
+                    match poll_watcher.poll().await {
+                        Ok(0) => {}
+                        Ok(changed) => tracing::info!(changed, "Updated deposit confirmations"),
+                        Err(e) => tracing::warn!("Deposit confirmation check failed: {}", e),
+                    }
+                }
+            });
+            tracing::info!("Deposit confirmations enabled at /internal/record-deposit");
+            Some(DepositIntake {
+                watcher,
+                internal_secret: config.deposits.internal_secret.to_string(),
+                addresses: deposit_addresses.as_ref().map(|addresses| addresses.repo().clone()),
+            })
+        } else {
+            None
+        };
+        let address_book_repo = AddressBookRepository::new(pool.clone(), cipher.clone());
+
+        let mut command_processor = CommandProcessor::with_repos(
             Some(user_repo),
             Some(voucher_repo.clone()),
             Some(deposit_repo),
             Some(address_book_repo),
+            KeyVault::from_config(&config.vault)?,
             provider,
         );
+        command_processor.set_services(&config.services);
+        command_processor.set_flow_repo(FlowRepository::new(pool.clone()));
+        command_processor.set_agent_repos(AgentRepository::new(pool.clone()), LedgerRepository::new(pool.clone()));
+        command_processor.set_notifier(twilio.clone());
+        command_processor.set_alerts(BalanceAlertRepository::new(pool.clone(), cipher.clone()));
+        if let Some(ref addresses) = deposit_addresses {
+            command_processor.set_deposit_addresses(addresses.clone());
+        }
+        // Hash-chained audit trail of state-changing commands and admin actions
+        let audit = AuditLogRepository::new(pool.clone(), cipher.clone());
+        command_processor.set_audit_log(audit.clone());
+        local_admin.set_audit(audit.clone());
+        command_processor.set_payment_links(PaymentLinkRepository::new(pool.clone()), &config.server.public_base_url);
+        command_processor.set_statements(StatementRepository::new(pool.clone()), config.server.statement_link_minutes);
+        command_processor.set_bills(BillRepository::new(pool.clone()));
+
+        // Beta launch mode (optional - BETA_MODE): allowlist + waitlist, with
+        // waiting numbers let in as seats free up when BETA_CAPACITY is set
+        let beta = BetaAccess::from_config(&config.beta, BetaRepository::new(pool.clone()));
+        if let Some(ref beta) = beta {
+            command_processor.set_beta(beta.clone());
+            if beta.capacity() > 0 {
+                let release_beta = beta.clone();
+                let release_twilio = twilio.clone();
+                let period = std::time::Duration::from_secs(config.beta.release_secs.max(1));
+                tokio::spawn(async move {
+                    let mut ticker = tokio::time::interval(period);
+                    loop {
+                        ticker.tick().await;
+                        match release_beta.release(&release_twilio).await {
+                            Ok(0) => {}
+                            Ok(admitted) => tracing::info!(admitted, "Admitted numbers from the beta waitlist"),
+                            Err(e) => tracing::warn!("Beta waitlist release failed: {}", e),
+                        }
+                    }
+                });
+            }
+            tracing::info!(capacity = beta.capacity(), "Beta mode enabled");
+        }
+        let beta = beta.map(|beta| AdminBetaState { beta, twilio: twilio.clone() });
+
+        // Referral bonuses (optional - REFERRAL_BONUS / REFERRAL_NEW_USER_BONUS),
+        // reported at /admin/referrals
+        let referrals = Referrals::from_config(&config.referrals, ReferralRepository::new(pool.clone(), cipher.clone()));
+        if let Some(ref referrals) = referrals {
+            tracing::info!(referrer_bonus = %referrals.referrer_bonus(), new_user_bonus = %referrals.new_user_bonus(), "Referral bonuses enabled");
+            command_processor.set_referrals(referrals.clone());
+        }
+        let referrals = referrals.map(|referrals| referrals.repo().clone());
+
+        // Email commands (optional - requires SMTP_HOST, EMAIL_FROM_ADDRESS and
+        // EMAIL_INBOUND_TOKEN): verified addresses act for their linked phone
+        let email = if config.email.is_enabled() {
+            let channel = EmailChannel {
+                links: EmailLinkRepository::new(pool.clone(), cipher.clone()),
+                client: EmailClient::new(&config.email)?,
+                inbound_token: config.email.inbound_token.to_string(),
+            };
+            command_processor.set_email(channel.clone());
+            tracing::info!(smtp = %config.email.smtp_host, "Email commands enabled at /email/inbound");
+            Some(channel)
+        } else {
+            None
+        };
+
+        // Voice menu (optional - VOICE_IVR): balance and history read out to callers
+        let voice = config.voice.enabled.then(|| {
+            tracing::info!(language = %config.voice.language, "Voice menu enabled at /voice/incoming");
+            VoiceChannel {
+                ivr: VoiceIvr::new(&config.voice, &config.server.public_base_url, config.twilio.auth_token.expose()),
+                users: UserRepository::new(pool.clone(), cipher.clone()),
+            }
+        });
+
+        // Signed receipts (optional - RECEIPT_SIGNING_KEY): RECEIPT <ref> proofs
+        // checked at /receipts/verify
+        let receipts = ReceiptSigner::from_config(&config.receipts)
+            .map_err(|e| anyhow::anyhow!("Invalid RECEIPT_SIGNING_KEY: {}", e))?;
+        if let Some(ref signer) = receipts {
+            command_processor.set_receipts(signer.clone());
+            tracing::info!(signer = ?signer.address(), "Signed receipts enabled");
+        }
+
+        // Testnet faucet (optional - FAUCET_CHAINS): gas for every new wallet
+        if let Some(faucet) = Faucet::from_config(&config.faucet)? {
+            tracing::info!(chains = ?faucet.chains(), "Testnet faucet enabled");
+            command_processor.set_faucet(Arc::new(faucet));
+        }
+
+        // Feature flags: FEATURES_DISABLED defaults plus feature_flags overrides,
+        // re-read periodically so direct DB edits apply without a restart
+        let features = FeatureFlags::load(&config.features, FeatureFlagRepository::new(pool.clone())).await?;
+        if let Some(ref mut store) = live_config {
+            store.set_features(features.clone());
+        }
+        if config.features.reload_secs > 0 {
+            let features = features.clone();
+            let period = std::time::Duration::from_secs(config.features.reload_secs);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(period);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    if let Err(e) = features.reload().await {
+                        tracing::warn!("Feature flag reload failed: {}", e);
+                    }
+                }
+            });
+        }
+        tracing::info!(?features, "Feature flags loaded");
+
+        // Token contract addresses: built-ins, TOKEN_ADDRESSES, then
+        // token_overrides rows, re-read periodically like feature flags
+        let tokens = TokenRegistry::load(&config.tokens, TokenOverrideRepository::new(pool.clone())).await?;
+        if config.tokens.reload_secs > 0 {
+            let tokens = tokens.clone();
+            let period = std::time::Duration::from_secs(config.tokens.reload_secs);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(period);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    if let Err(e) = tokens.reload().await {
+                        tracing::warn!("Token override reload failed: {}", e);
+                    }
+                }
+            });
+        }
+        tracing::info!(?tokens, "Token addresses loaded");
+        command_processor.set_features(features.clone());
+        command_processor.set_events(events.clone());
+
+        // Kill switch: read from kill_switch on every money-moving command, so
+        // POST /admin/kill-switch on any instance stops the whole fleet
+        let kill_switch = KillSwitch::from_config(&config.kill_switch, Some(KillSwitchRepository::new(pool.clone())));
+        if config.kill_switch.engaged {
+            tracing::warn!("KILL_SWITCH is set: money-moving commands are stopped");
+        }
+        command_processor.set_kill_switch(kill_switch);
+
+        // Carriers for per-market command metrics (optional - CARRIER_LOOKUP)
+        let carrier_lookup = CarrierLookup::load(&config.carrier_lookup, &config.twilio, PhoneCarrierRepository::new(pool.clone())).await?;
+        if let Some(ref lookup) = carrier_lookup {
+            tracing::info!(cached = lookup.cached(), "Carrier lookups enabled for /metrics/commands");
+        }
+        command_processor.set_metrics(CommandMetrics::new(carrier_lookup));
+        // SIM-swap checks before sensitive commands (optional - SIM_SWAP_PROVIDER)
+        if let Some(guard) = SimSwapGuard::from_config(&config.sim_swap, &config.twilio)? {
+            tracing::info!(source = guard.source(), window_hours = config.sim_swap.window_hours, "SIM swap checks enabled");
+            command_processor.set_sim_swap(guard);
+        }
+        command_processor.set_campaigns(CampaignRepository::new(pool.clone()));
+        command_processor.set_name_gifts(NameGiftRepository::new(pool.clone()));
+        command_processor.set_name_market(NameListingRepository::new(pool.clone()));
+        command_processor.set_unrecognized_log(UnrecognizedCommandRepository::new(pool.clone()));
+        // Reserved subdomain labels, managed at /admin/reserved-names
+        let name_policy = NamePolicy::load(&config.ens_names, ReservedNameRepository::new(pool.clone())).await?;
+        command_processor.set_name_policy(name_policy.clone());
+        local_admin.set_reloadable(features.clone(), tokens.clone(), name_policy.clone());
+
+        // Large SEND approvals (optional - TRANSFER_APPROVAL_THRESHOLDS)
+        if let Some(policy) = TransferPolicy::from_config(&config.transfer_approval, TransferApprovalRepository::new(pool.clone()))? {
+            tracing::info!(tokens = ?policy.tokens(), "Large transfer approvals enabled");
+            command_processor.set_transfer_policy(policy);
+        }
+        // Dust protection for on-chain transfers (optional - SEND_MINIMUMS)
+        if let Some(minimums) = SendMinimums::from_config(&config.send_minimums)? {
+            tracing::info!(chains = ?minimums.chains(), "Send minimums enabled");
+            command_processor.set_send_minimums(minimums);
+        }
+        // On-chain SENDs only to saved addresses (optional - WITHDRAWAL_ALLOWLIST)
+        if let Some(allowlist) =
+            WithdrawalAllowlist::from_config(&config.withdrawals, AddressBookRepository::new(pool.clone(), cipher.clone()))
+        {
+            tracing::info!(cooldown_hours = allowlist.cooldown_hours(), "Withdrawals limited to saved addresses");
+            command_processor.set_withdrawal_allowlist(allowlist);
+        }
+        // Cash SENDs to outside addresses (optional - CASH_PAYOUT_CHAIN), refunded if they fail on chain
+        if let Some(payouts) = Payouts::from_config(
+            &config.payouts,
+            &config.admin_private_key,
+            PayoutRepository::new(pool.clone()),
+            UserRepository::new(pool.clone(), cipher.clone()),
+            twilio.clone(),
+            events.clone(),
+        )? {
+            tracing::info!(chain = %payouts.chain(), "Cash payouts enabled");
+            payouts.start(config.payouts.poll_secs);
+            command_processor.set_payouts(payouts);
+        }
+        // Encrypted database backups (optional - BACKUP_S3_BUCKET)
+        let vault = KeyVault::from_config(&config.vault)?;
+        if let Some(backups) =
+            Backups::from_config(&config.backups, &vault, KeyVault::previous(&config.vault).as_ref(), config.database.url.expose())?
+        {
+            tracing::info!(
+                bucket = %config.backups.bucket,
+                interval_hours = config.backups.interval_hours,
+                keep = config.backups.keep,
+                "Database backups enabled"
+            );
+            local_admin.set_backups(backups.clone());
+            backups.start(pool.clone(), config.backups.interval_hours);
+        }
+        command_processor.set_notifications(notifications);
+        // USD values next to balances (optional - TOKEN_PRICES_URL)
+        if let Some(prices) = TokenPrices::from_config(&config.rates) {
+            tracing::info!(ttl_secs = config.rates.price_ttl_secs, "Token prices enabled for balances");
+            command_processor.set_prices(prices);
+        }
+        // Cancellation window for SENDs (SEND_CANCEL_SECS, 0 = send immediately)
+        local_admin.set_send_jobs(SendJobRepository::new(pool.clone()));
+        if let Some(ref mut backlog) = backlog {
+            backlog.set_send_jobs(SendJobRepository::new(pool.clone()));
+        }
+        if let Some(queue) = SendQueue::from_config(&config.send_queue, SendJobRepository::new(pool.clone())) {
+            tracing::info!(window_secs = queue.window_secs(), "SENDs wait for CANCEL SEND before going out");
+            command_processor.set_send_queue(queue);
+        }
+        // Identical SENDs within REPEAT_SEND_MINUTES ask for AGAIN (0 = off)
+        if let Some(guard) = RepeatSendGuard::from_config(&config.repeat_send, RecentSendRepository::new(pool.clone())) {
+            tracing::info!(window_minutes = guard.window_minutes(), "Repeated SENDs ask for AGAIN");
+            command_processor.set_repeat_sends(guard);
+        }
+        // Onboarding tiers with per-tier send limits (optional - KYC_TIERS)
+        if let Some(policy) = KycPolicy::from_config(&config.kyc, KycRepository::new(pool.clone()))? {
+            tracing::info!("KYC tiers enabled; tier changes at /admin/kyc");
+            command_processor.set_kyc_policy(policy);
+        }
+        // Stored ENS names re-resolved in the background (ENS_VERIFY_INTERVAL_SECS,
+        // 0 = off), with drifted .ttcip.eth names re-pointed by the backend
+        let ens_verifier =
+            EnsVerifier::from_config(&config.ens_verify, EnsCheckRepository::new(pool.clone()), ens_cache.clone(), &config.services.backend_url);
+        if let Some(ref verifier) = ens_verifier {
+            tracing::info!(batch = config.ens_verify.batch_size, repair = verifier.repairs(), "ENS verification enabled at /metrics/ens");
+            verifier.start();
+            local_admin.set_ens_verifier(verifier.clone());
+        }
+        command_processor.set_ens_cache(ens_cache);
+
+        // USDC savings (optional - SAVINGS_VAULT_ADDRESS): SAVE / UNSAVE into
+        // an ERC-4626 vault on the TXTC chain
+        if config.savings.is_enabled() {
+            let vault = SavingsVault {
+                chain: TXTC_CHAIN,
+                address: config.savings.vault_address.parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid SAVINGS_VAULT_ADDRESS"))?,
+            };
+            command_processor.set_savings(SavingsRepository::new(pool.clone()), vault);
+            tracing::info!(vault = %config.savings.vault_address, chain = %TXTC_CHAIN, "Savings enabled");
+        }
+
+        // WalletConnect (optional - requires WALLETCONNECT_BRIDGE_URL and WALLETCONNECT_BRIDGE_TOKEN)
+        let walletconnect = if config.walletconnect.is_enabled() {
+            let bridge = WalletConnectBridge::new(&config.walletconnect);
+            let wc_repo = WalletConnectRepository::new(pool.clone());
+            command_processor.set_walletconnect(wc_repo.clone(), bridge.clone());
+            tracing::info!(bridge = %config.walletconnect.bridge_url, "WalletConnect enabled");
+            Some(WalletConnectState {
+                repo: wc_repo,
+                bridge,
+                twilio: twilio.clone(),
+                bridge_token: config.walletconnect.bridge_token.to_string(),
+                users: UserRepository::new(pool.clone(), cipher.clone()),
+            })
+        } else {
+            None
+        };
+
+        // Treasury multisig (optional - requires SAFE_ADDRESS and ADMIN_PRIVATE_KEY)
+        let treasury = if config.safe.is_enabled() {
+            let chain = Chain::from_input(&config.safe.chain).unwrap_or(Chain::EthereumSepolia);
+            let safe_address = config.safe.safe_address.parse()
+                .map_err(|_| anyhow::anyhow!("Invalid SAFE_ADDRESS"))?;
+            let safe = SafeClient::new(
+                &config.safe.tx_service_url,
+                safe_address,
+                chain,
+                create_chain_provider(chain),
+                &config.admin_private_key,
+            )?;
+            tracing::info!(safe = %config.safe.safe_address, chain = %chain, "Treasury Safe enabled");
+            Some(AdminTreasuryState {
+                safe: Arc::new(safe),
+                hot_wallet_key: config.admin_private_key.to_string(),
+                large_withdrawal_thresholds: WithdrawalThresholds::parse(&config.safe.large_withdrawal_thresholds)?,
+            })
+        } else {
+            None
+        };
+
+        // Partner GraphQL API (optional - requires GRAPHQL_API_KEYS)
+        let graphql = if config.graphql.is_enabled() {
+            let api_keys = graphql::parse_api_keys(&config.graphql.api_keys);
+            tracing::info!(partners = api_keys.len(), "GraphQL API enabled at /graphql");
+            Some(GraphqlState::new(pools.read.clone(), cipher.clone(), api_keys))
+        } else {
+            None
+        };
+
+        // Admin broadcasts go through one rate-limited queue; any cut off by
+        // the last restart are marked interrupted
+        let broadcast_repo = BroadcastRepository::new(pool.clone(), cipher.clone());
+        match broadcast_repo.mark_interrupted().await {
+            Ok(0) => {}
+            Ok(interrupted) => tracing::warn!(interrupted, "Marked unfinished broadcasts as interrupted"),
+            Err(e) => tracing::warn!("Failed to check unfinished broadcasts: {}", e),
+        }
+        let queue = BroadcastQueue::start(broadcast_repo.clone(), twilio.clone(), config.broadcast.rate_per_sec);
+        if let Some(ref mut store) = live_config {
+            store.set_broadcasts(queue.clone());
+        }
+        let broadcasts = AdminBroadcastState {
+            queue,
+            repo: broadcast_repo,
+            twilio: twilio.clone(),
+        };
+
+        // Probe down carriers, then send deferred notifications whose quiet
+        // hours or outage are over (no-op when off)
+        let outbox_twilio = twilio.clone();
+        local_admin.set_sms(twilio.clone(), SmsOutboxRepository::new(pool.clone()));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(60));
+            loop {
+                ticker.tick().await;
+                match outbox_twilio.probe_outages().await {
+                    Ok(recovered) if !recovered.is_empty() => tracing::info!(?recovered, "SMS carriers back up"),
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("Carrier outage probe failed: {}", e),
+                }
+                match outbox_twilio.flush_outbox().await {
+                    Ok(0) => {}
+                    Ok(sent) => tracing::info!(sent, "Sent deferred notifications"),
+                    Err(e) => tracing::warn!("Outbox flush failed: {}", e),
+                }
+            }
+        });
+
+        // Forget admin idempotency keys after a day
+        let idempotency = IdempotencyRepository::new(pool.clone());
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(std::time::Duration::from_secs(3600));
+            loop {
+                ticker.tick().await;
+                match idempotency.purge_older_than(KEY_TTL_HOURS).await {
+                    Ok(0) => {}
+                    Ok(purged) => tracing::info!(purged, "Purged expired idempotency keys"),
+                    Err(e) => tracing::warn!("Idempotency key purge failed: {}", e),
+                }
+            }
+        });
+
+        // Partner deposit and voucher API, authenticated per key
+        let partners = PartnerApiState {
+            keys: PartnerKeyRepository::new(pool.clone(), cipher.clone()),
+            users: UserRepository::new(pool.clone(), cipher.clone()),
+            vouchers: VoucherRepository::new(pool.clone()),
+            twilio: twilio.clone(),
+            alerts: BalanceAlertRepository::new(pool.clone(), cipher.clone()),
+            limiter: RateLimiter::default(),
+            webhooks: PartnerWebhooks::default(),
+        };
+
+        // Monthly statements for admins and STATEMENT download links
+        let statements = StatementState {
+            statements: StatementRepository::new(pool.clone()),
+            users: UserRepository::new(pool.clone(), cipher.clone()),
+        };
+
+        // Signer gas balances (optional - GAS_TANK_THRESHOLDS), alerting operators when low
+        let gas = GasMonitor::from_config(&config.gas, &config.admin_private_key, &config.faucet, twilio.clone())?;
+        if let Some(ref gas) = gas {
+            let (wallets, chains) = gas.describe();
+            tracing::info!(wallets, ?chains, "Gas tank monitor enabled at /metrics/gas-tanks");
+            gas.start();
+        }
+
+        // Deposit address sweeps into the treasury (optional - DEPOSIT_MNEMONIC)
+        let sweeper = match deposit_addresses {
+            Some(addresses) => {
+                let sweeper = DepositSweeper::from_config(&config.deposit_addresses, addresses, &config.admin_private_key)?;
+                let timed = sweeper.clone();
+                let period = std::time::Duration::from_secs(config.deposit_addresses.sweep_secs.max(60));
+                tokio::spawn(async move {
+                    let mut ticker = tokio::time::interval(period);
+                    loop {
+                        ticker.tick().await;
+                        match timed.run().await {
+                            Ok(report) if report.swept.is_empty() && report.failed == 0 => {}
+                            Ok(report) => tracing::info!(swept = report.swept.len(), failed = report.failed, "Swept deposit addresses"),
+                            Err(e) => tracing::warn!("Deposit sweep failed: {}", e),
+                        }
+                    }
+                });
+                tracing::info!(treasury = ?sweeper.treasury(), "Deposit addresses enabled; sweeps at /admin/deposit-sweeps");
+                Some(sweeper)
+            }
+            None => None,
+        };
 
         tracing::info!("Admin routes enabled at /admin/*");
-        create_router_with_admin(twilio, command_processor, voucher_repo, admin_token, pool.clone())
+        // Admin transcript lookups read from the read pool; recording stays on write
+        let transcripts = transcripts.map(|_| TranscriptRepository::new(pools.read.clone(), cipher.clone()));
+        // Admin event stream (optional - ADMIN_EVENTS_TOKEN)
+        let events = config.events.is_enabled().then(|| {
+            tracing::info!("Admin event stream enabled at /admin/events");
+            AdminEventsState { bus: events, token: config.events.token.to_string() }
+        });
+
+        // Admin tier changes, only when tiers are enforced
+        let kyc = config.kyc.is_enabled().then(|| KycRepository::new(pool.clone()));
+
+        // Every live config target is attached by now
+        if let Some(ref store) = live_config {
+            if config.live.reload_secs > 0 {
+                store.clone().start(std::time::Duration::from_secs(config.live.reload_secs));
+            }
+            tracing::info!("Live config admin routes enabled at /admin/config");
+        }
+
+        let optional = OptionalRoutes { treasury, graphql, walletconnect, transcripts, beta, email, voice, receipts, deposits, tokens: Some(tokens), chains, broadcasts: Some(broadcasts), events, partners: Some(partners), gas, reserved_names: Some(name_policy), sweeper, config: live_config, kyc, statements: Some(statements), ens_verifier, referrals, spam };
+        let admin_state = AdminState {
+            voucher_repo: Arc::new(voucher_repo),
+            admin_tokens,
+            rates: FxRates::from_config(&config.rates)?,
+            sms_number: config.twilio.phone_number.clone(),
+            cipher: cipher.clone(),
+        };
+        // Queued SENDs go out once their window closes
+        command_processor.start_send_queue();
+
+        create_router_with_admin(twilio, command_processor, workers, admin_state, pools.clone(), audit, optional)
     } else {
-        let command_processor = CommandProcessor::new(
-            None, 
+        // Without a database no private key is ever stored
+        let mut command_processor = CommandProcessor::new(
+            None,
+            KeyVault::unsealed(),
             provider,
         );
-        create_router(twilio, command_processor)
+        command_processor.set_services(&config.services);
+        let features = FeatureFlags::from_config(&config.features)?;
+        if let Some(mut store) = live_config {
+            store.set_features(features.clone());
+            if config.live.reload_secs > 0 {
+                store.start(std::time::Duration::from_secs(config.live.reload_secs));
+            }
+        }
+        command_processor.set_features(features);
+        command_processor.set_kill_switch(KillSwitch::from_config(&config.kill_switch, None));
+        command_processor.set_ens_cache(ens_cache);
+        command_processor.set_name_policy(NamePolicy::from_config(&config.ens_names)?);
+        // TOKEN_ADDRESSES still applies without a database
+        TokenRegistry::from_config(&config.tokens)?;
+        if let Some(filter) = spam_filter {
+            twilio.set_spam_filter(filter);
+        }
+        create_router(twilio, command_processor, workers)
     };
 
+    local_admin.start(&config.local_admin)?;
+    if let Some(backlog) = backlog {
+        backlog.start();
+    }
+
     // Start server
     let listener = tokio::net::TcpListener::bind(config.bind_addr()).await?;
     
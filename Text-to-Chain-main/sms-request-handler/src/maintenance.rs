@@ -0,0 +1,444 @@
+//! One-off fixes to rows written in older formats
+//!
+//! `textchain --data-migrate` runs each job over its table in batches
+//! ordered by id. Progress is kept in `data_migrations` (see
+//! `db::DataMigrationRepository`) after every batch, so a job that is
+//! stopped carries on from its cursor next time, and a completed job is
+//! skipped. `--dry-run` reports what would change without writing anything.
+//! Every real run is written to the audit log.
+
+use std::fmt;
+
+use ethers::types::Address;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::{decode_error, AuditLogRepository, DataMigration, DataMigrationRepository, FieldCipher};
+use crate::wallet::address::checksummed;
+use crate::wallet::KeyVault;
+
+/// Rows read per batch unless `--batch-size` says otherwise
+pub const DEFAULT_BATCH_SIZE: i64 = 500;
+
+/// The jobs, in the order `--data-migrate` runs them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    /// Users' numbers to E.164, with everything keyed by them
+    NormalizePhones,
+    /// Voucher codes to upper case
+    UppercaseVouchers,
+    /// Users' wallet addresses to their EIP-55 checksummed form
+    ChecksumWallets,
+}
+
+impl Job {
+    pub const ALL: [Job; 3] = [Job::NormalizePhones, Job::UppercaseVouchers, Job::ChecksumWallets];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Job::NormalizePhones => "normalize_phones",
+            Job::UppercaseVouchers => "uppercase_vouchers",
+            Job::ChecksumWallets => "checksum_wallets",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|job| job.name() == name)
+    }
+}
+
+impl fmt::Display for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// `--data-migrate [job ...] [--dry-run] [--restart] [--batch-size N] [--max-batches N]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrateArgs {
+    /// Every job when none are named
+    pub jobs: Vec<Job>,
+    pub dry_run: bool,
+    /// Start over, even when the job completed before
+    pub restart: bool,
+    pub batch_size: i64,
+    /// Stop after this many batches; the next run resumes
+    pub max_batches: Option<u64>,
+}
+
+impl MigrateArgs {
+    /// Read the arguments after `--data-migrate`
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Self { jobs: Vec::new(), dry_run: false, restart: false, batch_size: DEFAULT_BATCH_SIZE, max_batches: None };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--dry-run" => parsed.dry_run = true,
+                "--restart" => parsed.restart = true,
+                "--batch-size" => {
+                    parsed.batch_size = match args.next().and_then(|n| n.parse().ok()) {
+                        Some(size) if size > 0 => size,
+                        _ => return Err("--batch-size needs a positive number".to_string()),
+                    };
+                }
+                "--max-batches" => {
+                    parsed.max_batches = match args.next().and_then(|n| n.parse().ok()) {
+                        Some(batches) if batches > 0 => Some(batches),
+                        _ => return Err("--max-batches needs a positive number".to_string()),
+                    };
+                }
+                name => match Job::parse(name) {
+                    Some(job) if !parsed.jobs.contains(&job) => parsed.jobs.push(job),
+                    Some(_) => {}
+                    None => {
+                        let known: Vec<&str> = Job::ALL.iter().map(|job| job.name()).collect();
+                        return Err(format!("Unknown data migration {} (known: {})", name, known.join(", ")));
+                    }
+                },
+            }
+        }
+        if parsed.jobs.is_empty() {
+            parsed.jobs = Job::ALL.to_vec();
+        }
+        Ok(parsed)
+    }
+}
+
+/// A number in E.164 form: separators dropped and a leading 00 read as +.
+/// None for anything without a country code, which can't be fixed safely.
+pub fn normalize_phone(phone: &str) -> Option<String> {
+    let compact: String = phone.chars().filter(|c| !matches!(c, ' ' | '-' | '.' | '(' | ')')).collect();
+    let digits = compact.strip_prefix('+').or_else(|| compact.strip_prefix("00"))?;
+    let valid = (8..=15).contains(&digits.len())
+        && digits.chars().all(|c| c.is_ascii_digit())
+        && !digits.starts_with('0');
+    valid.then(|| format!("+{}", digits))
+}
+
+/// The checksummed form of a stored wallet address
+fn checksum_wallet(address: &str) -> Option<String> {
+    address.parse::<Address>().ok().map(|parsed| checksummed(&parsed))
+}
+
+/// A row a job would rewrite
+#[derive(Debug)]
+struct Fix {
+    id: Uuid,
+    from: String,
+    to: String,
+}
+
+/// One batch of rows and what should change in it
+#[derive(Debug, Default)]
+struct Batch {
+    /// Id of the last row read; None once the table is exhausted
+    last: Option<Uuid>,
+    scanned: u64,
+    fixes: Vec<Fix>,
+    /// Rows that can't be fixed (unreadable or unrecognisable)
+    failed: u64,
+}
+
+/// What a job did in this run
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RunReport {
+    pub scanned: u64,
+    pub changed: u64,
+    pub failed: u64,
+    /// Every row has been scanned
+    pub finished: bool,
+    /// Completed before and not restarted
+    pub skipped: bool,
+}
+
+/// Runs the jobs against the database
+pub struct Maintenance {
+    pool: PgPool,
+    cipher: FieldCipher,
+    progress: DataMigrationRepository,
+    audit: AuditLogRepository,
+}
+
+impl Maintenance {
+    pub fn new(pool: PgPool, cipher: FieldCipher) -> Self {
+        Self {
+            progress: DataMigrationRepository::new(pool.clone()),
+            audit: AuditLogRepository::new(pool.clone(), cipher.clone()),
+            pool,
+            cipher,
+        }
+    }
+
+    /// Progress of every job that has run
+    pub async fn status(&self) -> Result<Vec<DataMigration>, sqlx::Error> {
+        self.progress.list().await
+    }
+
+    /// Run one job from its saved cursor (or the start, on a dry run)
+    pub async fn run(&self, job: Job, args: &MigrateArgs) -> Result<RunReport, sqlx::Error> {
+        let mut report = RunReport::default();
+        let mut cursor = if args.dry_run {
+            Uuid::nil()
+        } else {
+            if !args.restart && self.progress.get(job.name()).await?.is_some_and(|done| done.is_completed()) {
+                report.skipped = true;
+                return Ok(report);
+            }
+            let state = self.progress.begin(job.name(), args.restart).await?;
+            state.cursor.and_then(|cursor| cursor.parse().ok()).unwrap_or_else(Uuid::nil)
+        };
+
+        let mut batches = 0;
+        loop {
+            if args.max_batches.is_some_and(|max| batches >= max) {
+                break;
+            }
+            let batch = self.scan(job, cursor, args.batch_size).await?;
+            let Some(last) = batch.last else {
+                report.finished = true;
+                break;
+            };
+            batches += 1;
+
+            let mut changed = 0;
+            let mut failed = batch.failed;
+            for fix in &batch.fixes {
+                if args.dry_run {
+                    tracing::info!(job = %job, row = %fix.id, "Would rewrite {} as {}", self.shown(job, &fix.from), self.shown(job, &fix.to));
+                    changed += 1;
+                    continue;
+                }
+                match self.apply(job, fix).await {
+                    Ok(()) => changed += 1,
+                    Err(e) => {
+                        tracing::error!(job = %job, row = %fix.id, "Data migration failed for row: {}", e);
+                        failed += 1;
+                    }
+                }
+            }
+            if !args.dry_run {
+                self.progress.advance(job.name(), &last.to_string(), batch.scanned, changed, failed).await?;
+            }
+            report.scanned += batch.scanned;
+            report.changed += changed;
+            report.failed += failed;
+            cursor = last;
+        }
+
+        if !args.dry_run {
+            if report.finished {
+                self.progress.complete(job.name()).await?;
+            }
+            let detail = format!(
+                "{}: scanned {}, changed {}, failed {}{}",
+                job,
+                report.scanned,
+                report.changed,
+                report.failed,
+                if report.finished { ", completed" } else { ", paused" }
+            );
+            if let Err(e) = self.audit.record_admin("DATA_MIGRATION", &detail).await {
+                tracing::error!(job = %job, "Audit log write failed: {}", e);
+            }
+        }
+        Ok(report)
+    }
+
+    /// Numbers aren't logged in full
+    fn shown(&self, job: Job, value: &str) -> String {
+        match job {
+            Job::NormalizePhones => format!("{}***", value.chars().take(4).collect::<String>()),
+            _ => value.to_string(),
+        }
+    }
+
+    async fn scan(&self, job: Job, after: Uuid, limit: i64) -> Result<Batch, sqlx::Error> {
+        let mut batch = Batch::default();
+        match job {
+            Job::NormalizePhones => {
+                let rows = sqlx::query_as::<_, (Uuid, String, Option<String>)>(
+                    "SELECT id, phone, phone_encrypted FROM users WHERE id > $1 ORDER BY id LIMIT $2"
+                )
+                .bind(after)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+                for (id, phone, phone_encrypted) in rows {
+                    batch.last = Some(id);
+                    batch.scanned += 1;
+                    let plaintext = match phone_encrypted.as_deref().map(|v| self.cipher.decrypt(v)) {
+                        Some(Ok(plaintext)) => plaintext,
+                        Some(Err(e)) => {
+                            tracing::error!(user = %id, "Cannot decrypt phone: {}", e);
+                            batch.failed += 1;
+                            continue;
+                        }
+                        // Only the blind index is kept; nothing to normalize
+                        None if KeyVault::is_blind_index(&phone) => continue,
+                        None => phone,
+                    };
+                    match normalize_phone(&plaintext) {
+                        Some(normalized) if normalized != plaintext => {
+                            batch.fixes.push(Fix { id, from: plaintext, to: normalized })
+                        }
+                        Some(_) => {}
+                        None => {
+                            tracing::warn!(user = %id, "Phone number has no country code; left as it is");
+                            batch.failed += 1;
+                        }
+                    }
+                }
+            }
+            Job::UppercaseVouchers => {
+                let rows = sqlx::query_as::<_, (Uuid, String)>(
+                    "SELECT id, code FROM vouchers WHERE id > $1 ORDER BY id LIMIT $2"
+                )
+                .bind(after)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+                for (id, code) in rows {
+                    batch.last = Some(id);
+                    batch.scanned += 1;
+                    let upper = code.to_uppercase();
+                    if upper != code {
+                        batch.fixes.push(Fix { id, from: code, to: upper });
+                    }
+                }
+            }
+            Job::ChecksumWallets => {
+                let rows = sqlx::query_as::<_, (Uuid, String)>(
+                    "SELECT id, wallet_address FROM users WHERE id > $1 ORDER BY id LIMIT $2"
+                )
+                .bind(after)
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await?;
+                for (id, address) in rows {
+                    batch.last = Some(id);
+                    batch.scanned += 1;
+                    match checksum_wallet(&address) {
+                        Some(checksummed) if checksummed != address => {
+                            batch.fixes.push(Fix { id, from: address, to: checksummed })
+                        }
+                        Some(_) => {}
+                        None => {
+                            tracing::warn!(user = %id, "Wallet address is not an address; left as it is");
+                            batch.failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(batch)
+    }
+
+    async fn apply(&self, job: Job, fix: &Fix) -> Result<(), sqlx::Error> {
+        match job {
+            Job::NormalizePhones => self.rewrite_phone(fix).await,
+            Job::UppercaseVouchers => {
+                // A code that differs from another only in case stays put
+                sqlx::query("UPDATE vouchers SET code = $1 WHERE id = $2 AND code = $3")
+                    .bind(&fix.to)
+                    .bind(fix.id)
+                    .bind(&fix.from)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+            Job::ChecksumWallets => {
+                sqlx::query("UPDATE users SET wallet_address = $1 WHERE id = $2 AND wallet_address = $3")
+                    .bind(&fix.to)
+                    .bind(fix.id)
+                    .bind(&fix.from)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Move a user to their normalized number, along with the contacts,
+    /// deposits and deposit address keyed by the old one, all or nothing
+    async fn rewrite_phone(&self, fix: &Fix) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let Some(stored) = sqlx::query_scalar::<_, String>("SELECT phone FROM users WHERE id = $1 FOR UPDATE")
+            .bind(fix.id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(());
+        };
+        let index = self.cipher.blind_index(&fix.to);
+
+        sqlx::query("UPDATE users SET phone = $1, phone_encrypted = $2, phone_prefixes = $3 WHERE id = $4")
+            .bind(&index)
+            .bind(self.cipher.encrypt_phone(&fix.to).map_err(decode_error)?)
+            .bind(self.cipher.prefix_indexes(&fix.to))
+            .bind(fix.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE address_book SET user_phone = $1 WHERE user_phone = $2")
+            .bind(&index)
+            .bind(&stored)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE deposits SET user_phone = $1 WHERE user_phone = $2 OR user_phone = $3")
+            .bind(self.cipher.phone_ref(&fix.to))
+            .bind(&fix.from)
+            .bind(self.cipher.phone_ref(&fix.from))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE deposit_addresses SET user_phone = $1 WHERE user_phone = $2")
+            .bind(&fix.to)
+            .bind(&fix.from)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Result<MigrateArgs, String> {
+        MigrateArgs::parse(list.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_normalize_phone() {
+        assert_eq!(normalize_phone("+1 (555) 123-4567").as_deref(), Some("+15551234567"));
+        assert_eq!(normalize_phone("0044 7700.900123").as_deref(), Some("+447700900123"));
+        assert_eq!(normalize_phone("+15551234567").as_deref(), Some("+15551234567"));
+        // No country code, too short, or not a number
+        assert_eq!(normalize_phone("555-123-4567"), None);
+        assert_eq!(normalize_phone("+1234"), None);
+        assert_eq!(normalize_phone("+1555CALLNOW"), None);
+        assert_eq!(normalize_phone("+0555123456"), None);
+    }
+
+    #[test]
+    fn test_checksum_wallet() {
+        assert_eq!(
+            checksum_wallet("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").as_deref(),
+            Some("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed")
+        );
+        assert_eq!(checksum_wallet("not-an-address"), None);
+    }
+
+    #[test]
+    fn test_migrate_args() {
+        let all = args(&[]).unwrap();
+        assert_eq!(all.jobs, Job::ALL.to_vec());
+        assert_eq!((all.dry_run, all.restart, all.batch_size, all.max_batches), (false, false, DEFAULT_BATCH_SIZE, None));
+
+        let some = args(&["checksum_wallets", "--dry-run", "--batch-size", "50", "--max-batches", "2", "checksum_wallets"]).unwrap();
+        assert_eq!(some.jobs, vec![Job::ChecksumWallets]);
+        assert_eq!((some.dry_run, some.batch_size, some.max_batches), (true, 50, Some(2)));
+
+        assert!(args(&["fix_everything"]).unwrap_err().contains("normalize_phones"));
+        assert!(args(&["--batch-size", "0"]).is_err());
+        assert!(args(&["--max-batches"]).is_err());
+    }
+}
@@ -0,0 +1,325 @@
+//! Exact money amounts
+//!
+//! `Money` is a whole number of micro-units (6 decimals, like USDC) tagged
+//! with a currency, so amounts never round-trip through f64 on the way to
+//! the database. Arithmetic is checked and refuses to mix currencies.
+//!
+//! In Postgres the micro-units are a BIGINT column, which is micro-USDC
+//! unless the row says otherwise (e.g. a deposit's `token`). In JSON an
+//! amount is a plain number of USDC; requests may also send a decimal
+//! string such as `"12.50"`.
+
+use std::fmt;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef};
+use sqlx::{Decode, Encode, Postgres, Type};
+
+/// Micro-units in one whole unit
+pub const MICROS_PER_UNIT: i64 = 1_000_000;
+const DECIMALS: usize = 6;
+const MAX_CODE_LEN: usize = 8;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum MoneyError {
+    #[error("Invalid amount: {0}")]
+    Invalid(String),
+    #[error("At most {DECIMALS} decimal places")]
+    TooPrecise,
+    #[error("Amount out of range")]
+    Overflow,
+    #[error("Cannot combine {0} with {1}")]
+    CurrencyMismatch(Currency, Currency),
+}
+
+/// Currency or token symbol, e.g. `USDC`, `ETH`, `KES`
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency {
+    code: [u8; MAX_CODE_LEN],
+    len: u8,
+}
+
+impl Currency {
+    pub const USDC: Currency = Currency::from_static(b"USDC");
+
+    const fn from_static(code: &[u8]) -> Self {
+        let mut bytes = [0u8; MAX_CODE_LEN];
+        let mut i = 0;
+        while i < code.len() {
+            bytes[i] = code[i];
+            i += 1;
+        }
+        Self { code: bytes, len: code.len() as u8 }
+    }
+
+    /// 2-8 letters or digits, case-insensitive
+    pub fn parse(code: &str) -> Option<Self> {
+        let code = code.trim();
+        if !(2..=MAX_CODE_LEN).contains(&code.len()) || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return None;
+        }
+        Some(Self::from_static(code.to_ascii_uppercase().as_bytes()))
+    }
+
+    pub fn code(&self) -> &str {
+        // Only ASCII is ever stored
+        std::str::from_utf8(&self.code[..self.len as usize]).unwrap_or("")
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+/// An amount in micro-units of one currency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    micros: i64,
+    currency: Currency,
+}
+
+impl Money {
+    pub const fn from_micros(micros: i64, currency: Currency) -> Self {
+        Self { micros, currency }
+    }
+
+    /// An amount of micro-USDC
+    pub const fn usdc(micros: i64) -> Self {
+        Self::from_micros(micros, Currency::USDC)
+    }
+
+    /// Parse a decimal string such as `12`, `-0.5` or `7.722007` exactly
+    pub fn parse(amount: &str, currency: Currency) -> Result<Self, MoneyError> {
+        let invalid = || MoneyError::Invalid(amount.to_string());
+        let trimmed = amount.trim();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+        if (whole.is_empty() && fraction.is_empty()) || !all_digits(whole) || !all_digits(fraction) {
+            return Err(invalid());
+        }
+        if fraction.len() > DECIMALS {
+            return Err(MoneyError::TooPrecise);
+        }
+
+        let whole: i64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| MoneyError::Overflow)? };
+        let fraction: i64 = format!("{:0<width$}", fraction, width = DECIMALS).parse().map_err(|_| invalid())?;
+        let micros = whole
+            .checked_mul(MICROS_PER_UNIT)
+            .and_then(|m| m.checked_add(fraction))
+            .ok_or(MoneyError::Overflow)?;
+        Ok(Self::from_micros(if negative { -micros } else { micros }, currency))
+    }
+
+    /// Round a float amount to the nearest micro-unit
+    pub fn from_f64(amount: f64, currency: Currency) -> Result<Self, MoneyError> {
+        let micros = (amount * MICROS_PER_UNIT as f64).round();
+        if !micros.is_finite() || micros.abs() >= i64::MAX as f64 {
+            return Err(MoneyError::Invalid(amount.to_string()));
+        }
+        Ok(Self::from_micros(micros as i64, currency))
+    }
+
+    pub fn micros(&self) -> i64 {
+        self.micros
+    }
+
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.micros > 0
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        let micros = self.micros.checked_add(other.micros).ok_or(MoneyError::Overflow)?;
+        Ok(Self::from_micros(micros, self.currency))
+    }
+
+    pub fn checked_mul(self, factor: i64) -> Result<Money, MoneyError> {
+        let micros = self.micros.checked_mul(factor).ok_or(MoneyError::Overflow)?;
+        Ok(Self::from_micros(micros, self.currency))
+    }
+
+    fn same_currency(&self, other: Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch(self.currency, other.currency));
+        }
+        Ok(())
+    }
+
+    /// Nearest f64, for display-only consumers such as GraphQL floats
+    pub fn to_f64(self) -> f64 {
+        self.micros as f64 / MICROS_PER_UNIT as f64
+    }
+
+    /// Amount with exactly `decimals` places, rounded half away from zero,
+    /// e.g. `8.75`
+    pub fn format(&self, decimals: usize) -> String {
+        let decimals = decimals.min(DECIMALS);
+        let step = 10i128.pow((DECIMALS - decimals) as u32);
+        let micros = self.micros as i128;
+        let scaled = (micros.abs() + step / 2) / step;
+        let unit = 10i128.pow(decimals as u32);
+        let sign = if micros < 0 && scaled != 0 { "-" } else { "" };
+        match decimals {
+            0 => format!("{}{}", sign, scaled),
+            _ => format!("{}{}.{:0width$}", sign, scaled / unit, scaled % unit, width = decimals),
+        }
+    }
+
+    /// `format` followed by the currency code, e.g. `8.75 USDC`
+    pub fn format_with_code(&self, decimals: usize) -> String {
+        format!("{} {}", self.format(decimals), self.currency)
+    }
+
+    /// Amount without trailing zeros, e.g. `10` or `7.722007`
+    pub fn amount(&self) -> String {
+        let full = self.format(DECIMALS);
+        full.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// `12.5 USDC`
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount(), self.currency)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+/// Reads USDC from a JSON number or decimal string
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an amount as a number or decimal string")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Money, E> {
+                v.checked_mul(MICROS_PER_UNIT).map(Money::usdc).ok_or_else(|| E::custom(MoneyError::Overflow))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Money, E> {
+                let v = i64::try_from(v).map_err(|_| E::custom(MoneyError::Overflow))?;
+                self.visit_i64(v)
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Money, E> {
+                Money::from_f64(v, Currency::USDC).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Money, E> {
+                Money::parse(v, Currency::USDC).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+/// Stored as BIGINT micro-units; decoded amounts are USDC
+impl Type<Postgres> for Money {
+    fn type_info() -> PgTypeInfo {
+        <i64 as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <i64 as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Money {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        <i64 as Encode<Postgres>>::encode_by_ref(&self.micros, buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Money {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(Money::usdc(<i64 as Decode<Postgres>>::decode(value)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_is_exact() {
+        assert_eq!(Money::parse("7.722007", Currency::USDC), Ok(Money::usdc(7_722_007)));
+        assert_eq!(Money::parse("0.29", Currency::USDC), Ok(Money::usdc(290_000)));
+        assert_eq!(Money::parse("-1.5", Currency::USDC), Ok(Money::usdc(-1_500_000)));
+        assert_eq!(Money::parse(".5", Currency::USDC), Ok(Money::usdc(500_000)));
+        assert_eq!(Money::parse("10", Currency::USDC), Ok(Money::usdc(10_000_000)));
+        assert_eq!(Money::parse("0.0000001", Currency::USDC), Err(MoneyError::TooPrecise));
+        assert!(matches!(Money::parse("1e3", Currency::USDC), Err(MoneyError::Invalid(_))));
+        assert!(matches!(Money::parse(".", Currency::USDC), Err(MoneyError::Invalid(_))));
+        assert_eq!(Money::parse("99999999999999", Currency::USDC), Err(MoneyError::Overflow));
+        // The float route used to truncate this to 289999
+        assert_eq!(Money::from_f64(0.29, Currency::USDC), Ok(Money::usdc(290_000)));
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let eth = Currency::parse("eth").unwrap();
+        assert_eq!(eth.code(), "ETH");
+        assert_eq!(Money::usdc(1).checked_add(Money::usdc(2)), Ok(Money::usdc(3)));
+        assert_eq!(Money::usdc(1).checked_add(Money::usdc(-2)), Ok(Money::usdc(-1)));
+        assert_eq!(Money::usdc(5_000_000).checked_mul(3), Ok(Money::usdc(15_000_000)));
+        assert_eq!(Money::usdc(i64::MAX).checked_add(Money::usdc(1)), Err(MoneyError::Overflow));
+        assert_eq!(
+            Money::usdc(1).checked_add(Money::from_micros(1, eth)),
+            Err(MoneyError::CurrencyMismatch(Currency::USDC, eth))
+        );
+        assert!(Currency::parse("E").is_none());
+        assert!(Currency::parse("TOOLONGCODE").is_none());
+    }
+
+    #[test]
+    fn test_formatting() {
+        assert_eq!(Money::usdc(8_750_000).format(2), "8.75");
+        assert_eq!(Money::usdc(8_755_000).format(2), "8.76");
+        assert_eq!(Money::usdc(-4_000).format(2), "0.00");
+        assert_eq!(Money::usdc(-1_250_000).format(1), "-1.3");
+        assert_eq!(Money::usdc(12_500_000).format(0), "13");
+        assert_eq!(Money::usdc(10_000_000).to_string(), "10 USDC");
+        assert_eq!(Money::from_micros(500_000, Currency::parse("ETH").unwrap()).to_string(), "0.5 ETH");
+        assert_eq!(Money::usdc(7_722_007).amount(), "7.722007");
+        assert_eq!(Money::usdc(12_500_000).format_with_code(2), "12.50 USDC");
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(serde_json::to_string(&Money::usdc(12_500_000)).unwrap(), "12.5");
+        assert_eq!(serde_json::from_str::<Money>("12.5").unwrap(), Money::usdc(12_500_000));
+        assert_eq!(serde_json::from_str::<Money>("5").unwrap(), Money::usdc(5_000_000));
+        assert_eq!(serde_json::from_str::<Money>("\"0.29\"").unwrap(), Money::usdc(290_000));
+        assert!(serde_json::from_str::<Money>("\"abc\"").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Which subdomain labels may be minted
+//!
+//! A label is refused when it matches any rule: the built-in reserved
+//! labels (`admin`, `support`, `pay`, ...), the `ENS_DENY_PATTERN` regex,
+//! or an admin-managed row in `reserved_names`. `reserved` rules match the
+//! whole label, `brand` and `offensive` rules match anywhere in it (so
+//! `paypalsupport` is caught by `paypal`), and `pattern` rules are regexes.
+//! The ENS service CLI pulls the same list from `GET /admin/reserved-names`.
+
+use std::sync::{Arc, RwLock};
+
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+use crate::config::NamePolicyConfig;
+use crate::db::ReservedNameRepository;
+
+/// Labels nobody can mint, whatever the database says
+pub const BUILTIN_RESERVED: [&str; 24] = [
+    "admin", "administrator", "root", "system", "support", "help", "helpdesk", "info", "security", "official",
+    "staff", "team", "moderator", "pay", "payment", "payments", "billing", "wallet", "account", "verify",
+    "textchain", "ttc", "ttcip", "ens",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum NamePolicyError {
+    #[error("Invalid ENS_DENY_PATTERN: {0}")]
+    Config(regex::Error),
+    #[error("Unknown kind {0}; use reserved, brand, offensive or pattern")]
+    Kind(String),
+    #[error("Invalid pattern: {0}")]
+    Pattern(regex::Error),
+    #[error("Label is required")]
+    EmptyLabel,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// How a rule matches a label
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    /// The whole label
+    Reserved,
+    /// Anywhere in the label
+    Brand,
+    /// Anywhere in the label
+    Offensive,
+    /// Regex
+    Pattern,
+}
+
+impl RuleKind {
+    pub fn parse(kind: &str) -> Result<Self, NamePolicyError> {
+        match kind.trim().to_lowercase().as_str() {
+            "reserved" => Ok(RuleKind::Reserved),
+            "brand" => Ok(RuleKind::Brand),
+            "offensive" => Ok(RuleKind::Offensive),
+            "pattern" => Ok(RuleKind::Pattern),
+            other => Err(NamePolicyError::Kind(other.to_string())),
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RuleKind::Reserved => "reserved",
+            RuleKind::Brand => "brand",
+            RuleKind::Offensive => "offensive",
+            RuleKind::Pattern => "pattern",
+        }
+    }
+}
+
+/// Where a rule comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleSource {
+    Builtin,
+    Config,
+    Admin,
+}
+
+/// One deny rule, as listed by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct NameRule {
+    pub label: String,
+    pub kind: RuleKind,
+    pub source: RuleSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(skip)]
+    regex: Option<Regex>,
+}
+
+impl NameRule {
+    fn new(label: &str, kind: RuleKind, source: RuleSource, reason: Option<String>) -> Result<Self, NamePolicyError> {
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(NamePolicyError::EmptyLabel);
+        }
+        let (label, regex) = match kind {
+            RuleKind::Pattern => {
+                let regex = RegexBuilder::new(label).case_insensitive(true).build().map_err(NamePolicyError::Pattern)?;
+                (label.to_string(), Some(regex))
+            }
+            _ => (label.to_lowercase(), None),
+        };
+        Ok(Self { label, kind, source, reason, regex })
+    }
+
+    fn matches(&self, label: &str) -> bool {
+        match self.kind {
+            RuleKind::Reserved => label == self.label,
+            RuleKind::Brand | RuleKind::Offensive => label.contains(&self.label),
+            RuleKind::Pattern => self.regex.as_ref().is_some_and(|regex| regex.is_match(label)),
+        }
+    }
+}
+
+/// Built-in and configured rules plus the admin-managed ones
+#[derive(Clone)]
+pub struct NamePolicy {
+    fixed: Arc<Vec<NameRule>>,
+    admin: Arc<RwLock<Vec<NameRule>>>,
+    repo: Option<ReservedNameRepository>,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        Self::from_config(&NamePolicyConfig::default()).expect("built-in rules are valid")
+    }
+}
+
+impl NamePolicy {
+    /// Built-in rules and `ENS_DENY_PATTERN` (no database)
+    pub fn from_config(config: &NamePolicyConfig) -> Result<Self, NamePolicyError> {
+        let mut fixed = BUILTIN_RESERVED
+            .iter()
+            .map(|label| NameRule::new(label, RuleKind::Reserved, RuleSource::Builtin, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        if !config.deny_pattern.trim().is_empty() {
+            let rule = NameRule::new(&config.deny_pattern, RuleKind::Pattern, RuleSource::Config, None).map_err(|e| match e {
+                NamePolicyError::Pattern(e) => NamePolicyError::Config(e),
+                other => other,
+            })?;
+            fixed.push(rule);
+        }
+        Ok(Self { fixed: Arc::new(fixed), admin: Arc::default(), repo: None })
+    }
+
+    /// Config rules plus `reserved_names`
+    pub async fn load(config: &NamePolicyConfig, repo: ReservedNameRepository) -> Result<Self, NamePolicyError> {
+        let mut policy = Self::from_config(config)?;
+        policy.repo = Some(repo);
+        policy.reload().await?;
+        Ok(policy)
+    }
+
+    /// Re-read `reserved_names`; rows with a bad kind or regex are skipped
+    pub async fn reload(&self) -> Result<(), sqlx::Error> {
+        let Some(ref repo) = self.repo else {
+            return Ok(());
+        };
+        let rules = repo
+            .list()
+            .await?
+            .into_iter()
+            .filter_map(|row| {
+                let rule = RuleKind::parse(&row.kind)
+                    .and_then(|kind| NameRule::new(&row.label, kind, RuleSource::Admin, row.reason));
+                rule.map_err(|e| tracing::warn!(label = %row.label, "Ignoring reserved name: {}", e)).ok()
+            })
+            .collect();
+        if let Ok(mut admin) = self.admin.write() {
+            *admin = rules;
+        }
+        Ok(())
+    }
+
+    /// The first rule a label breaks, if any
+    pub fn check(&self, label: &str) -> Option<NameRule> {
+        let label = label.trim().to_lowercase();
+        let admin = self.admin.read().ok()?;
+        self.fixed.iter().chain(admin.iter()).find(|rule| rule.matches(&label)).cloned()
+    }
+
+    pub fn rules(&self) -> Vec<NameRule> {
+        let admin = self.admin.read().map(|admin| admin.clone()).unwrap_or_default();
+        self.fixed.iter().cloned().chain(admin).collect()
+    }
+
+    /// Add or replace an admin rule and persist it
+    pub async fn add(&self, label: &str, kind: &str, reason: Option<String>) -> Result<NameRule, NamePolicyError> {
+        let rule = NameRule::new(label, RuleKind::parse(kind)?, RuleSource::Admin, reason)?;
+        if let Some(ref repo) = self.repo {
+            repo.add(&rule.label, rule.kind.as_str(), rule.reason.as_deref()).await?;
+        }
+        if let Ok(mut admin) = self.admin.write() {
+            admin.retain(|existing| existing.label != rule.label);
+            admin.push(rule.clone());
+        }
+        tracing::info!(label = %rule.label, kind = rule.kind.as_str(), "Reserved name added");
+        Ok(rule)
+    }
+
+    /// Remove an admin rule; false when there was none
+    pub async fn remove(&self, label: &str) -> Result<bool, NamePolicyError> {
+        let removed = match self.repo {
+            Some(ref repo) => repo.remove(label).await?,
+            None => false,
+        };
+        let removed_here = match self.admin.write() {
+            Ok(mut admin) => {
+                let before = admin.len();
+                admin.retain(|rule| rule.label != label);
+                admin.len() < before
+            }
+            Err(_) => false,
+        };
+        if removed || removed_here {
+            tracing::info!(%label, "Reserved name removed");
+        }
+        Ok(removed || removed_here)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rule_kinds() {
+        let policy = NamePolicy::from_config(&NamePolicyConfig { deny_pattern: "^x{3,}".to_string() }).unwrap();
+        policy.add("paypal", "brand", None).await.unwrap();
+        policy.add("badword", "offensive", Some("slur".to_string())).await.unwrap();
+
+        assert_eq!(policy.check("Admin").map(|r| r.source), Some(RuleSource::Builtin));
+        assert!(policy.check("admins").is_none());
+        assert_eq!(policy.check("paypalsupport").map(|r| r.kind), Some(RuleKind::Brand));
+        assert_eq!(policy.check("mybadword1").map(|r| r.kind), Some(RuleKind::Offensive));
+        assert_eq!(policy.check("xxxalice").map(|r| r.source), Some(RuleSource::Config));
+        assert!(policy.check("alice").is_none());
+
+        assert!(policy.remove("paypal").await.unwrap());
+        assert!(policy.check("paypalsupport").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_rules() {
+        let policy = NamePolicy::default();
+        assert!(matches!(policy.add("abc", "celebrity", None).await, Err(NamePolicyError::Kind(_))));
+        assert!(matches!(policy.add("(", "pattern", None).await, Err(NamePolicyError::Pattern(_))));
+        assert!(matches!(policy.add(" ", "reserved", None).await, Err(NamePolicyError::EmptyLabel)));
+        assert!(NamePolicy::from_config(&NamePolicyConfig { deny_pattern: "[".to_string() }).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! NOTIFY preferences and the daily digest
+//!
+//! Deposit and receipt notices go through `Notifications::deliver`, which
+//! texts them, queues them for the digest or drops them according to the
+//! recipient's `NOTIFY` mode. Codes, approval requests, alerts and replies
+//! to the user's own commands don't come through here and always go out.
+//!
+//! The digest covers everything queued before `NOTIFY_DIGEST_HOUR` (UTC)
+//! each day. Every instance polls, and a notice is claimed only once, so a
+//! restart or a second instance neither skips nor repeats a digest.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::config::NotifyDigestConfig;
+use crate::db::{NotificationRepository, NotifyMode, UserRepository};
+use crate::sms::{Delivery, SmsGateway};
+
+/// Seconds between checks for a due digest
+const POLL_SECS: u64 = 300;
+/// Notices listed in one digest before "+N more"
+const MAX_DIGEST_LINES: usize = 5;
+
+/// Routes non-critical notices by preference and sends the daily digest
+#[derive(Clone)]
+pub struct Notifications {
+    repo: NotificationRepository,
+    users: UserRepository,
+    twilio: SmsGateway,
+    hour: u32,
+}
+
+impl Notifications {
+    pub fn new(config: &NotifyDigestConfig, repo: NotificationRepository, users: UserRepository, twilio: SmsGateway) -> Self {
+        Self { repo, users, twilio, hour: config.hour }
+    }
+
+    pub fn repo(&self) -> &NotificationRepository {
+        &self.repo
+    }
+
+    pub fn digest_hour(&self) -> u32 {
+        self.hour
+    }
+
+    /// Text, queue or drop a non-critical notice according to the
+    /// recipient's NOTIFY mode. Numbers without a wallet are texted.
+    pub async fn deliver(&self, to: &str, body: &str) {
+        match self.repo.mode_for_phone(to).await {
+            Ok(Some((_, NotifyMode::Off))) => {
+                tracing::debug!(to = %to, "Notification dropped: NOTIFY OFF");
+                return;
+            }
+            Ok(Some((user_id, NotifyMode::Digest))) => match self.repo.queue(user_id, body).await {
+                Ok(()) => return,
+                Err(e) => tracing::warn!(user = %user_id, "Digest queue failed, sending now: {}", e),
+            },
+            Ok(_) => {}
+            Err(e) => tracing::warn!(to = %to, "Notify mode unavailable: {}", e),
+        }
+        match self.twilio.send_notification(to, body).await {
+            Ok(Delivery::Sent(result)) => tracing::debug!(to = %to, sid = %result.message_sid, "Notification sent"),
+            Ok(Delivery::Deferred(until)) => tracing::debug!(to = %to, %until, "Notification deferred for quiet hours"),
+            Ok(Delivery::HeldForOutage) => tracing::debug!(to = %to, "Notification held for carrier outage"),
+            Err(e) => tracing::warn!(to = %to, "Notification not sent: {}", e),
+        }
+    }
+
+    /// Check for a due digest now and every few minutes
+    pub fn start(&self) {
+        let notifications = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(POLL_SECS));
+            loop {
+                ticker.tick().await;
+                match notifications.run().await {
+                    Ok(0) => {}
+                    Ok(sent) => tracing::info!(sent, "Notification digests sent"),
+                    Err(e) => tracing::warn!("Notification digest failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Send one digest per user with notices queued before the latest
+    /// digest hour; returns how many were sent
+    pub async fn run(&self) -> Result<usize, sqlx::Error> {
+        let items = self.repo.take_before(last_cutoff(Utc::now(), self.hour)).await?;
+        let mut order: Vec<Uuid> = Vec::new();
+        let mut by_user: HashMap<Uuid, Vec<String>> = HashMap::new();
+        for item in items {
+            by_user
+                .entry(item.user_id)
+                .or_insert_with(|| {
+                    order.push(item.user_id);
+                    Vec::new()
+                })
+                .push(item.body);
+        }
+
+        let mut sent = 0;
+        for user_id in order {
+            let user = match self.users.find_by_id(user_id).await {
+                Ok(Some(user)) => user,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!(user = %user_id, "Digest recipient lookup failed: {}", e);
+                    continue;
+                }
+            };
+            match self.twilio.send_notification(&user.phone, &compose(&by_user[&user_id])).await {
+                Ok(_) => sent += 1,
+                Err(e) => tracing::warn!(user = %user_id, "Digest not sent: {}", e),
+            }
+        }
+        Ok(sent)
+    }
+}
+
+/// The most recent `hour`:00 UTC at or before `now`
+fn last_cutoff(now: DateTime<Utc>, hour: u32) -> DateTime<Utc> {
+    let today = now
+        .date_naive()
+        .and_hms_opt(hour.min(23), 0, 0)
+        .unwrap_or_default()
+        .and_utc();
+    if today <= now {
+        today
+    } else {
+        today - chrono::Duration::days(1)
+    }
+}
+
+/// One SMS summing up a day's notices: the first line of each
+fn compose(bodies: &[String]) -> String {
+    let mut text = format!("Daily summary ({}):", bodies.len());
+    for body in bodies.iter().take(MAX_DIGEST_LINES) {
+        text.push_str("\n- ");
+        text.push_str(body.lines().next().unwrap_or_default());
+    }
+    if bodies.len() > MAX_DIGEST_LINES {
+        text.push_str(&format!("\n+{} more. Reply HISTORY to see them.", bodies.len() - MAX_DIGEST_LINES));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_last_cutoff() {
+        let morning = Utc.with_ymd_and_hms(2026, 3, 10, 9, 30, 0).unwrap();
+        let evening = Utc.with_ymd_and_hms(2026, 3, 10, 18, 0, 0).unwrap();
+        assert_eq!(last_cutoff(morning, 18), Utc.with_ymd_and_hms(2026, 3, 9, 18, 0, 0).unwrap());
+        assert_eq!(last_cutoff(evening, 18), evening);
+        assert_eq!(last_cutoff(morning, 0), Utc.with_ymd_and_hms(2026, 3, 10, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn test_compose_digest() {
+        let bodies: Vec<String> = (1..=7).map(|n| format!("Received {}.00 USDC from bob.\nRef R{}", n, n)).collect();
+        let digest = compose(&bodies);
+        assert!(digest.starts_with("Daily summary (7):\n- Received 1.00 USDC from bob."));
+        assert!(!digest.contains("Ref"));
+        assert!(digest.ends_with("+2 more. Reply HISTORY to see them."));
+
+        assert_eq!(compose(&bodies[..1]), "Daily summary (1):\n- Received 1.00 USDC from bob.");
+    }
+}
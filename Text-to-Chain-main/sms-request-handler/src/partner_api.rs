@@ -0,0 +1,369 @@
+//! Partner REST API for mobile-money integrations
+//!
+//! Partners call `/partner/*` with `Authorization: Bearer <key>`. Each key
+//! has scopes (`deposit:create`, `voucher:create`), a per-minute rate limit
+//! and a daily quota, and every request is metered per key, endpoint and day
+//! for `GET /admin/partner-keys/:id/usage`.
+//!
+//! Keys with `signed_requests` must also sign each call, and keys with a
+//! webhook URL are sent a signed `deposit.credited` webhook for every new
+//! deposit (see `partner_webhooks`).
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::db::{
+    BalanceAlertRepository, LedgerError, PartnerDeposit, PartnerDepositError, PartnerKey, PartnerKeyRepository, PartnerScope, UserRepository,
+    VoucherRepository, MAX_CODE_PREFIX_LEN,
+};
+use crate::money::Money;
+use crate::partner_webhooks::{self, PartnerWebhooks, SignatureError, SIGNATURE_HEADER, TOLERANCE_SECS};
+use crate::sms::SmsGateway;
+
+/// Longest partner deposit reference
+const MAX_REFERENCE_LEN: usize = 100;
+/// Most vouchers one request may create
+const MAX_VOUCHERS_PER_REQUEST: usize = 100;
+
+/// Fixed one-minute windows of requests per key, per instance
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    windows: Arc<Mutex<HashMap<Uuid, (i64, u32)>>>,
+}
+
+impl RateLimiter {
+    /// Count a request at `now_secs`; Err holds the seconds until the next window
+    fn check(&self, key_id: Uuid, limit: i32, now_secs: i64) -> Result<(), i64> {
+        if limit <= 0 {
+            return Ok(());
+        }
+        let minute = now_secs.div_euclid(60);
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let window = windows.entry(key_id).or_insert((minute, 0));
+        if window.0 != minute {
+            *window = (minute, 0);
+        }
+        if window.1 >= limit as u32 {
+            return Err(60 - now_secs.rem_euclid(60));
+        }
+        window.1 += 1;
+        Ok(())
+    }
+}
+
+/// Partner API route state
+#[derive(Clone)]
+pub struct PartnerApiState {
+    pub keys: PartnerKeyRepository,
+    pub users: UserRepository,
+    pub vouchers: VoucherRepository,
+    /// Tells users about credited deposits
+    pub twilio: SmsGateway,
+    /// Users who turned deposit notifications off
+    pub alerts: BalanceAlertRepository,
+    pub limiter: RateLimiter,
+    pub webhooks: PartnerWebhooks,
+}
+
+/// Create partner API routes
+pub fn partner_routes(state: PartnerApiState) -> Router {
+    Router::new()
+        .route("/partner/deposits", post(create_deposit))
+        .route("/partner/vouchers", post(create_vouchers))
+        .with_state(state)
+}
+
+fn error(status: StatusCode, message: impl ToString) -> Response {
+    (status, Json(json!({ "success": false, "error": message.to_string() }))).into_response()
+}
+
+/// Check a request's signature: required for keys with `signed_requests`,
+/// and checked whenever one is sent. Err is the response to refuse with.
+async fn check_signature(state: &PartnerApiState, key: &PartnerKey, headers: &HeaderMap, body: &[u8]) -> Result<(), Response> {
+    let header = headers.get(SIGNATURE_HEADER).and_then(|v| v.to_str().ok());
+    let header = match header {
+        Some(header) => header,
+        None if key.signed_requests => return Err(error(StatusCode::UNAUTHORIZED, SignatureError::Missing)),
+        None => return Ok(()),
+    };
+    let secret = match state.keys.webhook_secret(key.id).await {
+        Ok(Some(secret)) => secret,
+        Ok(None) => return Err(error(StatusCode::UNAUTHORIZED, "no webhook secret issued for this key")),
+        Err(e) => {
+            tracing::error!(key = %key.id, "Webhook secret lookup failed: {}", e);
+            return Err(error(StatusCode::INTERNAL_SERVER_ERROR, "database error"));
+        }
+    };
+    let signed = partner_webhooks::verify(&secret, header, body, Utc::now().timestamp())
+        .map_err(|e| error(StatusCode::UNAUTHORIZED, e))?;
+    match state.keys.claim_nonce(key.id, &signed.nonce, 2 * TOLERANCE_SECS).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(error(StatusCode::UNAUTHORIZED, SignatureError::Replayed)),
+        Err(e) => {
+            tracing::error!(key = %key.id, "Nonce check failed: {}", e);
+            Err(error(StatusCode::INTERNAL_SERVER_ERROR, "database error"))
+        }
+    }
+}
+
+/// Authenticate the key and check signature, scope, rate and quota; each
+/// refusal after authentication counts as a rejected request
+async fn authorize(
+    state: &PartnerApiState,
+    headers: &HeaderMap,
+    body: &[u8],
+    scope: PartnerScope,
+    endpoint: &str,
+) -> Result<PartnerKey, Response> {
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| error(StatusCode::UNAUTHORIZED, "missing API key"))?;
+    let key = match state.keys.authenticate(presented).await {
+        Ok(Some(key)) => key,
+        Ok(None) => return Err(error(StatusCode::UNAUTHORIZED, "invalid API key")),
+        Err(e) => {
+            tracing::error!("Partner key lookup failed: {}", e);
+            return Err(error(StatusCode::INTERNAL_SERVER_ERROR, "database error"));
+        }
+    };
+
+    let refusal = if let Err(response) = check_signature(state, &key, headers, body).await {
+        Some(response)
+    } else if !key.has_scope(scope) {
+        Some(error(StatusCode::FORBIDDEN, format!("missing scope {}", scope.as_str())))
+    } else if let Err(retry_after) = state.limiter.check(key.id, key.rate_per_minute, Utc::now().timestamp()) {
+        let mut response = error(StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded");
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        Some(response)
+    } else if key.daily_quota > 0 && state.keys.requests_today(key.id).await.unwrap_or(0) >= key.daily_quota as i64 {
+        Some(error(StatusCode::TOO_MANY_REQUESTS, "daily quota exceeded"))
+    } else {
+        None
+    };
+
+    match refusal {
+        Some(response) => {
+            tracing::warn!(key = %key.id, partner = %key.partner, endpoint, status = %response.status(), "Partner request refused");
+            meter(state, key.id, endpoint, false, 0).await;
+            Err(response)
+        }
+        None => Ok(key),
+    }
+}
+
+async fn meter(state: &PartnerApiState, key_id: Uuid, endpoint: &str, served: bool, amount: i64) {
+    if let Err(e) = state.keys.record_usage(key_id, endpoint, served, amount).await {
+        tracing::warn!(key = %key_id, endpoint, "Failed to record partner usage: {}", e);
+    }
+}
+
+/// Credit a user's cash balance
+#[derive(Debug, Deserialize)]
+pub struct PartnerDepositRequest {
+    /// User's phone number in E.164 format
+    pub phone: String,
+    /// Amount in USDC
+    pub amount: Money,
+    /// The partner's transaction id; retries with the same one are not credited twice
+    pub reference: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PartnerDepositResponse {
+    pub success: bool,
+    /// False when this reference was already credited
+    pub created: bool,
+    pub deposit_id: Uuid,
+    pub reference: String,
+    /// Amount in USDC
+    pub amount: Money,
+    pub transfer_id: Option<Uuid>,
+    pub created_at: String,
+}
+
+impl PartnerDepositResponse {
+    fn new(deposit: PartnerDeposit, created: bool) -> Self {
+        Self {
+            success: true,
+            created,
+            deposit_id: deposit.id,
+            reference: deposit.reference,
+            amount: Money::usdc(deposit.amount),
+            transfer_id: deposit.transfer_id,
+            created_at: deposit.created_at.to_rfc3339(),
+        }
+    }
+}
+
+async fn create_deposit(State(state): State<PartnerApiState>, headers: HeaderMap, body: Bytes) -> Response {
+    const ENDPOINT: &str = "deposits";
+    let key = match authorize(&state, &headers, &body, PartnerScope::DepositCreate, ENDPOINT).await {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
+    // Parsed after authorization, as signatures cover the raw bytes
+    let req: PartnerDepositRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("invalid body: {}", e)),
+    };
+
+    let amount = req.amount;
+    let reference = req.reference.trim();
+    if !amount.is_positive() || reference.is_empty() || reference.len() > MAX_REFERENCE_LEN {
+        meter(&state, key.id, ENDPOINT, false, 0).await;
+        return error(StatusCode::BAD_REQUEST, format!("amount must be positive and reference 1-{} characters", MAX_REFERENCE_LEN));
+    }
+    let user = match state.users.find_by_phone(req.phone.trim()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            meter(&state, key.id, ENDPOINT, false, 0).await;
+            return error(StatusCode::NOT_FOUND, "no user with this phone number");
+        }
+        Err(e) => {
+            tracing::error!("User lookup failed: {}", e);
+            return error(StatusCode::INTERNAL_SERVER_ERROR, "database error");
+        }
+    };
+
+    match state.keys.credit_deposit(key.id, user.id, amount.micros(), reference).await {
+        Ok((deposit, created)) => {
+            meter(&state, key.id, ENDPOINT, true, if created { amount.micros() } else { 0 }).await;
+            if created {
+                tracing::info!(key = %key.id, partner = %key.partner, deposit = %deposit.id, %amount, "Partner deposit credited");
+                let notice = format!(
+                    "You received {} USDC from {}.\nRef: {}\nReply BALANCE to check.",
+                    amount.format(2), key.partner, deposit.reference
+                );
+                let notify = state.alerts.get(user.id).await.map_or(true, |settings| settings.deposit_notifications);
+                if notify {
+                    if let Err(e) = state.twilio.send_notification(&user.phone, &notice).await {
+                        tracing::warn!(deposit = %deposit.id, "Failed to notify user of partner deposit: {}", e);
+                    }
+                }
+                send_deposit_webhook(&state, &key, &deposit).await;
+            }
+            let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+            (status, Json(PartnerDepositResponse::new(deposit, created))).into_response()
+        }
+        Err(PartnerDepositError::ReferenceConflict) => {
+            meter(&state, key.id, ENDPOINT, false, 0).await;
+            error(StatusCode::CONFLICT, PartnerDepositError::ReferenceConflict)
+        }
+        Err(PartnerDepositError::Ledger(LedgerError::InvalidAmount)) => error(StatusCode::BAD_REQUEST, "invalid amount"),
+        Err(e) => {
+            tracing::error!(key = %key.id, "Partner deposit failed: {}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        }
+    }
+}
+
+/// Signed `deposit.credited` to the key's webhook URL, if it has one
+async fn send_deposit_webhook(state: &PartnerApiState, key: &PartnerKey, deposit: &PartnerDeposit) {
+    let Some(ref url) = key.webhook_url else {
+        return;
+    };
+    let secret = match state.keys.webhook_secret(key.id).await {
+        Ok(Some(secret)) => secret,
+        Ok(None) => return,
+        Err(e) => {
+            tracing::warn!(key = %key.id, deposit = %deposit.id, "Deposit webhook skipped: {}", e);
+            return;
+        }
+    };
+    let data = json!({
+        "deposit_id": deposit.id,
+        "reference": deposit.reference,
+        "amount": Money::usdc(deposit.amount),
+        "transfer_id": deposit.transfer_id,
+        "created_at": deposit.created_at.to_rfc3339(),
+    });
+    state.webhooks.send(url.clone(), secret, "deposit.credited", data);
+}
+
+/// Issue vouchers the partner distributes itself
+#[derive(Debug, Deserialize)]
+pub struct PartnerVoucherRequest {
+    pub count: usize,
+    /// USDC per voucher
+    pub usdc_amount: Money,
+    /// Letters before each code
+    #[serde(default)]
+    pub prefix: String,
+    pub expires_in_days: Option<i64>,
+}
+
+async fn create_vouchers(State(state): State<PartnerApiState>, headers: HeaderMap, body: Bytes) -> Response {
+    const ENDPOINT: &str = "vouchers";
+    let key = match authorize(&state, &headers, &body, PartnerScope::VoucherCreate, ENDPOINT).await {
+        Ok(key) => key,
+        Err(response) => return response,
+    };
+    // Parsed after authorization, as signatures cover the raw bytes
+    let req: PartnerVoucherRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => return error(StatusCode::BAD_REQUEST, format!("invalid body: {}", e)),
+    };
+
+    let amount = req.usdc_amount;
+    let valid_prefix = req.prefix.len() <= MAX_CODE_PREFIX_LEN && req.prefix.chars().all(|c| c.is_ascii_alphabetic());
+    if !(1..=MAX_VOUCHERS_PER_REQUEST).contains(&req.count) || !amount.is_positive() || !valid_prefix {
+        meter(&state, key.id, ENDPOINT, false, 0).await;
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "count must be 1-{}, usdc_amount positive and prefix at most {} letters",
+                MAX_VOUCHERS_PER_REQUEST, MAX_CODE_PREFIX_LEN
+            ),
+        );
+    }
+
+    let expires_at = req.expires_in_days.map(|days| Utc::now() + chrono::Duration::days(days));
+    match state.vouchers.create_generated(req.count, &req.prefix, amount, expires_at, None).await {
+        Ok((batch_id, vouchers)) => {
+            let issued = amount.checked_mul(vouchers.len() as i64).map_or(0, |total| total.micros());
+            meter(&state, key.id, ENDPOINT, true, issued).await;
+            tracing::info!(key = %key.id, partner = %key.partner, %batch_id, count = vouchers.len(), "Partner vouchers issued");
+            let codes: Vec<String> = vouchers.into_iter().map(|v| v.code).collect();
+            (StatusCode::CREATED, Json(json!({ "success": true, "batch_id": batch_id, "codes": codes }))).into_response()
+        }
+        Err(e) => {
+            tracing::error!(key = %key.id, "Partner voucher creation failed: {}", e);
+            error(StatusCode::INTERNAL_SERVER_ERROR, "database error")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter_windows() {
+        let limiter = RateLimiter::default();
+        let key = Uuid::new_v4();
+        assert!(limiter.check(key, 2, 600).is_ok());
+        assert!(limiter.check(key, 2, 610).is_ok());
+        assert_eq!(limiter.check(key, 2, 615), Err(45));
+        // Other keys have their own window
+        assert!(limiter.check(Uuid::new_v4(), 2, 615).is_ok());
+        // A new minute starts over
+        assert!(limiter.check(key, 2, 660).is_ok());
+        // 0 = unlimited
+        assert!((0..100).all(|_| limiter.check(key, 0, 661).is_ok()));
+    }
+}
@@ -0,0 +1,181 @@
+//! Signed webhooks to partners, and signed requests from them
+//!
+//! Every webhook sent to a partner carries
+//! `X-TextChain-Signature: t=<unix secs>,n=<nonce>,v1=<hex>`, where `v1` is
+//! HMAC-SHA256 of `<t>.<n>.<body>` keyed with the partner's webhook secret
+//! (`whsec_...`, issued by `POST /admin/partner-keys/:id/webhook`). Partners
+//! check it with `verify` or the same few lines in their own language, and
+//! should refuse timestamps more than `TOLERANCE_SECS` away and nonces they
+//! have already seen.
+//!
+//! Keys with `signed_requests` must sign their calls to `/partner/*` the
+//! same way. Stale ones are refused, and each nonce is accepted once
+//! (`partner_request_nonces`), so a captured request can't be replayed.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde_json::json;
+use sha2::Sha256;
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "x-textchain-signature";
+/// Largest difference between a signature's timestamp and our clock
+pub const TOLERANCE_SECS: i64 = 300;
+/// Tries per webhook before it is dropped
+const DELIVERY_ATTEMPTS: u32 = 3;
+const DELIVERY_TIMEOUT_SECS: u64 = 10;
+/// Longest nonce accepted from a partner
+const MAX_NONCE_LEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SignatureError {
+    #[error("missing {} header", SIGNATURE_HEADER)]
+    Missing,
+    #[error("malformed {} header", SIGNATURE_HEADER)]
+    Malformed,
+    #[error("signature timestamp is more than {}s away", TOLERANCE_SECS)]
+    Stale,
+    #[error("signature does not match")]
+    Mismatch,
+    #[error("nonce already used")]
+    Replayed,
+}
+
+/// A signature that checked out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signed {
+    pub timestamp: i64,
+    pub nonce: String,
+}
+
+/// A new webhook secret for a partner
+pub fn new_secret() -> String {
+    let mut secret = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut secret);
+    format!("whsec_{}", hex::encode(secret))
+}
+
+fn new_nonce() -> String {
+    let mut nonce = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    hex::encode(nonce)
+}
+
+fn mac(secret: &str, timestamp: i64, nonce: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(format!("{}.{}.", timestamp, nonce).as_bytes());
+    mac.update(body);
+    mac
+}
+
+/// `X-TextChain-Signature` value for a body
+pub fn sign(secret: &str, timestamp: i64, nonce: &str, body: &[u8]) -> String {
+    let signature = hex::encode(mac(secret, timestamp, nonce, body).finalize().into_bytes());
+    format!("t={},n={},v1={}", timestamp, nonce, signature)
+}
+
+/// Check an `X-TextChain-Signature` value against the body and the clock
+/// (`now`, unix seconds). Remembering nonces is up to the caller.
+pub fn verify(secret: &str, header: &str, body: &[u8], now: i64) -> Result<Signed, SignatureError> {
+    let (mut timestamp, mut nonce, mut signature) = (None, None, None);
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("n", value)) => nonce = Some(value),
+            Some(("v1", value)) => signature = hex::decode(value).ok(),
+            _ => {}
+        }
+    }
+    let (Some(timestamp), Some(nonce), Some(signature)) = (timestamp, nonce, signature) else {
+        return Err(SignatureError::Malformed);
+    };
+    if nonce.is_empty() || nonce.len() > MAX_NONCE_LEN || !nonce.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err(SignatureError::Malformed);
+    }
+    if (now - timestamp).abs() > TOLERANCE_SECS {
+        return Err(SignatureError::Stale);
+    }
+    mac(secret, timestamp, nonce, body)
+        .verify_slice(&signature)
+        .map_err(|_| SignatureError::Mismatch)?;
+    Ok(Signed { timestamp, nonce: nonce.to_string() })
+}
+
+/// Sends signed webhooks in the background
+#[derive(Clone)]
+pub struct PartnerWebhooks {
+    client: reqwest::Client,
+}
+
+impl Default for PartnerWebhooks {
+    fn default() -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+        Self { client }
+    }
+}
+
+impl PartnerWebhooks {
+    /// POST `{"id", "event", "created_at", "data"}` to `url`. Failed
+    /// deliveries are retried with a fresh signature; partners can drop
+    /// duplicates by `id`.
+    pub fn send(&self, url: String, secret: String, event: &'static str, data: serde_json::Value) {
+        let client = self.client.clone();
+        let id = Uuid::new_v4();
+        let body = json!({ "id": id, "event": event, "created_at": Utc::now().to_rfc3339(), "data": data }).to_string();
+        tokio::spawn(async move {
+            for attempt in 1..=DELIVERY_ATTEMPTS {
+                let signature = sign(&secret, Utc::now().timestamp(), &new_nonce(), body.as_bytes());
+                let result = client
+                    .post(&url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .header(SIGNATURE_HEADER, signature)
+                    .body(body.clone())
+                    .send()
+                    .await;
+                match result {
+                    Ok(response) if response.status().is_success() => {
+                        tracing::debug!(%id, event, attempt, "Partner webhook delivered");
+                        return;
+                    }
+                    Ok(response) => tracing::warn!(%id, event, attempt, status = %response.status(), "Partner webhook refused"),
+                    Err(e) => tracing::warn!(%id, event, attempt, "Partner webhook failed: {}", e),
+                }
+                tokio::time::sleep(Duration::from_secs(5u64.pow(attempt))).await;
+            }
+            tracing::error!(%id, event, url = %url, "Partner webhook dropped after {} attempts", DELIVERY_ATTEMPTS);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let secret = new_secret();
+        let body = br#"{"event":"deposit.credited"}"#;
+        let header = sign(&secret, 1_700_000_000, "abc123", body);
+        assert!(header.starts_with("t=1700000000,n=abc123,v1="));
+
+        let signed = verify(&secret, &header, body, 1_700_000_100).unwrap();
+        assert_eq!(signed, Signed { timestamp: 1_700_000_000, nonce: "abc123".to_string() });
+
+        assert_eq!(verify(&secret, &header, b"{}", 1_700_000_100), Err(SignatureError::Mismatch));
+        assert_eq!(verify(&new_secret(), &header, body, 1_700_000_100), Err(SignatureError::Mismatch));
+        assert_eq!(verify(&secret, &header, body, 1_700_000_000 + TOLERANCE_SECS + 1), Err(SignatureError::Stale));
+        assert_eq!(verify(&secret, "t=1700000000,v1=00", body, 1_700_000_000), Err(SignatureError::Malformed));
+
+        // The nonce is covered by the signature
+        let swapped = header.replace("n=abc123", "n=abc124");
+        assert_eq!(verify(&secret, &swapped, body, 1_700_000_000), Err(SignatureError::Mismatch));
+    }
+}
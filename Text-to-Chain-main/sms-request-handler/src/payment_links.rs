@@ -0,0 +1,32 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+
+use crate::db::PaymentLinkRepository;
+
+/// Public short-link routes for REQUEST payment links
+pub fn payment_link_routes(link_repo: PaymentLinkRepository) -> Router {
+    Router::new()
+        .route("/p/:code", get(open_payment_link))
+        .with_state(link_repo)
+}
+
+/// Redirect a short link to its EIP-681 URI, which mobile browsers hand
+/// to the installed wallet app
+async fn open_payment_link(
+    State(link_repo): State<PaymentLinkRepository>,
+    Path(code): Path<String>,
+) -> Response {
+    match link_repo.resolve(&code).await {
+        Ok(Some(uri)) => (StatusCode::FOUND, [(header::LOCATION, uri)]).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Payment link not found").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to resolve payment link: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Try again later").into_response()
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Cash payouts to outside addresses, and their refunds
+//!
+//! With `CASH_PAYOUT_CHAIN` set, `SEND <amount> USDC <address>` to an
+//! address that isn't a TextChain user debits the sender's cash balance and
+//! pays the USDC out from the hot wallet (`ADMIN_PRIVATE_KEY`) on that
+//! chain. The debit is recorded first, so a payout that can't be delivered
+//! always has something to give back:
+//!
+//! - a broadcast the RPC refuses is refunded on the spot, and the SEND reply
+//!   says nothing was taken. Only a refusal from the node counts: after a
+//!   timeout or dropped connection the transaction may still be out, so the
+//!   payout is marked sent under the hash signed for it and left to the poll;
+//! - a sent payout is followed by a background poll (`CASH_PAYOUT_POLL_SECS`)
+//!   until it is the chain's confirmation depth deep; if its transaction
+//!   reverts, the refund job credits the sender back, marks the payout
+//!   reversed and texts the sender, and the recipient too when the address
+//!   is a user's wallet.
+//!
+//! The hash is stored before the broadcast, so a payout that went out but
+//! couldn't be marked sent is still followed: the poll also takes pending
+//! payouts older than `STALE_PENDING_MINUTES`. A stale one with no hash was
+//! never signed, let alone sent, and is refunded.
+//!
+//! A transaction the chain has never seen is not refunded automatically - it
+//! could still be mined and the money would go out twice - but operators are
+//! alerted once it has been missing for `STUCK_MINUTES`.
+//!
+//! Payouts share one nonce-managed client and go out one at a time, so
+//! back-to-back sends get consecutive nonces.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use ethers::middleware::{NonceManagerMiddleware, SignerMiddleware};
+use ethers::providers::{Http, JsonRpcError, Middleware, MiddlewareError, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, BlockNumber, H256, U256, U64};
+use serde_json::json;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::alerts::{self, Alert};
+use crate::config::PayoutConfig;
+use crate::db::{LedgerError, Payout, PayoutRepository, UserRepository};
+use crate::events::{EventBus, Topic};
+use crate::money::Money;
+use crate::sms::display::short_hash;
+use crate::sms::SmsGateway;
+use crate::wallet::token_registry::token_address;
+use crate::wallet::{create_chain_provider, Chain, MultiChainProvider, IERC20};
+
+/// Sent payouts checked per poll
+const POLL_BATCH: i64 = 100;
+/// Minutes a sent payout may be missing from the chain before operators hear
+const STUCK_MINUTES: i64 = 30;
+/// Minutes after which a payout still pending is taken up by the poll; a
+/// send in progress is long done by then
+const STALE_PENDING_MINUTES: i64 = 10;
+/// Tries at marking a broadcast payout sent
+const MARK_SENT_ATTEMPTS: u32 = 3;
+
+/// Hot wallet client shared by every payout
+type PayoutClient = NonceManagerMiddleware<SignerMiddleware<Arc<Provider<Http>>, LocalWallet>>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PayoutError {
+    #[error("Invalid cash payout config: {0}")]
+    Config(String),
+}
+
+/// What became of a payout once it was looked up on chain
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    /// Mined and deep enough
+    Confirmed,
+    /// Mined but reverted
+    Failed,
+    /// Not mined yet, or not deep enough
+    Waiting,
+    /// Unknown to the chain for too long
+    Missing,
+}
+
+fn outcome(receipt: Option<(i64, bool)>, head: i64, required: u64, minutes_out: i64) -> Outcome {
+    match receipt {
+        Some((_, false)) => Outcome::Failed,
+        Some((block, true)) if (head - block + 1).max(0) as u64 >= required => Outcome::Confirmed,
+        Some(_) => Outcome::Waiting,
+        None if minutes_out >= STUCK_MINUTES => Outcome::Missing,
+        None => Outcome::Waiting,
+    }
+}
+
+/// Pays cash out on chain and follows each payout until it lands or is refunded
+#[derive(Clone)]
+pub struct Payouts {
+    repo: PayoutRepository,
+    users: UserRepository,
+    chain: Chain,
+    usdc: Address,
+    signer: LocalWallet,
+    /// Held for each send; replaced after a refusal, since the refused
+    /// transaction's nonce is free again
+    client: Arc<Mutex<Arc<PayoutClient>>>,
+    chains: MultiChainProvider,
+    twilio: SmsGateway,
+    events: EventBus,
+}
+
+impl Payouts {
+    /// None when `CASH_PAYOUT_CHAIN` is not set
+    pub fn from_config(
+        config: &PayoutConfig,
+        admin_private_key: &str,
+        repo: PayoutRepository,
+        users: UserRepository,
+        twilio: SmsGateway,
+        events: EventBus,
+    ) -> Result<Option<Self>, PayoutError> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        let chain = Chain::from_input(config.chain.trim())
+            .ok_or_else(|| PayoutError::Config(format!("CASH_PAYOUT_CHAIN {}", config.chain)))?;
+        let usdc = token_address(chain, "USDC")
+            .ok_or_else(|| PayoutError::Config(format!("no USDC address on {}", chain.name())))?;
+        let signer = admin_private_key
+            .trim()
+            .trim_start_matches("0x")
+            .parse::<LocalWallet>()
+            .map_err(|_| PayoutError::Config("ADMIN_PRIVATE_KEY is not a private key".to_string()))?
+            .with_chain_id(chain.chain_id());
+        Ok(Some(Self {
+            repo,
+            users,
+            chain,
+            usdc,
+            client: Arc::new(Mutex::new(new_client(chain, &signer))),
+            signer,
+            chains: MultiChainProvider::with_chains(&[chain]),
+            twilio,
+            events,
+        }))
+    }
+
+    pub fn chain(&self) -> Chain {
+        self.chain
+    }
+
+    /// Debit the sender and send the USDC; on `PayError::Refunded` the
+    /// node refused the transaction and the debit was given back. When it is
+    /// unknown whether the transaction went out, the payout is sent as far as
+    /// the caller is concerned and the poll settles it.
+    pub async fn pay(&self, user_id: Uuid, amount: Money, to_address: &str) -> Result<Payout, PayError> {
+        let mut payout = self.repo.create(user_id, amount, self.chain.short_code(), to_address).await?;
+        let tx_hash = match self.broadcast(&payout).await {
+            Ok(tx_hash) => tx_hash,
+            Err(Broadcast::Unknown { tx_hash, error }) => {
+                tracing::warn!(id = %payout.id, chain = %self.chain, %tx_hash, "Cash payout may not have gone out: {}", error);
+                tx_hash
+            }
+            Err(Broadcast::Refused(e)) => {
+                tracing::warn!(id = %payout.id, chain = %self.chain, "Cash payout not sent: {}", e);
+                match self.repo.refund(payout.id, "the transaction was refused").await? {
+                    Some(refunded) => self.publish(&refunded),
+                    None => tracing::error!(id = %payout.id, "Refused cash payout was already settled"),
+                }
+                return Err(PayError::Refunded(e));
+            }
+        };
+
+        self.mark_sent(&payout, &tx_hash).await;
+        tracing::info!(id = %payout.id, chain = %self.chain, %tx_hash, %amount, "Cash payout sent");
+        payout.status = "sent".to_string();
+        payout.tx_hash = Some(tx_hash);
+        self.publish(&payout);
+        Ok(payout)
+    }
+
+    /// The money is out either way; if it can't be marked sent, its stored
+    /// hash still gets it polled once it is stale, and operators are told
+    async fn mark_sent(&self, payout: &Payout, tx_hash: &str) {
+        let mut attempt = 1;
+        loop {
+            match self.repo.mark_sent(payout.id, tx_hash).await {
+                Ok(()) => return,
+                Err(e) if attempt < MARK_SENT_ATTEMPTS => {
+                    tracing::warn!(id = %payout.id, attempt, "Marking cash payout sent failed: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    tracing::error!(id = %payout.id, %tx_hash, "Sent cash payout not recorded: {}", e);
+                    alerts::raise(Alert::payout_unrecorded(&payout.id.to_string(), self.chain, tx_hash, &e.to_string()));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// ERC-20 transfer from the hot wallet; the ledger and USDC both use 6
+    /// decimals. The transaction is signed here so its hash is known, and
+    /// stored, before it is broadcast.
+    async fn broadcast(&self, payout: &Payout) -> Result<String, Broadcast> {
+        let to: Address = payout.to_address.parse().map_err(|_| Broadcast::Refused(format!("bad address {}", payout.to_address)))?;
+        let mut client = self.client.lock().await;
+        let refused = |client: &mut Arc<PayoutClient>, error: String| {
+            // The nonce handed out for this transaction is unused
+            *client = new_client(self.chain, &self.signer);
+            Broadcast::Refused(error)
+        };
+
+        let mut tx = IERC20::new(self.usdc, client.clone()).transfer(to, U256::from(payout.amount.micros() as u64)).tx;
+        if let Err(e) = client.fill_transaction(&mut tx, Some(BlockNumber::Pending.into())).await {
+            return Err(refused(&mut client, e.to_string()));
+        }
+        let signature = match self.signer.sign_transaction(&tx).await {
+            Ok(signature) => signature,
+            Err(e) => return Err(refused(&mut client, e.to_string())),
+        };
+        let tx_hash = format!("{:?}", tx.hash(&signature));
+        if let Err(e) = self.repo.set_tx_hash(payout.id, &tx_hash).await {
+            return Err(refused(&mut client, format!("hash not recorded: {}", e)));
+        }
+
+        let sent = client.send_raw_transaction(tx.rlp_signed(&signature)).await.map(|pending| pending.tx_hash());
+        match sent {
+            Ok(_) => Ok(tx_hash),
+            Err(e) => match refusal(e.as_error_response()) {
+                Some(reason) => Err(refused(&mut client, reason)),
+                None => Err(Broadcast::Unknown { tx_hash, error: e.to_string() }),
+            },
+        }
+    }
+
+    /// Check every sent payout, and every stale pending one, once; returns
+    /// how many were settled
+    pub async fn poll(&self) -> Result<usize, sqlx::Error> {
+        let payouts = self.repo.list_unsettled(POLL_BATCH, STALE_PENDING_MINUTES).await?;
+        if payouts.is_empty() {
+            return Ok(0);
+        }
+        let head = match self.chains.call(self.chain, |p| async move { p.get_block_number().await }).await {
+            Ok(head) => head.as_u64() as i64,
+            Err(e) => {
+                tracing::warn!(chain = %self.chain, "Skipping payout checks: {}", e);
+                return Ok(0);
+            }
+        };
+
+        let mut settled = 0;
+        for payout in payouts {
+            if payout.tx_hash.is_none() {
+                // Cut short before it was signed, so nothing went out
+                if self.refund(&payout, "the transaction was never sent").await? {
+                    settled += 1;
+                }
+                continue;
+            }
+            let tx_hash: H256 = payout.tx_hash.as_deref().and_then(|h| h.parse().ok()).unwrap_or_default();
+            let receipt = match self.chains.call(self.chain, |p| async move { p.get_transaction_receipt(tx_hash).await }).await {
+                Ok(receipt) => receipt.and_then(|r| Some((r.block_number?.as_u64() as i64, r.status != Some(U64::zero())))),
+                Err(e) => {
+                    tracing::warn!(id = %payout.id, "Payout check failed: {}", e);
+                    continue;
+                }
+            };
+            let minutes_out = (chrono::Utc::now() - payout.created_at).num_minutes();
+
+            match outcome(receipt, head, self.chain.default_confirmations(), minutes_out) {
+                Outcome::Confirmed => {
+                    if self.repo.mark_confirmed(payout.id).await? {
+                        tracing::info!(id = %payout.id, chain = %self.chain, "Cash payout confirmed");
+                        self.publish(&Payout { status: "confirmed".to_string(), ..payout });
+                        settled += 1;
+                    }
+                }
+                Outcome::Failed => {
+                    if self.refund(&payout, "the transaction failed").await? {
+                        settled += 1;
+                    }
+                }
+                Outcome::Missing => alerts::raise(Alert::payout_stuck(
+                    &payout.id.to_string(),
+                    self.chain,
+                    payout.tx_hash.as_deref().unwrap_or_default(),
+                    minutes_out,
+                )),
+                Outcome::Waiting => {}
+            }
+        }
+        Ok(settled)
+    }
+
+    /// Refund job: credit the sender back, mark the payout reversed and
+    /// tell both sides. False when it was already settled.
+    async fn refund(&self, payout: &Payout, reason: &str) -> Result<bool, sqlx::Error> {
+        let refunded = match self.repo.refund(payout.id, reason).await {
+            Ok(Some(refunded)) => refunded,
+            Ok(None) => return Ok(false),
+            Err(LedgerError::Database(e)) => return Err(e),
+            Err(e) => {
+                tracing::error!(id = %payout.id, "Cash payout refund failed: {}", e);
+                return Ok(false);
+            }
+        };
+        tracing::warn!(id = %payout.id, chain = %self.chain, reason, refund = ?refunded.refund_transfer_id, "Cash payout refunded");
+        self.publish(&refunded);
+
+        let amount = payout.amount.format_with_code(2);
+        let tx = short_hash(payout.tx_hash.as_deref().unwrap_or_default()).to_string();
+        if let Some(sender) = self.users.find_by_id(payout.user_id).await? {
+            let message = format!(
+                "Your send of {} to {} was reversed because {}.\n{} is back in your cash balance.\nTx {}",
+                amount, payout.to_address, reason, amount, tx
+            );
+            if let Err(e) = self.twilio.send_sms(&sender.phone, &message).await {
+                tracing::warn!(id = %payout.id, "Payout refund SMS not sent: {}", e);
+            }
+        }
+        if let Some(recipient) = self.users.find_by_wallet(&payout.to_address).await? {
+            let message = format!("A payment of {} to your wallet failed and was returned to the sender.\nTx {}", amount, tx);
+            if let Err(e) = self.twilio.send_sms(&recipient.phone, &message).await {
+                tracing::warn!(id = %payout.id, "Payout refund SMS not sent: {}", e);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Report a payout state change on the admin event stream
+    fn publish(&self, payout: &Payout) {
+        self.events.publish(Topic::Transfer, json!({
+            "kind": "payout",
+            "id": payout.id,
+            "status": payout.status,
+            "reason": payout.reason,
+            "chain": payout.chain,
+            "from": payout.user_id,
+            "to": payout.to_address,
+            "amount": payout.amount,
+            "token": payout.amount.currency().code(),
+            "tx_hash": payout.tx_hash,
+            "transfer_id": payout.transfer_id,
+            "refund_transfer_id": payout.refund_transfer_id,
+        }));
+    }
+
+    /// Follow sent payouts in the background
+    pub fn start(&self, poll_secs: u64) {
+        let payouts = self.clone();
+        let period = std::time::Duration::from_secs(poll_secs.max(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                ticker.tick().await;
+                match payouts.poll().await {
+                    Ok(0) => {}
+                    Ok(settled) => tracing::info!(settled, "Settled cash payouts"),
+                    Err(e) => tracing::warn!("Cash payout check failed: {}", e),
+                }
+            }
+        });
+    }
+}
+
+/// A broadcast that didn't go through
+#[derive(Debug)]
+enum Broadcast {
+    /// Not sent: refused by the node, or failed before it was sent
+    Refused(String),
+    /// Sent, but whether the node took it is unknown (timeout, dropped connection)
+    Unknown { tx_hash: String, error: String },
+}
+
+/// Why the node refused a transaction, from its JSON-RPC error. `None` when
+/// there was no error response, or the node already has the transaction.
+fn refusal(response: Option<&JsonRpcError>) -> Option<String> {
+    let response = response?;
+    let message = response.message.to_lowercase();
+    if message.contains("already known") || message.contains("known transaction") {
+        return None;
+    }
+    Some(response.message.clone())
+}
+
+fn new_client(chain: Chain, signer: &LocalWallet) -> Arc<PayoutClient> {
+    let client = SignerMiddleware::new(create_chain_provider(chain), signer.clone());
+    Arc::new(NonceManagerMiddleware::new(client, signer.address()))
+}
+
+/// Why a payout didn't go out
+#[derive(Debug, thiserror::Error)]
+pub enum PayError {
+    #[error(transparent)]
+    Ledger(#[from] LedgerError),
+    /// The node refused the transaction and the sender was refunded
+    #[error("Payout refunded: {0}")]
+    Refunded(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(outcome(Some((100, false)), 100, 12, 0), Outcome::Failed);
+        assert_eq!(outcome(Some((100, true)), 111, 12, 5), Outcome::Confirmed);
+        assert_eq!(outcome(Some((100, true)), 105, 12, 5), Outcome::Waiting);
+        assert_eq!(outcome(None, 105, 12, 5), Outcome::Waiting);
+        assert_eq!(outcome(None, 105, 12, STUCK_MINUTES), Outcome::Missing);
+    }
+
+    #[test]
+    fn test_refusal() {
+        let response = |message: &str| JsonRpcError { code: -32000, message: message.to_string(), data: None };
+        assert_eq!(refusal(None), None);
+        assert_eq!(refusal(Some(&response("insufficient funds for gas"))).as_deref(), Some("insufficient funds for gas"));
+        assert_eq!(refusal(Some(&response("nonce too low"))).as_deref(), Some("nonce too low"));
+        // Already in the node's pool: it went out
+        assert_eq!(refusal(Some(&response("already known"))), None);
+        assert_eq!(refusal(Some(&response("Known transaction: 0xabc"))), None);
+    }
+}
@@ -0,0 +1,299 @@
+//! Fiat exchange rates for local-currency amounts, and token prices
+//!
+//! Rates are quoted as local units per 1 USDC (treated as 1 USD). When
+//! `FX_RATES_URL` is set the live rate is fetched from it, with the static
+//! `FX_RATES` table used as a fallback if the source is unreachable.
+//!
+//! Token prices (`TOKEN_PRICES_URL`) are USD per token for the chains'
+//! native tokens and the registered ERC-20s, shown next to balances. They
+//! are cached for `TOKEN_PRICES_TTL_SECS`, including a failed fetch, so a
+//! feed that is down costs one slow request per TTL and balances fall back
+//! to amounts alone.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::RatesConfig;
+use crate::money::Money;
+use crate::wallet::token_registry::KNOWN_TOKENS;
+use crate::wallet::Chain;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RateError {
+    #[error("No exchange rate for {0}")]
+    Unsupported(String),
+    #[error("Invalid FX_RATES entry: {0}")]
+    InvalidSpec(String),
+}
+
+/// Exchange rate lookup for local-currency denominations
+#[derive(Debug, Clone)]
+pub struct FxRates {
+    fallback: HashMap<String, f64>,
+    source_url: Option<String>,
+    client: reqwest::Client,
+}
+
+impl FxRates {
+    pub fn new(fallback: HashMap<String, f64>, source_url: Option<String>) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap_or_default();
+        Self { fallback, source_url, client }
+    }
+
+    pub fn from_config(config: &RatesConfig) -> Result<Self, RateError> {
+        let source_url = Some(config.source_url.trim().to_string()).filter(|url| !url.is_empty());
+        Ok(Self::new(parse_rates(&config.fx_rates)?, source_url))
+    }
+
+    /// Local units per 1 USDC for `currency` (ISO 4217 code)
+    pub async fn rate(&self, currency: &str) -> Result<f64, RateError> {
+        let currency = currency.trim().to_uppercase();
+
+        if let Some(ref url) = self.source_url {
+            match self.fetch(url, &currency).await {
+                Ok(Some(rate)) => return Ok(rate),
+                Ok(None) => {}
+                Err(e) => tracing::warn!(currency = %currency, "FX source unavailable, using fallback rate: {}", e),
+            }
+        }
+
+        self.fallback
+            .get(&currency)
+            .copied()
+            .ok_or(RateError::Unsupported(currency))
+    }
+
+    /// Fetch from a `{"rates": {"KES": 129.5, ...}}` endpoint (USD base)
+    async fn fetch(&self, url: &str, currency: &str) -> Result<Option<f64>, reqwest::Error> {
+        let body: serde_json::Value = self.client.get(url).send().await?.error_for_status()?.json().await?;
+        Ok(body["rates"][currency].as_f64().filter(|rate| *rate > 0.0))
+    }
+}
+
+/// USD prices by token symbol; USDC is always 1
+#[derive(Debug, Clone, Default)]
+pub struct Prices(HashMap<String, f64>);
+
+impl Prices {
+    pub fn usd(&self, symbol: &str) -> Option<f64> {
+        let symbol = symbol.trim().to_uppercase();
+        if symbol == "USDC" {
+            return Some(1.0);
+        }
+        self.0.get(&symbol).copied()
+    }
+
+    /// ` (~$1,520)` for `amount` of `symbol`, or nothing without a price or
+    /// for dust and for USDC, whose amount already is dollars
+    pub fn annotate(&self, amount: f64, symbol: &str) -> String {
+        self.usd_value(amount, symbol).map(|value| format!(" (~{})", format_usd(value))).unwrap_or_default()
+    }
+
+    /// USD value worth showing next to `amount` of `symbol`, as `annotate` decides
+    pub fn usd_value(&self, amount: f64, symbol: &str) -> Option<f64> {
+        if symbol.eq_ignore_ascii_case("USDC") {
+            return None;
+        }
+        self.usd(symbol).map(|price| price * amount).filter(|value| *value >= 0.01)
+    }
+}
+
+/// Fetched prices, or None after a failed fetch
+#[derive(Debug)]
+struct PriceSnapshot {
+    fetched_at: Instant,
+    prices: Option<Prices>,
+}
+
+/// Token price feed with a TTL cache
+#[derive(Debug, Clone)]
+pub struct TokenPrices {
+    source_url: String,
+    ttl: Duration,
+    client: reqwest::Client,
+    cache: Arc<Mutex<Option<PriceSnapshot>>>,
+}
+
+impl TokenPrices {
+    /// None when TOKEN_PRICES_URL is unset
+    pub fn from_config(config: &RatesConfig) -> Option<Self> {
+        let source_url = config.price_url.trim();
+        if source_url.is_empty() {
+            return None;
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(3))
+            .build()
+            .unwrap_or_default();
+        Some(Self {
+            source_url: source_url.to_string(),
+            ttl: Duration::from_secs(config.price_ttl_secs),
+            client,
+            cache: Arc::default(),
+        })
+    }
+
+    /// Current prices; empty while the feed is down
+    pub async fn prices(&self) -> Prices {
+        if let Some(snapshot) = self.cache.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+            if snapshot.fetched_at.elapsed() < self.ttl {
+                return snapshot.prices.clone().unwrap_or_default();
+            }
+        }
+
+        let prices = match self.fetch().await {
+            Ok(prices) => Some(prices),
+            Err(e) => {
+                tracing::warn!("Token price feed unavailable, showing amounts only: {}", e);
+                None
+            }
+        };
+        *self.cache.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(PriceSnapshot { fetched_at: Instant::now(), prices: prices.clone() });
+        prices.unwrap_or_default()
+    }
+
+    /// Fetch from a `{"prices": {"ETH": 1520.4, ...}}` endpoint (USD)
+    async fn fetch(&self) -> Result<Prices, reqwest::Error> {
+        let body: serde_json::Value = self.client.get(&self.source_url).send().await?.error_for_status()?.json().await?;
+        Ok(parse_prices(&body))
+    }
+}
+
+/// Positive prices for the symbols balances can show: every chain's native
+/// token and the registered ERC-20s
+pub(crate) fn parse_prices(body: &serde_json::Value) -> Prices {
+    let Some(feed) = body["prices"].as_object() else {
+        return Prices::default();
+    };
+    let symbols = Chain::all()
+        .into_iter()
+        .map(|chain| chain.native_token())
+        .chain(KNOWN_TOKENS);
+    Prices(
+        symbols
+            .filter_map(|symbol| {
+                let price = feed
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case(symbol))
+                    .and_then(|(_, price)| price.as_f64())
+                    .filter(|price| price.is_finite() && *price > 0.0)?;
+                Some((symbol.to_string(), price))
+            })
+            .collect(),
+    )
+}
+
+/// Parse `KES:129.5,NGN:1550` into a rate table
+pub fn parse_rates(spec: &str) -> Result<HashMap<String, f64>, RateError> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (code, rate) = entry
+                .split_once(':')
+                .ok_or_else(|| RateError::InvalidSpec(entry.to_string()))?;
+            let rate: f64 = rate
+                .trim()
+                .parse()
+                .ok()
+                .filter(|rate: &f64| *rate > 0.0)
+                .ok_or_else(|| RateError::InvalidSpec(entry.to_string()))?;
+            Ok((code.trim().to_uppercase(), rate))
+        })
+        .collect()
+}
+
+/// USDC value of a local amount, rounded down to whole micro-USDC
+pub fn local_to_usdc(local_amount: f64, rate: f64) -> Money {
+    Money::usdc((local_amount / rate * 1_000_000.0).floor() as i64)
+}
+
+/// `1234567` as `1,234,567`
+fn group_thousands(whole: i64) -> String {
+    let digits = whole.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// Format a USD value for SMS: whole dollars from $100, cents below,
+/// e.g. `$1,520` or `$4.07`
+pub fn format_usd(value: f64) -> String {
+    if value >= 100.0 {
+        format!("${}", group_thousands(value.round() as i64))
+    } else {
+        format!("${:.2}", value)
+    }
+}
+
+/// Format a local amount for SMS, e.g. `KES 1,000` or `NGN 2,500.50`
+pub fn format_local(currency: &str, amount: f64) -> String {
+    let cents = (amount * 100.0).round() as i64;
+    let whole = cents / 100;
+    let fraction = cents % 100;
+    let grouped = group_thousands(whole);
+
+    if fraction == 0 {
+        format!("{} {}", currency, grouped)
+    } else {
+        format!("{} {}.{:02}", currency, grouped, fraction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rates() {
+        let rates = parse_rates("kes:129.5, NGN:1550").unwrap();
+        assert_eq!(rates.get("KES"), Some(&129.5));
+        assert_eq!(rates.get("NGN"), Some(&1550.0));
+        assert!(parse_rates("").unwrap().is_empty());
+        assert!(parse_rates("KES").is_err());
+        assert!(parse_rates("KES:0").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_rate_and_conversion() {
+        let rates = FxRates::new(parse_rates("KES:129.5").unwrap(), None);
+        let rate = rates.rate("kes").await.unwrap();
+        assert_eq!(local_to_usdc(1000.0, rate), Money::usdc(7_722_007));
+        assert!(matches!(rates.rate("NGN").await, Err(RateError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_format_local() {
+        assert_eq!(format_local("KES", 1000.0), "KES 1,000");
+        assert_eq!(format_local("NGN", 2500.5), "NGN 2,500.50");
+        assert_eq!(format_local("KES", 50.0), "KES 50");
+    }
+
+    #[test]
+    fn test_token_prices() {
+        let prices = parse_prices(&serde_json::json!({
+            "prices": {"eth": 3378.2, "MATIC": 0.52, "TXTC": 0, "DOGE": 0.1}
+        }));
+        assert_eq!(prices.usd("ETH"), Some(3378.2));
+        assert_eq!(prices.usd("USDC"), Some(1.0));
+        assert_eq!(prices.usd("TXTC"), None);
+        assert_eq!(prices.usd("DOGE"), None);
+
+        assert_eq!(prices.annotate(0.45, "ETH"), " (~$1,520)");
+        assert_eq!(prices.annotate(3.0, "MATIC"), " (~$1.56)");
+        assert_eq!(prices.annotate(0.0, "ETH"), "");
+        assert_eq!(prices.annotate(5.0, "USDC"), "");
+        assert_eq!(Prices::default().annotate(0.45, "ETH"), "");
+        assert!(parse_prices(&serde_json::json!({"error": "rate limited"})).usd("ETH").is_none());
+    }
+}
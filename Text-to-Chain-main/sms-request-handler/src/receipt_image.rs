@@ -0,0 +1,261 @@
+//! Receipt pictures for MMS
+//!
+//! Draws a signed receipt as a PNG: amount, sender, recipient, time and a
+//! QR code of the transaction. Text uses a built-in 5x7 bitmap font, so no
+//! font files or image libraries are needed. Twilio fetches the picture
+//! from `GET /receipts/image.png?proof=...` when it sends the MMS.
+
+use chrono::DateTime;
+use qrcode::{Color, QrCode};
+
+use crate::receipts::Receipt;
+use crate::wallet::address::{display_address, grayscale_png};
+
+const WIDTH: usize = 600;
+const MARGIN: usize = 24;
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+/// Pixels per font dot for ordinary lines and the amount
+const TEXT_SCALE: usize = 2;
+const AMOUNT_SCALE: usize = 5;
+/// Pixels per QR module, and blank modules around the code
+const QR_MODULE_PX: usize = 4;
+const QR_QUIET_ZONE: usize = 2;
+
+/// Rows of each glyph, leftmost dot in bit 4. Missing characters draw as `?`
+const FONT: &[(char, [u8; GLYPH_HEIGHT])] = &[
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('$', [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('@', [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E]),
+    ('A', [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    ('a', [0x00, 0x00, 0x0E, 0x01, 0x0F, 0x11, 0x0F]),
+    ('b', [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1E]),
+    ('c', [0x00, 0x00, 0x0E, 0x10, 0x10, 0x11, 0x0E]),
+    ('d', [0x01, 0x01, 0x0D, 0x13, 0x11, 0x11, 0x0F]),
+    ('e', [0x00, 0x00, 0x0E, 0x11, 0x1F, 0x10, 0x0E]),
+    ('f', [0x06, 0x09, 0x08, 0x1C, 0x08, 0x08, 0x08]),
+    ('g', [0x00, 0x0F, 0x11, 0x11, 0x0F, 0x01, 0x0E]),
+    ('h', [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11]),
+    ('i', [0x04, 0x00, 0x0C, 0x04, 0x04, 0x04, 0x0E]),
+    ('j', [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0C]),
+    ('k', [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12]),
+    ('l', [0x0C, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('m', [0x00, 0x00, 0x1A, 0x15, 0x15, 0x11, 0x11]),
+    ('n', [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11]),
+    ('o', [0x00, 0x00, 0x0E, 0x11, 0x11, 0x11, 0x0E]),
+    ('p', [0x00, 0x00, 0x1E, 0x11, 0x1E, 0x10, 0x10]),
+    ('q', [0x00, 0x00, 0x0D, 0x13, 0x0F, 0x01, 0x01]),
+    ('r', [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10]),
+    ('s', [0x00, 0x00, 0x0E, 0x10, 0x0E, 0x01, 0x1E]),
+    ('t', [0x08, 0x08, 0x1C, 0x08, 0x08, 0x09, 0x06]),
+    ('u', [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0D]),
+    ('v', [0x00, 0x00, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('w', [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0A]),
+    ('x', [0x00, 0x00, 0x11, 0x0A, 0x04, 0x0A, 0x11]),
+    ('y', [0x00, 0x00, 0x11, 0x11, 0x0F, 0x01, 0x0E]),
+    ('z', [0x00, 0x00, 0x1F, 0x02, 0x04, 0x08, 0x1F]),
+    ('|', [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
+
+/// One line of the picture and its scale
+struct Line {
+    text: String,
+    scale: usize,
+}
+
+impl Line {
+    fn new(text: impl Into<String>, scale: usize) -> Self {
+        Self { text: text.into(), scale }
+    }
+
+    fn height(&self) -> usize {
+        (GLYPH_HEIGHT + 4) * self.scale
+    }
+}
+
+/// 8-bit grayscale pixels, white to start with
+struct Canvas {
+    width: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self { width, pixels: vec![0xFF; width * height] }
+    }
+
+    fn fill(&mut self, left: usize, top: usize, size: usize) {
+        for row in top..top + size {
+            self.pixels[row * self.width + left..row * self.width + left + size].fill(0);
+        }
+    }
+
+    fn text(&mut self, left: usize, top: usize, text: &str, scale: usize) {
+        for (index, c) in text.chars().enumerate() {
+            let x = left + index * (GLYPH_WIDTH + 1) * scale;
+            for (dy, bits) in glyph(c).iter().enumerate() {
+                for dx in 0..GLYPH_WIDTH {
+                    if bits & (0x10 >> dx) != 0 {
+                        self.fill(x + dx * scale, top + dy * scale, scale);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    let find = |c| FONT.iter().find(|(g, _)| *g == c).map(|(_, rows)| *rows);
+    find(c).or_else(|| find('?')).unwrap_or_default()
+}
+
+/// Characters that fit across the picture at `scale`
+fn line_chars(scale: usize) -> usize {
+    (WIDTH - 2 * MARGIN) / ((GLYPH_WIDTH + 1) * scale)
+}
+
+/// `text` cut into lines of at most `width` characters
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    chars.chunks(width.max(1)).map(|chunk| chunk.iter().collect()).collect()
+}
+
+/// Lines of text, top to bottom
+fn receipt_lines(receipt: &Receipt) -> Vec<Line> {
+    let width = line_chars(TEXT_SCALE);
+    let mut lines = vec![
+        Line::new("TextChain receipt", TEXT_SCALE),
+        Line::new(receipt.amount_with_token(), AMOUNT_SCALE),
+    ];
+    let time = DateTime::from_timestamp(receipt.timestamp, 0)
+        .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+    for (label, value) in [
+        ("From", display_address(&receipt.from)),
+        ("To", display_address(&receipt.to)),
+        ("Time", time),
+        ("Tx", receipt.tx.clone()),
+    ] {
+        lines.push(Line::new(label, TEXT_SCALE));
+        lines.extend(wrap(&value, width).into_iter().map(|part| Line::new(part, TEXT_SCALE)));
+    }
+    lines
+}
+
+/// Render `receipt` as a PNG
+pub fn render(receipt: &Receipt) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(receipt.tx.as_bytes()).map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let qr_size = (modules + 2 * QR_QUIET_ZONE) * QR_MODULE_PX;
+
+    let lines = receipt_lines(receipt);
+    let text_height: usize = lines.iter().map(Line::height).sum();
+    let height = MARGIN + text_height + qr_size + MARGIN;
+    let mut canvas = Canvas::new(WIDTH, height);
+
+    let mut top = MARGIN;
+    for line in &lines {
+        canvas.text(MARGIN, top, &line.text, line.scale);
+        top += line.height();
+    }
+
+    let left = (WIDTH - qr_size) / 2 + QR_QUIET_ZONE * QR_MODULE_PX;
+    let top = top + QR_QUIET_ZONE * QR_MODULE_PX;
+    for (index, color) in code.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let (x, y) = (index % modules, index / modules);
+            canvas.fill(left + x * QR_MODULE_PX, top + y * QR_MODULE_PX, QR_MODULE_PX);
+        }
+    }
+
+    grayscale_png(&canvas.pixels, WIDTH, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn receipt(tx: &str) -> Receipt {
+        Receipt {
+            from: "0x1111111111111111111111111111111111111111".to_string(),
+            to: "0x2222222222222222222222222222222222222222".to_string(),
+            amount: 5_000_000,
+            token: "USDC".to_string(),
+            timestamp: 1_760_000_000,
+            tx: tx.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_lines_fit_the_picture() {
+        let tx = format!("0x{}", "ab".repeat(32));
+        let lines = receipt_lines(&receipt(&tx));
+        assert_eq!(lines[1].text, "5.00 USDC");
+        assert!(lines.iter().any(|line| line.text == "2025-10-09 08:53 UTC"));
+        for line in &lines {
+            assert!(line.text.chars().count() <= line_chars(line.scale), "{} overflows", line.text);
+        }
+        // The 66-character hash is split, nothing dropped
+        let tx_lines: Vec<&str> = lines.iter().skip_while(|line| line.text != "Tx").skip(1).map(|l| l.text.as_str()).collect();
+        assert_eq!(tx_lines.concat(), tx);
+    }
+
+    #[test]
+    fn test_render_png() {
+        let png = render(&receipt("ledger:7d9f1c2e-0b6a-4b8e-9a51-3f2d6c8e4a10")).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // Every character a receipt uses has its own glyph
+        for c in "TextChain receipt 0123456789.USDCETHTXTC:-ledgerxFromTo".chars() {
+            assert!(FONT.iter().any(|(g, _)| *g == c), "no glyph for {:?}", c);
+        }
+    }
+}
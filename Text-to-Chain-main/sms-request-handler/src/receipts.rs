@@ -0,0 +1,234 @@
+//! Signed payment receipts
+//!
+//! `RECEIPT <ref>` returns a proof for a transfer: the receipt fields
+//! (sender, recipient, amount, token, time, transaction) signed by the
+//! server's receipt key as an EIP-191 message. A merchant can check a proof
+//! with `GET /receipts/verify`, or offline against the address published at
+//! `GET /receipts/signer` using any Ethereum signature tool.
+//! `GET /receipts/image.png` draws a proof as a picture for MMS (see
+//! `receipt_image`).
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ethers::signers::{LocalWallet, Signer, WalletError};
+use ethers::types::{Address, Signature};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ReceiptConfig;
+use crate::money::{Currency, Money};
+use crate::receipt_image;
+
+/// Version tag at the start of every signed payload
+const PAYLOAD_VERSION: &str = "ttc-receipt/v1";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReceiptError {
+    #[error("Malformed proof")]
+    Malformed,
+    #[error("Bad signature: {0}")]
+    Signature(#[from] ethers::types::SignatureError),
+    #[error("Signed by {0:?}, not the TextChain receipt key")]
+    WrongSigner(Address),
+}
+
+/// What a receipt attests to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Receipt {
+    /// Sender wallet address, or ledger account for agents / system accounts
+    pub from: String,
+    pub to: String,
+    /// Amount in micro-units (6 decimals)
+    pub amount: i64,
+    pub token: String,
+    /// Unix seconds the transfer settled
+    pub timestamp: i64,
+    /// On-chain tx hash, or `ledger:<transfer id>` for internal transfers
+    pub tx: String,
+}
+
+impl Receipt {
+    /// Amount with its token to 2 decimals, e.g. `12.50 USDC`
+    pub fn amount_with_token(&self) -> String {
+        match Currency::parse(&self.token) {
+            Some(currency) => Money::from_micros(self.amount, currency).format_with_code(2),
+            None => format!("{} {}", Money::usdc(self.amount).format(2), self.token),
+        }
+    }
+
+    /// Canonical text that gets signed
+    fn payload(&self) -> String {
+        [
+            PAYLOAD_VERSION,
+            &self.tx,
+            &self.from,
+            &self.to,
+            &self.amount.to_string(),
+            &self.token,
+            &self.timestamp.to_string(),
+        ]
+        .join("|")
+    }
+
+    fn from_payload(payload: &str) -> Option<Self> {
+        let fields: Vec<&str> = payload.split('|').collect();
+        let [PAYLOAD_VERSION, tx, from, to, amount, token, timestamp] = fields[..] else {
+            return None;
+        };
+        Some(Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount: amount.parse().ok()?,
+            token: token.to_string(),
+            timestamp: timestamp.parse().ok()?,
+            tx: tx.to_string(),
+        })
+    }
+}
+
+/// Signs receipts with the server's receipt key
+#[derive(Clone)]
+pub struct ReceiptSigner {
+    wallet: LocalWallet,
+}
+
+impl ReceiptSigner {
+    /// None when `RECEIPT_SIGNING_KEY` is not set
+    pub fn from_config(config: &ReceiptConfig) -> Result<Option<Self>, WalletError> {
+        if config.signing_key.is_empty() {
+            return Ok(None);
+        }
+        let wallet = config.signing_key.trim_start_matches("0x").parse::<LocalWallet>()?;
+        Ok(Some(Self { wallet }))
+    }
+
+    /// Address merchants check proofs against
+    pub fn address(&self) -> Address {
+        self.wallet.address()
+    }
+
+    /// Compact proof: `<payload>.<signature>`, both base64url
+    pub async fn sign(&self, receipt: &Receipt) -> Result<String, WalletError> {
+        let payload = receipt.payload();
+        let signature = self.wallet.sign_message(payload.as_bytes()).await?;
+        Ok(format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload.as_bytes()),
+            URL_SAFE_NO_PAD.encode(signature.to_vec())
+        ))
+    }
+
+    /// Check a proof was signed by this key and return what it attests to
+    pub fn verify(&self, proof: &str) -> Result<Receipt, ReceiptError> {
+        let (payload, signature) = proof.trim().split_once('.').ok_or(ReceiptError::Malformed)?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| ReceiptError::Malformed)?;
+        let payload = String::from_utf8(payload).map_err(|_| ReceiptError::Malformed)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| ReceiptError::Malformed)?;
+        let signature = Signature::try_from(signature.as_slice())?;
+
+        let signer = signature.recover(payload.as_str())?;
+        if signer != self.address() {
+            return Err(ReceiptError::WrongSigner(signer));
+        }
+        Receipt::from_payload(&payload).ok_or(ReceiptError::Malformed)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VerifyQuery {
+    proof: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerifyResponse {
+    pub valid: bool,
+    pub signer: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<Receipt>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SignerResponse {
+    pub address: String,
+    pub scheme: &'static str,
+}
+
+/// Public receipt verification routes
+pub fn receipt_routes(signer: ReceiptSigner) -> Router {
+    Router::new()
+        .route("/receipts/verify", get(verify_receipt))
+        .route("/receipts/signer", get(receipt_signer))
+        .route("/receipts/image.png", get(receipt_picture))
+        .with_state(signer)
+}
+
+async fn verify_receipt(State(signer): State<ReceiptSigner>, Query(query): Query<VerifyQuery>) -> Json<VerifyResponse> {
+    let signer_address = format!("{:?}", signer.address());
+    match signer.verify(&query.proof) {
+        Ok(receipt) => Json(VerifyResponse { valid: true, signer: signer_address, receipt: Some(receipt), error: None }),
+        Err(e) => Json(VerifyResponse { valid: false, signer: signer_address, receipt: None, error: Some(e.to_string()) }),
+    }
+}
+
+/// A valid proof drawn as a PNG; only proofs we signed are drawn
+async fn receipt_picture(State(signer): State<ReceiptSigner>, Query(query): Query<VerifyQuery>) -> Response {
+    let receipt = match signer.verify(&query.proof) {
+        Ok(receipt) => receipt,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+    match receipt_image::render(&receipt) {
+        Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render receipt picture: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// The key to verify proofs against offline
+async fn receipt_signer(State(signer): State<ReceiptSigner>) -> Json<SignerResponse> {
+    Json(SignerResponse { address: format!("{:?}", signer.address()), scheme: "EIP-191 personal_sign" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer_for(key: &str) -> ReceiptSigner {
+        ReceiptSigner::from_config(&ReceiptConfig { signing_key: key.into() }).unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_sign_and_verify() {
+        let signer = signer_for("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318");
+        let receipt = Receipt {
+            from: "0x1111111111111111111111111111111111111111".to_string(),
+            to: "0x2222222222222222222222222222222222222222".to_string(),
+            amount: 5_000_000,
+            token: "USDC".to_string(),
+            timestamp: 1_760_000_000,
+            tx: "ledger:7d9f1c2e-0b6a-4b8e-9a51-3f2d6c8e4a10".to_string(),
+        };
+
+        let proof = signer.sign(&receipt).await.unwrap();
+        assert_eq!(signer.verify(&proof).unwrap(), receipt);
+
+        // Any edit to the payload breaks the signature
+        let (payload, signature) = proof.split_once('.').unwrap();
+        let tampered = String::from_utf8(URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap().replace("5000000", "9000000");
+        let tampered = format!("{}.{}", URL_SAFE_NO_PAD.encode(tampered), signature);
+        assert!(matches!(signer.verify(&tampered), Err(ReceiptError::WrongSigner(_))));
+
+        let other = signer_for("0x59c6995e998f97a5a0044966f0945389dc9e86dae88c7a8412f4603b6b78690d");
+        assert!(matches!(other.verify(&proof), Err(ReceiptError::WrongSigner(_))));
+        assert!(matches!(signer.verify("not-a-proof"), Err(ReceiptError::Malformed)));
+    }
+}
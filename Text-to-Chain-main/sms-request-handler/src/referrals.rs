@@ -0,0 +1,142 @@
+//! Referral program
+//!
+//! With `REFERRAL_BONUS` or `REFERRAL_NEW_USER_BONUS` set, `INVITE` gives a
+//! user a personal code. A new number that texts `INVITE <code>` and then
+//! creates its wallet earns both sides their bonus, paid on the ledger from
+//! `system:referrals`. Before paying, the sign-up is checked for the usual
+//! ways of farming bonuses:
+//!
+//! - the referrer is past `REFERRAL_MAX_PER_REFERRER` paid referrals, or
+//!   `REFERRAL_MAX_PER_DAY` in the last 24 hours;
+//! - the new number is in the referrer's own number block (same number but
+//!   for the last three digits), or in the block of someone they were
+//!   already paid for: numbers bought in bulk come in runs;
+//! - a cached carrier lookup says the number isn't a mobile line (VoIP,
+//!   toll-free, ...);
+//! - the new number moved to a new SIM within `SIM_SWAP_WINDOW_HOURS`, when
+//!   SIM swap checks are on: a fresh SIM in a reused handset is how one
+//!   device signs up many numbers.
+//!
+//! A sign-up that fails a check still gets its wallet; the referral is kept
+//! as `rejected` with the reason, for `/admin/referrals`.
+
+use uuid::Uuid;
+
+use crate::config::ReferralConfig;
+use crate::db::{Claim, LedgerError, ReferralFacts, ReferralRepository, Settlement};
+use crate::money::Money;
+
+/// Line types a bonus is paid for; an unknown line type passes
+const MOBILE_LINE_TYPES: &[&str] = &["mobile", "personal", "unknown"];
+
+#[derive(Clone)]
+pub struct Referrals {
+    repo: ReferralRepository,
+    referrer_bonus: Money,
+    new_user_bonus: Money,
+    max_per_referrer: i64,
+    max_per_day: i64,
+}
+
+impl Referrals {
+    /// None while both bonuses are 0
+    pub fn from_config(config: &ReferralConfig, repo: ReferralRepository) -> Option<Self> {
+        config.is_enabled().then(|| Self {
+            repo,
+            referrer_bonus: config.referrer_bonus,
+            new_user_bonus: config.new_user_bonus,
+            max_per_referrer: config.max_per_referrer,
+            max_per_day: config.max_per_day,
+        })
+    }
+
+    pub fn repo(&self) -> &ReferralRepository {
+        &self.repo
+    }
+
+    pub fn referrer_bonus(&self) -> Money {
+        self.referrer_bonus
+    }
+
+    pub fn new_user_bonus(&self) -> Money {
+        self.new_user_bonus
+    }
+
+    pub fn max_per_referrer(&self) -> i64 {
+        self.max_per_referrer
+    }
+
+    pub async fn claim(&self, code: &str, phone: &str) -> Result<Claim, sqlx::Error> {
+        self.repo.claim(code, phone).await
+    }
+
+    /// Pay (or turn down) the number's pending referral, once its wallet
+    /// exists; None when it didn't sign up with a code
+    pub async fn settle(&self, phone: &str, referee_id: Uuid, sim_swapped: bool) -> Result<Option<Settlement>, LedgerError> {
+        let settlement = self
+            .repo
+            .settle(phone, referee_id, self.referrer_bonus.micros(), self.new_user_bonus.micros(), |facts| {
+                rejection(facts, self.max_per_referrer, self.max_per_day, sim_swapped)
+            })
+            .await?;
+        match settlement {
+            Some(Settlement::Credited { referrer_id, .. }) => {
+                tracing::info!(referrer = %referrer_id, referee = %referee_id, "Referral bonus paid")
+            }
+            Some(Settlement::Rejected { referrer_id, reason }) => {
+                tracing::warn!(referrer = %referrer_id, referee = %referee_id, reason, "Referral bonus refused")
+            }
+            None => {}
+        }
+        Ok(settlement)
+    }
+}
+
+/// Why a sign-up isn't paid for, if it isn't
+fn rejection(facts: &ReferralFacts, max_per_referrer: i64, max_per_day: i64, sim_swapped: bool) -> Option<&'static str> {
+    if max_per_referrer > 0 && facts.credited_total >= max_per_referrer {
+        return Some("referrer_cap");
+    }
+    if max_per_day > 0 && facts.credited_today >= max_per_day {
+        return Some("daily_cap");
+    }
+    if facts.same_block_as_referrer {
+        return Some("referrer_block");
+    }
+    if facts.block_already_credited {
+        return Some("repeat_block");
+    }
+    if facts.line_type.as_deref().is_some_and(|line| !MOBILE_LINE_TYPES.contains(&line)) {
+        return Some("line_type");
+    }
+    if sim_swapped {
+        return Some("sim_swap");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejection() {
+        let clean = ReferralFacts { credited_total: 9, credited_today: 1, line_type: Some("mobile".to_string()), ..Default::default() };
+        assert_eq!(rejection(&clean, 10, 2, false), None);
+        assert_eq!(rejection(&ReferralFacts::default(), 10, 2, false), None);
+        // 0 = no limit
+        assert_eq!(rejection(&ReferralFacts { credited_total: 500, credited_today: 50, ..clean.clone() }, 0, 0, false), None);
+
+        let cases = [
+            (ReferralFacts { credited_total: 10, ..clean.clone() }, "referrer_cap"),
+            (ReferralFacts { credited_today: 2, ..clean.clone() }, "daily_cap"),
+            (ReferralFacts { same_block_as_referrer: true, ..clean.clone() }, "referrer_block"),
+            (ReferralFacts { block_already_credited: true, ..clean.clone() }, "repeat_block"),
+            (ReferralFacts { line_type: Some("nonFixedVoip".to_string()), ..clean.clone() }, "line_type"),
+        ];
+        for (facts, reason) in cases {
+            assert_eq!(rejection(&facts, 10, 2, false), Some(reason));
+        }
+        assert_eq!(rejection(&clean, 10, 2, true), Some("sim_swap"));
+    }
+}
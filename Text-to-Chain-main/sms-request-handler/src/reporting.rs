@@ -0,0 +1,602 @@
+//! Monthly statements of the custodial (ledger) balance
+//!
+//! A statement covers one calendar month in UTC: the opening balance, each
+//! ledger entry grouped as a deposit, receipt, send, cash-out or fee, and
+//! the closing balance. STATEMENT texts the summary; the full statement is
+//! rendered here as CSV or PDF, both for the admin API and for short-lived
+//! links served under `/statements/<token>`.
+
+use std::fmt;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::commands::receipts::transfer_ref;
+use crate::db::{user_account, StatementEntry, StatementRepository, User, UserRepository, PARTNER_DEPOSIT_KIND};
+use crate::money::Money;
+use crate::voucher_cards::pdf_text;
+use crate::wallet::address::display_address;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 48.0;
+const ROW_HEIGHT: f32 = 14.0;
+/// Table rows on the first page (below the summary) and on later pages
+const FIRST_PAGE_ROWS: usize = 36;
+const PAGE_ROWS: usize = 50;
+/// Left edge of each table column: date, type, description, amount, balance, ref
+const COLUMNS: [f32; 6] = [MARGIN, 118.0, 186.0, 330.0, 410.0, 490.0];
+
+const CATALOG_ID: Ref = Ref::new(1);
+const PAGE_TREE_ID: Ref = Ref::new(2);
+const FONT_REGULAR_ID: Ref = Ref::new(3);
+const FONT_BOLD_ID: Ref = Ref::new(4);
+/// First id used for per-page objects (page + content stream)
+const FIRST_PAGE_ID: i32 = 5;
+
+const FONT_REGULAR: Name = Name(b"F1");
+const FONT_BOLD: Name = Name(b"F2");
+
+/// A calendar month (UTC)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Month {
+    year: i32,
+    month: u32,
+}
+
+impl Month {
+    pub fn containing(date: NaiveDate) -> Self {
+        Self { year: date.year(), month: date.month() }
+    }
+
+    /// `2026-09`, or a month name (`SEP`, `september`) meaning its most
+    /// recent occurrence up to `today`
+    pub fn parse(text: &str, today: NaiveDate) -> Option<Self> {
+        let text = text.trim();
+        if let Some((year, month)) = text.split_once('-') {
+            let date = NaiveDate::from_ymd_opt(year.parse().ok()?, month.parse().ok()?, 1)?;
+            return Some(Self::containing(date));
+        }
+        let month = text.parse::<chrono::Month>().ok()?.number_from_month();
+        let year = if month > today.month() { today.year() - 1 } else { today.year() };
+        Some(Self { year, month })
+    }
+
+    pub fn first_day(self) -> NaiveDate {
+        NaiveDate::from_ymd_opt(self.year, self.month, 1).expect("months are built from valid dates")
+    }
+
+    pub fn next(self) -> Self {
+        match self.month {
+            12 => Self { year: self.year + 1, month: 1 },
+            month => Self { year: self.year, month: month + 1 },
+        }
+    }
+
+    pub fn previous(self) -> Self {
+        match self.month {
+            1 => Self { year: self.year - 1, month: 12 },
+            month => Self { year: self.year, month: month - 1 },
+        }
+    }
+
+    pub fn start(self) -> DateTime<Utc> {
+        self.first_day().and_hms_opt(0, 0, 0).expect("midnight").and_utc()
+    }
+
+    /// Start of the next month
+    pub fn end(self) -> DateTime<Utc> {
+        self.next().start()
+    }
+
+    /// `Sep 2026`
+    pub fn label(self) -> String {
+        self.first_day().format("%b %Y").to_string()
+    }
+}
+
+/// `2026-09`
+impl fmt::Display for Month {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}", self.year, self.month)
+    }
+}
+
+impl Serialize for Month {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// How a statement line moved the balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Money in from outside the ledger (agents, partners, ...)
+    Deposit,
+    /// Money in from another user
+    Received,
+    /// Money out to another user
+    Sent,
+    /// Money out of the ledger (agent cash-outs)
+    Withdrawal,
+    Fee,
+}
+
+impl Category {
+    /// Fees are entries of kind `fee` or `*_fee`; the rest are grouped by
+    /// direction and by whether a user is on the other side
+    pub fn of(entry: &StatementEntry) -> Self {
+        let to_user = entry.counterparty.starts_with("user:");
+        match (entry.delta > 0, to_user) {
+            _ if entry.kind == "fee" || entry.kind.ends_with("_fee") => Category::Fee,
+            (true, true) => Category::Received,
+            (true, false) => Category::Deposit,
+            (false, true) => Category::Sent,
+            (false, false) => Category::Withdrawal,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Deposit => "Deposit",
+            Category::Received => "Received",
+            Category::Sent => "Sent",
+            Category::Withdrawal => "Cash out",
+            Category::Fee => "Fee",
+        }
+    }
+}
+
+/// One ledger entry on a statement
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementLine {
+    pub at: DateTime<Utc>,
+    pub category: Category,
+    pub description: String,
+    /// Negative when money left the account
+    pub amount: Money,
+    /// Balance after this line
+    pub balance: Money,
+    /// Short transfer ref, as used by RECEIPT
+    pub reference: String,
+}
+
+/// A month of activity on a user's cash balance
+#[derive(Debug, Clone, Serialize)]
+pub struct Statement {
+    pub month: Month,
+    /// ENS name or wallet address of the account holder
+    pub holder: String,
+    pub opening: Money,
+    pub deposits: Money,
+    pub received: Money,
+    /// Totals of money leaving the account are negative
+    pub sent: Money,
+    pub withdrawals: Money,
+    pub fees: Money,
+    pub closing: Money,
+    pub lines: Vec<StatementLine>,
+}
+
+impl Statement {
+    /// Group `entries` (oldest first) on top of the `opening` balance, both
+    /// in micro-USDC
+    pub fn build(month: Month, holder: String, opening: i64, entries: &[StatementEntry]) -> Self {
+        let mut balance = opening;
+        let mut totals = [0i64; 5];
+        let lines = entries
+            .iter()
+            .map(|entry| {
+                let category = Category::of(entry);
+                balance += entry.delta;
+                totals[category as usize] += entry.delta;
+                StatementLine {
+                    at: entry.created_at,
+                    category,
+                    description: describe(&entry.kind),
+                    amount: Money::usdc(entry.delta),
+                    balance: Money::usdc(balance),
+                    reference: transfer_ref(entry.transfer_id),
+                }
+            })
+            .collect();
+
+        let [deposits, received, sent, withdrawals, fees] = totals.map(Money::usdc);
+        Self {
+            month,
+            holder,
+            opening: Money::usdc(opening),
+            deposits,
+            received,
+            sent,
+            withdrawals,
+            fees,
+            closing: Money::usdc(balance),
+            lines,
+        }
+    }
+
+    /// SMS reply; `to_date` marks a month that has not ended yet
+    pub fn sms_summary(&self, to_date: bool) -> String {
+        let mut summary = format!(
+            "Statement {}{}\nOpening {}\nDeposits {}",
+            self.month.label(),
+            if to_date { " (to date)" } else { "" },
+            self.opening.format(2),
+            signed(self.deposits)
+        );
+        if self.received.micros() != 0 {
+            summary.push_str(&format!("\nReceived {}", signed(self.received)));
+        }
+        summary.push_str(&format!("\nSent {}", signed(self.sent)));
+        if self.withdrawals.micros() != 0 {
+            summary.push_str(&format!("\nCash out {}", signed(self.withdrawals)));
+        }
+        summary.push_str(&format!("\nFees {}\nClosing {}", signed(self.fees), self.closing));
+        summary
+    }
+
+    /// One row per line between opening and closing balance rows
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("date,type,description,amount,balance,ref\n");
+        let mut row = |date: String, kind: &str, description: &str, amount: String, balance: Money, reference: &str| {
+            let fields = [date, kind.to_string(), description.to_string(), amount, balance.format(6), reference.to_string()];
+            csv.push_str(&fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+            csv.push('\n');
+        };
+
+        row(self.month.first_day().to_string(), "opening", "Opening balance", String::new(), self.opening, "");
+        for line in &self.lines {
+            row(
+                line.at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                line.category.label(),
+                &line.description,
+                line.amount.format(6),
+                line.balance,
+                &line.reference,
+            );
+        }
+        let last_day = self.month.next().first_day().pred_opt().unwrap_or(self.month.first_day());
+        row(last_day.to_string(), "closing", "Closing balance", String::new(), self.closing, "");
+        csv
+    }
+
+    /// A4 pages: summary on the first, then every line
+    pub fn to_pdf(&self) -> Vec<u8> {
+        let mut pages: Vec<&[StatementLine]> = vec![&self.lines[..self.lines.len().min(FIRST_PAGE_ROWS)]];
+        if self.lines.len() > FIRST_PAGE_ROWS {
+            pages.extend(self.lines[FIRST_PAGE_ROWS..].chunks(PAGE_ROWS));
+        }
+        let page_ids: Vec<Ref> = (0..pages.len()).map(|i| Ref::new(FIRST_PAGE_ID + 2 * i as i32)).collect();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(CATALOG_ID).pages(PAGE_TREE_ID);
+        pdf.pages(PAGE_TREE_ID).kids(page_ids.iter().copied()).count(pages.len() as i32);
+        pdf.type1_font(FONT_REGULAR_ID).base_font(Name(b"Helvetica"));
+        pdf.type1_font(FONT_BOLD_ID).base_font(Name(b"Helvetica-Bold"));
+
+        let page_count = pages.len();
+        for (index, (lines, page_id)) in pages.into_iter().zip(page_ids).enumerate() {
+            let content_id = Ref::new(page_id.get() + 1);
+
+            let mut content = Content::new();
+            let mut y = PAGE_HEIGHT - MARGIN;
+            if index == 0 {
+                y = self.draw_summary(&mut content, y);
+            }
+            draw_table(&mut content, lines, y);
+            let footer = format!("{} - {} - page {} of {}", self.holder, self.month, index + 1, page_count);
+            text(&mut content, FONT_REGULAR, 7.0, MARGIN, MARGIN / 2.0, &footer);
+            let data = content.finish();
+
+            let mut page = pdf.page(page_id);
+            page.parent(PAGE_TREE_ID)
+                .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .contents(content_id);
+            page.resources().fonts().pair(FONT_REGULAR, FONT_REGULAR_ID).pair(FONT_BOLD, FONT_BOLD_ID);
+            page.finish();
+
+            pdf.stream(content_id, &data);
+        }
+
+        pdf.finish()
+    }
+
+    /// Title and totals from `top` down; returns where the table starts
+    fn draw_summary(&self, content: &mut Content, top: f32) -> f32 {
+        let mut y = top - 16.0;
+        text(content, FONT_BOLD, 16.0, MARGIN, y, "TEXTCHAIN STATEMENT");
+        y -= 20.0;
+        text(content, FONT_REGULAR, 10.0, MARGIN, y, &format!("{} - {}", self.month.label(), self.holder));
+        y -= 24.0;
+
+        let totals = [
+            ("Opening balance", self.opening.format(2)),
+            ("Deposits", signed(self.deposits)),
+            ("Received", signed(self.received)),
+            ("Sent", signed(self.sent)),
+            ("Cash out", signed(self.withdrawals)),
+            ("Fees", signed(self.fees)),
+            ("Closing balance", format!("{}", self.closing)),
+        ];
+        for (label, amount) in totals {
+            let font = if label.ends_with("balance") { FONT_BOLD } else { FONT_REGULAR };
+            text(content, font, 10.0, MARGIN, y, label);
+            text(content, font, 10.0, COLUMNS[2], y, &amount);
+            y -= ROW_HEIGHT;
+        }
+        y - 16.0
+    }
+}
+
+/// Header row and `lines` from `top` down
+fn draw_table(content: &mut Content, lines: &[StatementLine], top: f32) {
+    let header = ["Date", "Type", "Description", "Amount", "Balance", "Ref"];
+    for (x, title) in COLUMNS.iter().zip(header) {
+        text(content, FONT_BOLD, 8.0, *x, top, title);
+    }
+    content
+        .set_stroke_gray(0.6)
+        .set_line_width(0.5)
+        .move_to(MARGIN, top - 4.0)
+        .line_to(PAGE_WIDTH - MARGIN, top - 4.0)
+        .stroke();
+
+    let mut y = top - ROW_HEIGHT;
+    if lines.is_empty() {
+        text(content, FONT_REGULAR, 8.0, MARGIN, y, "No activity this month");
+    }
+    for line in lines {
+        let cells = [
+            line.at.format("%Y-%m-%d %H:%M").to_string(),
+            line.category.label().to_string(),
+            line.description.clone(),
+            signed(line.amount),
+            line.balance.format(2),
+            line.reference.clone(),
+        ];
+        for (x, cell) in COLUMNS.iter().zip(&cells) {
+            text(content, FONT_REGULAR, 8.0, *x, y, cell);
+        }
+        y -= ROW_HEIGHT;
+    }
+}
+
+fn text(content: &mut Content, font: Name, size: f32, x: f32, y: f32, value: &str) {
+    content
+        .set_fill_gray(0.0)
+        .begin_text()
+        .set_font(font, size)
+        .next_line(x, y)
+        .show(Str(pdf_text(value).as_bytes()))
+        .end_text();
+}
+
+/// `+5.00`, `-3.25`, `0.00`
+fn signed(amount: Money) -> String {
+    match amount.micros() {
+        micros if micros > 0 => format!("+{}", amount.format(2)),
+        _ => amount.format(2),
+    }
+}
+
+/// Readable name for a ledger entry kind
+fn describe(kind: &str) -> String {
+    match kind {
+        "p2p" => "Transfer".to_string(),
+        "cashin" => "Agent cash-in".to_string(),
+        "cashout" => "Agent cash-out".to_string(),
+        PARTNER_DEPOSIT_KIND => "Partner deposit".to_string(),
+        other => other.replace('_', " "),
+    }
+}
+
+/// Quote a CSV field when it holds a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// What a full statement is rendered as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatementFormat {
+    Json,
+    Csv,
+    Pdf,
+}
+
+impl StatementFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_ascii_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "pdf" => Some(Self::Pdf),
+            _ => None,
+        }
+    }
+
+    /// The statement as a download (JSON is returned inline)
+    pub fn respond(self, statement: &Statement) -> Response {
+        let attachment = |extension: &str| format!("attachment; filename=\"statement-{}.{}\"", statement.month, extension);
+        match self {
+            Self::Json => Json(statement).into_response(),
+            Self::Csv => (
+                [
+                    (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                    (header::CONTENT_DISPOSITION, attachment("csv")),
+                ],
+                statement.to_csv(),
+            )
+                .into_response(),
+            Self::Pdf => (
+                [
+                    (header::CONTENT_TYPE, "application/pdf".to_string()),
+                    (header::CONTENT_DISPOSITION, attachment("pdf")),
+                ],
+                statement.to_pdf(),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// Repositories a statement is built from
+#[derive(Clone)]
+pub struct StatementState {
+    pub statements: StatementRepository,
+    pub users: UserRepository,
+}
+
+impl StatementState {
+    pub async fn load(&self, user: &User, month: Month) -> Result<Statement, sqlx::Error> {
+        load_statement(&self.statements, user, month).await
+    }
+}
+
+/// `user`'s statement for `month`
+pub async fn load_statement(statements: &StatementRepository, user: &User, month: Month) -> Result<Statement, sqlx::Error> {
+    let account = user_account(user.id);
+    let opening = statements.balance_at(&account, month.start()).await?;
+    let entries = statements.entries(&account, month.start(), month.end()).await?;
+    let holder = user.ens_name.clone().unwrap_or_else(|| display_address(&user.wallet_address));
+    Ok(Statement::build(month, holder, opening, &entries))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StatementQuery {
+    /// `YYYY-MM`; admin requests default to last month
+    pub month: Option<String>,
+    /// `pdf` or `csv` on links, also `json` (the default) for admins
+    pub format: Option<String>,
+}
+
+/// Public routes for STATEMENT download links
+pub fn statement_routes(state: StatementState) -> Router {
+    Router::new()
+        .route("/statements/:token", get(open_statement_link))
+        .with_state(state)
+}
+
+/// Full statement behind a link, as PDF unless `?format=csv`
+async fn open_statement_link(
+    State(state): State<StatementState>,
+    Path(token): Path<String>,
+    Query(query): Query<StatementQuery>,
+) -> Response {
+    let format = match query.format.as_deref().map(StatementFormat::parse) {
+        None => StatementFormat::Pdf,
+        Some(Some(format)) if format != StatementFormat::Json => format,
+        Some(_) => return (StatusCode::BAD_REQUEST, "format must be pdf or csv").into_response(),
+    };
+
+    let (user_id, month) = match state.statements.resolve_link(&token).await {
+        Ok(Some(link)) => link,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Link expired. Text STATEMENT for a new one.").into_response(),
+        Err(e) => {
+            tracing::error!("Failed to resolve statement link: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, "Try again later").into_response();
+        }
+    };
+    let statement = match state.users.find_by_id(user_id).await {
+        Ok(Some(user)) => state.load(&user, Month::containing(month)).await,
+        Ok(None) => return (StatusCode::NOT_FOUND, "Link expired. Text STATEMENT for a new one.").into_response(),
+        Err(e) => Err(e),
+    };
+    match statement {
+        Ok(statement) => format.respond(&statement),
+        Err(e) => {
+            tracing::error!("Failed to load statement: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "Try again later").into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use uuid::Uuid;
+
+    fn entry(delta: i64, kind: &str, counterparty: &str, day: u32) -> StatementEntry {
+        StatementEntry {
+            transfer_id: Uuid::new_v4(),
+            delta,
+            kind: kind.to_string(),
+            counterparty: counterparty.to_string(),
+            created_at: Utc.with_ymd_and_hms(2026, 9, day, 12, 0, 0).unwrap(),
+        }
+    }
+
+    fn september() -> Month {
+        Month::parse("2026-09", NaiveDate::from_ymd_opt(2026, 10, 16).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_month_parse() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        assert_eq!(Month::parse("SEP", today), Some(september()));
+        assert_eq!(Month::parse("september", today), Some(september()));
+        assert_eq!(Month::parse("Dec", today).map(|m| m.to_string()), Some("2025-12".to_string()));
+        assert_eq!(Month::parse("2026-13", today), None);
+        assert_eq!(Month::parse("soon", today), None);
+
+        assert_eq!(september().label(), "Sep 2026");
+        assert_eq!(september().end(), Utc.with_ymd_and_hms(2026, 10, 1, 0, 0, 0).unwrap());
+        assert_eq!(Month::containing(NaiveDate::from_ymd_opt(2026, 1, 5).unwrap()).previous().to_string(), "2025-12");
+    }
+
+    #[test]
+    fn test_statement_totals() {
+        let entries = [
+            entry(25_000_000, "cashin", "agent:1", 2),
+            entry(5_000_000, "p2p", "user:2", 3),
+            entry(-12_000_000, "p2p", "user:3", 4),
+            entry(-3_000_000, "cashout", "agent:1", 5),
+            entry(-250_000, "send_fee", "system:fees", 5),
+        ];
+        let statement = Statement::build(september(), "alice.ttcip.eth".to_string(), 10_000_000, &entries);
+
+        assert_eq!(statement.deposits, Money::usdc(25_000_000));
+        assert_eq!(statement.received, Money::usdc(5_000_000));
+        assert_eq!(statement.sent, Money::usdc(-12_000_000));
+        assert_eq!(statement.withdrawals, Money::usdc(-3_000_000));
+        assert_eq!(statement.fees, Money::usdc(-250_000));
+        assert_eq!(statement.closing, Money::usdc(24_750_000));
+        assert_eq!(statement.lines[2].balance, Money::usdc(28_000_000));
+
+        let sms = statement.sms_summary(false);
+        assert_eq!(
+            sms,
+            "Statement Sep 2026\nOpening 10.00\nDeposits +25.00\nReceived +5.00\nSent -12.00\nCash out -3.00\nFees -0.25\nClosing 24.75 USDC"
+        );
+
+        let csv = statement.to_csv();
+        assert_eq!(csv.lines().count(), 1 + entries.len() + 2);
+        assert!(csv.lines().nth(1).unwrap().starts_with("2026-09-01,opening,Opening balance,,10.000000,"));
+        assert!(csv.lines().last().unwrap().starts_with("2026-09-30,closing,"));
+    }
+
+    #[test]
+    fn test_statement_pdf_paginates() {
+        let entries: Vec<StatementEntry> = (0..FIRST_PAGE_ROWS + 1).map(|_| entry(1_000_000, "cashin", "agent:1", 1)).collect();
+        let pdf = Statement::build(september(), "0x12...34".to_string(), 0, &entries).to_pdf();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(String::from_utf8_lossy(&pdf).contains("/Count 2"));
+
+        let empty = Statement::build(september(), "0x12...34".to_string(), 0, &[]);
+        assert!(String::from_utf8_lossy(&empty.to_pdf()).contains("/Count 1"));
+        assert_eq!(empty.sms_summary(true).lines().next(), Some("Statement Sep 2026 (to date)"));
+    }
+}
@@ -1,23 +1,69 @@
 use axum::{
+    extract::State,
+    http::header,
+    response::IntoResponse,
     routing::{get, post},
-    Router,
+    Json, Router,
 };
 use std::sync::Arc;
 use tower_http::trace::TraceLayer;
 
-use crate::admin::{admin_routes, AdminState};
+use crate::admin::{admin_routes, require_admin_token, AdminState};
+use crate::admin_agents::admin_agent_routes;
+use crate::admin_audit::{admin_audit_routes, audit_admin_requests};
+use crate::admin_beta::{admin_beta_routes, AdminBetaState};
+use crate::admin_broadcast::{admin_broadcast_routes, AdminBroadcastState};
+use crate::admin_campaigns::admin_campaign_routes;
+use crate::admin_chains::admin_chain_routes;
+use crate::admin_commands::admin_command_routes;
+use crate::admin_config::admin_config_routes;
+use crate::admin_ens::admin_ens_routes;
+use crate::admin_features::admin_feature_routes;
+use crate::admin_idempotency::idempotent_admin_requests;
+use crate::admin_kill_switch::{admin_kill_switch_routes, AdminKillSwitchState};
+use crate::admin_kyc::admin_kyc_routes;
+use crate::admin_partner_keys::admin_partner_key_routes;
+use crate::admin_referrals::admin_referral_routes;
+use crate::admin_reserved_names::admin_reserved_name_routes;
+use crate::admin_spam::{admin_spam_routes, AdminSpamState};
+use crate::admin_statements::admin_statement_routes;
+use crate::admin_tokens::admin_token_routes;
+use crate::admin_transcripts::admin_transcript_routes;
+use crate::admin_treasury::{admin_treasury_routes, AdminTreasuryState};
 use crate::admin_wallet::admin_wallet_routes;
 use crate::commands::CommandProcessor;
-use crate::db::VoucherRepository;
-use crate::sms::{incoming_sms_handler, incoming_sms_json_handler, TwilioClient};
+use crate::commands::metrics::CommandReport;
+use crate::deposit_watcher::{deposit_routes, DepositIntake};
+use crate::db::{AgentRepository, AuditLogRepository, CampaignRepository, DbPools, IdempotencyRepository, KycRepository, PaymentLinkRepository, PoolMetrics, ReferralRepository, TranscriptRepository, UnrecognizedCommandRepository};
+use crate::email::{email_routes, EmailChannel};
+use crate::events::{admin_event_routes, AdminEventsState};
+use crate::deposit_sweeper::{sweep_routes, DepositSweeper};
+use crate::ens_verifier::{admin_ens_check_routes, ens_metrics_routes, EnsVerifier};
+use crate::gas_monitor::{gas_routes, GasMonitor};
+use crate::graphql::{graphql_routes, GraphqlState};
+use crate::live_config::ConfigStore;
+use crate::name_policy::NamePolicy;
+use crate::partner_api::{partner_routes, PartnerApiState};
+use crate::payment_links::payment_link_routes;
+use crate::receipts::{receipt_routes, ReceiptSigner};
+use crate::reporting::{statement_routes, StatementState};
+use crate::sms::cost::SpendReport;
+use crate::sms::{
+    incoming_sms_handler, incoming_sms_json_handler, vonage_inbound_get_handler, vonage_inbound_post_handler, SmsGateway,
+};
 use crate::sms::webhook::AppState;
-use sqlx::PgPool;
+use crate::wallet::chain_registry::ChainRegistry;
+use crate::wallet::token_registry::TokenRegistry;
+use crate::voice::{voice_routes, VoiceChannel};
+use crate::walletconnect_bridge::{walletconnect_routes, WalletConnectState};
+use crate::workers::{LaneMetrics, WorkerPool};
 
 /// Build the application router with all routes
-pub fn create_router(twilio: TwilioClient, command_processor: CommandProcessor) -> Router {
+pub fn create_router(twilio: SmsGateway, command_processor: CommandProcessor, workers: WorkerPool) -> Router {
     let state = AppState {
         twilio: Arc::new(twilio),
         command_processor: Arc::new(command_processor),
+        workers,
     };
 
     Router::new()
@@ -25,10 +71,18 @@ pub fn create_router(twilio: TwilioClient, command_processor: CommandProcessor)
         .route("/sms/incoming", post(incoming_sms_handler))
         // SMS webhook endpoint - SMSCountry/generic JSON webhooks
         .route("/webhook/sms", post(incoming_sms_json_handler))
+        // SMS webhook endpoint - Vonage inbound messages (404 unless Vonage is configured)
+        .route("/sms/vonage/inbound", get(vonage_inbound_get_handler).post(vonage_inbound_post_handler))
         // Health check endpoint
         .route("/health", get(health_check))
         // Ready check endpoint
         .route("/ready", get(ready_check))
+        // Background worker queue metrics
+        .route("/metrics/workers", get(worker_metrics))
+        // Outbound SMS spend against the daily budget
+        .route("/metrics/sms-spend", get(sms_spend))
+        // Command counts per country and carrier
+        .route("/metrics/commands", get(command_metrics))
         // Add tracing middleware
         .layer(TraceLayer::new_for_http())
         // Add shared state
@@ -36,44 +90,285 @@ pub fn create_router(twilio: TwilioClient, command_processor: CommandProcessor)
 
 }
 
-/// Build router with admin routes (requires voucher repo and db pool)
+/// Route groups that are only mounted when configured
+#[derive(Default)]
+pub struct OptionalRoutes {
+    /// Treasury multisig admin routes (requires a Safe)
+    pub treasury: Option<AdminTreasuryState>,
+    /// Partner GraphQL API (requires API keys)
+    pub graphql: Option<GraphqlState>,
+    /// WalletConnect bridge events (requires a bridge)
+    pub walletconnect: Option<WalletConnectState>,
+    /// Support transcripts (requires TRANSCRIPT_RETENTION_DAYS)
+    pub transcripts: Option<TranscriptRepository>,
+    /// Beta allowlist, invites and waitlist (requires BETA_MODE)
+    pub beta: Option<AdminBetaState>,
+    /// Inbound email commands (requires SMTP and EMAIL_INBOUND_TOKEN)
+    pub email: Option<EmailChannel>,
+    /// Twilio Voice menu (requires VOICE_IVR)
+    pub voice: Option<VoiceChannel>,
+    /// Public receipt verification (requires RECEIPT_SIGNING_KEY)
+    pub receipts: Option<ReceiptSigner>,
+    /// On-chain deposit intake from the deposit monitor (requires INTERNAL_SECRET)
+    pub deposits: Option<DepositIntake>,
+    /// Token address overrides (requires the database)
+    pub tokens: Option<TokenRegistry>,
+    /// EVM chains added at runtime (requires the database)
+    pub chains: Option<ChainRegistry>,
+    /// Admin SMS broadcasts (requires the database)
+    pub broadcasts: Option<AdminBroadcastState>,
+    /// Live admin event stream (requires ADMIN_EVENTS_TOKEN)
+    pub events: Option<AdminEventsState>,
+    /// Partner deposit/voucher API and key management (requires the database)
+    pub partners: Option<PartnerApiState>,
+    /// Signer gas balances (requires GAS_TANK_THRESHOLDS)
+    pub gas: Option<GasMonitor>,
+    /// Reserved subdomain labels (requires the database)
+    pub reserved_names: Option<NamePolicy>,
+    /// Deposit address sweeps (requires DEPOSIT_MNEMONIC)
+    pub sweeper: Option<DepositSweeper>,
+    /// Live config status and reloads (requires CONFIG_FILE)
+    pub config: Option<ConfigStore>,
+    /// Onboarding tier upgrades and downgrades (requires KYC_TIERS)
+    pub kyc: Option<KycRepository>,
+    /// Monthly statements and their download links (requires the database)
+    pub statements: Option<StatementState>,
+    /// Stored ENS name checks (requires ENS_VERIFY_INTERVAL_SECS)
+    pub ens_verifier: Option<EnsVerifier>,
+    /// Referral performance (requires REFERRAL_BONUS or REFERRAL_NEW_USER_BONUS)
+    pub referrals: Option<ReferralRepository>,
+    /// Spam samples and muted numbers (requires SPAM_FILTER)
+    pub spam: Option<AdminSpamState>,
+}
+
+/// Build router with admin routes (requires voucher repo and db pools)
 pub fn create_router_with_admin(
-    twilio: TwilioClient, 
+    twilio: SmsGateway, 
     command_processor: CommandProcessor,
-    voucher_repo: VoucherRepository,
-    admin_token: String,
-    db_pool: PgPool,
+    workers: WorkerPool,
+    admin_state: AdminState,
+    db: DbPools,
+    audit: AuditLogRepository,
+    optional: OptionalRoutes,
 ) -> Router {
+    // Feature flag overrides share state with the command processor
+    let feature_admin_router = admin_feature_routes(command_processor.features().clone());
+
+    // Emergency stop for money-moving commands, audited with who and why
+    let kill_switch_router = admin_kill_switch_routes(AdminKillSwitchState {
+        switch: command_processor.kill_switch().clone(),
+        audit: audit.clone(),
+    });
+
+    // Bulk ENS pre-minting and lookup cache control; the cache is shared with SEND
+    let ens_admin_router = admin_ens_routes(command_processor.backend_url().to_string(), command_processor.ens_cache().clone());
+
+    let sms_gateway = twilio.clone();
     let sms_state = AppState {
         twilio: Arc::new(twilio),
         command_processor: Arc::new(command_processor),
+        workers,
     };
 
-    let admin_state = AdminState {
-        voucher_repo: Arc::new(voucher_repo),
-        admin_token,
-    };
+    // Email commands run through the same processor and worker lanes as SMS
+    let email_router = optional
+        .email
+        .map(|channel| email_routes(channel, sms_state.command_processor.clone(), sms_state.workers.clone()));
+    // Calls run the same processor; slow replies follow by SMS
+    let voice_router = optional.voice.map(|channel| {
+        voice_routes(channel, sms_state.command_processor.clone(), sms_state.twilio.clone(), sms_state.workers.clone())
+    });
 
     // Create SMS routes with their state
     let sms_routes = Router::new()
         .route("/sms/incoming", post(incoming_sms_handler))
         .route("/webhook/sms", post(incoming_sms_json_handler))
+        .route("/sms/vonage/inbound", get(vonage_inbound_get_handler).post(vonage_inbound_post_handler))
+        .route("/metrics/workers", get(worker_metrics))
+        .route("/metrics/sms-spend", get(sms_spend))
+        .route("/metrics/commands", get(command_metrics))
         .with_state(sms_state);
 
 
+    // Create admin wallet routes (list/export queries use the read pool)
+    let wallet_admin_router = admin_wallet_routes(Arc::new(db.read.clone()), admin_state.cipher.clone());
+
+    // Every /admin route checks this token, whichever router it comes from
+    let admin_tokens = admin_state.admin_tokens.clone();
+
     // Create admin routes with their state (already has state applied)
     let admin_router = admin_routes(admin_state);
     
-    // Create admin wallet routes
-    let wallet_admin_router = admin_wallet_routes(Arc::new(db_pool));
+    // Agent registration and float top-ups
+    let agent_admin_router = admin_agent_routes(AgentRepository::new(db.write.clone()));
+
+    // Partner campaigns paying ENS mint gas
+    let campaign_admin_router = admin_campaign_routes(CampaignRepository::new(db.write.clone()));
+
+    // Public short links for REQUEST payment URIs
+    let payment_link_router = payment_link_routes(PaymentLinkRepository::new(db.write.clone()));
+
+    // Messages that weren't a command, counted by the SMS processor
+    let command_admin_router = admin_command_routes(UnrecognizedCommandRepository::new(db.read.clone()));
+
+    // Audit chain verification
+    let audit_admin_router = admin_audit_routes(audit.clone());
+
+    // Stored responses for admin retries carrying an Idempotency-Key
+    let idempotency = IdempotencyRepository::new(db.write.clone());
+
+    // Connection usage per database pool
+    let db_metrics_router = Router::new()
+        .route("/metrics/db-pools", get(db_pool_metrics))
+        .route("/metrics/db", get(db_metrics))
+        .with_state(db);
 
     // Merge all routes together
-    Router::new()
+    let mut router = Router::new()
         .merge(sms_routes)
+        .merge(payment_link_router)
+        .merge(db_metrics_router)
         .nest("/admin", admin_router)
         .nest("/admin", wallet_admin_router)
+        .nest("/admin", agent_admin_router)
+        .nest("/admin", campaign_admin_router)
+        .nest("/admin", ens_admin_router)
+        .nest("/admin", feature_admin_router)
+        .nest("/admin", kill_switch_router)
+        .nest("/admin", audit_admin_router)
+        .nest("/admin", command_admin_router);
+
+    // Treasury routes only when a Safe is configured
+    if let Some(treasury) = optional.treasury {
+        router = router.nest("/admin", admin_treasury_routes(treasury));
+    }
+
+    // Partner GraphQL API only when API keys are configured
+    if let Some(graphql) = optional.graphql {
+        router = router.merge(graphql_routes(graphql));
+    }
+
+    // WalletConnect bridge events only when a bridge is configured
+    if let Some(walletconnect) = optional.walletconnect {
+        router = router.merge(walletconnect_routes(walletconnect));
+    }
+
+    // Support transcripts only when they are being recorded
+    if let Some(transcripts) = optional.transcripts {
+        router = router.nest("/admin", admin_transcript_routes(transcripts));
+    }
+
+    // Token contract overrides, shared with every address lookup
+    if let Some(tokens) = optional.tokens {
+        router = router.nest("/admin", admin_token_routes(tokens));
+    }
+
+    // Runtime chain registrations, shared with every Chain lookup
+    if let Some(chains) = optional.chains {
+        router = router.nest("/admin", admin_chain_routes(chains));
+    }
+
+    // Subdomain deny-list, shared with the SMS mint flow
+    if let Some(reserved_names) = optional.reserved_names {
+        router = router.nest("/admin", admin_reserved_name_routes(reserved_names));
+    }
+
+    // Deposit address consolidation into the treasury
+    if let Some(sweeper) = optional.sweeper {
+        router = router.nest("/admin", sweep_routes(sweeper));
+    }
+
+    // Settings that reload without a restart
+    if let Some(config) = optional.config {
+        router = router.nest("/admin", admin_config_routes(config));
+    }
+
+    // Tier changes for the SMS send limits
+    if let Some(kyc) = optional.kyc {
+        router = router.nest("/admin", admin_kyc_routes(kyc));
+    }
+
+    // Referral sign-ups, bonuses and refusals
+    if let Some(referrals) = optional.referrals {
+        router = router.nest("/admin", admin_referral_routes(referrals));
+    }
+
+    // Review of suspected spam, and who is muted for it
+    if let Some(spam) = optional.spam {
+        router = router.nest("/admin", admin_spam_routes(spam));
+    }
+
+    // Statements for admins, and the links STATEMENT texts to users
+    if let Some(statements) = optional.statements {
+        router = router
+            .nest("/admin", admin_statement_routes(statements.clone()))
+            .merge(statement_routes(statements));
+    }
+
+    if let Some(broadcasts) = optional.broadcasts {
+        router = router.nest("/admin", admin_broadcast_routes(broadcasts));
+    }
+
+    // Partner REST API, with admins managing its keys and watching usage
+    if let Some(partners) = optional.partners {
+        router = router
+            .nest("/admin", admin_partner_key_routes(partners.keys.clone()))
+            .merge(partner_routes(partners));
+    }
+
+    // Operator overview, with gas tanks when they are watched
+    let summary = SummaryState { gas: optional.gas.clone(), sms: sms_gateway };
+    router = router.nest("/admin", Router::new().route("/summary", get(admin_summary)).with_state(summary));
+
+    // Signer gas balances for dashboards
+    if let Some(gas) = optional.gas {
+        router = router.merge(gas_routes(gas));
+    }
+
+    // Drifted ENS names for admins, verdict counts for dashboards
+    if let Some(verifier) = optional.ens_verifier {
+        router = router
+            .nest("/admin", admin_ens_check_routes(verifier.clone()))
+            .merge(ens_metrics_routes(verifier));
+    }
+
+    // Live activity for ops dashboards
+    if let Some(events) = optional.events {
+        router = router.nest("/admin", admin_event_routes(events));
+    }
+
+    // Beta access management only in beta mode
+    if let Some(beta) = optional.beta {
+        router = router.nest("/admin", admin_beta_routes(beta));
+    }
+
+    // Merchants verify RECEIPT proofs here
+    if let Some(receipts) = optional.receipts {
+        router = router.merge(receipt_routes(receipts));
+    }
+
+    // The deposit monitor reports incoming transfers here
+    if let Some(deposits) = optional.deposits {
+        router = router.merge(deposit_routes(deposits));
+    }
+
+    if let Some(email_router) = email_router {
+        router = router.merge(email_router);
+    }
+
+    if let Some(voice_router) = voice_router {
+        router = router.merge(voice_router);
+    }
+
+    router
         .route("/health", get(health_check))
         .route("/ready", get(ready_check))
+        // Retried admin mutations with a known Idempotency-Key get the first response
+        .layer(axum::middleware::from_fn_with_state(idempotency, idempotent_admin_requests))
+        // Admin requests need the admin token, checked before any replay
+        .layer(axum::middleware::from_fn_with_state(admin_tokens, require_admin_token))
+        // Every state-changing admin request goes to the audit log, refused ones too
+        .layer(axum::middleware::from_fn_with_state(audit, audit_admin_requests))
         .layer(TraceLayer::new_for_http())
 }
 
@@ -82,6 +377,59 @@ async fn health_check() -> &'static str {
     "OK"
 }
 
+/// State for the operator overview
+#[derive(Clone)]
+struct SummaryState {
+    gas: Option<GasMonitor>,
+    sms: SmsGateway,
+}
+
+/// Operator overview: signers that need topping up and carriers that are down
+async fn admin_summary(State(state): State<SummaryState>) -> Json<serde_json::Value> {
+    let mut summary = serde_json::Map::new();
+    summary.insert("success".to_string(), serde_json::Value::Bool(true));
+    if let Some(ref gas) = state.gas {
+        summary.extend(gas.summary());
+    }
+    let outages = match state.sms.outage_report().await {
+        Ok(outages) => serde_json::json!(outages),
+        Err(e) => {
+            tracing::warn!("Failed to read SMS outages: {}", e);
+            serde_json::Value::Null
+        }
+    };
+    summary.insert("sms_outages".to_string(), outages);
+    Json(serde_json::Value::Object(summary))
+}
+
+/// Queue length and in-flight counts per background task class
+async fn worker_metrics(State(state): State<AppState>) -> Json<Vec<LaneMetrics>> {
+    Json(state.workers.metrics())
+}
+
+/// Open, idle and in-use connections for the write and read pools
+async fn db_pool_metrics(State(db): State<DbPools>) -> Json<Vec<PoolMetrics>> {
+    Json(db.metrics())
+}
+
+/// Pool gauges, acquire waits and per-query timings for Prometheus
+async fn db_metrics(State(db): State<DbPools>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::db::metrics::render(&db.metrics()),
+    )
+}
+
+/// Today's outbound SMS spend handler
+async fn sms_spend(State(state): State<AppState>) -> Json<SpendReport> {
+    Json(state.twilio.costs().report())
+}
+
+/// Command counts per command, country and carrier since startup
+async fn command_metrics(State(state): State<AppState>) -> Json<CommandReport> {
+    Json(state.command_processor.metrics().report())
+}
+
 /// Ready check handler
 async fn ready_check() -> &'static str {
     "READY"
@@ -0,0 +1,219 @@
+//! Keeping outbound SMS in the GSM-7 alphabet
+//!
+//! One character outside GSM-7 - a curly quote pasted into a template, a
+//! ❌ in a reply - sends the whole message as UCS-2, which fits 70
+//! characters per segment instead of 160 and so costs two to three times as
+//! much. `SMS_GSM7` picks how hard each deployment tries to avoid that:
+//!
+//! - `off`: messages go out as written;
+//! - `substitute`: typographic characters become their GSM-7 look-alikes
+//!   (quotes, dashes, ellipses, spaces, accented letters) and emoji are
+//!   dropped. Text in other scripts is kept, so a name in Cyrillic still
+//!   reads right, as UCS-2;
+//! - `strict`: as `substitute`, and anything still outside GSM-7 becomes
+//!   `?`, so every message is GSM-7.
+
+use std::borrow::Cow;
+
+use super::cost::{is_gsm7, is_gsm7_char, segment_count};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gsm7Mode {
+    Off,
+    Substitute,
+    Strict,
+}
+
+impl Gsm7Mode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "off" => Some(Gsm7Mode::Off),
+            "substitute" => Some(Gsm7Mode::Substitute),
+            "strict" => Some(Gsm7Mode::Strict),
+            _ => None,
+        }
+    }
+}
+
+/// How a message is encoded on the air
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Gsm7,
+    Ucs2,
+}
+
+impl Encoding {
+    pub fn of(body: &str) -> Self {
+        if is_gsm7(body) {
+            Encoding::Gsm7
+        } else {
+            Encoding::Ucs2
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Gsm7 => "gsm7",
+            Encoding::Ucs2 => "ucs2",
+        }
+    }
+}
+
+/// A message ready to send, with its segment estimate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Composed<'a> {
+    pub body: Cow<'a, str>,
+    pub encoding: Encoding,
+    pub segments: u32,
+    /// Characters swapped for a GSM-7 look-alike (or `?`)
+    pub replaced: usize,
+    /// Emoji and invisible characters dropped
+    pub removed: usize,
+}
+
+/// GSM-7 stand-in for a common character outside the alphabet
+fn substitute(c: char) -> Option<&'static str> {
+    Some(match c {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' | '`' | '´' => "'",
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' | '«' | '»' => "\"",
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => "-",
+        '\u{2026}' => "...",
+        '\u{2022}' | '\u{00B7}' | '\u{2023}' => "*",
+        '\u{00A0}' | '\u{2002}'..='\u{200A}' | '\u{202F}' | '\t' => " ",
+        '\u{2192}' | '\u{27A1}' => "->",
+        '\u{2190}' => "<-",
+        '\u{00D7}' => "x",
+        'á' | 'â' | 'ã' | 'ā' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ā' => "A",
+        'ê' | 'ë' | 'ē' => "e",
+        'È' | 'Ê' | 'Ë' | 'Ē' => "E",
+        'í' | 'î' | 'ï' | 'ī' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' => "I",
+        'ó' | 'ô' | 'õ' | 'ō' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ō' => "O",
+        'ú' | 'û' | 'ū' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ū' => "U",
+        'ç' => "c",
+        _ => return None,
+    })
+}
+
+/// Emoji, their modifiers and zero-width characters: decoration that can go
+fn is_droppable(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF       // emoji, flags, skin tones
+            | 0x2600..=0x27BF   // misc symbols and dingbats (❌ ✅ ✓)
+            | 0x2300..=0x23FF   // technical (⌛ ⏰)
+            | 0x2B00..=0x2BFF   // arrows and stars (⭐)
+            | 0xFE00..=0xFE0F   // variation selectors
+            | 0x200B..=0x200D   // zero-width space and joiners
+            | 0x2060 | 0xFEFF | 0x20E3
+            | 0xE0020..=0xE007F // flag tags
+    )
+}
+
+/// Applies `SMS_GSM7` to outbound messages
+#[derive(Debug, Clone, Copy)]
+pub struct Composer {
+    mode: Gsm7Mode,
+}
+
+impl Default for Composer {
+    fn default() -> Self {
+        Self::new(Gsm7Mode::Off)
+    }
+}
+
+impl Composer {
+    pub fn new(mode: Gsm7Mode) -> Self {
+        Self { mode }
+    }
+
+    /// The body as it should go out, with how many segments it will take
+    pub fn compose<'a>(&self, body: &'a str) -> Composed<'a> {
+        if self.mode == Gsm7Mode::Off || is_gsm7(body) {
+            return finish(Cow::Borrowed(body), 0, 0);
+        }
+
+        let (mut replaced, mut removed) = (0, 0);
+        let mut out = String::with_capacity(body.len());
+        for c in body.chars() {
+            if is_gsm7_char(c) {
+                out.push(c);
+            } else if let Some(stand_in) = substitute(c) {
+                out.push_str(stand_in);
+                replaced += 1;
+            } else if is_droppable(c) {
+                removed += 1;
+                // `❌ Name taken` reads `Name taken`, not ` Name taken`
+                if out.is_empty() || out.ends_with([' ', '\n']) {
+                    out.push('\u{0}');
+                }
+            } else if self.mode == Gsm7Mode::Strict {
+                out.push('?');
+                replaced += 1;
+            } else {
+                out.push(c);
+            }
+        }
+        // A space right after a dropped character at the start of a word goes
+        // too, and so does one left at the end of a line
+        let mut out = out.replace("\u{0} ", "").replace('\u{0}', "");
+        if removed > 0 {
+            out = out.split('\n').map(str::trim_end).collect::<Vec<_>>().join("\n");
+        }
+        finish(Cow::Owned(out), replaced, removed)
+    }
+}
+
+fn finish(body: Cow<'_, str>, replaced: usize, removed: usize) -> Composed<'_> {
+    let (encoding, segments) = (Encoding::of(&body), segment_count(&body));
+    Composed { body, encoding, segments, replaced, removed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_off_leaves_messages_alone() {
+        let composed = Composer::default().compose("❌ Name taken");
+        assert_eq!(composed.body, "❌ Name taken");
+        assert_eq!((composed.encoding, composed.segments), (Encoding::Ucs2, 1));
+    }
+
+    #[test]
+    fn test_substitute() {
+        let composer = Composer::new(Gsm7Mode::Substitute);
+        let composed = composer.compose("❌ Name taken\n“alice” isn’t free — try ✅alice2…");
+        assert_eq!(composed.body, "Name taken\n\"alice\" isn't free - try alice2...");
+        assert_eq!((composed.encoding, composed.replaced, composed.removed), (Encoding::Gsm7, 5, 2));
+
+        // Other scripts stay, so the message stays UCS-2
+        let composed = composer.compose("Sent to Иван 👍");
+        assert_eq!(composed.body, "Sent to Иван");
+        assert_eq!(composed.encoding, Encoding::Ucs2);
+
+        // Already GSM-7 is returned as is
+        assert!(matches!(composer.compose("Ça coûte 5 €").body, Cow::Owned(_)));
+        assert!(matches!(composer.compose("Ça coute 5 €").body, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_strict_is_always_gsm7() {
+        let composed = Composer::new(Gsm7Mode::Strict).compose("Sent to Иван 👍🏽");
+        assert_eq!(composed.body, "Sent to ????");
+        assert_eq!(composed.encoding, Encoding::Gsm7);
+    }
+
+    #[test]
+    fn test_segments_saved() {
+        // 100 characters plus one emoji: two UCS-2 segments, or one GSM-7
+        let body = format!("{}✅", "a".repeat(100));
+        assert_eq!(Composer::default().compose(&body).segments, 2);
+        assert_eq!(Composer::new(Gsm7Mode::Substitute).compose(&body).segments, 1);
+        assert_eq!(Gsm7Mode::parse(" Strict "), Some(Gsm7Mode::Strict));
+        assert_eq!(Gsm7Mode::parse("emoji"), None);
+    }
+}
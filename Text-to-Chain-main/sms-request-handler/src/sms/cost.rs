@@ -0,0 +1,419 @@
+//! Outbound SMS cost estimation and daily budgets
+//!
+//! Providers bill per segment, at a rate that depends on the destination
+//! country. Every message sent through `SmsGateway` is costed here and
+//! added to the current UTC day's spend; crossing the alert threshold or the
+//! budget itself is logged once per day. When configured, non-critical
+//! messages are trimmed to a single segment while over budget.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use chrono::{NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::config::SmsCostConfig;
+use crate::db::SmsSpendRepository;
+
+/// GSM 03.38 basic character set (one septet each)
+const GSM7_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+
+/// GSM 03.38 extension table (escape + septet, so two each)
+const GSM7_EXTENDED: &str = "^{}\\[~]|€\u{c}";
+
+/// Whether a message should be cut down when the daily budget is spent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessagePriority {
+    /// Transaction results, confirmation codes - always sent in full
+    Critical,
+    /// Menus, help and listings - trimmed to one segment when over budget
+    Normal,
+}
+
+/// Number of billed segments (GSM-7 where possible, otherwise UCS-2)
+pub fn segment_count(body: &str) -> u32 {
+    let (units, single, multi) = match gsm7_length(body) {
+        Some(septets) => (septets, 160, 153),
+        // UCS-2: billed in UTF-16 code units
+        None => (body.encode_utf16().count(), 70, 67),
+    };
+    if units <= single {
+        1
+    } else {
+        units.div_ceil(multi) as u32
+    }
+}
+
+/// Whether the body fits the GSM-7 alphabet (otherwise it goes out as UCS-2)
+pub fn is_gsm7(body: &str) -> bool {
+    gsm7_length(body).is_some()
+}
+
+/// Whether a single character is in the GSM-7 alphabet (basic or extension)
+pub fn is_gsm7_char(c: char) -> bool {
+    GSM7_BASIC.contains(c) || GSM7_EXTENDED.contains(c)
+}
+
+/// Length in septets, or None if the body needs UCS-2
+fn gsm7_length(body: &str) -> Option<usize> {
+    body.chars().try_fold(0, |len, c| {
+        if GSM7_BASIC.contains(c) {
+            Some(len + 1)
+        } else if GSM7_EXTENDED.contains(c) {
+            Some(len + 2)
+        } else {
+            None
+        }
+    })
+}
+
+/// Cut a message down to a single segment, keeping whole lines where possible
+pub fn trim_to_single_segment(body: &str) -> String {
+    if segment_count(body) == 1 {
+        return body.to_string();
+    }
+
+    let mut kept = String::new();
+    for line in body.lines() {
+        let candidate = if kept.is_empty() { line.to_string() } else { format!("{}\n{}", kept, line) };
+        if segment_count(&candidate) > 1 {
+            break;
+        }
+        kept = candidate;
+    }
+
+    if kept.is_empty() {
+        // First line alone is too long - cut it character by character
+        for c in body.chars() {
+            kept.push(c);
+            if segment_count(&kept) > 1 {
+                kept.pop();
+                break;
+            }
+        }
+    }
+    kept
+}
+
+/// Per-segment prices keyed by E.164 calling code
+#[derive(Debug, Clone)]
+pub struct RateTable {
+    /// (calling code, USD per segment), longest code first
+    rates: Vec<(String, f64)>,
+    default_rate: f64,
+}
+
+impl RateTable {
+    /// Parse `1:0.0079,254:0.12,234:0.25` (malformed entries are skipped)
+    pub fn parse(spec: &str, default_rate: f64) -> Self {
+        let mut rates: Vec<(String, f64)> = spec.split(',').filter_map(parse_rate).collect();
+        rates.sort_by_key(|(code, _)| std::cmp::Reverse(code.len()));
+        Self { rates, default_rate }
+    }
+
+    /// First malformed entry in a rate spec, if any
+    pub fn invalid_entry(spec: &str) -> Option<String> {
+        spec.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .find(|entry| parse_rate(entry).is_none_or(|(_, rate)| rate < 0.0))
+            .map(str::to_string)
+    }
+
+    /// Calling code and per-segment rate for a destination number
+    pub fn lookup(&self, phone: &str) -> (String, f64) {
+        let digits = phone.trim_start_matches('+');
+        self.rates
+            .iter()
+            .find(|(code, _)| digits.starts_with(code.as_str()))
+            .map(|(code, rate)| (code.clone(), *rate))
+            .unwrap_or_else(|| ("other".to_string(), self.default_rate))
+    }
+}
+
+/// `<calling code>:<USD per segment>`
+fn parse_rate(entry: &str) -> Option<(String, f64)> {
+    let (code, rate) = entry.split_once(':')?;
+    let code = code.trim().trim_start_matches('+');
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((code.to_string(), rate.trim().parse().ok()?))
+}
+
+/// Spend for one calling code
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CountrySpend {
+    pub messages: u64,
+    pub segments: u64,
+    pub cost: f64,
+}
+
+/// Today's spend, as reported by `/metrics/sms-spend`
+#[derive(Debug, Clone, Serialize)]
+pub struct SpendReport {
+    pub day: NaiveDate,
+    pub messages: u64,
+    pub segments: u64,
+    pub cost: f64,
+    /// Daily budget in USD (None = unlimited)
+    pub budget: Option<f64>,
+    pub over_budget: bool,
+    pub by_country: HashMap<String, CountrySpend>,
+}
+
+#[derive(Debug)]
+struct DaySpend {
+    day: NaiveDate,
+    by_country: HashMap<String, CountrySpend>,
+    alert_logged: bool,
+    budget_logged: bool,
+}
+
+impl DaySpend {
+    fn new(day: NaiveDate) -> Self {
+        Self { day, by_country: HashMap::new(), alert_logged: false, budget_logged: false }
+    }
+
+    fn total(&self) -> f64 {
+        self.by_country.values().map(|s| s.cost).sum()
+    }
+}
+
+/// Rates and budget, swappable while running
+#[derive(Debug)]
+struct SpendPolicy {
+    rates: RateTable,
+    /// Daily budget in USD (0 = unlimited)
+    daily_budget: f64,
+    alert_percent: f64,
+    trim_over_budget: bool,
+}
+
+impl SpendPolicy {
+    fn new(config: &SmsCostConfig) -> Self {
+        Self {
+            rates: RateTable::parse(&config.rates, config.default_rate),
+            daily_budget: config.daily_budget,
+            alert_percent: config.alert_percent,
+            trim_over_budget: config.trim_over_budget,
+        }
+    }
+}
+
+/// Shared daily spend tracker
+#[derive(Clone)]
+pub struct SpendTracker {
+    policy: Arc<RwLock<SpendPolicy>>,
+    today: Arc<Mutex<DaySpend>>,
+    repo: Option<SmsSpendRepository>,
+}
+
+impl Default for SpendTracker {
+    fn default() -> Self {
+        Self::new(&SmsCostConfig::default())
+    }
+}
+
+impl SpendTracker {
+    /// In-memory tracker (spend resets on restart)
+    pub fn new(config: &SmsCostConfig) -> Self {
+        Self {
+            policy: Arc::new(RwLock::new(SpendPolicy::new(config))),
+            today: Arc::new(Mutex::new(DaySpend::new(Utc::now().date_naive()))),
+            repo: None,
+        }
+    }
+
+    /// Resume today's persisted spend and keep recording to the database
+    pub async fn load(config: &SmsCostConfig, repo: SmsSpendRepository) -> Result<Self, sqlx::Error> {
+        let mut tracker = Self::new(config);
+        let day = Utc::now().date_naive();
+        let mut spend = DaySpend::new(day);
+        for (country, messages, segments, cost) in repo.day_totals(day).await? {
+            spend.by_country.insert(
+                country,
+                CountrySpend { messages: messages as u64, segments: segments as u64, cost },
+            );
+        }
+        tracker.today = Arc::new(Mutex::new(spend));
+        tracker.repo = Some(repo);
+        Ok(tracker)
+    }
+
+    /// Swap in new rates and budget; today's spend carries over
+    pub fn reconfigure(&self, config: &SmsCostConfig) {
+        if let Ok(mut policy) = self.policy.write() {
+            *policy = SpendPolicy::new(config);
+        }
+    }
+
+    /// Estimated cost of sending `body` to `to`, with its calling code
+    pub fn estimate(&self, to: &str, body: &str) -> (String, u32, f64) {
+        let (country, rate) = self.policy().rates.lookup(to);
+        let segments = segment_count(body);
+        (country, segments, segments as f64 * rate)
+    }
+
+    pub fn over_budget(&self) -> bool {
+        let budget = self.policy().daily_budget;
+        budget > 0.0 && self.current_day().total() >= budget
+    }
+
+    /// Body to actually send, trimmed if over budget and not critical
+    pub fn prepare(&self, body: &str, priority: MessagePriority) -> String {
+        let trim = self.policy().trim_over_budget;
+        if trim && priority == MessagePriority::Normal && self.over_budget() {
+            let trimmed = trim_to_single_segment(body);
+            if trimmed.len() < body.len() {
+                tracing::info!(
+                    from_segments = segment_count(body),
+                    "Over SMS budget - trimmed non-critical message to one segment"
+                );
+            }
+            return trimmed;
+        }
+        body.to_string()
+    }
+
+    /// Add a sent message to today's spend
+    pub async fn record(&self, to: &str, body: &str) {
+        let (country, segments, cost) = self.estimate(to, body);
+
+        let (budget, alert_percent) = {
+            let policy = self.policy();
+            (policy.daily_budget, policy.alert_percent)
+        };
+        let (day, total, alert, exceeded) = {
+            let mut today = self.current_day();
+            let entry = today.by_country.entry(country.clone()).or_default();
+            entry.messages += 1;
+            entry.segments += segments as u64;
+            entry.cost += cost;
+
+            let total = today.total();
+            let (mut alert, mut exceeded) = (false, false);
+            if budget > 0.0 {
+                if !today.budget_logged && total >= budget {
+                    today.budget_logged = true;
+                    today.alert_logged = true;
+                    exceeded = true;
+                } else if !today.alert_logged && total >= budget * alert_percent / 100.0 {
+                    today.alert_logged = true;
+                    alert = true;
+                }
+            }
+            (today.day, total, alert, exceeded)
+        };
+
+        if exceeded {
+            tracing::error!(spend = total, budget, "Daily SMS budget exceeded");
+        } else if alert {
+            tracing::warn!(
+                spend = total,
+                budget,
+                percent = alert_percent,
+                "SMS spend nearing daily budget"
+            );
+        }
+
+        if let Some(ref repo) = self.repo {
+            if let Err(e) = repo.record(day, &country, segments as i64, cost).await {
+                tracing::error!("Failed to persist SMS spend: {}", e);
+            }
+        }
+    }
+
+    pub fn report(&self) -> SpendReport {
+        let budget = self.policy().daily_budget;
+        let today = self.current_day();
+        SpendReport {
+            day: today.day,
+            messages: today.by_country.values().map(|s| s.messages).sum(),
+            segments: today.by_country.values().map(|s| s.segments).sum(),
+            cost: today.total(),
+            budget: (budget > 0.0).then_some(budget),
+            over_budget: budget > 0.0 && today.total() >= budget,
+            by_country: today.by_country.clone(),
+        }
+    }
+
+    fn policy(&self) -> std::sync::RwLockReadGuard<'_, SpendPolicy> {
+        self.policy.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Today's spend, rolling over at UTC midnight
+    fn current_day(&self) -> std::sync::MutexGuard<'_, DaySpend> {
+        let mut today = self.today.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Utc::now().date_naive();
+        if today.day != now {
+            *today = DaySpend::new(now);
+        }
+        today
+    }
+}
+
+impl std::fmt::Debug for SpendTracker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpendTracker")
+            .field("daily_budget", &self.policy().daily_budget)
+            .field("persistent", &self.repo.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_count() {
+        assert_eq!(segment_count(&"a".repeat(160)), 1);
+        assert_eq!(segment_count(&"a".repeat(161)), 2);
+        assert_eq!(segment_count(&"a".repeat(306)), 2);
+        // Extension characters take two septets
+        assert_eq!(segment_count(&"€".repeat(80)), 1);
+        assert_eq!(segment_count(&"€".repeat(81)), 2);
+        // Any non-GSM character switches the whole message to UCS-2
+        assert_eq!(segment_count(&format!("{}✓", "a".repeat(69))), 1);
+        assert_eq!(segment_count(&format!("{}✓", "a".repeat(70))), 2);
+    }
+
+    #[test]
+    fn test_rate_lookup_longest_prefix() {
+        let rates = RateTable::parse("1:0.0079,254:0.12,+2547:0.10,bad", 0.05);
+        assert_eq!(rates.lookup("+15551234567"), ("1".to_string(), 0.0079));
+        assert_eq!(rates.lookup("+254712345678"), ("2547".to_string(), 0.10));
+        assert_eq!(rates.lookup("+254212345678"), ("254".to_string(), 0.12));
+        assert_eq!(rates.lookup("+447700900000"), ("other".to_string(), 0.05));
+        assert_eq!(RateTable::invalid_entry("1:0.0079, 254:0.12,"), None);
+        assert_eq!(RateTable::invalid_entry("1:0.0079,bad"), Some("bad".to_string()));
+        assert_eq!(RateTable::invalid_entry("44:-1"), Some("44:-1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_trims_only_normal_messages_over_budget() {
+        let tracker = SpendTracker::new(&SmsCostConfig {
+            rates: "1:1.0".to_string(),
+            default_rate: 1.0,
+            daily_budget: 2.0,
+            alert_percent: 80.0,
+            trim_over_budget: true,
+            gsm7: "off".to_string(),
+        });
+        let long = format!("{}\n{}", "a".repeat(100), "b".repeat(100));
+
+        assert_eq!(tracker.prepare(&long, MessagePriority::Normal), long);
+        tracker.record("+15551234567", &long).await;
+        assert!(tracker.over_budget());
+        assert_eq!(tracker.report().segments, 2);
+
+        assert_eq!(tracker.prepare(&long, MessagePriority::Normal), "a".repeat(100));
+        assert_eq!(tracker.prepare(&long, MessagePriority::Critical), long);
+
+        // A raised budget applies to today's spend so far
+        tracker.reconfigure(&SmsCostConfig { daily_budget: 10.0, ..SmsCostConfig::default() });
+        assert!(!tracker.over_budget());
+        assert_eq!(tracker.report().budget, Some(10.0));
+    }
+}
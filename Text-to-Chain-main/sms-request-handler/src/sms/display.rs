@@ -0,0 +1,293 @@
+//! How records are written into SMS replies
+//!
+//! Types that appear in replies implement `SmsDisplay` instead of each
+//! hand-rolling a `to_sms_string`. The helpers here are the shared pieces:
+//! cutting to a width, shortening addresses, hashes and phone numbers, and
+//! writing numbers the way the user's country does (`1,234.5`, `1.234,5`
+//! or `1 234,5`), picked from their calling code.
+
+use crate::money::Money;
+use crate::rates::format_usd;
+use crate::wallet::address::display_address;
+
+/// How a country writes `1234.5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberLocale {
+    /// `1,234.5`
+    #[default]
+    Point,
+    /// `1.234,5`
+    Comma,
+    /// `1 234,5`
+    Space,
+}
+
+/// Calling codes that don't write numbers as `1,234.5`, longest first
+const CALLING_CODE_LOCALES: &[(&str, NumberLocale)] = &[
+    ("244", NumberLocale::Space),
+    ("258", NumberLocale::Space),
+    ("221", NumberLocale::Space),
+    ("225", NumberLocale::Space),
+    ("237", NumberLocale::Space),
+    ("31", NumberLocale::Comma),
+    ("32", NumberLocale::Comma),
+    ("33", NumberLocale::Space),
+    ("34", NumberLocale::Comma),
+    ("39", NumberLocale::Comma),
+    ("49", NumberLocale::Comma),
+    ("54", NumberLocale::Comma),
+    ("55", NumberLocale::Comma),
+    ("57", NumberLocale::Comma),
+    ("62", NumberLocale::Comma),
+    ("90", NumberLocale::Comma),
+    ("7", NumberLocale::Space),
+];
+
+impl NumberLocale {
+    /// Locale for an E.164 number; `1,234.5` for unknown codes and hashed numbers
+    pub fn for_phone(phone: &str) -> Self {
+        let Some(digits) = phone.strip_prefix('+') else {
+            return Self::default();
+        };
+        CALLING_CODE_LOCALES
+            .iter()
+            .find(|(code, _)| digits.starts_with(code))
+            .map(|(_, locale)| *locale)
+            .unwrap_or_default()
+    }
+
+    fn separators(self) -> (&'static str, &'static str) {
+        match self {
+            NumberLocale::Point => (",", "."),
+            NumberLocale::Comma => (".", ","),
+            NumberLocale::Space => (" ", ","),
+        }
+    }
+
+    /// Rewrite a plain number (`-1234.50`, `1,234`) with this locale's
+    /// grouping and decimal mark; anything else is returned unchanged
+    pub fn number(self, plain: &str) -> String {
+        let (sign, unsigned) = match plain.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", plain),
+        };
+        let (whole, fraction) = match unsigned.split_once('.') {
+            Some((whole, fraction)) => (whole.replace(',', ""), Some(fraction)),
+            None => (unsigned.replace(',', ""), None),
+        };
+        let digits_only = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !digits_only(&whole) || !fraction.is_none_or(|f| !f.is_empty() && digits_only(f)) {
+            return plain.to_string();
+        }
+
+        let (group, decimal) = self.separators();
+        let mut grouped = String::new();
+        for (i, c) in whole.chars().enumerate() {
+            if i > 0 && (whole.len() - i).is_multiple_of(3) {
+                grouped.push_str(group);
+            }
+            grouped.push(c);
+        }
+        match fraction {
+            Some(fraction) => format!("{}{}{}{}", sign, grouped, decimal, fraction),
+            None => format!("{}{}", sign, grouped),
+        }
+    }
+
+    /// `$1,520` or `$4.07`, in this locale
+    pub fn usd(self, value: f64) -> String {
+        format!("${}", self.number(format_usd(value).trim_start_matches('$')))
+    }
+}
+
+/// A record as it is written into SMS replies
+pub trait SmsDisplay {
+    /// Compact one-line form, numbers written for `locale`
+    fn sms_string(&self, locale: NumberLocale) -> String;
+
+    /// Compact one-line form with `1,234.5` numbers
+    fn to_sms_string(&self) -> String {
+        self.sms_string(NumberLocale::default())
+    }
+}
+
+/// `12.5 USDC`
+impl SmsDisplay for Money {
+    fn sms_string(&self, locale: NumberLocale) -> String {
+        format!("{} {}", locale.number(&self.amount()), self.currency())
+    }
+}
+
+/// At most `width` characters; a longer text keeps `width - 3` and ends in `...`
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(width.saturating_sub(3)).collect();
+    format!("{}...", kept)
+}
+
+/// `0x742d...f44e`, checksummed; anything not an address is returned as is
+pub fn short_address(address: &str) -> String {
+    let address = display_address(address);
+    match (address.get(..6), address.get(address.len().saturating_sub(4)..)) {
+        (Some(head), Some(tail)) if address.len() > 13 && address.starts_with("0x") => format!("{}...{}", head, tail),
+        _ => address,
+    }
+}
+
+/// First 10 characters of a transaction hash
+pub fn short_hash(hash: &str) -> &str {
+    hash.get(..10).unwrap_or(hash)
+}
+
+/// `+254700000001` -> `+2547******01`
+pub fn short_phone(phone: &str) -> String {
+    let chars: Vec<char> = phone.chars().collect();
+    if chars.len() <= 7 {
+        return "*".repeat(chars.len());
+    }
+    let (head, rest) = chars.split_at(5);
+    let (middle, tail) = rest.split_at(rest.len() - 2);
+    format!("{}{}{}", head.iter().collect::<String>(), "*".repeat(middle.len()), tail.iter().collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{Contact, Deposit, Voucher};
+    use crate::money::Currency;
+    use crate::wallet::allowance::{AllowanceEntry, ApprovableToken, Spender};
+    use crate::wallet::tokens::{ChainBalances, TokenBalance};
+    use crate::wallet::Chain;
+    use chrono::Utc;
+    use ethers::types::{Address, U256};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_number_locales() {
+        assert_eq!(NumberLocale::Point.number("1234567.5"), "1,234,567.5");
+        assert_eq!(NumberLocale::Comma.number("1234567.5"), "1.234.567,5");
+        assert_eq!(NumberLocale::Space.number("-1234.50"), "-1 234,50");
+        assert_eq!(NumberLocale::Comma.number("1,520"), "1.520");
+        assert_eq!(NumberLocale::Point.number("999"), "999");
+        for odd in ["", "abc", "1.2.3", "1.", "-"] {
+            assert_eq!(NumberLocale::Comma.number(odd), odd);
+        }
+        assert_eq!(NumberLocale::Comma.usd(1520.4), "$1.520");
+        assert_eq!(NumberLocale::Space.usd(4.07), "$4,07");
+    }
+
+    #[test]
+    fn test_locale_for_phone() {
+        assert_eq!(NumberLocale::for_phone("+254700000001"), NumberLocale::Point);
+        assert_eq!(NumberLocale::for_phone("+5511999990000"), NumberLocale::Comma);
+        assert_eq!(NumberLocale::for_phone("+33612345678"), NumberLocale::Space);
+        assert_eq!(NumberLocale::for_phone("+79161234567"), NumberLocale::Space);
+        // Blind index of a hashed number
+        assert_eq!(NumberLocale::for_phone("bi1:5511999990000"), NumberLocale::Point);
+    }
+
+    #[test]
+    fn test_shortening() {
+        assert_eq!(truncate("sign a message", 10), "sign a ...");
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("ünïcödé text", 8), "ünïcö...");
+        assert_eq!(short_address("0x742d35cc6634c0532925a3b844bc454e4438f44e"), "0x742d...f44e");
+        assert_eq!(short_address("alice.ttcip.eth"), "alice.ttcip.eth");
+        assert_eq!(short_hash("0xabcdef0123456789"), "0xabcdef01");
+        assert_eq!(short_phone("+254700000001"), "+2547******01");
+        assert_eq!(short_phone("12345"), "*****");
+    }
+
+    #[test]
+    fn test_money_snapshot() {
+        let money = Money::from_micros(1_234_500_000, Currency::USDC);
+        insta::assert_snapshot!(money.to_sms_string(), @"1,234.5 USDC");
+        insta::assert_snapshot!(money.sms_string(NumberLocale::Comma), @"1.234,5 USDC");
+        insta::assert_snapshot!(truncate(&money.sms_string(NumberLocale::Space), 8), @"1 234...");
+    }
+
+    #[test]
+    fn test_db_type_snapshots() {
+        let contact = |phone: Option<&str>, address: Option<&str>| Contact {
+            id: Uuid::nil(),
+            user_phone: "+254700000001".to_string(),
+            name: "mum".to_string(),
+            contact_phone: phone.map(str::to_string),
+            wallet_address: address.map(str::to_string),
+            created_at: Utc::now(),
+        };
+        insta::assert_snapshot!(contact(Some("+254700000002"), None).to_sms_string(), @"mum: +254700000002");
+        insta::assert_snapshot!(
+            contact(None, Some("0x742d35cc6634c0532925a3b844bc454e4438f44e")).to_sms_string(),
+            @"mum: 0x742d...f44e"
+        );
+        insta::assert_snapshot!(contact(Some("bi1:abc"), None).to_sms_string(), @"mum");
+
+        let deposit = Deposit {
+            id: Uuid::nil(),
+            user_phone: "+5511999990000".to_string(),
+            amount: Money::usdc(1_250_500_000),
+            source: "voucher".to_string(),
+            source_ref: None,
+            chain: None,
+            created_at: Utc::now(),
+            status: "pending".to_string(),
+            token: None,
+            block_number: None,
+            block_hash: None,
+            confirmations: 0,
+        };
+        insta::assert_snapshot!(deposit.to_sms_string(), @"$1,250.50 via voucher (pending)");
+        insta::assert_snapshot!(deposit.sms_string(NumberLocale::Comma), @"$1.250,50 via voucher (pending)");
+
+        let mut voucher = Voucher {
+            id: Uuid::nil(),
+            code: "TTC-ABCD".to_string(),
+            usdc_amount: Money::usdc(7_720_000),
+            status: "unused".to_string(),
+            redeemed_by: None,
+            redeemed_at: None,
+            expires_at: None,
+            created_at: Utc::now(),
+            local_currency: None,
+            local_amount: None,
+            fx_rate: None,
+            batch_id: None,
+        };
+        insta::assert_snapshot!(voucher.to_sms_string(), @"7.72 USDC");
+        voucher.local_currency = Some("KES".to_string());
+        voucher.local_amount = Some(1000.0);
+        insta::assert_snapshot!(voucher.to_sms_string(), @"KES 1,000 (7.72 USDC)");
+        insta::assert_snapshot!(voucher.sms_string(NumberLocale::Space), @"KES 1 000 (7,72 USDC)");
+    }
+
+    #[test]
+    fn test_wallet_type_snapshots() {
+        let balance = |symbol: &str, balance: u64, decimals: u8| TokenBalance {
+            chain: Chain::BaseMainnet,
+            symbol: symbol.to_string(),
+            balance: U256::from(balance),
+            decimals,
+        };
+        let balances = ChainBalances {
+            chain: Chain::BaseMainnet,
+            native: balance("ETH", 450_000_000_000_000_000, 18),
+            usdc: Some(balance("USDC", 1_025_500_000, 6)),
+        };
+        let prices = crate::rates::parse_prices(&serde_json::json!({"prices": {"ETH": 3378.0}}));
+        insta::assert_snapshot!(balances.to_sms_string(), @"BASE: 0.450000 ETH | 1,025.500000 USDC");
+        insta::assert_snapshot!(
+            balances.with_prices(&prices).sms_string(NumberLocale::Comma),
+            @"BASE: 0,450000 ETH (~$1.520) | 1.025,500000 USDC"
+        );
+
+        let entry = AllowanceEntry {
+            token: ApprovableToken { symbol: "USDC", address: Address::zero(), decimals: 6 },
+            spender: Spender { name: Some("Uniswap"), address: Address::zero() },
+            amount: U256::from(2_500_000_000u64),
+        };
+        insta::assert_snapshot!(entry.sms_string(NumberLocale::Space), @"2 500,000000 USDC -> Uniswap");
+    }
+}
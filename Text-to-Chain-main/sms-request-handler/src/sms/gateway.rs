@@ -0,0 +1,410 @@
+use chrono::{DateTime, Utc};
+
+use crate::db::SmsOutboxRepository;
+use crate::sms::composer::{Composer, Encoding};
+use crate::sms::cost::{MessagePriority, SpendTracker};
+use crate::sms::opt_out::OptOutList;
+use crate::sms::outages::{CarrierOutages, OutageStatus};
+use crate::sms::provider::SmsRouter;
+use crate::sms::quiet_hours::QuietHours;
+use crate::sms::spam::SpamFilter;
+use crate::sms::transcript::TranscriptLog;
+use crate::wallet::KeyVault;
+
+/// Outbound SMS, whichever provider carries it
+///
+/// Opt-outs, budgets, transcripts, quiet hours and carrier outages apply
+/// here; the router picks the provider (Twilio, Vonage) for each destination.
+#[derive(Debug, Clone)]
+pub struct SmsGateway {
+    router: SmsRouter,
+    /// Numbers that must not be messaged (STOP compliance)
+    opt_outs: OptOutList,
+    /// GSM-7 substitutions before sending (off by default)
+    composer: Composer,
+    /// Per-segment cost estimation and daily budget
+    costs: SpendTracker,
+    /// Support transcripts (no-op unless enabled)
+    transcripts: TranscriptLog,
+    /// Local quiet hours for non-urgent notifications (None = off)
+    quiet_hours: Option<QuietHours>,
+    /// Where notifications wait out quiet hours and carrier outages
+    outbox: Option<SmsOutboxRepository>,
+    /// Calling codes whose carriers are failing (None = off)
+    outages: Option<CarrierOutages>,
+    /// Inbound spam screening (None = off)
+    spam: Option<SpamFilter>,
+}
+
+/// Outcome of a non-urgent notification
+#[derive(Debug)]
+pub enum Delivery {
+    Sent(SendResult),
+    /// Held until the end of the recipient's quiet hours
+    Deferred(DateTime<Utc>),
+    /// Held until the carrier outage in the recipient's calling code ends
+    HeldForOutage,
+}
+
+/// Deferred messages sent per outbox flush
+const OUTBOX_BATCH: i64 = 50;
+
+/// Result of sending an SMS
+#[derive(Debug)]
+pub struct SendResult {
+    /// Provider's message ID (Twilio SID, Vonage message-id)
+    pub message_sid: String,
+    pub status: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SmsError {
+    #[error("HTTP request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("API error: {0}")]
+    Api(String),
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("Recipient has opted out")]
+    OptedOut,
+    /// The number is stored hashed (PHONE_STORAGE=hashed) and can't be messaged
+    #[error("Recipient number is stored hashed")]
+    HashedNumber,
+    /// The carrier or provider for the number can't take MMS
+    #[error("Recipient can't receive MMS")]
+    MediaUnsupported,
+}
+
+impl SmsGateway {
+    /// Create a gateway sending through `router`
+    pub fn new(router: SmsRouter) -> Self {
+        Self {
+            router,
+            opt_outs: OptOutList::new(),
+            composer: Composer::default(),
+            costs: SpendTracker::default(),
+            transcripts: TranscriptLog::default(),
+            quiet_hours: None,
+            outbox: None,
+            outages: None,
+            spam: None,
+        }
+    }
+
+    /// Providers and per-country routes
+    pub fn router(&self) -> &SmsRouter {
+        &self.router
+    }
+
+    /// Use a shared (usually persisted) opt-out list
+    pub fn set_opt_outs(&mut self, opt_outs: OptOutList) {
+        self.opt_outs = opt_outs;
+    }
+
+    pub fn opt_outs(&self) -> &OptOutList {
+        &self.opt_outs
+    }
+
+    /// Swap characters outside GSM-7 before sending (SMS_GSM7)
+    pub fn set_composer(&mut self, composer: Composer) {
+        self.composer = composer;
+    }
+
+    pub fn composer(&self) -> &Composer {
+        &self.composer
+    }
+
+    /// Use a shared (usually persisted) spend tracker
+    pub fn set_costs(&mut self, costs: SpendTracker) {
+        self.costs = costs;
+    }
+
+    pub fn costs(&self) -> &SpendTracker {
+        &self.costs
+    }
+
+    /// Store inbound and outbound messages for support
+    pub fn set_transcripts(&mut self, transcripts: TranscriptLog) {
+        self.transcripts = transcripts;
+    }
+
+    pub fn transcripts(&self) -> &TranscriptLog {
+        &self.transcripts
+    }
+
+    /// Where non-urgent notifications wait (quiet hours, outages)
+    pub fn set_outbox(&mut self, outbox: SmsOutboxRepository) {
+        self.outbox = Some(outbox);
+    }
+
+    /// Hold non-urgent notifications in the outbox during local quiet hours
+    pub fn set_quiet_hours(&mut self, quiet_hours: QuietHours) {
+        self.quiet_hours = Some(quiet_hours);
+    }
+
+    /// Screen inbound messages for spam before they are processed
+    pub fn set_spam_filter(&mut self, spam: SpamFilter) {
+        self.spam = Some(spam);
+    }
+
+    pub fn spam_filter(&self) -> Option<&SpamFilter> {
+        self.spam.as_ref()
+    }
+
+    /// Hold non-urgent notifications in the outbox while a calling code's
+    /// carrier is down; codes with messages still held are probed again
+    pub async fn set_outages(&mut self, outages: CarrierOutages) -> Result<(), sqlx::Error> {
+        if let Some(ref outbox) = self.outbox {
+            for (code, _) in outbox.held_counts().await? {
+                outages.mark_down(&code, Utc::now());
+            }
+        }
+        self.outages = Some(outages);
+        Ok(())
+    }
+
+    /// Send a non-urgent notification (receipt, reminder, announcement),
+    /// deferring it to the morning if the recipient is in quiet hours.
+    /// Replies and security messages must use `send_sms` instead.
+    pub async fn send_notification(&self, to: &str, body: &str) -> Result<Delivery, SmsError> {
+        if KeyVault::is_blind_index(to) {
+            return Err(SmsError::HashedNumber);
+        }
+        if let (Some(quiet), Some(outbox)) = (&self.quiet_hours, &self.outbox) {
+            if let Some(send_after) = quiet.defer_until(to, Utc::now()) {
+                match outbox.enqueue(to, body, send_after).await {
+                    Ok(()) => {
+                        tracing::info!(to = %to, %send_after, "Deferring notification until quiet hours end");
+                        return Ok(Delivery::Deferred(send_after));
+                    }
+                    // Better at night than never
+                    Err(e) => tracing::error!(to = %to, "Failed to defer notification, sending now: {}", e),
+                }
+            }
+        }
+        if let Some(code) = self.outages.as_ref().and_then(|o| o.down_code(to)) {
+            if self.hold_for_outage(to, body, &code).await {
+                return Ok(Delivery::HeldForOutage);
+            }
+        }
+        match self.send_sms_with_priority(to, body, MessagePriority::Normal).await {
+            Ok(result) => Ok(Delivery::Sent(result)),
+            // This failure may be the one that showed the outage
+            Err(e) => match self.outages.as_ref().and_then(|o| o.down_code(to)) {
+                Some(code) if self.hold_for_outage(to, body, &code).await => Ok(Delivery::HeldForOutage),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Queue a notification behind a carrier outage; false if it couldn't be
+    async fn hold_for_outage(&self, to: &str, body: &str, code: &str) -> bool {
+        let Some(ref outbox) = self.outbox else {
+            return false;
+        };
+        match outbox.hold_for_outage(to, body, code).await {
+            Ok(()) => {
+                tracing::info!(to = %to, calling_code = code, "Holding notification during carrier outage");
+                true
+            }
+            Err(e) => {
+                tracing::error!(to = %to, "Failed to hold notification for outage: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Retry one held message per calling code due a probe; if it goes out
+    /// the rest are released to `flush_outbox`. Returns codes back up
+    pub async fn probe_outages(&self) -> Result<Vec<String>, sqlx::Error> {
+        let (Some(outages), Some(outbox)) = (&self.outages, &self.outbox) else {
+            return Ok(vec![]);
+        };
+        let mut recovered = Vec::new();
+        for code in outages.due_probes(Utc::now()) {
+            let Some(message) = outbox.oldest_held(&code).await? else {
+                // Nothing to probe with; the next failures will tell
+                outages.clear(&code);
+                continue;
+            };
+            match self.send_sms_with_priority(&message.phone, &message.body, MessagePriority::Normal).await {
+                Ok(_) => {
+                    outbox.remove(message.id).await?;
+                    let released = outbox.release_outage(&code).await?;
+                    tracing::info!(calling_code = %code, released, "Carrier probe delivered; releasing held notifications");
+                    recovered.push(code);
+                }
+                Err(SmsError::OptedOut) => outbox.remove(message.id).await?,
+                Err(e) => tracing::debug!(calling_code = %code, "Carrier probe failed: {}", e),
+            }
+        }
+        Ok(recovered)
+    }
+
+    /// Calling codes that are down, with how many messages each is holding
+    pub async fn outage_report(&self) -> Result<Vec<OutageStatus>, sqlx::Error> {
+        let Some(ref outages) = self.outages else {
+            return Ok(vec![]);
+        };
+        let mut statuses = outages.statuses();
+        if let Some(ref outbox) = self.outbox {
+            let held = outbox.held_counts().await?;
+            for status in &mut statuses {
+                status.queued = held.iter().find(|(code, _)| *code == status.calling_code).map_or(0, |(_, n)| *n);
+            }
+        }
+        Ok(statuses)
+    }
+
+    /// Send deferred notifications whose quiet hours have ended; returns how many were sent
+    pub async fn flush_outbox(&self) -> Result<usize, sqlx::Error> {
+        let Some(ref outbox) = self.outbox else {
+            return Ok(0);
+        };
+        let mut sent = 0;
+        for message in outbox.take_due(OUTBOX_BATCH).await? {
+            match self.send_sms_with_priority(&message.phone, &message.body, MessagePriority::Normal).await {
+                Ok(_) => sent += 1,
+                Err(e) => match self.outages.as_ref().and_then(|o| o.down_code(&message.phone)) {
+                    Some(code) => {
+                        self.hold_for_outage(&message.phone, &message.body, &code).await;
+                    }
+                    None => tracing::warn!(to = %message.phone, id = %message.id, "Deferred notification not sent: {}", e),
+                },
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Send an MMS with the image at `media_url`, in full. Fails with
+    /// `MediaUnsupported` where pictures can't go, so the caller can text
+    /// instead
+    pub async fn send_mms(&self, to: &str, body: &str, media_url: &str) -> Result<SendResult, SmsError> {
+        if KeyVault::is_blind_index(to) {
+            return Err(SmsError::HashedNumber);
+        }
+        if self.opt_outs.is_opted_out(to) {
+            tracing::info!(to = %to, "Suppressing MMS to opted-out number");
+            return Err(SmsError::OptedOut);
+        }
+        if !self.router.takes_media(to) {
+            return Err(SmsError::MediaUnsupported);
+        }
+        let provider = self.router.for_number(to);
+        let Some(sending) = provider.send_media(to, body, media_url) else {
+            return Err(SmsError::MediaUnsupported);
+        };
+        let result = match sending.await {
+            Ok(result) => result,
+            Err(e) => {
+                if let Some(ref outages) = self.outages {
+                    outages.record_failure(to, &e.to_string(), Utc::now());
+                }
+                return Err(e);
+            }
+        };
+        if let Some(ref outages) = self.outages {
+            outages.record_success(to);
+        }
+        tracing::debug!(to = %to, provider = provider.name(), sid = %result.message_sid, "MMS sent");
+
+        self.costs.record(to, body).await;
+        self.transcripts.record_outbound(to, &format!("{}\n[{}]", body, media_url)).await;
+        Ok(result)
+    }
+
+    /// Send an SMS message that must be delivered in full
+    pub async fn send_sms(&self, to: &str, body: &str) -> Result<SendResult, SmsError> {
+        self.send_sms_with_priority(to, body, MessagePriority::Critical).await
+    }
+
+    /// Send an SMS message
+    ///
+    /// Every outbound message goes through here, so opted-out numbers are
+    /// suppressed, GSM-7 substitutions apply, and spend (and the transcript)
+    /// is recorded for replies and background notifications alike.
+    pub async fn send_sms_with_priority(
+        &self,
+        to: &str,
+        body: &str,
+        priority: MessagePriority,
+    ) -> Result<SendResult, SmsError> {
+        if KeyVault::is_blind_index(to) {
+            tracing::debug!("Not messaging a hashed number");
+            return Err(SmsError::HashedNumber);
+        }
+        if self.opt_outs.is_opted_out(to) {
+            tracing::info!(to = %to, "Suppressing SMS to opted-out number");
+            return Err(SmsError::OptedOut);
+        }
+
+        let composed = self.composer.compose(body);
+        let body = self.costs.prepare(&composed.body, priority);
+        let body = body.as_str();
+        let (country, segments, cost) = self.costs.estimate(to, body);
+
+        let provider = self.router.for_number(to);
+        let result = match provider.send(to, body).await {
+            Ok(result) => result,
+            Err(e) => {
+                if let Some(ref outages) = self.outages {
+                    outages.record_failure(to, &e.to_string(), Utc::now());
+                }
+                return Err(e);
+            }
+        };
+        if let Some(ref outages) = self.outages {
+            outages.record_success(to);
+        }
+        // One line per message, so spend can be traced to what was sent
+        tracing::info!(
+            sid = %result.message_sid,
+            provider = provider.name(),
+            country = %country,
+            ?priority,
+            segments,
+            encoding = Encoding::of(body).as_str(),
+            cost,
+            replaced = composed.replaced,
+            removed = composed.removed,
+            "SMS sent"
+        );
+
+        self.costs.record(to, body).await;
+        self.transcripts.record_outbound(to, body).await;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SmsRoutingConfig, TwilioConfig, VonageConfig};
+
+    #[tokio::test]
+    async fn test_send_suppressed_after_opt_out() {
+        let config = TwilioConfig {
+            account_sid: "test_sid".to_string(),
+            auth_token: "12345".into(),
+            phone_number: "+1234567890".to_string(),
+            api_base: "https://api.twilio.com".to_string(),
+        };
+        let router = SmsRouter::from_config(&config, &VonageConfig::default(), &SmsRoutingConfig::default()).unwrap();
+        let gateway = SmsGateway::new(router);
+
+        gateway.opt_outs().opt_out("+15551234567", "STOP").await;
+        assert!(matches!(
+            gateway.send_sms("+15551234567", "Your balance...").await,
+            Err(SmsError::OptedOut)
+        ));
+        assert!(matches!(
+            gateway.send_mms("+15551234567", "Receipt", "https://example.com/r.png").await,
+            Err(SmsError::OptedOut)
+        ));
+        // Pictures only go where carriers take them (MMS_CALLING_CODES=1)
+        assert!(matches!(
+            gateway.send_mms("+254700000000", "Receipt", "https://example.com/r.png").await,
+            Err(SmsError::MediaUnsupported)
+        ));
+    }
+}
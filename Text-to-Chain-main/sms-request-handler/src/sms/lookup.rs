@@ -0,0 +1,211 @@
+//! Country and carrier of a sender, for segmenting command metrics
+//!
+//! The country is the E.164 calling code, read straight off the number. The
+//! carrier comes from Twilio Lookup (line type intelligence), which is billed
+//! per request, so results are cached in memory and in `phone_carriers` and
+//! only looked up again once they are older than the refresh period. Lookups
+//! run in the background: a number seen for the first time is reported as
+//! `unknown` until its result is in.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::config::{CarrierLookupConfig, TwilioConfig};
+use crate::db::PhoneCarrierRepository;
+use crate::sms::gateway::SmsError;
+
+/// Wait before looking a number up again after a failed lookup
+const RETRY_AFTER_FAILURE_MINUTES: i64 = 60;
+
+/// E.164 calling code of a number (`+254712345678` -> `254`)
+///
+/// Codes are prefix-free and 1-3 digits long; which length applies follows
+/// from the leading digits of the ITU-T E.164 assignment.
+pub fn calling_code(phone: &str) -> Option<&str> {
+    let digits = phone.strip_prefix('+')?;
+    let bytes = digits.as_bytes();
+    if bytes.len() < 4 || !bytes.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let len = match (bytes[0], bytes[1]) {
+        (b'0', _) => return None,
+        (b'1' | b'7', _) => 1,
+        (b'2', b'0' | b'7') => 2,
+        (b'3', b'0'..=b'4' | b'6' | b'9') => 2,
+        (b'4', b'0' | b'1' | b'3'..=b'9') => 2,
+        (b'5', b'1'..=b'8') => 2,
+        (b'6', b'0'..=b'6') => 2,
+        (b'8', b'1' | b'2' | b'4' | b'6') => 2,
+        (b'9', b'0'..=b'5' | b'8') => 2,
+        _ => 3,
+    };
+    Some(&digits[..len])
+}
+
+/// Lookup v2 response, reduced to the fields we keep
+#[derive(Debug, Deserialize)]
+struct LookupResponse {
+    line_type_intelligence: Option<LineTypeIntelligence>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LineTypeIntelligence {
+    carrier_name: Option<String>,
+    #[serde(rename = "type")]
+    line_type: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedCarrier {
+    carrier: Option<String>,
+    /// Look the number up again from here on
+    refresh_at: DateTime<Utc>,
+    refreshing: bool,
+}
+
+/// Cached Twilio Lookup client
+#[derive(Clone)]
+pub struct CarrierLookup {
+    client: Client,
+    account_sid: String,
+    auth_token: String,
+    refresh: Duration,
+    cache: Arc<Mutex<HashMap<String, CachedCarrier>>>,
+    repo: PhoneCarrierRepository,
+}
+
+impl CarrierLookup {
+    /// Load cached lookups (None when `CARRIER_LOOKUP` is off)
+    pub async fn load(
+        config: &CarrierLookupConfig,
+        twilio: &TwilioConfig,
+        repo: PhoneCarrierRepository,
+    ) -> Result<Option<Self>, sqlx::Error> {
+        if !config.is_enabled() {
+            return Ok(None);
+        }
+        let refresh = Duration::days(config.refresh_days.max(1));
+        let cache = repo
+            .list()
+            .await?
+            .into_iter()
+            .map(|row| {
+                let cached = CachedCarrier { carrier: row.carrier, refresh_at: row.looked_up_at + refresh, refreshing: false };
+                (row.phone, cached)
+            })
+            .collect();
+        Ok(Some(Self {
+            client: Client::new(),
+            account_sid: twilio.account_sid.clone(),
+            auth_token: twilio.auth_token.to_string(),
+            refresh,
+            cache: Arc::new(Mutex::new(cache)),
+            repo,
+        }))
+    }
+
+    /// Numbers with a cached lookup
+    pub fn cached(&self) -> usize {
+        self.cache.lock().map(|cache| cache.len()).unwrap_or(0)
+    }
+
+    /// Cached carrier of a number; a missing or stale entry is looked up in
+    /// the background and the old value (if any) returned meanwhile
+    pub fn carrier(&self, phone: &str) -> Option<String> {
+        calling_code(phone)?;
+        let mut cache = self.cache.lock().ok()?;
+        let entry = cache.entry(phone.to_string()).or_insert_with(|| CachedCarrier {
+            carrier: None,
+            refresh_at: Utc::now(),
+            refreshing: false,
+        });
+        if !entry.refreshing && entry.refresh_at <= Utc::now() {
+            entry.refreshing = true;
+            let lookup = self.clone();
+            let phone = phone.to_string();
+            tokio::spawn(async move { lookup.refresh(phone).await });
+        }
+        entry.carrier.clone()
+    }
+
+    async fn refresh(&self, phone: String) {
+        let looked_up = match self.fetch(&phone).await {
+            Ok((carrier, line_type)) => {
+                if let Err(e) = self.repo.upsert(&phone, carrier.as_deref(), line_type.as_deref()).await {
+                    tracing::warn!(phone = %phone, "Failed to cache carrier lookup: {}", e);
+                }
+                tracing::debug!(phone = %phone, carrier = ?carrier, "Carrier looked up");
+                Some(carrier)
+            }
+            Err(e) => {
+                tracing::warn!(phone = %phone, "Carrier lookup failed: {}", e);
+                None
+            }
+        };
+
+        if let Ok(mut cache) = self.cache.lock() {
+            if let Some(entry) = cache.get_mut(&phone) {
+                entry.refreshing = false;
+                match looked_up {
+                    Some(carrier) => {
+                        entry.carrier = carrier;
+                        entry.refresh_at = Utc::now() + self.refresh;
+                    }
+                    None => entry.refresh_at = Utc::now() + Duration::minutes(RETRY_AFTER_FAILURE_MINUTES),
+                }
+            }
+        }
+    }
+
+    /// Carrier name and line type from Lookup v2
+    async fn fetch(&self, phone: &str) -> Result<(Option<String>, Option<String>), SmsError> {
+        let url = format!("https://lookups.twilio.com/v2/PhoneNumbers/{}", phone);
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("Fields", "line_type_intelligence")])
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(SmsError::Api(error_text));
+        }
+
+        let lookup: LookupResponse = response.json().await?;
+        Ok(lookup
+            .line_type_intelligence
+            .map(|info| (info.carrier_name.filter(|c| !c.is_empty()), info.line_type))
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calling_code() {
+        assert_eq!(calling_code("+15551234567"), Some("1"));
+        assert_eq!(calling_code("+79161234567"), Some("7"));
+        assert_eq!(calling_code("+254712345678"), Some("254"));
+        assert_eq!(calling_code("+2348012345678"), Some("234"));
+        assert_eq!(calling_code("+27821234567"), Some("27"));
+        assert_eq!(calling_code("+447700900000"), Some("44"));
+        assert_eq!(calling_code("+420601123456"), Some("420"));
+        assert_eq!(calling_code("+919812345678"), Some("91"));
+        assert_eq!(calling_code("+971501234567"), Some("971"));
+    }
+
+    #[test]
+    fn test_calling_code_rejects_non_e164() {
+        assert_eq!(calling_code("0712345678"), None);
+        assert_eq!(calling_code("+0712345678"), None);
+        assert_eq!(calling_code("+2547abc"), None);
+        assert_eq!(calling_code("+1"), None);
+    }
+}
@@ -1,5 +1,29 @@
+pub mod composer;
+pub mod cost;
+pub mod display;
+pub mod gateway;
+pub mod lookup;
+pub mod opt_out;
+pub mod outages;
+pub mod provider;
+pub mod quiet_hours;
+pub mod sim_swap;
+pub mod spam;
+pub mod transcript;
 pub mod twilio;
+pub mod vonage;
 pub mod webhook;
 
-pub use twilio::TwilioClient;
-pub use webhook::{incoming_sms_handler, incoming_sms_json_handler};
+pub use composer::{Composer, Gsm7Mode};
+pub use cost::{MessagePriority, SpendTracker};
+pub use display::{NumberLocale, SmsDisplay};
+pub use lookup::CarrierLookup;
+pub use opt_out::OptOutList;
+pub use outages::CarrierOutages;
+pub use quiet_hours::QuietHours;
+pub use sim_swap::SimSwapGuard;
+pub use spam::SpamFilter;
+pub use transcript::TranscriptLog;
+pub use gateway::{Delivery, SmsGateway};
+pub use provider::SmsRouter;
+pub use webhook::{incoming_sms_handler, incoming_sms_json_handler, vonage_inbound_get_handler, vonage_inbound_post_handler};
@@ -0,0 +1,175 @@
+//! Carrier keyword compliance (STOP / START / HELP)
+//!
+//! Opt-outs are kept in memory for a cheap check on every outbound message
+//! and persisted to `sms_opt_outs` when a database is available.
+
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use crate::db::OptOutRepository;
+
+/// Reply confirming an opt-out (sent inline, the only message allowed after STOP)
+pub const STOP_REPLY: &str =
+    "TextChain: You are unsubscribed and will receive no further messages. Reply START to resubscribe.";
+
+/// Reply confirming a re-subscription
+pub const START_REPLY: &str =
+    "TextChain: You are resubscribed. Reply MENU for commands, HELP for help, STOP to opt out.";
+
+/// Program information required for HELP
+pub const HELP_REPLY: &str =
+    "TextChain: SMS crypto wallet. Reply MENU for commands. Msg&data rates may apply. Reply STOP to opt out.";
+
+/// Carrier-defined keywords that must be honoured regardless of app state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComplianceKeyword {
+    OptOut,
+    OptIn,
+    Help,
+}
+
+impl ComplianceKeyword {
+    /// Match a whole message against the standard keyword list
+    pub fn parse(body: &str) -> Option<Self> {
+        match body.trim().to_uppercase().as_str() {
+            "STOP" | "STOPALL" | "UNSUBSCRIBE" | "CANCEL" | "END" | "QUIT" => Some(ComplianceKeyword::OptOut),
+            "START" | "UNSTOP" | "YES" => Some(ComplianceKeyword::OptIn),
+            "HELP" | "INFO" => Some(ComplianceKeyword::Help),
+            _ => None,
+        }
+    }
+}
+
+/// What the webhook should do with an incoming message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InboundAction {
+    /// Reply with this compliance message and stop
+    Reply(&'static str),
+    /// Sender is opted out - drop the message without replying
+    Ignore,
+    /// Handle as a normal command
+    Process,
+}
+
+/// Shared set of opted-out numbers
+#[derive(Clone, Default)]
+pub struct OptOutList {
+    numbers: Arc<RwLock<HashSet<String>>>,
+    repo: Option<OptOutRepository>,
+}
+
+impl OptOutList {
+    /// In-memory list (no database)
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load persisted opt-outs and keep writing changes to the database
+    pub async fn load(repo: OptOutRepository) -> Result<Self, sqlx::Error> {
+        let numbers = repo.list().await?.into_iter().collect();
+        Ok(Self {
+            numbers: Arc::new(RwLock::new(numbers)),
+            repo: Some(repo),
+        })
+    }
+
+    pub fn is_opted_out(&self, phone: &str) -> bool {
+        self.numbers.read().map(|n| n.contains(phone)).unwrap_or(false)
+    }
+
+    pub async fn opt_out(&self, phone: &str, keyword: &str) {
+        if let Ok(mut numbers) = self.numbers.write() {
+            numbers.insert(phone.to_string());
+        }
+        if let Some(ref repo) = self.repo {
+            if let Err(e) = repo.opt_out(phone, keyword).await {
+                tracing::error!(phone = %phone, "Failed to persist opt-out: {}", e);
+            }
+        }
+        tracing::info!(phone = %phone, keyword = %keyword, "Number opted out");
+    }
+
+    pub async fn opt_in(&self, phone: &str) {
+        if let Ok(mut numbers) = self.numbers.write() {
+            numbers.remove(phone);
+        }
+        if let Some(ref repo) = self.repo {
+            if let Err(e) = repo.opt_in(phone).await {
+                tracing::error!(phone = %phone, "Failed to persist opt-in: {}", e);
+            }
+        }
+        tracing::info!(phone = %phone, "Number opted back in");
+    }
+
+    /// Apply compliance keywords and decide how the webhook should proceed.
+    ///
+    /// START from a number that isn't opted out falls through to normal
+    /// processing, where it begins onboarding.
+    pub async fn handle_inbound(&self, phone: &str, body: &str) -> InboundAction {
+        let opted_out = self.is_opted_out(phone);
+
+        match ComplianceKeyword::parse(body) {
+            Some(ComplianceKeyword::OptOut) => {
+                self.opt_out(phone, &body.trim().to_uppercase()).await;
+                InboundAction::Reply(STOP_REPLY)
+            }
+            Some(ComplianceKeyword::OptIn) if opted_out => {
+                self.opt_in(phone).await;
+                InboundAction::Reply(START_REPLY)
+            }
+            Some(ComplianceKeyword::Help) => InboundAction::Reply(HELP_REPLY),
+            _ if opted_out => InboundAction::Ignore,
+            _ => InboundAction::Process,
+        }
+    }
+}
+
+impl std::fmt::Debug for OptOutList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OptOutList")
+            .field("count", &self.numbers.read().map(|n| n.len()).unwrap_or(0))
+            .field("persistent", &self.repo.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHONE: &str = "+15551234567";
+
+    #[test]
+    fn test_keyword_parsing() {
+        assert_eq!(ComplianceKeyword::parse(" stop "), Some(ComplianceKeyword::OptOut));
+        assert_eq!(ComplianceKeyword::parse("Unsubscribe"), Some(ComplianceKeyword::OptOut));
+        assert_eq!(ComplianceKeyword::parse("UNSTOP"), Some(ComplianceKeyword::OptIn));
+        assert_eq!(ComplianceKeyword::parse("help"), Some(ComplianceKeyword::Help));
+        assert_eq!(ComplianceKeyword::parse("STOP SENDING 10"), None);
+        assert_eq!(ComplianceKeyword::parse("BALANCE"), None);
+    }
+
+    #[tokio::test]
+    async fn test_opt_out_lifecycle() {
+        let list = OptOutList::new();
+
+        // Normal traffic, and START for a subscribed number goes to onboarding
+        assert_eq!(list.handle_inbound(PHONE, "BALANCE").await, InboundAction::Process);
+        assert_eq!(list.handle_inbound(PHONE, "START").await, InboundAction::Process);
+
+        // STOP opts out and suppresses everything but compliance keywords
+        assert_eq!(list.handle_inbound(PHONE, "stop").await, InboundAction::Reply(STOP_REPLY));
+        assert!(list.is_opted_out(PHONE));
+        assert_eq!(list.handle_inbound(PHONE, "BALANCE").await, InboundAction::Ignore);
+        assert_eq!(list.handle_inbound(PHONE, "HELP").await, InboundAction::Reply(HELP_REPLY));
+        assert!(list.is_opted_out(PHONE));
+
+        // START re-enables
+        assert_eq!(list.handle_inbound(PHONE, "start").await, InboundAction::Reply(START_REPLY));
+        assert!(!list.is_opted_out(PHONE));
+        assert_eq!(list.handle_inbound(PHONE, "BALANCE").await, InboundAction::Process);
+
+        // Other numbers are unaffected throughout
+        assert!(!list.is_opted_out("+15550000000"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Carrier outage detection per calling code
+//!
+//! When sends to several different numbers in one calling code fail within
+//! the window, the code is marked down. Non-urgent notifications to it then
+//! wait in `sms_outbox` instead of being dropped, and every probe interval
+//! the oldest waiting message is tried again: once one goes out the outage
+//! is over and the rest are released to the regular outbox flush. Replies
+//! and security messages are still attempted, and one that goes through
+//! ends the outage too.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::config::SmsOutageConfig;
+use crate::sms::lookup::calling_code;
+
+/// Detection state for one calling code
+#[derive(Debug, Default)]
+struct CodeState {
+    /// Recent failed sends: (number, when)
+    failures: Vec<(String, DateTime<Utc>)>,
+    /// Set while the code is considered down
+    down_since: Option<DateTime<Utc>>,
+    last_probe: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+/// A calling code that is down, for /admin/summary
+#[derive(Debug, Clone, Serialize)]
+pub struct OutageStatus {
+    pub calling_code: String,
+    pub since: DateTime<Utc>,
+    pub last_probe: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    /// Notifications waiting for the outage to end
+    pub queued: i64,
+}
+
+/// Outage state shared by every clone of the gateway
+#[derive(Debug, Clone)]
+pub struct CarrierOutages {
+    codes: Arc<Mutex<HashMap<String, CodeState>>>,
+    /// Distinct failing numbers that mark a code down
+    threshold: usize,
+    window: Duration,
+    probe_every: Duration,
+}
+
+impl CarrierOutages {
+    /// None when `SMS_OUTAGE_THRESHOLD` is 0
+    pub fn from_config(config: &SmsOutageConfig) -> Option<Self> {
+        (config.threshold > 0).then(|| Self {
+            codes: Arc::default(),
+            threshold: config.threshold,
+            window: Duration::seconds(config.window_secs.max(1) as i64),
+            probe_every: Duration::seconds(config.probe_secs.max(1) as i64),
+        })
+    }
+
+    fn codes(&self) -> std::sync::MutexGuard<'_, HashMap<String, CodeState>> {
+        self.codes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The calling code of `phone` if it is down
+    pub fn down_code(&self, phone: &str) -> Option<String> {
+        let code = calling_code(phone)?;
+        self.codes().get(code).filter(|state| state.down_since.is_some()).map(|_| code.to_string())
+    }
+
+    /// Count a failed send; true if it marked the number's code down
+    pub fn record_failure(&self, phone: &str, error: &str, now: DateTime<Utc>) -> bool {
+        let Some(code) = calling_code(phone) else {
+            return false;
+        };
+        let mut codes = self.codes();
+        let state = codes.entry(code.to_string()).or_default();
+        state.last_error = Some(error.to_string());
+        state.failures.retain(|(number, at)| now - *at < self.window && number != phone);
+        state.failures.push((phone.to_string(), now));
+        if state.down_since.is_some() || state.failures.len() < self.threshold {
+            return false;
+        }
+        state.down_since = Some(now);
+        state.last_probe = Some(now);
+        tracing::warn!(calling_code = code, failures = state.failures.len(), error, "SMS carrier outage detected");
+        true
+    }
+
+    /// A send went out; true if it ended an outage
+    pub fn record_success(&self, phone: &str) -> bool {
+        let Some(code) = calling_code(phone) else {
+            return false;
+        };
+        let ended = self.codes().remove(code).is_some_and(|state| state.down_since.is_some());
+        if ended {
+            tracing::info!(calling_code = code, "SMS carrier outage over");
+        }
+        ended
+    }
+
+    /// Mark a code down without probing it straight away (e.g. messages
+    /// still held from before a restart)
+    pub fn mark_down(&self, code: &str, now: DateTime<Utc>) {
+        let mut codes = self.codes();
+        let state = codes.entry(code.to_string()).or_default();
+        state.down_since.get_or_insert(now);
+        state.last_probe.get_or_insert(now);
+    }
+
+    /// End an outage with nothing left to probe with
+    pub fn clear(&self, code: &str) {
+        self.codes().remove(code);
+    }
+
+    /// Codes due a probe, which is taken as started now
+    pub fn due_probes(&self, now: DateTime<Utc>) -> Vec<String> {
+        let mut codes = self.codes();
+        codes
+            .iter_mut()
+            .filter(|(_, state)| state.down_since.is_some() && state.last_probe.is_none_or(|at| now - at >= self.probe_every))
+            .map(|(code, state)| {
+                state.last_probe = Some(now);
+                code.clone()
+            })
+            .collect()
+    }
+
+    /// Codes currently down, with `queued` left at 0 for the caller to fill in
+    pub fn statuses(&self) -> Vec<OutageStatus> {
+        let mut statuses: Vec<OutageStatus> = self
+            .codes()
+            .iter()
+            .filter_map(|(code, state)| {
+                Some(OutageStatus {
+                    calling_code: code.clone(),
+                    since: state.down_since?,
+                    last_probe: state.last_probe,
+                    last_error: state.last_error.clone(),
+                    queued: 0,
+                })
+            })
+            .collect();
+        statuses.sort_by(|a, b| a.calling_code.cmp(&b.calling_code));
+        statuses
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outages() -> CarrierOutages {
+        CarrierOutages::from_config(&SmsOutageConfig { threshold: 3, window_secs: 300, probe_secs: 60 }).unwrap()
+    }
+
+    #[test]
+    fn test_distinct_failures_mark_code_down() {
+        let outages = outages();
+        let now = Utc::now();
+        // The same number failing again is one failure
+        assert!(!outages.record_failure("+254700000001", "timeout", now));
+        assert!(!outages.record_failure("+254700000001", "timeout", now));
+        assert!(!outages.record_failure("+254700000002", "timeout", now));
+        // Other codes are counted apart
+        assert!(!outages.record_failure("+2348000000001", "timeout", now));
+        assert!(outages.down_code("+254700000009").is_none());
+        assert!(outages.record_failure("+254700000003", "timeout", now));
+        assert_eq!(outages.down_code("+254700000009").as_deref(), Some("254"));
+        assert!(outages.down_code("+2348000000001").is_none());
+
+        // Probed once per interval, and a delivery ends it
+        assert!(outages.due_probes(now).is_empty());
+        assert_eq!(outages.due_probes(now + Duration::seconds(60)), ["254"]);
+        assert!(outages.due_probes(now + Duration::seconds(90)).is_empty());
+        assert!(outages.record_success("+254700000004"));
+        assert!(outages.down_code("+254700000009").is_none());
+        assert!(!outages.record_success("+254700000004"));
+    }
+
+    #[test]
+    fn test_failures_outside_window_do_not_count() {
+        let outages = outages();
+        let start = Utc::now();
+        outages.record_failure("+254700000001", "error", start);
+        outages.record_failure("+254700000002", "error", start);
+        assert!(!outages.record_failure("+254700000003", "error", start + Duration::seconds(400)));
+        assert!(outages.statuses().is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+//! SMS providers and per-country routing
+//!
+//! Each provider sends through its own API and owns the format and signing
+//! of its inbound webhooks. `SmsRouter` picks the provider for a
+//! destination: `SMS_ROUTES` maps calling codes to providers (longest code
+//! wins), and every other number goes through `SMS_PROVIDER`. Pictures
+//! only go to calling codes in `MMS_CALLING_CODES`, through providers that
+//! can send them.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use crate::config::{SmsRoutingConfig, TwilioConfig, VonageConfig};
+use crate::sms::gateway::{SendResult, SmsError};
+use crate::sms::twilio::TwilioClient;
+use crate::sms::vonage::VonageClient;
+
+/// A message received by any provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct InboundSms {
+    /// Sender in E.164 (`+` and digits)
+    pub from: String,
+    /// Number the message was sent to
+    pub to: String,
+    pub body: String,
+    pub message_id: String,
+}
+
+/// An inbound webhook as received
+#[derive(Debug, Clone, Default)]
+pub struct InboundWebhook {
+    /// Public URL the provider called (part of Twilio's signature)
+    pub url: String,
+    /// Form, query or JSON fields
+    pub params: HashMap<String, String>,
+    /// Signature header, for providers that sign with one
+    pub signature: Option<String>,
+}
+
+/// An SMS API: sending, and reading its inbound webhooks
+///
+/// Opt-outs, budgets and transcripts are applied by `SmsGateway`, not here.
+pub trait SmsProvider: Send + Sync {
+    /// Name used in `SMS_PROVIDER` and `SMS_ROUTES`
+    fn name(&self) -> &'static str;
+
+    fn send<'a>(&'a self, to: &'a str, body: &'a str) -> BoxFuture<'a, Result<SendResult, SmsError>>;
+
+    /// An MMS with the image the provider fetches from `media_url`; None
+    /// when the provider can't send media
+    fn send_media<'a>(
+        &'a self,
+        _to: &'a str,
+        _body: &'a str,
+        _media_url: &'a str,
+    ) -> Option<BoxFuture<'a, Result<SendResult, SmsError>>> {
+        None
+    }
+
+    /// The message in a webhook (None if it isn't one, e.g. a delivery receipt)
+    fn parse_inbound(&self, webhook: &InboundWebhook) -> Option<InboundSms>;
+
+    /// Whether the webhook was signed by this provider
+    fn validate_signature(&self, webhook: &InboundWebhook) -> bool;
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RoutingError {
+    #[error("Unknown SMS provider: {0}")]
+    Unknown(String),
+    #[error("SMS provider {0} is not configured")]
+    NotConfigured(&'static str),
+    #[error("Invalid SMS_ROUTES entry: {0}")]
+    InvalidRoute(String),
+    #[error("Invalid MMS_CALLING_CODES entry: {0}")]
+    InvalidMmsCode(String),
+}
+
+/// Provider for each destination
+#[derive(Clone)]
+pub struct SmsRouter {
+    providers: HashMap<&'static str, Arc<dyn SmsProvider>>,
+    default: Arc<dyn SmsProvider>,
+    /// Calling code and provider, longest code first
+    routes: Vec<(String, Arc<dyn SmsProvider>)>,
+    /// Calling codes whose carriers take MMS
+    mms_codes: Vec<String>,
+}
+
+impl fmt::Debug for SmsRouter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let routes: Vec<_> = self.routes.iter().map(|(code, p)| format!("+{}:{}", code, p.name())).collect();
+        f.debug_struct("SmsRouter")
+            .field("default", &self.default.name())
+            .field("routes", &routes)
+            .field("mms_codes", &self.mms_codes)
+            .finish()
+    }
+}
+
+impl SmsRouter {
+    /// Twilio, plus Vonage when its credentials are set
+    pub fn from_config(
+        twilio: &TwilioConfig,
+        vonage: &VonageConfig,
+        routing: &SmsRoutingConfig,
+    ) -> Result<Self, RoutingError> {
+        let mut providers: HashMap<&'static str, Arc<dyn SmsProvider>> = HashMap::new();
+        providers.insert("twilio", Arc::new(TwilioClient::new(twilio)));
+        if vonage.is_enabled() {
+            providers.insert("vonage", Arc::new(VonageClient::new(vonage)));
+        }
+
+        let lookup = |name: &str| -> Result<Arc<dyn SmsProvider>, RoutingError> {
+            let name = match name.trim().to_lowercase().as_str() {
+                "twilio" => "twilio",
+                "vonage" | "nexmo" => "vonage",
+                other => return Err(RoutingError::Unknown(other.to_string())),
+            };
+            providers.get(name).cloned().ok_or(RoutingError::NotConfigured(name))
+        };
+
+        let default = lookup(&routing.default_provider)?;
+        let mut routes = Vec::new();
+        for entry in routing.routes.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (code, name) = entry
+                .split_once(':')
+                .map(|(code, name)| (code.trim().trim_start_matches('+'), name))
+                .filter(|(code, _)| !code.is_empty() && code.bytes().all(|b| b.is_ascii_digit()))
+                .ok_or_else(|| RoutingError::InvalidRoute(entry.to_string()))?;
+            routes.push((code.to_string(), lookup(name)?));
+        }
+        routes.sort_by_key(|(code, _)| std::cmp::Reverse(code.len()));
+
+        let mut mms_codes = Vec::new();
+        for code in routing.mms_codes.split(',').map(|c| c.trim().trim_start_matches('+')).filter(|c| !c.is_empty()) {
+            if !code.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(RoutingError::InvalidMmsCode(code.to_string()));
+            }
+            mms_codes.push(code.to_string());
+        }
+
+        Ok(Self { providers, default, routes, mms_codes })
+    }
+
+    /// Provider for a destination number
+    pub fn for_number(&self, to: &str) -> &Arc<dyn SmsProvider> {
+        let digits = to.trim().trim_start_matches('+');
+        self.routes
+            .iter()
+            .find(|(code, _)| digits.starts_with(code.as_str()))
+            .map(|(_, provider)| provider)
+            .unwrap_or(&self.default)
+    }
+
+    /// Whether the destination's carrier takes MMS
+    pub fn takes_media(&self, to: &str) -> bool {
+        let digits = to.trim().trim_start_matches('+');
+        self.mms_codes.iter().any(|code| digits.starts_with(code.as_str()))
+    }
+
+    /// A configured provider by name, e.g. to read its webhooks
+    pub fn provider(&self, name: &str) -> Option<&Arc<dyn SmsProvider>> {
+        self.providers.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn twilio() -> TwilioConfig {
+        TwilioConfig {
+            account_sid: "sid".to_string(),
+            auth_token: "token".into(),
+            phone_number: "+15550000000".to_string(),
+            api_base: "https://api.twilio.com".to_string(),
+        }
+    }
+
+    fn vonage() -> VonageConfig {
+        VonageConfig {
+            api_key: "key".to_string(),
+            api_secret: "secret".into(),
+            from: "TextChain".to_string(),
+            signature_secret: Default::default(),
+        }
+    }
+
+    fn routing(default_provider: &str, routes: &str) -> SmsRoutingConfig {
+        SmsRoutingConfig { default_provider: default_provider.to_string(), routes: routes.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_routes_by_longest_calling_code() {
+        let router = SmsRouter::from_config(&twilio(), &vonage(), &routing("twilio", "254:vonage, +2547:twilio,44:nexmo")).unwrap();
+        assert_eq!(router.for_number("+254700000000").name(), "twilio");
+        assert_eq!(router.for_number("+254110000000").name(), "vonage");
+        assert_eq!(router.for_number("+447700900000").name(), "vonage");
+        assert_eq!(router.for_number("+15551234567").name(), "twilio");
+    }
+
+    #[test]
+    fn test_rejects_unusable_routes() {
+        let no_vonage = VonageConfig { api_key: String::new(), ..vonage() };
+        assert!(matches!(
+            SmsRouter::from_config(&twilio(), &no_vonage, &routing("twilio", "254:vonage")),
+            Err(RoutingError::NotConfigured("vonage"))
+        ));
+        assert!(matches!(
+            SmsRouter::from_config(&twilio(), &vonage(), &routing("plivo", "")),
+            Err(RoutingError::Unknown(_))
+        ));
+        assert!(matches!(
+            SmsRouter::from_config(&twilio(), &vonage(), &routing("twilio", "KE:vonage")),
+            Err(RoutingError::InvalidRoute(_))
+        ));
+
+        let router = SmsRouter::from_config(&twilio(), &vonage(), &routing("vonage", "")).unwrap();
+        assert_eq!(router.for_number("+15551234567").name(), "vonage");
+        assert!(router.provider("twilio").is_some());
+
+        let bad_mms = SmsRoutingConfig { mms_codes: "1,UK".to_string(), ..routing("twilio", "") };
+        assert!(matches!(SmsRouter::from_config(&twilio(), &vonage(), &bad_mms), Err(RoutingError::InvalidMmsCode(_))));
+    }
+
+    #[test]
+    fn test_media_only_to_mms_codes() {
+        let routing = SmsRoutingConfig { mms_codes: "1, +44".to_string(), ..routing("twilio", "44:vonage") };
+        let router = SmsRouter::from_config(&twilio(), &vonage(), &routing).unwrap();
+        assert!(router.takes_media("+15551234567"));
+        assert!(router.takes_media("+447700900000"));
+        assert!(!router.takes_media("+254700000000"));
+        // Vonage can't send media, whatever the carrier takes
+        assert!(router.provider("vonage").unwrap().send_media("+447700900000", "hi", "https://x/a.png").is_none());
+    }
+}
@@ -0,0 +1,187 @@
+//! Quiet hours in the recipient's local time
+//!
+//! The time zone is inferred from the number's calling code. Non-urgent
+//! notifications (receipts, reminders, announcements) sent during quiet
+//! hours are held in `sms_outbox` until the morning; replies to the user's
+//! own command and security messages are always sent straight away.
+
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+
+use crate::config::QuietHoursConfig;
+
+/// Representative zone per calling code for the markets we serve; countries
+/// spanning several zones use their most populous one
+const DEFAULT_ZONES: &[(&str, Tz)] = &[
+    ("1", chrono_tz::America::New_York),
+    ("7", chrono_tz::Europe::Moscow),
+    ("20", chrono_tz::Africa::Cairo),
+    ("27", chrono_tz::Africa::Johannesburg),
+    ("33", chrono_tz::Europe::Paris),
+    ("44", chrono_tz::Europe::London),
+    ("49", chrono_tz::Europe::Berlin),
+    ("52", chrono_tz::America::Mexico_City),
+    ("55", chrono_tz::America::Sao_Paulo),
+    ("61", chrono_tz::Australia::Sydney),
+    ("62", chrono_tz::Asia::Jakarta),
+    ("63", chrono_tz::Asia::Manila),
+    ("81", chrono_tz::Asia::Tokyo),
+    ("86", chrono_tz::Asia::Shanghai),
+    ("91", chrono_tz::Asia::Kolkata),
+    ("92", chrono_tz::Asia::Karachi),
+    ("221", chrono_tz::Africa::Dakar),
+    ("225", chrono_tz::Africa::Abidjan),
+    ("233", chrono_tz::Africa::Accra),
+    ("234", chrono_tz::Africa::Lagos),
+    ("237", chrono_tz::Africa::Douala),
+    ("250", chrono_tz::Africa::Kigali),
+    ("251", chrono_tz::Africa::Addis_Ababa),
+    ("254", chrono_tz::Africa::Nairobi),
+    ("255", chrono_tz::Africa::Dar_es_Salaam),
+    ("256", chrono_tz::Africa::Kampala),
+    ("260", chrono_tz::Africa::Lusaka),
+    ("263", chrono_tz::Africa::Harare),
+    ("880", chrono_tz::Asia::Dhaka),
+    ("971", chrono_tz::Asia::Dubai),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum QuietHoursError {
+    #[error("Invalid QUIET_HOURS (expected e.g. 21-8): {0}")]
+    Window(String),
+    #[error("Unknown time zone: {0}")]
+    TimeZone(String),
+}
+
+/// Local quiet-hours window, e.g. 21:00 to 08:00
+#[derive(Debug, Clone)]
+pub struct QuietHours {
+    /// First quiet hour (local)
+    start: u32,
+    /// First hour messages may go out again (local)
+    end: u32,
+    /// (calling code, zone), longest code first
+    zones: Vec<(String, Tz)>,
+    default_zone: Tz,
+}
+
+impl QuietHours {
+    /// None when `QUIET_HOURS` is empty
+    pub fn from_config(config: &QuietHoursConfig) -> Result<Option<Self>, QuietHoursError> {
+        let window = config.hours.trim();
+        if window.is_empty() {
+            return Ok(None);
+        }
+        let (start, end) = window
+            .split_once('-')
+            .and_then(|(start, end)| Some((start.trim().parse::<u32>().ok()?, end.trim().parse::<u32>().ok()?)))
+            .filter(|(start, end)| *start < 24 && *end < 24 && start != end)
+            .ok_or_else(|| QuietHoursError::Window(window.to_string()))?;
+
+        let default_zone = parse_zone(&config.default_timezone)?;
+        let mut zones: Vec<(String, Tz)> = DEFAULT_ZONES.iter().map(|(code, tz)| (code.to_string(), *tz)).collect();
+        for entry in config.timezones.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let Some((code, zone)) = entry.split_once(':') else {
+                return Err(QuietHoursError::TimeZone(entry.to_string()));
+            };
+            let code = code.trim().trim_start_matches('+').to_string();
+            let zone = parse_zone(zone)?;
+            zones.retain(|(existing, _)| *existing != code);
+            zones.push((code, zone));
+        }
+        zones.sort_by_key(|(code, _)| std::cmp::Reverse(code.len()));
+
+        Ok(Some(Self { start, end, zones, default_zone }))
+    }
+
+    /// Time zone inferred from the number's calling code
+    pub fn timezone_for(&self, phone: &str) -> Tz {
+        let digits = phone.trim_start_matches('+');
+        self.zones
+            .iter()
+            .find(|(code, _)| digits.starts_with(code.as_str()))
+            .map(|(_, tz)| *tz)
+            .unwrap_or(self.default_zone)
+    }
+
+    fn is_quiet_hour(&self, hour: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&hour)
+        } else {
+            hour >= self.start || hour < self.end
+        }
+    }
+
+    /// When a non-urgent message to `phone` may be sent: None = now,
+    /// otherwise the end of the current quiet period
+    pub fn defer_until(&self, phone: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let tz = self.timezone_for(phone);
+        let local = now.with_timezone(&tz);
+        if !self.is_quiet_hour(local.hour()) {
+            return None;
+        }
+
+        let end = NaiveTime::from_hms_opt(self.end, 0, 0)?;
+        let mut day = local.date_naive();
+        if local.hour() >= self.end {
+            day += Duration::days(1);
+        }
+        // A DST gap at the end hour falls back to an hour later
+        let resume = tz
+            .from_local_datetime(&day.and_time(end))
+            .earliest()
+            .or_else(|| tz.from_local_datetime(&(day.and_time(end) + Duration::hours(1))).earliest())?;
+        Some(resume.with_timezone(&Utc))
+    }
+}
+
+fn parse_zone(name: &str) -> Result<Tz, QuietHoursError> {
+    name.trim().parse::<Tz>().map_err(|_| QuietHoursError::TimeZone(name.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet(hours: &str, timezones: &str) -> QuietHours {
+        let config = QuietHoursConfig {
+            hours: hours.to_string(),
+            default_timezone: "UTC".to_string(),
+            timezones: timezones.to_string(),
+        };
+        QuietHours::from_config(&config).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_defers_until_local_morning() {
+        let quiet = quiet("21-8", "");
+        // 01:00 UTC = 04:00 in Nairobi (UTC+3): held until 08:00 local
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 1, 0, 0).unwrap();
+        assert_eq!(quiet.defer_until("+254700000001", now), Some(Utc.with_ymd_and_hms(2024, 3, 10, 5, 0, 0).unwrap()));
+
+        // 22:00 Lagos (UTC+1) waits for the next day
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 21, 0, 0).unwrap();
+        assert_eq!(quiet.defer_until("+2348000000001", now), Some(Utc.with_ymd_and_hms(2024, 3, 11, 7, 0, 0).unwrap()));
+
+        // Midday in London is not quiet
+        let now = Utc.with_ymd_and_hms(2024, 3, 10, 12, 0, 0).unwrap();
+        assert_eq!(quiet.defer_until("+447700900000", now), None);
+    }
+
+    #[test]
+    fn test_config_overrides_and_errors() {
+        let quiet = quiet("22-7", "+1:America/Los_Angeles, 999:Asia/Tokyo");
+        assert_eq!(quiet.timezone_for("+14155550100"), chrono_tz::America::Los_Angeles);
+        assert_eq!(quiet.timezone_for("+9991234"), chrono_tz::Asia::Tokyo);
+        assert_eq!(quiet.timezone_for("+5999123"), chrono_tz::UTC);
+
+        let config = |hours: &str, tz: &str| QuietHoursConfig {
+            hours: hours.to_string(),
+            default_timezone: tz.to_string(),
+            timezones: String::new(),
+        };
+        assert!(QuietHours::from_config(&config("", "UTC")).unwrap().is_none());
+        assert!(QuietHours::from_config(&config("25-8", "UTC")).is_err());
+        assert!(QuietHours::from_config(&config("21-8", "Mars/Olympus")).is_err());
+    }
+}
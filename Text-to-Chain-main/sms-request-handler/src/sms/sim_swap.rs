@@ -0,0 +1,309 @@
+//! SIM-swap checks before sensitive commands
+//!
+//! Taking over a phone number by moving it to a new SIM gives an attacker
+//! every SMS the owner would get, PIN prompts included. Before commands
+//! that move money out or change security settings, the sender's last SIM
+//! change is looked up; within `SIM_SWAP_WINDOW_HOURS` of one the command
+//! is refused until the window has passed. Results are cached for
+//! `SIM_SWAP_CACHE_MINUTES`, since the carrier lookups are billed.
+//!
+//! `SIM_SWAP_PROVIDER=twilio` uses Twilio Lookup's `sim_swap` package;
+//! `mock` reads fixed swap dates from `SIM_SWAP_MOCK`, for test deployments.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::config::{SimSwapConfig, TwilioConfig};
+use crate::sms::gateway::SmsError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SimSwapConfigError {
+    #[error("Unknown SIM_SWAP_PROVIDER: {0}")]
+    Provider(String),
+    #[error("Invalid SIM_SWAP_MOCK entry {0} (use +phone=RFC 3339 date)")]
+    Mock(String),
+}
+
+/// Where the date of a number's last SIM change comes from
+pub trait SimSwapSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Last SIM change (None = none known)
+    fn last_swap<'a>(&'a self, phone: &'a str) -> BoxFuture<'a, Result<Option<DateTime<Utc>>, SmsError>>;
+}
+
+/// Lookup v2 response, reduced to the SIM swap package
+#[derive(Debug, Deserialize)]
+struct LookupResponse {
+    sim_swap: Option<SimSwapInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SimSwapInfo {
+    last_sim_swap: Option<LastSimSwap>,
+    error_code: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LastSimSwap {
+    last_sim_swap_date: Option<DateTime<Utc>>,
+    /// Whether a swap happened within the carrier's reporting period
+    #[serde(default)]
+    swapped_in_period: bool,
+}
+
+/// Twilio Lookup SIM swap (billed per request, not offered by every carrier)
+pub struct TwilioSimSwap {
+    client: Client,
+    account_sid: String,
+    auth_token: String,
+}
+
+impl TwilioSimSwap {
+    pub fn new(twilio: &TwilioConfig) -> Self {
+        Self {
+            client: Client::new(),
+            account_sid: twilio.account_sid.clone(),
+            auth_token: twilio.auth_token.to_string(),
+        }
+    }
+
+    async fn fetch(&self, phone: &str) -> Result<Option<DateTime<Utc>>, SmsError> {
+        let url = format!("https://lookups.twilio.com/v2/PhoneNumbers/{}", phone);
+        let response = self
+            .client
+            .get(&url)
+            .query(&[("Fields", "sim_swap")])
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(SmsError::Api(error_text));
+        }
+        let lookup: LookupResponse = response.json().await?;
+        last_swap_from(lookup, Utc::now())
+    }
+}
+
+/// Swap date from a lookup; carriers that only say "swapped recently" count as now
+fn last_swap_from(lookup: LookupResponse, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, SmsError> {
+    let Some(info) = lookup.sim_swap else {
+        return Err(SmsError::Api("no sim_swap in lookup response".to_string()));
+    };
+    if let Some(code) = info.error_code {
+        return Err(SmsError::Api(format!("sim_swap error {}", code)));
+    }
+    Ok(info.last_sim_swap.and_then(|swap| match swap.last_sim_swap_date {
+        Some(date) => Some(date),
+        None => swap.swapped_in_period.then_some(now),
+    }))
+}
+
+impl SimSwapSource for TwilioSimSwap {
+    fn name(&self) -> &'static str {
+        "twilio"
+    }
+
+    fn last_swap<'a>(&'a self, phone: &'a str) -> BoxFuture<'a, Result<Option<DateTime<Utc>>, SmsError>> {
+        Box::pin(self.fetch(phone))
+    }
+}
+
+/// Fixed swap dates, for test deployments and tests
+pub struct MockSimSwap {
+    swaps: HashMap<String, DateTime<Utc>>,
+}
+
+impl MockSimSwap {
+    /// `+254700000001=2026-10-15T08:00:00Z,...`
+    pub fn parse(spec: &str) -> Result<Self, SimSwapConfigError> {
+        let swaps = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .split_once('=')
+                    .and_then(|(phone, date)| Some((phone.trim().to_string(), date.trim().parse().ok()?)))
+                    .ok_or_else(|| SimSwapConfigError::Mock(entry.to_string()))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { swaps })
+    }
+}
+
+impl SimSwapSource for MockSimSwap {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn last_swap<'a>(&'a self, phone: &'a str) -> BoxFuture<'a, Result<Option<DateTime<Utc>>, SmsError>> {
+        Box::pin(std::future::ready(Ok(self.swaps.get(phone).copied())))
+    }
+}
+
+/// Outcome of a check
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimSwapCheck {
+    Clear,
+    /// Recent SIM change: sensitive commands wait until then
+    Hold { until: DateTime<Utc> },
+    /// Lookup failed and `SIM_SWAP_BLOCK_ON_ERROR` is set
+    Unavailable,
+}
+
+/// Last swap of a number and when it was looked up
+type CachedSwap = (Option<DateTime<Utc>>, DateTime<Utc>);
+
+/// Cached SIM-swap checks for sensitive commands
+#[derive(Clone)]
+pub struct SimSwapGuard {
+    source: Arc<dyn SimSwapSource>,
+    window: Duration,
+    cache_ttl: Duration,
+    block_on_error: bool,
+    cache: Arc<Mutex<HashMap<String, CachedSwap>>>,
+}
+
+impl SimSwapGuard {
+    /// None when `SIM_SWAP_PROVIDER` is empty
+    pub fn from_config(config: &SimSwapConfig, twilio: &TwilioConfig) -> Result<Option<Self>, SimSwapConfigError> {
+        let source: Arc<dyn SimSwapSource> = match config.provider.trim().to_lowercase().as_str() {
+            "" => return Ok(None),
+            "twilio" => Arc::new(TwilioSimSwap::new(twilio)),
+            "mock" => Arc::new(MockSimSwap::parse(&config.mock)?),
+            other => return Err(SimSwapConfigError::Provider(other.to_string())),
+        };
+        Ok(Some(Self::new(source, config)))
+    }
+
+    pub fn new(source: Arc<dyn SimSwapSource>, config: &SimSwapConfig) -> Self {
+        Self {
+            source,
+            window: Duration::hours(config.window_hours.max(0)),
+            cache_ttl: Duration::minutes(config.cache_minutes.max(0)),
+            block_on_error: config.block_on_error,
+            cache: Arc::default(),
+        }
+    }
+
+    pub fn source(&self) -> &'static str {
+        self.source.name()
+    }
+
+    /// Whether `phone` may run a sensitive command now
+    pub async fn check(&self, phone: &str) -> SimSwapCheck {
+        let now = Utc::now();
+        let cached = self
+            .cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(phone).copied())
+            .filter(|(_, checked_at)| now - *checked_at < self.cache_ttl);
+
+        let last_swap = match cached {
+            Some((last_swap, _)) => last_swap,
+            None => match self.source.last_swap(phone).await {
+                Ok(last_swap) => {
+                    if let Ok(mut cache) = self.cache.lock() {
+                        cache.insert(phone.to_string(), (last_swap, now));
+                    }
+                    last_swap
+                }
+                Err(e) => {
+                    tracing::warn!(phone = %phone, source = self.source.name(), "SIM swap lookup failed: {}", e);
+                    return if self.block_on_error { SimSwapCheck::Unavailable } else { SimSwapCheck::Clear };
+                }
+            },
+        };
+
+        match last_swap.map(|swapped| swapped + self.window) {
+            Some(until) if until > now => SimSwapCheck::Hold { until },
+            _ => SimSwapCheck::Clear,
+        }
+    }
+}
+
+/// Reply to a sensitive command that has to wait
+pub fn hold_reply(command: &str, check: &SimSwapCheck) -> Option<String> {
+    match check {
+        SimSwapCheck::Clear => None,
+        SimSwapCheck::Hold { until } => Some(format!(
+            "This number moved to a new SIM recently, so {} is paused until {} UTC.\nIf you did not change SIM, call your carrier now.",
+            command,
+            until.format("%d %b %H:%M")
+        )),
+        SimSwapCheck::Unavailable => Some(format!("Can't verify this number for {} right now. Try later.", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(block_on_error: bool) -> SimSwapConfig {
+        SimSwapConfig {
+            provider: "mock".to_string(),
+            window_hours: 72,
+            cache_minutes: 60,
+            block_on_error,
+            mock: String::new(),
+        }
+    }
+
+    struct Failing;
+
+    impl SimSwapSource for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn last_swap<'a>(&'a self, _phone: &'a str) -> BoxFuture<'a, Result<Option<DateTime<Utc>>, SmsError>> {
+            Box::pin(std::future::ready(Err(SmsError::Api("unsupported carrier".to_string()))))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recent_swap_holds_until_window_ends() {
+        let swapped = Utc::now() - Duration::hours(10);
+        let old = Utc::now() - Duration::days(30);
+        let mock = MockSimSwap::parse(&format!("+254700000001={},+254700000002={}", swapped.to_rfc3339(), old.to_rfc3339())).unwrap();
+        let guard = SimSwapGuard::new(Arc::new(mock), &config(false));
+
+        let check = guard.check("+254700000001").await;
+        assert_eq!(check, SimSwapCheck::Hold { until: swapped + Duration::hours(72) });
+        assert!(hold_reply("SEND", &check).unwrap().contains("SEND is paused"));
+        assert_eq!(guard.check("+254700000002").await, SimSwapCheck::Clear);
+        assert_eq!(guard.check("+254700000003").await, SimSwapCheck::Clear);
+        assert_eq!(hold_reply("SEND", &SimSwapCheck::Clear), None);
+    }
+
+    #[tokio::test]
+    async fn test_lookup_failure_follows_block_on_error() {
+        let open = SimSwapGuard::new(Arc::new(Failing), &config(false));
+        assert_eq!(open.check("+254700000001").await, SimSwapCheck::Clear);
+        let closed = SimSwapGuard::new(Arc::new(Failing), &config(true));
+        assert_eq!(closed.check("+254700000001").await, SimSwapCheck::Unavailable);
+    }
+
+    #[test]
+    fn test_lookup_response() {
+        let now = Utc::now();
+        let parse = |json: &str| last_swap_from(serde_json::from_str(json).unwrap(), now);
+
+        let dated = parse(r#"{"sim_swap": {"last_sim_swap": {"last_sim_swap_date": "2026-10-14T09:30:00Z", "swapped_period": "PT48H", "swapped_in_period": true}, "error_code": null}}"#);
+        assert_eq!(dated.unwrap(), Some("2026-10-14T09:30:00Z".parse().unwrap()));
+        let undated = parse(r#"{"sim_swap": {"last_sim_swap": {"last_sim_swap_date": null, "swapped_in_period": true}}}"#);
+        assert_eq!(undated.unwrap(), Some(now));
+        let none = parse(r#"{"sim_swap": {"last_sim_swap": {"last_sim_swap_date": null, "swapped_in_period": false}}}"#);
+        assert_eq!(none.unwrap(), None);
+        assert!(parse(r#"{"sim_swap": {"last_sim_swap": null, "error_code": 60606}}"#).is_err());
+        assert!(MockSimSwap::parse("+254700000001=yesterday").is_err());
+    }
+}
@@ -0,0 +1,484 @@
+//! Spam and abuse screening of inbound messages
+//!
+//! With `SPAM_FILTER=true`, every inbound message that isn't a carrier
+//! keyword is scored before the command processor sees it. The built-in
+//! rules look for links, URL shorteners and the phrasing of phishing and
+//! prize scams; with `SPAM_MODEL_URL` set, an external model scores the
+//! message too and the higher score counts. A model that fails or times out
+//! (`SPAM_MODEL_TIMEOUT_MS`) is skipped, so the rules alone decide.
+//!
+//! - From `SPAM_FLAG_SCORE` a message is flagged: it is processed as usual,
+//!   but counts as a strike against the number and is kept as a sample.
+//! - From `SPAM_DROP_SCORE` it is dropped without a reply, which also counts.
+//! - `SPAM_STRIKES` strikes within `SPAM_STRIKE_WINDOW_MINUTES` mute the
+//!   number for `SPAM_MUTE_MINUTES`, twice as long each time after, up to a
+//!   day. Everything a muted number sends is dropped unread, except
+//!   STOP/HELP, which are handled before this stage.
+//!
+//! Samples (PINs masked) go to `spam_samples` for review at
+//! `/admin/spam/samples`. Marking one `ham` lifts its number's strikes and
+//! mute. Strikes and mutes are kept in memory, per instance.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use futures::future::BoxFuture;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SpamConfig;
+use crate::db::SpamSampleRepository;
+use crate::sms::gateway::SmsError;
+use crate::sms::transcript::redact;
+
+/// Longest a number is ever muted
+const MAX_MUTE_HOURS: i64 = 24;
+
+/// A classifier's opinion of one message
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Verdict {
+    /// 0 = clean, 1 = certainly spam
+    pub score: f64,
+    /// What gave it away, e.g. `link` or `phishing_phrase`
+    pub reasons: Vec<String>,
+}
+
+/// Scores inbound messages
+pub trait SpamClassifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn classify<'a>(&'a self, from: &'a str, body: &'a str) -> BoxFuture<'a, Result<Verdict, SmsError>>;
+}
+
+/// Hosts of URL shorteners; a command never needs one
+const SHORTENERS: &[&str] = &[
+    "bit.ly/", "tinyurl.com/", "t.co/", "goo.gl/", "is.gd/", "cutt.ly/", "rb.gy/", "shorturl.at/", "tiny.cc/", "ow.ly/",
+];
+
+/// Phrasing of phishing, prize and advance-fee messages
+const SCAM_PHRASES: &[&str] = &[
+    "seed phrase",
+    "recovery phrase",
+    "secret phrase",
+    "private key",
+    "verify your account",
+    "confirm your identity",
+    "account has been suspended",
+    "account has been locked",
+    "click the link",
+    "click this link",
+    "claim your prize",
+    "claim your reward",
+    "you have won",
+    "you've won",
+    "gift card",
+    "wire transfer",
+    "bank details",
+    "send your pin",
+    "double your",
+    "guaranteed return",
+];
+
+/// Pleas to move the conversation elsewhere
+const OFF_CHANNEL: &[&str] = &["whatsapp", "telegram", "call now", "dm me"];
+
+/// Longer than three segments: commands never are
+const LONG_MESSAGE: usize = 459;
+
+/// Keyword and link rules; no network calls
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RuleClassifier;
+
+impl RuleClassifier {
+    pub fn score(&self, body: &str) -> Verdict {
+        let text = body.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let mut verdict = Verdict::default();
+        let mut hit = |reason: &str, weight: f64| {
+            verdict.score += weight;
+            verdict.reasons.push(reason.to_string());
+        };
+
+        // ENS names look like domains, so only explicit links count
+        if SHORTENERS.iter().any(|host| text.contains(host)) {
+            hit("shortener", 0.7);
+        } else if text.contains("http://") || text.contains("https://") || text.contains("www.") {
+            hit("link", 0.5);
+        }
+        let phrases = SCAM_PHRASES.iter().filter(|phrase| text.contains(*phrase)).count();
+        if phrases > 0 {
+            hit("scam_phrase", 0.5 + 0.2 * (phrases - 1) as f64);
+        }
+        if OFF_CHANNEL.iter().any(|word| text.contains(word)) {
+            hit("off_channel", 0.3);
+        }
+        if text.chars().count() > LONG_MESSAGE {
+            hit("long", 0.2);
+        }
+
+        verdict.score = verdict.score.min(1.0);
+        verdict
+    }
+}
+
+impl SpamClassifier for RuleClassifier {
+    fn name(&self) -> &'static str {
+        "rules"
+    }
+
+    fn classify<'a>(&'a self, _from: &'a str, body: &'a str) -> BoxFuture<'a, Result<Verdict, SmsError>> {
+        Box::pin(std::future::ready(Ok(self.score(body))))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ModelRequest<'a> {
+    from: &'a str,
+    body: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelResponse {
+    score: f64,
+    #[serde(default)]
+    reasons: Vec<String>,
+}
+
+/// External model: `POST {"from", "body"}` answered with
+/// `{"score": 0-1, "reasons": [...]}`; bodies are sent with PINs masked
+pub struct ModelClassifier {
+    client: Client,
+    url: String,
+    token: String,
+}
+
+impl ModelClassifier {
+    pub fn new(url: &str, token: &str, timeout: std::time::Duration) -> Self {
+        Self {
+            client: Client::builder().timeout(timeout).build().unwrap_or_default(),
+            url: url.to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    async fn fetch(&self, from: &str, body: &str) -> Result<Verdict, SmsError> {
+        let mut request = self.client.post(&self.url).json(&ModelRequest { from, body: &redact(body) });
+        if !self.token.is_empty() {
+            request = request.bearer_auth(&self.token);
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(SmsError::Api(format!("spam model returned {}", response.status())));
+        }
+        let scored: ModelResponse = response.json().await?;
+        if !(0.0..=1.0).contains(&scored.score) {
+            return Err(SmsError::Api(format!("spam model score {} out of range", scored.score)));
+        }
+        Ok(Verdict { score: scored.score, reasons: scored.reasons })
+    }
+}
+
+impl SpamClassifier for ModelClassifier {
+    fn name(&self) -> &'static str {
+        "model"
+    }
+
+    fn classify<'a>(&'a self, from: &'a str, body: &'a str) -> BoxFuture<'a, Result<Verdict, SmsError>> {
+        Box::pin(self.fetch(from, body))
+    }
+}
+
+/// What to do with an inbound message
+#[derive(Debug, Clone, PartialEq)]
+pub enum Screening {
+    Allow,
+    /// Processed as usual, but counted against the number and sampled
+    Flag(Verdict),
+    /// Not processed and not answered
+    Drop(Verdict),
+    /// The number is muted for repeated spam until then
+    Muted { until: DateTime<Utc> },
+}
+
+impl Screening {
+    pub fn is_dropped(&self) -> bool {
+        matches!(self, Screening::Drop(_) | Screening::Muted { .. })
+    }
+}
+
+/// Strikes and mutes of one number
+#[derive(Debug, Clone, Default)]
+struct Offender {
+    strikes: Vec<DateTime<Utc>>,
+    /// Mutes so far; each is twice as long as the last
+    mutes: u32,
+    muted_until: Option<DateTime<Utc>>,
+}
+
+/// A muted number, for the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct MutedNumber {
+    pub phone: String,
+    pub until: DateTime<Utc>,
+    pub mutes: u32,
+}
+
+/// Length of a number's next mute after `mutes` earlier ones
+fn mute_for(base: Duration, mutes: u32) -> Duration {
+    let max = Duration::hours(MAX_MUTE_HOURS);
+    base.checked_mul(1 << mutes.min(16)).unwrap_or(max).min(max)
+}
+
+/// The inbound screening stage: classifiers, thresholds and repeat offenders
+#[derive(Clone)]
+pub struct SpamFilter {
+    classifiers: Vec<Arc<dyn SpamClassifier>>,
+    flag_score: f64,
+    drop_score: f64,
+    strikes: usize,
+    strike_window: Duration,
+    mute: Duration,
+    offenders: Arc<Mutex<HashMap<String, Offender>>>,
+    samples: Option<SpamSampleRepository>,
+}
+
+impl SpamFilter {
+    /// None unless `SPAM_FILTER` is on; the model joins the rules when
+    /// `SPAM_MODEL_URL` is set
+    pub fn from_config(config: &SpamConfig) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let mut classifiers: Vec<Arc<dyn SpamClassifier>> = vec![Arc::new(RuleClassifier)];
+        if !config.model_url.trim().is_empty() {
+            let timeout = std::time::Duration::from_millis(config.model_timeout_ms.max(1));
+            classifiers.push(Arc::new(ModelClassifier::new(config.model_url.trim(), config.model_token.expose(), timeout)));
+        }
+        Some(Self::new(classifiers, config))
+    }
+
+    pub fn new(classifiers: Vec<Arc<dyn SpamClassifier>>, config: &SpamConfig) -> Self {
+        Self {
+            classifiers,
+            flag_score: config.flag_score,
+            drop_score: config.drop_score,
+            strikes: config.strikes.max(1) as usize,
+            strike_window: Duration::minutes(config.strike_window_minutes.max(1)),
+            mute: Duration::minutes(config.mute_minutes.max(1)),
+            offenders: Arc::default(),
+            samples: None,
+        }
+    }
+
+    /// Keep flagged and dropped messages for review
+    pub fn set_samples(&mut self, samples: SpamSampleRepository) {
+        self.samples = Some(samples);
+    }
+
+    pub fn classifiers(&self) -> Vec<&'static str> {
+        self.classifiers.iter().map(|c| c.name()).collect()
+    }
+
+    /// Score `body` and decide what happens to it
+    pub async fn screen(&self, from: &str, body: &str) -> Screening {
+        let now = Utc::now();
+        if let Some(until) = self.muted_until(from, now) {
+            tracing::debug!(phone = %from, %until, "Dropping message from muted number");
+            return Screening::Muted { until };
+        }
+
+        let verdict = self.classify(from, body).await;
+        let screening = if verdict.score >= self.drop_score {
+            Screening::Drop(verdict.clone())
+        } else if verdict.score >= self.flag_score {
+            Screening::Flag(verdict.clone())
+        } else {
+            return Screening::Allow;
+        };
+        let action = if screening.is_dropped() { "drop" } else { "flag" };
+        tracing::warn!(phone = %from, score = verdict.score, reasons = %verdict.reasons.join(","), action, "Suspected spam");
+
+        if let Some(until) = self.strike(from, now) {
+            tracing::warn!(phone = %from, %until, "Muting number for repeated spam");
+        }
+        if let Some(ref samples) = self.samples {
+            if let Err(e) = samples.record(from, &redact(body), verdict.score, action, &verdict.reasons).await {
+                tracing::warn!(phone = %from, "Failed to store spam sample: {}", e);
+            }
+        }
+        screening
+    }
+
+    /// Highest score of any classifier, with every classifier's reasons
+    async fn classify(&self, from: &str, body: &str) -> Verdict {
+        let mut verdict = Verdict::default();
+        for classifier in &self.classifiers {
+            match classifier.classify(from, body).await {
+                Ok(found) => {
+                    verdict.score = verdict.score.max(found.score);
+                    verdict.reasons.extend(found.reasons);
+                }
+                Err(e) => tracing::warn!(classifier = classifier.name(), "Spam classifier failed: {}", e),
+            }
+        }
+        verdict
+    }
+
+    fn muted_until(&self, phone: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let offenders = self.offenders.lock().ok()?;
+        offenders.get(phone)?.muted_until.filter(|until| *until > now)
+    }
+
+    /// Count a strike; returns the end of the mute it triggers, if any
+    fn strike(&self, phone: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut offenders = self.offenders.lock().ok()?;
+        // Numbers that have behaved for a day start over
+        let forget = now - Duration::hours(MAX_MUTE_HOURS);
+        offenders.retain(|_, o| {
+            o.strikes.last().is_some_and(|at| *at > forget) || o.muted_until.is_some_and(|until| until > forget)
+        });
+
+        let offender = offenders.entry(phone.to_string()).or_default();
+        offender.strikes.retain(|at| now - *at < self.strike_window);
+        offender.strikes.push(now);
+        if offender.strikes.len() < self.strikes {
+            return None;
+        }
+        let until = now + mute_for(self.mute, offender.mutes);
+        offender.mutes += 1;
+        offender.muted_until = Some(until);
+        offender.strikes.clear();
+        Some(until)
+    }
+
+    /// Forget a number's strikes and lift its mute (a sample reviewed as ham)
+    pub fn lift(&self, phone: &str) -> bool {
+        self.offenders.lock().map(|mut offenders| offenders.remove(phone).is_some()).unwrap_or(false)
+    }
+
+    /// Numbers muted right now, longest mute first
+    pub fn muted(&self) -> Vec<MutedNumber> {
+        let now = Utc::now();
+        let Ok(offenders) = self.offenders.lock() else {
+            return Vec::new();
+        };
+        let mut muted: Vec<MutedNumber> = offenders
+            .iter()
+            .filter_map(|(phone, o)| {
+                let until = o.muted_until.filter(|until| *until > now)?;
+                Some(MutedNumber { phone: phone.clone(), until, mutes: o.mutes })
+            })
+            .collect();
+        muted.sort_by_key(|m| std::cmp::Reverse(m.until));
+        muted
+    }
+}
+
+impl std::fmt::Debug for SpamFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpamFilter")
+            .field("classifiers", &self.classifiers())
+            .field("flag_score", &self.flag_score)
+            .field("drop_score", &self.drop_score)
+            .field("samples", &self.samples.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SpamConfig {
+        SpamConfig {
+            enabled: true,
+            flag_score: 0.5,
+            drop_score: 0.9,
+            strikes: 3,
+            strike_window_minutes: 60,
+            mute_minutes: 15,
+            model_url: String::new(),
+            model_token: String::new().into(),
+            model_timeout_ms: 800,
+        }
+    }
+
+    struct Fixed(f64);
+
+    impl SpamClassifier for Fixed {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn classify<'a>(&'a self, _from: &'a str, _body: &'a str) -> BoxFuture<'a, Result<Verdict, SmsError>> {
+            let verdict = Verdict { score: self.0, reasons: vec!["fixed".to_string()] };
+            Box::pin(std::future::ready(Ok(verdict)))
+        }
+    }
+
+    struct Down;
+
+    impl SpamClassifier for Down {
+        fn name(&self) -> &'static str {
+            "down"
+        }
+
+        fn classify<'a>(&'a self, _from: &'a str, _body: &'a str) -> BoxFuture<'a, Result<Verdict, SmsError>> {
+            Box::pin(std::future::ready(Err(SmsError::Api("timeout".to_string()))))
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules = RuleClassifier;
+        for command in ["SEND 10 USDC TO alice.ttcip.eth", "BALANCE", "JOIN bob", "EMAIL me@example.com", "alice.cb.id"] {
+            assert_eq!(rules.score(command), Verdict::default(), "{}", command);
+        }
+
+        let link = rules.score("Check https://example.com");
+        assert_eq!((link.score, link.reasons.as_slice()), (0.5, ["link".to_string()].as_slice()));
+        assert_eq!(rules.score("see bit.ly/x1").reasons, ["shortener"]);
+        let phish = rules.score("Your account has been  SUSPENDED? Verify your account at www.textchain-help.com");
+        assert_eq!(phish.score, 1.0);
+        assert_eq!(phish.reasons, ["link", "scam_phrase"]);
+        assert_eq!(rules.score("You have won! Reply on WhatsApp").score, 0.8);
+    }
+
+    #[test]
+    fn test_mute_doubles_up_to_a_day() {
+        let base = Duration::minutes(15);
+        assert_eq!(mute_for(base, 0), base);
+        assert_eq!(mute_for(base, 2), Duration::hours(1));
+        assert_eq!(mute_for(base, 10), Duration::hours(MAX_MUTE_HOURS));
+        assert_eq!(mute_for(base, u32::MAX), Duration::hours(MAX_MUTE_HOURS));
+    }
+
+    #[tokio::test]
+    async fn test_thresholds_and_failing_model() {
+        let screen = |score| async move { SpamFilter::new(vec![Arc::new(Fixed(score)), Arc::new(Down)], &config()).screen("+1", "hi").await };
+        assert_eq!(screen(0.2).await, Screening::Allow);
+        assert!(matches!(screen(0.6).await, Screening::Flag(_)));
+        assert!(screen(0.95).await.is_dropped());
+        assert!(!screen(0.6).await.is_dropped());
+    }
+
+    #[tokio::test]
+    async fn test_repeat_offenders_are_muted() {
+        let filter = SpamFilter::new(vec![Arc::new(RuleClassifier)], &config());
+        let spam = "Claim your prize: https://example.com";
+        assert!(filter.screen("+1", spam).await.is_dropped());
+        assert!(filter.screen("+1", spam).await.is_dropped());
+        assert_eq!(filter.screen("+1", "BALANCE").await, Screening::Allow);
+        assert!(filter.muted().is_empty());
+
+        // Third strike mutes: even clean messages are dropped now
+        filter.screen("+1", spam).await;
+        assert!(matches!(filter.screen("+1", "BALANCE").await, Screening::Muted { .. }));
+        assert_eq!(filter.screen("+2", "BALANCE").await, Screening::Allow);
+        let muted = filter.muted();
+        assert_eq!((muted.len(), muted[0].mutes), (1, 1));
+
+        assert!(filter.lift("+1"));
+        assert_eq!(filter.screen("+1", "BALANCE").await, Screening::Allow);
+    }
+}
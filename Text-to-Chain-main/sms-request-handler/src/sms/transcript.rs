@@ -0,0 +1,77 @@
+//! Support transcripts
+//!
+//! When `TRANSCRIPT_RETENTION_DAYS` is set, every inbound message and every
+//! reply is stored per user so support staff can see what was actually
+//! typed and answered. PINs are masked before anything is written.
+
+use crate::db::TranscriptRepository;
+
+/// Mask shown in place of a PIN
+const PIN_MASK: &str = "****";
+
+/// Shared transcript writer; does nothing unless a repository is attached
+#[derive(Clone, Default)]
+pub struct TranscriptLog {
+    repo: Option<TranscriptRepository>,
+}
+
+impl TranscriptLog {
+    pub fn new(repo: TranscriptRepository) -> Self {
+        Self { repo: Some(repo) }
+    }
+
+    pub async fn record_inbound(&self, phone: &str, body: &str) {
+        self.record(phone, "in", body).await;
+    }
+
+    pub async fn record_outbound(&self, phone: &str, body: &str) {
+        self.record(phone, "out", body).await;
+    }
+
+    async fn record(&self, phone: &str, direction: &str, body: &str) {
+        let Some(ref repo) = self.repo else {
+            return;
+        };
+        if let Err(e) = repo.record(phone, direction, &redact(body)).await {
+            tracing::warn!(phone = %phone, "Failed to store transcript message: {}", e);
+        }
+    }
+}
+
+impl std::fmt::Debug for TranscriptLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TranscriptLog").field("enabled", &self.repo.is_some()).finish()
+    }
+}
+
+/// Mask PINs: `PIN 1234`, `SIGN 1234` and a bare 4-6 digit reply (the
+/// onboarding PIN step)
+pub fn redact(body: &str) -> String {
+    let trimmed = body.trim();
+    if (4..=6).contains(&trimmed.len()) && trimmed.chars().all(|c| c.is_ascii_digit()) {
+        return PIN_MASK.to_string();
+    }
+
+    let mut words = trimmed.split_whitespace();
+    match words.next() {
+        Some(keyword) if ["PIN", "SIGN"].contains(&keyword.to_uppercase().as_str()) && words.next().is_some() => {
+            format!("{} {}", keyword, PIN_MASK)
+        }
+        _ => body.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_pins() {
+        assert_eq!(redact("PIN 1234"), "PIN ****");
+        assert_eq!(redact("sign 987654"), "sign ****");
+        assert_eq!(redact(" 4321 "), "****");
+        assert_eq!(redact("PIN"), "PIN");
+        assert_eq!(redact("SEND 10 TXTC alice.ttcip.eth"), "SEND 10 TXTC alice.ttcip.eth");
+        assert_eq!(redact("BUY 10"), "BUY 10");
+    }
+}
@@ -1,10 +1,13 @@
 use base64::Engine;
+use futures::future::BoxFuture;
 use hmac::{Hmac, Mac};
 use reqwest::Client;
 use sha1::Sha1;
 use std::collections::HashMap;
 
 use crate::config::TwilioConfig;
+use crate::sms::gateway::{SendResult, SmsError};
+use crate::sms::provider::{InboundSms, InboundWebhook, SmsProvider};
 
 type HmacSha1 = Hmac<Sha1>;
 
@@ -15,23 +18,7 @@ pub struct TwilioClient {
     account_sid: String,
     auth_token: String,
     phone_number: String,
-}
-
-/// Result of sending an SMS
-#[derive(Debug)]
-pub struct SendResult {
-    pub message_sid: String,
-    pub status: String,
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum TwilioError {
-    #[error("HTTP request failed: {0}")]
-    Request(#[from] reqwest::Error),
-    #[error("API error: {0}")]
-    Api(String),
-    #[error("Invalid signature")]
-    InvalidSignature,
+    api_base: String,
 }
 
 impl TwilioClient {
@@ -40,22 +27,24 @@ impl TwilioClient {
         Self {
             client: Client::new(),
             account_sid: config.account_sid.clone(),
-            auth_token: config.auth_token.clone(),
+            auth_token: config.auth_token.to_string(),
             phone_number: config.phone_number.clone(),
+            api_base: config.api_base.trim_end_matches('/').to_string(),
         }
     }
 
-    /// Send an SMS message
-    pub async fn send_sms(&self, to: &str, body: &str) -> Result<SendResult, TwilioError> {
-        let url = format!(
-            "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
-            self.account_sid
-        );
+    /// Send a message; with `media_url` Twilio fetches the image and sends
+    /// an MMS
+    async fn send_message(&self, to: &str, body: &str, media_url: Option<&str>) -> Result<SendResult, SmsError> {
+        let url = format!("{}/2010-04-01/Accounts/{}/Messages.json", self.api_base, self.account_sid);
 
         let mut params = HashMap::new();
         params.insert("To", to);
         params.insert("From", &self.phone_number);
         params.insert("Body", body);
+        if let Some(media_url) = media_url {
+            params.insert("MediaUrl", media_url);
+        }
 
         let response = self
             .client
@@ -67,7 +56,7 @@ impl TwilioClient {
 
         if !response.status().is_success() {
             let error_text = response.text().await.unwrap_or_default();
-            return Err(TwilioError::Api(error_text));
+            return Err(SmsError::Api(error_text));
         }
 
         let json: serde_json::Value = response.json().await?;
@@ -77,22 +66,51 @@ impl TwilioClient {
             status: json["status"].as_str().unwrap_or("").to_string(),
         })
     }
+}
+
+impl SmsProvider for TwilioClient {
+    fn name(&self) -> &'static str {
+        "twilio"
+    }
+
+    fn send<'a>(&'a self, to: &'a str, body: &'a str) -> BoxFuture<'a, Result<SendResult, SmsError>> {
+        Box::pin(self.send_message(to, body, None))
+    }
+
+    fn send_media<'a>(
+        &'a self,
+        to: &'a str,
+        body: &'a str,
+        media_url: &'a str,
+    ) -> Option<BoxFuture<'a, Result<SendResult, SmsError>>> {
+        Some(Box::pin(self.send_message(to, body, Some(media_url))))
+    }
+
+    /// Form fields of a Twilio messaging webhook
+    fn parse_inbound(&self, webhook: &InboundWebhook) -> Option<InboundSms> {
+        let field = |name: &str| webhook.params.get(name).cloned();
+        Some(InboundSms {
+            from: field("From")?,
+            to: field("To").unwrap_or_default(),
+            body: field("Body")?,
+            message_id: field("MessageSid").unwrap_or_default(),
+        })
+    }
 
     /// Validate Twilio request signature
-    /// 
+    ///
     /// This ensures the webhook request actually came from Twilio
-    pub fn validate_signature(
-        &self,
-        signature: &str,
-        url: &str,
-        params: &HashMap<String, String>,
-    ) -> bool {
+    fn validate_signature(&self, webhook: &InboundWebhook) -> bool {
+        let Some(ref signature) = webhook.signature else {
+            return false;
+        };
+
         // Build the string to sign: URL + sorted params
-        let mut data = url.to_string();
-        
-        let mut sorted_params: Vec<_> = params.iter().collect();
+        let mut data = webhook.url.clone();
+
+        let mut sorted_params: Vec<_> = webhook.params.iter().collect();
         sorted_params.sort_by(|a, b| a.0.cmp(b.0));
-        
+
         for (key, value) in sorted_params {
             data.push_str(key);
             data.push_str(value);
@@ -103,17 +121,12 @@ impl TwilioClient {
             .expect("HMAC can take key of any size");
         mac.update(data.as_bytes());
         let result = mac.finalize();
-        
+
         // Base64 encode
         let calculated = base64::engine::general_purpose::STANDARD.encode(result.into_bytes());
-        
-        // Compare
-        calculated == signature
-    }
 
-    /// Get the Twilio phone number
-    pub fn phone_number(&self) -> &str {
-        &self.phone_number
+        // Compare
+        calculated == *signature
     }
 }
 
@@ -125,18 +138,31 @@ mod tests {
     fn test_signature_validation() {
         let config = TwilioConfig {
             account_sid: "test_sid".to_string(),
-            auth_token: "12345".to_string(),
+            auth_token: "12345".into(),
             phone_number: "+1234567890".to_string(),
+            api_base: "https://api.twilio.com".to_string(),
         };
-        
+
         let client = TwilioClient::new(&config);
-        
-        // This is a simplified test - real signatures would come from Twilio
+
         let mut params = HashMap::new();
         params.insert("From".to_string(), "+1234".to_string());
         params.insert("Body".to_string(), "test".to_string());
-        
-        // The signature validation logic is correct; actual testing would need real Twilio data
-        assert!(!client.validate_signature("invalid", "https://example.com", &params));
+        let mut webhook = InboundWebhook {
+            url: "https://example.com".to_string(),
+            params,
+            signature: Some("invalid".to_string()),
+        };
+        assert!(!client.validate_signature(&webhook));
+
+        // Base64 HMAC-SHA1 of "https://example.comBodytestFrom+1234" keyed with the auth token
+        let mut mac = HmacSha1::new_from_slice(b"12345").unwrap();
+        mac.update(b"https://example.comBodytestFrom+1234");
+        let signature = base64::engine::general_purpose::STANDARD.encode(mac.finalize().into_bytes());
+        webhook.signature = Some(signature);
+        assert!(client.validate_signature(&webhook));
+
+        let inbound = client.parse_inbound(&webhook).unwrap();
+        assert_eq!((inbound.from.as_str(), inbound.body.as_str()), ("+1234", "test"));
     }
 }
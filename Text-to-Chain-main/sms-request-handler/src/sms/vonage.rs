@@ -0,0 +1,201 @@
+//! Vonage (formerly Nexmo) SMS API
+//!
+//! Messages go out through the legacy SMS API (`rest.nexmo.com/sms/json`),
+//! which is what Vonage's low-cost routes in Africa and Asia are sold on.
+//! Inbound messages arrive on `/sms/vonage/inbound` as GET query, POST form
+//! or POST JSON, whichever the dashboard is set to. With
+//! `VONAGE_SIGNATURE_SECRET` set, unsigned or stale webhooks are refused;
+//! the account must sign with HMAC-SHA256.
+
+use std::collections::HashMap;
+
+use futures::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use sha2::Sha256;
+
+use crate::config::VonageConfig;
+use crate::sms::cost::is_gsm7;
+use crate::sms::gateway::{SendResult, SmsError};
+use crate::sms::provider::{InboundSms, InboundWebhook, SmsProvider};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SEND_URL: &str = "https://rest.nexmo.com/sms/json";
+
+/// Oldest signed webhook accepted, against replays
+const MAX_SIGNATURE_AGE_SECS: i64 = 300;
+
+#[derive(Debug, Clone)]
+pub struct VonageClient {
+    client: Client,
+    api_key: String,
+    api_secret: String,
+    /// Number or alphanumeric sender ID
+    from: String,
+    /// Webhook signing secret (None = accept unsigned webhooks)
+    signature_secret: Option<String>,
+}
+
+impl VonageClient {
+    pub fn new(config: &VonageConfig) -> Self {
+        Self {
+            client: Client::new(),
+            api_key: config.api_key.clone(),
+            api_secret: config.api_secret.to_string(),
+            from: config.from.trim_start_matches('+').to_string(),
+            signature_secret: Some(config.signature_secret.to_string()).filter(|s| !s.is_empty()),
+        }
+    }
+
+    async fn send_message(&self, to: &str, body: &str) -> Result<SendResult, SmsError> {
+        let mut request = serde_json::json!({
+            "api_key": self.api_key,
+            "api_secret": self.api_secret,
+            "from": self.from,
+            "to": to.trim_start_matches('+'),
+            "text": body,
+        });
+        if !is_gsm7(body) {
+            request["type"] = "unicode".into();
+        }
+
+        let response = self.client.post(SEND_URL).json(&request).send().await?;
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(SmsError::Api(error_text));
+        }
+
+        // A 200 can still carry a rejection, per message part
+        let json: serde_json::Value = response.json().await?;
+        let first = &json["messages"][0];
+        if first["status"].as_str() != Some("0") {
+            let error = first["error-text"].as_str().unwrap_or("no message status");
+            return Err(SmsError::Api(format!("status {}: {}", first["status"], error)));
+        }
+
+        Ok(SendResult {
+            message_sid: first["message-id"].as_str().unwrap_or("").to_string(),
+            status: "submitted".to_string(),
+        })
+    }
+}
+
+/// HMAC-SHA256 of the sorted `&key=value` pairs (without `sig`), uppercase hex
+fn sign(params: &HashMap<String, String>, secret: &str) -> String {
+    let mut sorted: Vec<_> = params.iter().filter(|(key, _)| key.as_str() != "sig").collect();
+    sorted.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut data = String::new();
+    for (key, value) in sorted {
+        data.push('&');
+        data.push_str(key);
+        data.push('=');
+        data.push_str(&value.replace(['&', '='], "_"));
+    }
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(data.as_bytes());
+    hex::encode_upper(mac.finalize().into_bytes())
+}
+
+impl SmsProvider for VonageClient {
+    fn name(&self) -> &'static str {
+        "vonage"
+    }
+
+    fn send<'a>(&'a self, to: &'a str, body: &'a str) -> BoxFuture<'a, Result<SendResult, SmsError>> {
+        Box::pin(self.send_message(to, body))
+    }
+
+    /// `msisdn`, `to`, `text` and `messageId` of an inbound-message webhook
+    fn parse_inbound(&self, webhook: &InboundWebhook) -> Option<InboundSms> {
+        let field = |name: &str| webhook.params.get(name).cloned();
+        let msisdn = field("msisdn")?;
+        Some(InboundSms {
+            from: format!("+{}", msisdn.trim_start_matches('+')),
+            to: field("to").unwrap_or_default(),
+            body: field("text")?,
+            message_id: field("messageId").unwrap_or_default(),
+        })
+    }
+
+    fn validate_signature(&self, webhook: &InboundWebhook) -> bool {
+        let Some(ref secret) = self.signature_secret else {
+            return true;
+        };
+        let (Some(sig), Some(timestamp)) = (webhook.params.get("sig"), webhook.params.get("timestamp")) else {
+            return false;
+        };
+        let fresh = timestamp
+            .parse::<i64>()
+            .is_ok_and(|ts| (chrono::Utc::now().timestamp() - ts).abs() <= MAX_SIGNATURE_AGE_SECS);
+        fresh && sign(&webhook.params, secret).eq_ignore_ascii_case(sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(signature_secret: &str) -> VonageClient {
+        VonageClient::new(&VonageConfig {
+            api_key: "key".to_string(),
+            api_secret: "secret".into(),
+            from: "TextChain".to_string(),
+            signature_secret: signature_secret.into(),
+        })
+    }
+
+    fn inbound(text: &str) -> InboundWebhook {
+        let params = [
+            ("msisdn", "254712345678"),
+            ("to", "447700900000"),
+            ("messageId", "0A0000000123ABCD1"),
+            ("text", text),
+            ("type", "text"),
+            ("timestamp", &chrono::Utc::now().timestamp().to_string()),
+        ];
+        InboundWebhook {
+            params: params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_inbound() {
+        let sms = client("").parse_inbound(&inbound("BALANCE")).unwrap();
+        assert_eq!(sms.from, "+254712345678");
+        assert_eq!(sms.body, "BALANCE");
+        assert_eq!(sms.message_id, "0A0000000123ABCD1");
+
+        // Delivery receipts have no text
+        let mut receipt = inbound("");
+        receipt.params.remove("text");
+        assert!(client("").parse_inbound(&receipt).is_none());
+    }
+
+    #[test]
+    fn test_signature() {
+        let vonage = client("s3cret");
+        let mut webhook = inbound("SEND 5 USDC to=alice&x");
+        assert!(!vonage.validate_signature(&webhook));
+
+        let sig = sign(&webhook.params, "s3cret");
+        webhook.params.insert("sig".to_string(), sig.to_lowercase());
+        assert!(vonage.validate_signature(&webhook));
+
+        // Tampered text
+        webhook.params.insert("text".to_string(), "SEND 500 USDC".to_string());
+        assert!(!vonage.validate_signature(&webhook));
+
+        // Stale timestamp, correctly signed
+        webhook.params.insert("timestamp".to_string(), "1700000000".to_string());
+        let sig = sign(&webhook.params, "s3cret");
+        webhook.params.insert("sig".to_string(), sig);
+        assert!(!vonage.validate_signature(&webhook));
+
+        // No secret configured: unsigned webhooks are accepted
+        assert!(client("").validate_signature(&inbound("HELP")));
+    }
+}
@@ -1,14 +1,22 @@
 use axum::{
-    extract::State,
-    http::StatusCode,
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     Form,
 };
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
 
 use crate::commands::CommandProcessor;
-use crate::sms::TwilioClient;
+use crate::events::Topic;
+use crate::sms::opt_out::InboundAction;
+use crate::sms::provider::InboundWebhook;
+use crate::sms::SmsGateway;
+use crate::workers::WorkerPool;
 
 /// Incoming SMS webhook payload from Twilio
 #[derive(Debug, Deserialize)]
@@ -32,12 +40,13 @@ pub struct IncomingSms {
 /// Application state shared across handlers
 #[derive(Clone)]
 pub struct AppState {
-    pub twilio: Arc<TwilioClient>,
+    pub twilio: Arc<SmsGateway>,
     pub command_processor: Arc<CommandProcessor>,
+    pub workers: WorkerPool,
 }
 
 /// TwiML response for Twilio
-struct TwimlResponse(String);
+pub(crate) struct TwimlResponse(pub(crate) String);
 
 impl IntoResponse for TwimlResponse {
     fn into_response(self) -> Response {
@@ -67,7 +76,7 @@ impl IntoResponse for JsonResponse {
 /// Handler for incoming SMS messages from Twilio (Form-encoded)
 ///
 /// Responds immediately with empty TwiML to avoid Twilio's 15s timeout,
-/// then processes the command and sends the reply via Twilio REST API.
+/// then processes the command and sends the reply via the SMS gateway.
 pub async fn incoming_sms_handler(
     State(state): State<AppState>,
     Form(sms): Form<IncomingSms>,
@@ -77,23 +86,131 @@ pub async fn incoming_sms_handler(
         body = %sms.body,
         "Received SMS (Twilio format)"
     );
+    match accept_inbound(&state, &sms.from, &sms.body).await {
+        Some(reply) => TwimlResponse(twiml_message(reply)),
+        None => TwimlResponse(EMPTY_TWIML.to_string()),
+    }
+}
+
+/// Handler for inbound SMS from Vonage sent as a GET query
+pub async fn vonage_inbound_get_handler(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+) -> StatusCode {
+    vonage_inbound(&state, params).await
+}
+
+/// Handler for inbound SMS from Vonage sent as a POST (JSON or form-encoded)
+pub async fn vonage_inbound_post_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let params = if is_json {
+        // JSON webhooks mix strings with numbers and booleans
+        serde_json::from_slice::<HashMap<String, serde_json::Value>>(&body).map(|fields| {
+            fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    serde_json::Value::String(s) => (key, s),
+                    other => (key, other.to_string()),
+                })
+                .collect()
+        }).ok()
+    } else {
+        serde_urlencoded::from_bytes::<HashMap<String, String>>(&body).ok()
+    };
+    match params {
+        Some(params) => vonage_inbound(&state, params).await,
+        None => StatusCode::BAD_REQUEST,
+    }
+}
+
+/// Vonage wants a 200 and nothing else; replies go out through the gateway
+async fn vonage_inbound(state: &AppState, params: HashMap<String, String>) -> StatusCode {
+    let Some(vonage) = state.twilio.router().provider("vonage") else {
+        return StatusCode::NOT_FOUND;
+    };
+    let webhook = InboundWebhook { params, ..Default::default() };
+    if !vonage.validate_signature(&webhook) {
+        tracing::warn!("Rejecting Vonage webhook with a missing or bad signature");
+        return StatusCode::UNAUTHORIZED;
+    }
+    // Delivery receipts and other non-message callbacks
+    let Some(sms) = vonage.parse_inbound(&webhook) else {
+        return StatusCode::OK;
+    };
+
+    tracing::info!(
+        from = %sms.from,
+        body = %sms.body,
+        message_id = %sms.message_id,
+        "Received SMS (Vonage format)"
+    );
+    if let Some(reply) = accept_inbound(state, &sms.from, &sms.body).await {
+        if let Err(e) = state.twilio.send_sms(&sms.from, reply).await {
+            tracing::error!(to = %sms.from, error = %e, "Failed to send SMS reply");
+        }
+    }
+    StatusCode::OK
+}
+
+/// Record, screen and queue one inbound message; returns a reply the
+/// provider should send right away (carrier keywords, or busy)
+async fn accept_inbound(state: &AppState, from: &str, body: &str) -> Option<&'static str> {
+    state.twilio.transcripts().record_inbound(from, body).await;
 
-    let from = sms.from.clone();
-    let body = sms.body.clone();
+    // Carrier keywords (STOP/START/HELP) are answered inline; opted-out
+    // numbers get no processing and no reply
+    match state.twilio.opt_outs().handle_inbound(from, body).await {
+        InboundAction::Reply(reply) => {
+            state.twilio.transcripts().record_outbound(from, reply).await;
+            return Some(reply);
+        }
+        InboundAction::Ignore => return None,
+        InboundAction::Process => {}
+    }
+    if screened_out(state, from, body).await {
+        return None;
+    }
+
+    let from = from.to_string();
+    let body = body.to_string();
     let processor = state.command_processor.clone();
     let twilio = state.twilio.clone();
+    let command = processor.parse(&body);
+    processor.publish_incoming(&from, &command);
+    let class = command.task_class();
+    let priority = command.reply_priority();
+    let timeout = state.workers.command_timeout(command.name());
 
-    // Process command in background and send reply via Twilio API
-    tokio::spawn(async move {
-        let response_text = processor.process(&from, &body).await;
+    // Process command in background (bounded per task class) and send the reply
+    let sender = from.clone();
+    let spawned = state.workers.spawn(class, async move {
+        let mut job = spawn_process(&processor, &from, &body);
+        let response_text = match reply_within(&mut job, timeout).await {
+            Some(reply) => reply,
+            None => {
+                // Slow RPC or backend: say so now, then follow up with the result
+                tracing::warn!(to = %from, command = command.name(), "Command still running, sending holding reply");
+                if let Err(e) = twilio.send_sms_with_priority(&from, STILL_WORKING_REPLY, priority).await {
+                    tracing::error!(to = %from, error = %e, "Failed to send holding reply");
+                }
+                finish(&mut job).await
+            }
+        };
 
         tracing::info!(
             to = %from,
             response = %response_text,
-            "Sending SMS response via Twilio API"
+            "Sending SMS response"
         );
 
-        match twilio.send_sms(&from, &response_text).await {
+        match twilio.send_sms_with_priority(&from, &response_text, priority).await {
             Ok(result) => {
                 tracing::info!(
                     message_sid = %result.message_sid,
@@ -107,15 +224,49 @@ pub async fn incoming_sms_handler(
                     error = %e,
                     "Failed to send SMS reply"
                 );
+                processor.events().publish(Topic::Error, serde_json::json!({ "source": "sms_reply", "to": from, "error": e.to_string() }));
             }
         }
     });
 
-    // Respond immediately with empty TwiML so Twilio doesn't timeout
-    let twiml = r#"<?xml version="1.0" encoding="UTF-8"?>
-<Response></Response>"#.to_string();
+    // Overloaded - tell the user inline instead of queueing more work
+    if spawned.is_err() {
+        state.command_processor.events().publish(Topic::Error, serde_json::json!({ "source": "workers", "from": sender, "error": "worker queue full" }));
+        state.twilio.transcripts().record_outbound(&sender, BUSY_REPLY).await;
+        return Some(BUSY_REPLY);
+    }
 
-    TwimlResponse(twiml)
+    None
+}
+
+/// Whether the spam filter drops the message (suspected spam or a muted number)
+async fn screened_out(state: &AppState, from: &str, body: &str) -> bool {
+    match state.twilio.spam_filter() {
+        Some(filter) => filter.screen(from, body).await.is_dropped(),
+        None => false,
+    }
+}
+
+/// Process a message in its own task, so it keeps running past a timeout
+pub(crate) fn spawn_process(processor: &Arc<CommandProcessor>, from: &str, body: &str) -> JoinHandle<String> {
+    let (processor, from, body) = (processor.clone(), from.to_string(), body.to_string());
+    tokio::spawn(async move { processor.process(&from, &body).await })
+}
+
+/// The command's reply, or None if it is still running after `limit`
+pub(crate) async fn reply_within(job: &mut JoinHandle<String>, limit: Option<Duration>) -> Option<String> {
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, finish(job)).await.ok(),
+        None => Some(finish(job).await),
+    }
+}
+
+/// Wait for the command's reply
+pub(crate) async fn finish(job: &mut JoinHandle<String>) -> String {
+    job.await.unwrap_or_else(|e| {
+        tracing::error!("Command task failed: {}", e);
+        "Error. Try later.".to_string()
+    })
 }
 
 /// Handler for incoming SMS messages from SMSCountry (JSON format)
@@ -128,18 +279,53 @@ pub async fn incoming_sms_json_handler(
         body = %sms.body,
         "Received SMS (JSON format)"
     );
+    state.twilio.transcripts().record_inbound(&sms.from, &sms.body).await;
 
-    // Process the command
-    let response_text = state
-        .command_processor
-        .process(&sms.from, &sms.body)
-        .await;
+    match state.twilio.opt_outs().handle_inbound(&sms.from, &sms.body).await {
+        InboundAction::Reply(reply) => {
+            state.twilio.transcripts().record_outbound(&sms.from, reply).await;
+            let json_response = serde_json::json!({ "success": true, "response": reply });
+            return JsonResponse(json_response.to_string());
+        }
+        InboundAction::Ignore => {
+            // Opted out - tell the provider there is nothing to send
+            let json_response = serde_json::json!({ "success": true, "response": null, "suppressed": true });
+            return JsonResponse(json_response.to_string());
+        }
+        InboundAction::Process => {}
+    }
+    if screened_out(&state, &sms.from, &sms.body).await {
+        let json_response = serde_json::json!({ "success": true, "response": null, "suppressed": true });
+        return JsonResponse(json_response.to_string());
+    }
+
+    let command = state.command_processor.parse(&sms.body);
+    state.command_processor.publish_incoming(&sms.from, &command);
+
+    // Process the command; if it runs long, answer now and text the result later
+    let mut job = spawn_process(&state.command_processor, &sms.from, &sms.body);
+    let response_text = match reply_within(&mut job, state.workers.command_timeout(command.name())).await {
+        Some(reply) => reply,
+        None => {
+            tracing::warn!(to = %sms.from, command = command.name(), "Command still running, replying that it's in progress");
+            let twilio = state.twilio.clone();
+            let to = sms.from.clone();
+            tokio::spawn(async move {
+                let reply = finish(&mut job).await;
+                if let Err(e) = twilio.send_sms(&to, &reply).await {
+                    tracing::error!(to = %to, error = %e, "Failed to send follow-up SMS");
+                }
+            });
+            STILL_WORKING_REPLY.to_string()
+        }
+    };
 
     tracing::info!(
         to = %sms.from,
         response = %response_text,
         "Sending SMS response"
     );
+    state.twilio.transcripts().record_outbound(&sms.from, &response_text).await;
 
     // Return JSON response
     let json_response = serde_json::json!({
@@ -151,8 +337,27 @@ pub async fn incoming_sms_json_handler(
 }
 
 
+/// Empty TwiML - acknowledges the webhook without replying
+const EMPTY_TWIML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Response></Response>"#;
+
+/// Reply sent inline when the worker queue for a command is full
+const BUSY_REPLY: &str = "Busy right now. Please try again in a minute.";
+
+/// Sent when a command outlasts its timeout; the result follows in a second text
+const STILL_WORKING_REPLY: &str = "Still working on it, we'll text you shortly.";
+
+/// TwiML response carrying a single reply message
+fn twiml_message(body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<Response><Message>{}</Message></Response>"#,
+        escape_xml(body)
+    )
+}
+
 /// Escape special XML characters
-fn escape_xml(s: &str) -> String {
+pub(crate) fn escape_xml(s: &str) -> String {
     s.replace('&', "&amp;")
         .replace('<', "&lt;")
         .replace('>', "&gt;")
@@ -169,4 +374,10 @@ mod tests {
         assert_eq!(escape_xml("Hello & Goodbye"), "Hello &amp; Goodbye");
         assert_eq!(escape_xml("<script>"), "&lt;script&gt;");
     }
+
+    #[test]
+    fn test_twiml_message() {
+        let twiml = twiml_message("A & B");
+        assert!(twiml.contains("<Message>A &amp; B</Message>"));
+    }
 }
@@ -0,0 +1,334 @@
+//! Voice menu (IVR) for callers who can't read SMS replies
+//!
+//! Twilio Voice calls `/voice/incoming` when someone rings our number. A
+//! caller with a PIN enters it first, since caller ID is easy to fake. The
+//! menu then maps key presses to commands (1 = BALANCE, 2 = HISTORY), runs
+//! them through the same `CommandProcessor` as SMS and reads the reply back
+//! with text-to-speech. Every webhook must carry a valid Twilio signature,
+//! and the menu URL carries an HMAC of the call so a PIN can't be skipped.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::post,
+    Form, Router,
+};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::commands::parser::hash_pin;
+use crate::commands::CommandProcessor;
+use crate::config::VoiceConfig;
+use crate::db::UserRepository;
+use crate::sms::provider::InboundWebhook;
+use crate::sms::webhook::{escape_xml, finish, reply_within, spawn_process, TwimlResponse};
+use crate::sms::SmsGateway;
+use crate::workers::WorkerPool;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Twilio hangs up on a webhook after 15 seconds
+const MAX_WAIT: Duration = Duration::from_secs(10);
+
+/// Wrong PINs allowed per call
+const PIN_ATTEMPTS: u32 = 2;
+
+/// Key presses and the command each one runs
+const MENU: &[(&str, &str, &str)] = &[("1", "BALANCE", "hear your balance"), ("2", "HISTORY", "hear your recent transactions")];
+
+/// Voice menu settings
+#[derive(Clone)]
+pub struct VoiceIvr {
+    /// Public URL Twilio calls, for checking signatures
+    pub base_url: String,
+    /// Twilio auth token: checks signatures and keys menu tokens
+    pub auth_token: String,
+    /// `<Say>` language, e.g. `en-US`
+    pub language: String,
+}
+
+impl VoiceIvr {
+    pub fn new(config: &VoiceConfig, base_url: &str, auth_token: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            auth_token: auth_token.to_string(),
+            language: config.language.clone(),
+        }
+    }
+
+    /// Proof that this call got past the PIN
+    fn call_token(&self, call_sid: &str, from: &str) -> String {
+        hex::encode(self.call_mac(call_sid, from).finalize().into_bytes())
+    }
+
+    fn check_call_token(&self, call_sid: &str, from: &str, token: &str) -> bool {
+        let Ok(token) = hex::decode(token) else {
+            return false;
+        };
+        self.call_mac(call_sid, from).verify_slice(&token).is_ok()
+    }
+
+    fn call_mac(&self, call_sid: &str, from: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.auth_token.as_bytes()).expect("HMAC can take key of any size");
+        mac.update(format!("voice:{}:{}", call_sid, from).as_bytes());
+        mac
+    }
+}
+
+/// Voice menu: its settings plus callers' PINs
+#[derive(Clone)]
+pub struct VoiceChannel {
+    pub ivr: VoiceIvr,
+    pub users: UserRepository,
+}
+
+#[derive(Clone)]
+struct VoiceState {
+    ivr: VoiceIvr,
+    users: UserRepository,
+    command_processor: Arc<CommandProcessor>,
+    gateway: Arc<SmsGateway>,
+    workers: WorkerPool,
+}
+
+#[derive(Debug, Deserialize)]
+struct PinQuery {
+    #[serde(default)]
+    attempt: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct MenuQuery {
+    #[serde(default)]
+    token: String,
+}
+
+/// Create the Twilio Voice webhook routes
+pub fn voice_routes(channel: VoiceChannel, command_processor: Arc<CommandProcessor>, gateway: Arc<SmsGateway>, workers: WorkerPool) -> Router {
+    let state = VoiceState { ivr: channel.ivr, users: channel.users, command_processor, gateway, workers };
+    Router::new()
+        .route("/voice/incoming", post(incoming_call))
+        .route("/voice/pin", post(enter_pin))
+        .route("/voice/menu", post(choose))
+        .with_state(state)
+}
+
+/// A call comes in: ask for the PIN, or go straight to the menu without one
+async fn incoming_call(State(state): State<VoiceState>, uri: Uri, headers: HeaderMap, Form(params): Form<HashMap<String, String>>) -> Response {
+    let Some(call) = signed_call(&state, &uri, &headers, params) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    tracing::info!(from = %call.from, call_sid = %call.call_sid, "Incoming voice call");
+
+    let ivr = &state.ivr;
+    let twiml = match state.users.find_by_phone(&call.from).await {
+        Ok(Some(user)) if user.pin_hash.is_some() => pin_prompt(ivr, "Welcome to TextChain. Enter your PIN, then press the pound key.", 0),
+        Ok(Some(_)) => menu(ivr, &ivr.call_token(&call.call_sid, &call.from), "Welcome to TextChain."),
+        Ok(None) => hang_up(ivr, "Welcome to TextChain. You don't have a wallet yet. Text JOIN to this number to create one. Goodbye."),
+        Err(e) => {
+            tracing::error!("Voice caller lookup failed: {}", e);
+            hang_up(ivr, "Sorry, something went wrong. Please call again later.")
+        }
+    };
+    TwimlResponse(twiml).into_response()
+}
+
+/// Check the PIN typed on the keypad
+async fn enter_pin(
+    State(state): State<VoiceState>,
+    uri: Uri,
+    headers: HeaderMap,
+    Query(query): Query<PinQuery>,
+    Form(params): Form<HashMap<String, String>>,
+) -> Response {
+    let Some(call) = signed_call(&state, &uri, &headers, params) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+
+    let ivr = &state.ivr;
+    let twiml = match state.users.find_by_phone(&call.from).await {
+        Ok(Some(user)) if user.pin_hash.as_deref() == Some(hash_pin(&call.digits).as_str()) => {
+            menu(ivr, &ivr.call_token(&call.call_sid, &call.from), "")
+        }
+        Ok(Some(_)) if query.attempt + 1 < PIN_ATTEMPTS => {
+            pin_prompt(ivr, "That PIN is not right. Enter your PIN, then press the pound key.", query.attempt + 1)
+        }
+        Ok(Some(_)) => {
+            tracing::warn!(from = %call.from, call_sid = %call.call_sid, "Voice call ended after wrong PINs");
+            hang_up(ivr, "That PIN is not right. Goodbye.")
+        }
+        Ok(None) => hang_up(ivr, "Goodbye."),
+        Err(e) => {
+            tracing::error!("Voice caller lookup failed: {}", e);
+            hang_up(ivr, "Sorry, something went wrong. Please call again later.")
+        }
+    };
+    TwimlResponse(twiml).into_response()
+}
+
+/// Run the command for the key pressed and read its reply
+async fn choose(
+    State(state): State<VoiceState>,
+    uri: Uri,
+    headers: HeaderMap,
+    Query(query): Query<MenuQuery>,
+    Form(params): Form<HashMap<String, String>>,
+) -> Response {
+    let Some(call) = signed_call(&state, &uri, &headers, params) else {
+        return StatusCode::UNAUTHORIZED.into_response();
+    };
+    let ivr = &state.ivr;
+    if !ivr.check_call_token(&call.call_sid, &call.from, &query.token) {
+        return TwimlResponse(hang_up(ivr, "Goodbye.")).into_response();
+    }
+
+    let Some((_, command, _)) = MENU.iter().find(|(key, _, _)| *key == call.digits) else {
+        return TwimlResponse(menu(ivr, &query.token, "")).into_response();
+    };
+    tracing::info!(from = %call.from, command, "Voice menu choice");
+
+    let limit = state.workers.command_timeout(command).map_or(MAX_WAIT, |limit| limit.min(MAX_WAIT));
+    let mut job = spawn_process(&state.command_processor, &call.from, command);
+    let spoken = match reply_within(&mut job, Some(limit)).await {
+        Some(reply) => speakable(&reply),
+        None => {
+            // Too slow to keep the caller waiting: text the answer instead
+            let gateway = state.gateway.clone();
+            let to = call.from.clone();
+            tokio::spawn(async move {
+                let reply = finish(&mut job).await;
+                if let Err(e) = gateway.send_sms(&to, &reply).await {
+                    tracing::error!(to = %to, error = %e, "Failed to text voice menu reply");
+                }
+            });
+            "That is taking a while. We will text you the answer.".to_string()
+        }
+    };
+    TwimlResponse(menu(ivr, &query.token, &spoken)).into_response()
+}
+
+/// The fields of a Twilio Voice webhook the menu uses
+struct Call {
+    from: String,
+    call_sid: String,
+    digits: String,
+}
+
+/// The call, when the webhook carries a valid Twilio signature
+fn signed_call(state: &VoiceState, uri: &Uri, headers: &HeaderMap, params: HashMap<String, String>) -> Option<Call> {
+    let twilio = state.gateway.router().provider("twilio")?;
+    let path = uri.path_and_query().map_or(uri.path(), |path| path.as_str());
+    let webhook = InboundWebhook {
+        url: format!("{}{}", state.ivr.base_url, path),
+        signature: headers.get("X-Twilio-Signature").and_then(|value| value.to_str().ok()).map(str::to_string),
+        params,
+    };
+    if !twilio.validate_signature(&webhook) {
+        tracing::warn!(url = %webhook.url, "Rejecting voice webhook with a missing or bad signature");
+        return None;
+    }
+    let field = |name: &str| webhook.params.get(name).map(|value| value.trim().to_string()).unwrap_or_default();
+    Some(Call { from: field("From"), call_sid: field("CallSid"), digits: field("Digits") })
+}
+
+fn say(ivr: &VoiceIvr, text: &str) -> String {
+    if text.is_empty() {
+        return String::new();
+    }
+    format!(r#"<Say language="{}">{}</Say>"#, escape_xml(&ivr.language), escape_xml(text))
+}
+
+/// `before`, then the menu; silence ends the call
+fn menu(ivr: &VoiceIvr, token: &str, before: &str) -> String {
+    let options: Vec<String> = MENU.iter().map(|(key, _, what)| format!("Press {} to {}.", key, what)).collect();
+    twiml(&format!(
+        r#"{}<Gather input="dtmf" numDigits="1" timeout="8" action="/voice/menu?token={}" method="POST">{}</Gather>{}"#,
+        say(ivr, before),
+        token,
+        say(ivr, &options.join(" ")),
+        say(ivr, "Goodbye.")
+    ))
+}
+
+fn pin_prompt(ivr: &VoiceIvr, prompt: &str, attempt: u32) -> String {
+    twiml(&format!(
+        r##"<Gather input="dtmf" finishOnKey="#" timeout="10" action="/voice/pin?attempt={}" method="POST">{}</Gather>{}"##,
+        attempt,
+        say(ivr, prompt),
+        say(ivr, "We did not get your PIN. Goodbye.")
+    ))
+}
+
+fn hang_up(ivr: &VoiceIvr, text: &str) -> String {
+    twiml(&format!("{}<Hangup/>", say(ivr, text)))
+}
+
+fn twiml(verbs: &str) -> String {
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Response>{}</Response>", verbs)
+}
+
+/// An SMS reply as a sentence text-to-speech can read: lines become
+/// sentences, and addresses nobody could note down by ear are left out
+fn speakable(reply: &str) -> String {
+    let sentences: Vec<String> = reply
+        .lines()
+        .map(|line| {
+            line.split_whitespace()
+                .filter(|word| !(word.starts_with("0x") && word.len() > 10))
+                .collect::<Vec<_>>()
+                .join(" ")
+                .replace('~', "about ")
+        })
+        .map(|line| line.trim_end_matches([':', '.']).to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    if sentences.is_empty() {
+        String::new()
+    } else {
+        format!("{}.", sentences.join(". "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ivr() -> VoiceIvr {
+        VoiceIvr::new(&VoiceConfig { enabled: true, language: "en-US".to_string() }, "https://example.com/", "token")
+    }
+
+    #[test]
+    fn test_speakable() {
+        assert_eq!(
+            speakable("Balance:\n10 TXTC (~$1.00)\n0.5 ETH\n\nSepolia testnet"),
+            "Balance. 10 TXTC (about $1.00). 0.5 ETH. Sepolia testnet."
+        );
+        assert_eq!(speakable("Sent to 0x1234567890abcdef1234567890abcdef12345678\nDone."), "Sent to. Done.");
+        assert_eq!(speakable("\n\n"), "");
+    }
+
+    #[test]
+    fn test_call_token() {
+        let ivr = ivr();
+        let token = ivr.call_token("CA123", "+254700000001");
+        assert!(ivr.check_call_token("CA123", "+254700000001", &token));
+        assert!(!ivr.check_call_token("CA124", "+254700000001", &token));
+        assert!(!ivr.check_call_token("CA123", "+254700000002", &token));
+        assert!(!ivr.check_call_token("CA123", "+254700000001", "zz"));
+    }
+
+    #[test]
+    fn test_menu_twiml() {
+        let ivr = ivr();
+        let twiml = menu(&ivr, "abc", "Balance & more.");
+        assert!(twiml.contains(r#"<Say language="en-US">Balance &amp; more.</Say>"#));
+        assert!(twiml.contains(r#"action="/voice/menu?token=abc""#));
+        assert!(twiml.contains("Press 1 to hear your balance. Press 2 to hear your recent transactions."));
+        assert!(hang_up(&ivr, "Goodbye.").ends_with("<Say language=\"en-US\">Goodbye.</Say><Hangup/></Response>"));
+    }
+}
@@ -0,0 +1,194 @@
+//! Printable voucher cards
+//!
+//! Renders a voucher batch as an A4 PDF with eight cut-out cards per page.
+//! Each card has the value, code, expiry, redemption instructions and a QR
+//! code that opens the phone's SMS app with `REDEEM <code>` filled in.
+
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+use qrcode::{Color, QrCode};
+
+use crate::db::Voucher;
+use crate::sms::SmsDisplay;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 36.0;
+const GUTTER: f32 = 12.0;
+const COLUMNS: usize = 2;
+const ROWS: usize = 4;
+const CARDS_PER_PAGE: usize = COLUMNS * ROWS;
+const CARD_PADDING: f32 = 12.0;
+const QR_SIZE: f32 = 96.0;
+
+const CATALOG_ID: Ref = Ref::new(1);
+const PAGE_TREE_ID: Ref = Ref::new(2);
+const FONT_REGULAR_ID: Ref = Ref::new(3);
+const FONT_BOLD_ID: Ref = Ref::new(4);
+const FONT_CODE_ID: Ref = Ref::new(5);
+/// First id used for per-page objects (page + content stream)
+const FIRST_PAGE_ID: i32 = 6;
+
+const FONT_REGULAR: Name = Name(b"F1");
+const FONT_BOLD: Name = Name(b"F2");
+const FONT_CODE: Name = Name(b"F3");
+
+/// Render `vouchers` as cards; `sms_number` is where REDEEM is sent
+pub fn render_batch(vouchers: &[Voucher], sms_number: &str) -> Result<Vec<u8>, String> {
+    if vouchers.is_empty() {
+        return Err("No vouchers to print".to_string());
+    }
+
+    let pages: Vec<&[Voucher]> = vouchers.chunks(CARDS_PER_PAGE).collect();
+    let page_ids: Vec<Ref> = (0..pages.len()).map(|i| Ref::new(FIRST_PAGE_ID + 2 * i as i32)).collect();
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(CATALOG_ID).pages(PAGE_TREE_ID);
+    pdf.pages(PAGE_TREE_ID).kids(page_ids.iter().copied()).count(pages.len() as i32);
+    pdf.type1_font(FONT_REGULAR_ID).base_font(Name(b"Helvetica"));
+    pdf.type1_font(FONT_BOLD_ID).base_font(Name(b"Helvetica-Bold"));
+    pdf.type1_font(FONT_CODE_ID).base_font(Name(b"Courier-Bold"));
+
+    // Footer so stacks of printed pages can be matched to their batch
+    let batch = match vouchers[0].batch_id {
+        Some(id) => format!("Batch {} - ", &id.simple().to_string()[..8]),
+        None => String::new(),
+    };
+    let page_count = pages.len();
+
+    for (index, (cards, page_id)) in pages.into_iter().zip(page_ids).enumerate() {
+        let content_id = Ref::new(page_id.get() + 1);
+
+        let mut content = Content::new();
+        for (slot, voucher) in cards.iter().enumerate() {
+            draw_card(&mut content, slot, voucher, sms_number)?;
+        }
+        let footer = format!("{}page {} of {}", batch, index + 1, page_count);
+        content
+            .set_fill_gray(0.4)
+            .begin_text()
+            .set_font(FONT_REGULAR, 7.0)
+            .next_line(MARGIN, MARGIN / 2.0)
+            .show(Str(footer.as_bytes()))
+            .end_text();
+        let data = content.finish();
+
+        let mut page = pdf.page(page_id);
+        page.parent(PAGE_TREE_ID)
+            .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+            .contents(content_id);
+        page.resources()
+            .fonts()
+            .pair(FONT_REGULAR, FONT_REGULAR_ID)
+            .pair(FONT_BOLD, FONT_BOLD_ID)
+            .pair(FONT_CODE, FONT_CODE_ID);
+        page.finish();
+
+        pdf.stream(content_id, &data);
+    }
+
+    Ok(pdf.finish())
+}
+
+fn draw_card(content: &mut Content, slot: usize, voucher: &Voucher, sms_number: &str) -> Result<(), String> {
+    let width = (PAGE_WIDTH - 2.0 * MARGIN - (COLUMNS as f32 - 1.0) * GUTTER) / COLUMNS as f32;
+    let height = (PAGE_HEIGHT - 2.0 * MARGIN - (ROWS as f32 - 1.0) * GUTTER) / ROWS as f32;
+    let (column, row) = (slot % COLUMNS, slot / COLUMNS);
+    let x = MARGIN + column as f32 * (width + GUTTER);
+    // PDF origin is bottom-left; fill rows from the top
+    let y = PAGE_HEIGHT - MARGIN - (row as f32 + 1.0) * height - row as f32 * GUTTER;
+
+    // Cut line
+    content.set_stroke_gray(0.6).set_line_width(0.5).rect(x, y, width, height).stroke();
+
+    let text_x = x + CARD_PADDING;
+    let top = y + height - CARD_PADDING;
+    let expiry = match voucher.expires_at {
+        Some(at) => format!("Expires {}", at.format("%Y-%m-%d")),
+        None => "No expiry".to_string(),
+    };
+    let lines: [(Name<'static>, f32, String, f32); 7] = [
+        (FONT_BOLD, 9.0, "TEXTCHAIN VOUCHER".to_string(), 10.0),
+        (FONT_BOLD, 16.0, voucher.to_sms_string(), 26.0),
+        (FONT_CODE, 13.0, voucher.code.clone(), 26.0),
+        (FONT_REGULAR, 8.0, expiry, 14.0),
+        (FONT_REGULAR, 8.0, "To redeem, text".to_string(), 22.0),
+        (FONT_BOLD, 8.0, format!("REDEEM {}", voucher.code), 11.0),
+        (FONT_REGULAR, 8.0, format!("to {} or scan the code", sms_number), 11.0),
+    ];
+
+    content.set_fill_gray(0.0);
+    let mut line_y = top;
+    for (font, size, text, advance) in lines {
+        line_y -= advance;
+        content
+            .begin_text()
+            .set_font(font, size)
+            .next_line(text_x, line_y)
+            .show(Str(pdf_text(&text).as_bytes()))
+            .end_text();
+    }
+
+    let qr_x = x + width - CARD_PADDING - QR_SIZE;
+    let qr_y = top - QR_SIZE;
+    draw_qr(content, &format!("SMSTO:{}:REDEEM {}", sms_number, voucher.code), qr_x, qr_y)
+}
+
+/// QR code as filled squares, `QR_SIZE` points wide with its bottom-left at (x, y)
+fn draw_qr(content: &mut Content, data: &str, x: f32, y: f32) -> Result<(), String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let module = QR_SIZE / modules as f32;
+
+    for row in 0..modules {
+        for column in 0..modules {
+            if colors[row * modules + column] == Color::Dark {
+                let top = y + QR_SIZE - row as f32 * module;
+                content.rect(x + column as f32 * module, top - module, module, module);
+            }
+        }
+    }
+    content.fill_nonzero();
+    Ok(())
+}
+
+/// Standard fonts only cover Latin-1; replace anything else
+pub(crate) fn pdf_text(text: &str) -> String {
+    text.chars().map(|c| if c.is_ascii() { c } else { '?' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    fn voucher(code: &str) -> Voucher {
+        Voucher {
+            id: Uuid::new_v4(),
+            code: code.to_string(),
+            usdc_amount: Money::usdc(10_000_000),
+            status: "unused".to_string(),
+            redeemed_by: None,
+            redeemed_at: None,
+            expires_at: None,
+            created_at: Utc::now(),
+            local_currency: None,
+            local_amount: None,
+            fx_rate: None,
+            batch_id: None,
+        }
+    }
+
+    #[test]
+    fn test_render_batch_paginates() {
+        let vouchers: Vec<Voucher> = (0..9).map(|i| voucher(&format!("TTC000000000{}", i))).collect();
+        let pdf = render_batch(&vouchers, "+18449862896").unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(text.contains("/Count 2"));
+        assert!(render_batch(&[], "+18449862896").is_err());
+    }
+}
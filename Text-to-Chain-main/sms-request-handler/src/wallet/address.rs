@@ -0,0 +1,159 @@
+//! EIP-55 address formatting, validation, check words and deposit QR codes
+
+use ethers::types::Address;
+use ethers::utils::to_checksum;
+use qrcode::{Color, QrCode};
+
+/// Pixels per QR module in rendered PNGs
+const QR_MODULE_PX: usize = 8;
+
+/// Blank modules around the code, as required by the QR spec
+const QR_QUIET_ZONE: usize = 4;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum AddressError {
+    #[error("Invalid address")]
+    Invalid,
+    /// Mixed-case input whose capitalisation doesn't match EIP-55
+    #[error("Address checksum mismatch, did you mean {0}?")]
+    BadChecksum(String),
+}
+
+impl AddressError {
+    /// Short reply for SMS users
+    pub fn sms_message(&self) -> String {
+        match self {
+            AddressError::Invalid => "Invalid address.\nUse 0x followed by 40 hex characters.".to_string(),
+            AddressError::BadChecksum(correct) => {
+                format!("Address checksum wrong - check for a typo.\nDid you mean:\n{}", correct)
+            }
+        }
+    }
+}
+
+/// EIP-55 checksummed form of an address
+pub fn checksummed(address: &Address) -> String {
+    to_checksum(address, None)
+}
+
+/// Checksum a stored address string for display; non-address strings are
+/// returned unchanged
+pub fn display_address(address: &str) -> String {
+    match address.parse::<Address>() {
+        Ok(parsed) => checksummed(&parsed),
+        Err(_) => address.to_string(),
+    }
+}
+
+/// Fingerprint words for a raw address, as a reply line the other side can
+/// read back; empty for names and phone numbers
+pub fn check_words_line(recipient: &str) -> String {
+    match parse_address(recipient) {
+        Ok(address) => format!("\nCheck words: {}", fingerprint::fingerprint(address.as_fixed_bytes())),
+        Err(_) => String::new(),
+    }
+}
+
+/// Parse user-supplied address input.
+///
+/// All-lowercase and all-uppercase input carries no checksum and is
+/// accepted; mixed-case input must match its EIP-55 checksum.
+pub fn parse_address(input: &str) -> Result<Address, AddressError> {
+    let input = input.trim();
+    let hex = input
+        .strip_prefix("0x")
+        .or_else(|| input.strip_prefix("0X"))
+        .ok_or(AddressError::Invalid)?;
+    if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AddressError::Invalid);
+    }
+
+    let address: Address = format!("0x{}", hex).parse().map_err(|_| AddressError::Invalid)?;
+
+    let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    let correct = checksummed(&address);
+    if mixed_case && correct[2..] != *hex {
+        return Err(AddressError::BadChecksum(correct));
+    }
+
+    Ok(address)
+}
+
+/// Render `data` as a black-on-white QR code PNG
+pub fn qr_png(data: &str) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| e.to_string())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+
+    let size = (modules + 2 * QR_QUIET_ZONE) * QR_MODULE_PX;
+    let mut pixels = vec![0xFFu8; size * size];
+    for y in 0..modules {
+        for x in 0..modules {
+            if colors[y * modules + x] != Color::Dark {
+                continue;
+            }
+            let (left, top) = ((x + QR_QUIET_ZONE) * QR_MODULE_PX, (y + QR_QUIET_ZONE) * QR_MODULE_PX);
+            for row in top..top + QR_MODULE_PX {
+                pixels[row * size + left..row * size + left + QR_MODULE_PX].fill(0);
+            }
+        }
+    }
+
+    grayscale_png(&pixels, size, size)
+}
+
+/// Encode 8-bit grayscale pixels, row by row, as a PNG
+pub fn grayscale_png(pixels: &[u8], width: usize, height: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test vector from EIP-55
+    const CHECKSUMMED: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+    #[test]
+    fn test_display_checksums_lowercase() {
+        assert_eq!(display_address(&CHECKSUMMED.to_lowercase()), CHECKSUMMED);
+        assert_eq!(display_address("alice.ttcip.eth"), "alice.ttcip.eth");
+    }
+
+    #[test]
+    fn test_check_words_only_for_addresses() {
+        let line = check_words_line(CHECKSUMMED);
+        assert!(line.starts_with("\nCheck words: "));
+        assert_eq!(check_words_line(&CHECKSUMMED.to_lowercase()), line);
+        assert_eq!(check_words_line("alice.ttcip.eth"), "");
+        assert_eq!(check_words_line("+254700000001"), "");
+    }
+
+    #[test]
+    fn test_parse_address_checksum_rules() {
+        assert!(parse_address(CHECKSUMMED).is_ok());
+        assert!(parse_address(&CHECKSUMMED.to_lowercase()).is_ok());
+        assert!(parse_address(&format!("0x{}", CHECKSUMMED[2..].to_uppercase())).is_ok());
+
+        // One letter's case flipped
+        let typo = CHECKSUMMED.replacen("aA", "aa", 1);
+        assert_eq!(parse_address(&typo), Err(AddressError::BadChecksum(CHECKSUMMED.to_string())));
+
+        assert_eq!(parse_address("0x1234"), Err(AddressError::Invalid));
+        assert_eq!(parse_address("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"), Err(AddressError::Invalid));
+    }
+
+    #[test]
+    fn test_qr_png_has_png_signature() {
+        let png = qr_png(CHECKSUMMED).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    }
+}
@@ -0,0 +1,213 @@
+//! ERC-20 allowances for user EOAs
+//!
+//! Lets SMS users approve (and revoke) a spender for tokens held directly
+//! in their wallet, e.g. the Uniswap router before swapping. Only a fixed
+//! list of tokens on the TXTC chain is supported, and approvals are always
+//! for an exact amount - unlimited approvals are refused.
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use ethers::middleware::SignerMiddleware;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, TxHash, U256};
+use ethers::utils::parse_units;
+
+use super::address::checksummed;
+use super::chains::{Chain, ChainProvider};
+use super::payment_uri::{TXTC_CHAIN, TXTC_DECIMALS};
+use super::token_registry::token_address;
+use super::tokens::{format_token_balance, IERC20};
+use crate::sms::display::{NumberLocale, SmsDisplay};
+
+/// Uniswap V3 SwapRouter02 on Ethereum Sepolia
+pub const SWAP_ROUTER_ADDRESS: &str = "0x3bFA4769FB09eefC5a80d6E87c3B9C650f7Ae48E";
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum AllowanceError {
+    #[error("Unsupported token: {0}")]
+    UnknownToken(String),
+    #[error("Invalid spender: {0}")]
+    InvalidSpender(String),
+    #[error("Invalid amount")]
+    InvalidAmount,
+    #[error("Not enough ETH for gas")]
+    NoGas,
+    #[error("Transaction failed: {0}")]
+    Transaction(String),
+}
+
+/// Token a user can approve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ApprovableToken {
+    pub symbol: &'static str,
+    pub address: Address,
+    pub decimals: u32,
+}
+
+impl ApprovableToken {
+    /// Tokens on the TXTC chain
+    pub fn all() -> Vec<Self> {
+        let mut tokens = Vec::new();
+        if let Some(txtc) = token_address(TXTC_CHAIN, "TXTC") {
+            tokens.push(ApprovableToken { symbol: "TXTC", address: txtc, decimals: TXTC_DECIMALS });
+        }
+        if let Some(usdc) = TXTC_CHAIN.usdc_address() {
+            tokens.push(ApprovableToken { symbol: "USDC", address: usdc, decimals: 6 });
+        }
+        tokens
+    }
+
+    pub fn from_symbol(symbol: &str) -> Result<Self, AllowanceError> {
+        let symbol = symbol.to_uppercase();
+        Self::all()
+            .into_iter()
+            .find(|t| t.symbol == symbol)
+            .ok_or(AllowanceError::UnknownToken(symbol))
+    }
+
+    /// Exact amount in base units
+    pub fn units(&self, amount: f64) -> Result<U256, AllowanceError> {
+        if !amount.is_finite() || amount <= 0.0 {
+            return Err(AllowanceError::InvalidAmount);
+        }
+        parse_units(amount.to_string(), self.decimals)
+            .map(Into::into)
+            .map_err(|_| AllowanceError::InvalidAmount)
+    }
+}
+
+/// Contract allowed to spend a user's tokens
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spender {
+    /// Display name; None for an arbitrary address
+    pub name: Option<&'static str>,
+    pub address: Address,
+}
+
+impl Spender {
+    /// Spenders users can approve by name
+    pub fn known() -> Vec<Self> {
+        vec![Spender {
+            name: Some("Uniswap router"),
+            address: Address::from_str(SWAP_ROUTER_ADDRESS).expect("valid router address"),
+        }]
+    }
+
+    /// Spender from SMS input: nothing or ROUTER for the swap router,
+    /// otherwise a 0x address
+    pub fn parse(input: Option<&str>) -> Result<Self, AllowanceError> {
+        match input {
+            None => Ok(Self::known().remove(0)),
+            Some(name) if name.eq_ignore_ascii_case("ROUTER") || name.eq_ignore_ascii_case("UNISWAP") => {
+                Ok(Self::known().remove(0))
+            }
+            Some(other) => {
+                let address = Address::from_str(other).map_err(|_| AllowanceError::InvalidSpender(other.to_string()))?;
+                Ok(Self::known()
+                    .into_iter()
+                    .find(|s| s.address == address)
+                    .unwrap_or(Spender { name: None, address }))
+            }
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self.name {
+            Some(name) => name.to_string(),
+            None => checksummed(&self.address),
+        }
+    }
+}
+
+/// Safety notice sent with every approval
+pub fn approval_warning(spender: &Spender) -> String {
+    match spender.name {
+        Some(name) => format!("{} can move up to this amount of your tokens until you REVOKE.", name),
+        None => "WARNING: unknown contract. It can take these tokens at any time. Only approve addresses you trust.".to_string(),
+    }
+}
+
+/// A non-zero allowance
+#[derive(Debug, Clone)]
+pub struct AllowanceEntry {
+    pub token: ApprovableToken,
+    pub spender: Spender,
+    pub amount: U256,
+}
+
+/// `100.000000 USDC -> Uniswap`
+impl SmsDisplay for AllowanceEntry {
+    fn sms_string(&self, locale: NumberLocale) -> String {
+        format!(
+            "{} {} -> {}",
+            locale.number(&format_token_balance(self.amount, self.token.decimals as u8)),
+            self.token.symbol,
+            self.spender.label()
+        )
+    }
+}
+
+/// Read current allowances for every supported token and known spender
+pub async fn read_allowances(provider: Arc<ChainProvider>, owner: Address) -> Result<Vec<AllowanceEntry>, AllowanceError> {
+    let mut entries = Vec::new();
+    for token in ApprovableToken::all() {
+        let contract = IERC20::new(token.address, provider.clone());
+        for spender in Spender::known() {
+            let amount = contract
+                .allowance(owner, spender.address)
+                .call()
+                .await
+                .map_err(|e| AllowanceError::Transaction(e.to_string()))?;
+            if !amount.is_zero() {
+                entries.push(AllowanceEntry { token, spender, amount });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Submit `approve(spender, amount)` from the user's key; zero revokes
+pub async fn send_approve(
+    provider: Arc<ChainProvider>,
+    chain: Chain,
+    private_key: &[u8; 32],
+    token: &ApprovableToken,
+    spender: &Spender,
+    amount: U256,
+) -> Result<TxHash, AllowanceError> {
+    let wallet = LocalWallet::from_bytes(private_key)
+        .map_err(|e| AllowanceError::Transaction(e.to_string()))?
+        .with_chain_id(chain.chain_id());
+    let client = Arc::new(SignerMiddleware::new(provider.as_ref().clone(), wallet));
+
+    let call = IERC20::new(token.address, client).approve(spender.address, amount);
+    let sent = match call.send().await {
+        Ok(pending) => Ok(pending.tx_hash()),
+        Err(e) if e.to_string().contains("insufficient funds") => Err(AllowanceError::NoGas),
+        Err(e) => Err(AllowanceError::Transaction(e.to_string())),
+    };
+    sent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spender_and_token_parsing() {
+        let router = Spender::parse(None).unwrap();
+        assert_eq!(router.name, Some("Uniswap router"));
+        assert_eq!(Spender::parse(Some(&SWAP_ROUTER_ADDRESS.to_lowercase())).unwrap(), router);
+
+        let other = Spender::parse(Some("0x0000000000000000000000000000000000000001")).unwrap();
+        assert_eq!(other.name, None);
+        assert!(approval_warning(&other).starts_with("WARNING"));
+        assert!(Spender::parse(Some("alice.eth")).is_err());
+
+        let txtc = ApprovableToken::from_symbol("txtc").unwrap();
+        assert_eq!(txtc.units(1.5).unwrap(), U256::from(1_500_000_000_000_000_000u64));
+        assert_eq!(txtc.units(0.0), Err(AllowanceError::InvalidAmount));
+        assert!(ApprovableToken::from_symbol("DOGE").is_err());
+    }
+}
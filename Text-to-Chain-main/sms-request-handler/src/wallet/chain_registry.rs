@@ -0,0 +1,248 @@
+//! EVM chains added at runtime
+//!
+//! `POST /admin/chains` puts a chain in the process-wide registry
+//! (`chains::register_chain`) and stores it in `chains`, which is read back
+//! at startup. From then on every `Chain` lookup sees it like a built-in:
+//! SMS and config input, token addresses, providers and circuit breakers.
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use ethers::types::Address;
+use serde::{Deserialize, Serialize};
+
+use super::chains::{register_chain, validate_chain, Chain, ChainSpec, ChainSpecError, DEFAULT_CONFIRMATIONS};
+use super::token_registry::{token_address, KNOWN_TOKENS};
+use crate::db::{ChainRepository, ChainRow};
+
+/// A chain to register
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewChain {
+    pub name: String,
+    pub chain_id: u64,
+    pub rpc_urls: Vec<String>,
+    #[serde(default)]
+    pub explorer_url: Option<String>,
+    /// Token contracts by symbol, e.g. `{"USDC": "0x..."}`
+    #[serde(default)]
+    pub tokens: HashMap<String, String>,
+    /// Defaults to the name, uppercased with dashes for spaces
+    #[serde(default)]
+    pub short_code: Option<String>,
+    /// Defaults to ETH
+    #[serde(default)]
+    pub native_token: Option<String>,
+    #[serde(default)]
+    pub testnet: bool,
+    /// Defaults to `DEFAULT_CONFIRMATIONS`
+    #[serde(default)]
+    pub confirmations: Option<u64>,
+}
+
+impl NewChain {
+    fn into_spec(self) -> Result<ChainSpec, ChainSpecError> {
+        let name = self.name.trim().to_string();
+        let short_code = self.short_code.unwrap_or_else(|| name.replace(' ', "-"));
+        Ok(ChainSpec {
+            chain_id: self.chain_id,
+            short_code: short_code.trim().to_uppercase(),
+            native_token: self.native_token.unwrap_or_else(|| "ETH".to_string()).trim().to_uppercase(),
+            rpc_urls: self.rpc_urls.iter().map(|url| url.trim().to_string()).collect(),
+            explorer_url: self.explorer_url.map(|url| url.trim().trim_end_matches('/').to_string()),
+            testnet: self.testnet,
+            confirmations: self.confirmations.unwrap_or(DEFAULT_CONFIRMATIONS),
+            tokens: parse_tokens(self.tokens.iter().map(|(symbol, address)| (symbol.as_str(), address.as_str())))?,
+            aliases: Vec::new(),
+            name,
+        })
+    }
+}
+
+fn parse_tokens<'a>(tokens: impl Iterator<Item = (&'a str, &'a str)>) -> Result<HashMap<String, Address>, ChainSpecError> {
+    tokens
+        .map(|(symbol, address)| {
+            let symbol = symbol.trim().to_uppercase();
+            let address = Address::from_str(address.trim())
+                .map_err(|_| ChainSpecError::Invalid(format!("{} address {}", symbol, address)))?;
+            Ok((symbol, address))
+        })
+        .collect()
+}
+
+fn to_row(spec: &ChainSpec) -> Result<ChainRow, ChainSpecError> {
+    let chain_id = i64::try_from(spec.chain_id).map_err(|_| ChainSpecError::Invalid("chain id too large".to_string()))?;
+    Ok(ChainRow {
+        chain_id,
+        name: spec.name.clone(),
+        short_code: spec.short_code.clone(),
+        native_token: spec.native_token.clone(),
+        rpc_urls: spec.rpc_urls.clone(),
+        explorer_url: spec.explorer_url.clone(),
+        testnet: spec.testnet,
+        confirmations: spec.confirmations as i64,
+        tokens: spec.tokens.iter().map(|(symbol, address)| format!("{}={:?}", symbol, address)).collect(),
+    })
+}
+
+fn from_row(row: ChainRow) -> Result<ChainSpec, ChainSpecError> {
+    let tokens = row
+        .tokens
+        .iter()
+        .map(|entry| entry.split_once('=').ok_or_else(|| ChainSpecError::Invalid(format!("token {}", entry))))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(ChainSpec {
+        chain_id: row.chain_id as u64,
+        name: row.name,
+        short_code: row.short_code,
+        native_token: row.native_token,
+        rpc_urls: row.rpc_urls,
+        explorer_url: row.explorer_url,
+        testnet: row.testnet,
+        confirmations: row.confirmations.max(0) as u64,
+        tokens: parse_tokens(tokens.into_iter())?,
+        aliases: Vec::new(),
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChainRegistryError {
+    #[error(transparent)]
+    Spec(#[from] ChainSpecError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// One chain as the admin API shows it
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainState {
+    pub chain_id: u64,
+    pub name: &'static str,
+    pub short_code: &'static str,
+    pub native_token: &'static str,
+    /// Endpoint in use, including any live config override
+    pub rpc_url: String,
+    pub rpc_urls: &'static [String],
+    pub explorer_url: Option<&'static str>,
+    pub testnet: bool,
+    pub confirmations: u64,
+    /// Current token addresses, overrides included
+    pub tokens: BTreeMap<&'static str, String>,
+    pub builtin: bool,
+}
+
+impl From<Chain> for ChainState {
+    fn from(chain: Chain) -> Self {
+        let spec = chain.spec();
+        Self {
+            chain_id: chain.chain_id(),
+            name: chain.name(),
+            short_code: chain.short_code(),
+            native_token: chain.native_token(),
+            rpc_url: chain.rpc_url(),
+            rpc_urls: &spec.rpc_urls,
+            explorer_url: chain.explorer_url(),
+            testnet: spec.testnet,
+            confirmations: spec.confirmations,
+            tokens: KNOWN_TOKENS
+                .into_iter()
+                .filter_map(|symbol| Some((symbol, format!("{:?}", token_address(chain, symbol)?))))
+                .collect(),
+            builtin: chain.is_builtin(),
+        }
+    }
+}
+
+/// Persistent side of the chain registry
+#[derive(Clone)]
+pub struct ChainRegistry {
+    repo: ChainRepository,
+}
+
+impl ChainRegistry {
+    /// Register every stored chain; rows that no longer validate (say, a
+    /// name a built-in took since) are skipped
+    pub async fn load(repo: ChainRepository) -> Result<Self, sqlx::Error> {
+        for row in repo.list().await? {
+            let chain_id = row.chain_id;
+            match from_row(row).and_then(register_chain) {
+                Ok(chain) => tracing::info!(chain_id, name = chain.name(), "Registered chain loaded"),
+                Err(e) => tracing::warn!(chain_id, "Ignoring stored chain: {}", e),
+            }
+        }
+        Ok(Self { repo })
+    }
+
+    /// Register a chain, or update one added earlier, and persist it
+    pub async fn add(&self, chain: NewChain) -> Result<Chain, ChainRegistryError> {
+        let spec = chain.into_spec()?;
+        validate_chain(&spec)?;
+        self.repo.upsert(&to_row(&spec)?).await?;
+        let chain = register_chain(spec)?;
+        tracing::info!(chain_id = chain.chain_id(), name = chain.name(), "Chain registered");
+        Ok(chain)
+    }
+
+    /// Every chain, built-ins first
+    pub fn snapshot(&self) -> Vec<ChainState> {
+        Chain::all().into_iter().map(ChainState::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_chain(name: &str, chain_id: u64) -> NewChain {
+        NewChain {
+            name: name.to_string(),
+            chain_id,
+            rpc_urls: vec!["https://rpc.example.org".to_string()],
+            explorer_url: Some("https://explorer.example.org/".to_string()),
+            tokens: HashMap::from([("usdc".to_string(), "0x0000000000000000000000000000000000000007".to_string())]),
+            short_code: None,
+            native_token: None,
+            testnet: true,
+            confirmations: None,
+        }
+    }
+
+    #[test]
+    fn test_registered_chain_resolves_everywhere() {
+        let spec = new_chain("Celo Alfajores", 44787).into_spec().unwrap();
+        assert_eq!(spec.short_code, "CELO-ALFAJORES");
+        assert_eq!(spec.explorer_url.as_deref(), Some("https://explorer.example.org"));
+
+        let chain = register_chain(spec).unwrap();
+        assert_eq!(Chain::from_input("celo-alfajores"), Some(chain));
+        assert_eq!(Chain::from_id(44787), Some(chain));
+        assert_eq!(chain.name(), "Celo Alfajores");
+        assert_eq!(chain.default_confirmations(), DEFAULT_CONFIRMATIONS);
+        assert_eq!(chain.usdc_address(), Some(Address::from_low_u64_be(7)));
+        assert!(Chain::testnets().contains(&chain));
+        assert!(!chain.is_builtin());
+
+        // Stored and read back unchanged
+        let row = to_row(chain.spec()).unwrap();
+        assert_eq!(from_row(row).unwrap().tokens, chain.spec().tokens);
+    }
+
+    #[test]
+    fn test_invalid_chains_rejected() {
+        let builtin = new_chain("Other Amoy", 80002).into_spec().unwrap();
+        assert!(matches!(validate_chain(&builtin), Err(ChainSpecError::BuiltIn(80002))));
+
+        let mut clash = new_chain("Gnosis", 100).into_spec().unwrap();
+        clash.short_code = "ETH-T".to_string();
+        assert!(matches!(validate_chain(&clash), Err(ChainSpecError::NameTaken(_, 11155111))));
+
+        let mut bad_rpc = new_chain("Gnosis", 100).into_spec().unwrap();
+        bad_rpc.rpc_urls = vec!["not a url".to_string()];
+        assert!(matches!(validate_chain(&bad_rpc), Err(ChainSpecError::InvalidRpcUrl(_))));
+
+        let mut bad_token = new_chain("Gnosis", 100);
+        bad_token.tokens = HashMap::from([("DAI".to_string(), "0x0000000000000000000000000000000000000001".to_string())]);
+        assert!(validate_chain(&bad_token.into_spec().unwrap()).is_err());
+
+        assert!(Chain::from_input("gnosis").is_none());
+    }
+}
@@ -1,155 +1,349 @@
-use ethers::providers::{Http, Provider};
+use ethers::providers::{Http, Provider, ProviderError};
 use ethers::types::Address;
+use std::collections::HashMap;
+use std::future::Future;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::Duration;
 
-/// Supported blockchain networks
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum Chain {
+use crate::alerts::{self, Alert};
+
+use super::circuit::{CircuitBreaker, CircuitState, DEFAULT_CALL_TIMEOUT};
+use super::token_registry::{token_address, KNOWN_TOKENS};
+use super::tokens::{fetch_chain_balances, ChainBalances};
+
+/// Overriding RPC URL and its provider
+type RpcOverride = (String, Arc<ChainProvider>);
+
+/// RPC endpoints replacing the built-in ones
+static RPC_OVERRIDES: LazyLock<RwLock<HashMap<Chain, RpcOverride>>> = LazyLock::new(Default::default);
+
+/// Every chain the wallet knows, by chain id: the built-ins plus any added
+/// with `register_chain`. Specs are leaked so lookups can hand out
+/// `&'static str`; chains are added rarely, by an operator.
+static REGISTRY: LazyLock<RwLock<HashMap<u64, &'static ChainSpec>>> = LazyLock::new(|| {
+    let builtins = BUILTINS.iter().map(|b| (b.chain.0, &*Box::leak(Box::new(b.spec())))).collect();
+    RwLock::new(builtins)
+});
+
+/// Providers for registered chains, rebuilt when a chain is re-registered
+static REGISTERED_PROVIDERS: LazyLock<RwLock<HashMap<Chain, Arc<ChainProvider>>>> = LazyLock::new(Default::default);
+
+/// Reorg depth for registered chains that don't set one
+pub const DEFAULT_CONFIRMATIONS: u64 = 64;
+
+/// A network in the chain registry, identified by its EVM chain id.
+/// Only the built-in constants and registry lookups create one, so every
+/// `Chain` has a spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Chain(u64);
+
+/// Everything the wallet needs to know about a network
+#[derive(Debug, Clone)]
+pub struct ChainSpec {
+    pub chain_id: u64,
+    /// Display name, e.g. "Polygon Amoy"
+    pub name: String,
+    /// Short code for SMS display, e.g. "POL-T"
+    pub short_code: String,
+    pub native_token: String,
+    /// RPC endpoints; the first is used unless live config overrides it
+    pub rpc_urls: Vec<String>,
+    pub explorer_url: Option<String>,
+    pub testnet: bool,
+    /// Blocks a deposit needs before it is treated as final
+    pub confirmations: u64,
+    /// Token contracts by symbol (see `token_registry::KNOWN_TOKENS`)
+    pub tokens: HashMap<String, Address>,
+    /// Extra words `Chain::from_input` accepts, besides the name and short code
+    pub aliases: Vec<String>,
+}
+
+impl ChainSpec {
+    /// Words users and config can name this chain by, uppercased
+    fn input_words(&self) -> impl Iterator<Item = String> + '_ {
+        [self.short_code.to_uppercase(), self.name.to_uppercase().replace(' ', "-")]
+            .into_iter()
+            .chain(self.aliases.iter().map(|alias| alias.to_uppercase()))
+    }
+}
+
+/// Why a chain can't be registered
+#[derive(Debug, thiserror::Error)]
+pub enum ChainSpecError {
+    #[error("Chain id {0} is built in")]
+    BuiltIn(u64),
+    #[error("Invalid chain: {0}")]
+    Invalid(String),
+    #[error("Invalid RPC URL: {0}")]
+    InvalidRpcUrl(String),
+    #[error("{0} already names chain {1}")]
+    NameTaken(String, u64),
+}
+
+/// A built-in chain, kept as a const table so `Chain` constants stay `const`
+struct Builtin {
+    chain: Chain,
+    name: &'static str,
+    short_code: &'static str,
+    native_token: &'static str,
+    rpc_url: &'static str,
+    explorer_url: &'static str,
+    testnet: bool,
+    /// Polygon PoS has seen reorgs over 100 blocks deep; rollups follow
+    /// their L1 but produce blocks every few seconds or faster.
+    confirmations: u64,
+    usdc: &'static str,
+    aliases: &'static [&'static str],
+}
+
+impl Builtin {
+    fn spec(&self) -> ChainSpec {
+        ChainSpec {
+            chain_id: self.chain.0,
+            name: self.name.to_string(),
+            short_code: self.short_code.to_string(),
+            native_token: self.native_token.to_string(),
+            rpc_urls: vec![self.rpc_url.to_string()],
+            explorer_url: Some(self.explorer_url.to_string()),
+            testnet: self.testnet,
+            confirmations: self.confirmations,
+            tokens: Address::from_str(self.usdc).map(|usdc| HashMap::from([("USDC".to_string(), usdc)])).unwrap_or_default(),
+            aliases: self.aliases.iter().map(|alias| alias.to_string()).collect(),
+        }
+    }
+}
+
+const BUILTINS: [Builtin; 8] = [
+    Builtin {
+        chain: Chain::PolygonAmoy,
+        name: "Polygon Amoy",
+        short_code: "POL-T",
+        native_token: "MATIC",
+        rpc_url: "https://rpc-amoy.polygon.technology",
+        explorer_url: "https://amoy.polygonscan.com",
+        testnet: true,
+        confirmations: 64,
+        usdc: "0x41E94Eb019C0762f9Bfcf9Fb1E58725BfB0e7582", // Test USDC
+        aliases: &["AMOY"],
+    },
+    Builtin {
+        chain: Chain::BaseSepolia,
+        name: "Base Sepolia",
+        short_code: "BASE-T",
+        native_token: "ETH",
+        rpc_url: "https://sepolia.base.org",
+        explorer_url: "https://sepolia.basescan.org",
+        testnet: true,
+        confirmations: 30,
+        usdc: "0x036CbD53842c5426634e7929541eC2318f3dCF7e", // Test USDC
+        aliases: &[],
+    },
+    Builtin {
+        chain: Chain::EthereumSepolia,
+        name: "Ethereum Sepolia",
+        short_code: "ETH-T",
+        native_token: "ETH",
+        rpc_url: "https://1rpc.io/sepolia",
+        explorer_url: "https://sepolia.etherscan.io",
+        testnet: true,
+        confirmations: 12,
+        usdc: "0x1c7D4B196Cb0C7B01d743Fbc6116a902379C7238", // Test USDC
+        aliases: &["ETH-SEPOLIA", "SEPOLIA"],
+    },
+    Builtin {
+        chain: Chain::ArbitrumSepolia,
+        name: "Arbitrum Sepolia",
+        short_code: "ARB-T",
+        native_token: "ETH",
+        rpc_url: "https://sepolia-rollup.arbitrum.io/rpc",
+        explorer_url: "https://sepolia.arbiscan.io",
+        testnet: true,
+        confirmations: 60,
+        usdc: "0x75faf114eafb1BDbe2F0316DF893fd58CE46AA4d", // Test USDC
+        aliases: &["ARB-SEPOLIA"],
+    },
+    Builtin {
+        chain: Chain::PolygonMainnet,
+        name: "Polygon",
+        short_code: "POL",
+        native_token: "MATIC",
+        rpc_url: "https://polygon-rpc.com",
+        explorer_url: "https://polygonscan.com",
+        testnet: false,
+        confirmations: 128,
+        usdc: "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359",
+        aliases: &["MATIC"],
+    },
+    Builtin {
+        chain: Chain::BaseMainnet,
+        name: "Base",
+        short_code: "BASE",
+        native_token: "ETH",
+        rpc_url: "https://mainnet.base.org",
+        explorer_url: "https://basescan.org",
+        testnet: false,
+        confirmations: 30,
+        usdc: "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913",
+        aliases: &[],
+    },
+    Builtin {
+        chain: Chain::EthereumMainnet,
+        name: "Ethereum",
+        short_code: "ETH",
+        native_token: "ETH",
+        rpc_url: "https://eth.llamarpc.com",
+        explorer_url: "https://etherscan.io",
+        testnet: false,
+        confirmations: 12,
+        usdc: "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+        aliases: &[],
+    },
+    Builtin {
+        chain: Chain::ArbitrumOne,
+        name: "Arbitrum",
+        short_code: "ARB",
+        native_token: "ETH",
+        rpc_url: "https://arb1.arbitrum.io/rpc",
+        explorer_url: "https://arbiscan.io",
+        testnet: false,
+        confirmations: 60,
+        usdc: "0xaf88d065e77c8cC2239327C5EDb3A432268e5831",
+        aliases: &[],
+    },
+];
+
+// The built-ins keep the names they had as enum variants
+#[allow(non_upper_case_globals)]
+impl Chain {
     /// Polygon Amoy Testnet
-    PolygonAmoy,
+    pub const PolygonAmoy: Chain = Chain(80002);
     /// Polygon Mainnet
-    PolygonMainnet,
+    pub const PolygonMainnet: Chain = Chain(137);
     /// Base Sepolia Testnet
-    BaseSepolia,
+    pub const BaseSepolia: Chain = Chain(84532);
     /// Base Mainnet
-    BaseMainnet,
+    pub const BaseMainnet: Chain = Chain(8453);
     /// Ethereum Sepolia Testnet
-    EthereumSepolia,
+    pub const EthereumSepolia: Chain = Chain(11155111);
     /// Ethereum Mainnet
-    EthereumMainnet,
+    pub const EthereumMainnet: Chain = Chain(1);
     /// Arbitrum Sepolia Testnet
-    ArbitrumSepolia,
+    pub const ArbitrumSepolia: Chain = Chain(421614);
     /// Arbitrum One Mainnet
-    ArbitrumOne,
+    pub const ArbitrumOne: Chain = Chain(42161);
 }
 
 impl Chain {
+    /// Registered chain with this id
+    pub fn from_id(chain_id: u64) -> Option<Chain> {
+        let known = REGISTRY.read().ok()?.contains_key(&chain_id);
+        known.then_some(Chain(chain_id))
+    }
+
+    /// Registry entry for this chain
+    pub fn spec(&self) -> &'static ChainSpec {
+        let spec = REGISTRY.read().ok().and_then(|registry| registry.get(&self.0).copied());
+        // Entries are never removed, and a Chain only exists for an entry
+        spec.expect("chain missing from registry")
+    }
+
     /// Get chain ID
     pub fn chain_id(&self) -> u64 {
-        match self {
-            Chain::PolygonAmoy => 80002,
-            Chain::PolygonMainnet => 137,
-            Chain::BaseSepolia => 84532,
-            Chain::BaseMainnet => 8453,
-            Chain::EthereumSepolia => 11155111,
-            Chain::EthereumMainnet => 1,
-            Chain::ArbitrumSepolia => 421614,
-            Chain::ArbitrumOne => 42161,
-        }
+        self.0
     }
 
-    /// Get RPC URL (public endpoints)
-    pub fn rpc_url(&self) -> &'static str {
-        match self {
-            Chain::PolygonAmoy => "https://rpc-amoy.polygon.technology",
-            Chain::PolygonMainnet => "https://polygon-rpc.com",
-            Chain::BaseSepolia => "https://sepolia.base.org",
-            Chain::BaseMainnet => "https://mainnet.base.org",
-            Chain::EthereumSepolia => "https://1rpc.io/sepolia",
-            Chain::EthereumMainnet => "https://eth.llamarpc.com",
-            Chain::ArbitrumSepolia => "https://sepolia-rollup.arbitrum.io/rpc",
-            Chain::ArbitrumOne => "https://arb1.arbitrum.io/rpc",
-        }
+    /// RPC URL in use, including any override from live config
+    pub fn rpc_url(&self) -> String {
+        let active = RPC_OVERRIDES.read().ok().and_then(|o| o.get(self).map(|(url, _)| url.clone()));
+        active.unwrap_or_else(|| self.default_rpc_url().to_string())
+    }
+
+    /// Registered RPC URL (public endpoints for the built-ins)
+    pub fn default_rpc_url(&self) -> &'static str {
+        self.spec().rpc_urls.first().map(String::as_str).unwrap_or_default()
+    }
+
+    /// Block explorer, if one is known
+    pub fn explorer_url(&self) -> Option<&'static str> {
+        self.spec().explorer_url.as_deref()
     }
 
     /// Get display name
     pub fn name(&self) -> &'static str {
-        match self {
-            Chain::PolygonAmoy => "Polygon Amoy",
-            Chain::PolygonMainnet => "Polygon",
-            Chain::BaseSepolia => "Base Sepolia",
-            Chain::BaseMainnet => "Base",
-            Chain::EthereumSepolia => "Ethereum Sepolia",
-            Chain::EthereumMainnet => "Ethereum",
-            Chain::ArbitrumSepolia => "Arbitrum Sepolia",
-            Chain::ArbitrumOne => "Arbitrum",
-        }
+        &self.spec().name
     }
 
     /// Get short code for SMS display
     pub fn short_code(&self) -> &'static str {
-        match self {
-            Chain::PolygonAmoy => "POL-T",
-            Chain::PolygonMainnet => "POL",
-            Chain::BaseSepolia => "BASE-T",
-            Chain::BaseMainnet => "BASE",
-            Chain::EthereumSepolia => "ETH-T",
-            Chain::EthereumMainnet => "ETH",
-            Chain::ArbitrumSepolia => "ARB-T",
-            Chain::ArbitrumOne => "ARB",
-        }
+        &self.spec().short_code
     }
 
     /// Get native token symbol
     pub fn native_token(&self) -> &'static str {
-        match self {
-            Chain::PolygonAmoy | Chain::PolygonMainnet => "MATIC",
-            Chain::BaseSepolia | Chain::BaseMainnet => "ETH",
-            Chain::EthereumSepolia | Chain::EthereumMainnet => "ETH",
-            Chain::ArbitrumSepolia | Chain::ArbitrumOne => "ETH",
-        }
+        &self.spec().native_token
     }
 
-    /// Get USDC contract address (None if not deployed)
+    /// Get USDC contract address (None if not deployed), including any
+    /// operator override from the token registry
     pub fn usdc_address(&self) -> Option<Address> {
-        let addr_str = match self {
-            Chain::PolygonAmoy => "0x41E94Eb019C0762f9Bfcf9Fb1E58725BfB0e7582", // Test USDC
-            Chain::PolygonMainnet => "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359",
-            Chain::BaseSepolia => "0x036CbD53842c5426634e7929541eC2318f3dCF7e", // Test USDC
-            Chain::BaseMainnet => "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913",
-            Chain::EthereumSepolia => "0x1c7D4B196Cb0C7B01d743Fbc6116a902379C7238", // Test USDC
-            Chain::EthereumMainnet => "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
-            Chain::ArbitrumSepolia => return None, // No official test USDC
-            Chain::ArbitrumOne => "0xaf88d065e77c8cC2239327C5EDb3A432268e5831",
-        };
-        Address::from_str(addr_str).ok()
+        token_address(*self, "USDC")
+    }
+
+    /// Registered contract address of `symbol`, before overrides
+    pub fn default_token_address(&self, symbol: &str) -> Option<Address> {
+        self.spec().tokens.get(symbol).copied()
     }
 
     /// Check if chain is a testnet
     pub fn is_testnet(&self) -> bool {
-        matches!(
-            self,
-            Chain::PolygonAmoy
-                | Chain::BaseSepolia
-                | Chain::EthereumSepolia
-                | Chain::ArbitrumSepolia
-        )
+        self.spec().testnet
+    }
+
+    /// One of the chains compiled in, rather than added at runtime
+    pub fn is_builtin(&self) -> bool {
+        BUILTINS.iter().any(|b| b.chain == *self)
+    }
+
+    /// Blocks a deposit needs before it is treated as final
+    pub fn default_confirmations(&self) -> u64 {
+        self.spec().confirmations
+    }
+
+    /// Every registered chain: built-ins in their usual order, then the
+    /// rest by chain id
+    fn registered() -> Vec<Chain> {
+        let mut added: Vec<Chain> = REGISTRY
+            .read()
+            .map(|registry| registry.keys().map(|id| Chain(*id)).filter(|chain| !chain.is_builtin()).collect())
+            .unwrap_or_default();
+        added.sort();
+        BUILTINS.iter().map(|b| b.chain).chain(added).collect()
     }
 
     /// Get all supported testnets
     pub fn testnets() -> Vec<Chain> {
-        vec![
-            Chain::PolygonAmoy,
-            Chain::BaseSepolia,
-            Chain::EthereumSepolia,
-            Chain::ArbitrumSepolia,
-        ]
+        Self::registered().into_iter().filter(Chain::is_testnet).collect()
     }
 
     /// Get all supported mainnets
     pub fn mainnets() -> Vec<Chain> {
-        vec![
-            Chain::PolygonMainnet,
-            Chain::BaseMainnet,
-            Chain::EthereumMainnet,
-            Chain::ArbitrumOne,
-        ]
+        Self::registered().into_iter().filter(|chain| !chain.is_testnet()).collect()
+    }
+
+    /// Testnets, then mainnets
+    pub fn all() -> Vec<Chain> {
+        Self::testnets().into_iter().chain(Self::mainnets()).collect()
     }
 
-    /// Parse chain from user input (case-insensitive)
+    /// Parse chain from user input (case-insensitive): a short code, the
+    /// name with dashes for spaces, an alias or the chain id
     pub fn from_input(input: &str) -> Option<Chain> {
-        match input.to_uppercase().as_str() {
-            "POLYGON" | "POL" | "MATIC" => Some(Chain::PolygonMainnet),
-            "POLYGON-AMOY" | "POL-T" | "AMOY" => Some(Chain::PolygonAmoy),
-            "BASE" => Some(Chain::BaseMainnet),
-            "BASE-SEPOLIA" | "BASE-T" => Some(Chain::BaseSepolia),
-            "ETH" | "ETHEREUM" => Some(Chain::EthereumMainnet),
-            "ETH-SEPOLIA" | "ETH-T" | "SEPOLIA" => Some(Chain::EthereumSepolia),
-            "ARB" | "ARBITRUM" => Some(Chain::ArbitrumOne),
-            "ARB-SEPOLIA" | "ARB-T" => Some(Chain::ArbitrumSepolia),
-            _ => None,
+        let input = input.trim().to_uppercase();
+        if let Ok(chain_id) = input.parse::<u64>() {
+            return Chain::from_id(chain_id);
         }
+        Self::registered().into_iter().find(|chain| chain.spec().input_words().any(|word| word == input))
     }
 }
 
@@ -159,13 +353,121 @@ impl std::fmt::Display for Chain {
     }
 }
 
+/// Check that `spec` could be registered, without registering it
+pub fn validate_chain(spec: &ChainSpec) -> Result<(), ChainSpecError> {
+    if spec.chain_id == 0 {
+        return Err(ChainSpecError::Invalid("chain id must be positive".to_string()));
+    }
+    if BUILTINS.iter().any(|b| b.chain.0 == spec.chain_id) {
+        return Err(ChainSpecError::BuiltIn(spec.chain_id));
+    }
+    for (field, value) in [("name", &spec.name), ("short code", &spec.short_code), ("native token", &spec.native_token)] {
+        if value.trim().is_empty() {
+            return Err(ChainSpecError::Invalid(format!("{} is required", field)));
+        }
+    }
+    if spec.rpc_urls.is_empty() {
+        return Err(ChainSpecError::Invalid("at least one RPC URL is required".to_string()));
+    }
+    for url in &spec.rpc_urls {
+        if !url.starts_with("http") || Provider::<Http>::try_from(url.as_str()).is_err() {
+            return Err(ChainSpecError::InvalidRpcUrl(url.clone()));
+        }
+    }
+    if let Some(ref explorer) = spec.explorer_url {
+        if !explorer.starts_with("https://") && !explorer.starts_with("http://") {
+            return Err(ChainSpecError::Invalid(format!("explorer URL {}", explorer)));
+        }
+    }
+    if let Some(symbol) = spec.tokens.keys().find(|symbol| !KNOWN_TOKENS.contains(&symbol.as_str())) {
+        return Err(ChainSpecError::Invalid(format!("unknown token {}", symbol)));
+    }
+
+    // Input words must stay unambiguous across chains
+    for chain in Chain::registered().into_iter().filter(|chain| chain.0 != spec.chain_id) {
+        if let Some(word) = spec.input_words().find(|word| chain.spec().input_words().any(|taken| taken == *word)) {
+            return Err(ChainSpecError::NameTaken(word, chain.0));
+        }
+    }
+    Ok(())
+}
+
+/// Add a chain to the registry, or replace an earlier registration with
+/// the same id. Built-in chains can't be replaced.
+pub fn register_chain(spec: ChainSpec) -> Result<Chain, ChainSpecError> {
+    validate_chain(&spec)?;
+    let chain = Chain(spec.chain_id);
+    let provider = Provider::<Http>::try_from(spec.rpc_urls[0].as_str())
+        .map_err(|_| ChainSpecError::InvalidRpcUrl(spec.rpc_urls[0].clone()))?;
+    let spec: &'static ChainSpec = Box::leak(Box::new(spec));
+    if let Ok(mut registry) = REGISTRY.write() {
+        registry.insert(chain.0, spec);
+    }
+    if let Ok(mut providers) = REGISTERED_PROVIDERS.write() {
+        providers.insert(chain, Arc::new(provider));
+    }
+    Ok(chain)
+}
+
+/// Provider for a chain added with `register_chain`
+fn registered_provider(chain: Chain) -> Option<Arc<ChainProvider>> {
+    REGISTERED_PROVIDERS.read().ok().and_then(|p| p.get(&chain).cloned())
+}
+
 /// Provider type alias
 pub type ChainProvider = Provider<Http>;
 
+/// Point chains at other RPC endpoints, replacing any earlier overrides.
+/// URLs that don't parse are skipped; callers validate them first.
+pub fn set_rpc_urls(urls: &HashMap<Chain, String>) {
+    let providers = urls
+        .iter()
+        .filter_map(|(chain, url)| {
+            let provider = Provider::<Http>::try_from(url.as_str()).ok()?;
+            Some((*chain, (url.clone(), Arc::new(provider))))
+        })
+        .collect();
+    if let Ok(mut active) = RPC_OVERRIDES.write() {
+        *active = providers;
+    }
+}
+
+/// Provider for an overridden chain's RPC endpoint
+pub fn rpc_override(chain: Chain) -> Option<Arc<ChainProvider>> {
+    RPC_OVERRIDES.read().ok().and_then(|o| o.get(&chain).map(|(_, provider)| provider.clone()))
+}
+
+/// Error from an RPC call made through `MultiChainProvider::call`
+#[derive(Debug, thiserror::Error)]
+pub enum ChainError {
+    /// Circuit is open - the call was not attempted
+    #[error("{0} temporarily unavailable")]
+    Unavailable(Chain),
+    #[error("{0} RPC timed out")]
+    Timeout(Chain),
+    #[error("{0} RPC error: {1}")]
+    Rpc(Chain, ProviderError),
+}
+
+impl ChainError {
+    /// Short reply for SMS users
+    pub fn sms_message(&self) -> String {
+        match self {
+            ChainError::Unavailable(chain) | ChainError::Timeout(chain) | ChainError::Rpc(chain, _) => {
+                format!("{} temporarily unavailable. Try again in a few minutes.", chain.name())
+            }
+        }
+    }
+}
+
 /// Chain-specific provider
 #[derive(Clone)]
 pub struct MultiChainProvider {
     providers: std::collections::HashMap<Chain, Arc<ChainProvider>>,
+    /// One breaker per chain, shared between clones; chains registered
+    /// later get theirs on first use
+    breakers: Arc<std::sync::Mutex<HashMap<Chain, Arc<CircuitBreaker>>>>,
+    call_timeout: Duration,
 }
 
 impl MultiChainProvider {
@@ -175,12 +477,12 @@ impl MultiChainProvider {
 
         // Initialize providers for all testnets by default
         for chain in Chain::testnets() {
-            if let Ok(provider) = Provider::<Http>::try_from(chain.rpc_url()) {
+            if let Ok(provider) = Provider::<Http>::try_from(chain.rpc_url().as_str()) {
                 providers.insert(chain, Arc::new(provider));
             }
         }
 
-        Self { providers }
+        Self::from_providers(providers)
     }
 
     /// Create provider with specific chains
@@ -188,27 +490,44 @@ impl MultiChainProvider {
         let mut providers = std::collections::HashMap::new();
 
         for chain in chains {
-            if let Ok(provider) = Provider::<Http>::try_from(chain.rpc_url()) {
+            if let Ok(provider) = Provider::<Http>::try_from(chain.rpc_url().as_str()) {
                 providers.insert(*chain, Arc::new(provider));
             }
         }
 
-        Self { providers }
+        Self::from_providers(providers)
     }
 
-    /// Get provider for a specific chain
+    fn from_providers(providers: std::collections::HashMap<Chain, Arc<ChainProvider>>) -> Self {
+        let breakers = Chain::all()
+            .into_iter()
+            .map(|chain| (chain, Arc::new(CircuitBreaker::default())))
+            .collect();
+
+        Self {
+            providers,
+            breakers: Arc::new(std::sync::Mutex::new(breakers)),
+            call_timeout: DEFAULT_CALL_TIMEOUT,
+        }
+    }
+
+    /// Provider for a chain; an RPC override wins over the one built at
+    /// startup, and registered chains use the provider from their latest
+    /// registration
     pub fn get(&self, chain: Chain) -> Option<Arc<ChainProvider>> {
-        self.providers.get(&chain).cloned()
+        rpc_override(chain)
+            .or_else(|| registered_provider(chain))
+            .or_else(|| self.providers.get(&chain).cloned())
     }
 
     /// Get or create provider for a chain
     pub fn get_or_create(&mut self, chain: Chain) -> Arc<ChainProvider> {
-        if let Some(provider) = self.providers.get(&chain) {
-            return provider.clone();
+        if let Some(provider) = self.get(chain) {
+            return provider;
         }
 
         let provider = Arc::new(
-            Provider::<Http>::try_from(chain.rpc_url()).expect("Invalid RPC URL"),
+            Provider::<Http>::try_from(chain.rpc_url().as_str()).expect("Invalid RPC URL"),
         );
         self.providers.insert(chain, provider.clone());
         provider
@@ -218,6 +537,85 @@ impl MultiChainProvider {
     pub fn available_chains(&self) -> Vec<Chain> {
         self.providers.keys().copied().collect()
     }
+
+    /// Run an RPC call against a chain behind its circuit breaker.
+    ///
+    /// Fails fast with `ChainError::Unavailable` while the chain's circuit is
+    /// open; timeouts and RPC errors count towards opening it.
+    pub async fn call<T, F, Fut>(&self, chain: Chain, f: F) -> Result<T, ChainError>
+    where
+        F: FnOnce(Arc<ChainProvider>) -> Fut,
+        Fut: Future<Output = Result<T, ProviderError>>,
+    {
+        let breaker = self.breaker(chain);
+        if !breaker.try_acquire() {
+            return Err(ChainError::Unavailable(chain));
+        }
+
+        // Chains not set up at construction get a one-off provider
+        let provider = match self.get(chain) {
+            Some(provider) => provider,
+            None => match Provider::<Http>::try_from(chain.rpc_url().as_str()) {
+                Ok(provider) => Arc::new(provider),
+                Err(_) => {
+                    breaker.record_failure();
+                    return Err(ChainError::Unavailable(chain));
+                }
+            },
+        };
+
+        match tokio::time::timeout(self.call_timeout, f(provider)).await {
+            Ok(Ok(value)) => {
+                // A probe getting through closes the circuit
+                if breaker.state() == CircuitState::HalfOpen {
+                    alerts::resolve(Alert::circuit_open_key(chain));
+                }
+                breaker.record_success();
+                Ok(value)
+            }
+            Ok(Err(e)) => {
+                tracing::warn!(chain = chain.name(), "RPC call failed: {}", e);
+                Self::trip(chain, &breaker);
+                Err(ChainError::Rpc(chain, e))
+            }
+            Err(_) => {
+                tracing::warn!(chain = chain.name(), "RPC call timed out");
+                Self::trip(chain, &breaker);
+                Err(ChainError::Timeout(chain))
+            }
+        }
+    }
+
+    /// Native and USDC balances on every available chain, read concurrently.
+    /// Each chain is behind its own breaker and timeout, so a slow RPC costs
+    /// only its own line. Registered chains are always included. Testnets
+    /// first, each group in `Chain` order
+    pub async fn balances_everywhere(&self, address: Address) -> Vec<(Chain, Result<ChainBalances, ChainError>)> {
+        let chains: Vec<Chain> = Chain::all()
+            .into_iter()
+            .filter(|chain| self.providers.contains_key(chain) || !chain.is_builtin())
+            .collect();
+        let reads = chains.iter().map(|&chain| async move {
+            let result = self.call(chain, |provider| fetch_chain_balances(provider, chain, address)).await;
+            (chain, result)
+        });
+        futures::future::join_all(reads).await
+    }
+
+    fn trip(chain: Chain, breaker: &CircuitBreaker) {
+        breaker.record_failure();
+        if breaker.state() == CircuitState::Open {
+            tracing::error!(chain = chain.name(), "Circuit open - failing fast until cooldown");
+            alerts::raise(Alert::circuit_open(chain));
+        }
+    }
+
+    fn breaker(&self, chain: Chain) -> Arc<CircuitBreaker> {
+        match self.breakers.lock() {
+            Ok(mut breakers) => breakers.entry(chain).or_default().clone(),
+            Err(_) => Arc::default(),
+        }
+    }
 }
 
 impl Default for MultiChainProvider {
@@ -242,6 +640,8 @@ mod tests {
         assert_eq!(Chain::from_input("polygon"), Some(Chain::PolygonMainnet));
         assert_eq!(Chain::from_input("BASE"), Some(Chain::BaseMainnet));
         assert_eq!(Chain::from_input("eth"), Some(Chain::EthereumMainnet));
+        assert_eq!(Chain::from_input("sepolia"), Some(Chain::EthereumSepolia));
+        assert_eq!(Chain::from_input("8453"), Some(Chain::BaseMainnet));
         assert_eq!(Chain::from_input("unknown"), None);
     }
 
@@ -250,6 +650,7 @@ mod tests {
         assert!(Chain::PolygonMainnet.usdc_address().is_some());
         assert!(Chain::BaseMainnet.usdc_address().is_some());
         assert!(Chain::EthereumMainnet.usdc_address().is_some());
+        assert!(Chain::ArbitrumSepolia.default_token_address("USDC").is_some());
     }
 
     #[test]
@@ -257,4 +658,78 @@ mod tests {
         let provider = MultiChainProvider::new();
         assert!(provider.get(Chain::PolygonAmoy).is_some());
     }
+
+    #[tokio::test]
+    async fn test_open_circuit_fails_fast() {
+        let provider = MultiChainProvider::with_chains(&[Chain::PolygonMainnet]);
+        for _ in 0..crate::wallet::circuit::DEFAULT_FAILURE_THRESHOLD {
+            provider.breaker(Chain::PolygonMainnet).record_failure();
+        }
+
+        let result = provider
+            .call(Chain::PolygonMainnet, |_| async { Ok::<_, ProviderError>(()) })
+            .await;
+        let err = result.unwrap_err();
+        assert!(matches!(err, ChainError::Unavailable(Chain::PolygonMainnet)));
+        assert!(err.sms_message().starts_with("Polygon temporarily unavailable"));
+
+        // Other chains keep their own breaker
+        assert_eq!(provider.breaker(Chain::BaseMainnet).state(), CircuitState::Closed);
+    }
+
+    /// JSON-RPC stand-in: `/ok` answers balances, `/no-usdc` refuses
+    /// contract calls and `/slow` never answers in time
+    async fn mock_rpc() -> String {
+        use axum::{extract::Path, routing::post, Json, Router};
+        use serde_json::{json, Value};
+
+        async fn rpc(Path(mode): Path<String>, Json(request): Json<Value>) -> Json<Value> {
+            if mode == "slow" {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+            let id = request["id"].clone();
+            Json(match (request["method"].as_str(), mode.as_str()) {
+                // 1.5 native
+                (Some("eth_getBalance"), _) => json!({ "jsonrpc": "2.0", "id": id, "result": "0x14d1120d7b160000" }),
+                // 25.5 USDC
+                (Some("eth_call"), "ok") => json!({ "jsonrpc": "2.0", "id": id, "result": format!("0x{:064x}", 25_500_000) }),
+                _ => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": -32000, "message": "execution reverted" } }),
+            })
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, Router::new().route("/:mode", post(rpc))).await });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_balances_everywhere() {
+        let url = mock_rpc().await;
+        let at = |mode: &str| Arc::new(Provider::<Http>::try_from(format!("{}/{}", url, mode)).unwrap());
+        let mut provider = MultiChainProvider::from_providers(HashMap::from([
+            (Chain::PolygonAmoy, at("ok")),
+            (Chain::EthereumSepolia, at("no-usdc")),
+            (Chain::BaseSepolia, at("slow")),
+            (Chain::ArbitrumSepolia, at("ok")),
+        ]));
+        provider.call_timeout = Duration::from_millis(300);
+        for _ in 0..crate::wallet::circuit::DEFAULT_FAILURE_THRESHOLD {
+            provider.breaker(Chain::ArbitrumSepolia).record_failure();
+        }
+
+        // Chains without a provider are left out; registered ones are always in
+        let results = provider.balances_everywhere(Address::repeat_byte(0x11)).await;
+        let results: HashMap<Chain, _> = results.into_iter().filter(|(chain, _)| chain.is_builtin()).collect();
+        assert_eq!(results.len(), 4);
+
+        let amoy = results[&Chain::PolygonAmoy].as_ref().unwrap();
+        assert_eq!(amoy.native.formatted(), "1.500000");
+        assert_eq!(amoy.usdc.as_ref().map(|usdc| usdc.formatted()).as_deref(), Some("25.500000"));
+        // A failed USDC read only drops USDC
+        let sepolia = results[&Chain::EthereumSepolia].as_ref().unwrap();
+        assert_eq!((sepolia.native.formatted().as_str(), sepolia.usdc.is_none()), ("1.500000", true));
+        assert!(matches!(results[&Chain::BaseSepolia], Err(ChainError::Timeout(Chain::BaseSepolia))));
+        assert!(matches!(results[&Chain::ArbitrumSepolia], Err(ChainError::Unavailable(Chain::ArbitrumSepolia))));
+    }
 }
@@ -0,0 +1,181 @@
+//! Per-chain circuit breaker
+//!
+//! After `failure_threshold` consecutive failures the circuit opens and calls
+//! fail immediately for `cooldown`. Once the cooldown has passed a single
+//! probe is let through (half-open): success closes the circuit, failure
+//! re-opens it for another cooldown.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Consecutive failures before a chain is marked unavailable
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// How long an open circuit rejects calls before probing again
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Upper bound on a single RPC call made through the breaker
+pub const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// Breaker state as reported to callers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: CircuitState,
+    failures: u32,
+    opened_at: Option<Instant>,
+    /// A half-open probe is in flight; other calls keep failing fast
+    probing: bool,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            inner: Mutex::new(Inner {
+                state: CircuitState::Closed,
+                failures: 0,
+                opened_at: None,
+                probing: false,
+            }),
+        }
+    }
+
+    /// Current state, moving Open to HalfOpen once the cooldown has passed
+    pub fn state(&self) -> CircuitState {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        self.refresh(&mut inner, Instant::now());
+        inner.state
+    }
+
+    /// Whether a call may go ahead now. In half-open state only the first
+    /// caller is admitted as the probe.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: Instant) -> bool {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        self.refresh(&mut inner, now);
+
+        match inner.state {
+            CircuitState::Closed => true,
+            CircuitState::Open => false,
+            CircuitState::HalfOpen if inner.probing => false,
+            CircuitState::HalfOpen => {
+                inner.probing = true;
+                true
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.state = CircuitState::Closed;
+        inner.failures = 0;
+        inner.opened_at = None;
+        inner.probing = false;
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.failures += 1;
+        inner.probing = false;
+
+        if inner.state == CircuitState::HalfOpen || inner.failures >= self.failure_threshold {
+            inner.state = CircuitState::Open;
+            inner.opened_at = Some(now);
+        }
+    }
+
+    fn refresh(&self, inner: &mut Inner, now: Instant) {
+        if inner.state != CircuitState::Open {
+            return;
+        }
+        let cooled = inner
+            .opened_at
+            .map(|opened| now.duration_since(opened) >= self.cooldown)
+            .unwrap_or(true);
+        if cooled {
+            inner.state = CircuitState::HalfOpen;
+            inner.probing = false;
+        }
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(30));
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.try_acquire());
+
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_admits_single_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let start = Instant::now();
+
+        breaker.record_failure_at(start);
+        assert!(!breaker.try_acquire_at(start + Duration::from_secs(10)));
+
+        // Cooldown passed: one probe goes through, concurrent calls still fail fast
+        let later = start + Duration::from_secs(31);
+        assert!(breaker.try_acquire_at(later));
+        assert!(!breaker.try_acquire_at(later));
+
+        // Failed probe re-opens the circuit
+        breaker.record_failure_at(later);
+        assert!(!breaker.try_acquire_at(later + Duration::from_secs(1)));
+
+        // Successful probe closes it
+        let probe = later + Duration::from_secs(31);
+        assert!(breaker.try_acquire_at(probe));
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire());
+    }
+}
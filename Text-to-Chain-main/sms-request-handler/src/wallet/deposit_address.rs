@@ -0,0 +1,102 @@
+//! Per-user deposit addresses derived from one HD seed
+//!
+//! Each user gets the address at `m/44'/60'/0'/0/<index>` of the
+//! `DEPOSIT_MNEMONIC` seed, with indices handed out in order. Funds sent
+//! there are credited like any other on-chain deposit and later swept into
+//! the treasury, so user wallets never need watching. The seed also signs
+//! the sweeps; keep it as guarded as the treasury key.
+
+use ethers::signers::coins_bip39::English;
+use ethers::signers::{LocalWallet, MnemonicBuilder, Signer};
+use ethers::types::{Address, PathOrString};
+
+use crate::db::{DepositAddress, DepositAddressRepository};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DepositAddressError {
+    #[error("Invalid DEPOSIT_MNEMONIC: {0}")]
+    Seed(String),
+    #[error("Invalid derivation index {0}")]
+    Index(i64),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Derives deposit addresses and their signing keys
+#[derive(Clone)]
+pub struct DepositKeys {
+    phrase: String,
+}
+
+impl DepositKeys {
+    pub fn from_phrase(phrase: &str) -> Result<Self, DepositAddressError> {
+        let keys = Self { phrase: phrase.trim().to_string() };
+        keys.signer(0)?;
+        Ok(keys)
+    }
+
+    /// Signing key of the address at `index`
+    pub fn signer(&self, index: i64) -> Result<LocalWallet, DepositAddressError> {
+        let index = u32::try_from(index).map_err(|_| DepositAddressError::Index(index))?;
+        MnemonicBuilder::<English>::default()
+            // Always the phrase itself, never a file that happens to share its name
+            .phrase(PathOrString::String(self.phrase.clone()))
+            .index(index)
+            .and_then(|builder| builder.build())
+            .map_err(|e| DepositAddressError::Seed(e.to_string()))
+    }
+
+    pub fn address(&self, index: i64) -> Result<Address, DepositAddressError> {
+        Ok(self.signer(index)?.address())
+    }
+}
+
+/// Hands out and looks up users' deposit addresses
+#[derive(Clone)]
+pub struct DepositAddresses {
+    keys: DepositKeys,
+    repo: DepositAddressRepository,
+}
+
+impl DepositAddresses {
+    pub fn new(keys: DepositKeys, repo: DepositAddressRepository) -> Self {
+        Self { keys, repo }
+    }
+
+    pub fn keys(&self) -> &DepositKeys {
+        &self.keys
+    }
+
+    pub fn repo(&self) -> &DepositAddressRepository {
+        &self.repo
+    }
+
+    /// The user's deposit address, deriving the next one on first use
+    pub async fn for_user(&self, phone: &str) -> Result<DepositAddress, DepositAddressError> {
+        if let Some(existing) = self.repo.find_by_user(phone).await? {
+            return Ok(existing);
+        }
+        let index = self.repo.next_index().await?;
+        let address = format!("{:?}", self.keys.address(index)?);
+        let assigned = self.repo.assign(phone, index, &address).await?;
+        tracing::info!(index = assigned.derivation_index, "Deposit address assigned");
+        Ok(assigned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn test_derives_standard_path() {
+        let keys = DepositKeys::from_phrase(TEST_MNEMONIC).unwrap();
+        let expected: Address = "0x70997970C51812dc3A010C7d01b50e0d17dc79C8".parse().unwrap();
+        assert_eq!(keys.address(1).unwrap(), expected);
+        assert_ne!(keys.address(2).unwrap(), expected);
+        assert!(keys.address(-1).is_err());
+        assert!(DepositKeys::from_phrase("not a mnemonic").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Testnet faucet for new wallets
+//!
+//! In test deployments, new wallets are funded with a little native gas
+//! token so end-to-end demos work without manual top-ups. Each configured
+//! testnet either calls an external faucet API or, without one, receives a
+//! transfer from a treasury faucet wallet. Mainnets are never funded.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::Middleware;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, TransactionRequest, U256};
+use ethers::utils::parse_ether;
+use tokio::sync::Mutex;
+
+use super::chains::Chain;
+use super::provider::create_chain_provider;
+use crate::config::FaucetConfig;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FaucetError {
+    #[error("Invalid faucet config: {0}")]
+    Config(String),
+    #[error("Faucet request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Faucet refused: {0}")]
+    Refused(String),
+    #[error("Drip failed: {0}")]
+    Transaction(String),
+}
+
+/// How a chain is funded
+#[derive(Debug, Clone)]
+enum Source {
+    /// External faucet API: POST `{"address", "chain_id"}`
+    Api(String),
+    /// Transfer from the faucet wallet
+    Wallet,
+}
+
+/// Funds new wallets on configured testnets
+pub struct Faucet {
+    chains: Vec<(Chain, Source)>,
+    drip: U256,
+    wallet: Option<LocalWallet>,
+    http: reqwest::Client,
+    /// One drip at a time so faucet wallet nonces don't collide
+    lock: Mutex<()>,
+}
+
+impl Faucet {
+    /// None when `FAUCET_CHAINS` is empty
+    pub fn from_config(config: &FaucetConfig) -> Result<Option<Self>, FaucetError> {
+        if config.chains.trim().is_empty() {
+            return Ok(None);
+        }
+
+        let mut urls = HashMap::new();
+        for entry in config.urls.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (chain, url) = entry
+                .split_once('=')
+                .and_then(|(chain, url)| Some((Chain::from_input(chain.trim())?, url.trim().to_string())))
+                .ok_or_else(|| FaucetError::Config(format!("FAUCET_URLS entry {}", entry)))?;
+            urls.insert(chain, url);
+        }
+
+        let wallet = match config.private_key.trim() {
+            "" => None,
+            key => Some(
+                key.trim_start_matches("0x")
+                    .parse::<LocalWallet>()
+                    .map_err(|_| FaucetError::Config("FAUCET_PRIVATE_KEY".to_string()))?,
+            ),
+        };
+
+        let mut chains = Vec::new();
+        for name in config.chains.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let chain = Chain::from_input(name).ok_or_else(|| FaucetError::Config(format!("unknown chain {}", name)))?;
+            if !chain.is_testnet() {
+                return Err(FaucetError::Config(format!("{} is not a testnet", chain)));
+            }
+            let source = match urls.remove(&chain) {
+                Some(url) => Source::Api(url),
+                None if wallet.is_some() => Source::Wallet,
+                None => return Err(FaucetError::Config(format!("no faucet URL or FAUCET_PRIVATE_KEY for {}", chain))),
+            };
+            chains.push((chain, source));
+        }
+
+        let drip = parse_ether(config.drip_amount).map_err(|_| FaucetError::Config("FAUCET_DRIP_AMOUNT".to_string()))?;
+
+        Ok(Some(Self {
+            chains,
+            drip,
+            wallet,
+            http: reqwest::Client::new(),
+            lock: Mutex::new(()),
+        }))
+    }
+
+    pub fn chains(&self) -> Vec<Chain> {
+        self.chains.iter().map(|(chain, _)| *chain).collect()
+    }
+
+    /// Fund `address` in the background; failures are only logged
+    pub fn spawn_fund(self: &Arc<Self>, address: Address) {
+        let faucet = self.clone();
+        tokio::spawn(async move {
+            for (chain, source) in &faucet.chains {
+                match faucet.fund(*chain, source, address).await {
+                    Ok(Some(reference)) => tracing::info!(%chain, ?address, %reference, "Faucet funded new wallet"),
+                    Ok(None) => tracing::debug!(%chain, ?address, "Wallet already has gas, faucet skipped"),
+                    Err(e) => tracing::warn!(%chain, ?address, "Faucet funding failed: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Fund one chain; None if the wallet already holds a drip's worth
+    async fn fund(&self, chain: Chain, source: &Source, address: Address) -> Result<Option<String>, FaucetError> {
+        let provider = create_chain_provider(chain);
+        let balance = provider
+            .get_balance(address, None)
+            .await
+            .map_err(|e| FaucetError::Transaction(e.to_string()))?;
+        if balance >= self.drip {
+            return Ok(None);
+        }
+
+        match source {
+            Source::Api(url) => {
+                let body = serde_json::json!({ "address": format!("{:?}", address), "chain_id": chain.chain_id() });
+                let response = self.http.post(url).json(&body).send().await?;
+                if !response.status().is_success() {
+                    return Err(FaucetError::Refused(response.text().await.unwrap_or_default()));
+                }
+                Ok(Some(url.clone()))
+            }
+            Source::Wallet => {
+                let Some(ref wallet) = self.wallet else {
+                    return Err(FaucetError::Config("no faucet wallet".to_string()));
+                };
+                let _guard = self.lock.lock().await;
+                let client = SignerMiddleware::new(provider, wallet.clone().with_chain_id(chain.chain_id()));
+                let tx = TransactionRequest::new().to(address).value(self.drip - balance);
+                let pending = client
+                    .send_transaction(tx, None)
+                    .await
+                    .map_err(|e| FaucetError::Transaction(e.to_string()))?;
+                Ok(Some(format!("{:?}", pending.tx_hash())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(chains: &str, urls: &str, key: &str) -> FaucetConfig {
+        FaucetConfig {
+            chains: chains.to_string(),
+            drip_amount: 0.01,
+            private_key: key.into(),
+            urls: urls.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_faucet_config() {
+        assert!(Faucet::from_config(&config("", "", "")).unwrap().is_none());
+
+        let faucet = Faucet::from_config(&config("sepolia, amoy", "amoy=https://faucet.example/claim", &"11".repeat(32)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(faucet.chains(), vec![Chain::EthereumSepolia, Chain::PolygonAmoy]);
+        assert!(matches!(faucet.chains[1].1, Source::Api(_)));
+
+        // Mainnets are refused, and every chain needs a source
+        assert!(Faucet::from_config(&config("eth", "", &"11".repeat(32))).is_err());
+        assert!(Faucet::from_config(&config("sepolia", "", "")).is_err());
+    }
+}
@@ -1,12 +1,28 @@
 pub mod aa;
+pub mod address;
+pub mod allowance;
+pub mod chain_registry;
 pub mod chains;
+pub mod circuit;
+pub mod deposit_address;
+pub mod ens_cache;
+pub mod faucet;
+pub mod payment_uri;
 pub mod provider;
+pub mod safe;
+pub mod savings;
+pub mod token_registry;
 pub mod tokens;
+pub mod vault;
 pub mod wallet;
+pub mod walletconnect;
 
 pub use aa::*;
 pub use chains::*;
+pub use deposit_address::{DepositAddresses, DepositKeys};
 pub use provider::*;
+pub use safe::{SafeClient, SafeTransaction};
 pub use tokens::*;
+pub use vault::KeyVault;
 pub use wallet::*;
 
@@ -0,0 +1,106 @@
+//! EIP-681 payment request URIs
+//!
+//! `ethereum:<token>@<chain>/transfer?address=<to>&uint256=<amount>` for
+//! ERC-20 tokens and `ethereum:<to>@<chain>?value=<wei>` for the native
+//! token. Wallets like MetaMask open these with the payment pre-filled.
+
+use ethers::types::{Address, U256};
+use ethers::utils::parse_units;
+
+use super::address::checksummed;
+use super::chains::Chain;
+use super::token_registry::token_address;
+
+/// TXTC token contract (Ethereum Sepolia), unless overridden in the token registry
+pub const TXTC_ADDRESS: &str = "0x4d054FB258A260982F0bFab9560340d33D9E698B";
+
+/// TXTC uses the OpenZeppelin ERC20 default
+pub const TXTC_DECIMALS: u32 = 18;
+
+/// Chain TXTC and user wallets live on
+pub const TXTC_CHAIN: Chain = Chain::EthereumSepolia;
+
+/// What is being requested
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PaymentAsset {
+    /// Native token (ETH), amount in ether
+    Native,
+    /// ERC-20 token at `address` with `decimals`
+    Token { address: Address, decimals: u32 },
+}
+
+impl PaymentAsset {
+    /// Asset for an SMS token symbol (TXTC or ETH)
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol.to_uppercase().as_str() {
+            "TXTC" => Some(PaymentAsset::Token {
+                address: token_address(TXTC_CHAIN, "TXTC")?,
+                decimals: TXTC_DECIMALS,
+            }),
+            "ETH" => Some(PaymentAsset::Native),
+            _ => None,
+        }
+    }
+
+    fn decimals(&self) -> u32 {
+        match self {
+            PaymentAsset::Native => 18,
+            PaymentAsset::Token { decimals, .. } => *decimals,
+        }
+    }
+}
+
+/// Build an EIP-681 URI asking for `amount` of `asset` to `recipient`
+pub fn payment_uri(chain: Chain, recipient: Address, asset: PaymentAsset, amount: f64) -> Result<String, String> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err("Amount must be positive".to_string());
+    }
+    let units: U256 = parse_units(amount.to_string(), asset.decimals())
+        .map_err(|e| e.to_string())?
+        .into();
+
+    Ok(match asset {
+        PaymentAsset::Native => format!(
+            "ethereum:{}@{}?value={}",
+            checksummed(&recipient),
+            chain.chain_id(),
+            units
+        ),
+        PaymentAsset::Token { address, .. } => format!(
+            "ethereum:{}@{}/transfer?address={}&uint256={}",
+            checksummed(&address),
+            chain.chain_id(),
+            checksummed(&recipient),
+            units
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECIPIENT: &str = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+
+    #[test]
+    fn test_token_and_native_uris() {
+        let recipient = RECIPIENT.parse().unwrap();
+
+        let txtc = PaymentAsset::from_symbol("txtc").unwrap();
+        assert_eq!(
+            payment_uri(TXTC_CHAIN, recipient, txtc, 10.5).unwrap(),
+            format!(
+                "ethereum:{}@11155111/transfer?address={}&uint256=10500000000000000000",
+                TXTC_ADDRESS, RECIPIENT
+            )
+        );
+
+        assert_eq!(
+            payment_uri(Chain::BaseMainnet, recipient, PaymentAsset::Native, 0.01).unwrap(),
+            format!("ethereum:{}@8453?value=10000000000000000", RECIPIENT)
+        );
+
+        assert!(payment_uri(TXTC_CHAIN, recipient, PaymentAsset::Native, 0.0).is_err());
+        assert!(PaymentAsset::from_symbol("DOGE").is_none());
+    }
+}
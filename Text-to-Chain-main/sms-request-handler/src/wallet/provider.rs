@@ -1,7 +1,7 @@
 use ethers::providers::{Http, Middleware, Provider};
 use std::sync::Arc;
 
-use super::chains::{Chain, MultiChainProvider};
+use super::chains::{rpc_override, Chain, MultiChainProvider};
 
 /// Polygon Amoy testnet chain ID (deprecated, use Chain::PolygonAmoy.chain_id())
 pub const POLYGON_AMOY_CHAIN_ID: u64 = 80002;
@@ -27,9 +27,11 @@ pub fn create_multi_chain_provider() -> MultiChainProvider {
     MultiChainProvider::new()
 }
 
-/// Create a provider for a specific chain
+/// Create a provider for a specific chain (its RPC override, if any)
 pub fn create_chain_provider(chain: Chain) -> Arc<Provider<Http>> {
-    Arc::new(Provider::<Http>::try_from(chain.rpc_url()).expect("Invalid RPC URL"))
+    rpc_override(chain).unwrap_or_else(|| {
+        Arc::new(Provider::<Http>::try_from(chain.rpc_url().as_str()).expect("Invalid RPC URL"))
+    })
 }
 
 #[cfg(test)]
@@ -0,0 +1,381 @@
+use ethers::prelude::*;
+use ethers::types::{Address, Bytes, H256, U256};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+
+use super::chains::{Chain, ChainProvider};
+use super::tokens::IERC20;
+
+// Gnosis Safe (v1.3.0+) contract ABI - only the calls we need
+abigen!(
+    GnosisSafe,
+    r#"[
+        function nonce() external view returns (uint256)
+        function getThreshold() external view returns (uint256)
+        function getOwners() external view returns (address[])
+        function getTransactionHash(address to, uint256 value, bytes data, uint8 operation, uint256 safeTxGas, uint256 baseGas, uint256 gasPrice, address gasToken, address refundReceiver, uint256 _nonce) external view returns (bytes32)
+        function execTransaction(address to, uint256 value, bytes data, uint8 operation, uint256 safeTxGas, uint256 baseGas, uint256 gasPrice, address gasToken, address refundReceiver, bytes signatures) external payable returns (bool success)
+    ]"#
+);
+
+#[derive(Error, Debug)]
+pub enum SafeError {
+    #[error("Invalid signer key: {0}")]
+    InvalidKey(String),
+    #[error("Contract call failed: {0}")]
+    Contract(String),
+    #[error("Transaction service error: {0}")]
+    Service(String),
+    #[error("Signer {0:?} is not an owner of the Safe")]
+    NotOwner(Address),
+    #[error("Not enough confirmations: {have}/{need}")]
+    ThresholdNotReached { have: usize, need: usize },
+    #[error("Transaction already executed")]
+    AlreadyExecuted,
+}
+
+/// A Safe multisig transaction (call only, no gas refunds)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafeTransaction {
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub nonce: U256,
+}
+
+impl SafeTransaction {
+    /// Native token (ETH/MATIC) transfer out of the Safe
+    pub fn native_transfer(to: Address, value: U256, nonce: U256) -> Self {
+        Self {
+            to,
+            value,
+            data: Bytes::default(),
+            nonce,
+        }
+    }
+
+    /// ERC20 `transfer(to, amount)` executed by the Safe
+    pub fn erc20_transfer(token: Address, to: Address, amount: U256, nonce: U256, provider: Arc<ChainProvider>) -> Self {
+        let data = IERC20::new(token, provider)
+            .transfer(to, amount)
+            .calldata()
+            .unwrap_or_default();
+
+        Self {
+            to: token,
+            value: U256::zero(),
+            data,
+            nonce,
+        }
+    }
+}
+
+/// Proposal payload for the Safe Transaction Service
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ProposeRequest {
+    to: String,
+    value: String,
+    data: Option<String>,
+    operation: u8,
+    safe_tx_gas: String,
+    base_gas: String,
+    gas_price: String,
+    gas_token: String,
+    refund_receiver: String,
+    nonce: String,
+    contract_transaction_hash: String,
+    sender: String,
+    signature: String,
+    origin: String,
+}
+
+/// Confirmation as returned by the Safe Transaction Service
+#[derive(Debug, Clone, Deserialize)]
+pub struct SafeConfirmation {
+    pub owner: Address,
+    pub signature: Bytes,
+}
+
+/// Multisig transaction status as returned by the Safe Transaction Service
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeTransactionStatus {
+    pub safe_tx_hash: H256,
+    pub to: Address,
+    pub value: String,
+    pub data: Option<Bytes>,
+    pub nonce: u64,
+    #[serde(default)]
+    pub confirmations: Vec<SafeConfirmation>,
+    pub confirmations_required: Option<usize>,
+    #[serde(default)]
+    pub is_executed: bool,
+    pub transaction_hash: Option<H256>,
+}
+
+/// Concatenate owner signatures in ascending owner order, as `execTransaction` expects
+pub fn pack_signatures(mut confirmations: Vec<SafeConfirmation>) -> Bytes {
+    confirmations.sort_by_key(|c| c.owner);
+    confirmations.dedup_by_key(|c| c.owner);
+
+    let mut packed = Vec::with_capacity(confirmations.len() * 65);
+    for confirmation in confirmations {
+        packed.extend_from_slice(&confirmation.signature);
+    }
+    Bytes::from(packed)
+}
+
+type SafeSigner = SignerMiddleware<Arc<ChainProvider>, LocalWallet>;
+
+/// Treasury Safe client
+///
+/// Proposes transactions to the Safe Transaction Service signed by one owner
+/// (the backend key) and executes them on-chain once the owner threshold is met.
+#[derive(Clone)]
+pub struct SafeClient {
+    http: reqwest::Client,
+    service_url: String,
+    safe_address: Address,
+    chain: Chain,
+    provider: Arc<ChainProvider>,
+    safe: GnosisSafe<SafeSigner>,
+    signer: LocalWallet,
+}
+
+impl SafeClient {
+    pub fn new(
+        service_url: &str,
+        safe_address: Address,
+        chain: Chain,
+        provider: Arc<ChainProvider>,
+        owner_private_key: &str,
+    ) -> Result<Self, SafeError> {
+        let signer: LocalWallet = owner_private_key
+            .trim_start_matches("0x")
+            .parse::<LocalWallet>()
+            .map_err(|e| SafeError::InvalidKey(e.to_string()))?
+            .with_chain_id(chain.chain_id());
+
+        let client = Arc::new(SignerMiddleware::new(provider.clone(), signer.clone()));
+        let safe = GnosisSafe::new(safe_address, client);
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            service_url: service_url.trim_end_matches('/').to_string(),
+            safe_address,
+            chain,
+            provider,
+            safe,
+            signer,
+        })
+    }
+
+    pub fn safe_address(&self) -> Address {
+        self.safe_address
+    }
+
+    pub fn chain(&self) -> Chain {
+        self.chain
+    }
+
+    pub fn provider(&self) -> Arc<ChainProvider> {
+        self.provider.clone()
+    }
+
+    /// Current on-chain Safe nonce
+    pub async fn nonce(&self) -> Result<U256, SafeError> {
+        self.safe.nonce().call().await.map_err(|e| SafeError::Contract(e.to_string()))
+    }
+
+    /// Number of owner signatures required to execute
+    pub async fn threshold(&self) -> Result<usize, SafeError> {
+        let threshold = self.safe
+            .get_threshold()
+            .call()
+            .await
+            .map_err(|e| SafeError::Contract(e.to_string()))?;
+        Ok(threshold.as_usize())
+    }
+
+    /// Hash that owners sign (EIP-712 SafeTx hash, computed by the Safe itself)
+    pub async fn transaction_hash(&self, tx: &SafeTransaction) -> Result<H256, SafeError> {
+        let hash = self.safe
+            .get_transaction_hash(
+                tx.to,
+                tx.value,
+                tx.data.clone(),
+                0,
+                U256::zero(),
+                U256::zero(),
+                U256::zero(),
+                Address::zero(),
+                Address::zero(),
+                tx.nonce,
+            )
+            .call()
+            .await
+            .map_err(|e| SafeError::Contract(e.to_string()))?;
+        Ok(H256::from(hash))
+    }
+
+    fn sign(&self, safe_tx_hash: H256) -> Result<Bytes, SafeError> {
+        let signature = self.signer
+            .sign_hash(safe_tx_hash)
+            .map_err(|e| SafeError::InvalidKey(e.to_string()))?;
+        Ok(Bytes::from(signature.to_vec()))
+    }
+
+    /// Propose a transaction to the Safe Transaction Service, signed by our owner key
+    pub async fn propose(&self, tx: &SafeTransaction, origin: &str) -> Result<H256, SafeError> {
+        let owners = self.safe
+            .get_owners()
+            .call()
+            .await
+            .map_err(|e| SafeError::Contract(e.to_string()))?;
+        if !owners.contains(&self.signer.address()) {
+            return Err(SafeError::NotOwner(self.signer.address()));
+        }
+
+        let safe_tx_hash = self.transaction_hash(tx).await?;
+        let signature = self.sign(safe_tx_hash)?;
+
+        let payload = ProposeRequest {
+            to: ethers::utils::to_checksum(&tx.to, None),
+            value: tx.value.to_string(),
+            data: if tx.data.is_empty() { None } else { Some(format!("{}", tx.data)) },
+            operation: 0,
+            safe_tx_gas: "0".to_string(),
+            base_gas: "0".to_string(),
+            gas_price: "0".to_string(),
+            gas_token: format!("{:?}", Address::zero()),
+            refund_receiver: format!("{:?}", Address::zero()),
+            nonce: tx.nonce.to_string(),
+            contract_transaction_hash: format!("{:?}", safe_tx_hash),
+            sender: ethers::utils::to_checksum(&self.signer.address(), None),
+            signature: format!("{}", signature),
+            origin: origin.to_string(),
+        };
+
+        let url = format!(
+            "{}/api/v1/safes/{}/multisig-transactions/",
+            self.service_url,
+            ethers::utils::to_checksum(&self.safe_address, None)
+        );
+
+        let response = self.http
+            .post(&url)
+            .json(&payload)
+            .send()
+            .await
+            .map_err(|e| SafeError::Service(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(SafeError::Service(error_text));
+        }
+
+        tracing::info!(safe_tx_hash = ?safe_tx_hash, to = ?tx.to, value = %tx.value, "Proposed Safe transaction");
+        Ok(safe_tx_hash)
+    }
+
+    /// Fetch a proposed transaction and its confirmations
+    pub async fn status(&self, safe_tx_hash: H256) -> Result<SafeTransactionStatus, SafeError> {
+        let url = format!("{}/api/v1/multisig-transactions/{:?}/", self.service_url, safe_tx_hash);
+
+        let response = self.http
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| SafeError::Service(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(SafeError::Service(error_text));
+        }
+
+        response.json().await.map_err(|e| SafeError::Service(e.to_string()))
+    }
+
+    /// Execute a proposed transaction once enough owners have confirmed it
+    pub async fn execute_if_ready(&self, safe_tx_hash: H256) -> Result<H256, SafeError> {
+        let status = self.status(safe_tx_hash).await?;
+        if status.is_executed {
+            return Err(SafeError::AlreadyExecuted);
+        }
+
+        let need = match status.confirmations_required {
+            Some(n) => n,
+            None => self.threshold().await?,
+        };
+        let have = status.confirmations.len();
+        if have < need {
+            return Err(SafeError::ThresholdNotReached { have, need });
+        }
+
+        let value = U256::from_dec_str(&status.value).map_err(|e| SafeError::Service(e.to_string()))?;
+        let signatures = pack_signatures(status.confirmations);
+
+        let call = self.safe.exec_transaction(
+            status.to,
+            value,
+            status.data.unwrap_or_default(),
+            0,
+            U256::zero(),
+            U256::zero(),
+            U256::zero(),
+            Address::zero(),
+            Address::zero(),
+            signatures,
+        );
+
+        let pending = call.send().await.map_err(|e| SafeError::Contract(e.to_string()))?;
+        let tx_hash = pending.tx_hash();
+
+        tracing::info!(safe_tx_hash = ?safe_tx_hash, tx_hash = ?tx_hash, "Executed Safe transaction");
+        Ok(tx_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_pack_signatures_sorted_by_owner() {
+        let high = Address::from_str("0x2222222222222222222222222222222222222222").unwrap();
+        let low = Address::from_str("0x1111111111111111111111111111111111111111").unwrap();
+
+        let packed = pack_signatures(vec![
+            SafeConfirmation { owner: high, signature: Bytes::from(vec![0xbb; 65]) },
+            SafeConfirmation { owner: low, signature: Bytes::from(vec![0xaa; 65]) },
+            SafeConfirmation { owner: low, signature: Bytes::from(vec![0xaa; 65]) },
+        ]);
+
+        assert_eq!(packed.len(), 130);
+        assert_eq!(packed[0], 0xaa);
+        assert_eq!(packed[65], 0xbb);
+    }
+
+    #[test]
+    fn test_parse_service_status() {
+        let json = serde_json::json!({
+            "safeTxHash": "0x1111111111111111111111111111111111111111111111111111111111111111",
+            "to": "0x2222222222222222222222222222222222222222",
+            "value": "1000",
+            "data": null,
+            "nonce": 3,
+            "confirmations": [],
+            "confirmationsRequired": 2,
+            "isExecuted": false,
+            "transactionHash": null
+        });
+
+        let status: SafeTransactionStatus = serde_json::from_value(json).unwrap();
+        assert_eq!(status.nonce, 3);
+        assert_eq!(status.confirmations_required, Some(2));
+        assert!(!status.is_executed);
+    }
+}
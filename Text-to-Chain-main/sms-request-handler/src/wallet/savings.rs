@@ -0,0 +1,177 @@
+//! USDC savings in an ERC-4626 vault
+//!
+//! SAVE deposits USDC from the user's EOA into one configured vault (e.g. an
+//! Aave wrapper) on the TXTC chain and UNSAVE withdraws it. The vault's share
+//! price only goes up as it earns, so yield is the shares' current value
+//! minus what was put in. USDC has 6 decimals, so amounts here are micro-USDC.
+
+use std::sync::Arc;
+
+use ethers::contract::abigen;
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, TxHash, U256};
+
+use super::chains::{Chain, ChainProvider};
+use super::tokens::IERC20;
+
+abigen!(
+    IERC4626,
+    r#"[
+        function asset() external view returns (address)
+        function balanceOf(address account) external view returns (uint256)
+        function convertToAssets(uint256 shares) external view returns (uint256)
+        function deposit(uint256 assets, address receiver) external returns (uint256)
+        function withdraw(uint256 assets, address receiver, address owner) external returns (uint256)
+        function redeem(uint256 shares, address receiver, address owner) external returns (uint256)
+    ]"#
+);
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum SavingsError {
+    #[error("Invalid amount")]
+    InvalidAmount,
+    #[error("Not enough USDC")]
+    InsufficientFunds,
+    #[error("Not enough ETH for gas")]
+    NoGas,
+    #[error("Vault does not hold USDC")]
+    WrongAsset,
+    #[error("Transaction failed: {0}")]
+    Transaction(String),
+}
+
+/// A mined SAVE or UNSAVE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VaultReceipt {
+    pub tx_hash: TxHash,
+    /// Shares minted by SAVE or burned by UNSAVE
+    pub shares: U256,
+    /// USDC moved, in micro-USDC
+    pub assets: i64,
+}
+
+type SignedClient = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+/// The configured vault on one chain
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SavingsVault {
+    pub chain: Chain,
+    pub address: Address,
+}
+
+impl SavingsVault {
+    fn signed(&self, provider: &Arc<ChainProvider>, private_key: &[u8; 32]) -> Result<(Arc<SignedClient>, Address), SavingsError> {
+        let wallet = LocalWallet::from_bytes(private_key)
+            .map_err(|e| SavingsError::Transaction(e.to_string()))?
+            .with_chain_id(self.chain.chain_id());
+        let owner = wallet.address();
+        Ok((Arc::new(SignerMiddleware::new(provider.as_ref().clone(), wallet)), owner))
+    }
+
+    /// Approve the vault for `assets` USDC, then deposit them for the owner
+    pub async fn deposit(&self, provider: Arc<ChainProvider>, private_key: &[u8; 32], assets: i64) -> Result<VaultReceipt, SavingsError> {
+        if assets <= 0 {
+            return Err(SavingsError::InvalidAmount);
+        }
+        let (client, owner) = self.signed(&provider, private_key)?;
+        let vault = IERC4626::new(self.address, client.clone());
+        let usdc = self.chain.usdc_address().ok_or(SavingsError::WrongAsset)?;
+        if vault.asset().call().await.map_err(tx_error)? != usdc {
+            return Err(SavingsError::WrongAsset);
+        }
+
+        let amount = U256::from(assets);
+        let token = IERC20::new(usdc, client);
+        if token.balance_of(owner).call().await.map_err(tx_error)? < amount {
+            return Err(SavingsError::InsufficientFunds);
+        }
+        // The deposit pulls the USDC, so the approval has to be mined first
+        send_and_wait(token.approve(self.address, amount)).await?;
+
+        let before = vault.balance_of(owner).call().await.map_err(tx_error)?;
+        let tx_hash = send_and_wait(vault.deposit(amount, owner)).await?;
+        let after = vault.balance_of(owner).call().await.map_err(tx_error)?;
+        Ok(VaultReceipt { tx_hash, shares: after.saturating_sub(before), assets })
+    }
+
+    /// Withdraw `assets` USDC back to the owner's wallet; None redeems every share
+    pub async fn withdraw(&self, provider: Arc<ChainProvider>, private_key: &[u8; 32], assets: Option<i64>) -> Result<VaultReceipt, SavingsError> {
+        let (client, owner) = self.signed(&provider, private_key)?;
+        let vault = IERC4626::new(self.address, client.clone());
+        let usdc = IERC20::new(self.chain.usdc_address().ok_or(SavingsError::WrongAsset)?, client);
+
+        let shares_before = vault.balance_of(owner).call().await.map_err(tx_error)?;
+        let usdc_before = usdc.balance_of(owner).call().await.map_err(tx_error)?;
+        let tx_hash = match assets {
+            Some(assets) if assets <= 0 => return Err(SavingsError::InvalidAmount),
+            Some(assets) => {
+                let amount = U256::from(assets);
+                if vault.convert_to_assets(shares_before).call().await.map_err(tx_error)? < amount {
+                    return Err(SavingsError::InsufficientFunds);
+                }
+                send_and_wait(vault.withdraw(amount, owner, owner)).await?
+            }
+            None if shares_before.is_zero() => return Err(SavingsError::InsufficientFunds),
+            None => send_and_wait(vault.redeem(shares_before, owner, owner)).await?,
+        };
+        let shares_after = vault.balance_of(owner).call().await.map_err(tx_error)?;
+        let usdc_after = usdc.balance_of(owner).call().await.map_err(tx_error)?;
+
+        Ok(VaultReceipt {
+            tx_hash,
+            shares: shares_before.saturating_sub(shares_after),
+            assets: i64::try_from(usdc_after.saturating_sub(usdc_before).as_u128()).unwrap_or(i64::MAX),
+        })
+    }
+
+    /// Current value of `shares` in micro-USDC
+    pub async fn value_of(&self, provider: Arc<ChainProvider>, shares: U256) -> Result<i64, SavingsError> {
+        if shares.is_zero() {
+            return Ok(0);
+        }
+        let assets = IERC4626::new(self.address, provider)
+            .convert_to_assets(shares)
+            .call()
+            .await
+            .map_err(tx_error)?;
+        Ok(i64::try_from(assets.as_u128()).unwrap_or(i64::MAX))
+    }
+}
+
+/// Send a transaction and wait for it to be mined successfully
+async fn send_and_wait<D: ethers::abi::Detokenize>(
+    call: ethers::contract::ContractCall<SignedClient, D>,
+) -> Result<TxHash, SavingsError> {
+    let pending = match call.send().await {
+        Ok(pending) => pending,
+        Err(e) if e.to_string().contains("insufficient funds") => return Err(SavingsError::NoGas),
+        Err(e) => return Err(tx_error(e)),
+    };
+    let tx_hash = pending.tx_hash();
+    match pending.await.map_err(tx_error)? {
+        Some(receipt) if receipt.status == Some(1.into()) => Ok(tx_hash),
+        _ => Err(SavingsError::Transaction(format!("{:?} reverted", tx_hash))),
+    }
+}
+
+fn tx_error(e: impl std::fmt::Display) -> SavingsError {
+    SavingsError::Transaction(e.to_string())
+}
+
+/// Yield so far in micro-USDC; a vault loss shows as zero, not negative
+pub fn accrued_yield(value: i64, principal: i64) -> i64 {
+    (value - principal).max(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accrued_yield() {
+        assert_eq!(accrued_yield(10_050_000, 10_000_000), 50_000);
+        assert_eq!(accrued_yield(9_990_000, 10_000_000), 0);
+    }
+}
@@ -0,0 +1,245 @@
+//! Token contract addresses per chain
+//!
+//! Default addresses come from the chain registry (`ChainSpec::tokens`)
+//! and `TXTC_ADDRESS`. `TOKEN_ADDRESSES` points a chain at another deployment,
+//! such as an operator's own test token, and rows in `token_overrides`
+//! override both per chain and token. Overrides apply process-wide, so every
+//! `Chain::usdc_address` lookup sees them without a redeploy.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, LazyLock, RwLock};
+
+use ethers::types::Address;
+use serde::Serialize;
+
+use super::chains::Chain;
+use super::payment_uri::{TXTC_ADDRESS, TXTC_CHAIN};
+use crate::config::TokensConfig;
+use crate::db::TokenOverrideRepository;
+
+/// Tokens whose addresses can be overridden
+pub const KNOWN_TOKENS: [&str; 2] = ["USDC", "TXTC"];
+
+type TokenKey = (Chain, &'static str);
+
+/// Overrides in effect (config, then database on top)
+static ACTIVE: LazyLock<RwLock<HashMap<TokenKey, Address>>> = LazyLock::new(Default::default);
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("Unknown chain: {0}")]
+    UnknownChain(String),
+    #[error("Unknown token: {0}")]
+    UnknownToken(String),
+    #[error("Invalid address for {0}")]
+    InvalidAddress(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Contract address of `symbol` on `chain`, override first
+pub fn token_address(chain: Chain, symbol: &str) -> Option<Address> {
+    let symbol = known_token(symbol).ok()?;
+    let active = ACTIVE.read().ok().and_then(|a| a.get(&(chain, symbol)).copied());
+    active.or_else(|| default_token_address(chain, symbol))
+}
+
+/// Built-in address, before any override
+pub fn default_token_address(chain: Chain, symbol: &str) -> Option<Address> {
+    match symbol {
+        "TXTC" if chain == TXTC_CHAIN => Address::from_str(TXTC_ADDRESS).ok(),
+        _ => chain.default_token_address(symbol),
+    }
+}
+
+fn known_token(symbol: &str) -> Result<&'static str, TokenError> {
+    let symbol = symbol.trim().to_uppercase();
+    KNOWN_TOKENS
+        .into_iter()
+        .find(|known| *known == symbol)
+        .ok_or(TokenError::UnknownToken(symbol))
+}
+
+/// Parse one `<chain>`, `<token>`, `<address>` triple
+fn parse_entry(chain: &str, symbol: &str, address: &str) -> Result<(TokenKey, Address), TokenError> {
+    let chain = Chain::from_input(chain.trim()).ok_or_else(|| TokenError::UnknownChain(chain.to_string()))?;
+    let symbol = known_token(symbol)?;
+    let address = Address::from_str(address.trim()).map_err(|_| TokenError::InvalidAddress(format!("{} on {}", symbol, chain)))?;
+    Ok(((chain, symbol), address))
+}
+
+/// Parse `TOKEN_ADDRESSES`, e.g. `arb-sepolia:USDC=0x...,amoy:TXTC=0x...`
+fn parse_config(addresses: &str) -> Result<HashMap<TokenKey, Address>, TokenError> {
+    addresses
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (chain, rest) = entry.split_once(':').ok_or_else(|| TokenError::UnknownChain(entry.to_string()))?;
+            let (symbol, address) = rest.split_once('=').ok_or_else(|| TokenError::InvalidAddress(entry.to_string()))?;
+            parse_entry(chain, symbol, address)
+        })
+        .collect()
+}
+
+/// Where a token's address comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenSource {
+    Default,
+    Config,
+    Override,
+}
+
+/// Current address of one token on one chain
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenState {
+    pub chain: &'static str,
+    pub symbol: &'static str,
+    pub address: Option<String>,
+    pub source: TokenSource,
+}
+
+/// Shared token registry: config overrides plus database overrides
+#[derive(Clone, Default)]
+pub struct TokenRegistry {
+    config: HashMap<TokenKey, Address>,
+    overrides: Arc<RwLock<HashMap<TokenKey, Address>>>,
+    repo: Option<TokenOverrideRepository>,
+}
+
+impl TokenRegistry {
+    /// Registry from config only (no database)
+    pub fn from_config(config: &TokensConfig) -> Result<Self, TokenError> {
+        let registry = Self {
+            config: parse_config(&config.addresses)?,
+            ..Self::default()
+        };
+        registry.activate();
+        Ok(registry)
+    }
+
+    /// Registry from config with overrides loaded from the database
+    pub async fn load(config: &TokensConfig, repo: TokenOverrideRepository) -> Result<Self, TokenError> {
+        let mut registry = Self::from_config(config)?;
+        registry.repo = Some(repo);
+        registry.reload().await?;
+        Ok(registry)
+    }
+
+    /// Re-read overrides from the database; invalid rows are skipped
+    pub async fn reload(&self) -> Result<(), sqlx::Error> {
+        let Some(ref repo) = self.repo else {
+            return Ok(());
+        };
+        let overrides = repo
+            .list()
+            .await?
+            .into_iter()
+            .filter_map(|(chain, symbol, address)| match parse_entry(&chain, &symbol, &address) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    tracing::warn!(%chain, %symbol, "Ignoring token override: {}", e);
+                    None
+                }
+            })
+            .collect();
+        if let Ok(mut current) = self.overrides.write() {
+            *current = overrides;
+        }
+        self.activate();
+        Ok(())
+    }
+
+    /// Set (Some) or clear (None) an override and persist it
+    pub async fn set_override(&self, chain: &str, symbol: &str, address: Option<&str>) -> Result<(), TokenError> {
+        let chain = Chain::from_input(chain.trim()).ok_or_else(|| TokenError::UnknownChain(chain.to_string()))?;
+        let symbol = known_token(symbol)?;
+        let address = address
+            .map(|a| Address::from_str(a.trim()).map_err(|_| TokenError::InvalidAddress(format!("{} on {}", symbol, chain))))
+            .transpose()?;
+
+        if let Some(ref repo) = self.repo {
+            match address {
+                Some(address) => repo.set(chain.short_code(), symbol, &format!("{:?}", address)).await?,
+                None => repo.clear(chain.short_code(), symbol).await?,
+            }
+        }
+        if let Ok(mut overrides) = self.overrides.write() {
+            match address {
+                Some(address) => overrides.insert((chain, symbol), address),
+                None => overrides.remove(&(chain, symbol)),
+            };
+        }
+        self.activate();
+        tracing::info!(%chain, symbol, address = ?address, "Token address override changed");
+        Ok(())
+    }
+
+    /// Every chain and token with its current address
+    pub fn snapshot(&self) -> Vec<TokenState> {
+        let overrides = self.overrides.read().map(|o| o.clone()).unwrap_or_default();
+        Chain::all()
+            .into_iter()
+            .flat_map(|chain| KNOWN_TOKENS.map(|symbol| (chain, symbol)))
+            .map(|key| {
+                let (address, source) = match (overrides.get(&key), self.config.get(&key)) {
+                    (Some(address), _) => (Some(*address), TokenSource::Override),
+                    (None, Some(address)) => (Some(*address), TokenSource::Config),
+                    (None, None) => (default_token_address(key.0, key.1), TokenSource::Default),
+                };
+                TokenState {
+                    chain: key.0.short_code(),
+                    symbol: key.1,
+                    address: address.map(|a| format!("{:?}", a)),
+                    source,
+                }
+            })
+            .collect()
+    }
+
+    /// Publish config + database overrides to process-wide lookups
+    fn activate(&self) {
+        let mut merged = self.config.clone();
+        if let Ok(overrides) = self.overrides.read() {
+            merged.extend(overrides.iter().map(|(key, address)| (*key, *address)));
+        }
+        if let Ok(mut active) = ACTIVE.write() {
+            *active = merged;
+        }
+    }
+}
+
+impl std::fmt::Debug for TokenRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenRegistry")
+            .field("config", &self.config.len())
+            .field("persistent", &self.repo.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_token_config() {
+        let config = parse_config("arb-sepolia:usdc=0x0000000000000000000000000000000000000001, amoy:TXTC=0x0000000000000000000000000000000000000002").unwrap();
+        assert_eq!(config[&(Chain::ArbitrumSepolia, "USDC")], Address::from_low_u64_be(1));
+        assert_eq!(config[&(Chain::PolygonAmoy, "TXTC")], Address::from_low_u64_be(2));
+        assert!(parse_config("").unwrap().is_empty());
+
+        assert!(matches!(parse_config("nowhere:USDC=0x0000000000000000000000000000000000000001"), Err(TokenError::UnknownChain(_))));
+        assert!(matches!(parse_config("amoy:DAI=0x0000000000000000000000000000000000000001"), Err(TokenError::UnknownToken(_))));
+        assert!(matches!(parse_config("amoy:USDC=0x12"), Err(TokenError::InvalidAddress(_))));
+    }
+
+    #[test]
+    fn test_default_token_address() {
+        assert_eq!(default_token_address(TXTC_CHAIN, "TXTC"), Address::from_str(TXTC_ADDRESS).ok());
+        assert_eq!(default_token_address(Chain::BaseMainnet, "TXTC"), None);
+        assert!(default_token_address(Chain::ArbitrumSepolia, "USDC").is_some());
+    }
+}
@@ -1,6 +1,8 @@
 use ethers::prelude::*;
 use ethers::contract::abigen;
-use super::chains::{Chain, ChainProvider};
+use super::chains::{Chain, ChainError, ChainProvider};
+use crate::rates::Prices;
+use crate::sms::display::{NumberLocale, SmsDisplay};
 use std::sync::Arc;
 
 // Generate ERC20 contract bindings for USDC
@@ -12,6 +14,7 @@ abigen!(
         function symbol() external view returns (string)
         function transfer(address to, uint256 amount) external returns (bool)
         function approve(address spender, uint256 amount) external returns (bool)
+        function allowance(address owner, address spender) external view returns (uint256)
     ]"#
 );
 
@@ -29,6 +32,21 @@ impl TokenBalance {
     pub fn formatted(&self) -> String {
         format_token_balance(self.balance, self.decimals)
     }
+
+    /// Formatted with its USD value when there is a price, e.g. `0.45 ETH (~$1,520)`
+    pub fn priced(&self, prices: &Prices, locale: NumberLocale) -> String {
+        let formatted = self.formatted();
+        let amount = formatted.parse().unwrap_or_default();
+        let usd = prices.usd_value(amount, &self.symbol).map(|value| format!(" (~{})", locale.usd(value)));
+        format!("{} {}{}", locale.number(&formatted), self.symbol, usd.unwrap_or_default())
+    }
+}
+
+/// `1.500000 MATIC`
+impl SmsDisplay for TokenBalance {
+    fn sms_string(&self, locale: NumberLocale) -> String {
+        format!("{} {}", locale.number(&self.formatted()), self.symbol)
+    }
 }
 
 /// Format token balance with proper decimals
@@ -104,18 +122,33 @@ pub struct ChainBalances {
 }
 
 impl ChainBalances {
-    /// Format for SMS display (compact)
-    pub fn to_sms_string(&self) -> String {
-        let native = format!("{} {}", self.native.formatted(), self.native.symbol);
-        
-        match &self.usdc {
-            Some(usdc) => format!(
-                "{}: {} | {} USDC",
-                self.chain.short_code(),
-                native,
-                usdc.formatted()
-            ),
-            None => format!("{}: {}", self.chain.short_code(), native),
+    /// These balances with USD values where `prices` has them
+    pub fn with_prices<'a>(&'a self, prices: &'a Prices) -> PricedBalances<'a> {
+        PricedBalances { balances: self, prices }
+    }
+}
+
+/// `POL-T: 1.500000 MATIC | 25.500000 USDC`
+impl SmsDisplay for ChainBalances {
+    fn sms_string(&self, locale: NumberLocale) -> String {
+        self.with_prices(&Prices::default()).sms_string(locale)
+    }
+}
+
+/// Chain balances shown with their USD values
+pub struct PricedBalances<'a> {
+    balances: &'a ChainBalances,
+    prices: &'a Prices,
+}
+
+/// `POL-T: 1.500000 MATIC (~$0.78) | 25.500000 USDC`
+impl SmsDisplay for PricedBalances<'_> {
+    fn sms_string(&self, locale: NumberLocale) -> String {
+        let ChainBalances { chain, native, usdc } = self.balances;
+        let native = native.priced(self.prices, locale);
+        match usdc {
+            Some(usdc) => format!("{}: {} | {}", chain.short_code(), native, usdc.sms_string(locale)),
+            None => format!("{}: {}", chain.short_code(), native),
         }
     }
 }
@@ -137,6 +170,46 @@ pub async fn get_chain_balances(
     Ok(ChainBalances { chain, native, usdc })
 }
 
+/// Native and USDC balances on one chain, both read at once; a failed
+/// USDC read leaves it out rather than failing the chain
+pub async fn fetch_chain_balances(
+    provider: Arc<ChainProvider>,
+    chain: Chain,
+    address: Address,
+) -> Result<ChainBalances, ProviderError> {
+    let usdc = async {
+        let usdc_address = chain.usdc_address()?;
+        let balance = IERC20::new(usdc_address, provider.clone()).balance_of(address).call().await.ok()?;
+        Some(TokenBalance { chain, symbol: "USDC".to_string(), balance, decimals: 6 })
+    };
+    let (native, usdc) = tokio::join!(provider.get_balance(address, None), usdc);
+
+    let native = TokenBalance {
+        chain,
+        symbol: chain.native_token().to_string(),
+        balance: native?,
+        decimals: 18,
+    };
+    Ok(ChainBalances { chain, native, usdc })
+}
+
+/// BALANCE ALL reply: one line per chain, in the order given
+pub fn format_all_balances(
+    results: &[(Chain, Result<ChainBalances, ChainError>)],
+    prices: &Prices,
+    locale: NumberLocale,
+) -> String {
+    let lines: Vec<String> = results
+        .iter()
+        .map(|(chain, result)| match result {
+            Ok(balances) => balances.with_prices(prices).sms_string(locale),
+            Err(ChainError::Timeout(_)) => format!("{}: timed out", chain.short_code()),
+            Err(_) => format!("{}: unavailable", chain.short_code()),
+        })
+        .collect();
+    format!("Balances:\n{}", lines.join("\n"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,5 +251,18 @@ mod tests {
         assert!(sms.contains("POL-T"));
         assert!(sms.contains("MATIC"));
         assert!(sms.contains("USDC"));
+
+        let all = format_all_balances(&[
+            (Chain::PolygonAmoy, Ok(balances.clone())),
+            (Chain::BaseSepolia, Err(ChainError::Timeout(Chain::BaseSepolia))),
+            (Chain::ArbitrumSepolia, Err(ChainError::Unavailable(Chain::ArbitrumSepolia))),
+        ], &Prices::default(), NumberLocale::Point);
+        assert_eq!(
+            all,
+            "Balances:\nPOL-T: 1.500000 MATIC | 25.500000 USDC\nBASE-T: timed out\nARB-T: unavailable"
+        );
+
+        let prices = crate::rates::parse_prices(&serde_json::json!({"prices": {"MATIC": 0.52}}));
+        assert_eq!(balances.with_prices(&prices).to_sms_string(), "POL-T: 1.500000 MATIC (~$0.78) | 25.500000 USDC");
     }
 }
@@ -0,0 +1,245 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::config::VaultConfig;
+
+/// Prefix for values sealed with the current vault format
+const SEALED_PREFIX: &str = "v1:";
+
+/// Prefix for blind index values
+const BLIND_INDEX_PREFIX: &str = "bi1:";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Error, Debug)]
+pub enum VaultError {
+    #[error("Invalid master key: {0}")]
+    InvalidMasterKey(String),
+    #[error("Malformed sealed value")]
+    Malformed,
+    #[error("Decryption failed")]
+    Decryption,
+    #[error("Vault is locked - KEY_VAULT_MASTER_KEY not set")]
+    Locked,
+}
+
+/// Encrypts user private keys at rest with AES-256-GCM
+///
+/// Sealed values look like `v1:<nonce hex>:<ciphertext hex>`. Values written
+/// before the vault existed are plain hex keys and are still readable.
+#[derive(Clone)]
+pub struct KeyVault {
+    cipher: Option<Aes256Gcm>,
+    /// HMAC key for blind indexes, derived from the master key
+    index_key: Option<[u8; 32]>,
+    /// AES key for database backups, derived from the master key
+    backup_key: Option<[u8; 32]>,
+}
+
+impl KeyVault {
+    /// Create a vault from a 32-byte hex master key
+    pub fn new(master_key_hex: &str) -> Result<Self, VaultError> {
+        let key = hex::decode(master_key_hex.trim_start_matches("0x"))
+            .map_err(|e| VaultError::InvalidMasterKey(e.to_string()))?;
+        if key.len() != 32 {
+            return Err(VaultError::InvalidMasterKey("expected 32 bytes".to_string()));
+        }
+
+        let cipher = Aes256Gcm::new_from_slice(&key)
+            .map_err(|e| VaultError::InvalidMasterKey(e.to_string()))?;
+
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&key)
+            .map_err(|e| VaultError::InvalidMasterKey(e.to_string()))?;
+        mac.update(b"textchain/blind-index/v1");
+        let index_key = mac.finalize().into_bytes().into();
+
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&key)
+            .map_err(|e| VaultError::InvalidMasterKey(e.to_string()))?;
+        mac.update(b"textchain/backup/v1");
+        let backup_key = mac.finalize().into_bytes().into();
+
+        Ok(Self { cipher: Some(cipher), index_key: Some(index_key), backup_key: Some(backup_key) })
+    }
+
+    /// Vault without a master key - seals as plain hex (legacy dev behaviour)
+    pub fn unsealed() -> Self {
+        Self { cipher: None, index_key: None, backup_key: None }
+    }
+
+    /// The key being rotated away from (`KEY_VAULT_PREVIOUS_MASTER_KEY`).
+    /// Returns `None` when unset or invalid.
+    pub fn previous(config: &VaultConfig) -> Option<Self> {
+        let key = config.previous_master_key.expose().trim();
+        if key.is_empty() {
+            return None;
+        }
+        match Self::new(key) {
+            Ok(vault) => Some(vault),
+            Err(e) => {
+                tracing::error!("Invalid KEY_VAULT_PREVIOUS_MASTER_KEY ({}) - ignoring", e);
+                None
+            }
+        }
+    }
+
+    /// Load from `KEY_VAULT_MASTER_KEY`. Without one, keys would be stored
+    /// as plain hex, so that is refused unless `KEY_VAULT_ALLOW_PLAINTEXT=true`
+    /// (local development only).
+    pub fn from_config(config: &VaultConfig) -> Result<Self, VaultError> {
+        let key = Some(config.master_key.expose().trim()).filter(|key| !key.is_empty());
+        Self::from_master_key(key, config.allow_plaintext)
+    }
+
+    /// A set key must be valid; a missing one is only tolerated with
+    /// `allow_plaintext`
+    fn from_master_key(key: Option<&str>, allow_plaintext: bool) -> Result<Self, VaultError> {
+        match key {
+            Some(key) => Self::new(key),
+            None if allow_plaintext => {
+                tracing::warn!("KEY_VAULT_MASTER_KEY not set - keys will NOT be encrypted (KEY_VAULT_ALLOW_PLAINTEXT)");
+                Ok(Self::unsealed())
+            }
+            None => Err(VaultError::Locked),
+        }
+    }
+
+    /// Whether values are actually encrypted
+    pub fn is_sealed(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Key the backup files are encrypted with; `None` without a master key
+    pub fn backup_key(&self) -> Option<[u8; 32]> {
+        self.backup_key
+    }
+
+    /// Encrypt a private key for storage
+    pub fn seal(&self, plaintext: &[u8]) -> Result<String, VaultError> {
+        let Some(ref cipher) = self.cipher else {
+            return Ok(hex::encode(plaintext));
+        };
+
+        let mut nonce_bytes = [0u8; 12];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let nonce = Nonce::from_slice(&nonce_bytes);
+
+        let ciphertext = cipher
+            .encrypt(nonce, plaintext)
+            .map_err(|_| VaultError::Decryption)?;
+
+        Ok(format!("{}{}:{}", SEALED_PREFIX, hex::encode(nonce_bytes), hex::encode(ciphertext)))
+    }
+
+    /// Whether a stored value is in the sealed format
+    pub fn is_sealed_value(stored: &str) -> bool {
+        stored.starts_with(SEALED_PREFIX)
+    }
+
+    /// Whether a stored value is a blind index
+    pub fn is_blind_index(stored: &str) -> bool {
+        stored.starts_with(BLIND_INDEX_PREFIX)
+    }
+
+    /// Deterministic keyed hash of a value, for equality lookups on encrypted
+    /// columns. `None` when the vault has no master key.
+    pub fn blind_index(&self, value: &str) -> Option<String> {
+        let key = self.index_key.as_ref()?;
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(key).ok()?;
+        mac.update(value.as_bytes());
+        Some(format!("{}{}", BLIND_INDEX_PREFIX, hex::encode(mac.finalize().into_bytes())))
+    }
+
+    /// Decrypt a stored value (sealed or legacy plain hex)
+    pub fn open(&self, stored: &str) -> Result<Vec<u8>, VaultError> {
+        let Some(body) = stored.strip_prefix(SEALED_PREFIX) else {
+            return hex::decode(stored.trim_start_matches("0x")).map_err(|_| VaultError::Malformed);
+        };
+
+        let cipher = self.cipher.as_ref().ok_or(VaultError::Locked)?;
+        let (nonce_hex, ciphertext_hex) = body.split_once(':').ok_or(VaultError::Malformed)?;
+        let nonce_bytes = hex::decode(nonce_hex).map_err(|_| VaultError::Malformed)?;
+        let ciphertext = hex::decode(ciphertext_hex).map_err(|_| VaultError::Malformed)?;
+        if nonce_bytes.len() != 12 {
+            return Err(VaultError::Malformed);
+        }
+
+        cipher
+            .decrypt(Nonce::from_slice(&nonce_bytes), ciphertext.as_ref())
+            .map_err(|_| VaultError::Decryption)
+    }
+
+    /// Decrypt a stored private key to the 32-byte form
+    pub fn open_private_key(&self, stored: &str) -> Result<[u8; 32], VaultError> {
+        let bytes = self.open(stored)?;
+        bytes.try_into().map_err(|_| VaultError::Malformed)
+    }
+}
+
+impl std::fmt::Debug for KeyVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyVault")
+            .field("sealed", &self.is_sealed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASTER: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_seal_roundtrip() {
+        let vault = KeyVault::new(MASTER).unwrap();
+        let key = [7u8; 32];
+
+        let sealed = vault.seal(&key).unwrap();
+        assert!(sealed.starts_with("v1:"));
+        assert!(!sealed.contains(&hex::encode(key)));
+        assert_eq!(vault.open_private_key(&sealed).unwrap(), key);
+    }
+
+    #[test]
+    fn test_open_legacy_hex() {
+        let vault = KeyVault::new(MASTER).unwrap();
+        let key = [9u8; 32];
+        assert_eq!(vault.open_private_key(&hex::encode(key)).unwrap(), key);
+    }
+
+    #[test]
+    fn test_wrong_master_key_fails() {
+        let vault = KeyVault::new(MASTER).unwrap();
+        let other = KeyVault::new(&"ff".repeat(32)).unwrap();
+
+        let sealed = vault.seal(&[1u8; 32]).unwrap();
+        assert!(other.open(&sealed).is_err());
+        assert!(matches!(KeyVault::unsealed().open(&sealed), Err(VaultError::Locked)));
+    }
+
+    #[test]
+    fn test_blind_index_is_keyed_and_deterministic() {
+        let vault = KeyVault::new(MASTER).unwrap();
+        let other = KeyVault::new(&"ff".repeat(32)).unwrap();
+
+        let index = vault.blind_index("+15551234567").unwrap();
+        assert!(index.starts_with("bi1:"));
+        assert_eq!(vault.blind_index("+15551234567"), Some(index.clone()));
+        assert_ne!(other.blind_index("+15551234567"), Some(index));
+        assert_eq!(KeyVault::unsealed().blind_index("+15551234567"), None);
+    }
+
+    #[test]
+    fn test_master_key_required() {
+        assert!(KeyVault::from_master_key(Some(MASTER), false).unwrap().is_sealed());
+        assert!(KeyVault::from_master_key(Some(MASTER), true).unwrap().is_sealed());
+        // A bad key never falls back to plaintext, even when allowed
+        assert!(matches!(KeyVault::from_master_key(Some("abcd"), true), Err(VaultError::InvalidMasterKey(_))));
+        assert!(matches!(KeyVault::from_master_key(None, false), Err(VaultError::Locked)));
+        assert!(!KeyVault::from_master_key(None, true).unwrap().is_sealed());
+    }
+}
@@ -0,0 +1,188 @@
+//! WalletConnect v2 pairing URIs and signing of dApp requests
+//!
+//! The relay connection itself lives in the WalletConnect bridge sidecar;
+//! this module only creates pairings and turns an approved JSON-RPC request
+//! into a signature with the user's custodial key.
+
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::transaction::eip712::TypedData;
+use ethers::types::{Address, Signature};
+use rand::RngCore;
+use serde_json::Value;
+
+use crate::sms::display::truncate;
+
+/// How long a pairing URI can be used before it expires
+pub const PAIRING_TTL_SECS: i64 = 300;
+
+/// Methods a user can approve over SMS
+pub const SUPPORTED_METHODS: &[&str] = &["personal_sign", "eth_sign", "eth_signTypedData", "eth_signTypedData_v4"];
+
+/// WalletConnect error code for a user-rejected request
+pub const USER_REJECTED_CODE: i64 = 5000;
+
+/// WalletConnect error code for an unsupported method
+pub const UNSUPPORTED_METHOD_CODE: i64 = 5101;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum WcError {
+    #[error("Unsupported method: {0}")]
+    UnsupportedMethod(String),
+    #[error("Malformed params: {0}")]
+    BadParams(&'static str),
+    #[error("Request is for {0:?}, not this wallet")]
+    WrongAccount(Address),
+    #[error("Signing failed: {0}")]
+    Signing(String),
+}
+
+/// A fresh pairing: random topic and symmetric key
+#[derive(Debug, Clone)]
+pub struct Pairing {
+    pub topic: String,
+    pub sym_key: String,
+    /// Unix timestamp after which the URI is no longer valid
+    pub expiry: i64,
+}
+
+impl Pairing {
+    pub fn generate(now: i64) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut topic = [0u8; 32];
+        let mut sym_key = [0u8; 32];
+        rng.fill_bytes(&mut topic);
+        rng.fill_bytes(&mut sym_key);
+        Self {
+            topic: hex::encode(topic),
+            sym_key: hex::encode(sym_key),
+            expiry: now + PAIRING_TTL_SECS,
+        }
+    }
+
+    /// `wc:` URI to paste into (or scan from) the dApp
+    pub fn uri(&self) -> String {
+        format!(
+            "wc:{}@2?relay-protocol=irn&symKey={}&expiryTimestamp={}",
+            self.topic, self.sym_key, self.expiry
+        )
+    }
+}
+
+/// One-line description of a request for the approval SMS
+pub fn summarize(method: &str, params: &Value) -> String {
+    match method {
+        "personal_sign" | "eth_sign" => match message_param(method, params) {
+            Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(text) => format!("sign a message:\n\"{}\"", truncate(&text, 83)),
+                Err(_) => "sign a binary message".to_string(),
+            },
+            Err(_) => "sign a message".to_string(),
+        },
+        "eth_signTypedData" | "eth_signTypedData_v4" => match typed_data_param(params) {
+            Ok(typed) => format!("sign {} data", typed.primary_type),
+            Err(_) => "sign typed data".to_string(),
+        },
+        other => format!("call {}", other),
+    }
+}
+
+/// Sign an approved request, returning the JSON-RPC result
+pub async fn sign_request(wallet: &LocalWallet, method: &str, params: &Value) -> Result<Value, WcError> {
+    let signature: Signature = match method {
+        "personal_sign" | "eth_sign" => {
+            check_account(wallet, account_param(method, params))?;
+            let message = message_param(method, params)?;
+            wallet.sign_message(message).await.map_err(|e| WcError::Signing(e.to_string()))?
+        }
+        "eth_signTypedData" | "eth_signTypedData_v4" => {
+            check_account(wallet, params.get(0).and_then(Value::as_str))?;
+            let typed = typed_data_param(params)?;
+            wallet.sign_typed_data(&typed).await.map_err(|e| WcError::Signing(e.to_string()))?
+        }
+        other => return Err(WcError::UnsupportedMethod(other.to_string())),
+    };
+    Ok(Value::String(format!("0x{}", hex::encode(signature.to_vec()))))
+}
+
+/// personal_sign is [message, address]; eth_sign is [address, message]
+fn account_param<'a>(method: &str, params: &'a Value) -> Option<&'a str> {
+    let index = if method == "personal_sign" { 1 } else { 0 };
+    params.get(index).and_then(Value::as_str)
+}
+
+fn message_param(method: &str, params: &Value) -> Result<Vec<u8>, WcError> {
+    let index = if method == "personal_sign" { 0 } else { 1 };
+    let raw = params.get(index).and_then(Value::as_str).ok_or(WcError::BadParams("missing message"))?;
+    // dApps send hex-encoded bytes; fall back to the literal text
+    match raw.strip_prefix("0x").map(hex::decode) {
+        Some(Ok(bytes)) => Ok(bytes),
+        _ => Ok(raw.as_bytes().to_vec()),
+    }
+}
+
+/// Typed data arrives either as a JSON string or an object
+fn typed_data_param(params: &Value) -> Result<TypedData, WcError> {
+    let raw = params.get(1).ok_or(WcError::BadParams("missing typed data"))?;
+    let parsed = match raw {
+        Value::String(json) => serde_json::from_str(json),
+        other => serde_json::from_value(other.clone()),
+    };
+    parsed.map_err(|_| WcError::BadParams("invalid typed data"))
+}
+
+fn check_account(wallet: &LocalWallet, account: Option<&str>) -> Result<(), WcError> {
+    let account: Address = account
+        .and_then(|a| a.parse().ok())
+        .ok_or(WcError::BadParams("missing account"))?;
+    if account != wallet.address() {
+        return Err(WcError::WrongAccount(account));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Well-known anvil/hardhat test key #0
+    const KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+
+    #[test]
+    fn test_pairing_uri() {
+        let pairing = Pairing::generate(1_700_000_000);
+        let uri = pairing.uri();
+        assert!(uri.starts_with(&format!("wc:{}@2?relay-protocol=irn&symKey=", pairing.topic)));
+        assert!(uri.ends_with("&expiryTimestamp=1700000300"));
+        assert_eq!(pairing.topic.len(), 64);
+        assert_eq!(pairing.sym_key.len(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_personal_sign_recovers_to_wallet() {
+        let wallet: LocalWallet = KEY.parse().unwrap();
+        let address = format!("{:?}", wallet.address());
+        let params = json!(["0x48656c6c6f", address]);
+
+        assert_eq!(summarize("personal_sign", &params), "sign a message:\n\"Hello\"");
+
+        let result = sign_request(&wallet, "personal_sign", &params).await.unwrap();
+        let signature: Signature = result.as_str().unwrap().parse().unwrap();
+        assert_eq!(signature.recover("Hello").unwrap(), wallet.address());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_other_accounts_and_methods() {
+        let wallet: LocalWallet = KEY.parse().unwrap();
+        let other = "0x0000000000000000000000000000000000000001";
+
+        assert!(matches!(
+            sign_request(&wallet, "personal_sign", &json!(["0x00", other])).await,
+            Err(WcError::WrongAccount(_))
+        ));
+        assert_eq!(
+            sign_request(&wallet, "eth_sendTransaction", &json!([{}])).await,
+            Err(WcError::UnsupportedMethod("eth_sendTransaction".to_string()))
+        );
+    }
+}
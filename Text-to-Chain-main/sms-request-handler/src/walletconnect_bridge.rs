@@ -0,0 +1,220 @@
+//! WalletConnect relay bridge
+//!
+//! The bridge sidecar holds the WalletConnect relay connection. We ask it to
+//! subscribe to new pairings and to deliver responses; it posts session
+//! events and dApp requests to `POST /walletconnect/events`. Both directions
+//! carry the shared `WALLETCONNECT_BRIDGE_TOKEN` as a bearer token.
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::commands::greeting::with_greeting;
+use crate::config::WalletConnectConfig;
+use crate::db::{UserRepository, WalletConnectRepository, WC_REQUEST_TTL_MINUTES};
+use crate::sms::SmsGateway;
+use crate::wallet::walletconnect::{summarize, SUPPORTED_METHODS, UNSUPPORTED_METHOD_CODE};
+
+#[derive(Debug, thiserror::Error)]
+pub enum BridgeError {
+    #[error("HTTP request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("Bridge returned {0}")]
+    Status(reqwest::StatusCode),
+}
+
+/// HTTP client for the bridge sidecar
+#[derive(Debug, Clone)]
+pub struct WalletConnectBridge {
+    client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+impl WalletConnectBridge {
+    pub fn new(config: &WalletConnectConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: config.bridge_url.trim_end_matches('/').to_string(),
+            token: config.bridge_token.to_string(),
+        }
+    }
+
+    /// Subscribe to a new pairing; sessions on it are approved for `address`
+    pub async fn pair(&self, topic: &str, sym_key: &str, expiry: i64, address: &str) -> Result<(), BridgeError> {
+        self.post("pair", json!({ "topic": topic, "symKey": sym_key, "expiry": expiry, "address": address }))
+            .await
+    }
+
+    /// Deliver a JSON-RPC result to the dApp
+    pub async fn respond(&self, topic: &str, rpc_id: i64, result: Value) -> Result<(), BridgeError> {
+        self.post("respond", json!({ "topic": topic, "id": rpc_id, "result": result }))
+            .await
+    }
+
+    /// Deliver a JSON-RPC error to the dApp
+    pub async fn respond_error(&self, topic: &str, rpc_id: i64, code: i64, message: &str) -> Result<(), BridgeError> {
+        self.post(
+            "respond",
+            json!({ "topic": topic, "id": rpc_id, "error": { "code": code, "message": message } }),
+        )
+        .await
+    }
+
+    /// End a session from our side
+    pub async fn disconnect(&self, topic: &str) -> Result<(), BridgeError> {
+        self.post("disconnect", json!({ "topic": topic })).await
+    }
+
+    async fn post(&self, action: &str, body: Value) -> Result<(), BridgeError> {
+        let response = self
+            .client
+            .post(format!("{}/api/walletconnect/{}", self.base_url, action))
+            .bearer_auth(&self.token)
+            .json(&body)
+            .timeout(std::time::Duration::from_secs(10))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(BridgeError::Status(response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// WalletConnect event route state
+#[derive(Clone)]
+pub struct WalletConnectState {
+    pub repo: WalletConnectRepository,
+    pub bridge: WalletConnectBridge,
+    pub twilio: SmsGateway,
+    pub bridge_token: String,
+    /// For the greeting on signing requests
+    pub users: UserRepository,
+}
+
+/// Event posted by the bridge
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum BridgeEvent {
+    /// A dApp approved the pairing and opened a session
+    #[serde(rename = "session_connected")]
+    Connected { topic: String, peer_name: String },
+    /// A dApp asks the wallet to do something
+    #[serde(rename = "session_request")]
+    Request {
+        topic: String,
+        id: i64,
+        method: String,
+        #[serde(default)]
+        params: Value,
+    },
+    /// The dApp ended the session
+    #[serde(rename = "session_deleted")]
+    Deleted { topic: String },
+}
+
+/// Create WalletConnect bridge routes
+pub fn walletconnect_routes(state: WalletConnectState) -> Router {
+    Router::new()
+        .route("/walletconnect/events", post(bridge_event))
+        .with_state(state)
+}
+
+/// Handle a session event or dApp request from the bridge
+async fn bridge_event(
+    State(state): State<WalletConnectState>,
+    headers: HeaderMap,
+    Json(event): Json<BridgeEvent>,
+) -> StatusCode {
+    let authorized = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|token| token == state.bridge_token);
+    if !authorized {
+        return StatusCode::UNAUTHORIZED;
+    }
+
+    let topic = match &event {
+        BridgeEvent::Connected { topic, .. }
+        | BridgeEvent::Request { topic, .. }
+        | BridgeEvent::Deleted { topic } => topic.clone(),
+    };
+    let session = match state.repo.find_session(&topic).await {
+        Ok(Some(session)) if session.status != "closed" => session,
+        Ok(_) => return StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!("Failed to load WalletConnect session: {}", e);
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    };
+    if session.status == "pending" && session.expires_at < Utc::now() {
+        return StatusCode::GONE;
+    }
+    let peer = session.peer_name.clone().unwrap_or_else(|| "The dApp".to_string());
+
+    let sms = match event {
+        BridgeEvent::Connected { peer_name, .. } => {
+            match state.repo.activate_session(&topic, &peer_name).await {
+                Ok(Some(_)) => {}
+                Ok(None) => return StatusCode::GONE,
+                Err(e) => {
+                    tracing::error!("Failed to activate WalletConnect session: {}", e);
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+            }
+            format!(
+                "Connected to {}.\nEach signature needs your OK by SMS.\nReply DISCONNECT to end.",
+                peer_name
+            )
+        }
+        BridgeEvent::Request { id, method, params, .. } => {
+            if session.status != "active" {
+                return StatusCode::CONFLICT;
+            }
+            if !SUPPORTED_METHODS.contains(&method.as_str()) {
+                if let Err(e) = state.bridge.respond_error(&topic, id, UNSUPPORTED_METHOD_CODE, "Unsupported method").await {
+                    tracing::warn!("Failed to reject unsupported WalletConnect method: {}", e);
+                }
+                format!("{} asked to {} - not supported over SMS.", peer, summarize(&method, &params))
+            } else {
+                if let Err(e) = state.repo.add_request(&session, id, &method, &params.to_string()).await {
+                    tracing::error!("Failed to queue WalletConnect request: {}", e);
+                    return StatusCode::INTERNAL_SERVER_ERROR;
+                }
+                let prompt = format!(
+                    "{} wants you to {}\n\nReply SIGN <PIN> to approve or REJECT.\nExpires in {} min.",
+                    peer,
+                    summarize(&method, &params),
+                    WC_REQUEST_TTL_MINUTES
+                );
+                match state.users.greeting(&session.phone).await {
+                    Ok(greeting) => with_greeting(greeting.as_deref(), &prompt),
+                    Err(e) => {
+                        tracing::warn!("Greeting lookup failed: {}", e);
+                        prompt
+                    }
+                }
+            }
+        }
+        BridgeEvent::Deleted { .. } => {
+            if let Err(e) = state.repo.close_session(&topic).await {
+                tracing::error!("Failed to close WalletConnect session: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR;
+            }
+            format!("Disconnected from {}.", peer)
+        }
+    };
+
+    if let Err(e) = state.twilio.send_sms(&session.phone, &sms).await {
+        tracing::warn!("WalletConnect notification not sent: {}", e);
+    }
+    StatusCode::OK
+}
@@ -0,0 +1,293 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::config::WorkerConfig;
+
+/// Class of background work, each with its own concurrency limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TaskClass {
+    /// Plain command processing + SMS reply
+    SmsReply,
+    /// Commands that hit RPC nodes or the contract backend
+    OnChain,
+    /// Commands that run heavier database queries
+    DbHeavy,
+}
+
+impl TaskClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TaskClass::SmsReply => "sms-reply",
+            TaskClass::OnChain => "on-chain",
+            TaskClass::DbHeavy => "db-heavy",
+        }
+    }
+
+    pub fn all() -> [TaskClass; 3] {
+        [TaskClass::SmsReply, TaskClass::OnChain, TaskClass::DbHeavy]
+    }
+}
+
+/// Returned when a class's queue is full
+#[derive(Debug, thiserror::Error)]
+#[error("{0} queue is full")]
+pub struct QueueFull(pub &'static str);
+
+/// How long a command may run before the sender is told it's still working
+#[derive(Debug, Clone)]
+pub struct CommandTimeouts {
+    default: Option<Duration>,
+    per_command: HashMap<String, Option<Duration>>,
+}
+
+impl CommandTimeouts {
+    /// `default_secs` for every command, overridden per command name by
+    /// `SEND:30,SWAP:45` (0 = no limit; malformed entries are skipped)
+    pub fn parse(spec: &str, default_secs: u64) -> Self {
+        let per_command = spec
+            .split(',')
+            .filter_map(|entry| {
+                let (name, secs) = entry.split_once(':')?;
+                let name = name.trim().to_uppercase();
+                let secs = secs.trim().parse::<u64>().ok()?;
+                (!name.is_empty()).then(|| (name, limit(secs)))
+            })
+            .collect();
+        Self { default: limit(default_secs), per_command }
+    }
+
+    /// Limit for a command by `Command::name()`, None if it may run as long as it needs
+    pub fn get(&self, command: &str) -> Option<Duration> {
+        self.per_command.get(command).copied().unwrap_or(self.default)
+    }
+}
+
+fn limit(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Limits and counters for one task class
+struct Lane {
+    semaphore: Arc<Semaphore>,
+    concurrency: usize,
+    max_queue: usize,
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    completed: AtomicU64,
+    panicked: AtomicU64,
+    rejected: AtomicU64,
+}
+
+impl Lane {
+    fn new(concurrency: usize, max_queue: usize) -> Self {
+        let concurrency = concurrency.max(1);
+        Self {
+            semaphore: Arc::new(Semaphore::new(concurrency)),
+            concurrency,
+            max_queue,
+            queued: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            completed: AtomicU64::new(0),
+            panicked: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Counts a task as running until dropped, so a panicking task still
+/// leaves `running` (and is counted in `panicked` instead of `completed`)
+struct Running(Arc<Lane>);
+
+impl Running {
+    fn start(lane: Arc<Lane>) -> Self {
+        lane.running.fetch_add(1, Ordering::SeqCst);
+        Self(lane)
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.running.fetch_sub(1, Ordering::SeqCst);
+        if std::thread::panicking() {
+            self.0.panicked.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Point-in-time metrics for one task class
+#[derive(Debug, Clone, Serialize)]
+pub struct LaneMetrics {
+    pub class: &'static str,
+    pub concurrency: usize,
+    pub max_queue: usize,
+    pub queued: usize,
+    pub running: usize,
+    pub completed: u64,
+    pub panicked: u64,
+    pub rejected: u64,
+}
+
+/// Bounded pool for background tasks spawned by webhook handlers
+///
+/// Each task class gets a semaphore sized to its concurrency limit and a
+/// bounded wait queue; once the queue is full new work is rejected instead
+/// of piling up and exhausting DB connections or RPC quotas.
+#[derive(Clone)]
+pub struct WorkerPool {
+    sms_reply: Arc<Lane>,
+    on_chain: Arc<Lane>,
+    db_heavy: Arc<Lane>,
+    timeouts: Arc<CommandTimeouts>,
+}
+
+impl WorkerPool {
+    pub fn new(config: &WorkerConfig) -> Self {
+        Self {
+            sms_reply: Arc::new(Lane::new(config.sms_reply_concurrency, config.max_queue)),
+            on_chain: Arc::new(Lane::new(config.on_chain_concurrency, config.max_queue)),
+            db_heavy: Arc::new(Lane::new(config.db_heavy_concurrency, config.max_queue)),
+            timeouts: Arc::new(CommandTimeouts::parse(&config.command_timeouts, config.command_timeout_secs)),
+        }
+    }
+
+    /// How long a command may run before a holding reply goes out
+    pub fn command_timeout(&self, command: &str) -> Option<Duration> {
+        self.timeouts.get(command)
+    }
+
+    fn lane(&self, class: TaskClass) -> &Arc<Lane> {
+        match class {
+            TaskClass::SmsReply => &self.sms_reply,
+            TaskClass::OnChain => &self.on_chain,
+            TaskClass::DbHeavy => &self.db_heavy,
+        }
+    }
+
+    /// Spawn a task in the given class, or reject it if that class's queue is full
+    pub fn spawn<F>(&self, class: TaskClass, task: F) -> Result<(), QueueFull>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let lane = self.lane(class).clone();
+
+        // Reserve a queue slot up front so the check and increment can't race
+        let reserved = lane.queued.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+            (queued < lane.max_queue).then_some(queued + 1)
+        });
+        if reserved.is_err() {
+            lane.rejected.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(class = class.name(), "Worker queue full - rejecting task");
+            return Err(QueueFull(class.name()));
+        }
+
+        tokio::spawn(async move {
+            let permit = lane.semaphore.clone().acquire_owned().await;
+            lane.queued.fetch_sub(1, Ordering::SeqCst);
+            let Ok(_permit) = permit else {
+                return;
+            };
+
+            let _running = Running::start(lane.clone());
+            task.await;
+            lane.completed.fetch_add(1, Ordering::Relaxed);
+        });
+
+        Ok(())
+    }
+
+    /// Current queue length, in-flight count and totals per class
+    pub fn metrics(&self) -> Vec<LaneMetrics> {
+        TaskClass::all()
+            .into_iter()
+            .map(|class| {
+                let lane = self.lane(class);
+                LaneMetrics {
+                    class: class.name(),
+                    concurrency: lane.concurrency,
+                    max_queue: lane.max_queue,
+                    queued: lane.queued.load(Ordering::SeqCst),
+                    running: lane.running.load(Ordering::SeqCst),
+                    completed: lane.completed.load(Ordering::Relaxed),
+                    panicked: lane.panicked.load(Ordering::Relaxed),
+                    rejected: lane.rejected.load(Ordering::Relaxed),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(concurrency: usize, max_queue: usize) -> WorkerConfig {
+        WorkerConfig {
+            sms_reply_concurrency: concurrency,
+            on_chain_concurrency: concurrency,
+            db_heavy_concurrency: concurrency,
+            max_queue,
+            command_timeout_secs: 10,
+            command_timeouts: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_when_queue_full() {
+        let pool = WorkerPool::new(&config(1, 2));
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        let rx = Arc::new(tokio::sync::Mutex::new(Some(rx)));
+
+        // Occupies the single permit until released
+        let blocker = rx.clone();
+        pool.spawn(TaskClass::OnChain, async move {
+            let rx = blocker.lock().await.take().unwrap();
+            let _ = rx.await;
+        })
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        pool.spawn(TaskClass::OnChain, async {}).unwrap();
+        pool.spawn(TaskClass::OnChain, async {}).unwrap();
+        assert!(pool.spawn(TaskClass::OnChain, async {}).is_err());
+
+        // Other classes are unaffected
+        assert!(pool.spawn(TaskClass::SmsReply, async {}).is_ok());
+
+        let on_chain = &pool.metrics()[1];
+        assert_eq!(on_chain.queued, 2);
+        assert_eq!(on_chain.running, 1);
+        assert_eq!(on_chain.rejected, 1);
+
+        tx.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(pool.metrics()[1].completed, 3);
+    }
+
+    #[tokio::test]
+    async fn test_panicking_task_leaves_running() {
+        let pool = WorkerPool::new(&config(2, 4));
+        pool.spawn(TaskClass::DbHeavy, async { panic!("task failed") }).unwrap();
+        pool.spawn(TaskClass::DbHeavy, async {}).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let db_heavy = &pool.metrics()[2];
+        assert_eq!(db_heavy.running, 0);
+        assert_eq!(db_heavy.panicked, 1);
+        assert_eq!(db_heavy.completed, 1);
+    }
+
+    #[test]
+    fn test_command_timeouts() {
+        let timeouts = CommandTimeouts::parse("send:30, SWAP:0, bad, HISTORY:x", 10);
+        assert_eq!(timeouts.get("SEND"), Some(Duration::from_secs(30)));
+        assert_eq!(timeouts.get("SWAP"), None);
+        assert_eq!(timeouts.get("HISTORY"), Some(Duration::from_secs(10)));
+        assert_eq!(timeouts.get("BALANCE"), Some(Duration::from_secs(10)));
+        assert_eq!(CommandTimeouts::parse("", 0).get("BALANCE"), None);
+    }
+}
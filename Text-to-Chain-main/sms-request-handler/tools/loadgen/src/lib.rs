@@ -0,0 +1,40 @@
+//! Load generator for the SMS command pipeline
+//!
+//! Replays synthetic Twilio webhooks (`POST /sms/incoming`) at a fixed rate
+//! against a running server whose Twilio messaging API and chain RPC point
+//! at local mocks. Each run reports p50/p99 reply latency per command, plus
+//! how many DB queries (from `/metrics/db`), RPC calls and outbound SMS the
+//! traffic caused. Used by the `loadgen` binary and the
+//! `command_pipeline` bench.
+
+pub mod mocks;
+pub mod report;
+pub mod server;
+pub mod traffic;
+
+pub use mocks::{CallCounts, Mocks};
+pub use report::Report;
+pub use server::Server;
+pub use traffic::{Mix, RunConfig, Sample};
+
+/// Wait after the last reply before reading the counters
+const SETTLE: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Run `config` against `base_url` and report what the measured traffic
+/// cost (setup messages are sent first and not counted)
+pub async fn measure(base_url: &str, mocks: &Mocks, config: &RunConfig) -> Result<Report, reqwest::Error> {
+    let client = reqwest::Client::new();
+    traffic::setup(&client, base_url, config).await;
+    tokio::time::sleep(SETTLE).await;
+    let db_before = report::scrape_db_calls(&client, base_url).await?;
+    let calls_before = mocks.counts();
+
+    let started = std::time::Instant::now();
+    let samples = traffic::run(&client, base_url, config).await;
+    let elapsed = started.elapsed();
+    // Replies sent through the gateway can land just after the webhook returns
+    tokio::time::sleep(SETTLE).await;
+
+    let db_after = report::scrape_db_calls(&client, base_url).await?;
+    Ok(Report::new(&samples, elapsed, report::diff(&db_before, &db_after), mocks.counts().since(&calls_before)))
+}
@@ -0,0 +1,128 @@
+//! `loadgen`: replay synthetic Twilio webhooks against the SMS handler
+//!
+//! ```text
+//! loadgen --server target/release/textchain [--rate 50] [--duration 30] [--users 200]
+//!         [--mix "5:BALANCE,1:HELP,2:SEND 1 USDC TO {peer}"] [--setup JOIN] [--seed 1]
+//!         [--max-p99-ms 250]
+//! loadgen --target http://127.0.0.1:3000 [--mock-port 4599] ...
+//! ```
+//!
+//! With `--server` the binary is started against the mocks. With `--target`
+//! an already running server is used; start it with `TWILIO_API_BASE` and
+//! `CONFIG_FILE` pointing at the mocks (printed at startup) to count its
+//! SMS and RPC calls.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use loadgen::{measure, Mix, Mocks, RunConfig, Server};
+
+const USAGE: &str = "Usage: loadgen (--server <binary> | --target <url>) [--rate <per sec>] [--duration <secs>] \
+    [--users <n>] [--mix <weight:body,...>] [--setup <body>] [--seed <n>] [--mock-port <port>] [--max-p99-ms <ms>]";
+
+#[derive(Default)]
+struct Options {
+    server: Option<PathBuf>,
+    target: Option<String>,
+    mock_port: Option<u16>,
+    max_p99_ms: Option<f64>,
+    run: RunConfig,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = || rest.next().cloned().ok_or_else(|| format!("{} needs a value", arg));
+        let number = |v: String| v.parse::<f64>().map_err(|_| format!("{} needs a number", arg));
+        match arg.as_str() {
+            "--server" => options.server = Some(PathBuf::from(value()?)),
+            "--target" => options.target = Some(value()?),
+            "--rate" => options.run.rate = number(value()?)?,
+            "--duration" => options.run.duration = Duration::from_secs_f64(number(value()?)?),
+            "--users" => options.run.users = number(value()?)? as usize,
+            "--mix" => options.run.mix = Mix::parse(&value()?)?,
+            "--setup" => options.run.setup = Some(value()?),
+            "--seed" => options.run.seed = number(value()?)? as u64,
+            "--mock-port" => options.mock_port = Some(number(value()?)? as u16),
+            "--max-p99-ms" => options.max_p99_ms = Some(number(value()?)?),
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            other => return Err(format!("Unknown option: {}\n{}", other, USAGE)),
+        }
+    }
+    if options.server.is_some() == options.target.is_some() {
+        return Err(USAGE.to_string());
+    }
+    if options.run.rate <= 0.0 {
+        return Err("--rate must be above 0".to_string());
+    }
+    Ok(options)
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    // A running server needs the mocks on a port it was configured with
+    let port = options.mock_port.unwrap_or(if options.target.is_some() { 4599 } else { 0 });
+    let mocks = match Mocks::start(port).await {
+        Ok(mocks) => mocks,
+        Err(e) => {
+            eprintln!("Could not start mocks on port {}: {}", port, e);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let server;
+    let base_url = match (&options.server, &options.target) {
+        (Some(binary), _) => match Server::spawn(binary, &mocks).await {
+            Ok(spawned) => {
+                server = spawned;
+                server.base_url().to_string()
+            }
+            Err(e) => {
+                eprintln!("Could not start {}: {}", binary.display(), e);
+                return ExitCode::FAILURE;
+            }
+        },
+        (None, Some(target)) => {
+            println!("Mocks listening. For SMS and RPC counts, start the server with:");
+            println!("  TWILIO_API_BASE={}", mocks.twilio_base());
+            println!("  CONFIG_FILE=<file containing {}>", mocks.live_config());
+            target.clone()
+        }
+        (None, None) => unreachable!("checked in parse_args"),
+    };
+
+    println!(
+        "Sending {:.0} webhooks/s for {:.0}s from {} users to {}\n",
+        options.run.rate,
+        options.run.duration.as_secs_f64(),
+        options.run.users,
+        base_url
+    );
+    let report = match measure(&base_url, &mocks, &options.run).await {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Run failed: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    println!("{}", report);
+
+    match options.max_p99_ms {
+        Some(budget) if report.overall.p99.as_secs_f64() * 1000.0 > budget => {
+            eprintln!("p99 {:.1} ms is over the {} ms budget", report.overall.p99.as_secs_f64() * 1000.0, budget);
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
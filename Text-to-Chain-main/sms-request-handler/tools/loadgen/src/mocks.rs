@@ -0,0 +1,261 @@
+//! Mock Twilio messaging API and JSON-RPC node
+//!
+//! Both are served from one local listener: Twilio's
+//! `/2010-04-01/Accounts/:sid/Messages.json` and a node per chain at
+//! `/rpc/:chain_id`. The node answers the calls the server makes for
+//! balances, gas and sends with fixed values, so commands run to completion
+//! without touching a real chain. Every call is counted.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Path, State};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+
+/// 10 ETH, returned for every native balance
+const BALANCE_WEI: &str = "0x8ac7230489e80000";
+/// 1 gwei
+const GAS_PRICE: &str = "0x3b9aca00";
+const BLOCK_NUMBER: &str = "0x100000";
+/// Returned for every `eth_call`, e.g. `balanceOf` (1000 USDC at 6 decimals)
+const CALL_RESULT: u64 = 1_000_000_000;
+
+#[derive(Default)]
+struct Counters {
+    sms_sent: AtomicU64,
+    /// Calls per JSON-RPC method
+    rpc: Mutex<BTreeMap<String, u64>>,
+    /// Source of transaction hashes
+    sent_txs: AtomicU64,
+}
+
+/// Calls made to the mocks
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CallCounts {
+    pub sms_sent: u64,
+    pub rpc: BTreeMap<String, u64>,
+}
+
+impl CallCounts {
+    pub fn rpc_total(&self) -> u64 {
+        self.rpc.values().sum()
+    }
+
+    /// Calls made after `earlier` was taken
+    pub fn since(&self, earlier: &CallCounts) -> CallCounts {
+        CallCounts {
+            sms_sent: self.sms_sent - earlier.sms_sent,
+            rpc: crate::report::diff(&earlier.rpc, &self.rpc),
+        }
+    }
+}
+
+/// Running mocks; they stop when the process exits
+pub struct Mocks {
+    addr: SocketAddr,
+    counters: Arc<Counters>,
+}
+
+impl Mocks {
+    /// Serve the mocks on `127.0.0.1:port` (0 = any free port)
+    pub async fn start(port: u16) -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+        let addr = listener.local_addr()?;
+        let counters = Arc::new(Counters::default());
+        let app = Router::new()
+            .route("/2010-04-01/Accounts/:sid/Messages.json", post(twilio_send))
+            .route("/rpc/:chain_id", post(rpc))
+            .with_state(counters.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                eprintln!("mock server stopped: {}", e);
+            }
+        });
+        Ok(Self { addr, counters })
+    }
+
+    /// Value for `TWILIO_API_BASE`
+    pub fn twilio_base(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// RPC endpoint answering as `chain_id`
+    pub fn rpc_url(&self, chain_id: u64) -> String {
+        format!("http://{}/rpc/{}", self.addr, chain_id)
+    }
+
+    /// Live config (`CONFIG_FILE`) pointing every chain the server knows at the mock node
+    pub fn live_config(&self) -> Value {
+        let chains = [
+            ("amoy", 80002),
+            ("polygon", 137),
+            ("base-sepolia", 84532),
+            ("base", 8453),
+            ("sepolia", 11155111),
+            ("eth", 1),
+            ("arb-sepolia", 421614),
+            ("arb", 42161),
+        ];
+        let rpc_urls: serde_json::Map<String, Value> =
+            chains.iter().map(|(name, id)| (name.to_string(), Value::String(self.rpc_url(*id)))).collect();
+        json!({ "rpc_urls": rpc_urls })
+    }
+
+    pub fn counts(&self) -> CallCounts {
+        CallCounts {
+            sms_sent: self.counters.sms_sent.load(Ordering::Relaxed),
+            rpc: self.counters.rpc.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        }
+    }
+}
+
+async fn twilio_send(State(counters): State<Arc<Counters>>, Path(sid): Path<String>) -> Json<Value> {
+    let n = counters.sms_sent.fetch_add(1, Ordering::Relaxed);
+    Json(json!({ "sid": format!("SM{:032x}", n), "status": "queued", "account_sid": sid }))
+}
+
+async fn rpc(State(counters): State<Arc<Counters>>, Path(chain_id): Path<u64>, Json(request): Json<Value>) -> Json<Value> {
+    match request {
+        Value::Array(batch) => Json(Value::Array(batch.iter().map(|call| answer(&counters, chain_id, call)).collect())),
+        call => Json(answer(&counters, chain_id, &call)),
+    }
+}
+
+/// JSON-RPC response to one call
+fn answer(counters: &Counters, chain_id: u64, call: &Value) -> Value {
+    let method = call["method"].as_str().unwrap_or_default();
+    *counters.rpc.lock().unwrap_or_else(|e| e.into_inner()).entry(method.to_string()).or_default() += 1;
+    let params = &call["params"];
+
+    let result = match method {
+        "eth_chainId" => json!(format!("{:#x}", chain_id)),
+        "net_version" => json!(chain_id.to_string()),
+        "eth_blockNumber" => json!(BLOCK_NUMBER),
+        "eth_getBalance" => json!(BALANCE_WEI),
+        "eth_gasPrice" | "eth_maxPriorityFeePerGas" => json!(GAS_PRICE),
+        "eth_estimateGas" => json!("0x186a0"),
+        "eth_getTransactionCount" => json!("0x0"),
+        "eth_getCode" => json!("0x"),
+        "eth_call" => json!(format!("0x{:064x}", CALL_RESULT)),
+        "eth_feeHistory" => json!({
+            "oldestBlock": BLOCK_NUMBER,
+            "baseFeePerGas": [GAS_PRICE, GAS_PRICE],
+            "gasUsedRatio": [0.5],
+            "reward": [[GAS_PRICE]],
+        }),
+        "eth_getBlockByNumber" => block(),
+        "eth_sendRawTransaction" => {
+            let n = counters.sent_txs.fetch_add(1, Ordering::Relaxed) + 1;
+            json!(format!("0x{:064x}", n))
+        }
+        "eth_getTransactionByHash" => transaction(params[0].as_str().unwrap_or_default()),
+        "eth_getTransactionReceipt" => receipt(params[0].as_str().unwrap_or_default()),
+        _ => {
+            return json!({
+                "jsonrpc": "2.0",
+                "id": call["id"],
+                "error": { "code": -32601, "message": format!("{} not supported by the mock node", method) },
+            })
+        }
+    };
+    json!({ "jsonrpc": "2.0", "id": call["id"], "result": result })
+}
+
+fn zero_hash() -> String {
+    format!("0x{:064x}", 0)
+}
+
+fn zero_address() -> String {
+    format!("0x{:040x}", 0)
+}
+
+fn block() -> Value {
+    json!({
+        "number": BLOCK_NUMBER,
+        "hash": format!("0x{:064x}", 1),
+        "parentHash": zero_hash(),
+        "sha3Uncles": zero_hash(),
+        "miner": zero_address(),
+        "stateRoot": zero_hash(),
+        "transactionsRoot": zero_hash(),
+        "receiptsRoot": zero_hash(),
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "difficulty": "0x0",
+        "gasLimit": "0x1c9c380",
+        "gasUsed": "0x0",
+        "timestamp": "0x65000000",
+        "extraData": "0x",
+        "mixHash": zero_hash(),
+        "nonce": "0x0000000000000000",
+        "baseFeePerGas": GAS_PRICE,
+        "uncles": [],
+        "transactions": [],
+        "size": "0x0",
+    })
+}
+
+/// A mined transaction, so pending sends resolve at once
+fn transaction(hash: &str) -> Value {
+    json!({
+        "hash": hash,
+        "nonce": "0x0",
+        "blockHash": format!("0x{:064x}", 1),
+        "blockNumber": BLOCK_NUMBER,
+        "transactionIndex": "0x0",
+        "from": zero_address(),
+        "to": zero_address(),
+        "value": "0x0",
+        "gasPrice": GAS_PRICE,
+        "gas": "0x186a0",
+        "input": "0x",
+        "v": "0x0",
+        "r": "0x0",
+        "s": "0x0",
+        "type": "0x0",
+    })
+}
+
+fn receipt(hash: &str) -> Value {
+    json!({
+        "transactionHash": hash,
+        "transactionIndex": "0x0",
+        "blockHash": format!("0x{:064x}", 1),
+        "blockNumber": BLOCK_NUMBER,
+        "from": zero_address(),
+        "to": zero_address(),
+        "cumulativeGasUsed": "0x5208",
+        "gasUsed": "0x5208",
+        "effectiveGasPrice": GAS_PRICE,
+        "contractAddress": null,
+        "logs": [],
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "status": "0x1",
+        "type": "0x2",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rpc_answers_and_counts() {
+        let counters = Counters::default();
+        let call = |method: &str| answer(&counters, 80002, &json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": [] }));
+
+        assert_eq!(call("eth_chainId")["result"], "0x13882");
+        assert_eq!(call("eth_chainId")["id"], 7);
+        assert_eq!(call("eth_sendRawTransaction")["result"], format!("0x{:064x}", 1));
+        assert_eq!(call("eth_sendRawTransaction")["result"], format!("0x{:064x}", 2));
+        assert_eq!(call("debug_traceTransaction")["error"]["code"], -32601);
+
+        let rpc = counters.rpc.lock().unwrap();
+        assert_eq!(rpc["eth_chainId"], 2);
+        assert_eq!(rpc["eth_sendRawTransaction"], 2);
+        assert_eq!(rpc["debug_traceTransaction"], 1);
+    }
+}
@@ -0,0 +1,199 @@
+//! Latency percentiles and call counts for a run
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::mocks::CallCounts;
+use crate::traffic::Sample;
+
+/// Per-method query counter in `GET /metrics/db`
+const DB_QUERY_COUNT: &str = "textchain_db_query_seconds_count{query=\"";
+
+/// Latency of one command across a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandStats {
+    pub command: String,
+    pub count: usize,
+    pub errors: usize,
+    pub p50: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl CommandStats {
+    fn from_latencies(command: &str, mut latencies: Vec<Duration>, errors: usize) -> Self {
+        latencies.sort_unstable();
+        Self {
+            command: command.to_string(),
+            count: latencies.len(),
+            errors,
+            p50: percentile(&latencies, 0.50),
+            p99: percentile(&latencies, 0.99),
+            max: latencies.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted latencies (zero when empty)
+pub fn percentile(sorted: &[Duration], quantile: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// What a run measured
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// One row per command, by name
+    pub commands: Vec<CommandStats>,
+    /// Every command together
+    pub overall: CommandStats,
+    pub elapsed: Duration,
+    /// Queries per repository method during the run
+    pub db_calls: BTreeMap<String, u64>,
+    pub calls: CallCounts,
+}
+
+impl Report {
+    pub fn new(samples: &[Sample], elapsed: Duration, db_calls: BTreeMap<String, u64>, calls: CallCounts) -> Self {
+        let mut by_command: BTreeMap<&str, (Vec<Duration>, usize)> = BTreeMap::new();
+        for sample in samples {
+            let (latencies, errors) = by_command.entry(&sample.command).or_default();
+            latencies.push(sample.latency);
+            *errors += usize::from(!sample.ok);
+        }
+        let commands = by_command
+            .into_iter()
+            .map(|(command, (latencies, errors))| CommandStats::from_latencies(command, latencies, errors))
+            .collect();
+        let overall = CommandStats::from_latencies(
+            "ALL",
+            samples.iter().map(|s| s.latency).collect(),
+            samples.iter().filter(|s| !s.ok).count(),
+        );
+        Self { commands, overall, elapsed, db_calls, calls }
+    }
+
+    pub fn db_total(&self) -> u64 {
+        self.db_calls.values().sum()
+    }
+
+    /// Achieved webhooks per second
+    pub fn rate(&self) -> f64 {
+        self.overall.count as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        writeln!(f, "{:<12} {:>7} {:>7} {:>9} {:>9} {:>9}", "command", "count", "errors", "p50 ms", "p99 ms", "max ms")?;
+        for stats in self.commands.iter().chain([&self.overall]) {
+            writeln!(
+                f,
+                "{:<12} {:>7} {:>7} {:>9.1} {:>9.1} {:>9.1}",
+                stats.command,
+                stats.count,
+                stats.errors,
+                ms(stats.p50),
+                ms(stats.p99),
+                ms(stats.max)
+            )?;
+        }
+        let per = |total: u64| total as f64 / self.overall.count.max(1) as f64;
+        writeln!(f, "\n{:.1} webhooks/s over {:.1}s", self.rate(), self.elapsed.as_secs_f64())?;
+        writeln!(f, "DB queries:   {} ({:.2} per webhook)", self.db_total(), per(self.db_total()))?;
+        writeln!(f, "RPC calls:    {} ({:.2} per webhook)", self.calls.rpc_total(), per(self.calls.rpc_total()))?;
+        writeln!(f, "SMS sent:     {}", self.calls.sms_sent)?;
+        for (label, counts) in [("DB", &self.db_calls), ("RPC", &self.calls.rpc)] {
+            let mut top: Vec<_> = counts.iter().filter(|(_, n)| **n > 0).collect();
+            top.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            for (name, n) in top.into_iter().take(10) {
+                writeln!(f, "  {:<4} {:<40} {:>7}", label, name, n)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Query counts per repository method from `/metrics/db` (empty when the
+/// server runs without a database)
+pub fn parse_db_calls(metrics: &str) -> BTreeMap<String, u64> {
+    metrics
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix(DB_QUERY_COUNT)?;
+            let (query, value) = rest.split_once("\"}")?;
+            Some((query.to_string(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+pub async fn scrape_db_calls(client: &reqwest::Client, base_url: &str) -> Result<BTreeMap<String, u64>, reqwest::Error> {
+    let response = client.get(format!("{}/metrics/db", base_url.trim_end_matches('/'))).send().await?;
+    if !response.status().is_success() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(parse_db_calls(&response.text().await?))
+}
+
+/// Growth of each counter from `before` to `after`
+pub fn diff(before: &BTreeMap<String, u64>, after: &BTreeMap<String, u64>) -> BTreeMap<String, u64> {
+    after
+        .iter()
+        .map(|(name, n)| (name.clone(), n.saturating_sub(before.get(name).copied().unwrap_or_default())))
+        .filter(|(_, n)| *n > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let latencies: Vec<Duration> = (1..=100).map(ms).collect();
+        assert_eq!(percentile(&latencies, 0.50), ms(50));
+        assert_eq!(percentile(&latencies, 0.99), ms(99));
+        assert_eq!(percentile(&latencies, 1.0), ms(100));
+        assert_eq!(percentile(&[ms(7)], 0.99), ms(7));
+        assert_eq!(percentile(&[], 0.5), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_report_groups_by_command() {
+        let sample = |command: &str, latency: u64, ok: bool| Sample { command: command.to_string(), latency: ms(latency), ok };
+        let samples = [sample("BALANCE", 10, true), sample("SEND", 40, true), sample("BALANCE", 30, false), sample("SEND", 20, true)];
+        let report = Report::new(&samples, Duration::from_secs(2), BTreeMap::new(), CallCounts::default());
+
+        assert_eq!(report.commands.len(), 2);
+        assert_eq!(report.commands[0].command, "BALANCE");
+        assert_eq!(report.commands[0].errors, 1);
+        assert_eq!(report.commands[0].p99, ms(30));
+        assert_eq!(report.overall.count, 4);
+        assert_eq!(report.overall.p50, ms(20));
+        assert_eq!(report.rate(), 2.0);
+    }
+
+    #[test]
+    fn test_db_calls_from_metrics() {
+        let metrics = "# TYPE textchain_db_query_seconds histogram\n\
+            textchain_db_query_seconds_bucket{query=\"users.find_by_phone\",le=\"0.001\"} 3\n\
+            textchain_db_query_seconds_count{query=\"users.find_by_phone\"} 12\n\
+            textchain_db_query_seconds_count{query=\"audit_log.record\"} 4\n\
+            textchain_db_slow_queries_total{query=\"users.find_by_phone\"} 1\n";
+        let before = parse_db_calls(metrics);
+        assert_eq!(before.len(), 2);
+        assert_eq!(before["users.find_by_phone"], 12);
+
+        let after = parse_db_calls(&metrics.replace("} 12", "} 20"));
+        assert_eq!(diff(&before, &after), BTreeMap::from([("users.find_by_phone".to_string(), 8)]));
+    }
+}
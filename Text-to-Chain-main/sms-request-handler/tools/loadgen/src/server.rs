@@ -0,0 +1,81 @@
+//! The SMS handler started against the mocks
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use tokio::process::{Child, Command};
+
+use crate::mocks::Mocks;
+
+/// How long the server gets to answer `/health`
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A server process, killed when dropped
+pub struct Server {
+    child: Child,
+    base_url: String,
+    /// Scratch directory holding the live config file
+    dir: PathBuf,
+}
+
+impl Server {
+    /// Start `binary` with Twilio and every chain's RPC pointed at `mocks`.
+    /// `DATABASE_URL` and other settings come from this process's
+    /// environment; `.env` files are not read.
+    pub async fn spawn(binary: &Path, mocks: &Mocks) -> std::io::Result<Self> {
+        // The server runs in a scratch directory, away from any `.env`
+        let binary = std::fs::canonicalize(binary)?;
+        let port = std::net::TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+        let dir = std::env::temp_dir().join(format!("loadgen-{}-{}", std::process::id(), port));
+        std::fs::create_dir_all(&dir)?;
+        let config_file = dir.join("live-config.json");
+        std::fs::write(&config_file, mocks.live_config().to_string())?;
+
+        let child = Command::new(&binary)
+            .current_dir(&dir)
+            .env("SERVER_HOST", "127.0.0.1")
+            .env("SERVER_PORT", port.to_string())
+            .env("TWILIO_ACCOUNT_SID", "ACloadgen")
+            .env("TWILIO_AUTH_TOKEN", "loadgen")
+            .env("TWILIO_PHONE_NUMBER", "+15550000000")
+            .env("TWILIO_API_BASE", mocks.twilio_base())
+            .env("CONFIG_FILE", &config_file)
+            .env("RPC_URL", mocks.rpc_url(80002))
+            .env("RUST_LOG", std::env::var("LOADGEN_SERVER_LOG").unwrap_or_else(|_| "warn".to_string()))
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let mut server = Self { child, base_url: format!("http://127.0.0.1:{}", port), dir };
+        server.wait_healthy().await?;
+        Ok(server)
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn wait_healthy(&mut self) -> std::io::Result<()> {
+        let client = reqwest::Client::new();
+        let started = Instant::now();
+        while started.elapsed() < STARTUP_TIMEOUT {
+            if let Some(status) = self.child.try_wait()? {
+                return Err(std::io::Error::other(format!("server exited at startup ({})", status)));
+            }
+            let health = client.get(format!("{}/health", self.base_url)).send().await;
+            if health.is_ok_and(|response| response.status().is_success()) {
+                return Ok(());
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "server did not become healthy"))
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.start_kill();
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,216 @@
+//! Synthetic webhook traffic
+//!
+//! Requests go out open-loop at a fixed rate, so a slow server shows up as
+//! latency rather than as a lower request rate. Senders and commands come
+//! from a seeded generator, and the same seed replays the same traffic.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::task::JoinSet;
+
+/// Commands to send and how often, relative to each other
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mix {
+    entries: Vec<(u32, String)>,
+    total: u32,
+}
+
+impl Mix {
+    /// `weight:body` pairs separated by commas, e.g.
+    /// `5:BALANCE,1:HELP,2:SEND 1 USDC TO {peer}`. `{peer}` becomes
+    /// another synthetic user's number
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (weight, body) = match entry.split_once(':') {
+                Some((weight, body)) if weight.trim().chars().all(|c| c.is_ascii_digit()) => {
+                    (weight.trim().parse::<u32>().map_err(|_| format!("bad weight in {}", entry))?, body.trim())
+                }
+                _ => (1, entry),
+            };
+            if weight > 0 && !body.is_empty() {
+                entries.push((weight, body.to_string()));
+            }
+        }
+        let total = entries.iter().map(|(weight, _)| weight).sum();
+        if total == 0 {
+            return Err(format!("no commands in mix {:?}", spec));
+        }
+        Ok(Self { entries, total })
+    }
+
+    /// Body for a draw in `0..total`
+    fn pick(&self, draw: u32) -> &str {
+        let mut draw = draw % self.total;
+        for (weight, body) in &self.entries {
+            if draw < *weight {
+                return body;
+            }
+            draw -= weight;
+        }
+        &self.entries[0].1
+    }
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self::parse("5:BALANCE,2:HISTORY,1:HELP,2:SEND 1 USDC TO {peer}").expect("default mix")
+    }
+}
+
+/// One load run
+#[derive(Debug, Clone)]
+pub struct RunConfig {
+    /// Webhooks per second
+    pub rate: f64,
+    pub duration: Duration,
+    /// Distinct synthetic senders
+    pub users: usize,
+    pub mix: Mix,
+    /// Sent once by every user before the measured traffic (e.g. `JOIN`)
+    pub setup: Option<String>,
+    pub seed: u64,
+    /// Synthetic numbers are this prefix plus a 7-digit index
+    pub phone_prefix: String,
+}
+
+impl Default for RunConfig {
+    fn default() -> Self {
+        Self {
+            rate: 20.0,
+            duration: Duration::from_secs(10),
+            users: 50,
+            mix: Mix::default(),
+            setup: None,
+            seed: 1,
+            phone_prefix: "+1555".to_string(),
+        }
+    }
+}
+
+impl RunConfig {
+    fn phone(&self, user: usize) -> String {
+        format!("{}{:07}", self.phone_prefix, user)
+    }
+}
+
+/// One webhook and its reply
+#[derive(Debug, Clone)]
+pub struct Sample {
+    /// First word of the body, upper-cased (`SEND`, `BALANCE`, ...)
+    pub command: String,
+    pub latency: Duration,
+    /// 2xx with a TwiML body
+    pub ok: bool,
+}
+
+/// xorshift64*, enough to spread senders and commands
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+/// Send the setup message, if any, once from every user
+pub async fn setup(client: &reqwest::Client, base_url: &str, config: &RunConfig) {
+    let Some(setup) = &config.setup else {
+        return;
+    };
+    let url = format!("{}/sms/incoming", base_url.trim_end_matches('/'));
+    let mut tasks = JoinSet::new();
+    for user in 0..config.users.max(1) {
+        tasks.spawn(send(client.clone(), url.clone(), config.phone(user), setup.clone()));
+        // Keep setup within the configured rate too
+        if tasks.len() >= config.rate.max(1.0) as usize {
+            tasks.join_next().await;
+        }
+    }
+    while tasks.join_next().await.is_some() {}
+}
+
+/// Send the measured traffic
+pub async fn run(client: &reqwest::Client, base_url: &str, config: &RunConfig) -> Vec<Sample> {
+    let url = format!("{}/sms/incoming", base_url.trim_end_matches('/'));
+    let users = config.users.max(1);
+
+    let mut rng = Rng::new(config.seed);
+    let total = (config.rate * config.duration.as_secs_f64()).round() as u64;
+    let every = Duration::from_secs_f64(1.0 / config.rate.max(0.001));
+    let mut ticks = tokio::time::interval(every);
+    ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Burst);
+
+    let mut tasks = JoinSet::new();
+    for _ in 0..total {
+        ticks.tick().await;
+        let user = rng.next() as usize % users;
+        let peer = (user + 1 + rng.next() as usize % users.saturating_sub(1).max(1)) % users;
+        let body = config.mix.pick(rng.next() as u32).replace("{peer}", &config.phone(peer));
+        tasks.spawn(send(client.clone(), url.clone(), config.phone(user), body));
+    }
+
+    let mut samples = Vec::with_capacity(total as usize);
+    while let Some(sample) = tasks.join_next().await {
+        if let Ok(sample) = sample {
+            samples.push(sample);
+        }
+    }
+    samples
+}
+
+/// Numbers the `MessageSid` of each webhook
+static SENT: AtomicU64 = AtomicU64::new(0);
+
+/// One Twilio-format webhook, timed until the TwiML reply is read
+async fn send(client: reqwest::Client, url: String, from: String, body: String) -> Sample {
+    let command = body.split_whitespace().next().unwrap_or_default().to_uppercase();
+    let sid = format!("SM{:032x}", SENT.fetch_add(1, Ordering::Relaxed));
+    let started = Instant::now();
+    let response = client
+        .post(&url)
+        .form(&[("From", from.as_str()), ("To", "+15550000000"), ("Body", body.as_str()), ("MessageSid", sid.as_str())])
+        .send()
+        .await;
+    let ok = match response {
+        Ok(response) if response.status().is_success() => response.text().await.is_ok_and(|twiml| twiml.contains("<Response")),
+        _ => false,
+    };
+    Sample { command, latency: started.elapsed(), ok }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mix_parse_and_pick() {
+        let mix = Mix::parse("3:BALANCE, 1:SEND 1 USDC TO {peer},HELP").unwrap();
+        assert_eq!(mix.total, 5);
+        assert_eq!(mix.pick(0), "BALANCE");
+        assert_eq!(mix.pick(2), "BALANCE");
+        assert_eq!(mix.pick(3), "SEND 1 USDC TO {peer}");
+        assert_eq!(mix.pick(4), "HELP");
+        assert_eq!(mix.pick(5), "BALANCE");
+
+        assert!(Mix::parse("").is_err());
+        assert!(Mix::parse("0:HELP").is_err());
+        assert!(Mix::parse("99999999999:HELP").is_err());
+    }
+
+    #[test]
+    fn test_same_seed_same_draws() {
+        let (mut a, mut b) = (Rng::new(42), Rng::new(42));
+        let draws: Vec<u64> = (0..5).map(|_| a.next()).collect();
+        assert_eq!(draws, (0..5).map(|_| b.next()).collect::<Vec<_>>());
+        assert_ne!(draws[0], Rng::new(43).next());
+    }
+}